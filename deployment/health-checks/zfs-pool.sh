@@ -0,0 +1,37 @@
+#!/bin/sh
+# Jarvis health check for a ZFS pool: reports its state and free space.
+#
+#   [[health_checks]]
+#   name = "zfs-tank"
+#   command = "/usr/local/lib/jarvis/checks/zfs-pool.sh"
+#   args = ["tank"]
+#
+# Warns below WARN_FREE_PCT free (default 20), critical below CRIT_FREE_PCT
+# (default 10) or when the pool is not ONLINE.
+
+pool="${1:-tank}"
+warn_pct="${WARN_FREE_PCT:-20}"
+crit_pct="${CRIT_FREE_PCT:-10}"
+
+if ! line=$(zpool list -Hp -o health,size,free "$pool" 2>&1); then
+    printf '{"status": "critical", "message": "zpool list %s failed: %s"}\n' \
+        "$pool" "$(printf '%s' "$line" | tr -d '"\\' | head -n 1)"
+    exit 2
+fi
+
+set -- $line
+health="$1"
+size="$2"
+free="$3"
+free_pct=$((free * 100 / size))
+free_tib=$(awk -v b="$free" 'BEGIN { printf "%.2f", b / 1099511627776 }')
+
+status=ok
+if [ "$health" != "ONLINE" ] || [ "$free_pct" -lt "$crit_pct" ]; then
+    status=critical
+elif [ "$free_pct" -lt "$warn_pct" ]; then
+    status=warn
+fi
+
+printf '{"status": "%s", "message": "%s is %s, %s%% free", "metrics": {"free_tib": %s, "free_percent": %s}}\n' \
+    "$status" "$pool" "$health" "$free_pct" "$free_tib" "$free_pct"
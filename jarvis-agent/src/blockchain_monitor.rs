@@ -4,6 +4,7 @@
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
+use jarvis_core::severity::Severity;
 use jarvis_core::{GhostChainClient, MemoryStore};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -53,6 +54,26 @@ pub enum AlertSeverity {
     Emergency,
 }
 
+impl From<AlertSeverity> for Severity {
+    fn from(severity: AlertSeverity) -> Self {
+        match severity {
+            AlertSeverity::Info => Severity::Info,
+            AlertSeverity::Warning => Severity::Medium,
+            AlertSeverity::Critical | AlertSeverity::Emergency => Severity::Critical,
+        }
+    }
+}
+
+impl From<Severity> for AlertSeverity {
+    fn from(severity: Severity) -> Self {
+        match severity {
+            Severity::Info | Severity::Low => AlertSeverity::Info,
+            Severity::Medium | Severity::High => AlertSeverity::Warning,
+            Severity::Critical => AlertSeverity::Critical,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct MonitoringConfig {
     pub check_interval: Duration,
@@ -60,6 +81,8 @@ pub struct MonitoringConfig {
     pub throughput_threshold: f64,
     pub packet_loss_threshold: f64,
     pub enable_ai_analysis: bool,
+    /// Alerts below this are dropped before they are stored or analyzed
+    pub min_alert_severity: Severity,
 }
 
 impl Default for MonitoringConfig {
@@ -70,6 +93,7 @@ impl Default for MonitoringConfig {
             throughput_threshold: 10.0, // 10 Mbps minimum
             packet_loss_threshold: 1.0, // 1% packet loss
             enable_ai_analysis: true,
+            min_alert_severity: Severity::Info,
         }
     }
 }
@@ -174,8 +198,12 @@ impl BlockchainMonitorAgent {
         // Check for alerts
         let alerts = self.analyze_metrics(&metrics).await?;
 
-        // Process any alerts
-        for alert in alerts {
+        // Process alerts that meet the configured minimum
+        let min = self.config.min_alert_severity;
+        for alert in alerts
+            .into_iter()
+            .filter(|a| Severity::from(a.severity.clone()).meets(min))
+        {
             self.handle_alert(alert).await?;
         }
 
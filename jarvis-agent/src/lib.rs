@@ -1,6 +1,7 @@
 pub mod ai_analyzer;
 pub mod blockchain_monitor;
 pub mod orchestrator;
+pub mod probes;
 pub mod runner;
 pub mod tools;
 
@@ -11,4 +12,4 @@ pub use blockchain_monitor::{
 pub use orchestrator::{
     AgentMessage, AgentStatus, BlockchainAgentOrchestrator, OrchestratorConfig,
 };
-pub use runner::AgentRunner;
+pub use runner::{AgentRunner, WriteOptions};
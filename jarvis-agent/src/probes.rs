@@ -0,0 +1,522 @@
+//! Probe selection and concurrent execution for diagnose and check
+//!
+//! A target is matched against a relevance table (keywords to probes), so
+//! "diagnose nginx" looks at failed units and the error journal but never
+//! runs smartctl. The selected probes run concurrently, each under its own
+//! timeout and all under one deadline; a probe that runs out of time is
+//! reported as timed out instead of holding up the others or vanishing from
+//! the output.
+
+use anyhow::Result;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+
+/// One piece of evidence a diagnosis or status check can gather
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Probe {
+    ServiceStatus(String),
+    ServiceLogs(String),
+    UnitDrift(String),
+    FailedServices,
+    JournalErrors,
+    Network,
+    Storage,
+    DiskHealth,
+    BtrfsUsage,
+    Mounts,
+    Containers,
+    Packages,
+    Gpus,
+    Sensors,
+    CustomChecks,
+}
+
+impl Probe {
+    /// Name in reports and traces, e.g. "service logs nginx"
+    pub fn name(&self) -> String {
+        match self {
+            Probe::ServiceStatus(unit) => format!("service status {}", unit),
+            Probe::ServiceLogs(unit) => format!("service logs {}", unit),
+            Probe::UnitDrift(unit) => format!("unit drift {}", unit),
+            Probe::FailedServices => "failed services".to_string(),
+            Probe::JournalErrors => "journal errors".to_string(),
+            Probe::Network => "network".to_string(),
+            Probe::Storage => "storage".to_string(),
+            Probe::DiskHealth => "disk health".to_string(),
+            Probe::BtrfsUsage => "btrfs usage".to_string(),
+            Probe::Mounts => "mounts".to_string(),
+            Probe::Containers => "containers".to_string(),
+            Probe::Packages => "packages".to_string(),
+            Probe::Gpus => "gpus".to_string(),
+            Probe::Sensors => "sensors".to_string(),
+            Probe::CustomChecks => "custom checks".to_string(),
+        }
+    }
+
+    /// Probes that are not about one unit
+    pub const SIMPLE: &'static [Probe] = &[
+        Probe::FailedServices,
+        Probe::JournalErrors,
+        Probe::Network,
+        Probe::Storage,
+        Probe::DiskHealth,
+        Probe::BtrfsUsage,
+        Probe::Mounts,
+        Probe::Containers,
+        Probe::Packages,
+        Probe::Gpus,
+        Probe::Sensors,
+        Probe::CustomChecks,
+    ];
+
+    /// The probe [`Probe::name`] gives `name`, e.g. "service logs nginx";
+    /// probes that are not about a unit may also be spelled with dashes, as
+    /// in "disk-health"
+    pub fn parse(name: &str) -> Option<Probe> {
+        let name = name.trim();
+        if let [first, second, unit] = name.splitn(3, ' ').collect::<Vec<_>>()[..]
+            && !unit.trim().is_empty()
+        {
+            let unit = unit.trim().to_string();
+            match (first, second) {
+                ("service", "status") => return Some(Probe::ServiceStatus(unit)),
+                ("service", "logs") => return Some(Probe::ServiceLogs(unit)),
+                ("unit", "drift") => return Some(Probe::UnitDrift(unit)),
+                _ => {}
+            }
+        }
+
+        let spaced = name.replace('-', " ");
+        Probe::SIMPLE
+            .iter()
+            .find(|probe| probe.name() == spaced)
+            .cloned()
+    }
+}
+
+/// Entries of the relevance tables; `Service` becomes the probes of one unit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProbeKind {
+    Service,
+    FailedServices,
+    JournalErrors,
+    Network,
+    Storage,
+    DiskHealth,
+    BtrfsUsage,
+    Mounts,
+    Containers,
+    Packages,
+    Gpus,
+    Sensors,
+    CustomChecks,
+}
+
+/// Keywords in a `diagnose` target and the probes they call for
+const DIAGNOSE_RELEVANCE: &[(&[&str], &[ProbeKind])] = &[
+    (&["service", "systemd", "unit"], &[ProbeKind::Service]),
+    (
+        &["network", "interface", "dns", "wifi", "ethernet"],
+        &[ProbeKind::Network],
+    ),
+    (
+        &["disk", "btrfs", "mount", "storage", "space"],
+        &[ProbeKind::Storage],
+    ),
+    (
+        &["disk", "smart", "ssd", "nvme", "hdd", "drive"],
+        &[ProbeKind::DiskHealth],
+    ),
+    (
+        &["docker", "container", "compose"],
+        &[ProbeKind::Containers],
+    ),
+    (&["gpu", "nvidia", "cuda"], &[ProbeKind::Gpus]),
+    (
+        &["temperature", "thermal", "overheating", "throttling", "fan", "battery"],
+        &[ProbeKind::Sensors],
+    ),
+    (
+        &["boot", "crash", "kernel", "journal", "error"],
+        &[ProbeKind::JournalErrors],
+    ),
+];
+
+/// Keywords in a `check` target and the probes they call for
+const CHECK_RELEVANCE: &[(&[&str], &[ProbeKind])] = &[
+    (&["btrfs"], &[ProbeKind::BtrfsUsage]),
+    (&["mount"], &[ProbeKind::Mounts]),
+    (&["service"], &[ProbeKind::FailedServices]),
+    (&["package", "update"], &[ProbeKind::Packages]),
+    (&["gpu"], &[ProbeKind::Gpus]),
+    (
+        &["sensor", "temperature", "temp", "thermal", "fan", "battery"],
+        &[ProbeKind::Sensors],
+    ),
+    (&["custom", "script"], &[ProbeKind::CustomChecks]),
+];
+
+/// What `diagnose` gathers for a target that names nothing more specific
+const DIAGNOSE_FALLBACK: &[ProbeKind] = &[ProbeKind::FailedServices, ProbeKind::JournalErrors];
+
+/// Probes relevant to diagnosing `target`
+pub fn for_diagnosis(target: &str) -> Vec<Probe> {
+    let kinds = matching_kinds(target, DIAGNOSE_RELEVANCE);
+    let kinds = if kinds.is_empty() {
+        DIAGNOSE_FALLBACK.to_vec()
+    } else {
+        kinds
+    };
+
+    let unit = unit_name(target);
+    let mut probes = Vec::new();
+    for kind in kinds {
+        match (kind, &unit) {
+            (ProbeKind::Service, Some(unit)) => probes.extend([
+                Probe::ServiceStatus(unit.clone()),
+                Probe::ServiceLogs(unit.clone()),
+                Probe::UnitDrift(unit.clone()),
+            ]),
+            // "diagnose my services" names no unit to look at
+            (ProbeKind::Service, None) => probes.push(Probe::FailedServices),
+            (kind, _) => probes.push(simple_probe(kind)),
+        }
+    }
+    probes.dedup();
+    probes
+}
+
+/// Probes relevant to checking the status of `target`
+pub fn for_status(target: &str) -> Vec<Probe> {
+    matching_kinds(target, CHECK_RELEVANCE)
+        .into_iter()
+        .map(simple_probe)
+        .collect()
+}
+
+fn simple_probe(kind: ProbeKind) -> Probe {
+    match kind {
+        ProbeKind::Service | ProbeKind::FailedServices => Probe::FailedServices,
+        ProbeKind::JournalErrors => Probe::JournalErrors,
+        ProbeKind::Network => Probe::Network,
+        ProbeKind::Storage => Probe::Storage,
+        ProbeKind::DiskHealth => Probe::DiskHealth,
+        ProbeKind::BtrfsUsage => Probe::BtrfsUsage,
+        ProbeKind::Mounts => Probe::Mounts,
+        ProbeKind::Containers => Probe::Containers,
+        ProbeKind::Packages => Probe::Packages,
+        ProbeKind::Gpus => Probe::Gpus,
+        ProbeKind::Sensors => Probe::Sensors,
+        ProbeKind::CustomChecks => Probe::CustomChecks,
+    }
+}
+
+/// Words of `target`, lowercased, keeping the characters of unit names
+fn words(target: &str) -> impl Iterator<Item = String> + '_ {
+    target
+        .split(|c: char| !(c.is_alphanumeric() || matches!(c, '.' | '-' | '_' | '@')))
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+/// Kinds whose keywords occur in `target`, in table order, each once. A
+/// keyword matches a whole word or its plural; a `.service` word counts as
+/// "service".
+fn matching_kinds(target: &str, table: &[(&[&str], &[ProbeKind])]) -> Vec<ProbeKind> {
+    let words: Vec<String> = words(target)
+        .map(|word| {
+            if word.ends_with(".service") {
+                "service".to_string()
+            } else {
+                word
+            }
+        })
+        .collect();
+    let mut kinds = Vec::new();
+    for (keywords, probe_kinds) in table {
+        let relevant = keywords.iter().any(|keyword| {
+            words
+                .iter()
+                .any(|word| word == keyword || word.strip_suffix('s') == Some(keyword))
+        });
+        if relevant {
+            for kind in probe_kinds.iter() {
+                if !kinds.contains(kind) {
+                    kinds.push(*kind);
+                }
+            }
+        }
+    }
+    kinds
+}
+
+/// The unit a target is about: an explicit `*.service`, or the word before
+/// "service", as in "nginx service"
+fn unit_name(target: &str) -> Option<String> {
+    let words: Vec<String> = words(target).collect();
+    if let Some(unit) = words.iter().find(|word| word.ends_with(".service")) {
+        return unit.strip_suffix(".service").map(str::to_string);
+    }
+    let position = words.iter().position(|word| word == "service")?;
+    let unit = words.get(position.checked_sub(1)?)?;
+    (!matches!(unit.as_str(), "the" | "my" | "a" | "this" | "systemd")).then(|| unit.clone())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProbeStatus {
+    Completed,
+    Failed(String),
+    TimedOut,
+}
+
+impl ProbeStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProbeStatus::Completed => "completed",
+            ProbeStatus::Failed(_) => "failed",
+            ProbeStatus::TimedOut => "timed_out",
+        }
+    }
+}
+
+/// What one probe produced, or why it produced nothing
+#[derive(Debug, Clone)]
+pub struct ProbeResult {
+    pub probe: Probe,
+    pub status: ProbeStatus,
+    pub elapsed: Duration,
+    pub output: String,
+}
+
+/// Run `probes` concurrently through `run`, giving each `timeout` and all of
+/// them `deadline`. Results come back in the order of `probes`, including
+/// probes that failed or ran out of time. Each probe is a phase of the
+/// current trace, tagged with its status.
+pub async fn run_all<F, Fut>(
+    probes: Vec<Probe>,
+    timeout: Duration,
+    deadline: Duration,
+    run: F,
+) -> Vec<ProbeResult>
+where
+    F: Fn(Probe) -> Fut,
+    Fut: Future<Output = Result<String>> + Send + 'static,
+{
+    let parent = jarvis_core::trace::phase("probes");
+    parent.attr("count", probes.len());
+    let started = Instant::now();
+    let deadline_at = tokio::time::Instant::now() + deadline;
+
+    let mut phases: Vec<Option<jarvis_core::trace::Phase>> = probes
+        .iter()
+        .map(|probe| Some(parent.child(&probe.name())))
+        .collect();
+    let mut results: Vec<Option<ProbeResult>> = vec![None; probes.len()];
+
+    let mut set = JoinSet::new();
+    for (index, probe) in probes.iter().enumerate() {
+        let future = run(probe.clone());
+        set.spawn(async move {
+            let probe_started = Instant::now();
+            let outcome = tokio::time::timeout(timeout, future).await;
+            (index, outcome, probe_started.elapsed())
+        });
+    }
+
+    loop {
+        match tokio::time::timeout_at(deadline_at, set.join_next()).await {
+            Ok(Some(Ok((index, outcome, elapsed)))) => {
+                let (status, output) = match outcome {
+                    Ok(Ok(output)) => (ProbeStatus::Completed, output),
+                    Ok(Err(e)) => (ProbeStatus::Failed(format!("{:#}", e)), String::new()),
+                    Err(_) => (ProbeStatus::TimedOut, String::new()),
+                };
+                if let Some(phase) = phases[index].take() {
+                    phase.attr("status", status.as_str());
+                }
+                results[index] = Some(ProbeResult {
+                    probe: probes[index].clone(),
+                    status,
+                    elapsed,
+                    output,
+                });
+            }
+            Ok(Some(Err(e))) => tracing::warn!("A probe task failed: {}", e),
+            Ok(None) => break,
+            // Out of time: whatever is still running is abandoned
+            Err(_) => {
+                set.abort_all();
+                break;
+            }
+        }
+    }
+
+    let elapsed = started.elapsed();
+    probes
+        .into_iter()
+        .zip(results)
+        .zip(phases)
+        .map(|((probe, result), phase)| {
+            result.unwrap_or_else(|| {
+                if let Some(phase) = phase {
+                    phase.attr("status", ProbeStatus::TimedOut.as_str());
+                }
+                ProbeResult {
+                    probe,
+                    status: ProbeStatus::TimedOut,
+                    elapsed,
+                    output: String::new(),
+                }
+            })
+        })
+        .collect()
+}
+
+/// Probe output for the prompt, with a line for each probe that failed or
+/// timed out so the model knows the evidence is incomplete
+pub fn render(results: &[ProbeResult]) -> String {
+    let mut output = String::new();
+    for result in results {
+        match &result.status {
+            ProbeStatus::Completed => output.push_str(&result.output),
+            ProbeStatus::Failed(error) => output.push_str(&format!(
+                "\n[{}] {}: {}\n",
+                result.status.as_str(),
+                result.probe.name(),
+                error
+            )),
+            ProbeStatus::TimedOut => output.push_str(&format!(
+                "\n[{}] {}: no answer after {:.1}s\n",
+                result.status.as_str(),
+                result.probe.name(),
+                result.elapsed.as_secs_f64()
+            )),
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probes_follow_the_target() {
+        assert_eq!(
+            for_diagnosis("nginx"),
+            vec![Probe::FailedServices, Probe::JournalErrors]
+        );
+        assert_eq!(
+            for_diagnosis("nginx.service keeps restarting"),
+            vec![
+                Probe::ServiceStatus("nginx".to_string()),
+                Probe::ServiceLogs("nginx".to_string()),
+                Probe::UnitDrift("nginx".to_string()),
+            ]
+        );
+        assert_eq!(
+            for_diagnosis("my nginx service")[0],
+            Probe::ServiceStatus("nginx".to_string())
+        );
+        assert_eq!(for_diagnosis("my services"), vec![Probe::FailedServices]);
+        assert_eq!(
+            for_diagnosis("slow disk"),
+            vec![Probe::Storage, Probe::DiskHealth]
+        );
+        assert!(!for_diagnosis("docker containers").contains(&Probe::DiskHealth));
+        assert_eq!(
+            for_status("btrfs and pending updates"),
+            vec![Probe::BtrfsUsage, Probe::Packages]
+        );
+        assert!(for_status("nginx").is_empty());
+        assert_eq!(for_status("temps and fans"), vec![Probe::Sensors]);
+        assert_eq!(for_diagnosis("laptop overheating"), vec![Probe::Sensors]);
+        assert_eq!(for_status("custom checks"), vec![Probe::CustomChecks]);
+    }
+
+    #[test]
+    fn test_probe_names_parse_back() {
+        for probe in Probe::SIMPLE {
+            assert_eq!(Probe::parse(&probe.name()).as_ref(), Some(probe));
+        }
+        assert_eq!(Probe::parse("disk-health"), Some(Probe::DiskHealth));
+        assert_eq!(
+            Probe::parse("service logs systemd-resolved"),
+            Some(Probe::ServiceLogs("systemd-resolved".to_string()))
+        );
+        assert_eq!(Probe::parse("service status"), None);
+        assert_eq!(Probe::parse("smart"), None);
+    }
+
+    #[tokio::test]
+    async fn test_slow_probes_time_out_without_holding_up_the_rest() {
+        let trace = jarvis_core::trace::Trace::new("diagnose slow disk");
+        let probes = vec![Probe::Storage, Probe::DiskHealth, Probe::Network];
+        let run = |probe: Probe| async move {
+            match probe {
+                Probe::DiskHealth => {
+                    tokio::time::sleep(Duration::from_secs(30)).await;
+                    Ok("never".to_string())
+                }
+                Probe::Network => anyhow::bail!("ip: command not found"),
+                _ => Ok("Disk Usage: 40%\n".to_string()),
+            }
+        };
+
+        // The per-probe timeout cuts the slow probe short
+        let started = Instant::now();
+        let results = trace
+            .scope(run_all(
+                probes.clone(),
+                Duration::from_millis(100),
+                Duration::from_secs(10),
+                run,
+            ))
+            .await;
+        assert!(started.elapsed() < Duration::from_secs(2));
+        let statuses: Vec<&str> = results.iter().map(|r| r.status.as_str()).collect();
+        assert_eq!(statuses, vec!["completed", "timed_out", "failed"]);
+
+        // So does the overall deadline
+        let started = Instant::now();
+        let results = run_all(
+            probes,
+            Duration::from_secs(10),
+            Duration::from_millis(100),
+            run,
+        )
+        .await;
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(results[1].status, ProbeStatus::TimedOut);
+        assert_eq!(results[0].output, "Disk Usage: 40%\n");
+
+        let rendered = render(&results);
+        assert!(rendered.contains("Disk Usage: 40%"));
+        assert!(rendered.contains("[timed_out] disk health"));
+        assert!(rendered.contains("[failed] network: ip: command not found"));
+
+        let report = trace.report();
+        let phases: Vec<(&str, usize, Option<&str>)> = report
+            .phases
+            .iter()
+            .map(|p| {
+                (
+                    p.name.as_str(),
+                    p.depth,
+                    p.attributes.get("status").map(String::as_str),
+                )
+            })
+            .collect();
+        assert_eq!(
+            phases,
+            vec![
+                ("probes", 0, None),
+                ("storage", 1, Some("completed")),
+                ("disk health", 1, Some("timed_out")),
+                ("network", 1, Some("failed")),
+            ]
+        );
+        assert!(report.phases.iter().all(|p| p.duration_ms.is_some()));
+    }
+}
@@ -1,35 +1,144 @@
+use crate::probes::{Probe, ProbeStatus};
 use crate::tools::SystemTools;
-use anyhow::Result;
-use jarvis_core::{LLMRouter, MemoryStore};
+use anyhow::{Context, Result};
+use jarvis_core::bus::{self, BusConfig};
+use jarvis_core::chat::{self, Attachment, AttachmentKind, ChatCommand, ChatSession};
+use jarvis_core::chat_actions::{self, PendingAction, Risk};
+use jarvis_core::config_files::ConfigExplanation;
+use jarvis_core::file_access::{FileAccess, FileAccessConfig};
+use jarvis_core::health_checks::HealthCheckConfig;
+use jarvis_core::host_profile::{HostProfile, HostProfileConfig};
+use jarvis_core::llm::{ContextWindowManager, ModelReadiness};
+use jarvis_core::read_only;
+use jarvis_core::remedies;
+use jarvis_core::scaffold;
+use jarvis_core::sensors::SensorsConfig;
+use jarvis_core::time_range;
+use jarvis_core::types::MessageRole;
+use jarvis_core::{CommandExecutor, JarvisError, JarvisResult, LLMRouter, MemoryStore, OutputFormat};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Options for `jarvis write`
+#[derive(Debug, Clone, Default)]
+pub struct WriteOptions {
+    /// Project directory; defaults to `./generated-<slug>`
+    pub out: Option<PathBuf>,
+    /// Replace files that already exist
+    pub force: bool,
+    /// Run `cargo check` and allow one repair round
+    pub verify: bool,
+}
 
 pub struct AgentRunner {
     memory: MemoryStore,
     llm: LLMRouter,
     tools: SystemTools,
+    bus: Option<BusConfig>,
+    host_profile: Option<HostProfileConfig>,
+    files: FileAccess,
 }
 
 impl AgentRunner {
     pub async fn new(memory: MemoryStore, llm: LLMRouter) -> Result<Self> {
         let tools = SystemTools::new().await?;
 
-        Ok(Self { memory, llm, tools })
+        Ok(Self {
+            memory,
+            llm,
+            tools,
+            bus: None,
+            host_profile: None,
+            files: FileAccess::new(&FileAccessConfig::default()),
+        })
+    }
+
+    /// Run system probes through `executor` while the LLM and memory stay local
+    pub fn with_executor(mut self, executor: CommandExecutor) -> Self {
+        let (timeout, deadline) = self.tools.probe_limits();
+        self.tools = SystemTools::with_executor(executor)
+            .with_files_db_max_age(self.tools.files_db_max_age())
+            .with_probe_limits(timeout, deadline)
+            .with_sensors(self.tools.sensors().clone())
+            .with_health_checks(self.tools.health_checks().to_vec());
+        self
+    }
+
+    /// How old the pacman file database may get before ownership lookups
+    /// during diagnosis refresh it
+    pub fn with_files_db_max_age(mut self, max_age: Duration) -> Self {
+        self.tools = self.tools.with_files_db_max_age(max_age);
+        self
+    }
+
+    /// Per-probe timeout and overall deadline for diagnose and check
+    pub fn with_probe_limits(mut self, timeout: Duration, deadline: Duration) -> Self {
+        self.tools = self.tools.with_probe_limits(timeout, deadline);
+        self
+    }
+
+    /// Sensor threshold overrides for `check sensors`
+    pub fn with_sensors(mut self, sensors: SensorsConfig) -> Self {
+        self.tools = self.tools.with_sensors(sensors);
+        self
+    }
+
+    /// `[[health_checks]]` scripts for `check custom`
+    pub fn with_health_checks(mut self, health_checks: Vec<HealthCheckConfig>) -> Self {
+        self.tools = self.tools.with_health_checks(health_checks);
+        self
+    }
+
+    /// Add recent events from jarvisd's event bus to diagnoses of this host
+    pub fn with_bus(mut self, bus: BusConfig) -> Self {
+        self.bus = Some(bus);
+        self
+    }
+
+    /// Prepend the stored profile of the target host to explain, diagnose,
+    /// and fix prompts
+    pub fn with_host_profile(mut self, config: HostProfileConfig) -> Self {
+        self.host_profile = Some(config);
+        self
+    }
+
+    /// Which files `/attach file` may read in chat
+    pub fn with_file_access(mut self, files: FileAccess) -> Self {
+        self.files = files;
+        self
     }
 
+    fn executor(&self) -> &CommandExecutor {
+        self.tools.executor()
+    }
+
+    /// `input` is attached text such as a log file or piped command output
     pub async fn explain(
         &self,
         query: &str,
+        input: Option<&str>,
         environment: &jarvis_shell::Environment,
-    ) -> Result<()> {
+        format: OutputFormat,
+    ) -> JarvisResult<()> {
+        if input.is_none() && let Some(file) = self.read_config_file(query).await {
+            return self.explain_config_file(file, format).await;
+        }
+
         println!("🤖 Jarvis: Let me explain '{}'...", query);
 
         // Gather context
-        let context = self.gather_context(query, environment).await?;
+        let mut context = self.gather_context(query, environment).await?;
+        if let Some(input) = input {
+            context = format!("{}\n\nProvided Input:\n{}", context, input);
+        }
 
         // Generate explanation
-        let prompt = format!(
-            "Explain this query in the context of an Arch Linux system: {}\n\nSystem Context:\n{}",
-            query, context
+        let instruction = format!(
+            "{}Explain this query in the context of an Arch Linux system: {}\n\nSystem Context:",
+            self.profile_context().await,
+            query
         );
+        let prompt = self.fit_prompt(&instruction, &context, query).await?;
 
         let response = self.llm.generate(&prompt, None).await?;
         println!("\n📚 Explanation:\n{}", response);
@@ -37,20 +146,81 @@ impl AgentRunner {
         Ok(())
     }
 
+    /// `query` taken apart key by key, if it names a config file in a format
+    /// `config_files` knows; anything else gets the prose explanation
+    async fn read_config_file(&self, query: &str) -> Option<ConfigExplanation> {
+        let query = query.trim();
+        if query.contains(char::is_whitespace) || !query.contains('/') {
+            return None;
+        }
+        // Read through the executor so `--host` explains the remote file
+        let contents = self.executor().run_stdout("cat", &[query]).await.ok()?;
+        match ConfigExplanation::from_file(Path::new(query), &contents) {
+            Ok(file) => file,
+            Err(e) => {
+                tracing::debug!("Not explaining {} key by key: {}", query, e);
+                None
+            }
+        }
+    }
+
+    async fn explain_config_file(&self, mut file: ConfigExplanation, format: OutputFormat) -> JarvisResult<()> {
+        if format == OutputFormat::Pretty {
+            println!("🤖 Jarvis: Let me explain {} key by key...", file.path);
+        }
+        if !file.keys.is_empty() {
+            let prompt = format!("{}{}", self.profile_context().await, file.explanation_prompt());
+            match self.llm.generate(&prompt, None).await {
+                Ok(response) => file.apply_explanations(&response),
+                // The values and defaults are still worth showing
+                Err(e) => eprintln!("⚠️ No explanations, the LLM is unavailable: {}", e),
+            }
+        }
+
+        match format {
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&file.keys)?),
+            OutputFormat::Pretty => {
+                println!("\n📄 {}\n", file.summary());
+                print!("{}", file.render_table());
+            }
+        }
+        Ok(())
+    }
+
     pub async fn diagnose(
         &self,
         target: &str,
+        input: Option<&str>,
         _environment: &jarvis_shell::Environment,
-    ) -> Result<()> {
-        println!("🔍 Jarvis: Diagnosing '{}'...", target);
+    ) -> JarvisResult<()> {
+        println!(
+            "🔍 Jarvis: Diagnosing '{}' on {}...",
+            target,
+            self.executor().host_label()
+        );
 
         // Run diagnostic tools
-        let diagnostic_info = self.tools.diagnose(target).await?;
+        let mut diagnostic_info = self.tools.diagnose(target).await;
+        if let Some(input) = input {
+            diagnostic_info.push_str(&format!("\n\nProvided Input:\n{}", input));
+        }
+        // Paths and libraries in error messages, resolved to their packages
+        let owners = self
+            .tools
+            .file_owners(&format!("{}\n{}", target, diagnostic_info))
+            .await;
+        diagnostic_info.push_str(&owners);
+        // What the agents saw lately, e.g. a driver upgrade just before an anomaly
+        if let Some(events) = self.recent_bus_events().await {
+            diagnostic_info.push_str(&events);
+        }
 
-        let prompt = format!(
-            "Diagnose this system issue: {}\n\nDiagnostic Information:\n{}",
-            target, diagnostic_info
+        let instruction = format!(
+            "{}Diagnose this system issue: {}\n\nDiagnostic Information:",
+            self.profile_context().await,
+            target
         );
+        let prompt = self.fit_prompt(&instruction, &diagnostic_info, target).await?;
 
         let response = self.llm.generate(&prompt, None).await?;
         println!("\n🔍 Diagnosis:\n{}", response);
@@ -58,56 +228,313 @@ impl AgentRunner {
         Ok(())
     }
 
+    /// Generate code and, when the response annotates its code blocks with file
+    /// paths, write them out as a project
     pub async fn write_code(
         &self,
         description: &str,
-        _environment: &jarvis_shell::Environment,
-    ) -> Result<()> {
+        options: &WriteOptions,
+        environment: &jarvis_shell::Environment,
+    ) -> JarvisResult<()> {
         println!("✍️ Jarvis: Writing code for '{}'...", description);
 
-        let prompt = format!(
-            "Write code based on this description: {}\n\nEnvironment: Arch Linux, Rust ecosystem",
-            description
+        let instruction = format!(
+            "Write code based on this description: {}\n\nEnvironment: Arch Linux, Rust ecosystem\n\n{}",
+            description,
+            scaffold::SCAFFOLD_INSTRUCTIONS
         );
+        let prompt = match &environment.git_context {
+            Some(git) => {
+                let mut repo_context = git.summary();
+                if let Some(changes) = self.relevant_git_changes(description, environment) {
+                    repo_context.push_str(&changes);
+                }
+                self.fit_prompt(&format!("{}\n\nRepository Context:", instruction), &repo_context, description)
+                    .await?
+            }
+            None => instruction,
+        };
 
         let response = self.llm.generate(&prompt, None).await?;
-        println!("\n💻 Generated Code:\n{}", response);
+        let plan = scaffold::parse_response(&response);
+        if plan.is_empty() {
+            println!("\n💻 Generated Code:\n{}", response);
+            return Ok(());
+        }
+
+        let out = options
+            .out
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(format!("generated-{}", scaffold::slug(description))));
+        if !options.force {
+            let conflicts = plan.conflicts(&out);
+            if !conflicts.is_empty() {
+                let list: Vec<String> = conflicts.iter().map(|p| p.display().to_string()).collect();
+                return Err(JarvisError::PermissionDenied(format!(
+                    "Refusing to overwrite existing files (use --force): {}",
+                    list.join(", ")
+                )));
+            }
+        }
+        std::fs::create_dir_all(&out)?;
+
+        let rust = plan.is_rust();
+        if rust && !out.join("Cargo.toml").exists() && !plan.has_manifest() {
+            run_cargo(&out, &["init", "--quiet", "--name", &scaffold::slug(description)]).await?;
+        }
+
+        // Files cargo init just created are ours to replace
+        let written = plan.write(&out, true)?;
+
+        if rust && !plan.has_manifest() {
+            for dependency in &plan.dependencies {
+                let mut args = vec!["add", "--quiet"];
+                args.extend(dependency.split_whitespace());
+                if let Err(e) = run_cargo(&out, &args).await {
+                    println!("⚠️  cargo add {} failed: {}", dependency, e);
+                }
+            }
+        }
+
+        println!("\n📁 Created {} file(s):\n{}", written.len(), scaffold::render_tree(&out, &written));
+
+        if options.verify && rust {
+            self.verify_project(description, &out, &plan).await?;
+        }
 
         Ok(())
     }
 
+    /// Draft a conventional-commit message for the staged changes
+    pub async fn commit_message(&self, environment: &jarvis_shell::Environment) -> JarvisResult<()> {
+        let diff = jarvis_shell::git::staged_diff(&environment.working_directory)?;
+        if diff.trim().is_empty() {
+            return Err(JarvisError::NotFound(
+                "Nothing is staged; `git add` the changes to describe first".to_string(),
+            ));
+        }
+
+        let instruction = "Write a commit message for the staged diff below using the Conventional \
+            Commits format: `type(scope): summary` on the first line (imperative, at most 72 \
+            characters), a blank line, then a short body explaining what changed and why. \
+            Reply with the message only.\n\nStaged Diff:";
+        let prompt = self.fit_prompt(instruction, &diff, "commit message summary").await?;
+
+        let response = self.llm.generate(&prompt, None).await?;
+        println!("{}", response.trim().trim_matches('`').trim());
+
+        Ok(())
+    }
+
+    /// `cargo check` the generated project, giving the LLM one chance to repair errors
+    async fn verify_project(&self, description: &str, out: &Path, plan: &scaffold::ScaffoldPlan) -> Result<()> {
+        println!("🔎 Running cargo check...");
+        let errors = match run_cargo(out, &["check", "--quiet", "--message-format", "short"]).await {
+            Ok(()) => {
+                println!("✅ cargo check passed");
+                return Ok(());
+            }
+            Err(e) => e.to_string(),
+        };
+
+        println!("🔁 cargo check failed; asking for a fix...");
+        let mut sources = String::new();
+        for file in &plan.files {
+            let content = std::fs::read_to_string(out.join(&file.path)).unwrap_or_default();
+            sources.push_str(&format!("```{}:{}\n{}```\n\n", file.language, file.path.display(), content));
+        }
+        let instruction = format!(
+            "This project was generated for: {}\nIt fails `cargo check`. Return corrected versions of \
+             only the files that need changes.\n\n{}\n\nCompiler errors:\n{}\n\nCurrent files:",
+            description,
+            scaffold::SCAFFOLD_INSTRUCTIONS,
+            errors
+        );
+        let prompt = self.fit_prompt(&instruction, &sources, &errors).await?;
+        let repair = scaffold::parse_response(&self.llm.generate(&prompt, None).await?);
+        if repair.is_empty() {
+            println!("⚠️  No corrected files in the response; errors:\n{}", errors);
+            return Ok(());
+        }
+        let rewritten = repair.write(out, true)?;
+        println!("✏️  Rewrote {} file(s)", rewritten.len());
+
+        match run_cargo(out, &["check", "--quiet", "--message-format", "short"]).await {
+            Ok(()) => println!("✅ cargo check passed after repair"),
+            Err(e) => println!("❌ cargo check still fails:\n{}", e),
+        }
+        Ok(())
+    }
+
     pub async fn check_status(
         &self,
         target: &str,
         _environment: &jarvis_shell::Environment,
-    ) -> Result<()> {
-        println!("✅ Jarvis: Checking status of '{}'...", target);
+    ) -> JarvisResult<()> {
+        println!(
+            "✅ Jarvis: Checking status of '{}' on {}...",
+            target,
+            self.executor().host_label()
+        );
 
-        let status_info = self.tools.check_status(target).await?;
+        let status_info = self.tools.check_status(target).await;
         println!("\n📊 Status:\n{}", status_info);
 
+        if ["llm", "ollama", "model"]
+            .iter()
+            .any(|t| target.contains(t))
+        {
+            self.print_model_readiness().await;
+        }
+
+        // GPU readings are hard to judge without knowing the card's limits
+        if target.contains("gpu") && status_info.contains("GPUs:") {
+            let prompt = format!(
+                "Interpret these GPU readings from an Arch Linux system. Point out \
+                 anything abnormal for the card and, where temperatures or power draw \
+                 are high, suggest concrete fixes such as a fan curve or power limit \
+                 change with the commands to apply them (e.g. via sysfs, \
+                 nvidia-smi -pl, or a tool like CoreCtrl or LACT).\n\n{}",
+                status_info
+            );
+            let response = self.llm.generate(&prompt, None).await?;
+            println!("\n🧠 Interpretation:\n{}", response);
+        }
+
         Ok(())
     }
 
+    /// Whether each model Jarvis uses would answer right now
+    async fn print_model_readiness(&self) {
+        println!("\n🧠 Models:");
+        let readiness = self.llm.ollama_model_readiness().await;
+        if readiness.is_empty() {
+            println!("  (no Ollama backend configured)");
+        }
+        for (model, state) in readiness {
+            match state {
+                ModelReadiness::Ready => println!("  ✅ {}: loaded", model),
+                ModelReadiness::Cold => {
+                    println!("  💤 {}: installed, loads on the next request", model)
+                }
+                ModelReadiness::Missing => println!(
+                    "  ❌ {}: not installed; run `jarvis train pull {}`",
+                    model, model
+                ),
+                ModelReadiness::Unreachable => println!("  ❌ {}: Ollama is not reachable", model),
+            }
+        }
+    }
+
     pub async fn fix_issue(
         &self,
         issue: &str,
-        _environment: &jarvis_shell::Environment,
-    ) -> Result<()> {
+        input: Option<&str>,
+        environment: &jarvis_shell::Environment,
+        consensus: bool,
+        dry_run: bool,
+    ) -> JarvisResult<()> {
+        // A dry run only suggests, so it is fine on hosts that refuse mutations
+        if dry_run {
+            println!("🧪 Dry run: fixes are suggested but nothing is applied");
+        } else if read_only::is_read_only() {
+            println!("🔒 Read-only mode: fixes are suggested for you to run; nothing is applied");
+        } else {
+            self.executor().ensure_mutation_allowed("apply fixes")?;
+        }
         println!("🔧 Jarvis: Attempting to fix '{}'...", issue);
 
-        // This would analyze the issue and propose fixes
-        let prompt = format!(
-            "Analyze this issue and suggest fixes for an Arch Linux system: {}",
+        // Known problems get their fix from the offline knowledge base, which
+        // also works when the LLM is unreachable
+        let journals = self.unit_journals(issue).await;
+        let evidence: Vec<&str> = [Some(issue), input, journals.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect();
+        let known = remedies::KnowledgeBase::load()?
+            .diagnose(&evidence.join("\n"), self.executor())
+            .await;
+        if !known.is_empty() {
+            println!("\n📚 Known fixes:");
+            for remedy in &known {
+                println!("{}", remedy.render());
+            }
+        }
+        if known.first().is_some_and(|r| r.confidence >= remedies::CONFIDENT) {
+            return Ok(());
+        }
+
+        let mut instruction = format!(
+            "{}Analyze this issue and suggest fixes for an Arch Linux system: {}",
+            self.profile_context().await,
             issue
         );
-
-        let response = self.llm.generate(&prompt, None).await?;
-        println!("\n🔧 Suggested Fix:\n{}", response);
+        if !known.is_empty() {
+            let candidates: Vec<String> = known
+                .iter()
+                .map(|r| format!("- {} ({:.0}% confidence)", r.title, r.confidence * 100.0))
+                .collect();
+            instruction.push_str(&format!(
+                "\n\nJarvis's rule base suggests these causes; confirm or rule them out:\n{}",
+                candidates.join("\n")
+            ));
+        }
+        // Local repo changes only make sense when fixing this machine
+        let changes = if self.executor().is_remote() {
+            None
+        } else {
+            self.relevant_git_changes(issue, environment)
+        };
+        let attached: Vec<&str> = [input, changes.as_deref()].into_iter().flatten().collect();
+        let prompt = if attached.is_empty() {
+            instruction
+        } else {
+            self.fit_prompt(&format!("{}\n\nProvided Input:", instruction), &attached.join("\n\n"), issue)
+                .await?
+        };
+
+        let suggested = if consensus {
+            self.llm
+                .generate_with_consensus(&prompt, jarvis_core::Intent::System)
+                .await
+                .map(|result| format!("\n🔧 Suggested Fix (consensus):\n{}", result.render()))
+        } else {
+            self.llm
+                .generate(&prompt, None)
+                .await
+                .map(|response| format!("\n🔧 Suggested Fix:\n{}", response))
+        };
+        match suggested {
+            Ok(suggested) => println!("{}", suggested),
+            // The known fixes above are still an answer
+            Err(e) if !known.is_empty() => {
+                println!("\n⚠️  No LLM suggestion ({}); the known fixes above still apply", e)
+            }
+            Err(e) => return Err(e.into()),
+        }
 
         Ok(())
     }
 
+    /// This boot's journal for each `*.service` the issue names
+    async fn unit_journals(&self, issue: &str) -> Option<String> {
+        let units = issue
+            .split(|c: char| c.is_whitespace() || c == ',' || c == '\'' || c == '"' || c == '`')
+            .filter(|word| word.len() > ".service".len() && word.ends_with(".service"));
+        let mut journals = Vec::new();
+        for unit in units {
+            match self
+                .executor()
+                .run_stdout("journalctl", &["-u", unit, "-b", "-n", "50", "--no-pager"])
+                .await
+            {
+                Ok(journal) => journals.push(journal),
+                Err(e) => tracing::debug!("No journal for {}: {}", unit, e),
+            }
+        }
+        (!journals.is_empty()).then(|| journals.join("\n"))
+    }
+
     pub async fn train_model(&self, model_name: &str, data_path: &str) -> Result<()> {
         println!(
             "🧠 Training model '{}' with data from '{}'",
@@ -119,7 +546,108 @@ impl AgentRunner {
 
     pub async fn list_models(&self) -> Result<()> {
         println!("📋 Available Models:");
-        // TODO: List available models
+        let models = self.llm.list_ollama_models().await?;
+        if models.is_empty() {
+            println!("  (none installed — try `jarvis train pull {}`)", self.llm.default_model());
+        }
+        for model in models {
+            let marker = if model == self.llm.default_model() { " (default)" } else { "" };
+            println!("  • {}{}", model, marker);
+        }
+        Ok(())
+    }
+
+    pub async fn pull_model(&self, model_name: &str, format: OutputFormat) -> Result<()> {
+        if format == OutputFormat::Pretty {
+            println!("📥 Pulling model '{}'", model_name);
+        }
+
+        use std::io::{self, Write};
+        let mut last_status = String::new();
+
+        self.llm
+            .pull_model(model_name, |progress| match format {
+                OutputFormat::Json => {
+                    if let Ok(line) = serde_json::to_string(progress) {
+                        println!("{}", line);
+                    }
+                }
+                OutputFormat::Pretty => match (progress.total, progress.completed) {
+                    (Some(total), Some(completed)) if total > 0 => {
+                        let ratio = completed as f64 / total as f64;
+                        let filled = (ratio * 30.0) as usize;
+                        print!(
+                            "\r  {} [{}{}] {:>5.1}% ({:.2}/{:.2} GB)",
+                            progress.status,
+                            "█".repeat(filled),
+                            "░".repeat(30 - filled),
+                            ratio * 100.0,
+                            completed as f64 / 1e9,
+                            total as f64 / 1e9
+                        );
+                        let _ = io::stdout().flush();
+                        last_status = progress.status.clone();
+                    }
+                    _ => {
+                        if progress.status != last_status {
+                            if !last_status.is_empty() {
+                                println!();
+                            }
+                            print!("  {}", progress.status);
+                            let _ = io::stdout().flush();
+                            last_status = progress.status.clone();
+                        }
+                    }
+                },
+            })
+            .await?;
+
+        if format == OutputFormat::Pretty {
+            println!("\n✅ Model '{}' is ready", model_name);
+        }
+        Ok(())
+    }
+
+    pub async fn remove_model(&self, model_name: &str, format: OutputFormat) -> Result<()> {
+        self.llm.delete_model(model_name).await?;
+
+        match format {
+            OutputFormat::Json => println!(
+                "{}",
+                serde_json::json!({ "model": model_name, "removed": true })
+            ),
+            OutputFormat::Pretty => println!("🗑️ Removed model '{}'", model_name),
+        }
+        Ok(())
+    }
+
+    pub async fn show_model(&self, model_name: &str, format: OutputFormat) -> Result<()> {
+        let info = self.llm.show_model(model_name).await?;
+
+        match format {
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&info)?),
+            OutputFormat::Pretty => {
+                println!("🧠 Model: {}", info.name);
+                println!("  • Family: {}", info.family);
+                println!("  • Parameters: {}", info.parameter_size);
+                println!("  • Quantization: {}", info.quantization);
+                if let Some(context) = info.context_length {
+                    println!("  • Context: {} tokens", context);
+                }
+                if let Some(size) = info.size_bytes {
+                    println!("  • Size: {:.2} GB", size as f64 / 1e9);
+                }
+                if let Some(modified) = &info.modified_at {
+                    println!("  • Modified: {}", modified);
+                }
+                if !info.parameters.is_empty() {
+                    println!("  • Runtime parameters:");
+                    for line in info.parameters.lines() {
+                        println!("      {}", line);
+                    }
+                }
+            }
+        }
         Ok(())
     }
 
@@ -129,27 +657,315 @@ impl AgentRunner {
         Ok(())
     }
 
-    pub async fn interactive_chat(&self, _environment: &jarvis_shell::Environment) -> Result<()> {
-        println!("💬 Entering interactive chat mode. Type 'exit' to quit.");
+    /// Interactive chat; `resume` is a session id or `last`
+    pub async fn interactive_chat(
+        &self,
+        _environment: &jarvis_shell::Environment,
+        resume: Option<&str>,
+    ) -> Result<()> {
+        let mut session = match resume {
+            Some(selector) => {
+                let session = ChatSession::resume(&self.memory, selector).await?;
+                println!(
+                    "↩️ Resumed '{}' ({} turns)",
+                    session.title(),
+                    session.turns().len()
+                );
+                session
+            }
+            None => ChatSession::start(&self.memory).await?,
+        };
+        println!(
+            "💬 Entering interactive chat mode. Type 'exit' to quit, '/history [page]' to scroll back."
+        );
+        println!("   {}, /attachments, /detach <name>", chat::ATTACH_USAGE);
+        for attachment in session.attachments() {
+            println!("📎 {}", describe_attachment(attachment));
+        }
+        print_pending_actions(&session);
 
-        use std::io::{self, Write};
+        let window = ContextWindowManager::for_router(&self.llm);
 
         loop {
             print!("You: ");
-            io::stdout().flush()?;
+            std::io::Write::flush(&mut std::io::stdout())?;
 
-            let mut input = String::new();
-            io::stdin().read_line(&mut input)?;
-            let input = input.trim();
+            let Some(line) = read_chat_line().await? else {
+                println!();
+                break;
+            };
+            let input = line.trim();
 
+            if input.is_empty() {
+                continue;
+            }
             if input == "exit" {
                 break;
             }
+            if let Some(page) = input.strip_prefix("/history") {
+                print_history(&session, page.trim());
+                continue;
+            }
+            if let Some(command) = ChatCommand::parse(input) {
+                let reply = match command {
+                    Ok(command) => self.chat_command(&mut session, command).await,
+                    Err(e) => Err(e),
+                };
+                match reply {
+                    Ok(reply) => println!("{}\n", reply),
+                    Err(e) => println!("⚠️ {:#}\n", e),
+                }
+                continue;
+            }
 
-            let response = self.llm.generate(input, None).await?;
-            println!("Jarvis: {}\n", response);
+            session.record(&self.memory, MessageRole::User, input).await?;
+            match self.follow_up(&mut session, input).await {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(e) => {
+                    println!("⚠️ {:#}\n", e);
+                    continue;
+                }
+            }
+            let prompt = session.prompt(&window);
+
+            // Dropping the generation future cancels the request; the user turn is already saved
+            tokio::select! {
+                response = self.llm.generate(&prompt, None) => {
+                    let response = response?;
+                    println!("Jarvis: {}\n", response);
+                    session
+                        .record(&self.memory, MessageRole::Assistant, &response)
+                        .await?;
+                    print_pending_actions(&session);
+                }
+                _ = tokio::signal::ctrl_c() => {
+                    println!("\n⏹️ Generation aborted");
+                }
+            }
         }
 
+        println!(
+            "💾 Session saved. Resume with: jarvis chat --resume {}",
+            session.id()
+        );
+        Ok(())
+    }
+
+    /// Carry out the pending actions `input` refers to, recording each
+    /// outcome as a tool turn. False when there is nothing left for the
+    /// model to reply to, as after only skipping.
+    async fn follow_up(&self, session: &mut ChatSession, input: &str) -> Result<bool> {
+        let Some(resolution) =
+            chat_actions::resolve(input, session.actions().pending(), &self.llm).await?
+        else {
+            return Ok(true);
+        };
+
+        for number in &resolution.skip {
+            if let Some(action) = session.actions_mut().remove(*number) {
+                println!("⏭️ Skipped {}", action.describe());
+                let note = format!("Skipped `{}` at the user's request", action.command);
+                session.record(&self.memory, MessageRole::Tool, &note).await?;
+            }
+        }
+        if resolution.run.is_empty() {
+            println!();
+            return Ok(false);
+        }
+
+        for number in &resolution.run {
+            let Some(action) = session.actions_mut().remove(*number) else {
+                continue;
+            };
+            let outcome = self.run_chat_action(&action).await?;
+            session.record(&self.memory, MessageRole::Tool, &outcome).await?;
+        }
+        println!();
+        Ok(true)
+    }
+
+    /// Run a pending action on the target host. Anything that changes the
+    /// system is checked against read-only mode and remote mutation rules,
+    /// then confirmed; the result says what happened, for the conversation.
+    async fn run_chat_action(&self, action: &PendingAction) -> Result<String> {
+        let host = self.executor().host_label();
+        println!("▶️ {}", action.describe());
+
+        if action.risk > Risk::ReadOnly {
+            let operation = format!("run `{}`", action.command);
+            if let Err(e) = self.executor().ensure_mutation_allowed(&operation) {
+                println!("🔒 {:#}", e);
+                return Ok(format!("Not run `{}`: {:#}", action.command, e));
+            }
+            let question = match action.risk {
+                Risk::Dangerous => format!("⚠️ This is dangerous. Run it on {}?", host),
+                _ => format!("Run it on {}?", host),
+            };
+            print!("{} [y/N] ", question);
+            std::io::Write::flush(&mut std::io::stdout())?;
+            let answer = read_chat_line().await?.unwrap_or_default();
+            if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+                println!("Cancelled.");
+                return Ok(format!("Not run `{}`: the user declined", action.command));
+            }
+        }
+
+        let output = tokio::select! {
+            output = action.run(self.executor()) => output,
+            _ = tokio::signal::ctrl_c() => {
+                println!("\n⏹️ Interrupted");
+                return Ok(format!("`{}` was interrupted by the user", action.command));
+            }
+        };
+        match output {
+            Ok(output) => {
+                let report = action.report(&output);
+                let icon = if output.status.success() { "✅" } else { "❌" };
+                println!("{} {}", icon, report);
+                Ok(report)
+            }
+            Err(e) => {
+                println!("❌ {:#}", e);
+                Ok(format!("`{}` could not run: {:#}", action.command, e))
+            }
+        }
+    }
+
+    /// Carry out an attachment command in `session`; the reply says what changed
+    pub async fn chat_command(
+        &self,
+        session: &mut ChatSession,
+        command: ChatCommand,
+    ) -> Result<String> {
+        match command {
+            ChatCommand::Attach { kind, target } => {
+                let attachment = self.attachment(kind, &target).await?;
+                let reply = format!("📎 Attached {}", describe_attachment(&attachment));
+                session.attach(&self.memory, attachment).await?;
+                Ok(reply)
+            }
+            ChatCommand::Attachments => {
+                if session.attachments().is_empty() {
+                    return Ok(format!("📎 No attachments. {}", chat::ATTACH_USAGE));
+                }
+                let lines: Vec<String> = session
+                    .attachments()
+                    .iter()
+                    .map(|a| format!("  • {}", describe_attachment(a)))
+                    .collect();
+                Ok(format!("📎 Attachments:\n{}", lines.join("\n")))
+            }
+            ChatCommand::Detach(name) => {
+                if session.detach(&self.memory, &name).await? {
+                    Ok(format!("📎 Detached '{}'", name))
+                } else {
+                    anyhow::bail!("No attachment named '{}'; /attachments lists them", name)
+                }
+            }
+        }
+    }
+
+    /// Content for `/attach`, summarized when it would crowd the chat
+    pub async fn attachment(&self, kind: AttachmentKind, target: &str) -> Result<Attachment> {
+        let attachment = match kind {
+            AttachmentKind::File => {
+                let path = PathBuf::from(shellexpand::tilde(target).to_string());
+                let content = self.files.read_to_string(&path).await?;
+                let name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_else(|| target.to_string());
+                Attachment::new(&name, kind, &path.display().to_string(), &content)
+            }
+            AttachmentKind::Probe => {
+                let probe = Probe::parse(target).with_context(|| {
+                    let names: Vec<String> = Probe::SIMPLE.iter().map(Probe::name).collect();
+                    format!(
+                        "Unknown probe '{}'; try one of {}, or service status|service logs|unit drift <unit>",
+                        target,
+                        names.join(", ")
+                    )
+                })?;
+                let result = self.tools.probe(probe).await;
+                let name = result.probe.name();
+                let output = match &result.status {
+                    ProbeStatus::Completed => result.output.trim().to_string(),
+                    ProbeStatus::Failed(error) => error.clone(),
+                    ProbeStatus::TimedOut => "no answer in time".to_string(),
+                };
+                let content = format!(
+                    "probe: {}\nstatus: {}\nelapsed: {:.1}s\n\n{}",
+                    name,
+                    result.status.as_str(),
+                    result.elapsed.as_secs_f64(),
+                    output
+                );
+                Attachment::new(&name.replace(' ', "-"), kind, &name, &content)
+            }
+            AttachmentKind::LastOp => {
+                let data = self
+                    .memory
+                    .get_document(chat::LAST_OPERATION_DOCUMENT)
+                    .await?
+                    .context(
+                        "No operation recorded yet; `jarvis arch` records the last one it runs",
+                    )?;
+                let result: serde_json::Value = serde_json::from_str(&data)?;
+                let operation = match &result["operation"] {
+                    serde_json::Value::String(name) => name.clone(),
+                    serde_json::Value::Object(map) => {
+                        map.keys().next().cloned().unwrap_or_default()
+                    }
+                    _ => "operation".to_string(),
+                };
+                let source = format!(
+                    "{} at {}",
+                    operation,
+                    result["executed_at"].as_str().unwrap_or("an unknown time")
+                );
+                Attachment::new(
+                    "last-op",
+                    kind,
+                    &source,
+                    &serde_json::to_string_pretty(&result)?,
+                )
+            }
+        };
+
+        attachment
+            .condense(&self.llm, &ContextWindowManager::for_router(&self.llm))
+            .await
+    }
+
+    pub async fn list_chat_sessions(&self, format: OutputFormat) -> Result<()> {
+        let sessions = self.memory.list_conversations(50).await?;
+
+        match format {
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&sessions)?),
+            OutputFormat::Pretty => {
+                if sessions.is_empty() {
+                    println!("💬 No chat sessions yet");
+                    return Ok(());
+                }
+                println!("💬 Chat sessions:");
+                for session in sessions {
+                    let first = session
+                        .first_message
+                        .as_deref()
+                        .map(|m| preview(m, 60))
+                        .unwrap_or_else(|| "(empty)".to_string());
+                    println!("  • {} — {}", session.id, session.title);
+                    println!(
+                        "      {} turns, started {}, last active {}",
+                        session.message_count,
+                        local_time(session.created_at),
+                        local_time(session.updated_at),
+                    );
+                    println!("      \"{}\"", first);
+                }
+            }
+        }
         Ok(())
     }
 
@@ -269,12 +1085,19 @@ impl AgentRunner {
         Ok(())
     }
 
+    /// `when` is "now", "in 1 hour", "tomorrow 03:00", or any time ahead
+    /// that [`time_range::parse_when`] reads
     pub async fn schedule_maintenance(&self, task_type: &str, when: &str) -> Result<()> {
-        println!("🗓️ Scheduling maintenance task: {} at {}", task_type, when);
+        let at = time_range::parse_when(when)?;
+        println!(
+            "🗓️ Scheduling maintenance task: {} at {}",
+            task_type,
+            local_time(at)
+        );
 
         println!("📅 Maintenance Task Scheduled:");
         println!("  • Task Type: {}", task_type);
-        println!("  • Scheduled: {}", when);
+        println!("  • Scheduled: {}", local_time(at));
         println!("  • Estimated Duration: 30 minutes");
         println!(
             "  • Requires Downtime: {}",
@@ -370,6 +1193,92 @@ impl AgentRunner {
         Ok(())
     }
 
+    /// Events from the local bus within `[bus] recent_minutes`, as a timeline;
+    /// `None` when diagnosing a remote host or no daemon is serving the bus
+    async fn recent_bus_events(&self) -> Option<String> {
+        let config = self.bus.as_ref().filter(|config| config.enabled)?;
+        if self.executor().is_remote() {
+            return None;
+        }
+        let now = chrono::Utc::now();
+        let since = now - chrono::Duration::minutes(config.recent_minutes as i64);
+        match bus::recent_events(&config.socket, since).await {
+            Ok(messages) if !messages.is_empty() => Some(format!(
+                "\n\nRecent events from Jarvis agents:\n{}",
+                bus::render_timeline(&messages, now)
+            )),
+            Ok(_) => None,
+            Err(e) => {
+                tracing::debug!("Skipping event bus context: {:#}", e);
+                None
+            }
+        }
+    }
+
+    /// The target host's profile followed by a blank line, or nothing when
+    /// profiles are off or none has been generated yet. In read-only mode it
+    /// starts with [`read_only::PROMPT_NOTE`] so suggestions are for the user to run.
+    async fn profile_context(&self) -> String {
+        let mut context = String::new();
+        if read_only::is_read_only() {
+            context.push_str(&format!("{}\n\n", read_only::PROMPT_NOTE));
+        }
+        if !self.host_profile.as_ref().is_some_and(|config| config.enabled) {
+            return context;
+        }
+        match HostProfile::load(&self.memory, &self.executor().host_label()).await {
+            Ok(profile) => {
+                if let Some(profile) = profile.prompt_context() {
+                    context.push_str(&format!("{}\n", profile));
+                }
+            }
+            Err(e) => tracing::debug!("Skipping host profile: {:#}", e),
+        }
+        context
+    }
+
+    /// Join `instruction` and `content` into a prompt, condensing `content` if it
+    /// would overflow the backend's context window
+    async fn fit_prompt(&self, instruction: &str, content: &str, query: &str) -> Result<String> {
+        let fitted = ContextWindowManager::for_router(&self.llm)
+            .fit(&self.llm, instruction, content, query)
+            .await?;
+
+        if fitted.final_tokens < fitted.original_tokens {
+            tracing::debug!(
+                "Condensed prompt input from ~{} to ~{} tokens ({} summarized, {} verbatim, {} dropped)",
+                fitted.original_tokens,
+                fitted.final_tokens,
+                fitted.summarized_chunks,
+                fitted.verbatim_chunks,
+                fitted.dropped_chunks
+            );
+        }
+
+        Ok(format!("{}\n{}", instruction, fitted.content))
+    }
+
+    /// Uncommitted changes in the current repo that look relevant to `query`
+    fn relevant_git_changes(&self, query: &str, environment: &jarvis_shell::Environment) -> Option<String> {
+        environment.git_context.as_ref()?;
+        let diffs = match jarvis_shell::git::relevant_diffs(&environment.working_directory, query, 3) {
+            Ok(diffs) => diffs,
+            Err(e) => {
+                tracing::debug!("Skipping git diff context: {}", e);
+                return None;
+            }
+        };
+        if diffs.is_empty() {
+            return None;
+        }
+
+        let mut out = String::from("Relevant Uncommitted Changes:\n");
+        for diff in diffs {
+            out.push_str(&diff.patch);
+        }
+        Some(out)
+    }
+
     async fn gather_context(
         &self,
         _query: &str,
@@ -377,13 +1286,23 @@ impl AgentRunner {
     ) -> Result<String> {
         let mut context = String::new();
 
+        // The local environment says nothing useful about a remote host
+        if self.executor().is_remote() {
+            let summary = self.tools.host_summary().await?;
+            context.push_str(&format!(
+                "Remote Host: {} - {}\n",
+                self.executor().host_label(),
+                summary
+            ));
+            return Ok(context);
+        }
+
         // Add system information
         context.push_str(&format!("System: {}\n", environment.system_info()));
 
         // Add current directory context
         if let Some(git_info) = &environment.git_context {
-            context.push_str(&format!("Git Repository: {}\n", git_info.repo_path));
-            context.push_str(&format!("Branch: {}\n", git_info.current_branch));
+            context.push_str(&git_info.summary());
         }
 
         // Add relevant file contents based on query
@@ -392,3 +1311,192 @@ impl AgentRunner {
         Ok(context)
     }
 }
+
+/// Run cargo in `dir`, returning its stderr as the error on failure
+async fn run_cargo(dir: &Path, args: &[&str]) -> Result<()> {
+    let output = tokio::process::Command::new("cargo")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to run cargo: {}", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("{}", String::from_utf8_lossy(&output.stderr).trim()))
+    }
+}
+
+const HISTORY_PAGE_SIZE: usize = 10;
+
+/// Read a line from stdin; `None` on EOF. Ctrl-C at the prompt does not end
+/// the session, it only reminds how to leave.
+async fn read_chat_line() -> Result<Option<String>> {
+    let mut read = tokio::task::spawn_blocking(|| {
+        let mut line = String::new();
+        let bytes = std::io::stdin().read_line(&mut line)?;
+        Ok::<_, std::io::Error>((bytes > 0).then_some(line))
+    });
+
+    loop {
+        tokio::select! {
+            line = &mut read => return Ok(line??),
+            _ = tokio::signal::ctrl_c() => {
+                print!("\n(type 'exit' or press Ctrl-D to leave)\nYou: ");
+                std::io::Write::flush(&mut std::io::stdout())?;
+            }
+        }
+    }
+}
+
+/// The actions the latest reply proposed, with how to run them
+fn print_pending_actions(session: &ChatSession) {
+    if !session.actions().is_fresh() {
+        return;
+    }
+    println!("💡 Suggested commands:\n{}", session.actions().render());
+    println!("   Reply \"run 1\", \"do it\", or \"skip the dangerous one\" to act on them\n");
+}
+
+/// `/history [page]`, where page 1 is the most recent turns
+fn print_history(session: &ChatSession, page: &str) {
+    let pages = session.page_count(HISTORY_PAGE_SIZE);
+    if pages == 0 {
+        println!("📜 No earlier turns\n");
+        return;
+    }
+
+    let page = match page {
+        "" => 1,
+        page => match page.parse::<usize>() {
+            Ok(page) if (1..=pages).contains(&page) => page,
+            _ => {
+                println!("📜 Usage: /history [1-{}]\n", pages);
+                return;
+            }
+        },
+    };
+
+    println!("📜 History page {}/{}:", page, pages);
+    for turn in session.history_page(page, HISTORY_PAGE_SIZE) {
+        println!(
+            "  [{}] {}: {}",
+            turn.created_at
+                .with_timezone(&chrono::Local)
+                .format("%H:%M"),
+            chat::speaker(&turn.role),
+            preview(&turn.content, 200)
+        );
+    }
+    if page < pages {
+        println!("  (older: /history {})", page + 1);
+    }
+    println!();
+}
+
+/// One line for an attachment chip, e.g.
+/// "probe 'disk-health' from disk health (120 tokens)"
+fn describe_attachment(attachment: &Attachment) -> String {
+    let size = if attachment.summarized {
+        format!(
+            "{} tokens, summarized from {}",
+            attachment.tokens(),
+            attachment.original_tokens
+        )
+    } else {
+        format!("{} tokens", attachment.tokens())
+    };
+    format!(
+        "{} '{}' from {} ({})",
+        attachment.kind.as_str(),
+        attachment.name,
+        attachment.source,
+        size
+    )
+}
+
+fn local_time(time: chrono::DateTime<chrono::Utc>) -> String {
+    time.with_timezone(&chrono::Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+/// First line of `text`, cut to `max_chars`
+fn preview(text: &str, max_chars: usize) -> String {
+    let line = text.lines().next().unwrap_or("").trim();
+    if line.chars().count() > max_chars {
+        format!("{}…", line.chars().take(max_chars).collect::<String>())
+    } else if text.trim().lines().count() > 1 {
+        format!("{} …", line)
+    } else {
+        line.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jarvis_core::llm::cassette::{Cassette, CassetteMode, RECORD_ENV};
+
+    const ISSUE: &str = "laptop fan keeps spinning at full speed after resume";
+
+    /// A runner whose LLM answers come from `cassette` under tests/fixtures
+    async fn replaying(cassette: &str, dir: &Path) -> AgentRunner {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/cassettes")
+            .join(cassette);
+        let cassette = Cassette::open(path, "ollama").unwrap();
+        let mut config = jarvis_core::Config::default();
+        if cassette.mode() == CassetteMode::Replay {
+            // Nothing listens here, so a miss can't reach a real backend
+            config.llm.ollama_url = "http://127.0.0.1:9".to_string();
+        }
+        let llm = LLMRouter::new(&config)
+            .await
+            .unwrap()
+            .with_cassette(cassette);
+        let memory = MemoryStore::new(dir.join("memory.db").to_str().unwrap())
+            .await
+            .unwrap();
+        AgentRunner::new(memory, llm).await.unwrap()
+    }
+
+    /// No git repository, so no diffs end up in the prompt
+    fn environment(dir: &Path) -> jarvis_shell::Environment {
+        jarvis_shell::Environment {
+            working_directory: dir.to_path_buf(),
+            git_context: None,
+            system_info: Default::default(),
+            dotfiles_path: None,
+            arch_info: jarvis_shell::environment::ArchInfo {
+                package_manager: "pacman".to_string(),
+                aur_helper: None,
+                kernel_version: "6.10.0-arch1-1".to_string(),
+                desktop_environment: None,
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fix_issue_replays_recorded_suggestion() {
+        let dir = tempfile::tempdir().unwrap();
+        let runner = replaying("fix_issue.json", dir.path()).await;
+        let environment = environment(dir.path());
+
+        runner
+            .fix_issue(ISSUE, None, &environment, false, true)
+            .await
+            .unwrap();
+        if CassetteMode::from_env() == CassetteMode::Record {
+            return;
+        }
+
+        // A prompt the cassette doesn't know fails instead of going live
+        let issue = "screen flickers on the second monitor";
+        let err = runner
+            .fix_issue(issue, None, &environment, false, true)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains(RECORD_ENV), "{}", err);
+    }
+}
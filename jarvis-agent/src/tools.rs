@@ -1,59 +1,325 @@
+use crate::probes::{self, Probe, ProbeResult};
 use anyhow::Result;
-use std::process::Command;
+use jarvis_core::CommandExecutor;
+use jarvis_core::gpu::{self, GpuThresholds};
+use jarvis_core::health_checks::{self, HealthCheckConfig};
+use jarvis_core::package_files;
+use jarvis_core::sensors::{self, SensorSampler, SensorsConfig};
+use jarvis_core::unit_drift::{self, DriftKind};
+use std::time::Duration;
 
-pub struct SystemTools;
+#[derive(Clone)]
+pub struct SystemTools {
+    executor: CommandExecutor,
+    files_db_max_age: Duration,
+    probe_timeout: Duration,
+    probe_deadline: Duration,
+    sensors: SensorsConfig,
+    health_checks: Vec<HealthCheckConfig>,
+}
 
 impl SystemTools {
     pub async fn new() -> Result<Self> {
-        Ok(Self)
+        Ok(Self::with_executor(CommandExecutor::Local))
     }
 
-    pub async fn diagnose(&self, target: &str) -> Result<String> {
-        let mut output = String::new();
-
-        // Check if it's a systemd service
-        if target.contains("service") || target.contains(".service") {
-            let service_name = target.replace(" service", "").replace(".service", "");
-            output.push_str(&self.check_systemd_service(&service_name).await?);
+    /// Run probes through `executor`, e.g. against a remote host over SSH
+    pub fn with_executor(executor: CommandExecutor) -> Self {
+        Self {
+            executor,
+            files_db_max_age: package_files::DEFAULT_FILES_DB_MAX_AGE,
+            probe_timeout: Duration::from_secs(10),
+            probe_deadline: Duration::from_secs(20),
+            sensors: SensorsConfig::default(),
+            health_checks: Vec::new(),
         }
+    }
 
-        // Check if it's a network interface
-        if target.contains("network") || target.contains("interface") {
-            output.push_str(&self.check_network().await?);
-        }
+    /// Refresh the pacman file database before ownership lookups when it is
+    /// older than `max_age`
+    pub fn with_files_db_max_age(mut self, max_age: Duration) -> Self {
+        self.files_db_max_age = max_age;
+        self
+    }
 
-        // Check if it's about disk/storage
-        if target.contains("disk") || target.contains("btrfs") || target.contains("mount") {
-            output.push_str(&self.check_storage().await?);
-        }
+    pub fn files_db_max_age(&self) -> Duration {
+        self.files_db_max_age
+    }
 
-        Ok(output)
+    /// Give each diagnose or check probe `timeout`, and all of one request's
+    /// probes together `deadline`
+    pub fn with_probe_limits(mut self, timeout: Duration, deadline: Duration) -> Self {
+        self.probe_timeout = timeout;
+        self.probe_deadline = deadline;
+        self
     }
 
-    pub async fn check_status(&self, target: &str) -> Result<String> {
-        let mut output = String::new();
+    pub fn probe_limits(&self) -> (Duration, Duration) {
+        (self.probe_timeout, self.probe_deadline)
+    }
 
-        if target.contains("btrfs") {
-            output.push_str(&self.check_btrfs_status().await?);
+    /// Threshold overrides for the sensors probe
+    pub fn with_sensors(mut self, sensors: SensorsConfig) -> Self {
+        self.sensors = sensors;
+        self
+    }
+
+    pub fn sensors(&self) -> &SensorsConfig {
+        &self.sensors
+    }
+
+    /// Scripts for the custom checks probe
+    pub fn with_health_checks(mut self, health_checks: Vec<HealthCheckConfig>) -> Self {
+        self.health_checks = health_checks;
+        self
+    }
+
+    pub fn health_checks(&self) -> &[HealthCheckConfig] {
+        &self.health_checks
+    }
+
+    pub fn executor(&self) -> &CommandExecutor {
+        &self.executor
+    }
+
+    /// Evidence for diagnosing `target`, from the probes relevant to it
+    pub async fn diagnose(&self, target: &str) -> String {
+        self.run_probes(probes::for_diagnosis(target)).await
+    }
+
+    /// The packages owning files mentioned in `text`, such as paths and
+    /// shared libraries in error messages; empty when none are mentioned
+    pub async fn file_owners(&self, text: &str) -> String {
+        let lines =
+            package_files::describe_mentioned_files(&self.executor, text, self.files_db_max_age)
+                .await;
+        if lines.is_empty() {
+            return String::new();
         }
+        format!("\n\nPackage Ownership:\n- {}", lines.join("\n- "))
+    }
 
-        if target.contains("mount") {
-            output.push_str(&self.check_mounts().await?);
+    /// Status readings for `target`, from the probes relevant to it
+    pub async fn check_status(&self, target: &str) -> String {
+        self.run_probes(probes::for_status(target)).await
+    }
+
+    /// Run one probe under the configured limits
+    pub async fn probe(&self, probe: Probe) -> ProbeResult {
+        self.probe_results(vec![probe]).await.remove(0)
+    }
+
+    /// Run `probes` concurrently under the configured limits
+    async fn run_probes(&self, probes: Vec<Probe>) -> String {
+        probes::render(&self.probe_results(probes).await)
+    }
+
+    async fn probe_results(&self, probes: Vec<Probe>) -> Vec<ProbeResult> {
+        probes::run_all(probes, self.probe_timeout, self.probe_deadline, |probe| {
+            let tools = self.clone();
+            async move { tools.run_probe(&probe).await }
+        })
+        .await
+    }
+
+    async fn run_probe(&self, probe: &Probe) -> Result<String> {
+        match probe {
+            Probe::ServiceStatus(unit) => self.check_systemd_service(unit).await,
+            Probe::ServiceLogs(unit) => self.service_logs(unit).await,
+            Probe::UnitDrift(unit) => Ok(self.unit_drift(unit).await),
+            Probe::FailedServices => self.check_failed_services().await,
+            Probe::JournalErrors => self.journal_errors().await,
+            Probe::Network => self.check_network().await,
+            Probe::Storage => self.check_storage().await,
+            Probe::DiskHealth => self.check_disk_health().await,
+            Probe::BtrfsUsage => self.check_btrfs_status().await,
+            Probe::Mounts => self.check_mounts().await,
+            Probe::Containers => self.describe_containers(None).await,
+            Probe::Packages => self.check_packages().await,
+            Probe::Gpus => self.check_gpus().await,
+            Probe::Sensors => self.check_sensors().await,
+            Probe::CustomChecks => self.check_custom().await,
         }
+    }
+
+    /// Basic identity of the host the probes run on
+    pub async fn host_summary(&self) -> Result<String> {
+        let uname = self.executor.run_stdout("uname", &["-srm"]).await?;
+        let hostname = self
+            .executor
+            .run_stdout("cat", &["/etc/hostname"])
+            .await
+            .unwrap_or_default();
+
+        Ok(format!("{} ({})", hostname.trim(), uname.trim()))
+    }
+
+    /// Names of the service units on the host, without the `.service` suffix
+    pub async fn list_services(&self) -> Result<Vec<String>> {
+        let stdout = self
+            .executor
+            .run_stdout(
+                "systemctl",
+                &[
+                    "list-units",
+                    "--type=service",
+                    "--all",
+                    "--plain",
+                    "--no-legend",
+                    "--no-pager",
+                ],
+            )
+            .await?;
+
+        Ok(stdout
+            .lines()
+            .filter_map(|line| line.split_whitespace().next())
+            .filter_map(|unit| unit.strip_suffix(".service"))
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Names of all Docker containers on the host, running or not
+    pub async fn list_containers(&self) -> Result<Vec<String>> {
+        let stdout = self
+            .executor
+            .run_stdout("docker", &["ps", "--all", "--format", "{{.Names}}"])
+            .await?;
+
+        Ok(stdout
+            .lines()
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// The container table, or one container's state and recent logs
+    pub async fn describe_containers(&self, name: Option<&str>) -> Result<String> {
+        let Some(name) = name else {
+            return self
+                .executor
+                .run_stdout(
+                    "docker",
+                    &[
+                        "ps",
+                        "--all",
+                        "--format",
+                        "table {{.Names}}\t{{.Image}}\t{{.Status}}",
+                    ],
+                )
+                .await;
+        };
+
+        let state = self
+            .executor
+            .run_stdout(
+                "docker",
+                &[
+                    "inspect",
+                    "--format",
+                    "{{.State.Status}} (exit {{.State.ExitCode}}, restarts {{.RestartCount}})",
+                    name,
+                ],
+            )
+            .await?;
+        let logs = self
+            .executor
+            .run("docker", &["logs", "--tail", "50", name])
+            .await?;
 
-        Ok(output)
+        Ok(format!(
+            "{}: {}\nRecent Logs:\n{}{}",
+            name,
+            state.trim(),
+            String::from_utf8_lossy(&logs.stdout),
+            String::from_utf8_lossy(&logs.stderr)
+        ))
     }
 
     async fn check_systemd_service(&self, service: &str) -> Result<String> {
-        let output = Command::new("systemctl")
-            .args(&["status", service])
-            .output()?;
+        let output = self
+            .executor
+            .run("systemctl", &["status", "--no-pager", service])
+            .await?;
 
         Ok(String::from_utf8_lossy(&output.stdout).to_string())
     }
 
+    async fn service_logs(&self, service: &str) -> Result<String> {
+        let output = self
+            .privileged_or_plain("journalctl", &["-u", service, "-n", "50", "--no-pager"])
+            .await?;
+
+        Ok(format!(
+            "\nRecent Logs:\n{}",
+            String::from_utf8_lossy(&output.stdout)
+        ))
+    }
+
+    /// Overrides or an edited vendor file behind the unit; nothing when it is as packaged
+    async fn unit_drift(&self, service: &str) -> String {
+        match unit_drift::inspect_unit(&self.executor, service).await {
+            Ok(drift) if drift.drift != DriftKind::Clean => {
+                format!("\nUnit File Drift:\n{}", drift.summary())
+            }
+            Ok(_) => String::new(),
+            Err(e) => {
+                tracing::debug!("Unit drift check for {} failed: {}", service, e);
+                String::new()
+            }
+        }
+    }
+
+    async fn check_gpus(&self) -> Result<String> {
+        let gpus = gpu::collect(&self.executor).await?;
+        if gpus.is_empty() {
+            return Ok("No discrete GPU with telemetry found\n".to_string());
+        }
+        Ok(format!(
+            "GPUs:\n{}",
+            gpu::render(&gpus, &GpuThresholds::default())
+        ))
+    }
+
+    /// Sensors come straight from sysfs, so only the local host has them.
+    /// Two samples half a second apart give package power.
+    async fn check_sensors(&self) -> Result<String> {
+        if !matches!(self.executor, CommandExecutor::Local) {
+            anyhow::bail!("Sensor readings are only available for the local host");
+        }
+        let mut sampler = SensorSampler::default();
+        sampler.sample(&self.sensors, chrono::Utc::now());
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        let snapshot = sampler.sample(&self.sensors, chrono::Utc::now());
+        if snapshot.is_empty() {
+            return Ok("No hwmon sensors or batteries found\n".to_string());
+        }
+        Ok(format!("Sensors:\n{}", sensors::render(&snapshot, &self.sensors)))
+    }
+
+    /// The `[[health_checks]]` scripts live on this host, so they only
+    /// describe it
+    async fn check_custom(&self) -> Result<String> {
+        if !matches!(self.executor, CommandExecutor::Local) {
+            anyhow::bail!("Custom health checks only run on the local host");
+        }
+        if self.health_checks.is_empty() {
+            return Ok("No [[health_checks]] configured\n".to_string());
+        }
+        let outcomes = health_checks::run_all(
+            &jarvis_core::exec::SystemRunner::default(),
+            &self.health_checks,
+        )
+        .await;
+        Ok(format!(
+            "Custom checks:\n{}",
+            health_checks::render(&outcomes)
+        ))
+    }
+
     async fn check_network(&self) -> Result<String> {
-        let output = Command::new("ip").args(&["addr", "show"]).output()?;
+        let output = self.executor.run("ip", &["addr", "show"]).await?;
 
         Ok(String::from_utf8_lossy(&output.stdout).to_string())
     }
@@ -62,21 +328,16 @@ impl SystemTools {
         let mut result = String::new();
 
         // Check disk usage
-        let df_output = Command::new("df").args(&["-h"]).output()?;
+        let df_output = self.executor.run("df", &["-h"]).await?;
         result.push_str("Disk Usage:\n");
         result.push_str(&String::from_utf8_lossy(&df_output.stdout));
         result.push_str("\n");
 
         // Check if btrfs is available
-        if Command::new("which")
-            .arg("btrfs")
-            .output()?
-            .status
-            .success()
-        {
-            let btrfs_output = Command::new("btrfs")
-                .args(&["filesystem", "show"])
-                .output()?;
+        if self.executor.has_command("btrfs").await {
+            let btrfs_output = self
+                .privileged_or_plain("btrfs", &["filesystem", "show"])
+                .await?;
             result.push_str("Btrfs Filesystems:\n");
             result.push_str(&String::from_utf8_lossy(&btrfs_output.stdout));
         }
@@ -84,17 +345,87 @@ impl SystemTools {
         Ok(result)
     }
 
+    /// Errors logged since boot, for targets that name no specific component
+    async fn journal_errors(&self) -> Result<String> {
+        let output = self
+            .privileged_or_plain("journalctl", &["-p", "err", "-b", "-n", "50", "--no-pager"])
+            .await?;
+
+        Ok(format!(
+            "\nErrors This Boot:\n{}",
+            String::from_utf8_lossy(&output.stdout)
+        ))
+    }
+
+    /// SMART overall health of each drive smartctl can see
+    async fn check_disk_health(&self) -> Result<String> {
+        let scan = self.executor.run_stdout("smartctl", &["--scan"]).await?;
+        let mut result = String::from("Disk Health:\n");
+        for device in scan
+            .lines()
+            .filter_map(|line| line.split_whitespace().next())
+        {
+            let output = self
+                .privileged_or_plain("smartctl", &["-H", device])
+                .await?;
+            let verdict = String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .find(|line| line.contains("overall-health") || line.contains("Health Status"))
+                .map(|line| line.trim().to_string())
+                .unwrap_or_else(|| "no health status reported".to_string());
+            result.push_str(&format!("{}: {}\n", device, verdict));
+        }
+        Ok(result)
+    }
+
     async fn check_btrfs_status(&self) -> Result<String> {
-        let output = Command::new("btrfs")
-            .args(&["filesystem", "usage", "/"])
-            .output()?;
+        let output = self
+            .privileged_or_plain("btrfs", &["filesystem", "usage", "/"])
+            .await?;
+
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    async fn check_failed_services(&self) -> Result<String> {
+        let output = self
+            .executor
+            .run("systemctl", &["list-units", "--failed", "--no-pager"])
+            .await?;
 
         Ok(String::from_utf8_lossy(&output.stdout).to_string())
     }
 
+    async fn check_packages(&self) -> Result<String> {
+        let mut result = String::new();
+
+        let installed = self.executor.run("pacman", &["-Q"]).await?;
+        let count = String::from_utf8_lossy(&installed.stdout).lines().count();
+        result.push_str(&format!("Installed Packages: {}\n", count));
+
+        // Reads the local sync db only; `checkupdates` would need network and a temp db
+        let pending = self.executor.run("pacman", &["-Qu"]).await?;
+        result.push_str("Pending Updates:\n");
+        result.push_str(&String::from_utf8_lossy(&pending.stdout));
+
+        Ok(result)
+    }
+
     async fn check_mounts(&self) -> Result<String> {
-        let output = Command::new("mount").output()?;
+        let output = self.executor.run("mount", &[]).await?;
 
         Ok(String::from_utf8_lossy(&output.stdout).to_string())
     }
+
+    /// Read-only probes that give more detail as root. Use escalation when the
+    /// host has it configured, otherwise fall back to an unprivileged run.
+    async fn privileged_or_plain(&self, program: &str, args: &[&str]) -> Result<std::process::Output> {
+        match self.executor.run_privileged(program, args).await {
+            Ok(output) => Ok(output),
+            Err(e) if self.executor.is_remote() => {
+                tracing::debug!("Falling back to unprivileged {}: {}", program, e);
+                self.executor.run(program, args).await
+            }
+            Err(e) => Err(e),
+        }
+    }
 }
@@ -0,0 +1,864 @@
+//! Wazuh active response: remediation commands from the manager
+//!
+//! [`WazuhIntegration`](crate::WazuhIntegration) only sends events; this
+//! closes the loop. Commands arrive on a local listener, one signed JSON
+//! object per line:
+//!
+//! ```text
+//! {"command":"block_ip","target":"203.0.113.7","issued_at":1760000000,"signature":"9f2c…"}
+//! ```
+//!
+//! where `signature` is the hex HMAC-SHA256 of `command\ntarget\nissued_at`
+//! under the shared secret. When the Wazuh agent runs `jarvis-arch
+//! active-response` as an active-response script instead, the standard JSON
+//! message arrives on stdin and its `extra_args` carry the command and the
+//! shared secret; targets not given there come from the alert. Only commands
+//! enabled in `[wazuh.active_response]` run, and each one that does is
+//! recorded as an operation and announced.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use jarvis_core::exec::{CommandRunner, SystemRunner};
+use jarvis_core::journal::{self, ActionRecord, ActionType};
+use jarvis_core::notify::{Notification, NotifyEvent, NotifySeverity};
+use jarvis_core::types::AuditStatus;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::sync::RwLock;
+
+use crate::config::ActiveResponseConfig;
+use crate::zqlite_integration::{MaintenanceRecord, MaintenanceStatus};
+use crate::{
+    ArchAgent, ArchLinuxAgent, ArchOperation, OperationResult, ServiceOperation, ZQLiteDatabase,
+};
+
+/// Configuration key listing AUR packages quarantined by an active response
+pub const AUR_BLOCKLIST_KEY: &str = "aur_blocklist";
+
+/// Longest request line the listener reads
+const MAX_REQUEST_BYTES: u64 = 16 * 1024;
+
+/// nftables table holding the block sets and the rules that use them
+const NFT_TABLE: &str = "jarvis_active_response";
+
+/// A remediation the manager may ask for
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum ResponseCommand {
+    BlockIp { ip: IpAddr },
+    DisableService { unit: String },
+    Rescan,
+    QuarantineAurPackage { package: String },
+}
+
+impl ResponseCommand {
+    /// Build a command from its wire name and target, validating the target
+    pub fn parse(command: &str, target: Option<&str>) -> Result<Self, Rejection> {
+        let invalid = |reason: &str| Rejection::InvalidTarget {
+            command: command.to_string(),
+            reason: reason.to_string(),
+        };
+        let target = target.map(str::trim).filter(|t| !t.is_empty());
+
+        match command {
+            "block_ip" => {
+                let ip: IpAddr = target
+                    .ok_or_else(|| invalid("an IP address is required"))?
+                    .parse()
+                    .map_err(|_| invalid("not an IP address"))?;
+                if ip.is_loopback() || ip.is_unspecified() || ip.is_multicast() {
+                    return Err(invalid(
+                        "refusing to block a loopback, unspecified, or multicast address",
+                    ));
+                }
+                Ok(Self::BlockIp { ip })
+            }
+            "disable_service" => {
+                let unit = target.ok_or_else(|| invalid("a unit name is required"))?;
+                let valid = !unit.starts_with('-')
+                    && unit.len() <= 255
+                    && unit
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || ":-_.@\\".contains(c));
+                if !valid {
+                    return Err(invalid("not a systemd unit name"));
+                }
+                Ok(Self::DisableService {
+                    unit: normalize_unit(unit),
+                })
+            }
+            "rescan" => Ok(Self::Rescan),
+            "quarantine_aur_package" => {
+                let package = target.ok_or_else(|| invalid("a package name is required"))?;
+                let valid = !package.starts_with(['-', '.'])
+                    && package.chars().all(|c| {
+                        c.is_ascii_lowercase() || c.is_ascii_digit() || "@._+-".contains(c)
+                    });
+                if !valid {
+                    return Err(invalid("not a package name"));
+                }
+                Ok(Self::QuarantineAurPackage {
+                    package: package.to_string(),
+                })
+            }
+            other => Err(Rejection::UnknownCommand(other.to_string())),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::BlockIp { .. } => "block_ip",
+            Self::DisableService { .. } => "disable_service",
+            Self::Rescan => "rescan",
+            Self::QuarantineAurPackage { .. } => "quarantine_aur_package",
+        }
+    }
+
+    pub fn target(&self) -> Option<String> {
+        match self {
+            Self::BlockIp { ip } => Some(ip.to_string()),
+            Self::DisableService { unit } => Some(unit.clone()),
+            Self::Rescan => None,
+            Self::QuarantineAurPackage { package } => Some(package.clone()),
+        }
+    }
+
+    fn enabled(&self, config: &ActiveResponseConfig) -> bool {
+        match self {
+            Self::BlockIp { .. } => config.block_ip,
+            Self::DisableService { .. } => config.disable_service,
+            Self::Rescan => config.rescan,
+            Self::QuarantineAurPackage { .. } => config.quarantine_aur,
+        }
+    }
+
+    /// Alert field holding the target when the stdin message doesn't name one
+    fn alert_field(command: &str) -> Option<&'static str> {
+        match command {
+            "block_ip" => Some("srcip"),
+            "disable_service" => Some("unit"),
+            "quarantine_aur_package" => Some("package_name"),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ResponseCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.target() {
+            Some(target) => write!(f, "{} {}", self.name(), target),
+            None => write!(f, "{}", self.name()),
+        }
+    }
+}
+
+/// `sshd` and `sshd.service` name the same unit
+fn normalize_unit(unit: &str) -> String {
+    if unit.contains('.') {
+        unit.to_string()
+    } else {
+        format!("{}.service", unit)
+    }
+}
+
+/// Why a command was not run
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum Rejection {
+    #[error("Malformed request: {0}")]
+    Malformed(String),
+    #[error("Unknown command '{0}'")]
+    UnknownCommand(String),
+    #[error("Invalid target for {command}: {reason}")]
+    InvalidTarget { command: String, reason: String },
+    #[error("No shared secret configured; refusing all commands")]
+    NoSecret,
+    #[error("Command is not signed")]
+    Unsigned,
+    #[error("Signature does not match")]
+    BadSignature,
+    #[error("Command issued at {0} is outside the allowed clock skew")]
+    Stale(i64),
+    #[error("Command was already received")]
+    Replayed,
+    #[error("{0} is disabled in [wazuh.active_response]")]
+    Disabled(&'static str),
+    #[error("{0} is protected and can't be disabled remotely")]
+    Protected(String),
+}
+
+/// A command as the listener receives it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedCommand {
+    pub command: String,
+    #[serde(default)]
+    pub target: Option<String>,
+    /// Unix time the manager issued the command
+    pub issued_at: i64,
+    /// Hex HMAC-SHA256 of `command\ntarget\nissued_at`
+    #[serde(default)]
+    pub signature: Option<String>,
+}
+
+impl SignedCommand {
+    /// Sign a command the way the manager side does
+    pub fn sign(secret: &str, command: &str, target: Option<&str>, issued_at: i64) -> Self {
+        let mut mac = hmac_for(secret);
+        mac.update(signing_payload(command, target, issued_at).as_bytes());
+        Self {
+            command: command.to_string(),
+            target: target.map(str::to_string),
+            issued_at,
+            signature: Some(hex::encode(mac.finalize().into_bytes())),
+        }
+    }
+
+    fn verify(&self, secret: &str) -> Result<(), Rejection> {
+        let signature = self.signature.as_deref().ok_or(Rejection::Unsigned)?;
+        let signature = hex::decode(signature).map_err(|_| Rejection::BadSignature)?;
+        let mut mac = hmac_for(secret);
+        mac.update(
+            signing_payload(&self.command, self.target.as_deref(), self.issued_at).as_bytes(),
+        );
+        mac.verify_slice(&signature)
+            .map_err(|_| Rejection::BadSignature)
+    }
+}
+
+fn signing_payload(command: &str, target: Option<&str>, issued_at: i64) -> String {
+    format!("{}\n{}\n{}", command, target.unwrap_or(""), issued_at)
+}
+
+fn hmac_for(secret: &str) -> Hmac<Sha256> {
+    Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length")
+}
+
+/// Compare without leaking where the first difference is
+fn secrets_match(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+/// The active-response message `wazuh-execd` writes to a script's stdin
+#[derive(Debug, Deserialize)]
+struct WazuhMessage {
+    command: String,
+    #[serde(default)]
+    parameters: WazuhParameters,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct WazuhParameters {
+    /// `<extra_args>` from the manager: command, shared secret, optional target
+    #[serde(default)]
+    extra_args: Vec<String>,
+    #[serde(default)]
+    alert: serde_json::Value,
+}
+
+/// First string value stored under `key` anywhere inside `value`
+fn find_field<'a>(value: &'a serde_json::Value, key: &str) -> Option<&'a str> {
+    match value {
+        serde_json::Value::Object(map) => map
+            .get(key)
+            .and_then(|v| v.as_str())
+            .or_else(|| map.values().find_map(|v| find_field(v, key))),
+        serde_json::Value::Array(items) => items.iter().find_map(|v| find_field(v, key)),
+        _ => None,
+    }
+}
+
+/// What the sender gets back
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponseReply {
+    /// Whether the command passed verification and was run
+    pub accepted: bool,
+    pub command: Option<String>,
+    pub success: bool,
+    #[serde(default)]
+    pub output: serde_json::Value,
+    pub error: Option<String>,
+}
+
+impl ResponseReply {
+    fn rejected(command: Option<String>, rejection: &Rejection) -> Self {
+        Self {
+            accepted: false,
+            command,
+            success: false,
+            output: serde_json::Value::Null,
+            error: Some(rejection.to_string()),
+        }
+    }
+}
+
+/// One executed response, for the operations log
+#[derive(Debug, Clone, Serialize)]
+pub struct ResponseRecord {
+    pub command: ResponseCommand,
+    /// Peer address of the listener connection, or `wazuh-execd`
+    pub source: String,
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub success: bool,
+    pub output: serde_json::Value,
+    pub error: Option<String>,
+}
+
+impl ResponseRecord {
+    pub fn notification(&self) -> Notification {
+        let (severity, title) = if self.success {
+            (
+                NotifySeverity::Warning,
+                format!("Wazuh active response: {}", self.command),
+            )
+        } else {
+            (
+                NotifySeverity::Critical,
+                format!("Wazuh active response failed: {}", self.command),
+            )
+        };
+        let body = match &self.error {
+            Some(error) => format!("Requested by {}: {}", self.source, error),
+            None => format!(
+                "Requested by {}; completed in {}ms",
+                self.source, self.duration_ms
+            ),
+        };
+        Notification::new(NotifyEvent::ActiveResponse, severity, title, body)
+    }
+}
+
+/// Carries out verified commands
+#[async_trait]
+pub trait ResponseExecutor: Send + Sync {
+    async fn execute(&self, command: &ResponseCommand) -> Result<serde_json::Value>;
+
+    /// Log an executed response as an operation and announce it
+    async fn record(&self, record: &ResponseRecord);
+}
+
+/// Verifies incoming commands and hands the accepted ones to an executor
+pub struct ActiveResponder {
+    config: ActiveResponseConfig,
+    executor: Arc<dyn ResponseExecutor>,
+    /// Signatures seen within the skew window, so a captured command can't be replayed
+    seen: Mutex<HashMap<String, i64>>,
+}
+
+impl ActiveResponder {
+    pub fn new(config: ActiveResponseConfig, executor: Arc<dyn ResponseExecutor>) -> Self {
+        Self {
+            config,
+            executor,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn secret(&self) -> Result<&str, Rejection> {
+        self.config
+            .shared_secret
+            .as_deref()
+            .filter(|s| !s.is_empty())
+            .ok_or(Rejection::NoSecret)
+    }
+
+    /// Check signature, freshness, and target of a listener command
+    pub fn verify_signed(
+        &self,
+        request: &SignedCommand,
+        now: i64,
+    ) -> Result<ResponseCommand, Rejection> {
+        let secret = self.secret()?;
+        request.verify(secret)?;
+
+        let skew = self.config.max_clock_skew_secs as i64;
+        if (now - request.issued_at).abs() > skew {
+            return Err(Rejection::Stale(request.issued_at));
+        }
+        let signature = request.signature.clone().unwrap_or_default();
+        let mut seen = self.seen.lock().unwrap();
+        seen.retain(|_, issued_at| (now - *issued_at).abs() <= skew);
+        if seen.insert(signature, request.issued_at).is_some() {
+            return Err(Rejection::Replayed);
+        }
+        drop(seen);
+
+        self.authorize(ResponseCommand::parse(
+            &request.command,
+            request.target.as_deref(),
+        )?)
+    }
+
+    /// Per-command enable flags and protected units
+    fn authorize(&self, command: ResponseCommand) -> Result<ResponseCommand, Rejection> {
+        if !command.enabled(&self.config) {
+            return Err(Rejection::Disabled(command.name()));
+        }
+        if let ResponseCommand::DisableService { unit } = &command
+            && self
+                .config
+                .protected_services
+                .iter()
+                .any(|p| normalize_unit(p) == *unit)
+        {
+            return Err(Rejection::Protected(unit.clone()));
+        }
+        Ok(command)
+    }
+
+    /// Handle one line received by the listener from `source`
+    pub async fn handle_line(&self, line: &str, source: &str) -> ResponseReply {
+        let request: SignedCommand = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(e) => return self.reject(None, source, Rejection::Malformed(e.to_string())),
+        };
+        match self.verify_signed(&request, Utc::now().timestamp()) {
+            Ok(command) => self.dispatch(command, source).await,
+            Err(rejection) => self.reject(Some(request.command), source, rejection),
+        }
+    }
+
+    /// Handle the stdin message of the active-response script protocol.
+    /// Only `add` acts; `delete` is ignored since blocks expire on their own.
+    pub async fn handle_wazuh_message(&self, message: &str) -> Option<ResponseReply> {
+        const SOURCE: &str = "wazuh-execd";
+
+        let message: WazuhMessage = match serde_json::from_str(message) {
+            Ok(message) => message,
+            Err(e) => return Some(self.reject(None, SOURCE, Rejection::Malformed(e.to_string()))),
+        };
+        if message.command != "add" {
+            tracing::debug!("Ignoring active-response '{}' message", message.command);
+            return None;
+        }
+
+        let args = &message.parameters.extra_args;
+        let Some(command) = args.first() else {
+            return Some(self.reject(
+                None,
+                SOURCE,
+                Rejection::Malformed("extra_args names no command".to_string()),
+            ));
+        };
+        let verified = self.secret().and_then(|secret| match args.get(1) {
+            Some(token) if secrets_match(token, secret) => Ok(()),
+            Some(_) => Err(Rejection::BadSignature),
+            None => Err(Rejection::Unsigned),
+        });
+        if let Err(rejection) = verified {
+            return Some(self.reject(Some(command.clone()), SOURCE, rejection));
+        }
+
+        let target = args.get(2).map(String::as_str).or_else(|| {
+            ResponseCommand::alert_field(command)
+                .and_then(|field| find_field(&message.parameters.alert, field))
+        });
+        match ResponseCommand::parse(command, target).and_then(|c| self.authorize(c)) {
+            Ok(command) => Some(self.dispatch(command, SOURCE).await),
+            Err(rejection) => Some(self.reject(Some(command.clone()), SOURCE, rejection)),
+        }
+    }
+
+    fn reject(&self, command: Option<String>, source: &str, rejection: Rejection) -> ResponseReply {
+        tracing::warn!(
+            "Rejected active response {} from {}: {}",
+            command.as_deref().unwrap_or("<unparsed>"),
+            source,
+            rejection
+        );
+        ResponseReply::rejected(command, &rejection)
+    }
+
+    async fn dispatch(&self, command: ResponseCommand, source: &str) -> ResponseReply {
+        tracing::info!("Running active response {} from {}", command, source);
+        let started_at = Utc::now();
+        let started = std::time::Instant::now();
+        let result = self.executor.execute(&command).await;
+
+        let record = ResponseRecord {
+            command: command.clone(),
+            source: source.to_string(),
+            started_at,
+            duration_ms: started.elapsed().as_millis() as u64,
+            success: result.is_ok(),
+            output: result.as_ref().ok().cloned().unwrap_or_default(),
+            error: result.as_ref().err().map(|e| format!("{:#}", e)),
+        };
+        self.executor.record(&record).await;
+
+        ResponseReply {
+            accepted: true,
+            command: Some(command.name().to_string()),
+            success: record.success,
+            output: record.output,
+            error: record.error,
+        }
+    }
+
+    /// Accept connections until the listener fails; each connection sends
+    /// one command line and gets one reply line
+    pub async fn serve(self: Arc<Self>, listener: TcpListener) -> Result<()> {
+        loop {
+            let (stream, peer) = listener.accept().await?;
+            let responder = self.clone();
+            tokio::spawn(async move {
+                let (reader, mut writer) = stream.into_split();
+                let mut line = String::new();
+                let read = BufReader::new(reader.take(MAX_REQUEST_BYTES))
+                    .read_line(&mut line)
+                    .await;
+                let reply = match read {
+                    Ok(_) => responder.handle_line(line.trim(), &peer.to_string()).await,
+                    Err(e) => responder.reject(
+                        None,
+                        &peer.to_string(),
+                        Rejection::Malformed(e.to_string()),
+                    ),
+                };
+                if let Ok(mut json) = serde_json::to_string(&reply) {
+                    json.push('\n');
+                    if let Err(e) = writer.write_all(json.as_bytes()).await {
+                        tracing::debug!("Could not reply to {}: {}", peer, e);
+                    }
+                }
+            });
+        }
+    }
+}
+
+/// Runs responses through the agent's operations
+pub struct AgentExecutor {
+    agent: Arc<RwLock<ArchLinuxAgent>>,
+    config: ActiveResponseConfig,
+    runner: Arc<dyn CommandRunner>,
+}
+
+impl AgentExecutor {
+    pub fn new(agent: Arc<RwLock<ArchLinuxAgent>>, config: ActiveResponseConfig) -> Self {
+        Self {
+            agent,
+            config,
+            runner: Arc::new(SystemRunner::default()),
+        }
+    }
+}
+
+#[async_trait]
+impl ResponseExecutor for AgentExecutor {
+    async fn execute(&self, command: &ResponseCommand) -> Result<serde_json::Value> {
+        let agent = self.agent.read().await;
+        match command {
+            ResponseCommand::BlockIp { ip } => {
+                block_ip(self.runner.as_ref(), *ip, self.config.block_duration_secs).await
+            }
+            ResponseCommand::DisableService { unit } => {
+                let mut steps = Vec::new();
+                for operation in [ServiceOperation::Stop, ServiceOperation::Disable] {
+                    let result = agent
+                        .execute_operation(ArchOperation::ServiceOperation {
+                            service: unit.clone(),
+                            operation,
+                        })
+                        .await?;
+                    steps.push(operation_output(result)?);
+                }
+                Ok(serde_json::json!({ "unit": unit, "steps": steps }))
+            }
+            ResponseCommand::Rescan => {
+                let vulnerabilities = agent
+                    .execute_operation(ArchOperation::VulnerabilityScan { packages: None })
+                    .await?;
+                let security = agent
+                    .execute_operation(ArchOperation::SecurityScan { full_scan: true })
+                    .await?;
+                // The manager asked, so it gets the fresh AUR findings too
+                if let Some(wazuh) = agent.wazuh_integration() {
+                    wazuh.scan_aur_packages().await?;
+                }
+                Ok(serde_json::json!({
+                    "vulnerability_scan": operation_output(vulnerabilities)?,
+                    "security_scan": operation_output(security)?,
+                }))
+            }
+            ResponseCommand::QuarantineAurPackage { package } => {
+                let database = agent.database().ok_or_else(|| {
+                    anyhow::anyhow!("Database not initialized; can't record the blocklist")
+                })?;
+                let foreign = self.runner.output("pacman", &["-Qmq", package]).await?;
+                if !foreign.status.success() {
+                    anyhow::bail!("{} is not an installed AUR package", package);
+                }
+                let removed = agent
+                    .execute_operation(ArchOperation::RemovePackage {
+                        package: package.clone(),
+                        remove_deps: false,
+                    })
+                    .await?;
+                let removed = operation_output(removed)?;
+                add_to_aur_blocklist(database, package).await?;
+                Ok(serde_json::json!({
+                    "package": package,
+                    "removed": removed,
+                    "blocklisted": true,
+                }))
+            }
+        }
+    }
+
+    async fn record(&self, record: &ResponseRecord) {
+        let agent = self.agent.read().await;
+        if let Some(database) = agent.database() {
+            let entry = MaintenanceRecord {
+                id: uuid::Uuid::new_v4(),
+                operation_type: format!("active_response_{}", record.command.name()),
+                status: if record.success {
+                    MaintenanceStatus::Completed
+                } else {
+                    MaintenanceStatus::Failed
+                },
+                started_at: record.started_at,
+                completed_at: Some(Utc::now()),
+                duration_ms: Some(record.duration_ms),
+                packages_affected: match &record.command {
+                    ResponseCommand::QuarantineAurPackage { package } => vec![package.clone()],
+                    _ => Vec::new(),
+                },
+                output: record.output.to_string(),
+                error_message: record.error.clone(),
+                delta: None,
+                snapshot_id: None,
+                rollback_of: None,
+            };
+            if let Err(e) = database.record_maintenance(&entry).await {
+                tracing::warn!("Failed to record active response: {}", e);
+            }
+        }
+        // The other commands run as agent operations, which journal themselves
+        if let ResponseCommand::BlockIp { ip } = &record.command {
+            let result = if record.success {
+                AuditStatus::Success
+            } else {
+                AuditStatus::Error
+            };
+            journal::emit(
+                &ActionRecord::new(
+                    uuid::Uuid::new_v4().to_string(),
+                    ActionType::Command,
+                    record.command.name(),
+                    ip.to_string(),
+                )
+                .with_result(result),
+            );
+        }
+        agent.notifier().notify(record.notification());
+    }
+}
+
+/// The output of a successful operation, or its error
+fn operation_output(result: OperationResult) -> Result<serde_json::Value> {
+    match result.failure() {
+        None => Ok(serde_json::to_value(&result.output)?),
+        Some(error) => Err(anyhow::anyhow!("{}", error)),
+    }
+}
+
+/// Drop traffic from `ip` for `duration_secs`, creating the table on first use
+pub async fn block_ip(
+    runner: &dyn CommandRunner,
+    ip: IpAddr,
+    duration_secs: u64,
+) -> Result<serde_json::Value> {
+    let table_exists = runner
+        .output("nft", &["list", "table", "inet", NFT_TABLE])
+        .await?
+        .status
+        .success();
+    if !table_exists {
+        for args in [
+            vec!["add", "table", "inet", NFT_TABLE],
+            vec![
+                "add",
+                "set",
+                "inet",
+                NFT_TABLE,
+                "blocked_v4",
+                "{ type ipv4_addr; flags timeout; }",
+            ],
+            vec![
+                "add",
+                "set",
+                "inet",
+                NFT_TABLE,
+                "blocked_v6",
+                "{ type ipv6_addr; flags timeout; }",
+            ],
+            vec![
+                "add",
+                "chain",
+                "inet",
+                NFT_TABLE,
+                "input",
+                "{ type filter hook input priority -10; policy accept; }",
+            ],
+            vec![
+                "add",
+                "rule",
+                "inet",
+                NFT_TABLE,
+                "input",
+                "ip",
+                "saddr",
+                "@blocked_v4",
+                "drop",
+            ],
+            vec![
+                "add",
+                "rule",
+                "inet",
+                NFT_TABLE,
+                "input",
+                "ip6",
+                "saddr",
+                "@blocked_v6",
+                "drop",
+            ],
+        ] {
+            run_nft(runner, &args).await?;
+        }
+    }
+
+    let set = if ip.is_ipv4() {
+        "blocked_v4"
+    } else {
+        "blocked_v6"
+    };
+    let element = format!("{{ {} timeout {}s }}", ip, duration_secs);
+    run_nft(
+        runner,
+        &["add", "element", "inet", NFT_TABLE, set, &element],
+    )
+    .await?;
+    Ok(serde_json::json!({
+        "ip": ip,
+        "table": NFT_TABLE,
+        "set": set,
+        "expires_at": Utc::now() + chrono::Duration::seconds(duration_secs as i64),
+    }))
+}
+
+async fn run_nft(runner: &dyn CommandRunner, args: &[&str]) -> Result<()> {
+    let output = runner
+        .output("nft", args)
+        .await
+        .context("Failed to run nft")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "nft {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// AUR packages an active response quarantined; installing them is refused
+pub async fn aur_blocklist(database: &ZQLiteDatabase) -> Result<Vec<String>> {
+    match database.get_config_value(AUR_BLOCKLIST_KEY).await? {
+        Some(json) => Ok(serde_json::from_str(&json)?),
+        None => Ok(Vec::new()),
+    }
+}
+
+async fn add_to_aur_blocklist(database: &ZQLiteDatabase, package: &str) -> Result<()> {
+    let mut blocklist = aur_blocklist(database).await?;
+    if !blocklist.iter().any(|p| p == package) {
+        blocklist.push(package.to_string());
+        database
+            .set_config_value(
+                AUR_BLOCKLIST_KEY,
+                &serde_json::to_string(&blocklist)?,
+                "AUR packages quarantined by a Wazuh active response",
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jarvis_core::exec::{RecordingRunner, fake_output};
+
+    #[test]
+    fn test_targets_are_validated() {
+        assert_eq!(
+            ResponseCommand::parse("disable_service", Some("cups")).unwrap(),
+            ResponseCommand::DisableService {
+                unit: "cups.service".to_string()
+            }
+        );
+        assert!(matches!(
+            ResponseCommand::parse("block_ip", Some("127.0.0.1")),
+            Err(Rejection::InvalidTarget { .. })
+        ));
+        assert!(matches!(
+            ResponseCommand::parse("disable_service", Some("--root=/ sshd")),
+            Err(Rejection::InvalidTarget { .. })
+        ));
+        assert!(matches!(
+            ResponseCommand::parse("quarantine_aur_package", Some("-Rns")),
+            Err(Rejection::InvalidTarget { .. })
+        ));
+        assert_eq!(
+            ResponseCommand::parse("reboot", None),
+            Err(Rejection::UnknownCommand("reboot".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_block_ip_creates_table_once() {
+        let runner = RecordingRunner::new().respond_with(
+            "nft list table",
+            fake_output(1, "", "No such file or directory"),
+        );
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+        block_ip(&runner, ip, 3600).await.unwrap();
+
+        let calls = runner.calls();
+        assert!(calls.contains(&format!("nft add table inet {}", NFT_TABLE)));
+        assert_eq!(
+            calls.last().unwrap(),
+            &format!(
+                "nft add element inet {} blocked_v4 {{ 203.0.113.7 timeout 3600s }}",
+                NFT_TABLE
+            )
+        );
+
+        // With the table in place only the element is added
+        let runner = RecordingRunner::new();
+        block_ip(&runner, "2001:db8::1".parse().unwrap(), 60)
+            .await
+            .unwrap();
+        assert_eq!(
+            runner.calls(),
+            vec![
+                format!("nft list table inet {}", NFT_TABLE),
+                format!(
+                    "nft add element inet {} blocked_v6 {{ 2001:db8::1 timeout 60s }}",
+                    NFT_TABLE
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,464 @@
+/// Sandboxed AUR builds
+/// Builds AUR packages inside a disposable systemd-nspawn container or
+/// bubblewrap chroot so makepkg never runs in the host environment
+use anyhow::{Result, Context};
+use chrono::Utc;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use crate::config::{AurConfig, SandboxBackend};
+
+const AUR_BASE_URL: &str = "https://aur.archlinux.org";
+
+/// Unprivileged user makepkg runs as inside the sandbox
+const BUILD_USER: &str = "builder";
+const BUILD_UID: u32 = 1000;
+
+/// How serious a PKGBUILD heuristic hit is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum FindingSeverity {
+    Low,
+    Medium,
+    High,
+}
+
+/// A suspicious construct found in a PKGBUILD or install script
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PkgbuildFinding {
+    pub severity: FindingSeverity,
+    pub file: String,
+    pub line: usize,
+    pub rule: String,
+    pub excerpt: String,
+}
+
+/// A package produced by a sandboxed build
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuiltPackage {
+    pub path: PathBuf,
+    pub sha256: String,
+}
+
+/// Result of a sandboxed AUR build
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AurBuildResult {
+    pub package: String,
+    pub backend: SandboxBackend,
+    pub success: bool,
+    pub installed: bool,
+    pub build_log: PathBuf,
+    pub packages: Vec<BuiltPackage>,
+    pub findings: Vec<PkgbuildFinding>,
+    pub duration_ms: u64,
+    pub error: Option<String>,
+}
+
+/// Heuristic rules applied to PKGBUILDs and install scripts
+fn heuristic_rules() -> Vec<(FindingSeverity, &'static str, &'static str)> {
+    vec![
+        (FindingSeverity::High, "pipe-to-shell", r"(curl|wget)[^|]*\|\s*(ba|z)?sh\b"),
+        (FindingSeverity::High, "reverse-shell", r"/dev/tcp/|\bnc\s+(-\w+\s+)*-e\b"),
+        (FindingSeverity::High, "encoded-payload", r"base64\s+(-d|--decode)"),
+        (FindingSeverity::High, "privilege-escalation", r"\bsudo\s|\bsu\s+-c\b"),
+        (FindingSeverity::Medium, "eval", r"\beval\s"),
+        (FindingSeverity::Medium, "world-writable", r"chmod\s+(-R\s+)?[0-7]?777"),
+        (FindingSeverity::Medium, "home-directory-access", r"(\$HOME|~)/\.(ssh|gnupg|config)"),
+        (FindingSeverity::Low, "inline-interpreter", r"\b(python3?|perl|ruby)\s+-[ce]\s"),
+    ]
+}
+
+/// Scan a PKGBUILD or install script for suspicious constructs
+pub fn inspect_pkgbuild(file: &str, contents: &str) -> Vec<PkgbuildFinding> {
+    let rules: Vec<(FindingSeverity, &str, Regex)> = heuristic_rules()
+        .into_iter()
+        .filter_map(|(severity, rule, pattern)| Regex::new(pattern).ok().map(|re| (severity, rule, re)))
+        .collect();
+
+    let mut findings = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('#') {
+            continue;
+        }
+        for (severity, rule, re) in &rules {
+            if re.is_match(trimmed) {
+                findings.push(PkgbuildFinding {
+                    severity: *severity,
+                    file: file.to_string(),
+                    line: index + 1,
+                    rule: rule.to_string(),
+                    excerpt: trimmed.chars().take(200).collect(),
+                });
+            }
+        }
+    }
+    findings
+}
+
+/// Builds AUR packages in a disposable sandbox and installs the result on the host
+#[derive(Debug, Clone)]
+pub struct AurSandbox {
+    config: AurConfig,
+}
+
+impl AurSandbox {
+    pub fn new(config: &AurConfig) -> Self {
+        Self {
+            config: config.clone(),
+        }
+    }
+
+    fn cache_dir(&self) -> &Path {
+        &self.config.sandbox.cache_dir
+    }
+
+    fn base_image(&self) -> PathBuf {
+        self.cache_dir().join("base")
+    }
+
+    /// Clone `package` and run the PKGBUILD heuristics without building
+    pub async fn security_check(&self, package: &str) -> Result<Vec<PkgbuildFinding>> {
+        let workdir = self.cache_dir().join("checks").join(format!("{}-{}", package, uuid::Uuid::new_v4()));
+        tokio::fs::create_dir_all(&workdir).await?;
+
+        let result = async {
+            let source = workdir.join(package);
+            self.clone_package(package, &source, None).await?;
+            self.inspect_sources(&source).await
+        }
+        .await;
+
+        let _ = tokio::fs::remove_dir_all(&workdir).await;
+        result
+    }
+
+    /// Build `package` in the configured sandbox and install the produced packages
+    pub async fn build_and_install(&self, package: &str, no_confirm: bool) -> Result<AurBuildResult> {
+        let start_time = std::time::Instant::now();
+        let backend = self.config.sandbox.backend;
+
+        if backend == SandboxBackend::None && !self.config.sandbox.allow_unsandboxed_builds {
+            return Err(anyhow::anyhow!(
+                "Unsandboxed AUR builds are disabled; set agent.aur.sandbox.allow_unsandboxed_builds to enable them"
+            ));
+        }
+
+        let logs_dir = self.cache_dir().join("logs");
+        tokio::fs::create_dir_all(&logs_dir).await?;
+        let build_log = logs_dir.join(format!("{}-{}.log", package, Utc::now().format("%Y%m%d-%H%M%S")));
+        let mut log = tokio::fs::File::create(&build_log)
+            .await
+            .with_context(|| format!("Failed to create build log {}", build_log.display()))?;
+
+        let build_id = uuid::Uuid::new_v4();
+        let build_dir = self.cache_dir().join("builds").join(format!("{}-{}", package, build_id));
+        let root = build_dir.join("root");
+
+        let mut result = AurBuildResult {
+            package: package.to_string(),
+            backend,
+            success: false,
+            installed: false,
+            build_log: build_log.clone(),
+            packages: Vec::new(),
+            findings: Vec::new(),
+            duration_ms: 0,
+            error: None,
+        };
+
+        let outcome = self
+            .run_build(package, &build_dir, &root, no_confirm, &mut log, &mut result)
+            .await;
+
+        if let Err(e) = &outcome {
+            let _ = log.write_all(format!("\n==> ERROR: {}\n", e).as_bytes()).await;
+            result.error = Some(e.to_string());
+        }
+        let _ = log.flush().await;
+
+        // The container is disposable; built packages were copied out already
+        if let Err(e) = self.remove_build_dir(&build_dir).await {
+            tracing::warn!("Failed to remove build directory {}: {}", build_dir.display(), e);
+        }
+
+        result.success = outcome.is_ok();
+        result.duration_ms = start_time.elapsed().as_millis() as u64;
+        Ok(result)
+    }
+
+    async fn run_build(
+        &self,
+        package: &str,
+        build_dir: &Path,
+        root: &Path,
+        no_confirm: bool,
+        log: &mut tokio::fs::File,
+        result: &mut AurBuildResult,
+    ) -> Result<()> {
+        let backend = self.config.sandbox.backend;
+        tokio::fs::create_dir_all(build_dir).await?;
+
+        // Sources live inside the container root so both backends see them at /build
+        let (source, sandbox_source) = if backend == SandboxBackend::None {
+            (build_dir.join(package), build_dir.join(package))
+        } else {
+            self.ensure_base_image(log).await?;
+            self.clone_base_image(root, log).await?;
+            (root.join("build").join(package), PathBuf::from("/build").join(package))
+        };
+
+        self.clone_package(package, &source, Some(&mut *log)).await?;
+
+        result.findings = self.inspect_sources(&source).await?;
+        let blocking: Vec<&PkgbuildFinding> = result
+            .findings
+            .iter()
+            .filter(|f| f.severity == FindingSeverity::High)
+            .collect();
+        if !blocking.is_empty() {
+            let rules: Vec<String> = blocking.iter().map(|f| format!("{}:{} {}", f.file, f.line, f.rule)).collect();
+            return Err(anyhow::anyhow!(
+                "AUR security check failed for {}: {}",
+                package,
+                rules.join(", ")
+            ));
+        }
+
+        if backend != SandboxBackend::None {
+            self.run_logged(
+                self.sandbox_command(root, &PathBuf::from("/"), true, false, &["chown", "-R", BUILD_USER, "/build"]),
+                "prepare build directory",
+                log,
+            )
+            .await?;
+
+            // Dependencies come from the official repos and need the network
+            let depends = read_srcinfo_depends(&source).await?;
+            if !depends.is_empty() {
+                let mut args = vec!["pacman", "-S", "--needed", "--noconfirm", "--asdeps"];
+                args.extend(depends.iter().map(|d| d.as_str()));
+                self.run_logged(
+                    self.sandbox_command(root, &PathBuf::from("/"), true, true, &args),
+                    "install build dependencies",
+                    log,
+                )
+                .await?;
+            }
+        }
+
+        // Fetch phase: the only makepkg step with network access
+        self.run_logged(
+            self.sandbox_command(root, &sandbox_source, false, true, &["makepkg", "--verifysource", "--nodeps"]),
+            "fetch sources",
+            log,
+        )
+        .await?;
+
+        // Build phase: no network
+        let build = self.run_logged(
+            self.sandbox_command(root, &sandbox_source, false, false, &["makepkg", "--nodeps", "--noconfirm"]),
+            "build package",
+            log,
+        );
+        tokio::time::timeout(std::time::Duration::from_secs(self.config.build_timeout as u64), build)
+            .await
+            .map_err(|_| anyhow::anyhow!("Build timed out after {}s", self.config.build_timeout))??;
+
+        // Copy packages out of the disposable root before it is removed
+        let packages_dir = self.cache_dir().join("packages");
+        tokio::fs::create_dir_all(&packages_dir).await?;
+        let pattern = format!("{}/*.pkg.tar.zst", source.display());
+        for entry in glob::glob(&pattern).context("Invalid package glob")?.filter_map(|e| e.ok()) {
+            let file_name = entry.file_name().context("Package path has no file name")?;
+            let destination = packages_dir.join(file_name);
+            tokio::fs::copy(&entry, &destination).await?;
+            result.packages.push(BuiltPackage {
+                sha256: sha256_file(&destination).await?,
+                path: destination,
+            });
+        }
+        if result.packages.is_empty() {
+            return Err(anyhow::anyhow!("Build produced no packages"));
+        }
+
+        let mut install = Command::new("pacman");
+        install.arg("-U");
+        if no_confirm {
+            install.arg("--noconfirm");
+        }
+        install.args(result.packages.iter().map(|p| p.path.as_os_str()));
+        self.run_logged(install, "install on host", log).await?;
+        result.installed = true;
+
+        tracing::info!(
+            "Built and installed {} in {:?} sandbox ({} packages)",
+            package,
+            backend,
+            result.packages.len()
+        );
+        Ok(())
+    }
+
+    async fn clone_package(&self, package: &str, destination: &Path, log: Option<&mut tokio::fs::File>) -> Result<()> {
+        let mut cmd = Command::new("git");
+        cmd.args(["clone", "--depth", "1"])
+            .arg(format!("{}/{}.git", AUR_BASE_URL, package))
+            .arg(destination);
+
+        match log {
+            Some(log) => self.run_logged(cmd, "clone AUR repository", log).await?,
+            None => {
+                let output = cmd.output().await.context("Failed to run git clone")?;
+                if !output.status.success() {
+                    return Err(anyhow::anyhow!(
+                        "Failed to clone {}: {}",
+                        package,
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    ));
+                }
+            }
+        }
+
+        if !destination.join("PKGBUILD").exists() {
+            return Err(anyhow::anyhow!("{} is not an AUR package (no PKGBUILD)", package));
+        }
+        Ok(())
+    }
+
+    async fn inspect_sources(&self, source: &Path) -> Result<Vec<PkgbuildFinding>> {
+        let mut findings = Vec::new();
+        let pattern = format!("{}/*", source.display());
+        for entry in glob::glob(&pattern).context("Invalid source glob")?.filter_map(|e| e.ok()) {
+            let name = entry.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            if name == "PKGBUILD" || name.ends_with(".install") {
+                let contents = tokio::fs::read_to_string(&entry).await?;
+                findings.extend(inspect_pkgbuild(&name, &contents));
+            }
+        }
+        Ok(findings)
+    }
+
+    /// Create the cached base image on first use
+    async fn ensure_base_image(&self, log: &mut tokio::fs::File) -> Result<()> {
+        let base = self.base_image();
+        if base.join("usr/bin/makepkg").exists() {
+            return Ok(());
+        }
+
+        tracing::info!("Creating AUR build base image at {}", base.display());
+        tokio::fs::create_dir_all(&base).await?;
+
+        let mut pacstrap = Command::new("pacstrap");
+        pacstrap.args(["-c", "-G", "-M"]).arg(&base).args(["base-devel", "git"]);
+        pacstrap.args(&self.config.sandbox.base_packages);
+        self.run_logged(pacstrap, "create base image", log).await?;
+
+        let mut useradd = Command::new("systemd-nspawn");
+        useradd
+            .arg("--quiet")
+            .arg(format!("--directory={}", base.display()))
+            .args(["useradd", "-m", "-u", &BUILD_UID.to_string(), BUILD_USER]);
+        self.run_logged(useradd, "create build user", log).await
+    }
+
+    async fn clone_base_image(&self, root: &Path, log: &mut tokio::fs::File) -> Result<()> {
+        let mut cp = Command::new("cp");
+        cp.args(["-a", "--reflink=auto"]).arg(self.base_image()).arg(root);
+        self.run_logged(cp, "clone base image", log).await?;
+        tokio::fs::create_dir_all(root.join("build")).await?;
+        Ok(())
+    }
+
+    /// Command running `args` in the sandbox at `chdir`
+    fn sandbox_command(&self, root: &Path, chdir: &Path, as_root: bool, network: bool, args: &[&str]) -> Command {
+        match self.config.sandbox.backend {
+            SandboxBackend::Nspawn => {
+                let mut cmd = Command::new("systemd-nspawn");
+                cmd.arg("--quiet")
+                    .arg(format!("--directory={}", root.display()))
+                    .arg(format!("--chdir={}", chdir.display()));
+                if !as_root {
+                    cmd.arg(format!("--user={}", BUILD_USER));
+                }
+                if !network {
+                    cmd.arg("--private-network");
+                }
+                cmd.arg("--").args(args);
+                cmd
+            }
+            SandboxBackend::Bubblewrap => {
+                let mut cmd = Command::new("bwrap");
+                cmd.arg("--bind").arg(root).arg("/")
+                    .args(["--dev", "/dev", "--proc", "/proc", "--tmpfs", "/tmp"])
+                    .args(["--unshare-all", "--die-with-parent"]);
+                if network {
+                    cmd.arg("--share-net").args(["--ro-bind", "/etc/resolv.conf", "/etc/resolv.conf"]);
+                }
+                if !as_root {
+                    cmd.args(["--uid", &BUILD_UID.to_string(), "--gid", &BUILD_UID.to_string()]);
+                }
+                cmd.arg("--chdir").arg(chdir).args(args);
+                cmd
+            }
+            SandboxBackend::None => {
+                let mut cmd = Command::new(args[0]);
+                cmd.current_dir(chdir).args(&args[1..]);
+                cmd
+            }
+        }
+    }
+
+    /// Run `cmd`, appending its output to the build log
+    async fn run_logged(&self, mut cmd: Command, phase: &str, log: &mut tokio::fs::File) -> Result<()> {
+        log.write_all(format!("==> {}\n", phase).as_bytes()).await?;
+
+        let output = cmd
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+            .await
+            .with_context(|| format!("Failed to start {}", phase))?;
+
+        log.write_all(&output.stdout).await?;
+        log.write_all(&output.stderr).await?;
+
+        if !output.status.success() {
+            return Err(anyhow::anyhow!("{} failed ({})", phase, output.status));
+        }
+        Ok(())
+    }
+
+    async fn remove_build_dir(&self, build_dir: &Path) -> Result<()> {
+        if build_dir.exists() {
+            tokio::fs::remove_dir_all(build_dir).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Repo dependencies (depends + makedepends + checkdepends) from .SRCINFO
+async fn read_srcinfo_depends(source: &Path) -> Result<Vec<String>> {
+    let srcinfo = match tokio::fs::read_to_string(source.join(".SRCINFO")).await {
+        Ok(contents) => contents,
+        Err(_) => return Ok(Vec::new()),
+    };
+
+    let mut depends: Vec<String> = srcinfo
+        .lines()
+        .filter_map(|line| line.trim().split_once(" = "))
+        .filter(|(key, _)| matches!(*key, "depends" | "makedepends" | "checkdepends"))
+        .map(|(_, value)| value.to_string())
+        .collect();
+    depends.sort();
+    depends.dedup();
+    Ok(depends)
+}
+
+async fn sha256_file(path: &Path) -> Result<String> {
+    let contents = tokio::fs::read(path).await?;
+    Ok(hex::encode(Sha256::digest(&contents)))
+}
@@ -3,13 +3,20 @@
 use anyhow::{Result, Context};
 use clap::{Parser, Subcommand};
 use jarvis_arch::{
-    ArchLinuxAgent, ArchAgent, ArchOperation, ArchConfig,
-    PackageManager, SystemHealth, SecurityScanner,
-    zqlite_integration::{JarvisDatabase, DatabaseConfig}
+    ArchLinuxAgent, ArchAgent, ArchOperation, ArchConfig, ExecOptions,
+    PackageManager, SystemHealth, SecurityScanner, ActiveResponder, AgentExecutor, RebuildReport,
+    zqlite_integration::{JarvisDatabase, DatabaseConfig},
+    config::{ActiveResponseConfig, MaintenanceScheduleConfig as MaintenanceSchedule, ScheduledTask, ShutdownConfig},
+    maintenance_guard::SkipNotices,
+    maintenance_chain::ChainPreset,
+    shutdown::{self, DrainStep, ShutdownOutcome},
 };
+use jarvis_core::exec::{CommandRunner, OutputText, SystemRunner};
+use jarvis_core::notify::{HealthTransitions, Notification, NotifyEvent, NotifySeverity};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::sync::Arc;
+use tokio::signal::unix::{SignalKind, signal};
 use tokio::sync::RwLock;
 use tracing::{info, warn, error};
 use uuid::Uuid;
@@ -33,6 +40,14 @@ struct Cli {
     /// Run as daemon (systemd service mode)
     #[arg(short = 'D', long)]
     daemon: bool,
+    
+    /// Show what package and maintenance commands would do without changing anything
+    #[arg(long, global = true)]
+    dry_run: bool,
+    
+    /// Inspect and report but refuse every operation that changes the system
+    #[arg(long, global = true)]
+    read_only: bool,
 }
 
 #[derive(Subcommand)]
@@ -67,6 +82,9 @@ enum Commands {
         #[command(subcommand)]
         operation: AgentCommands,
     },
+    
+    /// Handle one Wazuh active-response message from stdin (run by wazuh-execd)
+    ActiveResponse,
 }
 
 #[derive(Subcommand)]
@@ -78,6 +96,12 @@ enum PackageCommands {
         /// Include AUR packages
         #[arg(long)]
         aur: bool,
+        /// Update Flatpak refs instead of pacman packages
+        #[arg(long)]
+        flatpak: bool,
+        /// Skip the pre-flight confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
     },
     
     /// Install package
@@ -87,6 +111,9 @@ enum PackageCommands {
         /// Install from AUR
         #[arg(long)]
         aur: bool,
+        /// Skip the pre-flight confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
     },
     
     /// Remove package
@@ -96,6 +123,9 @@ enum PackageCommands {
         /// Remove dependencies
         #[arg(long)]
         deps: bool,
+        /// Skip the pre-flight confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
     },
     
     /// Search packages
@@ -112,10 +142,17 @@ enum PackageCommands {
         /// Filter by repository
         #[arg(long)]
         repo: Option<String>,
+        /// List Flatpak apps and runtimes instead
+        #[arg(long)]
+        flatpak: bool,
     },
     
     /// Check for updates
-    Check,
+    Check {
+        /// Check Flatpak remotes instead
+        #[arg(long)]
+        flatpak: bool,
+    },
     
     /// Clean package cache
     Clean {
@@ -146,9 +183,9 @@ enum HealthCommands {
         /// Service name
         #[arg(long)]
         service: Option<String>,
-        /// Hours to analyze
-        #[arg(long, default_value = "24")]
-        hours: u32,
+        /// Time range to analyze, e.g. 24h, yesterday, since boot
+        #[arg(long, default_value = "24h")]
+        since: String,
     },
 }
 
@@ -193,6 +230,25 @@ enum AgentCommands {
     
     /// Run health check
     Health,
+    
+    /// Run a scheduled maintenance task now
+    RunTask {
+        /// update, clean, security-scan, stage-updates, apply-staged-updates,
+        /// backup-configs, or rebuild-aur
+        task: ScheduledTask,
+        /// Skip the health and disk space checks
+        #[arg(long)]
+        emergency: bool,
+    },
+
+    /// Run a composite maintenance preset now
+    RunPreset {
+        /// full-update, security-audit, or deep-clean
+        preset: ChainPreset,
+        /// Skip the health and disk space checks
+        #[arg(long)]
+        emergency: bool,
+    },
 }
 
 /// Service configuration
@@ -213,15 +269,8 @@ struct ServiceSettings {
     pub enable_systemd_notifications: bool,
     pub health_check_interval_seconds: u64,
     pub maintenance_schedule: MaintenanceSchedule,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct MaintenanceSchedule {
-    pub auto_update: bool,
-    pub update_schedule: String, // Cron expression
-    pub auto_clean: bool,
-    pub clean_schedule: String,
-    pub security_scan_schedule: String,
+    #[serde(default)]
+    pub shutdown: ShutdownConfig,
 }
 
 /// Main service manager
@@ -248,13 +297,14 @@ async fn main() -> Result<()> {
     // Load configuration
     let config = load_config(&cli.config).await
         .with_context(|| format!("Failed to load config from {:?}", cli.config))?;
+    jarvis_core::read_only::configure(config.agent.read_only || cli.read_only);
     
     match cli.command {
         Commands::Service { systemd_fd } => {
             run_service(config, systemd_fd).await
         }
         Commands::Package { operation } => {
-            run_package_command(config, operation).await
+            run_package_command(config, operation, ExecOptions { dry_run: cli.dry_run }).await
         }
         Commands::Health { operation } => {
             run_health_command(config, operation).await
@@ -265,6 +315,9 @@ async fn main() -> Result<()> {
         Commands::Agent { operation } => {
             run_agent_command(config, operation).await
         }
+        Commands::ActiveResponse => {
+            run_active_response_script(config).await
+        }
     }
 }
 
@@ -299,6 +352,7 @@ async fn run_service(config: ServiceConfig, _systemd_fd: Option<i32>) -> Result<
     // Initialize agent
     let mut agent = ArchLinuxAgent::new();
     agent.initialize(config.agent.clone()).await?;
+    let operations = agent.operations();
     let agent = Arc::new(RwLock::new(agent));
     
     let service = JarvisService {
@@ -312,25 +366,41 @@ async fn run_service(config: ServiceConfig, _systemd_fd: Option<i32>) -> Result<
     let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel();
     
     // Start service components
-    let health_check_task = start_health_monitor(agent.clone(), config.service.health_check_interval_seconds);
-    let maintenance_task = start_maintenance_scheduler(agent.clone(), config.service.maintenance_schedule.clone());
+    let health_check_task = start_health_monitor(agent.clone(), config.service.health_check_interval_seconds).await;
+    let checkpoint = Arc::new(std::sync::Mutex::new(
+        load_scheduler_checkpoint(&*database.read().await).await,
+    ));
+    let maintenance_task = start_maintenance_scheduler(
+        agent.clone(),
+        config.service.maintenance_schedule.clone(),
+        checkpoint.clone(),
+    )
+    .await;
     let metrics_task = if config.service.enable_metrics {
-        Some(start_metrics_server(config.service.metrics_port))
+        Some(start_metrics_server(config.service.metrics_port).await)
+    } else {
+        None
+    };
+    let active_response_task = if config.agent.wazuh.active_response.enabled {
+        Some(start_active_response_listener(agent.clone(), config.agent.wazuh.active_response.clone()).await?)
     } else {
         None
     };
     
     // Notify systemd that we're ready
-    if config.service.enable_systemd_notifications {
+    let notify = config.service.enable_systemd_notifications;
+    if notify {
         #[cfg(target_os = "linux")]
         {
-            systemd_notify_ready().await?;
+            systemd_notify("READY=1")?;
+            info!("Notified systemd that service is ready");
         }
     }
     
     info!("Jarvis service is running and ready");
     
-    // Wait for shutdown signal
+    // `systemctl stop` sends SIGTERM
+    let mut sigterm = signal(SignalKind::terminate())?;
     tokio::select! {
         _ = shutdown_rx => {
             info!("Received shutdown signal");
@@ -338,54 +408,156 @@ async fn run_service(config: ServiceConfig, _systemd_fd: Option<i32>) -> Result<
         _ = tokio::signal::ctrl_c() => {
             info!("Received Ctrl+C, shutting down");
         }
+        _ = sigterm.recv() => {
+            info!("Received SIGTERM, shutting down");
+        }
     }
     
-    // Graceful shutdown
+    // Staged shutdown; see jarvis_arch::shutdown. systemd SIGKILLs everything
+    // left, pacman included, once TimeoutStopSec (90s in jarvis-arch.service)
+    // runs out, so the drain and grace timeouts must fit inside it and a
+    // running transaction keeps pushing that deadline back.
     info!("Shutting down Jarvis service...");
+    if notify {
+        #[cfg(target_os = "linux")]
+        notify_or_warn("STOPPING=1");
+    }
     
-    // Cancel all tasks
+    // Nothing new starts: monitoring stops and the scheduler sees the closed registry
     health_check_task.abort();
-    maintenance_task.abort();
     if let Some(task) = metrics_task {
         task.abort();
     }
     
-    // Shutdown agent
-    agent.write().await.shutdown().await?;
+    let extend_timeout = format!(
+        "EXTEND_TIMEOUT_USEC={}",
+        (shutdown::PROGRESS_INTERVAL * 3).as_micros()
+    );
+    let second_request = async {
+        tokio::select! {
+            _ = sigterm.recv() => {}
+            _ = tokio::signal::ctrl_c() => {}
+        }
+    };
+    let report = shutdown::drain(&operations, &config.service.shutdown, second_request, |step| {
+        // Buys time from systemd only while a transaction must not be killed
+        if notify && matches!(step, DrainStep::WaitingOnTransactions { .. }) {
+            #[cfg(target_os = "linux")]
+            notify_or_warn(&extend_timeout);
+        }
+    })
+    .await;
+    
+    maintenance_task.abort();
+    if let Some(task) = active_response_task {
+        task.abort();
+    }
+    
+    // Abandoned operations may still hold the agent; don't wait on them forever
+    match tokio::time::timeout(std::time::Duration::from_secs(5), agent.write()).await {
+        Ok(mut agent) => {
+            if let Err(e) = agent.shutdown().await {
+                error!("Agent shutdown failed: {}", e);
+            }
+        }
+        Err(_) => error!("Agent still busy with abandoned operations; skipping its shutdown"),
+    }
+    
+    let last_check = *checkpoint.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Err(e) = save_scheduler_checkpoint(&*database.read().await, last_check).await {
+        warn!("Scheduler checkpoint not saved: {}", e);
+    }
     
     // Close database
     database.write().await.close().await?;
     
-    info!("Jarvis service shutdown complete");
-    Ok(())
+    match report.outcome {
+        ShutdownOutcome::Clean => {
+            info!("Jarvis service shutdown complete");
+            Ok(())
+        }
+        ShutdownOutcome::Forced => {
+            warn!("Jarvis service shutdown forced");
+            std::process::exit(report.outcome.exit_code());
+        }
+    }
+}
+
+/// Configuration key holding the last time the scheduler checked for due tasks
+const SCHEDULER_CHECKPOINT_KEY: &str = "scheduler_checkpoint";
+
+/// Where the scheduler picks up: the saved checkpoint when it is recent, so
+/// tasks that fell due during a restart still run, otherwise now
+async fn load_scheduler_checkpoint(database: &JarvisDatabase) -> chrono::DateTime<chrono::Utc> {
+    let now = chrono::Utc::now();
+    let saved = match database.get_config_value(SCHEDULER_CHECKPOINT_KEY).await {
+        Ok(saved) => saved,
+        Err(e) => {
+            warn!("Scheduler checkpoint unavailable: {}", e);
+            None
+        }
+    };
+    saved
+        .and_then(|saved| chrono::DateTime::parse_from_rfc3339(&saved).ok())
+        .map(|saved| saved.with_timezone(&chrono::Utc))
+        // A long outage shouldn't replay a backlog of maintenance at once
+        .filter(|saved| *saved <= now && now - *saved <= chrono::Duration::hours(1))
+        .unwrap_or(now)
 }
 
-async fn run_package_command(config: ServiceConfig, operation: PackageCommands) -> Result<()> {
+async fn save_scheduler_checkpoint(
+    database: &JarvisDatabase,
+    last_check: chrono::DateTime<chrono::Utc>,
+) -> Result<()> {
+    database
+        .set_config_value(
+            SCHEDULER_CHECKPOINT_KEY,
+            &last_check.to_rfc3339(),
+            "Last time the maintenance scheduler checked for due tasks",
+        )
+        .await
+}
+
+async fn run_package_command(
+    config: ServiceConfig,
+    operation: PackageCommands,
+    options: ExecOptions,
+) -> Result<()> {
     let mut agent = ArchLinuxAgent::new();
     agent.initialize(config.agent).await?;
     
+    let mut skip_confirm = false;
     let arch_operation = match operation {
-        PackageCommands::Update { packages, aur: _ } => {
+        PackageCommands::Update { packages, aur: _, flatpak, yes } => {
+            skip_confirm = yes;
             let packages = if packages.is_empty() { None } else { Some(packages) };
-            ArchOperation::UpdatePackages { packages }
+            if flatpak {
+                ArchOperation::UpdateFlatpaks { refs: packages }
+            } else {
+                ArchOperation::UpdatePackages { packages }
+            }
         }
-        PackageCommands::Install { package, aur } => {
+        PackageCommands::Install { package, aur, yes } => {
+            skip_confirm = yes;
             ArchOperation::InstallPackage { package, from_aur: aur }
         }
-        PackageCommands::Remove { package, deps } => {
+        PackageCommands::Remove { package, deps, yes } => {
+            skip_confirm = yes;
             ArchOperation::RemovePackage { package, remove_deps: deps }
         }
         PackageCommands::Search { query, aur } => {
             ArchOperation::SearchPackages { query, include_aur: aur }
         }
-        PackageCommands::List { repo: _ } => {
+        PackageCommands::List { flatpak: true, .. } => ArchOperation::ListFlatpaks,
+        PackageCommands::Check { flatpak: true } => ArchOperation::CheckFlatpakUpdates,
+        PackageCommands::List { repo: _, flatpak: false } => {
             // Convert to appropriate operation
             ArchOperation::CustomCommand { 
                 command: "pacman".to_string(), 
                 args: vec!["-Q".to_string()] 
             }
         }
-        PackageCommands::Check => {
+        PackageCommands::Check { flatpak: false } => {
             ArchOperation::CustomCommand { 
                 command: "pacman".to_string(), 
                 args: vec!["-Qu".to_string()] 
@@ -399,12 +571,42 @@ async fn run_package_command(config: ServiceConfig, operation: PackageCommands)
         }
     };
     
-    let result = agent.execute_operation(arch_operation).await?;
+    // Nothing changes in a dry run, so there is nothing to confirm
+    if !skip_confirm && !options.dry_run {
+        if let Some(report) = agent.preflight(&arch_operation).await? {
+            println!("{}", report.render());
+            if !confirm("Proceed?")? {
+                println!("Aborted; nothing was changed.");
+                return Ok(());
+            }
+        } else if let ArchOperation::UpdateFlatpaks { .. } = &arch_operation {
+            let pending = agent.execute_operation(ArchOperation::CheckFlatpakUpdates).await?;
+            println!("{}", serde_json::to_string_pretty(&pending.output)?);
+            if !confirm("Apply these Flatpak updates?")? {
+                println!("Aborted; nothing was changed.");
+                return Ok(());
+            }
+        }
+    }
+    
+    let result = agent.execute_operation_with_options(arch_operation, options).await?;
     println!("{}", serde_json::to_string_pretty(&result)?);
     
     Ok(())
 }
 
+/// Ask a yes/no question on the terminal, defaulting to no
+fn confirm(question: &str) -> Result<bool> {
+    use std::io::Write;
+    
+    print!("{} [y/N] ", question);
+    std::io::stdout().flush()?;
+    
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
 async fn run_health_command(config: ServiceConfig, operation: HealthCommands) -> Result<()> {
     let mut agent = ArchLinuxAgent::new();
     agent.initialize(config.agent).await?;
@@ -416,8 +618,8 @@ async fn run_health_command(config: ServiceConfig, operation: HealthCommands) ->
         HealthCommands::Monitor { duration } => {
             ArchOperation::PerformanceAnalysis { duration_minutes: duration }
         }
-        HealthCommands::Logs { service, hours } => {
-            ArchOperation::LogAnalysis { service, hours }
+        HealthCommands::Logs { service, since } => {
+            ArchOperation::LogAnalysis { service, since }
         }
     };
     
@@ -455,7 +657,14 @@ async fn run_agent_command(config: ServiceConfig, operation: AgentCommands) -> R
     match operation {
         AgentCommands::Status => {
             // Check if service is running
-            let status = check_service_status().await?;
+            let mut status = check_service_status().await?;
+
+            // What this host's agent can do, so an orchestrator knows what to ask for
+            let mut agent = ArchLinuxAgent::new();
+            agent.initialize(config.agent).await?;
+            let capabilities: Vec<&str> = agent.capabilities().into_iter().map(|c| c.name()).collect();
+            status["capabilities"] = serde_json::json!(capabilities);
+            status["subsystems"] = serde_json::to_value(agent.subsystems())?;
             println!("{}", serde_json::to_string_pretty(&status)?);
         }
         AgentCommands::Start => {
@@ -476,6 +685,23 @@ async fn run_agent_command(config: ServiceConfig, operation: AgentCommands) -> R
             let health = agent.health_check().await?;
             println!("{}", serde_json::to_string_pretty(&health)?);
         }
+        AgentCommands::RunTask { task, emergency } => {
+            let mut agent = ArchLinuxAgent::new();
+            agent.initialize(config.agent).await?;
+            run_scheduled_task(&agent, task, emergency, &mut SkipNotices::default()).await;
+        }
+        AgentCommands::RunPreset { preset, emergency } => {
+            let mut agent = ArchLinuxAgent::new();
+            agent.initialize(config.agent).await?;
+            if let Some(result) =
+                run_scheduled_preset(&agent, preset, emergency, &mut SkipNotices::default()).await
+            {
+                println!("{}", serde_json::to_string_pretty(&result)?);
+                if !result.success {
+                    std::process::exit(1);
+                }
+            }
+        }
     }
     
     Ok(())
@@ -487,19 +713,60 @@ async fn start_health_monitor(
 ) -> tokio::task::JoinHandle<()> {
     tokio::spawn(async move {
         let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(interval_seconds));
+        let mut transitions = HealthTransitions::new();
         
         loop {
             interval.tick().await;
             
-            match agent.read().await.health_check().await {
-                Ok(health) => {
-                    if matches!(health.status, jarvis_arch::HealthStatus::Critical) {
+            let agent = agent.read().await;
+            let health = agent.health_check().await;
+            let severity = match &health {
+                Ok(health) => match health.status {
+                    jarvis_arch::HealthStatus::Critical => {
                         error!("Critical health issue detected: {:?}", health);
-                        // Could trigger alerts or auto-recovery here
+                        NotifySeverity::Critical
                     }
-                }
+                    jarvis_arch::HealthStatus::Warning => NotifySeverity::Warning,
+                    _ => NotifySeverity::Info,
+                },
                 Err(e) => {
                     error!("Health check failed: {}", e);
+                    NotifySeverity::Critical
+                }
+            };
+            
+            // Only report entering Warning/Critical, not every check that stays there
+            if transitions.observe("system", severity).is_some() {
+                agent.notifier().notify(Notification::new(
+                    NotifyEvent::HealthChanged,
+                    severity,
+                    format!("System health: {:?}", severity),
+                    "Run `jarvis-arch health check` for details",
+                ));
+            }
+            
+            // Packages waiting on a rebuild are reported on their own, since
+            // they leave the overall status alone
+            if let Ok(health) = &health {
+                let rebuilds = RebuildReport {
+                    checked_at: health.last_check,
+                    packages: health.soname_rebuilds.clone(),
+                };
+                let severity = if rebuilds.is_empty() {
+                    NotifySeverity::Info
+                } else {
+                    NotifySeverity::Warning
+                };
+                if transitions.observe("aur_rebuilds", severity).is_some() {
+                    agent.notifier().notify(Notification::new(
+                        NotifyEvent::HealthChanged,
+                        severity,
+                        "AUR packages need a rebuild",
+                        format!(
+                            "{}. Run `jarvis-arch agent run-task rebuild-aur` to rebuild them in the sandbox",
+                            rebuilds.summary()
+                        ),
+                    ));
                 }
             }
         }
@@ -508,36 +775,236 @@ async fn start_health_monitor(
 
 async fn start_maintenance_scheduler(
     agent: Arc<RwLock<ArchLinuxAgent>>, 
-    schedule: MaintenanceSchedule
+    schedule: MaintenanceSchedule,
+    checkpoint: Arc<std::sync::Mutex<chrono::DateTime<chrono::Utc>>>,
 ) -> tokio::task::JoinHandle<()> {
     tokio::spawn(async move {
-        // This would implement cron-like scheduling
-        // For now, just a placeholder
-        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(3600)); // 1 hour
+        // Check the cron schedules once a minute and run whatever fell due
+        let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(60));
+        let operations = agent.read().await.operations();
+        let mut last_check = *checkpoint.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut skip_notices = SkipNotices::default();
         
         loop {
             interval.tick().await;
+            // Shutting down: leave what is due to the next start
+            if operations.is_closed() {
+                break;
+            }
+            let now = chrono::Utc::now();
             
-            if schedule.auto_update {
-                info!("Running scheduled package update");
-                let operation = ArchOperation::UpdatePackages { packages: None };
-                match agent.read().await.execute_operation(operation).await {
-                    Ok(result) => {
-                        if result.success {
-                            info!("Scheduled update completed successfully");
-                        } else {
-                            warn!("Scheduled update failed: {:?}", result.error);
-                        }
-                    }
-                    Err(e) => {
-                        error!("Scheduled update error: {}", e);
-                    }
-                }
+            for task in schedule.due_between(last_check, now) {
+                let agent = agent.read().await;
+                run_scheduled_task(&agent, task, false, &mut skip_notices).await;
             }
+            for preset in schedule.presets_due_between(last_check, now) {
+                let agent = agent.read().await;
+                run_scheduled_preset(&agent, preset, false, &mut skip_notices).await;
+            }
+            
+            last_check = now;
+            *checkpoint.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = now;
         }
     })
 }
 
+/// Run one maintenance task and notify about the outcome. Unless `emergency`
+/// is set, the task is skipped when system health or free space say it
+/// should not run; skips are recorded and announced at most once a day.
+async fn run_scheduled_task(
+    agent: &ArchLinuxAgent,
+    task: ScheduledTask,
+    emergency: bool,
+    skip_notices: &mut SkipNotices,
+) {
+    if emergency {
+        warn!("Emergency run of {:?}; skipping health checks", task);
+    } else if skip_unhealthy(
+        agent,
+        task,
+        task.name(),
+        &format!("jarvis-arch agent run-task {} --emergency", task.name()),
+        skip_notices,
+    )
+    .await
+    {
+        return;
+    }
+    
+    let operation = match task {
+        ScheduledTask::Update => ArchOperation::UpdatePackages { packages: None },
+        ScheduledTask::Clean => ArchOperation::SystemCleanup { clean_cache: true, clean_logs: true },
+        ScheduledTask::SecurityScan => ArchOperation::SecurityScan { full_scan: false },
+        ScheduledTask::StageUpdates => ArchOperation::StageUpdates,
+        ScheduledTask::ApplyStagedUpdates => ArchOperation::ApplyStagedUpdates,
+        ScheduledTask::BackupConfigs => ArchOperation::BackupConfigs {
+            destination: agent.config_backup().destination.display().to_string(),
+        },
+        ScheduledTask::RebuildAur => ArchOperation::RebuildAurPackages { packages: None },
+    };
+    
+    info!("Running scheduled maintenance task: {:?}", task);
+    let notification = match agent.execute_operation(operation).await {
+        Ok(result) if result.succeeded() => {
+            info!("Scheduled {:?} completed successfully", task);
+            Notification::new(
+                NotifyEvent::MaintenanceFinished,
+                NotifySeverity::Info,
+                format!("Scheduled {:?} finished", task),
+                format!("Completed in {:.1}s", result.duration_ms as f64 / 1000.0),
+            )
+        }
+        Ok(result) => {
+            warn!("Scheduled {:?} failed: {:?}", task, result.output);
+            Notification::new(
+                NotifyEvent::MaintenanceFailed,
+                NotifySeverity::Warning,
+                format!("Scheduled {:?} failed", task),
+                result
+                    .failure()
+                    .unwrap_or_else(|| "See the service log for details".to_string()),
+            )
+        }
+        Err(e) => {
+            error!("Scheduled {:?} error: {}", task, e);
+            Notification::new(
+                NotifyEvent::MaintenanceFailed,
+                NotifySeverity::Critical,
+                format!("Scheduled {:?} errored", task),
+                e.to_string(),
+            )
+        }
+    };
+    agent.notifier().notify(notification);
+}
+
+/// Run a composite preset under the same health and disk space checks as
+/// the scheduled task it stands in for; `None` when it was skipped
+async fn run_scheduled_preset(
+    agent: &ArchLinuxAgent,
+    preset: ChainPreset,
+    emergency: bool,
+    skip_notices: &mut SkipNotices,
+) -> Option<jarvis_arch::ChainResult> {
+    if emergency {
+        warn!("Emergency run of {}; skipping health checks", preset.name());
+    } else if skip_unhealthy(
+        agent,
+        preset.guarded_as(),
+        preset.name(),
+        &format!("jarvis-arch agent run-preset {} --emergency", preset.name()),
+        skip_notices,
+    )
+    .await
+    {
+        return None;
+    }
+
+    info!("Running maintenance preset: {}", preset.name());
+    let result = agent.run_preset(preset).await;
+    let failed = result.failed_steps().count();
+    let notification = match (result.success, &result.aborted_at) {
+        (true, _) => Notification::new(
+            NotifyEvent::MaintenanceFinished,
+            NotifySeverity::Info,
+            format!("{} finished", preset.name()),
+            format!(
+                "{} steps in {:.1}s",
+                result.steps.len(),
+                (result.finished_at - result.started_at).num_milliseconds() as f64 / 1000.0
+            ),
+        ),
+        (false, Some(step)) => Notification::new(
+            NotifyEvent::MaintenanceFailed,
+            NotifySeverity::Warning,
+            format!("{} aborted", preset.name()),
+            format!("{} failed; the steps after it did not run", step),
+        ),
+        (false, None) => Notification::new(
+            NotifyEvent::MaintenanceFailed,
+            NotifySeverity::Warning,
+            format!("{} finished with failures", preset.name()),
+            format!("{} of {} steps failed", failed, result.steps.len()),
+        ),
+    };
+    agent.notifier().notify(notification);
+    Some(result)
+}
+
+/// Whether scheduled work guarded as `guard` has to wait because of system
+/// health or free space. Skips are recorded under `name` and announced at
+/// most once a day, pointing at `force` to run it anyway.
+async fn skip_unhealthy(
+    agent: &ArchLinuxAgent,
+    guard: ScheduledTask,
+    name: &str,
+    force: &str,
+    skip_notices: &mut SkipNotices,
+) -> bool {
+    let blocker = match agent.maintenance_blocker(guard).await {
+        Ok(blocker) => blocker,
+        Err(e) => Some(format!("Could not check system health: {}", e)),
+    };
+    let Some(reason) = blocker else {
+        return false;
+    };
+
+    warn!("Skipping scheduled {}: {}", name, reason);
+    if let Err(e) = agent.record_skipped_task(name, &reason).await {
+        warn!("Failed to record skipped {}: {}", name, e);
+    }
+    if skip_notices.should_notify(guard, chrono::Utc::now()) {
+        agent.notifier().notify(Notification::new(
+            NotifyEvent::MaintenanceFailed,
+            NotifySeverity::Warning,
+            format!("Scheduled {} skipped", name),
+            format!("{}. Run `{}` to force it", reason, force),
+        ));
+    }
+    true
+}
+
+/// Accept signed remediation commands from the Wazuh manager
+async fn start_active_response_listener(
+    agent: Arc<RwLock<ArchLinuxAgent>>,
+    config: ActiveResponseConfig,
+) -> Result<tokio::task::JoinHandle<()>> {
+    let listener = tokio::net::TcpListener::bind(&config.listen_address).await
+        .with_context(|| format!("Failed to bind active response listener on {}", config.listen_address))?;
+    info!("Accepting Wazuh active responses on {}", config.listen_address);
+    
+    let executor = Arc::new(AgentExecutor::new(agent, config.clone()));
+    let responder = Arc::new(ActiveResponder::new(config, executor));
+    Ok(tokio::spawn(async move {
+        if let Err(e) = responder.serve(listener).await {
+            error!("Active response listener stopped: {}", e);
+        }
+    }))
+}
+
+/// Active-response script mode: read the message wazuh-execd writes to
+/// stdin, run it, and print the reply
+async fn run_active_response_script(config: ServiceConfig) -> Result<()> {
+    use tokio::io::AsyncBufReadExt;
+    
+    let mut message = String::new();
+    tokio::io::BufReader::new(tokio::io::stdin()).read_line(&mut message).await?;
+    
+    let active_response = config.agent.wazuh.active_response.clone();
+    let mut agent = ArchLinuxAgent::new();
+    agent.initialize(config.agent).await?;
+    let executor = Arc::new(AgentExecutor::new(Arc::new(RwLock::new(agent)), active_response.clone()));
+    let responder = ActiveResponder::new(active_response, executor);
+    
+    if let Some(reply) = responder.handle_wazuh_message(message.trim()).await {
+        println!("{}", serde_json::to_string(&reply)?);
+        if !reply.success {
+            std::process::exit(1);
+        }
+    }
+    Ok(())
+}
+
 async fn start_metrics_server(port: u16) -> tokio::task::JoinHandle<()> {
     tokio::spawn(async move {
         // Placeholder for metrics server (Prometheus, etc.)
@@ -546,27 +1013,34 @@ async fn start_metrics_server(port: u16) -> tokio::task::JoinHandle<()> {
     })
 }
 
+/// Send `state` to systemd, e.g. READY=1; see sd_notify(3)
 #[cfg(target_os = "linux")]
-async fn systemd_notify_ready() -> Result<()> {
+fn systemd_notify(state: &str) -> Result<()> {
     use std::os::unix::net::UnixDatagram;
     
     if let Ok(socket_path) = std::env::var("NOTIFY_SOCKET") {
         let socket = UnixDatagram::unbound()?;
-        socket.send_to(b"READY=1", &socket_path)?;
-        info!("Notified systemd that service is ready");
+        socket.send_to(state.as_bytes(), &socket_path)?;
     }
     
     Ok(())
 }
 
+/// Shutdown carries on whether or not systemd heard about it
+#[cfg(target_os = "linux")]
+fn notify_or_warn(state: &str) {
+    if let Err(e) = systemd_notify(state) {
+        warn!("Failed to notify systemd of {}: {}", state, e);
+    }
+}
+
 async fn check_service_status() -> Result<serde_json::Value> {
     // Check systemd service status
-    let output = tokio::process::Command::new("systemctl")
-        .args(&["is-active", "jarvis-arch"])
-        .output()
+    let output = SystemRunner::default()
+        .output("systemctl", &["is-active", "jarvis-arch"])
         .await?;
     
-    let status = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let status = OutputText::decode(&output).stdout.trim().to_string();
     
     Ok(serde_json::json!({
         "service": "jarvis-arch",
@@ -627,18 +1101,7 @@ impl Default for ServiceSettings {
             enable_systemd_notifications: true,
             health_check_interval_seconds: 300, // 5 minutes
             maintenance_schedule: MaintenanceSchedule::default(),
+            shutdown: ShutdownConfig::default(),
         }
     }
 }
-
-impl Default for MaintenanceSchedule {
-    fn default() -> Self {
-        Self {
-            auto_update: false, // Disabled by default for safety
-            update_schedule: "0 2 * * 0".to_string(), // Sunday 2 AM
-            auto_clean: true,
-            clean_schedule: "0 3 * * 0".to_string(), // Sunday 3 AM
-            security_scan_schedule: "0 1 * * *".to_string(), // Daily 1 AM
-        }
-    }
-}
\ No newline at end of file
@@ -1,6 +1,14 @@
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use jarvis_core::privilege::PrivilegeConfig;
+use jarvis_core::severity::Severity;
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::config_backup::RetentionPolicy;
+use crate::maintenance_chain::ChainPreset;
 
 /// Main configuration structure for Jarvis Arch agent
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,6 +27,9 @@ pub struct Config {
 /// Agent-specific configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentConfig {
+    /// Refuse every operation that changes the system
+    #[serde(default)]
+    pub read_only: bool,
     pub pacman: PacmanConfig,
     pub aur: AurConfig,
     pub system: SystemConfig,
@@ -26,6 +37,11 @@ pub struct AgentConfig {
     pub maintenance: MaintenanceConfig,
     pub services: ServicesConfig,
     pub vulnerability: VulnerabilityConfig,
+    /// How operations that need root get it
+    #[serde(default)]
+    pub privilege: PrivilegeConfig,
+    #[serde(default)]
+    pub config_backup: ConfigBackupConfig,
 }
 
 /// Pacman configuration
@@ -36,6 +52,9 @@ pub struct PacmanConfig {
     pub check_space: bool,
     pub download_timeout: u32,
     pub parallel_downloads: u32,
+    /// Packages that are never installed, upgraded, or removed without review
+    #[serde(default)]
+    pub hold_packages: Vec<String>,
 }
 
 /// AUR configuration
@@ -46,6 +65,31 @@ pub struct AurConfig {
     pub check_updates: bool,
     pub build_timeout: u32,
     pub pgp_verify: bool,
+    #[serde(default)]
+    pub sandbox: AurSandboxConfig,
+}
+
+/// Isolation used when building AUR packages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SandboxBackend {
+    /// systemd-nspawn container cloned from a cached base image
+    Nspawn,
+    /// bubblewrap with a bind-mounted copy of the base chroot
+    Bubblewrap,
+    /// Run makepkg directly on the host (requires `allow_unsandboxed_builds`)
+    None,
+}
+
+/// AUR build sandbox configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AurSandboxConfig {
+    pub backend: SandboxBackend,
+    /// Holds the base image, per-build work directories, and build logs
+    pub cache_dir: PathBuf,
+    /// Extra packages installed into the base image besides base-devel
+    pub base_packages: Vec<String>,
+    pub allow_unsandboxed_builds: bool,
 }
 
 /// System monitoring configuration
@@ -65,7 +109,8 @@ pub struct SecurityConfig {
     pub vulnerability_feeds: Vec<String>,
     pub check_interval: u32,
     pub auto_patch: bool,
-    pub severity_threshold: String,
+    /// Minimum severity to report; accepts the historical lowercase names
+    pub severity_threshold: Severity,
 }
 
 /// Maintenance configuration
@@ -78,6 +123,28 @@ pub struct MaintenanceConfig {
     pub vacuum_database: bool,
 }
 
+/// Differential backups of configuration files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigBackupConfig {
+    /// Directories and files to back up
+    pub paths: Vec<PathBuf>,
+    /// Backup store; every run adds one set under it
+    pub destination: PathBuf,
+    /// Newest set of each of the last N days is kept
+    pub keep_daily: u32,
+    /// Newest set of each of the last M weeks is kept
+    pub keep_weekly: u32,
+}
+
+impl ConfigBackupConfig {
+    pub fn retention(&self) -> RetentionPolicy {
+        RetentionPolicy {
+            keep_daily: self.keep_daily,
+            keep_weekly: self.keep_weekly,
+        }
+    }
+}
+
 /// Services monitoring configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServicesConfig {
@@ -119,6 +186,30 @@ pub struct ServiceConfig {
     pub enable_systemd_notifications: bool,
     pub health_check_interval_seconds: u32,
     pub maintenance_schedule: MaintenanceScheduleConfig,
+    #[serde(default)]
+    pub shutdown: ShutdownConfig,
+}
+
+/// How long the service waits for running operations when it is stopped.
+/// Their sum should stay under the unit's `TimeoutStopSec`; see
+/// [`crate::shutdown`] for how package transactions get more time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShutdownConfig {
+    /// Wait this long for operations to finish on their own before
+    /// cancelling them
+    pub drain_timeout_secs: u64,
+    /// After cancelling, wait this long for operations to wind down
+    pub cancel_grace_secs: u64,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            drain_timeout_secs: 60,
+            cancel_grace_secs: 15,
+        }
+    }
 }
 
 /// Maintenance scheduling configuration
@@ -129,6 +220,237 @@ pub struct MaintenanceScheduleConfig {
     pub auto_clean: bool,
     pub clean_schedule: String,
     pub security_scan_schedule: String,
+    /// Download updates into the package cache without installing them
+    #[serde(default)]
+    pub stage_updates: bool,
+    #[serde(default = "default_stage_schedule")]
+    pub stage_schedule: String,
+    /// When to install staged updates; `None` means only on request
+    #[serde(default)]
+    pub apply_staged_schedule: Option<String>,
+    /// When to rebuild AUR packages that lost a library to an update; `None`
+    /// means only on request
+    #[serde(default)]
+    pub rebuild_aur_schedule: Option<String>,
+    /// Composite presets, each on its own cron schedule
+    #[serde(default)]
+    pub presets: Vec<ScheduledPreset>,
+    /// Back up configuration files on `backup_schedule`
+    #[serde(default = "default_auto_backup")]
+    pub auto_backup: bool,
+    #[serde(default = "default_backup_schedule")]
+    pub backup_schedule: String,
+}
+
+/// A composite maintenance preset placed on a schedule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledPreset {
+    pub preset: ChainPreset,
+    pub schedule: String,
+}
+
+fn default_stage_schedule() -> String {
+    "0 4 * * *".to_string()
+}
+
+fn default_auto_backup() -> bool {
+    true
+}
+
+fn default_backup_schedule() -> String {
+    "30 0 * * *".to_string()
+}
+
+/// Named maintenance schedules
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaintenancePreset {
+    /// Weekly cleanup and daily security scans, no automatic updates
+    Standard,
+    /// Pre-download updates overnight; install only when asked
+    Metered,
+    /// Pre-download updates overnight and install them Sunday morning
+    StagedWeekly,
+    /// Security scans only
+    Manual,
+}
+
+impl FromStr for MaintenancePreset {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "standard" => Ok(Self::Standard),
+            "metered" => Ok(Self::Metered),
+            "staged_weekly" | "staged-weekly" => Ok(Self::StagedWeekly),
+            "manual" => Ok(Self::Manual),
+            other => Err(anyhow::anyhow!("Unknown maintenance preset: {}", other)),
+        }
+    }
+}
+
+/// A maintenance task that can be placed on a schedule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduledTask {
+    Update,
+    Clean,
+    SecurityScan,
+    StageUpdates,
+    ApplyStagedUpdates,
+    BackupConfigs,
+    RebuildAur,
+}
+
+impl ScheduledTask {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Update => "update",
+            Self::Clean => "clean",
+            Self::SecurityScan => "security_scan",
+            Self::StageUpdates => "stage_updates",
+            Self::ApplyStagedUpdates => "apply_staged_updates",
+            Self::BackupConfigs => "backup_configs",
+            Self::RebuildAur => "rebuild_aur",
+        }
+    }
+
+    /// Worst system health the task may run in. Cleanup frees space and scans
+    /// only read, so both still run on a struggling system; backups write
+    /// but hold off when it is critical, and anything that installs or
+    /// downloads packages waits for a healthy one.
+    pub fn required_health(self) -> RequiredHealth {
+        match self {
+            Self::Clean | Self::SecurityScan => RequiredHealth::Any,
+            Self::BackupConfigs => RequiredHealth::WarningOk,
+            Self::Update | Self::StageUpdates | Self::ApplyStagedUpdates | Self::RebuildAur => {
+                RequiredHealth::Healthy
+            }
+        }
+    }
+
+    /// Whether the task downloads packages into the pacman cache
+    pub fn downloads_packages(self) -> bool {
+        matches!(self, Self::Update | Self::StageUpdates)
+    }
+}
+
+impl FromStr for ScheduledTask {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "update" => Ok(Self::Update),
+            "clean" => Ok(Self::Clean),
+            "security_scan" | "security-scan" => Ok(Self::SecurityScan),
+            "stage_updates" | "stage-updates" => Ok(Self::StageUpdates),
+            "apply_staged_updates" | "apply-staged-updates" => Ok(Self::ApplyStagedUpdates),
+            "backup_configs" | "backup-configs" => Ok(Self::BackupConfigs),
+            "rebuild_aur" | "rebuild-aur" => Ok(Self::RebuildAur),
+            other => Err(anyhow::anyhow!("Unknown maintenance task: {}", other)),
+        }
+    }
+}
+
+/// Worst system health a scheduled task is still allowed to run in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequiredHealth {
+    Healthy,
+    /// Runs unless the system is Critical
+    WarningOk,
+    Any,
+}
+
+impl MaintenanceScheduleConfig {
+    pub fn preset(preset: MaintenancePreset) -> Self {
+        let standard = Self::default();
+        match preset {
+            MaintenancePreset::Standard => standard,
+            MaintenancePreset::Metered => Self {
+                auto_update: false,
+                stage_updates: true,
+                stage_schedule: "0 3 * * *".to_string(),
+                apply_staged_schedule: None,
+                ..standard
+            },
+            MaintenancePreset::StagedWeekly => Self {
+                auto_update: false,
+                stage_updates: true,
+                stage_schedule: "0 3 * * *".to_string(),
+                apply_staged_schedule: Some("0 9 * * 0".to_string()),
+                ..standard
+            },
+            MaintenancePreset::Manual => Self {
+                auto_update: false,
+                auto_clean: false,
+                auto_backup: false,
+                ..standard
+            },
+        }
+    }
+
+    /// Enabled tasks with their cron expressions
+    pub fn tasks(&self) -> Vec<(ScheduledTask, &str)> {
+        let mut tasks = vec![(ScheduledTask::SecurityScan, self.security_scan_schedule.as_str())];
+        if self.auto_update {
+            tasks.push((ScheduledTask::Update, self.update_schedule.as_str()));
+        }
+        if self.auto_clean {
+            tasks.push((ScheduledTask::Clean, self.clean_schedule.as_str()));
+        }
+        if self.stage_updates {
+            tasks.push((ScheduledTask::StageUpdates, self.stage_schedule.as_str()));
+            if let Some(apply) = &self.apply_staged_schedule {
+                tasks.push((ScheduledTask::ApplyStagedUpdates, apply.as_str()));
+            }
+        }
+        if self.auto_backup {
+            tasks.push((ScheduledTask::BackupConfigs, self.backup_schedule.as_str()));
+        }
+        if let Some(rebuild) = &self.rebuild_aur_schedule {
+            tasks.push((ScheduledTask::RebuildAur, rebuild.as_str()));
+        }
+        tasks
+    }
+
+    /// The next task to run after `after`, if any schedule is valid
+    pub fn next_run(&self, after: DateTime<Utc>) -> Option<(ScheduledTask, DateTime<Utc>)> {
+        self.tasks()
+            .into_iter()
+            .filter_map(|(task, expr)| next_cron_time(expr, after).map(|at| (task, at)))
+            .min_by_key(|(_, at)| *at)
+    }
+
+    /// Tasks whose schedule fired in `(from, to]`
+    pub fn due_between(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<ScheduledTask> {
+        self.tasks()
+            .into_iter()
+            .filter(|(_, expr)| next_cron_time(expr, from).is_some_and(|at| at <= to))
+            .map(|(task, _)| task)
+            .collect()
+    }
+
+    /// Presets whose schedule fired in `(from, to]`
+    pub fn presets_due_between(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<ChainPreset> {
+        self.presets
+            .iter()
+            .filter(|p| next_cron_time(&p.schedule, from).is_some_and(|at| at <= to))
+            .map(|p| p.preset)
+            .collect()
+    }
+}
+
+/// Next fire time of a five-field cron expression
+fn next_cron_time(expr: &str, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    // The cron crate expects a leading seconds field
+    match cron::Schedule::from_str(&format!("0 {}", expr)) {
+        Ok(schedule) => schedule.after(&after).next(),
+        Err(e) => {
+            tracing::warn!("Invalid maintenance schedule '{}': {}", expr, e);
+            None
+        }
+    }
 }
 
 /// Wazuh SIEM integration configuration
@@ -141,6 +463,57 @@ pub struct WazuhConfig {
     pub encryption: bool,
     pub certificate_path: Option<PathBuf>,
     pub key_path: Option<PathBuf>,
+    /// Remediation commands accepted from the manager
+    #[serde(default)]
+    pub active_response: ActiveResponseConfig,
+}
+
+/// Wazuh active response: which remediation commands the manager may send.
+/// Every command is off until enabled here, and nothing runs without a
+/// shared secret.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ActiveResponseConfig {
+    /// Listen for signed commands; the stdin script mode works regardless
+    pub enabled: bool,
+    pub listen_address: String,
+    /// Key for command signatures, and the token the stdin mode expects
+    pub shared_secret: Option<String>,
+    /// Signed commands older or further in the future than this are refused
+    pub max_clock_skew_secs: u64,
+    /// Block an address with nftables
+    pub block_ip: bool,
+    /// How long a block lasts before nftables drops it
+    pub block_duration_secs: u64,
+    /// Stop and disable a systemd service
+    pub disable_service: bool,
+    /// Units that are never disabled remotely
+    pub protected_services: Vec<String>,
+    /// Re-run the vulnerability and AUR scans and report to the manager
+    pub rescan: bool,
+    /// Remove an AUR package and refuse to install it again
+    pub quarantine_aur: bool,
+}
+
+impl Default for ActiveResponseConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen_address: "127.0.0.1:7420".to_string(),
+            shared_secret: None,
+            max_clock_skew_secs: 300,
+            block_ip: false,
+            block_duration_secs: 86400,
+            disable_service: false,
+            protected_services: vec![
+                "sshd.service".to_string(),
+                "wazuh-agent.service".to_string(),
+                "jarvis-arch.service".to_string(),
+            ],
+            rescan: false,
+            quarantine_aur: false,
+        }
+    }
 }
 
 /// Logging configuration
@@ -163,6 +536,34 @@ pub struct NotificationsConfig {
     pub webhook_enabled: bool,
     pub webhook_url: Option<String>,
     pub desktop_enabled: bool,
+    #[serde(default)]
+    pub ntfy: Option<jarvis_core::config::NtfyConfig>,
+    #[serde(default)]
+    pub gotify: Option<jarvis_core::config::GotifyConfig>,
+    /// Minimum severity per event type, e.g. `maintenance_finished = "warning"`
+    #[serde(default)]
+    pub events: HashMap<jarvis_core::NotifyEvent, jarvis_core::NotifySeverity>,
+}
+
+impl NotificationsConfig {
+    /// The shared notifier settings these options map onto
+    pub fn notifier_config(&self) -> jarvis_core::config::NotificationConfig {
+        let mut config = jarvis_core::config::NotificationConfig::default();
+        config.enabled = self.enabled;
+        config.desktop.enabled = self.desktop_enabled;
+        config.ntfy = self.ntfy.clone();
+        config.gotify = self.gotify.clone();
+        config.webhook = self
+            .webhook_url
+            .clone()
+            .filter(|_| self.webhook_enabled)
+            .map(|url| jarvis_core::config::WebhookNotifyConfig {
+                url,
+                min_severity: jarvis_core::NotifySeverity::Info,
+            });
+        config.events = self.events.clone();
+        config
+    }
 }
 
 /// Performance tuning configuration
@@ -234,6 +635,10 @@ impl Config {
             if !["tcp", "udp"].contains(&self.wazuh.protocol.as_str()) {
                 return Err(anyhow::anyhow!("Wazuh protocol must be 'tcp' or 'udp'"));
             }
+            let active_response = &self.wazuh.active_response;
+            if active_response.enabled && active_response.shared_secret.as_deref().unwrap_or("").is_empty() {
+                return Err(anyhow::anyhow!("Wazuh active response needs a shared_secret"));
+            }
         }
 
         // Validate AUR helper if AUR is enabled
@@ -267,6 +672,7 @@ impl Default for Config {
 impl Default for AgentConfig {
     fn default() -> Self {
         Self {
+            read_only: false,
             pacman: PacmanConfig::default(),
             aur: AurConfig::default(),
             system: SystemConfig::default(),
@@ -274,6 +680,19 @@ impl Default for AgentConfig {
             maintenance: MaintenanceConfig::default(),
             services: ServicesConfig::default(),
             vulnerability: VulnerabilityConfig::default(),
+            privilege: PrivilegeConfig::default(),
+            config_backup: ConfigBackupConfig::default(),
+        }
+    }
+}
+
+impl Default for ConfigBackupConfig {
+    fn default() -> Self {
+        Self {
+            paths: vec![PathBuf::from("/etc")],
+            destination: PathBuf::from("/var/lib/jarvis/config-backups"),
+            keep_daily: 7,
+            keep_weekly: 4,
         }
     }
 }
@@ -286,6 +705,7 @@ impl Default for PacmanConfig {
             check_space: true,
             download_timeout: 30,
             parallel_downloads: 5,
+            hold_packages: Vec::new(),
         }
     }
 }
@@ -298,6 +718,18 @@ impl Default for AurConfig {
             check_updates: true,
             build_timeout: 1800,
             pgp_verify: true,
+            sandbox: AurSandboxConfig::default(),
+        }
+    }
+}
+
+impl Default for AurSandboxConfig {
+    fn default() -> Self {
+        Self {
+            backend: SandboxBackend::Nspawn,
+            cache_dir: PathBuf::from("/var/cache/jarvis/aur"),
+            base_packages: Vec::new(),
+            allow_unsandboxed_builds: false,
         }
     }
 }
@@ -324,7 +756,7 @@ impl Default for SecurityConfig {
             ],
             check_interval: 3600,
             auto_patch: false,
-            severity_threshold: "medium".to_string(),
+            severity_threshold: Severity::Medium,
         }
     }
 }
@@ -393,6 +825,7 @@ impl Default for ServiceConfig {
             enable_systemd_notifications: true,
             health_check_interval_seconds: 300,
             maintenance_schedule: MaintenanceScheduleConfig::default(),
+            shutdown: ShutdownConfig::default(),
         }
     }
 }
@@ -405,6 +838,13 @@ impl Default for MaintenanceScheduleConfig {
             auto_clean: true,
             clean_schedule: "0 3 * * 0".to_string(),
             security_scan_schedule: "0 1 * * *".to_string(),
+            stage_updates: false,
+            stage_schedule: default_stage_schedule(),
+            apply_staged_schedule: None,
+            rebuild_aur_schedule: None,
+            presets: Vec::new(),
+            auto_backup: default_auto_backup(),
+            backup_schedule: default_backup_schedule(),
         }
     }
 }
@@ -419,6 +859,7 @@ impl Default for WazuhConfig {
             encryption: true,
             certificate_path: Some(PathBuf::from("/etc/jarvis/wazuh.crt")),
             key_path: Some(PathBuf::from("/etc/jarvis/wazuh.key")),
+            active_response: ActiveResponseConfig::default(),
         }
     }
 }
@@ -445,6 +886,9 @@ impl Default for NotificationsConfig {
             webhook_enabled: false,
             webhook_url: Some("https://hooks.slack.com/your/webhook/url".to_string()),
             desktop_enabled: false,
+            ntfy: None,
+            gotify: None,
+            events: HashMap::new(),
         }
     }
 }
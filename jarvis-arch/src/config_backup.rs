@@ -0,0 +1,731 @@
+//! Differential configuration backups
+//!
+//! A backup set stores only the files whose content changed since the set
+//! before it; every other file points at the older set that holds its bytes.
+//! Sets live under the backup root as
+//!
+//! ```text
+//! sets/<id>/manifest.json
+//! sets/<id>/files/<absolute path without the leading />
+//! ```
+//!
+//! The manifest is written last, so an interrupted backup leaves no set
+//! behind. Pruning copies whatever a retained set still needs out of a set
+//! before removing it, so the chain never breaks.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+const MANIFEST: &str = "manifest.json";
+
+/// A backed-up file: what it looked like and which set holds its content
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupEntry {
+    /// SHA-256 of the content, hex encoded
+    pub hash: String,
+    pub size: u64,
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    /// Set whose `files/` directory holds the content
+    pub stored_in: String,
+}
+
+impl BackupEntry {
+    /// Same content, permissions, and owner
+    fn matches(&self, other: &BackupEntry) -> bool {
+        self.hash == other.hash
+            && self.mode == other.mode
+            && self.uid == other.uid
+            && self.gid == other.gid
+    }
+}
+
+/// One backup run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupSet {
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    /// The set this one was taken against; `None` for a full backup
+    pub parent: Option<String>,
+    pub roots: Vec<PathBuf>,
+    pub files: BTreeMap<PathBuf, BackupEntry>,
+}
+
+impl BackupSet {
+    /// Catalog row, with changes counted against `parent`
+    pub fn summary(&self, parent: Option<&BackupSet>) -> BackupSummary {
+        let changed_files = match parent {
+            Some(parent) => diff_sets(parent, self)
+                .iter()
+                .filter(|c| c.kind != ChangeKind::Removed)
+                .count(),
+            None => self.files.len(),
+        };
+        let stored = self.files.values().filter(|e| e.stored_in == self.id);
+        BackupSummary {
+            id: self.id.clone(),
+            created_at: self.created_at,
+            parent: self.parent.clone(),
+            file_count: self.files.len(),
+            changed_files,
+            stored_bytes: stored.map(|e| e.size).sum(),
+            total_bytes: self.files.values().map(|e| e.size).sum(),
+        }
+    }
+}
+
+/// Catalog row for a backup set
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupSummary {
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    pub parent: Option<String>,
+    pub file_count: usize,
+    /// Files added or changed since the parent set
+    pub changed_files: usize,
+    /// Bytes this set stores itself
+    pub stored_bytes: u64,
+    /// Bytes of every file the set can restore
+    pub total_bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Removed,
+}
+
+/// A file that differs between two sets, or between a set and the system
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileChange {
+    pub path: PathBuf,
+    pub kind: ChangeKind,
+}
+
+/// Files that differ going from set `from` to set `to`
+pub fn diff_sets(from: &BackupSet, to: &BackupSet) -> Vec<FileChange> {
+    let mut changes = Vec::new();
+    for (path, entry) in &to.files {
+        let kind = match from.files.get(path) {
+            None => ChangeKind::Added,
+            Some(old) if !old.matches(entry) => ChangeKind::Modified,
+            Some(_) => continue,
+        };
+        changes.push(FileChange { path: path.clone(), kind });
+    }
+    for path in from.files.keys().filter(|p| !to.files.contains_key(*p)) {
+        changes.push(FileChange {
+            path: path.clone(),
+            kind: ChangeKind::Removed,
+        });
+    }
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+    changes
+}
+
+/// How many backup sets survive pruning
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    /// Newest set of each of the last N days that have one
+    pub keep_daily: u32,
+    /// Newest set of each of the last M ISO weeks that have one
+    pub keep_weekly: u32,
+}
+
+impl RetentionPolicy {
+    /// Ids of the sets to keep; the newest set is always kept. Days and
+    /// weeks are counted in UTC.
+    pub fn retained(&self, sets: &[BackupSet]) -> BTreeSet<String> {
+        let mut newest_first: Vec<&BackupSet> = sets.iter().collect();
+        newest_first.sort_by(|a, b| (b.created_at, &b.id).cmp(&(a.created_at, &a.id)));
+
+        let mut keep = BTreeSet::new();
+        let mut days = BTreeSet::new();
+        let mut weeks = BTreeSet::new();
+        for set in &newest_first {
+            let day = set.created_at.date_naive();
+            if days.len() < self.keep_daily as usize && days.insert(day) {
+                keep.insert(set.id.clone());
+            }
+            let week = set.created_at.iso_week();
+            if weeks.len() < self.keep_weekly as usize && weeks.insert((week.year(), week.week())) {
+                keep.insert(set.id.clone());
+            }
+        }
+        if let Some(newest) = newest_first.first() {
+            keep.insert(newest.id.clone());
+        }
+        keep
+    }
+}
+
+/// Backup sets under one directory
+#[derive(Debug, Clone)]
+pub struct BackupStore {
+    root: PathBuf,
+}
+
+impl BackupStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn sets_dir(&self) -> PathBuf {
+        self.root.join("sets")
+    }
+
+    fn set_dir(&self, id: &str) -> PathBuf {
+        self.sets_dir().join(id)
+    }
+
+    fn blob_path(&self, stored_in: &str, path: &Path) -> PathBuf {
+        let relative = path.strip_prefix("/").unwrap_or(path);
+        self.set_dir(stored_in).join("files").join(relative)
+    }
+
+    /// Every complete set, oldest first
+    pub fn list(&self) -> Result<Vec<BackupSet>> {
+        let dir = self.sets_dir();
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut sets = Vec::new();
+        for entry in std::fs::read_dir(&dir)
+            .with_context(|| format!("Failed to read {}", dir.display()))?
+        {
+            let manifest = entry?.path().join(MANIFEST);
+            if manifest.exists() {
+                sets.push(read_manifest(&manifest)?);
+            }
+        }
+        sets.sort_by(|a, b| (a.created_at, &a.id).cmp(&(b.created_at, &b.id)));
+        Ok(sets)
+    }
+
+    pub fn load(&self, id: &str) -> Result<BackupSet> {
+        let manifest = self.set_dir(id).join(MANIFEST);
+        if !manifest.exists() {
+            anyhow::bail!("No backup set {} in {}", id, self.root.display());
+        }
+        read_manifest(&manifest)
+    }
+
+    /// Summaries of every set, oldest first
+    pub fn summaries(&self) -> Result<Vec<BackupSummary>> {
+        let sets = self.list()?;
+        let by_id: HashMap<&str, &BackupSet> = sets.iter().map(|s| (s.id.as_str(), s)).collect();
+        Ok(sets
+            .iter()
+            .map(|set| set.summary(set.parent.as_deref().and_then(|p| by_id.get(p).copied())))
+            .collect())
+    }
+
+    /// Content of `path` as of `set`
+    pub fn read(&self, set: &BackupSet, path: &Path) -> Result<Vec<u8>> {
+        let entry = set
+            .files
+            .get(path)
+            .with_context(|| format!("{} is not in backup set {}", path.display(), set.id))?;
+        let blob = self.blob_path(&entry.stored_in, path);
+        let content = std::fs::read(&blob)
+            .with_context(|| format!("Failed to read {}", blob.display()))?;
+        if hash(&content) != entry.hash {
+            anyhow::bail!("Backup copy of {} in set {} is corrupt", path.display(), entry.stored_in);
+        }
+        Ok(content)
+    }
+
+    /// Back up the files under `roots`, storing only those that changed since
+    /// the newest set. Unreadable files are skipped with a warning.
+    pub fn create(&self, roots: &[PathBuf], now: DateTime<Utc>) -> Result<BackupSet> {
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(self.sets_dir())
+            .with_context(|| format!("Failed to create {}", self.sets_dir().display()))?;
+
+        let parent = self.list()?.pop();
+        let id = self.unused_id(now);
+        let mut files = BTreeMap::new();
+        for root in roots {
+            for entry in WalkDir::new(root).follow_links(false) {
+                let entry = match entry {
+                    Ok(entry) if entry.file_type().is_file() => entry,
+                    Ok(_) => continue,
+                    Err(e) => {
+                        tracing::warn!("Skipping part of {} in backup: {}", root.display(), e);
+                        continue;
+                    }
+                };
+                let path = entry.path();
+                let read = std::fs::read(path)
+                    .and_then(|content| Ok((content, std::fs::symlink_metadata(path)?)));
+                let (content, metadata) = match read {
+                    Ok(read) => read,
+                    Err(e) => {
+                        tracing::warn!("Skipping {} in backup: {}", path.display(), e);
+                        continue;
+                    }
+                };
+
+                let hash = hash(&content);
+                let unchanged = parent
+                    .as_ref()
+                    .and_then(|p| p.files.get(path))
+                    .filter(|previous| previous.hash == hash);
+                let stored_in = match unchanged {
+                    Some(previous) => previous.stored_in.clone(),
+                    None => {
+                        let blob = self.blob_path(&id, path);
+                        if let Some(dir) = blob.parent() {
+                            std::fs::create_dir_all(dir)?;
+                        }
+                        std::fs::write(&blob, &content)
+                            .with_context(|| format!("Failed to write {}", blob.display()))?;
+                        id.clone()
+                    }
+                };
+                files.insert(
+                    path.to_path_buf(),
+                    BackupEntry {
+                        hash,
+                        size: content.len() as u64,
+                        mode: metadata.permissions().mode() & 0o7777,
+                        uid: metadata.uid(),
+                        gid: metadata.gid(),
+                        stored_in,
+                    },
+                );
+            }
+        }
+
+        let set = BackupSet {
+            id,
+            created_at: now,
+            parent: parent.map(|p| p.id),
+            roots: roots.to_vec(),
+            files,
+        };
+        self.write_manifest(&set)?;
+        Ok(set)
+    }
+
+    /// Files restoring `set` would write: those missing from the system are
+    /// `Added`, those that differ `Modified`. Files the set doesn't know
+    /// about are left alone, so nothing is ever `Removed`.
+    pub fn restore_plan(&self, set: &BackupSet) -> Result<Vec<FileChange>> {
+        let mut changes = Vec::new();
+        for (path, entry) in &set.files {
+            let kind = match current_entry(path)? {
+                None => ChangeKind::Added,
+                Some(current) if !current.matches(entry) => ChangeKind::Modified,
+                Some(_) => continue,
+            };
+            changes.push(FileChange { path: path.clone(), kind });
+        }
+        Ok(changes)
+    }
+
+    /// Write the files in `changes` back as they were in `set`, with their
+    /// permissions and owner. Each file is replaced in one rename.
+    pub fn restore(&self, set: &BackupSet, changes: &[FileChange]) -> Result<()> {
+        for change in changes.iter().filter(|c| c.kind != ChangeKind::Removed) {
+            let path = &change.path;
+            let entry = &set.files[path];
+            let content = self.read(set, path)?;
+            let dir = path
+                .parent()
+                .with_context(|| format!("Cannot restore {}", path.display()))?;
+            std::fs::create_dir_all(dir)?;
+
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let staged = dir.join(format!(".{}.jarvis-restore", name));
+            std::fs::write(&staged, &content)
+                .with_context(|| format!("Failed to write {}", staged.display()))?;
+            std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(entry.mode))?;
+            std::os::unix::fs::chown(&staged, Some(entry.uid), Some(entry.gid))
+                .with_context(|| format!("Failed to set the owner of {}", path.display()))?;
+            std::fs::rename(&staged, path)
+                .with_context(|| format!("Failed to restore {}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Remove the sets `policy` doesn't keep and return their ids. Content a
+    /// kept set still points at is first copied into the oldest kept set that
+    /// uses it, and kept sets are re-parented onto their nearest kept ancestor.
+    pub fn prune(&self, policy: &RetentionPolicy) -> Result<Vec<String>> {
+        let sets = self.list()?;
+        let keep = policy.retained(&sets);
+        let pruned: BTreeSet<String> = sets
+            .iter()
+            .map(|s| s.id.clone())
+            .filter(|id| !keep.contains(id))
+            .collect();
+        if pruned.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut kept: Vec<BackupSet> = sets.iter().filter(|s| keep.contains(&s.id)).cloned().collect();
+        // Oldest first, so the first kept set to reference pruned content adopts it
+        let mut adopted: HashMap<(String, PathBuf), String> = HashMap::new();
+        for set in &mut kept {
+            for (path, entry) in set.files.iter_mut() {
+                if !pruned.contains(&entry.stored_in) {
+                    continue;
+                }
+                let key = (entry.stored_in.clone(), path.clone());
+                let owner = match adopted.get(&key) {
+                    Some(owner) => owner.clone(),
+                    None => {
+                        let from = self.blob_path(&entry.stored_in, path);
+                        let to = self.blob_path(&set.id, path);
+                        if let Some(dir) = to.parent() {
+                            std::fs::create_dir_all(dir)?;
+                        }
+                        std::fs::copy(&from, &to).with_context(|| {
+                            format!("Failed to re-base {} into set {}", path.display(), set.id)
+                        })?;
+                        adopted.insert(key, set.id.clone());
+                        set.id.clone()
+                    }
+                };
+                entry.stored_in = owner;
+            }
+        }
+
+        let mut previous: Option<String> = None;
+        for set in &mut kept {
+            set.parent = previous.replace(set.id.clone());
+        }
+        // Every kept manifest is complete before any pruned content goes away
+        for set in &kept {
+            self.write_manifest(set)?;
+        }
+        for id in &pruned {
+            let dir = self.set_dir(id);
+            std::fs::remove_dir_all(&dir)
+                .with_context(|| format!("Failed to remove {}", dir.display()))?;
+        }
+        Ok(pruned.into_iter().collect())
+    }
+
+    /// A set id for `now` that isn't taken yet
+    fn unused_id(&self, now: DateTime<Utc>) -> String {
+        let base = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let mut id = base.clone();
+        let mut n = 1;
+        while self.set_dir(&id).exists() {
+            n += 1;
+            id = format!("{}-{}", base, n);
+        }
+        id
+    }
+
+    fn write_manifest(&self, set: &BackupSet) -> Result<()> {
+        let dir = self.set_dir(&set.id);
+        std::fs::create_dir_all(&dir)?;
+        let staged = dir.join(format!("{}.tmp", MANIFEST));
+        std::fs::write(&staged, serde_json::to_vec_pretty(set)?)
+            .with_context(|| format!("Failed to write {}", staged.display()))?;
+        std::fs::rename(&staged, dir.join(MANIFEST))?;
+        Ok(())
+    }
+}
+
+fn read_manifest(path: &Path) -> Result<BackupSet> {
+    let content = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_slice(&content).with_context(|| format!("Invalid backup manifest {}", path.display()))
+}
+
+fn hash(content: &[u8]) -> String {
+    hex::encode(Sha256::digest(content))
+}
+
+/// `path` as it is on the system now, with no `stored_in`, or `None` when
+/// it doesn't exist
+pub fn current_entry(path: &Path) -> Result<Option<BackupEntry>> {
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("Failed to inspect {}", path.display())),
+    };
+    let content = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(Some(BackupEntry {
+        hash: hash(&content),
+        size: content.len() as u64,
+        mode: metadata.permissions().mode() & 0o7777,
+        uid: metadata.uid(),
+        gid: metadata.gid(),
+        stored_in: String::new(),
+    }))
+}
+
+/// One line of a line-by-line diff
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+/// Files with more lines than this on both sides are only reported as changed
+const MAX_DIFF_LINES: usize = 5000;
+
+/// Line diff from `old` to `new` along their longest common subsequence;
+/// `None` when either side isn't text or both are too long to compare
+pub fn line_diff(old: &[u8], new: &[u8]) -> Option<Vec<DiffLine>> {
+    let old = std::str::from_utf8(old).ok()?;
+    let new = std::str::from_utf8(new).ok()?;
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    if a.len().min(b.len()) > MAX_DIFF_LINES {
+        return None;
+    }
+
+    // lcs[i][j] is the common subsequence length of a[i..] and b[j..]
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::new();
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            lines.push(DiffLine::Same(a[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if j < b.len() && (i == a.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            lines.push(DiffLine::Added(b[j].to_string()));
+            j += 1;
+        } else {
+            lines.push(DiffLine::Removed(a[i].to_string()));
+            i += 1;
+        }
+    }
+    Some(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+    use uuid::Uuid;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("jarvis-backup-{}-{}", name, Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// A small stand-in for /etc under `dir`
+    fn synthetic_etc(dir: &Path) -> PathBuf {
+        let etc = dir.join("etc");
+        std::fs::create_dir_all(etc.join("pacman.d")).unwrap();
+        std::fs::create_dir_all(etc.join("ssh")).unwrap();
+        std::fs::write(etc.join("hostname"), "archbox\n").unwrap();
+        std::fs::write(etc.join("pacman.conf"), "[options]\nHoldPkg = pacman glibc\nParallelDownloads = 5\n").unwrap();
+        std::fs::write(etc.join("pacman.d/mirrorlist"), "Server = https://mirror.example/$repo/os/$arch\n").unwrap();
+        std::fs::write(etc.join("ssh/sshd_config"), "PermitRootLogin no\n").unwrap();
+        std::fs::set_permissions(etc.join("ssh/sshd_config"), std::fs::Permissions::from_mode(0o600)).unwrap();
+        etc
+    }
+
+    fn at(day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, day, hour, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_backup_stores_only_changed_files() {
+        let dir = scratch("incremental");
+        let etc = synthetic_etc(&dir);
+        let store = BackupStore::new(dir.join("backups"));
+
+        let first = store.create(&[etc.clone()], at(1, 2)).unwrap();
+        assert_eq!(first.parent, None);
+        assert_eq!(first.files.len(), 4);
+        assert!(first.files.values().all(|e| e.stored_in == first.id));
+
+        std::fs::write(etc.join("hostname"), "archbox2\n").unwrap();
+        std::fs::write(etc.join("vconsole.conf"), "KEYMAP=de\n").unwrap();
+        let second = store.create(&[etc.clone()], at(2, 2)).unwrap();
+
+        assert_eq!(second.parent.as_deref(), Some(first.id.as_str()));
+        assert_eq!(second.files[&etc.join("hostname")].stored_in, second.id);
+        assert_eq!(second.files[&etc.join("vconsole.conf")].stored_in, second.id);
+        assert_eq!(second.files[&etc.join("pacman.conf")].stored_in, first.id);
+        assert!(!store.blob_path(&second.id, &etc.join("pacman.conf")).exists());
+
+        let summary = second.summary(Some(&first));
+        assert_eq!(summary.file_count, 5);
+        assert_eq!(summary.changed_files, 2);
+        assert_eq!(summary.stored_bytes, ("archbox2\n".len() + "KEYMAP=de\n".len()) as u64);
+
+        assert_eq!(
+            diff_sets(&first, &second),
+            vec![
+                FileChange { path: etc.join("hostname"), kind: ChangeKind::Modified },
+                FileChange { path: etc.join("vconsole.conf"), kind: ChangeKind::Added },
+            ]
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_restore_round_trip() {
+        let dir = scratch("restore");
+        let etc = synthetic_etc(&dir);
+        let store = BackupStore::new(dir.join("backups"));
+        let first = store.create(&[etc.clone()], at(1, 2)).unwrap();
+        let second = store.create(&[etc.clone()], at(2, 2)).unwrap();
+        assert!(second.files.values().all(|e| e.stored_in == first.id));
+
+        std::fs::write(etc.join("pacman.conf"), "[options]\nParallelDownloads = 5\n").unwrap();
+        std::fs::remove_file(etc.join("hostname")).unwrap();
+        std::fs::set_permissions(etc.join("ssh/sshd_config"), std::fs::Permissions::from_mode(0o644)).unwrap();
+        std::fs::write(etc.join("extra.conf"), "left alone\n").unwrap();
+
+        // Every file in the second set is read through the chain from the first
+        let plan = store.restore_plan(&second).unwrap();
+        assert_eq!(
+            plan,
+            vec![
+                FileChange { path: etc.join("hostname"), kind: ChangeKind::Added },
+                FileChange { path: etc.join("pacman.conf"), kind: ChangeKind::Modified },
+                FileChange { path: etc.join("ssh/sshd_config"), kind: ChangeKind::Modified },
+            ]
+        );
+        store.restore(&second, &plan).unwrap();
+
+        assert_eq!(std::fs::read_to_string(etc.join("hostname")).unwrap(), "archbox\n");
+        assert!(std::fs::read_to_string(etc.join("pacman.conf")).unwrap().contains("HoldPkg"));
+        let mode = std::fs::metadata(etc.join("ssh/sshd_config")).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(etc.join("extra.conf").exists());
+        assert!(store.restore_plan(&second).unwrap().is_empty());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_retention_keeps_daily_and_weekly_sets() {
+        let policy = RetentionPolicy { keep_daily: 3, keep_weekly: 2 };
+        let set = |id: &str, created_at| BackupSet {
+            id: id.to_string(),
+            created_at,
+            parent: None,
+            roots: Vec::new(),
+            files: BTreeMap::new(),
+        };
+        // 2026-10-05 is a Monday
+        let sets = vec![
+            set("sep-28", at(1, 0) - Duration::days(3)),
+            set("oct-04", at(4, 1)),
+            set("oct-05-early", at(5, 1)),
+            set("oct-05-late", at(5, 20)),
+            set("oct-09", at(9, 1)),
+            set("oct-10", at(10, 1)),
+            set("oct-11", at(11, 1)),
+        ];
+
+        let kept: Vec<String> = policy.retained(&sets).into_iter().collect();
+        // Three newest days, plus the newest of the previous week
+        assert_eq!(kept, vec!["oct-04", "oct-09", "oct-10", "oct-11"]);
+
+        let none = RetentionPolicy { keep_daily: 0, keep_weekly: 0 };
+        assert_eq!(none.retained(&sets).into_iter().collect::<Vec<_>>(), vec!["oct-11"]);
+    }
+
+    #[test]
+    fn test_prune_rebases_retained_sets() {
+        let dir = scratch("prune");
+        let etc = synthetic_etc(&dir);
+        let store = BackupStore::new(dir.join("backups"));
+
+        let full = store.create(&[etc.clone()], at(1, 2)).unwrap();
+        std::fs::write(etc.join("hostname"), "archbox2\n").unwrap();
+        let middle = store.create(&[etc.clone()], at(1, 3)).unwrap();
+        std::fs::write(etc.join("pacman.d/mirrorlist"), "Server = https://other.example/$repo/os/$arch\n").unwrap();
+        let newest = store.create(&[etc.clone()], at(2, 2)).unwrap();
+        assert_eq!(newest.files[&etc.join("pacman.conf")].stored_in, full.id);
+        assert_eq!(newest.files[&etc.join("hostname")].stored_in, middle.id);
+
+        // One set per day: the full set and the middle one on day 1 both go
+        let pruned = store
+            .prune(&RetentionPolicy { keep_daily: 1, keep_weekly: 0 })
+            .unwrap();
+        assert_eq!(pruned, vec![full.id.clone(), middle.id.clone()]);
+        assert!(!store.set_dir(&full.id).exists());
+
+        let rebased = store.load(&newest.id).unwrap();
+        assert_eq!(rebased.parent, None);
+        assert!(rebased.files.values().all(|e| e.stored_in == newest.id));
+        for path in rebased.files.keys() {
+            assert_eq!(store.read(&rebased, path).unwrap(), std::fs::read(path).unwrap());
+        }
+        assert_eq!(store.summaries().unwrap()[0].stored_bytes, rebased.summary(None).total_bytes);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_prune_moves_shared_content_once() {
+        let dir = scratch("shared");
+        let etc = synthetic_etc(&dir);
+        let store = BackupStore::new(dir.join("backups"));
+
+        let full = store.create(&[etc.clone()], at(1, 2)).unwrap();
+        std::fs::write(etc.join("hostname"), "archbox2\n").unwrap();
+        let second = store.create(&[etc.clone()], at(2, 2)).unwrap();
+        let third = store.create(&[etc.clone()], at(3, 2)).unwrap();
+
+        let pruned = store
+            .prune(&RetentionPolicy { keep_daily: 2, keep_weekly: 0 })
+            .unwrap();
+        assert_eq!(pruned, vec![full.id.clone()]);
+
+        // Content from the pruned set lands in the oldest kept set, and the
+        // newer set points there instead of copying it again
+        let second = store.load(&second.id).unwrap();
+        let third = store.load(&third.id).unwrap();
+        assert_eq!(second.parent, None);
+        assert_eq!(third.parent.as_deref(), Some(second.id.as_str()));
+        assert_eq!(third.files[&etc.join("pacman.conf")].stored_in, second.id);
+        assert!(!store.blob_path(&third.id, &etc.join("pacman.conf")).exists());
+        assert_eq!(store.restore_plan(&third).unwrap(), Vec::new());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_line_diff() {
+        let diff = line_diff(b"a\nb\nc\n", b"a\nc\nd\n").unwrap();
+        assert_eq!(
+            diff,
+            vec![
+                DiffLine::Same("a".to_string()),
+                DiffLine::Removed("b".to_string()),
+                DiffLine::Same("c".to_string()),
+                DiffLine::Added("d".to_string()),
+            ]
+        );
+        assert_eq!(line_diff(&[0xff, 0xfe], b"text"), None);
+    }
+}
@@ -0,0 +1,393 @@
+//! Dry-run support for agent operations
+//!
+//! A dry run reports what an operation would change without changing it.
+//! Where the underlying tool has a preview mode of its own (`pacman --print`,
+//! `paccache --dryrun`, `reflector` without `--save`), that preview runs and
+//! its output is returned; otherwise the planned commands are only described.
+//! Read-only operations have nothing to hold back and run as usual.
+
+use jarvis_core::exec::CommandRunner;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::ArchOperation;
+
+/// Options for `ArchAgent::execute_operation_with_options`
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct ExecOptions {
+    /// Report planned actions instead of performing them
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommandLine {
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+impl CommandLine {
+    pub fn new(program: &str, args: &[&str]) -> Self {
+        Self {
+            program: program.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+
+    fn with_args(mut self, args: &[String]) -> Self {
+        self.args.extend(args.iter().cloned());
+        self
+    }
+}
+
+impl fmt::Display for CommandLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.program)?;
+        for arg in &self.args {
+            write!(f, " {}", arg)?;
+        }
+        Ok(())
+    }
+}
+
+/// What an operation would do
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DryRunPlan {
+    /// Commands the operation runs for real; a dry run never executes these
+    pub actions: Vec<CommandLine>,
+    /// Read-only commands that show what `actions` would change
+    pub previews: Vec<CommandLine>,
+    /// Steps that have no single command to show
+    pub notes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreviewOutput {
+    pub command: CommandLine,
+    pub success: bool,
+    pub output: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DryRunReport {
+    pub actions: Vec<CommandLine>,
+    pub notes: Vec<String>,
+    pub previews: Vec<PreviewOutput>,
+    /// No native preview exists, so the plan is a description only
+    pub simulated: bool,
+}
+
+/// The commands and previews behind a state-changing operation. Read-only
+/// operations produce an empty plan.
+pub fn plan(operation: &ArchOperation) -> DryRunPlan {
+    let mut plan = DryRunPlan::default();
+    let pacman_preview = |args: &[&str], targets: &[String]| {
+        CommandLine::new("pacman", args)
+            .with_args(&["--print".to_string()])
+            .with_args(targets)
+    };
+
+    match operation {
+        ArchOperation::UpdatePackages { packages: None } => {
+            plan.actions
+                .push(CommandLine::new("pacman", &["-Syu", "--noconfirm"]));
+            plan.previews.push(pacman_preview(&["-Su"], &[]));
+            plan.notes.push(
+                "Preview uses the current sync databases; a real run refreshes them first"
+                    .to_string(),
+            );
+        }
+        ArchOperation::UpdatePackages {
+            packages: Some(packages),
+        } => {
+            plan.actions
+                .push(CommandLine::new("pacman", &["-S", "--noconfirm"]).with_args(packages));
+            plan.previews.push(pacman_preview(&["-S"], packages));
+        }
+        ArchOperation::InstallPackage {
+            package,
+            from_aur: false,
+        } => {
+            let targets = [package.clone()];
+            plan.actions
+                .push(CommandLine::new("pacman", &["-S", "--noconfirm"]).with_args(&targets));
+            plan.previews.push(pacman_preview(&["-S"], &targets));
+        }
+        ArchOperation::InstallPackage {
+            package,
+            from_aur: true,
+        } => {
+            plan.notes.push(format!(
+                "Build {} from the AUR in the sandbox, then install the built packages",
+                package
+            ));
+        }
+        ArchOperation::RebuildAurPackages { packages } => {
+            let targets = match packages {
+                Some(packages) => packages.join(", "),
+                None => "every package the last soname check found broken".to_string(),
+            };
+            plan.notes.push(format!(
+                "Rebuild {} from the AUR in the sandbox, then check foreign packages for missing libraries again",
+                targets
+            ));
+        }
+        ArchOperation::RemovePackage {
+            package,
+            remove_deps,
+        } => {
+            let flag = if *remove_deps { "-Rs" } else { "-R" };
+            let targets = [package.clone()];
+            plan.actions
+                .push(CommandLine::new("pacman", &[flag, "--noconfirm"]).with_args(&targets));
+            plan.previews.push(pacman_preview(&[flag], &targets));
+        }
+        ArchOperation::StageUpdates => {
+            plan.actions
+                .push(CommandLine::new("pacman", &["-Syuw", "--noconfirm"]));
+            plan.previews.push(pacman_preview(&["-Su"], &[]));
+        }
+        ArchOperation::ApplyStagedUpdates => {
+            plan.notes
+                .push("Install the staged update set from the package cache".to_string());
+            plan.previews.push(pacman_preview(&["-Su"], &[]));
+        }
+        ArchOperation::UpdateFlatpaks { refs } => {
+            plan.actions.push(
+                CommandLine::new("flatpak", &["update", "--noninteractive", "-y"])
+                    .with_args(refs.as_deref().unwrap_or_default()),
+            );
+            plan.previews.push(CommandLine::new(
+                "flatpak",
+                &[
+                    "remote-ls",
+                    "--updates",
+                    "--columns=application,version,branch,origin",
+                ],
+            ));
+        }
+        ArchOperation::SystemCleanup {
+            clean_cache,
+            clean_logs,
+        } => {
+            if *clean_cache {
+                plan.actions.push(CommandLine::new("paccache", &["-r"]));
+                plan.previews
+                    .push(CommandLine::new("paccache", &["--dryrun"]));
+            }
+            if *clean_logs {
+                plan.actions
+                    .push(CommandLine::new("journalctl", &["--vacuum-time=2weeks"]));
+                plan.previews
+                    .push(CommandLine::new("journalctl", &["--disk-usage"]));
+            }
+        }
+        ArchOperation::UpdateMirrorlist { country } => {
+            let mut reflector =
+                CommandLine::new("reflector", &["--latest", "20", "--sort", "rate"]);
+            if let Some(country) = country {
+                reflector = reflector.with_args(&["--country".to_string(), country.clone()]);
+            }
+            // Without --save, reflector prints the mirrorlist it would write
+            plan.previews.push(reflector.clone());
+            plan.actions.push(
+                reflector
+                    .with_args(&["--save".to_string(), "/etc/pacman.d/mirrorlist".to_string()]),
+            );
+        }
+        ArchOperation::ServiceOperation { service, operation } => {
+            let verb = format!("{:?}", operation).to_lowercase();
+            plan.actions
+                .push(CommandLine::new("systemctl", &[&verb, service]));
+            plan.previews.push(CommandLine::new(
+                "systemctl",
+                &["status", "--no-pager", service],
+            ));
+        }
+        ArchOperation::BackupConfigs { destination } => {
+            plan.notes.push(format!(
+                "Copy configuration files changed since the last backup to {} and prune old sets",
+                destination
+            ));
+        }
+        ArchOperation::RestoreConfigs { source } => {
+            plan.notes.push(format!(
+                "Overwrite configuration files that differ from backup set {}",
+                source
+            ));
+        }
+        ArchOperation::CustomCommand { command, args } => {
+            plan.actions
+                .push(CommandLine::new(command, &[]).with_args(args));
+            plan.notes
+                .push("Custom commands are never run in a dry run".to_string());
+        }
+        _ => {}
+    }
+
+    plan
+}
+
+/// Plan `operation` and run only its previews through `runner`
+pub async fn run(operation: &ArchOperation, runner: &dyn CommandRunner) -> DryRunReport {
+    let plan = plan(operation);
+    let mut previews = Vec::new();
+
+    for command in &plan.previews {
+        let args: Vec<&str> = command.args.iter().map(String::as_str).collect();
+        let preview = match runner.output(&command.program, &args).await {
+            Ok(output) => PreviewOutput {
+                command: command.clone(),
+                success: output.status.success(),
+                output: format!(
+                    "{}{}",
+                    String::from_utf8_lossy(&output.stdout),
+                    String::from_utf8_lossy(&output.stderr)
+                )
+                .trim()
+                .to_string(),
+            },
+            Err(e) => PreviewOutput {
+                command: command.clone(),
+                success: false,
+                output: e.to_string(),
+            },
+        };
+        previews.push(preview);
+    }
+
+    DryRunReport {
+        simulated: previews.is_empty(),
+        actions: plan.actions,
+        notes: plan.notes,
+        previews,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jarvis_core::exec::RecordingRunner;
+
+    /// Whether running `command` could change the system
+    fn mutates(command: &CommandLine) -> bool {
+        let has = |flag: &str| command.args.iter().any(|a| a == flag);
+        let first = command.args.first().map(String::as_str).unwrap_or("");
+        match command.program.as_str() {
+            "pacman" => {
+                !has("--print")
+                    && (first.starts_with("-S")
+                        || first.starts_with("-R")
+                        || first.starts_with("-U"))
+            }
+            "paccache" => !has("--dryrun") && !has("-d"),
+            "reflector" => has("--save"),
+            "flatpak" => !matches!(first, "remote-ls" | "list"),
+            "journalctl" => command.args.iter().any(|a| a.starts_with("--vacuum")),
+            "systemctl" => !matches!(first, "status" | "show" | "cat" | "is-active"),
+            _ => true,
+        }
+    }
+
+    fn mutating_operations() -> Vec<ArchOperation> {
+        vec![
+            ArchOperation::UpdatePackages { packages: None },
+            ArchOperation::UpdatePackages {
+                packages: Some(vec!["linux".to_string(), "mesa".to_string()]),
+            },
+            ArchOperation::InstallPackage {
+                package: "ripgrep".to_string(),
+                from_aur: false,
+            },
+            ArchOperation::InstallPackage {
+                package: "yay".to_string(),
+                from_aur: true,
+            },
+            ArchOperation::RemovePackage {
+                package: "nano".to_string(),
+                remove_deps: true,
+            },
+            ArchOperation::StageUpdates,
+            ArchOperation::ApplyStagedUpdates,
+            ArchOperation::UpdateFlatpaks { refs: None },
+            ArchOperation::SystemCleanup {
+                clean_cache: true,
+                clean_logs: true,
+            },
+            ArchOperation::UpdateMirrorlist {
+                country: Some("DE".to_string()),
+            },
+            ArchOperation::BackupConfigs {
+                destination: "/backup".to_string(),
+            },
+            ArchOperation::RestoreConfigs {
+                source: "/backup".to_string(),
+            },
+            ArchOperation::CustomCommand {
+                command: "rm".to_string(),
+                args: vec!["-rf".to_string(), "/var/cache".to_string()],
+            },
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_never_spawns_mutating_commands() {
+        for operation in mutating_operations() {
+            assert!(!operation.is_read_only(), "{:?}", operation);
+            let runner = RecordingRunner::new();
+            let report = run(&operation, &runner).await;
+
+            let spawned: Vec<CommandLine> = runner
+                .calls()
+                .iter()
+                .map(|line| {
+                    let mut parts = line.split(' ');
+                    let program = parts.next().unwrap_or_default();
+                    CommandLine::new(program, &parts.collect::<Vec<_>>())
+                })
+                .collect();
+            for command in &spawned {
+                assert!(!mutates(command), "{:?} spawned `{}`", operation, command);
+            }
+            assert_eq!(spawned, plan(&operation).previews);
+            assert!(
+                !report.actions.is_empty() || !report.notes.is_empty(),
+                "{:?} has an empty plan",
+                operation
+            );
+        }
+    }
+
+    #[test]
+    fn test_plan_actions_are_the_real_commands() {
+        let cleanup = plan(&ArchOperation::SystemCleanup {
+            clean_cache: true,
+            clean_logs: false,
+        });
+        assert_eq!(cleanup.actions, vec![CommandLine::new("paccache", &["-r"])]);
+        assert!(cleanup.actions.iter().all(mutates));
+        assert_eq!(cleanup.previews[0].to_string(), "paccache --dryrun");
+
+        let remove = plan(&ArchOperation::RemovePackage {
+            package: "nano".to_string(),
+            remove_deps: false,
+        });
+        assert_eq!(remove.previews[0].to_string(), "pacman -R --print nano");
+    }
+
+    #[tokio::test]
+    async fn test_operations_without_preview_are_simulated() {
+        let runner = RecordingRunner::new();
+        let report = run(
+            &ArchOperation::CustomCommand {
+                command: "rm".to_string(),
+                args: vec!["-rf".to_string(), "/".to_string()],
+            },
+            &runner,
+        )
+        .await;
+
+        assert!(report.simulated);
+        assert!(runner.calls().is_empty());
+        assert_eq!(report.actions[0].to_string(), "rm -rf /");
+    }
+}
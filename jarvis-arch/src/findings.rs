@@ -0,0 +1,271 @@
+//! Security finding lifecycle across scans
+//!
+//! Each finding a scan reports gets a stable fingerprint from its check,
+//! subject, and identifying attributes. Comparing a scan against the findings
+//! still open from earlier ones sorts it into what is new, what is still
+//! present, and what has been resolved, so only changes need attention.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use jarvis_core::severity::Severity;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::ZQLiteDatabase;
+
+/// Configuration key holding the open findings between scans
+pub const FINDINGS_KEY: &str = "security_findings";
+
+/// Attributes that tell two findings of one check on one subject apart
+const KEY_ATTRIBUTES: &[&str] = &["cve_id", "rule", "port", "permission", "setting"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrackedFinding {
+    pub fingerprint: String,
+    pub check: String,
+    pub subject: String,
+    pub severity: Severity,
+    pub description: String,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    #[serde(default)]
+    pub resolved_at: Option<DateTime<Utc>>,
+    /// The finding as the scanner reported it
+    #[serde(default)]
+    pub details: serde_json::Value,
+}
+
+/// Open findings carried from one scan to the next
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FindingStore {
+    pub open: Vec<TrackedFinding>,
+    /// Findings resolved over the store's lifetime
+    pub resolved_total: u64,
+}
+
+/// One scan's findings grouped by lifecycle state
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScanLifecycle {
+    pub new: Vec<TrackedFinding>,
+    pub still_present: Vec<TrackedFinding>,
+    pub resolved: Vec<TrackedFinding>,
+}
+
+impl ScanLifecycle {
+    /// Whether anything changed since the previous scan
+    pub fn has_changes(&self) -> bool {
+        !self.new.is_empty() || !self.resolved.is_empty()
+    }
+}
+
+/// Stable identity of a finding: the check, its subject, and the key
+/// attributes in a fixed order
+pub fn fingerprint(check: &str, subject: &str, attributes: &[(&str, &str)]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(check.as_bytes());
+    hasher.update([0]);
+    hasher.update(subject.as_bytes());
+    for (key, value) in attributes {
+        hasher.update([0]);
+        hasher.update(key.as_bytes());
+        hasher.update(b"=");
+        hasher.update(value.as_bytes());
+    }
+    hex::encode(&hasher.finalize()[..16])
+}
+
+/// Findings in a scan result's `issues` array, first and last seen `now`.
+/// The check comes from `check`, `category`, or `issue_type`, and the subject
+/// from `package`, `path`, `subject`, or `target`.
+pub fn from_scan(scan: &serde_json::Value, now: DateTime<Utc>) -> Vec<TrackedFinding> {
+    let Some(issues) = scan.get("issues").and_then(|i| i.as_array()) else {
+        return Vec::new();
+    };
+    let text = |issue: &serde_json::Value, keys: &[&str]| {
+        keys.iter()
+            .find_map(|key| issue.get(*key).and_then(|v| v.as_str()))
+            .unwrap_or_default()
+            .to_string()
+    };
+
+    issues
+        .iter()
+        .map(|issue| {
+            let check = text(issue, &["check", "category", "issue_type"]);
+            let subject = text(issue, &["package", "path", "subject", "target"]);
+            let attributes: Vec<(&str, String)> = KEY_ATTRIBUTES
+                .iter()
+                .filter_map(|key| {
+                    issue.get(*key).filter(|v| !v.is_null()).map(|v| {
+                        let value = v
+                            .as_str()
+                            .map(str::to_string)
+                            .unwrap_or_else(|| v.to_string());
+                        (*key, value)
+                    })
+                })
+                .collect();
+            let attributes: Vec<(&str, &str)> =
+                attributes.iter().map(|(k, v)| (*k, v.as_str())).collect();
+
+            TrackedFinding {
+                fingerprint: fingerprint(&check, &subject, &attributes),
+                severity: text(issue, &["severity"]).parse().unwrap_or_default(),
+                description: text(issue, &["description", "message", "title"]),
+                check,
+                subject,
+                first_seen: now,
+                last_seen: now,
+                resolved_at: None,
+                details: issue.clone(),
+            }
+        })
+        .collect()
+}
+
+impl FindingStore {
+    pub async fn load(database: &ZQLiteDatabase) -> Result<Self> {
+        match database.get_config_value(FINDINGS_KEY).await? {
+            Some(json) => Ok(serde_json::from_str(&json)?),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub async fn save(&self, database: &ZQLiteDatabase) -> Result<()> {
+        database
+            .set_config_value(
+                FINDINGS_KEY,
+                &serde_json::to_string(self)?,
+                "Security findings open as of the last scan",
+            )
+            .await
+    }
+
+    /// Fold one scan's findings into the store. A finding seen before keeps
+    /// its `first_seen`; one missing from this scan is resolved.
+    pub fn reconcile(&mut self, found: Vec<TrackedFinding>, now: DateTime<Utc>) -> ScanLifecycle {
+        let mut lifecycle = ScanLifecycle::default();
+        let mut previous = std::mem::take(&mut self.open);
+
+        for mut finding in found {
+            if self
+                .open
+                .iter()
+                .any(|f| f.fingerprint == finding.fingerprint)
+            {
+                continue; // reported twice in one scan
+            }
+            match previous
+                .iter()
+                .position(|f| f.fingerprint == finding.fingerprint)
+            {
+                Some(index) => {
+                    finding.first_seen = previous.swap_remove(index).first_seen;
+                    finding.last_seen = now;
+                    lifecycle.still_present.push(finding.clone());
+                }
+                None => lifecycle.new.push(finding.clone()),
+            }
+            self.open.push(finding);
+        }
+
+        for mut finding in previous {
+            finding.resolved_at = Some(now);
+            lifecycle.resolved.push(finding);
+        }
+        self.resolved_total += lifecycle.resolved.len() as u64;
+
+        lifecycle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn scan(issues: serde_json::Value) -> serde_json::Value {
+        serde_json::json!({ "issues": issues })
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_wording_but_not_identity() {
+        let now = Utc::now();
+        let a = from_scan(
+            &scan(serde_json::json!([
+                { "check": "world_writable", "path": "/etc/foo", "severity": "high", "description": "foo is writable" },
+                { "check": "world_writable", "path": "/etc/bar", "severity": "high" },
+                { "check": "cve", "package": "openssl", "cve_id": "CVE-2024-1", "severity": "critical" },
+                { "check": "cve", "package": "openssl", "cve_id": "CVE-2024-2", "severity": "critical" },
+            ])),
+            now,
+        );
+        let b = from_scan(
+            &scan(serde_json::json!([
+                { "check": "world_writable", "path": "/etc/foo", "severity": "medium", "description": "reworded" },
+            ])),
+            now,
+        );
+
+        assert_eq!(a[0].fingerprint, b[0].fingerprint);
+        assert_ne!(a[0].fingerprint, a[1].fingerprint);
+        assert_ne!(a[2].fingerprint, a[3].fingerprint);
+        assert_eq!(a[2].severity, Severity::Critical);
+    }
+
+    #[test]
+    fn test_reconcile_classifies_new_present_and_resolved() {
+        let first = Utc::now() - Duration::days(2);
+        let now = Utc::now();
+        let mut store = FindingStore::default();
+
+        let lifecycle = store.reconcile(
+            from_scan(
+                &scan(serde_json::json!([
+                    { "check": "ssh", "subject": "PermitRootLogin", "severity": "high" },
+                    { "check": "firewall", "subject": "nftables", "severity": "medium" },
+                ])),
+                first,
+            ),
+            first,
+        );
+        assert_eq!(lifecycle.new.len(), 2);
+        assert!(lifecycle.has_changes());
+
+        let lifecycle = store.reconcile(
+            from_scan(
+                &scan(serde_json::json!([
+                    { "check": "ssh", "subject": "PermitRootLogin", "severity": "high" },
+                    { "check": "sudo", "subject": "NOPASSWD", "severity": "medium" },
+                    { "check": "sudo", "subject": "NOPASSWD", "severity": "medium" },
+                ])),
+                now,
+            ),
+            now,
+        );
+
+        assert_eq!(lifecycle.new.len(), 1);
+        assert_eq!(lifecycle.new[0].check, "sudo");
+        assert_eq!(lifecycle.still_present.len(), 1);
+        assert_eq!(lifecycle.still_present[0].first_seen, first);
+        assert_eq!(lifecycle.still_present[0].last_seen, now);
+        assert_eq!(lifecycle.resolved.len(), 1);
+        assert_eq!(lifecycle.resolved[0].check, "firewall");
+        assert_eq!(lifecycle.resolved[0].resolved_at, Some(now));
+        assert_eq!(store.open.len(), 2);
+        assert_eq!(store.resolved_total, 1);
+
+        // An unchanged rescan has nothing to report
+        let again = store.reconcile(
+            from_scan(
+                &scan(serde_json::json!([
+                    { "check": "ssh", "subject": "PermitRootLogin", "severity": "high" },
+                    { "check": "sudo", "subject": "NOPASSWD", "severity": "medium" },
+                ])),
+                now,
+            ),
+            now,
+        );
+        assert!(!again.has_changes());
+    }
+}
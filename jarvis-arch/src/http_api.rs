@@ -0,0 +1,582 @@
+//! HTTP API over an `ArchAgent`
+//!
+//! Mirrors the `jarvis arch` CLI: `GET /health`, `GET /status`,
+//! `GET /operations?since=`, `POST /operations`, `GET /operations/:id`, and
+//! `GET /metrics` in the Prometheus text format, plus `GET /findings.sarif`
+//! with the open findings as SARIF 2.1.0 for code scanning tools. A posted
+//! operation runs in the background and the response carries the id to poll
+//! for its result.
+//!
+//! Submissions follow the CLI's rules. An operation that changes the system
+//! needs `?confirm=true`, the API's `--yes`, or `?dry_run=true` to preview it;
+//! operations that need root are refused up front when the daemon has no way
+//! to elevate them. The routes are thin wrappers over `ApiState`, so other
+//! front ends share the same logic.
+//!
+//! A `POST /operations` with an `Idempotency-Key` header is safe to retry:
+//! the same key with the same operation within the configured window answers
+//! `200` with the operation the first attempt started instead of starting
+//! another, and the same key with a different operation is refused with `422`.
+//!
+//! With an MCP server attached, `GET /mcp` upgrades to a WebSocket speaking
+//! MCP, one JSON-RPC message per text frame, behind the same token.
+
+use anyhow::Result;
+use axum::{
+    Router,
+    extract::{
+        ConnectInfo, Path, Query, Request, State,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+    },
+    http::{StatusCode, header},
+    middleware::{self, Next},
+    response::{IntoResponse, Json, Response},
+    routing::get,
+};
+use chrono::{DateTime, Utc};
+use futures::{SinkExt, StreamExt};
+use jarvis_core::idempotency::{self, Claim, IdempotencyKeys, KeyReused};
+use jarvis_core::mcp::{NetworkServer, Peer};
+use jarvis_core::privilege::{NotPermitted, Privilege, PrivilegeConfig};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::{RwLock, mpsc};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::dry_run::{self, DryRunPlan, ExecOptions};
+use crate::sarif;
+use crate::{AgentHealth, AgentStatus, ArchAgent, ArchOperation, OperationResult};
+
+/// Submitted operations kept for `GET /operations`; the oldest are dropped first
+pub const MAX_OPERATIONS: usize = 500;
+
+/// Header a client sets to make `POST /operations` safe to retry
+pub const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationState {
+    Running,
+    Finished,
+    Failed,
+}
+
+/// An operation submitted through the API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperationEntry {
+    pub id: Uuid,
+    pub operation: ArchOperation,
+    pub dry_run: bool,
+    pub state: OperationState,
+    pub submitted_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Set once the agent returns a result, successful or not
+    pub result: Option<OperationResult>,
+    /// Set when the agent could not run the operation at all
+    pub error: Option<String>,
+}
+
+/// Query parameters of `POST /operations`
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct SubmitOptions {
+    /// Report what the operation would do without doing it
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Run an operation that changes the system, like the CLI's `--yes`
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+/// Why a submitted operation was turned away
+#[derive(Debug, thiserror::Error)]
+pub enum SubmitError {
+    #[error("{name} changes the system; resubmit with confirm=true, or dry_run=true to preview it")]
+    NeedsConfirmation {
+        name: &'static str,
+        plan: DryRunPlan,
+    },
+    #[error("{name} was refused: {refused}. Use dry_run=true to preview it")]
+    NotPermitted {
+        name: &'static str,
+        refused: NotPermitted,
+    },
+    #[error(transparent)]
+    KeyReused(#[from] KeyReused),
+}
+
+/// An accepted submission
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Submission {
+    pub id: Uuid,
+    /// The idempotency key was used before, and `id` is the operation that
+    /// submission started
+    pub replayed: bool,
+}
+
+/// Agent and operation log shared by the routes
+#[derive(Clone)]
+pub struct ApiState {
+    agent: Arc<dyn ArchAgent>,
+    token: Option<String>,
+    operations: Arc<RwLock<VecDeque<OperationEntry>>>,
+    /// Idempotency key -> id of the operation it started
+    idempotency: Arc<IdempotencyKeys<Uuid>>,
+    idempotency_window: Duration,
+    privilege: Privilege,
+    mcp: Option<Arc<NetworkServer>>,
+}
+
+impl ApiState {
+    /// `token`, when set, must be sent as `Authorization: Bearer <token>`
+    pub fn new(agent: Arc<dyn ArchAgent>, token: Option<String>) -> Self {
+        Self {
+            agent,
+            token,
+            operations: Arc::new(RwLock::new(VecDeque::new())),
+            idempotency: Arc::new(IdempotencyKeys::new()),
+            idempotency_window: idempotency::DEFAULT_WINDOW,
+            privilege: Privilege::detect(&PrivilegeConfig::default()),
+            mcp: None,
+        }
+    }
+
+    /// How the agent elevates operations that need root, so they can be
+    /// refused up front when it can't
+    pub fn with_privilege(mut self, privilege: Privilege) -> Self {
+        self.privilege = privilege;
+        self
+    }
+
+    /// How long idempotency keys are remembered
+    pub fn with_idempotency_window(mut self, window: Duration) -> Self {
+        self.idempotency_window = window;
+        self
+    }
+
+    /// Serve MCP clients over a WebSocket at `/mcp`
+    pub fn with_mcp(mut self, server: Arc<NetworkServer>) -> Self {
+        self.mcp = Some(server);
+        self
+    }
+
+    /// The idempotency keys, for the owner's cleanup job to prune
+    pub fn idempotency_keys(&self) -> Arc<IdempotencyKeys<Uuid>> {
+        self.idempotency.clone()
+    }
+
+    pub async fn health(&self) -> Result<AgentHealth> {
+        self.agent.health_check().await
+    }
+
+    pub async fn status(&self) -> Result<AgentStatus> {
+        self.agent.get_status().await
+    }
+
+    /// Submitted operations, newest first, optionally only those submitted since a time
+    pub async fn operations(&self, since: Option<DateTime<Utc>>) -> Vec<OperationEntry> {
+        self.operations
+            .read()
+            .await
+            .iter()
+            .rev()
+            .filter(|entry| since.is_none_or(|since| entry.submitted_at >= since))
+            .cloned()
+            .collect()
+    }
+
+    pub async fn operation(&self, id: Uuid) -> Option<OperationEntry> {
+        self.operations
+            .read()
+            .await
+            .iter()
+            .find(|entry| entry.id == id)
+            .cloned()
+    }
+
+    /// Check an operation against the CLI's rules and start it in the
+    /// background, unless `idempotency_key` already started it; returns the
+    /// id to poll
+    pub async fn submit(
+        &self,
+        operation: ArchOperation,
+        options: SubmitOptions,
+        idempotency_key: Option<&str>,
+    ) -> Result<Submission, SubmitError> {
+        let name = operation.name();
+        if !options.dry_run && !options.confirm && !operation.is_read_only() {
+            return Err(SubmitError::NeedsConfirmation {
+                name,
+                plan: dry_run::plan(&operation),
+            });
+        }
+        if !options.dry_run
+            && let Err(refused) = self.privilege.elevation(&operation.privileges())
+        {
+            return Err(SubmitError::NotPermitted { name, refused });
+        }
+
+        let id = Uuid::new_v4();
+        if let Some(key) = idempotency_key {
+            let request = serde_json::json!({ "operation": operation, "dry_run": options.dry_run });
+            let claim = self.idempotency.claim(
+                key,
+                &request.to_string(),
+                self.idempotency_window,
+                || id,
+            )?;
+            if let Claim::Existing(existing) = claim {
+                info!(
+                    "API: {} retried with key {}, already {}",
+                    name, key, existing
+                );
+                return Ok(Submission {
+                    id: existing,
+                    replayed: true,
+                });
+            }
+        }
+        {
+            let mut operations = self.operations.write().await;
+            operations.push_back(OperationEntry {
+                id,
+                operation: operation.clone(),
+                dry_run: options.dry_run,
+                state: OperationState::Running,
+                submitted_at: Utc::now(),
+                finished_at: None,
+                result: None,
+                error: None,
+            });
+            // Never drop an operation that is still running
+            while operations.len() > MAX_OPERATIONS {
+                match operations
+                    .iter()
+                    .position(|entry| entry.state != OperationState::Running)
+                {
+                    Some(index) => {
+                        operations.remove(index);
+                    }
+                    None => break,
+                }
+            }
+        }
+        info!(
+            "API: {} submitted as {} (dry run: {})",
+            name, id, options.dry_run
+        );
+
+        let state = self.clone();
+        tokio::spawn(async move {
+            let outcome = if options.dry_run {
+                state
+                    .agent
+                    .execute_operation_with_options(operation, ExecOptions { dry_run: true })
+                    .await
+            } else {
+                state.agent.execute_cancellable(id, operation).await
+            };
+            if let Err(e) = &outcome {
+                warn!("API operation {} failed: {}", id, e);
+            }
+
+            let mut operations = state.operations.write().await;
+            if let Some(entry) = operations.iter_mut().find(|entry| entry.id == id) {
+                entry.finished_at = Some(Utc::now());
+                match outcome {
+                    Ok(result) => {
+                        entry.state = if result.succeeded() {
+                            OperationState::Finished
+                        } else {
+                            OperationState::Failed
+                        };
+                        entry.result = Some(result);
+                    }
+                    Err(e) => {
+                        entry.state = OperationState::Failed;
+                        entry.error = Some(e.to_string());
+                    }
+                }
+            }
+        });
+
+        Ok(Submission {
+            id,
+            replayed: false,
+        })
+    }
+
+    /// Open findings as a SARIF log
+    pub async fn findings_sarif(&self) -> Result<serde_json::Value> {
+        Ok(self.agent.open_findings().await?.to_sarif())
+    }
+
+    /// Agent health and API operation counts in the Prometheus text format
+    pub async fn metrics(&self) -> Result<String> {
+        let health = self.agent.health_check().await?;
+        let mut out = String::new();
+        let mut gauge = |name: &str, help: &str, value: f64| {
+            let _ = writeln!(out, "# HELP jarvis_arch_{} {}", name, help);
+            let _ = writeln!(out, "# TYPE jarvis_arch_{} gauge", name);
+            let _ = writeln!(out, "jarvis_arch_{} {}", name, value);
+        };
+        gauge(
+            "uptime_seconds",
+            "Seconds since the agent started",
+            health.uptime_seconds as f64,
+        );
+        gauge(
+            "error_count",
+            "Operations that failed",
+            health.error_count as f64,
+        );
+        gauge(
+            "success_rate",
+            "Share of operations that succeeded",
+            health.success_rate,
+        );
+        gauge("system_load", "One-minute load average", health.system_load);
+        gauge(
+            "memory_usage_percent",
+            "Memory in use",
+            health.memory_usage_percent,
+        );
+        gauge(
+            "disk_usage_percent",
+            "Root filesystem in use",
+            health.disk_usage_percent,
+        );
+        gauge(
+            "active_operations",
+            "Operations the agent is running",
+            health.active_operations as f64,
+        );
+
+        let status = serde_json::to_value(&health.status)
+            .ok()
+            .and_then(|s| s.as_str().map(str::to_lowercase))
+            .unwrap_or_default();
+        let _ = writeln!(out, "# HELP jarvis_arch_health Agent health status");
+        let _ = writeln!(out, "# TYPE jarvis_arch_health gauge");
+        let _ = writeln!(out, "jarvis_arch_health{{status=\"{}\"}} 1", status);
+
+        let operations = self.operations.read().await;
+        let _ = writeln!(
+            out,
+            "# HELP jarvis_arch_api_operations Operations submitted through the API"
+        );
+        let _ = writeln!(out, "# TYPE jarvis_arch_api_operations gauge");
+        for (label, state) in [
+            ("running", OperationState::Running),
+            ("finished", OperationState::Finished),
+            ("failed", OperationState::Failed),
+        ] {
+            let count = operations.iter().filter(|e| e.state == state).count();
+            let _ = writeln!(
+                out,
+                "jarvis_arch_api_operations{{state=\"{}\"}} {}",
+                label, count
+            );
+        }
+        Ok(out)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct OperationsQuery {
+    /// Time range such as "24h", "7d", or "since monday", as in `jarvis arch history --since`
+    since: Option<String>,
+}
+
+/// Build the API router
+pub fn router(state: ApiState) -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .route("/status", get(status))
+        .route("/operations", get(list_operations).post(submit_operation))
+        .route("/operations/:id", get(get_operation))
+        .route("/metrics", get(metrics))
+        .route("/findings.sarif", get(findings_sarif))
+        .route("/mcp", get(mcp_socket))
+        .layer(middleware::from_fn_with_state(state.clone(), require_token))
+        .with_state(state)
+}
+
+/// Serve the API on `listener` until the task is dropped
+pub async fn serve(listener: TcpListener, state: ApiState) -> Result<()> {
+    info!("HTTP API listening on {}", listener.local_addr()?);
+    let app = router(state).into_make_service_with_connect_info::<SocketAddr>();
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+async fn require_token(State(state): State<ApiState>, request: Request, next: Next) -> Response {
+    let Some(expected) = &state.token else {
+        return next.run(request).await;
+    };
+    let token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim);
+
+    if token == Some(expected.as_str()) {
+        return next.run(request).await;
+    }
+    let mut response = error(StatusCode::UNAUTHORIZED, "Missing or invalid API token");
+    response.headers_mut().insert(
+        header::WWW_AUTHENTICATE,
+        header::HeaderValue::from_static("Bearer"),
+    );
+    response
+}
+
+fn error(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(serde_json::json!({ "error": message.into() }))).into_response()
+}
+
+fn internal(e: anyhow::Error) -> Response {
+    error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+async fn health(State(state): State<ApiState>) -> Response {
+    match state.health().await {
+        Ok(health) => Json(health).into_response(),
+        Err(e) => internal(e),
+    }
+}
+
+async fn status(State(state): State<ApiState>) -> Response {
+    match state.status().await {
+        Ok(status) => Json(status).into_response(),
+        Err(e) => internal(e),
+    }
+}
+
+async fn list_operations(
+    State(state): State<ApiState>,
+    Query(query): Query<OperationsQuery>,
+) -> Response {
+    let since = match query
+        .since
+        .as_deref()
+        .map(jarvis_core::time_range::parse_range)
+    {
+        Some(Ok(range)) => Some(range.start),
+        Some(Err(e)) => return error(StatusCode::BAD_REQUEST, format!("since: {}", e)),
+        None => None,
+    };
+    Json(state.operations(since).await).into_response()
+}
+
+async fn submit_operation(
+    State(state): State<ApiState>,
+    Query(options): Query<SubmitOptions>,
+    headers: header::HeaderMap,
+    Json(operation): Json<ArchOperation>,
+) -> Response {
+    let key = match headers.get(IDEMPOTENCY_KEY).map(|value| value.to_str()) {
+        Some(Ok(key)) if !key.trim().is_empty() => Some(key.trim()),
+        Some(_) => return error(StatusCode::BAD_REQUEST, "Invalid Idempotency-Key header"),
+        None => None,
+    };
+    match state.submit(operation, options, key).await {
+        // A retry gets the operation's current state
+        Ok(Submission { id, replayed: true }) => match state.operation(id).await {
+            Some(entry) => Json(entry).into_response(),
+            None => {
+                Json(serde_json::json!({ "id": id, "dry_run": options.dry_run })).into_response()
+            }
+        },
+        Ok(Submission { id, .. }) => (
+            StatusCode::ACCEPTED,
+            Json(serde_json::json!({ "id": id, "dry_run": options.dry_run })),
+        )
+            .into_response(),
+        Err(e) => {
+            let message = e.to_string();
+            match e {
+                SubmitError::NeedsConfirmation { plan, .. } => (
+                    StatusCode::PRECONDITION_REQUIRED,
+                    Json(serde_json::json!({ "error": message, "plan": plan })),
+                )
+                    .into_response(),
+                SubmitError::NotPermitted { .. } => error(StatusCode::FORBIDDEN, message),
+                SubmitError::KeyReused(_) => error(StatusCode::UNPROCESSABLE_ENTITY, message),
+            }
+        }
+    }
+}
+
+async fn mcp_socket(
+    State(state): State<ApiState>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    let Some(server) = state.mcp.clone() else {
+        return error(StatusCode::NOT_FOUND, "MCP is not enabled on this daemon");
+    };
+    let addr = peer
+        .map(|ConnectInfo(addr)| addr)
+        .unwrap_or_else(|| SocketAddr::from(([0, 0, 0, 0], 0)));
+    // The token middleware already let this request through
+    let peer = Peer::new("ws", addr).authenticated(state.token.is_some());
+    upgrade.on_upgrade(move |socket| serve_mcp(server, socket, peer))
+}
+
+async fn serve_mcp(server: Arc<NetworkServer>, socket: WebSocket, peer: Peer) {
+    let (mut sink, stream) = socket.split();
+    let (tx, mut rx) = mpsc::channel::<String>(32);
+    let writing = tokio::spawn(async move {
+        while let Some(message) = rx.recv().await {
+            if sink.send(Message::Text(message)).await.is_err() {
+                break;
+            }
+        }
+        let _ = sink.close().await;
+    });
+
+    let incoming = stream
+        .take_while(|message| {
+            std::future::ready(!matches!(message, Ok(Message::Close(_)) | Err(_)))
+        })
+        .filter_map(|message| {
+            std::future::ready(match message {
+                Ok(Message::Text(text)) => Some(Ok::<_, std::io::Error>(text)),
+                _ => None,
+            })
+        });
+    server.run_session(peer, Box::pin(incoming), tx).await;
+    let _ = writing.await;
+}
+
+async fn get_operation(State(state): State<ApiState>, Path(id): Path<Uuid>) -> Response {
+    match state.operation(id).await {
+        Some(entry) => Json(entry).into_response(),
+        None => error(StatusCode::NOT_FOUND, format!("No operation {}", id)),
+    }
+}
+
+async fn metrics(State(state): State<ApiState>) -> Response {
+    match state.metrics().await {
+        Ok(body) => ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body).into_response(),
+        Err(e) => internal(e),
+    }
+}
+
+async fn findings_sarif(State(state): State<ApiState>) -> Response {
+    match state.findings_sarif().await {
+        Ok(sarif) => (
+            [(header::CONTENT_TYPE, sarif::SARIF_CONTENT_TYPE)],
+            sarif.to_string(),
+        )
+            .into_response(),
+        Err(e) => internal(e),
+    }
+}
@@ -1,30 +1,78 @@
+pub mod active_response;
 pub mod package_manager;
 pub mod aur_monitor;
+pub mod aur_sandbox;
 pub mod system_health;
+pub mod findings;
 pub mod security_scanner;
 pub mod maintenance_scheduler;
+pub mod maintenance_chain;
+pub mod maintenance_guard;
 pub mod config;
+pub mod config_backup;
+pub mod dry_run;
+pub mod http_api;
+pub mod log_analysis;
+pub mod operation_output;
+pub mod operation_registry;
+pub mod rollback;
+pub mod sarif;
 pub mod vulnerability_scanner;
 pub mod service_manager;
+pub mod shutdown;
+pub mod soname_rebuild;
+pub mod subsystem;
 pub mod wazuh;
 pub mod zqlite_integration;
 
 // Re-export main types
+pub use active_response::{ActiveResponder, AgentExecutor, ResponseCommand, ResponseExecutor, SignedCommand};
 pub use package_manager::{PackageManager, PackageInfo, PackageOperation, PackageStatus};
 pub use aur_monitor::{AURMonitor, AURPackage, AURSecurityIssue};
+pub use aur_sandbox::{AurSandbox, AurBuildResult, PkgbuildFinding};
 pub use system_health::{SystemHealth, HealthMetric, HealthStatus};
 pub use security_scanner::{SecurityScanner, SecurityIssue, SecuritySeverity};
 pub use maintenance_scheduler::{MaintenanceScheduler, MaintenanceTask, MaintenanceResult};
-pub use config::{Config, AgentConfig, PacmanConfig, SystemConfig, WazuhConfig};
+pub use maintenance_chain::{ChainPreset, ChainResult, OnFailure};
+pub use config::{Config, AgentConfig, PacmanConfig, ShutdownConfig, SystemConfig, WazuhConfig};
+pub use config_backup::{BackupSet, BackupStore, BackupSummary, RetentionPolicy};
+pub use dry_run::{DryRunReport, ExecOptions};
+pub use operation_output::{
+    CleanupReport, FailureReport, HealthReport, OperationOutput, PackageUpdateReport, ScanReport,
+    ServiceActionReport,
+};
+pub use operation_registry::OperationRegistry;
+pub use rollback::{RollbackAction, RollbackPlan};
 pub use vulnerability_scanner::{VulnerabilityScanner, Vulnerability, CVEInfo};
 pub use service_manager::{ServiceManager, ServiceInfo, ServiceOperation};
+pub use shutdown::{DrainReport, DrainStep, ShutdownOutcome};
+pub use soname_rebuild::{RebuildNeeded, RebuildReport};
+pub use subsystem::{NotAvailable, Subsystem, SubsystemStatus};
 pub use wazuh::{WazuhIntegration, SecurityEvent, RiskLevel};
 pub use zqlite_integration::{ZQLiteDatabase, DatabaseConfig};
+pub use jarvis_core::privilege::running_as_root;
 
 use anyhow::Result;
 use async_trait::async_trait;
+use jarvis_core::bus::{self, BusEvent};
+use jarvis_core::exec::{CommandRunner, OutputText, SystemRunner};
+use jarvis_core::health_checks::LatestOutcomes;
+use jarvis_core::journal::{self, ActionRecord, ActionType};
+use jarvis_core::notify::{Notification, Notifier, NotifyEvent, NotifySeverity};
+use jarvis_core::pacman_lock::LockHeld;
+use jarvis_core::preflight::{PackageAction, PreflightReport};
+use jarvis_core::privilege::{
+    Capability, Elevation, ElevatingRunner, NotPermitted, Privilege, PrivilegeConfig,
+};
+use jarvis_core::trivy::{ImageScan, ImageScanCache};
+use jarvis_core::types::AuditStatus;
+use jarvis_core::vuln::Acknowledgements;
+use operation_output::{
+    AurPackageCheck, CommandStep, FindingSummary, ImageFindings, MetricSummary, PackageList,
+};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 use uuid::Uuid;
 
 /// Main Arch Linux agent interface
@@ -42,9 +90,33 @@ pub trait ArchAgent: Send + Sync {
     /// Execute an operation
     async fn execute_operation(&self, operation: ArchOperation) -> Result<OperationResult>;
     
+    /// Execute an operation, or with `dry_run` report what it would do without side effects
+    async fn execute_operation_with_options(
+        &self,
+        operation: ArchOperation,
+        options: ExecOptions,
+    ) -> Result<OperationResult>;
+    
+    /// Execute an operation that `cancel_operation(operation_id)` can stop
+    /// before it finishes; a cancelled operation fails with `JarvisError::Cancelled`
+    async fn execute_cancellable(
+        &self,
+        operation_id: Uuid,
+        operation: ArchOperation,
+    ) -> Result<OperationResult>;
+    
+    /// Stop an operation started with `execute_cancellable`; false when none
+    /// runs under `operation_id`
+    fn cancel_operation(&self, operation_id: Uuid) -> bool;
+    
     /// Get current status
     async fn get_status(&self) -> Result<AgentStatus>;
     
+    /// Findings still open as of the last run of each check, for SARIF export
+    async fn open_findings(&self) -> Result<sarif::OpenFindings> {
+        Ok(sarif::OpenFindings::default())
+    }
+    
     /// Shutdown the agent
     async fn shutdown(&mut self) -> Result<()>;
 }
@@ -57,6 +129,16 @@ pub enum ArchOperation {
     InstallPackage { package: String, from_aur: bool },
     RemovePackage { package: String, remove_deps: bool },
     SearchPackages { query: String, include_aur: bool },
+    StageUpdates,
+    ApplyStagedUpdates,
+    /// Rebuild AUR packages in the sandbox; `None` rebuilds every package the
+    /// last soname check found linked against a library that is gone
+    RebuildAurPackages { packages: Option<Vec<String>> },
+    
+    // Flatpak
+    ListFlatpaks,
+    CheckFlatpakUpdates,
+    UpdateFlatpaks { refs: Option<Vec<String>> },
     
     // System maintenance
     SystemCleanup { clean_cache: bool, clean_logs: bool },
@@ -75,10 +157,13 @@ pub enum ArchOperation {
     // System monitoring
     HealthCheck { include_services: bool },
     PerformanceAnalysis { duration_minutes: u32 },
-    LogAnalysis { service: Option<String>, hours: u32 },
+    /// `since` is any time range, e.g. "24h", "yesterday", "since boot"
+    LogAnalysis { service: Option<String>, since: String },
     
     // Configuration management
+    /// Differential backup of the configured paths into the store at `destination`
     BackupConfigs { destination: String },
+    /// `source` is a backup set id in the configured store
     RestoreConfigs { source: String },
     ValidateConfigs,
     
@@ -86,17 +171,247 @@ pub enum ArchOperation {
     CustomCommand { command: String, args: Vec<String> },
 }
 
+impl ArchOperation {
+    /// Every variant name, as used in serialized operations
+    pub const NAMES: &'static [&'static str] = &[
+        "UpdatePackages",
+        "InstallPackage",
+        "RemovePackage",
+        "SearchPackages",
+        "StageUpdates",
+        "ApplyStagedUpdates",
+        "RebuildAurPackages",
+        "ListFlatpaks",
+        "CheckFlatpakUpdates",
+        "UpdateFlatpaks",
+        "SystemCleanup",
+        "UpdateMirrorlist",
+        "CheckDiskUsage",
+        "SecurityScan",
+        "VulnerabilityScan",
+        "AURSecurityCheck",
+        "ServiceOperation",
+        "ListServices",
+        "HealthCheck",
+        "PerformanceAnalysis",
+        "LogAnalysis",
+        "BackupConfigs",
+        "RestoreConfigs",
+        "ValidateConfigs",
+        "CustomCommand",
+    ];
+    
+    /// The variant name, one of `NAMES`
+    pub fn name(&self) -> &'static str {
+        match self {
+            ArchOperation::UpdatePackages { .. } => "UpdatePackages",
+            ArchOperation::InstallPackage { .. } => "InstallPackage",
+            ArchOperation::RemovePackage { .. } => "RemovePackage",
+            ArchOperation::SearchPackages { .. } => "SearchPackages",
+            ArchOperation::StageUpdates => "StageUpdates",
+            ArchOperation::ApplyStagedUpdates => "ApplyStagedUpdates",
+            ArchOperation::RebuildAurPackages { .. } => "RebuildAurPackages",
+            ArchOperation::ListFlatpaks => "ListFlatpaks",
+            ArchOperation::CheckFlatpakUpdates => "CheckFlatpakUpdates",
+            ArchOperation::UpdateFlatpaks { .. } => "UpdateFlatpaks",
+            ArchOperation::SystemCleanup { .. } => "SystemCleanup",
+            ArchOperation::UpdateMirrorlist { .. } => "UpdateMirrorlist",
+            ArchOperation::CheckDiskUsage { .. } => "CheckDiskUsage",
+            ArchOperation::SecurityScan { .. } => "SecurityScan",
+            ArchOperation::VulnerabilityScan { .. } => "VulnerabilityScan",
+            ArchOperation::AURSecurityCheck { .. } => "AURSecurityCheck",
+            ArchOperation::ServiceOperation { .. } => "ServiceOperation",
+            ArchOperation::ListServices { .. } => "ListServices",
+            ArchOperation::HealthCheck { .. } => "HealthCheck",
+            ArchOperation::PerformanceAnalysis { .. } => "PerformanceAnalysis",
+            ArchOperation::LogAnalysis { .. } => "LogAnalysis",
+            ArchOperation::BackupConfigs { .. } => "BackupConfigs",
+            ArchOperation::RestoreConfigs { .. } => "RestoreConfigs",
+            ArchOperation::ValidateConfigs => "ValidateConfigs",
+            ArchOperation::CustomCommand { .. } => "CustomCommand",
+        }
+    }
+    
+    /// Operations that only inspect the system: safe during a dry run and the
+    /// only ones allowed in read-only mode
+    pub fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            ArchOperation::SearchPackages { .. }
+                | ArchOperation::ListFlatpaks
+                | ArchOperation::CheckFlatpakUpdates
+                | ArchOperation::CheckDiskUsage { .. }
+                | ArchOperation::SecurityScan { .. }
+                | ArchOperation::VulnerabilityScan { .. }
+                | ArchOperation::AURSecurityCheck { .. }
+                | ArchOperation::ListServices { .. }
+                | ArchOperation::HealthCheck { .. }
+                | ArchOperation::PerformanceAnalysis { .. }
+                | ArchOperation::LogAnalysis { .. }
+                | ArchOperation::ValidateConfigs
+        )
+    }
+    
+    /// Operations that run a pacman transaction, which shutdown never cancels
+    /// since killing pacman mid-transaction can leave the system unbootable
+    pub fn is_package_transaction(&self) -> bool {
+        matches!(
+            self,
+            ArchOperation::UpdatePackages { .. }
+                | ArchOperation::InstallPackage { .. }
+                | ArchOperation::RemovePackage { .. }
+                | ArchOperation::ApplyStagedUpdates
+                | ArchOperation::RebuildAurPackages { .. }
+        )
+    }
+    
+    /// How the journal records this operation, as a change of which type to
+    /// what target; `None` for read-only operations
+    pub fn journal_action(&self) -> Option<(ActionType, String)> {
+        let packages = |packages: &Option<Vec<String>>| {
+            packages
+                .as_ref()
+                .map(|p| p.join(" "))
+                .unwrap_or_else(|| "system".to_string())
+        };
+        let action = match self {
+            ArchOperation::UpdatePackages { packages: p }
+            | ArchOperation::RebuildAurPackages { packages: p } => {
+                (ActionType::PackageTransaction, packages(p))
+            }
+            ArchOperation::InstallPackage { package, .. }
+            | ArchOperation::RemovePackage { package, .. } => {
+                (ActionType::PackageTransaction, package.clone())
+            }
+            ArchOperation::StageUpdates | ArchOperation::ApplyStagedUpdates => {
+                (ActionType::PackageTransaction, "system".to_string())
+            }
+            ArchOperation::UpdateFlatpaks { refs } => {
+                (ActionType::PackageTransaction, packages(refs))
+            }
+            ArchOperation::ServiceOperation { service, .. } => {
+                (ActionType::ServiceOperation, service.clone())
+            }
+            ArchOperation::UpdateMirrorlist { .. } => (
+                ActionType::FileWrite,
+                "/etc/pacman.d/mirrorlist".to_string(),
+            ),
+            ArchOperation::BackupConfigs { destination } => {
+                (ActionType::FileWrite, destination.clone())
+            }
+            ArchOperation::RestoreConfigs { source } => (ActionType::FileWrite, source.clone()),
+            ArchOperation::SystemCleanup { .. } => (ActionType::Command, "system".to_string()),
+            ArchOperation::CustomCommand { command, args } => (
+                ActionType::Command,
+                std::iter::once(command)
+                    .chain(args)
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+            _ => return None,
+        };
+        Some(action)
+    }
+    
+    /// What the operation needs root for; empty if it runs as anyone.
+    /// AUR installs and rebuilds are left out because the AUR helper elevates
+    /// itself.
+    pub fn privileges(&self) -> Vec<Capability> {
+        match self {
+            ArchOperation::UpdatePackages { .. }
+            | ArchOperation::InstallPackage { from_aur: false, .. }
+            | ArchOperation::RemovePackage { .. }
+            | ArchOperation::StageUpdates
+            | ArchOperation::ApplyStagedUpdates
+            | ArchOperation::UpdateMirrorlist { .. } => vec![Capability::Packages],
+            ArchOperation::SystemCleanup { clean_cache, clean_logs } => {
+                let mut capabilities = Vec::new();
+                if *clean_cache {
+                    capabilities.push(Capability::Packages);
+                }
+                if *clean_logs {
+                    capabilities.push(Capability::Journal);
+                }
+                capabilities
+            }
+            ArchOperation::ServiceOperation { .. } => vec![Capability::Services],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Write `operation` to the journal if it changes the system, with how it got
+/// root if it needed it, and tell the other agents on the event bus once it
+/// has really run
+fn record_action(
+    operation: &ArchOperation,
+    success: bool,
+    dry_run: bool,
+    privilege: Option<&Elevation>,
+) {
+    let Some((operation_type, target)) = operation.journal_action() else {
+        return;
+    };
+    let result = if success {
+        AuditStatus::Success
+    } else {
+        AuditStatus::Error
+    };
+    if !dry_run {
+        bus::publish(BusEvent::OperationCompleted {
+            operation: operation_type.to_string(),
+            target: target.clone(),
+            success,
+        });
+    }
+    let mut record = ActionRecord::new(
+        Uuid::new_v4().to_string(),
+        operation_type,
+        operation.name(),
+        target,
+    )
+    .with_result(result)
+    .with_dry_run(dry_run);
+    if let Some(privilege) = privilege {
+        record = record.with_privilege(privilege.clone());
+    }
+    journal::emit(&record);
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OperationResult {
     pub operation: ArchOperation,
     pub success: bool,
-    pub output: serde_json::Value,
+    pub output: OperationOutput,
     pub error: Option<String>,
     pub duration_ms: u64,
     pub executed_at: chrono::DateTime<chrono::Utc>,
     pub metadata: HashMap<String, serde_json::Value>,
 }
 
+impl OperationResult {
+    /// The operation ran and its report says the work succeeded too, e.g.
+    /// every cleanup step exited zero
+    pub fn succeeded(&self) -> bool {
+        self.success && self.output.succeeded()
+    }
+    
+    /// Why it did not succeed: the report's error, else the result's
+    pub fn failure(&self) -> Option<String> {
+        if self.succeeded() {
+            return None;
+        }
+        Some(
+            self.output
+                .error()
+                .map(str::to_string)
+                .or_else(|| self.error.clone())
+                .unwrap_or_else(|| "Operation failed".to_string()),
+        )
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentHealth {
     pub status: HealthStatus,
@@ -108,9 +423,21 @@ pub struct AgentHealth {
     pub memory_usage_percent: f64,
     pub disk_usage_percent: f64,
     pub active_operations: u32,
+    /// Discrete GPUs with telemetry; empty when there are none
+    #[serde(default)]
+    pub gpus: Vec<jarvis_core::gpu::GpuReading>,
+    /// Temperatures, fans, and batteries from sysfs
+    #[serde(default)]
+    pub sensors: jarvis_core::sensors::SensorSnapshot,
+    /// `[[health_checks]]` script results from the daemon's last health check
+    #[serde(default)]
+    pub custom_checks: Vec<jarvis_core::health_checks::CheckOutcome>,
+    /// AUR packages that need a library the last update removed
+    #[serde(default)]
+    pub soname_rebuilds: Vec<RebuildNeeded>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum AgentCapability {
     PackageManagement,
     AURSupport,
@@ -123,6 +450,46 @@ pub enum AgentCapability {
     PerformanceAnalysis,
     AutomatedMaintenance,
     WazuhIntegration,
+    /// Maintenance history and staged updates survive restarts
+    DatabasePersistence,
+    MaintenanceScheduling,
+}
+
+impl AgentCapability {
+    /// Offered whichever subsystems came up
+    pub const BASE: [AgentCapability; 2] = [
+        AgentCapability::ConfigurationManagement,
+        AgentCapability::LogAnalysis,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AgentCapability::PackageManagement => "package_management",
+            AgentCapability::AURSupport => "aur_support",
+            AgentCapability::SecurityScanning => "security_scanning",
+            AgentCapability::VulnerabilityAssessment => "vulnerability_assessment",
+            AgentCapability::SystemMonitoring => "system_monitoring",
+            AgentCapability::ServiceManagement => "service_management",
+            AgentCapability::ConfigurationManagement => "configuration_management",
+            AgentCapability::LogAnalysis => "log_analysis",
+            AgentCapability::PerformanceAnalysis => "performance_analysis",
+            AgentCapability::AutomatedMaintenance => "automated_maintenance",
+            AgentCapability::WazuhIntegration => "wazuh_integration",
+            AgentCapability::DatabasePersistence => "database_persistence",
+            AgentCapability::MaintenanceScheduling => "maintenance_scheduling",
+        }
+    }
+
+    /// What an agent whose `running` subsystems are up can do
+    pub fn for_subsystems(running: &[Subsystem]) -> Vec<AgentCapability> {
+        let mut caps = Self::BASE.to_vec();
+        for subsystem in Subsystem::ALL {
+            if running.contains(&subsystem) {
+                caps.extend_from_slice(subsystem.capabilities());
+            }
+        }
+        caps
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -135,12 +502,18 @@ pub struct AgentStatus {
     pub last_maintenance: Option<chrono::DateTime<chrono::Utc>>,
     pub next_scheduled_maintenance: Option<chrono::DateTime<chrono::Utc>>,
     pub statistics: AgentStatistics,
+    pub subsystems: Vec<SubsystemStatus>,
+    /// Operations that change the system are refused
+    #[serde(default)]
+    pub read_only: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum AgentState {
     Initializing,
     Ready,
+    /// Running with one or more subsystems unavailable
+    Degraded,
     Busy,
     Maintenance,
     Error,
@@ -164,6 +537,7 @@ pub struct ArchLinuxAgent {
     config: Option<Config>,
     package_manager: Option<PackageManager>,
     aur_monitor: Option<AURMonitor>,
+    aur_sandbox: Option<AurSandbox>,
     system_health: Option<SystemHealth>,
     security_scanner: Option<SecurityScanner>,
     maintenance_scheduler: Option<MaintenanceScheduler>,
@@ -171,8 +545,13 @@ pub struct ArchLinuxAgent {
     service_manager: Option<ServiceManager>,
     wazuh_integration: Option<WazuhIntegration>,
     database: Option<ZQLiteDatabase>,
+    notifier: Notifier,
+    privilege: Privilege,
+    subsystems: subsystem::Subsystems,
+    operations: Arc<OperationRegistry>,
+    custom_checks: LatestOutcomes,
     agent_id: Uuid,
-    statistics: AgentStatistics,
+    statistics: std::sync::Mutex<AgentStatistics>,
     state: AgentState,
     start_time: chrono::DateTime<chrono::Utc>,
 }
@@ -183,6 +562,7 @@ impl ArchLinuxAgent {
             config: None,
             package_manager: None,
             aur_monitor: None,
+            aur_sandbox: None,
             system_health: None,
             security_scanner: None,
             maintenance_scheduler: None,
@@ -190,13 +570,36 @@ impl ArchLinuxAgent {
             service_manager: None,
             wazuh_integration: None,
             database: None,
+            notifier: Notifier::disabled(),
+            privilege: Privilege::detect(&PrivilegeConfig::default()),
+            subsystems: subsystem::Subsystems::default(),
+            operations: Arc::new(OperationRegistry::default()),
+            custom_checks: LatestOutcomes::default(),
             agent_id: Uuid::new_v4(),
-            statistics: AgentStatistics::default(),
+            statistics: std::sync::Mutex::new(AgentStatistics::default()),
             state: AgentState::Initializing,
             start_time: chrono::Utc::now(),
         }
     }
     
+    /// Report custom health check results that the owner keeps up to date,
+    /// rather than running the scripts on every `/health` request
+    pub fn with_custom_checks(mut self, custom_checks: LatestOutcomes) -> Self {
+        self.custom_checks = custom_checks;
+        self
+    }
+    
+    /// How operations that need root get it on this host
+    pub fn privilege(&self) -> &Privilege {
+        &self.privilege
+    }
+    
+    /// Runs commands locally, elevating those that need root
+    fn elevating_runner(&self) -> Arc<dyn CommandRunner> {
+        let privilege = self.privilege.clone();
+        Arc::new(ElevatingRunner::new(Arc::new(SystemRunner::default()), privilege))
+    }
+    
     /// Get package manager instance
     pub fn package_manager(&self) -> Option<&PackageManager> {
         self.package_manager.as_ref()
@@ -241,73 +644,66 @@ impl ArchLinuxAgent {
     pub fn database(&self) -> Option<&ZQLiteDatabase> {
         self.database.as_ref()
     }
+    
+    /// Get notifier for agent events
+    pub fn notifier(&self) -> &Notifier {
+        &self.notifier
+    }
+    
+    /// Operations in flight, which the service drains on shutdown
+    pub fn operations(&self) -> Arc<OperationRegistry> {
+        self.operations.clone()
+    }
+    
+    /// Configuration the agent was initialized with
+    pub fn config(&self) -> Option<&Config> {
+        self.config.as_ref()
+    }
+    
+    /// Configuration backup settings, or the defaults before initialization
+    pub fn config_backup(&self) -> config::ConfigBackupConfig {
+        self.config
+            .as_ref()
+            .map(|c| c.agent.config_backup.clone())
+            .unwrap_or_default()
+    }
 }
 
 #[async_trait]
 impl ArchAgent for ArchLinuxAgent {
     async fn initialize(&mut self, config: Config) -> Result<()> {
         self.state = AgentState::Initializing;
+        self.privilege = Privilege::detect(&config.agent.privilege);
+        tracing::info!(
+            "Privileged operations use {} ({})",
+            self.privilege.strategy(),
+            self.privilege.reason()
+        );
+        // Only ever turns the mode on, so an embedding process's --read-only stands
+        if config.agent.read_only {
+            jarvis_core::read_only::set_read_only(true);
+        }
         
-        // Initialize ZQLite database first
-        let mut database = ZQLiteDatabase::new();
-        database.initialize(&config.database).await?;
-        self.database = Some(database);
-        
-        // Initialize package manager
-        let mut package_manager = PackageManager::new();
-        package_manager.initialize(&config.agent.pacman).await?;
-        self.package_manager = Some(package_manager);
-        
-        // Initialize AUR monitor if enabled
-        if config.agent.aur.enabled {
-            let mut aur_monitor = AURMonitor::new();
-            aur_monitor.initialize(&config.agent.aur).await?;
-            self.aur_monitor = Some(aur_monitor);
-        }
-        
-        // Initialize system health monitor
-        let mut system_health = SystemHealth::new();
-        system_health.initialize(&config.agent.system).await?;
-        self.system_health = Some(system_health);
-        
-        // Initialize security scanner
-        let mut security_scanner = SecurityScanner::new();
-        security_scanner.initialize(&config.agent.security).await?;
-        self.security_scanner = Some(security_scanner);
-        
-        // Initialize maintenance scheduler
-        let mut maintenance_scheduler = MaintenanceScheduler::new();
-        maintenance_scheduler.initialize(&config.agent.maintenance).await?;
-        self.maintenance_scheduler = Some(maintenance_scheduler);
-        
-        // Initialize vulnerability scanner
-        let mut vulnerability_scanner = VulnerabilityScanner::new();
-        vulnerability_scanner.initialize(&config.agent.vulnerability).await?;
-        self.vulnerability_scanner = Some(vulnerability_scanner);
-        
-        // Initialize service manager
-        let mut service_manager = ServiceManager::new();
-        service_manager.initialize(&config.agent.services).await?;
-        self.service_manager = Some(service_manager);
-        
-        // Initialize Wazuh integration if enabled
-        if config.wazuh.enabled {
-            if let Some(ref package_manager) = self.package_manager {
-                let wazuh_integration = WazuhIntegration::new(
-                    config.wazuh.clone(),
-                    package_manager.clone(),
-                );
-                wazuh_integration.initialize().await?;
-                self.wazuh_integration = Some(wazuh_integration);
-                
-                tracing::info!("Wazuh integration initialized for AUR package monitoring");
-            }
+        // A failing subsystem is recorded and skipped rather than aborting startup
+        for subsystem in Subsystem::ALL {
+            self.start_subsystem(subsystem, &config).await;
         }
         
+        self.notifier = Notifier::from_config(&config.notifications.notifier_config());
+        self.load_statistics().await;
+        
         self.config = Some(config);
-        self.state = AgentState::Ready;
+        self.update_state();
         
-        tracing::info!("Arch Linux agent initialized successfully with Wazuh integration");
+        let failed = self.subsystems.failed();
+        if failed.is_empty() {
+            tracing::info!("Arch Linux agent initialized successfully");
+        } else {
+            tracing::warn!(
+                "Arch Linux agent initialized without: {}",
+                failed.iter().map(|s| s.name()).collect::<Vec<_>>().join(", ")
+            );
+        }
         Ok(())
     }
     
@@ -317,75 +713,538 @@ impl ArchAgent for ArchLinuxAgent {
             .signed_duration_since(self.start_time)
             .num_seconds() as u64;
         
-        let success_rate = if self.statistics.total_operations > 0 {
-            self.statistics.successful_operations as f64 / self.statistics.total_operations as f64
+        let statistics = self.statistics();
+        let success_rate = if statistics.total_operations > 0 {
+            statistics.successful_operations as f64 / statistics.total_operations as f64
         } else {
             1.0
         };
         
+        let disk_usage_percent = maintenance_guard::disk_usage(&SystemRunner::default(), "/")
+            .await
+            .map(|usage| usage.used_percent)
+            .unwrap_or(0.0);
+        
+        let gpus = jarvis_core::gpu::collect(&jarvis_core::CommandExecutor::Local)
+            .await
+            .unwrap_or_default();
+        let mut status = maintenance_guard::effective_health(self.determine_health_status(), disk_usage_percent);
+        // GPU warnings stay with the readings so a warm card under load doesn't
+        // hold back maintenance; a critical GPU makes the whole system critical
+        let gpu_thresholds = jarvis_core::gpu::GpuThresholds::default();
+        if gpus
+            .iter()
+            .any(|gpu| gpu.assess(&gpu_thresholds).0 == jarvis_core::HostHealth::Critical)
+        {
+            status = HealthStatus::Critical;
+        }
+        // Likewise for sensors; [agent.system] temp_threshold is the warning
+        // level for temperatures whose driver gives no crit value
+        let mut sensor_config = jarvis_core::sensors::SensorsConfig::default();
+        if let Some(config) = &self.config {
+            sensor_config.temperature_warning_c = config.agent.system.temp_threshold as f64;
+            sensor_config.temperature_critical_c = sensor_config
+                .temperature_critical_c
+                .max(sensor_config.temperature_warning_c + 10.0);
+        }
+        let sensors = jarvis_core::sensors::SensorSampler::default()
+            .sample(&sensor_config, chrono::Utc::now());
+        if sensors
+            .assess(&sensor_config)
+            .iter()
+            .any(|(_, health, _)| *health == jarvis_core::HostHealth::Critical)
+        {
+            status = HealthStatus::Critical;
+        }
+        let custom_checks = self.custom_checks.read().await.clone();
+        if custom_checks
+            .iter()
+            .any(|check| check.health() == jarvis_core::HostHealth::Critical)
+        {
+            status = HealthStatus::Critical;
+        }
+        // Packages waiting on a rebuild are a warning of their own: holding
+        // back maintenance would also hold back the rebuild that fixes them
+        let soname_rebuilds = match self.load_rebuilds().await {
+            Some(report) if !report.is_empty() => {
+                let rechecked = report.recheck(&soname_rebuild::LibraryPaths::system());
+                if rechecked.packages != report.packages {
+                    self.store_rebuilds(&rechecked).await;
+                }
+                rechecked.packages
+            }
+            _ => Vec::new(),
+        };
+        
         Ok(AgentHealth {
-            status: self.determine_health_status(),
+            status,
             last_check: chrono::Utc::now(),
             uptime_seconds: uptime,
-            error_count: self.statistics.failed_operations as u32,
+            error_count: statistics.failed_operations as u32,
             success_rate,
             system_load: system_info.load_average().one,
             memory_usage_percent: (system_info.used_memory() as f64 / system_info.total_memory() as f64) * 100.0,
-            disk_usage_percent: 0.0, // Would implement actual disk usage check
-            active_operations: 0, // Would track active operations
+            disk_usage_percent,
+            active_operations: self.operations.len() as u32,
+            gpus,
+            sensors,
+            custom_checks,
+            soname_rebuilds,
         })
     }
     
     fn capabilities(&self) -> Vec<AgentCapability> {
-        let mut caps = vec![
-            AgentCapability::PackageManagement,
-            AgentCapability::SystemMonitoring,
-            AgentCapability::ServiceManagement,
-            AgentCapability::ConfigurationManagement,
-            AgentCapability::LogAnalysis,
-            AgentCapability::PerformanceAnalysis,
-            AgentCapability::AutomatedMaintenance,
-        ];
+        let running: Vec<Subsystem> = Subsystem::ALL
+            .into_iter()
+            .filter(|s| self.has_instance(*s))
+            .collect();
+        AgentCapability::for_subsystems(&running)
+    }
+    
+    async fn execute_operation(&self, operation: ArchOperation) -> Result<OperationResult> {
+        self.run_registered(Uuid::new_v4(), operation).await
+    }
+    
+    async fn execute_operation_with_options(
+        &self,
+        operation: ArchOperation,
+        options: ExecOptions,
+    ) -> Result<OperationResult> {
+        if !options.dry_run {
+            return self.execute_operation(operation).await;
+        }
+        if operation.is_read_only() {
+            let mut result = self.execute_operation(operation).await?;
+            result.metadata.insert("dry_run".to_string(), serde_json::json!(true));
+            return Ok(result);
+        }
+        
+        let start_time = std::time::Instant::now();
+        let executed_at = chrono::Utc::now();
+        let mut metadata = HashMap::new();
+        metadata.insert("dry_run".to_string(), serde_json::json!(true));
+        
+        match self.preflight(&operation).await {
+            Ok(Some(report)) => {
+                metadata.insert("preflight".to_string(), serde_json::to_value(&report)?);
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Pre-flight check failed: {}", e),
+        }
+        
+        let report = dry_run::run(&operation, &SystemRunner::default()).await;
+        record_action(&operation, true, true, None);
+        
+        Ok(OperationResult {
+            operation,
+            success: true,
+            output: OperationOutput::Raw(serde_json::to_value(&report)?),
+            error: None,
+            duration_ms: start_time.elapsed().as_millis() as u64,
+            executed_at,
+            metadata,
+        })
+    }
+    
+    async fn execute_cancellable(
+        &self,
+        operation_id: Uuid,
+        operation: ArchOperation,
+    ) -> Result<OperationResult> {
+        self.run_registered(operation_id, operation).await
+    }
+    
+    fn cancel_operation(&self, operation_id: Uuid) -> bool {
+        self.operations.cancel(operation_id)
+    }
+    
+    async fn get_status(&self) -> Result<AgentStatus> {
+        let mut statistics = self.statistics();
+        if let Some(database) = &self.database
+            && let Ok(store) = findings::FindingStore::load(database).await
+        {
+            statistics.security_issues_resolved = store.resolved_total;
+        }
+
+        Ok(AgentStatus {
+            agent_id: self.agent_id,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            status: self.state.clone(),
+            capabilities: self.capabilities(),
+            active_operations: self.operations.names(),
+            last_maintenance: None, // Would track from scheduler
+            next_scheduled_maintenance: self.next_scheduled_run().map(|(_, at)| at),
+            statistics,
+            subsystems: self.subsystems.list(),
+            read_only: jarvis_core::read_only::is_read_only(),
+        })
+    }
+    
+    async fn open_findings(&self) -> Result<sarif::OpenFindings> {
+        let mut open = sarif::OpenFindings::default();
+        if let Some(database) = &self.database {
+            open.security = findings::FindingStore::load(database).await?.open;
+            if let Some(json) = database.get_config_value(sarif::AUR_FINDINGS_KEY).await? {
+                open.aur = serde_json::from_str(&json)?;
+            }
+            if let Some(json) = database.get_config_value(sarif::UNIT_DRIFT_KEY).await? {
+                open.unit_drift = serde_json::from_str(&json)?;
+            }
+        }
+        // Package and image advisories live in the main Jarvis store
+        match open_jarvis_memory().await {
+            Ok(memory) => {
+                let security = jarvis_core::report::gather_security(&memory).await;
+                open.vulnerabilities = security.findings;
+                open.vulnerabilities.extend(security.images);
+            }
+            Err(e) => tracing::warn!("Package advisories unavailable: {}", e),
+        }
+        Ok(open)
+    }
+    
+    async fn shutdown(&mut self) -> Result<()> {
+        self.state = AgentState::Shutdown;
+        self.operations.close();
         
-        if self.aur_monitor.is_some() {
-            caps.push(AgentCapability::AURSupport);
+        // Events Wazuh couldn't take yet go to disk for the next start
+        if let Some(wazuh) = &self.wazuh_integration {
+            match wazuh.flush_spool().await {
+                Ok(0) => {}
+                Ok(sent) => tracing::info!("Sent {} spooled Wazuh events", sent),
+                Err(e) => tracing::warn!("Wazuh spool not flushed: {}", e),
+            }
+        }
+        if let Err(e) = self.persist_statistics().await {
+            tracing::warn!("Agent statistics not saved: {}", e);
         }
         
-        if self.security_scanner.is_some() {
-            caps.push(AgentCapability::SecurityScanning);
+        // Shutdown all components
+        if let Some(scheduler) = &mut self.maintenance_scheduler {
+            scheduler.shutdown().await?;
         }
         
-        if self.vulnerability_scanner.is_some() {
-            caps.push(AgentCapability::VulnerabilityAssessment);
+        tracing::info!("Arch Linux agent shutdown completed");
+        Ok(())
+    }
+}
+
+/// Configuration key holding agent statistics across restarts
+const STATISTICS_KEY: &str = "agent_statistics";
+
+impl ArchLinuxAgent {
+    /// Run `operation` under `id`, where shutdown can see and cancel it.
+    /// Refused once shutdown has started, and in read-only mode unless it only
+    /// inspects the system.
+    async fn run_registered(&self, id: Uuid, operation: ArchOperation) -> Result<OperationResult> {
+        let name = operation.name();
+        if !operation.is_read_only() {
+            jarvis_core::read_only::check(&format!("run {}", name))?;
+        }
+        if self.operations.is_closed() {
+            return Err(jarvis_core::JarvisError::Cancelled(format!(
+                "{} not started: the agent is shutting down",
+                name
+            ))
+            .into());
         }
         
-        caps
+        let transaction = operation.is_package_transaction();
+        let run = self.run_operation(operation);
+        let result = if transaction {
+            self.operations.run_transaction(id, name, run).await
+        } else {
+            self.operations.run(id, name, run).await
+        }
+        .unwrap_or_else(|| {
+            Err(jarvis_core::JarvisError::Cancelled(format!("{} was cancelled", name)).into())
+        });
+        self.record_statistics(&result);
+        result
     }
     
-    async fn execute_operation(&self, operation: ArchOperation) -> Result<OperationResult> {
+    /// Statistics as of now
+    pub fn statistics(&self) -> AgentStatistics {
+        let mut statistics = self
+            .statistics
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone();
+        statistics.uptime_hours = chrono::Utc::now()
+            .signed_duration_since(self.start_time)
+            .num_seconds() as f64
+            / 3600.0;
+        statistics
+    }
+    
+    fn record_statistics(&self, result: &Result<OperationResult>) {
+        let mut statistics = self
+            .statistics
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let duration_ms = result.as_ref().map(|r| r.duration_ms).unwrap_or(0);
+        statistics.record(result.as_ref().is_ok_and(|r| r.success), duration_ms);
+    }
+    
+    /// Pick up the counts saved by the last shutdown
+    async fn load_statistics(&self) {
+        let Some(database) = &self.database else {
+            return;
+        };
+        match database.get_config_value(STATISTICS_KEY).await {
+            Ok(Some(json)) => match serde_json::from_str::<AgentStatistics>(&json) {
+                Ok(saved) => {
+                    *self
+                        .statistics
+                        .lock()
+                        .unwrap_or_else(|poisoned| poisoned.into_inner()) = saved
+                }
+                Err(e) => tracing::warn!("Ignoring unreadable saved statistics: {}", e),
+            },
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Saved statistics unavailable: {}", e),
+        }
+    }
+    
+    /// Save statistics so the next start carries on counting
+    pub async fn persist_statistics(&self) -> Result<()> {
+        let Some(database) = &self.database else {
+            return Ok(());
+        };
+        let json = serde_json::to_string(&self.statistics())?;
+        database
+            .set_config_value(STATISTICS_KEY, &json, "Agent operation statistics")
+            .await
+    }
+    
+    async fn run_operation(&self, operation: ArchOperation) -> Result<OperationResult> {
         let start_time = std::time::Instant::now();
         let executed_at = chrono::Utc::now();
+        let mut metadata = HashMap::new();
+        
+        for subsystem in Subsystem::required_by(&operation) {
+            self.subsystems.check(*subsystem)?;
+        }
+        
+        // Package changes never run without a pre-flight report on record
+        let preflight = match self.preflight(&operation).await {
+            Ok(report) => report,
+            Err(e) => {
+                tracing::warn!("Pre-flight check failed: {}", e);
+                None
+            }
+        };
+        if let Some(report) = &preflight {
+            metadata.insert("preflight".to_string(), serde_json::to_value(report)?);
+            if !report.held.is_empty() {
+                let error = format!("Packages on hold: {}", report.held.join(", "));
+                return Ok(OperationResult {
+                    operation,
+                    success: false,
+                    output: FailureReport {
+                        preflight: Some(report.clone()),
+                        ..FailureReport::new(error.clone())
+                    }
+                    .into(),
+                    error: Some(error),
+                    duration_ms: start_time.elapsed().as_millis() as u64,
+                    executed_at,
+                    metadata,
+                });
+            }
+        }
+        
+        // Operations that need root run elevated, or not at all
+        let mut elevation = match self.privilege.elevation(&operation.privileges()) {
+            Ok(elevation) => elevation,
+            Err(refused) => {
+                let elevation = refused.elevation();
+                metadata.insert("privilege".to_string(), serde_json::to_value(&elevation)?);
+                record_action(&operation, false, false, Some(&elevation));
+                return Ok(OperationResult {
+                    operation,
+                    success: false,
+                    output: FailureReport {
+                        not_permitted: true,
+                        ..FailureReport::new(refused.to_string())
+                    }
+                    .into(),
+                    error: Some(refused.to_string()),
+                    duration_ms: start_time.elapsed().as_millis() as u64,
+                    executed_at,
+                    metadata,
+                });
+            }
+        };
         
-        let result = match operation.clone() {
+        let result: Result<OperationOutput> = match operation.clone() {
             ArchOperation::UpdatePackages { packages } => {
                 if let Some(pm) = &self.package_manager {
-                    pm.update_packages(packages).await
+                    let snapshot_id =
+                        rollback::pre_snapshot(&SystemRunner::default(), "jarvis: before update_packages").await;
+                    let mut result = pm.update_packages(packages).await;
+                    if let Ok(report) = &mut result {
+                        self.record_update_transaction(executed_at, report, snapshot_id).await;
+                        if report.success {
+                            report.rebuilds_needed = self.detect_soname_rebuilds().await;
+                        }
+                    }
+                    result.map(Into::into)
                 } else {
                     Err(anyhow::anyhow!("Package manager not initialized"))
                 }
             }
             
+            ArchOperation::RemovePackage { package, remove_deps } => {
+                if let Some(pm) = &self.package_manager {
+                    pm.remove_package(&package, remove_deps).await.map(Into::into)
+                } else {
+                    Err(anyhow::anyhow!("Package manager not initialized"))
+                }
+            }
+            
+            ArchOperation::InstallPackage { package, from_aur } => {
+                if from_aur {
+                    self.install_from_aur(&package).await.map(Into::into)
+                } else if let Some(pm) = &self.package_manager {
+                    pm.install_package(&package, false).await.map(Into::into)
+                } else {
+                    Err(anyhow::anyhow!("Package manager not initialized"))
+                }
+            }
+            
+            ArchOperation::RebuildAurPackages { packages } => {
+                self.rebuild_aur_packages(packages).await.map(Into::into)
+            }
+            
+            ArchOperation::AURSecurityCheck { packages } => {
+                self.aur_security_check(packages).await.map(Into::into)
+            }
+            
+            ArchOperation::ListFlatpaks => {
+                let refs = jarvis_core::flatpak::list_installed().await?;
+                Ok(serde_json::json!({ "operation": "list_flatpaks", "installed": refs }).into())
+            }
+            
+            ArchOperation::CheckFlatpakUpdates => {
+                let updates = jarvis_core::flatpak::check_updates().await?;
+                Ok(serde_json::json!({ "operation": "check_flatpak_updates", "updates": updates }).into())
+            }
+            
+            ArchOperation::UpdateFlatpaks { refs } => {
+                jarvis_core::flatpak::update(&refs.unwrap_or_default()).await.map(Into::into)
+            }
+            
+            ArchOperation::VulnerabilityScan { packages: _ } => {
+                // Flatpak runtimes are outside pacman's view, so EOL ones are reported here
+                let flatpak_runtimes = if jarvis_core::flatpak::is_available().await {
+                    jarvis_core::flatpak::runtime_advisories().await?
+                } else {
+                    Vec::new()
+                };
+                Ok(ScanReport {
+                    flatpak_eol_runtimes: Some(flatpak_runtimes),
+                    ..ScanReport::new("vulnerability_scan")
+                }
+                .into())
+            }
+            
+            ArchOperation::ValidateConfigs => {
+                let units = jarvis_core::unit_drift::scan_enabled_services(
+                    &jarvis_core::CommandExecutor::Local,
+                ).await?;
+                let drifted: Vec<_> = units
+                    .iter()
+                    .filter(|u| u.drift != jarvis_core::unit_drift::DriftKind::Clean)
+                    .collect();
+                self.cache_findings(
+                    sarif::UNIT_DRIFT_KEY,
+                    &drifted,
+                    "Units drifted from their package as of the last config validation",
+                ).await;
+                let units_drifted = drifted.len();
+                Ok(ScanReport {
+                    units_checked: Some(units.len()),
+                    units_drifted: Some(units_drifted),
+                    unit_drift: Some(units),
+                    ..ScanReport::new("validate_configs")
+                }
+                .into())
+            }
+            
+            ArchOperation::StageUpdates => self.stage_updates().await.map(Into::into),
+            
+            ArchOperation::SystemCleanup { .. } => {
+                self.run_maintenance("system_cleanup", &operation).await.map(Into::into)
+            }
+            
+            ArchOperation::UpdateMirrorlist { .. } => {
+                self.run_maintenance("update_mirrorlist", &operation).await.map(Into::into)
+            }
+            
+            ArchOperation::ServiceOperation { service, operation: action } => {
+                self.run_planned_actions(&operation).await.map(|(success, steps)| {
+                    ServiceActionReport {
+                        operation: "service_operation".to_string(),
+                        service: Some(service),
+                        action: Some(action),
+                        success,
+                        steps,
+                    }
+                    .into()
+                })
+            }
+            
+            ArchOperation::ApplyStagedUpdates => {
+                self.apply_staged_updates(executed_at).await.map(Into::into)
+            }
+            
+            ArchOperation::BackupConfigs { destination } => {
+                self.backup_configs(&destination).await.map(Into::into)
+            }
+            
+            ArchOperation::RestoreConfigs { source } => {
+                self.restore_configs(&source).await.map(Into::into)
+            }
+            
             ArchOperation::SecurityScan { full_scan } => {
                 if let Some(scanner) = &self.security_scanner {
-                    scanner.scan_system(full_scan).await
+                    match scanner.scan_system(full_scan).await {
+                        Ok(scan) => {
+                            let mut report = ScanReport::from_scanner("security_scan", scan);
+                            if full_scan {
+                                self.add_image_scans(&mut report).await;
+                            }
+                            self.track_security_scan(report).await.map(Into::into)
+                        }
+                        Err(e) => Err(e),
+                    }
                 } else {
                     Err(anyhow::anyhow!("Security scanner not initialized"))
                 }
             }
             
+            ArchOperation::PerformanceAnalysis { duration_minutes } => {
+                self.analyze_performance(duration_minutes).await.map(Into::into)
+            }
+            
+            ArchOperation::LogAnalysis { service, since } => {
+                match jarvis_core::time_range::parse_range(&since) {
+                    Ok(range) => {
+                        log_analysis::analyze(&SystemRunner::default(), service.as_deref(), range)
+                            .await
+                            .map(Into::into)
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+            
             ArchOperation::HealthCheck { include_services } => {
                 if let Some(health) = &self.system_health {
-                    health.check_system_health(include_services).await
+                    health
+                        .check_system_health(include_services)
+                        .await
+                        .map(|report| HealthReport::from_monitor("health_check", report).into())
                 } else {
                     Err(anyhow::anyhow!("System health monitor not initialized"))
                 }
@@ -399,51 +1258,1121 @@ impl ArchAgent for ArchLinuxAgent {
         
         let duration = start_time.elapsed();
         let success = result.is_ok();
+        // Command output with bytes that weren't UTF-8 was decoded lossily
+        if let Ok(output) = &result
+            && output.output_lossy()
+        {
+            metadata.insert("output_lossy".to_string(), serde_json::json!(true));
+        }
+        // A command sudo or polkit refused never ran elevated
+        let refused = result
+            .as_ref()
+            .err()
+            .and_then(|e| e.chain().find_map(|cause| cause.downcast_ref::<NotPermitted>()))
+            .cloned();
+        if refused.is_some() {
+            elevation.elevated = false;
+        }
+        // A locked package database is reported with its age and holder
+        let db_lock = result
+            .as_ref()
+            .err()
+            .and_then(|e| e.chain().find_map(|cause| cause.downcast_ref::<LockHeld>()))
+            .cloned();
+        metadata.insert("privilege".to_string(), serde_json::to_value(&elevation)?);
+        record_action(&operation, success, false, Some(&elevation));
         
         Ok(OperationResult {
             operation,
             success,
             output: match result {
-                Ok(data) => data,
-                Err(e) => serde_json::json!({"error": e.to_string()}),
+                Ok(output) => output,
+                Err(e) => FailureReport {
+                    not_permitted: refused.is_some(),
+                    db_lock: db_lock.clone(),
+                    ..FailureReport::new(e.to_string())
+                }
+                .into(),
+            },
+            error: match (&refused, success) {
+                (Some(refused), _) => Some(refused.to_string()),
+                (None, false) if db_lock.is_some() => db_lock.as_ref().map(LockHeld::to_string),
+                (None, true) => None,
+                (None, false) => Some("Operation failed".to_string()),
             },
-            error: if success { None } else { Some("Operation failed".to_string()) },
             duration_ms: duration.as_millis() as u64,
             executed_at,
-            metadata: HashMap::new(),
+            metadata,
         })
     }
+}
+
+/// Configuration key holding the currently staged update set
+const STAGED_UPDATE_KEY: &str = "staged_update";
+
+/// Configuration key holding the last soname check's `RebuildReport`
+const SONAME_REBUILDS_KEY: &str = "soname_rebuilds";
+
+#[async_trait]
+impl maintenance_chain::StepRunner for ArchLinuxAgent {
+    async fn run_step(
+        &self,
+        task: &maintenance_chain::StepTask,
+        context: &maintenance_chain::ChainContext,
+    ) -> Result<serde_json::Value> {
+        use maintenance_chain::StepTask;
+        
+        match task {
+            StepTask::Operation(operation) => {
+                // Package changes reuse the run's snapshot instead of taking their own
+                let result = match context.snapshot_id {
+                    Some(snapshot) => {
+                        rollback::with_snapshot(snapshot, self.execute_operation(operation.clone())).await?
+                    }
+                    None => self.execute_operation(operation.clone()).await?,
+                };
+                if let Some(reason) = result.failure() {
+                    return Err(anyhow::anyhow!("{} failed: {}", operation.name(), reason));
+                }
+                Ok(serde_json::to_value(&result.output)?)
+            }
+            StepTask::Snapshot => {
+                let snapshot_id =
+                    rollback::create_pre_snapshot(&SystemRunner::default(), "jarvis: before composite maintenance").await;
+                Ok(serde_json::json!({ "snapshot_id": snapshot_id }))
+            }
+            StepTask::RestartServices => {
+                let restarted =
+                    maintenance_chain::restart_services(&SystemRunner::default(), &context.upgraded_packages).await?;
+                Ok(serde_json::json!({ "restarted": restarted }))
+            }
+        }
+    }
+}
+
+impl ArchLinuxAgent {
+    /// Initialization state of every subsystem
+    pub fn subsystems(&self) -> Vec<SubsystemStatus> {
+        self.subsystems.list()
+    }
     
-    async fn get_status(&self) -> Result<AgentStatus> {
-        Ok(AgentStatus {
-            agent_id: self.agent_id,
-            version: env!("CARGO_PKG_VERSION").to_string(),
-            status: self.state.clone(),
-            capabilities: self.capabilities(),
-            active_operations: vec![], // Would track active operations
-            last_maintenance: None, // Would track from scheduler
-            next_scheduled_maintenance: None, // Would get from scheduler
-            statistics: self.statistics.clone(),
-        })
+    /// Re-initialize one subsystem, e.g. after fixing what made it fail.
+    /// Uses the configuration from `initialize` unless `set_config` replaced it.
+    pub async fn retry_subsystem(&mut self, name: &str) -> Result<SubsystemStatus> {
+        let subsystem = Subsystem::from_name(name).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Unknown subsystem '{}'; expected one of: {}",
+                name,
+                Subsystem::ALL.map(|s| s.name()).join(", ")
+            )
+        })?;
+        let config = self
+            .config
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Agent not initialized"))?;
+        
+        self.start_subsystem(subsystem, &config).await;
+        self.update_state();
+        
+        let status = self.subsystems.status(subsystem);
+        match &status.last_error {
+            Some(error) => Err(anyhow::anyhow!("{} failed to initialize: {}", subsystem, error)),
+            None => Ok(status),
+        }
     }
     
-    async fn shutdown(&mut self) -> Result<()> {
-        self.state = AgentState::Shutdown;
+    /// Replace the configuration subsequent `retry_subsystem` calls use
+    pub fn set_config(&mut self, config: Config) {
+        self.config = Some(config);
+    }
+    
+    /// Initialize `subsystem` if it is enabled and record how that went
+    async fn start_subsystem(&mut self, subsystem: Subsystem, config: &Config) {
+        self.clear_subsystem(subsystem);
         
-        // Shutdown all components
-        if let Some(scheduler) = &mut self.maintenance_scheduler {
-            scheduler.shutdown().await?;
+        let enabled = match subsystem {
+            Subsystem::Aur => config.agent.aur.enabled,
+            Subsystem::Wazuh => config.wazuh.enabled,
+            _ => true,
+        };
+        if !enabled {
+            self.subsystems.record_disabled(subsystem);
+            return;
         }
         
-        tracing::info!("Arch Linux agent shutdown completed");
+        match self.init_subsystem(subsystem, config).await {
+            Ok(()) => self.subsystems.record_ready(subsystem),
+            Err(e) => {
+                tracing::warn!("{} failed to initialize: {:#}", subsystem, e);
+                self.subsystems.record_failed(subsystem, format!("{:#}", e));
+            }
+        }
+    }
+    
+    async fn init_subsystem(&mut self, subsystem: Subsystem, config: &Config) -> Result<()> {
+        match subsystem {
+            Subsystem::Database => {
+                let mut database = ZQLiteDatabase::new();
+                database.initialize(&config.database).await?;
+                self.database = Some(database);
+            }
+            Subsystem::PackageManager => {
+                let mut package_manager = PackageManager::new().with_runner(self.elevating_runner());
+                package_manager.initialize(&config.agent.pacman).await?;
+                self.package_manager = Some(package_manager);
+            }
+            Subsystem::Aur => {
+                let mut aur_monitor = AURMonitor::new();
+                aur_monitor.initialize(&config.agent.aur).await?;
+                self.aur_monitor = Some(aur_monitor);
+                self.aur_sandbox = Some(AurSandbox::new(&config.agent.aur));
+            }
+            Subsystem::SystemHealth => {
+                let mut system_health = SystemHealth::new();
+                system_health.initialize(&config.agent.system).await?;
+                self.system_health = Some(system_health);
+            }
+            Subsystem::SecurityScanner => {
+                let mut security_scanner = SecurityScanner::new();
+                security_scanner.initialize(&config.agent.security).await?;
+                self.security_scanner = Some(security_scanner);
+            }
+            Subsystem::MaintenanceScheduler => {
+                let mut maintenance_scheduler = MaintenanceScheduler::new();
+                maintenance_scheduler.initialize(&config.agent.maintenance).await?;
+                self.maintenance_scheduler = Some(maintenance_scheduler);
+            }
+            Subsystem::VulnerabilityScanner => {
+                let mut vulnerability_scanner = VulnerabilityScanner::new();
+                vulnerability_scanner.initialize(&config.agent.vulnerability).await?;
+                self.vulnerability_scanner = Some(vulnerability_scanner);
+            }
+            Subsystem::ServiceManager => {
+                let mut service_manager = ServiceManager::new();
+                service_manager.initialize(&config.agent.services).await?;
+                self.service_manager = Some(service_manager);
+            }
+            Subsystem::Wazuh => {
+                // Wazuh checks AUR packages through the package manager
+                self.subsystems.check(Subsystem::PackageManager)?;
+                let package_manager = self
+                    .package_manager
+                    .clone()
+                    .ok_or_else(|| anyhow::anyhow!("Package manager not initialized"))?;
+                let mut wazuh_integration = WazuhIntegration::new(config.wazuh.clone(), package_manager);
+                match open_jarvis_memory().await {
+                    Ok(memory) => wazuh_integration = wazuh_integration.with_memory(memory),
+                    Err(e) => tracing::warn!("Vulnerability acknowledgements unavailable: {}", e),
+                }
+                wazuh_integration.initialize().await?;
+                self.wazuh_integration = Some(wazuh_integration);
+                
+                tracing::info!("Wazuh integration initialized for AUR package monitoring");
+            }
+        }
         Ok(())
     }
-}
+    
+    /// Whether `subsystem` has a live instance
+    fn has_instance(&self, subsystem: Subsystem) -> bool {
+        match subsystem {
+            Subsystem::Database => self.database.is_some(),
+            Subsystem::PackageManager => self.package_manager.is_some(),
+            Subsystem::Aur => self.aur_monitor.is_some(),
+            Subsystem::SystemHealth => self.system_health.is_some(),
+            Subsystem::SecurityScanner => self.security_scanner.is_some(),
+            Subsystem::MaintenanceScheduler => self.maintenance_scheduler.is_some(),
+            Subsystem::VulnerabilityScanner => self.vulnerability_scanner.is_some(),
+            Subsystem::ServiceManager => self.service_manager.is_some(),
+            Subsystem::Wazuh => self.wazuh_integration.is_some(),
+        }
+    }
+    
+    /// Drop a subsystem's instance so a failed retry can't leave a stale one behind
+    fn clear_subsystem(&mut self, subsystem: Subsystem) {
+        match subsystem {
+            Subsystem::Database => self.database = None,
+            Subsystem::PackageManager => self.package_manager = None,
+            Subsystem::Aur => {
+                self.aur_monitor = None;
+                self.aur_sandbox = None;
+            }
+            Subsystem::SystemHealth => self.system_health = None,
+            Subsystem::SecurityScanner => self.security_scanner = None,
+            Subsystem::MaintenanceScheduler => self.maintenance_scheduler = None,
+            Subsystem::VulnerabilityScanner => self.vulnerability_scanner = None,
+            Subsystem::ServiceManager => self.service_manager = None,
+            Subsystem::Wazuh => self.wazuh_integration = None,
+        }
+    }
+    
+    fn update_state(&mut self) {
+        self.state = if self.subsystems.failed().is_empty() {
+            AgentState::Ready
+        } else {
+            AgentState::Degraded
+        };
+    }
+    
+    /// What a package operation would change, for operations that change packages
+    pub async fn preflight(&self, operation: &ArchOperation) -> Result<Option<PreflightReport>> {
+        let (action, targets) = match operation {
+            ArchOperation::InstallPackage { package, .. } => (PackageAction::Install, vec![package.clone()]),
+            ArchOperation::RemovePackage { package, .. } => (PackageAction::Remove, vec![package.clone()]),
+            ArchOperation::UpdatePackages { packages: Some(packages) } => (PackageAction::Install, packages.clone()),
+            ArchOperation::UpdatePackages { packages: None } | ArchOperation::ApplyStagedUpdates => {
+                (PackageAction::Update, Vec::new())
+            }
+            _ => return Ok(None),
+        };
+
+        let holds = self
+            .config
+            .as_ref()
+            .map(|c| c.agent.pacman.hold_packages.clone())
+            .unwrap_or_default();
+
+        Ok(Some(jarvis_core::preflight::preflight(action, &targets, &holds).await?))
+    }
+
+    /// Next maintenance task according to the service schedule
+    pub fn next_scheduled_run(&self) -> Option<(config::ScheduledTask, chrono::DateTime<chrono::Utc>)> {
+        self.config
+            .as_ref()?
+            .service
+            .maintenance_schedule
+            .next_run(chrono::Utc::now())
+    }
+
+    /// Why scheduled `task` must not run now, or `None` when it may. Checks the
+    /// task's health requirement and, for tasks that download packages,
+    /// whether the pending downloads fit in the package cache.
+    pub async fn maintenance_blocker(&self, task: config::ScheduledTask) -> Result<Option<String>> {
+        let health = self.health_check().await?;
+        if let Some(reason) = maintenance_guard::health_blocker(task, &health.status, health.disk_usage_percent) {
+            return Ok(Some(reason));
+        }
+        
+        if task.downloads_packages() {
+            if let Some(pm) = &self.package_manager {
+                let required = pm.pending_download_bytes().await?;
+                let cache = maintenance_guard::disk_usage(
+                    &SystemRunner::default(),
+                    maintenance_guard::PACKAGE_CACHE_DIR,
+                )
+                .await?;
+                return Ok(maintenance_guard::space_blocker(
+                    required,
+                    cache.available_bytes,
+                    maintenance_guard::PACKAGE_CACHE_DIR,
+                ));
+            }
+        }
+        
+        Ok(None)
+    }
+    
+    /// Record a scheduled task or preset that was skipped, with the reason,
+    /// in the maintenance history
+    pub async fn record_skipped_task(&self, name: &str, reason: &str) -> Result<()> {
+        let Some(database) = &self.database else {
+            return Ok(());
+        };
+        let now = chrono::Utc::now();
+        let record = zqlite_integration::MaintenanceRecord {
+            id: Uuid::new_v4(),
+            operation_type: name.to_string(),
+            status: zqlite_integration::MaintenanceStatus::Skipped,
+            started_at: now,
+            completed_at: Some(now),
+            duration_ms: Some(0),
+            packages_affected: Vec::new(),
+            output: String::new(),
+            error_message: Some(reason.to_string()),
+            delta: None,
+            snapshot_id: None,
+            rollback_of: None,
+        };
+        database.record_maintenance(&record).await
+    }
+    
+    /// Run a composite maintenance preset, record it as one operation with
+    /// its steps in the output, and notify about failed steps
+    pub async fn run_preset(&self, preset: maintenance_chain::ChainPreset) -> maintenance_chain::ChainResult {
+        let result = maintenance_chain::run(preset, self).await;
+        
+        for step in result.failed_steps() {
+            let severity = match step.on_failure {
+                maintenance_chain::OnFailure::NotifyOnly => NotifySeverity::Info,
+                _ => NotifySeverity::Warning,
+            };
+            self.notifier.notify(Notification::new(
+                NotifyEvent::MaintenanceFailed,
+                severity,
+                format!("{}: {} failed", preset.name(), step.name),
+                step.error.clone().unwrap_or_default(),
+            ));
+        }
+        
+        if let Some(database) = &self.database {
+            let failed: Vec<&str> = result.failed_steps().map(|s| s.name.as_str()).collect();
+            let record = zqlite_integration::MaintenanceRecord {
+                id: result.id,
+                operation_type: format!("preset:{}", preset.name()),
+                status: if result.success {
+                    zqlite_integration::MaintenanceStatus::Completed
+                } else {
+                    zqlite_integration::MaintenanceStatus::Failed
+                },
+                started_at: result.started_at,
+                completed_at: Some(result.finished_at),
+                duration_ms: Some((result.finished_at - result.started_at).num_milliseconds().max(0) as u64),
+                packages_affected: result.context.upgraded_packages.clone(),
+                output: serde_json::to_string(&result.steps).unwrap_or_default(),
+                error_message: (!failed.is_empty()).then(|| format!("Failed steps: {}", failed.join(", "))),
+                delta: None,
+                snapshot_id: result.context.snapshot_id,
+                rollback_of: None,
+            };
+            if let Err(e) = database.record_maintenance(&record).await {
+                tracing::warn!("Failed to record {} run: {}", preset.name(), e);
+            }
+        }
+        
+        result
+    }
+    
+    /// Work out how to undo a recorded operation. `target` is an operation id,
+    /// or `last` for the most recent operation that changed packages
+    pub async fn plan_rollback(&self, target: &str) -> Result<RollbackPlan> {
+        let database = self
+            .database
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Rollback needs the maintenance history database"))?;
+
+        let record = if target == "last" {
+            database
+                .get_maintenance_history(50)
+                .await?
+                .into_iter()
+                .find(|r| r.delta.is_some() || r.snapshot_id.is_some())
+                .ok_or_else(|| anyhow::anyhow!("No recorded operation changed packages"))?
+        } else {
+            let id: Uuid = target
+                .parse()
+                .map_err(|_| anyhow::anyhow!("'{}' is neither an operation id nor `last`", target))?;
+            database
+                .get_maintenance_record(id)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("No operation {} in the maintenance history", id))?
+        };
+
+        Ok(rollback::plan(
+            &record,
+            &SystemRunner::default(),
+            std::path::Path::new(maintenance_guard::PACKAGE_CACHE_DIR),
+        )
+        .await)
+    }
+
+    /// Carry out every action of `plan`, stopping at the first failure, and
+    /// record the rollback as an operation linked to the one it undoes
+    pub async fn execute_rollback(&self, plan: &RollbackPlan) -> Result<zqlite_integration::MaintenanceRecord> {
+        let runner = SystemRunner::default();
+        let started_at = chrono::Utc::now();
+        let before = match &self.package_manager {
+            Some(pm) => Some(pm.snapshot_installed().await?),
+            None => None,
+        };
+        let snapshot_id = rollback::create_pre_snapshot(
+            &runner,
+            &format!("jarvis: before rolling back {}", plan.operation_id),
+        )
+        .await;
+
+        let mut output = Vec::new();
+        let mut error_message = None;
+        for action in &plan.actions {
+            match rollback::execute(action, &runner).await {
+                Ok(out) => output.push(format!("{}\n{}", action.command(), out)),
+                Err(e) => {
+                    error_message = Some(e.to_string());
+                    break;
+                }
+            }
+        }
+
+        let delta = match (&self.package_manager, &before) {
+            (Some(pm), Some(before)) => {
+                let after = pm.snapshot_installed().await?;
+                Some(serde_json::to_value(package_manager::compute_transaction_delta(before, &after))?)
+            }
+            _ => None,
+        };
+        let packages_affected = plan
+            .actions
+            .iter()
+            .flat_map(|action| match action {
+                RollbackAction::Downgrade { packages } => packages.iter().map(|p| p.name.clone()).collect(),
+                RollbackAction::RestoreSnapshot { .. } => Vec::new(),
+            })
+            .collect();
+
+        let record = zqlite_integration::MaintenanceRecord {
+            id: Uuid::new_v4(),
+            operation_type: "rollback".to_string(),
+            status: if error_message.is_none() {
+                zqlite_integration::MaintenanceStatus::Completed
+            } else {
+                zqlite_integration::MaintenanceStatus::Failed
+            },
+            started_at,
+            completed_at: Some(chrono::Utc::now()),
+            duration_ms: Some((chrono::Utc::now() - started_at).num_milliseconds().max(0) as u64),
+            packages_affected,
+            output: output.join("\n\n"),
+            error_message,
+            delta,
+            snapshot_id,
+            rollback_of: Some(plan.operation_id),
+        };
+        if let Some(database) = &self.database
+            && let Err(e) = database.record_maintenance(&record).await
+        {
+            tracing::warn!("Failed to record rollback: {}", e);
+        }
+
+        Ok(record)
+    }
+    
+    /// Run the commands `dry_run::plan` lists for `operation`, so a real run does
+    /// exactly what its dry run showed. Stops at the first failing command;
+    /// `false` with the steps run when one failed.
+    async fn run_planned_actions(&self, operation: &ArchOperation) -> Result<(bool, Vec<CommandStep>)> {
+        let runner = self.elevating_runner();
+        let mut steps = Vec::new();
+        let mut success = true;
+        
+        for command in dry_run::plan(operation).actions {
+            let args: Vec<&str> = command.args.iter().map(String::as_str).collect();
+            let output = runner.output(&command.program, &args).await?;
+            let text = OutputText::decode(&output);
+            success = output.status.success();
+            steps.push(CommandStep {
+                command: command.to_string(),
+                success,
+                output: text.stdout.trim().to_string(),
+                error: text.stderr.trim().to_string(),
+                output_lossy: text.lossy,
+            });
+            if !success {
+                break;
+            }
+        }
+        
+        Ok((success, steps))
+    }
+    
+    async fn run_maintenance(&self, name: &str, operation: &ArchOperation) -> Result<CleanupReport> {
+        let (success, steps) = self.run_planned_actions(operation).await?;
+        Ok(CleanupReport {
+            operation: name.to_string(),
+            success,
+            steps,
+        })
+    }
+    
+    /// Download updates without installing them and remember what was staged
+    async fn stage_updates(&self) -> Result<PackageUpdateReport> {
+        let pm = self
+            .package_manager
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Package manager not initialized"))?;
+        let started_at = chrono::Utc::now();
+        let staged = pm.stage_updates().await?;
+
+        if let Some(database) = &self.database {
+            database
+                .set_config_value(
+                    STAGED_UPDATE_KEY,
+                    &serde_json::to_string(&staged)?,
+                    "Updates downloaded but not yet installed",
+                )
+                .await?;
+
+            let record = zqlite_integration::MaintenanceRecord {
+                id: Uuid::new_v4(),
+                operation_type: "stage_updates".to_string(),
+                status: zqlite_integration::MaintenanceStatus::Completed,
+                started_at,
+                completed_at: Some(chrono::Utc::now()),
+                duration_ms: Some((chrono::Utc::now() - started_at).num_milliseconds().max(0) as u64),
+                packages_affected: staged.packages.iter().map(|p| p.name.clone()).collect(),
+                output: String::new(),
+                error_message: None,
+                delta: None,
+                snapshot_id: None,
+                rollback_of: None,
+            };
+            if let Err(e) = database.record_maintenance(&record).await {
+                tracing::warn!("Failed to record staged updates: {}", e);
+            }
+        }
+
+        if !staged.packages.is_empty() {
+            self.notifier.notify(Notification::new(
+                NotifyEvent::OperationCompleted,
+                NotifySeverity::Info,
+                "Updates staged",
+                format!(
+                    "{} packages ({:.1} MiB) downloaded and ready to install",
+                    staged.packages.len(),
+                    staged.total_download_bytes as f64 / (1024.0 * 1024.0)
+                ),
+            ));
+        }
+
+        Ok(PackageUpdateReport {
+            staged_at: Some(staged.staged_at),
+            packages: Some(PackageList::Staged(staged.packages)),
+            total_download_bytes: Some(staged.total_download_bytes),
+            ..PackageUpdateReport::new("stage_updates", true)
+        })
+    }
+
+    /// Take a differential backup of the configured paths into `destination`,
+    /// prune it by the retention policy, and bring the catalog up to date
+    async fn backup_configs(&self, destination: &str) -> Result<serde_json::Value> {
+        let settings = self.config_backup();
+        let store = config_backup::BackupStore::new(destination);
+        let retention = settings.retention();
+        let (set, pruned, summaries) = tokio::task::spawn_blocking(move || {
+            let set = store.create(&settings.paths, chrono::Utc::now())?;
+            let pruned = store.prune(&retention)?;
+            let summaries = store.summaries()?;
+            anyhow::Ok((set, pruned, summaries))
+        })
+        .await??;
+
+        // Re-basing changes what the kept sets store, so every row is rewritten
+        if let Some(database) = &self.database {
+            for summary in &summaries {
+                if let Err(e) = database.record_backup_set(summary).await {
+                    tracing::warn!("Failed to catalog backup set {}: {}", summary.id, e);
+                }
+            }
+            for id in &pruned {
+                if let Err(e) = database.delete_backup_set(id).await {
+                    tracing::warn!("Failed to drop pruned backup set {}: {}", id, e);
+                }
+            }
+        }
+
+        let summary = summaries
+            .into_iter()
+            .find(|s| s.id == set.id)
+            .ok_or_else(|| anyhow::anyhow!("Backup set {} was pruned right away", set.id))?;
+        Ok(serde_json::json!({
+            "operation": "backup_configs",
+            "success": true,
+            "destination": destination,
+            "set": summary,
+            "pruned": pruned,
+        }))
+    }
+
+    /// Restore every file that differs from backup set `set_id` in the
+    /// configured store, after a snapshot when snapper is available
+    async fn restore_configs(&self, set_id: &str) -> Result<serde_json::Value> {
+        let store = config_backup::BackupStore::new(self.config_backup().destination);
+        let set = store.load(set_id)?;
+        let changes = store.restore_plan(&set)?;
+        if changes.is_empty() {
+            return Ok(serde_json::json!({
+                "operation": "restore_configs",
+                "success": true,
+                "set": set_id,
+                "restored": changes,
+            }));
+        }
+
+        let snapshot_id =
+            rollback::pre_snapshot(&SystemRunner::default(), "jarvis: before restore_configs").await;
+        let restored = changes.clone();
+        tokio::task::spawn_blocking(move || store.restore(&set, &restored)).await??;
+        Ok(serde_json::json!({
+            "operation": "restore_configs",
+            "success": true,
+            "set": set_id,
+            "restored": changes,
+            "snapshot_id": snapshot_id,
+        }))
+    }
+
+    /// Install the staged update set from the package cache
+    async fn apply_staged_updates(&self, started_at: chrono::DateTime<chrono::Utc>) -> Result<PackageUpdateReport> {
+        let pm = self
+            .package_manager
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Package manager not initialized"))?;
+        let database = self
+            .database
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Database not initialized"))?;
+
+        let staged: package_manager::StagedUpdate = match database.get_config_value(STAGED_UPDATE_KEY).await? {
+            Some(json) => serde_json::from_str(&json)?,
+            None => return Err(anyhow::anyhow!("No staged updates; run stage_updates first")),
+        };
+
+        let snapshot_id =
+            rollback::pre_snapshot(&SystemRunner::default(), "jarvis: before apply_staged_updates").await;
+        let mut result = pm.apply_staged_updates(&staged).await?;
+        self.record_update_transaction(started_at, &result, snapshot_id).await;
+
+        if result.success {
+            database.delete_config_value(STAGED_UPDATE_KEY).await?;
+            result.rebuilds_needed = self.detect_soname_rebuilds().await;
+        }
+
+        Ok(result)
+    }
+
+    /// Build an AUR package in the sandbox and install the result
+    async fn install_from_aur(&self, package: &str) -> Result<PackageUpdateReport> {
+        let sandbox = self
+            .aur_sandbox
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("AUR support not enabled"))?;
+        if let Some(database) = &self.database
+            && active_response::aur_blocklist(database).await?.iter().any(|p| p == package)
+        {
+            return Err(anyhow::anyhow!(
+                "{} was quarantined by a Wazuh active response; remove it from the '{}' blocklist to install it",
+                package,
+                active_response::AUR_BLOCKLIST_KEY
+            ));
+        }
+        let no_confirm = self
+            .config
+            .as_ref()
+            .map(|c| c.agent.pacman.no_confirm)
+            .unwrap_or(false);
+
+        let result = sandbox.build_and_install(package, no_confirm).await?;
+        if !result.success {
+            tracing::warn!(
+                "Sandboxed AUR build of {} failed; see {}",
+                package,
+                result.build_log.display()
+            );
+        }
+
+        Ok(PackageUpdateReport {
+            package: Some(package.to_string()),
+            from_aur: Some(true),
+            duration_ms: Some(result.duration_ms),
+            sandbox: Some(result.backend),
+            build_log: Some(result.build_log),
+            packages: Some(PackageList::Built(result.packages)),
+            findings: Some(result.findings),
+            installed: Some(result.installed),
+            error: result.error,
+            ..PackageUpdateReport::new("install_package", result.success)
+        })
+    }
+
+    /// Rebuild AUR packages in the sandbox one by one, by default those the
+    /// last soname check found broken, then check every foreign package again
+    async fn rebuild_aur_packages(&self, packages: Option<Vec<String>>) -> Result<PackageUpdateReport> {
+        let start_time = std::time::Instant::now();
+        let packages = match packages {
+            Some(packages) => packages,
+            None => self
+                .load_rebuilds()
+                .await
+                .unwrap_or_default()
+                .packages
+                .into_iter()
+                .map(|rebuild| rebuild.package)
+                .collect(),
+        };
+
+        let mut completed = Vec::new();
+        let mut incomplete = Vec::new();
+        let mut errors = Vec::new();
+        for package in packages {
+            match self.install_from_aur(&package).await {
+                Ok(report) if report.success => completed.push(package),
+                Ok(report) => {
+                    errors.push(format!(
+                        "{}: {}",
+                        package,
+                        report.error.unwrap_or_else(|| "build failed".to_string())
+                    ));
+                    incomplete.push(package);
+                }
+                Err(e) => {
+                    errors.push(format!("{}: {}", package, e));
+                    incomplete.push(package);
+                }
+            }
+        }
+        let rebuilds_needed = if completed.is_empty() {
+            self.load_rebuilds().await.map(|report| report.packages)
+        } else {
+            self.detect_soname_rebuilds().await
+        };
+
+        Ok(PackageUpdateReport {
+            packages_updated: Some(completed.len()),
+            duration_ms: Some(start_time.elapsed().as_millis() as u64),
+            error: (!errors.is_empty()).then(|| errors.join("; ")),
+            rebuilds_needed,
+            completed: Some(completed),
+            incomplete: Some(incomplete),
+            ..PackageUpdateReport::new("rebuild_aur_packages", errors.is_empty())
+        })
+    }
+
+    /// Look for foreign packages an update left without their libraries and
+    /// keep the result for health checks. Best effort: the update itself
+    /// already succeeded.
+    async fn detect_soname_rebuilds(&self) -> Option<Vec<RebuildNeeded>> {
+        let paths = soname_rebuild::LibraryPaths::system();
+        match soname_rebuild::scan_foreign(&SystemRunner::default(), &paths).await {
+            Ok(report) => {
+                if !report.is_empty() {
+                    tracing::warn!("{}", report.summary());
+                }
+                self.store_rebuilds(&report).await;
+                Some(report.packages)
+            }
+            Err(e) => {
+                tracing::warn!("Soname check after the update failed: {}", e);
+                None
+            }
+        }
+    }
+
+    /// The last soname check's report, if there was one
+    async fn load_rebuilds(&self) -> Option<RebuildReport> {
+        let json = self
+            .database
+            .as_ref()?
+            .get_config_value(SONAME_REBUILDS_KEY)
+            .await
+            .ok()
+            .flatten()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    /// Keep `report`, and tell the other agents when packages start or stop
+    /// needing a rebuild
+    async fn store_rebuilds(&self, report: &RebuildReport) {
+        let was_broken = self.load_rebuilds().await.is_some_and(|last| !last.is_empty());
+        self.cache_findings(
+            SONAME_REBUILDS_KEY,
+            report,
+            "AUR packages needing a rebuild as of the last soname check",
+        ).await;
+        if was_broken != !report.is_empty() {
+            bus::publish(BusEvent::HealthStateChanged {
+                component: "aur_rebuilds".to_string(),
+                state: if report.is_empty() { "healthy" } else { "degraded" }.to_string(),
+            });
+        }
+    }
+
+    /// Run the PKGBUILD heuristics for the given (or all foreign) packages
+    async fn aur_security_check(&self, packages: Option<Vec<String>>) -> Result<ScanReport> {
+        let sandbox = self
+            .aur_sandbox
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("AUR support not enabled"))?;
+
+        let all_foreign = packages.is_none();
+        let packages = match packages {
+            Some(packages) => packages,
+            None => jarvis_core::package_cache::PackageCache::shared()
+                .index()
+                .await?
+                .foreign()
+                .into_iter()
+                .map(|package| package.name.clone())
+                .collect(),
+        };
+
+        // Checking every foreign package replaces the cached findings;
+        // checking a few updates just those
+        let mut cached: std::collections::BTreeMap<String, Vec<PkgbuildFinding>> =
+            match (&self.database, all_foreign) {
+                (Some(database), false) => database
+                    .get_config_value(sarif::AUR_FINDINGS_KEY)
+                    .await
+                    .ok()
+                    .flatten()
+                    .and_then(|json| serde_json::from_str(&json).ok())
+                    .unwrap_or_default(),
+                _ => Default::default(),
+            };
+        let mut results = std::collections::BTreeMap::new();
+        for package in &packages {
+            let mut entry = match sandbox.security_check(package).await {
+                Ok(findings) => {
+                    cached.insert(package.clone(), findings.clone());
+                    AurPackageCheck {
+                        findings: Some(findings),
+                        ..Default::default()
+                    }
+                }
+                Err(e) => AurPackageCheck {
+                    error: Some(e.to_string()),
+                    ..Default::default()
+                },
+            };
+            // Maintainers pin install-time warnings as comments on the AUR page
+            let comments = jarvis_core::aur_comments::pinned_comments(package).await;
+            entry.pinned_comments = comments.pinned;
+            entry.pinned_comments_unavailable = comments.unavailable;
+            results.insert(package.clone(), entry);
+        }
+        cached.retain(|_, findings| !findings.is_empty());
+        self.cache_findings(
+            sarif::AUR_FINDINGS_KEY,
+            &cached,
+            "PKGBUILD findings per AUR package as of the last check",
+        ).await;
+
+        Ok(ScanReport {
+            packages_checked: Some(packages.len()),
+            results: Some(results),
+            ..ScanReport::new("aur_security_check")
+        })
+    }
+
+    /// Sample host metrics every 30 seconds for `duration_minutes` into the
+    /// main Jarvis store, where `jarvis check trend` and the weekly report
+    /// read them, and summarize what was seen
+    async fn analyze_performance(&self, duration_minutes: u32) -> Result<HealthReport> {
+        let memory = open_jarvis_memory().await?;
+        let mut sampler = jarvis_core::metrics::HostSampler::new();
+        let started = chrono::Utc::now();
+        let until = started + chrono::Duration::minutes(duration_minutes as i64);
+        let mut samples = 0u32;
+        loop {
+            let now = chrono::Utc::now();
+            let readings = sampler.sample(now);
+            memory.record_metrics(&readings).await?;
+            samples += 1;
+            if now >= until {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+        }
+
+        let end = chrono::Utc::now() + chrono::Duration::seconds(1);
+        let mut metrics = std::collections::BTreeMap::new();
+        for name in memory.metric_names_since(started).await? {
+            for series in memory.query_range(&name, started, end, end - started).await? {
+                if let Some((min, avg, max)) = series.summary() {
+                    metrics.insert(series.display_name(), MetricSummary { min, avg, max });
+                }
+            }
+        }
+
+        Ok(HealthReport {
+            duration_minutes: Some(duration_minutes),
+            samples: Some(samples),
+            metrics: Some(metrics),
+            ..HealthReport::new("performance_analysis")
+        })
+    }
+
+    /// Keep a check's findings for `open_findings`. Caching is best effort:
+    /// the check itself already succeeded.
+    async fn cache_findings<T: Serialize + ?Sized>(
+        &self,
+        key: &str,
+        findings: &T,
+        description: &str,
+    ) {
+        let Some(database) = &self.database else {
+            return;
+        };
+        let stored = match serde_json::to_string(findings) {
+            Ok(json) => database.set_config_value(key, &json, description).await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = stored {
+            tracing::warn!("Failed to cache {}: {}", key, e);
+        }
+    }
+
+    /// Group a scan's findings into new, still present, and resolved against
+    /// those open after the previous scan, and pass on only the changes.
+    /// Without the database there is nothing to compare with, so the scan is
+    /// returned as is.
+    async fn track_security_scan(&self, mut scan: ScanReport) -> Result<ScanReport> {
+        let Some(database) = &self.database else {
+            return Ok(scan);
+        };
+        let now = chrono::Utc::now();
+        let mut store = findings::FindingStore::load(database).await?;
+        let scanned = serde_json::Value::Object(scan.scanner.clone());
+        let lifecycle = store.reconcile(findings::from_scan(&scanned, now), now);
+        store.save(database).await?;
+
+        if lifecycle.has_changes() {
+            self.notify_finding_changes(&lifecycle);
+            if let Some(wazuh) = &self.wazuh_integration
+                && let Err(e) = wazuh.report_finding_changes(&lifecycle).await
+            {
+                tracing::warn!("Failed to forward security findings to Wazuh: {}", e);
+            }
+        }
+
+        scan.scanner.remove("issues");
+        scan.summary = Some(FindingSummary {
+            new: lifecycle.new.len(),
+            still_present: lifecycle.still_present.len(),
+            resolved: lifecycle.resolved.len(),
+        });
+        scan.findings = Some(lifecycle);
+        Ok(scan)
+    }
+
+    /// Attach the last Trivy scan of container images to a full security scan
+    /// and forward it to Wazuh. jarvisd scans images on its `[trivy]`
+    /// schedule; acknowledged advisories are listed apart from the findings.
+    async fn add_image_scans(&self, scan: &mut ScanReport) {
+        let loaded = async {
+            let memory = open_jarvis_memory().await?;
+            let cache = ImageScanCache::load(&memory).await?;
+            let acknowledgements = Acknowledgements::load(&memory).await?;
+            anyhow::Ok((cache, acknowledgements))
+        };
+        let (cache, acknowledgements) = match loaded.await {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                tracing::warn!("Container image scans unavailable: {}", e);
+                return;
+            }
+        };
+        if cache.is_empty() {
+            return;
+        }
+
+        let now = chrono::Utc::now();
+        let images: Vec<ImageFindings> = cache
+            .scans()
+            .map(|image| {
+                let (findings, acknowledged) = acknowledgements.triage(image.findings(), now);
+                ImageFindings {
+                    image: image.image.clone(),
+                    digest: image.digest.clone(),
+                    scanned_at: image.scanned_at,
+                    findings,
+                    acknowledged,
+                }
+            })
+            .collect();
+
+        if let Some(wazuh) = &self.wazuh_integration {
+            let scans: Vec<ImageScan> = cache.scans().cloned().collect();
+            if let Err(e) = wazuh.report_image_vulnerabilities(&scans).await {
+                tracing::warn!("Failed to forward container image scans to Wazuh: {}", e);
+            }
+        }
+        scan.container_images = Some(images);
+    }
+
+    /// Summarize a scan's changes in one notification, and put each new
+    /// finding on the event bus for the incident correlator
+    fn notify_finding_changes(&self, lifecycle: &findings::ScanLifecycle) {
+        for finding in &lifecycle.new {
+            bus::publish(BusEvent::AnomalyDetected {
+                category: "security".to_string(),
+                component: finding.subject.clone(),
+                severity: finding.severity,
+                description: format!("{}: {}", finding.check, finding.description),
+            });
+        }
+        let worst = lifecycle
+            .new
+            .iter()
+            .map(|f| f.severity)
+            .max()
+            .unwrap_or_default();
+        let mut lines: Vec<String> = lifecycle
+            .new
+            .iter()
+            .map(|f| {
+                format!(
+                    "new [{}] {} {}: {}",
+                    f.severity, f.check, f.subject, f.description
+                )
+            })
+            .collect();
+        lines.extend(
+            lifecycle
+                .resolved
+                .iter()
+                .map(|f| format!("resolved {} {}", f.check, f.subject)),
+        );
+
+        self.notifier.notify(Notification::new(
+            NotifyEvent::SecurityFindings,
+            worst.into(),
+            format!(
+                "Security scan: {} new, {} resolved",
+                lifecycle.new.len(),
+                lifecycle.resolved.len()
+            ),
+            lines.join("\n"),
+        ));
+    }
+
+    /// Persist an update transaction and its package delta in the operations history
+    async fn record_update_transaction(
+        &self,
+        started_at: chrono::DateTime<chrono::Utc>,
+        report: &PackageUpdateReport,
+        snapshot_id: Option<u32>,
+    ) {
+        let Some(database) = &self.database else {
+            return;
+        };
+        let success = report.success;
+
+        let record = zqlite_integration::MaintenanceRecord {
+            id: Uuid::new_v4(),
+            operation_type: "update_packages".to_string(),
+            status: if success {
+                zqlite_integration::MaintenanceStatus::Completed
+            } else {
+                zqlite_integration::MaintenanceStatus::Failed
+            },
+            started_at,
+            completed_at: Some(chrono::Utc::now()),
+            duration_ms: report.duration_ms,
+            packages_affected: report.packages_affected(),
+            output: report.output.clone().unwrap_or_default(),
+            error_message: report.error.clone(),
+            delta: report.delta.as_ref().and_then(|d| serde_json::to_value(d).ok()),
+            snapshot_id,
+            rollback_of: None,
+        };
+
+        if let Err(e) = database.record_maintenance(&record).await {
+            tracing::warn!("Failed to record update transaction: {}", e);
+        }
+    }
 
-impl ArchLinuxAgent {
     fn determine_health_status(&self) -> HealthStatus {
         match self.state {
             AgentState::Ready => HealthStatus::Healthy,
+            AgentState::Degraded => HealthStatus::Warning,
             AgentState::Busy => HealthStatus::Healthy,
             AgentState::Maintenance => HealthStatus::Warning,
             AgentState::Error => HealthStatus::Critical,
@@ -453,6 +2382,26 @@ impl ArchLinuxAgent {
     }
 }
 
+/// The main Jarvis memory store, where `jarvis vuln ack` keeps acknowledgements
+async fn open_jarvis_memory() -> Result<jarvis_core::MemoryStore> {
+    let config = jarvis_core::Config::load(None).await?;
+    jarvis_core::MemoryStore::new(&config.database_path).await
+}
+
+impl AgentStatistics {
+    /// Count one finished operation
+    pub fn record(&mut self, success: bool, duration_ms: u64) {
+        self.total_operations += 1;
+        if success {
+            self.successful_operations += 1;
+        } else {
+            self.failed_operations += 1;
+        }
+        self.average_operation_time_ms += (duration_ms as f64 - self.average_operation_time_ms)
+            / self.total_operations as f64;
+    }
+}
+
 impl Default for AgentStatistics {
     fn default() -> Self {
         Self {
@@ -466,4 +2415,113 @@ impl Default for AgentStatistics {
             average_operation_time_ms: 0.0,
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every operation and whether read-only mode lets it run. A new
+    /// operation fails this until it is classified here and in `is_read_only`.
+    #[test]
+    fn test_read_only_classification_covers_every_operation() {
+        let table = [
+            (ArchOperation::UpdatePackages { packages: None }, false),
+            (
+                ArchOperation::InstallPackage {
+                    package: "ripgrep".to_string(),
+                    from_aur: false,
+                },
+                false,
+            ),
+            (
+                ArchOperation::RemovePackage {
+                    package: "ripgrep".to_string(),
+                    remove_deps: true,
+                },
+                false,
+            ),
+            (
+                ArchOperation::SearchPackages {
+                    query: "neovim".to_string(),
+                    include_aur: true,
+                },
+                true,
+            ),
+            (ArchOperation::StageUpdates, false),
+            (ArchOperation::ApplyStagedUpdates, false),
+            (ArchOperation::RebuildAurPackages { packages: None }, false),
+            (ArchOperation::ListFlatpaks, true),
+            (ArchOperation::CheckFlatpakUpdates, true),
+            (ArchOperation::UpdateFlatpaks { refs: None }, false),
+            (
+                ArchOperation::SystemCleanup {
+                    clean_cache: true,
+                    clean_logs: false,
+                },
+                false,
+            ),
+            (ArchOperation::UpdateMirrorlist { country: None }, false),
+            (ArchOperation::CheckDiskUsage { path: None }, true),
+            (ArchOperation::SecurityScan { full_scan: true }, true),
+            (ArchOperation::VulnerabilityScan { packages: None }, true),
+            (ArchOperation::AURSecurityCheck { packages: None }, true),
+            (
+                ArchOperation::ServiceOperation {
+                    service: "sshd".to_string(),
+                    operation: ServiceOperation::Stop,
+                },
+                false,
+            ),
+            (ArchOperation::ListServices { filter: None }, true),
+            (
+                ArchOperation::HealthCheck {
+                    include_services: true,
+                },
+                true,
+            ),
+            (
+                ArchOperation::PerformanceAnalysis {
+                    duration_minutes: 5,
+                },
+                true,
+            ),
+            (
+                ArchOperation::LogAnalysis {
+                    service: None,
+                    since: "24h".to_string(),
+                },
+                true,
+            ),
+            (
+                ArchOperation::BackupConfigs {
+                    destination: "/backup".to_string(),
+                },
+                false,
+            ),
+            (
+                ArchOperation::RestoreConfigs {
+                    source: "latest".to_string(),
+                },
+                false,
+            ),
+            (ArchOperation::ValidateConfigs, true),
+            (
+                ArchOperation::CustomCommand {
+                    command: "uptime".to_string(),
+                    args: Vec::new(),
+                },
+                false,
+            ),
+        ];
+
+        let mut names: Vec<&str> = table.iter().map(|(op, _)| op.name()).collect();
+        names.sort();
+        let mut expected = ArchOperation::NAMES.to_vec();
+        expected.sort();
+        assert_eq!(names, expected);
+
+        for (operation, read_only) in &table {
+            assert_eq!(operation.is_read_only(), *read_only, "{}", operation.name());
+        }
+    }
+}
@@ -0,0 +1,175 @@
+//! Warnings and errors from the journal over a time range
+//!
+//! `ArchOperation::LogAnalysis` takes the same ranges as every other
+//! `--since` ("24h", "yesterday", "since boot"); journalctl gets the resolved
+//! bounds as epoch seconds so it never re-reads them in its own timezone.
+//! Messages that differ only in numbers, addresses, or ids are clustered
+//! with [`jarvis_core::log_clusters`], the same grouping `jarvis logs follow`
+//! uses.
+
+use crate::operation_output::HealthReport;
+use anyhow::Result;
+use jarvis_core::exec::{CommandRunner, OutputText};
+use jarvis_core::log_clusters::Clusters;
+use jarvis_core::time_range::TimeRange;
+use std::collections::BTreeMap;
+
+/// Lines of the newest entries kept in the result
+const RECENT_ENTRIES: usize = 20;
+
+/// Largest message clusters kept in the result
+const TOP_CLUSTERS: usize = 10;
+
+/// Journal entries at warning priority or worse in `range`, optionally for
+/// one unit, counted per unit
+pub async fn analyze(
+    runner: &dyn CommandRunner,
+    service: Option<&str>,
+    range: TimeRange,
+) -> Result<HealthReport> {
+    let since = format!("@{}", range.start.timestamp());
+    let until = format!("@{}", range.end.timestamp());
+    let mut args = vec![
+        "--since",
+        since.as_str(),
+        "--until",
+        until.as_str(),
+        "--priority",
+        "warning",
+        "--output",
+        "short-iso",
+        "--quiet",
+        "--no-pager",
+    ];
+    if let Some(service) = service {
+        args.extend(["--unit", service]);
+    }
+    let output = runner.output("journalctl", &args).await?;
+    let text = OutputText::decode(&output);
+    if !output.status.success() {
+        anyhow::bail!("journalctl failed: {}", text.stderr.trim());
+    }
+
+    let entries: Vec<&str> = text
+        .stdout
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    let mut by_unit: BTreeMap<String, usize> = BTreeMap::new();
+    let mut clusters = Clusters::new();
+    for entry in &entries {
+        if let Some(unit) = entry_unit(entry) {
+            *by_unit.entry(unit.to_string()).or_default() += 1;
+            if let Some(message) = entry_message(entry) {
+                clusters.add(unit, message);
+            }
+        }
+    }
+    let recent = &entries[entries.len().saturating_sub(RECENT_ENTRIES)..];
+
+    Ok(HealthReport {
+        service: service.map(str::to_string),
+        since: Some(range.start),
+        until: Some(range.end),
+        entries: Some(entries.len()),
+        by_unit: Some(by_unit),
+        clusters: Some(clusters.top(TOP_CLUSTERS)),
+        recent: Some(recent.iter().map(|line| line.to_string()).collect()),
+        output_lossy: Some(text.lossy),
+        ..HealthReport::new("log_analysis")
+    })
+}
+
+/// The identifier of a short-iso line: "2024-05-01T08:00:00+0200 host sshd[42]: ..." gives "sshd"
+fn entry_unit(line: &str) -> Option<&str> {
+    let identifier = line.split_whitespace().nth(2)?.strip_suffix(':')?;
+    Some(identifier.split('[').next().unwrap_or(identifier))
+}
+
+/// The message of a short-iso line, after the identifier
+fn entry_message(line: &str) -> Option<&str> {
+    line.splitn(4, ' ').nth(3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Utc};
+    use jarvis_core::exec::{RecordingRunner, fake_output};
+
+    fn range() -> TimeRange {
+        TimeRange {
+            start: DateTime::parse_from_rfc3339("2024-05-01T00:00:00Z")
+                .unwrap()
+                .with_timezone(&Utc),
+            end: DateTime::parse_from_rfc3339("2024-05-02T00:00:00Z")
+                .unwrap()
+                .with_timezone(&Utc),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_counts_entries_per_unit() {
+        let runner = RecordingRunner::new().respond(
+            "journalctl",
+            "2024-05-01T08:00:00+0000 arch sshd[42]: Failed password for root\n\
+             2024-05-01T09:00:00+0000 arch kernel: nvme0: I/O timeout\n\
+             2024-05-01T09:30:00+0000 arch sshd[43]: Failed password for admin\n",
+        );
+        let result = analyze(&runner, None, range()).await.unwrap();
+
+        assert_eq!(result.entries, Some(3));
+        let by_unit = result.by_unit.unwrap();
+        assert_eq!(by_unit["sshd"], 2);
+        assert_eq!(by_unit["kernel"], 1);
+        assert_eq!(
+            result.recent.unwrap()[2],
+            "2024-05-01T09:30:00+0000 arch sshd[43]: Failed password for admin"
+        );
+        assert_eq!(
+            runner.calls(),
+            vec![
+                "journalctl --since @1714521600 --until @1714608000 --priority warning \
+                 --output short-iso --quiet --no-pager"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_clusters_messages_differing_in_numbers() {
+        let runner = RecordingRunner::new().respond(
+            "journalctl",
+            "2024-05-01T08:00:00+0000 arch sshd[42]: Failed password for root from 10.0.0.5 port 51234\n\
+             2024-05-01T09:00:00+0000 arch kernel: nvme0: I/O timeout\n\
+             2024-05-01T09:30:00+0000 arch sshd[43]: Failed password for root from 10.0.0.9 port 40022\n",
+        );
+        let result = analyze(&runner, None, range()).await.unwrap();
+
+        let clusters = result.clusters.unwrap();
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[0].identifier, "sshd");
+        assert_eq!(clusters[0].count, 2);
+        assert_eq!(
+            clusters[0].template,
+            "Failed password for root from <ip> port <n>"
+        );
+        assert_eq!(clusters[1].template, "nvme<n>: I/O timeout");
+    }
+
+    #[tokio::test]
+    async fn test_one_service_and_failure() {
+        let runner = RecordingRunner::new();
+        let result = analyze(&runner, Some("nginx.service"), range())
+            .await
+            .unwrap();
+        assert_eq!(result.entries, Some(0));
+        assert!(runner.calls()[0].ends_with("--unit nginx.service"));
+
+        let runner = RecordingRunner::new().respond_with(
+            "journalctl",
+            fake_output(1, "", "Failed to add match 'nope': Invalid argument"),
+        );
+        let err = analyze(&runner, Some("nope"), range()).await.unwrap_err();
+        assert!(err.to_string().contains("Invalid argument"));
+    }
+}
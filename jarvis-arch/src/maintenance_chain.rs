@@ -0,0 +1,547 @@
+//! Composite maintenance runs
+//!
+//! A system update is really several tasks in a fixed order: refresh the
+//! mirrors, download updates, snapshot, apply, restart what the upgrade
+//! touched, and vacuum the journal. A preset lists such steps, each with a
+//! policy for what its failure means for the rest of the chain. Steps share a
+//! context, so a later step can use what an earlier one produced, such as the
+//! snapshot number or the packages that were upgraded.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use jarvis_core::exec::{CommandRunner, OutputText};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use uuid::Uuid;
+
+use crate::ArchOperation;
+use crate::config::ScheduledTask;
+use crate::package_manager::TransactionDelta;
+
+/// Units a restart would take the session or the logging down with
+const NEVER_RESTART: &[&str] = &[
+    "dbus.service",
+    "dbus-broker.service",
+    "systemd-logind.service",
+    "systemd-journald.service",
+    "display-manager.service",
+];
+
+/// What a failed step means for the rest of the chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnFailure {
+    /// Skip the remaining steps; the run fails
+    Abort,
+    /// Run the remaining steps; the run still fails
+    Continue,
+    /// Run the remaining steps and only send a notification
+    NotifyOnly,
+}
+
+/// The work one step does
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepTask {
+    Operation(ArchOperation),
+    /// Take a snapper snapshot for the package changes after it to share
+    Snapshot,
+    /// Restart active services shipped by packages upgraded earlier in the run
+    RestartServices,
+}
+
+impl StepTask {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StepTask::Operation(operation) => operation.name(),
+            StepTask::Snapshot => "Snapshot",
+            StepTask::RestartServices => "RestartServices",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChainStep {
+    pub task: StepTask,
+    pub on_failure: OnFailure,
+}
+
+impl ChainStep {
+    fn new(task: StepTask, on_failure: OnFailure) -> Self {
+        Self { task, on_failure }
+    }
+
+    fn operation(operation: ArchOperation, on_failure: OnFailure) -> Self {
+        Self::new(StepTask::Operation(operation), on_failure)
+    }
+}
+
+/// Built-in composite tasks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChainPreset {
+    FullUpdate,
+    SecurityAudit,
+    DeepClean,
+}
+
+impl ChainPreset {
+    pub const ALL: [ChainPreset; 3] = [
+        ChainPreset::FullUpdate,
+        ChainPreset::SecurityAudit,
+        ChainPreset::DeepClean,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ChainPreset::FullUpdate => "full-update",
+            ChainPreset::SecurityAudit => "security-audit",
+            ChainPreset::DeepClean => "deep-clean",
+        }
+    }
+
+    /// The scheduled task whose health and disk space requirements the
+    /// preset has to meet
+    pub fn guarded_as(self) -> ScheduledTask {
+        match self {
+            ChainPreset::FullUpdate => ScheduledTask::Update,
+            ChainPreset::SecurityAudit => ScheduledTask::SecurityScan,
+            ChainPreset::DeepClean => ScheduledTask::Clean,
+        }
+    }
+
+    /// The steps in the order they run
+    pub fn steps(self) -> Vec<ChainStep> {
+        use OnFailure::*;
+
+        match self {
+            // Nothing is installed unless the download succeeded, and the
+            // snapshot is only worth having if the packages go in after it
+            ChainPreset::FullUpdate => vec![
+                ChainStep::operation(
+                    ArchOperation::UpdateMirrorlist { country: None },
+                    NotifyOnly,
+                ),
+                ChainStep::operation(ArchOperation::StageUpdates, Abort),
+                ChainStep::new(StepTask::Snapshot, Continue),
+                ChainStep::operation(ArchOperation::ApplyStagedUpdates, Abort),
+                ChainStep::new(StepTask::RestartServices, NotifyOnly),
+                ChainStep::operation(
+                    ArchOperation::SystemCleanup {
+                        clean_cache: false,
+                        clean_logs: true,
+                    },
+                    Continue,
+                ),
+            ],
+            // Each scan stands on its own
+            ChainPreset::SecurityAudit => vec![
+                ChainStep::operation(ArchOperation::SecurityScan { full_scan: true }, Continue),
+                ChainStep::operation(
+                    ArchOperation::VulnerabilityScan { packages: None },
+                    Continue,
+                ),
+                ChainStep::operation(ArchOperation::AURSecurityCheck { packages: None }, Continue),
+                ChainStep::operation(ArchOperation::ValidateConfigs, NotifyOnly),
+            ],
+            ChainPreset::DeepClean => vec![
+                ChainStep::operation(
+                    ArchOperation::SystemCleanup {
+                        clean_cache: true,
+                        clean_logs: true,
+                    },
+                    Continue,
+                ),
+                ChainStep::operation(ArchOperation::CheckDiskUsage { path: None }, NotifyOnly),
+            ],
+        }
+    }
+}
+
+impl FromStr for ChainPreset {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        ChainPreset::ALL
+            .into_iter()
+            .find(|preset| preset.name() == s.replace('_', "-"))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown maintenance preset: {}; expected one of: {}",
+                    s,
+                    ChainPreset::ALL.map(|p| p.name()).join(", ")
+                )
+            })
+    }
+}
+
+/// What earlier steps hand to later ones
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChainContext {
+    pub snapshot_id: Option<u32>,
+    pub staged_packages: Vec<String>,
+    pub upgraded_packages: Vec<String>,
+    pub restarted_services: Vec<String>,
+}
+
+impl ChainContext {
+    /// Pick up what a step's output means for the steps after it
+    pub fn absorb(&mut self, output: &serde_json::Value) {
+        if let Some(snapshot) = output.get("snapshot_id").and_then(|v| v.as_u64()) {
+            self.snapshot_id = Some(snapshot as u32);
+        }
+        if output.get("operation").and_then(|v| v.as_str()) == Some("stage_updates")
+            && let Some(packages) = output.get("packages").and_then(|v| v.as_array())
+        {
+            self.staged_packages = packages
+                .iter()
+                .filter_map(|p| p.get("name").and_then(|n| n.as_str()))
+                .map(str::to_string)
+                .collect();
+        }
+        if let Some(delta) = output
+            .get("delta")
+            .and_then(|d| serde_json::from_value::<TransactionDelta>(d.clone()).ok())
+        {
+            self.upgraded_packages
+                .extend(delta.upgraded.into_iter().map(|c| c.package));
+        }
+        if let Some(restarted) = output.get("restarted").and_then(|v| v.as_array()) {
+            self.restarted_services.extend(
+                restarted
+                    .iter()
+                    .filter_map(|s| s.as_str())
+                    .map(str::to_string),
+            );
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    Succeeded,
+    Failed,
+    /// Not run because an earlier step aborted the chain
+    Skipped,
+}
+
+/// One step of a composite run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepResult {
+    pub name: String,
+    pub on_failure: OnFailure,
+    pub status: StepStatus,
+    pub output: serde_json::Value,
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+/// A composite run, with its steps as children
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChainResult {
+    pub id: Uuid,
+    pub preset: ChainPreset,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    /// False when a step with an `Abort` or `Continue` policy failed
+    pub success: bool,
+    /// The step whose failure skipped the rest
+    pub aborted_at: Option<String>,
+    pub steps: Vec<StepResult>,
+    pub context: ChainContext,
+}
+
+impl ChainResult {
+    /// Failed steps, including those whose policy is only to notify
+    pub fn failed_steps(&self) -> impl Iterator<Item = &StepResult> {
+        self.steps
+            .iter()
+            .filter(|step| step.status == StepStatus::Failed)
+    }
+}
+
+/// Runs the steps of a chain; the agent in production, fakes in tests
+#[async_trait]
+pub trait StepRunner: Send + Sync {
+    /// Run `task` and return its output; a step that did not do its job
+    /// returns an error
+    async fn run_step(&self, task: &StepTask, context: &ChainContext) -> Result<serde_json::Value>;
+}
+
+/// Run `preset` step by step, applying each step's failure policy
+pub async fn run(preset: ChainPreset, runner: &dyn StepRunner) -> ChainResult {
+    let started_at = Utc::now();
+    let mut context = ChainContext::default();
+    let mut steps = Vec::new();
+    let mut success = true;
+    let mut aborted_at: Option<String> = None;
+
+    for step in preset.steps() {
+        let name = step.task.name().to_string();
+        if aborted_at.is_some() {
+            steps.push(StepResult {
+                name,
+                on_failure: step.on_failure,
+                status: StepStatus::Skipped,
+                output: serde_json::Value::Null,
+                error: None,
+                duration_ms: 0,
+            });
+            continue;
+        }
+
+        tracing::info!("{}: running {}", preset.name(), name);
+        let start = std::time::Instant::now();
+        let (status, output, error) = match runner.run_step(&step.task, &context).await {
+            Ok(output) => {
+                context.absorb(&output);
+                (StepStatus::Succeeded, output, None)
+            }
+            Err(e) => {
+                tracing::warn!("{}: {} failed: {:#}", preset.name(), name, e);
+                match step.on_failure {
+                    OnFailure::Abort => {
+                        success = false;
+                        aborted_at = Some(name.clone());
+                    }
+                    OnFailure::Continue => success = false,
+                    OnFailure::NotifyOnly => {}
+                }
+                (
+                    StepStatus::Failed,
+                    serde_json::Value::Null,
+                    Some(format!("{:#}", e)),
+                )
+            }
+        };
+        steps.push(StepResult {
+            name,
+            on_failure: step.on_failure,
+            status,
+            output,
+            error,
+            duration_ms: start.elapsed().as_millis() as u64,
+        });
+    }
+
+    ChainResult {
+        id: Uuid::new_v4(),
+        preset,
+        started_at,
+        finished_at: Utc::now(),
+        success,
+        aborted_at,
+        steps,
+        context,
+    }
+}
+
+/// Try-restart the active services that `packages` ship, except those whose
+/// restart would end the session; returns the units restarted
+pub async fn restart_services(
+    runner: &dyn CommandRunner,
+    packages: &[String],
+) -> Result<Vec<String>> {
+    if packages.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut args = vec!["-Qlq"];
+    args.extend(packages.iter().map(String::as_str));
+    let output = runner.output("pacman", &args).await?;
+    let mut units: Vec<String> = OutputText::decode(&output)
+        .stdout
+        .lines()
+        .filter_map(|path| path.strip_prefix("/usr/lib/systemd/system/"))
+        .filter(|unit| unit.ends_with(".service") && !unit.contains('@'))
+        .filter(|unit| !NEVER_RESTART.contains(unit))
+        .map(str::to_string)
+        .collect();
+    units.sort();
+    units.dedup();
+    if units.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // One line per unit, in the order asked
+    let mut args = vec!["is-active"];
+    args.extend(units.iter().map(String::as_str));
+    let output = runner.output("systemctl", &args).await?;
+    let states = OutputText::decode(&output).stdout;
+    let active: Vec<String> = units
+        .into_iter()
+        .zip(states.lines())
+        .filter(|(_, state)| state.trim() == "active")
+        .map(|(unit, _)| unit)
+        .collect();
+    if active.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut args = vec!["try-restart"];
+    args.extend(active.iter().map(String::as_str));
+    let output = runner.output("systemctl", &args).await?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "systemctl try-restart failed: {}",
+            OutputText::decode(&output).stderr.trim()
+        );
+    }
+    Ok(active)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jarvis_core::exec::{RecordingRunner, fake_output};
+    use std::sync::Mutex;
+
+    /// Fails the steps named in `failing` and records what it ran
+    struct FakeRunner {
+        failing: Vec<&'static str>,
+        ran: Mutex<Vec<String>>,
+    }
+
+    impl FakeRunner {
+        fn failing(failing: &[&'static str]) -> Self {
+            Self {
+                failing: failing.to_vec(),
+                ran: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl StepRunner for FakeRunner {
+        async fn run_step(
+            &self,
+            task: &StepTask,
+            context: &ChainContext,
+        ) -> Result<serde_json::Value> {
+            let name = task.name();
+            self.ran.lock().unwrap().push(name.to_string());
+            if self.failing.contains(&name) {
+                anyhow::bail!("{} broke", name);
+            }
+            Ok(match task {
+                StepTask::Snapshot => serde_json::json!({ "snapshot_id": 42 }),
+                StepTask::Operation(ArchOperation::ApplyStagedUpdates) => serde_json::json!({
+                    "success": true,
+                    "delta": {
+                        "upgraded": [{ "package": "openssh", "old_version": "9.7", "new_version": "9.8", "operation": "upgrade" }],
+                        "installed": [],
+                        "removed": [],
+                        "reboot_relevant": [],
+                    },
+                }),
+                StepTask::RestartServices => {
+                    serde_json::json!({ "restarted": context.upgraded_packages.iter().map(|p| format!("{}d.service", p)).collect::<Vec<_>>() })
+                }
+                _ => serde_json::json!({ "success": true }),
+            })
+        }
+    }
+
+    fn statuses(result: &ChainResult) -> Vec<(&str, StepStatus)> {
+        result
+            .steps
+            .iter()
+            .map(|s| (s.name.as_str(), s.status))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_full_update_passes_context_between_steps() {
+        let runner = FakeRunner::failing(&[]);
+        let result = run(ChainPreset::FullUpdate, &runner).await;
+
+        assert!(result.success);
+        assert_eq!(result.steps.len(), 6);
+        assert_eq!(result.context.snapshot_id, Some(42));
+        assert_eq!(result.context.upgraded_packages, ["openssh"]);
+        assert_eq!(result.context.restarted_services, ["opensshd.service"]);
+    }
+
+    #[tokio::test]
+    async fn test_abort_skips_the_rest_of_the_chain() {
+        let runner = FakeRunner::failing(&["StageUpdates"]);
+        let result = run(ChainPreset::FullUpdate, &runner).await;
+
+        assert!(!result.success);
+        assert_eq!(result.aborted_at.as_deref(), Some("StageUpdates"));
+        assert_eq!(
+            statuses(&result),
+            [
+                ("UpdateMirrorlist", StepStatus::Succeeded),
+                ("StageUpdates", StepStatus::Failed),
+                ("Snapshot", StepStatus::Skipped),
+                ("ApplyStagedUpdates", StepStatus::Skipped),
+                ("RestartServices", StepStatus::Skipped),
+                ("SystemCleanup", StepStatus::Skipped),
+            ]
+        );
+        assert_eq!(
+            *runner.ran.lock().unwrap(),
+            ["UpdateMirrorlist", "StageUpdates"]
+        );
+        assert!(result.steps[1].error.as_deref().unwrap().contains("broke"));
+    }
+
+    #[tokio::test]
+    async fn test_continue_runs_the_rest_but_fails_the_run() {
+        let runner = FakeRunner::failing(&["VulnerabilityScan"]);
+        let result = run(ChainPreset::SecurityAudit, &runner).await;
+
+        assert!(!result.success);
+        assert!(result.aborted_at.is_none());
+        assert_eq!(runner.ran.lock().unwrap().len(), 4);
+        assert_eq!(result.failed_steps().count(), 1);
+        assert_eq!(result.steps[2].status, StepStatus::Succeeded);
+    }
+
+    #[tokio::test]
+    async fn test_notify_only_failure_keeps_the_run_successful() {
+        let runner = FakeRunner::failing(&["UpdateMirrorlist"]);
+        let result = run(ChainPreset::FullUpdate, &runner).await;
+
+        assert!(result.success);
+        assert_eq!(result.failed_steps().count(), 1);
+        assert_eq!(result.context.upgraded_packages, ["openssh"]);
+    }
+
+    #[test]
+    fn test_preset_names() {
+        for preset in ChainPreset::ALL {
+            assert_eq!(preset.name().parse::<ChainPreset>().unwrap(), preset);
+        }
+        assert_eq!(
+            "deep_clean".parse::<ChainPreset>().unwrap(),
+            ChainPreset::DeepClean
+        );
+        assert!("everything".parse::<ChainPreset>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_restart_services_only_restarts_active_units() {
+        let runner = RecordingRunner::new()
+            .respond(
+                "pacman -Qlq openssh dbus",
+                "/usr/bin/sshd\n/usr/lib/systemd/system/sshd.service\n/usr/lib/systemd/system/sshdgenkeys.service\n\
+                 /usr/lib/systemd/system/sshd@.service\n/usr/lib/systemd/system/dbus.service\n",
+            )
+            .respond("systemctl is-active", "active\ninactive\n")
+            .respond_with("systemctl try-restart", fake_output(0, "", ""));
+
+        let packages = ["openssh".to_string(), "dbus".to_string()];
+        let restarted = restart_services(&runner, &packages).await.unwrap();
+
+        assert_eq!(restarted, ["sshd.service"]);
+        assert_eq!(
+            runner.calls()[1],
+            "systemctl is-active sshd.service sshdgenkeys.service"
+        );
+        assert_eq!(runner.calls()[2], "systemctl try-restart sshd.service");
+    }
+}
@@ -0,0 +1,200 @@
+//! Health gate for scheduled maintenance
+//!
+//! Scheduled maintenance on a struggling system can make things worse: a big
+//! update on a nearly full disk is the classic case. Before each scheduled run
+//! the service asks for a blocker; a blocked task is skipped, recorded with
+//! its reason, and announced at most once a day. Emergency runs bypass the
+//! gate entirely.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use jarvis_core::exec::CommandRunner;
+use std::collections::HashMap;
+
+use crate::HealthStatus;
+use crate::config::{RequiredHealth, ScheduledTask};
+
+/// Root filesystem usage at which the system counts as Critical
+pub const DISK_CRITICAL_PERCENT: f64 = 95.0;
+/// Root filesystem usage at which the system counts as Warning
+pub const DISK_WARNING_PERCENT: f64 = 85.0;
+/// Where pacman downloads packages
+pub const PACKAGE_CACHE_DIR: &str = "/var/cache/pacman/pkg";
+/// Space that must remain after downloading, for unpacking and hooks
+const DOWNLOAD_HEADROOM_BYTES: u64 = 512 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiskUsage {
+    pub used_percent: f64,
+    pub available_bytes: u64,
+}
+
+/// Usage of the filesystem holding `path`, from `df`
+pub async fn disk_usage(runner: &dyn CommandRunner, path: &str) -> Result<DiskUsage> {
+    let output = runner
+        .output("df", &["--output=pcent,avail", "-B1", path])
+        .await?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "df {} failed: {}",
+            path,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    parse_df(&String::from_utf8_lossy(&output.stdout))
+        .with_context(|| format!("Unexpected df output for {}", path))
+}
+
+fn parse_df(output: &str) -> Option<DiskUsage> {
+    let mut fields = output.lines().nth(1)?.split_whitespace();
+    let used_percent = fields.next()?.trim_end_matches('%').parse().ok()?;
+    let available_bytes = fields.next()?.parse().ok()?;
+    Some(DiskUsage {
+        used_percent,
+        available_bytes,
+    })
+}
+
+/// The agent's own status, made worse by a filling root filesystem
+pub fn effective_health(agent: HealthStatus, root_used_percent: f64) -> HealthStatus {
+    if root_used_percent >= DISK_CRITICAL_PERCENT {
+        HealthStatus::Critical
+    } else if root_used_percent >= DISK_WARNING_PERCENT && matches!(agent, HealthStatus::Healthy) {
+        HealthStatus::Warning
+    } else {
+        agent
+    }
+}
+
+/// Whether a task requiring `required` may run in `health`. Unknown health
+/// counts as Warning.
+pub fn health_allows(required: RequiredHealth, health: &HealthStatus) -> bool {
+    match required {
+        RequiredHealth::Any => true,
+        RequiredHealth::WarningOk => !matches!(health, HealthStatus::Critical),
+        RequiredHealth::Healthy => matches!(health, HealthStatus::Healthy),
+    }
+}
+
+/// Why `task` may not run in `health`, if it may not
+pub fn health_blocker(
+    task: ScheduledTask,
+    health: &HealthStatus,
+    root_used_percent: f64,
+) -> Option<String> {
+    let required = task.required_health();
+    if health_allows(required, health) {
+        return None;
+    }
+    Some(format!(
+        "{:?} requires {:?} system health but it is {:?} (root filesystem {:.0}% full)",
+        task, required, health, root_used_percent
+    ))
+}
+
+/// Why downloading `required_bytes` into `dir` should not start, if it should not
+pub fn space_blocker(required_bytes: u64, available_bytes: u64, dir: &str) -> Option<String> {
+    let needed = required_bytes + DOWNLOAD_HEADROOM_BYTES;
+    if available_bytes >= needed {
+        return None;
+    }
+    Some(format!(
+        "Downloads need {} plus {} headroom in {}, but only {} is free ({} short)",
+        mib(required_bytes),
+        mib(DOWNLOAD_HEADROOM_BYTES),
+        dir,
+        mib(available_bytes),
+        mib(needed - available_bytes)
+    ))
+}
+
+fn mib(bytes: u64) -> String {
+    format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+/// Limits skip notifications to one per task per day
+#[derive(Debug, Default)]
+pub struct SkipNotices {
+    last_sent: HashMap<ScheduledTask, DateTime<Utc>>,
+}
+
+impl SkipNotices {
+    /// Whether a skip of `task` at `now` should be announced; records it if so
+    pub fn should_notify(&mut self, task: ScheduledTask, now: DateTime<Utc>) -> bool {
+        match self.last_sent.get(&task) {
+            Some(last) if now - *last < Duration::days(1) => false,
+            _ => {
+                self.last_sent.insert(task, now);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jarvis_core::exec::RecordingRunner;
+
+    #[tokio::test]
+    async fn test_disk_usage_from_df() {
+        let runner = RecordingRunner::new().respond("df", "Use%        Avail\n 97% 1073741824\n");
+        let usage = disk_usage(&runner, "/").await.unwrap();
+
+        assert_eq!(usage.used_percent, 97.0);
+        assert_eq!(usage.available_bytes, 1_073_741_824);
+        assert_eq!(runner.calls(), vec!["df --output=pcent,avail -B1 /"]);
+    }
+
+    #[test]
+    fn test_full_disk_blocks_updates_but_not_cleanup() {
+        let health = effective_health(HealthStatus::Healthy, 99.0);
+        assert!(matches!(health, HealthStatus::Critical));
+
+        let reason = health_blocker(ScheduledTask::Update, &health, 99.0).unwrap();
+        assert!(reason.contains("99% full"), "{}", reason);
+        assert!(health_blocker(ScheduledTask::Clean, &health, 99.0).is_none());
+        assert!(health_blocker(ScheduledTask::SecurityScan, &health, 99.0).is_none());
+    }
+
+    #[test]
+    fn test_health_levels() {
+        assert!(health_allows(
+            RequiredHealth::WarningOk,
+            &HealthStatus::Warning
+        ));
+        assert!(!health_allows(
+            RequiredHealth::WarningOk,
+            &HealthStatus::Critical
+        ));
+        assert!(!health_allows(
+            RequiredHealth::Healthy,
+            &HealthStatus::Unknown
+        ));
+        assert!(health_allows(RequiredHealth::Any, &HealthStatus::Critical));
+    }
+
+    #[test]
+    fn test_space_blocker_reports_shortfall() {
+        let gib = 1024 * 1024 * 1024;
+        assert!(space_blocker(gib, 4 * gib, PACKAGE_CACHE_DIR).is_none());
+
+        let reason = space_blocker(gib, gib, PACKAGE_CACHE_DIR).unwrap();
+        assert_eq!(
+            reason,
+            "Downloads need 1024.0 MiB plus 512.0 MiB headroom in /var/cache/pacman/pkg, \
+             but only 1024.0 MiB is free (512.0 MiB short)"
+        );
+    }
+
+    #[test]
+    fn test_skip_notices_once_per_day() {
+        let mut notices = SkipNotices::default();
+        let start = Utc::now();
+
+        assert!(notices.should_notify(ScheduledTask::Update, start));
+        assert!(!notices.should_notify(ScheduledTask::Update, start + Duration::hours(23)));
+        assert!(notices.should_notify(ScheduledTask::StageUpdates, start));
+        assert!(notices.should_notify(ScheduledTask::Update, start + Duration::hours(25)));
+    }
+}
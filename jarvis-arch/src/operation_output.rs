@@ -0,0 +1,714 @@
+//! Typed output of an `OperationResult`
+//!
+//! Each family of operations reports in its own struct: package transactions
+//! in [`PackageUpdateReport`], scans in [`ScanReport`], health and log
+//! analysis in [`HealthReport`], maintenance commands in [`CleanupReport`],
+//! systemd actions in [`ServiceActionReport`], and operations that could not
+//! run in [`FailureReport`]. Anything else, such as dry-run plans and Flatpak
+//! listings, stays [`OperationOutput::Raw`].
+//!
+//! A report serializes as its own fields plus a `kind` tag, e.g.
+//! `{"kind": "package_update", "operation": "update_packages", "success": true, ...}`,
+//! so JSON consumers that read fields by name keep working. Raw output is
+//! written as is, without a tag.
+//!
+//! Schema evolution:
+//! - Readers ignore fields they don't know, so adding a field is compatible.
+//!   New fields are `Option` or `#[serde(default)]` so older output still
+//!   reads; an absent field and `null` mean the same.
+//! - Renaming or retyping a field is not compatible. Add a variant with a
+//!   versioned kind (`package_update_v2`) instead and keep reading the old one.
+//! - Output with a kind this build doesn't know, or whose fields don't fit its
+//!   kind, reads as `Raw` rather than failing, so an older client can still
+//!   show a newer agent's results.
+//! - Output written before the tag existed is recognised by its `operation`
+//!   field, or as a failure when it only carries an `error`.
+
+use crate::aur_sandbox::{BuiltPackage, PkgbuildFinding};
+use crate::config::SandboxBackend;
+use crate::findings::ScanLifecycle;
+use crate::package_manager::{PackageChange, StagedPackage, TransactionDelta, TransactionLine};
+use crate::service_manager::ServiceOperation;
+use crate::soname_rebuild::RebuildNeeded;
+use chrono::{DateTime, Utc};
+use jarvis_core::aur_comments::PinnedComment;
+use jarvis_core::flatpak::RuntimeAdvisory;
+use jarvis_core::log_clusters::Cluster;
+use jarvis_core::pacman_lock::LockHeld;
+use jarvis_core::preflight::PreflightReport;
+use jarvis_core::report::SecurityFinding;
+use jarvis_core::unit_drift::UnitDrift;
+use jarvis_core::vuln::AcknowledgedFinding;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// What an operation produced, by family
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum OperationOutput {
+    PackageUpdate(PackageUpdateReport),
+    Scan(ScanReport),
+    Health(HealthReport),
+    Cleanup(CleanupReport),
+    ServiceAction(ServiceActionReport),
+    Failure(FailureReport),
+    /// Output no report describes yet, as the operation produced it
+    #[serde(untagged)]
+    Raw(Value),
+}
+
+impl Default for OperationOutput {
+    fn default() -> Self {
+        OperationOutput::Raw(Value::Null)
+    }
+}
+
+impl<'de> Deserialize<'de> for OperationOutput {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(OperationOutput::from_value(Value::deserialize(
+            deserializer,
+        )?))
+    }
+}
+
+impl From<Value> for OperationOutput {
+    fn from(value: Value) -> Self {
+        OperationOutput::Raw(value)
+    }
+}
+
+impl OperationOutput {
+    /// Read output in the current format or any earlier one; see the module
+    /// docs for what becomes `Raw`
+    pub fn from_value(value: Value) -> Self {
+        let kind = match value.get("kind").and_then(Value::as_str) {
+            Some(kind) => kind.to_string(),
+            None => match legacy_kind(&value) {
+                Some(kind) => kind.to_string(),
+                None => return OperationOutput::Raw(value),
+            },
+        };
+        let mut fields = value.clone();
+        if let Some(object) = fields.as_object_mut() {
+            object.remove("kind");
+        }
+        let typed = match kind.as_str() {
+            "package_update" => serde_json::from_value(fields).map(OperationOutput::PackageUpdate),
+            "scan" => serde_json::from_value(fields).map(OperationOutput::Scan),
+            "health" => serde_json::from_value(fields).map(OperationOutput::Health),
+            "cleanup" => serde_json::from_value(fields).map(OperationOutput::Cleanup),
+            "service_action" => serde_json::from_value(fields).map(OperationOutput::ServiceAction),
+            "failure" => serde_json::from_value(fields).map(OperationOutput::Failure),
+            _ => return OperationOutput::Raw(value),
+        };
+        typed.unwrap_or(OperationOutput::Raw(value))
+    }
+
+    /// Whether the report says the work itself succeeded, e.g. pacman exited
+    /// zero; scans and health reports succeed by having run
+    pub fn succeeded(&self) -> bool {
+        match self {
+            OperationOutput::PackageUpdate(report) => report.success,
+            OperationOutput::Scan(_) | OperationOutput::Health(_) => true,
+            OperationOutput::Cleanup(report) => report.success,
+            OperationOutput::ServiceAction(report) => report.success,
+            OperationOutput::Failure(_) => false,
+            OperationOutput::Raw(value) => value
+                .get("success")
+                .and_then(Value::as_bool)
+                .unwrap_or(true),
+        }
+    }
+
+    /// The error the report carries, if any
+    pub fn error(&self) -> Option<&str> {
+        match self {
+            OperationOutput::PackageUpdate(report) => report.error.as_deref(),
+            OperationOutput::Scan(_) | OperationOutput::Health(_) => None,
+            OperationOutput::Cleanup(CleanupReport { steps, .. })
+            | OperationOutput::ServiceAction(ServiceActionReport { steps, .. }) => steps
+                .last()
+                .filter(|step| !step.success && !step.error.is_empty())
+                .map(|step| step.error.as_str()),
+            OperationOutput::Failure(report) => Some(report.error.as_str()),
+            OperationOutput::Raw(value) => value.get("error").and_then(Value::as_str),
+        }
+    }
+
+    /// Whether command output with bytes that weren't UTF-8 was decoded lossily
+    pub fn output_lossy(&self) -> bool {
+        match self {
+            OperationOutput::PackageUpdate(report) => report.output_lossy == Some(true),
+            OperationOutput::Scan(_) | OperationOutput::Failure(_) => false,
+            OperationOutput::Health(report) => report.output_lossy == Some(true),
+            OperationOutput::Cleanup(CleanupReport { steps, .. })
+            | OperationOutput::ServiceAction(ServiceActionReport { steps, .. }) => {
+                steps.iter().any(|step| step.output_lossy)
+            }
+            OperationOutput::Raw(value) => json_flag_set(value, "output_lossy"),
+        }
+    }
+
+    /// The package database lock the operation failed on
+    pub fn db_lock(&self) -> Option<&LockHeld> {
+        match self {
+            OperationOutput::Failure(report) => report.db_lock.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, OperationOutput::Raw(Value::Null))
+    }
+}
+
+/// The kind of output written before reports were tagged
+fn legacy_kind(value: &Value) -> Option<&'static str> {
+    let object = value.as_object()?;
+    let Some(operation) = object.get("operation").and_then(Value::as_str) else {
+        return (object.contains_key("error") || object.contains_key("preflight"))
+            .then_some("failure");
+    };
+    match operation {
+        "update_packages"
+        | "apply_staged_updates"
+        | "stage_updates"
+        | "install_package"
+        | "remove_package" => Some("package_update"),
+        "security_scan" | "vulnerability_scan" | "aur_security_check" | "validate_configs" => {
+            Some("scan")
+        }
+        "health_check" | "performance_analysis" | "log_analysis" => Some("health"),
+        "system_cleanup" | "update_mirrorlist" => Some("cleanup"),
+        "service_operation" => Some("service_action"),
+        _ => None,
+    }
+}
+
+/// Whether `key` is true anywhere in `value`, e.g. in one step of several
+fn json_flag_set(value: &Value, key: &str) -> bool {
+    match value {
+        Value::Object(map) => {
+            map.get(key).and_then(Value::as_bool) == Some(true)
+                || map.values().any(|v| json_flag_set(v, key))
+        }
+        Value::Array(items) => items.iter().any(|v| json_flag_set(v, key)),
+        _ => false,
+    }
+}
+
+/// A pacman or AUR transaction: update_packages, apply_staged_updates,
+/// stage_updates, install_package, and remove_package
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PackageUpdateReport {
+    pub operation: String,
+    pub success: bool,
+    /// The package installed or removed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_aur: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remove_deps: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub packages_updated: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+    /// pacman's stdout
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changes: Option<Vec<PackageChange>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delta: Option<TransactionDelta>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transaction: Option<Vec<TransactionLine>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reboot_required: Option<bool>,
+    /// Targets that made it through, and on failure those that didn't
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub incomplete: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub staged_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub packages: Option<PackageList>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_download_bytes: Option<u64>,
+    /// How an AUR package was built, and where its log went
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sandbox: Option<SandboxBackend>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_log: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub findings: Option<Vec<PkgbuildFinding>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub installed: Option<bool>,
+    /// AUR packages linked against a library the transaction removed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rebuilds_needed: Option<Vec<RebuildNeeded>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_lossy: Option<bool>,
+}
+
+impl PackageUpdateReport {
+    pub fn new(operation: &str, success: bool) -> Self {
+        Self {
+            operation: operation.to_string(),
+            success,
+            ..Default::default()
+        }
+    }
+
+    /// Names of the packages the transaction upgraded, installed, or removed
+    pub fn packages_affected(&self) -> Vec<String> {
+        self.delta
+            .iter()
+            .flat_map(|delta| {
+                delta
+                    .upgraded
+                    .iter()
+                    .chain(delta.installed.iter())
+                    .chain(delta.removed.iter())
+            })
+            .map(|change| change.package.clone())
+            .collect()
+    }
+}
+
+/// `packages` of a package report: what stage_updates downloaded, or what an
+/// AUR build produced
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PackageList {
+    Staged(Vec<StagedPackage>),
+    Built(Vec<BuiltPackage>),
+}
+
+/// A security, vulnerability, AUR, or unit configuration scan
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScanReport {
+    pub operation: String,
+    /// Counts of `findings` once a security scan was compared with the last one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<FindingSummary>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub findings: Option<ScanLifecycle>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container_images: Option<Vec<ImageFindings>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flatpak_eol_runtimes: Option<Vec<RuntimeAdvisory>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub packages_checked: Option<usize>,
+    /// PKGBUILD findings per AUR package
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub results: Option<BTreeMap<String, AurPackageCheck>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub units_checked: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub units_drifted: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit_drift: Option<Vec<UnitDrift>>,
+    /// The rest of what the security scanner reported, as it reported it
+    #[serde(flatten)]
+    pub scanner: Map<String, Value>,
+}
+
+impl ScanReport {
+    pub fn new(operation: &str) -> Self {
+        Self {
+            operation: operation.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// The security scanner's output; non-objects are kept under `result`
+    pub fn from_scanner(operation: &str, scan: Value) -> Self {
+        let mut scanner = match scan {
+            Value::Object(object) => object,
+            other => Map::from_iter([("result".to_string(), other)]),
+        };
+        let operation = match scanner.remove("operation") {
+            Some(Value::String(name)) => name,
+            _ => operation.to_string(),
+        };
+        Self {
+            operation,
+            scanner,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FindingSummary {
+    pub new: usize,
+    pub still_present: usize,
+    pub resolved: usize,
+}
+
+/// The last Trivy scan of one container image
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageFindings {
+    pub image: String,
+    pub digest: String,
+    pub scanned_at: DateTime<Utc>,
+    pub findings: Vec<SecurityFinding>,
+    pub acknowledged: Vec<AcknowledgedFinding>,
+}
+
+/// One package of an AUR security check
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AurPackageCheck {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub findings: Option<Vec<PkgbuildFinding>>,
+    /// Why the PKGBUILD couldn't be checked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub pinned_comments: Vec<PinnedComment>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pinned_comments_unavailable: Option<String>,
+}
+
+/// A health check, performance analysis, or journal analysis
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HealthReport {
+    pub operation: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_minutes: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub samples: Option<u32>,
+    /// Min, average, and max of each metric series sampled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<BTreeMap<String, MetricSummary>>,
+    /// The unit whose journal was read; all units when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entries: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub by_unit: Option<BTreeMap<String, usize>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clusters: Option<Vec<Cluster>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recent: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_lossy: Option<bool>,
+    /// The rest of what the system health monitor reported, as it reported it
+    #[serde(flatten)]
+    pub monitor: Map<String, Value>,
+}
+
+impl HealthReport {
+    pub fn new(operation: &str) -> Self {
+        Self {
+            operation: operation.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// The system health monitor's output; non-objects are kept under `result`
+    pub fn from_monitor(operation: &str, health: Value) -> Self {
+        let mut monitor = match health {
+            Value::Object(object) => object,
+            other => Map::from_iter([("result".to_string(), other)]),
+        };
+        let operation = match monitor.remove("operation") {
+            Some(Value::String(name)) => name,
+            _ => operation.to_string(),
+        };
+        Self {
+            operation,
+            monitor,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MetricSummary {
+    pub min: f64,
+    pub avg: f64,
+    pub max: f64,
+}
+
+/// One command a maintenance or service action ran
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandStep {
+    pub command: String,
+    pub success: bool,
+    #[serde(default)]
+    pub output: String,
+    #[serde(default)]
+    pub error: String,
+    #[serde(default)]
+    pub output_lossy: bool,
+}
+
+/// Maintenance that runs planned commands: system_cleanup and
+/// update_mirrorlist. Stops at the first failing step.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CleanupReport {
+    pub operation: String,
+    pub success: bool,
+    pub steps: Vec<CommandStep>,
+}
+
+/// A systemd action on one unit. Stops at the first failing step.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServiceActionReport {
+    pub operation: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<ServiceOperation>,
+    pub success: bool,
+    pub steps: Vec<CommandStep>,
+}
+
+/// Why an operation did not run or did not finish
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FailureReport {
+    #[serde(default)]
+    pub error: String,
+    /// sudo or polkit refused to elevate it
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub not_permitted: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub db_lock: Option<LockHeld>,
+    /// The pre-flight report that held the transaction back
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preflight: Option<PreflightReport>,
+}
+
+impl FailureReport {
+    pub fn new(error: impl Into<String>) -> Self {
+        Self {
+            error: error.into(),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<PackageUpdateReport> for OperationOutput {
+    fn from(report: PackageUpdateReport) -> Self {
+        OperationOutput::PackageUpdate(report)
+    }
+}
+
+impl From<ScanReport> for OperationOutput {
+    fn from(report: ScanReport) -> Self {
+        OperationOutput::Scan(report)
+    }
+}
+
+impl From<HealthReport> for OperationOutput {
+    fn from(report: HealthReport) -> Self {
+        OperationOutput::Health(report)
+    }
+}
+
+impl From<CleanupReport> for OperationOutput {
+    fn from(report: CleanupReport) -> Self {
+        OperationOutput::Cleanup(report)
+    }
+}
+
+impl From<ServiceActionReport> for OperationOutput {
+    fn from(report: ServiceActionReport) -> Self {
+        OperationOutput::ServiceAction(report)
+    }
+}
+
+impl From<FailureReport> for OperationOutput {
+    fn from(report: FailureReport) -> Self {
+        OperationOutput::Failure(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Output as stored before reports were tagged, keyed by fixture name
+    fn fixture(name: &str) -> Value {
+        let text = match name {
+            "update_packages" => {
+                include_str!("../tests/fixtures/operation_output/update_packages.json")
+            }
+            "stage_updates" => {
+                include_str!("../tests/fixtures/operation_output/stage_updates.json")
+            }
+            "install_aur" => include_str!("../tests/fixtures/operation_output/install_aur.json"),
+            "security_scan" => {
+                include_str!("../tests/fixtures/operation_output/security_scan.json")
+            }
+            "aur_security_check" => {
+                include_str!("../tests/fixtures/operation_output/aur_security_check.json")
+            }
+            "log_analysis" => include_str!("../tests/fixtures/operation_output/log_analysis.json"),
+            "system_cleanup" => {
+                include_str!("../tests/fixtures/operation_output/system_cleanup.json")
+            }
+            "service_operation" => {
+                include_str!("../tests/fixtures/operation_output/service_operation.json")
+            }
+            "db_lock" => include_str!("../tests/fixtures/operation_output/db_lock.json"),
+            _ => panic!("no fixture {}", name),
+        };
+        serde_json::from_str(text).unwrap()
+    }
+
+    /// Absent and null mean the same to readers
+    fn without_nulls(value: Value) -> Value {
+        match value {
+            Value::Object(map) => Value::Object(
+                map.into_iter()
+                    .filter(|(_, v)| !v.is_null())
+                    .map(|(k, v)| (k, without_nulls(v)))
+                    .collect(),
+            ),
+            Value::Array(items) => Value::Array(items.into_iter().map(without_nulls).collect()),
+            other => other,
+        }
+    }
+
+    /// Read a stored fixture, check its kind, and check that writing it back
+    /// keeps every field and adds only the tag
+    fn round_trip(name: &str, kind: &str) -> OperationOutput {
+        let stored = fixture(name);
+        let output: OperationOutput = serde_json::from_value(stored.clone()).unwrap();
+        assert!(
+            !matches!(output, OperationOutput::Raw(_)),
+            "{} read as raw output",
+            name
+        );
+
+        let mut written = serde_json::to_value(&output).unwrap();
+        assert_eq!(written["kind"], json!(kind), "{}", name);
+        written.as_object_mut().unwrap().remove("kind");
+        assert_eq!(without_nulls(written), without_nulls(stored), "{}", name);
+
+        let tagged = serde_json::to_value(&output).unwrap();
+        let reread: OperationOutput = serde_json::from_value(tagged.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&reread).unwrap(), tagged, "{}", name);
+        output
+    }
+
+    #[test]
+    fn test_package_fixtures_round_trip() {
+        let OperationOutput::PackageUpdate(update) =
+            round_trip("update_packages", "package_update")
+        else {
+            panic!("update_packages is not a package report");
+        };
+        assert!(update.success);
+        assert_eq!(update.packages_affected(), vec!["linux", "linux-firmware"]);
+
+        let OperationOutput::PackageUpdate(staged) = round_trip("stage_updates", "package_update")
+        else {
+            panic!("stage_updates is not a package report");
+        };
+        assert!(matches!(staged.packages, Some(PackageList::Staged(ref p)) if p.len() == 2));
+
+        let OperationOutput::PackageUpdate(aur) = round_trip("install_aur", "package_update")
+        else {
+            panic!("install_aur is not a package report");
+        };
+        assert!(matches!(aur.packages, Some(PackageList::Built(ref p)) if p.len() == 1));
+        assert_eq!(aur.sandbox, Some(SandboxBackend::Nspawn));
+    }
+
+    #[test]
+    fn test_scan_and_health_fixtures_round_trip() {
+        let OperationOutput::Scan(scan) = round_trip("security_scan", "scan") else {
+            panic!("security_scan is not a scan report");
+        };
+        assert_eq!(scan.summary.unwrap().resolved, 0);
+        assert_eq!(scan.scanner["scan_type"], json!("full"));
+
+        let OperationOutput::Scan(aur) = round_trip("aur_security_check", "scan") else {
+            panic!("aur_security_check is not a scan report");
+        };
+        assert!(aur.results.unwrap()["yay-bin"].error.is_some());
+
+        let OperationOutput::Health(logs) = round_trip("log_analysis", "health") else {
+            panic!("log_analysis is not a health report");
+        };
+        assert_eq!(logs.entries, Some(3));
+    }
+
+    #[test]
+    fn test_step_and_failure_fixtures_round_trip() {
+        let cleanup = round_trip("system_cleanup", "cleanup");
+        assert!(!cleanup.succeeded());
+        assert_eq!(cleanup.error(), Some("error: could not remove cache"));
+
+        let service = round_trip("service_operation", "service_action");
+        assert!(service.succeeded());
+        assert!(service.output_lossy());
+
+        let failure = round_trip("db_lock", "failure");
+        assert!(!failure.succeeded());
+        assert!(failure.db_lock().unwrap().is_stale());
+    }
+
+    #[test]
+    fn test_unknown_fields_are_ignored() {
+        let output = OperationOutput::from_value(json!({
+            "kind": "cleanup",
+            "operation": "system_cleanup",
+            "success": true,
+            "steps": [],
+            "freed_bytes": 1024,
+        }));
+        let OperationOutput::Cleanup(report) = output else {
+            panic!("not read as a cleanup report");
+        };
+        assert!(report.success);
+        assert!(
+            serde_json::to_value(&report)
+                .unwrap()
+                .get("freed_bytes")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_unknown_or_mismatched_kind_reads_as_raw() {
+        let newer = json!({ "kind": "package_update_v2", "operation": "update_packages" });
+        assert!(matches!(
+            OperationOutput::from_value(newer.clone()),
+            OperationOutput::Raw(ref value) if *value == newer
+        ));
+
+        let mismatched =
+            json!({ "kind": "cleanup", "operation": "system_cleanup", "steps": "none" });
+        let output = OperationOutput::from_value(mismatched.clone());
+        assert!(matches!(output, OperationOutput::Raw(_)));
+        assert_eq!(serde_json::to_value(&output).unwrap(), mismatched);
+    }
+
+    #[test]
+    fn test_raw_output_is_written_untagged() {
+        let listing = json!({ "operation": "list_flatpaks", "installed": [] });
+        let output = OperationOutput::from(listing.clone());
+        assert_eq!(serde_json::to_value(&output).unwrap(), listing);
+        assert!(matches!(
+            OperationOutput::from_value(listing),
+            OperationOutput::Raw(_)
+        ));
+        assert!(OperationOutput::default().is_null());
+    }
+}
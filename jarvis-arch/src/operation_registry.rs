@@ -0,0 +1,263 @@
+//! Operations in flight and how to stop them
+//!
+//! `execute_cancellable` registers each operation under an id its caller picks,
+//! so `cancel_operation` can stop it from elsewhere, e.g. a workflow node whose
+//! time ran out. A cancelled operation's future is dropped at its next await
+//! point; commands started through `SystemRunner` are killed with it.
+//!
+//! The daemon's shutdown also works from here: it [`close`](OperationRegistry::close)s
+//! the registry, waits for what is running, and cancels with
+//! [`cancel_all`](OperationRegistry::cancel_all), which leaves package
+//! transactions alone since killing pacman mid-transaction can leave the
+//! system unbootable.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+struct Running {
+    name: &'static str,
+    cancel: Arc<Notify>,
+    transaction: bool,
+    started: Instant,
+}
+
+/// A snapshot of one running operation
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunningOperation {
+    pub id: Uuid,
+    pub name: &'static str,
+    /// A package transaction, which `cancel_all` never stops
+    pub transaction: bool,
+    pub started: Instant,
+}
+
+#[derive(Default)]
+pub struct OperationRegistry {
+    running: Mutex<HashMap<Uuid, Running>>,
+    closed: AtomicBool,
+}
+
+impl OperationRegistry {
+    /// Run `operation` under `id` until it finishes or is cancelled; `None`
+    /// when cancelled
+    pub async fn run<F: Future>(
+        &self,
+        id: Uuid,
+        name: &'static str,
+        operation: F,
+    ) -> Option<F::Output> {
+        self.register(id, name, false, operation).await
+    }
+
+    /// [`run`](Self::run) for a package transaction: `cancel` still stops it,
+    /// but `cancel_all` waits for it instead
+    pub async fn run_transaction<F: Future>(
+        &self,
+        id: Uuid,
+        name: &'static str,
+        operation: F,
+    ) -> Option<F::Output> {
+        self.register(id, name, true, operation).await
+    }
+
+    async fn register<F: Future>(
+        &self,
+        id: Uuid,
+        name: &'static str,
+        transaction: bool,
+        operation: F,
+    ) -> Option<F::Output> {
+        let cancel = Arc::new(Notify::new());
+        self.lock().insert(
+            id,
+            Running {
+                name,
+                cancel: cancel.clone(),
+                transaction,
+                started: Instant::now(),
+            },
+        );
+        // Deregisters even when the caller drops this future
+        let _registered = Registered { registry: self, id };
+
+        tokio::select! {
+            output = operation => Some(output),
+            _ = cancel.notified() => None,
+        }
+    }
+
+    /// Stop the operation running under `id`; false when there is none
+    pub fn cancel(&self, id: Uuid) -> bool {
+        match self.lock().get(&id) {
+            Some(running) => {
+                // Stores a permit, so a cancel that races registration still lands
+                running.cancel.notify_one();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Cancel everything running except package transactions; returns the
+    /// names of the operations cancelled
+    pub fn cancel_all(&self) -> Vec<String> {
+        let mut cancelled: Vec<String> = self
+            .lock()
+            .values()
+            .filter(|running| !running.transaction)
+            .map(|running| {
+                running.cancel.notify_one();
+                running.name.to_string()
+            })
+            .collect();
+        cancelled.sort();
+        cancelled
+    }
+
+    /// Stop accepting operations; callers check [`is_closed`](Self::is_closed)
+    /// before starting one. Running operations carry on.
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    /// Running operations, oldest first
+    pub fn running(&self) -> Vec<RunningOperation> {
+        let mut running: Vec<RunningOperation> = self
+            .lock()
+            .iter()
+            .map(|(id, running)| RunningOperation {
+                id: *id,
+                name: running.name,
+                transaction: running.transaction,
+                started: running.started,
+            })
+            .collect();
+        running.sort_by_key(|operation| operation.started);
+        running
+    }
+
+    /// Names of the running operations, for status reports
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.lock().values().map(|r| r.name.to_string()).collect();
+        names.sort();
+        names
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<Uuid, Running>> {
+        self.running
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+struct Registered<'a> {
+    registry: &'a OperationRegistry,
+    id: Uuid,
+}
+
+impl Drop for Registered<'_> {
+    fn drop(&mut self) {
+        self.registry.lock().remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_cancel_stops_a_running_operation() {
+        let registry = Arc::new(OperationRegistry::default());
+        let id = Uuid::new_v4();
+
+        let running = {
+            let registry = registry.clone();
+            tokio::spawn(async move {
+                registry
+                    .run(
+                        id,
+                        "SecurityScan",
+                        tokio::time::sleep(Duration::from_secs(30)),
+                    )
+                    .await
+            })
+        };
+        while registry.is_empty() {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(registry.names(), ["SecurityScan"]);
+
+        assert!(registry.cancel(id));
+        assert_eq!(running.await.unwrap(), None);
+        assert!(registry.is_empty());
+        assert!(!registry.cancel(id));
+    }
+
+    #[tokio::test]
+    async fn test_finished_and_dropped_operations_deregister() {
+        let registry = OperationRegistry::default();
+        assert_eq!(
+            registry
+                .run(Uuid::new_v4(), "HealthCheck", async { 7 })
+                .await,
+            Some(7)
+        );
+        assert!(registry.is_empty());
+
+        let dropped = tokio::time::timeout(
+            Duration::from_millis(10),
+            registry.run(Uuid::new_v4(), "HealthCheck", std::future::pending::<()>()),
+        )
+        .await;
+        assert!(dropped.is_err());
+        assert!(registry.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_all_leaves_transactions_running() {
+        let registry = Arc::new(OperationRegistry::default());
+        let spawn = |transaction: bool, name: &'static str| {
+            let registry = registry.clone();
+            tokio::spawn(async move {
+                let operation = tokio::time::sleep(Duration::from_millis(50));
+                if transaction {
+                    registry.run_transaction(Uuid::new_v4(), name, operation).await
+                } else {
+                    registry.run(Uuid::new_v4(), name, operation).await
+                }
+            })
+        };
+        let scan = spawn(false, "SecurityScan");
+        let update = spawn(true, "UpdatePackages");
+        while registry.len() < 2 {
+            tokio::task::yield_now().await;
+        }
+
+        registry.close();
+        assert!(registry.is_closed());
+        assert_eq!(registry.cancel_all(), ["SecurityScan"]);
+        assert_eq!(scan.await.unwrap(), None);
+        let running = registry.running();
+        assert_eq!(running.len(), 1);
+        assert!(running[0].transaction);
+        assert_eq!(update.await.unwrap(), Some(()));
+    }
+}
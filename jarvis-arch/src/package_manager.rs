@@ -1,11 +1,14 @@
 use anyhow::{Result, Context};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::process::Stdio;
-use tokio::process::Command;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
 use chrono::{DateTime, Utc};
 use regex::Regex;
+use jarvis_core::exec::{CommandRunner, OutputText, SystemRunner};
+use jarvis_core::package_cache::{InstalledPackage, PackageCache};
+use jarvis_core::pacman_lock::{self, LockHeld};
 use crate::arch_config::PacmanConfig;
+use crate::operation_output::PackageUpdateReport;
 
 /// Package manager for Arch Linux operations
 #[derive(Debug, Clone)]
@@ -13,14 +16,9 @@ pub struct PackageManager {
     config: Option<PacmanConfig>,
     pacman_path: String,
     yay_path: Option<String>,
-    cache: PackageCache,
-}
-
-#[derive(Debug, Clone)]
-struct PackageCache {
-    packages: HashMap<String, PackageInfo>,
-    last_update: DateTime<Utc>,
-    cache_duration_hours: u64,
+    cache: Arc<PackageCache>,
+    runner: Arc<dyn CommandRunner>,
+    db_lock_path: std::path::PathBuf,
 }
 
 /// Information about a package
@@ -43,6 +41,28 @@ pub struct PackageInfo {
     pub signature: Option<String>,
 }
 
+impl From<&InstalledPackage> for PackageInfo {
+    fn from(package: &InstalledPackage) -> Self {
+        Self {
+            name: package.name.clone(),
+            version: package.version.clone(),
+            description: package.description.clone().unwrap_or_default(),
+            repository: if package.foreign { "foreign" } else { "local" }.to_string(),
+            installed_size: package.installed_size,
+            install_date: package.install_date,
+            dependencies: Vec::new(),
+            required_by: Vec::new(),
+            groups: Vec::new(),
+            url: package.url.clone(),
+            license: Vec::new(),
+            maintainer: package.packager.clone(),
+            build_date: package.build_date,
+            checksum: None,
+            signature: None,
+        }
+    }
+}
+
 /// Package operation types
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum PackageOperation {
@@ -88,17 +108,182 @@ pub struct PackageChange {
     pub operation: String,
 }
 
+/// Installed packages at a point in time (name -> version)
+pub type PackageSnapshot = BTreeMap<String, String>;
+
+/// Difference between two package snapshots around a transaction
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TransactionDelta {
+    pub upgraded: Vec<PackageChange>,
+    /// Packages that did not exist before, typically pulled in as dependencies
+    pub installed: Vec<PackageChange>,
+    pub removed: Vec<PackageChange>,
+    /// Kernel, driver, microcode, and core library packages that changed
+    pub reboot_relevant: Vec<String>,
+}
+
+/// One per-package line of pacman's transaction output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionLine {
+    pub step: u32,
+    pub total: u32,
+    pub action: String,
+    pub package: String,
+}
+
+/// Updates downloaded into the package cache but not yet installed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StagedUpdate {
+    pub staged_at: DateTime<Utc>,
+    /// Hash of the sync databases at staging time
+    pub sync_db_fingerprint: String,
+    pub packages: Vec<StagedPackage>,
+    pub total_download_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StagedPackage {
+    pub name: String,
+    pub version: String,
+    pub download_bytes: u64,
+}
+
+/// Packages whose update generally requires a reboot to take effect
+pub fn is_reboot_relevant(package: &str) -> bool {
+    matches!(
+        package,
+        "linux" | "linux-lts" | "linux-zen" | "linux-hardened" | "linux-rt"
+            | "glibc" | "systemd" | "amd-ucode" | "intel-ucode"
+    ) || package.starts_with("nvidia")
+}
+
+/// Compute upgraded/installed/removed packages between two snapshots
+pub fn compute_transaction_delta(before: &PackageSnapshot, after: &PackageSnapshot) -> TransactionDelta {
+    let mut delta = TransactionDelta::default();
+
+    for (name, new_version) in after {
+        match before.get(name) {
+            Some(old_version) if old_version != new_version => {
+                delta.upgraded.push(PackageChange {
+                    package: name.clone(),
+                    old_version: Some(old_version.clone()),
+                    new_version: Some(new_version.clone()),
+                    operation: "upgraded".to_string(),
+                });
+            }
+            Some(_) => {}
+            None => {
+                delta.installed.push(PackageChange {
+                    package: name.clone(),
+                    old_version: None,
+                    new_version: Some(new_version.clone()),
+                    operation: "installed".to_string(),
+                });
+            }
+        }
+    }
+
+    for (name, old_version) in before {
+        if !after.contains_key(name) {
+            delta.removed.push(PackageChange {
+                package: name.clone(),
+                old_version: Some(old_version.clone()),
+                new_version: None,
+                operation: "removed".to_string(),
+            });
+        }
+    }
+
+    delta.reboot_relevant = delta
+        .upgraded
+        .iter()
+        .chain(delta.installed.iter())
+        .chain(delta.removed.iter())
+        .map(|c| c.package.clone())
+        .filter(|name| is_reboot_relevant(name))
+        .collect();
+
+    delta
+}
+
+/// Parse pacman's "( 3/12) upgrading foo" progress lines
+pub fn parse_transaction_lines(output: &str) -> Vec<TransactionLine> {
+    let re = match Regex::new(
+        r"^\(\s*(\d+)/(\d+)\)\s+(installing|upgrading|reinstalling|downgrading|removing)\s+(\S+)",
+    ) {
+        Ok(re) => re,
+        Err(_) => return Vec::new(),
+    };
+
+    output
+        .lines()
+        .filter_map(|line| {
+            let captures = re.captures(line.trim())?;
+            Some(TransactionLine {
+                step: captures.get(1)?.as_str().parse().ok()?,
+                total: captures.get(2)?.as_str().parse().ok()?,
+                action: captures.get(3)?.as_str().to_string(),
+                package: captures.get(4)?.as_str().trim_end_matches("...").to_string(),
+            })
+        })
+        .collect()
+}
+
 impl PackageManager {
     pub fn new() -> Self {
         Self {
             config: None,
             pacman_path: "/usr/bin/pacman".to_string(),
             yay_path: None,
-            cache: PackageCache {
-                packages: HashMap::new(),
-                last_update: DateTime::UNIX_EPOCH,
-                cache_duration_hours: 1,
-            },
+            cache: PackageCache::shared(),
+            runner: Arc::new(SystemRunner::default()),
+            db_lock_path: pacman_lock::DB_LOCK_PATH.into(),
+        }
+    }
+
+    /// Answer installed-package lookups from `cache` instead of the shared one
+    pub fn with_package_cache(mut self, cache: Arc<PackageCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Spawn pacman and yay through `runner` instead of the local system
+    pub fn with_runner(mut self, runner: Arc<dyn CommandRunner>) -> Self {
+        self.runner = runner;
+        self
+    }
+
+    /// Check for the database lock at `path` instead of pacman's
+    pub fn with_db_lock_path(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.db_lock_path = path.into();
+        self
+    }
+
+    /// The database lock, if one is in place, and who may hold it
+    pub async fn db_lock(&self) -> Result<Option<LockHeld>> {
+        pacman_lock::check(self.runner.as_ref(), &self.db_lock_path).await
+    }
+
+    /// Fail with [`LockHeld`] before a transaction that pacman would refuse
+    /// anyway, so the caller learns why instead of reading pacman's stderr
+    async fn ensure_unlocked(&self) -> Result<()> {
+        match self.db_lock().await? {
+            Some(lock) => Err(lock.into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Remove the database lock if no package manager is running; `false`
+    /// when there was no lock
+    pub async fn remove_stale_db_lock(&self) -> Result<bool> {
+        pacman_lock::remove_stale(self.runner.as_ref(), &self.db_lock_path).await
+    }
+
+    /// Options from `[pacman]` that apply to every transaction
+    fn transaction_flags(&self) -> Vec<&'static str> {
+        match &self.config {
+            Some(config) if config.no_confirm => vec!["--noconfirm"],
+            _ => Vec::new(),
         }
     }
 
@@ -111,7 +296,7 @@ impl PackageManager {
         }
 
         // Check for AUR helper (yay)
-        if let Ok(output) = Command::new("which").arg("yay").output().await {
+        if let Ok(output) = self.runner.output("which", &["yay"]).await {
             if output.status.success() {
                 let yay_path = String::from_utf8_lossy(&output.stdout).trim().to_string();
                 self.yay_path = Some(yay_path);
@@ -119,145 +304,301 @@ impl PackageManager {
             }
         }
 
-        // Initialize package database
-        self.update_package_cache().await?;
+        // Load the local package database once up front
+        let installed = self.cache.index().await?;
+        tracing::info!("Package cache loaded with {} packages", installed.len());
         
         tracing::info!("Package manager initialized successfully");
         Ok(())
     }
 
-    /// Update system packages
-    pub async fn update_packages(&self, packages: Option<Vec<String>>) -> Result<serde_json::Value> {
+    /// Update system packages.
+    ///
+    /// Captures the installed package set before and after the pacman
+    /// transaction so the result contains an authoritative delta rather than
+    /// whatever could be scraped from pacman's output.
+    pub async fn update_packages(&self, packages: Option<Vec<String>>) -> Result<PackageUpdateReport> {
+        self.ensure_unlocked().await?;
         let start_time = std::time::Instant::now();
-        
-        let mut cmd = Command::new(&self.pacman_path);
-        cmd.arg("-S");
-        
-        if let Some(config) = &self.config {
-            if config.no_confirm {
-                cmd.arg("--noconfirm");
-            }
-            if config.verbose {
-                cmd.arg("-v");
-            }
+
+        let before = self.snapshot_installed().await?;
+        let targets: Vec<String> = match &packages {
+            Some(pkg_list) => pkg_list.clone(),
+            None => self
+                .check_updates()
+                .await
+                .unwrap_or_default()
+                .into_iter()
+                .map(|p| p.name)
+                .collect(),
+        };
+
+        let mut args = vec!["-S"];
+        args.extend(self.transaction_flags());
+        if self.config.as_ref().is_some_and(|config| config.verbose) {
+            args.push("-v");
         }
 
-        match packages {
+        match &packages {
             Some(pkg_list) => {
-                cmd.args(&pkg_list);
+                args.extend(pkg_list.iter().map(String::as_str));
                 tracing::info!("Updating specific packages: {:?}", pkg_list);
             }
             None => {
-                cmd.arg("-u");
+                args.push("-u");
                 tracing::info!("Updating all packages");
             }
         }
 
-        let output = cmd
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()
+        let output = self
+            .runner
+            .output(&self.pacman_path, &args)
             .await
             .context("Failed to execute pacman update")?;
+        self.cache.invalidate().await;
 
         let duration = start_time.elapsed().as_millis() as u64;
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let stderr = String::from_utf8_lossy(&output.stderr);
+        let OutputText { stdout, stderr, lossy } = OutputText::decode(&output);
 
+        let after = self.snapshot_installed().await?;
+        let delta = compute_transaction_delta(&before, &after);
+        let transaction = parse_transaction_lines(&stdout);
         let changes = self.parse_pacman_output(&stdout).await?;
 
-        Ok(serde_json::json!({
-            "operation": "update_packages",
-            "success": output.status.success(),
-            "packages_updated": changes.len(),
-            "duration_ms": duration,
-            "output": stdout.to_string(),
-            "error": if stderr.is_empty() { None } else { Some(stderr.to_string()) },
-            "changes": changes
-        }))
+        // On failure, report which targets made it through before pacman stopped
+        let changed: std::collections::HashSet<&str> = delta
+            .upgraded
+            .iter()
+            .chain(delta.installed.iter())
+            .map(|c| c.package.as_str())
+            .collect();
+        let (completed, incomplete): (Vec<String>, Vec<String>) = targets
+            .iter()
+            .cloned()
+            .partition(|name| changed.contains(name.as_str()));
+
+        if !output.status.success() {
+            tracing::warn!(
+                "Package update failed after {} of {} packages",
+                completed.len(),
+                targets.len()
+            );
+        }
+        if !delta.reboot_relevant.is_empty() {
+            tracing::info!("Reboot-relevant packages updated: {:?}", delta.reboot_relevant);
+        }
+
+        Ok(PackageUpdateReport {
+            packages_updated: Some(delta.upgraded.len()),
+            duration_ms: Some(duration),
+            output: Some(stdout.to_string()),
+            error: if stderr.is_empty() { None } else { Some(stderr.to_string()) },
+            changes: Some(changes),
+            transaction: Some(transaction),
+            reboot_required: Some(!delta.reboot_relevant.is_empty()),
+            delta: Some(delta),
+            completed: Some(completed),
+            incomplete: Some(if output.status.success() { Vec::new() } else { incomplete }),
+            output_lossy: Some(lossy),
+            ..PackageUpdateReport::new("update_packages", output.status.success())
+        })
     }
 
-    /// Install a package
-    pub async fn install_package(&self, package: &str, from_aur: bool) -> Result<serde_json::Value> {
-        let start_time = std::time::Instant::now();
-        
-        let mut cmd = if from_aur && self.yay_path.is_some() {
-            Command::new(self.yay_path.as_ref().unwrap())
-        } else {
-            Command::new(&self.pacman_path)
-        };
+    /// Bytes `pacman -Su` would download with the current sync databases
+    pub async fn pending_download_bytes(&self) -> Result<u64> {
+        let output = self
+            .runner
+            .output(&self.pacman_path, &["-Sup", "--print-format", "%s"])
+            .await
+            .context("Failed to list pending downloads")?;
 
-        cmd.arg("-S").arg(package);
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "Failed to list pending downloads: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| line.trim().parse::<u64>().ok())
+            .sum())
+    }
+
+    /// Download pending updates into the package cache without installing them
+    pub async fn stage_updates(&self) -> Result<StagedUpdate> {
+        tracing::info!("Staging updates (download only)");
+        self.ensure_unlocked().await?;
+
+        let output = self
+            .runner
+            .output(&self.pacman_path, &["-Syuw", "--noconfirm"])
+            .await
+            .context("Failed to execute pacman -Syuw")?;
+
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "Failed to download updates: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        // List what -Su will install, now that the sync databases are fresh
+        let targets = self
+            .runner
+            .output(&self.pacman_path, &["-Sup", "--print-format", "%n %v %s"])
+            .await
+            .context("Failed to list staged packages")?;
+
+        let packages: Vec<StagedPackage> = String::from_utf8_lossy(&targets.stdout)
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.split_whitespace();
+                Some(StagedPackage {
+                    name: parts.next()?.to_string(),
+                    version: parts.next()?.to_string(),
+                    download_bytes: parts.next()?.parse().unwrap_or(0),
+                })
+            })
+            .collect();
+        let total_download_bytes = packages.iter().map(|p| p.download_bytes).sum();
+
+        Ok(StagedUpdate {
+            staged_at: Utc::now(),
+            sync_db_fingerprint: self.sync_db_fingerprint().await?,
+            packages,
+            total_download_bytes,
+        })
+    }
 
-        if let Some(config) = &self.config {
-            if config.no_confirm {
-                cmd.arg("--noconfirm");
+    /// Install previously staged updates from the package cache.
+    ///
+    /// Refuses if the sync databases changed since staging, since `-Su` would
+    /// then install a different set than was downloaded.
+    pub async fn apply_staged_updates(&self, staged: &StagedUpdate) -> Result<PackageUpdateReport> {
+        let current = self.sync_db_fingerprint().await?;
+        if current != staged.sync_db_fingerprint {
+            return Err(anyhow::anyhow!(
+                "Sync database changed since updates were staged at {}; re-stage before applying",
+                staged.staged_at.to_rfc3339()
+            ));
+        }
+
+        tracing::info!("Applying {} staged updates", staged.packages.len());
+        let mut result = self.update_packages(None).await?;
+        result.operation = "apply_staged_updates".to_string();
+        result.staged_at = Some(staged.staged_at);
+        Ok(result)
+    }
+
+    /// SHA-256 over the sync database files, used to detect a moved-on sync state
+    pub async fn sync_db_fingerprint(&self) -> Result<String> {
+        use sha2::{Digest, Sha256};
+
+        let mut paths: Vec<std::path::PathBuf> = glob::glob("/var/lib/pacman/sync/*.db")
+            .context("Invalid sync database pattern")?
+            .filter_map(|entry| entry.ok())
+            .collect();
+        paths.sort();
+
+        let mut hasher = Sha256::new();
+        for path in paths {
+            let contents = tokio::fs::read(&path)
+                .await
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            hasher.update(path.to_string_lossy().as_bytes());
+            hasher.update(&contents);
+        }
+
+        Ok(hex::encode(hasher.finalize()))
+    }
+
+    /// Read the installed package set directly from pacman, bypassing the cache
+    pub async fn snapshot_installed(&self) -> Result<PackageSnapshot> {
+        let output = self
+            .runner
+            .output(&self.pacman_path, &["-Q"])
+            .await
+            .context("Failed to snapshot installed packages")?;
+
+        let mut snapshot = BTreeMap::new();
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let mut parts = line.split_whitespace();
+            if let (Some(name), Some(version)) = (parts.next(), parts.next()) {
+                snapshot.insert(name.to_string(), version.to_string());
             }
         }
 
+        Ok(snapshot)
+    }
+
+    /// Install a package
+    pub async fn install_package(&self, package: &str, from_aur: bool) -> Result<PackageUpdateReport> {
+        self.ensure_unlocked().await?;
+        let start_time = std::time::Instant::now();
+        
+        let program = match &self.yay_path {
+            Some(yay_path) if from_aur => yay_path,
+            _ => &self.pacman_path,
+        };
+        let mut args = vec!["-S", package];
+        args.extend(self.transaction_flags());
+
         tracing::info!("Installing package: {} (AUR: {})", package, from_aur);
 
-        let output = cmd
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()
+        let output = self
+            .runner
+            .output(program, &args)
             .await
             .context("Failed to execute package install")?;
+        self.cache.invalidate().await;
 
         let duration = start_time.elapsed().as_millis() as u64;
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let stderr = String::from_utf8_lossy(&output.stderr);
-
-        Ok(serde_json::json!({
-            "operation": "install_package",
-            "package": package,
-            "from_aur": from_aur,
-            "success": output.status.success(),
-            "duration_ms": duration,
-            "output": stdout.to_string(),
-            "error": if stderr.is_empty() { None } else { Some(stderr.to_string()) }
-        }))
+        let OutputText { stdout, stderr, lossy } = OutputText::decode(&output);
+
+        Ok(PackageUpdateReport {
+            package: Some(package.to_string()),
+            from_aur: Some(from_aur),
+            duration_ms: Some(duration),
+            output: Some(stdout),
+            error: if stderr.is_empty() { None } else { Some(stderr) },
+            output_lossy: Some(lossy),
+            ..PackageUpdateReport::new("install_package", output.status.success())
+        })
     }
 
     /// Remove a package
-    pub async fn remove_package(&self, package: &str, remove_deps: bool) -> Result<serde_json::Value> {
+    pub async fn remove_package(&self, package: &str, remove_deps: bool) -> Result<PackageUpdateReport> {
+        self.ensure_unlocked().await?;
         let start_time = std::time::Instant::now();
         
-        let mut cmd = Command::new(&self.pacman_path);
-        cmd.arg("-R").arg(package);
-
+        let mut args = vec!["-R", package];
         if remove_deps {
-            cmd.arg("-s"); // Remove dependencies
-        }
-
-        if let Some(config) = &self.config {
-            if config.no_confirm {
-                cmd.arg("--noconfirm");
-            }
+            args.push("-s"); // Remove dependencies
         }
+        args.extend(self.transaction_flags());
 
         tracing::info!("Removing package: {} (remove deps: {})", package, remove_deps);
 
-        let output = cmd
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()
+        let output = self
+            .runner
+            .output(&self.pacman_path, &args)
             .await
             .context("Failed to execute package removal")?;
+        self.cache.invalidate().await;
 
         let duration = start_time.elapsed().as_millis() as u64;
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let stderr = String::from_utf8_lossy(&output.stderr);
-
-        Ok(serde_json::json!({
-            "operation": "remove_package",
-            "package": package,
-            "remove_deps": remove_deps,
-            "success": output.status.success(),
-            "duration_ms": duration,
-            "output": stdout.to_string(),
-            "error": if stderr.is_empty() { None } else { Some(stderr.to_string()) }
-        }))
+        let OutputText { stdout, stderr, lossy } = OutputText::decode(&output);
+
+        Ok(PackageUpdateReport {
+            package: Some(package.to_string()),
+            remove_deps: Some(remove_deps),
+            duration_ms: Some(duration),
+            output: Some(stdout),
+            error: if stderr.is_empty() { None } else { Some(stderr) },
+            output_lossy: Some(lossy),
+            ..PackageUpdateReport::new("remove_package", output.status.success())
+        })
     }
 
     /// Search for packages
@@ -265,24 +606,17 @@ impl PackageManager {
         let start_time = std::time::Instant::now();
         
         // Search official repositories
-        let mut cmd = Command::new(&self.pacman_path);
-        cmd.arg("-Ss").arg(query);
-
-        let output = cmd
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()
+        let output = self
+            .runner
+            .output(&self.pacman_path, &["-Ss", query])
             .await
             .context("Failed to search packages")?;
 
         let mut results = self.parse_search_output(&String::from_utf8_lossy(&output.stdout)).await?;
 
         // Search AUR if requested and available
-        if include_aur && self.yay_path.is_some() {
-            let mut aur_cmd = Command::new(self.yay_path.as_ref().unwrap());
-            aur_cmd.arg("-Ss").arg(query);
-
-            if let Ok(aur_output) = aur_cmd.output().await {
+        if let Some(yay_path) = self.yay_path.as_ref().filter(|_| include_aur) {
+            if let Ok(aur_output) = self.runner.output(yay_path, &["-Ss", query]).await {
                 let aur_results = self.parse_search_output(&String::from_utf8_lossy(&aur_output.stdout)).await?;
                 results.extend(aur_results);
             }
@@ -302,21 +636,7 @@ impl PackageManager {
 
     /// Get information about a package
     pub async fn get_package_info(&self, package: &str) -> Result<Option<PackageInfo>> {
-        // Check cache first
-        if let Some(cached) = self.cache.packages.get(package) {
-            if self.is_cache_valid() {
-                return Ok(Some(cached.clone()));
-            }
-        }
-
-        let mut cmd = Command::new(&self.pacman_path);
-        cmd.arg("-Qi").arg(package);
-
-        let output = cmd
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()
-            .await?;
+        let output = self.runner.output(&self.pacman_path, &["-Qi", package]).await?;
 
         if !output.status.success() {
             return Ok(None);
@@ -328,38 +648,23 @@ impl PackageManager {
         Ok(package_info)
     }
 
-    /// List installed packages
+    /// List installed packages, by name, from the package cache
     pub async fn list_installed_packages(&self) -> Result<Vec<PackageInfo>> {
-        if self.is_cache_valid() {
-            return Ok(self.cache.packages.values().cloned().collect());
-        }
-
-        let mut cmd = Command::new(&self.pacman_path);
-        cmd.arg("-Q");
-
-        let output = cmd
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()
-            .await
-            .context("Failed to list installed packages")?;
-
-        let package_list = String::from_utf8_lossy(&output.stdout);
-        let packages = self.parse_package_list(&package_list).await?;
-
+        let installed = self.cache.index().await?;
+        let mut packages: Vec<PackageInfo> = installed.iter().map(PackageInfo::from).collect();
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
         Ok(packages)
     }
 
+    /// Installed packages that are in no repository, from the package cache
+    pub async fn list_foreign_packages(&self) -> Result<Vec<PackageInfo>> {
+        let installed = self.cache.index().await?;
+        Ok(installed.foreign().into_iter().map(PackageInfo::from).collect())
+    }
+
     /// Check for available updates
     pub async fn check_updates(&self) -> Result<Vec<PackageInfo>> {
-        let mut cmd = Command::new(&self.pacman_path);
-        cmd.arg("-Qu");
-
-        let output = cmd
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()
-            .await?;
+        let output = self.runner.output(&self.pacman_path, &["-Qu"]).await?;
 
         if !output.status.success() {
             return Ok(vec![]); // No updates available
@@ -373,39 +678,32 @@ impl PackageManager {
 
     /// Clean package cache
     pub async fn clean_cache(&self, aggressive: bool) -> Result<serde_json::Value> {
+        self.ensure_unlocked().await?;
         let start_time = std::time::Instant::now();
         
-        let mut cmd = Command::new(&self.pacman_path);
-        cmd.arg("-Sc");
-
+        let mut args = vec!["-Sc"];
         if aggressive {
-            cmd.arg("-c"); // Clean all cached packages
-        }
-
-        if let Some(config) = &self.config {
-            if config.no_confirm {
-                cmd.arg("--noconfirm");
-            }
+            args.push("-c"); // Clean all cached packages
         }
+        args.extend(self.transaction_flags());
 
-        let output = cmd
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()
+        let output = self
+            .runner
+            .output(&self.pacman_path, &args)
             .await
             .context("Failed to clean package cache")?;
 
         let duration = start_time.elapsed().as_millis() as u64;
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let stderr = String::from_utf8_lossy(&output.stderr);
+        let OutputText { stdout, stderr, lossy } = OutputText::decode(&output);
 
         Ok(serde_json::json!({
             "operation": "clean_cache",
             "aggressive": aggressive,
             "success": output.status.success(),
             "duration_ms": duration,
-            "output": stdout.to_string(),
-            "error": if stderr.is_empty() { None } else { Some(stderr.to_string()) }
+            "output": stdout,
+            "error": if stderr.is_empty() { None } else { Some(stderr) },
+            "output_lossy": lossy,
         }))
     }
 
@@ -413,23 +711,19 @@ impl PackageManager {
     pub async fn verify_packages(&self, packages: Option<Vec<String>>) -> Result<serde_json::Value> {
         let start_time = std::time::Instant::now();
         
-        let mut cmd = Command::new(&self.pacman_path);
-        cmd.arg("-Qk");
-
-        if let Some(pkg_list) = packages {
-            cmd.args(&pkg_list);
+        let mut args = vec!["-Qk"];
+        if let Some(pkg_list) = &packages {
+            args.extend(pkg_list.iter().map(String::as_str));
         }
 
-        let output = cmd
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()
+        let output = self
+            .runner
+            .output(&self.pacman_path, &args)
             .await
             .context("Failed to verify packages")?;
 
         let duration = start_time.elapsed().as_millis() as u64;
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let stderr = String::from_utf8_lossy(&output.stderr);
+        let OutputText { stdout, stderr, lossy } = OutputText::decode(&output);
 
         let verification_results = self.parse_verification_output(&stdout).await?;
 
@@ -438,35 +732,13 @@ impl PackageManager {
             "success": output.status.success(),
             "duration_ms": duration,
             "results": verification_results,
-            "error": if stderr.is_empty() { None } else { Some(stderr.to_string()) }
+            "error": if stderr.is_empty() { None } else { Some(stderr) },
+            "output_lossy": lossy,
         }))
     }
 
     // Private helper methods
 
-    async fn update_package_cache(&mut self) -> Result<()> {
-        tracing::info!("Updating package cache...");
-        
-        let packages = self.list_installed_packages().await?;
-        
-        self.cache.packages.clear();
-        for package in packages {
-            self.cache.packages.insert(package.name.clone(), package);
-        }
-        
-        self.cache.last_update = Utc::now();
-        tracing::info!("Package cache updated with {} packages", self.cache.packages.len());
-        
-        Ok(())
-    }
-
-    fn is_cache_valid(&self) -> bool {
-        let age = Utc::now()
-            .signed_duration_since(self.cache.last_update)
-            .num_hours();
-        age < self.cache.cache_duration_hours as i64
-    }
-
     async fn parse_pacman_output(&self, output: &str) -> Result<Vec<PackageChange>> {
         let mut changes = Vec::new();
         
@@ -571,12 +843,11 @@ impl PackageManager {
                 repository: package_info.get("repository").cloned().unwrap_or_default(),
                 installed_size: package_info
                     .get("installed_size")
-                    .and_then(|s| s.parse().ok())
+                    .and_then(|s| jarvis_core::preflight::parse_size(s))
                     .unwrap_or(0),
                 install_date: package_info
                     .get("install_date")
-                    .and_then(|s| chrono::DateTime::parse_from_str(s, "%a %d %b %Y %I:%M:%S %p %Z").ok())
-                    .map(|dt| dt.with_timezone(&chrono::Utc)),
+                    .and_then(|s| parse_pacman_date(s)),
                 dependencies: package_info
                     .get("depends_on")
                     .map(|s| s.split_whitespace().map(String::from).collect())
@@ -597,8 +868,7 @@ impl PackageManager {
                 maintainer: package_info.get("packager").cloned(),
                 build_date: package_info
                     .get("build_date")
-                    .and_then(|s| chrono::DateTime::parse_from_str(s, "%a %d %b %Y %I:%M:%S %p %Z").ok())
-                    .map(|dt| dt.with_timezone(&chrono::Utc)),
+                    .and_then(|s| parse_pacman_date(s)),
                 checksum: package_info.get("md5_sum").cloned(),
                 signature: package_info.get("validated_by").cloned(),
             }))
@@ -607,35 +877,6 @@ impl PackageManager {
         }
     }
 
-    async fn parse_package_list(&self, package_list: &str) -> Result<Vec<PackageInfo>> {
-        let mut packages = Vec::new();
-        
-        for line in package_list.lines() {
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 2 {
-                packages.push(PackageInfo {
-                    name: parts[0].to_string(),
-                    version: parts[1].to_string(),
-                    description: String::new(),
-                    repository: "local".to_string(),
-                    installed_size: 0,
-                    install_date: None,
-                    dependencies: Vec::new(),
-                    required_by: Vec::new(),
-                    groups: Vec::new(),
-                    url: None,
-                    license: Vec::new(),
-                    maintainer: None,
-                    build_date: None,
-                    checksum: None,
-                    signature: None,
-                });
-            }
-        }
-        
-        Ok(packages)
-    }
-
     async fn parse_update_list(&self, update_list: &str) -> Result<Vec<PackageInfo>> {
         let mut updates = Vec::new();
         
@@ -681,4 +922,152 @@ impl PackageManager {
         
         Ok(results)
     }
-}
\ No newline at end of file
+}
+/// A date from `pacman -Qi`. Commands run in the C locale, where pacman
+/// prints local time as `Tue Oct  1 10:00:00 2024`; the en_US form with a
+/// zone is accepted too.
+pub fn parse_pacman_date(value: &str) -> Option<DateTime<Utc>> {
+    use chrono::TimeZone;
+
+    if let Ok(date) = DateTime::parse_from_str(value, "%a %d %b %Y %I:%M:%S %p %Z") {
+        return Some(date.with_timezone(&Utc));
+    }
+    let naive = chrono::NaiveDateTime::parse_from_str(value, "%a %b %e %H:%M:%S %Y").ok()?;
+    chrono::Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jarvis_core::exec::{fake_output, RecordingRunner};
+
+    fn manager(runner: Arc<RecordingRunner>) -> PackageManager {
+        PackageManager::new()
+            .with_runner(runner)
+            .with_db_lock_path("/nonexistent/jarvis-test/db.lck")
+    }
+
+    /// A manager whose database lock file exists, and the lock's path
+    fn locked_manager(runner: Arc<RecordingRunner>) -> (PackageManager, std::path::PathBuf) {
+        let lock = std::env::temp_dir().join(format!("jarvis-db-{}.lck", uuid::Uuid::new_v4()));
+        std::fs::write(&lock, "").unwrap();
+        let manager = PackageManager::new()
+            .with_runner(runner)
+            .with_db_lock_path(&lock);
+        (manager, lock)
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_installed_from_fixture() {
+        let runner = Arc::new(
+            RecordingRunner::new().respond("/usr/bin/pacman -Q", "linux 6.9.1.arch1-1\nmesa 1:24.1.0-1\n"),
+        );
+        let snapshot = manager(runner.clone()).snapshot_installed().await.unwrap();
+
+        assert_eq!(snapshot.get("mesa").map(String::as_str), Some("1:24.1.0-1"));
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(runner.calls(), vec!["/usr/bin/pacman -Q"]);
+    }
+
+    #[tokio::test]
+    async fn test_check_updates_treats_failure_as_none_pending() {
+        let runner = Arc::new(
+            RecordingRunner::new().respond_with("/usr/bin/pacman -Qu", fake_output(1, "", "")),
+        );
+        assert!(manager(runner).check_updates().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_remove_package_arguments() {
+        let runner = Arc::new(RecordingRunner::new());
+        let result = manager(runner.clone()).remove_package("nano", true).await.unwrap();
+
+        assert!(result.success);
+        assert_eq!(runner.calls(), vec!["/usr/bin/pacman -R nano -s"]);
+    }
+
+    #[tokio::test]
+    async fn test_aur_search_skipped_without_yay() {
+        let runner = Arc::new(RecordingRunner::new());
+        manager(runner.clone()).search_packages("paru", true).await.unwrap();
+        assert_eq!(runner.calls(), vec!["/usr/bin/pacman -Ss paru"]);
+    }
+
+    #[tokio::test]
+    async fn test_pending_download_bytes_sums_sizes() {
+        let runner = Arc::new(
+            RecordingRunner::new().respond("/usr/bin/pacman -Sup", "1048576\n524288\n0\n"),
+        );
+        assert_eq!(manager(runner).pending_download_bytes().await.unwrap(), 1_572_864);
+    }
+
+    #[tokio::test]
+    async fn test_package_info_from_c_locale_fixture() {
+        // `LC_ALL=C pacman -Qi nano`
+        let fixture = "Name            : nano\n\
+                       Version         : 8.2-1\n\
+                       Installed Size  : 2.54 MiB\n\
+                       Packager        : Levente Polyak <anthraxx@archlinux.org>\n\
+                       Build Date      : Tue Oct  1 10:00:00 2024\n\
+                       Install Date    : Wed Oct 16 08:30:12 2024\n";
+        let runner = Arc::new(RecordingRunner::new().respond("/usr/bin/pacman -Qi nano", fixture));
+        let info = manager(runner).get_package_info("nano").await.unwrap().unwrap();
+
+        assert_eq!(info.version, "8.2-1");
+        assert_eq!(info.installed_size, 2_663_383);
+        assert!(info.build_date.is_some());
+        assert!(info.install_date.unwrap() > info.build_date.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_utf8_output_is_flagged() {
+        let mut output = fake_output(0, "", "");
+        output.stdout = b"Paket gel\xf6scht\n".to_vec();
+        let runner = Arc::new(RecordingRunner::new().respond_with("/usr/bin/pacman -R", output));
+        let result = manager(runner).remove_package("nano", false).await.unwrap();
+
+        assert_eq!(result.output_lossy, Some(true));
+        assert!(serde_json::to_string(&result).unwrap().contains("gel\u{fffd}scht"));
+    }
+
+    #[tokio::test]
+    async fn test_held_lock_blocks_transaction() {
+        let runner = Arc::new(RecordingRunner::new().respond("pgrep", "4242 pacman -Syu\n"));
+        let (manager, lock_path) = locked_manager(runner.clone());
+
+        let error = manager.install_package("nano", false).await.unwrap_err();
+        let lock = error.downcast_ref::<LockHeld>().unwrap();
+        assert!(!lock.is_stale());
+        assert_eq!(lock.holders[0].pid, 4242);
+        assert!(error.to_string().contains("locked by pacman -Syu (pid 4242)"));
+
+        // Someone else's lock is never removed
+        assert!(manager.remove_stale_db_lock().await.is_err());
+        assert!(runner.calls().iter().all(|call| call.starts_with("pgrep")));
+        std::fs::remove_file(lock_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stale_lock_is_reported_then_removed() {
+        let runner = Arc::new(
+            RecordingRunner::new().respond_with("pgrep", fake_output(1, "", "")),
+        );
+        let (manager, lock_path) = locked_manager(runner.clone());
+
+        let error = manager.update_packages(None).await.unwrap_err();
+        let lock = error.downcast_ref::<LockHeld>().unwrap();
+        assert!(lock.is_stale());
+        assert!(error.to_string().contains("jarvis arch unlock-db"));
+
+        assert!(manager.remove_stale_db_lock().await.unwrap());
+        assert_eq!(
+            runner.calls().last().unwrap(),
+            &format!("rm -f {}", lock_path.display())
+        );
+        assert!(!runner.calls().iter().any(|call| call.contains("pacman -S")));
+        std::fs::remove_file(lock_path).unwrap();
+    }
+}
@@ -0,0 +1,430 @@
+//! Undoing recorded maintenance operations
+//!
+//! Update transactions are recorded with their package delta and, when snapper
+//! manages the root filesystem, the number of a snapshot taken just before
+//! them. A rollback plan turns such a record into concrete actions: reinstall
+//! the exact previous versions from the pacman cache, or revert files to the
+//! snapshot. A path that is no longer possible is reported with the reason
+//! instead of being attempted in part.
+
+use anyhow::Result;
+use jarvis_core::exec::{CommandRunner, RunOptions};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::dry_run::CommandLine;
+use crate::package_manager::TransactionDelta;
+use crate::zqlite_integration::MaintenanceRecord;
+
+/// Snapper configuration covering the root filesystem
+pub const SNAPPER_CONFIG: &str = "root";
+
+/// Downgrades and file reverts can take a while on slow disks
+const ROLLBACK_TIMEOUT: Duration = Duration::from_secs(1800);
+
+/// A package file in the pacman cache
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedPackage {
+    pub name: String,
+    pub version: String,
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RollbackAction {
+    /// Reinstall the versions that were installed before the operation
+    Downgrade { packages: Vec<CachedPackage> },
+    /// Revert every file changed since the pre-operation snapshot; meant for
+    /// configuration damage the package versions alone do not explain
+    RestoreSnapshot { config: String, snapshot: u32 },
+}
+
+impl RollbackAction {
+    pub fn command(&self) -> CommandLine {
+        match self {
+            RollbackAction::Downgrade { packages } => {
+                let mut command = CommandLine::new("pacman", &["-U", "--noconfirm"]);
+                command
+                    .args
+                    .extend(packages.iter().map(|p| p.path.display().to_string()));
+                command
+            }
+            RollbackAction::RestoreSnapshot { config, snapshot } => CommandLine::new(
+                "snapper",
+                &["-c", config, "undochange", &format!("{}..0", snapshot)],
+            ),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            RollbackAction::Downgrade { packages } => format!(
+                "Reinstall {} package(s) from the cache: {}",
+                packages.len(),
+                packages
+                    .iter()
+                    .map(|p| format!("{} {}", p.name, p.version))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            RollbackAction::RestoreSnapshot { config, snapshot } => format!(
+                "Revert files changed since snapper snapshot #{} ({} config)",
+                snapshot, config
+            ),
+        }
+    }
+}
+
+/// What undoing one recorded operation takes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RollbackPlan {
+    pub operation_id: Uuid,
+    pub operation_type: String,
+    pub actions: Vec<RollbackAction>,
+    /// Rollback paths that are no longer possible, and why
+    pub unavailable: Vec<String>,
+    /// Changes the actions leave in place
+    pub notes: Vec<String>,
+}
+
+impl RollbackPlan {
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+}
+
+/// Build the rollback plan for `record`, checking the package cache in
+/// `cache_dir` and asking snapper whether the snapshot still exists
+pub async fn plan(
+    record: &MaintenanceRecord,
+    runner: &dyn CommandRunner,
+    cache_dir: &Path,
+) -> RollbackPlan {
+    let mut plan = RollbackPlan {
+        operation_id: record.id,
+        operation_type: record.operation_type.clone(),
+        actions: Vec::new(),
+        unavailable: Vec::new(),
+        notes: Vec::new(),
+    };
+
+    let delta = record
+        .delta
+        .clone()
+        .and_then(|d| serde_json::from_value::<TransactionDelta>(d).ok());
+    match &delta {
+        Some(delta) => plan_downgrade(delta, cache_dir, &mut plan),
+        None => plan.unavailable.push(
+            "Package downgrade: no package delta was recorded for this operation".to_string(),
+        ),
+    }
+
+    match record.snapshot_id {
+        Some(snapshot) => match snapshot_exists(runner, snapshot).await {
+            Ok(true) => plan.actions.push(RollbackAction::RestoreSnapshot {
+                config: SNAPPER_CONFIG.to_string(),
+                snapshot,
+            }),
+            Ok(false) => plan.unavailable.push(format!(
+                "Snapshot restore: snapper snapshot #{} no longer exists",
+                snapshot
+            )),
+            Err(e) => plan.unavailable.push(format!(
+                "Snapshot restore: could not list snapper snapshots: {}",
+                e
+            )),
+        },
+        None => plan
+            .unavailable
+            .push("Snapshot restore: no snapshot was taken before this operation".to_string()),
+    }
+
+    plan
+}
+
+/// Offer a downgrade only when every previous version is still cached;
+/// reverting some packages of a transaction and not others leaves a mix
+/// that was never installed together
+fn plan_downgrade(delta: &TransactionDelta, cache_dir: &Path, plan: &mut RollbackPlan) {
+    let mut packages = Vec::new();
+    let mut missing = Vec::new();
+    for change in delta.upgraded.iter().chain(delta.removed.iter()) {
+        let Some(version) = &change.old_version else {
+            continue;
+        };
+        match find_cached_package(cache_dir, &change.package, version) {
+            Some(path) => packages.push(CachedPackage {
+                name: change.package.clone(),
+                version: version.clone(),
+                path,
+            }),
+            None => missing.push(format!("{} {}", change.package, version)),
+        }
+    }
+
+    if !missing.is_empty() {
+        plan.unavailable.push(format!(
+            "Package downgrade: not in {} (cache cleaned?): {}",
+            cache_dir.display(),
+            missing.join(", ")
+        ));
+    } else if !packages.is_empty() {
+        plan.actions.push(RollbackAction::Downgrade { packages });
+    }
+
+    for change in &delta.installed {
+        plan.notes.push(format!(
+            "{} {} was newly installed and stays; remove it with `pacman -Rs {}` if nothing needs it",
+            change.package,
+            change.new_version.as_deref().unwrap_or("?"),
+            change.package
+        ));
+    }
+}
+
+/// Path of the cached package file for exactly `name` at `version`
+pub fn find_cached_package(cache_dir: &Path, name: &str, version: &str) -> Option<PathBuf> {
+    let prefix = format!("{}-{}-", name, version);
+    let mut matches: Vec<PathBuf> = std::fs::read_dir(cache_dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let Some(file) = path.file_name().and_then(|f| f.to_str()) else {
+                return false;
+            };
+            // What follows the version is `<arch>.pkg.tar.<ext>`, and an
+            // architecture never contains a dash
+            file.strip_prefix(&prefix).is_some_and(|rest| {
+                !rest.contains('-') && rest.contains(".pkg.tar") && !rest.ends_with(".sig")
+            })
+        })
+        .collect();
+    matches.sort();
+    matches.into_iter().next()
+}
+
+/// Whether snapper still has `snapshot` in the root config
+pub async fn snapshot_exists(runner: &dyn CommandRunner, snapshot: u32) -> Result<bool> {
+    let output = runner
+        .output(
+            "snapper",
+            &["-c", SNAPPER_CONFIG, "list", "--columns", "number"],
+        )
+        .await?;
+    if !output.status.success() {
+        anyhow::bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            line.trim()
+                .trim_end_matches(['-', '+', '*'])
+                .parse::<u32>()
+                .ok()
+        })
+        .any(|number| number == snapshot))
+}
+
+tokio::task_local! {
+    /// Snapshot a composite run already took for the changes inside it
+    static SHARED_SNAPSHOT: u32;
+}
+
+/// Run `future` with `snapshot` standing in for the pre-change snapshots it
+/// would otherwise take
+pub async fn with_snapshot<F: std::future::Future>(snapshot: u32, future: F) -> F::Output {
+    SHARED_SNAPSHOT.scope(snapshot, future).await
+}
+
+/// The snapshot shared through [`with_snapshot`], or a new one from
+/// [`create_pre_snapshot`]
+pub async fn pre_snapshot(runner: &dyn CommandRunner, description: &str) -> Option<u32> {
+    match SHARED_SNAPSHOT.try_with(|snapshot| *snapshot) {
+        Ok(snapshot) => Some(snapshot),
+        Err(_) => create_pre_snapshot(runner, description).await,
+    }
+}
+
+/// Take a snapper snapshot before a change; `None` when snapper is missing,
+/// unconfigured, or fails, since the change itself does not depend on it
+pub async fn create_pre_snapshot(runner: &dyn CommandRunner, description: &str) -> Option<u32> {
+    let output = runner
+        .output(
+            "snapper",
+            &[
+                "-c",
+                SNAPPER_CONFIG,
+                "create",
+                "--type",
+                "single",
+                "--cleanup-algorithm",
+                "number",
+                "--print-number",
+                "--description",
+                description,
+            ],
+        )
+        .await;
+    match output {
+        Ok(output) if output.status.success() => {
+            String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+        }
+        Ok(output) => {
+            tracing::debug!(
+                "No pre-operation snapshot: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            None
+        }
+        Err(e) => {
+            tracing::debug!("No pre-operation snapshot: {}", e);
+            None
+        }
+    }
+}
+
+/// Run one action of a plan, returning its output
+pub async fn execute(action: &RollbackAction, runner: &dyn CommandRunner) -> Result<String> {
+    let command = action.command();
+    let args: Vec<&str> = command.args.iter().map(String::as_str).collect();
+    let output = runner
+        .run(
+            &command.program,
+            &args,
+            &RunOptions::default().with_timeout(ROLLBACK_TIMEOUT),
+        )
+        .await?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "`{}` failed: {}",
+            command,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zqlite_integration::MaintenanceStatus;
+    use jarvis_core::exec::{RecordingRunner, fake_output};
+
+    fn update_record(snapshot_id: Option<u32>) -> MaintenanceRecord {
+        MaintenanceRecord {
+            id: Uuid::new_v4(),
+            operation_type: "update_packages".to_string(),
+            status: MaintenanceStatus::Completed,
+            started_at: chrono::Utc::now(),
+            completed_at: None,
+            duration_ms: None,
+            packages_affected: vec!["linux".to_string(), "mesa".to_string()],
+            output: String::new(),
+            error_message: None,
+            delta: Some(serde_json::json!({
+                "upgraded": [
+                    { "package": "linux", "old_version": "6.9.1.arch1-1", "new_version": "6.9.2.arch1-1", "operation": "upgraded" },
+                    { "package": "mesa", "old_version": "1:24.1.0-1", "new_version": "1:24.1.1-1", "operation": "upgraded" }
+                ],
+                "installed": [
+                    { "package": "libfoo", "old_version": null, "new_version": "2.0-1", "operation": "installed" }
+                ],
+                "removed": [],
+                "reboot_relevant": ["linux"]
+            })),
+            snapshot_id,
+            rollback_of: None,
+        }
+    }
+
+    fn cache_with(files: &[&str]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("jarvis-rollback-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for file in files {
+            std::fs::write(dir.join(file), b"").unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_find_cached_package_matches_exact_version() {
+        let cache = cache_with(&[
+            "linux-6.9.1.arch1-1-x86_64.pkg.tar.zst",
+            "linux-6.9.1.arch1-1-x86_64.pkg.tar.zst.sig",
+            "linux-headers-6.9.1.arch1-1-x86_64.pkg.tar.zst",
+            "linux-6.9.2.arch1-1-x86_64.pkg.tar.zst",
+        ]);
+
+        assert_eq!(
+            find_cached_package(&cache, "linux", "6.9.1.arch1-1"),
+            Some(cache.join("linux-6.9.1.arch1-1-x86_64.pkg.tar.zst"))
+        );
+        assert_eq!(find_cached_package(&cache, "linux", "6.8.0.arch1-1"), None);
+        std::fs::remove_dir_all(cache).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_plan_offers_downgrade_and_snapshot() {
+        let cache = cache_with(&[
+            "linux-6.9.1.arch1-1-x86_64.pkg.tar.zst",
+            "mesa-1:24.1.0-1-x86_64.pkg.tar.zst",
+        ]);
+        let runner = RecordingRunner::new().respond("snapper", "  # \n---+\n 0  \n41  \n42  \n");
+
+        let plan = plan(&update_record(Some(42)), &runner, &cache).await;
+
+        assert_eq!(plan.actions.len(), 2);
+        assert_eq!(
+            plan.actions[0].command().to_string(),
+            format!(
+                "pacman -U --noconfirm {} {}",
+                cache
+                    .join("linux-6.9.1.arch1-1-x86_64.pkg.tar.zst")
+                    .display(),
+                cache.join("mesa-1:24.1.0-1-x86_64.pkg.tar.zst").display()
+            )
+        );
+        assert_eq!(
+            plan.actions[1].command().to_string(),
+            "snapper -c root undochange 42..0"
+        );
+        assert!(plan.unavailable.is_empty());
+        assert!(plan.notes[0].starts_with("libfoo 2.0-1 was newly installed"));
+        std::fs::remove_dir_all(cache).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_plan_reports_cleaned_cache_and_deleted_snapshot() {
+        let cache = cache_with(&["linux-6.9.1.arch1-1-x86_64.pkg.tar.zst"]);
+        let runner = RecordingRunner::new().respond("snapper", "  # \n---+\n 0  \n41  \n");
+
+        let plan = plan(&update_record(Some(42)), &runner, &cache).await;
+
+        // A partial downgrade is never offered
+        assert!(plan.is_empty());
+        assert!(plan.unavailable[0].contains("mesa 1:24.1.0-1"));
+        assert!(!plan.unavailable[0].contains("linux"));
+        assert_eq!(
+            plan.unavailable[1],
+            "Snapshot restore: snapper snapshot #42 no longer exists"
+        );
+        std::fs::remove_dir_all(cache).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pre_snapshot_is_optional() {
+        let runner = RecordingRunner::new().respond("snapper", "57\n");
+        assert_eq!(
+            create_pre_snapshot(&runner, "before update").await,
+            Some(57)
+        );
+
+        let runner =
+            RecordingRunner::new().respond_with("snapper", fake_output(1, "", "Unknown config."));
+        assert_eq!(create_pre_snapshot(&runner, "before update").await, None);
+    }
+}
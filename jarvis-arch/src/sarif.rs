@@ -0,0 +1,490 @@
+//! SARIF 2.1.0 export of open findings
+//!
+//! Renders everything Jarvis currently reports as open — tracked security
+//! scan findings, package and container image advisories, PKGBUILD heuristic
+//! hits, and systemd unit drift — as one SARIF run, so CI jobs and code
+//! scanning dashboards can ingest it next to other tools. Results carry the
+//! finding-lifecycle fingerprint, letting viewers match a result across runs
+//! the same way `arch scan` does.
+
+use jarvis_core::report::SecurityFinding;
+use jarvis_core::severity::Severity;
+use jarvis_core::unit_drift::{DriftKind, UnitDrift};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::aur_sandbox::{FindingSeverity, PkgbuildFinding};
+use crate::findings::{TrackedFinding, fingerprint};
+
+pub const SARIF_VERSION: &str = "2.1.0";
+pub const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+/// Media type for `GET /findings.sarif`
+pub const SARIF_CONTENT_TYPE: &str = "application/sarif+json";
+
+const INFORMATION_URI: &str = "https://github.com/ghostkellz/jarvis";
+/// Key under `partialFingerprints`; bump the version if the hashing changes
+const FINGERPRINT_KEY: &str = "jarvisFinding/v1";
+
+/// Configuration key caching the last AUR PKGBUILD check per package
+pub const AUR_FINDINGS_KEY: &str = "aur_pkgbuild_findings";
+/// Configuration key caching the last unit drift validation
+pub const UNIT_DRIFT_KEY: &str = "unit_drift";
+
+/// Findings open as of the last run of each check
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OpenFindings {
+    /// Tracked security scan findings
+    pub security: Vec<TrackedFinding>,
+    /// Vulnerable packages and container image packages, minus acknowledged advisories
+    pub vulnerabilities: Vec<SecurityFinding>,
+    /// PKGBUILD heuristic hits by AUR package
+    pub aur: BTreeMap<String, Vec<PkgbuildFinding>>,
+    /// Units not loaded exactly as packaged
+    pub unit_drift: Vec<UnitDrift>,
+}
+
+impl OpenFindings {
+    pub fn is_empty(&self) -> bool {
+        self.security.is_empty()
+            && self.vulnerabilities.is_empty()
+            && self.aur.values().all(Vec::is_empty)
+            && self.unit_drift.iter().all(|u| u.drift == DriftKind::Clean)
+    }
+
+    /// The findings as a SARIF log with a single run
+    pub fn to_sarif(&self) -> Value {
+        let mut log = SarifLog::default();
+
+        for finding in &self.security {
+            log.add_tracked(finding);
+        }
+        for finding in &self.vulnerabilities {
+            log.add_vulnerability(finding);
+        }
+        for (package, findings) in &self.aur {
+            for finding in findings {
+                log.add_pkgbuild(package, finding);
+            }
+        }
+        for unit in &self.unit_drift {
+            log.add_unit_drift(unit);
+        }
+
+        log.finish()
+    }
+}
+
+/// SARIF `level` for a severity
+pub fn level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Critical | Severity::High => "error",
+        Severity::Medium => "warning",
+        Severity::Low | Severity::Info => "note",
+    }
+}
+
+/// The `security-severity` score code scanning dashboards sort by
+fn security_severity(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Critical => "9.5",
+        Severity::High => "8.0",
+        Severity::Medium => "5.5",
+        Severity::Low => "2.0",
+        Severity::Info => "0.0",
+    }
+}
+
+/// Where to read about an advisory: the Arch security tracker for AVGs and
+/// ASAs, NVD for CVEs, GitHub for GHSAs
+pub fn advisory_uri(advisory: &str) -> Option<String> {
+    let advisory = advisory.trim();
+    if advisory.starts_with("AVG-") || advisory.starts_with("ASA-") {
+        Some(format!("https://security.archlinux.org/{}", advisory))
+    } else if advisory.starts_with("CVE-") {
+        Some(format!("https://nvd.nist.gov/vuln/detail/{}", advisory))
+    } else if advisory.starts_with("GHSA-") {
+        Some(format!("https://github.com/advisories/{}", advisory))
+    } else {
+        None
+    }
+}
+
+fn pkgbuild_severity(severity: FindingSeverity) -> Severity {
+    match severity {
+        FindingSeverity::Low => Severity::Low,
+        FindingSeverity::Medium => Severity::Medium,
+        FindingSeverity::High => Severity::High,
+    }
+}
+
+/// A file on this host, with the line when known
+fn physical_location(path: &str, line: Option<u64>) -> Value {
+    let mut location = json!({
+        "physicalLocation": {
+            "artifactLocation": { "uri": format!("file://{}", path) },
+        }
+    });
+    if let Some(line) = line.filter(|l| *l > 0) {
+        location["physicalLocation"]["region"] = json!({ "startLine": line });
+    }
+    location
+}
+
+/// A subject with no file behind it: a package, setting, or service
+fn logical_location(name: &str, kind: &str) -> Value {
+    json!({
+        "logicalLocations": [{ "name": name, "kind": kind }]
+    })
+}
+
+#[derive(Default)]
+struct SarifLog {
+    rules: Vec<Value>,
+    rule_index: HashMap<String, usize>,
+    results: Vec<Value>,
+}
+
+impl SarifLog {
+    /// Index of rule `id`, added on first use. A rule's severity is the
+    /// highest any of its results has.
+    fn rule(
+        &mut self,
+        id: &str,
+        description: &str,
+        help_uri: Option<String>,
+        severity: Severity,
+    ) -> usize {
+        if let Some(&index) = self.rule_index.get(id) {
+            let rule = &mut self.rules[index];
+            let current: Severity = rule["properties"]["jarvisSeverity"]
+                .as_str()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or_default();
+            if severity > current {
+                rule["defaultConfiguration"]["level"] = json!(level(severity));
+                rule["properties"]["jarvisSeverity"] = json!(severity.as_str());
+                rule["properties"]["security-severity"] = json!(security_severity(severity));
+            }
+            return index;
+        }
+
+        let mut rule = json!({
+            "id": id,
+            "shortDescription": { "text": description },
+            "defaultConfiguration": { "level": level(severity) },
+            "properties": {
+                "jarvisSeverity": severity.as_str(),
+                "security-severity": security_severity(severity),
+            },
+        });
+        if let Some(uri) = help_uri {
+            rule["helpUri"] = json!(uri);
+        }
+        self.rules.push(rule);
+        self.rule_index.insert(id.to_string(), self.rules.len() - 1);
+        self.rules.len() - 1
+    }
+
+    fn result(
+        &mut self,
+        rule: usize,
+        severity: Severity,
+        message: String,
+        location: Value,
+        fingerprint: &str,
+    ) {
+        self.results.push(json!({
+            "ruleId": self.rules[rule]["id"],
+            "ruleIndex": rule,
+            "level": level(severity),
+            "message": { "text": message },
+            "locations": [location],
+            "partialFingerprints": { FINGERPRINT_KEY: fingerprint },
+        }));
+    }
+
+    /// A security scan finding: CVE findings are ruled by their advisory,
+    /// everything else by its check
+    fn add_tracked(&mut self, finding: &TrackedFinding) {
+        let advisory = finding.details.get("cve_id").and_then(|v| v.as_str());
+        let rule = match advisory {
+            Some(advisory) => self.rule(
+                advisory,
+                &format!("Vulnerable package ({})", advisory),
+                advisory_uri(advisory),
+                finding.severity,
+            ),
+            None => self.rule(
+                &finding.check,
+                &format!("Security scan check: {}", finding.check),
+                None,
+                finding.severity,
+            ),
+        };
+
+        let location = if finding.subject.starts_with('/') {
+            let line = finding.details.get("line").and_then(|v| v.as_u64());
+            physical_location(&finding.subject, line)
+        } else {
+            logical_location(&finding.subject, "resource")
+        };
+        let message = if finding.description.is_empty() {
+            format!("{}: {} finding", finding.subject, finding.check)
+        } else {
+            format!("{}: {}", finding.subject, finding.description)
+        };
+        self.result(
+            rule,
+            finding.severity,
+            message,
+            location,
+            &finding.fingerprint,
+        );
+    }
+
+    /// One result per advisory affecting the package
+    fn add_vulnerability(&mut self, finding: &SecurityFinding) {
+        for advisory in &finding.advisories {
+            let rule = self.rule(
+                advisory,
+                &format!("Vulnerable package ({})", advisory),
+                advisory_uri(advisory),
+                finding.severity,
+            );
+            let fingerprint = fingerprint(
+                "vulnerability",
+                &finding.package,
+                &[("cve_id", advisory.as_str())],
+            );
+            self.result(
+                rule,
+                finding.severity,
+                format!("{} is affected by {}", finding.package, advisory),
+                logical_location(&finding.package, "package"),
+                &fingerprint,
+            );
+        }
+    }
+
+    /// A PKGBUILD heuristic hit, located in the package's AUR git tree
+    fn add_pkgbuild(&mut self, package: &str, finding: &PkgbuildFinding) {
+        let severity = pkgbuild_severity(finding.severity);
+        let rule = self.rule(
+            &format!("aur/{}", finding.rule),
+            &format!("Suspicious PKGBUILD construct: {}", finding.rule),
+            None,
+            severity,
+        );
+        let mut location = json!({
+            "physicalLocation": {
+                "artifactLocation": {
+                    "uri": format!(
+                        "https://aur.archlinux.org/cgit/aur.git/tree/{}?h={}",
+                        finding.file, package
+                    ),
+                },
+            }
+        });
+        if finding.line > 0 {
+            location["physicalLocation"]["region"] = json!({
+                "startLine": finding.line,
+                "snippet": { "text": finding.excerpt },
+            });
+        }
+        let fingerprint = fingerprint(
+            "aur_pkgbuild",
+            package,
+            &[
+                ("rule", finding.rule.as_str()),
+                ("setting", finding.excerpt.as_str()),
+            ],
+        );
+        self.result(
+            rule,
+            severity,
+            format!("{} {}: {}", package, finding.file, finding.excerpt),
+            location,
+            &fingerprint,
+        );
+    }
+
+    /// Units loaded as packaged are not findings
+    fn add_unit_drift(&mut self, unit: &UnitDrift) {
+        let (id, description, severity) = match unit.drift {
+            DriftKind::Clean => return,
+            DriftKind::ExpectedOverride => (
+                "unit-drift/override",
+                "systemd unit customized through /etc or /run",
+                Severity::Info,
+            ),
+            DriftKind::ModifiedVendorFile => (
+                "unit-drift/modified-vendor-file",
+                "Packaged systemd unit file edited in place; the change is lost on upgrade",
+                Severity::Medium,
+            ),
+        };
+        let rule = self.rule(id, description, None, severity);
+        let location = match &unit.fragment_path {
+            Some(path) => physical_location(path, None),
+            None => logical_location(&unit.unit, "service"),
+        };
+        let fingerprint = fingerprint("unit_drift", &unit.unit, &[("setting", id)]);
+        self.result(
+            rule,
+            severity,
+            format!("Unit {} {}", unit.unit, unit.describe()),
+            location,
+            &fingerprint,
+        );
+    }
+
+    fn finish(self) -> Value {
+        json!({
+            "$schema": SARIF_SCHEMA,
+            "version": SARIF_VERSION,
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": "jarvis",
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "informationUri": INFORMATION_URI,
+                        "rules": self.rules,
+                    }
+                },
+                "results": self.results,
+            }]
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::findings::from_scan;
+
+    fn findings() -> OpenFindings {
+        let scan = json!({
+            "issues": [
+                { "check": "world_writable", "path": "/etc/sudoers.d/local", "severity": "high", "description": "World-writable sudoers drop-in" },
+                { "check": "ssh", "subject": "PermitRootLogin", "severity": "medium", "description": "Root login over SSH is allowed" },
+                { "check": "cve", "package": "openssl", "cve_id": "CVE-2024-5535", "severity": "critical", "description": "openssl buffer overread" },
+            ]
+        });
+        let unit = |name: &str, drift: DriftKind| UnitDrift {
+            unit: name.to_string(),
+            fragment_path: Some(format!("/usr/lib/systemd/system/{}", name)),
+            package: Some("systemd".to_string()),
+            replaces_vendor_file: false,
+            overrides: Vec::new(),
+            vendor_file_modified: drift == DriftKind::ModifiedVendorFile,
+            drift,
+        };
+
+        OpenFindings {
+            security: from_scan(&scan, chrono::Utc::now()),
+            vulnerabilities: vec![
+                SecurityFinding {
+                    package: "openssl".to_string(),
+                    severity: Severity::High,
+                    advisories: vec!["AVG-2843".to_string(), "CVE-2024-5535".to_string()],
+                },
+                SecurityFinding {
+                    package: "zlib in nginx:latest".to_string(),
+                    severity: Severity::Medium,
+                    advisories: vec!["CVE-2023-45853".to_string()],
+                },
+            ],
+            aur: BTreeMap::from([(
+                "foo-bin".to_string(),
+                vec![PkgbuildFinding {
+                    severity: FindingSeverity::High,
+                    file: "PKGBUILD".to_string(),
+                    line: 12,
+                    rule: "curl-pipe-shell".to_string(),
+                    excerpt: "curl -s https://example.com/install.sh | sh".to_string(),
+                }],
+            )]),
+            unit_drift: vec![
+                unit("sshd.service", DriftKind::ModifiedVendorFile),
+                unit("nginx.service", DriftKind::ExpectedOverride),
+                unit("cronie.service", DriftKind::Clean),
+            ],
+        }
+    }
+
+    /// The golden file is pinned to version 0.0.0 so releases don't churn it
+    fn rendered() -> Value {
+        let mut sarif = findings().to_sarif();
+        sarif["runs"][0]["tool"]["driver"]["version"] = json!("0.0.0");
+        sarif
+    }
+
+    #[test]
+    fn test_sarif_matches_golden_file() {
+        let golden: Value =
+            serde_json::from_str(include_str!("../tests/fixtures/sarif/findings.sarif")).unwrap();
+        assert_eq!(rendered(), golden);
+    }
+
+    #[test]
+    fn test_sarif_validates_against_schema() {
+        let schema: Value = serde_json::from_str(include_str!(
+            "../tests/fixtures/sarif/sarif-2.1.0-subset.schema.json"
+        ))
+        .unwrap();
+        let schema = jsonschema::JSONSchema::compile(&schema).unwrap();
+
+        assert!(schema.is_valid(&rendered()));
+        assert!(schema.is_valid(&OpenFindings::default().to_sarif()));
+
+        let mut invalid = rendered();
+        invalid["runs"][0]["results"][0]["level"] = json!("critical");
+        assert!(!schema.is_valid(&invalid));
+    }
+
+    #[test]
+    fn test_sarif_levels_rules_and_fingerprints() {
+        let findings = findings();
+        let sarif = findings.to_sarif();
+        let run = &sarif["runs"][0];
+        let results = run["results"].as_array().unwrap();
+        let rules = run["tool"]["driver"]["rules"].as_array().unwrap();
+
+        // The CVE is reported by the scan and by arch-audit but ruled once,
+        // at the highest severity either gave it
+        let cve: Vec<_> = rules
+            .iter()
+            .filter(|r| r["id"] == "CVE-2024-5535")
+            .collect();
+        assert_eq!(cve.len(), 1);
+        assert_eq!(cve[0]["defaultConfiguration"]["level"], "error");
+        assert_eq!(cve[0]["properties"]["jarvisSeverity"], "critical");
+        assert_eq!(
+            cve[0]["helpUri"],
+            "https://nvd.nist.gov/vuln/detail/CVE-2024-5535"
+        );
+
+        // Tracked findings keep their lifecycle fingerprint
+        let tracked = &results[0];
+        assert_eq!(
+            tracked["partialFingerprints"][FINGERPRINT_KEY],
+            json!(findings.security[0].fingerprint)
+        );
+        assert_eq!(
+            tracked["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+            "file:///etc/sudoers.d/local"
+        );
+
+        // Clean units are not findings
+        assert!(
+            results
+                .iter()
+                .all(|r| !r["message"]["text"].as_str().unwrap().contains("cronie"))
+        );
+        for result in results {
+            let index = result["ruleIndex"].as_u64().unwrap() as usize;
+            assert_eq!(rules[index]["id"], result["ruleId"]);
+        }
+    }
+}
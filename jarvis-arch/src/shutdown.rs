@@ -0,0 +1,406 @@
+//! Staged shutdown of the service
+//!
+//! Stopping the service must not interrupt a pacman transaction, so a stop
+//! request drains the agent's [`OperationRegistry`] instead of dropping
+//! everything:
+//!
+//! 1. The registry is closed; new operations are refused.
+//! 2. Running operations get `drain_timeout_secs` to finish, with progress
+//!    logged every few seconds.
+//! 3. Past that soft deadline everything but package transactions is
+//!    cancelled, and gets `cancel_grace_secs` to wind down.
+//! 4. Package transactions are never cancelled. Shutdown waits for them,
+//!    however long they take, and logs loudly while it does.
+//!
+//! A second stop request skips the waiting in 2 and 3 (the forced path) but
+//! still waits for transactions. The service then flushes the Wazuh spool,
+//! persists statistics, checkpoints the scheduler, and exits with
+//! [`EXIT_CLEAN`] or [`EXIT_FORCED`].
+//!
+//! systemd sends SIGTERM on `systemctl stop` and SIGKILLs the whole unit,
+//! pacman included, once `TimeoutStopSec` runs out. The unit's timeout is
+//! therefore set above `drain_timeout_secs + cancel_grace_secs`, and while a
+//! transaction holds shutdown up the service sends `EXTEND_TIMEOUT_USEC` so
+//! systemd keeps waiting too. Lowering `TimeoutStopSec` below the drain
+//! timeout means systemd, not this module, decides when operations stop.
+
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{error, info, warn};
+
+use crate::config::ShutdownConfig;
+use crate::operation_registry::OperationRegistry;
+
+/// Exit status when every operation finished on its own
+pub const EXIT_CLEAN: i32 = 0;
+/// Exit status when operations were cancelled or abandoned (EX_TEMPFAIL)
+pub const EXIT_FORCED: i32 = 75;
+
+/// How often the registry is checked while draining
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// How often progress is logged while draining
+pub const PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownOutcome {
+    /// Everything running finished on its own
+    Clean,
+    /// Operations were cancelled, abandoned, or a second stop request came in
+    Forced,
+}
+
+impl ShutdownOutcome {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ShutdownOutcome::Clean => EXIT_CLEAN,
+            ShutdownOutcome::Forced => EXIT_FORCED,
+        }
+    }
+}
+
+/// One step of a drain, in the order they happen
+#[derive(Debug, Clone, PartialEq)]
+pub enum DrainStep {
+    /// New operations are refused from here on
+    Closed {
+        running: Vec<String>,
+    },
+    /// Still waiting before the soft deadline
+    Waiting {
+        running: Vec<String>,
+    },
+    /// A second stop request arrived
+    Escalated,
+    Cancelled {
+        operations: Vec<String>,
+    },
+    /// Package transactions are holding shutdown up
+    WaitingOnTransactions {
+        transactions: Vec<String>,
+    },
+    /// Cancelled operations that had still not stopped when shutdown gave up on them
+    Abandoned {
+        operations: Vec<String>,
+    },
+    /// Nothing is running any more
+    Drained,
+}
+
+/// What a drain did, with each step's time since it started
+#[derive(Debug)]
+pub struct DrainReport {
+    pub outcome: ShutdownOutcome,
+    pub steps: Vec<(Duration, DrainStep)>,
+}
+
+/// Close `operations` and wait for them as described in the module docs.
+/// `escalate` resolves on a second stop request. `on_step` sees each step as
+/// it happens, e.g. to extend systemd's stop timeout.
+pub async fn drain<E: Future<Output = ()>>(
+    operations: &OperationRegistry,
+    config: &ShutdownConfig,
+    escalate: E,
+    mut on_step: impl FnMut(&DrainStep),
+) -> DrainReport {
+    let started = Instant::now();
+    let soft_deadline = started + Duration::from_secs(config.drain_timeout_secs);
+    let cancel_grace = Duration::from_secs(config.cancel_grace_secs);
+    let mut steps = Vec::new();
+    let mut record = |step: DrainStep| {
+        log_step(&step, started.elapsed());
+        on_step(&step);
+        steps.push((started.elapsed(), step));
+    };
+
+    operations.close();
+    record(DrainStep::Closed {
+        running: names(operations, |_| true),
+    });
+
+    tokio::pin!(escalate);
+    let mut escalated = false;
+    let mut forced = false;
+    let mut cancelled_at: Option<Instant> = None;
+    let mut next_progress = started + PROGRESS_INTERVAL;
+
+    loop {
+        let running = operations.running();
+        if running.is_empty() {
+            record(DrainStep::Drained);
+            break;
+        }
+        let now = Instant::now();
+
+        if cancelled_at.is_none() && (escalated || now >= soft_deadline) {
+            let cancelled = operations.cancel_all();
+            if !cancelled.is_empty() {
+                forced = true;
+                record(DrainStep::Cancelled {
+                    operations: cancelled,
+                });
+            }
+            cancelled_at = Some(now);
+        }
+
+        let transactions = names(operations, |transaction| transaction);
+        if let Some(at) = cancelled_at
+            && transactions.is_empty()
+            && (escalated || now >= at + cancel_grace)
+        {
+            forced = true;
+            record(DrainStep::Abandoned {
+                operations: names(operations, |_| true),
+            });
+            break;
+        }
+
+        if now >= next_progress {
+            match cancelled_at {
+                Some(_) if !transactions.is_empty() => {
+                    record(DrainStep::WaitingOnTransactions { transactions })
+                }
+                _ => record(DrainStep::Waiting {
+                    running: names(operations, |_| true),
+                }),
+            }
+            next_progress = now + PROGRESS_INTERVAL;
+        }
+
+        tokio::select! {
+            _ = &mut escalate, if !escalated => {
+                escalated = true;
+                forced = true;
+                record(DrainStep::Escalated);
+            }
+            _ = tokio::time::sleep(POLL_INTERVAL) => {}
+        }
+    }
+
+    DrainReport {
+        outcome: if forced {
+            ShutdownOutcome::Forced
+        } else {
+            ShutdownOutcome::Clean
+        },
+        steps,
+    }
+}
+
+/// Names of the running operations whose transaction flag passes `filter`
+fn names(operations: &OperationRegistry, filter: impl Fn(bool) -> bool) -> Vec<String> {
+    operations
+        .running()
+        .into_iter()
+        .filter(|operation| filter(operation.transaction))
+        .map(|operation| operation.name.to_string())
+        .collect()
+}
+
+fn log_step(step: &DrainStep, elapsed: Duration) {
+    let elapsed = elapsed.as_secs();
+    match step {
+        DrainStep::Closed { running } if running.is_empty() => {
+            info!("Shutting down: no operations running")
+        }
+        DrainStep::Closed { running } => info!(
+            "Shutting down: refusing new operations, waiting for {}",
+            running.join(", ")
+        ),
+        DrainStep::Waiting { running } => {
+            info!(
+                "Still waiting after {}s for {}",
+                elapsed,
+                running.join(", ")
+            )
+        }
+        DrainStep::Escalated => warn!("Second stop request after {}s; forcing shutdown", elapsed),
+        DrainStep::Cancelled { operations } => {
+            warn!("Cancelling after {}s: {}", elapsed, operations.join(", "))
+        }
+        DrainStep::WaitingOnTransactions { transactions } => error!(
+            "Package transaction still running after {}s: {}. NOT killing it; shutdown waits until it finishes",
+            elapsed,
+            transactions.join(", ")
+        ),
+        DrainStep::Abandoned { operations } => error!(
+            "Giving up after {}s on cancelled operations that did not stop: {}",
+            elapsed,
+            operations.join(", ")
+        ),
+        DrainStep::Drained => info!("All operations finished after {}s", elapsed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    fn config() -> ShutdownConfig {
+        ShutdownConfig {
+            drain_timeout_secs: 60,
+            cancel_grace_secs: 15,
+        }
+    }
+
+    /// Register an operation that takes `secs` of (paused) time
+    async fn start(
+        registry: &Arc<OperationRegistry>,
+        name: &'static str,
+        transaction: bool,
+        secs: u64,
+    ) -> tokio::task::JoinHandle<Option<()>> {
+        let before = registry.len();
+        let handle = {
+            let registry = registry.clone();
+            tokio::spawn(async move {
+                let operation = tokio::time::sleep(Duration::from_secs(secs));
+                if transaction {
+                    registry
+                        .run_transaction(Uuid::new_v4(), name, operation)
+                        .await
+                } else {
+                    registry.run(Uuid::new_v4(), name, operation).await
+                }
+            })
+        };
+        while registry.len() == before {
+            tokio::task::yield_now().await;
+        }
+        handle
+    }
+
+    fn kinds(report: &DrainReport) -> Vec<&'static str> {
+        report
+            .steps
+            .iter()
+            .map(|(_, step)| match step {
+                DrainStep::Closed { .. } => "closed",
+                DrainStep::Waiting { .. } => "waiting",
+                DrainStep::Escalated => "escalated",
+                DrainStep::Cancelled { .. } => "cancelled",
+                DrainStep::WaitingOnTransactions { .. } => "transactions",
+                DrainStep::Abandoned { .. } => "abandoned",
+                DrainStep::Drained => "drained",
+            })
+            .filter(|kind| *kind != "waiting" && *kind != "transactions")
+            .collect()
+    }
+
+    fn at(report: &DrainReport, kind: fn(&DrainStep) -> bool) -> Duration {
+        report
+            .steps
+            .iter()
+            .find(|(_, step)| kind(step))
+            .map(|(elapsed, _)| *elapsed)
+            .unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_operations_finishing_in_time_drain_cleanly() {
+        let registry = Arc::new(OperationRegistry::default());
+        let scan = start(&registry, "SecurityScan", false, 20).await;
+
+        let report = drain(&registry, &config(), std::future::pending(), |_| {}).await;
+        assert_eq!(report.outcome, ShutdownOutcome::Clean);
+        assert_eq!(kinds(&report), ["closed", "drained"]);
+        assert!(at(&report, |s| *s == DrainStep::Drained) < Duration::from_secs(21));
+        assert_eq!(scan.await.unwrap(), Some(()));
+        assert!(registry.is_closed());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_soft_deadline_cancels_all_but_transactions() {
+        let registry = Arc::new(OperationRegistry::default());
+        let scan = start(&registry, "SecurityScan", false, 600).await;
+        let update = start(&registry, "UpdatePackages", true, 90).await;
+
+        let mut extensions = 0;
+        let report = drain(&registry, &config(), std::future::pending(), |step| {
+            if matches!(step, DrainStep::WaitingOnTransactions { .. }) {
+                extensions += 1;
+            }
+        })
+        .await;
+
+        assert_eq!(report.outcome, ShutdownOutcome::Forced);
+        assert_eq!(kinds(&report), ["closed", "cancelled", "drained"]);
+        let cancelled = at(&report, |s| matches!(s, DrainStep::Cancelled { .. }));
+        assert!(cancelled >= Duration::from_secs(60) && cancelled < Duration::from_secs(61));
+        assert_eq!(
+            report.steps.iter().find_map(|(_, s)| match s {
+                DrainStep::Cancelled { operations } => Some(operations.clone()),
+                _ => None,
+            }),
+            Some(vec!["SecurityScan".to_string()])
+        );
+        assert_eq!(scan.await.unwrap(), None);
+
+        // The transaction ran to completion, past the grace period
+        assert_eq!(update.await.unwrap(), Some(()));
+        assert!(at(&report, |s| *s == DrainStep::Drained) >= Duration::from_secs(90));
+        assert!(extensions >= 5, "{}", extensions);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_second_stop_request_skips_the_wait_but_not_transactions() {
+        let registry = Arc::new(OperationRegistry::default());
+        let scan = start(&registry, "SecurityScan", false, 600).await;
+        let update = start(&registry, "UpdatePackages", true, 20).await;
+
+        let report = drain(
+            &registry,
+            &config(),
+            tokio::time::sleep(Duration::from_secs(3)),
+            |_| {},
+        )
+        .await;
+
+        assert_eq!(report.outcome, ShutdownOutcome::Forced);
+        assert_eq!(
+            kinds(&report),
+            ["closed", "escalated", "cancelled", "drained"]
+        );
+        assert!(at(&report, |s| matches!(s, DrainStep::Cancelled { .. })) < Duration::from_secs(4));
+        assert_eq!(scan.await.unwrap(), None);
+        assert_eq!(update.await.unwrap(), Some(()));
+        assert!(at(&report, |s| *s == DrainStep::Drained) >= Duration::from_secs(20));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_operations_ignoring_cancellation_are_abandoned_after_grace() {
+        let registry = Arc::new(OperationRegistry::default());
+        // Blocks its thread, so the cancellation never gets to drop it
+        let stuck = {
+            let registry = registry.clone();
+            std::thread::spawn(move || {
+                tokio::runtime::Builder::new_current_thread()
+                    .build()
+                    .unwrap()
+                    .block_on(registry.run(Uuid::new_v4(), "CustomCommand", async {
+                        std::thread::sleep(std::time::Duration::from_millis(300));
+                    }))
+            })
+        };
+        while registry.is_empty() {
+            tokio::task::yield_now().await;
+        }
+
+        let config = ShutdownConfig {
+            drain_timeout_secs: 0,
+            cancel_grace_secs: 0,
+        };
+        let report = drain(&registry, &config, std::future::pending(), |_| {}).await;
+        assert_eq!(report.outcome, ShutdownOutcome::Forced);
+        assert_eq!(kinds(&report), ["closed", "cancelled", "abandoned"]);
+        stuck.join().unwrap();
+    }
+
+    #[test]
+    fn test_exit_codes_tell_clean_from_forced() {
+        assert_eq!(ShutdownOutcome::Clean.exit_code(), 0);
+        assert_ne!(ShutdownOutcome::Forced.exit_code(), 0);
+    }
+}
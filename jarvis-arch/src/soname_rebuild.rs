@@ -0,0 +1,782 @@
+//! AUR packages left broken by a shared library soname bump
+//!
+//! An update that moves a repository library to a new soname (ICU, readline,
+//! openssl) leaves foreign packages linked against the old one unable to
+//! start until they are rebuilt. After each update the agent reads the
+//! `DT_NEEDED` entries of every file foreign packages own and resolves them
+//! the way the dynamic loader would: the package's own runpath, then the
+//! system library directories for the file's ELF class. Scripts and other
+//! non-ELF files are skipped, and 32-bit binaries are resolved against the
+//! 32-bit directories.
+//!
+//! Libraries a package bundles itself count as resolved, since such packages
+//! usually point `LD_LIBRARY_PATH` at them from a wrapper script.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use jarvis_core::exec::{CommandRunner, OutputText};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+const ELF_MAGIC: &[u8; 4] = b"\x7fELF";
+
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+
+const DT_NULL: u64 = 0;
+const DT_NEEDED: u64 = 1;
+const DT_STRTAB: u64 = 5;
+const DT_STRSZ: u64 = 10;
+const DT_RPATH: u64 = 15;
+const DT_RUNPATH: u64 = 29;
+
+/// Larger program header tables, dynamic sections, or string tables than
+/// this mean the file is not a real ELF object
+const MAX_TABLE_BYTES: u64 = 1 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ElfClass {
+    Elf32,
+    Elf64,
+}
+
+/// What an ELF object asks the dynamic loader for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElfDependencies {
+    pub class: ElfClass,
+    /// `DT_NEEDED` sonames in link order; empty for static binaries
+    pub needed: Vec<String>,
+    /// `DT_RUNPATH`, or `DT_RPATH` when there is no runpath, with `$ORIGIN`
+    /// left unexpanded
+    pub runpath: Vec<String>,
+}
+
+/// Reads the fields of one ELF file's class and byte order
+struct Fields {
+    class: ElfClass,
+    big_endian: bool,
+}
+
+impl Fields {
+    fn bytes<const N: usize>(&self, data: &[u8], at: usize) -> io::Result<[u8; N]> {
+        data.get(at..at + N)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| invalid("truncated ELF structure"))
+    }
+
+    fn u16(&self, data: &[u8], at: usize) -> io::Result<u16> {
+        let bytes = self.bytes(data, at)?;
+        Ok(if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    fn u32(&self, data: &[u8], at: usize) -> io::Result<u32> {
+        let bytes = self.bytes(data, at)?;
+        Ok(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    /// An address, offset, or dynamic entry field: 4 bytes in ELF32, 8 in ELF64
+    fn word(&self, data: &[u8], at: usize) -> io::Result<u64> {
+        match self.class {
+            ElfClass::Elf32 => self.u32(data, at).map(u64::from),
+            ElfClass::Elf64 => {
+                let bytes = self.bytes(data, at)?;
+                Ok(if self.big_endian {
+                    u64::from_be_bytes(bytes)
+                } else {
+                    u64::from_le_bytes(bytes)
+                })
+            }
+        }
+    }
+
+    fn word_size(&self) -> usize {
+        match self.class {
+            ElfClass::Elf32 => 4,
+            ElfClass::Elf64 => 8,
+        }
+    }
+}
+
+/// A `PT_LOAD` segment, for mapping addresses in the dynamic section back to
+/// file offsets
+struct Segment {
+    offset: u64,
+    vaddr: u64,
+    filesz: u64,
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Fill `buf` as far as the reader goes; the number of bytes read
+fn read_up_to<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// `len` bytes at `offset`; a file that ends first is malformed
+fn read_at<R: Read + Seek>(reader: &mut R, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+    if len > MAX_TABLE_BYTES {
+        return Err(invalid("ELF table too large"));
+    }
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut buf = vec![0; len as usize];
+    reader.read_exact(&mut buf).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => invalid("truncated ELF file"),
+        _ => e,
+    })?;
+    Ok(buf)
+}
+
+/// The shared objects an ELF file needs. `None` for anything that is not
+/// ELF, such as scripts; an `InvalidData` error for ELF files too truncated
+/// or malformed to read.
+pub fn read_dependencies<R: Read + Seek>(reader: &mut R) -> io::Result<Option<ElfDependencies>> {
+    let mut header = [0u8; 64];
+    let read = read_up_to(reader, &mut header)?;
+    if read < ELF_MAGIC.len() || &header[..4] != ELF_MAGIC {
+        return Ok(None);
+    }
+    let class = match header[4] {
+        1 => ElfClass::Elf32,
+        2 => ElfClass::Elf64,
+        _ => return Err(invalid("unknown ELF class")),
+    };
+    let big_endian = match header[5] {
+        1 => false,
+        2 => true,
+        _ => return Err(invalid("unknown ELF byte order")),
+    };
+    let elf = Fields { class, big_endian };
+    let header = &header[..read];
+
+    // e_phoff, e_phentsize, e_phnum, and the smallest program header that
+    // holds the fields read below
+    let (phoff, phentsize, phnum, min_phentsize) = match class {
+        ElfClass::Elf32 => (
+            elf.word(header, 0x1C)?,
+            elf.u16(header, 0x2A)?,
+            elf.u16(header, 0x2C)?,
+            32,
+        ),
+        ElfClass::Elf64 => (
+            elf.word(header, 0x20)?,
+            elf.u16(header, 0x36)?,
+            elf.u16(header, 0x38)?,
+            56,
+        ),
+    };
+    let mut dependencies = ElfDependencies {
+        class,
+        needed: Vec::new(),
+        runpath: Vec::new(),
+    };
+    if phnum == 0 {
+        return Ok(Some(dependencies));
+    }
+    if (phentsize as usize) < min_phentsize {
+        return Err(invalid("ELF program headers too small"));
+    }
+
+    let table = read_at(reader, phoff, phentsize as u64 * phnum as u64)?;
+    let mut segments = Vec::new();
+    let mut dynamic = None;
+    for entry in table.chunks_exact(phentsize as usize) {
+        // p_offset, p_vaddr, p_filesz
+        let (offset, vaddr, filesz) = match class {
+            ElfClass::Elf32 => (
+                elf.word(entry, 4)?,
+                elf.word(entry, 8)?,
+                elf.word(entry, 16)?,
+            ),
+            ElfClass::Elf64 => (
+                elf.word(entry, 8)?,
+                elf.word(entry, 16)?,
+                elf.word(entry, 32)?,
+            ),
+        };
+        match elf.u32(entry, 0)? {
+            PT_LOAD => segments.push(Segment {
+                offset,
+                vaddr,
+                filesz,
+            }),
+            PT_DYNAMIC => dynamic = Some((offset, filesz)),
+            _ => {}
+        }
+    }
+    // Statically linked
+    let Some((dynamic_offset, dynamic_size)) = dynamic else {
+        return Ok(Some(dependencies));
+    };
+
+    let section = read_at(reader, dynamic_offset, dynamic_size)?;
+    let word = elf.word_size();
+    let mut needed = Vec::new();
+    let mut runpath = None;
+    let mut rpath = None;
+    let mut strtab = None;
+    let mut strsz = None;
+    for entry in section.chunks_exact(word * 2) {
+        let value = elf.word(entry, word)?;
+        match elf.word(entry, 0)? {
+            DT_NULL => break,
+            DT_NEEDED => needed.push(value),
+            DT_STRTAB => strtab = Some(value),
+            DT_STRSZ => strsz = Some(value),
+            DT_RPATH => rpath = Some(value),
+            DT_RUNPATH => runpath = Some(value),
+            _ => {}
+        }
+    }
+    // The loader ignores DT_RPATH when DT_RUNPATH is present
+    let search = runpath.or(rpath);
+    if needed.is_empty() && search.is_none() {
+        return Ok(Some(dependencies));
+    }
+
+    let (Some(strtab), Some(strsz)) = (strtab, strsz) else {
+        return Err(invalid("ELF dynamic section without a string table"));
+    };
+    let strtab_offset = segments
+        .iter()
+        .find(|segment| strtab >= segment.vaddr && strtab - segment.vaddr < segment.filesz)
+        .map(|segment| segment.offset + (strtab - segment.vaddr))
+        .ok_or_else(|| invalid("ELF string table outside every loaded segment"))?;
+    let strings = read_at(reader, strtab_offset, strsz)?;
+    let string = |offset: u64| -> io::Result<String> {
+        let rest = strings
+            .get(offset as usize..)
+            .ok_or_else(|| invalid("ELF string offset out of range"))?;
+        let end = rest.iter().position(|b| *b == 0).unwrap_or(rest.len());
+        Ok(String::from_utf8_lossy(&rest[..end]).into_owned())
+    };
+
+    for offset in needed {
+        dependencies.needed.push(string(offset)?);
+    }
+    if let Some(offset) = search {
+        dependencies.runpath = string(offset)?
+            .split(':')
+            .filter(|dir| !dir.is_empty())
+            .map(str::to_string)
+            .collect();
+    }
+    Ok(Some(dependencies))
+}
+
+/// Where the loader looks for libraries that no runpath names
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LibraryPaths {
+    pub lib64: Vec<PathBuf>,
+    pub lib32: Vec<PathBuf>,
+}
+
+impl LibraryPaths {
+    /// Arch's library directories plus those listed in `/etc/ld.so.conf.d`,
+    /// which the loader searches for both classes
+    pub fn system() -> Self {
+        let mut paths = Self {
+            lib64: ["/usr/lib", "/usr/lib64", "/lib", "/lib64"]
+                .iter()
+                .map(PathBuf::from)
+                .collect(),
+            lib32: ["/usr/lib32", "/lib32"].iter().map(PathBuf::from).collect(),
+        };
+        let configs = glob::glob("/etc/ld.so.conf.d/*.conf")
+            .into_iter()
+            .flatten()
+            .flatten();
+        for config in configs {
+            let Ok(text) = std::fs::read_to_string(&config) else {
+                continue;
+            };
+            for line in text.lines() {
+                let dir = line.split('#').next().unwrap_or_default().trim();
+                if dir.starts_with('/') {
+                    paths.lib64.push(PathBuf::from(dir));
+                    paths.lib32.push(PathBuf::from(dir));
+                }
+            }
+        }
+        paths
+    }
+
+    fn for_class(&self, class: ElfClass) -> &[PathBuf] {
+        match class {
+            ElfClass::Elf32 => &self.lib32,
+            ElfClass::Elf64 => &self.lib64,
+        }
+    }
+}
+
+/// Whether the loader would find `soname` for the object at `path`
+fn resolves(
+    soname: &str,
+    path: &Path,
+    dependencies: &ElfDependencies,
+    bundled: &BTreeSet<String>,
+    paths: &LibraryPaths,
+) -> bool {
+    // A path is opened as is; a relative one depends on the working directory
+    if soname.contains('/') {
+        return !soname.starts_with('/') || Path::new(soname).exists();
+    }
+    if bundled.contains(soname) {
+        return true;
+    }
+    let origin = path
+        .parent()
+        .map(|dir| dir.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let runpath = dependencies.runpath.iter().map(|dir| {
+        PathBuf::from(
+            dir.replace("${ORIGIN}", &origin)
+                .replace("$ORIGIN", &origin),
+        )
+    });
+    runpath
+        .chain(paths.for_class(dependencies.class).iter().cloned())
+        .any(|dir| dir.join(soname).exists())
+}
+
+/// The sonames `path` needs that the loader would not find; empty for
+/// scripts and anything else that is not ELF. `bundled` holds the file names
+/// of libraries the same package ships.
+pub fn check_file(
+    path: &Path,
+    bundled: &BTreeSet<String>,
+    paths: &LibraryPaths,
+) -> io::Result<Vec<String>> {
+    let mut file = std::fs::File::open(path)?;
+    let Some(dependencies) = read_dependencies(&mut file)? else {
+        return Ok(Vec::new());
+    };
+    Ok(dependencies
+        .needed
+        .iter()
+        .filter(|soname| !resolves(soname, path, &dependencies, bundled, paths))
+        .cloned()
+        .collect())
+}
+
+/// A file that needs shared objects the loader can't find
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BrokenFile {
+    pub path: PathBuf,
+    pub missing: Vec<String>,
+}
+
+/// A foreign package to rebuild against the current libraries
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RebuildNeeded {
+    pub package: String,
+    pub files: Vec<BrokenFile>,
+}
+
+impl RebuildNeeded {
+    /// Every missing soname across the package's files, once each
+    pub fn missing(&self) -> Vec<&str> {
+        self.files
+            .iter()
+            .flat_map(|file| file.missing.iter().map(String::as_str))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+}
+
+/// Check every regular file of each package; packages with nothing missing
+/// are left out. Files that can't be read or parsed are skipped.
+pub fn scan_packages(
+    files: &BTreeMap<String, Vec<PathBuf>>,
+    paths: &LibraryPaths,
+) -> Vec<RebuildNeeded> {
+    let mut rebuilds = Vec::new();
+    for (package, owned) in files {
+        let bundled: BTreeSet<String> = owned
+            .iter()
+            .filter_map(|path| path.file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .collect();
+        let mut broken = Vec::new();
+        for path in owned {
+            // Symlinks are checked through the file they point at
+            if !std::fs::symlink_metadata(path).is_ok_and(|meta| meta.is_file()) {
+                continue;
+            }
+            match check_file(path, &bundled, paths) {
+                Ok(missing) if !missing.is_empty() => broken.push(BrokenFile {
+                    path: path.clone(),
+                    missing,
+                }),
+                Ok(_) => {}
+                Err(e) => tracing::debug!("Skipping {}: {}", path.display(), e),
+            }
+        }
+        if !broken.is_empty() {
+            rebuilds.push(RebuildNeeded {
+                package: package.clone(),
+                files: broken,
+            });
+        }
+    }
+    rebuilds
+}
+
+/// `pacman -Ql` output as the files of each package; directories are left out
+pub fn parse_file_list(text: &str) -> BTreeMap<String, Vec<PathBuf>> {
+    let mut files: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    for line in text.lines() {
+        let Some((package, path)) = line.split_once(' ') else {
+            continue;
+        };
+        if path.is_empty() || path.ends_with('/') {
+            continue;
+        }
+        files
+            .entry(package.to_string())
+            .or_default()
+            .push(PathBuf::from(path));
+    }
+    files
+}
+
+/// Foreign packages that need a rebuild, as of `checked_at`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RebuildReport {
+    pub checked_at: DateTime<Utc>,
+    pub packages: Vec<RebuildNeeded>,
+}
+
+impl RebuildReport {
+    pub fn is_empty(&self) -> bool {
+        self.packages.is_empty()
+    }
+
+    /// One line naming each package and what it is missing
+    pub fn summary(&self) -> String {
+        if self.packages.is_empty() {
+            return "No AUR packages need a rebuild".to_string();
+        }
+        let packages: Vec<String> = self
+            .packages
+            .iter()
+            .map(|rebuild| format!("{} ({})", rebuild.package, rebuild.missing().join(", ")))
+            .collect();
+        format!(
+            "{} AUR package{} need{} a rebuild: {}",
+            self.packages.len(),
+            if self.packages.len() == 1 { "" } else { "s" },
+            if self.packages.len() == 1 { "s" } else { "" },
+            packages.join(", ")
+        )
+    }
+
+    /// Check only the files this report lists again. A file that is gone or
+    /// whose libraries now resolve drops out, and so does a package with no
+    /// broken files left, e.g. after it was rebuilt or removed.
+    pub fn recheck(&self, paths: &LibraryPaths) -> RebuildReport {
+        let no_bundled = BTreeSet::new();
+        let packages = self
+            .packages
+            .iter()
+            .filter_map(|rebuild| {
+                let files: Vec<BrokenFile> = rebuild
+                    .files
+                    .iter()
+                    .filter_map(|file| {
+                        let still_missing = check_file(&file.path, &no_bundled, paths).ok()?;
+                        let missing: Vec<String> = still_missing
+                            .into_iter()
+                            .filter(|soname| file.missing.contains(soname))
+                            .collect();
+                        (!missing.is_empty()).then(|| BrokenFile {
+                            path: file.path.clone(),
+                            missing,
+                        })
+                    })
+                    .collect();
+                (!files.is_empty()).then(|| RebuildNeeded {
+                    package: rebuild.package.clone(),
+                    files,
+                })
+            })
+            .collect();
+        RebuildReport {
+            checked_at: Utc::now(),
+            packages,
+        }
+    }
+}
+
+/// Check the files of every foreign (AUR or locally built) package
+pub async fn scan_foreign(
+    runner: &dyn CommandRunner,
+    paths: &LibraryPaths,
+) -> Result<RebuildReport> {
+    let checked_at = Utc::now();
+    let output = runner.output("pacman", &["-Qmq"]).await?;
+    let text = OutputText::decode(&output);
+    // pacman exits 1 without a word when no package is foreign
+    if !output.status.success() && !text.stderr.trim().is_empty() {
+        anyhow::bail!("pacman -Qm failed: {}", text.stderr.trim());
+    }
+    let foreign: Vec<&str> = text
+        .stdout
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    if foreign.is_empty() {
+        return Ok(RebuildReport {
+            checked_at,
+            packages: Vec::new(),
+        });
+    }
+
+    let mut args = vec!["-Ql"];
+    args.extend(foreign.iter().copied());
+    let output = runner.output("pacman", &args).await?;
+    let listing = OutputText::decode(&output);
+    if !output.status.success() {
+        anyhow::bail!("pacman -Ql failed: {}", listing.stderr.trim());
+    }
+
+    let files = parse_file_list(&listing.stdout);
+    let paths = paths.clone();
+    let packages = tokio::task::spawn_blocking(move || scan_packages(&files, &paths)).await?;
+    Ok(RebuildReport {
+        checked_at,
+        packages,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jarvis_core::exec::{RecordingRunner, fake_output};
+    use std::io::Cursor;
+    use uuid::Uuid;
+
+    /// x86_64 PIE needing libicuuc.so.72 and libc.so.6, runpath `$ORIGIN/../lib`
+    const UCONV: &[u8] = include_bytes!("../tests/fixtures/soname/uconv");
+    /// i386 shared object needing libreadline.so.8, runpath /opt/game/lib
+    const LIBGAME32: &[u8] = include_bytes!("../tests/fixtures/soname/libgame32.so");
+    const LAUNCHER: &[u8] = include_bytes!("../tests/fixtures/soname/launcher.sh");
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("jarvis-soname-{}-{}", name, Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn install(path: &Path, content: &[u8]) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    /// A filesystem where libc and the ICU of the last update exist, but the
+    /// ICU uconv was linked against does not
+    fn after_icu_bump(root: &Path) -> LibraryPaths {
+        install(&root.join("usr/lib/libc.so.6"), b"");
+        install(&root.join("usr/lib/libicuuc.so.74"), b"");
+        std::fs::create_dir_all(root.join("usr/lib32")).unwrap();
+        LibraryPaths {
+            lib64: vec![root.join("usr/lib")],
+            lib32: vec![root.join("usr/lib32")],
+        }
+    }
+
+    #[test]
+    fn test_reads_elf64_needed_and_runpath() {
+        let deps = read_dependencies(&mut Cursor::new(UCONV)).unwrap().unwrap();
+        assert_eq!(deps.class, ElfClass::Elf64);
+        assert_eq!(deps.needed, ["libicuuc.so.72", "libc.so.6"]);
+        assert_eq!(deps.runpath, ["$ORIGIN/../lib"]);
+    }
+
+    #[test]
+    fn test_reads_elf32_needed() {
+        let deps = read_dependencies(&mut Cursor::new(LIBGAME32))
+            .unwrap()
+            .unwrap();
+        assert_eq!(deps.class, ElfClass::Elf32);
+        assert_eq!(deps.needed, ["libreadline.so.8"]);
+        assert_eq!(deps.runpath, ["/opt/game/lib"]);
+    }
+
+    #[test]
+    fn test_skips_scripts_and_rejects_truncated_elf() {
+        assert_eq!(read_dependencies(&mut Cursor::new(LAUNCHER)).unwrap(), None);
+        assert_eq!(read_dependencies(&mut Cursor::new(b"\x7fE")).unwrap(), None);
+        assert_eq!(read_dependencies(&mut Cursor::new(b"")).unwrap(), None);
+
+        let err = read_dependencies(&mut Cursor::new(&UCONV[..40])).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        // The header is intact but the program headers it points at are not
+        let err = read_dependencies(&mut Cursor::new(&UCONV[..100])).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_finds_missing_sonames_per_class() {
+        let root = scratch("class");
+        let paths = after_icu_bump(&root);
+        let uconv = root.join("usr/bin/uconv");
+        let game = root.join("opt/game/lib/libgame32.so");
+        let launcher = root.join("usr/bin/game");
+        install(&uconv, UCONV);
+        install(&game, LIBGAME32);
+        install(&launcher, LAUNCHER);
+        // Only the 64-bit readline is installed, which a 32-bit library can't load
+        install(&root.join("usr/lib/libreadline.so.8"), b"");
+
+        let none = BTreeSet::new();
+        assert_eq!(
+            check_file(&uconv, &none, &paths).unwrap(),
+            ["libicuuc.so.72"]
+        );
+        assert_eq!(
+            check_file(&game, &none, &paths).unwrap(),
+            ["libreadline.so.8"]
+        );
+        assert!(check_file(&launcher, &none, &paths).unwrap().is_empty());
+
+        install(&root.join("usr/lib32/libreadline.so.8"), b"");
+        assert!(check_file(&game, &none, &paths).unwrap().is_empty());
+        // $ORIGIN/../lib is usr/lib, relative to uconv
+        install(&root.join("usr/lib/libicuuc.so.72"), b"");
+        let runpath_only = LibraryPaths::default();
+        assert!(check_file(&uconv, &none, &runpath_only).unwrap().is_empty());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_scan_reports_packages_and_counts_bundled_libraries() {
+        let root = scratch("scan");
+        let paths = after_icu_bump(&root);
+        let uconv = root.join("opt/icu-tools/bin/uconv");
+        install(&uconv, UCONV);
+        install(&root.join("opt/icu-tools/bin/uconv.sh"), LAUNCHER);
+        let game = root.join("opt/game/libgame32.so");
+        install(&game, LIBGAME32);
+        install(&root.join("opt/game/libs/libreadline.so.8"), b"");
+        std::os::unix::fs::symlink(&uconv, root.join("uconv-link")).unwrap();
+
+        let files = BTreeMap::from([
+            (
+                "icu-tools".to_string(),
+                vec![
+                    root.join("opt/icu-tools/bin/"),
+                    uconv.clone(),
+                    root.join("opt/icu-tools/bin/uconv.sh"),
+                    root.join("uconv-link"),
+                ],
+            ),
+            // Ships its own readline next to the library
+            (
+                "game-bin".to_string(),
+                vec![game.clone(), root.join("opt/game/libs/libreadline.so.8")],
+            ),
+        ]);
+        let rebuilds = scan_packages(&files, &paths);
+        assert_eq!(
+            rebuilds,
+            [RebuildNeeded {
+                package: "icu-tools".to_string(),
+                files: vec![BrokenFile {
+                    path: uconv.clone(),
+                    missing: vec!["libicuuc.so.72".to_string()],
+                }],
+            }]
+        );
+        assert_eq!(rebuilds[0].missing(), ["libicuuc.so.72"]);
+
+        let report = RebuildReport {
+            checked_at: Utc::now(),
+            packages: rebuilds,
+        };
+        assert_eq!(
+            report.summary(),
+            "1 AUR package needs a rebuild: icu-tools (libicuuc.so.72)"
+        );
+        assert_eq!(report.recheck(&paths).packages, report.packages);
+
+        // A rebuild against ICU 74 fixes it; here the old soname coming back does
+        install(&root.join("usr/lib/libicuuc.so.72"), b"");
+        assert!(report.recheck(&paths).is_empty());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_parse_file_list_skips_directories() {
+        let files = parse_file_list(
+            "icu-tools /opt/icu-tools/\n\
+             icu-tools /opt/icu-tools/bin/uconv\n\
+             game-bin /opt/game/bin/game\n\
+             game-bin /opt/game/lib/libgame32.so\n",
+        );
+        assert_eq!(files.len(), 2);
+        assert_eq!(
+            files["icu-tools"],
+            [PathBuf::from("/opt/icu-tools/bin/uconv")]
+        );
+        assert_eq!(files["game-bin"].len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_scan_foreign_lists_packages_with_pacman() {
+        let root = scratch("foreign");
+        let paths = after_icu_bump(&root);
+        let uconv = root.join("opt/icu-tools/bin/uconv");
+        install(&uconv, UCONV);
+
+        let runner = RecordingRunner::new()
+            .respond("pacman -Qmq", "icu-tools\nyay-bin\n")
+            .respond(
+                "pacman -Ql",
+                &format!(
+                    "icu-tools {}/\nicu-tools {}\n",
+                    root.display(),
+                    uconv.display()
+                ),
+            );
+        let report = scan_foreign(&runner, &paths).await.unwrap();
+        assert_eq!(
+            runner.calls(),
+            ["pacman -Qmq", "pacman -Ql icu-tools yay-bin"]
+        );
+        assert_eq!(report.packages.len(), 1);
+        assert_eq!(report.packages[0].missing(), ["libicuuc.so.72"]);
+
+        // No foreign packages: pacman exits 1 and there is nothing to list
+        let runner = RecordingRunner::new().respond_with("pacman -Qmq", fake_output(1, "", ""));
+        let report = scan_foreign(&runner, &paths).await.unwrap();
+        assert!(report.is_empty());
+        assert_eq!(runner.calls(), ["pacman -Qmq"]);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,314 @@
+//! Per-subsystem initialization state
+//!
+//! Each agent component starts on its own. A component that fails to come up
+//! is recorded here instead of aborting startup, so package management keeps
+//! working when, say, Wazuh is misconfigured, and operations that need the
+//! missing component fail with the reason it is unavailable.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::{AgentCapability, ArchOperation};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Subsystem {
+    Database,
+    PackageManager,
+    Aur,
+    SystemHealth,
+    SecurityScanner,
+    MaintenanceScheduler,
+    VulnerabilityScanner,
+    ServiceManager,
+    Wazuh,
+}
+
+impl Subsystem {
+    /// Initialization order; Wazuh builds on the package manager
+    pub const ALL: [Subsystem; 9] = [
+        Subsystem::Database,
+        Subsystem::PackageManager,
+        Subsystem::Aur,
+        Subsystem::SystemHealth,
+        Subsystem::SecurityScanner,
+        Subsystem::MaintenanceScheduler,
+        Subsystem::VulnerabilityScanner,
+        Subsystem::ServiceManager,
+        Subsystem::Wazuh,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Subsystem::Database => "database",
+            Subsystem::PackageManager => "package_manager",
+            Subsystem::Aur => "aur",
+            Subsystem::SystemHealth => "system_health",
+            Subsystem::SecurityScanner => "security_scanner",
+            Subsystem::MaintenanceScheduler => "maintenance_scheduler",
+            Subsystem::VulnerabilityScanner => "vulnerability_scanner",
+            Subsystem::ServiceManager => "service_manager",
+            Subsystem::Wazuh => "wazuh",
+        }
+    }
+
+    /// Parse a subsystem name; dashes are accepted in place of underscores
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase().replace('-', "_");
+        Self::ALL.into_iter().find(|s| s.name() == name)
+    }
+
+    /// What the agent can do while this subsystem is up
+    pub fn capabilities(self) -> &'static [AgentCapability] {
+        match self {
+            Subsystem::Database => &[AgentCapability::DatabasePersistence],
+            Subsystem::PackageManager => &[AgentCapability::PackageManagement],
+            Subsystem::Aur => &[AgentCapability::AURSupport],
+            Subsystem::SystemHealth => &[
+                AgentCapability::SystemMonitoring,
+                AgentCapability::PerformanceAnalysis,
+            ],
+            Subsystem::SecurityScanner => &[AgentCapability::SecurityScanning],
+            Subsystem::MaintenanceScheduler => &[
+                AgentCapability::MaintenanceScheduling,
+                AgentCapability::AutomatedMaintenance,
+            ],
+            Subsystem::VulnerabilityScanner => &[AgentCapability::VulnerabilityAssessment],
+            Subsystem::ServiceManager => &[AgentCapability::ServiceManagement],
+            Subsystem::Wazuh => &[AgentCapability::WazuhIntegration],
+        }
+    }
+
+    /// Subsystems an operation cannot run without
+    pub fn required_by(operation: &ArchOperation) -> &'static [Subsystem] {
+        match operation {
+            ArchOperation::InstallPackage { from_aur: true, .. }
+            | ArchOperation::RebuildAurPackages { .. }
+            | ArchOperation::AURSecurityCheck { .. } => &[Subsystem::Aur],
+            ArchOperation::UpdatePackages { .. }
+            | ArchOperation::InstallPackage { .. }
+            | ArchOperation::RemovePackage { .. }
+            | ArchOperation::SearchPackages { .. }
+            | ArchOperation::StageUpdates => &[Subsystem::PackageManager],
+            ArchOperation::ApplyStagedUpdates => &[Subsystem::PackageManager, Subsystem::Database],
+            ArchOperation::SecurityScan { .. } => &[Subsystem::SecurityScanner],
+            ArchOperation::HealthCheck { .. } | ArchOperation::PerformanceAnalysis { .. } => {
+                &[Subsystem::SystemHealth]
+            }
+            ArchOperation::ServiceOperation { .. } | ArchOperation::ListServices { .. } => {
+                &[Subsystem::ServiceManager]
+            }
+            _ => &[],
+        }
+    }
+}
+
+impl fmt::Display for Subsystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubsystemStatus {
+    pub name: String,
+    /// Turned on in configuration
+    pub enabled: bool,
+    pub initialized: bool,
+    /// Error from the most recent initialization attempt
+    pub last_error: Option<String>,
+}
+
+/// A requested operation needs a subsystem that did not come up
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("{subsystem} is not available: {reason}")]
+pub struct NotAvailable {
+    pub subsystem: Subsystem,
+    pub reason: String,
+}
+
+/// Status of every subsystem the agent has tried to start
+#[derive(Debug, Clone, Default)]
+pub struct Subsystems {
+    statuses: HashMap<Subsystem, SubsystemStatus>,
+}
+
+impl Subsystems {
+    pub fn record_ready(&mut self, subsystem: Subsystem) {
+        self.record(subsystem, true, true, None);
+    }
+
+    pub fn record_disabled(&mut self, subsystem: Subsystem) {
+        self.record(subsystem, false, false, None);
+    }
+
+    pub fn record_failed(&mut self, subsystem: Subsystem, error: String) {
+        self.record(subsystem, true, false, Some(error));
+    }
+
+    fn record(
+        &mut self,
+        subsystem: Subsystem,
+        enabled: bool,
+        initialized: bool,
+        last_error: Option<String>,
+    ) {
+        self.statuses.insert(
+            subsystem,
+            SubsystemStatus {
+                name: subsystem.name().to_string(),
+                enabled,
+                initialized,
+                last_error,
+            },
+        );
+    }
+
+    pub fn status(&self, subsystem: Subsystem) -> SubsystemStatus {
+        self.statuses
+            .get(&subsystem)
+            .cloned()
+            .unwrap_or_else(|| SubsystemStatus {
+                name: subsystem.name().to_string(),
+                enabled: true,
+                initialized: false,
+                last_error: None,
+            })
+    }
+
+    /// Every subsystem in initialization order
+    pub fn list(&self) -> Vec<SubsystemStatus> {
+        Subsystem::ALL.into_iter().map(|s| self.status(s)).collect()
+    }
+
+    /// Enabled subsystems that failed to initialize
+    pub fn failed(&self) -> Vec<Subsystem> {
+        Subsystem::ALL
+            .into_iter()
+            .filter(|s| {
+                let status = self.status(*s);
+                status.enabled && !status.initialized
+            })
+            .collect()
+    }
+
+    pub fn is_ready(&self, subsystem: Subsystem) -> bool {
+        self.status(subsystem).initialized
+    }
+
+    /// Ok when `subsystem` is up, otherwise why it is not
+    pub fn check(&self, subsystem: Subsystem) -> Result<(), NotAvailable> {
+        let status = self.status(subsystem);
+        let reason = match (status.enabled, status.initialized, status.last_error) {
+            (_, true, _) => return Ok(()),
+            (false, _, _) => "disabled in configuration".to_string(),
+            (true, false, Some(error)) => format!("initialization failed: {}", error),
+            (true, false, None) => "not initialized".to_string(),
+        };
+        Err(NotAvailable { subsystem, reason })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_reports_why_subsystem_is_missing() {
+        let mut subsystems = Subsystems::default();
+        subsystems.record_ready(Subsystem::PackageManager);
+        subsystems.record_disabled(Subsystem::Aur);
+        subsystems.record_failed(Subsystem::Wazuh, "connection refused".to_string());
+
+        assert!(subsystems.check(Subsystem::PackageManager).is_ok());
+        assert_eq!(
+            subsystems.check(Subsystem::Aur).unwrap_err().to_string(),
+            "aur is not available: disabled in configuration"
+        );
+        assert_eq!(
+            subsystems.check(Subsystem::Wazuh).unwrap_err().to_string(),
+            "wazuh is not available: initialization failed: connection refused"
+        );
+        assert!(subsystems.check(Subsystem::Database).is_err());
+    }
+
+    #[test]
+    fn test_failed_ignores_disabled_subsystems() {
+        let mut subsystems = Subsystems::default();
+        for subsystem in Subsystem::ALL {
+            subsystems.record_ready(subsystem);
+        }
+        subsystems.record_disabled(Subsystem::Aur);
+        assert!(subsystems.failed().is_empty());
+
+        subsystems.record_failed(Subsystem::VulnerabilityScanner, "boom".to_string());
+        assert_eq!(subsystems.failed(), vec![Subsystem::VulnerabilityScanner]);
+        assert_eq!(subsystems.list().len(), Subsystem::ALL.len());
+    }
+
+    #[test]
+    fn test_required_by() {
+        let aur_install = ArchOperation::InstallPackage {
+            package: "yay".to_string(),
+            from_aur: true,
+        };
+        let install = ArchOperation::InstallPackage {
+            package: "ripgrep".to_string(),
+            from_aur: false,
+        };
+        assert_eq!(Subsystem::required_by(&aur_install), &[Subsystem::Aur]);
+        assert_eq!(
+            Subsystem::required_by(&install),
+            &[Subsystem::PackageManager]
+        );
+        assert!(Subsystem::required_by(&ArchOperation::ListFlatpaks).is_empty());
+    }
+
+    #[test]
+    fn test_capabilities_follow_running_subsystems() {
+        use crate::{ArchAgent, ArchLinuxAgent};
+        use AgentCapability::*;
+
+        assert_eq!(ArchLinuxAgent::new().capabilities(), AgentCapability::BASE);
+
+        assert_eq!(
+            AgentCapability::for_subsystems(&[Subsystem::Wazuh, Subsystem::PackageManager]),
+            vec![
+                ConfigurationManagement,
+                LogAnalysis,
+                PackageManagement,
+                WazuhIntegration
+            ]
+        );
+        assert_eq!(
+            AgentCapability::for_subsystems(&[
+                Subsystem::Database,
+                Subsystem::MaintenanceScheduler
+            ]),
+            vec![
+                ConfigurationManagement,
+                LogAnalysis,
+                DatabasePersistence,
+                MaintenanceScheduling,
+                AutomatedMaintenance
+            ]
+        );
+
+        // Every subsystem contributes something, and nothing twice
+        let all = AgentCapability::for_subsystems(&Subsystem::ALL);
+        let unique: std::collections::HashSet<_> = all.iter().collect();
+        assert_eq!(unique.len(), all.len());
+        assert_eq!(all.len(), 13);
+    }
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(
+            Subsystem::from_name("package-manager"),
+            Some(Subsystem::PackageManager)
+        );
+        assert_eq!(Subsystem::from_name("Wazuh"), Some(Subsystem::Wazuh));
+        assert_eq!(Subsystem::from_name("gpu"), None);
+    }
+}
@@ -1,21 +1,39 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::fs;
-use std::process::Command;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use tokio::net::TcpStream;
 use tokio::io::{AsyncWriteExt, BufWriter};
 use tracing::{debug, error, info, warn};
 
+use jarvis_core::package_cache::PackageCache;
+use jarvis_core::severity::Severity;
+use jarvis_core::trivy::ImageScan;
+use jarvis_core::vuln::Acknowledgements;
+use jarvis_core::MemoryStore;
+
 use crate::config::WazuhConfig;
+use crate::findings::ScanLifecycle;
 use crate::package_manager::{PackageInfo, PackageManager};
 
 /// Wazuh integration for security monitoring and AUR package tracking
 pub struct WazuhIntegration {
     config: WazuhConfig,
     package_manager: PackageManager,
+    /// Jarvis memory store holding `jarvis vuln ack` acknowledgements
+    memory: Option<MemoryStore>,
+    /// Events the manager didn't take, oldest first, resent by `flush_spool`
+    spool: Mutex<VecDeque<WazuhLogEntry>>,
+    spool_path: PathBuf,
 }
 
+/// Most events kept while the manager is unreachable; the oldest go first
+const SPOOL_LIMIT: usize = 1000;
+/// Where spooled events wait between runs of the service
+const SPOOL_PATH: &str = "/var/lib/jarvis/wazuh-spool.jsonl";
+
 /// Security event types for Wazuh SIEM
 #[derive(Debug, Serialize, Deserialize)]
 pub enum SecurityEvent {
@@ -40,8 +58,26 @@ pub enum SecurityEvent {
         package_name: String,
         version: String,
         vulnerability_id: String,
-        severity: String,
+        severity: Severity,
+        description: String,
+        /// Accepted with `jarvis vuln ack`; still reported so the SIEM keeps the full picture
+        acknowledged: bool,
+        acknowledgement_reason: Option<String>,
+        acknowledged_until: Option<chrono::NaiveDate>,
+    },
+    /// Vulnerable package in a container image, from the last Trivy scan
+    VulnerableImage {
+        image: String,
+        digest: String,
+        package_name: String,
+        version: String,
+        fixed_version: Option<String>,
+        vulnerability_id: String,
+        severity: Severity,
         description: String,
+        acknowledged: bool,
+        acknowledgement_reason: Option<String>,
+        acknowledged_until: Option<chrono::NaiveDate>,
     },
     /// Suspicious package behavior
     SuspiciousActivity {
@@ -56,9 +92,20 @@ pub enum SecurityEvent {
         description: String,
         packages_affected: Vec<String>,
     },
+    /// A security scan finding appeared or went away
+    SecurityFinding {
+        fingerprint: String,
+        check: String,
+        subject: String,
+        severity: Severity,
+        description: String,
+        /// `new` or `resolved`
+        state: String,
+        first_seen: chrono::DateTime<chrono::Utc>,
+    },
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum RiskLevel {
     Low,
     Medium,
@@ -66,8 +113,57 @@ pub enum RiskLevel {
     Critical,
 }
 
+impl From<RiskLevel> for Severity {
+    fn from(risk: RiskLevel) -> Self {
+        match risk {
+            RiskLevel::Low => Severity::Low,
+            RiskLevel::Medium => Severity::Medium,
+            RiskLevel::High => Severity::High,
+            RiskLevel::Critical => Severity::Critical,
+        }
+    }
+}
+
+impl From<Severity> for RiskLevel {
+    fn from(severity: Severity) -> Self {
+        match severity {
+            Severity::Info | Severity::Low => RiskLevel::Low,
+            Severity::Medium => RiskLevel::Medium,
+            Severity::High => RiskLevel::High,
+            Severity::Critical => RiskLevel::Critical,
+        }
+    }
+}
+
+impl SecurityEvent {
+    /// Canonical severity; inventory events are informational
+    pub fn severity(&self) -> Severity {
+        match self {
+            SecurityEvent::VulnerablePackage { severity, .. }
+            | SecurityEvent::VulnerableImage { severity, .. } => *severity,
+            SecurityEvent::SuspiciousActivity { risk_level, .. } => (*risk_level).into(),
+            SecurityEvent::SecurityFinding { state, .. } if state == "resolved" => Severity::Info,
+            SecurityEvent::SecurityFinding { severity, .. } => *severity,
+            SecurityEvent::AurPackageInstalled { .. }
+            | SecurityEvent::PackageUpdated { .. }
+            | SecurityEvent::MaintenanceEvent { .. } => Severity::Info,
+        }
+    }
+}
+
+/// The `level` field of a Wazuh log entry
+fn wazuh_level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Info => "INFO",
+        Severity::Low => "NOTICE",
+        Severity::Medium => "WARNING",
+        Severity::High => "ERROR",
+        Severity::Critical => "CRITICAL",
+    }
+}
+
 /// Wazuh log entry structure
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 struct WazuhLogEntry {
     timestamp: chrono::DateTime<chrono::Utc>,
     level: String,
@@ -84,9 +180,29 @@ impl WazuhIntegration {
         Self {
             config,
             package_manager,
+            memory: None,
+            spool: Mutex::new(VecDeque::new()),
+            spool_path: PathBuf::from(SPOOL_PATH),
         }
     }
 
+    /// Read vulnerability acknowledgements from the Jarvis memory store
+    pub fn with_memory(mut self, memory: MemoryStore) -> Self {
+        self.memory = Some(memory);
+        self
+    }
+
+    /// Current acknowledgements; reloaded per scan so new and expired ones take effect
+    async fn load_acknowledgements(&self) -> Acknowledgements {
+        let Some(memory) = &self.memory else {
+            return Acknowledgements::default();
+        };
+        Acknowledgements::load(memory).await.unwrap_or_else(|e| {
+            warn!("Ignoring vulnerability acknowledgements: {}", e);
+            Acknowledgements::default()
+        })
+    }
+
     /// Initialize Wazuh integration and perform initial AUR scan
     pub async fn initialize(&self) -> Result<()> {
         if !self.config.enabled {
@@ -96,8 +212,22 @@ impl WazuhIntegration {
 
         info!("Initializing Wazuh integration for AUR package monitoring");
 
+        // Events spooled by the last shutdown go out ahead of new ones
+        match load_spool(&self.spool_path) {
+            Ok(entries) if !entries.is_empty() => {
+                info!("Loaded {} spooled Wazuh events", entries.len());
+                self.lock_spool().extend(entries);
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Ignoring Wazuh spool {}: {}", self.spool_path.display(), e),
+        }
+
         // Test connection to Wazuh manager
         self.test_connection().await?;
+        let sent = self.flush_spool().await?;
+        if sent > 0 {
+            info!("Sent {} spooled Wazuh events", sent);
+        }
 
         // Perform initial AUR package scan
         self.scan_aur_packages().await?;
@@ -129,23 +259,79 @@ impl WazuhIntegration {
         }
     }
 
+    /// Forward the findings a scan added or resolved; ones still present were
+    /// already reported when they first appeared
+    pub async fn report_finding_changes(&self, lifecycle: &ScanLifecycle) -> Result<()> {
+        let changes = lifecycle
+            .new
+            .iter()
+            .map(|f| (f, "new"))
+            .chain(lifecycle.resolved.iter().map(|f| (f, "resolved")));
+        for (finding, state) in changes {
+            self.send_event(SecurityEvent::SecurityFinding {
+                fingerprint: finding.fingerprint.clone(),
+                check: finding.check.clone(),
+                subject: finding.subject.clone(),
+                severity: finding.severity,
+                description: finding.description.clone(),
+                state: state.to_string(),
+                first_seen: finding.first_seen,
+            })
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Forward every advisory of the cached container image scans, marking
+    /// acknowledged ones like package vulnerabilities
+    pub async fn report_image_vulnerabilities(&self, scans: &[ImageScan]) -> Result<()> {
+        let acknowledgements = self.load_acknowledgements().await;
+        let now = chrono::Utc::now();
+
+        for scan in scans {
+            for vuln in &scan.vulnerabilities {
+                let ack = acknowledgements.active(&vuln.id, now);
+                self.send_event(SecurityEvent::VulnerableImage {
+                    image: scan.image.clone(),
+                    digest: scan.digest.clone(),
+                    package_name: vuln.package.clone(),
+                    version: vuln.installed_version.clone(),
+                    fixed_version: vuln.fixed_version.clone(),
+                    vulnerability_id: vuln.id.clone(),
+                    severity: vuln.severity,
+                    description: vuln.title.clone(),
+                    acknowledged: ack.is_some(),
+                    acknowledgement_reason: ack.map(|a| a.reason.clone()),
+                    acknowledged_until: ack.and_then(|a| a.until),
+                }).await?;
+            }
+        }
+        Ok(())
+    }
+
     /// Scan all installed AUR packages and report to Wazuh
     pub async fn scan_aur_packages(&self) -> Result<()> {
         info!("Scanning AUR packages for security monitoring");
 
         let aur_packages = self.get_aur_packages().await?;
         info!("Found {} AUR packages installed", aur_packages.len());
+        let acknowledgements = self.load_acknowledgements().await;
+        let now = chrono::Utc::now();
 
         for package in aur_packages {
             // Check if package has known vulnerabilities
             if let Some(vulnerabilities) = self.check_package_vulnerabilities(&package).await? {
                 for vuln in vulnerabilities {
+                    let ack = acknowledgements.active(&vuln.id, now);
                     self.send_event(SecurityEvent::VulnerablePackage {
                         package_name: package.name.clone(),
                         version: package.version.clone(),
-                        vulnerability_id: vuln.id,
+                        vulnerability_id: vuln.id.clone(),
                         severity: vuln.severity,
                         description: vuln.description,
+                        acknowledged: ack.is_some(),
+                        acknowledgement_reason: ack.map(|a| a.reason.clone()),
+                        acknowledged_until: ack.and_then(|a| a.until),
                     }).await?;
                 }
             }
@@ -166,86 +352,23 @@ impl WazuhIntegration {
         Ok(())
     }
 
-    /// Get list of installed AUR packages
+    /// Installed AUR packages, from the package cache
     async fn get_aur_packages(&self) -> Result<Vec<PackageInfo>> {
-        // Use pacman to get foreign packages (AUR packages)
-        let output = Command::new("pacman")
-            .args(&["-Qm", "--color", "never"])
-            .output()
-            .context("Failed to execute pacman -Qm")?;
-
-        if !output.status.success() {
-            return Ok(vec![]); // No AUR packages installed
-        }
-
-        let stdout = String::from_utf8(output.stdout)?;
-        let mut packages = Vec::new();
-
-        for line in stdout.lines() {
-            if let Some((name, version)) = line.split_once(' ') {
-                let package_info = self.get_package_details(name).await?;
-                packages.push(PackageInfo {
-                    name: name.to_string(),
-                    version: version.to_string(),
-                    ..package_info
-                });
-            }
-        }
-
-        Ok(packages)
-    }
-
-    /// Get detailed package information
-    async fn get_package_details(&self, package_name: &str) -> Result<PackageInfo> {
-        // Use pacman to get package details
-        let output = Command::new("pacman")
-            .args(&["-Qi", package_name])
-            .output()
-            .context("Failed to get package details")?;
-
-        let stdout = String::from_utf8(output.stdout)?;
-        let mut package_info = PackageInfo {
-            name: package_name.to_string(),
-            version: String::new(),
-            description: None,
-            maintainer: None,
-            install_date: None,
-            url: None,
-            dependencies: vec![],
-            size: 0,
-        };
-
-        // Parse pacman output
-        for line in stdout.lines() {
-            if let Some((key, value)) = line.split_once(':') {
-                let key = key.trim();
-                let value = value.trim();
-
-                match key {
-                    "Version" => package_info.version = value.to_string(),
-                    "Description" => package_info.description = Some(value.to_string()),
-                    "Packager" => package_info.maintainer = Some(value.to_string()),
-                    "Install Date" => {
-                        // Parse install date if available
-                        if let Ok(date) = chrono::DateTime::parse_from_str(value, "%a %d %b %Y %I:%M:%S %p %Z") {
-                            package_info.install_date = Some(date.with_timezone(&chrono::Utc));
-                        }
-                    }
-                    "URL" => package_info.url = Some(value.to_string()),
-                    "Installed Size" => {
-                        // Parse size (format: "123.45 KiB")
-                        if let Some(size_str) = value.split_whitespace().next() {
-                            if let Ok(size) = size_str.parse::<f64>() {
-                                package_info.size = (size * 1024.0) as u64; // Convert to bytes
-                            }
-                        }
-                    }
-                    _ => {}
-                }
-            }
-        }
-
-        Ok(package_info)
+        let installed = PackageCache::shared().index().await?;
+        Ok(installed
+            .foreign()
+            .into_iter()
+            .map(|package| PackageInfo {
+                name: package.name.clone(),
+                version: package.version.clone(),
+                description: package.description.clone(),
+                maintainer: package.packager.clone(),
+                install_date: package.install_date,
+                url: package.url.clone(),
+                dependencies: vec![],
+                size: package.installed_size,
+            })
+            .collect())
     }
 
     /// Check for known vulnerabilities in a package
@@ -259,7 +382,7 @@ impl WazuhIntegration {
         if self.is_suspicious_package(package) {
             vulnerabilities.push(Vulnerability {
                 id: format!("JARVIS-SUSP-{}", package.name.to_uppercase()),
-                severity: "medium".to_string(),
+                severity: Severity::Medium,
                 description: "Package exhibits suspicious characteristics".to_string(),
             });
         }
@@ -268,7 +391,7 @@ impl WazuhIntegration {
         if package.version.contains("git") || package.version.contains("dev") {
             vulnerabilities.push(Vulnerability {
                 id: format!("JARVIS-DEV-{}", package.name.to_uppercase()),
-                severity: "low".to_string(),
+                severity: Severity::Low,
                 description: "Development version package may contain unstable code".to_string(),
             });
         }
@@ -358,31 +481,78 @@ impl WazuhIntegration {
 
         let log_entry = WazuhLogEntry {
             timestamp: chrono::Utc::now(),
-            level: "INFO".to_string(),
+            level: wazuh_level(event.severity()).to_string(),
             source: "jarvis-arch".to_string(),
             event_type: match &event {
                 SecurityEvent::AurPackageInstalled { .. } => "aur_install",
                 SecurityEvent::PackageUpdated { .. } => "package_update",
                 SecurityEvent::VulnerablePackage { .. } => "vulnerability",
+                SecurityEvent::VulnerableImage { .. } => "image_vulnerability",
                 SecurityEvent::SuspiciousActivity { .. } => "suspicious_activity",
                 SecurityEvent::MaintenanceEvent { .. } => "maintenance",
+                SecurityEvent::SecurityFinding { .. } => "security_finding",
             }.to_string(),
             data: serde_json::to_value(&event)?,
             host: gethostname::gethostname().to_string_lossy().to_string(),
             agent_name: "jarvis-arch".to_string(),
         };
 
-        // Send to Wazuh manager
-        match self.config.protocol.as_str() {
-            "tcp" => self.send_tcp_event(&log_entry).await?,
-            "udp" => self.send_udp_event(&log_entry).await?,
-            _ => return Err(anyhow::anyhow!("Unsupported Wazuh protocol: {}", self.config.protocol)),
+        // A manager that is down gets the event later from the spool
+        if let Err(e) = self.deliver(&log_entry).await {
+            self.spool_entry(log_entry);
+            return Err(e);
         }
 
         debug!("Sent event to Wazuh: {:?}", event);
         Ok(())
     }
 
+    /// Send to Wazuh manager
+    async fn deliver(&self, entry: &WazuhLogEntry) -> Result<()> {
+        match self.config.protocol.as_str() {
+            "tcp" => self.send_tcp_event(entry).await,
+            "udp" => self.send_udp_event(entry).await,
+            _ => Err(anyhow::anyhow!("Unsupported Wazuh protocol: {}", self.config.protocol)),
+        }
+    }
+
+    fn spool_entry(&self, entry: WazuhLogEntry) {
+        let mut spool = self.lock_spool();
+        if spool.len() >= SPOOL_LIMIT {
+            spool.pop_front();
+        }
+        spool.push_back(entry);
+    }
+
+    fn lock_spool(&self) -> std::sync::MutexGuard<'_, VecDeque<WazuhLogEntry>> {
+        self.spool.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Resend spooled events, oldest first, and save what is still unsent to
+    /// disk for the next start. Returns how many went out.
+    pub async fn flush_spool(&self) -> Result<usize> {
+        let mut pending = std::mem::take(&mut *self.lock_spool());
+        let mut sent = 0;
+        while let Some(entry) = pending.front() {
+            if let Err(e) = self.deliver(entry).await {
+                debug!("Wazuh spool flush stopped: {}", e);
+                break;
+            }
+            pending.pop_front();
+            sent += 1;
+        }
+
+        let mut spool = self.lock_spool();
+        // Events spooled while flushing are newer than the ones left over
+        pending.extend(spool.drain(..));
+        while pending.len() > SPOOL_LIMIT {
+            pending.pop_front();
+        }
+        save_spool(&self.spool_path, pending.iter())?;
+        *spool = pending;
+        Ok(sent)
+    }
+
     /// Send event via TCP to Wazuh manager
     async fn send_tcp_event(&self, entry: &WazuhLogEntry) -> Result<()> {
         let address = format!("{}:{}", self.config.server, self.config.port);
@@ -490,6 +660,93 @@ enum PackageChange {
 #[derive(Debug)]
 struct Vulnerability {
     id: String,
-    severity: String,
+    severity: Severity,
     description: String,
-}
\ No newline at end of file
+}
+
+/// Spooled events saved by `save_spool`; none when there is no file
+fn load_spool(path: &Path) -> Result<Vec<WazuhLogEntry>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).context("Corrupt spooled event"))
+        .collect()
+}
+
+/// Write `entries` one JSON object per line, removing the file when there are none
+fn save_spool<'a>(path: &Path, entries: impl ExactSizeIterator<Item = &'a WazuhLogEntry>) -> Result<()> {
+    if entries.len() == 0 {
+        return match fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        };
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut contents = String::new();
+    for entry in entries {
+        contents.push_str(&serde_json::to_string(entry)?);
+        contents.push('\n');
+    }
+    fs::write(path, contents)
+        .with_context(|| format!("Failed to write Wazuh spool {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spool_survives_a_restart_and_empties_cleanly() {
+        let dir = std::env::temp_dir().join(format!("jarvis-wazuh-spool-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("wazuh-spool.jsonl");
+        let entry = WazuhLogEntry {
+            timestamp: chrono::Utc::now(),
+            level: "ERROR".to_string(),
+            source: "jarvis-arch".to_string(),
+            event_type: "vulnerability".to_string(),
+            data: serde_json::json!({"package_name": "foo"}),
+            host: "arch".to_string(),
+            agent_name: "jarvis-arch".to_string(),
+        };
+
+        assert!(load_spool(&path).unwrap().is_empty());
+        save_spool(&path, [entry.clone(), entry.clone()].iter()).unwrap();
+        assert_eq!(load_spool(&path).unwrap(), vec![entry.clone(), entry]);
+
+        save_spool(&path, std::iter::empty()).unwrap();
+        assert!(!path.exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_event_severity_sets_wazuh_level() {
+        let event = SecurityEvent::SuspiciousActivity {
+            package_name: "xmrig-bin".to_string(),
+            activity_type: "elevated_privileges".to_string(),
+            details: String::new(),
+            risk_level: RiskLevel::High,
+        };
+        assert_eq!(event.severity(), Severity::High);
+        assert_eq!(wazuh_level(event.severity()), "ERROR");
+    }
+
+    #[test]
+    fn test_vulnerability_events_keep_their_wire_format() {
+        // Wazuh rules match the lowercase severity strings sent before the
+        // canonical type existed
+        let json = r#"{"VulnerablePackage":{"package_name":"foo","version":"1.0",
+            "vulnerability_id":"JARVIS-DEV-FOO","severity":"low","description":"",
+            "acknowledged":false,"acknowledgement_reason":null,"acknowledged_until":null}}"#;
+        let event: SecurityEvent = serde_json::from_str(json).unwrap();
+        assert_eq!(event.severity(), Severity::Low);
+        let value = serde_json::to_value(&event).unwrap();
+        assert_eq!(value["VulnerablePackage"]["severity"], "low");
+    }
+}
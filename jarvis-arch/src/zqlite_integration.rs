@@ -10,6 +10,9 @@ use tokio::sync::RwLock;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 use zeroize::Zeroize;
+use jarvis_core::severity::Severity;
+
+use crate::config_backup::BackupSummary;
 
 // FFI function declarations for ZQLite
 extern "C" {
@@ -53,6 +56,8 @@ extern "C" {
         index: u32,
     ) -> i64;
     
+    fn zqlite_column_count(stmt: *mut c_void) -> u32;
+    
     fn zqlite_finalize(stmt: *mut c_void);
     
     fn zqlite_close(db: *mut c_void);
@@ -136,6 +141,15 @@ pub struct MaintenanceRecord {
     pub packages_affected: Vec<String>,
     pub output: String,
     pub error_message: Option<String>,
+    /// Before/after package delta for update transactions
+    #[serde(default)]
+    pub delta: Option<serde_json::Value>,
+    /// Snapper snapshot taken just before the operation
+    #[serde(default)]
+    pub snapshot_id: Option<u32>,
+    /// The operation this one rolled back
+    #[serde(default)]
+    pub rollback_of: Option<Uuid>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -154,6 +168,29 @@ pub enum SecuritySeverity {
     Critical,
 }
 
+impl From<SecuritySeverity> for Severity {
+    fn from(severity: SecuritySeverity) -> Self {
+        match severity {
+            SecuritySeverity::Low => Severity::Low,
+            SecuritySeverity::Medium => Severity::Medium,
+            SecuritySeverity::High => Severity::High,
+            SecuritySeverity::Critical => Severity::Critical,
+        }
+    }
+}
+
+impl From<Severity> for SecuritySeverity {
+    /// Security issues have no informational level; those count as low
+    fn from(severity: Severity) -> Self {
+        match severity {
+            Severity::Info | Severity::Low => SecuritySeverity::Low,
+            Severity::Medium => SecuritySeverity::Medium,
+            Severity::High => SecuritySeverity::High,
+            Severity::Critical => SecuritySeverity::Critical,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum MaintenanceStatus {
     Pending,
@@ -161,6 +198,8 @@ pub enum MaintenanceStatus {
     Completed,
     Failed,
     Cancelled,
+    /// Not run because a pre-condition such as system health was not met
+    Skipped,
 }
 
 impl JarvisDatabase {
@@ -261,6 +300,9 @@ impl JarvisDatabase {
                 packages_affected TEXT, -- JSON array
                 output TEXT,
                 error_message TEXT,
+                transaction_delta TEXT, -- JSON object
+                snapshot_id INTEGER,
+                rollback_of TEXT,
                 created_at TEXT DEFAULT CURRENT_TIMESTAMP,
                 INDEX(operation_type),
                 INDEX(status),
@@ -292,6 +334,20 @@ impl JarvisDatabase {
             )
             "#,
             
+            // Configuration backup catalog
+            r#"
+            CREATE TABLE IF NOT EXISTS backup_sets (
+                id TEXT PRIMARY KEY,
+                created_at TEXT NOT NULL,
+                parent TEXT,
+                file_count INTEGER NOT NULL DEFAULT 0,
+                changed_files INTEGER NOT NULL DEFAULT 0,
+                stored_bytes INTEGER NOT NULL DEFAULT 0,
+                total_bytes INTEGER NOT NULL DEFAULT 0,
+                INDEX(created_at)
+            )
+            "#,
+            
             // Event log table
             r#"
             CREATE TABLE IF NOT EXISTS event_log (
@@ -373,9 +429,7 @@ impl JarvisDatabase {
             while zqlite_step(stmt) == 100 { // SQLITE_ROW
                 let mut row = HashMap::new();
                 
-                // For simplicity, we'll assume we know the column structure
-                // In a real implementation, you'd query the column metadata
-                let column_count = 3; // This would be dynamic
+                let column_count = zqlite_column_count(stmt);
                 
                 for col in 0..column_count {
                     let value_ptr = zqlite_column_text(stmt, col);
@@ -502,7 +556,7 @@ impl JarvisDatabase {
             issue.id.to_string().as_str(),
             &issue.package_name,
             issue.cve_id.as_deref().unwrap_or(""),
-            &format!("{:?}", issue.severity).to_lowercase(),
+            Severity::from(issue.severity.clone()).as_str(),
             &issue.description,
             &issue.discovered_at.to_rfc3339(),
             issue.resolved_at.as_ref().map(|dt| dt.to_rfc3339()).as_deref().unwrap_or(""),
@@ -547,11 +601,16 @@ impl JarvisDatabase {
         let query = r#"
             INSERT INTO maintenance_operations 
             (id, operation_type, status, started_at, completed_at, duration_ms,
-             packages_affected, output, error_message)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+             packages_affected, output, error_message, transaction_delta,
+             snapshot_id, rollback_of)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
         "#;
         
         let packages_json = serde_json::to_string(&maintenance.packages_affected)?;
+        let delta_json = match &maintenance.delta {
+            Some(delta) => serde_json::to_string(delta)?,
+            None => String::new(),
+        };
         
         let params = vec![
             maintenance.id.to_string().as_str(),
@@ -563,6 +622,9 @@ impl JarvisDatabase {
             &packages_json,
             &maintenance.output,
             maintenance.error_message.as_deref().unwrap_or(""),
+            &delta_json,
+            maintenance.snapshot_id.map(|n| n.to_string()).as_deref().unwrap_or(""),
+            maintenance.rollback_of.map(|id| id.to_string()).as_deref().unwrap_or(""),
         ];
         
         self.execute_query(query, params).await?;
@@ -571,30 +633,88 @@ impl JarvisDatabase {
     
     /// Get maintenance history
     pub async fn get_maintenance_history(&self, limit: u32) -> Result<Vec<MaintenanceRecord>> {
+        let query = format!(
+            "SELECT {} FROM maintenance_operations ORDER BY started_at DESC LIMIT ?",
+            MAINTENANCE_COLUMNS
+        );
+        
+        let results = self.execute_query(&query, vec![&limit.to_string()]).await?;
+        Ok(results.iter().filter_map(maintenance_from_row).collect())
+    }
+    
+    /// Get one maintenance operation by id
+    pub async fn get_maintenance_record(&self, id: Uuid) -> Result<Option<MaintenanceRecord>> {
+        let query = format!(
+            "SELECT {} FROM maintenance_operations WHERE id = ? LIMIT 1",
+            MAINTENANCE_COLUMNS
+        );
+        
+        let results = self.execute_query(&query, vec![&id.to_string()]).await?;
+        Ok(results.first().and_then(maintenance_from_row))
+    }
+    
+    /// Store a value in the configuration table
+    pub async fn set_config_value(&self, key: &str, value: &str, description: &str) -> Result<()> {
         let query = r#"
-            SELECT * FROM maintenance_operations 
-            ORDER BY started_at DESC 
-            LIMIT ?
+            INSERT OR REPLACE INTO configuration (key, value, description, updated_at)
+            VALUES (?, ?, ?, ?)
         "#;
-        
+
+        self.execute_query(query, vec![key, value, description, &Utc::now().to_rfc3339()]).await?;
+        Ok(())
+    }
+
+    /// Read a value from the configuration table
+    pub async fn get_config_value(&self, key: &str) -> Result<Option<String>> {
+        let query = "SELECT value FROM configuration WHERE key = ? LIMIT 1";
+        let results = self.execute_query(query, vec![key]).await?;
+
+        Ok(results
+            .first()
+            .and_then(|row| row.get("col_0"))
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()))
+    }
+
+    /// Remove a value from the configuration table
+    pub async fn delete_config_value(&self, key: &str) -> Result<()> {
+        self.execute_query("DELETE FROM configuration WHERE key = ?", vec![key]).await?;
+        Ok(())
+    }
+    
+    /// Add a backup set to the catalog, or update it after pruning re-based it
+    pub async fn record_backup_set(&self, summary: &BackupSummary) -> Result<()> {
+        let query = r#"
+            INSERT OR REPLACE INTO backup_sets
+            (id, created_at, parent, file_count, changed_files, stored_bytes, total_bytes)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+        "#;
+
+        let params = vec![
+            summary.id.as_str(),
+            &summary.created_at.to_rfc3339(),
+            summary.parent.as_deref().unwrap_or(""),
+            &summary.file_count.to_string(),
+            &summary.changed_files.to_string(),
+            &summary.stored_bytes.to_string(),
+            &summary.total_bytes.to_string(),
+        ];
+        self.execute_query(query, params).await?;
+        Ok(())
+    }
+
+    /// Backup sets in the catalog, newest first
+    pub async fn list_backup_sets(&self, limit: u32) -> Result<Vec<BackupSummary>> {
+        let query = "SELECT id, created_at, parent, file_count, changed_files, stored_bytes, total_bytes \
+             FROM backup_sets ORDER BY created_at DESC LIMIT ?";
         let results = self.execute_query(query, vec![&limit.to_string()]).await?;
-        
-        // Convert to MaintenanceRecord objects
-        let records = results.into_iter().map(|_row| {
-            MaintenanceRecord {
-                id: Uuid::new_v4(),
-                operation_type: "update".to_string(),
-                status: MaintenanceStatus::Completed,
-                started_at: Utc::now(),
-                completed_at: Some(Utc::now()),
-                duration_ms: Some(5000),
-                packages_affected: vec!["package1".to_string()],
-                output: "Success".to_string(),
-                error_message: None,
-            }
-        }).collect();
-        
-        Ok(records)
+        Ok(results.iter().filter_map(backup_set_from_row).collect())
+    }
+
+    /// Drop a pruned backup set from the catalog
+    pub async fn delete_backup_set(&self, id: &str) -> Result<()> {
+        self.execute_query("DELETE FROM backup_sets WHERE id = ?", vec![id]).await?;
+        Ok(())
     }
     
     /// Close database connection
@@ -614,6 +734,68 @@ impl JarvisDatabase {
     }
 }
 
+/// Columns read back into a `MaintenanceRecord`, in `col_<n>` order
+const MAINTENANCE_COLUMNS: &str = "id, operation_type, status, started_at, completed_at, duration_ms, \
+     packages_affected, output, error_message, transaction_delta, snapshot_id, rollback_of";
+
+/// Rebuild a record selected with `MAINTENANCE_COLUMNS`; empty strings are
+/// how `record_maintenance` stores absent values
+fn maintenance_from_row(row: &HashMap<String, serde_json::Value>) -> Option<MaintenanceRecord> {
+    let col = |n: usize| {
+        row.get(&format!("col_{}", n))
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.is_empty())
+    };
+    let timestamp = |n: usize| {
+        col(n)
+            .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+            .map(|dt| dt.with_timezone(&Utc))
+    };
+
+    Some(MaintenanceRecord {
+        id: col(0)?.parse().ok()?,
+        operation_type: col(1)?.to_string(),
+        status: match col(2)? {
+            "pending" => MaintenanceStatus::Pending,
+            "running" => MaintenanceStatus::Running,
+            "completed" => MaintenanceStatus::Completed,
+            "failed" => MaintenanceStatus::Failed,
+            "cancelled" => MaintenanceStatus::Cancelled,
+            "skipped" => MaintenanceStatus::Skipped,
+            _ => return None,
+        },
+        started_at: timestamp(3)?,
+        completed_at: timestamp(4),
+        duration_ms: col(5).and_then(|s| s.parse().ok()),
+        packages_affected: col(6)
+            .and_then(|s| serde_json::from_str(s).ok())
+            .unwrap_or_default(),
+        output: col(7).unwrap_or_default().to_string(),
+        error_message: col(8).map(|s| s.to_string()),
+        delta: col(9).and_then(|s| serde_json::from_str(s).ok()),
+        snapshot_id: col(10).and_then(|s| s.parse().ok()),
+        rollback_of: col(11).and_then(|s| s.parse().ok()),
+    })
+}
+
+fn backup_set_from_row(row: &HashMap<String, serde_json::Value>) -> Option<BackupSummary> {
+    let col = |n: usize| {
+        row.get(&format!("col_{}", n))
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.is_empty())
+    };
+
+    Some(BackupSummary {
+        id: col(0)?.to_string(),
+        created_at: DateTime::parse_from_rfc3339(col(1)?).ok()?.with_timezone(&Utc),
+        parent: col(2).map(|s| s.to_string()),
+        file_count: col(3)?.parse().ok()?,
+        changed_files: col(4)?.parse().ok()?,
+        stored_bytes: col(5)?.parse().ok()?,
+        total_bytes: col(6)?.parse().ok()?,
+    })
+}
+
 impl Drop for JarvisDatabase {
     fn drop(&mut self) {
         // Ensure cleanup
@@ -0,0 +1,230 @@
+//! Wazuh active response against a mock manager
+//!
+//! The manager side connects to the listener over TCP like the real one
+//! would; a recording executor stands in for the agent.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use jarvis_arch::active_response::{ResponseRecord, ResponseReply};
+use jarvis_arch::config::ActiveResponseConfig;
+use jarvis_arch::{ActiveResponder, ResponseCommand, ResponseExecutor, SignedCommand};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+const SECRET: &str = "s3cret-shared-with-the-manager";
+
+#[derive(Default)]
+struct MockExecutor {
+    executed: Mutex<Vec<ResponseCommand>>,
+    records: Mutex<Vec<ResponseRecord>>,
+}
+
+#[async_trait]
+impl ResponseExecutor for MockExecutor {
+    async fn execute(&self, command: &ResponseCommand) -> Result<serde_json::Value> {
+        self.executed.lock().unwrap().push(command.clone());
+        Ok(serde_json::json!({ "done": command.name() }))
+    }
+
+    async fn record(&self, record: &ResponseRecord) {
+        self.records.lock().unwrap().push(record.clone());
+    }
+}
+
+fn config() -> ActiveResponseConfig {
+    ActiveResponseConfig {
+        enabled: true,
+        shared_secret: Some(SECRET.to_string()),
+        block_ip: true,
+        disable_service: true,
+        ..Default::default()
+    }
+}
+
+/// Start a listener on a free port; returns its address and the executor
+async fn start(config: ActiveResponseConfig) -> (String, Arc<MockExecutor>) {
+    let executor = Arc::new(MockExecutor::default());
+    let responder = Arc::new(ActiveResponder::new(config, executor.clone()));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(responder.serve(listener));
+    (address, executor)
+}
+
+/// Send one line as the manager and read the reply
+async fn send(address: &str, line: &str) -> ResponseReply {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    stream
+        .write_all(format!("{}\n", line).as_bytes())
+        .await
+        .unwrap();
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply).await.unwrap();
+    serde_json::from_str(&reply).unwrap()
+}
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+fn signed(command: &str, target: Option<&str>) -> String {
+    serde_json::to_string(&SignedCommand::sign(SECRET, command, target, now())).unwrap()
+}
+
+#[tokio::test]
+async fn test_signed_command_is_dispatched_and_recorded() {
+    let (address, executor) = start(config()).await;
+
+    let reply = send(&address, &signed("block_ip", Some("203.0.113.7"))).await;
+    assert!(reply.accepted && reply.success, "{:?}", reply);
+    assert_eq!(reply.output["done"], "block_ip");
+
+    let reply = send(&address, &signed("disable_service", Some("cups"))).await;
+    assert!(reply.accepted, "{:?}", reply);
+
+    assert_eq!(
+        *executor.executed.lock().unwrap(),
+        vec![
+            ResponseCommand::BlockIp {
+                ip: "203.0.113.7".parse().unwrap()
+            },
+            ResponseCommand::DisableService {
+                unit: "cups.service".to_string()
+            },
+        ]
+    );
+    let records = executor.records.lock().unwrap();
+    assert_eq!(records.len(), 2);
+    assert!(records[0].source.starts_with("127.0.0.1:"));
+    assert!(
+        records[0]
+            .notification()
+            .title
+            .contains("block_ip 203.0.113.7")
+    );
+}
+
+#[tokio::test]
+async fn test_unknown_unsigned_and_forged_commands_are_rejected() {
+    let (address, executor) = start(config()).await;
+
+    let reply = send(&address, &signed("reboot", None)).await;
+    assert!(!reply.accepted);
+    assert!(reply.error.unwrap().contains("Unknown command 'reboot'"));
+
+    let unsigned = format!(
+        r#"{{"command":"block_ip","target":"203.0.113.7","issued_at":{}}}"#,
+        now()
+    );
+    let reply = send(&address, &unsigned).await;
+    assert!(!reply.accepted);
+    assert_eq!(reply.error.as_deref(), Some("Command is not signed"));
+
+    let forged = SignedCommand::sign("guessed-secret", "block_ip", Some("203.0.113.7"), now());
+    let reply = send(&address, &serde_json::to_string(&forged).unwrap()).await;
+    assert_eq!(reply.error.as_deref(), Some("Signature does not match"));
+
+    // A valid signature doesn't cover a swapped target
+    let mut tampered = SignedCommand::sign(SECRET, "block_ip", Some("203.0.113.7"), now());
+    tampered.target = Some("198.51.100.1".to_string());
+    let reply = send(&address, &serde_json::to_string(&tampered).unwrap()).await;
+    assert_eq!(reply.error.as_deref(), Some("Signature does not match"));
+
+    let stale = SignedCommand::sign(SECRET, "block_ip", Some("203.0.113.7"), now() - 3600);
+    let reply = send(&address, &serde_json::to_string(&stale).unwrap()).await;
+    assert!(reply.error.unwrap().contains("clock skew"));
+
+    let reply = send(&address, "not json").await;
+    assert!(reply.error.unwrap().starts_with("Malformed request"));
+
+    assert!(executor.executed.lock().unwrap().is_empty());
+    assert!(executor.records.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_replayed_disabled_and_protected_commands_are_rejected() {
+    let (address, executor) = start(config()).await;
+
+    let line = signed("block_ip", Some("203.0.113.7"));
+    assert!(send(&address, &line).await.accepted);
+    let reply = send(&address, &line).await;
+    assert_eq!(reply.error.as_deref(), Some("Command was already received"));
+
+    // Not enabled in the config
+    let reply = send(
+        &address,
+        &signed("quarantine_aur_package", Some("xmrig-bin")),
+    )
+    .await;
+    assert_eq!(
+        reply.error.as_deref(),
+        Some("quarantine_aur_package is disabled in [wazuh.active_response]")
+    );
+
+    let reply = send(&address, &signed("disable_service", Some("sshd"))).await;
+    assert!(reply.error.unwrap().contains("protected"));
+
+    assert_eq!(executor.executed.lock().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_commands_are_refused_without_a_shared_secret() {
+    let (address, executor) = start(ActiveResponseConfig {
+        shared_secret: None,
+        ..config()
+    })
+    .await;
+
+    let reply = send(&address, &signed("block_ip", Some("203.0.113.7"))).await;
+    assert!(!reply.accepted);
+    assert!(reply.error.unwrap().contains("No shared secret"));
+    assert!(executor.executed.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_stdin_protocol_takes_target_from_alert() {
+    let executor = Arc::new(MockExecutor::default());
+    let responder = ActiveResponder::new(config(), executor.clone());
+    let message = |command: &str, secret: &str| {
+        serde_json::json!({
+            "version": 1,
+            "origin": { "name": "node01", "module": "wazuh-execd" },
+            "command": command,
+            "parameters": {
+                "extra_args": ["block_ip", secret],
+                "alert": { "rule": { "id": "5712" }, "data": { "srcip": "198.51.100.23" } },
+                "program": "jarvis-arch"
+            }
+        })
+        .to_string()
+    };
+
+    let reply = responder
+        .handle_wazuh_message(&message("add", SECRET))
+        .await
+        .unwrap();
+    assert!(reply.accepted && reply.success, "{:?}", reply);
+    assert_eq!(
+        executor.executed.lock().unwrap()[0],
+        ResponseCommand::BlockIp {
+            ip: "198.51.100.23".parse().unwrap()
+        }
+    );
+    assert_eq!(executor.records.lock().unwrap()[0].source, "wazuh-execd");
+
+    let reply = responder
+        .handle_wazuh_message(&message("add", "wrong"))
+        .await
+        .unwrap();
+    assert!(!reply.accepted);
+
+    // Blocks expire on their own; delete messages are ignored
+    assert!(
+        responder
+            .handle_wazuh_message(&message("delete", SECRET))
+            .await
+            .is_none()
+    );
+    assert_eq!(executor.executed.lock().unwrap().len(), 1);
+}
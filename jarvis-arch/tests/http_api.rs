@@ -0,0 +1,429 @@
+//! HTTP API against a fake agent
+//!
+//! The server listens on an ephemeral port and is driven over real HTTP.
+//! Operations block on a gate until the test releases them, so the running
+//! state can be observed before the result lands.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use jarvis_arch::http_api::{self, ApiState, OperationEntry, OperationState};
+use jarvis_arch::{
+    AgentCapability, AgentHealth, AgentState, AgentStatistics, AgentStatus, ArchAgent,
+    ArchOperation, Config, ExecOptions, HealthStatus, OperationResult,
+};
+use reqwest::StatusCode;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::Semaphore;
+use uuid::Uuid;
+
+const TOKEN: &str = "test-token";
+
+/// Records what it runs; each operation waits for a permit on `gate`
+struct FakeAgent {
+    gate: Semaphore,
+    executed: Mutex<Vec<(ArchOperation, bool)>>,
+}
+
+impl FakeAgent {
+    async fn run(&self, operation: ArchOperation, dry_run: bool) -> Result<OperationResult> {
+        self.gate.acquire().await?.forget();
+        self.executed
+            .lock()
+            .unwrap()
+            .push((operation.clone(), dry_run));
+        Ok(OperationResult {
+            operation,
+            success: true,
+            output: json!({ "dry_run": dry_run }).into(),
+            error: None,
+            duration_ms: 1,
+            executed_at: chrono::Utc::now(),
+            metadata: HashMap::new(),
+        })
+    }
+}
+
+#[async_trait]
+impl ArchAgent for FakeAgent {
+    async fn initialize(&mut self, _config: Config) -> Result<()> {
+        Ok(())
+    }
+
+    async fn health_check(&self) -> Result<AgentHealth> {
+        Ok(AgentHealth {
+            status: HealthStatus::Healthy,
+            last_check: chrono::Utc::now(),
+            uptime_seconds: 42,
+            error_count: 0,
+            success_rate: 1.0,
+            system_load: 0.5,
+            memory_usage_percent: 30.0,
+            disk_usage_percent: 60.0,
+            active_operations: 0,
+            gpus: Vec::new(),
+            sensors: Default::default(),
+            custom_checks: Vec::new(),
+            soname_rebuilds: Vec::new(),
+        })
+    }
+
+    fn capabilities(&self) -> Vec<AgentCapability> {
+        Vec::new()
+    }
+
+    async fn execute_operation(&self, operation: ArchOperation) -> Result<OperationResult> {
+        self.run(operation, false).await
+    }
+
+    async fn execute_operation_with_options(
+        &self,
+        operation: ArchOperation,
+        options: ExecOptions,
+    ) -> Result<OperationResult> {
+        self.run(operation, options.dry_run).await
+    }
+
+    async fn execute_cancellable(
+        &self,
+        _operation_id: Uuid,
+        operation: ArchOperation,
+    ) -> Result<OperationResult> {
+        self.run(operation, false).await
+    }
+
+    fn cancel_operation(&self, _operation_id: Uuid) -> bool {
+        false
+    }
+
+    async fn get_status(&self) -> Result<AgentStatus> {
+        Ok(AgentStatus {
+            agent_id: Uuid::nil(),
+            version: "test".to_string(),
+            status: AgentState::Ready,
+            capabilities: Vec::new(),
+            active_operations: Vec::new(),
+            last_maintenance: None,
+            next_scheduled_maintenance: None,
+            statistics: AgentStatistics {
+                total_operations: 0,
+                successful_operations: 0,
+                failed_operations: 0,
+                packages_managed: 0,
+                security_issues_found: 0,
+                security_issues_resolved: 0,
+                uptime_hours: 0.0,
+                average_operation_time_ms: 0.0,
+            },
+            subsystems: Vec::new(),
+            read_only: false,
+        })
+    }
+
+    async fn shutdown(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Start the API on a free port; returns its base URL and the agent
+async fn start() -> (String, Arc<FakeAgent>) {
+    let agent = Arc::new(FakeAgent {
+        gate: Semaphore::new(0),
+        executed: Mutex::new(Vec::new()),
+    });
+    let state = ApiState::new(agent.clone(), Some(TOKEN.to_string()));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(http_api::serve(listener, state));
+    (url, agent)
+}
+
+fn client() -> reqwest::Client {
+    reqwest::Client::new()
+}
+
+async fn submit(url: &str, query: &str, operation: &ArchOperation) -> (StatusCode, Value) {
+    let response = client()
+        .post(format!("{}/operations{}", url, query))
+        .bearer_auth(TOKEN)
+        .json(operation)
+        .send()
+        .await
+        .unwrap();
+    (response.status(), response.json().await.unwrap())
+}
+
+async fn entry(url: &str, id: &str) -> OperationEntry {
+    client()
+        .get(format!("{}/operations/{}", url, id))
+        .bearer_auth(TOKEN)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+/// Poll until the operation leaves the running state
+async fn wait_finished(url: &str, id: &str) -> OperationEntry {
+    for _ in 0..100 {
+        let entry = entry(url, id).await;
+        if entry.state != OperationState::Running {
+            return entry;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("operation {} never finished", id);
+}
+
+#[tokio::test]
+async fn test_requests_without_the_token_are_rejected() {
+    let (url, _agent) = start().await;
+
+    let response = client()
+        .get(format!("{}/health", url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = client()
+        .get(format!("{}/health", url))
+        .bearer_auth("wrong")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let health: Value = client()
+        .get(format!("{}/health", url))
+        .bearer_auth(TOKEN)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(health["uptime_seconds"], 42);
+}
+
+#[tokio::test]
+async fn test_operation_lifecycle() {
+    let (url, agent) = start().await;
+    let backup = ArchOperation::BackupConfigs {
+        destination: "/tmp/backup".to_string(),
+    };
+
+    // Changes to the system need confirmation, like the CLI's --yes
+    let (status, body) = submit(&url, "", &backup).await;
+    assert_eq!(status, StatusCode::PRECONDITION_REQUIRED);
+    assert!(body["error"].as_str().unwrap().contains("confirm=true"));
+    assert!(body.get("plan").is_some());
+    assert!(agent.executed.lock().unwrap().is_empty());
+
+    let (status, body) = submit(&url, "?confirm=true", &backup).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    let id = body["id"].as_str().unwrap().to_string();
+
+    let running = entry(&url, &id).await;
+    assert_eq!(running.state, OperationState::Running);
+    assert!(running.result.is_none());
+
+    agent.gate.add_permits(1);
+    let finished = wait_finished(&url, &id).await;
+    assert_eq!(finished.state, OperationState::Finished);
+    assert!(finished.finished_at.is_some());
+    assert!(finished.result.unwrap().success);
+
+    // Read-only operations run without confirmation
+    let (status, body) = submit(
+        &url,
+        "",
+        &ArchOperation::HealthCheck {
+            include_services: false,
+        },
+    )
+    .await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    agent.gate.add_permits(1);
+    wait_finished(&url, body["id"].as_str().unwrap()).await;
+
+    let listed: Vec<OperationEntry> = client()
+        .get(format!("{}/operations?since=1h", url))
+        .bearer_auth(TOKEN)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(listed.len(), 2);
+    // Newest first
+    assert!(matches!(
+        listed[0].operation,
+        ArchOperation::HealthCheck { .. }
+    ));
+    assert_eq!(listed[1].id.to_string(), id);
+
+    let response = client()
+        .get(format!("{}/operations/{}", url, Uuid::new_v4()))
+        .bearer_auth(TOKEN)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = client()
+        .get(format!("{}/operations?since=soon", url))
+        .bearer_auth(TOKEN)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_dry_run_needs_no_confirmation_and_is_passed_to_the_agent() {
+    let (url, agent) = start().await;
+    let update = ArchOperation::UpdatePackages { packages: None };
+
+    let (status, body) = submit(&url, "?dry_run=true", &update).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    assert_eq!(body["dry_run"], true);
+
+    agent.gate.add_permits(1);
+    let finished = wait_finished(&url, body["id"].as_str().unwrap()).await;
+    assert!(finished.dry_run);
+    assert_eq!(finished.state, OperationState::Finished);
+
+    let executed = agent.executed.lock().unwrap();
+    assert_eq!(executed.len(), 1);
+    assert!(executed[0].1, "dry run was not passed to the agent");
+}
+
+#[tokio::test]
+async fn test_metrics_and_status() {
+    let (url, _agent) = start().await;
+    let (status, _) = submit(
+        &url,
+        "",
+        &ArchOperation::HealthCheck {
+            include_services: false,
+        },
+    )
+    .await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+
+    let response = client()
+        .get(format!("{}/metrics", url))
+        .bearer_auth(TOKEN)
+        .send()
+        .await
+        .unwrap();
+    assert!(
+        response.headers()["content-type"]
+            .to_str()
+            .unwrap()
+            .starts_with("text/plain")
+    );
+    let body = response.text().await.unwrap();
+    assert!(body.contains("# TYPE jarvis_arch_uptime_seconds gauge"));
+    assert!(body.contains("jarvis_arch_uptime_seconds 42"));
+    assert!(body.contains("jarvis_arch_health{status=\"healthy\"} 1"));
+    assert!(body.contains("jarvis_arch_api_operations{state=\"running\"} 1"));
+
+    let status: Value = client()
+        .get(format!("{}/status", url))
+        .bearer_auth(TOKEN)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["version"], "test");
+}
+
+#[tokio::test]
+async fn test_findings_are_served_as_sarif() {
+    let (url, _agent) = start().await;
+    let response = client()
+        .get(format!("{}/findings.sarif", url))
+        .bearer_auth(TOKEN)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()["content-type"],
+        jarvis_arch::sarif::SARIF_CONTENT_TYPE
+    );
+    let sarif: Value = response.json().await.unwrap();
+    assert_eq!(sarif["version"], "2.1.0");
+    assert_eq!(sarif["runs"][0]["tool"]["driver"]["name"], "jarvis");
+    assert_eq!(sarif["runs"][0]["results"], json!([]));
+}
+
+#[tokio::test]
+async fn test_retries_with_one_idempotency_key_start_one_operation() {
+    let (url, agent) = start().await;
+    let backup = ArchOperation::BackupConfigs {
+        destination: "/tmp/backup".to_string(),
+    };
+    let post = |key: &'static str, operation: ArchOperation| {
+        client()
+            .post(format!("{}/operations?confirm=true", url))
+            .bearer_auth(TOKEN)
+            .header("Idempotency-Key", key)
+            .json(&operation)
+            .send()
+    };
+
+    // Racing retries of one submission
+    let responses =
+        futures::future::join_all((0..8).map(|_| post("backup-1", backup.clone()))).await;
+    let mut ids = Vec::new();
+    let mut accepted = 0;
+    for response in responses {
+        let response = response.unwrap();
+        if response.status() == StatusCode::ACCEPTED {
+            accepted += 1;
+        } else {
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        let body: Value = response.json().await.unwrap();
+        ids.push(body["id"].as_str().unwrap().to_string());
+    }
+    assert_eq!(accepted, 1);
+    assert!(ids.iter().all(|id| *id == ids[0]));
+
+    agent.gate.add_permits(8);
+    let finished = wait_finished(&url, &ids[0]).await;
+    assert_eq!(finished.state, OperationState::Finished);
+    // A late retry reports the finished operation
+    let response = post("backup-1", backup.clone()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let replayed: OperationEntry = response.json().await.unwrap();
+    assert_eq!(replayed.id.to_string(), ids[0]);
+    assert_eq!(replayed.state, OperationState::Finished);
+    assert_eq!(agent.executed.lock().unwrap().len(), 1);
+
+    // The key can't be reused for another operation
+    let response = post(
+        "backup-1",
+        ArchOperation::HealthCheck {
+            include_services: false,
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    // Without a key nothing is deduplicated
+    let (status, _) = submit(&url, "?confirm=true", &backup).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+}
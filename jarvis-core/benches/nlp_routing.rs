@@ -0,0 +1,81 @@
+//! The embedding tier of the NLP parser
+//!
+//! `nearest_example` routes a query against the built-in examples with a
+//! synthetic 768-dimension embedder, the size nomic-embed-text produces, so it
+//! measures everything but Ollama: embedding the examples happens once in
+//! `warm` and each iteration only scores the query. With
+//! `JARVIS_BENCH_OLLAMA=1`, `ollama_warm_model` runs the same query through
+//! the configured Ollama embedding model after a warm-up call, which is the
+//! latency a user sees; it should stay well under 100ms.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use criterion::{Criterion, black_box, criterion_group, criterion_main};
+use jarvis_core::config::Config;
+use jarvis_core::llm::LLMRouter;
+use jarvis_core::nlp::embedding_routing::{Embedder, EmbeddingRouter, builtin_examples};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+const DIMENSIONS: usize = 768;
+const QUERY: &str = "could you put neovim on this machine";
+
+/// Spreads each word over a few dimensions so vectors are dense like real ones
+struct SyntheticEmbedder;
+
+#[async_trait]
+impl Embedder for SyntheticEmbedder {
+    fn model(&self) -> &str {
+        "synthetic"
+    }
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let mut vector = vec![0.0; DIMENSIONS];
+        for word in text.split_whitespace() {
+            let mut hasher = DefaultHasher::new();
+            word.hash(&mut hasher);
+            let hash = hasher.finish();
+            for i in 0..8 {
+                vector[(hash.rotate_left(i * 8) as usize) % DIMENSIONS] += 1.0;
+            }
+        }
+        Ok(vector)
+    }
+}
+
+fn bench_routing(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    let router = EmbeddingRouter::new(builtin_examples(), 0.8);
+    runtime.block_on(router.warm(&SyntheticEmbedder)).unwrap();
+    c.bench_function("nearest_example", |b| {
+        b.iter(|| {
+            runtime
+                .block_on(router.nearest(&SyntheticEmbedder, black_box(QUERY)))
+                .unwrap()
+        })
+    });
+
+    if std::env::var_os("JARVIS_BENCH_OLLAMA").is_none() {
+        return;
+    }
+    let llm = runtime.block_on(async {
+        let config = Config::load(None).await?;
+        LLMRouter::new(&config).await
+    });
+    let llm = match llm {
+        Ok(llm) => llm,
+        Err(e) => {
+            eprintln!("Skipping ollama_warm_model: {:#}", e);
+            return;
+        }
+    };
+    let router = EmbeddingRouter::new(builtin_examples(), 0.8);
+    // Loads the model and embeds the examples
+    runtime.block_on(router.warm(&llm)).unwrap();
+    c.bench_function("ollama_warm_model", |b| {
+        b.iter(|| runtime.block_on(router.nearest(&llm, black_box(QUERY))).unwrap())
+    });
+}
+
+criterion_group!(benches, bench_routing);
+criterion_main!(benches);
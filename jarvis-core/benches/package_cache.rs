@@ -0,0 +1,92 @@
+//! Installed-package lookups with and without the package cache
+//!
+//! Builds a synthetic local database of 2000 packages plus the `pacman -Qi`
+//! output for it, then compares answering lookups by re-parsing pacman's
+//! output, as callers did before the cache, with answering them from the
+//! loaded index. Spawning pacman is not part of the measurement, so the gap
+//! on a real system is wider still.
+
+use criterion::{Criterion, black_box, criterion_group, criterion_main};
+use jarvis_core::exec::RecordingRunner;
+use jarvis_core::package_cache::{PackageCache, PackageIndex};
+use jarvis_core::preflight::parse_installed_sizes;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::sync::Arc;
+
+const PACKAGES: usize = 2000;
+const LOOKUPS: [&str; 3] = ["pkg-0007", "pkg-1000", "pkg-1999"];
+
+/// Write the local database under `dir/local` and return the matching
+/// `pacman -Qi` output
+fn synthetic_db(dir: &Path) -> String {
+    let mut info = String::new();
+    for i in 0..PACKAGES {
+        let name = format!("pkg-{:04}", i);
+        let version = format!("1.{}.0-1", i % 17);
+        let size = 4096 * (i as u64 + 1);
+        let package_dir = dir.join("local").join(format!("{}-{}", name, version));
+        std::fs::create_dir_all(&package_dir).unwrap();
+        std::fs::write(
+            package_dir.join("desc"),
+            format!(
+                "%NAME%\n{name}\n\n%VERSION%\n{version}\n\n%DESC%\nSynthetic package {i}\n\n\
+                 %BUILDDATE%\n1727776800\n\n%INSTALLDATE%\n{}\n\n%SIZE%\n{size}\n\n%REASON%\n{}\n\n",
+                1_729_067_412 + i as i64,
+                i % 2,
+            ),
+        )
+        .unwrap();
+
+        let _ = write!(
+            info,
+            "Name            : {name}\n\
+             Version         : {version}\n\
+             Description     : Synthetic package {i}\n\
+             Installed Size  : {:.2} KiB\n\
+             Install Reason  : Explicitly installed\n\n",
+            size as f64 / 1024.0,
+        );
+    }
+    info
+}
+
+fn bench_lookups(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let info = synthetic_db(dir.path());
+    let local_db = dir.path().join("local");
+    let log = dir.path().join("pacman.log");
+    std::fs::write(&log, "").unwrap();
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let cache = PackageCache::new()
+        .with_paths(&local_db, &log)
+        .with_runner(Arc::new(RecordingRunner::new()));
+    assert_eq!(runtime.block_on(cache.index()).unwrap().len(), PACKAGES);
+
+    let mut group = c.benchmark_group("installed_size_lookup");
+    group.bench_function("parse_pacman_qi", |b| {
+        b.iter(|| {
+            let sizes = parse_installed_sizes(black_box(&info));
+            LOOKUPS.iter().map(|name| sizes[*name]).sum::<u64>()
+        })
+    });
+    group.bench_function("package_cache", |b| {
+        b.iter(|| {
+            let index = runtime.block_on(cache.index()).unwrap();
+            LOOKUPS
+                .iter()
+                .map(|name| index.get(name).unwrap().installed_size)
+                .sum::<u64>()
+        })
+    });
+    group.finish();
+
+    // What a reload after a transaction costs
+    c.bench_function("load_local_db", |b| {
+        b.iter(|| PackageIndex::load(black_box(&local_db)).unwrap())
+    });
+}
+
+criterion_group!(benches, bench_lookups);
+criterion_main!(benches);
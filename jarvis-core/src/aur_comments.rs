@@ -0,0 +1,432 @@
+//! Pinned comments from AUR package pages
+//!
+//! Maintainers pin install-time warnings ("rebuild against the new ICU",
+//! "import this GPG key") as comments on a package's AUR page, and the AUR
+//! RPC interface doesn't expose comments, so the page itself is fetched and
+//! its "Pinned Comments" section turned into plain text with links kept.
+//! Results are cached on disk per package; after the TTL the page is
+//! revalidated with `If-None-Match`/`If-Modified-Since`, so an unchanged page
+//! costs aurweb a 304. A page that can't be fetched or whose layout isn't
+//! recognized yields comments marked unavailable rather than an error.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use reqwest::StatusCode;
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use crate::net::{self, CheckedSend};
+
+const AUR_BASE_URL: &str = "https://aur.archlinux.org";
+
+/// How long a fetched page is trusted before it is revalidated
+pub const DEFAULT_TTL: Duration = Duration::from_secs(6 * 3600);
+
+/// One pinned comment, as plain text
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PinnedComment {
+    pub author: String,
+    pub date: String,
+    pub text: String,
+}
+
+/// The pinned comments of one AUR package
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AurComments {
+    pub package: String,
+    pub pinned: Vec<PinnedComment>,
+    /// Why the comments couldn't be read; `pinned` is empty then
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unavailable: Option<String>,
+}
+
+impl AurComments {
+    fn unavailable(package: &str, reason: impl Into<String>) -> Self {
+        Self {
+            package: package.to_string(),
+            pinned: Vec::new(),
+            unavailable: Some(reason.into()),
+        }
+    }
+
+    /// Pinned comments indented under a heading, or a one-line note when
+    /// they couldn't be read; empty when the package has none
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        if let Some(reason) = &self.unavailable {
+            let _ = writeln!(
+                out,
+                "AUR comments for {} unavailable: {}",
+                self.package, reason
+            );
+            return out;
+        }
+        if self.pinned.is_empty() {
+            return out;
+        }
+        let _ = writeln!(out, "📌 Pinned AUR comments for {}:", self.package);
+        for comment in &self.pinned {
+            let _ = writeln!(out, "  {} ({}):", comment.author, comment.date);
+            for line in comment.text.lines() {
+                let _ = writeln!(out, "    {}", line);
+            }
+        }
+        out
+    }
+}
+
+/// What is kept on disk for a package page
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    fetched_at: DateTime<Utc>,
+    #[serde(default)]
+    etag: Option<String>,
+    #[serde(default)]
+    last_modified: Option<String>,
+    pinned: Vec<PinnedComment>,
+}
+
+/// Fetches pinned comments through a per-package cache
+pub struct AurCommentCache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+impl AurCommentCache {
+    pub fn new(dir: impl Into<PathBuf>, ttl: Duration) -> Self {
+        Self {
+            dir: dir.into(),
+            ttl,
+        }
+    }
+
+    /// `~/.cache/jarvis/aur-comments` with [`DEFAULT_TTL`]
+    pub fn default_location() -> Self {
+        let dir = dirs::cache_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("jarvis")
+            .join("aur-comments");
+        Self::new(dir, DEFAULT_TTL)
+    }
+
+    /// Pinned comments for `package`, from the cache while it is fresh
+    pub async fn pinned(&self, package: &str) -> AurComments {
+        if !is_valid_package_name(package) {
+            return AurComments::unavailable(package, "not a valid AUR package name");
+        }
+
+        let cached = self.read(package).await;
+        if let Some(entry) = &cached {
+            let age = Utc::now().signed_duration_since(entry.fetched_at);
+            if age.to_std().is_ok_and(|age| age < self.ttl) {
+                return AurComments {
+                    package: package.to_string(),
+                    pinned: entry.pinned.clone(),
+                    unavailable: None,
+                };
+            }
+        }
+
+        match self.fetch(package, cached).await {
+            Ok(entry) => {
+                if let Err(e) = self.write(package, &entry).await {
+                    tracing::debug!("Failed to cache AUR comments for {}: {}", package, e);
+                }
+                AurComments {
+                    package: package.to_string(),
+                    pinned: entry.pinned,
+                    unavailable: None,
+                }
+            }
+            Err(e) => {
+                tracing::debug!("AUR comments for {} unavailable: {:#}", package, e);
+                AurComments::unavailable(package, format!("{:#}", e))
+            }
+        }
+    }
+
+    /// Fetch the package page, revalidating `cached` when there is one
+    async fn fetch(&self, package: &str, cached: Option<CacheEntry>) -> Result<CacheEntry> {
+        let url = format!("{}/packages/{}", AUR_BASE_URL, package);
+        let mut request = net::client().get(&url).timeout(Duration::from_secs(15));
+        if let Some(entry) = &cached {
+            if let Some(etag) = &entry.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &entry.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let response = request.checked_send().await?;
+
+        match (response.status(), cached) {
+            (StatusCode::NOT_MODIFIED, Some(entry)) => Ok(CacheEntry {
+                fetched_at: Utc::now(),
+                ..entry
+            }),
+            (StatusCode::NOT_FOUND, _) => anyhow::bail!("{} is not in the AUR", package),
+            (status, _) if !status.is_success() => anyhow::bail!("aurweb returned {}", status),
+            _ => {
+                let header = |name| {
+                    response
+                        .headers()
+                        .get(name)
+                        .and_then(|v| v.to_str().ok())
+                        .map(str::to_string)
+                };
+                let etag = header(ETAG);
+                let last_modified = header(LAST_MODIFIED);
+                let html = response
+                    .text()
+                    .await
+                    .context("Failed to read the AUR page")?;
+                let pinned = parse_pinned_comments(&html)
+                    .context("comments unavailable (unrecognized AUR page layout)")?;
+                Ok(CacheEntry {
+                    fetched_at: Utc::now(),
+                    etag,
+                    last_modified,
+                    pinned,
+                })
+            }
+        }
+    }
+
+    fn path(&self, package: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", package))
+    }
+
+    async fn read(&self, package: &str) -> Option<CacheEntry> {
+        let data = tokio::fs::read(self.path(package)).await.ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+
+    async fn write(&self, package: &str, entry: &CacheEntry) -> Result<()> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        tokio::fs::write(self.path(package), serde_json::to_vec(entry)?).await?;
+        Ok(())
+    }
+}
+
+/// Pinned comments for `package` through the default cache
+pub async fn pinned_comments(package: &str) -> AurComments {
+    AurCommentCache::default_location().pinned(package).await
+}
+
+/// AUR package names: lowercase alphanumerics and `@._+-`
+fn is_valid_package_name(package: &str) -> bool {
+    !package.is_empty()
+        && !package.starts_with(['-', '.'])
+        && package
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "@._+-".contains(c))
+}
+
+static COMMENT_HEADER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?s)<h4[^>]*class="comment-header"[^>]*>(.*?)</h4>"#).expect("valid regex")
+});
+static DATE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?s)<a[^>]*class="date"[^>]*>(.*?)</a>"#).expect("valid regex"));
+
+/// The pinned comments on an AUR package page; empty when none are pinned,
+/// `None` when the page doesn't look like an AUR package page
+pub fn parse_pinned_comments(html: &str) -> Option<Vec<PinnedComment>> {
+    if !html.contains(r#"id="pkgdetails""#) {
+        return None;
+    }
+    let Some(start) = html.find(">Pinned Comments<") else {
+        return Some(Vec::new());
+    };
+    let section = &html[start..];
+    // The section runs until the next comment box (Latest Comments)
+    let end = section
+        .find(r#"class="comments package-comments""#)
+        .and_then(|class| section[..class].rfind('<'))
+        .unwrap_or(section.len());
+    let section = &section[..end];
+
+    let headers: Vec<_> = COMMENT_HEADER.captures_iter(section).collect();
+    let mut comments = Vec::new();
+    for (i, captures) in headers.iter().enumerate() {
+        let header = &captures[1];
+        let header_text = decode_entities(&TAG.replace_all(&strip_blocks(header, "form"), ""));
+        let (author, rest) = header_text.split_once(" commented on ")?;
+        let date = DATE
+            .captures(header)
+            .map(|c| html_to_text(&c[1]))
+            .unwrap_or_else(|| rest.trim().lines().next().unwrap_or_default().to_string());
+
+        let content_start = captures.get(0)?.end();
+        let content_end = headers
+            .get(i + 1)
+            .and_then(|next| next.get(0))
+            .map_or(section.len(), |next| next.start());
+        comments.push(PinnedComment {
+            author: author.trim().to_string(),
+            date,
+            text: html_to_text(&section[content_start..content_end]),
+        });
+    }
+    // A pinned section with no comments we can read means the markup changed
+    if comments.is_empty() {
+        return None;
+    }
+    Some(comments)
+}
+
+/// `html` without `<tag>...</tag>` blocks
+fn strip_blocks(html: &str, tag: &str) -> String {
+    Regex::new(&format!(r"(?is)<{0}\b.*?</{0}>", tag))
+        .map(|re| re.replace_all(html, "").into_owned())
+        .unwrap_or_else(|_| html.to_string())
+}
+
+static LINK: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?is)<a\b[^>]*?href="([^"]*)"[^>]*>(.*?)</a>"#).expect("valid regex")
+});
+static LINE_BREAK: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)<br\s*/?>|</(p|pre|li|div|h\d|tr|ul|ol)>").expect("valid regex")
+});
+static LIST_ITEM: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)<li\b[^>]*>").expect("valid regex"));
+static TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)<[^>]*>").expect("valid regex"));
+static SOURCE_NEWLINE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r">\s*\n\s*<").expect("valid regex"));
+static BLANK_LINES: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\n{3,}").expect("valid regex"));
+
+/// Plain text of an HTML fragment. Links become `text (url)`, or just the
+/// url when that is the text; block ends become line breaks.
+pub fn html_to_text(html: &str) -> String {
+    let html = strip_blocks(&strip_blocks(html, "script"), "style");
+    let html = LINK.replace_all(&html, |captures: &regex::Captures| {
+        let href = decode_entities(&captures[1]);
+        let text = TAG.replace_all(&captures[2], "");
+        let text = decode_entities(text.trim());
+        if href.starts_with('#') || href.is_empty() {
+            return text;
+        }
+        let href = if href.starts_with('/') {
+            format!("{}{}", AUR_BASE_URL, href)
+        } else {
+            href
+        };
+        if text.is_empty() || text == href {
+            href
+        } else {
+            format!("{} ({})", text, href)
+        }
+    });
+    // Newlines between tags are markup layout, not text
+    let html = SOURCE_NEWLINE.replace_all(&html, "><");
+    let html = LIST_ITEM.replace_all(&html, "- ");
+    let html = LINE_BREAK.replace_all(&html, "\n");
+    let text = decode_entities(&TAG.replace_all(&html, ""));
+
+    let lines: Vec<&str> = text.lines().map(str::trim).collect();
+    BLANK_LINES
+        .replace_all(&lines.join("\n"), "\n\n")
+        .trim()
+        .to_string()
+}
+
+/// Decode the named entities aurweb emits and numeric references. `&amp;` is
+/// decoded last so `&amp;lt;` stays `&lt;`.
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    static NUMERIC: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"&#(x[0-9a-fA-F]+|[0-9]+);").expect("valid regex"));
+    let text = NUMERIC.replace_all(text, |captures: &regex::Captures| {
+        let code = &captures[1];
+        let value = match code.strip_prefix('x') {
+            Some(hex) => u32::from_str_radix(hex, 16).ok(),
+            None => code.parse().ok(),
+        };
+        value
+            .and_then(char::from_u32)
+            .map(String::from)
+            .unwrap_or_else(|| captures[0].to_string())
+    });
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PINNED: &str = include_str!("../tests/fixtures/aur/pinned.html");
+    const NO_PINNED: &str = include_str!("../tests/fixtures/aur/no_pinned.html");
+    const CHANGED_LAYOUT: &str = include_str!("../tests/fixtures/aur/changed_layout.html");
+
+    #[test]
+    fn test_parses_pinned_comments() {
+        let comments = parse_pinned_comments(PINNED).unwrap();
+
+        assert_eq!(comments.len(), 2);
+        assert_eq!(comments[0].author, "alice");
+        assert_eq!(comments[0].date, "2024-02-11 09:14 (UTC)");
+        assert_eq!(
+            comments[0].text,
+            "If the build fails with a signature error, import the upstream key first:\n\
+             gpg --recv-keys 0123456789ABCDEF\n\
+             \n\
+             See the signing docs (https://example.org/signing) & https://example.org/faq."
+        );
+        assert_eq!(comments[1].author, "bob");
+        assert_eq!(
+            comments[1].text,
+            "After the ICU 74 update you must rebuild:\n\
+             paru -S --rebuild example-browser-bin\n\
+             - clear the build cache\n\
+             - re-run <makepkg -si>"
+        );
+        // Latest Comments aren't pinned
+        assert!(comments.iter().all(|c| c.author != "carol"));
+    }
+
+    #[test]
+    fn test_page_without_pinned_comments() {
+        assert_eq!(parse_pinned_comments(NO_PINNED), Some(Vec::new()));
+    }
+
+    #[test]
+    fn test_unrecognized_layout_degrades() {
+        assert_eq!(parse_pinned_comments(CHANGED_LAYOUT), None);
+        assert_eq!(parse_pinned_comments(""), None);
+    }
+
+    #[tokio::test]
+    async fn test_fresh_cache_entry_is_served() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = AurCommentCache::new(dir.path(), DEFAULT_TTL);
+        let entry = CacheEntry {
+            fetched_at: Utc::now(),
+            etag: Some("\"abc\"".to_string()),
+            last_modified: None,
+            pinned: parse_pinned_comments(PINNED).unwrap(),
+        };
+        cache.write("example-browser-bin", &entry).await.unwrap();
+
+        let comments = cache.pinned("example-browser-bin").await;
+        assert_eq!(comments.unavailable, None);
+        assert_eq!(comments.pinned, entry.pinned);
+        assert!(
+            comments
+                .render()
+                .contains("📌 Pinned AUR comments for example-browser-bin")
+        );
+
+        let invalid = cache.pinned("../etc/passwd").await;
+        assert!(invalid.unavailable.is_some());
+    }
+}
@@ -0,0 +1,692 @@
+//! Local event bus between jarvisd and jarvis-nv
+//!
+//! jarvisd serves a publish/subscribe broker on a Unix socket; every
+//! participant, jarvisd included, connects with a [`BusClient`]. Frames are
+//! newline-delimited JSON. A client says which [`EventKind`]s it wants in its
+//! hello, publishes [`BusEvent`]s, and receives other participants' events of
+//! those kinds. The broker keeps a short history so `jarvis diagnose` can put
+//! what the agents saw recently next to the issue being diagnosed.
+//!
+//! Clients reconnect on their own with exponential backoff, so either daemon
+//! can start or restart first; events published while disconnected are queued
+//! up to a limit and sent once the connection is back. [`local_events`] hands
+//! a process its own events, which the broker doesn't send back.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinSet;
+
+use crate::severity::Severity;
+
+pub const DEFAULT_SOCKET: &str = "/run/jarvis/bus.sock";
+
+/// Events published while disconnected that are kept for the reconnect
+const PUBLISH_QUEUE: usize = 256;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    OperationCompleted,
+    AnomalyDetected,
+    MaintenanceScheduled,
+    HealthStateChanged,
+}
+
+impl EventKind {
+    pub const ALL: [EventKind; 4] = [
+        EventKind::OperationCompleted,
+        EventKind::AnomalyDetected,
+        EventKind::MaintenanceScheduled,
+        EventKind::HealthStateChanged,
+    ];
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BusEvent {
+    /// A package transaction, service operation, or other change finished
+    OperationCompleted {
+        operation: String,
+        target: String,
+        success: bool,
+    },
+    /// A monitor saw something out of the ordinary
+    AnomalyDetected {
+        category: String,
+        component: String,
+        severity: Severity,
+        description: String,
+    },
+    /// Maintenance is queued to run at `at`
+    MaintenanceScheduled { task: String, at: DateTime<Utc> },
+    /// A component moved to another health state, e.g. "degraded"
+    HealthStateChanged { component: String, state: String },
+}
+
+impl BusEvent {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            BusEvent::OperationCompleted { .. } => EventKind::OperationCompleted,
+            BusEvent::AnomalyDetected { .. } => EventKind::AnomalyDetected,
+            BusEvent::MaintenanceScheduled { .. } => EventKind::MaintenanceScheduled,
+            BusEvent::HealthStateChanged { .. } => EventKind::HealthStateChanged,
+        }
+    }
+
+    /// One line for logs and LLM context
+    pub fn summary(&self) -> String {
+        match self {
+            BusEvent::OperationCompleted {
+                operation,
+                target,
+                success,
+            } => format!(
+                "{} {} {}",
+                operation,
+                target,
+                if *success { "completed" } else { "failed" }
+            ),
+            BusEvent::AnomalyDetected {
+                category,
+                component,
+                severity,
+                description,
+            } => format!(
+                "{} anomaly on {} ({}): {}",
+                category, component, severity, description
+            ),
+            BusEvent::MaintenanceScheduled { task, at } => {
+                format!("{} scheduled for {}", task, at.format("%Y-%m-%d %H:%M UTC"))
+            }
+            BusEvent::HealthStateChanged { component, state } => {
+                format!("{} is now {}", component, state)
+            }
+        }
+    }
+}
+
+/// An event with who published it and when
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BusMessage {
+    pub source: String,
+    pub timestamp: DateTime<Utc>,
+    pub event: BusEvent,
+}
+
+/// `[bus]` in jarvis.toml, and in the jarvis-nv config
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BusConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_socket")]
+    pub socket: String,
+    /// Kinds this process sends; others are dropped before they leave it
+    #[serde(default = "all_kinds")]
+    pub publish: Vec<EventKind>,
+    /// Kinds this process receives from the other participants
+    #[serde(default = "all_kinds")]
+    pub subscribe: Vec<EventKind>,
+    /// Events the broker keeps for recent-event queries
+    #[serde(default = "default_history")]
+    pub history: usize,
+    /// How far back `jarvis diagnose` looks
+    #[serde(default = "default_recent_minutes")]
+    pub recent_minutes: u64,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_socket() -> String {
+    DEFAULT_SOCKET.to_string()
+}
+
+fn all_kinds() -> Vec<EventKind> {
+    EventKind::ALL.to_vec()
+}
+
+fn default_history() -> usize {
+    500
+}
+
+fn default_recent_minutes() -> u64 {
+    60
+}
+
+impl Default for BusConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            socket: default_socket(),
+            publish: all_kinds(),
+            subscribe: all_kinds(),
+            history: default_history(),
+            recent_minutes: default_recent_minutes(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "frame", rename_all = "snake_case")]
+enum ClientFrame {
+    Hello {
+        name: String,
+        subscribe: Vec<EventKind>,
+    },
+    Publish {
+        message: BusMessage,
+    },
+    Recent {
+        since: DateTime<Utc>,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "frame", rename_all = "snake_case")]
+enum ServerFrame {
+    Event { message: BusMessage },
+    Recent { messages: Vec<BusMessage> },
+}
+
+async fn write_frame<W, F>(writer: &mut W, frame: &F) -> Result<()>
+where
+    W: AsyncWriteExt + Unpin,
+    F: Serialize,
+{
+    let mut line = serde_json::to_vec(frame)?;
+    line.push(b'\n');
+    writer.write_all(&line).await?;
+    Ok(())
+}
+
+struct Hub {
+    events: broadcast::Sender<BusMessage>,
+    history: Mutex<VecDeque<BusMessage>>,
+    capacity: usize,
+}
+
+impl Hub {
+    fn publish(&self, message: BusMessage) {
+        {
+            let mut history = self.history.lock().unwrap_or_else(|e| e.into_inner());
+            if history.len() >= self.capacity {
+                history.pop_front();
+            }
+            history.push_back(message.clone());
+        }
+        // No receivers just means nobody else is connected
+        let _ = self.events.send(message);
+    }
+
+    fn since(&self, since: DateTime<Utc>) -> Vec<BusMessage> {
+        let history = self.history.lock().unwrap_or_else(|e| e.into_inner());
+        history
+            .iter()
+            .filter(|m| m.timestamp >= since)
+            .cloned()
+            .collect()
+    }
+}
+
+/// The broker; stops and removes its socket when dropped
+pub struct BusServer {
+    path: PathBuf,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl BusServer {
+    /// Serve the bus on `path`, keeping the last `history` events. A socket
+    /// file left behind by a dead broker is replaced; a live one is an error.
+    pub async fn bind(path: impl Into<PathBuf>, history: usize) -> Result<Self> {
+        let path = path.into();
+        if path.exists() {
+            if UnixStream::connect(&path).await.is_ok() {
+                anyhow::bail!("Another bus is already serving {}", path.display());
+            }
+            tokio::fs::remove_file(&path).await?;
+        }
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let listener = UnixListener::bind(&path)
+            .with_context(|| format!("Failed to bind the event bus to {}", path.display()))?;
+        // Owner and group only: anyone connected can publish
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o660))?;
+        }
+
+        let hub = Arc::new(Hub {
+            events: broadcast::channel(PUBLISH_QUEUE).0,
+            history: Mutex::new(VecDeque::new()),
+            capacity: history.max(1),
+        });
+        let task = tokio::spawn(async move {
+            // Owned here so stopping the broker drops every connection
+            let mut participants = JoinSet::new();
+            loop {
+                tokio::select! {
+                    accepted = listener.accept() => match accepted {
+                        Ok((stream, _)) => {
+                            let hub = Arc::clone(&hub);
+                            participants.spawn(async move {
+                                if let Err(e) = serve_participant(stream, hub).await {
+                                    tracing::debug!("Bus participant disconnected: {}", e);
+                                }
+                            });
+                        }
+                        Err(e) => tracing::warn!("Bus accept failed: {}", e),
+                    },
+                    Some(_) = participants.join_next() => {}
+                }
+            }
+        });
+        Ok(Self { path, task })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for BusServer {
+    fn drop(&mut self) {
+        self.task.abort();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+async fn serve_participant(stream: UnixStream, hub: Arc<Hub>) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    let (frames, mut outgoing) = mpsc::channel::<ServerFrame>(PUBLISH_QUEUE);
+
+    // Aborted together when this participant goes away
+    let mut tasks = JoinSet::new();
+    tasks.spawn(async move {
+        while let Some(frame) = outgoing.recv().await {
+            if write_frame(&mut writer, &frame).await.is_err() {
+                break;
+            }
+        }
+    });
+    let mut forwarder: Option<tokio::task::AbortHandle> = None;
+
+    while let Some(line) = lines.next_line().await? {
+        match serde_json::from_str::<ClientFrame>(&line) {
+            Ok(ClientFrame::Hello { name, subscribe }) => {
+                tracing::debug!("{} joined the bus", name);
+                if let Some(previous) = forwarder.take() {
+                    previous.abort();
+                }
+                let subscribe: HashSet<EventKind> = subscribe.into_iter().collect();
+                let mut events = hub.events.subscribe();
+                let frames = frames.clone();
+                forwarder = Some(tasks.spawn(async move {
+                    loop {
+                        let message = match events.recv().await {
+                            Ok(message) => message,
+                            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                                tracing::warn!("{} missed {} bus events", name, skipped);
+                                continue;
+                            }
+                            Err(broadcast::error::RecvError::Closed) => break,
+                        };
+                        if message.source == name || !subscribe.contains(&message.event.kind()) {
+                            continue;
+                        }
+                        if frames.send(ServerFrame::Event { message }).await.is_err() {
+                            break;
+                        }
+                    }
+                }));
+            }
+            Ok(ClientFrame::Publish { message }) => hub.publish(message),
+            Ok(ClientFrame::Recent { since }) => {
+                let messages = hub.since(since);
+                if frames.send(ServerFrame::Recent { messages }).await.is_err() {
+                    break;
+                }
+            }
+            Err(e) => tracing::debug!("Ignoring malformed bus frame: {}", e),
+        }
+    }
+    Ok(())
+}
+
+/// A bus participant. Cheap to clone; the connection closes once every clone
+/// is dropped.
+#[derive(Clone)]
+pub struct BusClient {
+    name: String,
+    publish: Arc<HashSet<EventKind>>,
+    outgoing: mpsc::Sender<BusMessage>,
+    events: broadcast::Sender<BusMessage>,
+}
+
+impl BusClient {
+    /// Join the bus at `config.socket` as `name`. Connecting happens in the
+    /// background and is retried until it succeeds, so this never fails.
+    pub fn connect(config: &BusConfig, name: impl Into<String>) -> Self {
+        let name = name.into();
+        let (outgoing, queued) = mpsc::channel(PUBLISH_QUEUE);
+        let (events, _) = broadcast::channel(PUBLISH_QUEUE);
+        tokio::spawn(run_connection(
+            PathBuf::from(&config.socket),
+            name.clone(),
+            config.subscribe.clone(),
+            queued,
+            events.clone(),
+        ));
+        Self {
+            name,
+            publish: Arc::new(config.publish.iter().copied().collect()),
+            outgoing,
+            events,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Send `event` to the other participants if this process publishes its
+    /// kind. Never waits; when the queue is full the event is dropped.
+    pub fn publish(&self, event: BusEvent) {
+        if !self.publish.contains(&event.kind()) {
+            return;
+        }
+        let message = BusMessage {
+            source: self.name.clone(),
+            timestamp: Utc::now(),
+            event,
+        };
+        if let Err(e) = self.outgoing.try_send(message) {
+            tracing::debug!("Dropping bus event: {}", e);
+        }
+    }
+
+    /// Events from the other participants, of the subscribed kinds
+    pub fn subscribe(&self) -> broadcast::Receiver<BusMessage> {
+        self.events.subscribe()
+    }
+}
+
+/// Keep a connection to the broker up until every [`BusClient`] is dropped
+async fn run_connection(
+    path: PathBuf,
+    name: String,
+    subscribe: Vec<EventKind>,
+    mut queued: mpsc::Receiver<BusMessage>,
+    events: broadcast::Sender<BusMessage>,
+) {
+    let mut backoff = INITIAL_BACKOFF;
+    // An event whose write failed, resent first after reconnecting
+    let mut unsent: Option<BusMessage> = None;
+
+    loop {
+        let stream = match UnixStream::connect(&path).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                tracing::debug!("Bus at {} unavailable: {}", path.display(), e);
+                if queued.is_closed() {
+                    return;
+                }
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                continue;
+            }
+        };
+        backoff = INITIAL_BACKOFF;
+
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        let hello = ClientFrame::Hello {
+            name: name.clone(),
+            subscribe: subscribe.clone(),
+        };
+        if write_frame(&mut writer, &hello).await.is_err() {
+            continue;
+        }
+        tracing::info!("{} connected to the event bus", name);
+
+        loop {
+            if let Some(message) = unsent.take() {
+                let frame = ClientFrame::Publish { message };
+                if let Err(e) = write_frame(&mut writer, &frame).await {
+                    tracing::debug!("Bus write failed: {}", e);
+                    if let ClientFrame::Publish { message } = frame {
+                        unsent = Some(message);
+                    }
+                    break;
+                }
+            }
+            tokio::select! {
+                message = queued.recv() => match message {
+                    Some(message) => unsent = Some(message),
+                    None => return,
+                },
+                line = lines.next_line() => match line {
+                    Ok(Some(line)) => {
+                        if let Ok(ServerFrame::Event { message }) = serde_json::from_str(&line) {
+                            let _ = events.send(message);
+                        }
+                    }
+                    Ok(None) | Err(_) => break,
+                },
+            }
+        }
+        tracing::warn!("{} lost the event bus connection; reconnecting", name);
+    }
+}
+
+/// Events published since `since`, asked of the broker at `socket`
+pub async fn recent_events(
+    socket: impl AsRef<Path>,
+    since: DateTime<Utc>,
+) -> Result<Vec<BusMessage>> {
+    let socket = socket.as_ref();
+    let query = async {
+        let stream = UnixStream::connect(socket)
+            .await
+            .with_context(|| format!("No event bus at {}", socket.display()))?;
+        let (reader, mut writer) = stream.into_split();
+        let hello = ClientFrame::Hello {
+            name: format!("jarvis-{}", std::process::id()),
+            subscribe: Vec::new(),
+        };
+        write_frame(&mut writer, &hello).await?;
+        write_frame(&mut writer, &ClientFrame::Recent { since }).await?;
+
+        let mut lines = BufReader::new(reader).lines();
+        while let Some(line) = lines.next_line().await? {
+            if let Ok(ServerFrame::Recent { messages }) = serde_json::from_str(&line) {
+                return Ok(messages);
+            }
+        }
+        anyhow::bail!("The event bus closed the connection")
+    };
+    tokio::time::timeout(Duration::from_secs(2), query)
+        .await
+        .context("The event bus did not answer")?
+}
+
+/// `messages` as a timeline relative to `now`, oldest first
+pub fn render_timeline(messages: &[BusMessage], now: DateTime<Utc>) -> String {
+    let mut out = String::new();
+    for message in messages {
+        let minutes = (now - message.timestamp).num_minutes().max(0);
+        let _ = writeln!(
+            out,
+            "- {} ({} min ago) [{}] {}",
+            message.timestamp.format("%H:%M:%S"),
+            minutes,
+            message.source,
+            message.event.summary()
+        );
+    }
+    out
+}
+
+static GLOBAL: OnceLock<BusClient> = OnceLock::new();
+static LOCAL: OnceLock<broadcast::Sender<BusMessage>> = OnceLock::new();
+
+/// Make `client` the process's publisher for [`publish`]
+pub fn install(client: BusClient) {
+    if GLOBAL.set(client).is_err() {
+        tracing::debug!("An event bus client is already installed");
+    }
+}
+
+/// Publish through the installed client, and to this process's
+/// [`local_events`] subscribers whether or not a client is installed
+pub fn publish(event: BusEvent) {
+    let local = local();
+    if local.receiver_count() > 0 {
+        let _ = local.send(BusMessage {
+            source: GLOBAL
+                .get()
+                .map(|client| client.name().to_string())
+                .unwrap_or_else(|| "local".to_string()),
+            timestamp: Utc::now(),
+            event: event.clone(),
+        });
+    }
+    if let Some(client) = GLOBAL.get() {
+        client.publish(event);
+    }
+}
+
+/// Events this process publishes, for consumers in the same process such as
+/// the incident correlator; the broker never echoes a client's own events
+pub fn local_events() -> broadcast::Receiver<BusMessage> {
+    local().subscribe()
+}
+
+fn local() -> &'static broadcast::Sender<BusMessage> {
+    LOCAL.get_or_init(|| broadcast::channel(PUBLISH_QUEUE).0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn next(receiver: &mut broadcast::Receiver<BusMessage>) -> BusMessage {
+        tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+            .await
+            .expect("bus event within 5s")
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_two_participants_exchange_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("bus.sock");
+        let config = BusConfig {
+            socket: socket.to_string_lossy().into_owned(),
+            ..BusConfig::default()
+        };
+        let server = BusServer::bind(&socket, 10).await.unwrap();
+
+        let jarvisd = BusClient::connect(&config, "jarvisd");
+        let nv = BusClient::connect(
+            &BusConfig {
+                subscribe: vec![EventKind::OperationCompleted],
+                ..config.clone()
+            },
+            "jarvis-nv",
+        );
+        let mut jarvisd_events = jarvisd.subscribe();
+        let mut nv_events = nv.subscribe();
+        // Both hellos have to reach the broker before anything is published
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        jarvisd.publish(BusEvent::HealthStateChanged {
+            component: "agents".to_string(),
+            state: "degraded".to_string(),
+        });
+        jarvisd.publish(BusEvent::OperationCompleted {
+            operation: "package_transaction".to_string(),
+            target: "nvidia-dkms".to_string(),
+            success: true,
+        });
+        // jarvis-nv only subscribed to operations
+        let received = next(&mut nv_events).await;
+        assert_eq!(received.source, "jarvisd");
+        assert_eq!(received.event.kind(), EventKind::OperationCompleted);
+
+        nv.publish(BusEvent::AnomalyDetected {
+            category: "performance".to_string(),
+            component: "gpu0".to_string(),
+            severity: Severity::High,
+            description: "Temperature 91°C".to_string(),
+        });
+        let received = next(&mut jarvisd_events).await;
+        assert_eq!(received.source, "jarvis-nv");
+        assert!(received.event.summary().contains("anomaly on gpu0"));
+
+        let recent = recent_events(&socket, Utc::now() - chrono::Duration::minutes(5))
+            .await
+            .unwrap();
+        assert_eq!(recent.len(), 3);
+        let timeline = render_timeline(&recent, Utc::now());
+        assert!(timeline.contains("[jarvisd] package_transaction nvidia-dkms completed"));
+
+        // Events published while the broker is down reach the restarted one
+        drop(server);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let failed = BusEvent::OperationCompleted {
+            operation: "service_operation".to_string(),
+            target: "ollama.service".to_string(),
+            success: false,
+        };
+        jarvisd.publish(failed.clone());
+        let _server = BusServer::bind(&socket, 10).await.unwrap();
+        let mut recent = Vec::new();
+        for _ in 0..50 {
+            recent = recent_events(&socket, Utc::now() - chrono::Duration::minutes(5))
+                .await
+                .unwrap();
+            if !recent.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].event, failed);
+
+        // and jarvis-nv has resubscribed
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        jarvisd.publish(BusEvent::OperationCompleted {
+            operation: "package_transaction".to_string(),
+            target: "cuda".to_string(),
+            success: true,
+        });
+        let received = next(&mut nv_events).await;
+        assert!(received.event.summary().contains("cuda"));
+    }
+
+    #[tokio::test]
+    async fn test_local_events_without_a_client() {
+        let mut events = local_events();
+        publish(BusEvent::HealthStateChanged {
+            component: "container api".to_string(),
+            state: "unhealthy".to_string(),
+        });
+        let received = next(&mut events).await;
+        assert_eq!(received.source, "local");
+        assert_eq!(received.event.summary(), "container api is now unhealthy");
+    }
+}
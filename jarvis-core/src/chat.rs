@@ -0,0 +1,580 @@
+//! Persistent chat sessions
+//!
+//! Every `jarvis chat` run is a conversation in [`MemoryStore`]. Turns are
+//! written as they happen, so an interrupted session loses nothing, and a
+//! resumed session replays only as much recent history as fits the backend's
+//! context window.
+//!
+//! Files, probe results, and operation results can be attached to a session
+//! with `/attach`. Attachments are sent with every prompt until detached and
+//! are stored with the session, but they come second to the conversation:
+//! when the two don't fit together, attachments are cut first.
+//!
+//! Commands a reply proposes are tracked as pending actions, see
+//! [`chat_actions`](crate::chat_actions), so "run 2" can refer back to them.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::chat_actions::ActionRegistry;
+use crate::llm::context_window::truncate_to_tokens;
+use crate::llm::{ContextWindowManager, LLMRouter, estimate_tokens};
+use crate::memory::MemoryStore;
+use crate::types::{Message, MessageMetadata, MessageRole};
+
+const CHAT_INSTRUCTION: &str = "You are Jarvis, a local AI assistant for Rust, Linux, and homelab \
+operations. Continue the conversation below and reply to the user's last message.";
+
+/// Memory document with the most recent `jarvis arch` operation result
+pub const LAST_OPERATION_DOCUMENT: &str = "last_operation";
+
+pub const ATTACH_USAGE: &str =
+    "Usage: /attach file <path> | /attach probe <name> | /attach last-op";
+
+/// Attachments shorter than this are left out rather than cut to nothing
+const MIN_ATTACHMENT_TOKENS: usize = 32;
+
+/// A chat conversation and the turns recorded so far
+#[derive(Debug, Clone)]
+pub struct ChatSession {
+    id: Uuid,
+    title: String,
+    turns: Vec<Message>,
+    attachments: Vec<Attachment>,
+    /// Commands the latest replies proposed
+    actions: ActionRegistry,
+}
+
+/// Where an attachment came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AttachmentKind {
+    File,
+    Probe,
+    LastOp,
+}
+
+impl AttachmentKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AttachmentKind::File => "file",
+            AttachmentKind::Probe => "probe",
+            AttachmentKind::LastOp => "last-op",
+        }
+    }
+}
+
+/// Content attached to a session, shown as a named chip
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    /// What `/detach` calls it
+    pub name: String,
+    pub kind: AttachmentKind,
+    /// The path, probe, or operation it was taken from
+    pub source: String,
+    pub content: String,
+    /// Size of the content as attached, before any summarizing
+    pub original_tokens: usize,
+    pub summarized: bool,
+    pub attached_at: DateTime<Utc>,
+}
+
+impl Attachment {
+    pub fn new(name: &str, kind: AttachmentKind, source: &str, content: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+            source: source.to_string(),
+            content: content.to_string(),
+            original_tokens: estimate_tokens(content),
+            summarized: false,
+            attached_at: Utc::now(),
+        }
+    }
+
+    pub fn tokens(&self) -> usize {
+        estimate_tokens(&self.content)
+    }
+
+    /// Summarize the content when it alone would take more than half of the
+    /// chat budget in `window`
+    pub async fn condense(
+        mut self,
+        llm: &LLMRouter,
+        window: &ContextWindowManager,
+    ) -> Result<Self> {
+        let limit = window.budget(CHAT_INSTRUCTION) / 2;
+        if self.original_tokens <= limit {
+            return Ok(self);
+        }
+
+        let fitted = ContextWindowManager::new(limit)
+            .with_reserve(0)
+            .fit(llm, "", &self.content, &self.source)
+            .await?;
+        self.content = fitted.content;
+        self.summarized = true;
+        Ok(self)
+    }
+}
+
+/// The attachment commands of a chat, in the terminal and in the editor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChatCommand {
+    /// `/attach file <path>`, `/attach probe <name>`, or `/attach last-op`
+    Attach {
+        kind: AttachmentKind,
+        target: String,
+    },
+    /// `/attachments`
+    Attachments,
+    /// `/detach <name>`
+    Detach(String),
+}
+
+impl ChatCommand {
+    /// The command `input` spells; `None` when it is none of these, an
+    /// error with the usage when its arguments are wrong
+    pub fn parse(input: &str) -> Option<Result<Self>> {
+        let input = input.trim();
+        let (command, args) = input
+            .split_once(char::is_whitespace)
+            .map(|(command, args)| (command, args.trim()))
+            .unwrap_or((input, ""));
+
+        let parsed = match command {
+            "/attach" => parse_attach(args),
+            "/attachments" => Ok(ChatCommand::Attachments),
+            "/detach" if args.is_empty() => Err(anyhow::anyhow!("Usage: /detach <name>")),
+            "/detach" => Ok(ChatCommand::Detach(args.to_string())),
+            _ => return None,
+        };
+        Some(parsed)
+    }
+}
+
+fn parse_attach(args: &str) -> Result<ChatCommand> {
+    let (kind, target) = args
+        .split_once(char::is_whitespace)
+        .map(|(kind, target)| (kind, target.trim()))
+        .unwrap_or((args, ""));
+    let kind = match (kind, target.is_empty()) {
+        ("file", false) => AttachmentKind::File,
+        ("probe", false) => AttachmentKind::Probe,
+        ("last-op", true) => AttachmentKind::LastOp,
+        _ => anyhow::bail!(ATTACH_USAGE),
+    };
+    Ok(ChatCommand::Attach {
+        kind,
+        target: target.to_string(),
+    })
+}
+
+/// The suffix of a session that fits a token budget
+#[derive(Debug)]
+pub struct Replay<'a> {
+    pub turns: &'a [Message],
+    /// Earlier turns left out to stay within budget
+    pub omitted: usize,
+}
+
+impl ChatSession {
+    /// Start a new session
+    pub async fn start(memory: &MemoryStore) -> Result<Self> {
+        let title = format!("Chat {}", Local::now().format("%Y-%m-%d %H:%M"));
+        let conversation = memory.create_conversation(&title).await?;
+
+        Ok(Self {
+            id: Uuid::parse_str(&conversation.id)?,
+            title,
+            turns: Vec::new(),
+            attachments: Vec::new(),
+            actions: ActionRegistry::default(),
+        })
+    }
+
+    /// Continue a session by id, or the most recently active one with `last`
+    pub async fn resume(memory: &MemoryStore, selector: &str) -> Result<Self> {
+        let id = if selector == "last" {
+            let latest = memory
+                .list_conversations(1)
+                .await?
+                .into_iter()
+                .next()
+                .context("No chat sessions to resume")?;
+            Uuid::parse_str(&latest.id)?
+        } else {
+            Uuid::parse_str(selector)
+                .with_context(|| format!("Invalid session id '{}'", selector))?
+        };
+
+        let conversation = memory
+            .get_conversation(id)
+            .await?
+            .with_context(|| format!("No chat session with id {}", id))?;
+
+        let attachments = match memory.get_document(&attachments_key(id)).await? {
+            Some(data) => serde_json::from_str(&data)?,
+            None => Vec::new(),
+        };
+
+        // Replaying the turns leaves the actions as the session left them
+        let mut actions = ActionRegistry::default();
+        for turn in &conversation.messages {
+            actions.observe(turn);
+        }
+
+        Ok(Self {
+            id,
+            title: conversation.title,
+            turns: conversation.messages,
+            attachments,
+            actions,
+        })
+    }
+
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn turns(&self) -> &[Message] {
+        &self.turns
+    }
+
+    pub fn attachments(&self) -> &[Attachment] {
+        &self.attachments
+    }
+
+    pub fn actions(&self) -> &ActionRegistry {
+        &self.actions
+    }
+
+    pub fn actions_mut(&mut self) -> &mut ActionRegistry {
+        &mut self.actions
+    }
+
+    /// Add `attachment`, replacing one of the same name, and persist the set
+    pub async fn attach(&mut self, memory: &MemoryStore, attachment: Attachment) -> Result<()> {
+        self.attachments.retain(|a| a.name != attachment.name);
+        self.attachments.push(attachment);
+        self.save_attachments(memory).await
+    }
+
+    /// Remove the attachment called `name`; false when there is none
+    pub async fn detach(&mut self, memory: &MemoryStore, name: &str) -> Result<bool> {
+        let before = self.attachments.len();
+        self.attachments.retain(|a| a.name != name);
+        if self.attachments.len() == before {
+            return Ok(false);
+        }
+        self.save_attachments(memory).await?;
+        Ok(true)
+    }
+
+    async fn save_attachments(&self, memory: &MemoryStore) -> Result<()> {
+        memory
+            .store_document(
+                &attachments_key(self.id),
+                &serde_json::to_string(&self.attachments)?,
+            )
+            .await
+    }
+
+    /// Persist a turn immediately and append it to the session. Replies
+    /// register the commands they propose; user turns age them.
+    pub async fn record(
+        &mut self,
+        memory: &MemoryStore,
+        role: MessageRole,
+        content: &str,
+    ) -> Result<()> {
+        let message = memory
+            .add_message(self.id, role, content, MessageMetadata::default())
+            .await?;
+        memory.touch_conversation(self.id).await?;
+        self.actions.observe(&message);
+        self.turns.push(message);
+        Ok(())
+    }
+
+    /// The most recent turns whose combined size fits `budget` tokens.
+    /// The latest turn is always included, even when it alone exceeds the budget.
+    pub fn replay(&self, budget: usize) -> Replay<'_> {
+        let mut used = 0;
+        let mut start = self.turns.len();
+
+        for (index, turn) in self.turns.iter().enumerate().rev() {
+            let tokens = turn_tokens(turn);
+            if used + tokens > budget && start < self.turns.len() {
+                break;
+            }
+            used += tokens;
+            start = index;
+        }
+
+        Replay {
+            turns: &self.turns[start..],
+            omitted: start,
+        }
+    }
+
+    /// Prompt for the next assistant reply, replaying history within the
+    /// context window. Attachments get what the history leaves of the budget.
+    pub fn prompt(&self, window: &ContextWindowManager) -> String {
+        let budget = window.budget(CHAT_INSTRUCTION);
+        let replay = self.replay(budget);
+        let history: usize = replay.turns.iter().map(turn_tokens).sum();
+
+        let mut prompt = format!("{}\n\n", CHAT_INSTRUCTION);
+        if crate::read_only::is_read_only() {
+            prompt.push_str(&format!("{}\n\n", crate::read_only::PROMPT_NOTE));
+        }
+        prompt.push_str(&render_attachments(
+            &self.attachments,
+            budget.saturating_sub(history),
+        ));
+        if replay.omitted > 0 {
+            prompt.push_str(&format!("[{} earlier turns omitted]\n\n", replay.omitted));
+        }
+        for turn in replay.turns {
+            let content = truncate_to_tokens(&turn.content, budget);
+            prompt.push_str(&format!("{}: {}\n\n", speaker(&turn.role), content.trim()));
+        }
+        prompt.push_str("Jarvis:");
+        prompt
+    }
+
+    /// Number of `per_page`-sized pages of history
+    pub fn page_count(&self, per_page: usize) -> usize {
+        self.turns.len().div_ceil(per_page.max(1))
+    }
+
+    /// Page of history counted back from the newest turns; page 1 is the most recent
+    pub fn history_page(&self, page: usize, per_page: usize) -> &[Message] {
+        let per_page = per_page.max(1);
+        let end = self
+            .turns
+            .len()
+            .saturating_sub(page.saturating_sub(1) * per_page);
+        let start = end.saturating_sub(per_page);
+        &self.turns[start..end]
+    }
+}
+
+/// Display name for a turn's author
+pub fn speaker(role: &MessageRole) -> &'static str {
+    match role {
+        MessageRole::User => "User",
+        MessageRole::Assistant => "Jarvis",
+        MessageRole::System => "System",
+        MessageRole::Tool => "Tool",
+    }
+}
+
+fn attachments_key(id: Uuid) -> String {
+    format!("chat_attachments:{}", id)
+}
+
+/// Attachments in the order they were added, each cut to what is left of
+/// `budget`. Those with no room left are still named, so the model knows
+/// they exist.
+fn render_attachments(attachments: &[Attachment], budget: usize) -> String {
+    let mut remaining = budget;
+    let mut out = String::new();
+
+    for attachment in attachments {
+        let header = format!(
+            "[Attached {} '{}' from {}]",
+            attachment.kind.as_str(),
+            attachment.name,
+            attachment.source
+        );
+        let room = remaining.saturating_sub(estimate_tokens(&header));
+        if room < MIN_ATTACHMENT_TOKENS.min(attachment.tokens()) || room == 0 {
+            out.push_str(&format!(
+                "[Attached {} '{}' left out: no room in the context window]\n\n",
+                attachment.kind.as_str(),
+                attachment.name
+            ));
+            continue;
+        }
+
+        let content = truncate_to_tokens(attachment.content.trim(), room);
+        remaining = room.saturating_sub(estimate_tokens(&content));
+        out.push_str(&format!("{}\n{}\n\n", header, content));
+    }
+    out
+}
+
+fn turn_tokens(turn: &Message) -> usize {
+    // Speaker label and separators
+    estimate_tokens(&turn.content) + 4
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn session(contents: &[&str]) -> ChatSession {
+        let id = Uuid::new_v4();
+        ChatSession {
+            id,
+            title: "test".to_string(),
+            turns: contents
+                .iter()
+                .enumerate()
+                .map(|(i, content)| Message {
+                    id: Uuid::new_v4().to_string(),
+                    conversation_id: id.to_string(),
+                    role: if i % 2 == 0 {
+                        MessageRole::User
+                    } else {
+                        MessageRole::Assistant
+                    },
+                    content: content.to_string(),
+                    metadata: MessageMetadata::default(),
+                    created_at: Utc::now(),
+                })
+                .collect(),
+            attachments: Vec::new(),
+            actions: ActionRegistry::default(),
+        }
+    }
+
+    #[test]
+    fn test_replay_keeps_newest_turns_within_budget() {
+        let long = "word ".repeat(200);
+        let session = session(&[&long, &long, "short question", "short answer"]);
+
+        let replay = session.replay(50);
+        assert_eq!(replay.omitted, 2);
+        assert_eq!(replay.turns.len(), 2);
+        assert_eq!(replay.turns[1].content, "short answer");
+
+        let replay = session.replay(usize::MAX);
+        assert_eq!(replay.omitted, 0);
+        assert_eq!(replay.turns.len(), 4);
+    }
+
+    #[test]
+    fn test_replay_always_includes_latest_turn() {
+        let long = "word ".repeat(200);
+        let session = session(&["hello", &long]);
+
+        let replay = session.replay(10);
+        assert_eq!(replay.turns.len(), 1);
+        assert_eq!(replay.omitted, 1);
+    }
+
+    #[test]
+    fn test_prompt_notes_omitted_turns() {
+        let long = "word ".repeat(4000);
+        let session = session(&[&long, "reply", "next question"]);
+
+        let prompt = session.prompt(&ContextWindowManager::new(2048));
+        assert!(prompt.contains("[1 earlier turns omitted]"));
+        assert!(prompt.contains("User: next question"));
+        assert!(prompt.ends_with("Jarvis:"));
+    }
+
+    #[test]
+    fn test_prompt_trims_attachments_before_history() {
+        let window = ContextWindowManager::new(2048);
+        let turn = "word ".repeat(300);
+        let mut chat = session(&[&turn, &turn, "what does the log say?"]);
+        chat.attachments.push(Attachment::new(
+            "journal",
+            AttachmentKind::Probe,
+            "journal errors",
+            &"error line\n".repeat(1000),
+        ));
+
+        // The history fits, so it is replayed whole and the attachment is cut
+        let prompt = chat.prompt(&window);
+        assert!(!prompt.contains("earlier turns omitted"));
+        assert!(prompt.contains("User: what does the log say?"));
+        assert!(prompt.contains("[Attached probe 'journal' from journal errors]"));
+        assert!(prompt.contains("[...truncated]"));
+        assert!(estimate_tokens(&prompt) <= window.budget("") + 64);
+
+        // History that outgrows the budget crowds attachments out entirely
+        let long = "word ".repeat(3000);
+        let mut full = session(&[&long]);
+        full.attachments = chat.attachments.clone();
+        let prompt = full.prompt(&window);
+        assert!(prompt.contains("[Attached probe 'journal' left out"));
+        assert!(!prompt.contains("error line"));
+
+        // Without history in the way, a small attachment goes in verbatim
+        let mut short = session(&["hi"]);
+        short.attachments.push(Attachment::new(
+            "fstab",
+            AttachmentKind::File,
+            "/etc/fstab",
+            "UUID=abc / btrfs defaults 0 0",
+        ));
+        assert!(
+            short
+                .prompt(&window)
+                .contains("UUID=abc / btrfs defaults 0 0")
+        );
+    }
+
+    #[test]
+    fn test_chat_commands_parse() {
+        assert_eq!(
+            ChatCommand::parse("/attach file ~/notes/nginx.conf")
+                .unwrap()
+                .unwrap(),
+            ChatCommand::Attach {
+                kind: AttachmentKind::File,
+                target: "~/notes/nginx.conf".to_string(),
+            }
+        );
+        assert_eq!(
+            ChatCommand::parse("/attach last-op").unwrap().unwrap(),
+            ChatCommand::Attach {
+                kind: AttachmentKind::LastOp,
+                target: String::new(),
+            }
+        );
+        assert_eq!(
+            ChatCommand::parse(" /detach journal ").unwrap().unwrap(),
+            ChatCommand::Detach("journal".to_string())
+        );
+        assert_eq!(
+            ChatCommand::parse("/attachments").unwrap().unwrap(),
+            ChatCommand::Attachments
+        );
+        assert!(ChatCommand::parse("/attach probe").unwrap().is_err());
+        assert!(ChatCommand::parse("/attach last-op now").unwrap().is_err());
+        assert!(ChatCommand::parse("/detach").unwrap().is_err());
+        assert!(ChatCommand::parse("/history 2").is_none());
+        assert!(ChatCommand::parse("attach this please").is_none());
+    }
+
+    #[test]
+    fn test_history_pages_count_back_from_newest() {
+        let session = session(&["1", "2", "3", "4", "5"]);
+
+        assert_eq!(session.page_count(2), 3);
+        let contents = |page| {
+            session
+                .history_page(page, 2)
+                .iter()
+                .map(|m| m.content.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(contents(1), ["4", "5"]);
+        assert_eq!(contents(2), ["2", "3"]);
+        assert_eq!(contents(3), ["1"]);
+        assert!(contents(4).is_empty());
+    }
+}
@@ -0,0 +1,950 @@
+//! Commands proposed in chat that a short reply can run
+//!
+//! When a chat reply suggests commands, in fenced shell blocks or as a
+//! numbered plan with inline commands, each becomes a numbered
+//! [`PendingAction`] classified by [`Risk`]. A short reply such as "do it",
+//! "run 2", or "skip the dangerous one" is then matched against them: the
+//! [`match_reference`] rules handle the common phrasings, and replies they
+//! can't place are put to the model through [`Disambiguator`]. Pending
+//! actions are forgotten after a few user turns, so "do it" can't reach back
+//! to a suggestion the conversation has moved on from.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::process::Output;
+use std::sync::LazyLock;
+
+use crate::exec::OutputText;
+use crate::llm::context_window::truncate_to_tokens;
+use crate::llm::{Intent, LLMRouter};
+use crate::remote::CommandExecutor;
+use crate::types::{Message, MessageRole};
+
+/// User turns a proposed action stays pending for
+pub const DEFAULT_EXPIRY_TURNS: usize = 3;
+
+/// Replies longer than this are conversation, not references to actions
+const MAX_REFERENCE_WORDS: usize = 10;
+
+/// Output of a run action fed back into the conversation
+const MAX_OUTPUT_TOKENS: usize = 1000;
+
+/// Fence languages whose lines are all commands
+const SHELL_LANGS: &[&str] = &["sh", "bash", "shell", "zsh", "fish"];
+
+/// Fence languages that mix `$ `-prompted commands with their output
+const SESSION_LANGS: &[&str] = &["", "console", "shell-session", "terminal"];
+
+static LIST_ITEM: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*\d+[.)]\s+(.*)$").expect("valid regex"));
+static INLINE_CODE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"`([^`]+)`").expect("valid regex"));
+/// A program and at least one argument, but not `key = value` or `key: value`
+static INLINE_COMMAND: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:sudo\s+)?[a-z][a-z0-9._+-]*\s+[^=:\s]").expect("valid regex")
+});
+static JSON_OBJECT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)\{.*?\}").expect("valid regex"));
+
+/// How much running a command could change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Risk {
+    ReadOnly,
+    Changes,
+    /// Deletes data, takes the machine down, or runs a downloaded script
+    Dangerous,
+}
+
+impl Risk {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Risk::ReadOnly => "read-only",
+            Risk::Changes => "changes the system",
+            Risk::Dangerous => "dangerous",
+        }
+    }
+}
+
+/// Programs that destroy data or stop the machine whatever their arguments
+const DANGEROUS_PROGRAMS: &[&str] = &[
+    "dd",
+    "mkfs",
+    "wipefs",
+    "shred",
+    "fdisk",
+    "sfdisk",
+    "gdisk",
+    "sgdisk",
+    "parted",
+    "cryptsetup",
+    "reboot",
+    "poweroff",
+    "shutdown",
+    "halt",
+    "userdel",
+];
+
+/// The risk of the riskiest part of `command`, which may be a pipeline or a
+/// `&&` chain
+pub fn classify(command: &str) -> Risk {
+    let mut risk = Risk::ReadOnly;
+    for (position, segment) in segments(command).into_iter().enumerate() {
+        let owned = words(segment);
+        let words: Vec<&str> = owned.iter().map(String::as_str).collect();
+        let Some((program, args)) = unwrap_elevation(&words) else {
+            continue;
+        };
+        let segment_risk = if position > 0 && matches!(program, "sh" | "bash" | "zsh") {
+            // `curl ... | sh`
+            Risk::Dangerous
+        } else if is_dangerous(program, args) {
+            Risk::Dangerous
+        } else if crate::read_only::is_mutating_command(program, args) {
+            Risk::Changes
+        } else {
+            Risk::ReadOnly
+        };
+        risk = risk.max(segment_risk);
+    }
+
+    // Redirecting into a file writes it; into a block device destroys it
+    if let Some((_, target)) = command.split_once('>') {
+        let target = target.trim_start_matches(['>', '|']).trim_start();
+        if target.starts_with("/dev/sd") || target.starts_with("/dev/nvme") {
+            risk = Risk::Dangerous;
+        } else if !target.starts_with("/dev/null") && !target.starts_with('&') {
+            risk = risk.max(Risk::Changes);
+        }
+    }
+    risk
+}
+
+fn is_dangerous(program: &str, args: &[&str]) -> bool {
+    let program = program.rsplit('/').next().unwrap_or(program);
+    if DANGEROUS_PROGRAMS.contains(&program) || program.starts_with("mkfs.") {
+        return true;
+    }
+    let short = |flag: char| {
+        args.iter()
+            .any(|a| a.starts_with('-') && !a.starts_with("--") && a.contains(flag))
+    };
+    let first = args.iter().find(|a| !a.starts_with('-')).copied();
+    match program {
+        "rm" => {
+            short('r') || short('f') || args.contains(&"--recursive") || args.contains(&"--force")
+        }
+        "chmod" | "chown" => short('R') || args.contains(&"--recursive"),
+        // Removing packages can take the system's dependencies with them
+        "pacman" | "yay" | "paru" => args.iter().any(|a| {
+            a.starts_with("-R") || *a == "--remove" || (a.starts_with('-') && a.contains("dd"))
+        }),
+        "systemctl" => matches!(first, Some("poweroff" | "reboot" | "halt" | "kexec")),
+        "snapper" => matches!(first, Some("rollback" | "undochange" | "delete")),
+        "btrfs" => args.windows(2).any(|w| w == ["subvolume", "delete"]),
+        "docker" | "podman" => args
+            .windows(2)
+            .any(|w| w == ["system", "prune"] || w == ["volume", "rm"]),
+        _ => false,
+    }
+}
+
+/// The command `sudo` and friends would run, past their options
+fn unwrap_elevation<'a>(words: &'a [&'a str]) -> Option<(&'a str, &'a [&'a str])> {
+    let (program, args) = words.split_first()?;
+    if matches!(*program, "sudo" | "doas" | "run0" | "pkexec") {
+        let start = args.iter().position(|a| !a.starts_with('-'))?;
+        return unwrap_elevation(&args[start..]);
+    }
+    Some((*program, args))
+}
+
+/// Parts of a command line joined by pipes, `&&`, `||`, or `;`
+fn segments(command: &str) -> Vec<&str> {
+    command
+        .split(['|', '&', ';', '\n'])
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Whitespace-separated words with surrounding quotes removed
+fn words(segment: &str) -> Vec<String> {
+    segment
+        .split_whitespace()
+        .map(|w| w.trim_matches(['"', '\'']).to_string())
+        .collect()
+}
+
+/// A command found in a reply, before it is numbered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProposedAction {
+    pub command: String,
+    /// The prose that introduced it
+    pub description: Option<String>,
+}
+
+/// Commands `response` proposes, in the order they appear: each command line
+/// of a fenced shell block, and each numbered plan step with an inline command
+pub fn extract(response: &str) -> Vec<ProposedAction> {
+    let mut proposed = Vec::new();
+    // (language, pending continuation) while inside a fence
+    let mut fence: Option<(String, String)> = None;
+    let mut last_prose: Option<String> = None;
+
+    for line in response.lines() {
+        let trimmed = line.trim();
+        if let Some(lang) = trimmed.strip_prefix("```") {
+            fence = match fence {
+                Some(_) => None,
+                None => Some((lang.trim().to_lowercase(), String::new())),
+            };
+            continue;
+        }
+
+        if let Some((lang, continued)) = fence.as_mut() {
+            // A continued line belongs to its command whatever it looks like
+            let command = if !continued.is_empty() {
+                Some(trimmed)
+            } else if SHELL_LANGS.contains(&lang.as_str()) {
+                Some(trimmed.strip_prefix("$ ").unwrap_or(trimmed))
+            } else if SESSION_LANGS.contains(&lang.as_str()) {
+                trimmed.strip_prefix("$ ")
+            } else {
+                None
+            };
+            let Some(command) = command.filter(|c| !c.is_empty() && !c.starts_with('#')) else {
+                continue;
+            };
+            if let Some(head) = command.strip_suffix('\\') {
+                continued.push_str(head);
+                continue;
+            }
+            let command = format!("{}{}", std::mem::take(continued), command);
+            proposed.push(ProposedAction {
+                command: command.trim().to_string(),
+                description: last_prose.clone(),
+            });
+            continue;
+        }
+
+        if trimmed.is_empty() {
+            continue;
+        }
+        if let Some(item) = LIST_ITEM.captures(line).map(|c| c[1].to_string()) {
+            let command = INLINE_CODE
+                .captures_iter(&item)
+                .map(|c| c[1].trim().to_string())
+                .find(|code| INLINE_COMMAND.is_match(code));
+            if let Some(command) = command {
+                let description = INLINE_CODE.replace_all(&item, "");
+                let description = description
+                    .trim()
+                    .trim_end_matches([':', '-', '—', ','])
+                    .trim();
+                proposed.push(ProposedAction {
+                    command,
+                    description: (!description.is_empty()).then(|| description.to_string()),
+                });
+                last_prose = None;
+                continue;
+            }
+        }
+        last_prose = Some(
+            trimmed
+                .trim_start_matches(['-', '*', ' '])
+                .trim_end_matches(':')
+                .to_string(),
+        );
+    }
+    proposed
+}
+
+/// A proposed command waiting for the user's go-ahead
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PendingAction {
+    /// 1-based, in the order the reply proposed them
+    pub number: usize,
+    pub command: String,
+    pub description: Option<String>,
+    pub risk: Risk,
+}
+
+impl PendingAction {
+    /// "2. `sudo systemctl restart nginx` (changes the system)"
+    pub fn describe(&self) -> String {
+        format!(
+            "{}. `{}` ({})",
+            self.number,
+            self.command,
+            self.risk.as_str()
+        )
+    }
+
+    /// The program and arguments when the command needs no shell to run
+    pub fn argv(&self) -> Option<Vec<String>> {
+        let needs_shell = self.command.contains([
+            '|', '&', ';', '<', '>', '$', '`', '*', '?', '(', ')', '{', '}', '~', '\\', '"', '\'',
+        ]);
+        (!needs_shell).then(|| {
+            self.command
+                .split_whitespace()
+                .map(str::to_string)
+                .collect()
+        })
+    }
+
+    /// Run the command through `executor`, and through `sh -c` when it needs a shell
+    pub async fn run(&self, executor: &CommandExecutor) -> Result<Output> {
+        match self.argv() {
+            Some(argv) => {
+                let args: Vec<&str> = argv[1..].iter().map(String::as_str).collect();
+                executor.run(&argv[0], &args).await
+            }
+            None => executor.run("sh", &["-c", &self.command]).await,
+        }
+    }
+
+    /// What happened, as a turn for the conversation
+    pub fn report(&self, output: &Output) -> String {
+        let text = OutputText::decode(output);
+        let exit = output.status.code().map_or_else(
+            || "killed by a signal".to_string(),
+            |code| format!("exit {}", code),
+        );
+        let combined = format!("{}{}", text.stdout, text.stderr);
+        let combined = combined.trim();
+        if combined.is_empty() {
+            return format!("Ran `{}`: {}, no output", self.command, exit);
+        }
+        format!(
+            "Ran `{}`: {}\n{}",
+            self.command,
+            exit,
+            truncate_to_tokens(combined, MAX_OUTPUT_TOKENS)
+        )
+    }
+}
+
+/// The actions proposed by the most recent reply that proposed any
+#[derive(Debug, Clone)]
+pub struct ActionRegistry {
+    actions: Vec<PendingAction>,
+    /// User turns since the actions were proposed
+    age: usize,
+    expiry_turns: usize,
+}
+
+impl Default for ActionRegistry {
+    fn default() -> Self {
+        Self::new(DEFAULT_EXPIRY_TURNS)
+    }
+}
+
+impl ActionRegistry {
+    pub fn new(expiry_turns: usize) -> Self {
+        Self {
+            actions: Vec::new(),
+            age: 0,
+            expiry_turns,
+        }
+    }
+
+    /// Replace the pending actions with those `response` proposes; a reply
+    /// proposing nothing leaves them as they are. Returns how many it proposed.
+    pub fn register(&mut self, response: &str) -> usize {
+        let proposed = extract(response);
+        if proposed.is_empty() {
+            return 0;
+        }
+        self.actions = proposed
+            .into_iter()
+            .enumerate()
+            .map(|(i, p)| PendingAction {
+                number: i + 1,
+                risk: classify(&p.command),
+                command: p.command,
+                description: p.description,
+            })
+            .collect();
+        self.age = 0;
+        self.actions.len()
+    }
+
+    /// Count a user turn, forgetting the actions once they are too old
+    pub fn advance(&mut self) {
+        self.age += 1;
+        if self.age > self.expiry_turns {
+            self.actions.clear();
+        }
+    }
+
+    /// Follow a recorded turn, e.g. when replaying a resumed session
+    pub fn observe(&mut self, turn: &Message) {
+        match turn.role {
+            MessageRole::User => self.advance(),
+            MessageRole::Assistant => {
+                self.register(&turn.content);
+            }
+            MessageRole::System | MessageRole::Tool => {}
+        }
+    }
+
+    pub fn pending(&self) -> &[PendingAction] {
+        &self.actions
+    }
+
+    /// Whether the actions were proposed since the last user turn
+    pub fn is_fresh(&self) -> bool {
+        self.age == 0 && !self.actions.is_empty()
+    }
+
+    /// Take action `number` out of the registry; the others keep their numbers
+    pub fn remove(&mut self, number: usize) -> Option<PendingAction> {
+        let position = self.actions.iter().position(|a| a.number == number)?;
+        Some(self.actions.remove(position))
+    }
+
+    /// One line per pending action
+    pub fn render(&self) -> String {
+        self.actions
+            .iter()
+            .map(|action| match &action.description {
+                Some(description) => format!("  {} — {}", action.describe(), description),
+                None => format!("  {}", action.describe()),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// The pending actions a reply asks for
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct Resolution {
+    /// Action numbers to run, in order
+    #[serde(default)]
+    pub run: Vec<usize>,
+    /// Action numbers to drop without running
+    #[serde(default)]
+    pub skip: Vec<usize>,
+}
+
+impl Resolution {
+    fn is_empty(&self) -> bool {
+        self.run.is_empty() && self.skip.is_empty()
+    }
+}
+
+/// What the rules made of a reply
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Match {
+    Resolved(Resolution),
+    /// It reads like a reference, but not one the rules can place
+    Unclear,
+    /// Ordinary conversation
+    NotAReference,
+}
+
+const RUN_WORDS: &[&str] = &[
+    "run", "do", "execute", "exec", "apply", "proceed", "go", "try",
+];
+const ACK_WORDS: &[&str] = &[
+    "yes", "y", "yeah", "yep", "ok", "okay", "sure", "please", "ahead", "confirm", "fine",
+];
+const SKIP_WORDS: &[&str] = &["skip", "except", "without", "not", "dont", "ignore"];
+const ALL_WORDS: &[&str] = &[
+    "all",
+    "both",
+    "everything",
+    "them",
+    "rest",
+    "others",
+    "remaining",
+    "each",
+    "every",
+];
+const DECLINES: &[&str] = &[
+    "no",
+    "nope",
+    "cancel",
+    "never mind",
+    "nevermind",
+    "no thanks",
+    "dont",
+];
+const QUESTION_WORDS: &[&str] = &[
+    "how", "what", "why", "where", "when", "who", "which", "is", "are", "does", "did",
+];
+const FILLER: &[&str] = &[
+    "the", "a", "one", "ones", "it", "that", "this", "those", "these", "command", "commands",
+    "step", "steps", "number", "and", "then", "also", "too", "just", "only", "now", "of", "for",
+    "me", "but", "as", "well", "you", "can", "lets", "us", "i", "want", "to", "would", "like",
+    "on", "other", "thanks",
+];
+const ORDINALS: &[&str] = &[
+    "first", "second", "third", "fourth", "fifth", "sixth", "seventh", "eighth", "ninth", "tenth",
+];
+
+/// References within one clause of a reply
+#[derive(Debug, Default)]
+struct Refs {
+    numbers: Vec<usize>,
+    all: bool,
+    verb: bool,
+    ack: bool,
+    unknown: bool,
+    /// A number or risk that names no pending action
+    invalid: bool,
+}
+
+impl Refs {
+    fn read(words: &[&str], actions: &[PendingAction]) -> Self {
+        let mut refs = Refs::default();
+        for word in words {
+            let word = word.trim_start_matches('#');
+            let number = word
+                .trim_end_matches(|c: char| c.is_ascii_alphabetic())
+                .parse::<usize>()
+                .ok()
+                .or_else(|| ORDINALS.iter().position(|o| *o == word).map(|i| i + 1))
+                .or_else(|| (word == "last").then_some(actions.len()));
+            let risk = match word {
+                "dangerous" | "risky" | "destructive" | "unsafe" => Some(Risk::Dangerous),
+                "safe" | "harmless" | "readonly" => Some(Risk::ReadOnly),
+                _ => None,
+            };
+
+            if let Some(number) = number {
+                if actions.iter().any(|a| a.number == number) {
+                    refs.numbers.push(number);
+                } else {
+                    refs.invalid = true;
+                }
+            } else if let Some(risk) = risk {
+                let matching: Vec<usize> = actions
+                    .iter()
+                    .filter(|a| a.risk == risk)
+                    .map(|a| a.number)
+                    .collect();
+                refs.invalid |= matching.is_empty();
+                refs.numbers.extend(matching);
+            } else if ALL_WORDS.contains(&word) {
+                refs.all = true;
+            } else if RUN_WORDS.contains(&word) {
+                refs.verb = true;
+            } else if ACK_WORDS.contains(&word) {
+                refs.ack = true;
+            } else if !FILLER.contains(&word) {
+                refs.unknown = true;
+            }
+        }
+        refs
+    }
+
+    fn signals(&self) -> bool {
+        !self.numbers.is_empty() || self.all || self.verb || self.ack || self.invalid
+    }
+}
+
+/// Place a short reply against the pending `actions` by rule
+pub fn match_reference(input: &str, actions: &[PendingAction]) -> Match {
+    if actions.is_empty() {
+        return Match::NotAReference;
+    }
+    let normalized: String = input
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || c.is_whitespace() || *c == '#' || *c == '-')
+        .collect();
+    let normalized = normalized.replace("read-only", "readonly");
+    let words: Vec<&str> = normalized.split_whitespace().collect();
+    let Some(first) = words.first() else {
+        return Match::NotAReference;
+    };
+    if words.len() > MAX_REFERENCE_WORDS || QUESTION_WORDS.contains(first) {
+        return Match::NotAReference;
+    }
+    let all: Vec<usize> = actions.iter().map(|a| a.number).collect();
+    if DECLINES.contains(&words.join(" ").as_str()) {
+        return Match::Resolved(Resolution {
+            run: Vec::new(),
+            skip: all,
+        });
+    }
+
+    let split = words.iter().position(|w| SKIP_WORDS.contains(w));
+    let (run_words, skip_words) = match split {
+        Some(at) => (&words[..at], Some(&words[at + 1..])),
+        None => (&words[..], None),
+    };
+    let run_refs = Refs::read(run_words, actions);
+    let skip_refs = skip_words.map(|words| Refs::read(words, actions));
+
+    let signalled = run_refs.signals() || skip_refs.is_some();
+    if !signalled {
+        return Match::NotAReference;
+    }
+    let unknown = run_refs.unknown || skip_refs.as_ref().is_some_and(|r| r.unknown);
+    let invalid = run_refs.invalid || skip_refs.as_ref().is_some_and(|r| r.invalid);
+    if unknown || invalid {
+        return Match::Unclear;
+    }
+
+    let skip = match &skip_refs {
+        None => Vec::new(),
+        Some(refs) if refs.all => all.clone(),
+        Some(refs) if !refs.numbers.is_empty() => refs.numbers.clone(),
+        // "skip it" only names something when there is one thing to name
+        Some(_) if all.len() == 1 => all.clone(),
+        Some(_) => return Match::Unclear,
+    };
+    let run: Vec<usize> = if !run_refs.numbers.is_empty() {
+        run_refs.numbers.clone()
+    } else if run_refs.all || (run_refs.verb && !skip.is_empty()) {
+        all.clone()
+    } else if (run_refs.verb || run_refs.ack) && skip_refs.is_none() {
+        if all.len() != 1 {
+            return Match::Unclear;
+        }
+        all.clone()
+    } else {
+        Vec::new()
+    };
+
+    let mut resolution = Resolution { run, skip };
+    resolution.run.retain(|n| !resolution.skip.contains(n));
+    resolution.run.dedup();
+    if resolution.is_empty() {
+        return Match::Unclear;
+    }
+    Match::Resolved(resolution)
+}
+
+/// Asks a model which pending actions a reply means
+#[async_trait]
+pub trait Disambiguator: Send + Sync {
+    async fn disambiguate(&self, prompt: &str) -> Result<String>;
+}
+
+#[async_trait]
+impl Disambiguator for LLMRouter {
+    async fn disambiguate(&self, prompt: &str) -> Result<String> {
+        self.generate_with_intent(prompt, Intent::System).await
+    }
+}
+
+fn disambiguation_prompt(input: &str, actions: &[PendingAction]) -> String {
+    let listed: Vec<String> = actions
+        .iter()
+        .map(|a| match &a.description {
+            Some(description) => format!("{} — {}", a.describe(), description),
+            None => a.describe(),
+        })
+        .collect();
+    format!(
+        "Jarvis proposed these commands in a chat:\n{}\n\n\
+         The user replied: \"{}\"\n\n\
+         Which commands does the reply ask to run, and which to skip? Answer with only a JSON \
+         object such as {{\"run\": [2], \"skip\": [3]}} using the numbers above. If the reply \
+         is not about these commands, answer {{\"run\": [], \"skip\": []}}.",
+        listed.join("\n"),
+        input
+    )
+}
+
+/// The model's answer as a resolution of known numbers; `None` when it
+/// names none of them
+fn parse_disambiguation(answer: &str, actions: &[PendingAction]) -> Option<Resolution> {
+    let object = JSON_OBJECT.find(answer)?;
+    let mut resolution: Resolution = serde_json::from_str(object.as_str()).ok()?;
+    let known = |n: &usize| actions.iter().any(|a| a.number == *n);
+    resolution.run.retain(known);
+    resolution.skip.retain(known);
+    resolution.run.retain(|n| !resolution.skip.contains(n));
+    (!resolution.is_empty()).then_some(resolution)
+}
+
+/// The pending actions `input` refers to, asking `disambiguator` when the
+/// rules can't tell; `None` when the reply is ordinary conversation
+pub async fn resolve(
+    input: &str,
+    actions: &[PendingAction],
+    disambiguator: &dyn Disambiguator,
+) -> Result<Option<Resolution>> {
+    match match_reference(input, actions) {
+        Match::Resolved(resolution) => Ok(Some(resolution)),
+        Match::NotAReference => Ok(None),
+        Match::Unclear => {
+            let answer = disambiguator
+                .disambiguate(&disambiguation_prompt(input, actions))
+                .await?;
+            Ok(parse_disambiguation(&answer, actions))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    const REPLY: &str = "nginx is failing its config test. Check it first:\n\n\
+```bash\nsystemctl status nginx\n```\n\nThen restart it:\n\n\
+```bash\nsudo systemctl restart nginx\n```\n\n\
+If the cache is corrupt, clear it:\n\n```sh\nsudo rm -rf /var/cache/nginx/*\n```\n";
+
+    fn registry() -> ActionRegistry {
+        let mut registry = ActionRegistry::default();
+        assert_eq!(registry.register(REPLY), 3);
+        registry
+    }
+
+    fn resolved(run: &[usize], skip: &[usize]) -> Match {
+        Match::Resolved(Resolution {
+            run: run.to_vec(),
+            skip: skip.to_vec(),
+        })
+    }
+
+    /// Answers with a fixed string and remembers what it was asked
+    struct ScriptedDisambiguator {
+        answer: String,
+        prompts: Mutex<Vec<String>>,
+    }
+
+    impl ScriptedDisambiguator {
+        fn new(answer: &str) -> Self {
+            Self {
+                answer: answer.to_string(),
+                prompts: Mutex::new(Vec::new()),
+            }
+        }
+
+        fn calls(&self) -> usize {
+            self.prompts.lock().unwrap().len()
+        }
+    }
+
+    #[async_trait]
+    impl Disambiguator for ScriptedDisambiguator {
+        async fn disambiguate(&self, prompt: &str) -> Result<String> {
+            self.prompts.lock().unwrap().push(prompt.to_string());
+            Ok(self.answer.clone())
+        }
+    }
+
+    #[test]
+    fn test_extract_fenced_blocks_and_numbered_plans() {
+        let actions = registry();
+        let pending = actions.pending();
+        assert_eq!(pending[0].command, "systemctl status nginx");
+        assert_eq!(
+            pending[0].description.as_deref(),
+            Some("nginx is failing its config test. Check it first")
+        );
+        assert_eq!(pending[1].risk, Risk::Changes);
+        assert_eq!(pending[2].risk, Risk::Dangerous);
+
+        let plan = "Here's the plan:\n\
+1. Refresh the mirrors: `sudo reflector --latest 10 --save /etc/pacman.d/mirrorlist`\n\
+2. Set `ParallelDownloads = 5` in pacman.conf\n\
+3. Upgrade with `sudo pacman -Syu`\n\n\
+```console\n$ journalctl -b -p err \\\n    --no-pager\n-- No entries --\n```\n\
+```toml\n[options]\n```\n";
+        let proposed = extract(plan);
+        let commands: Vec<&str> = proposed.iter().map(|p| p.command.as_str()).collect();
+        assert_eq!(
+            commands,
+            [
+                "sudo reflector --latest 10 --save /etc/pacman.d/mirrorlist",
+                "sudo pacman -Syu",
+                "journalctl -b -p err --no-pager",
+            ]
+        );
+        assert_eq!(
+            proposed[0].description.as_deref(),
+            Some("Refresh the mirrors")
+        );
+        assert_eq!(proposed[1].description.as_deref(), Some("Upgrade with"));
+    }
+
+    #[test]
+    fn test_classify_risk() {
+        assert_eq!(classify("journalctl -u nginx | tail -n 20"), Risk::ReadOnly);
+        assert_eq!(classify("sudo systemctl restart nginx"), Risk::Changes);
+        assert_eq!(
+            classify("echo 'vm.swappiness=10' > /etc/sysctl.d/99.conf"),
+            Risk::Changes
+        );
+        assert_eq!(classify("ls /tmp 2>/dev/null"), Risk::ReadOnly);
+        assert_eq!(classify("sudo pacman -Rns foo"), Risk::Dangerous);
+        assert_eq!(
+            classify("curl -fsSL https://example.com/install.sh | sh"),
+            Risk::Dangerous
+        );
+        assert_eq!(classify("sudo dd if=disk.img of=/dev/sdb"), Risk::Dangerous);
+        assert_eq!(classify("sudo systemctl reboot"), Risk::Dangerous);
+    }
+
+    #[test]
+    fn test_match_references() {
+        let actions = registry();
+        let pending = actions.pending();
+
+        assert_eq!(match_reference("run 2", pending), resolved(&[2], &[]));
+        assert_eq!(
+            match_reference("ok do the first one", pending),
+            resolved(&[1], &[])
+        );
+        assert_eq!(
+            match_reference("run the second one please", pending),
+            resolved(&[2], &[])
+        );
+        assert_eq!(
+            match_reference("do #1 and 3", pending),
+            resolved(&[1, 3], &[])
+        );
+        assert_eq!(
+            match_reference("the last one", pending),
+            resolved(&[3], &[])
+        );
+        assert_eq!(
+            match_reference("skip the dangerous one", pending),
+            resolved(&[], &[3])
+        );
+        assert_eq!(
+            match_reference("run them all except the dangerous one", pending),
+            resolved(&[1, 2], &[3])
+        );
+        assert_eq!(
+            match_reference("just the read-only one", pending),
+            resolved(&[1], &[])
+        );
+        assert_eq!(
+            match_reference("never mind", pending),
+            resolved(&[], &[1, 2, 3])
+        );
+
+        // "do it" with three candidates needs the model; so does a reference by content
+        assert_eq!(match_reference("do it", pending), Match::Unclear);
+        assert_eq!(
+            match_reference("run the nginx restart", pending),
+            Match::Unclear
+        );
+        assert_eq!(match_reference("run 7", pending), Match::Unclear);
+
+        assert_eq!(match_reference("thanks", pending), Match::NotAReference);
+        assert_eq!(
+            match_reference("what does the second one do?", pending),
+            Match::NotAReference
+        );
+        assert_eq!(
+            match_reference(
+                "can you explain why nginx keeps failing after every single upgrade",
+                pending
+            ),
+            Match::NotAReference
+        );
+
+        let mut single = ActionRegistry::default();
+        single.register("```bash\nsudo systemctl restart nginx\n```");
+        assert_eq!(
+            match_reference("yes do it", single.pending()),
+            resolved(&[1], &[])
+        );
+        assert_eq!(
+            match_reference("skip it", single.pending()),
+            resolved(&[], &[1])
+        );
+        assert_eq!(match_reference("do it", &[]), Match::NotAReference);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_asks_the_model_only_when_unclear() {
+        let actions = registry();
+
+        let model = ScriptedDisambiguator::new("{\"run\": [9], \"skip\": []}");
+        let resolution = resolve("run 2", actions.pending(), &model).await.unwrap();
+        assert_eq!(resolution.unwrap().run, [2]);
+        assert_eq!(model.calls(), 0);
+
+        let model = ScriptedDisambiguator::new("Sure: {\"run\": [2, 9], \"skip\": [3]}");
+        let resolution = resolve("do the restart", actions.pending(), &model)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            resolution,
+            Resolution {
+                run: vec![2],
+                skip: vec![3]
+            }
+        );
+        let prompts = model.prompts.lock().unwrap();
+        assert!(prompts[0].contains("2. `sudo systemctl restart nginx` (changes the system)"));
+        assert!(prompts[0].contains("\"do the restart\""));
+        drop(prompts);
+
+        // The model deciding it isn't a reference makes it conversation
+        let model = ScriptedDisambiguator::new("{\"run\": [], \"skip\": []}");
+        assert!(
+            resolve("do it", actions.pending(), &model)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let model = ScriptedDisambiguator::new("I'm not sure what you mean");
+        assert!(
+            resolve("do it", actions.pending(), &model)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(model.calls(), 1);
+    }
+
+    #[test]
+    fn test_pending_actions_expire_and_keep_numbers() {
+        let mut actions = registry();
+        assert!(actions.is_fresh());
+
+        // A reply without commands keeps the earlier ones
+        actions.advance();
+        assert_eq!(actions.register("Done, nginx is back up."), 0);
+        assert_eq!(
+            actions.remove(2).unwrap().command,
+            "sudo systemctl restart nginx"
+        );
+        assert_eq!(actions.pending()[1].number, 3);
+
+        actions.advance();
+        actions.advance();
+        assert_eq!(actions.pending().len(), 2);
+        actions.advance();
+        assert!(actions.pending().is_empty());
+    }
+
+    #[test]
+    fn test_argv_and_report() {
+        let actions = registry();
+        let pending = actions.pending();
+        assert_eq!(
+            pending[1].argv().unwrap(),
+            ["sudo", "systemctl", "restart", "nginx"]
+        );
+        assert!(pending[2].argv().is_none());
+
+        let output = crate::exec::fake_output(3, "", "nginx.service: inactive\n");
+        assert_eq!(
+            pending[0].report(&output),
+            "Ran `systemctl status nginx`: exit 3\nnginx.service: inactive"
+        );
+        let output = crate::exec::fake_output(0, "", "");
+        assert_eq!(
+            pending[1].report(&output),
+            "Ran `sudo systemctl restart nginx`: exit 0, no output"
+        );
+    }
+}
@@ -12,18 +12,727 @@ pub struct Config {
     pub agents: AgentConfig,
     pub database_path: String,
     pub plugin_paths: Vec<String>,
+    /// Observe and advise but never change the system; see [`crate::read_only`]
+    #[serde(default)]
+    pub read_only: bool,
     // MCP server configuration
     #[serde(default)]
     pub mcp: McpConfig,
+    // Hosts reachable with `--host`
+    #[serde(default)]
+    pub remote: RemoteConfig,
+    #[serde(default)]
+    pub notifications: NotificationConfig,
+    #[serde(default)]
+    pub reports: ReportConfig,
+    #[serde(default)]
+    pub docker_maintenance: DockerMaintenanceConfig,
+    #[serde(default)]
+    pub btrfs_maintenance: BtrfsMaintenanceConfig,
+    /// Container image vulnerability scans run by jarvisd
+    #[serde(default)]
+    pub trivy: crate::trivy::TrivyConfig,
+    #[serde(default)]
+    pub trace: TraceConfig,
+    /// Paths Jarvis may read on a user's behalf
+    #[serde(default)]
+    pub files: crate::file_access::FileAccessConfig,
+    #[serde(default)]
+    pub nvim: NvimConfig,
+    #[serde(default)]
+    pub api: ApiConfig,
+    /// Event bus shared with jarvis-nv
+    #[serde(default)]
+    pub bus: crate::bus::BusConfig,
+    /// Per-host summaries prepended to explain, diagnose, and fix prompts
+    #[serde(default)]
+    pub host_profile: crate::host_profile::HostProfileConfig,
+    #[serde(default)]
+    pub nlp: NlpConfig,
+    /// Temperatures, fans, package power, and batteries read by jarvisd
+    #[serde(default)]
+    pub sensors: crate::sensors::SensorsConfig,
+    /// Site-specific checks jarvisd runs on each health check
+    #[serde(default)]
+    pub health_checks: Vec<crate::health_checks::HealthCheckConfig>,
+    /// How jarvisd groups events into incidents
+    #[serde(default)]
+    pub incidents: crate::incidents::IncidentsConfig,
+}
+
+/// Periodic system reports (`jarvis report generate`, scheduled by jarvisd)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportConfig {
+    /// Five-field cron expression, e.g. "0 8 * * 1" for Monday mornings; unset disables
+    pub schedule: Option<String>,
+    /// Window covered by scheduled reports
+    #[serde(default = "default_report_since")]
+    pub since: String,
+    #[serde(default = "default_report_dir")]
+    pub output_dir: String,
+    /// Also write an HTML copy next to the markdown
+    #[serde(default)]
+    pub html: bool,
+    pub html_template: Option<String>,
+    /// Ask the LLM for an executive summary
+    #[serde(default = "default_true")]
+    pub llm_summary: bool,
+    /// Send a `report_ready` notification when a scheduled report is written
+    #[serde(default = "default_true")]
+    pub notify: bool,
+}
+
+fn default_report_since() -> String {
+    "7d".to_string()
+}
+
+fn default_report_dir() -> String {
+    "~/.local/share/jarvis/reports".to_string()
+}
+
+impl Default for ReportConfig {
+    fn default() -> Self {
+        Self {
+            schedule: None,
+            since: default_report_since(),
+            output_dir: default_report_dir(),
+            html: false,
+            html_template: None,
+            llm_summary: true,
+            notify: true,
+        }
+    }
+}
+
+/// Docker housekeeping run by jarvisd
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DockerMaintenanceConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Report what each task would do without changing anything
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Cron schedule for pruning dangling images, stopped containers, and unused networks
+    #[serde(default = "default_docker_prune_schedule")]
+    pub prune_schedule: String,
+    /// Cron schedule for comparing running containers with their registry
+    #[serde(default = "default_docker_image_check_schedule")]
+    pub image_check_schedule: String,
+    /// Pull and recreate containers labeled `jarvis.autoupdate=true` when
+    /// their image has a newer digest; otherwise updates are only reported
+    #[serde(default)]
+    pub auto_update: bool,
+    /// Restart containers that have been unhealthy for this long
+    #[serde(default = "default_unhealthy_restart_minutes")]
+    pub unhealthy_restart_minutes: u64,
+    /// Containers that are never removed, recreated, or restarted
+    #[serde(default)]
+    pub protected_containers: Vec<String>,
+}
+
+fn default_docker_prune_schedule() -> String {
+    "0 4 * * 0".to_string()
+}
+
+fn default_docker_image_check_schedule() -> String {
+    "0 6 * * *".to_string()
+}
+
+fn default_unhealthy_restart_minutes() -> u64 {
+    10
+}
+
+impl Default for DockerMaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dry_run: false,
+            prune_schedule: default_docker_prune_schedule(),
+            image_check_schedule: default_docker_image_check_schedule(),
+            auto_update: false,
+            unhealthy_restart_minutes: default_unhealthy_restart_minutes(),
+            protected_containers: Vec::new(),
+        }
+    }
+}
+
+/// Btrfs scrubs and balances run by jarvisd
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BtrfsMaintenanceConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Scrub each filesystem when its last scrub is older than this
+    #[serde(default = "default_scrub_interval_days")]
+    pub scrub_interval_days: u32,
+    /// Local hour the maintenance window opens; a window may wrap past midnight
+    #[serde(default = "default_btrfs_window_start")]
+    pub window_start_hour: u8,
+    /// Local hour the maintenance window closes
+    #[serde(default = "default_btrfs_window_end")]
+    pub window_end_hour: u8,
+    /// Days maintenance may start on, e.g. `["Saturday", "Sunday"]`; empty allows every day
+    #[serde(default)]
+    pub allowed_days: Vec<String>,
+    /// Don't start while the 1-minute load average per CPU is above this
+    #[serde(default = "default_btrfs_max_load")]
+    pub max_load_per_cpu: f64,
+    /// Balance only when unallocated space is below this share of the device
+    #[serde(default = "default_balance_min_unallocated")]
+    pub balance_min_unallocated_percent: f64,
+    /// ...and data or metadata chunks are on average less full than this;
+    /// also the usage filter passed to `btrfs balance`
+    #[serde(default = "default_balance_chunk_usage")]
+    pub balance_chunk_usage_percent: u8,
+    /// How often to poll a running scrub or balance for progress
+    #[serde(default = "default_btrfs_poll_secs")]
+    pub poll_interval_secs: u64,
+}
+
+fn default_scrub_interval_days() -> u32 {
+    30
+}
+
+fn default_btrfs_window_start() -> u8 {
+    2
+}
+
+fn default_btrfs_window_end() -> u8 {
+    6
+}
+
+fn default_btrfs_max_load() -> f64 {
+    0.7
+}
+
+fn default_balance_min_unallocated() -> f64 {
+    10.0
+}
+
+fn default_balance_chunk_usage() -> u8 {
+    75
+}
+
+fn default_btrfs_poll_secs() -> u64 {
+    30
+}
+
+impl Default for BtrfsMaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            scrub_interval_days: default_scrub_interval_days(),
+            window_start_hour: default_btrfs_window_start(),
+            window_end_hour: default_btrfs_window_end(),
+            allowed_days: Vec::new(),
+            max_load_per_cpu: default_btrfs_max_load(),
+            balance_min_unallocated_percent: default_balance_min_unallocated(),
+            balance_chunk_usage_percent: default_balance_chunk_usage(),
+            poll_interval_secs: default_btrfs_poll_secs(),
+        }
+    }
+}
+
+/// Context attached to requests from the Neovim plugin
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NvimConfig {
+    /// Read the project root, Cargo.toml, and sibling modules; off sends
+    /// only what is in the editor
+    #[serde(default = "default_true")]
+    pub project_context: bool,
+    /// Lines above and below the cursor or selection
+    #[serde(default = "default_nvim_context_lines")]
+    pub context_lines: usize,
+    /// Tokens the assembled context may use
+    #[serde(default = "default_nvim_context_tokens")]
+    pub context_token_budget: usize,
+    /// Explain the symbol under the cursor on hover when the line has no
+    /// diagnostics; off by default since every such hover asks the model
+    #[serde(default)]
+    pub hover_symbols: bool,
+    /// Longest AI explanation shown in a hover, in characters
+    #[serde(default = "default_nvim_hover_chars")]
+    pub hover_max_chars: usize,
+}
+
+fn default_nvim_context_lines() -> usize {
+    40
+}
+
+fn default_nvim_context_tokens() -> usize {
+    1500
+}
+
+fn default_nvim_hover_chars() -> usize {
+    600
+}
+
+impl Default for NvimConfig {
+    fn default() -> Self {
+        Self {
+            project_context: true,
+            context_lines: default_nvim_context_lines(),
+            context_token_budget: default_nvim_context_tokens(),
+            hover_symbols: false,
+            hover_max_chars: default_nvim_hover_chars(),
+        }
+    }
+}
+
+/// HTTP API served by jarvisd (`/health`, `/status`, `/operations`, `/metrics`,
+/// `/findings.sarif`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_api_bind")]
+    pub bind: String,
+    /// Required as `Authorization: Bearer <token>` when set
+    pub token: Option<String>,
+    /// How long an `Idempotency-Key` of `POST /operations` is remembered
+    #[serde(default = "default_idempotency_window")]
+    pub idempotency_window_secs: u64,
+}
+
+fn default_api_bind() -> String {
+    "127.0.0.1:7333".to_string()
+}
+
+fn default_idempotency_window() -> u64 {
+    crate::idempotency::DEFAULT_WINDOW.as_secs()
+}
+
+impl ApiConfig {
+    pub fn idempotency_window(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.idempotency_window_secs)
+    }
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind: default_api_bind(),
+            token: None,
+            idempotency_window_secs: default_idempotency_window(),
+        }
+    }
+}
+
+/// Request traces kept for `jarvis trace list/show`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraceConfig {
+    /// Keep traces of requests that took at least this long
+    #[serde(default = "default_trace_threshold_ms")]
+    pub persist_threshold_ms: u64,
+    /// Oldest traces are dropped beyond this many
+    #[serde(default = "default_trace_max_stored")]
+    pub max_stored: usize,
+}
+
+fn default_trace_threshold_ms() -> u64 {
+    5000
+}
+
+fn default_trace_max_stored() -> usize {
+    100
+}
+
+impl Default for TraceConfig {
+    fn default() -> Self {
+        Self {
+            persist_threshold_ms: default_trace_threshold_ms(),
+            max_stored: default_trace_max_stored(),
+        }
+    }
+}
+
+/// Routing of natural language queries the built-in rules don't recognise
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NlpConfig {
+    /// Match queries against example utterances by embedding before asking the LLM
+    #[serde(default = "default_true")]
+    pub embedding_routing: bool,
+    /// Minimum cosine similarity to the nearest example to act on it
+    #[serde(default = "default_similarity_threshold")]
+    pub similarity_threshold: f32,
+    /// Extra examples, and where `jarvis nlp correct` records corrections;
+    /// defaults to ~/.config/jarvis/nlp_examples.toml
+    pub examples_file: Option<PathBuf>,
+}
+
+fn default_similarity_threshold() -> f32 {
+    0.8
+}
+
+impl NlpConfig {
+    pub fn examples_path(&self) -> Option<PathBuf> {
+        self.examples_file
+            .clone()
+            .or_else(|| dirs::config_dir().map(|dir| dir.join("jarvis").join("nlp_examples.toml")))
+    }
+}
+
+impl Default for NlpConfig {
+    fn default() -> Self {
+        Self {
+            embedding_routing: true,
+            similarity_threshold: default_similarity_threshold(),
+            examples_file: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Pending notifications before new ones are dropped
+    #[serde(default = "default_notify_queue")]
+    pub queue_size: usize,
+    #[serde(default)]
+    pub desktop: DesktopNotifyConfig,
+    pub ntfy: Option<NtfyConfig>,
+    pub gotify: Option<GotifyConfig>,
+    pub webhook: Option<WebhookNotifyConfig>,
+    /// Minimum severity per event type, e.g. `maintenance_finished = "warning"`
+    #[serde(default)]
+    pub events: std::collections::HashMap<crate::notify::NotifyEvent, crate::notify::NotifySeverity>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DesktopNotifyConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default = "default_min_severity")]
+    pub min_severity: crate::notify::NotifySeverity,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NtfyConfig {
+    /// Server base URL, e.g. "https://ntfy.sh"
+    pub url: String,
+    pub topic: String,
+    pub token: Option<String>,
+    #[serde(default = "default_min_severity")]
+    pub min_severity: crate::notify::NotifySeverity,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GotifyConfig {
+    pub url: String,
+    /// Application token
+    pub token: String,
+    #[serde(default = "default_min_severity")]
+    pub min_severity: crate::notify::NotifySeverity,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookNotifyConfig {
+    pub url: String,
+    #[serde(default = "default_min_severity")]
+    pub min_severity: crate::notify::NotifySeverity,
+}
+
+fn default_notify_queue() -> usize {
+    64
+}
+
+fn default_min_severity() -> crate::notify::NotifySeverity {
+    crate::notify::NotifySeverity::Info
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            queue_size: default_notify_queue(),
+            desktop: DesktopNotifyConfig::default(),
+            ntfy: None,
+            gotify: None,
+            webhook: None,
+            events: std::collections::HashMap::new(),
+        }
+    }
+}
+
+impl Default for DesktopNotifyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_severity: default_min_severity(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteConfig {
+    /// Allow fixes and other state-changing operations on remote hosts
+    #[serde(default)]
+    pub remote_mutations_allowed: bool,
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout_secs: u64,
+    /// Named hosts, selected with `--host <name>` and probed by `jarvis fleet status`
+    #[serde(default)]
+    pub hosts: std::collections::HashMap<String, RemoteHostConfig>,
+    /// Per-host budget for `jarvis fleet status`
+    #[serde(default = "default_fleet_timeout")]
+    pub fleet_timeout_secs: u64,
+    /// Commands `jarvis fleet status` runs at once across all hosts
+    #[serde(default = "default_fleet_concurrency")]
+    pub fleet_concurrency: usize,
+    #[serde(default)]
+    pub fleet_thresholds: crate::fleet::FleetThresholds,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteHostConfig {
+    pub host: String,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    pub identity_file: Option<String>,
+    /// Extra `-o` options, e.g. "StrictHostKeyChecking=accept-new"
+    #[serde(default)]
+    pub ssh_options: Vec<String>,
+    /// Non-interactive privilege escalation on this host
+    #[serde(default)]
+    pub escalation: crate::remote::Escalation,
+    /// For Wake-on-LAN, e.g. "aa:bb:cc:dd:ee:ff"
+    pub mac: Option<String>,
+    /// Broadcast address for the magic packet; defaults to 255.255.255.255
+    pub wol_broadcast: Option<String>,
+}
+
+fn default_connect_timeout() -> u64 {
+    10
+}
+
+fn default_fleet_timeout() -> u64 {
+    20
+}
+
+fn default_fleet_concurrency() -> usize {
+    8
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        Self {
+            remote_mutations_allowed: false,
+            connect_timeout_secs: default_connect_timeout(),
+            hosts: std::collections::HashMap::new(),
+            fleet_timeout_secs: default_fleet_timeout(),
+            fleet_concurrency: default_fleet_concurrency(),
+            fleet_thresholds: crate::fleet::FleetThresholds::default(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct McpConfig {
     pub enabled: bool,
-    pub transport: String, // "stdio", "ws", "http"
+    pub transport: String, // "stdio", "ws", "http", "tcp"
     pub address: Option<String>,
+    /// Token network clients must present (`auth_token` in `initialize`
+    /// over TCP); defaults to `[api] token`
+    pub token: Option<String>,
+    /// TLS for the TCP transport
+    #[serde(default)]
+    pub tls: crate::tls::TlsConfig,
+    /// Close a network connection after this long without a message
+    #[serde(default = "default_mcp_idle_timeout")]
+    pub idle_timeout_secs: u64,
+    /// Network clients served at once; more are turned away
+    #[serde(default = "default_mcp_max_connections")]
+    pub max_connections: usize,
     #[serde(default)]
     pub tools: ToolsConfig,
+    #[serde(default)]
+    pub rate_limits: RateLimitConfig,
+    #[serde(default = "default_true")]
+    pub audit_enabled: bool,
+    /// User-defined tools loaded from TOML files
+    #[serde(default)]
+    pub plugins: PluginsConfig,
+    /// Reuse of LLM analyses in docker `diagnose` and `health`
+    #[serde(default)]
+    pub diagnostics_cache: DiagnosticsCacheConfig,
+    /// How long the `idempotency_key` of a package tool call is remembered
+    #[serde(default = "default_idempotency_window")]
+    pub idempotency_window_secs: u64,
+    /// How long a tool call may run before it is abandoned
+    #[serde(default)]
+    pub timeouts: ToolTimeoutConfig,
+}
+
+fn default_mcp_idle_timeout() -> u64 {
+    900
+}
+
+fn default_mcp_max_connections() -> usize {
+    16
+}
+
+/// How long docker diagnostics reuse an LLM analysis
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticsCacheConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Reuse an analysis for this long while the facts are unchanged
+    #[serde(default = "default_diagnostics_ttl")]
+    pub ttl_secs: u64,
+    /// Analyze one container (or the overview) at most this often, even
+    /// when its facts change
+    #[serde(default = "default_diagnostics_min_interval")]
+    pub min_interval_secs: u64,
+}
+
+fn default_diagnostics_ttl() -> u64 {
+    300
+}
+
+fn default_diagnostics_min_interval() -> u64 {
+    30
+}
+
+impl DiagnosticsCacheConfig {
+    pub fn ttl(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.ttl_secs)
+    }
+
+    pub fn min_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.min_interval_secs)
+    }
+}
+
+impl Default for DiagnosticsCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ttl_secs: default_diagnostics_ttl(),
+            min_interval_secs: default_diagnostics_min_interval(),
+        }
+    }
+}
+
+/// Declarative tool plugins, one `*.toml` file per tool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginsConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default = "default_plugin_dir")]
+    pub dir: String,
+    /// Programs plugin commands may run; anything else fails validation
+    #[serde(default)]
+    pub allowed_commands: Vec<String>,
+    #[serde(default = "default_plugin_timeout")]
+    pub default_timeout_secs: u64,
+    #[serde(default = "default_plugin_output")]
+    pub max_output_bytes: usize,
+}
+
+fn default_plugin_dir() -> String {
+    "~/.config/jarvis/tools".to_string()
+}
+
+fn default_plugin_timeout() -> u64 {
+    30
+}
+
+fn default_plugin_output() -> usize {
+    64 * 1024
+}
+
+impl Default for PluginsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dir: default_plugin_dir(),
+            allowed_commands: Vec::new(),
+            default_timeout_secs: default_plugin_timeout(),
+            max_output_bytes: default_plugin_output(),
+        }
+    }
+}
+
+/// Token bucket settings for MCP tool invocations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Applied to any tool/action without a more specific rule
+    pub default: RateLimitRule,
+    /// Applied to state-changing actions (install, remove, stop, ...)
+    pub destructive: RateLimitRule,
+    pub destructive_actions: Vec<String>,
+    /// Overrides keyed by "tool" or "tool:action", e.g. "jarvis_docker:restart"
+    #[serde(default)]
+    pub per_tool: std::collections::HashMap<String, RateLimitRule>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RateLimitRule {
+    /// Maximum number of calls that can be made back-to-back
+    pub burst: u32,
+    /// Sustained refill rate
+    pub per_minute: u32,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            default: RateLimitRule {
+                burst: 30,
+                per_minute: 60,
+            },
+            destructive: RateLimitRule {
+                burst: 2,
+                per_minute: 4,
+            },
+            destructive_actions: vec![
+                "install".to_string(),
+                "remove".to_string(),
+                "update".to_string(),
+                "stop".to_string(),
+                "restart".to_string(),
+                "vm-stop".to_string(),
+                "poweroff".to_string(),
+                "reboot".to_string(),
+                "suspend".to_string(),
+            ],
+            per_tool: std::collections::HashMap::new(),
+        }
+    }
+}
+
+/// Per-call deadlines for MCP tools. A call that runs past its deadline is
+/// dropped, which kills the subprocesses it spawned.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolTimeoutConfig {
+    /// Applied to any tool/action without an override
+    #[serde(default = "default_tool_timeout")]
+    pub default_secs: u64,
+    /// Overrides keyed by "tool" or "tool:action", e.g. "jarvis_docker:diagnose"
+    #[serde(default)]
+    pub per_tool: std::collections::HashMap<String, u64>,
+}
+
+fn default_tool_timeout() -> u64 {
+    120
+}
+
+impl Default for ToolTimeoutConfig {
+    fn default() -> Self {
+        // Package transactions keep the subprocess runner's own limit
+        let transaction = crate::exec::DEFAULT_TIMEOUT.as_secs();
+        Self {
+            default_secs: default_tool_timeout(),
+            per_tool: ["install", "remove", "update"]
+                .iter()
+                .map(|action| (format!("jarvis_package_manager:{}", action), transaction))
+                .collect(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -50,11 +759,31 @@ impl Default for McpConfig {
             enabled: false,
             transport: "ws".to_string(),
             address: Some("127.0.0.1:7332".to_string()),
+            token: None,
+            tls: crate::tls::TlsConfig::default(),
+            idle_timeout_secs: default_mcp_idle_timeout(),
+            max_connections: default_mcp_max_connections(),
             tools: ToolsConfig::default(),
+            rate_limits: RateLimitConfig::default(),
+            audit_enabled: true,
+            plugins: PluginsConfig::default(),
+            diagnostics_cache: DiagnosticsCacheConfig::default(),
+            idempotency_window_secs: default_idempotency_window(),
+            timeouts: ToolTimeoutConfig::default(),
         }
     }
 }
 
+impl McpConfig {
+    pub fn idempotency_window(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.idempotency_window_secs)
+    }
+
+    pub fn idle_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.idle_timeout_secs)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LLMConfig {
     pub primary_provider: String,
@@ -68,6 +797,54 @@ pub struct LLMConfig {
     pub omen_enabled: Option<bool>,
     pub omen_base_url: Option<String>,
     pub omen_api_key: Option<String>,
+    /// Pull `default_model` from Ollama on startup if it is not installed
+    #[serde(default)]
+    pub auto_pull_default: bool,
+    /// Model used for embeddings; falls back to `default_model`
+    #[serde(default)]
+    pub embedding_model: Option<String>,
+    #[serde(default)]
+    pub consensus: ConsensusConfig,
+    /// How long a request waits for Ollama to load a cold model
+    #[serde(default = "default_model_load_timeout")]
+    pub model_load_timeout_secs: u64,
+    /// jarvisd re-warms `default_model` this often so Ollama never unloads
+    /// it; keep it under Ollama's keep_alive (5 minutes by default)
+    #[serde(default)]
+    pub keep_warm_secs: Option<u64>,
+}
+
+fn default_model_load_timeout() -> u64 {
+    300
+}
+
+/// Settings for asking two backends the same question
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsensusConfig {
+    /// Second Ollama model to use when only one provider is configured
+    pub secondary_model: Option<String>,
+    /// Per-backend timeout so one slow provider cannot stall the request
+    pub timeout_secs: u64,
+    pub strategy: ConsensusStrategy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConsensusStrategy {
+    /// Return both labeled answers side by side
+    Both,
+    /// Merge the answers with a third prompt that flags disagreements
+    Arbitrate,
+}
+
+impl Default for ConsensusConfig {
+    fn default() -> Self {
+        Self {
+            secondary_model: None,
+            timeout_secs: 90,
+            strategy: ConsensusStrategy::Arbitrate,
+        }
+    }
 }
 
 impl LLMConfig {
@@ -100,6 +877,45 @@ pub struct SystemConfig {
     pub homelab_config: Option<String>,
     pub gpu_enabled: bool,
     pub gpu_devices: Vec<String>,
+    /// Packages that pre-flight reports flag and that are never changed without review
+    #[serde(default)]
+    pub package_holds: Vec<String>,
+    /// Refresh the pacman file database (`pacman -Fy`) before ownership
+    /// queries when it is older than this
+    #[serde(default = "default_files_db_max_age_hours")]
+    pub files_db_max_age_hours: u64,
+    /// How long one diagnose or check probe may run before it is reported as timed out
+    #[serde(default = "default_probe_timeout_secs")]
+    pub probe_timeout_secs: u64,
+    /// How long all probes of one diagnose or check may take together
+    #[serde(default = "default_probe_deadline_secs")]
+    pub probe_deadline_secs: u64,
+}
+
+fn default_files_db_max_age_hours() -> u64 {
+    crate::package_files::DEFAULT_FILES_DB_MAX_AGE.as_secs() / 3600
+}
+
+fn default_probe_timeout_secs() -> u64 {
+    10
+}
+
+fn default_probe_deadline_secs() -> u64 {
+    20
+}
+
+impl SystemConfig {
+    pub fn files_db_max_age(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.files_db_max_age_hours * 3600)
+    }
+
+    pub fn probe_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.probe_timeout_secs)
+    }
+
+    pub fn probe_deadline(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.probe_deadline_secs)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -153,6 +969,12 @@ pub struct NetworkConfig {
     pub dns_over_https: bool,
     pub dns_servers: Vec<String>,
     pub optimization_level: NetworkOptimizationLevel,
+    /// Proxies for every HTTP client; unset values come from HTTP(S)_PROXY/NO_PROXY
+    #[serde(default)]
+    pub proxy: crate::net::ProxyConfig,
+    /// Contact nothing but localhost; cached data is used where there is some
+    #[serde(default)]
+    pub offline: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -242,6 +1064,11 @@ impl Default for Config {
                 omen_enabled: Some(false),
                 omen_base_url: Some("http://localhost:8080/v1".to_string()),
                 omen_api_key: None,
+                auto_pull_default: false,
+                embedding_model: None,
+                consensus: ConsensusConfig::default(),
+                model_load_timeout_secs: default_model_load_timeout(),
+                keep_warm_secs: None,
             },
             system: SystemConfig {
                 arch_package_manager: "pacman".to_string(),
@@ -249,6 +1076,10 @@ impl Default for Config {
                 homelab_config: None,
                 gpu_enabled: false,
                 gpu_devices: vec![],
+                package_holds: vec![],
+                files_db_max_age_hours: default_files_db_max_age_hours(),
+                probe_timeout_secs: default_probe_timeout_secs(),
+                probe_deadline_secs: default_probe_deadline_secs(),
             },
             blockchain: Some(BlockchainConfig {
                 ghostchain: Some(GhostChainConfig {
@@ -281,6 +1112,8 @@ impl Default for Config {
                     "1.0.0.1".to_string(),
                 ],
                 optimization_level: NetworkOptimizationLevel::BlockchainOptimized,
+                proxy: crate::net::ProxyConfig::default(),
+                offline: false,
             },
             agents: AgentConfig {
                 transaction_monitor: TransactionMonitorConfig {
@@ -316,7 +1149,24 @@ impl Default for Config {
                 "~/.config/jarvis/plugins".to_string(),
                 "/usr/local/share/jarvis/plugins".to_string(),
             ],
+            read_only: false,
             mcp: McpConfig::default(),
+            remote: RemoteConfig::default(),
+            notifications: NotificationConfig::default(),
+            reports: ReportConfig::default(),
+            docker_maintenance: DockerMaintenanceConfig::default(),
+            btrfs_maintenance: BtrfsMaintenanceConfig::default(),
+            trivy: crate::trivy::TrivyConfig::default(),
+            trace: TraceConfig::default(),
+            files: crate::file_access::FileAccessConfig::default(),
+            nvim: NvimConfig::default(),
+            api: ApiConfig::default(),
+            bus: crate::bus::BusConfig::default(),
+            host_profile: crate::host_profile::HostProfileConfig::default(),
+            nlp: NlpConfig::default(),
+            sensors: crate::sensors::SensorsConfig::default(),
+            health_checks: Vec::new(),
+            incidents: crate::incidents::IncidentsConfig::default(),
         }
     }
 }
@@ -333,8 +1183,14 @@ impl Config {
         };
 
         if path.exists() {
-            let content = tokio::fs::read_to_string(&path).await?;
-            let config: Config = toml::from_str(&content)?;
+            let validation = crate::config_validation::validate_file(&path).await?;
+            let Some(config) = validation.config else {
+                return validation.into_result();
+            };
+            // Startup keeps going on semantic issues; `jarvis config validate` reports them in full
+            for issue in &validation.issues {
+                tracing::warn!("{}: {}", path.display(), issue);
+            }
             Ok(config)
         } else {
             // Create default config
@@ -0,0 +1,688 @@
+//! Key-by-key explanations of system config files
+//!
+//! `jarvis explain /etc/pacman.conf` parses the file instead of handing it to
+//! the LLM as prose. Every key is annotated with its value, the program's own
+//! default where the bundled table (`config_files.toml`) knows it, and
+//! whether the file deviates from that default. The LLM only contributes a
+//! one-line explanation per key, all keys in one prompt. Files whose format
+//! isn't recognized are left to the plain explanation.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::LazyLock;
+
+const BUILTIN_DEFAULTS: &str = include_str!("config_files.toml");
+
+/// Default of options that are off or empty unless a file sets them
+const UNSET: &str = "unset";
+
+/// Longest value shown in the table; JSON output has the full value
+const MAX_VALUE_WIDTH: usize = 40;
+
+static DEFAULTS: LazyLock<Vec<FileDefaults>> = LazyLock::new(|| {
+    toml::from_str::<DefaultsTable>(BUILTIN_DEFAULTS)
+        .expect("built-in config defaults are valid")
+        .file
+});
+
+#[derive(Debug, Deserialize)]
+struct DefaultsTable {
+    file: Vec<FileDefaults>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FileDefaults {
+    #[serde(default)]
+    names: Vec<String>,
+    #[serde(default)]
+    extensions: Vec<String>,
+    defaults: BTreeMap<String, String>,
+}
+
+/// Formats `jarvis explain` can take apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigFormat {
+    /// `[section]` and `key = value`, like pacman.conf
+    Ini,
+    /// systemd units and their drop-ins
+    SystemdUnit,
+    Toml,
+    Yaml,
+    /// `Keyword value` lines with `Match` blocks, like sshd_config
+    Directives,
+}
+
+const UNIT_EXTENSIONS: &[&str] = &[
+    "service",
+    "socket",
+    "timer",
+    "mount",
+    "automount",
+    "path",
+    "target",
+    "slice",
+    "swap",
+    "network",
+    "netdev",
+    "link",
+];
+
+impl ConfigFormat {
+    /// The format of the file at `path` with `contents`, if it has one of
+    /// the known ones
+    pub fn detect(path: &Path, contents: &str) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy();
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let parent = path
+            .parent()
+            .and_then(|p| p.file_name())
+            .map(|p| p.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        if matches!(name.as_ref(), "sshd_config" | "ssh_config") || parent == "sshd_config.d" {
+            return Some(Self::Directives);
+        }
+        match extension.as_str() {
+            "toml" => return Some(Self::Toml),
+            "yaml" | "yml" => return Some(Self::Yaml),
+            ext if UNIT_EXTENSIONS.contains(&ext) => return Some(Self::SystemdUnit),
+            _ => {}
+        }
+        // Drop-ins, e.g. nginx.service.d/override.conf
+        if extension == "conf"
+            && UNIT_EXTENSIONS
+                .iter()
+                .any(|u| parent.ends_with(&format!(".{}.d", u)))
+        {
+            return Some(Self::SystemdUnit);
+        }
+        if matches!(extension.as_str(), "conf" | "ini" | "cfg") && looks_like_ini(contents) {
+            return Some(Self::Ini);
+        }
+        None
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ini => "ini",
+            Self::SystemdUnit => "systemd unit",
+            Self::Toml => "toml",
+            Self::Yaml => "yaml",
+            Self::Directives => "directives",
+        }
+    }
+}
+
+/// Every meaningful line is a section header, `key = value`, or a bare flag,
+/// and at least one is `key = value` (nginx.conf and friends aren't)
+fn looks_like_ini(contents: &str) -> bool {
+    let mut assignments = 0;
+    for line in meaningful_lines(contents) {
+        if line.starts_with('[') && line.ends_with(']') {
+            continue;
+        }
+        match line.split_once('=') {
+            Some((key, _)) if is_key(key.trim()) => assignments += 1,
+            None if is_key(line) => {}
+            _ => return false,
+        }
+    }
+    assignments > 0
+}
+
+fn is_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '/'))
+}
+
+fn meaningful_lines(contents: &str) -> impl Iterator<Item = &str> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with(';'))
+}
+
+/// One setting in a config file
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConfigEntry {
+    /// `[section]`, `Match` block, or table path the setting is in
+    pub section: Option<String>,
+    pub name: String,
+    /// Empty for bare flags such as pacman's `Color`
+    pub value: String,
+}
+
+impl ConfigEntry {
+    /// `section.name`, or just the name outside any section
+    pub fn key(&self) -> String {
+        match &self.section {
+            Some(section) => format!("{}.{}", section, self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// The settings in `contents`, in file order
+pub fn parse(format: ConfigFormat, contents: &str) -> Result<Vec<ConfigEntry>> {
+    match format {
+        ConfigFormat::Ini | ConfigFormat::SystemdUnit => Ok(parse_ini(contents)),
+        ConfigFormat::Directives => Ok(parse_directives(contents)),
+        ConfigFormat::Toml => {
+            let value: toml::Value = toml::from_str(contents).context("Invalid TOML")?;
+            let value = serde_json::to_value(value)?;
+            let mut entries = Vec::new();
+            flatten(None, &value, &mut entries);
+            Ok(entries)
+        }
+        ConfigFormat::Yaml => {
+            let value: serde_yaml::Value =
+                serde_yaml::from_str(contents).context("Invalid YAML")?;
+            let value = serde_json::to_value(value)?;
+            let mut entries = Vec::new();
+            flatten(None, &value, &mut entries);
+            Ok(entries)
+        }
+    }
+}
+
+fn parse_ini(contents: &str) -> Vec<ConfigEntry> {
+    let mut entries = Vec::new();
+    let mut section = None;
+    let mut pending = String::new();
+    for line in meaningful_lines(contents) {
+        // systemd continues lines ending in a backslash
+        if let Some(start) = line.strip_suffix('\\') {
+            pending.push_str(start.trim_end());
+            pending.push(' ');
+            continue;
+        }
+        let line = if pending.is_empty() {
+            line.to_string()
+        } else {
+            std::mem::take(&mut pending) + line
+        };
+
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = Some(name.trim().to_string());
+            continue;
+        }
+        let (name, value) = match line.split_once('=') {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None => (line.as_str(), ""),
+        };
+        entries.push(ConfigEntry {
+            section: section.clone(),
+            name: name.to_string(),
+            value: value.to_string(),
+        });
+    }
+    entries
+}
+
+fn parse_directives(contents: &str) -> Vec<ConfigEntry> {
+    let mut entries = Vec::new();
+    let mut section = None;
+    for line in meaningful_lines(contents) {
+        let (name, value) = match line.split_once(|c: char| c.is_whitespace() || c == '=') {
+            Some((name, value)) => (name, value.trim().trim_start_matches('=').trim()),
+            None => (line, ""),
+        };
+        if name.eq_ignore_ascii_case("Match") {
+            section = Some(format!("Match {}", value));
+            continue;
+        }
+        entries.push(ConfigEntry {
+            section: section.clone(),
+            name: name.to_string(),
+            value: value.to_string(),
+        });
+    }
+    entries
+}
+
+/// Leaves of a parsed TOML or YAML document; lists of scalars stay one value
+fn flatten(path: Option<&str>, value: &serde_json::Value, entries: &mut Vec<ConfigEntry>) {
+    use serde_json::Value;
+
+    let join = |key: &str| match path {
+        Some(path) => format!("{}.{}", path, key),
+        None => key.to_string(),
+    };
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                flatten(Some(&join(key)), value, entries);
+            }
+        }
+        Value::Array(items) if items.iter().any(|i| i.is_object() || i.is_array()) => {
+            for (i, item) in items.iter().enumerate() {
+                flatten(
+                    Some(&format!("{}[{}]", path.unwrap_or_default(), i)),
+                    item,
+                    entries,
+                );
+            }
+        }
+        leaf => {
+            let full = path.unwrap_or_default();
+            let (section, name) = match full.rsplit_once('.') {
+                Some((section, name)) => (Some(section.to_string()), name.to_string()),
+                None => (None, full.to_string()),
+            };
+            let value = match leaf {
+                Value::String(s) => s.clone(),
+                Value::Null => String::new(),
+                other => other.to_string(),
+            };
+            entries.push(ConfigEntry {
+                section,
+                name,
+                value,
+            });
+        }
+    }
+}
+
+/// One annotated key
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyAnnotation {
+    pub key: String,
+    pub value: String,
+    /// The program's default, where the bundled table knows it
+    pub default: Option<String>,
+    /// The value differs from a known default
+    pub deviates: bool,
+    pub explanation: String,
+}
+
+/// A config file taken apart key by key
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigExplanation {
+    pub path: String,
+    pub format: ConfigFormat,
+    pub keys: Vec<KeyAnnotation>,
+}
+
+impl ConfigExplanation {
+    /// Annotations of the file at `path`, or None for an unknown format
+    pub fn from_file(path: &Path, contents: &str) -> Result<Option<Self>> {
+        let Some(format) = ConfigFormat::detect(path, contents) else {
+            return Ok(None);
+        };
+        let defaults = defaults_for(path, format);
+        let keys = parse(format, contents)?
+            .into_iter()
+            .map(|entry| {
+                let default = lookup_default(&defaults, &entry, format);
+                let deviates = default
+                    .as_deref()
+                    .is_some_and(|default| deviates(&entry.value, default));
+                KeyAnnotation {
+                    key: entry.key(),
+                    value: entry.value,
+                    default,
+                    deviates,
+                    explanation: String::new(),
+                }
+            })
+            .collect();
+        Ok(Some(Self {
+            path: path.display().to_string(),
+            format,
+            keys,
+        }))
+    }
+
+    pub fn deviations(&self) -> usize {
+        self.keys.iter().filter(|k| k.deviates).count()
+    }
+
+    /// One prompt asking for a line about each distinct key
+    pub fn explanation_prompt(&self) -> String {
+        let mut prompt = format!(
+            "For each setting of {} below, write one short line explaining what it does with this value. \
+             Answer with exactly one line per setting, formatted as `key: explanation`, and nothing else.\n\n",
+            self.path
+        );
+        let mut seen = std::collections::HashSet::new();
+        for key in &self.keys {
+            if seen.insert(key.key.as_str()) {
+                prompt.push_str(&format!("{} = {}\n", key.key, key.value));
+            }
+        }
+        prompt
+    }
+
+    /// Fill in explanations from an answer to [`Self::explanation_prompt`];
+    /// keys the answer skips keep an empty one
+    pub fn apply_explanations(&mut self, response: &str) {
+        let mut explanations = BTreeMap::new();
+        for line in response.lines() {
+            let line = line
+                .trim()
+                .trim_start_matches(['-', '*', ' '])
+                .replace('`', "");
+            if let Some((key, text)) = line.split_once(':') {
+                explanations
+                    .entry(key.trim().to_lowercase())
+                    .or_insert_with(|| text.trim().to_string());
+            }
+        }
+        for key in &mut self.keys {
+            if let Some(text) = explanations.get(&key.key.to_lowercase()) {
+                key.explanation = text.clone();
+            }
+        }
+    }
+
+    /// Aligned table; `≠` marks values that differ from the default
+    pub fn render_table(&self) -> String {
+        let rows: Vec<[String; 4]> = self
+            .keys
+            .iter()
+            .map(|k| {
+                let value = if k.value.is_empty() {
+                    "(set)".to_string()
+                } else {
+                    truncate(&k.value)
+                };
+                [
+                    k.key.clone(),
+                    value,
+                    k.default
+                        .as_deref()
+                        .map(truncate)
+                        .unwrap_or_else(|| "?".to_string()),
+                    k.explanation.clone(),
+                ]
+            })
+            .collect();
+        let headers = ["KEY", "VALUE", "DEFAULT", "EXPLANATION"];
+        let width = |column: usize| {
+            rows.iter()
+                .map(|row| row[column].chars().count())
+                .chain(std::iter::once(headers[column].len()))
+                .max()
+                .unwrap_or(0)
+        };
+        let (key_width, value_width, default_width) = (width(0), width(1), width(2));
+
+        let mut table = format!(
+            "  {:<kw$}  {:<vw$}  {:<dw$}  {}\n",
+            headers[0],
+            headers[1],
+            headers[2],
+            headers[3],
+            kw = key_width,
+            vw = value_width,
+            dw = default_width
+        );
+        for (row, key) in rows.iter().zip(&self.keys) {
+            let marker = if key.deviates { "≠" } else { " " };
+            table.push_str(
+                format!(
+                    "{} {:<kw$}  {:<vw$}  {:<dw$}  {}",
+                    marker,
+                    row[0],
+                    row[1],
+                    row[2],
+                    row[3],
+                    kw = key_width,
+                    vw = value_width,
+                    dw = default_width
+                )
+                .trim_end(),
+            );
+            table.push('\n');
+        }
+        table
+    }
+
+    /// e.g. "/etc/pacman.conf (ini, 18 keys, 6 differ from the defaults)"
+    pub fn summary(&self) -> String {
+        format!(
+            "{} ({}, {} keys, {} differ from the defaults)",
+            self.path,
+            self.format.as_str(),
+            self.keys.len(),
+            self.deviations()
+        )
+    }
+}
+
+/// Defaults of every bundled table that applies to `path`
+fn defaults_for(path: &Path, format: ConfigFormat) -> BTreeMap<String, String> {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    // A drop-in takes its unit type from its directory, nginx.service.d
+    let unit_type = match format {
+        ConfigFormat::SystemdUnit => {
+            let unit = if name.ends_with(".conf") {
+                path.parent()
+                    .and_then(|p| p.file_name())
+                    .map(|p| p.to_string_lossy().trim_end_matches(".d").to_string())
+                    .unwrap_or_default()
+            } else {
+                name.clone()
+            };
+            unit.rsplit_once('.').map(|(_, ext)| ext.to_string())
+        }
+        _ => None,
+    };
+    let sshd = format == ConfigFormat::Directives && name != "ssh_config";
+
+    let mut defaults = BTreeMap::new();
+    for table in DEFAULTS.iter() {
+        let applies = table
+            .names
+            .iter()
+            .any(|n| *n == name || (sshd && n == "sshd_config"))
+            || unit_type
+                .as_ref()
+                .is_some_and(|ext| table.extensions.contains(ext));
+        if applies {
+            defaults.extend(
+                table
+                    .defaults
+                    .iter()
+                    .map(|(k, v)| (k.to_lowercase(), v.clone())),
+            );
+        }
+    }
+    defaults
+}
+
+/// Directives are looked up by keyword alone, since `Match` blocks take the
+/// same ones; keys compare case-insensitively as sshd does
+fn lookup_default(
+    defaults: &BTreeMap<String, String>,
+    entry: &ConfigEntry,
+    format: ConfigFormat,
+) -> Option<String> {
+    let key = match format {
+        ConfigFormat::Directives => entry.name.to_lowercase(),
+        _ => entry.key().to_lowercase(),
+    };
+    defaults.get(&key).cloned()
+}
+
+fn deviates(value: &str, default: &str) -> bool {
+    if default == UNSET {
+        return true;
+    }
+    normalize(value) != normalize(default)
+}
+
+/// Compare booleans by meaning and everything else ignoring case and spacing
+fn normalize(value: &str) -> String {
+    let value = value
+        .trim()
+        .trim_matches('"')
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    match value.as_str() {
+        "yes" | "true" | "on" | "1" => "yes".to_string(),
+        "no" | "false" | "off" | "0" => "no".to_string(),
+        _ => value,
+    }
+}
+
+fn truncate(value: &str) -> String {
+    if value.chars().count() <= MAX_VALUE_WIDTH {
+        return value.to_string();
+    }
+    let cut: String = value.chars().take(MAX_VALUE_WIDTH - 1).collect();
+    format!("{}…", cut)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn explain(path: &str, contents: &str) -> ConfigExplanation {
+        ConfigExplanation::from_file(Path::new(path), contents)
+            .unwrap()
+            .unwrap()
+    }
+
+    fn key<'a>(explanation: &'a ConfigExplanation, name: &str) -> &'a KeyAnnotation {
+        explanation
+            .keys
+            .iter()
+            .find(|k| k.key == name)
+            .unwrap_or_else(|| panic!("no key {}", name))
+    }
+
+    #[test]
+    fn test_pacman_conf_deviations() {
+        let conf = explain(
+            "/etc/pacman.conf",
+            include_str!("../tests/fixtures/config_files/pacman.conf"),
+        );
+        assert_eq!(conf.format, ConfigFormat::Ini);
+
+        let parallel = key(&conf, "options.ParallelDownloads");
+        assert_eq!(parallel.value, "5");
+        assert_eq!(parallel.default.as_deref(), Some("1"));
+        assert!(parallel.deviates);
+        // Flags are off by default, so setting one deviates
+        let color = key(&conf, "options.Color");
+        assert_eq!(color.value, "");
+        assert!(color.deviates);
+        // Spelled out as the default
+        assert!(!key(&conf, "options.LogFile").deviates);
+        // Repository sections have no defaults to compare with
+        let include = key(&conf, "core.Include");
+        assert_eq!(include.default, None);
+        assert!(!include.deviates);
+        assert_eq!(conf.deviations(), 4);
+    }
+
+    #[test]
+    fn test_sshd_config_deviations_inside_match_blocks() {
+        let conf = explain(
+            "/etc/ssh/sshd_config",
+            include_str!("../tests/fixtures/config_files/sshd_config"),
+        );
+        assert_eq!(conf.format, ConfigFormat::Directives);
+
+        assert!(key(&conf, "PermitRootLogin").deviates);
+        // `yes` is the default however it is capitalized
+        assert!(!key(&conf, "pubkeyauthentication").deviates);
+        assert!(key(&conf, "PasswordAuthentication").deviates);
+        let in_match = key(&conf, "Match User backup.PasswordAuthentication");
+        assert_eq!(in_match.value, "yes");
+        assert!(!in_match.deviates);
+        assert_eq!(key(&conf, "Subsystem").default, None);
+    }
+
+    #[test]
+    fn test_unit_files_and_drop_ins() {
+        let unit = explain(
+            "/etc/systemd/system/backup.service",
+            include_str!("../tests/fixtures/config_files/backup.service"),
+        );
+        assert_eq!(unit.format, ConfigFormat::SystemdUnit);
+        assert!(key(&unit, "Service.Restart").deviates);
+        assert!(!key(&unit, "Service.Type").deviates);
+        // Continued lines are joined
+        assert_eq!(
+            key(&unit, "Service.ExecStart").value,
+            "/usr/bin/restic backup --tag nightly /home"
+        );
+        assert!(key(&unit, "Service.ProtectSystem").deviates);
+        assert!(!key(&unit, "Unit.DefaultDependencies").deviates);
+
+        let drop_in = explain(
+            "/etc/systemd/system/backup.service.d/override.conf",
+            "[Service]\nRestart=no\n",
+        );
+        assert_eq!(drop_in.format, ConfigFormat::SystemdUnit);
+        assert!(!key(&drop_in, "Service.Restart").deviates);
+    }
+
+    #[test]
+    fn test_toml_yaml_and_unknown_formats() {
+        let toml = explain(
+            "/etc/jarvis/jarvis.toml",
+            "[llm]\nprimary_provider = \"ollama\"\n[llm.consensus]\nenabled = false\n",
+        );
+        assert_eq!(key(&toml, "llm.consensus.enabled").value, "false");
+        assert_eq!(key(&toml, "llm.primary_provider").default, None);
+
+        let yaml = explain(
+            "/srv/compose.yaml",
+            "services:\n  web:\n    image: nginx\n    ports: [\"80:80\"]\n",
+        );
+        assert_eq!(key(&yaml, "services.web.image").value, "nginx");
+        assert_eq!(key(&yaml, "services.web.ports").value, "[\"80:80\"]");
+
+        let nginx = "events {}\nhttp {\n  server { listen 80; }\n}\n";
+        assert!(
+            ConfigExplanation::from_file(Path::new("/etc/nginx/nginx.conf"), nginx)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_batched_explanations_and_table() {
+        let mut conf = explain(
+            "/etc/pacman.conf",
+            "[options]\nParallelDownloads = 5\nColor\n",
+        );
+        let prompt = conf.explanation_prompt();
+        assert!(prompt.contains("options.ParallelDownloads = 5\n"));
+
+        conf.apply_explanations(
+            "- `options.ParallelDownloads`: Downloads five packages at once\n\
+             options.color: Colors the output\n",
+        );
+        assert_eq!(
+            key(&conf, "options.ParallelDownloads").explanation,
+            "Downloads five packages at once"
+        );
+        assert_eq!(key(&conf, "options.Color").explanation, "Colors the output");
+
+        let table = conf.render_table();
+        let lines: Vec<&str> = table.lines().collect();
+        assert!(lines[0].starts_with("  KEY"));
+        assert!(lines[1].starts_with("≠ options.ParallelDownloads  5"));
+        assert!(lines[2].contains("(set)"));
+        // Columns line up
+        let column = |line: &str| line.find("Colors").or(line.find("Downloads five"));
+        assert_eq!(column(lines[1]), column(lines[2]));
+    }
+}
@@ -0,0 +1,699 @@
+//! Validation for `jarvis.toml`
+//!
+//! Parsing alone accepts misspelled keys silently and reports type errors
+//! without saying where they are. Validation runs in two stages: parsing
+//! records every unknown key and names the key path of any type error, then
+//! a semantic pass checks URLs, cron schedules, numeric ranges, referenced
+//! files, and settings that only make sense together. `jarvis config
+//! validate` prints the result; jarvisd refuses to hot-reload a file with
+//! errors.
+
+use crate::config::Config;
+use crate::report;
+use crate::time_range;
+use chrono::Utc;
+use serde::Serialize;
+use std::fmt;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueLevel {
+    /// The file should not be used as is
+    Error,
+    /// Works, but probably not as intended
+    Warning,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigIssue {
+    pub level: IssueLevel,
+    /// Dotted TOML key path, e.g. `llm.temperature`
+    pub path: String,
+    pub message: String,
+    /// The offending value as written
+    pub value: Option<String>,
+    pub suggestion: Option<String>,
+}
+
+impl ConfigIssue {
+    fn error(path: &str, message: impl Into<String>) -> Self {
+        Self {
+            level: IssueLevel::Error,
+            path: path.to_string(),
+            message: message.into(),
+            value: None,
+            suggestion: None,
+        }
+    }
+
+    fn warning(path: &str, message: impl Into<String>) -> Self {
+        Self {
+            level: IssueLevel::Warning,
+            ..Self::error(path, message)
+        }
+    }
+
+    fn with_value(mut self, value: impl fmt::Display) -> Self {
+        self.value = Some(value.to_string());
+        self
+    }
+
+    fn with_suggestion(mut self, suggestion: impl Into<String>) -> Self {
+        self.suggestion = Some(suggestion.into());
+        self
+    }
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)?;
+        if let Some(value) = &self.value {
+            write!(f, " (got {})", value)?;
+        }
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, "; {}", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+/// The outcome of validating one file
+#[derive(Debug, Clone, Default)]
+pub struct Validation {
+    /// Set when the file parsed, even if it has issues
+    pub config: Option<Config>,
+    pub issues: Vec<ConfigIssue>,
+}
+
+impl Validation {
+    pub fn has_errors(&self) -> bool {
+        self.issues.iter().any(|i| i.level == IssueLevel::Error)
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &ConfigIssue> {
+        self.issues.iter().filter(|i| i.level == IssueLevel::Error)
+    }
+
+    /// The config when it parsed and has no errors; otherwise every error,
+    /// one per line
+    pub fn into_result(self) -> anyhow::Result<Config> {
+        match self.config {
+            Some(config) if !self.has_errors() => Ok(config),
+            _ => {
+                let errors: Vec<String> = self.errors().map(|e| format!("  {}", e)).collect();
+                anyhow::bail!("Invalid configuration:\n{}", errors.join("\n"))
+            }
+        }
+    }
+}
+
+/// Parse and validate the contents of a `jarvis.toml`
+pub fn validate_str(content: &str) -> Validation {
+    let mut unknown = Vec::new();
+    let deserializer = toml::Deserializer::new(content);
+    let parsed: Result<Config, _> =
+        serde_path_to_error::deserialize(serde_ignored::Deserializer::new(deserializer, |path| {
+            unknown.push(path.to_string())
+        }));
+
+    let mut validation = Validation::default();
+    let known = toml::Value::try_from(Config::default()).ok();
+    let written = content.parse::<toml::Table>().ok();
+    for path in unknown {
+        validation
+            .issues
+            .push(unknown_key(&path, known.as_ref(), written.as_ref()));
+    }
+
+    match parsed {
+        Ok(config) => {
+            validation.issues.extend(config.validate());
+            validation.config = Some(config);
+        }
+        Err(e) => {
+            let path = e.path().to_string();
+            // A syntax error has no key path; toml's message already points at the line
+            let path = if path == "." {
+                "(file)".to_string()
+            } else {
+                path
+            };
+            validation
+                .issues
+                .push(ConfigIssue::error(&path, e.into_inner().to_string().trim()));
+        }
+    }
+    validation
+}
+
+/// Read and validate a file
+pub async fn validate_file(path: &Path) -> anyhow::Result<Validation> {
+    let content = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?;
+    Ok(validate_str(&content))
+}
+
+fn unknown_key(
+    path: &str,
+    known: Option<&toml::Value>,
+    written: Option<&toml::Table>,
+) -> ConfigIssue {
+    let (parent, key) = path.rsplit_once('.').unwrap_or(("", path));
+    let mut issue = ConfigIssue::error(path, "unknown key");
+    if let Some(value) = written.and_then(|t| lookup(t, path)) {
+        issue = issue.with_value(value);
+    }
+
+    let candidates = known
+        .and_then(|v| v.as_table())
+        .and_then(|t| table_at(t, parent))
+        .map(|t| t.keys().cloned().collect::<Vec<_>>())
+        .unwrap_or_default();
+    match closest(key, &candidates) {
+        Some(name) => issue.with_suggestion(format!("did you mean `{}`?", name)),
+        None => issue.with_suggestion("remove it, or check the key against jarvis.toml.example"),
+    }
+}
+
+fn table_at<'a>(table: &'a toml::Table, path: &str) -> Option<&'a toml::Table> {
+    if path.is_empty() {
+        return Some(table);
+    }
+    path.split('.')
+        .try_fold(table, |t, segment| t.get(segment)?.as_table())
+}
+
+fn lookup(table: &toml::Table, path: &str) -> Option<toml::Value> {
+    let (parent, key) = path.rsplit_once('.').unwrap_or(("", path));
+    table_at(table, parent)?.get(key).cloned()
+}
+
+/// The candidate nearest to `key`, if it is close enough to be a typo
+fn closest<'a>(key: &str, candidates: &'a [String]) -> Option<&'a str> {
+    let max_distance = (key.len() / 3).max(1);
+    candidates
+        .iter()
+        .map(|c| (levenshtein(key, c), c))
+        .filter(|(d, _)| *d <= max_distance)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, c)| c.as_str())
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous + usize::from(ca != *cb);
+            previous = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(previous + 1);
+        }
+    }
+    row[b.len()]
+}
+
+impl Config {
+    /// Checks that parsing can't express: value formats, ranges, referenced
+    /// files, and settings that require each other
+    pub fn validate(&self) -> Vec<ConfigIssue> {
+        let mut issues = Vec::new();
+
+        // LLM
+        let llm = &self.llm;
+        check_one_of(
+            &mut issues,
+            "llm.primary_provider",
+            &llm.primary_provider,
+            &["ollama", "openai", "claude", "omen"],
+        );
+        check_url(&mut issues, "llm.ollama_url", &llm.ollama_url);
+        match llm.primary_provider.as_str() {
+            "openai" if llm.openai_api_key.is_none() => issues.push(ConfigIssue::error(
+                "llm.openai_api_key",
+                "required when primary_provider is \"openai\"",
+            )),
+            "claude" if llm.claude_api_key.is_none() => issues.push(ConfigIssue::error(
+                "llm.claude_api_key",
+                "required when primary_provider is \"claude\"",
+            )),
+            _ => {}
+        }
+        match (&llm.omen_base_url, llm.omen_enabled) {
+            (Some(url), _) => check_url(&mut issues, "llm.omen_base_url", url),
+            (None, Some(true)) => issues.push(
+                ConfigIssue::error("llm.omen_base_url", "required when omen_enabled = true")
+                    .with_suggestion(
+                        "set it to your Omen endpoint, e.g. \"http://localhost:8080/v1\"",
+                    ),
+            ),
+            _ => {}
+        }
+        if !(0.0..=2.0).contains(&llm.temperature) {
+            issues.push(
+                ConfigIssue::error("llm.temperature", "must be between 0.0 and 2.0")
+                    .with_value(llm.temperature),
+            );
+        }
+        check_positive(&mut issues, "llm.context_window", llm.context_window as u64);
+        check_positive(
+            &mut issues,
+            "llm.consensus.timeout_secs",
+            llm.consensus.timeout_secs,
+        );
+        check_positive(
+            &mut issues,
+            "llm.model_load_timeout_secs",
+            llm.model_load_timeout_secs,
+        );
+        if let Some(secs) = llm.keep_warm_secs {
+            check_positive(&mut issues, "llm.keep_warm_secs", secs);
+        }
+
+        // System and storage
+        check_one_of(
+            &mut issues,
+            "system.arch_package_manager",
+            &self.system.arch_package_manager,
+            &["pacman", "yay", "paru"],
+        );
+        if let Some(dotfiles) = &self.system.dotfiles_path {
+            check_exists(
+                &mut issues,
+                "system.dotfiles_path",
+                dotfiles,
+                IssueLevel::Warning,
+            );
+        }
+        let database = shellexpand::tilde(&self.database_path).to_string();
+        if let Some(parent) = Path::new(&database).parent()
+            && !parent.as_os_str().is_empty()
+            && !parent.exists()
+        {
+            issues.push(
+                ConfigIssue::warning("database_path", "directory does not exist yet")
+                    .with_value(format!("\"{}\"", self.database_path))
+                    .with_suggestion("run `jarvis config init` or create it"),
+            );
+        }
+
+        // Proxies
+        for (path, proxy) in [
+            ("network.proxy.http", &self.network.proxy.http),
+            ("network.proxy.https", &self.network.proxy.https),
+        ] {
+            if let Some(proxy) = proxy {
+                check_url(&mut issues, path, proxy);
+            }
+        }
+
+        // Blockchain endpoints
+        if let Some(ghostchain) = self.blockchain.as_ref().and_then(|b| b.ghostchain.as_ref()) {
+            check_url(
+                &mut issues,
+                "blockchain.ghostchain.grpc_url",
+                &ghostchain.grpc_url,
+            );
+            check_url(
+                &mut issues,
+                "blockchain.ghostchain.rpc_url",
+                &ghostchain.rpc_url,
+            );
+        }
+        if let Some(ethereum) = self.blockchain.as_ref().and_then(|b| b.ethereum.as_ref()) {
+            check_url(
+                &mut issues,
+                "blockchain.ethereum.rpc_url",
+                &ethereum.rpc_url,
+            );
+        }
+
+        // MCP
+        let mcp = &self.mcp;
+        check_one_of(
+            &mut issues,
+            "mcp.transport",
+            &mcp.transport,
+            &["stdio", "ws", "http", "tcp"],
+        );
+        if mcp.enabled && mcp.transport != "stdio" && mcp.address.is_none() {
+            issues.push(ConfigIssue::error(
+                "mcp.address",
+                format!("required for the \"{}\" transport", mcp.transport),
+            ));
+        }
+        if let Err(e) = mcp.tls.validate() {
+            issues.push(ConfigIssue::error("mcp.tls", e.to_string()));
+        }
+        check_positive(&mut issues, "mcp.idle_timeout_secs", mcp.idle_timeout_secs);
+        check_positive(
+            &mut issues,
+            "mcp.max_connections",
+            mcp.max_connections as u64,
+        );
+        check_positive(
+            &mut issues,
+            "mcp.plugins.default_timeout_secs",
+            mcp.plugins.default_timeout_secs,
+        );
+        check_positive(
+            &mut issues,
+            "mcp.timeouts.default_secs",
+            mcp.timeouts.default_secs,
+        );
+        for (key, secs) in &mcp.timeouts.per_tool {
+            check_positive(
+                &mut issues,
+                &format!("mcp.timeouts.per_tool.\"{}\"", key),
+                *secs,
+            );
+        }
+        for (path, rule) in [
+            ("mcp.rate_limits.default", &mcp.rate_limits.default),
+            ("mcp.rate_limits.destructive", &mcp.rate_limits.destructive),
+        ] {
+            check_positive(&mut issues, &format!("{}.burst", path), rule.burst as u64);
+        }
+
+        // Notifications
+        let notifications = &self.notifications;
+        check_positive(
+            &mut issues,
+            "notifications.queue_size",
+            notifications.queue_size as u64,
+        );
+        if let Some(ntfy) = &notifications.ntfy {
+            check_url(&mut issues, "notifications.ntfy.url", &ntfy.url);
+        }
+        if let Some(gotify) = &notifications.gotify {
+            check_url(&mut issues, "notifications.gotify.url", &gotify.url);
+        }
+        if let Some(webhook) = &notifications.webhook {
+            check_url(&mut issues, "notifications.webhook.url", &webhook.url);
+        }
+
+        // Remote hosts
+        check_positive(
+            &mut issues,
+            "remote.connect_timeout_secs",
+            self.remote.connect_timeout_secs,
+        );
+        for (name, host) in &self.remote.hosts {
+            let path = format!("remote.hosts.{}", name);
+            if let Some(identity) = &host.identity_file {
+                check_exists(
+                    &mut issues,
+                    &format!("{}.identity_file", path),
+                    identity,
+                    IssueLevel::Error,
+                );
+            }
+            if let Some(mac) = &host.mac
+                && crate::power::parse_mac(mac).is_err()
+            {
+                issues.push(
+                    ConfigIssue::error(&format!("{}.mac", path), "not a MAC address")
+                        .with_value(format!("\"{}\"", mac))
+                        .with_suggestion("use the form \"aa:bb:cc:dd:ee:ff\""),
+                );
+            }
+        }
+
+        // Reports
+        let reports = &self.reports;
+        if let Some(schedule) = &reports.schedule {
+            check_cron(&mut issues, "reports.schedule", schedule);
+        }
+        if time_range::parse_range(&reports.since).is_err() {
+            issues.push(
+                ConfigIssue::error("reports.since", "not a time range")
+                    .with_value(format!("\"{}\"", reports.since))
+                    .with_suggestion("use e.g. \"24h\", \"7d\", \"2w\", or \"this week\""),
+            );
+        }
+        if let Some(template) = &reports.html_template {
+            check_exists(
+                &mut issues,
+                "reports.html_template",
+                template,
+                IssueLevel::Error,
+            );
+        }
+
+        // Maintenance
+        let docker = &self.docker_maintenance;
+        check_cron(
+            &mut issues,
+            "docker_maintenance.prune_schedule",
+            &docker.prune_schedule,
+        );
+        check_cron(
+            &mut issues,
+            "docker_maintenance.image_check_schedule",
+            &docker.image_check_schedule,
+        );
+        check_positive(
+            &mut issues,
+            "docker_maintenance.unhealthy_restart_minutes",
+            docker.unhealthy_restart_minutes,
+        );
+
+        let btrfs = &self.btrfs_maintenance;
+        for (path, hour) in [
+            (
+                "btrfs_maintenance.window_start_hour",
+                btrfs.window_start_hour,
+            ),
+            ("btrfs_maintenance.window_end_hour", btrfs.window_end_hour),
+        ] {
+            if hour > 23 {
+                issues.push(
+                    ConfigIssue::error(path, "must be an hour from 0 to 23").with_value(hour),
+                );
+            }
+        }
+        if btrfs.balance_chunk_usage_percent > 100 {
+            issues.push(
+                ConfigIssue::error(
+                    "btrfs_maintenance.balance_chunk_usage_percent",
+                    "must be a percentage from 0 to 100",
+                )
+                .with_value(btrfs.balance_chunk_usage_percent),
+            );
+        }
+        if btrfs.max_load_per_cpu <= 0.0 {
+            issues.push(
+                ConfigIssue::error("btrfs_maintenance.max_load_per_cpu", "must be above 0")
+                    .with_value(btrfs.max_load_per_cpu),
+            );
+        }
+        check_positive(
+            &mut issues,
+            "btrfs_maintenance.scrub_interval_days",
+            btrfs.scrub_interval_days as u64,
+        );
+
+        let trivy = &self.trivy;
+        check_cron(&mut issues, "trivy.schedule", &trivy.schedule);
+        check_positive(&mut issues, "trivy.timeout_secs", trivy.timeout_secs);
+
+        check_positive(
+            &mut issues,
+            "trace.max_stored",
+            self.trace.max_stored as u64,
+        );
+
+        let incidents = &self.incidents;
+        check_positive(
+            &mut issues,
+            "incidents.resolve_after_minutes",
+            incidents.resolve_after_minutes,
+        );
+        for (i, rule) in incidents.rules.iter().enumerate() {
+            let path = format!("incidents.rules[{}]", i);
+            if rule.name.trim().is_empty() {
+                issues.push(ConfigIssue::error(
+                    &format!("{}.name", path),
+                    "must not be empty",
+                ));
+            }
+            check_positive(
+                &mut issues,
+                &format!("{}.within_minutes", path),
+                rule.within_minutes,
+            );
+        }
+
+        // HTTP API
+        let api = &self.api;
+        match api.bind.parse::<std::net::SocketAddr>() {
+            Ok(addr) if api.enabled && api.token.is_none() && !addr.ip().is_loopback() => {
+                issues.push(ConfigIssue::error(
+                    "api.token",
+                    "required when api.bind is not a loopback address",
+                ));
+            }
+            Ok(_) => {}
+            Err(_) => issues.push(
+                ConfigIssue::error("api.bind", "not a socket address")
+                    .with_value(format!("\"{}\"", api.bind))
+                    .with_suggestion("use an address and port, e.g. \"127.0.0.1:7333\""),
+            ),
+        }
+
+        issues
+    }
+}
+
+fn check_url(issues: &mut Vec<ConfigIssue>, path: &str, url: &str) {
+    match reqwest::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {}
+        Ok(parsed) => issues.push(
+            ConfigIssue::error(
+                path,
+                format!("unsupported URL scheme \"{}\"", parsed.scheme()),
+            )
+            .with_value(format!("\"{}\"", url))
+            .with_suggestion("use http:// or https://"),
+        ),
+        Err(e) => {
+            let issue = ConfigIssue::error(path, format!("not a valid URL: {}", e))
+                .with_value(format!("\"{}\"", url));
+            // The most common mistake is leaving off the scheme
+            let issue = if !url.contains("://") {
+                issue.with_suggestion(format!("did you mean \"http://{}\"?", url))
+            } else {
+                issue
+            };
+            issues.push(issue);
+        }
+    }
+}
+
+fn check_cron(issues: &mut Vec<ConfigIssue>, path: &str, expr: &str) {
+    if report::next_scheduled(expr, Utc::now()).is_err() {
+        issues.push(
+            ConfigIssue::error(path, "not a valid cron schedule")
+                .with_value(format!("\"{}\"", expr))
+                .with_suggestion("use five fields: minute hour day-of-month month day-of-week"),
+        );
+    }
+}
+
+fn check_positive(issues: &mut Vec<ConfigIssue>, path: &str, value: u64) {
+    if value == 0 {
+        issues.push(ConfigIssue::error(path, "must be greater than 0").with_value(value));
+    }
+}
+
+fn check_one_of(issues: &mut Vec<ConfigIssue>, path: &str, value: &str, allowed: &[&str]) {
+    if allowed.contains(&value) {
+        return;
+    }
+    let allowed_names: Vec<String> = allowed.iter().map(|a| a.to_string()).collect();
+    let issue = ConfigIssue::error(path, format!("must be one of {}", allowed.join(", ")))
+        .with_value(format!("\"{}\"", value));
+    issues.push(match closest(value, &allowed_names) {
+        Some(name) => issue.with_suggestion(format!("did you mean \"{}\"?", name)),
+        None => issue,
+    });
+}
+
+fn check_exists(issues: &mut Vec<ConfigIssue>, path: &str, file: &str, level: IssueLevel) {
+    if !Path::new(shellexpand::tilde(file).as_ref()).exists() {
+        issues.push(ConfigIssue {
+            level,
+            ..ConfigIssue::error(path, "file does not exist").with_value(format!("\"{}\"", file))
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn default_toml() -> String {
+        toml::to_string_pretty(&Config::default()).unwrap()
+    }
+
+    #[test]
+    fn test_default_config_is_valid() {
+        let validation = validate_str(&default_toml());
+        let errors: Vec<String> = validation.errors().map(|e| e.to_string()).collect();
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert!(validation.into_result().is_ok());
+    }
+
+    #[test]
+    fn test_typo_suggests_closest_key() {
+        let content = default_toml().replace("temperature = ", "temprature = ");
+        let validation = validate_str(&content);
+
+        let issue = validation
+            .issues
+            .iter()
+            .find(|i| i.path == "llm.temprature")
+            .expect("unknown key reported");
+        assert_eq!(issue.level, IssueLevel::Error);
+        assert_eq!(
+            issue.suggestion.as_deref(),
+            Some("did you mean `temperature`?")
+        );
+        assert!(issue.value.is_some());
+    }
+
+    #[test]
+    fn test_type_error_names_key_path() {
+        let content = default_toml().replace("context_window = 8192", "context_window = \"big\"");
+        let validation = validate_str(&content);
+
+        assert!(validation.config.is_none());
+        let error = validation.errors().next().unwrap();
+        assert_eq!(error.path, "llm.context_window");
+        assert!(error.message.contains("invalid type"), "{}", error.message);
+    }
+
+    #[test]
+    fn test_semantic_checks() {
+        let mut config = Config::default();
+        config.llm.omen_enabled = Some(true);
+        config.llm.omen_base_url = None;
+        config.llm.ollama_url = "localhost:11434".to_string();
+        config.llm.temperature = 3.5;
+        config.system.arch_package_manager = "parru".to_string();
+        config.reports.schedule = Some("every monday".to_string());
+        config.api.enabled = true;
+        config.api.bind = "0.0.0.0:7333".to_string();
+
+        let issues = config.validate();
+        let find = |path: &str| issues.iter().find(|i| i.path == path).unwrap();
+
+        assert_eq!(
+            find("llm.omen_base_url").message,
+            "required when omen_enabled = true"
+        );
+        assert!(find("llm.temperature").to_string().contains("(got 3.5)"));
+        assert_eq!(
+            find("system.arch_package_manager").suggestion.as_deref(),
+            Some("did you mean \"paru\"?")
+        );
+        assert!(find("reports.schedule").message.contains("cron"));
+        // "localhost:11434" parses as a URL with scheme "localhost"
+        assert!(find("llm.ollama_url").message.contains("scheme"));
+        assert!(find("api.token").message.contains("loopback"));
+    }
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("temprature", "temperature"), 1);
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(
+            closest("enabeld", &["enabled".to_string(), "html".to_string()]),
+            Some("enabled")
+        );
+        assert_eq!(closest("xyz", &["enabled".to_string()]), None);
+    }
+}
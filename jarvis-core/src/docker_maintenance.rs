@@ -0,0 +1,754 @@
+//! Docker housekeeping
+//!
+//! Three tasks keep a Docker host from accumulating cruft: pruning what
+//! nothing uses, noticing when a running container's image has a newer
+//! registry digest, and restarting containers that stay unhealthy. Containers
+//! in `protected_containers` and anything labeled `jarvis.keep=true` are left
+//! alone, and dry-run mode reports the plan without changing anything.
+
+use crate::bus::{self, BusEvent};
+use crate::config::DockerMaintenanceConfig;
+use crate::exec::{CommandRunner, SystemRunner};
+use crate::notify::{Notification, NotifyEvent, NotifySeverity};
+use crate::report;
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Containers, images, and networks labeled `jarvis.keep=true` are never pruned
+pub const KEEP_LABEL: &str = "jarvis.keep";
+/// Containers labeled `jarvis.autoupdate=true` may be pulled and recreated
+pub const AUTOUPDATE_LABEL: &str = "jarvis.autoupdate";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DockerTask {
+    Prune,
+    CheckImages,
+    RestartUnhealthy,
+}
+
+impl DockerTask {
+    pub fn name(self) -> &'static str {
+        match self {
+            DockerTask::Prune => "prune",
+            DockerTask::CheckImages => "image check",
+            DockerTask::RestartUnhealthy => "unhealthy restart",
+        }
+    }
+}
+
+/// Outcome of one task run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceResult {
+    pub task: DockerTask,
+    pub dry_run: bool,
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    /// What was done, or in a dry run what would be done
+    pub actions: Vec<String>,
+    /// What was left alone, and why
+    pub skipped: Vec<String>,
+    /// Containers whose image has a newer digest that was not applied
+    pub updates_available: Vec<String>,
+    pub bytes_freed: u64,
+    pub errors: Vec<String>,
+}
+
+impl MaintenanceResult {
+    fn new(task: DockerTask, dry_run: bool, started_at: DateTime<Utc>) -> Self {
+        Self {
+            task,
+            dry_run,
+            started_at,
+            duration_ms: 0,
+            actions: Vec::new(),
+            skipped: Vec::new(),
+            updates_available: Vec::new(),
+            bytes_freed: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn success(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Notification for this run; `None` when there was nothing to report
+    pub fn notification(&self) -> Option<Notification> {
+        if !self.errors.is_empty() {
+            return Some(Notification::new(
+                NotifyEvent::MaintenanceFailed,
+                NotifySeverity::Warning,
+                format!("Docker {} failed", self.task.name()),
+                self.errors.join("\n"),
+            ));
+        }
+        if self.actions.is_empty() && self.updates_available.is_empty() {
+            return None;
+        }
+
+        let mut body = self.actions.clone();
+        if !self.updates_available.is_empty() {
+            body.push(format!(
+                "Newer images available: {}",
+                self.updates_available.join(", ")
+            ));
+        }
+        if self.bytes_freed > 0 {
+            body.push(format!("{} freed", format_size(self.bytes_freed)));
+        }
+        let event = if self.actions.is_empty() {
+            NotifyEvent::UpdatesAvailable
+        } else {
+            NotifyEvent::MaintenanceFinished
+        };
+        let prefix = if self.dry_run { "Dry run: " } else { "" };
+        Some(Notification::new(
+            event,
+            NotifySeverity::Info,
+            format!("{}Docker {} finished", prefix, self.task.name()),
+            body.join("\n"),
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct DockerMaintenanceAgent {
+    config: DockerMaintenanceConfig,
+    runner: Arc<dyn CommandRunner>,
+    /// When each currently unhealthy container was first seen unhealthy
+    unhealthy_since: Mutex<HashMap<String, DateTime<Utc>>>,
+}
+
+impl DockerMaintenanceAgent {
+    pub fn new(config: DockerMaintenanceConfig) -> Self {
+        Self {
+            config,
+            runner: Arc::new(SystemRunner::default()),
+            unhealthy_since: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Run docker through `runner` instead of spawning it directly
+    pub fn with_runner(mut self, runner: Arc<dyn CommandRunner>) -> Self {
+        self.runner = runner;
+        self
+    }
+
+    pub fn config(&self) -> &DockerMaintenanceConfig {
+        &self.config
+    }
+
+    /// Tasks whose schedule fired in `(from, to]`. The unhealthy check is
+    /// cheap and time-sensitive, so it is due on every call.
+    pub fn due_tasks(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<DockerTask> {
+        let mut tasks = Vec::new();
+        for (task, schedule) in [
+            (DockerTask::Prune, &self.config.prune_schedule),
+            (DockerTask::CheckImages, &self.config.image_check_schedule),
+        ] {
+            match report::next_scheduled(schedule, from) {
+                Ok(at) if at <= to => tasks.push(task),
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Docker {} schedule: {}", task.name(), e),
+            }
+        }
+        tasks.push(DockerTask::RestartUnhealthy);
+        tasks
+    }
+
+    pub async fn run(&self, task: DockerTask) -> MaintenanceResult {
+        self.run_at(task, Utc::now()).await
+    }
+
+    async fn run_at(&self, task: DockerTask, now: DateTime<Utc>) -> MaintenanceResult {
+        let started = Instant::now();
+        let mut result = MaintenanceResult::new(task, self.config.dry_run, now);
+
+        let outcome = match task {
+            DockerTask::Prune => self.prune(&mut result).await,
+            DockerTask::CheckImages => self.check_images(&mut result).await,
+            DockerTask::RestartUnhealthy => self.restart_unhealthy(&mut result, now).await,
+        };
+        if let Err(e) = outcome {
+            result.errors.push(e.to_string());
+        }
+
+        result.duration_ms = started.elapsed().as_millis() as u64;
+        result
+    }
+
+    fn is_protected(&self, container: &str) -> bool {
+        self.config
+            .protected_containers
+            .iter()
+            .any(|name| name == container)
+    }
+
+    async fn docker(&self, args: &[&str]) -> Result<String> {
+        let output = self.runner.output("docker", args).await?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "docker {} failed: {}",
+                args.first().copied().unwrap_or_default(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    async fn prune(&self, result: &mut MaintenanceResult) -> Result<()> {
+        // Stopped containers are removed by name rather than with `container
+        // prune`, which has no way to spare protected ones
+        let keep = format!("{{{{.Label \"{}\"}}}}", KEEP_LABEL);
+        let stopped = self
+            .docker(&[
+                "ps",
+                "-a",
+                "--filter",
+                "status=exited",
+                "--filter",
+                "status=created",
+                "--size",
+                "--format",
+                &format!("{{{{.Names}}}}\t{}\t{{{{.Size}}}}", keep),
+            ])
+            .await?;
+
+        let mut removable = Vec::new();
+        for line in stopped.lines().filter(|l| !l.trim().is_empty()) {
+            let mut cols = line.split('\t');
+            let name = cols.next().unwrap_or_default().trim();
+            let labeled_keep = cols.next().is_some_and(|v| v.trim() == "true");
+            // "12.5MB (virtual 180MB)": only the writable layer goes away
+            let size = cols
+                .next()
+                .and_then(|s| s.split_whitespace().next())
+                .map(parse_size)
+                .unwrap_or(0);
+
+            if self.is_protected(name) {
+                result
+                    .skipped
+                    .push(format!("container {} is protected", name));
+            } else if labeled_keep {
+                result
+                    .skipped
+                    .push(format!("container {} is labeled {}=true", name, KEEP_LABEL));
+            } else {
+                result.bytes_freed += size;
+                result
+                    .actions
+                    .push(format!("remove stopped container {}", name));
+                removable.push(name.to_string());
+            }
+        }
+        if !removable.is_empty() && !self.config.dry_run {
+            let mut args = vec!["rm"];
+            args.extend(removable.iter().map(String::as_str));
+            self.docker(&args).await?;
+        }
+
+        if self.config.dry_run {
+            let images = self
+                .docker(&[
+                    "images",
+                    "--filter",
+                    "dangling=true",
+                    "--format",
+                    "{{.ID}}\t{{.Size}}",
+                ])
+                .await?;
+            for line in images.lines().filter(|l| !l.trim().is_empty()) {
+                let (id, size) = line.split_once('\t').unwrap_or((line, ""));
+                result.bytes_freed += parse_size(size.trim());
+                result
+                    .actions
+                    .push(format!("remove dangling image {} ({})", id, size.trim()));
+            }
+            let networks = self
+                .docker(&[
+                    "network",
+                    "ls",
+                    "--filter",
+                    "dangling=true",
+                    "--format",
+                    "{{.Name}}",
+                ])
+                .await?;
+            for name in networks.lines().map(str::trim).filter(|l| !l.is_empty()) {
+                result
+                    .actions
+                    .push(format!("remove unused network {}", name));
+            }
+            return Ok(());
+        }
+
+        let keep_filter = format!("label!={}=true", KEEP_LABEL);
+        let images = self
+            .docker(&["image", "prune", "-f", "--filter", &keep_filter])
+            .await?;
+        let deleted = images.lines().filter(|l| l.starts_with("deleted:")).count();
+        if deleted > 0 {
+            result
+                .actions
+                .push(format!("removed {} dangling image layers", deleted));
+        }
+        result.bytes_freed += reclaimed_space(&images);
+
+        let networks = self
+            .docker(&["network", "prune", "-f", "--filter", &keep_filter])
+            .await?;
+        // Removed networks are listed under a "Deleted Networks:" header
+        for name in networks
+            .lines()
+            .skip_while(|l| !l.starts_with("Deleted Networks"))
+            .skip(1)
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+        {
+            result
+                .actions
+                .push(format!("removed unused network {}", name));
+        }
+        Ok(())
+    }
+
+    async fn check_images(&self, result: &mut MaintenanceResult) -> Result<()> {
+        let running = self
+            .docker(&[
+                "ps",
+                "--format",
+                &format!(
+                    "{{{{.Names}}}}\t{{{{.Image}}}}\t{{{{.Label \"{}\"}}}}",
+                    AUTOUPDATE_LABEL
+                ),
+            ])
+            .await?;
+
+        for line in running.lines().filter(|l| !l.trim().is_empty()) {
+            let mut cols = line.split('\t').map(str::trim);
+            let (Some(name), Some(image)) = (cols.next(), cols.next()) else {
+                continue;
+            };
+            let wants_update = cols.next() == Some("true");
+
+            if image.contains("@sha256:") || image.starts_with("sha256:") {
+                result
+                    .skipped
+                    .push(format!("{} is pinned to a digest", name));
+                continue;
+            }
+
+            let local = self
+                .docker(&[
+                    "image",
+                    "inspect",
+                    "--format",
+                    "{{join .RepoDigests \" \"}}",
+                    image,
+                ])
+                .await?;
+            let local_digests: Vec<&str> = local
+                .split_whitespace()
+                .filter_map(|d| d.split_once('@').map(|(_, digest)| digest))
+                .collect();
+            if local_digests.is_empty() {
+                result
+                    .skipped
+                    .push(format!("{} runs a locally built image", name));
+                continue;
+            }
+
+            let remote = match self
+                .docker(&[
+                    "buildx",
+                    "imagetools",
+                    "inspect",
+                    image,
+                    "--format",
+                    "{{.Manifest.Digest}}",
+                ])
+                .await
+            {
+                Ok(digest) => digest.trim().to_string(),
+                Err(e) => {
+                    result.errors.push(format!("{}: {}", name, e));
+                    continue;
+                }
+            };
+            if local_digests.contains(&remote.as_str()) {
+                continue;
+            }
+
+            let protected = self.is_protected(name);
+            if protected && wants_update {
+                result
+                    .skipped
+                    .push(format!("{} is protected; not updating", name));
+            }
+            if !self.config.auto_update || !wants_update || protected {
+                result
+                    .updates_available
+                    .push(format!("{} ({})", name, image));
+                continue;
+            }
+
+            if self.config.dry_run {
+                result
+                    .actions
+                    .push(format!("pull {} and recreate {}", image, name));
+                continue;
+            }
+            let updated = async {
+                self.docker(&["pull", image]).await?;
+                self.recreate(name).await
+            };
+            match updated.await {
+                Ok(()) => {
+                    result
+                        .actions
+                        .push(format!("updated {} to {}", name, short_digest(&remote)))
+                }
+                Err(e) => result.errors.push(format!("{}: {}", name, e)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Recreate a compose-managed container on its freshly pulled image
+    async fn recreate(&self, name: &str) -> Result<()> {
+        let labels = self
+            .docker(&[
+                "inspect",
+                "--format",
+                "{{index .Config.Labels \"com.docker.compose.project\"}}\t\
+                 {{index .Config.Labels \"com.docker.compose.service\"}}\t\
+                 {{index .Config.Labels \"com.docker.compose.project.working_dir\"}}",
+                name,
+            ])
+            .await?;
+        let cols: Vec<&str> = labels
+            .trim()
+            .split('\t')
+            .map(str::trim)
+            .filter(|c| !c.is_empty() && *c != "<no value>")
+            .collect();
+        let [project, service, dir] = cols[..] else {
+            anyhow::bail!(
+                "pulled the new image, but {} is not managed by docker compose; recreate it to use it",
+                name
+            );
+        };
+
+        self.docker(&[
+            "compose",
+            "--project-directory",
+            dir,
+            "-p",
+            project,
+            "up",
+            "-d",
+            "--no-deps",
+            service,
+        ])
+        .await?;
+        Ok(())
+    }
+
+    async fn restart_unhealthy(
+        &self,
+        result: &mut MaintenanceResult,
+        now: DateTime<Utc>,
+    ) -> Result<()> {
+        let output = self
+            .docker(&[
+                "ps",
+                "--filter",
+                "health=unhealthy",
+                "--format",
+                "{{.Names}}",
+            ])
+            .await?;
+        let unhealthy: Vec<String> = output
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(String::from)
+            .collect();
+
+        let threshold = Duration::minutes(self.config.unhealthy_restart_minutes as i64);
+        let overdue: Vec<(String, i64)> = {
+            let mut since = self.unhealthy_since.lock().unwrap();
+            // Changes in either direction go on the bus for the incident correlator
+            since.retain(|name, _| {
+                let still = unhealthy.contains(name);
+                if !still {
+                    publish_container_health(name, "healthy");
+                }
+                still
+            });
+            unhealthy
+                .iter()
+                .filter_map(|name| {
+                    let first = *since.entry(name.clone()).or_insert_with(|| {
+                        publish_container_health(name, "unhealthy");
+                        now
+                    });
+                    (now - first >= threshold).then(|| (name.clone(), (now - first).num_minutes()))
+                })
+                .collect()
+        };
+
+        for (name, minutes) in overdue {
+            if self.is_protected(&name) {
+                result.skipped.push(format!(
+                    "{} is protected; unhealthy for {} minutes",
+                    name, minutes
+                ));
+                continue;
+            }
+            if self.config.dry_run {
+                result.actions.push(format!(
+                    "restart {} (unhealthy for {} minutes)",
+                    name, minutes
+                ));
+                continue;
+            }
+            match self.docker(&["restart", &name]).await {
+                Ok(_) => {
+                    result.actions.push(format!(
+                        "restarted {} after {} minutes unhealthy",
+                        name, minutes
+                    ));
+                    // Give the restarted container a fresh grace period,
+                    // still unhealthy until a check finds it otherwise
+                    self.unhealthy_since.lock().unwrap().insert(name, now);
+                }
+                Err(e) => result.errors.push(format!("{}: {}", name, e)),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn publish_container_health(name: &str, state: &str) {
+    bus::publish(BusEvent::HealthStateChanged {
+        component: format!("container {}", name),
+        state: state.to_string(),
+    });
+}
+
+/// Bytes from the "Total reclaimed space: 1.2GB" line prune commands print
+fn reclaimed_space(output: &str) -> u64 {
+    output
+        .lines()
+        .find_map(|l| l.strip_prefix("Total reclaimed space:"))
+        .map(|size| parse_size(size.trim()))
+        .unwrap_or(0)
+}
+
+/// Docker's human-readable sizes use decimal units: `12.5MB`, `512kB`, `0B`
+fn parse_size(size: &str) -> u64 {
+    let split = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let Ok(number) = number.parse::<f64>() else {
+        return 0;
+    };
+    let multiplier = match unit.trim().to_uppercase().as_str() {
+        "" | "B" => 1.0,
+        "KB" => 1e3,
+        "MB" => 1e6,
+        "GB" => 1e9,
+        "TB" => 1e12,
+        _ => return 0,
+    };
+    (number * multiplier) as u64
+}
+
+fn format_size(bytes: u64) -> String {
+    if bytes >= 1_000_000_000 {
+        format!("{:.1} GB", bytes as f64 / 1e9)
+    } else {
+        format!("{:.1} MB", bytes as f64 / 1e6)
+    }
+}
+
+fn short_digest(digest: &str) -> &str {
+    let hex = digest.strip_prefix("sha256:").unwrap_or(digest);
+    &hex[..hex.len().min(12)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::RecordingRunner;
+
+    fn agent(
+        config: DockerMaintenanceConfig,
+        runner: &Arc<RecordingRunner>,
+    ) -> DockerMaintenanceAgent {
+        DockerMaintenanceAgent::new(config).with_runner(runner.clone())
+    }
+
+    fn config() -> DockerMaintenanceConfig {
+        DockerMaintenanceConfig {
+            enabled: true,
+            protected_containers: vec!["db".to_string()],
+            ..Default::default()
+        }
+    }
+
+    fn prune_fixture() -> RecordingRunner {
+        RecordingRunner::new()
+            .respond(
+                "docker ps -a",
+                "db\t\t1MB (virtual 300MB)\n\
+                 cache\ttrue\t2MB (virtual 50MB)\n\
+                 old-job\t\t12.5MB (virtual 180MB)\n",
+            )
+            .respond(
+                "docker image prune",
+                "Deleted Images:\ndeleted: sha256:aaa\ndeleted: sha256:bbb\n\n\
+                 Total reclaimed space: 1.5GB\n",
+            )
+            .respond("docker network prune", "Deleted Networks:\nold_default\n")
+            .respond("docker images", "3f2a1b\t820MB\n")
+            .respond("docker network ls", "old_default\n")
+    }
+
+    #[tokio::test]
+    async fn test_prune_spares_protected_and_labeled_containers() {
+        let runner = Arc::new(prune_fixture());
+        let result = agent(config(), &runner).run(DockerTask::Prune).await;
+
+        assert!(result.success(), "{:?}", result.errors);
+        assert!(runner.calls().contains(&"docker rm old-job".to_string()));
+        assert_eq!(result.skipped.len(), 2);
+        assert_eq!(result.bytes_freed, 12_500_000 + 1_500_000_000);
+        assert!(
+            result
+                .actions
+                .contains(&"removed unused network old_default".to_string())
+        );
+
+        let notification = result.notification().unwrap();
+        assert!(
+            notification.body.contains("1.5 GB freed"),
+            "{}",
+            notification.body
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_only_reads() {
+        let runner = Arc::new(prune_fixture());
+        let config = DockerMaintenanceConfig {
+            dry_run: true,
+            ..config()
+        };
+        let result = agent(config, &runner).run(DockerTask::Prune).await;
+
+        for call in runner.calls() {
+            assert!(
+                call.starts_with("docker ps")
+                    || call.starts_with("docker images")
+                    || call.starts_with("docker network ls"),
+                "dry run ran `{}`",
+                call
+            );
+        }
+        assert_eq!(result.bytes_freed, 12_500_000 + 820_000_000);
+        assert!(result.notification().unwrap().title.starts_with("Dry run"));
+    }
+
+    fn image_fixture() -> RecordingRunner {
+        RecordingRunner::new()
+            .respond(
+                "docker ps --format",
+                "web\tnginx:latest\ttrue\nworker\tmyapp:dev\t\n",
+            )
+            .respond(
+                "docker image inspect --format {{join .RepoDigests \" \"}} nginx:latest",
+                "nginx@sha256:old\n",
+            )
+            .respond("docker image inspect", "\n")
+            .respond(
+                "docker buildx imagetools inspect nginx:latest",
+                "sha256:0123456789abcdef\n",
+            )
+            .respond("docker inspect", "web\tweb\t/srv/web\n")
+    }
+
+    #[tokio::test]
+    async fn test_image_updates_are_report_only_by_default() {
+        let runner = Arc::new(image_fixture());
+        let result = agent(config(), &runner).run(DockerTask::CheckImages).await;
+
+        assert_eq!(result.updates_available, vec!["web (nginx:latest)"]);
+        assert_eq!(result.skipped, vec!["worker runs a locally built image"]);
+        assert!(!runner.calls().iter().any(|c| c.starts_with("docker pull")));
+        assert_eq!(
+            result.notification().unwrap().event,
+            NotifyEvent::UpdatesAvailable
+        );
+    }
+
+    #[tokio::test]
+    async fn test_auto_update_recreates_labeled_compose_container() {
+        let runner = Arc::new(image_fixture());
+        let config = DockerMaintenanceConfig {
+            auto_update: true,
+            ..config()
+        };
+        let result = agent(config, &runner).run(DockerTask::CheckImages).await;
+
+        assert!(result.success(), "{:?}", result.errors);
+        assert_eq!(result.actions, vec!["updated web to 0123456789ab"]);
+        let calls = runner.calls();
+        assert!(calls.contains(&"docker pull nginx:latest".to_string()));
+        assert!(calls.contains(
+            &"docker compose --project-directory /srv/web -p web up -d --no-deps web".to_string()
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unhealthy_containers_restart_after_grace_period() {
+        let runner = Arc::new(
+            RecordingRunner::new().respond("docker ps --filter health=unhealthy", "api\ndb\n"),
+        );
+        let agent = agent(config(), &runner);
+        let start = Utc::now();
+
+        let first = agent.run_at(DockerTask::RestartUnhealthy, start).await;
+        assert!(first.actions.is_empty());
+
+        let later = agent
+            .run_at(DockerTask::RestartUnhealthy, start + Duration::minutes(11))
+            .await;
+        assert_eq!(
+            later.actions,
+            vec!["restarted api after 11 minutes unhealthy"]
+        );
+        assert_eq!(
+            later.skipped,
+            vec!["db is protected; unhealthy for 11 minutes"]
+        );
+        assert!(runner.calls().contains(&"docker restart api".to_string()));
+        assert!(!runner.calls().contains(&"docker restart db".to_string()));
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("12.5MB"), 12_500_000);
+        assert_eq!(parse_size("512kB"), 512_000);
+        assert_eq!(parse_size("0B"), 0);
+        assert_eq!(parse_size("garbage"), 0);
+    }
+}
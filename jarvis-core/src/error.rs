@@ -1,33 +1,242 @@
 use std::fmt;
 
 /// Custom error types for Jarvis
-#[derive(Debug, Clone)]
+///
+/// Each variant is a category with a stable [`code`](JarvisError::code), a
+/// CLI exit status, and an HTTP status; see [`JarvisError::mapping`]. Code
+/// that works with `anyhow` can recover the category with
+/// [`JarvisError::from_anyhow`].
+#[derive(Debug, Clone, PartialEq)]
 pub enum JarvisError {
     /// Configuration related errors
     Config(String),
     /// LLM provider errors
-    LLM(String),
+    Llm { provider: String, message: String },
     /// Database/Memory errors
     Database(String),
+    /// Network/API errors
+    Network(String),
+    /// The operation is not allowed for this user or host
+    PermissionDenied(String),
+    /// The operation would change the system while read-only mode is on
+    ReadOnlyMode(String),
+    /// A file, package, unit, or record that doesn't exist
+    NotFound(String),
+    /// Gave up waiting
+    Timeout(String),
+    /// Stopped by the user or a shutdown
+    Cancelled(String),
+    /// An external command failed; `code` is `None` when a signal killed it
+    Subprocess { code: Option<i32>, message: String },
+    /// Input that cannot fit a limit even after trimming; `estimated` and
+    /// `limit` are in the limit's unit (tokens for prompts)
+    TooLong {
+        limit: usize,
+        estimated: usize,
+        message: String,
+    },
     /// System integration errors
     System(String),
     /// Plugin errors
     Plugin(String),
-    /// Network/API errors
-    Network(String),
     /// General internal errors
     Internal(String),
 }
 
+/// How an error category surfaces outside the process
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorMapping {
+    /// Stable identifier for scripts and API clients
+    pub code: &'static str,
+    /// Process exit status; follows sysexits(3) where it has a match
+    pub exit_code: i32,
+    pub http_status: u16,
+}
+
+impl JarvisError {
+    /// The single table mapping categories to codes and statuses
+    pub fn mapping(&self) -> ErrorMapping {
+        let (code, exit_code, http_status) = match self {
+            JarvisError::Config(_) => ("config", 78, 500),
+            JarvisError::Llm { .. } => ("llm", 69, 502),
+            JarvisError::Database(_) => ("database", 74, 500),
+            JarvisError::Network(_) => ("network", 69, 502),
+            JarvisError::PermissionDenied(_) => ("permission_denied", 77, 403),
+            JarvisError::ReadOnlyMode(_) => ("read_only_mode", 77, 403),
+            JarvisError::NotFound(_) => ("not_found", 66, 404),
+            JarvisError::Timeout(_) => ("timeout", 75, 504),
+            JarvisError::Cancelled(_) => ("cancelled", 130, 499),
+            JarvisError::Subprocess { .. } => ("subprocess", 1, 500),
+            JarvisError::TooLong { .. } => ("too_long", 65, 413),
+            JarvisError::System(_) => ("system", 71, 500),
+            JarvisError::Plugin(_) => ("plugin", 70, 500),
+            JarvisError::Internal(_) => ("internal", 70, 500),
+        };
+        ErrorMapping {
+            code,
+            exit_code,
+            http_status,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        self.mapping().code
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        self.mapping().exit_code
+    }
+
+    pub fn http_status(&self) -> u16 {
+        self.mapping().http_status
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            JarvisError::Config(msg)
+            | JarvisError::Database(msg)
+            | JarvisError::Network(msg)
+            | JarvisError::PermissionDenied(msg)
+            | JarvisError::ReadOnlyMode(msg)
+            | JarvisError::NotFound(msg)
+            | JarvisError::Timeout(msg)
+            | JarvisError::Cancelled(msg)
+            | JarvisError::System(msg)
+            | JarvisError::Plugin(msg)
+            | JarvisError::Internal(msg) => msg,
+            JarvisError::Llm { message, .. }
+            | JarvisError::Subprocess { message, .. }
+            | JarvisError::TooLong { message, .. } => message,
+        }
+    }
+
+    /// The same category with a different message
+    pub fn with_message(&self, message: impl Into<String>) -> Self {
+        let message = message.into();
+        match self {
+            JarvisError::Config(_) => JarvisError::Config(message),
+            JarvisError::Llm { provider, .. } => JarvisError::Llm {
+                provider: provider.clone(),
+                message,
+            },
+            JarvisError::Database(_) => JarvisError::Database(message),
+            JarvisError::Network(_) => JarvisError::Network(message),
+            JarvisError::PermissionDenied(_) => JarvisError::PermissionDenied(message),
+            JarvisError::ReadOnlyMode(_) => JarvisError::ReadOnlyMode(message),
+            JarvisError::NotFound(_) => JarvisError::NotFound(message),
+            JarvisError::Timeout(_) => JarvisError::Timeout(message),
+            JarvisError::Cancelled(_) => JarvisError::Cancelled(message),
+            JarvisError::Subprocess { code, .. } => JarvisError::Subprocess {
+                code: *code,
+                message,
+            },
+            JarvisError::TooLong {
+                limit, estimated, ..
+            } => JarvisError::TooLong {
+                limit: *limit,
+                estimated: *estimated,
+                message,
+            },
+            JarvisError::System(_) => JarvisError::System(message),
+            JarvisError::Plugin(_) => JarvisError::Plugin(message),
+            JarvisError::Internal(_) => JarvisError::Internal(message),
+        }
+    }
+
+    pub fn llm(provider: &str, message: impl fmt::Display) -> Self {
+        JarvisError::Llm {
+            provider: provider.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    /// Categorize an `anyhow` error. A `JarvisError` that was converted into
+    /// it comes back unchanged; one wrapped in context keeps its category and
+    /// gains the context in its message. Otherwise the outermost cause with a
+    /// known type decides, and anything else is `Internal`.
+    pub fn from_anyhow(err: &anyhow::Error) -> Self {
+        if let Some(jarvis) = err.downcast_ref::<JarvisError>() {
+            return jarvis.clone();
+        }
+        let message = format!("{:#}", err);
+        for cause in err.chain() {
+            if let Some(jarvis) = cause.downcast_ref::<JarvisError>() {
+                return jarvis.with_message(message);
+            }
+            if let Some(categorized) = categorize(cause, &message) {
+                return categorized;
+            }
+        }
+        JarvisError::Internal(message)
+    }
+}
+
+/// Category of a well-known error type, carrying `message`
+fn categorize(cause: &(dyn std::error::Error + 'static), message: &str) -> Option<JarvisError> {
+    let message = message.to_string();
+    if let Some(io) = cause.downcast_ref::<std::io::Error>() {
+        return Some(match io.kind() {
+            std::io::ErrorKind::PermissionDenied => JarvisError::PermissionDenied(message),
+            std::io::ErrorKind::NotFound => JarvisError::NotFound(message),
+            std::io::ErrorKind::TimedOut => JarvisError::Timeout(message),
+            _ => JarvisError::System(message),
+        });
+    }
+    if let Some(http) = cause.downcast_ref::<reqwest::Error>() {
+        return Some(if http.is_timeout() {
+            JarvisError::Timeout(message)
+        } else {
+            JarvisError::Network(message)
+        });
+    }
+    if cause.is::<crate::net::OfflineError>() {
+        return Some(JarvisError::Network(message));
+    }
+    if cause.is::<tokio::time::error::Elapsed>() {
+        return Some(JarvisError::Timeout(message));
+    }
+    if cause.is::<sqlx::Error>() {
+        return Some(JarvisError::Database(message));
+    }
+    if cause.is::<toml::de::Error>() {
+        return Some(JarvisError::Config(message));
+    }
+    None
+}
+
 impl fmt::Display for JarvisError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             JarvisError::Config(msg) => write!(f, "Configuration error: {}", msg),
-            JarvisError::LLM(msg) => write!(f, "LLM error: {}", msg),
+            JarvisError::Llm { provider, message } => {
+                write!(f, "LLM error ({}): {}", provider, message)
+            }
             JarvisError::Database(msg) => write!(f, "Database error: {}", msg),
+            JarvisError::Network(msg) => write!(f, "Network error: {}", msg),
+            JarvisError::PermissionDenied(msg) => write!(f, "Permission denied: {}", msg),
+            JarvisError::ReadOnlyMode(msg) => write!(f, "Read-only mode: {}", msg),
+            JarvisError::NotFound(msg) => write!(f, "Not found: {}", msg),
+            JarvisError::Timeout(msg) => write!(f, "Timed out: {}", msg),
+            JarvisError::Cancelled(msg) => write!(f, "Cancelled: {}", msg),
+            JarvisError::Subprocess {
+                code: Some(code),
+                message,
+            } => write!(f, "Command failed with exit code {}: {}", code, message),
+            JarvisError::Subprocess {
+                code: None,
+                message,
+            } => write!(f, "Command killed by a signal: {}", message),
+            JarvisError::TooLong {
+                limit,
+                estimated,
+                message,
+            } => write!(
+                f,
+                "Too long: {} (about {} tokens, limit {})",
+                message, estimated, limit
+            ),
             JarvisError::System(msg) => write!(f, "System error: {}", msg),
             JarvisError::Plugin(msg) => write!(f, "Plugin error: {}", msg),
-            JarvisError::Network(msg) => write!(f, "Network error: {}", msg),
             JarvisError::Internal(msg) => write!(f, "Internal error: {}", msg),
         }
     }
@@ -37,7 +246,7 @@ impl std::error::Error for JarvisError {}
 
 impl From<anyhow::Error> for JarvisError {
     fn from(err: anyhow::Error) -> Self {
-        JarvisError::Internal(err.to_string())
+        JarvisError::from_anyhow(&err)
     }
 }
 
@@ -49,7 +258,11 @@ impl From<sqlx::Error> for JarvisError {
 
 impl From<reqwest::Error> for JarvisError {
     fn from(err: reqwest::Error) -> Self {
-        JarvisError::Network(err.to_string())
+        if err.is_timeout() {
+            JarvisError::Timeout(err.to_string())
+        } else {
+            JarvisError::Network(err.to_string())
+        }
     }
 }
 
@@ -67,7 +280,13 @@ impl From<toml::de::Error> for JarvisError {
 
 impl From<std::io::Error> for JarvisError {
     fn from(err: std::io::Error) -> Self {
-        JarvisError::System(format!("IO error: {}", err))
+        let message = format!("IO error: {}", err);
+        match err.kind() {
+            std::io::ErrorKind::PermissionDenied => JarvisError::PermissionDenied(message),
+            std::io::ErrorKind::NotFound => JarvisError::NotFound(message),
+            std::io::ErrorKind::TimedOut => JarvisError::Timeout(message),
+            _ => JarvisError::System(message),
+        }
     }
 }
 
@@ -96,7 +315,7 @@ where
     }
 
     fn with_llm_context(self, context: &str) -> JarvisResult<T> {
-        self.map_err(|e| JarvisError::LLM(format!("{}: {}", context, e)))
+        self.map_err(|e| JarvisError::llm("unknown", format!("{}: {}", context, e)))
     }
 
     fn with_database_context(self, context: &str) -> JarvisResult<T> {
@@ -114,8 +333,8 @@ macro_rules! jarvis_error {
     (Config, $msg:expr) => {
         JarvisError::Config($msg.to_string())
     };
-    (LLM, $msg:expr) => {
-        JarvisError::LLM($msg.to_string())
+    (Llm, $provider:expr, $msg:expr) => {
+        JarvisError::llm($provider, $msg)
     };
     (Database, $msg:expr) => {
         JarvisError::Database($msg.to_string())
@@ -129,6 +348,21 @@ macro_rules! jarvis_error {
     (Network, $msg:expr) => {
         JarvisError::Network($msg.to_string())
     };
+    (PermissionDenied, $msg:expr) => {
+        JarvisError::PermissionDenied($msg.to_string())
+    };
+    (ReadOnlyMode, $msg:expr) => {
+        JarvisError::ReadOnlyMode($msg.to_string())
+    };
+    (NotFound, $msg:expr) => {
+        JarvisError::NotFound($msg.to_string())
+    };
+    (Timeout, $msg:expr) => {
+        JarvisError::Timeout($msg.to_string())
+    };
+    (Cancelled, $msg:expr) => {
+        JarvisError::Cancelled($msg.to_string())
+    };
     (Internal, $msg:expr) => {
         JarvisError::Internal($msg.to_string())
     };
@@ -140,8 +374,8 @@ macro_rules! jarvis_error_fmt {
     (Config, $fmt:expr $(, $args:expr)*) => {
         JarvisError::Config(format!($fmt $(, $args)*))
     };
-    (LLM, $fmt:expr $(, $args:expr)*) => {
-        JarvisError::LLM(format!($fmt $(, $args)*))
+    (Llm, $provider:expr, $fmt:expr $(, $args:expr)*) => {
+        JarvisError::llm($provider, format!($fmt $(, $args)*))
     };
     (Database, $fmt:expr $(, $args:expr)*) => {
         JarvisError::Database(format!($fmt $(, $args)*))
@@ -155,7 +389,120 @@ macro_rules! jarvis_error_fmt {
     (Network, $fmt:expr $(, $args:expr)*) => {
         JarvisError::Network(format!($fmt $(, $args)*))
     };
+    (PermissionDenied, $fmt:expr $(, $args:expr)*) => {
+        JarvisError::PermissionDenied(format!($fmt $(, $args)*))
+    };
+    (ReadOnlyMode, $fmt:expr $(, $args:expr)*) => {
+        JarvisError::ReadOnlyMode(format!($fmt $(, $args)*))
+    };
+    (NotFound, $fmt:expr $(, $args:expr)*) => {
+        JarvisError::NotFound(format!($fmt $(, $args)*))
+    };
+    (Timeout, $fmt:expr $(, $args:expr)*) => {
+        JarvisError::Timeout(format!($fmt $(, $args)*))
+    };
+    (Cancelled, $fmt:expr $(, $args:expr)*) => {
+        JarvisError::Cancelled(format!($fmt $(, $args)*))
+    };
     (Internal, $fmt:expr $(, $args:expr)*) => {
         JarvisError::Internal(format!($fmt $(, $args)*))
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_mapping_table() {
+        let cases = [
+            (JarvisError::Config(String::new()), "config", 78, 500),
+            (JarvisError::llm("ollama", ""), "llm", 69, 502),
+            (
+                JarvisError::PermissionDenied(String::new()),
+                "permission_denied",
+                77,
+                403,
+            ),
+            (
+                JarvisError::ReadOnlyMode(String::new()),
+                "read_only_mode",
+                77,
+                403,
+            ),
+            (JarvisError::NotFound(String::new()), "not_found", 66, 404),
+            (JarvisError::Timeout(String::new()), "timeout", 75, 504),
+            (JarvisError::Cancelled(String::new()), "cancelled", 130, 499),
+            (
+                JarvisError::Subprocess {
+                    code: Some(2),
+                    message: String::new(),
+                },
+                "subprocess",
+                1,
+                500,
+            ),
+            (
+                JarvisError::TooLong {
+                    limit: 8192,
+                    estimated: 9000,
+                    message: String::new(),
+                },
+                "too_long",
+                65,
+                413,
+            ),
+            (JarvisError::Internal(String::new()), "internal", 70, 500),
+        ];
+        for (err, code, exit_code, http_status) in cases {
+            assert_eq!(err.code(), code);
+            assert_eq!(err.exit_code(), exit_code, "{}", code);
+            assert_eq!(err.http_status(), http_status, "{}", code);
+        }
+    }
+
+    #[test]
+    fn test_anyhow_round_trip_keeps_variant() {
+        let errors = [
+            JarvisError::PermissionDenied("remote mutations are disabled".into()),
+            JarvisError::llm("ollama", "model not found"),
+            JarvisError::Subprocess {
+                code: None,
+                message: "pacman".into(),
+            },
+        ];
+        for err in errors {
+            let wrapped = anyhow::Error::from(err.clone());
+            assert_eq!(JarvisError::from(wrapped), err);
+        }
+    }
+
+    #[test]
+    fn test_context_keeps_category_and_message() {
+        let result: anyhow::Result<()> =
+            Err(JarvisError::NotFound("package foo".into())).context("Fixing issue");
+        let err = JarvisError::from(result.unwrap_err());
+        assert_eq!(err.code(), "not_found");
+        assert!(err.message().starts_with("Fixing issue: "));
+        assert!(err.message().contains("package foo"));
+    }
+
+    #[test]
+    fn test_foreign_causes_are_categorized() {
+        let io = std::io::Error::new(std::io::ErrorKind::PermissionDenied, "/etc/pacman.conf");
+        let err = JarvisError::from(anyhow::Error::from(io).context("Reading config"));
+        assert_eq!(err.exit_code(), 77);
+
+        let offline = crate::net::OfflineError {
+            url: "https://aur.archlinux.org/".into(),
+        };
+        assert_eq!(
+            JarvisError::from(anyhow::Error::from(offline)).code(),
+            "network"
+        );
+
+        let plain = JarvisError::from(anyhow::anyhow!("something odd"));
+        assert_eq!(plain, JarvisError::Internal("something odd".into()));
+    }
+}
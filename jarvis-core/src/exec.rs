@@ -0,0 +1,328 @@
+//! External command execution
+//!
+//! Tools and agents spawn programs through [`CommandRunner`] instead of using
+//! `tokio::process::Command` directly. [`SystemRunner`] is the single place
+//! that enforces timeouts, keeps secrets out of child environments, runs
+//! children in the C locale so parsers see untranslated output, refuses
+//! mutating commands in [read-only mode](crate::read_only), and logs every
+//! spawn; tests substitute [`RecordingRunner`] to replay fixture output
+//! without a live Arch system.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use std::borrow::Cow;
+use std::fmt;
+use std::os::unix::process::ExitStatusExt;
+use std::process::{ExitStatus, Output};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+
+/// Applied when neither the caller nor the runner sets a timeout
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Environment variable name fragments that are never passed to children
+const SENSITIVE_ENV_MARKERS: &[&str] = &[
+    "TOKEN",
+    "SECRET",
+    "PASSWORD",
+    "PASSWD",
+    "PASSPHRASE",
+    "API_KEY",
+    "APIKEY",
+    "CREDENTIAL",
+    "PRIVATE_KEY",
+];
+
+/// Locale every child runs in. Parsers match English pacman, systemctl, and
+/// df output, which a translated locale would silently break.
+pub const C_LOCALE: &[(&str, &str)] = &[("LC_ALL", "C"), ("LANG", "C")];
+
+/// Per-invocation settings
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// Kill the command after this long; `None` uses the runner's default
+    pub timeout: Option<Duration>,
+    /// Variables set for the child on top of the sanitized environment;
+    /// these may override [`C_LOCALE`]
+    pub env: Vec<(String, String)>,
+}
+
+impl RunOptions {
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_env(mut self, key: &str, value: &str) -> Self {
+        self.env.push((key.to_string(), value.to_string()));
+        self
+    }
+}
+
+/// Spawns external programs and captures their output
+#[async_trait]
+pub trait CommandRunner: Send + Sync + fmt::Debug {
+    async fn run(&self, program: &str, args: &[&str], options: &RunOptions) -> Result<Output>;
+
+    /// Run with default options
+    async fn output(&self, program: &str, args: &[&str]) -> Result<Output> {
+        self.run(program, args, &RunOptions::default()).await
+    }
+}
+
+/// Runs commands on this machine
+#[derive(Debug, Clone)]
+pub struct SystemRunner {
+    default_timeout: Duration,
+}
+
+impl SystemRunner {
+    pub fn new(default_timeout: Duration) -> Self {
+        Self { default_timeout }
+    }
+}
+
+impl Default for SystemRunner {
+    fn default() -> Self {
+        Self::new(DEFAULT_TIMEOUT)
+    }
+}
+
+#[async_trait]
+impl CommandRunner for SystemRunner {
+    async fn run(&self, program: &str, args: &[&str], options: &RunOptions) -> Result<Output> {
+        crate::read_only::check_command(program, args)?;
+        let timeout = options.timeout.unwrap_or(self.default_timeout);
+
+        let mut command = Command::new(program);
+        command.args(args).kill_on_drop(true);
+        for (key, _) in std::env::vars_os() {
+            if is_sensitive_env(&key.to_string_lossy()) {
+                command.env_remove(key);
+            }
+        }
+        // GNU gettext prefers LANGUAGE over LC_ALL for messages
+        command.env_remove("LANGUAGE");
+        command.envs(C_LOCALE.iter().copied());
+        command.envs(options.env.iter().cloned());
+
+        let phase = crate::trace::phase("subprocess");
+        phase.attr(
+            "command",
+            format!("{} {}", program, args.join(" ")).trim_end(),
+        );
+
+        let started = Instant::now();
+        let output = match tokio::time::timeout(timeout, command.output()).await {
+            Ok(output) => output?,
+            Err(_) => {
+                tracing::warn!(target: "jarvis::exec", program, ?args, "timed out");
+                phase.attr("status", "timeout");
+                anyhow::bail!("{} timed out after {}s", program, timeout.as_secs());
+            }
+        };
+        phase.attr(
+            "status",
+            output
+                .status
+                .code()
+                .map_or("signal".to_string(), |c| c.to_string()),
+        );
+
+        tracing::info!(
+            target: "jarvis::exec",
+            program,
+            ?args,
+            status = output.status.code(),
+            elapsed_ms = started.elapsed().as_millis() as u64,
+            "command finished"
+        );
+        Ok(output)
+    }
+}
+
+/// Whether a variable looks like it carries a credential
+pub fn is_sensitive_env(name: &str) -> bool {
+    let upper = name.to_uppercase();
+    SENSITIVE_ENV_MARKERS
+        .iter()
+        .any(|marker| upper.contains(marker))
+}
+
+/// A command's output as text. Bytes that are not UTF-8 become U+FFFD rather
+/// than failing the parse or leaking into serialized JSON.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OutputText {
+    pub stdout: String,
+    pub stderr: String,
+    /// Whether either stream held bytes that are not UTF-8
+    pub lossy: bool,
+}
+
+impl OutputText {
+    pub fn decode(output: &Output) -> Self {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        // from_utf8_lossy only allocates when it had to replace something
+        let lossy = matches!(stdout, Cow::Owned(_)) || matches!(stderr, Cow::Owned(_));
+        Self {
+            stdout: stdout.into_owned(),
+            stderr: stderr.into_owned(),
+            lossy,
+        }
+    }
+}
+
+/// Build an `Output` as a finished process would have produced it
+pub fn fake_output(code: i32, stdout: &str, stderr: &str) -> Output {
+    Output {
+        status: ExitStatus::from_raw(code << 8),
+        stdout: stdout.as_bytes().to_vec(),
+        stderr: stderr.as_bytes().to_vec(),
+    }
+}
+
+/// Never spawns anything: records each command line and answers with the
+/// first registered response whose prefix matches it, or an empty success
+#[derive(Debug, Default)]
+pub struct RecordingRunner {
+    responses: Mutex<Vec<(String, Output)>>,
+    calls: Mutex<Vec<String>>,
+}
+
+impl RecordingRunner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer command lines starting with `prefix` with a successful `stdout`
+    pub fn respond(self, prefix: &str, stdout: &str) -> Self {
+        self.respond_with(prefix, fake_output(0, stdout, ""))
+    }
+
+    /// Answer command lines starting with `prefix` with `output`
+    pub fn respond_with(self, prefix: &str, output: Output) -> Self {
+        self.responses
+            .lock()
+            .unwrap()
+            .push((prefix.to_string(), output));
+        self
+    }
+
+    /// Command lines run so far, program and arguments joined by spaces
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl CommandRunner for RecordingRunner {
+    async fn run(&self, program: &str, args: &[&str], _options: &RunOptions) -> Result<Output> {
+        let line = std::iter::once(program)
+            .chain(args.iter().copied())
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.calls.lock().unwrap().push(line.clone());
+
+        let responses = self.responses.lock().unwrap();
+        Ok(responses
+            .iter()
+            .find(|(prefix, _)| line.starts_with(prefix.as_str()))
+            .map(|(_, output)| output.clone())
+            .unwrap_or_else(|| fake_output(0, "", "")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sensitive_env_names() {
+        assert!(is_sensitive_env("GITHUB_TOKEN"));
+        assert!(is_sensitive_env("openai_api_key"));
+        assert!(is_sensitive_env("DB_PASSWORD"));
+        assert!(!is_sensitive_env("PATH"));
+        assert!(!is_sensitive_env("SSH_AUTH_SOCK"));
+    }
+
+    #[tokio::test]
+    async fn test_recording_runner_matches_first_prefix() {
+        let runner = RecordingRunner::new()
+            .respond("pacman -Qu", "linux 6.1 -> 6.2\n")
+            .respond_with("pacman", fake_output(1, "", "error: target not found"));
+
+        let updates = runner.output("pacman", &["-Qu"]).await.unwrap();
+        assert!(updates.status.success());
+        assert_eq!(updates.stdout, b"linux 6.1 -> 6.2\n");
+
+        let info = runner.output("pacman", &["-Si", "nope"]).await.unwrap();
+        assert_eq!(info.status.code(), Some(1));
+
+        let other = runner.output("docker", &["ps"]).await.unwrap();
+        assert!(other.status.success() && other.stdout.is_empty());
+
+        assert_eq!(
+            runner.calls(),
+            vec!["pacman -Qu", "pacman -Si nope", "docker ps"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_system_runner_enforces_timeout() {
+        let runner = SystemRunner::default();
+        let options = RunOptions::default().with_timeout(Duration::from_millis(100));
+        let err = runner.run("sleep", &["5"], &options).await.unwrap_err();
+        assert!(err.to_string().contains("timed out"));
+    }
+
+    #[tokio::test]
+    async fn test_system_runner_strips_secrets_and_sets_env() {
+        // SAFETY: the variable is unique to this test
+        unsafe { std::env::set_var("JARVIS_EXEC_TEST_TOKEN", "hunter2") };
+        let options = RunOptions::default().with_env("JARVIS_EXEC_TEST_VALUE", "visible");
+        let output = SystemRunner::default()
+            .run("env", &[], &options)
+            .await
+            .unwrap();
+        let env = String::from_utf8_lossy(&output.stdout);
+
+        assert!(env.contains("JARVIS_EXEC_TEST_VALUE=visible"));
+        assert!(!env.contains("hunter2"));
+    }
+
+    #[tokio::test]
+    async fn test_system_runner_forces_c_locale() {
+        let output = SystemRunner::default().output("env", &[]).await.unwrap();
+        let env = String::from_utf8_lossy(&output.stdout);
+        let vars: Vec<&str> = env.lines().collect();
+
+        assert!(vars.contains(&"LC_ALL=C"));
+        assert!(vars.contains(&"LANG=C"));
+        assert!(!vars.iter().any(|v| v.starts_with("LANGUAGE=")));
+
+        // Callers can still ask for something else
+        let options = RunOptions::default().with_env("LC_ALL", "C.UTF-8");
+        let output = SystemRunner::default()
+            .run("env", &[], &options)
+            .await
+            .unwrap();
+        assert!(String::from_utf8_lossy(&output.stdout).contains("LC_ALL=C.UTF-8\n"));
+    }
+
+    #[test]
+    fn test_output_text_flags_invalid_utf8() {
+        let clean = OutputText::decode(&fake_output(0, "grüße\n", ""));
+        assert_eq!(clean.stdout, "grüße\n");
+        assert!(!clean.lossy);
+
+        // "Größe" in Latin-1, as a misconfigured tool might print it
+        let mut output = fake_output(1, "", "");
+        output.stderr = b"Gr\xf6\xdfe: 12 MiB".to_vec();
+        let text = OutputText::decode(&output);
+        assert!(text.lossy);
+        assert_eq!(text.stderr, "Gr\u{fffd}\u{fffd}e: 12 MiB");
+        assert!(serde_json::to_string(&text.stderr).is_ok());
+    }
+}
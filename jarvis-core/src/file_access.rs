@@ -0,0 +1,227 @@
+//! Which local files Jarvis may read on someone's behalf
+//!
+//! Tools and editor integrations that read paths they were handed go through
+//! a [`FileAccess`] built from `[files]`, so a single allowlist decides for
+//! all of them. A path is readable when, after resolving symlinks and `..`,
+//! it lies under one of `allowed_paths` and none of its components match a
+//! `denied_names` pattern.
+
+use crate::error::{JarvisError, JarvisResult};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileAccessConfig {
+    /// Directories whose contents may be read; `~` is expanded
+    #[serde(default = "default_allowed_paths")]
+    pub allowed_paths: Vec<String>,
+    /// File or directory names never read, even under an allowed path;
+    /// `*` matches any run of characters
+    #[serde(default = "default_denied_names")]
+    pub denied_names: Vec<String>,
+    /// Larger files are refused rather than truncated
+    #[serde(default = "default_max_file_bytes")]
+    pub max_file_bytes: u64,
+}
+
+fn default_allowed_paths() -> Vec<String> {
+    vec!["~".to_string()]
+}
+
+fn default_denied_names() -> Vec<String> {
+    [
+        ".env", ".env.*", ".ssh", ".gnupg", ".netrc", "*.pem", "*.key", "id_*",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect()
+}
+
+fn default_max_file_bytes() -> u64 {
+    256 * 1024
+}
+
+impl Default for FileAccessConfig {
+    fn default() -> Self {
+        Self {
+            allowed_paths: default_allowed_paths(),
+            denied_names: default_denied_names(),
+            max_file_bytes: default_max_file_bytes(),
+        }
+    }
+}
+
+/// The allowlist from [`FileAccessConfig`], with roots resolved
+#[derive(Debug, Clone)]
+pub struct FileAccess {
+    roots: Vec<PathBuf>,
+    denied: Vec<String>,
+    max_file_bytes: u64,
+}
+
+impl FileAccess {
+    pub fn new(config: &FileAccessConfig) -> Self {
+        let roots = config
+            .allowed_paths
+            .iter()
+            .map(|p| PathBuf::from(shellexpand::tilde(p).to_string()))
+            // A root that doesn't exist yet can't contain anything readable
+            .filter_map(|p| p.canonicalize().ok())
+            .collect();
+        Self {
+            roots,
+            denied: config.denied_names.clone(),
+            max_file_bytes: config.max_file_bytes,
+        }
+    }
+
+    /// The resolved path, or `PermissionDenied` when the allowlist refuses it
+    pub fn check(&self, path: &Path) -> JarvisResult<PathBuf> {
+        let resolved = path.canonicalize()?;
+        let Some(root) = self.roots.iter().find(|r| resolved.starts_with(r)) else {
+            return Err(JarvisError::PermissionDenied(format!(
+                "{} is outside [files] allowed_paths",
+                path.display()
+            )));
+        };
+
+        let inside = resolved.strip_prefix(root).unwrap_or(&resolved);
+        if let Some(name) = inside
+            .components()
+            .filter_map(|c| c.as_os_str().to_str())
+            .find(|name| self.denied.iter().any(|p| name_matches(p, name)))
+        {
+            return Err(JarvisError::PermissionDenied(format!(
+                "{} matches [files] denied_names ({})",
+                path.display(),
+                name
+            )));
+        }
+        Ok(resolved)
+    }
+
+    pub fn is_allowed(&self, path: &Path) -> bool {
+        self.check(path).is_ok()
+    }
+
+    pub async fn read_to_string(&self, path: &Path) -> JarvisResult<String> {
+        let resolved = self.check(path)?;
+        let size = tokio::fs::metadata(&resolved).await?.len();
+        if size > self.max_file_bytes {
+            return Err(JarvisError::PermissionDenied(format!(
+                "{} is {} bytes, over the [files] max_file_bytes limit of {}",
+                path.display(),
+                size,
+                self.max_file_bytes
+            )));
+        }
+        Ok(tokio::fs::read_to_string(&resolved).await?)
+    }
+
+    /// Names of the entries in an allowed directory, skipping denied ones
+    pub async fn list_dir(&self, dir: &Path) -> JarvisResult<Vec<String>> {
+        let resolved = self.check(dir)?;
+        let mut entries = tokio::fs::read_dir(&resolved).await?;
+        let mut names = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !self.denied.iter().any(|p| name_matches(p, &name)) {
+                names.push(name);
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+}
+
+/// Glob match where `*` stands for any run of characters
+pub(crate) fn name_matches(pattern: &str, name: &str) -> bool {
+    let Some((head, rest)) = pattern.split_once('*') else {
+        return pattern == name;
+    };
+    let Some(mut remaining) = name.strip_prefix(head) else {
+        return false;
+    };
+    let mut parts: Vec<&str> = rest.split('*').collect();
+    let tail = parts.pop().unwrap_or_default();
+    for part in parts {
+        match remaining.find(part) {
+            Some(at) => remaining = &remaining[at + part.len()..],
+            None => return false,
+        }
+    }
+    remaining.len() >= tail.len() && remaining.ends_with(tail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn access(root: &Path) -> FileAccess {
+        FileAccess::new(&FileAccessConfig {
+            allowed_paths: vec![root.display().to_string()],
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_reads_only_inside_allowed_paths() {
+        let allowed = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(allowed.path().join("main.rs"), "fn main() {}").unwrap();
+        std::fs::write(outside.path().join("secret.txt"), "nope").unwrap();
+        let files = access(allowed.path());
+
+        assert_eq!(
+            files
+                .read_to_string(&allowed.path().join("main.rs"))
+                .await
+                .unwrap(),
+            "fn main() {}"
+        );
+
+        let err = files
+            .read_to_string(&outside.path().join("secret.txt"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "permission_denied");
+
+        // `..` and symlinks are resolved before the check
+        let escape = allowed.path().join("..").join(
+            outside
+                .path()
+                .strip_prefix(allowed.path().parent().unwrap())
+                .unwrap(),
+        );
+        assert!(!files.is_allowed(&escape.join("secret.txt")));
+        #[cfg(unix)]
+        {
+            let link = allowed.path().join("link");
+            std::os::unix::fs::symlink(outside.path(), &link).unwrap();
+            assert!(!files.is_allowed(&link.join("secret.txt")));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_denied_names_apply_inside_allowed_paths() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::write(root.path().join(".env"), "TOKEN=1").unwrap();
+        std::fs::write(root.path().join("server.pem"), "").unwrap();
+        std::fs::write(root.path().join("lib.rs"), "").unwrap();
+        let files = access(root.path());
+
+        assert!(!files.is_allowed(&root.path().join(".env")));
+        assert!(!files.is_allowed(&root.path().join("server.pem")));
+        assert_eq!(files.list_dir(root.path()).await.unwrap(), ["lib.rs"]);
+    }
+
+    #[test]
+    fn test_name_patterns() {
+        assert!(name_matches("*.pem", "server.pem"));
+        assert!(name_matches("id_*", "id_ed25519.pub"));
+        assert!(name_matches(".env.*", ".env.local"));
+        assert!(!name_matches(".env.*", ".env"));
+        assert!(!name_matches("*.key", "keys.rs"));
+        assert!(name_matches("a*b*c", "axxbyyc"));
+    }
+}
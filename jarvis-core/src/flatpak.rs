@@ -0,0 +1,299 @@
+//! Flatpak backend for the package tooling
+//!
+//! Wraps the `flatpak` CLI using `--columns` output so results are structured
+//! rather than scraped from the human-readable tables.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::exec::{CommandRunner, OutputText, RunOptions, SystemRunner};
+
+/// Updates download whole runtimes; `SystemRunner`'s default would cut them off
+const UPDATE_TIMEOUT: Duration = Duration::from_secs(3600);
+
+/// Oldest supported branch of the common platform runtimes. Anything older
+/// no longer receives security updates from upstream.
+const SUPPORTED_RUNTIME_BRANCHES: &[(&str, &str)] = &[
+    ("org.freedesktop.Platform", "23.08"),
+    ("org.freedesktop.Sdk", "23.08"),
+    ("org.gnome.Platform", "46"),
+    ("org.gnome.Sdk", "46"),
+    ("org.kde.Platform", "5.15-23.08"),
+    ("org.kde.Sdk", "5.15-23.08"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlatpakKind {
+    App,
+    Runtime,
+}
+
+/// An installed Flatpak application or runtime
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlatpakRef {
+    pub kind: FlatpakKind,
+    pub application: String,
+    pub name: String,
+    pub version: Option<String>,
+    pub branch: String,
+    pub arch: String,
+    pub origin: String,
+    /// "system" or "user"
+    pub installation: String,
+}
+
+impl FlatpakRef {
+    /// Full ref, e.g. `app/org.mozilla.firefox/x86_64/stable`
+    pub fn full_ref(&self) -> String {
+        let kind = match self.kind {
+            FlatpakKind::App => "app",
+            FlatpakKind::Runtime => "runtime",
+        };
+        format!("{}/{}/{}/{}", kind, self.application, self.arch, self.branch)
+    }
+}
+
+/// An available update reported by `flatpak remote-ls --updates`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlatpakUpdate {
+    pub application: String,
+    pub version: Option<String>,
+    pub branch: String,
+    pub origin: String,
+}
+
+/// A runtime that is past its upstream support window
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuntimeAdvisory {
+    pub runtime: String,
+    pub branch: String,
+    pub version: Option<String>,
+    pub oldest_supported: String,
+    /// Installed apps that still depend on this runtime
+    pub used_by: Vec<String>,
+}
+
+/// Whether the flatpak CLI is installed
+pub async fn is_available() -> bool {
+    SystemRunner::default()
+        .output("flatpak", &["--version"])
+        .await
+        .map(|o| o.status.success())
+        .unwrap_or(false)
+}
+
+/// Installed apps and runtimes
+pub async fn list_installed() -> Result<Vec<FlatpakRef>> {
+    let mut refs = list_kind(FlatpakKind::App).await?;
+    refs.extend(list_kind(FlatpakKind::Runtime).await?);
+    Ok(refs)
+}
+
+async fn list_kind(kind: FlatpakKind) -> Result<Vec<FlatpakRef>> {
+    let flag = match kind {
+        FlatpakKind::App => "--app",
+        FlatpakKind::Runtime => "--runtime",
+    };
+    let stdout = run_flatpak(&[
+        "list",
+        flag,
+        "--columns=application,name,version,branch,arch,origin,installation",
+    ])
+    .await?;
+
+    Ok(parse_list(&stdout, kind))
+}
+
+/// Pending updates across all configured remotes
+pub async fn check_updates() -> Result<Vec<FlatpakUpdate>> {
+    let stdout = run_flatpak(&[
+        "remote-ls",
+        "--updates",
+        "--columns=application,version,branch,origin",
+    ])
+    .await?;
+
+    Ok(parse_updates(&stdout))
+}
+
+/// Update the given refs, or everything when `refs` is empty
+pub async fn update(refs: &[String]) -> Result<serde_json::Value> {
+    let start_time = std::time::Instant::now();
+
+    let mut args = vec!["update", "--noninteractive", "-y"];
+    args.extend(refs.iter().map(String::as_str));
+    let output = SystemRunner::default()
+        .run(
+            "flatpak",
+            &args,
+            &RunOptions::default().with_timeout(UPDATE_TIMEOUT),
+        )
+        .await
+        .context("Failed to run flatpak update")?;
+    let text = OutputText::decode(&output);
+
+    Ok(serde_json::json!({
+        "operation": "flatpak_update",
+        "refs": refs,
+        "success": output.status.success(),
+        "duration_ms": start_time.elapsed().as_millis() as u64,
+        "output": text.stdout,
+        "error": if text.stderr.is_empty() {
+            None
+        } else {
+            Some(text.stderr)
+        },
+        "output_lossy": text.lossy,
+    }))
+}
+
+/// Installed runtimes older than their upstream support window
+pub async fn runtime_advisories() -> Result<Vec<RuntimeAdvisory>> {
+    let installed = list_installed().await?;
+    let mut advisories = eol_runtimes(&installed);
+
+    // Attribute each EOL runtime to the apps still using it
+    for advisory in &mut advisories {
+        for app in installed.iter().filter(|r| r.kind == FlatpakKind::App) {
+            let runtime = format!("{}/{}", advisory.runtime, advisory.branch);
+            if app_runtime(&app.application).await.as_deref() == Some(runtime.as_str()) {
+                advisory.used_by.push(app.application.clone());
+            }
+        }
+    }
+
+    Ok(advisories)
+}
+
+/// Runtimes in `installed` whose branch predates the supported window
+pub fn eol_runtimes(installed: &[FlatpakRef]) -> Vec<RuntimeAdvisory> {
+    installed
+        .iter()
+        .filter(|r| r.kind == FlatpakKind::Runtime)
+        .filter_map(|r| {
+            let (_, oldest) = SUPPORTED_RUNTIME_BRANCHES
+                .iter()
+                .find(|(id, _)| *id == r.application)?;
+            if compare_branches(&r.branch, oldest) == std::cmp::Ordering::Less {
+                Some(RuntimeAdvisory {
+                    runtime: r.application.clone(),
+                    branch: r.branch.clone(),
+                    version: r.version.clone(),
+                    oldest_supported: oldest.to_string(),
+                    used_by: Vec::new(),
+                })
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Compare runtime branches numerically ("22.08" < "23.08", "5.15-22.08" < "5.15-23.08")
+fn compare_branches(a: &str, b: &str) -> std::cmp::Ordering {
+    let numbers = |s: &str| -> Vec<u32> {
+        s.split(|c: char| !c.is_ascii_digit())
+            .filter(|p| !p.is_empty())
+            .filter_map(|p| p.parse().ok())
+            .collect()
+    };
+    numbers(a).cmp(&numbers(b))
+}
+
+async fn app_runtime(application: &str) -> Option<String> {
+    let stdout = run_flatpak(&["info", "--show-runtime", application]).await.ok()?;
+    // "org.gnome.Platform/x86_64/45" -> "org.gnome.Platform/45"
+    let parts: Vec<&str> = stdout.trim().split('/').collect();
+    match parts.as_slice() {
+        [id, _arch, branch] => Some(format!("{}/{}", id, branch)),
+        _ => None,
+    }
+}
+
+async fn run_flatpak(args: &[&str]) -> Result<String> {
+    let output = SystemRunner::default()
+        .output("flatpak", args)
+        .await
+        .context("Failed to run flatpak")?;
+
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "flatpak {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn non_empty(value: Option<&&str>) -> Option<String> {
+    value.map(|v| v.trim()).filter(|v| !v.is_empty()).map(|v| v.to_string())
+}
+
+/// Parse tab-separated `flatpak list --columns=application,name,version,branch,arch,origin,installation`
+pub fn parse_list(output: &str, kind: FlatpakKind) -> Vec<FlatpakRef> {
+    output
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            let cols: Vec<&str> = line.split('\t').collect();
+            Some(FlatpakRef {
+                kind,
+                application: non_empty(cols.first())?,
+                name: non_empty(cols.get(1)).unwrap_or_default(),
+                version: non_empty(cols.get(2)),
+                branch: non_empty(cols.get(3)).unwrap_or_default(),
+                arch: non_empty(cols.get(4)).unwrap_or_default(),
+                origin: non_empty(cols.get(5)).unwrap_or_default(),
+                installation: non_empty(cols.get(6)).unwrap_or_else(|| "system".to_string()),
+            })
+        })
+        .collect()
+}
+
+/// Parse tab-separated `flatpak remote-ls --updates --columns=application,version,branch,origin`
+pub fn parse_updates(output: &str) -> Vec<FlatpakUpdate> {
+    output
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            let cols: Vec<&str> = line.split('\t').collect();
+            Some(FlatpakUpdate {
+                application: non_empty(cols.first())?,
+                version: non_empty(cols.get(1)),
+                branch: non_empty(cols.get(2)).unwrap_or_default(),
+                origin: non_empty(cols.get(3)).unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_list() {
+        let output = "org.mozilla.firefox\tFirefox\t128.0\tstable\tx86_64\tflathub\tsystem\n\
+                      com.valvesoftware.Steam\tSteam\t\tstable\tx86_64\tflathub\tuser\n";
+        let refs = parse_list(output, FlatpakKind::App);
+
+        assert_eq!(refs.len(), 2);
+        assert_eq!(refs[0].full_ref(), "app/org.mozilla.firefox/x86_64/stable");
+        assert_eq!(refs[1].version, None);
+        assert_eq!(refs[1].installation, "user");
+    }
+
+    #[test]
+    fn test_eol_runtimes() {
+        let output = "org.gnome.Platform\tGNOME\t\t43\tx86_64\tflathub\tsystem\n\
+                      org.freedesktop.Platform\tFreedesktop\t23.08.20\t23.08\tx86_64\tflathub\tsystem\n\
+                      org.kde.Platform\tKDE\t\t5.15-22.08\tx86_64\tflathub\tsystem\n";
+        let runtimes = parse_list(output, FlatpakKind::Runtime);
+        let eol: Vec<String> = eol_runtimes(&runtimes).into_iter().map(|a| a.runtime).collect();
+
+        assert_eq!(eol, vec!["org.gnome.Platform", "org.kde.Platform"]);
+    }
+}
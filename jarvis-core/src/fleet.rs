@@ -0,0 +1,420 @@
+//! Fleet status aggregation
+//!
+//! Runs a small read-only probe on every configured host at once and rolls
+//! the results into one report. Probes go through [`ssh_pool`], so a
+//! `--watch` loop keeps one session per host open between rounds. Kept in core so the CLI and the GhostFlow
+//! orchestrator share the same types.
+
+use crate::gpu::{self, GpuReading, GpuThresholds};
+use crate::remote::CommandExecutor;
+use crate::ssh_pool::{self, CommandResult};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// One probe round trip; each section is introduced by a `##name` marker line
+fn probe_script() -> String {
+    format!(
+        "\
+echo '##updates'; pacman -Qu 2>/dev/null | wc -l; \
+echo '##failed'; systemctl list-units --failed --plain --no-legend 2>/dev/null; \
+echo '##disk'; df -P -x tmpfs -x devtmpfs -x efivarfs -x overlay 2>/dev/null; \
+echo '##maintenance'; grep 'starting full system upgrade' /var/log/pacman.log 2>/dev/null | tail -n1; \
+echo '##gpu'; {}
+echo '##nvidia'; command -v nvidia-smi >/dev/null && nvidia-smi {} 2>/dev/null; true",
+        gpu::SYSFS_PROBE,
+        gpu::NVIDIA_SMI_ARGS.join(" ")
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HostHealth {
+    Healthy,
+    Warning,
+    Critical,
+    /// The probe failed or timed out
+    Unreachable,
+}
+
+impl HostHealth {
+    pub fn icon(&self) -> &'static str {
+        match self {
+            HostHealth::Healthy => "✅",
+            HostHealth::Warning => "⚠️",
+            HostHealth::Critical => "🔴",
+            HostHealth::Unreachable => "❌",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiskUsage {
+    pub mount: String,
+    pub used_percent: u8,
+}
+
+/// Probe results for a single host
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostStatus {
+    pub host: String,
+    pub health: HostHealth,
+    pub pending_updates: Option<usize>,
+    pub failed_units: Vec<String>,
+    pub disks: Vec<DiskUsage>,
+    pub last_maintenance: Option<DateTime<Utc>>,
+    /// Discrete GPUs with telemetry; empty on hosts without one
+    #[serde(default)]
+    pub gpus: Vec<GpuReading>,
+    /// Why the host is not healthy
+    pub issues: Vec<String>,
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+impl HostStatus {
+    fn unreachable(host: &str, error: String, duration_ms: u64) -> Self {
+        Self {
+            host: host.to_string(),
+            health: HostHealth::Unreachable,
+            pending_updates: None,
+            failed_units: Vec::new(),
+            disks: Vec::new(),
+            last_maintenance: None,
+            gpus: Vec::new(),
+            issues: Vec::new(),
+            error: Some(error),
+            duration_ms,
+        }
+    }
+
+    /// Fullest filesystem on the host
+    pub fn worst_disk(&self) -> Option<&DiskUsage> {
+        self.disks.iter().max_by_key(|d| d.used_percent)
+    }
+}
+
+/// When a probe result counts as a warning or as critical
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FleetThresholds {
+    pub disk_warning_percent: u8,
+    pub disk_critical_percent: u8,
+    pub pending_updates_warning: usize,
+    pub maintenance_stale_days: i64,
+    pub gpu: GpuThresholds,
+}
+
+impl Default for FleetThresholds {
+    fn default() -> Self {
+        Self {
+            disk_warning_percent: 85,
+            disk_critical_percent: 95,
+            pending_updates_warning: 50,
+            maintenance_stale_days: 30,
+            gpu: GpuThresholds::default(),
+        }
+    }
+}
+
+/// Aggregated status of every probed host
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FleetReport {
+    pub generated_at: DateTime<Utc>,
+    pub hosts: Vec<HostStatus>,
+}
+
+impl FleetReport {
+    /// Worst health across the fleet
+    pub fn overall(&self) -> HostHealth {
+        self.hosts
+            .iter()
+            .map(|h| h.health)
+            .max()
+            .unwrap_or(HostHealth::Healthy)
+    }
+
+    /// Critical or unreachable hosts
+    pub fn has_critical(&self) -> bool {
+        self.overall() >= HostHealth::Critical
+    }
+
+    pub fn render_table(&self) -> String {
+        let mut out = format!(
+            "{:<3} {:<20} {:>8} {:>7} {:>12} {:<16}\n",
+            "", "HOST", "UPDATES", "FAILED", "DISK", "LAST UPGRADE"
+        );
+
+        for host in &self.hosts {
+            if let Some(error) = &host.error {
+                out.push_str(&format!(
+                    "{:<3} {:<20} {}\n",
+                    host.health.icon(),
+                    host.host,
+                    error
+                ));
+                continue;
+            }
+
+            let updates = host
+                .pending_updates
+                .map(|n| n.to_string())
+                .unwrap_or_else(|| "?".to_string());
+            let disk = host
+                .worst_disk()
+                .map(|d| format!("{}% {}", d.used_percent, d.mount))
+                .unwrap_or_else(|| "?".to_string());
+            let maintenance = host
+                .last_maintenance
+                .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_else(|| "never".to_string());
+
+            out.push_str(&format!(
+                "{:<3} {:<20} {:>8} {:>7} {:>12} {:<16}\n",
+                host.health.icon(),
+                host.host,
+                updates,
+                host.failed_units.len(),
+                disk,
+                maintenance
+            ));
+            for gpu in &host.gpus {
+                out.push_str(&format!("      🎮 {}\n", gpu.summary()));
+            }
+            for issue in &host.issues {
+                out.push_str(&format!("      • {}\n", issue));
+            }
+        }
+
+        out
+    }
+}
+
+/// Probe every host, running at most `concurrency` probes at once over the
+/// shared SSH sessions. A host that doesn't answer within `timeout` is
+/// reported as unreachable without holding up the rest.
+pub async fn fleet_status(
+    hosts: Vec<(String, CommandExecutor)>,
+    timeout: Duration,
+    concurrency: usize,
+    thresholds: &FleetThresholds,
+) -> FleetReport {
+    let results = ssh_pool::fan_out(
+        ssh_pool::pool(),
+        &hosts,
+        &[probe_script()],
+        concurrency,
+        timeout,
+    )
+    .await;
+
+    // fan_out keeps config order, so repeated --watch output doesn't shuffle
+    FleetReport {
+        generated_at: Utc::now(),
+        hosts: results
+            .iter()
+            .map(|host| host_status(&host.host, &host.results[0], thresholds))
+            .collect(),
+    }
+}
+
+/// Run the probe set on a single host
+pub async fn probe_host(
+    name: &str,
+    executor: &CommandExecutor,
+    timeout: Duration,
+    thresholds: &FleetThresholds,
+) -> HostStatus {
+    let hosts = [(name.to_string(), executor.clone())];
+    let results = ssh_pool::fan_out(ssh_pool::pool(), &hosts, &[probe_script()], 1, timeout).await;
+    host_status(name, &results[0].results[0], thresholds)
+}
+
+fn host_status(name: &str, probe: &CommandResult, thresholds: &FleetThresholds) -> HostStatus {
+    if let Some(error) = &probe.error {
+        return HostStatus::unreachable(name, error.clone(), probe.duration_ms);
+    }
+    if !probe.success() {
+        let error = format!("probe failed on {}: {}", name, probe.stderr.trim());
+        return HostStatus::unreachable(name, error, probe.duration_ms);
+    }
+    let mut status = parse_probe_output(name, &probe.stdout, thresholds, Utc::now());
+    status.duration_ms = probe.duration_ms;
+    status
+}
+
+/// Parse the sectioned output of the probe script and classify the host
+pub fn parse_probe_output(
+    host: &str,
+    output: &str,
+    thresholds: &FleetThresholds,
+    now: DateTime<Utc>,
+) -> HostStatus {
+    let mut section = "";
+    let mut pending_updates = None;
+    let mut failed_units = Vec::new();
+    let mut disks = Vec::new();
+    let mut last_maintenance = None;
+    let mut gpu_probe = String::new();
+    let mut nvidia_smi = String::new();
+
+    for line in output.lines() {
+        if let Some(name) = line.strip_prefix("##") {
+            section = name.trim();
+            continue;
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        match section {
+            "updates" => pending_updates = line.parse().ok(),
+            "failed" => {
+                if let Some(unit) = line.split_whitespace().next() {
+                    failed_units.push(unit.to_string());
+                }
+            }
+            "disk" => {
+                // Filesystem 1024-blocks Used Available Capacity Mounted-on
+                let cols: Vec<&str> = line.split_whitespace().collect();
+                if cols.len() >= 6 {
+                    if let Ok(used) = cols[4].trim_end_matches('%').parse() {
+                        disks.push(DiskUsage {
+                            mount: cols[5..].join(" "),
+                            used_percent: used,
+                        });
+                    }
+                }
+            }
+            "maintenance" => last_maintenance = parse_pacman_log_time(line),
+            "gpu" => {
+                gpu_probe.push_str(line);
+                gpu_probe.push('\n');
+            }
+            "nvidia" => {
+                nvidia_smi.push_str(line);
+                nvidia_smi.push('\n');
+            }
+            _ => {}
+        }
+    }
+
+    let gpus = gpu::merge(
+        gpu::parse_sysfs_probe(&gpu_probe),
+        Some(nvidia_smi.as_str()).filter(|csv| !csv.is_empty()),
+    );
+
+    let mut health = HostHealth::Healthy;
+    let mut issues = Vec::new();
+
+    for disk in &disks {
+        if disk.used_percent >= thresholds.disk_critical_percent {
+            health = health.max(HostHealth::Critical);
+            issues.push(format!("{} is {}% full", disk.mount, disk.used_percent));
+        } else if disk.used_percent >= thresholds.disk_warning_percent {
+            health = health.max(HostHealth::Warning);
+            issues.push(format!("{} is {}% full", disk.mount, disk.used_percent));
+        }
+    }
+    if !failed_units.is_empty() {
+        health = health.max(HostHealth::Warning);
+        issues.push(format!("failed units: {}", failed_units.join(", ")));
+    }
+    if let Some(count) = pending_updates.filter(|n| *n >= thresholds.pending_updates_warning) {
+        health = health.max(HostHealth::Warning);
+        issues.push(format!("{} pending updates", count));
+    }
+    for gpu in &gpus {
+        let (gpu_health, gpu_issues) = gpu.assess(&thresholds.gpu);
+        health = health.max(gpu_health);
+        issues.extend(gpu_issues);
+    }
+    match last_maintenance {
+        Some(t) if (now - t).num_days() >= thresholds.maintenance_stale_days => {
+            health = health.max(HostHealth::Warning);
+            issues.push(format!("last upgrade {} days ago", (now - t).num_days()));
+        }
+        _ => {}
+    }
+
+    HostStatus {
+        host: host.to_string(),
+        health,
+        pending_updates,
+        failed_units,
+        disks,
+        last_maintenance,
+        gpus,
+        issues,
+        error: None,
+        duration_ms: 0,
+    }
+}
+
+/// `[2024-05-01T10:00:00+0200] [PACMAN] starting full system upgrade`
+pub(crate) fn parse_pacman_log_time(line: &str) -> Option<DateTime<Utc>> {
+    let stamp = line.strip_prefix('[')?.split(']').next()?;
+    DateTime::parse_from_str(stamp, "%Y-%m-%dT%H:%M:%S%z")
+        .map(|t| t.with_timezone(&Utc))
+        .ok()
+        // Older pacman versions logged local time without an offset
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(stamp, "%Y-%m-%d %H:%M")
+                .ok()
+                .map(|t| t.and_utc())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROBE: &str = "##updates\n12\n##failed\nnfs-server.service loaded failed failed NFS server\n\
+        ##disk\nFilesystem 1024-blocks Used Available Capacity Mounted on\n\
+        /dev/nvme0n1p2 100 96 4 96% /\n/dev/sda1 100 40 60 40% /srv/data\n\
+        ##maintenance\n[2024-05-01T10:00:00+0200] [PACMAN] starting full system upgrade\n";
+
+    #[test]
+    fn test_parse_probe_output() {
+        let now = DateTime::parse_from_rfc3339("2024-05-10T00:00:00Z").unwrap().with_timezone(&Utc);
+        let status = parse_probe_output("nas", PROBE, &FleetThresholds::default(), now);
+
+        assert_eq!(status.pending_updates, Some(12));
+        assert_eq!(status.failed_units, vec!["nfs-server.service"]);
+        assert_eq!(status.worst_disk().unwrap().mount, "/");
+        assert_eq!(status.health, HostHealth::Critical);
+        assert_eq!(
+            status.last_maintenance.unwrap().to_rfc3339(),
+            "2024-05-01T08:00:00+00:00"
+        );
+        assert!(status.gpus.is_empty());
+    }
+
+    #[test]
+    fn test_parse_probe_output_gpu_sections() {
+        let probe = "##updates\n0\n##gpu\n@card0\nvendor 0x1002\ndevice 0x744c\ntemp1_input 84000\n\
+            @card1\nvendor 0x10de\ndevice 0x2684\n\
+            ##nvidia\n0, NVIDIA GeForce RTX 4090, 55, 10, 1024, 24564, 60.00, 450.00\n";
+        let status = parse_probe_output("ws", probe, &FleetThresholds::default(), Utc::now());
+
+        let ids: Vec<&str> = status.gpus.iter().map(|g| g.id.as_str()).collect();
+        assert_eq!(ids, vec!["card0", "nvidia0"]);
+        assert_eq!(status.health, HostHealth::Warning);
+        assert_eq!(status.issues, vec!["card0 is at 84°C"]);
+    }
+
+    #[test]
+    fn test_report_overall_health() {
+        let now = Utc::now();
+        let healthy = parse_probe_output("a", "##updates\n0\n", &FleetThresholds::default(), now);
+        assert_eq!(healthy.health, HostHealth::Healthy);
+
+        let report = FleetReport {
+            generated_at: now,
+            hosts: vec![healthy, HostStatus::unreachable("b", "timed out".to_string(), 0)],
+        };
+        assert_eq!(report.overall(), HostHealth::Unreachable);
+        assert!(report.has_critical());
+    }
+}
@@ -0,0 +1,360 @@
+//! Vendor-neutral GPU telemetry
+//!
+//! AMD (and other in-tree drivers) expose temperature, power, utilisation and
+//! VRAM through sysfs under `/sys/class/drm/card*/device`; the proprietary
+//! NVIDIA driver does not, so NVIDIA cards are read from `nvidia-smi` instead.
+//! Cards that expose no telemetry at all, typically integrated graphics, are
+//! left out, so a machine without a discrete GPU reports an empty list.
+
+use crate::fleet::HostHealth;
+use crate::remote::CommandExecutor;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// Prints an `@cardN` line per DRM card followed by `file value` lines for
+/// the telemetry files it exposes
+pub const SYSFS_PROBE: &str = r#"for dev in /sys/class/drm/card[0-9]*/device; do
+  card=${dev%/device}; card=${card##*/}
+  case $card in *-*) continue ;; esac
+  [ -r "$dev/vendor" ] || continue
+  echo "@$card"
+  for f in vendor device gpu_busy_percent mem_info_vram_used mem_info_vram_total; do
+    [ -r "$dev/$f" ] && echo "$f $(cat "$dev/$f" 2>/dev/null)"
+  done
+  for f in "$dev"/hwmon/hwmon*/temp1_input "$dev"/hwmon/hwmon*/power1_average "$dev"/hwmon/hwmon*/power1_input "$dev"/hwmon/hwmon*/power1_cap; do
+    [ -r "$f" ] && echo "${f##*/} $(cat "$f" 2>/dev/null)"
+  done
+done
+true"#;
+
+/// `nvidia-smi` arguments matching [`parse_nvidia_smi`]
+pub const NVIDIA_SMI_ARGS: &[&str] = &[
+    "--query-gpu=index,name,temperature.gpu,utilization.gpu,memory.used,memory.total,power.draw,power.limit",
+    "--format=csv,noheader,nounits",
+];
+
+const MIB: f32 = 1024.0 * 1024.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GpuVendor {
+    Amd,
+    Nvidia,
+    Intel,
+    Other,
+}
+
+impl GpuVendor {
+    /// From a PCI vendor id such as `0x1002`
+    pub fn from_pci_id(id: &str) -> Self {
+        match id.trim().to_lowercase().as_str() {
+            "0x1002" => GpuVendor::Amd,
+            "0x10de" => GpuVendor::Nvidia,
+            "0x8086" => GpuVendor::Intel,
+            _ => GpuVendor::Other,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            GpuVendor::Amd => "AMD",
+            GpuVendor::Nvidia => "NVIDIA",
+            GpuVendor::Intel => "Intel",
+            GpuVendor::Other => "GPU",
+        }
+    }
+}
+
+/// One GPU's readings; a field is `None` when the driver doesn't expose it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GpuReading {
+    /// `card0` for sysfs cards, `nvidia0` for cards read via nvidia-smi
+    pub id: String,
+    pub vendor: GpuVendor,
+    pub name: String,
+    pub temperature_c: Option<f32>,
+    pub busy_percent: Option<f32>,
+    pub vram_used_mib: Option<f32>,
+    pub vram_total_mib: Option<f32>,
+    pub power_w: Option<f32>,
+    pub power_limit_w: Option<f32>,
+}
+
+impl GpuReading {
+    fn new(id: &str, vendor: GpuVendor, name: String) -> Self {
+        Self {
+            id: id.to_string(),
+            vendor,
+            name,
+            temperature_c: None,
+            busy_percent: None,
+            vram_used_mib: None,
+            vram_total_mib: None,
+            power_w: None,
+            power_limit_w: None,
+        }
+    }
+
+    pub fn vram_percent(&self) -> Option<f32> {
+        match (self.vram_used_mib, self.vram_total_mib) {
+            (Some(used), Some(total)) if total > 0.0 => Some(used / total * 100.0),
+            _ => None,
+        }
+    }
+
+    fn has_telemetry(&self) -> bool {
+        self.temperature_c.is_some()
+            || self.busy_percent.is_some()
+            || self.vram_total_mib.is_some()
+            || self.power_w.is_some()
+    }
+
+    /// Health against `thresholds`, with a description of each problem
+    pub fn assess(&self, thresholds: &GpuThresholds) -> (HostHealth, Vec<String>) {
+        let mut health = HostHealth::Healthy;
+        let mut issues = Vec::new();
+
+        if let Some(temp) = self.temperature_c {
+            if temp >= thresholds.temperature_critical_c {
+                health = health.max(HostHealth::Critical);
+                issues.push(format!("{} is at {:.0}°C", self.id, temp));
+            } else if temp >= thresholds.temperature_warning_c {
+                health = health.max(HostHealth::Warning);
+                issues.push(format!("{} is at {:.0}°C", self.id, temp));
+            }
+        }
+        if let Some(vram) = self.vram_percent() {
+            if vram >= thresholds.vram_critical_percent {
+                health = health.max(HostHealth::Critical);
+                issues.push(format!("{} VRAM is {:.0}% used", self.id, vram));
+            } else if vram >= thresholds.vram_warning_percent {
+                health = health.max(HostHealth::Warning);
+                issues.push(format!("{} VRAM is {:.0}% used", self.id, vram));
+            }
+        }
+        if let (Some(power), Some(limit)) = (self.power_w, self.power_limit_w) {
+            // Sustained draw at the cap means the card is power-throttling
+            if limit > 0.0 && power >= limit * 0.98 {
+                health = health.max(HostHealth::Warning);
+                issues.push(format!(
+                    "{} draws {:.0} W against a {:.0} W limit",
+                    self.id, power, limit
+                ));
+            }
+        }
+
+        (health, issues)
+    }
+
+    /// One line: `card0 AMD 0x73bf  65°C  30% busy  VRAM 4.1/16.0 GiB  120/250 W`
+    pub fn summary(&self) -> String {
+        let mut parts = vec![format!("{} {}", self.id, self.name)];
+        if let Some(temp) = self.temperature_c {
+            parts.push(format!("{:.0}°C", temp));
+        }
+        if let Some(busy) = self.busy_percent {
+            parts.push(format!("{:.0}% busy", busy));
+        }
+        if let (Some(used), Some(total)) = (self.vram_used_mib, self.vram_total_mib) {
+            parts.push(format!(
+                "VRAM {:.1}/{:.1} GiB",
+                used / 1024.0,
+                total / 1024.0
+            ));
+        }
+        match (self.power_w, self.power_limit_w) {
+            (Some(power), Some(limit)) => parts.push(format!("{:.0}/{:.0} W", power, limit)),
+            (Some(power), None) => parts.push(format!("{:.0} W", power)),
+            _ => {}
+        }
+        parts.join("  ")
+    }
+}
+
+/// When GPU readings count as a warning or as critical
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GpuThresholds {
+    pub temperature_warning_c: f32,
+    pub temperature_critical_c: f32,
+    pub vram_warning_percent: f32,
+    pub vram_critical_percent: f32,
+}
+
+impl Default for GpuThresholds {
+    fn default() -> Self {
+        Self {
+            temperature_warning_c: 80.0,
+            temperature_critical_c: 90.0,
+            vram_warning_percent: 90.0,
+            vram_critical_percent: 98.0,
+        }
+    }
+}
+
+/// Read every GPU on the executor's host. NVIDIA cards are only reported
+/// when `nvidia-smi` is installed.
+pub async fn collect(executor: &CommandExecutor) -> Result<Vec<GpuReading>> {
+    let output = executor.run_stdout("sh", &["-c", SYSFS_PROBE]).await?;
+    let cards = parse_sysfs_probe(&output);
+
+    let nvidia = if cards.iter().any(|c| c.vendor == GpuVendor::Nvidia) {
+        match executor.run_stdout("nvidia-smi", NVIDIA_SMI_ARGS).await {
+            Ok(csv) => Some(csv),
+            Err(e) => {
+                tracing::debug!("nvidia-smi unavailable: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    Ok(merge(cards, nvidia.as_deref()))
+}
+
+/// Sysfs cards that expose telemetry, with NVIDIA cards replaced by the
+/// `nvidia-smi` readings when there are any
+pub fn merge(cards: Vec<GpuReading>, nvidia_smi: Option<&str>) -> Vec<GpuReading> {
+    let mut gpus: Vec<GpuReading> = cards
+        .into_iter()
+        .filter(|c| c.vendor != GpuVendor::Nvidia && c.has_telemetry())
+        .collect();
+    if let Some(csv) = nvidia_smi {
+        gpus.extend(parse_nvidia_smi(csv));
+    }
+    gpus
+}
+
+/// Every card in [`SYSFS_PROBE`] output, including ones without telemetry
+pub fn parse_sysfs_probe(output: &str) -> Vec<GpuReading> {
+    let mut cards: Vec<GpuReading> = Vec::new();
+
+    for line in output.lines().map(str::trim) {
+        if let Some(card) = line.strip_prefix('@') {
+            cards.push(GpuReading::new(card, GpuVendor::Other, String::new()));
+            continue;
+        }
+        let (Some(gpu), Some((key, value))) = (cards.last_mut(), line.split_once(' ')) else {
+            continue;
+        };
+        let number = value.trim().parse::<f32>().ok();
+
+        match key {
+            "vendor" => gpu.vendor = GpuVendor::from_pci_id(value),
+            "device" => gpu.name = value.trim().to_string(),
+            "gpu_busy_percent" => gpu.busy_percent = number,
+            "mem_info_vram_used" => gpu.vram_used_mib = number.map(|b| b / MIB),
+            "mem_info_vram_total" => gpu.vram_total_mib = number.map(|b| b / MIB),
+            "temp1_input" => gpu.temperature_c = number.map(|m| m / 1000.0),
+            // power1_average (amdgpu) and power1_input (newer kernels) are alternatives
+            "power1_average" | "power1_input" => {
+                gpu.power_w = gpu.power_w.or(number.map(|uw| uw / 1_000_000.0))
+            }
+            "power1_cap" => gpu.power_limit_w = number.map(|uw| uw / 1_000_000.0),
+            _ => {}
+        }
+    }
+
+    for gpu in &mut cards {
+        gpu.name = format!("{} {}", gpu.vendor.label(), gpu.name)
+            .trim()
+            .to_string();
+    }
+    cards
+}
+
+/// Rows of `nvidia-smi` CSV output queried with [`NVIDIA_SMI_ARGS`]
+pub fn parse_nvidia_smi(csv: &str) -> Vec<GpuReading> {
+    csv.lines()
+        .filter_map(|line| {
+            let cols: Vec<&str> = line.split(',').map(str::trim).collect();
+            if cols.len() < 8 {
+                return None;
+            }
+            // Unsupported fields read "[N/A]" or "[Not Supported]"
+            let number = |i: usize| cols[i].parse::<f32>().ok();
+            let mut gpu = GpuReading::new(
+                &format!("nvidia{}", cols[0]),
+                GpuVendor::Nvidia,
+                cols[1].to_string(),
+            );
+            gpu.temperature_c = number(2);
+            gpu.busy_percent = number(3);
+            gpu.vram_used_mib = number(4);
+            gpu.vram_total_mib = number(5);
+            gpu.power_w = number(6);
+            gpu.power_limit_w = number(7);
+            Some(gpu)
+        })
+        .collect()
+}
+
+/// Text section for status probes; empty when there are no GPUs
+pub fn render(gpus: &[GpuReading], thresholds: &GpuThresholds) -> String {
+    let mut out = String::new();
+    for gpu in gpus {
+        let (health, issues) = gpu.assess(thresholds);
+        out.push_str(&format!("{} {}\n", health.icon(), gpu.summary()));
+        for issue in issues {
+            out.push_str(&format!("    • {}\n", issue));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AMD_PROBE: &str = "@card0\nvendor 0x8086\ndevice 0x4680\n\
+        @card1\nvendor 0x1002\ndevice 0x73bf\ngpu_busy_percent 97\n\
+        mem_info_vram_used 4294967296\nmem_info_vram_total 17163091968\n\
+        temp1_input 91000\npower1_average 255000000\npower1_cap 255000000\n";
+
+    #[test]
+    fn test_parse_sysfs_probe() {
+        let cards = parse_sysfs_probe(AMD_PROBE);
+        assert_eq!(cards.len(), 2);
+        assert!(!cards[0].has_telemetry());
+
+        let amd = &cards[1];
+        assert_eq!(amd.vendor, GpuVendor::Amd);
+        assert_eq!(amd.name, "AMD 0x73bf");
+        assert_eq!(amd.temperature_c, Some(91.0));
+        assert_eq!(amd.busy_percent, Some(97.0));
+        assert_eq!(amd.vram_used_mib, Some(4096.0));
+        assert_eq!(amd.power_w, Some(255.0));
+    }
+
+    #[test]
+    fn test_assess_flags_heat_and_power_throttling() {
+        let amd = parse_sysfs_probe(AMD_PROBE).remove(1);
+        let (health, issues) = amd.assess(&GpuThresholds::default());
+
+        assert_eq!(health, HostHealth::Critical);
+        assert_eq!(
+            issues,
+            vec![
+                "card1 is at 91°C",
+                "card1 draws 255 W against a 255 W limit"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_nvidia_smi() {
+        let csv = "0, NVIDIA GeForce RTX 4090, 64, 35, 2048, 24564, 120.50, 450.00\n\
+                   1, Tesla T4, 40, [N/A], 0, 15360, [N/A], 70.00\n";
+        let gpus = parse_nvidia_smi(csv);
+
+        assert_eq!(gpus.len(), 2);
+        assert_eq!(gpus[0].id, "nvidia0");
+        assert_eq!(gpus[0].name, "NVIDIA GeForce RTX 4090");
+        assert_eq!(gpus[0].power_w, Some(120.5));
+        assert_eq!(gpus[1].busy_percent, None);
+        assert_eq!(
+            gpus[0].assess(&GpuThresholds::default()).0,
+            HostHealth::Healthy
+        );
+    }
+}
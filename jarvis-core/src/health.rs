@@ -2,7 +2,7 @@ use anyhow::Result;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use tokio::process::Command;
+use crate::exec::{CommandRunner, OutputText, SystemRunner};
 use crate::types::Environment;
 // use jarvis_shell::Environment; // Removed to avoid circular dependency
 
@@ -498,66 +498,32 @@ impl HealthMonitor {
     }
 
     async fn get_memory_usage(&self) -> Result<f32> {
-        let output = Command::new("free")
-            .arg("-m")
-            .output()
-            .await?;
-        
-        let stdout = String::from_utf8(output.stdout)?;
-        for line in stdout.lines() {
-            if line.starts_with("Mem:") {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() >= 3 {
-                    let total: f32 = parts[1].parse().unwrap_or(1.0);
-                    let used: f32 = parts[2].parse().unwrap_or(0.0);
-                    return Ok((used / total) * 100.0);
-                }
-            }
-        }
-        Ok(0.0)
+        // Field names in /proc/meminfo are fixed; `free` translates its "Mem:" row
+        let meminfo = tokio::fs::read_to_string("/proc/meminfo").await?;
+        Ok(parse_meminfo_usage(&meminfo).unwrap_or(0.0))
     }
 
     async fn get_disk_usage(&self) -> Result<f32> {
-        let output = Command::new("df")
-            .arg("-h")
-            .arg("/")
-            .output()
+        let output = SystemRunner::default()
+            .output("df", &["--output=pcent", "/"])
             .await?;
-        
-        let stdout = String::from_utf8(output.stdout)?;
-        for line in stdout.lines().skip(1) {
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 5 {
-                let usage_str = parts[4].trim_end_matches('%');
-                if let Ok(usage) = usage_str.parse::<f32>() {
-                    return Ok(usage);
-                }
-            }
-        }
-        Ok(0.0)
+        Ok(parse_df_percent(&OutputText::decode(&output).stdout).unwrap_or(0.0))
     }
 
     async fn check_network_connectivity(&self) -> Result<bool> {
-        let output = Command::new("ping")
-            .arg("-c")
-            .arg("1")
-            .arg("-W")
-            .arg("2")
-            .arg("1.1.1.1")
-            .output()
+        let output = SystemRunner::default()
+            .output("ping", &["-c", "1", "-W", "2", "1.1.1.1"])
             .await?;
         
         Ok(output.status.success())
     }
 
     async fn get_service_status(&self, service_name: &str) -> Result<ServiceHealth> {
-        let output = Command::new("systemctl")
-            .arg("is-active")
-            .arg(service_name)
-            .output()
+        let output = SystemRunner::default()
+            .output("systemctl", &["is-active", service_name])
             .await?;
         
-        let status_str = String::from_utf8(output.stdout)?.trim().to_string();
+        let status_str = OutputText::decode(&output).stdout.trim().to_string();
         let status = match status_str.as_str() {
             "active" => ServiceStatus::Active,
             "inactive" => ServiceStatus::Inactive,
@@ -576,10 +542,8 @@ impl HealthMonitor {
     }
 
     async fn restart_service(&self, service_name: &str) -> Result<()> {
-        let output = Command::new("systemctl")
-            .arg("restart")
-            .arg(service_name)
-            .output()
+        let output = SystemRunner::default()
+            .output("systemctl", &["restart", service_name])
             .await?;
         
         if output.status.success() {
@@ -611,4 +575,48 @@ impl HealthMonitor {
             None
         }
     }
-}
\ No newline at end of file
+}
+
+/// Percent of memory in use from `/proc/meminfo`, counting reclaimable cache as free
+fn parse_meminfo_usage(meminfo: &str) -> Option<f32> {
+    let field = |name: &str| {
+        meminfo
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .and_then(|rest| rest.split_whitespace().next())
+            .and_then(|kib| kib.parse::<f32>().ok())
+    };
+    let total = field("MemTotal").filter(|t| *t > 0.0)?;
+    let available = field("MemAvailable")?;
+    Some((total - available) / total * 100.0)
+}
+
+/// Usage from `df --output=pcent`; the header is translated, the value is not
+fn parse_df_percent(stdout: &str) -> Option<f32> {
+    stdout
+        .lines()
+        .skip(1)
+        .find_map(|line| line.trim().trim_end_matches('%').parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_df_percent_ignores_translated_header() {
+        assert_eq!(parse_df_percent("Use%\n 42%\n"), Some(42.0));
+        // Captured with LANG=de_DE.UTF-8
+        assert_eq!(parse_df_percent("Verw%\n  87%\n"), Some(87.0));
+        assert_eq!(parse_df_percent("Use%\n"), None);
+    }
+
+    #[test]
+    fn test_parse_meminfo_usage() {
+        let meminfo = "MemTotal:       16000000 kB\n\
+                       MemFree:         2000000 kB\n\
+                       MemAvailable:    4000000 kB\n";
+        assert_eq!(parse_meminfo_usage(meminfo), Some(75.0));
+        assert_eq!(parse_meminfo_usage("MemFree: 1 kB\n"), None);
+    }
+}
@@ -0,0 +1,402 @@
+//! User-defined health checks
+//!
+//! Site-specific things Jarvis can't know about, such as a ZFS pool on a NAS
+//! mount or a UPS, are checked by executables listed under
+//! `[[health_checks]]`. Each must print one JSON document before its timeout:
+//!
+//! ```json
+//! {"status": "ok|warn|critical", "message": "...", "metrics": {"free_tib": 3.2}}
+//! ```
+//!
+//! `metrics` is optional; its values are recorded as `check.<key>` samples
+//! labeled with the check's name. The exit status is ignored when the output
+//! honours the contract, so plugins that exit 2 on critical work unchanged.
+//! A check that times out, prints anything else, or exits nonzero without a
+//! result is a warning about the check itself, not the system.
+
+use crate::exec::{CommandRunner, RunOptions};
+use crate::fleet::HostHealth;
+use crate::metrics::MetricSample;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+/// Most a check may print; longer output breaks the contract
+const MAX_OUTPUT_BYTES: usize = 64 * 1024;
+
+/// One `[[health_checks]]` entry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HealthCheckConfig {
+    /// Shown in health output and alerts, e.g. "zfs-tank"
+    pub name: String,
+    /// Absolute path of the executable
+    pub command: PathBuf,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_timeout_secs() -> u64 {
+    10
+}
+
+impl HealthCheckConfig {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs.max(1))
+    }
+
+    /// Why the check can't run as configured, before trying it
+    pub fn problem(&self) -> Option<String> {
+        if self.name.trim().is_empty() {
+            return Some("has no name".to_string());
+        }
+        if !self.command.is_absolute() {
+            return Some(format!(
+                "{} is not an absolute path",
+                self.command.display()
+            ));
+        }
+        match std::fs::metadata(&self.command) {
+            Err(e) => Some(format!("{}: {}", self.command.display(), e)),
+            Ok(meta) if !meta.is_file() => {
+                Some(format!("{} is not a file", self.command.display()))
+            }
+            Ok(meta) if meta.permissions().mode() & 0o111 == 0 => {
+                Some(format!("{} is not executable", self.command.display()))
+            }
+            Ok(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    Warn,
+    Critical,
+}
+
+/// The document a check prints
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+struct CheckReport {
+    status: CheckStatus,
+    message: String,
+    #[serde(default)]
+    metrics: BTreeMap<String, f64>,
+}
+
+/// The result of one run of a check
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CheckOutcome {
+    pub name: String,
+    /// What the check reported; `None` when it broke its contract
+    pub status: Option<CheckStatus>,
+    pub message: String,
+    #[serde(default)]
+    pub metrics: BTreeMap<String, f64>,
+    /// Why the check's output couldn't be used: a timeout, a failed exit,
+    /// or output that isn't a result
+    pub check_error: Option<String>,
+    pub duration_ms: u64,
+    pub checked_at: DateTime<Utc>,
+}
+
+impl CheckOutcome {
+    /// A broken check is a warning, whatever it was watching
+    pub fn health(&self) -> HostHealth {
+        match self.status {
+            Some(CheckStatus::Ok) => HostHealth::Healthy,
+            Some(CheckStatus::Warn) | None => HostHealth::Warning,
+            Some(CheckStatus::Critical) => HostHealth::Critical,
+        }
+    }
+
+    /// e.g. "zfs-tank: pool DEGRADED, 1 faulted disk"
+    pub fn describe(&self) -> String {
+        match &self.check_error {
+            Some(error) => format!("check {} is broken: {}", self.name, error),
+            None => format!("{}: {}", self.name, self.message),
+        }
+    }
+}
+
+/// Results of the last round of checks, shared between the daemon that runs
+/// them and the health output that reports them
+pub type LatestOutcomes = Arc<RwLock<Vec<CheckOutcome>>>;
+
+/// Run `check` once; never fails, since a broken check is itself a result
+pub async fn run_check(runner: &dyn CommandRunner, check: &HealthCheckConfig) -> CheckOutcome {
+    let started = Instant::now();
+    let program = check.command.to_string_lossy();
+    let args: Vec<&str> = check.args.iter().map(String::as_str).collect();
+    let options = RunOptions::default().with_timeout(check.timeout());
+
+    // The runner enforces the timeout too; this covers runners that don't
+    let result =
+        match tokio::time::timeout(check.timeout(), runner.run(&program, &args, &options)).await {
+            Err(_) => Err(format!("timed out after {}s", check.timeout().as_secs())),
+            Ok(Err(e)) => Err(e.to_string()),
+            Ok(Ok(output)) => parse_output(&output),
+        };
+
+    let mut outcome = CheckOutcome {
+        name: check.name.clone(),
+        status: None,
+        message: String::new(),
+        metrics: BTreeMap::new(),
+        check_error: None,
+        duration_ms: started.elapsed().as_millis() as u64,
+        checked_at: Utc::now(),
+    };
+    match result {
+        Ok(report) => {
+            outcome.status = Some(report.status);
+            outcome.message = report.message;
+            outcome.metrics = report.metrics;
+        }
+        Err(error) => {
+            tracing::warn!("Health check {} is broken: {}", check.name, error);
+            outcome.check_error = Some(error);
+        }
+    }
+    outcome
+}
+
+/// Every check concurrently, in configured order
+pub async fn run_all(
+    runner: &dyn CommandRunner,
+    checks: &[HealthCheckConfig],
+) -> Vec<CheckOutcome> {
+    futures::future::join_all(checks.iter().map(|check| run_check(runner, check))).await
+}
+
+fn parse_output(output: &std::process::Output) -> Result<CheckReport, String> {
+    let failed_exit = || {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let mut reason = match output.status.code() {
+            Some(code) => format!("exited with status {}", code),
+            None => "was killed by a signal".to_string(),
+        };
+        if let Some(line) = stderr.lines().find(|line| !line.trim().is_empty()) {
+            reason.push_str(&format!(": {}", line.trim()));
+        }
+        reason
+    };
+
+    if output.stdout.len() > MAX_OUTPUT_BYTES {
+        return Err(format!("printed more than {} bytes", MAX_OUTPUT_BYTES));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if stdout.trim().is_empty() {
+        return Err(if output.status.success() {
+            "printed no result".to_string()
+        } else {
+            failed_exit()
+        });
+    }
+    serde_json::from_str(stdout.trim()).map_err(|e| {
+        if output.status.success() {
+            format!("printed something other than a result: {}", e)
+        } else {
+            failed_exit()
+        }
+    })
+}
+
+/// Labeled samples for the metrics store: each reported metric, plus the
+/// status as 0 (ok), 1 (warn), or 2 (critical)
+pub fn metrics(outcomes: &[CheckOutcome], now: DateTime<Utc>) -> Vec<MetricSample> {
+    let mut samples = Vec::new();
+    for outcome in outcomes {
+        let Some(status) = outcome.status else {
+            continue;
+        };
+        let level = match status {
+            CheckStatus::Ok => 0.0,
+            CheckStatus::Warn => 1.0,
+            CheckStatus::Critical => 2.0,
+        };
+        samples
+            .push(MetricSample::new("check.status", level, now).with_label("check", &outcome.name));
+        for (key, value) in &outcome.metrics {
+            samples.push(
+                MetricSample::new(&format!("check.{}", key), *value, now)
+                    .with_label("check", &outcome.name),
+            );
+        }
+    }
+    samples
+}
+
+/// Health of every check by id ("check <name>"), with what it reported
+pub fn assess(outcomes: &[CheckOutcome]) -> Vec<(String, HostHealth, String)> {
+    outcomes
+        .iter()
+        .map(|outcome| {
+            (
+                format!("check {}", outcome.name),
+                outcome.health(),
+                outcome.describe(),
+            )
+        })
+        .collect()
+}
+
+/// One line per check
+pub fn render(outcomes: &[CheckOutcome]) -> String {
+    let mut out = String::new();
+    for outcome in outcomes {
+        out.push_str(&format!(
+            "{} {}\n",
+            outcome.health().icon(),
+            outcome.describe()
+        ));
+        if !outcome.metrics.is_empty() {
+            let metrics: Vec<String> = outcome
+                .metrics
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect();
+            out.push_str(&format!("   {}\n", metrics.join(" ")));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::{RecordingRunner, fake_output};
+    use async_trait::async_trait;
+
+    fn check(name: &str) -> HealthCheckConfig {
+        HealthCheckConfig {
+            name: name.to_string(),
+            command: PathBuf::from(format!("/usr/local/lib/jarvis/{}", name)),
+            args: vec!["--json".to_string()],
+            timeout_secs: 1,
+        }
+    }
+
+    async fn outcome_for(stdout: &str, exit: i32) -> CheckOutcome {
+        let runner = RecordingRunner::new().respond_with(
+            "/usr/local/lib/jarvis/ups",
+            fake_output(exit, stdout, "upsc: connection refused\n"),
+        );
+        let outcome = run_check(&runner, &check("ups")).await;
+        assert_eq!(runner.calls(), vec!["/usr/local/lib/jarvis/ups --json"]);
+        outcome
+    }
+
+    #[tokio::test]
+    async fn test_valid_result_is_taken_as_reported() {
+        let outcome = outcome_for(
+            r#"{"status": "warn", "message": "on battery", "metrics": {"charge_percent": 87.5, "load_percent": 31}}"#,
+            0,
+        )
+        .await;
+
+        assert_eq!(outcome.status, Some(CheckStatus::Warn));
+        assert_eq!(outcome.health(), HostHealth::Warning);
+        assert_eq!(outcome.describe(), "ups: on battery");
+        assert!(outcome.check_error.is_none());
+
+        let samples = metrics(&[outcome], Utc::now());
+        let names: Vec<&str> = samples.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["check.status", "check.charge_percent", "check.load_percent"]
+        );
+        assert_eq!(samples[1].value, 87.5);
+        assert_eq!(samples[1].labels["check"], "ups");
+    }
+
+    #[tokio::test]
+    async fn test_malformed_output_is_a_broken_check() {
+        for stdout in [
+            "UPS OK - on line power",
+            r#"{"status": "fine", "message": "on line power"}"#,
+            r#"{"status": "ok"}"#,
+            r#"{"status": "ok", "message": "x", "metrics": {"charge": "full"}}"#,
+            "",
+        ] {
+            let outcome = outcome_for(stdout, 0).await;
+            assert_eq!(outcome.status, None, "{}", stdout);
+            assert_eq!(outcome.health(), HostHealth::Warning);
+            assert!(outcome.describe().starts_with("check ups is broken"));
+            assert!(metrics(&[outcome], Utc::now()).is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_nonzero_exit_keeps_a_valid_result() {
+        // Nagios-style: exit 2 with a proper result is a critical finding
+        let outcome = outcome_for(r#"{"status": "critical", "message": "battery low"}"#, 2).await;
+        assert_eq!(outcome.health(), HostHealth::Critical);
+        assert!(outcome.check_error.is_none());
+
+        // Without one, the check failed
+        let outcome = outcome_for("", 1).await;
+        assert_eq!(outcome.status, None);
+        assert_eq!(
+            outcome.check_error.as_deref(),
+            Some("exited with status 1: upsc: connection refused")
+        );
+    }
+
+    /// A runner whose commands never finish
+    #[derive(Debug)]
+    struct HangingRunner;
+
+    #[async_trait]
+    impl CommandRunner for HangingRunner {
+        async fn run(
+            &self,
+            _program: &str,
+            _args: &[&str],
+            _options: &RunOptions,
+        ) -> anyhow::Result<std::process::Output> {
+            std::future::pending().await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_timeout_is_a_broken_check() {
+        let outcomes = run_all(&HangingRunner, &[check("zfs-tank")]).await;
+        assert_eq!(
+            outcomes[0].check_error.as_deref(),
+            Some("timed out after 1s")
+        );
+        assert_eq!(
+            assess(&outcomes),
+            vec![(
+                "check zfs-tank".to_string(),
+                HostHealth::Warning,
+                "check zfs-tank is broken: timed out after 1s".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_config_problems() {
+        assert!(check("ups").problem().unwrap().contains("No such file"));
+        let mut relative = check("ups");
+        relative.command = PathBuf::from("ups.sh");
+        assert_eq!(
+            relative.problem().unwrap(),
+            "ups.sh is not an absolute path"
+        );
+
+        let config: HealthCheckConfig =
+            toml::from_str("name = \"ups\"\ncommand = \"/bin/sh\"").unwrap();
+        assert_eq!(config.timeout_secs, 10);
+        assert_eq!(config.problem(), None);
+    }
+}
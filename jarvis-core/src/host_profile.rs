@@ -0,0 +1,559 @@
+//! Host profiles: what the model should know about a machine up front
+//!
+//! Without one, every fresh conversation starts by re-explaining the system.
+//! A profile is the LLM's summary of probe output and Jarvis's own history on
+//! a host (hardware, distro, key services, Docker stacks, notable
+//! configuration, and recurring issues), kept to a few hundred tokens so
+//! `explain`, `diagnose`, and `fix` can prepend it to every prompt.
+//!
+//! jarvisd regenerates the local profile daily and `jarvis profile
+//! regenerate` does it on demand. Each host keeps its earlier versions, and
+//! each version records which lines changed from the one before, so it is
+//! visible when Jarvis's picture of a machine changed.
+
+use crate::llm::LLMRouter;
+use crate::memory::MemoryStore;
+use crate::remote::CommandExecutor;
+use crate::types::AuditStatus;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Lines of any one probe's output passed to the summarizer
+const MAX_PROBE_LINES: usize = 40;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HostProfileConfig {
+    /// Prepend the profile to prompts and let jarvisd regenerate it
+    pub enabled: bool,
+    /// How old the local profile may get before jarvisd regenerates it
+    pub regenerate_hours: u64,
+    /// Length the summary is asked to stay within
+    pub max_words: usize,
+    /// Versions kept per host, including the current one
+    pub history: usize,
+}
+
+impl Default for HostProfileConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            regenerate_hours: 24,
+            max_words: 200,
+            history: 30,
+        }
+    }
+}
+
+/// One generated summary
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProfileVersion {
+    pub version: u32,
+    pub generated_at: DateTime<Utc>,
+    /// Last regeneration that produced this same summary
+    pub confirmed_at: DateTime<Utc>,
+    pub model: String,
+    /// `- ` bullet lines
+    pub summary: String,
+    /// Lines not in the previous version
+    #[serde(default)]
+    pub added: Vec<String>,
+    /// Lines of the previous version that are gone
+    #[serde(default)]
+    pub removed: Vec<String>,
+}
+
+impl ProfileVersion {
+    pub fn changed(&self) -> bool {
+        !self.added.is_empty() || !self.removed.is_empty()
+    }
+
+    /// `+`/`-` lines of what changed from the previous version
+    pub fn render_changes(&self) -> String {
+        let mut out = String::new();
+        for line in &self.removed {
+            out.push_str(&format!("  - {}\n", line.trim_start_matches("- ")));
+        }
+        for line in &self.added {
+            out.push_str(&format!("  + {}\n", line.trim_start_matches("- ")));
+        }
+        out
+    }
+}
+
+/// Every stored version of one host's profile, oldest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HostProfile {
+    pub host: String,
+    pub versions: Vec<ProfileVersion>,
+}
+
+impl HostProfile {
+    fn document_key(host: &str) -> String {
+        format!("host_profile:{}", host)
+    }
+
+    pub async fn load(memory: &MemoryStore, host: &str) -> Result<Self> {
+        match memory.get_document(&Self::document_key(host)).await? {
+            Some(data) => serde_json::from_str(&data)
+                .with_context(|| format!("Corrupt host profile for {}", host)),
+            None => Ok(Self {
+                host: host.to_string(),
+                versions: Vec::new(),
+            }),
+        }
+    }
+
+    pub async fn save(&self, memory: &MemoryStore) -> Result<()> {
+        memory
+            .store_document(
+                &Self::document_key(&self.host),
+                &serde_json::to_string(self)?,
+            )
+            .await
+    }
+
+    pub fn latest(&self) -> Option<&ProfileVersion> {
+        self.versions.last()
+    }
+
+    /// Whether the profile is missing or older than `regenerate_hours`
+    pub fn is_due(&self, config: &HostProfileConfig, now: DateTime<Utc>) -> bool {
+        self.latest().is_none_or(|latest| {
+            now - latest.confirmed_at >= chrono::Duration::hours(config.regenerate_hours as i64)
+        })
+    }
+
+    /// Store `summary` as the current profile. An unchanged summary only
+    /// refreshes `confirmed_at`; otherwise it becomes a new version, and the
+    /// oldest are dropped beyond `keep`.
+    pub fn record(
+        &mut self,
+        summary: String,
+        model: &str,
+        now: DateTime<Utc>,
+        keep: usize,
+    ) -> &ProfileVersion {
+        let previous = self.latest().map(|v| v.summary.as_str()).unwrap_or("");
+        let (added, removed) = line_changes(previous, &summary);
+
+        match self.versions.last_mut() {
+            Some(latest) if added.is_empty() && removed.is_empty() => {
+                latest.confirmed_at = now;
+                latest.model = model.to_string();
+            }
+            latest => {
+                let version = latest.map_or(1, |v| v.version + 1);
+                self.versions.push(ProfileVersion {
+                    version,
+                    generated_at: now,
+                    confirmed_at: now,
+                    model: model.to_string(),
+                    summary,
+                    added,
+                    removed,
+                });
+                let excess = self.versions.len().saturating_sub(keep.max(1));
+                self.versions.drain(..excess);
+            }
+        }
+        self.versions.last().expect("a version was just recorded")
+    }
+
+    /// The current profile as a prompt preamble
+    pub fn prompt_context(&self) -> Option<String> {
+        let latest = self.latest()?;
+        Some(format!(
+            "Host Profile ({}, as of {}):\n{}\n",
+            self.host,
+            latest.confirmed_at.format("%Y-%m-%d"),
+            latest.summary
+        ))
+    }
+}
+
+/// Lines added to and removed from `old` to get `new`. Order is ignored, since
+/// the summarizer may regroup bullets without anything changing.
+pub fn line_changes(old: &str, new: &str) -> (Vec<String>, Vec<String>) {
+    fn counts(text: &str) -> HashMap<&str, usize> {
+        let mut counts = HashMap::new();
+        for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            *counts.entry(line).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    let mut old_counts = counts(old);
+    let mut added = Vec::new();
+    for line in new.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        match old_counts.get_mut(line) {
+            Some(count) if *count > 0 => *count -= 1,
+            _ => added.push(line.to_string()),
+        }
+    }
+    let mut new_counts = counts(new);
+    let mut removed = Vec::new();
+    for line in old.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        match new_counts.get_mut(line) {
+            Some(count) if *count > 0 => *count -= 1,
+            _ => removed.push(line.to_string()),
+        }
+    }
+    (added, removed)
+}
+
+/// Keep the bullet lines of an LLM answer, dropping any preamble, and stop
+/// once the summary is well past `max_words`
+fn clean_summary(response: &str, max_words: usize) -> Option<String> {
+    let mut words = 0;
+    let mut lines = Vec::new();
+    for line in response.lines().map(str::trim) {
+        let Some(fact) = line
+            .strip_prefix("- ")
+            .or_else(|| line.strip_prefix("* "))
+            .map(str::trim)
+            .filter(|fact| !fact.is_empty())
+        else {
+            continue;
+        };
+        words += fact.split_whitespace().count();
+        if words > max_words * 3 / 2 {
+            break;
+        }
+        lines.push(format!("- {}", fact));
+    }
+    (!lines.is_empty()).then(|| lines.join("\n"))
+}
+
+/// Probe output and Jarvis's history on the host, as summarizer input
+pub async fn gather_facts(executor: &CommandExecutor, memory: &MemoryStore) -> String {
+    let probes: &[(&str, &str, &[&str])] = &[
+        ("Hostname", "cat", &["/etc/hostname"]),
+        ("Distribution", "cat", &["/etc/os-release"]),
+        ("Kernel", "uname", &["-srm"]),
+        ("Kernel command line", "cat", &["/proc/cmdline"]),
+        ("CPU", "lscpu", &[]),
+        ("Memory", "free", &["-h"]),
+        ("GPU", "lspci", &[]),
+        (
+            "Filesystems",
+            "findmnt",
+            &[
+                "-rno",
+                "TARGET,FSTYPE,SOURCE",
+                "-t",
+                "btrfs,ext4,xfs,zfs,vfat,nfs4,cifs",
+            ],
+        ),
+        (
+            "Running services",
+            "systemctl",
+            &[
+                "list-units",
+                "--type=service",
+                "--state=running",
+                "--plain",
+                "--no-legend",
+                "--no-pager",
+            ],
+        ),
+        (
+            "Failed units",
+            "systemctl",
+            &["--failed", "--plain", "--no-legend", "--no-pager"],
+        ),
+        ("Docker Compose projects", "docker", &["compose", "ls"]),
+        (
+            "Docker containers",
+            "docker",
+            &["ps", "--format", "{{.Names}} {{.Image}} {{.Status}}"],
+        ),
+        ("Foreign (AUR) packages", "pacman", &["-Qqm"]),
+        (
+            "Jarvis operations, last 30 days",
+            "journalctl",
+            &[
+                "SYSLOG_IDENTIFIER=jarvis",
+                "--since=-30d",
+                "-o",
+                "cat",
+                "--no-pager",
+            ],
+        ),
+    ];
+
+    let mut facts = String::new();
+    for (title, program, args) in probes {
+        let output = match executor.run_stdout(program, args).await {
+            Ok(output) => output,
+            Err(e) => {
+                tracing::debug!("Host profile: skipping {}: {:#}", title, e);
+                continue;
+            }
+        };
+        let text = if title.starts_with("Jarvis operations") {
+            tally_lines(&output)
+        } else {
+            relevant_lines(title, &output)
+        };
+        if !text.trim().is_empty() {
+            facts.push_str(&format!("## {}\n{}\n\n", title, text.trim_end()));
+        }
+    }
+
+    // The MCP audit log lives with the local memory store
+    if !executor.is_remote() {
+        let since = Utc::now() - chrono::Duration::days(30);
+        match memory.audit_entries_since(since).await {
+            Ok(entries) => {
+                let failures: Vec<String> = entries
+                    .iter()
+                    .filter(|e| e.status == AuditStatus::Error)
+                    .map(|e| {
+                        format!(
+                            "{}: {}",
+                            e.tool,
+                            e.error
+                                .as_deref()
+                                .unwrap_or("failed")
+                                .lines()
+                                .next()
+                                .unwrap_or("")
+                        )
+                    })
+                    .collect();
+                let tally = tally_lines(&failures.join("\n"));
+                if !tally.is_empty() {
+                    facts.push_str(&format!(
+                        "## Failed tool calls, last 30 days\n{}\n\n",
+                        tally
+                    ));
+                }
+            }
+            Err(e) => tracing::debug!("Host profile: skipping the audit log: {:#}", e),
+        }
+    }
+    facts
+}
+
+/// The lines of a probe worth summarizing, at most [`MAX_PROBE_LINES`]
+fn relevant_lines(title: &str, output: &str) -> String {
+    let keep = |line: &&str| match title {
+        "Distribution" => line.starts_with("PRETTY_NAME=") || line.starts_with("BUILD_ID="),
+        "CPU" => [
+            "Model name:",
+            "CPU(s):",
+            "Thread(s) per core:",
+            "Virtualization:",
+        ]
+        .iter()
+        .any(|key| line.starts_with(key)),
+        "GPU" => [
+            "VGA compatible controller",
+            "3D controller",
+            "Display controller",
+        ]
+        .iter()
+        .any(|class| line.contains(class)),
+        _ => true,
+    };
+    output
+        .lines()
+        .filter(keep)
+        .take(MAX_PROBE_LINES)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Distinct lines with how often each occurred, most frequent first, so
+/// recurring problems stand out from one-offs
+fn tally_lines(output: &str) -> String {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for line in output.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        *counts.entry(line).or_insert(0) += 1;
+    }
+    let mut counts: Vec<(&str, usize)> = counts.into_iter().collect();
+    counts.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+    counts
+        .into_iter()
+        .take(MAX_PROBE_LINES)
+        .map(|(line, count)| format!("{}× {}", count, line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn summary_prompt(host: &str, facts: &str, previous: Option<&str>, max_words: usize) -> String {
+    let mut prompt = format!(
+        "Write a profile of the host '{}' for an assistant that will help administer it. \
+         Use at most {} words, as '- ' bullet lines with one fact each, in this order: \
+         hardware, distribution and kernel, key services, Docker stacks, notable configuration \
+         choices, recurring issues. Keep what matters for troubleshooting; leave out numbers \
+         that change by the hour, such as uptime, load, or free memory. Output only the bullets.",
+        host, max_words
+    );
+    if let Some(previous) = previous {
+        prompt.push_str(
+            "\n\nKeep the exact wording of the previous profile for facts that still hold:\n",
+        );
+        prompt.push_str(previous);
+    }
+    prompt.push_str("\n\nProbe output and history:\n");
+    prompt.push_str(facts);
+    prompt
+}
+
+/// Summarize the host behind `executor` and store the result as its current
+/// profile; changes from the previous version are logged
+pub async fn regenerate(
+    memory: &MemoryStore,
+    llm: &LLMRouter,
+    executor: &CommandExecutor,
+    config: &HostProfileConfig,
+) -> Result<ProfileVersion> {
+    let host = executor.host_label();
+    let mut profile = HostProfile::load(memory, &host).await?;
+
+    let facts = gather_facts(executor, memory).await;
+    if facts.is_empty() {
+        anyhow::bail!("No probe on {} returned anything to summarize", host);
+    }
+    let prompt = summary_prompt(
+        &host,
+        &facts,
+        profile.latest().map(|v| v.summary.as_str()),
+        config.max_words,
+    );
+    let response = llm.generate(&prompt, None).await?;
+    let summary = clean_summary(&response, config.max_words)
+        .with_context(|| format!("The model returned no profile bullets for {}", host))?;
+
+    let version = profile
+        .record(summary, llm.default_model(), Utc::now(), config.history)
+        .clone();
+    profile.save(memory).await?;
+
+    if version.changed() {
+        tracing::info!(
+            "Host profile for {} is now version {} ({} added, {} removed):\n{}",
+            host,
+            version.version,
+            version.added.len(),
+            version.removed.len(),
+            version.render_changes().trim_end()
+        );
+    } else {
+        tracing::info!(
+            "Host profile for {} unchanged at version {}",
+            host,
+            version.version
+        );
+    }
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(hour: u32) -> DateTime<Utc> {
+        format!("2026-03-01T{:02}:00:00Z", hour).parse().unwrap()
+    }
+
+    #[test]
+    fn test_record_versions_and_changes() {
+        let mut profile = HostProfile {
+            host: "localhost".to_string(),
+            versions: Vec::new(),
+        };
+        let first = profile.record(
+            "- Ryzen 9 7950X\n- Arch Linux".to_string(),
+            "llama3.1:8b",
+            at(1),
+            2,
+        );
+        assert_eq!(
+            (first.version, first.added.len(), first.removed.len()),
+            (1, 2, 0)
+        );
+
+        // Same facts in another order are no new version
+        let same = profile.record(
+            "- Arch Linux\n- Ryzen 9 7950X".to_string(),
+            "llama3.1:8b",
+            at(2),
+            2,
+        );
+        assert_eq!(
+            (same.version, same.generated_at, same.confirmed_at),
+            (1, at(1), at(2))
+        );
+
+        let second = profile.record(
+            "- Ryzen 9 7950X\n- Arch Linux\n- grafana.service fails after upgrades".to_string(),
+            "llama3.1:8b",
+            at(3),
+            2,
+        );
+        assert_eq!(second.version, 2);
+        assert_eq!(second.added, vec!["- grafana.service fails after upgrades"]);
+        assert!(second.render_changes().contains("+ grafana.service"));
+
+        let third = profile.record(
+            "- Ryzen 9 7950X\n- CachyOS".to_string(),
+            "qwen2.5:7b",
+            at(4),
+            2,
+        );
+        assert_eq!(
+            third.removed,
+            vec!["- Arch Linux", "- grafana.service fails after upgrades"]
+        );
+        assert_eq!(
+            profile
+                .versions
+                .iter()
+                .map(|v| v.version)
+                .collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+        assert!(profile.prompt_context().unwrap().contains("- CachyOS"));
+    }
+
+    #[test]
+    fn test_is_due() {
+        let config = HostProfileConfig::default();
+        let mut profile = HostProfile::default();
+        assert!(profile.is_due(&config, at(0)));
+        profile.record("- Arch Linux".to_string(), "llama3.1:8b", at(0), 5);
+        assert!(!profile.is_due(&config, at(23)));
+        assert!(profile.is_due(&config, at(0) + chrono::Duration::hours(24)));
+    }
+
+    #[test]
+    fn test_clean_summary_keeps_bullets() {
+        let response =
+            "Here is the profile:\n\n- Arch Linux, kernel 6.9\n* Docker stack: immich\n-\nThanks!";
+        assert_eq!(
+            clean_summary(response, 200).unwrap(),
+            "- Arch Linux, kernel 6.9\n- Docker stack: immich"
+        );
+        assert!(clean_summary("I could not tell.", 200).is_none());
+        // Far over budget, the summary is cut at a line
+        let long = "- one two three four\n".repeat(10);
+        assert_eq!(clean_summary(&long, 8).unwrap().lines().count(), 3);
+    }
+
+    #[test]
+    fn test_tally_lines_counts_repeats() {
+        let journal = "service_operation restart grafana.service: error\n\
+                       package_transaction upgrade system: success\n\
+                       service_operation restart grafana.service: error\n";
+        assert_eq!(
+            tally_lines(journal),
+            "2× service_operation restart grafana.service: error\n\
+             1× package_transaction upgrade system: success"
+        );
+    }
+}
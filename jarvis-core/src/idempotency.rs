@@ -0,0 +1,292 @@
+//! Idempotency keys for operations submitted over the network
+//!
+//! A client that retries a request after a network blip can't tell whether
+//! the first attempt went through. If it sends the same idempotency key with
+//! both attempts, the retry is answered with the first attempt's operation
+//! instead of starting a second one. Keys are remembered for a window and
+//! tied to the request they were first used with; reusing a key for a
+//! different request is refused rather than answered with the wrong
+//! operation.
+//!
+//! The HTTP API keeps key -> operation id and leaves expired keys to
+//! jarvisd's cleanup job. Front ends that answer synchronously (MCP tools,
+//! workflow nodes) use [`Replays`], which hands retries the first call's
+//! outcome, waiting for it if the first call is still running.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+/// How long keys are remembered unless configured otherwise
+pub const DEFAULT_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Past this many keys a claim prunes expired ones itself, for front ends
+/// without a cleanup job
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// Returned when a key comes back with a different request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyReused {
+    pub key: String,
+}
+
+impl fmt::Display for KeyReused {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Idempotency key '{}' was already used for a different request",
+            self.key
+        )
+    }
+}
+
+impl std::error::Error for KeyReused {}
+
+/// Outcome of [`IdempotencyKeys::claim`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Claim<T> {
+    /// First use of the key; the caller goes ahead
+    New,
+    /// The key was used within its window; the value recorded then
+    Existing(T),
+}
+
+struct Entry<T> {
+    /// What the key was first used for, e.g. the serialized operation
+    request: String,
+    value: T,
+    expires: Instant,
+}
+
+/// Keys seen within their window, each with a value such as an operation id
+pub struct IdempotencyKeys<T> {
+    entries: Mutex<HashMap<String, Entry<T>>>,
+}
+
+impl<T: Clone> IdempotencyKeys<T> {
+    pub fn new() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record `key` for `request` with the value from `make`, or return the
+    /// value recorded when it was used within the last `window`. Concurrent
+    /// claims of one key see exactly one `Claim::New`.
+    pub fn claim(
+        &self,
+        key: &str,
+        request: &str,
+        window: Duration,
+        make: impl FnOnce() -> T,
+    ) -> Result<Claim<T>, KeyReused> {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(entry) = entries.get(key).filter(|entry| entry.expires > now) {
+            if entry.request != request {
+                return Err(KeyReused {
+                    key: key.to_string(),
+                });
+            }
+            return Ok(Claim::Existing(entry.value.clone()));
+        }
+
+        if entries.len() >= PRUNE_THRESHOLD {
+            entries.retain(|_, entry| entry.expires > now);
+        }
+        entries.insert(
+            key.to_string(),
+            Entry {
+                request: request.to_string(),
+                value: make(),
+                expires: now + window,
+            },
+        );
+        Ok(Claim::New)
+    }
+
+    /// Drop keys whose window has passed; returns how many were dropped
+    pub fn prune_expired(&self) -> usize {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        let before = entries.len();
+        entries.retain(|_, entry| entry.expires > now);
+        before - entries.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forget `key` if its value still satisfies `stale`
+    fn forget_if(&self, key: &str, stale: impl FnOnce(&T) -> bool) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.get(key).is_some_and(|entry| stale(&entry.value)) {
+            entries.remove(key);
+        }
+    }
+}
+
+impl<T: Clone> Default for IdempotencyKeys<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Keys whose value is the outcome of the first call made with them
+pub type Replays<R> = IdempotencyKeys<watch::Receiver<Option<R>>>;
+
+/// A call's outcome, and whether it was that of an earlier call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replayed<R> {
+    pub value: R,
+    pub replayed: bool,
+}
+
+impl<R: Clone> Replays<R> {
+    /// Run `call` unless `key` was used for `request` within `window`, in
+    /// which case the earlier call's outcome is returned, once it has one.
+    /// If the earlier call was dropped before finishing, this one runs.
+    pub async fn run<F>(
+        &self,
+        key: &str,
+        request: &str,
+        window: Duration,
+        call: F,
+    ) -> Result<Replayed<R>, KeyReused>
+    where
+        F: Future<Output = R>,
+    {
+        loop {
+            let mut sender = None;
+            let claim = self.claim(key, request, window, || {
+                let (tx, rx) = watch::channel(None);
+                sender = Some(tx);
+                rx
+            })?;
+            match claim {
+                Claim::New => {
+                    let sender = sender.expect("a new claim creates its channel");
+                    let value = call.await;
+                    sender.send_replace(Some(value.clone()));
+                    return Ok(Replayed {
+                        value,
+                        replayed: false,
+                    });
+                }
+                Claim::Existing(mut outcome) => {
+                    if let Ok(value) = outcome.wait_for(Option::is_some).await {
+                        return Ok(Replayed {
+                            value: value.clone().expect("waited for an outcome"),
+                            replayed: true,
+                        });
+                    }
+                    // Abandoned without an outcome: take the key over
+                    self.forget_if(key, |current| current.same_channel(&outcome));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const WINDOW: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn test_keys_are_tied_to_their_request_and_window() {
+        let keys = IdempotencyKeys::new();
+        assert_eq!(keys.claim("k1", "update", WINDOW, || 1), Ok(Claim::New));
+        assert_eq!(
+            keys.claim("k1", "update", WINDOW, || 2),
+            Ok(Claim::Existing(1))
+        );
+        assert_eq!(
+            keys.claim("k1", "remove", WINDOW, || 3),
+            Err(KeyReused {
+                key: "k1".to_string()
+            })
+        );
+
+        // Expired keys start over, and cleanup drops them
+        assert_eq!(
+            keys.claim("k2", "update", Duration::ZERO, || 4),
+            Ok(Claim::New)
+        );
+        assert_eq!(
+            keys.claim("k2", "remove", Duration::ZERO, || 5),
+            Ok(Claim::New)
+        );
+        assert_eq!(keys.prune_expired(), 1);
+        assert_eq!(keys.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_racing_calls_with_one_key_run_once() {
+        let replays = Arc::new(Replays::<usize>::new());
+        let runs = Arc::new(AtomicUsize::new(0));
+        let (release, released) = watch::channel(false);
+
+        let calls: Vec<_> = (0..8)
+            .map(|_| {
+                let replays = replays.clone();
+                let runs = runs.clone();
+                let mut released = released.clone();
+                tokio::spawn(async move {
+                    replays
+                        .run("retry-1", "update", WINDOW, async move {
+                            let n = runs.fetch_add(1, Ordering::SeqCst) + 1;
+                            released.wait_for(|go| *go).await.unwrap();
+                            n
+                        })
+                        .await
+                        .unwrap()
+                })
+            })
+            .collect();
+        tokio::task::yield_now().await;
+        release.send_replace(true);
+
+        let mut outcomes = Vec::new();
+        for call in calls {
+            outcomes.push(call.await.unwrap());
+        }
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert!(outcomes.iter().all(|o| o.value == 1));
+        assert_eq!(outcomes.iter().filter(|o| !o.replayed).count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_calls_do_not_block_retries() {
+        let replays = Replays::<&str>::new();
+        let abandoned = replays.run("k", "update", WINDOW, std::future::pending());
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), abandoned)
+                .await
+                .is_err()
+        );
+
+        let retry = replays
+            .run("k", "update", WINDOW, async { "done" })
+            .await
+            .unwrap();
+        assert_eq!(
+            retry,
+            Replayed {
+                value: "done",
+                replayed: false
+            }
+        );
+    }
+}
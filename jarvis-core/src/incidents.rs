@@ -0,0 +1,1184 @@
+//! Incident correlation
+//!
+//! One fault rarely produces one event: an upgraded library takes a service
+//! down, its container goes unhealthy, a restart fails, and jarvis-nv sees the
+//! request rate drop. The [`Correlator`] folds events like these into a single
+//! [`Incident`] with a timeline, the components involved, and the worst
+//! severity seen, so the user gets one notification when it opens, a few as it
+//! grows, and one when it is over.
+//!
+//! Events join an incident through [`CorrelationRule`]s: close in time, about
+//! the same service, package, or container, or a known cause before its
+//! effect. The rules are plain data in `[incidents]`, and the defaults cover
+//! the usual cascades.
+
+use crate::bus::{BusEvent, BusMessage};
+use crate::memory::MemoryStore;
+use crate::notify::{Notification, NotifyEvent, NotifySeverity};
+use crate::severity::Severity;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, VecDeque};
+use uuid::Uuid;
+
+const DOCUMENT_KEY: &str = "incidents";
+
+/// Events kept per incident; older ones after the first are dropped
+const MAX_TIMELINE: usize = 200;
+
+/// Unit suffixes stripped so "nginx.service" and "container nginx" agree
+const UNIT_SUFFIXES: [&str; 5] = [".service", ".socket", ".timer", ".mount", ".target"];
+
+/// Words that say what kind of component something is, not which one
+const GENERIC_WORDS: [&str; 7] = [
+    "agents",
+    "check",
+    "container",
+    "package",
+    "sensor",
+    "service",
+    "system",
+];
+
+/// What an event says happened
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignalKind {
+    /// A health check, sensor, or the agents left the healthy state
+    HealthDegraded,
+    /// A component that was degraded or unhealthy is healthy again
+    HealthRecovered,
+    /// A container's health check started failing
+    ContainerUnhealthy,
+    /// A security scan found something new
+    SecurityFinding,
+    /// A jarvis-nv or other monitor anomaly
+    Anomaly,
+    /// A package transaction, service operation, or command failed
+    OperationFailed,
+    /// A package transaction succeeded; only ever the cause of an incident
+    PackageChange,
+}
+
+impl SignalKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SignalKind::HealthDegraded => "health_degraded",
+            SignalKind::HealthRecovered => "health_recovered",
+            SignalKind::ContainerUnhealthy => "container_unhealthy",
+            SignalKind::SecurityFinding => "security_finding",
+            SignalKind::Anomaly => "anomaly",
+            SignalKind::OperationFailed => "operation_failed",
+            SignalKind::PackageChange => "package_change",
+        }
+    }
+
+    /// Whether the component stays broken until a recovery says otherwise
+    fn degrades(self) -> bool {
+        matches!(
+            self,
+            SignalKind::HealthDegraded | SignalKind::ContainerUnhealthy
+        )
+    }
+}
+
+/// One event as the correlator sees it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Signal {
+    pub kind: SignalKind,
+    /// As published, e.g. "sensor nvme0/Composite" or "container api"
+    pub component: String,
+    /// Names the component is about, for matching events on the same subject
+    #[serde(default)]
+    pub subjects: Vec<String>,
+    pub severity: Severity,
+    pub summary: String,
+    /// Who published the event, e.g. "jarvisd" or "jarvis-nv"
+    pub source: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl Signal {
+    pub fn new(
+        kind: SignalKind,
+        component: impl Into<String>,
+        severity: Severity,
+        summary: impl Into<String>,
+        timestamp: DateTime<Utc>,
+    ) -> Self {
+        let component = component.into();
+        Self {
+            kind,
+            subjects: subjects(&component),
+            component,
+            severity,
+            summary: summary.into(),
+            source: "jarvisd".to_string(),
+            timestamp,
+        }
+    }
+
+    pub fn with_source(mut self, source: impl Into<String>) -> Self {
+        self.source = source.into();
+        self
+    }
+
+    /// The signal a bus event carries; `None` for events that say nothing
+    /// about faults, such as scheduled maintenance
+    pub fn from_message(message: &BusMessage) -> Option<Self> {
+        let (kind, component, severity) = match &message.event {
+            BusEvent::HealthStateChanged { component, state } => match state_severity(state) {
+                None => (SignalKind::HealthRecovered, component, Severity::Info),
+                Some(severity) if component.starts_with("container ") => {
+                    (SignalKind::ContainerUnhealthy, component, severity)
+                }
+                Some(severity) => (SignalKind::HealthDegraded, component, severity),
+            },
+            BusEvent::AnomalyDetected {
+                category,
+                component,
+                severity,
+                ..
+            } => {
+                let kind = if category == "security" {
+                    SignalKind::SecurityFinding
+                } else {
+                    SignalKind::Anomaly
+                };
+                (kind, component, *severity)
+            }
+            BusEvent::OperationCompleted {
+                target,
+                success: false,
+                ..
+            } => (SignalKind::OperationFailed, target, Severity::High),
+            BusEvent::OperationCompleted {
+                operation, target, ..
+            } if operation == "package_transaction" => {
+                (SignalKind::PackageChange, target, Severity::Info)
+            }
+            _ => return None,
+        };
+
+        Some(
+            Signal::new(
+                kind,
+                component.clone(),
+                severity,
+                message.event.summary(),
+                message.timestamp,
+            )
+            .with_source(message.source.clone()),
+        )
+    }
+
+    /// Whether both signals name a common subject; a package and its split
+    /// packages ("postgresql", "postgresql-libs") count as one
+    pub fn shares_subject(&self, other: &Signal) -> bool {
+        self.subjects
+            .iter()
+            .any(|a| other.subjects.iter().any(|b| related(a, b)))
+    }
+}
+
+/// Severity of a published health state; `None` for states that mean healthy
+fn state_severity(state: &str) -> Option<Severity> {
+    match state.trim().to_lowercase().as_str() {
+        "healthy" | "ok" | "running" | "recovered" => None,
+        "critical" | "failed" | "down" => Some(Severity::Critical),
+        _ => Some(Severity::Medium),
+    }
+}
+
+/// The names in a component or operation target: "nginx.service" and
+/// "container nginx" both give "nginx", "openssl nginx" gives both packages
+fn subjects(text: &str) -> Vec<String> {
+    let mut subjects: Vec<String> = Vec::new();
+    for word in text.split(|c: char| c.is_whitespace() || matches!(c, ',' | ':' | '/' | '@')) {
+        let word = word.trim().to_lowercase();
+        let word = UNIT_SUFFIXES
+            .iter()
+            .find_map(|suffix| word.strip_suffix(suffix))
+            .map(String::from)
+            .unwrap_or(word);
+        if !word.is_empty() && !GENERIC_WORDS.contains(&word.as_str()) && !subjects.contains(&word)
+        {
+            subjects.push(word);
+        }
+    }
+    subjects
+}
+
+fn related(a: &str, b: &str) -> bool {
+    a == b
+        || a.strip_prefix(b).is_some_and(|rest| rest.starts_with('-'))
+        || b.strip_prefix(a).is_some_and(|rest| rest.starts_with('-'))
+}
+
+/// Links an earlier event to a later one, putting both in the same incident
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CorrelationRule {
+    pub name: String,
+    /// Kinds the earlier event may have; empty matches any
+    #[serde(default)]
+    pub cause: Vec<SignalKind>,
+    /// Kinds the later event may have; empty matches any
+    #[serde(default)]
+    pub effect: Vec<SignalKind>,
+    /// Longest time from the earlier event to the later one
+    pub within_minutes: u64,
+    /// Only link events that name a common service, package, or container
+    #[serde(default)]
+    pub same_subject: bool,
+    /// Record the earlier event as a likely cause of the later one
+    #[serde(default)]
+    pub causal: bool,
+}
+
+impl CorrelationRule {
+    /// Whether this rule puts `later` in the incident `earlier` belongs to
+    pub fn links(&self, earlier: &Signal, later: &Signal) -> bool {
+        let gap = later.timestamp - earlier.timestamp;
+        (self.cause.is_empty() || self.cause.contains(&earlier.kind))
+            && (self.effect.is_empty() || self.effect.contains(&later.kind))
+            && gap >= Duration::zero()
+            && gap <= Duration::minutes(self.within_minutes as i64)
+            && (!self.same_subject || earlier.shares_subject(later))
+    }
+}
+
+/// The rules used when `[incidents]` names none
+pub fn default_rules() -> Vec<CorrelationRule> {
+    let failures = vec![
+        SignalKind::HealthDegraded,
+        SignalKind::ContainerUnhealthy,
+        SignalKind::OperationFailed,
+    ];
+    vec![
+        CorrelationRule {
+            name: "same_subject".to_string(),
+            cause: Vec::new(),
+            effect: Vec::new(),
+            within_minutes: 30,
+            same_subject: true,
+            causal: false,
+        },
+        CorrelationRule {
+            name: "burst".to_string(),
+            cause: Vec::new(),
+            effect: Vec::new(),
+            within_minutes: 2,
+            same_subject: false,
+            causal: false,
+        },
+        CorrelationRule {
+            name: "upgrade_before_failure".to_string(),
+            cause: vec![SignalKind::PackageChange],
+            effect: failures,
+            within_minutes: 60,
+            same_subject: false,
+            causal: true,
+        },
+        CorrelationRule {
+            name: "failed_operation_before_degradation".to_string(),
+            cause: vec![SignalKind::OperationFailed],
+            effect: vec![SignalKind::HealthDegraded, SignalKind::ContainerUnhealthy],
+            within_minutes: 15,
+            same_subject: false,
+            causal: true,
+        },
+    ]
+}
+
+/// `[incidents]` in jarvis.toml
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IncidentsConfig {
+    /// Group events into incidents and notify per incident instead of per
+    /// health change
+    pub enabled: bool,
+    /// Minutes without new events, with every degraded component healthy
+    /// again, before an incident resolves
+    pub resolve_after_minutes: u64,
+    /// Least time between two "updated" notifications about one incident,
+    /// unless its severity rises
+    pub notify_interval_minutes: u64,
+    /// Incidents below this severity are tracked but not notified
+    pub min_severity: Severity,
+    /// Resolved incidents kept for `jarvis incidents` and the report
+    pub max_stored: usize,
+    /// How events join an incident; replaces the defaults when given
+    pub rules: Vec<CorrelationRule>,
+}
+
+impl Default for IncidentsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            resolve_after_minutes: 15,
+            notify_interval_minutes: 30,
+            min_severity: Severity::Low,
+            max_stored: 200,
+            rules: default_rules(),
+        }
+    }
+}
+
+/// Related events, from the first until everything involved is healthy again
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Incident {
+    pub id: Uuid,
+    /// Summary of the event that opened it
+    pub title: String,
+    /// The worst severity of any event in it
+    pub severity: Severity,
+    pub opened_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    /// Every component an event named, in order of appearance
+    pub components: Vec<String>,
+    /// Components whose last health event was a failure
+    pub degraded: BTreeSet<String>,
+    /// Events in time order
+    pub timeline: Vec<Signal>,
+    /// What causal rules found, e.g. an upgrade shortly before a failure
+    pub causes: Vec<String>,
+    #[serde(default)]
+    pub notified_at: Option<DateTime<Utc>>,
+    /// Severity as of the last notification
+    #[serde(default)]
+    pub notified_severity: Option<Severity>,
+}
+
+impl Incident {
+    fn open(signal: Signal) -> Self {
+        let mut incident = Self {
+            id: Uuid::new_v4(),
+            title: signal.summary.clone(),
+            severity: Severity::Info,
+            opened_at: signal.timestamp,
+            updated_at: signal.timestamp,
+            resolved_at: None,
+            components: Vec::new(),
+            degraded: BTreeSet::new(),
+            timeline: Vec::new(),
+            causes: Vec::new(),
+            notified_at: None,
+            notified_severity: None,
+        };
+        incident.add(signal);
+        incident
+    }
+
+    pub fn short_id(&self) -> String {
+        crate::trace::short_id(self.id)
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.resolved_at.is_none()
+    }
+
+    /// From the first event until resolution, or until `now` while open
+    pub fn duration(&self, now: DateTime<Utc>) -> Duration {
+        self.resolved_at.unwrap_or(now) - self.opened_at
+    }
+
+    /// Whether the incident was open at any time between `since` and `until`
+    pub fn overlaps(&self, since: DateTime<Utc>, until: DateTime<Utc>) -> bool {
+        self.opened_at <= until && self.resolved_at.is_none_or(|resolved| resolved >= since)
+    }
+
+    fn add(&mut self, signal: Signal) {
+        self.severity = self.severity.max(signal.severity);
+        self.opened_at = self.opened_at.min(signal.timestamp);
+        self.updated_at = self.updated_at.max(signal.timestamp);
+        if !self.components.contains(&signal.component) {
+            self.components.push(signal.component.clone());
+        }
+        if signal.kind.degrades() {
+            self.degraded.insert(signal.component.clone());
+        } else if signal.kind == SignalKind::HealthRecovered {
+            self.degraded.remove(&signal.component);
+        }
+
+        if self.timeline.len() >= MAX_TIMELINE {
+            self.timeline.remove(1);
+        }
+        let at = self
+            .timeline
+            .partition_point(|entry| entry.timestamp <= signal.timestamp);
+        self.timeline.insert(at, signal);
+    }
+
+    fn add_cause(&mut self, cause: String) {
+        if !self.causes.contains(&cause) {
+            self.causes.push(cause);
+        }
+    }
+
+    /// Take over the events of an incident that turned out to be the same one
+    fn absorb(&mut self, other: Incident) {
+        // Replaying the other timeline can't tell which incident saw a
+        // component last; degraded in either means still degraded
+        let mut degraded = std::mem::take(&mut self.degraded);
+        degraded.extend(other.degraded);
+        for signal in other.timeline {
+            if !self.timeline.contains(&signal) {
+                self.add(signal);
+            }
+        }
+        self.degraded = degraded;
+        for cause in other.causes {
+            self.add_cause(cause);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum IncidentChange {
+    Opened,
+    Updated,
+    Resolved,
+    /// Folded into another incident when an event linked the two
+    Merged {
+        into: Uuid,
+    },
+}
+
+/// An incident as it stands after a change, and whether to tell the user
+#[derive(Debug, Clone)]
+pub struct IncidentUpdate {
+    pub change: IncidentChange,
+    pub incident: Incident,
+    pub notify: bool,
+}
+
+impl IncidentUpdate {
+    pub fn notification(&self) -> Notification {
+        let incident = &self.incident;
+        let (verb, severity) = match self.change {
+            IncidentChange::Opened => ("opened", incident.severity.into()),
+            IncidentChange::Updated => ("updated", incident.severity.into()),
+            IncidentChange::Resolved | IncidentChange::Merged { .. } => {
+                ("resolved", NotifySeverity::Info)
+            }
+        };
+
+        let mut lines = Vec::new();
+        if self.change == IncidentChange::Resolved {
+            lines.push(format!(
+                "Lasted {}; {} events across {}",
+                format_duration(incident.duration(Utc::now())),
+                incident.timeline.len(),
+                incident.components.join(", ")
+            ));
+        } else {
+            lines.push(format!(
+                "{} · {}",
+                incident.severity,
+                incident.components.join(", ")
+            ));
+            let recent = incident.timeline.len().saturating_sub(5);
+            for signal in &incident.timeline[recent..] {
+                lines.push(format!(
+                    "{} {}",
+                    signal.timestamp.format("%H:%M"),
+                    signal.summary
+                ));
+            }
+            for cause in &incident.causes {
+                lines.push(format!("Likely cause: {}", cause));
+            }
+        }
+        lines.push(format!(
+            "See `jarvis incidents show {}`",
+            incident.short_id()
+        ));
+
+        Notification::new(
+            NotifyEvent::Incident,
+            severity,
+            format!("Incident {}: {}", verb, incident.title),
+            lines.join("\n"),
+        )
+    }
+}
+
+/// Groups signals into incidents by the configured rules
+pub struct Correlator {
+    config: IncidentsConfig,
+    open: Vec<Incident>,
+    /// Recent package changes, kept as candidate causes of later failures
+    causes: VecDeque<Signal>,
+}
+
+impl Correlator {
+    pub fn new(config: IncidentsConfig) -> Self {
+        Self {
+            config,
+            open: Vec::new(),
+            causes: VecDeque::new(),
+        }
+    }
+
+    /// Continue incidents that were still open when the daemon stopped
+    pub fn with_open(mut self, incidents: impl IntoIterator<Item = Incident>) -> Self {
+        self.open
+            .extend(incidents.into_iter().filter(Incident::is_open));
+        self.open.sort_by_key(|incident| incident.opened_at);
+        self
+    }
+
+    pub fn open_incidents(&self) -> &[Incident] {
+        &self.open
+    }
+
+    /// Fold `signal` into the incidents; returns every incident that changed,
+    /// including ones that resolved before it arrived
+    pub fn observe(&mut self, signal: Signal) -> Vec<IncidentUpdate> {
+        let now = signal.timestamp;
+        let mut updates = self.tick(now);
+
+        match signal.kind {
+            SignalKind::PackageChange => self.causes.push_back(signal),
+            SignalKind::HealthRecovered => {
+                if let Some(index) = self
+                    .open
+                    .iter()
+                    .position(|incident| incident.degraded.contains(&signal.component))
+                {
+                    self.open[index].add(signal);
+                    updates.push(self.update(index, IncidentChange::Updated, now));
+                }
+            }
+            _ => updates.extend(self.correlate(signal)),
+        }
+        updates
+    }
+
+    /// Resolve incidents that have been quiet long enough with nothing left
+    /// degraded
+    pub fn tick(&mut self, now: DateTime<Utc>) -> Vec<IncidentUpdate> {
+        let quiet = Duration::minutes(self.config.resolve_after_minutes as i64);
+        let mut updates = Vec::new();
+        let mut index = 0;
+        while index < self.open.len() {
+            let incident = &self.open[index];
+            if !incident.degraded.is_empty() || now - incident.updated_at < quiet {
+                index += 1;
+                continue;
+            }
+            let mut incident = self.open.remove(index);
+            incident.resolved_at = Some(now);
+            let notify = should_notify(&self.config, &incident, IncidentChange::Resolved, now);
+            updates.push(IncidentUpdate {
+                change: IncidentChange::Resolved,
+                incident,
+                notify,
+            });
+        }
+
+        let longest = self
+            .config
+            .rules
+            .iter()
+            .map(|rule| rule.within_minutes)
+            .max()
+            .unwrap_or(0);
+        self.causes
+            .retain(|cause| now - cause.timestamp <= Duration::minutes(longest as i64));
+        updates
+    }
+
+    fn correlate(&mut self, signal: Signal) -> Vec<IncidentUpdate> {
+        let now = signal.timestamp;
+        let mut updates = Vec::new();
+        let mut hints = Vec::new();
+
+        let linked: Vec<usize> = (0..self.open.len())
+            .filter(|&index| {
+                let mut linked = false;
+                for entry in &self.open[index].timeline {
+                    for rule in self.rules_linking(entry, &signal) {
+                        linked = true;
+                        if rule.causal {
+                            hints.push(describe_cause(entry, &signal));
+                        }
+                    }
+                }
+                linked
+            })
+            .collect();
+        let earlier: Vec<Signal> = self
+            .causes
+            .iter()
+            .filter(|cause| {
+                let rules: Vec<_> = self.rules_linking(cause, &signal).collect();
+                for rule in &rules {
+                    if rule.causal {
+                        hints.push(describe_cause(cause, &signal));
+                    }
+                }
+                !rules.is_empty()
+            })
+            .cloned()
+            .collect();
+
+        let (index, change) = match linked.split_first() {
+            None => {
+                self.open.push(Incident::open(signal));
+                (self.open.len() - 1, IncidentChange::Opened)
+            }
+            Some((&first, rest)) => {
+                // Highest index first, so the remaining indices stay valid
+                for &other in rest.iter().rev() {
+                    let mut merged = self.open.remove(other);
+                    let into = self.open[first].id;
+                    self.open[first].absorb(merged.clone());
+                    merged.resolved_at = Some(now);
+                    updates.push(IncidentUpdate {
+                        change: IncidentChange::Merged { into },
+                        incident: merged,
+                        notify: false,
+                    });
+                }
+                self.open[first].add(signal);
+                (first, IncidentChange::Updated)
+            }
+        };
+
+        let incident = &mut self.open[index];
+        for cause in earlier {
+            if !incident.timeline.contains(&cause) {
+                incident.add(cause);
+            }
+        }
+        for hint in hints {
+            incident.add_cause(hint);
+        }
+        updates.push(self.update(index, change, now));
+        updates
+    }
+
+    fn rules_linking<'a>(
+        &'a self,
+        earlier: &'a Signal,
+        later: &'a Signal,
+    ) -> impl Iterator<Item = &'a CorrelationRule> {
+        self.config
+            .rules
+            .iter()
+            .filter(move |rule| rule.links(earlier, later))
+    }
+
+    fn update(
+        &mut self,
+        index: usize,
+        change: IncidentChange,
+        now: DateTime<Utc>,
+    ) -> IncidentUpdate {
+        let incident = &mut self.open[index];
+        let notify = should_notify(&self.config, incident, change, now);
+        if notify {
+            incident.notified_at = Some(now);
+            incident.notified_severity = Some(incident.severity);
+        }
+        IncidentUpdate {
+            change,
+            incident: incident.clone(),
+            notify,
+        }
+    }
+}
+
+/// Per-incident rate limit: the first notification at or above the minimum
+/// severity goes out, then further updates only when the severity rises or
+/// the interval has passed. A resolution is only announced for incidents the
+/// user heard about.
+fn should_notify(
+    config: &IncidentsConfig,
+    incident: &Incident,
+    change: IncidentChange,
+    now: DateTime<Utc>,
+) -> bool {
+    match change {
+        IncidentChange::Merged { .. } => false,
+        IncidentChange::Resolved => incident.notified_at.is_some(),
+        IncidentChange::Opened | IncidentChange::Updated => {
+            if !incident.severity.meets(config.min_severity) {
+                return false;
+            }
+            match (incident.notified_at, incident.notified_severity) {
+                (Some(at), Some(severity)) => {
+                    incident.severity > severity
+                        || now - at >= Duration::minutes(config.notify_interval_minutes as i64)
+                }
+                _ => true,
+            }
+        }
+    }
+}
+
+fn describe_cause(cause: &Signal, effect: &Signal) -> String {
+    format!(
+        "{} ({} before {})",
+        cause.summary,
+        format_duration(effect.timestamp - cause.timestamp),
+        effect.summary
+    )
+}
+
+/// "45s", "12m", "3h 5m", or "2d 4h"
+pub fn format_duration(duration: Duration) -> String {
+    let minutes = duration.num_minutes();
+    if minutes < 1 {
+        format!("{}s", duration.num_seconds().max(0))
+    } else if minutes < 60 {
+        format!("{}m", minutes)
+    } else if minutes < 24 * 60 {
+        format!("{}h {}m", minutes / 60, minutes % 60)
+    } else {
+        format!("{}d {}h", minutes / (24 * 60), (minutes / 60) % 24)
+    }
+}
+
+/// Incidents kept in the memory store, newest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IncidentLog {
+    incidents: Vec<Incident>,
+}
+
+impl IncidentLog {
+    pub async fn load(memory: &MemoryStore) -> Result<Self> {
+        match memory.get_document(DOCUMENT_KEY).await? {
+            Some(data) => serde_json::from_str(&data).context("Corrupt incident log"),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub async fn save(&self, memory: &MemoryStore) -> Result<()> {
+        memory
+            .store_document(DOCUMENT_KEY, &serde_json::to_string(self)?)
+            .await
+    }
+
+    /// Store the incident as it stands after `update`, dropping merged ones
+    /// and all but the newest `max_stored` resolved ones
+    pub fn apply(&mut self, update: &IncidentUpdate, max_stored: usize) {
+        self.incidents
+            .retain(|incident| incident.id != update.incident.id);
+        if let IncidentChange::Merged { .. } = update.change {
+            return;
+        }
+        let at = self
+            .incidents
+            .partition_point(|incident| incident.opened_at > update.incident.opened_at);
+        self.incidents.insert(at, update.incident.clone());
+
+        let mut resolved = 0;
+        self.incidents.retain(|incident| {
+            if incident.is_open() {
+                return true;
+            }
+            resolved += 1;
+            resolved <= max_stored
+        });
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Incident> {
+        self.incidents.iter()
+    }
+
+    pub fn open(&self) -> impl Iterator<Item = &Incident> {
+        self.incidents.iter().filter(|incident| incident.is_open())
+    }
+
+    /// Incidents open at any time in the window, oldest first
+    pub fn between(&self, since: DateTime<Utc>, until: DateTime<Utc>) -> Vec<Incident> {
+        self.incidents
+            .iter()
+            .rev()
+            .filter(|incident| incident.overlaps(since, until))
+            .cloned()
+            .collect()
+    }
+
+    /// An incident by full id or unique prefix
+    pub fn find(&self, id: &str) -> Result<&Incident> {
+        let matches: Vec<&Incident> = self
+            .incidents
+            .iter()
+            .filter(|incident| incident.id.to_string().starts_with(id))
+            .collect();
+        match matches.as_slice() {
+            [] => anyhow::bail!("No stored incident matches '{}'", id),
+            [incident] => Ok(*incident),
+            _ => anyhow::bail!(
+                "'{}' matches {} incidents; use more of the id",
+                id,
+                matches.len()
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(minutes: i64) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2026-03-02T10:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+            + Duration::minutes(minutes)
+    }
+
+    fn message(minutes: i64, source: &str, event: BusEvent) -> BusMessage {
+        BusMessage {
+            source: source.to_string(),
+            timestamp: at(minutes),
+            event,
+        }
+    }
+
+    fn health(minutes: i64, component: &str, state: &str) -> Signal {
+        Signal::from_message(&message(
+            minutes,
+            "jarvisd",
+            BusEvent::HealthStateChanged {
+                component: component.to_string(),
+                state: state.to_string(),
+            },
+        ))
+        .unwrap()
+    }
+
+    fn operation(minutes: i64, operation: &str, target: &str, success: bool) -> Signal {
+        Signal::from_message(&message(
+            minutes,
+            "jarvisd",
+            BusEvent::OperationCompleted {
+                operation: operation.to_string(),
+                target: target.to_string(),
+                success,
+            },
+        ))
+        .unwrap()
+    }
+
+    fn anomaly(minutes: i64, category: &str, component: &str, severity: Severity) -> Signal {
+        Signal::from_message(&message(
+            minutes,
+            "jarvis-nv",
+            BusEvent::AnomalyDetected {
+                category: category.to_string(),
+                component: component.to_string(),
+                severity,
+                description: "out of range".to_string(),
+            },
+        ))
+        .unwrap()
+    }
+
+    /// Feed `signals` in order and collect every update
+    fn run(correlator: &mut Correlator, signals: Vec<Signal>) -> Vec<IncidentUpdate> {
+        signals
+            .into_iter()
+            .flat_map(|signal| correlator.observe(signal))
+            .collect()
+    }
+
+    #[test]
+    fn test_signals_from_bus_events() {
+        let container = health(0, "container api", "unhealthy");
+        assert_eq!(container.kind, SignalKind::ContainerUnhealthy);
+        assert_eq!(container.subjects, ["api"]);
+        assert_eq!(
+            health(0, "nginx.service", "critical").severity,
+            Severity::Critical
+        );
+        assert_eq!(
+            health(0, "nginx.service", "healthy").kind,
+            SignalKind::HealthRecovered
+        );
+
+        let finding = anomaly(0, "security", "openssl", Severity::High);
+        assert_eq!(finding.kind, SignalKind::SecurityFinding);
+        assert_eq!(finding.source, "jarvis-nv");
+        assert_eq!(
+            anomaly(0, "gpu", "gpu0", Severity::Low).kind,
+            SignalKind::Anomaly
+        );
+
+        let upgrade = operation(0, "package_transaction", "openssl nginx-mainline", true);
+        assert_eq!(upgrade.kind, SignalKind::PackageChange);
+        assert_eq!(upgrade.subjects, ["openssl", "nginx-mainline"]);
+        assert_eq!(
+            operation(0, "command", "ls", false).kind,
+            SignalKind::OperationFailed
+        );
+        assert!(
+            Signal::from_message(&message(
+                0,
+                "jarvisd",
+                BusEvent::OperationCompleted {
+                    operation: "command".to_string(),
+                    target: "ls".to_string(),
+                    success: true,
+                },
+            ))
+            .is_none()
+        );
+
+        // Split packages and unit names count as the same subject
+        assert!(upgrade.shares_subject(&health(0, "nginx-mainline.service", "failed")));
+        assert!(!upgrade.shares_subject(&health(0, "container api", "unhealthy")));
+    }
+
+    #[test]
+    fn test_upgrade_then_unit_failure_is_one_incident() {
+        let mut correlator = Correlator::new(IncidentsConfig::default());
+        let updates = run(
+            &mut correlator,
+            vec![
+                operation(0, "package_transaction", "openssl", true),
+                health(12, "nginx.service", "critical"),
+                operation(13, "service_operation", "nginx", false),
+                anomaly(14, "traffic", "nginx", Severity::Medium),
+            ],
+        );
+
+        // The upgrade alone opens nothing
+        assert_eq!(updates[0].change, IncidentChange::Opened);
+        assert!(updates[0].notify);
+        assert!(
+            updates[1..]
+                .iter()
+                .all(|u| u.change == IncidentChange::Updated)
+        );
+        assert_eq!(correlator.open_incidents().len(), 1);
+
+        let incident = &correlator.open_incidents()[0];
+        assert_eq!(incident.timeline.len(), 4);
+        assert_eq!(incident.timeline[0].kind, SignalKind::PackageChange);
+        assert_eq!(incident.severity, Severity::Critical);
+        assert_eq!(incident.title, "nginx.service is now critical");
+        assert_eq!(incident.components, ["nginx.service", "openssl", "nginx"]);
+        assert_eq!(
+            incident.causes,
+            [
+                "package_transaction openssl completed (12m before nginx.service is now critical)",
+                "package_transaction openssl completed (13m before service_operation nginx failed)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_incident_resolves_after_recovery_and_quiet_period() {
+        let mut correlator = Correlator::new(IncidentsConfig::default());
+        run(
+            &mut correlator,
+            vec![
+                health(0, "container db", "unhealthy"),
+                health(1, "container api", "unhealthy"),
+                health(5, "container db", "healthy"),
+            ],
+        );
+        // api is still unhealthy, so quiet time alone doesn't resolve it
+        assert!(correlator.tick(at(60)).is_empty());
+
+        let updates = run(
+            &mut correlator,
+            vec![health(61, "container api", "healthy")],
+        );
+        assert_eq!(updates[0].change, IncidentChange::Updated);
+        assert!(correlator.tick(at(70)).is_empty());
+
+        let resolved = correlator.tick(at(76));
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].change, IncidentChange::Resolved);
+        assert!(resolved[0].notify);
+        assert_eq!(resolved[0].incident.resolved_at, Some(at(76)));
+        assert!(resolved[0].incident.degraded.is_empty());
+        assert!(correlator.open_incidents().is_empty());
+
+        // A recovery for something no incident tracks is ignored
+        assert!(
+            correlator
+                .observe(health(80, "container api", "healthy"))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_unrelated_events_stay_apart_and_merge_when_linked() {
+        let mut correlator = Correlator::new(IncidentsConfig::default());
+        let updates = run(
+            &mut correlator,
+            vec![
+                health(0, "container api", "unhealthy"),
+                anomaly(10, "temperature", "nvme0", Severity::Medium),
+            ],
+        );
+        assert_eq!(updates.len(), 2);
+        assert!(updates.iter().all(|u| u.change == IncidentChange::Opened));
+        assert_eq!(correlator.open_incidents().len(), 2);
+
+        // A failed compose restart names api, and comes a minute after the
+        // temperature anomaly: both incidents were the same fault
+        let updates = run(
+            &mut correlator,
+            vec![operation(11, "command", "docker restart api", false)],
+        );
+        let first = correlator.open_incidents()[0].id;
+        assert_eq!(updates[0].change, IncidentChange::Merged { into: first });
+        assert!(!updates[0].notify);
+        assert_eq!(updates[1].change, IncidentChange::Updated);
+
+        let open = correlator.open_incidents();
+        assert_eq!(open.len(), 1);
+        assert_eq!(open[0].timeline.len(), 3);
+        assert_eq!(
+            open[0].components,
+            ["container api", "nvme0", "docker restart api"]
+        );
+    }
+
+    #[test]
+    fn test_notifications_are_rate_limited_per_incident() {
+        let mut correlator = Correlator::new(IncidentsConfig::default());
+        let mut flaps = Vec::new();
+        for minute in 0..10 {
+            flaps.push(health(minute * 2, "sensor nvme0", "warning"));
+        }
+        let updates = run(&mut correlator, flaps);
+        assert_eq!(updates.iter().filter(|u| u.notify).count(), 1);
+
+        // Rising severity is announced at once
+        let updates = run(
+            &mut correlator,
+            vec![health(20, "sensor nvme0", "critical")],
+        );
+        assert!(updates[0].notify);
+        assert_eq!(updates[0].notification().severity, NotifySeverity::Critical);
+
+        // The same severity again waits for the interval
+        let updates = run(
+            &mut correlator,
+            vec![health(30, "sensor nvme0", "critical")],
+        );
+        assert!(!updates[0].notify);
+        let updates = run(
+            &mut correlator,
+            vec![health(50, "sensor nvme0", "critical")],
+        );
+        assert!(updates[0].notify);
+
+        // Below the minimum severity an incident is tracked silently, and its
+        // resolution isn't announced either
+        let mut correlator = Correlator::new(IncidentsConfig::default());
+        let updates = run(
+            &mut correlator,
+            vec![anomaly(0, "gpu", "gpu0", Severity::Info)],
+        );
+        assert_eq!(updates[0].change, IncidentChange::Opened);
+        assert!(!updates[0].notify);
+        let resolved = correlator.tick(at(30));
+        assert_eq!(resolved[0].change, IncidentChange::Resolved);
+        assert!(!resolved[0].notify);
+    }
+
+    #[test]
+    fn test_rules_come_from_config() {
+        let config: IncidentsConfig = toml::from_str(
+            r#"
+            resolve_after_minutes = 180
+
+            [[rules]]
+            name = "finding_before_anomaly"
+            cause = ["security_finding"]
+            effect = ["anomaly"]
+            within_minutes = 120
+            causal = true
+            "#,
+        )
+        .unwrap();
+        assert!(config.enabled);
+        assert_eq!(config.rules.len(), 1);
+
+        let mut correlator = Correlator::new(config);
+        run(
+            &mut correlator,
+            vec![
+                anomaly(0, "security", "sshd", Severity::High),
+                anomaly(90, "auth", "login", Severity::Medium),
+                // The defaults would link these by subject; this config doesn't
+                health(91, "container api", "unhealthy"),
+                health(92, "container api", "critical"),
+            ],
+        );
+        let open = correlator.open_incidents();
+        assert_eq!(open.len(), 3);
+        assert_eq!(open[0].timeline.len(), 2);
+        assert_eq!(
+            open[0].causes,
+            [
+                "security anomaly on sshd (High): out of range (1h 30m before auth anomaly on login (Medium): out of range)"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_log_keeps_open_and_newest_resolved_incidents() {
+        let mut correlator = Correlator::new(IncidentsConfig::default());
+        let mut log = IncidentLog::default();
+        let mut signals = Vec::new();
+        for hour in 0..4 {
+            signals.push(anomaly(
+                hour * 60,
+                "gpu",
+                &format!("gpu{}", hour),
+                Severity::High,
+            ));
+        }
+        signals.push(health(300, "container api", "unhealthy"));
+        for update in run(&mut correlator, signals) {
+            log.apply(&update, 2);
+        }
+
+        let incidents: Vec<&Incident> = log.iter().collect();
+        assert_eq!(incidents.len(), 3);
+        assert!(incidents[0].is_open());
+        assert_eq!(
+            incidents[1].title,
+            "gpu anomaly on gpu3 (High): out of range"
+        );
+        assert_eq!(
+            incidents[2].title,
+            "gpu anomaly on gpu2 (High): out of range"
+        );
+        assert_eq!(log.open().count(), 1);
+
+        let window = log.between(at(130), at(150));
+        assert_eq!(window.len(), 1);
+        assert_eq!(window[0].title, incidents[2].title);
+
+        let id = incidents[0].id.to_string();
+        assert_eq!(log.find(&id[..8]).unwrap().id, incidents[0].id);
+        assert!(log.find("zz").is_err());
+
+        // Open incidents carry over a restart
+        let restored = Correlator::new(IncidentsConfig::default()).with_open(log.iter().cloned());
+        assert_eq!(restored.open_incidents().len(), 1);
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::seconds(45)), "45s");
+        assert_eq!(format_duration(Duration::minutes(12)), "12m");
+        assert_eq!(format_duration(Duration::minutes(185)), "3h 5m");
+        assert_eq!(format_duration(Duration::hours(52)), "2d 4h");
+    }
+}
@@ -0,0 +1,124 @@
+//! User-supplied input for explain, diagnose, and fix
+//!
+//! Collects files given with `--file` and piped stdin into one labelled text
+//! block. Nothing is truncated here; oversized input is condensed later by the
+//! context-window manager.
+
+use anyhow::{Context, Result};
+use std::io::{IsTerminal, Read};
+use std::path::Path;
+
+/// How much of the start of an input is sniffed for binary content
+const SNIFF_BYTES: usize = 8192;
+
+/// One piece of attached input
+#[derive(Debug, Clone)]
+pub struct InputSource {
+    /// File path, or "stdin"
+    pub label: String,
+    pub content: String,
+}
+
+impl InputSource {
+    pub fn from_bytes(label: impl Into<String>, bytes: Vec<u8>) -> Result<Self> {
+        let label = label.into();
+        if looks_binary(&bytes) {
+            return Err(anyhow::anyhow!(
+                "{} looks like a binary file; pass text such as a log, config, or command output \
+                 (for journals use `journalctl --file ... | jarvis explain`)",
+                label
+            ));
+        }
+        Ok(Self {
+            label,
+            content: String::from_utf8_lossy(&bytes).into_owned(),
+        })
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let bytes =
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::from_bytes(path.display().to_string(), bytes)
+    }
+}
+
+/// True when stdin is redirected from a pipe or file rather than a terminal
+pub fn stdin_is_piped() -> bool {
+    !std::io::stdin().is_terminal()
+}
+
+pub fn read_stdin() -> Result<Option<InputSource>> {
+    let mut bytes = Vec::new();
+    std::io::stdin()
+        .read_to_end(&mut bytes)
+        .context("Failed to read stdin")?;
+    // e.g. stdin is /dev/null under cron or systemd
+    if bytes.iter().all(|b| b.is_ascii_whitespace()) {
+        return Ok(None);
+    }
+    InputSource::from_bytes("stdin", bytes).map(Some)
+}
+
+/// Gather `--file` paths plus stdin when asked for (`-`) or piped.
+/// Returns `None` when there is nothing attached.
+pub fn collect(files: &[impl AsRef<Path>], want_stdin: bool) -> Result<Option<String>> {
+    let mut sources = Vec::new();
+    for path in files {
+        sources.push(InputSource::from_file(path.as_ref())?);
+    }
+    if want_stdin {
+        if let Some(stdin) = read_stdin()? {
+            sources.push(stdin);
+        }
+    }
+
+    Ok((!sources.is_empty()).then(|| combine(&sources)))
+}
+
+/// Join sources into one block, each introduced by its label
+pub fn combine(sources: &[InputSource]) -> String {
+    if let [only] = sources {
+        return format!("--- {} ---\n{}", only.label, only.content);
+    }
+    sources
+        .iter()
+        .map(|s| format!("--- {} ---\n{}", s.label, s.content.trim_end()))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// NUL bytes or mostly invalid UTF-8 in the first few KB
+fn looks_binary(bytes: &[u8]) -> bool {
+    let sample = &bytes[..bytes.len().min(SNIFF_BYTES)];
+    if sample.contains(&0) {
+        return true;
+    }
+    let invalid = String::from_utf8_lossy(sample).matches('\u{FFFD}').count();
+    invalid * 10 > sample.len().max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binary_detection() {
+        assert!(!looks_binary(b"Oct 01 nginx[123]: bind() failed\n"));
+        assert!(!looks_binary("naïve — ünïcode".as_bytes()));
+        assert!(looks_binary(b"\x7fELF\x02\x01\x01\x00\x00"));
+        assert!(looks_binary(&[0xff, 0xfe, 0xfd, 0xfc]));
+        assert!(InputSource::from_bytes("a.out", b"\x00\x01".to_vec()).is_err());
+    }
+
+    #[test]
+    fn test_combine_labels_each_source() {
+        let sources = vec![
+            InputSource::from_bytes("/var/log/a.log", b"first\n".to_vec()).unwrap(),
+            InputSource::from_bytes("stdin", b"second\n".to_vec()).unwrap(),
+        ];
+        assert_eq!(
+            combine(&sources),
+            "--- /var/log/a.log ---\nfirst\n\n--- stdin ---\nsecond"
+        );
+    }
+}
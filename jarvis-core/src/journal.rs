@@ -0,0 +1,287 @@
+//! Structured journal records of the changes Jarvis makes
+//!
+//! Every mutating action (a package transaction, service operation, file
+//! write, or executed command) is logged as one journal entry carrying
+//! machine-readable `JARVIS_*` fields, so an audit is a `journalctl` query:
+//!
+//! ```text
+//! journalctl SYSLOG_IDENTIFIER=jarvis JARVIS_OPERATION_TYPE=package_transaction
+//! ```
+//!
+//! MCP tool calls reach this through the audit pipeline in
+//! [`crate::mcp::guard`]; the Arch agent emits for its own operations. Off
+//! systemd, records go to stderr as `KEY=value` pairs instead.
+
+use std::fmt;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+use crate::privilege::Elevation;
+use crate::types::AuditStatus;
+
+const SYSLOG_IDENTIFIER: &str = "jarvis";
+
+/// Where journald listens for native-protocol entries
+const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+
+/// What kind of change an action made
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionType {
+    PackageTransaction,
+    ServiceOperation,
+    FileWrite,
+    Command,
+}
+
+impl fmt::Display for ActionType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ActionType::PackageTransaction => "package_transaction",
+            ActionType::ServiceOperation => "service_operation",
+            ActionType::FileWrite => "file_write",
+            ActionType::Command => "command",
+        })
+    }
+}
+
+/// One mutating action, as written to the journal
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActionRecord {
+    /// Shared with the MCP audit log entry or operation the action belongs to
+    pub operation_id: String,
+    pub operation_type: ActionType,
+    /// What was done, e.g. `install` or `restart`; only used in the message
+    pub action: String,
+    /// What it was done to: a package, unit, path, or command line
+    pub target: String,
+    pub result: AuditStatus,
+    pub dry_run: bool,
+    /// How the action got root, for actions that needed it
+    pub privilege: Option<Elevation>,
+}
+
+impl ActionRecord {
+    pub fn new(
+        operation_id: impl Into<String>,
+        operation_type: ActionType,
+        action: impl Into<String>,
+        target: impl Into<String>,
+    ) -> Self {
+        Self {
+            operation_id: operation_id.into(),
+            operation_type,
+            action: action.into(),
+            target: target.into(),
+            result: AuditStatus::Success,
+            dry_run: false,
+            privilege: None,
+        }
+    }
+
+    pub fn with_result(mut self, result: AuditStatus) -> Self {
+        self.result = result;
+        self
+    }
+
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn with_privilege(mut self, privilege: Elevation) -> Self {
+        self.privilege = Some(privilege);
+        self
+    }
+
+    /// Human-readable summary for the journal's MESSAGE field
+    pub fn message(&self) -> String {
+        format!(
+            "{}{} {} {}: {}",
+            if self.dry_run { "[dry run] " } else { "" },
+            self.operation_type,
+            self.action,
+            self.target,
+            self.result
+        )
+    }
+
+    /// Journal fields in the order they are written
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        let priority = match self.result {
+            AuditStatus::Success => 6,
+            AuditStatus::Error | AuditStatus::RateLimited => 4,
+        };
+        let mut fields = vec![
+            ("MESSAGE", self.message()),
+            ("PRIORITY", priority.to_string()),
+            ("SYSLOG_IDENTIFIER", SYSLOG_IDENTIFIER.to_string()),
+            ("JARVIS_OPERATION_ID", self.operation_id.clone()),
+            ("JARVIS_OPERATION_TYPE", self.operation_type.to_string()),
+            ("JARVIS_TARGET", self.target.clone()),
+            ("JARVIS_RESULT", self.result.to_string()),
+            ("JARVIS_DRYRUN", self.dry_run.to_string()),
+        ];
+        if let Some(privilege) = &self.privilege {
+            fields.push(("JARVIS_PRIVILEGE", privilege.strategy.to_string()));
+            fields.push(("JARVIS_ELEVATED", privilege.elevated.to_string()));
+        }
+        fields
+    }
+}
+
+/// Format `record` as one line of `KEY=value` pairs. Values with spaces,
+/// quotes, or `=` are quoted, and newlines are escaped, so each record stays
+/// on one line.
+pub fn format_line(record: &ActionRecord) -> String {
+    record
+        .fields()
+        .into_iter()
+        .map(|(key, value)| {
+            if value.is_empty()
+                || value.contains(|c: char| c.is_whitespace() || c == '"' || c == '=')
+            {
+                format!("{}={:?}", key, value)
+            } else {
+                format!("{}={}", key, value)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+enum Sink {
+    Journald,
+    Writer(Mutex<Box<dyn Write + Send>>),
+}
+
+/// Writes action records to journald, or to a stream where there's none
+pub struct JournalEmitter {
+    sink: Sink,
+}
+
+impl JournalEmitter {
+    /// journald when its socket exists, stderr otherwise
+    pub fn detect() -> Self {
+        if Path::new(JOURNAL_SOCKET).exists() {
+            Self {
+                sink: Sink::Journald,
+            }
+        } else {
+            Self::to_writer(io::stderr())
+        }
+    }
+
+    /// Write each record to `writer` as a [`format_line`] line
+    pub fn to_writer(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            sink: Sink::Writer(Mutex::new(Box::new(writer))),
+        }
+    }
+
+    pub fn emit(&self, record: &ActionRecord) {
+        match &self.sink {
+            Sink::Journald => {
+                let fields: Vec<String> = record
+                    .fields()
+                    .into_iter()
+                    .map(|(key, value)| format!("{}={}", key, value))
+                    .collect();
+                let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+                let status = systemd::journal::send(&fields);
+                if status < 0 {
+                    tracing::warn!(
+                        "Failed to write {} to the journal (errno {})",
+                        record.operation_id,
+                        -status
+                    );
+                }
+            }
+            Sink::Writer(writer) => {
+                let mut writer = writer.lock().unwrap_or_else(|e| e.into_inner());
+                if let Err(e) = writeln!(writer, "jarvis-action: {}", format_line(record)) {
+                    tracing::warn!(
+                        "Failed to write action record {}: {}",
+                        record.operation_id,
+                        e
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// The process-wide emitter, chosen on first use
+pub fn emitter() -> &'static JournalEmitter {
+    static EMITTER: OnceLock<JournalEmitter> = OnceLock::new();
+    EMITTER.get_or_init(JournalEmitter::detect)
+}
+
+/// Record one mutating action through the process-wide emitter
+pub fn emit(record: &ActionRecord) {
+    emitter().emit(record);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_stderr_fallback_fields() {
+        let captured = Captured::default();
+        let emitter = JournalEmitter::to_writer(captured.clone());
+
+        emitter.emit(
+            &ActionRecord::new(
+                "op-1",
+                ActionType::PackageTransaction,
+                "install",
+                "linux-zen",
+            )
+            .with_dry_run(true),
+        );
+        emitter.emit(
+            &ActionRecord::new("op-2", ActionType::Command, "run", "paccache -rk2")
+                .with_result(AuditStatus::Error),
+        );
+        emitter.emit(
+            &ActionRecord::new("op-3", ActionType::ServiceOperation, "restart", "nginx")
+                .with_privilege(Elevation {
+                    strategy: crate::privilege::Strategy::Sudo,
+                    capabilities: vec![crate::privilege::Capability::Services],
+                    elevated: true,
+                }),
+        );
+
+        let output = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines[0],
+            "jarvis-action: MESSAGE=\"[dry run] package_transaction install linux-zen: success\" \
+             PRIORITY=6 SYSLOG_IDENTIFIER=jarvis JARVIS_OPERATION_ID=op-1 \
+             JARVIS_OPERATION_TYPE=package_transaction JARVIS_TARGET=linux-zen \
+             JARVIS_RESULT=success JARVIS_DRYRUN=true"
+        );
+        assert!(lines[1].contains(" PRIORITY=4 "));
+        assert!(lines[1].contains(" JARVIS_TARGET=\"paccache -rk2\" "));
+        assert!(lines[1].ends_with(" JARVIS_RESULT=error JARVIS_DRYRUN=false"));
+        assert!(
+            lines[2].ends_with(" JARVIS_DRYRUN=false JARVIS_PRIVILEGE=sudo JARVIS_ELEVATED=true")
+        );
+    }
+}
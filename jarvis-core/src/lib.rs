@@ -1,22 +1,80 @@
+pub mod aur_comments;
 pub mod blockchain_agents;
+pub mod bus;
+pub mod chat;
+pub mod chat_actions;
 pub mod config;
+pub mod config_files;
+pub mod config_validation;
+pub mod docker_maintenance;
 pub mod error;
+pub mod exec;
+pub mod file_access;
+pub mod flatpak;
+pub mod fleet;
+pub mod gpu;
+pub mod health_checks;
+pub mod host_profile;
+pub mod idempotency;
+pub mod incidents;
 pub mod grpc_client;
+pub mod input;
+pub mod journal;
 pub mod llm;
+pub mod log_clusters;
+pub mod log_follow;
 pub mod mcp;
 pub mod maintenance_agents;
 pub mod memory;
+pub mod memory_recovery;
+pub mod metrics;
+pub mod net;
 pub mod nlp;
+pub mod plugins;
+pub mod notify;
+pub mod package_cache;
+pub mod package_files;
+pub mod pacman_lock;
+pub mod operations;
+pub mod power;
+pub mod preflight;
+pub mod privilege;
+pub mod read_only;
+pub mod remedies;
+pub mod remote;
+pub mod report;
+pub mod scaffold;
+pub mod secrets;
+pub mod self_update;
+pub mod sensors;
+pub mod severity;
+pub mod snapshots;
 pub mod specialized_agents;
+pub mod ssh_pool;
+pub mod time_range;
+pub mod tls;
+pub mod trace;
+pub mod trivy;
 pub mod types;
+pub mod unit_drift;
+pub mod vuln;
 
 pub use blockchain_agents::BlockchainAgent;
+pub use chat::ChatSession;
 pub use config::Config;
 pub use error::{JarvisError, JarvisResult};
+pub use exec::{CommandRunner, RecordingRunner, RunOptions, SystemRunner};
+pub use fleet::{FleetReport, HostHealth, HostStatus};
 pub use grpc_client::GhostChainClient;
 pub use llm::{Intent, LLMRouter, OllamaClient, OmenClient};
 pub use maintenance_agents::*;
 pub use memory::MemoryStore;
-pub use nlp::{CommandIntent, CommandParser, ParsedCommand};
+pub use nlp::{CommandIntent, CommandParser, ParsedCommand, PreparedCommand};
+pub use notify::{Notification, Notifier, NotifyEvent, NotifySeverity};
+pub use power::{PowerAction, PowerOutcome};
+pub use preflight::{PackageAction, PreflightReport};
+pub use remote::{CommandExecutor, SshTarget};
+pub use time_range::{AmbiguousTime, TimeRange};
+pub use report::{ReportData, ReportWindow};
 pub use specialized_agents::*;
 pub use types::*;
@@ -0,0 +1,426 @@
+//! Record and replay of LLM calls for deterministic tests
+//!
+//! Point `JARVIS_LLM_CASSETTE` at a file (or call [`LLMRouter::with_cassette`])
+//! and the router serves every generation from it instead of a backend. With
+//! `JARVIS_LLM_RECORD=1` it calls the real backend and writes each request and
+//! response to the file instead, replacing what was there.
+//!
+//! Requests are keyed by a hash of the operation, model, intent, and prompt, so
+//! any change to a prompt shows up as a miss rather than a stale answer.
+//! Compiled only for tests and the `llm-replay` feature.
+//!
+//! [`LLMRouter::with_cassette`]: super::LLMRouter::with_cassette
+
+use crate::JarvisError;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use futures::stream::{BoxStream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Cassette file to serve LLM responses from
+pub const CASSETTE_ENV: &str = "JARVIS_LLM_CASSETTE";
+/// Set to `1` to record the cassette from a live backend
+pub const RECORD_ENV: &str = "JARVIS_LLM_RECORD";
+
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    Record,
+    Replay,
+}
+
+impl CassetteMode {
+    /// Record when `JARVIS_LLM_RECORD=1`, replay otherwise
+    pub fn from_env() -> Self {
+        match std::env::var(RECORD_ENV).as_deref() {
+            Ok("1") | Ok("true") => CassetteMode::Record,
+            _ => CassetteMode::Replay,
+        }
+    }
+}
+
+/// What identifies a call; two calls with equal requests get the same answer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CassetteRequest {
+    pub operation: String,
+    pub model: String,
+    pub intent: Option<String>,
+    pub prompt: String,
+}
+
+impl CassetteRequest {
+    /// MD5 of the request's JSON, whose field order is fixed by the struct
+    pub fn key(&self) -> String {
+        let json = serde_json::to_string(self).expect("request serializes");
+        format!("{:x}", md5::compute(json))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RecordedResponse {
+    Text {
+        text: String,
+    },
+    /// A streamed response, chunk by chunk as the backend sent it
+    Chunks {
+        chunks: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InteractionMetadata {
+    pub recorded_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub provider: String,
+    pub jarvis_version: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    pub key: String,
+    pub request: CassetteRequest,
+    pub response: RecordedResponse,
+    pub metadata: InteractionMetadata,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CassetteFile {
+    version: u32,
+    interactions: Vec<Interaction>,
+}
+
+#[derive(Default)]
+struct State {
+    file: CassetteFile,
+    /// Times each key was served; the nth call gets the nth recording, and
+    /// calls past the last recording get the last one again
+    served: HashMap<String, usize>,
+}
+
+pub struct Cassette {
+    path: PathBuf,
+    mode: CassetteMode,
+    provider: String,
+    state: Mutex<State>,
+}
+
+impl Cassette {
+    /// The cassette `JARVIS_LLM_CASSETTE` names, if it is set
+    pub fn from_env(provider: &str) -> Result<Option<Self>> {
+        match std::env::var_os(CASSETTE_ENV) {
+            Some(path) => Self::open(path, provider).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Replay `path`, or start recording over it when `JARVIS_LLM_RECORD=1`
+    pub fn open(path: impl Into<PathBuf>, provider: &str) -> Result<Self> {
+        let path = path.into();
+        let mode = CassetteMode::from_env();
+        let file = match mode {
+            CassetteMode::Record => CassetteFile {
+                version: FORMAT_VERSION,
+                interactions: Vec::new(),
+            },
+            CassetteMode::Replay => load(&path)?,
+        };
+        Ok(Self {
+            path,
+            mode,
+            provider: provider.to_string(),
+            state: Mutex::new(State {
+                file,
+                served: HashMap::new(),
+            }),
+        })
+    }
+
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Answer `request` from the cassette, or from `live` while recording
+    pub async fn text<F, Fut>(&self, request: CassetteRequest, live: F) -> Result<String>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<String>>,
+    {
+        match self.mode {
+            CassetteMode::Replay => match self.replay(&request)? {
+                RecordedResponse::Text { text } => Ok(text),
+                RecordedResponse::Chunks { chunks } => Ok(chunks.concat()),
+            },
+            CassetteMode::Record => {
+                let started = Instant::now();
+                let text = live().await?;
+                self.record(
+                    request,
+                    RecordedResponse::Text { text: text.clone() },
+                    started,
+                )?;
+                Ok(text)
+            }
+        }
+    }
+
+    /// Stream `request`'s chunks from the cassette, or pass `live`'s through
+    /// while recording them. A stream that fails part way is not recorded.
+    pub async fn stream<F, Fut>(
+        self: &Arc<Self>,
+        request: CassetteRequest,
+        live: F,
+    ) -> Result<BoxStream<'static, Result<String>>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<BoxStream<'static, Result<String>>>>,
+    {
+        if self.mode == CassetteMode::Replay {
+            let chunks = match self.replay(&request)? {
+                RecordedResponse::Chunks { chunks } => chunks,
+                RecordedResponse::Text { text } => vec![text],
+            };
+            return Ok(futures::stream::iter(chunks.into_iter().map(Ok)).boxed());
+        }
+
+        let started = Instant::now();
+        let seen: Arc<Mutex<Option<Vec<String>>>> = Arc::new(Mutex::new(Some(Vec::new())));
+        let tap = seen.clone();
+        let live = live().await?.inspect(move |chunk| {
+            let mut seen = tap.lock().unwrap();
+            match chunk {
+                Ok(chunk) => {
+                    if let Some(chunks) = seen.as_mut() {
+                        chunks.push(chunk.clone());
+                    }
+                }
+                Err(_) => *seen = None,
+            }
+        });
+
+        let cassette = self.clone();
+        let finish = futures::stream::once(async move {
+            let chunks = seen.lock().unwrap().take();
+            match chunks {
+                Some(chunks) => {
+                    cassette.record(request, RecordedResponse::Chunks { chunks }, started)
+                }
+                None => Ok(()),
+            }
+        })
+        .filter_map(|recorded| async move { recorded.err().map(Err) });
+
+        Ok(live.chain(finish).boxed())
+    }
+
+    fn replay(&self, request: &CassetteRequest) -> Result<RecordedResponse> {
+        let key = request.key();
+        let mut state = self.state.lock().unwrap();
+        let recorded: Vec<&Interaction> = state
+            .file
+            .interactions
+            .iter()
+            .filter(|interaction| interaction.key == key)
+            .collect();
+        if recorded.is_empty() {
+            return Err(self.miss(request, &key).into());
+        }
+        let served = state.served.get(&key).copied().unwrap_or(0);
+        let response = recorded[served.min(recorded.len() - 1)].response.clone();
+        state.served.insert(key, served + 1);
+        Ok(response)
+    }
+
+    fn miss(&self, request: &CassetteRequest, key: &str) -> JarvisError {
+        let excerpt: String = request.prompt.chars().take(120).collect();
+        JarvisError::llm(
+            "cassette",
+            format!(
+                "no recorded response for {} on {} (key {}) in {}. The prompt or model \
+                 changed since the cassette was recorded; re-record it against a live \
+                 backend with {}=1 {}={} and commit the result. Prompt begins: {:?}",
+                request.operation,
+                request.model,
+                key,
+                self.path.display(),
+                RECORD_ENV,
+                CASSETTE_ENV,
+                self.path.display(),
+                excerpt
+            ),
+        )
+    }
+
+    /// Add an interaction and rewrite the file, so an aborted test run still
+    /// leaves what it recorded
+    fn record(
+        &self,
+        request: CassetteRequest,
+        response: RecordedResponse,
+        started: Instant,
+    ) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.file.interactions.push(Interaction {
+            key: request.key(),
+            request,
+            response,
+            metadata: InteractionMetadata {
+                recorded_at: Utc::now(),
+                duration_ms: started.elapsed().as_millis() as u64,
+                provider: self.provider.clone(),
+                jarvis_version: env!("CARGO_PKG_VERSION").to_string(),
+            },
+        });
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let json = serde_json::to_string_pretty(&state.file)?;
+        std::fs::write(&self.path, json + "\n")
+            .with_context(|| format!("Failed to write cassette {}", self.path.display()))
+    }
+}
+
+fn load(path: &Path) -> Result<CassetteFile> {
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        JarvisError::llm(
+            "cassette",
+            format!(
+                "cannot read cassette {}: {}. Record it against a live backend with {}=1 {}={}",
+                path.display(),
+                e,
+                RECORD_ENV,
+                CASSETTE_ENV,
+                path.display()
+            ),
+        )
+    })?;
+    let file: CassetteFile = serde_json::from_str(&content)
+        .with_context(|| format!("Invalid cassette {}", path.display()))?;
+    if file.version != FORMAT_VERSION {
+        anyhow::bail!(
+            "Cassette {} is format version {}, expected {}; re-record it",
+            path.display(),
+            file.version,
+            FORMAT_VERSION
+        );
+    }
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(prompt: &str) -> CassetteRequest {
+        CassetteRequest {
+            operation: "generate".to_string(),
+            model: "llama3.1:8b".to_string(),
+            intent: None,
+            prompt: prompt.to_string(),
+        }
+    }
+
+    async fn offline() -> Result<String> {
+        panic!("replay must not call the backend")
+    }
+
+    async fn offline_stream() -> Result<BoxStream<'static, Result<String>>> {
+        panic!("replay must not call the backend")
+    }
+
+    fn recording(path: &Path) -> Arc<Cassette> {
+        Arc::new(Cassette {
+            path: path.to_path_buf(),
+            mode: CassetteMode::Record,
+            provider: "ollama".to_string(),
+            state: Mutex::new(State::default()),
+        })
+    }
+
+    #[test]
+    fn test_key_is_stable() {
+        // Cassettes on disk depend on this exact hash
+        assert_eq!(
+            serde_json::to_string(&request("hi")).unwrap(),
+            r#"{"operation":"generate","model":"llama3.1:8b","intent":null,"prompt":"hi"}"#
+        );
+        assert_eq!(
+            request("hi").key(),
+            format!(
+                "{:x}",
+                md5::compute(
+                    r#"{"operation":"generate","model":"llama3.1:8b","intent":null,"prompt":"hi"}"#
+                )
+            )
+        );
+        assert_ne!(request("hi").key(), request("hi!").key());
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay_text_and_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cassette.json");
+
+        let recorder = recording(&path);
+        let text = recorder
+            .text(request("first"), || async { Ok("one".to_string()) })
+            .await
+            .unwrap();
+        assert_eq!(text, "one");
+        recorder
+            .text(request("first"), || async { Ok("two".to_string()) })
+            .await
+            .unwrap();
+        let chunks: Vec<String> = recorder
+            .stream(request("streamed"), || async {
+                let live = futures::stream::iter(["a", "b", "c"].map(|c| Ok(c.to_string())));
+                Ok(live.boxed())
+            })
+            .await
+            .unwrap()
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+        assert_eq!(chunks, vec!["a", "b", "c"]);
+
+        let player = Arc::new(Cassette {
+            path: path.clone(),
+            mode: CassetteMode::Replay,
+            provider: "ollama".to_string(),
+            state: Mutex::new(State {
+                file: load(&path).unwrap(),
+                served: HashMap::new(),
+            }),
+        });
+        // Repeated requests get their recordings in order, then the last again
+        assert_eq!(player.text(request("first"), offline).await.unwrap(), "one");
+        assert_eq!(player.text(request("first"), offline).await.unwrap(), "two");
+        assert_eq!(player.text(request("first"), offline).await.unwrap(), "two");
+
+        let replayed: Vec<String> = player
+            .stream(request("streamed"), offline_stream)
+            .await
+            .unwrap()
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+        assert_eq!(replayed, vec!["a", "b", "c"]);
+
+        let err = player.text(request("changed"), offline).await.unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains(RECORD_ENV), "{}", message);
+        assert!(message.contains(&request("changed").key()), "{}", message);
+    }
+}
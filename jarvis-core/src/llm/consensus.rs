@@ -0,0 +1,313 @@
+//! Multi-backend consensus generation
+//!
+//! Sends the same prompt to two backends concurrently and either returns both
+//! answers or merges them with an arbitration prompt that calls out where they
+//! disagree. Intended for destructive recommendations where a second opinion
+//! is worth the extra latency.
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+use super::context_window::estimate_tokens;
+use super::{Intent, LLMRouter};
+use crate::config::ConsensusStrategy;
+
+/// A backend that can take part in consensus generation
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConsensusBackend {
+    Omen,
+    Ollama(String),
+}
+
+impl ConsensusBackend {
+    pub fn label(&self) -> String {
+        match self {
+            ConsensusBackend::Omen => "omen".to_string(),
+            ConsensusBackend::Ollama(model) => format!("ollama:{}", model),
+        }
+    }
+}
+
+/// One backend's answer (or failure) in a consensus request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackendAnswer {
+    pub backend: String,
+    pub answer: Option<String>,
+    pub error: Option<String>,
+    pub duration_ms: u64,
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+}
+
+/// Accumulated token usage for a single backend
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BackendUsage {
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsensusResult {
+    pub strategy: ConsensusStrategy,
+    pub answers: Vec<BackendAnswer>,
+    /// Arbitrated answer, present for `ConsensusStrategy::Arbitrate`
+    pub merged: Option<String>,
+}
+
+impl ConsensusResult {
+    /// Render the result for terminal output
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        if let Some(merged) = &self.merged {
+            out.push_str(merged);
+            out.push_str("\n\n");
+            out.push_str("Sources: ");
+            let labels: Vec<String> = self
+                .answers
+                .iter()
+                .map(|a| match &a.error {
+                    Some(_) => format!("{} (failed)", a.backend),
+                    None => format!("{} ({} ms)", a.backend, a.duration_ms),
+                })
+                .collect();
+            out.push_str(&labels.join(", "));
+            return out;
+        }
+
+        for answer in &self.answers {
+            out.push_str(&format!("=== {} ({} ms) ===\n", answer.backend, answer.duration_ms));
+            match (&answer.answer, &answer.error) {
+                (Some(text), _) => out.push_str(text),
+                (None, Some(error)) => out.push_str(&format!("⚠️ No answer: {}", error)),
+                (None, None) => out.push_str("⚠️ No answer"),
+            }
+            out.push_str("\n\n");
+        }
+        out
+    }
+}
+
+impl LLMRouter {
+    /// Backends available for consensus, in preference order
+    pub fn consensus_backends(&self) -> Vec<ConsensusBackend> {
+        let mut backends = Vec::new();
+
+        if self.omen_client.is_some() {
+            backends.push(ConsensusBackend::Omen);
+        }
+        if self.ollama_client.is_some() {
+            backends.push(ConsensusBackend::Ollama(self.default_model.clone()));
+
+            if let Some(secondary) = &self.consensus.secondary_model {
+                if secondary != &self.default_model {
+                    backends.push(ConsensusBackend::Ollama(secondary.clone()));
+                }
+            }
+        }
+
+        backends
+    }
+
+    /// Ask two backends concurrently using the configured strategy
+    pub async fn generate_with_consensus(
+        &self,
+        prompt: &str,
+        intent: Intent,
+    ) -> anyhow::Result<ConsensusResult> {
+        self.generate_with_consensus_strategy(prompt, intent, self.consensus.strategy)
+            .await
+    }
+
+    /// Ask two backends concurrently and combine their answers with `strategy`
+    pub async fn generate_with_consensus_strategy(
+        &self,
+        prompt: &str,
+        intent: Intent,
+        strategy: ConsensusStrategy,
+    ) -> anyhow::Result<ConsensusResult> {
+        let backends = self.consensus_backends();
+        if backends.len() < 2 {
+            anyhow::bail!(
+                "Consensus mode needs two backends; enable Omen or set llm.consensus.secondary_model"
+            );
+        }
+
+        let timeout = Duration::from_secs(self.consensus.timeout_secs);
+        let (first, second) = tokio::join!(
+            self.answer_from(&backends[0], prompt, intent, timeout),
+            self.answer_from(&backends[1], prompt, intent, timeout),
+        );
+        let answers = vec![first, second];
+
+        if answers.iter().all(|a| a.answer.is_none()) {
+            let errors: Vec<String> = answers
+                .iter()
+                .map(|a| format!("{}: {}", a.backend, a.error.clone().unwrap_or_default()))
+                .collect();
+            anyhow::bail!("All consensus backends failed: {}", errors.join("; "));
+        }
+
+        let merged = match strategy {
+            ConsensusStrategy::Both => None,
+            ConsensusStrategy::Arbitrate => Some(self.arbitrate(prompt, &answers).await?),
+        };
+
+        Ok(ConsensusResult {
+            strategy,
+            answers,
+            merged,
+        })
+    }
+
+    /// Token usage attributed to each backend since this router was created
+    pub fn usage_by_backend(&self) -> std::collections::HashMap<String, BackendUsage> {
+        self.usage
+            .lock()
+            .map(|usage| usage.clone())
+            .unwrap_or_default()
+    }
+
+    pub(super) fn record_usage(&self, backend: &str, prompt_tokens: usize, completion_tokens: usize) {
+        if let Ok(mut usage) = self.usage.lock() {
+            let entry = usage.entry(backend.to_string()).or_default();
+            entry.requests += 1;
+            entry.prompt_tokens += prompt_tokens as u64;
+            entry.completion_tokens += completion_tokens as u64;
+        }
+    }
+
+    async fn answer_from(
+        &self,
+        backend: &ConsensusBackend,
+        prompt: &str,
+        intent: Intent,
+        timeout: Duration,
+    ) -> BackendAnswer {
+        let label = backend.label();
+        let prompt_tokens = estimate_tokens(prompt);
+        let start = Instant::now();
+
+        let result = tokio::time::timeout(timeout, self.generate_on(backend, prompt, intent)).await;
+        let duration_ms = start.elapsed().as_millis() as u64;
+
+        match result {
+            Ok(Ok(answer)) => {
+                let completion_tokens = estimate_tokens(&answer);
+                self.record_usage(&label, prompt_tokens, completion_tokens);
+                BackendAnswer {
+                    backend: label,
+                    answer: Some(answer),
+                    error: None,
+                    duration_ms,
+                    prompt_tokens,
+                    completion_tokens,
+                }
+            }
+            Ok(Err(e)) => BackendAnswer {
+                backend: label,
+                answer: None,
+                error: Some(e.to_string()),
+                duration_ms,
+                prompt_tokens,
+                completion_tokens: 0,
+            },
+            Err(_) => {
+                tracing::warn!("Consensus backend {} timed out after {:?}", label, timeout);
+                BackendAnswer {
+                    backend: label,
+                    answer: None,
+                    error: Some(format!("timed out after {}s", timeout.as_secs())),
+                    duration_ms,
+                    prompt_tokens,
+                    completion_tokens: 0,
+                }
+            }
+        }
+    }
+
+    async fn generate_on(
+        &self,
+        backend: &ConsensusBackend,
+        prompt: &str,
+        intent: Intent,
+    ) -> anyhow::Result<String> {
+        self.recorded("consensus", &backend.label(), Some(intent), prompt, || {
+            self.generate_on_live(backend, prompt, intent)
+        })
+        .await
+    }
+
+    async fn generate_on_live(
+        &self,
+        backend: &ConsensusBackend,
+        prompt: &str,
+        intent: Intent,
+    ) -> anyhow::Result<String> {
+        match backend {
+            ConsensusBackend::Omen => {
+                let omen = self
+                    .omen_client
+                    .as_ref()
+                    .ok_or_else(|| anyhow::anyhow!("Omen is not configured"))?;
+                match intent {
+                    Intent::Code => omen.code(prompt).await,
+                    Intent::System => omen.system(prompt).await,
+                    Intent::DevOps => omen.devops(prompt).await,
+                    Intent::Reason => omen.reason(prompt).await,
+                }
+            }
+            ConsensusBackend::Ollama(model) => {
+                let ollama = self
+                    .ollama_client
+                    .as_ref()
+                    .ok_or_else(|| anyhow::anyhow!("Ollama is not configured"))?;
+                self.check_prompt_fits(model, prompt)?;
+                match intent {
+                    Intent::Code => ollama.code(model, prompt, Some(0.7)).await,
+                    Intent::System => ollama.system(model, prompt, Some(0.7)).await,
+                    Intent::DevOps => ollama.devops(model, prompt, Some(0.7)).await,
+                    Intent::Reason => ollama.complete(model, prompt, Some(0.8)).await,
+                }
+            }
+        }
+    }
+
+    async fn arbitrate(&self, prompt: &str, answers: &[BackendAnswer]) -> anyhow::Result<String> {
+        let successful: Vec<&BackendAnswer> = answers.iter().filter(|a| a.answer.is_some()).collect();
+
+        // Nothing to arbitrate when only one backend answered
+        if successful.len() == 1 {
+            let only = successful[0];
+            return Ok(format!(
+                "{}\n\n⚠️ Only {} answered; no second opinion was available.",
+                only.answer.clone().unwrap_or_default(),
+                only.backend
+            ));
+        }
+
+        let mut arbitration = format!(
+            "Two assistants answered the same request. Merge them into a single answer. \
+             Prefer the safer option where they differ, and finish with a section titled \
+             'Disagreements' listing every point where they conflict (or 'None').\n\n\
+             Request:\n{}\n",
+            prompt
+        );
+        for answer in &successful {
+            arbitration.push_str(&format!(
+                "\n--- Answer from {} ---\n{}\n",
+                answer.backend,
+                answer.answer.clone().unwrap_or_default()
+            ));
+        }
+
+        let merged = self.generate_with_intent(&arbitration, Intent::Reason).await?;
+        self.record_usage(
+            "arbiter",
+            estimate_tokens(&arbitration),
+            estimate_tokens(&merged),
+        );
+        Ok(merged)
+    }
+}
@@ -0,0 +1,360 @@
+//! Context Window Management
+//!
+//! Keeps prompts inside the backend's context window. Oversized input is split
+//! into chunks, each chunk is summarized by the LLM (map), and the final prompt
+//! is built from those summaries plus the chunks most relevant to the query
+//! (reduce).
+
+use anyhow::Result;
+
+use super::LLMRouter;
+
+/// Rough characters-per-token ratio for English text and logs
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Estimate the token count of `text`.
+///
+/// Uses a BPE-style heuristic: roughly four characters per token, with
+/// punctuation and symbols counted individually since tokenizers rarely merge
+/// them. Intentionally errs on the high side.
+pub fn estimate_tokens(text: &str) -> usize {
+    let mut word_chars = 0usize;
+    let mut symbols = 0usize;
+
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            word_chars += 1;
+        } else if !c.is_whitespace() {
+            symbols += 1;
+        }
+    }
+
+    word_chars.div_ceil(CHARS_PER_TOKEN) + symbols
+}
+
+/// Split `text` into chunks of at most `max_tokens`, preferring line boundaries
+pub fn chunk_text(text: &str, max_tokens: usize) -> Vec<String> {
+    let max_tokens = max_tokens.max(1);
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut current_tokens = 0;
+
+    for line in text.lines() {
+        let line_tokens = estimate_tokens(line) + 1;
+
+        // A single line longer than a chunk is split on character boundaries
+        if line_tokens > max_tokens {
+            if !current.is_empty() {
+                chunks.push(std::mem::take(&mut current));
+                current_tokens = 0;
+            }
+            let chars: Vec<char> = line.chars().collect();
+            for piece in chars.chunks(max_tokens * CHARS_PER_TOKEN) {
+                chunks.push(piece.iter().collect());
+            }
+            continue;
+        }
+
+        if current_tokens + line_tokens > max_tokens && !current.is_empty() {
+            chunks.push(std::mem::take(&mut current));
+            current_tokens = 0;
+        }
+
+        current.push_str(line);
+        current.push('\n');
+        current_tokens += line_tokens;
+    }
+
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+
+    chunks
+}
+
+/// Cut `text` down to roughly `max_tokens`
+pub fn truncate_to_tokens(text: &str, max_tokens: usize) -> String {
+    if estimate_tokens(text) <= max_tokens {
+        return text.to_string();
+    }
+
+    let mut out = String::new();
+    for c in text.chars() {
+        out.push(c);
+        if estimate_tokens(&out) >= max_tokens {
+            break;
+        }
+    }
+    out.push_str("\n[...truncated]");
+    out
+}
+
+/// Result of fitting content into the context window
+#[derive(Debug, Clone)]
+pub struct FittedContext {
+    pub content: String,
+    pub original_tokens: usize,
+    pub final_tokens: usize,
+    pub summarized_chunks: usize,
+    pub verbatim_chunks: usize,
+    pub dropped_chunks: usize,
+}
+
+/// Fits large inputs into a provider's context window via map/reduce summarization
+#[derive(Debug, Clone)]
+pub struct ContextWindowManager {
+    context_window: usize,
+    reserve_tokens: usize,
+    chunk_tokens: usize,
+    max_summarized_chunks: usize,
+}
+
+impl ContextWindowManager {
+    /// `context_window` is the backend limit in tokens. A quarter of it (at least
+    /// 512 tokens) is reserved for the model's answer.
+    pub fn new(context_window: usize) -> Self {
+        let reserve_tokens = (context_window / 4).max(512).min(context_window / 2);
+        Self {
+            context_window,
+            reserve_tokens,
+            chunk_tokens: (context_window / 8).max(256),
+            max_summarized_chunks: 16,
+        }
+    }
+
+    /// Build a manager for the context window of the router's default model
+    pub fn for_router(llm: &LLMRouter) -> Self {
+        Self::new(llm.context_window())
+    }
+
+    pub fn with_reserve(mut self, reserve_tokens: usize) -> Self {
+        self.reserve_tokens = reserve_tokens;
+        self
+    }
+
+    /// Tokens available for `content` once `instruction` and the reserve are accounted for
+    pub fn budget(&self, instruction: &str) -> usize {
+        self.context_window
+            .saturating_sub(self.reserve_tokens)
+            .saturating_sub(estimate_tokens(instruction))
+    }
+
+    /// Return `content` unchanged if it fits, otherwise a condensed version that does
+    pub async fn fit(
+        &self,
+        llm: &LLMRouter,
+        instruction: &str,
+        content: &str,
+        query: &str,
+    ) -> Result<FittedContext> {
+        let budget = self.budget(instruction);
+        let original_tokens = estimate_tokens(content);
+
+        // No amount of condensing makes room when the instruction fills the window
+        if budget == 0 {
+            return Err(crate::JarvisError::TooLong {
+                limit: self.context_window,
+                estimated: estimate_tokens(instruction) + self.reserve_tokens,
+                message: "instruction leaves no room in the context window".to_string(),
+            }
+            .into());
+        }
+
+        if original_tokens <= budget {
+            return Ok(FittedContext {
+                content: content.to_string(),
+                original_tokens,
+                final_tokens: original_tokens,
+                summarized_chunks: 0,
+                verbatim_chunks: 0,
+                dropped_chunks: 0,
+            });
+        }
+
+        let chunks = chunk_text(content, self.chunk_tokens);
+        let ranked = self.rank_chunks(llm, query, &chunks).await;
+
+        // Map: summarize the most relevant chunks, drop the rest
+        let summarize: Vec<usize> = ranked
+            .iter()
+            .take(self.max_summarized_chunks)
+            .copied()
+            .collect();
+        let dropped_chunks = chunks.len() - summarize.len();
+
+        let summary_budget = budget / 2;
+        let per_summary_tokens = (summary_budget / summarize.len().max(1)).max(32);
+
+        let mut summaries: Vec<(usize, String)> = Vec::new();
+        for &index in &summarize {
+            let prompt = format!(
+                "Summarize the following excerpt in at most {} words. Keep error messages, \
+                 identifiers, numbers, and timestamps verbatim where possible.\n\n{}",
+                per_summary_tokens * 3 / 4,
+                chunks[index]
+            );
+            match llm.generate(&prompt, None).await {
+                Ok(summary) => summaries.push((
+                    index,
+                    truncate_to_tokens(summary.trim(), per_summary_tokens),
+                )),
+                Err(e) => tracing::warn!("Failed to summarize chunk {}: {}", index, e),
+            }
+        }
+        summaries.sort_by_key(|(index, _)| *index);
+
+        let mut used_tokens = 0;
+        let mut summary_section = String::new();
+        for (index, summary) in &summaries {
+            let line = format!("[part {}/{}] {}\n", index + 1, chunks.len(), summary);
+            used_tokens += estimate_tokens(&line);
+            summary_section.push_str(&line);
+        }
+
+        // Reduce: add the most relevant chunks verbatim while budget remains
+        let mut verbatim: Vec<usize> = Vec::new();
+        for &index in &ranked {
+            let tokens = estimate_tokens(&chunks[index]);
+            if used_tokens + tokens > budget {
+                continue;
+            }
+            used_tokens += tokens;
+            verbatim.push(index);
+        }
+        verbatim.sort_unstable();
+
+        let mut fitted = String::new();
+        fitted.push_str(&format!(
+            "[Input too large for the context window: {} of {} parts summarized, {} included verbatim]\n\n",
+            summaries.len(),
+            chunks.len(),
+            verbatim.len()
+        ));
+        fitted.push_str("Summaries:\n");
+        fitted.push_str(&summary_section);
+        if !verbatim.is_empty() {
+            fitted.push_str("\nMost relevant excerpts:\n");
+            for index in &verbatim {
+                fitted.push_str(&format!("--- part {}/{} ---\n", index + 1, chunks.len()));
+                fitted.push_str(&chunks[*index]);
+            }
+        }
+
+        let final_tokens = estimate_tokens(&fitted);
+        tracing::debug!(
+            "Context window: input {} tokens > budget {}; {} chunks, {} summarized, {} verbatim, {} dropped, final {} tokens",
+            original_tokens,
+            budget,
+            chunks.len(),
+            summaries.len(),
+            verbatim.len(),
+            dropped_chunks,
+            final_tokens
+        );
+
+        Ok(FittedContext {
+            content: fitted,
+            original_tokens,
+            final_tokens,
+            summarized_chunks: summaries.len(),
+            verbatim_chunks: verbatim.len(),
+            dropped_chunks,
+        })
+    }
+
+    /// Chunk indices ordered by relevance to `query`, most relevant first.
+    /// Uses embeddings when the backend supports them, keyword overlap otherwise.
+    async fn rank_chunks(&self, llm: &LLMRouter, query: &str, chunks: &[String]) -> Vec<usize> {
+        let mut scores: Vec<(usize, f32)> = match self.embedding_scores(llm, query, chunks).await {
+            Some(scores) => scores,
+            None => chunks
+                .iter()
+                .enumerate()
+                .map(|(i, chunk)| (i, keyword_overlap(query, chunk)))
+                .collect(),
+        };
+
+        scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        scores.into_iter().map(|(i, _)| i).collect()
+    }
+
+    async fn embedding_scores(
+        &self,
+        llm: &LLMRouter,
+        query: &str,
+        chunks: &[String],
+    ) -> Option<Vec<(usize, f32)>> {
+        let query_embedding = llm.embed(query).await.ok()?;
+
+        let mut scores = Vec::with_capacity(chunks.len());
+        for (i, chunk) in chunks.iter().enumerate() {
+            let embedding = llm.embed(chunk).await.ok()?;
+            scores.push((i, cosine_similarity(&query_embedding, &embedding)));
+        }
+        Some(scores)
+    }
+}
+
+fn keyword_overlap(query: &str, chunk: &str) -> f32 {
+    let chunk_lower = chunk.to_lowercase();
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .filter(|t| t.len() > 2)
+        .map(|t| t.to_lowercase())
+        .collect();
+
+    let mut score = terms.iter().filter(|t| chunk_lower.contains(t.as_str())).count() as f32;
+
+    // Errors and warnings are usually what a diagnosis hinges on
+    if chunk_lower.contains("error") || chunk_lower.contains("fail") {
+        score += 1.0;
+    }
+    if chunk_lower.contains("warn") {
+        score += 0.5;
+    }
+
+    score
+}
+
+pub(crate) fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
+    let norm_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("hello"), 2);
+        // Symbols count individually
+        assert_eq!(estimate_tokens("a.b"), 3);
+    }
+
+    #[test]
+    fn test_chunk_text_respects_limit() {
+        let text = "systemd[1]: Started Network Manager.\n".repeat(200);
+        let chunks = chunk_text(&text, 100);
+
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert!(estimate_tokens(chunk) <= 100);
+        }
+        assert_eq!(chunks.concat(), text);
+    }
+
+    #[test]
+    fn test_budget_accounts_for_reserve() {
+        let manager = ContextWindowManager::new(8192);
+        assert_eq!(manager.budget(""), 8192 - 2048);
+    }
+}
@@ -1,8 +1,49 @@
+#[cfg(any(test, feature = "llm-replay"))]
+pub mod cassette;
+pub mod consensus;
+pub mod context_window;
 pub mod ollama_client;
 pub mod omen_client;
 
-pub use ollama_client::OllamaClient;
-pub use omen_client::OmenClient;
+pub use consensus::{BackendAnswer, BackendUsage, ConsensusBackend, ConsensusResult};
+pub use context_window::{ContextWindowManager, FittedContext, estimate_tokens};
+pub use ollama_client::{
+    ModelLoadProgress, ModelReadiness, OllamaClient, OllamaModelInfo, OllamaPullProgress,
+    is_model_loading,
+};
+pub use omen_client::{OmenClient, OmenGeneration, OmenModel, OmenResponseMeta};
+
+/// Context windows of hosted models, matched by name prefix; more specific
+/// prefixes come first
+const KNOWN_CONTEXT_WINDOWS: &[(&str, usize)] = &[
+    ("gpt-4.1", 1_047_576),
+    ("gpt-4o", 128_000),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4-32k", 32_768),
+    ("gpt-4", 8_192),
+    ("gpt-3.5-turbo", 16_385),
+    ("o1", 200_000),
+    ("o3", 200_000),
+    ("o4", 200_000),
+    ("claude", 200_000),
+];
+
+/// Context window of a hosted OpenAI or Claude model, ignoring any
+/// `provider/` prefix
+pub fn known_context_window(model: &str) -> Option<usize> {
+    let name = model
+        .rsplit('/')
+        .next()
+        .unwrap_or(model)
+        .to_ascii_lowercase();
+    KNOWN_CONTEXT_WINDOWS
+        .iter()
+        .find(|(prefix, _)| name.starts_with(prefix))
+        .map(|(_, window)| *window)
+}
+
+/// Called while a request waits for Ollama to load its model
+pub type LoadProgressCallback = std::sync::Arc<dyn Fn(&ModelLoadProgress) + Send + Sync>;
 
 /// LLMRouter routes LLM requests to appropriate backends
 #[derive(Clone)]
@@ -10,7 +51,19 @@ pub struct LLMRouter {
     omen_client: Option<OmenClient>,
     ollama_client: Option<OllamaClient>,
     default_model: String,
+    embedding_model: String,
     primary_provider: String,
+    /// Configured context window, for models that report none
+    context_window: usize,
+    /// Context windows Ollama reported for the models this router uses
+    context_windows: std::collections::HashMap<String, usize>,
+    consensus: crate::config::ConsensusConfig,
+    usage: std::sync::Arc<std::sync::Mutex<std::collections::HashMap<String, BackendUsage>>>,
+    model_load_timeout: std::time::Duration,
+    load_progress: Option<LoadProgressCallback>,
+    /// Serves or records every generation while a test cassette is loaded
+    #[cfg(any(test, feature = "llm-replay"))]
+    cassette: Option<std::sync::Arc<cassette::Cassette>>,
 }
 
 /// Intent type for routing decisions
@@ -22,6 +75,18 @@ pub enum Intent {
     Reason,
 }
 
+impl Intent {
+    /// Intent tag understood by Omen's router
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Intent::Code => "code",
+            Intent::System => "system",
+            Intent::DevOps => "devops",
+            Intent::Reason => "reason",
+        }
+    }
+}
+
 impl LLMRouter {
     pub async fn new(config: &crate::config::Config) -> anyhow::Result<Self> {
         let omen_client = if config.llm.omen_enabled.unwrap_or(false) {
@@ -44,72 +109,343 @@ impl LLMRouter {
         let default_model = config.llm.default_model.clone()
             .unwrap_or_else(|| "llama3.1:8b".to_string());
 
+        if config.llm.auto_pull_default {
+            if let Some(ollama) = &ollama_client {
+                Self::ensure_default_model(ollama, &default_model).await?;
+            }
+        }
+
+        let embedding_model = config.llm.embedding_model.clone()
+            .unwrap_or_else(|| default_model.clone());
+
+        let context_windows = match &ollama_client {
+            Some(ollama) => {
+                let mut models = vec![default_model.clone()];
+                models.extend(config.llm.consensus.secondary_model.clone());
+                Self::discover_context_windows(ollama, models).await
+            }
+            None => Default::default(),
+        };
+
         Ok(Self {
             omen_client,
             ollama_client,
             default_model,
+            embedding_model,
             primary_provider: config.llm.primary_provider.clone(),
+            context_window: config.llm.context_window,
+            context_windows,
+            consensus: config.llm.consensus.clone(),
+            usage: Default::default(),
+            model_load_timeout: std::time::Duration::from_secs(config.llm.model_load_timeout_secs),
+            load_progress: None,
+            #[cfg(any(test, feature = "llm-replay"))]
+            cassette: cassette::Cassette::from_env(&config.llm.primary_provider)?
+                .map(std::sync::Arc::new),
         })
     }
 
+    /// Serve generations from `cassette`, or record them into it
+    #[cfg(any(test, feature = "llm-replay"))]
+    pub fn with_cassette(mut self, cassette: cassette::Cassette) -> Self {
+        self.cassette = Some(std::sync::Arc::new(cassette));
+        self
+    }
+
+    /// Run `live` unless a cassette answers the request
+    #[cfg(any(test, feature = "llm-replay"))]
+    async fn recorded<F, Fut>(
+        &self,
+        operation: &str,
+        model: &str,
+        intent: Option<Intent>,
+        prompt: &str,
+        live: F,
+    ) -> anyhow::Result<String>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = anyhow::Result<String>>,
+    {
+        match &self.cassette {
+            Some(cassette) => {
+                let request = cassette_request(operation, model, intent, prompt);
+                cassette.text(request, live).await
+            }
+            None => live().await,
+        }
+    }
+
+    #[cfg(not(any(test, feature = "llm-replay")))]
+    async fn recorded<F, Fut>(
+        &self,
+        _operation: &str,
+        _model: &str,
+        _intent: Option<Intent>,
+        _prompt: &str,
+        live: F,
+    ) -> anyhow::Result<String>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = anyhow::Result<String>>,
+    {
+        live().await
+    }
+
+    /// Ask Ollama for each model's context length; models it can't describe
+    /// fall back to [`LLMRouter::context_window_for`]'s other sources
+    async fn discover_context_windows(
+        ollama: &OllamaClient,
+        models: Vec<String>,
+    ) -> std::collections::HashMap<String, usize> {
+        let mut windows = std::collections::HashMap::new();
+        for model in models {
+            if windows.contains_key(&model) {
+                continue;
+            }
+            match ollama.context_length(&model).await {
+                Ok(Some(length)) => {
+                    tracing::debug!("{} has a {}-token context window", model, length);
+                    windows.insert(model, length);
+                }
+                Ok(None) => {}
+                Err(e) => tracing::debug!("Could not read context length of {}: {:#}", model, e),
+            }
+        }
+        windows
+    }
+
+    /// Report model loads to `callback` instead of only logging them
+    pub fn with_load_progress(mut self, callback: LoadProgressCallback) -> Self {
+        self.load_progress = Some(callback);
+        self
+    }
+
+    /// Wait for Ollama to have `model` in memory, within the configured
+    /// deadline
+    async fn wait_for_model(&self, ollama: &OllamaClient, model: &str) -> anyhow::Result<()> {
+        let mut announced = false;
+        ollama
+            .wait_until_ready(model, self.model_load_timeout, |progress| {
+                match &self.load_progress {
+                    Some(callback) => callback(progress),
+                    None if !announced => {
+                        tracing::info!("Waiting for Ollama to load {}", progress.model);
+                        announced = true;
+                    }
+                    None => {}
+                }
+            })
+            .await
+    }
+
+    /// Run `call` once `model` is loaded; a request Ollama turns away because
+    /// the model is loading after all waits and is retried once
+    async fn with_ollama<F, Fut>(
+        &self,
+        ollama: &OllamaClient,
+        model: &str,
+        call: F,
+    ) -> anyhow::Result<String>
+    where
+        F: Fn() -> Fut,
+        Fut: std::future::Future<Output = anyhow::Result<String>>,
+    {
+        self.wait_for_model(ollama, model).await?;
+        match call().await {
+            Err(e) if is_model_loading(&e) => {
+                tracing::debug!("{} was unloaded mid-request; waiting and retrying", model);
+                self.wait_for_model(ollama, model).await?;
+                call().await
+            }
+            result => result,
+        }
+        .map_err(ollama_error)
+    }
+
     /// Generate a response using the configured LLM backend
     pub async fn generate(&self, prompt: &str, _options: Option<serde_json::Value>) -> anyhow::Result<String> {
+        let _phase = crate::trace::phase("llm");
+        self.check_prompt_fits(&self.default_model, prompt)?;
+        self.recorded("generate", &self.default_model, None, prompt, || {
+            self.generate_live(prompt)
+        })
+        .await
+    }
+
+    async fn generate_live(&self, prompt: &str) -> anyhow::Result<String> {
         // Try Omen first if available (intelligent routing)
         if let Some(omen) = &self.omen_client {
             tracing::debug!("Routing through Omen (auto-intent)");
-            return omen.code(prompt).await;
+            return self.generate_via_omen(omen, prompt, Intent::Code).await;
         }
 
         // Fallback to direct Ollama
         if let Some(ollama) = &self.ollama_client {
             tracing::debug!("Using direct Ollama: {}", self.default_model);
-            return ollama.complete(&self.default_model, prompt, Some(0.7)).await;
+            let response = self
+                .with_ollama(ollama, &self.default_model, || {
+                    ollama.complete(&self.default_model, prompt, Some(0.7))
+                })
+                .await?;
+            self.trace_ollama_usage(prompt, &response);
+            return Ok(response);
+        }
+
+        Err(crate::JarvisError::Config(
+            "No LLM backend configured. Enable Omen or Ollama in jarvis.toml".to_string(),
+        )
+        .into())
+    }
+
+    /// Stream a response chunk by chunk. Omen answers in one chunk.
+    pub async fn generate_stream(
+        &self,
+        prompt: &str,
+    ) -> anyhow::Result<futures::stream::BoxStream<'static, anyhow::Result<String>>> {
+        self.check_prompt_fits(&self.default_model, prompt)?;
+        #[cfg(any(test, feature = "llm-replay"))]
+        if let Some(cassette) = &self.cassette {
+            let request = cassette_request("generate_stream", &self.default_model, None, prompt);
+            return cassette.stream(request, || self.generate_stream_live(prompt)).await;
+        }
+        self.generate_stream_live(prompt).await
+    }
+
+    async fn generate_stream_live(
+        &self,
+        prompt: &str,
+    ) -> anyhow::Result<futures::stream::BoxStream<'static, anyhow::Result<String>>> {
+        use futures::stream::StreamExt;
+
+        if let Some(omen) = &self.omen_client {
+            let response = self.generate_via_omen(omen, prompt, Intent::Code).await?;
+            return Ok(futures::stream::once(async move { Ok(response) }).boxed());
         }
+        let Some(ollama) = &self.ollama_client else {
+            return Err(crate::JarvisError::Config(
+                "No LLM backend configured. Enable Omen or Ollama in jarvis.toml".to_string(),
+            )
+            .into());
+        };
 
-        anyhow::bail!("No LLM backend configured. Enable Omen or Ollama in jarvis.toml")
+        self.wait_for_model(ollama, &self.default_model).await?;
+        let messages = vec![ollama_client::OllamaMessage {
+            role: "user".to_string(),
+            content: prompt.to_string(),
+        }];
+        let stream = ollama
+            .chat_stream(&self.default_model, messages, Some(0.7))
+            .await
+            .map_err(ollama_error)?;
+        Ok(stream)
     }
 
     /// Generate with specific intent routing
     pub async fn generate_with_intent(&self, prompt: &str, intent: Intent) -> anyhow::Result<String> {
-        match (&self.omen_client, &self.ollama_client, intent) {
+        let _phase = crate::trace::phase("llm");
+        crate::trace::annotate("intent", intent.as_str());
+        self.check_prompt_fits(&self.default_model, prompt)?;
+        self.recorded("generate_with_intent", &self.default_model, Some(intent), prompt, || {
+            self.generate_with_intent_live(prompt, intent)
+        })
+        .await
+    }
+
+    async fn generate_with_intent_live(&self, prompt: &str, intent: Intent) -> anyhow::Result<String> {
+        let result = match (&self.omen_client, &self.ollama_client, intent) {
             // Omen available - use intelligent routing
-            (Some(omen), _, Intent::Code) => {
-                tracing::debug!("Routing code intent through Omen");
-                omen.code(prompt).await
-            }
-            (Some(omen), _, Intent::System) => {
-                tracing::debug!("Routing system intent through Omen");
-                omen.system(prompt).await
-            }
-            (Some(omen), _, Intent::DevOps) => {
-                tracing::debug!("Routing devops intent through Omen");
-                omen.devops(prompt).await
-            }
-            (Some(omen), _, Intent::Reason) => {
-                tracing::debug!("Routing reason intent through Omen");
-                omen.reason(prompt).await
+            (Some(omen), _, intent) => {
+                tracing::debug!("Routing {} intent through Omen", intent.as_str());
+                self.generate_via_omen(omen, prompt, intent).await
             }
 
             // Ollama fallback with specialized prompts
-            (None, Some(ollama), Intent::Code) => {
-                tracing::debug!("Using Ollama for code intent: {}", self.default_model);
-                ollama.code(&self.default_model, prompt, Some(0.7)).await
-            }
-            (None, Some(ollama), Intent::System) => {
-                tracing::debug!("Using Ollama for system intent: {}", self.default_model);
-                ollama.system(&self.default_model, prompt, Some(0.7)).await
-            }
-            (None, Some(ollama), Intent::DevOps) => {
-                tracing::debug!("Using Ollama for devops intent: {}", self.default_model);
-                ollama.devops(&self.default_model, prompt, Some(0.7)).await
-            }
-            (None, Some(ollama), Intent::Reason) => {
-                tracing::debug!("Using Ollama for reasoning: {}", self.default_model);
-                ollama.complete(&self.default_model, prompt, Some(0.8)).await
+            (None, Some(ollama), intent) => {
+                let model = self.default_model.as_str();
+                tracing::debug!("Using Ollama for {} intent: {}", intent.as_str(), model);
+                self.with_ollama(ollama, model, || async move {
+                    match intent {
+                        Intent::Code => ollama.code(model, prompt, Some(0.7)).await,
+                        Intent::System => ollama.system(model, prompt, Some(0.7)).await,
+                        Intent::DevOps => ollama.devops(model, prompt, Some(0.7)).await,
+                        Intent::Reason => ollama.complete(model, prompt, Some(0.8)).await,
+                    }
+                })
+                .await
             }
 
             // No backend available
             _ => anyhow::bail!("No LLM backend available for intent: {:?}", intent),
+        };
+
+        if let (None, Ok(response)) = (&self.omen_client, &result) {
+            self.trace_ollama_usage(prompt, response);
+        }
+        result
+    }
+
+    /// Tag the current trace phase with the model and estimated token counts;
+    /// Ollama's completion helpers don't surface its own counts
+    fn trace_ollama_usage(&self, prompt: &str, response: &str) {
+        crate::trace::annotate("provider", "ollama");
+        crate::trace::annotate("model", &self.default_model);
+        crate::trace::annotate("prompt_tokens", estimate_tokens(prompt));
+        crate::trace::annotate("completion_tokens", estimate_tokens(response));
+    }
+
+    /// Generate through Omen, logging which model served the request and
+    /// recording the usage Omen reports (estimated when it reports none)
+    async fn generate_via_omen(
+        &self,
+        omen: &OmenClient,
+        prompt: &str,
+        intent: Intent,
+    ) -> anyhow::Result<String> {
+        let generation = omen.generate_detailed(prompt, Some(intent.as_str())).await?;
+        let meta = &generation.meta;
+
+        tracing::debug!(
+            "Omen served {} intent with {} via {} in {}ms",
+            intent.as_str(),
+            meta.served_model.as_deref().unwrap_or("unknown model"),
+            meta.provider.as_deref().unwrap_or("unknown provider"),
+            meta.latency_ms.unwrap_or_default()
+        );
+
+        let prompt_tokens = meta
+            .prompt_tokens
+            .map(|t| t as usize)
+            .unwrap_or_else(|| estimate_tokens(prompt));
+        let completion_tokens = meta
+            .completion_tokens
+            .map(|t| t as usize)
+            .unwrap_or_else(|| estimate_tokens(&generation.text));
+        self.record_usage(
+            &ConsensusBackend::Omen.label(),
+            prompt_tokens,
+            completion_tokens,
+        );
+        crate::trace::annotate("provider", meta.provider.as_deref().unwrap_or("omen"));
+        crate::trace::annotate("model", meta.served_model.as_deref().unwrap_or("auto"));
+        crate::trace::annotate("prompt_tokens", prompt_tokens);
+        crate::trace::annotate("completion_tokens", completion_tokens);
+
+        Ok(generation.text)
+    }
+
+    /// Check if Omen is reachable
+    pub async fn check_omen_health(&self) -> bool {
+        match &self.omen_client {
+            Some(omen) => omen.health_check().await.unwrap_or(false),
+            None => false,
+        }
+    }
+
+    /// List the models Omen can route to
+    pub async fn list_omen_models(&self) -> anyhow::Result<Vec<OmenModel>> {
+        match &self.omen_client {
+            Some(omen) => omen.list_models().await,
+            None => Ok(vec![]),
         }
     }
 
@@ -122,6 +458,36 @@ impl LLMRouter {
         }
     }
 
+    /// Readiness of every model this router sends requests to: the default,
+    /// embedding, and consensus models
+    pub async fn ollama_model_readiness(&self) -> Vec<(String, ModelReadiness)> {
+        let Some(ollama) = &self.ollama_client else {
+            return Vec::new();
+        };
+        let mut models = vec![self.default_model.clone()];
+        models.push(self.embedding_model.clone());
+        models.extend(self.consensus.secondary_model.clone());
+
+        let mut readiness = Vec::new();
+        for model in models {
+            if readiness.iter().any(|(m, _)| *m == model) {
+                continue;
+            }
+            let state = ollama.readiness(&model).await;
+            readiness.push((model, state));
+        }
+        readiness
+    }
+
+    /// Issue an empty generation for the default model so Ollama keeps it
+    /// loaded; a no-op without an Ollama backend
+    pub async fn keep_warm(&self) -> anyhow::Result<()> {
+        match &self.ollama_client {
+            Some(ollama) => ollama.load_model(&self.default_model).await,
+            None => Ok(()),
+        }
+    }
+
     /// List available Ollama models
     pub async fn list_ollama_models(&self) -> anyhow::Result<Vec<String>> {
         if let Some(ollama) = &self.ollama_client {
@@ -132,6 +498,110 @@ impl LLMRouter {
         }
     }
 
+    /// Pull a model through Ollama, reporting streamed progress
+    pub async fn pull_model<F>(&self, model: &str, on_progress: F) -> anyhow::Result<()>
+    where
+        F: FnMut(&OllamaPullProgress),
+    {
+        match &self.ollama_client {
+            Some(ollama) => ollama.pull_model(model, on_progress).await,
+            None => anyhow::bail!("Ollama is not configured; cannot pull {}", model),
+        }
+    }
+
+    /// Remove a model from Ollama
+    pub async fn delete_model(&self, model: &str) -> anyhow::Result<()> {
+        match &self.ollama_client {
+            Some(ollama) => ollama.delete_model(model).await,
+            None => anyhow::bail!("Ollama is not configured; cannot remove {}", model),
+        }
+    }
+
+    /// Show Ollama model metadata
+    pub async fn show_model(&self, model: &str) -> anyhow::Result<OllamaModelInfo> {
+        match &self.ollama_client {
+            Some(ollama) => ollama.show_model(model).await,
+            None => anyhow::bail!("Ollama is not configured; cannot show {}", model),
+        }
+    }
+
+    /// Pull the default model if Ollama is reachable and it is missing
+    async fn ensure_default_model(ollama: &OllamaClient, model: &str) -> anyhow::Result<()> {
+        if !ollama.health_check().await.unwrap_or(false) {
+            tracing::warn!("Ollama is not reachable; skipping auto-pull of {}", model);
+            return Ok(());
+        }
+
+        if ollama.has_model(model).await? {
+            return Ok(());
+        }
+
+        tracing::info!("Default model {} not found, pulling from Ollama", model);
+        let mut last_status = String::new();
+        ollama
+            .pull_model(model, |progress| {
+                if progress.status != last_status {
+                    tracing::info!("Pulling {}: {}", model, progress.status);
+                    last_status = progress.status.clone();
+                }
+            })
+            .await?;
+        tracing::info!("Default model {} is ready", model);
+
+        Ok(())
+    }
+
+    /// Model `embed` uses
+    pub fn embedding_model(&self) -> &str {
+        &self.embedding_model
+    }
+
+    /// Compute an embedding vector for `text` (Ollama only)
+    pub async fn embed(&self, text: &str) -> anyhow::Result<Vec<f32>> {
+        match &self.ollama_client {
+            Some(ollama) => {
+                self.wait_for_model(ollama, &self.embedding_model).await?;
+                ollama.embeddings(&self.embedding_model, text).await
+            }
+            None => anyhow::bail!("Embeddings require an Ollama backend"),
+        }
+    }
+
+    /// Context window of the default model, in tokens
+    pub fn context_window(&self) -> usize {
+        self.context_window_for(&self.default_model)
+    }
+
+    /// Context window of `model` in tokens: what Ollama reported at startup,
+    /// else the known window of a hosted model, else the configured one
+    pub fn context_window_for(&self, model: &str) -> usize {
+        self.context_windows
+            .get(model)
+            .copied()
+            .or_else(|| known_context_window(model))
+            .unwrap_or(self.context_window)
+    }
+
+    /// Fail with [`crate::JarvisError::TooLong`] when `prompt` is larger than
+    /// `model`'s context window, rather than letting the backend reject it
+    pub fn check_prompt_fits(&self, model: &str, prompt: &str) -> Result<(), crate::JarvisError> {
+        let limit = self.context_window_for(model);
+        let estimated = estimate_tokens(prompt);
+        if estimated > limit {
+            return Err(crate::JarvisError::TooLong {
+                limit,
+                estimated,
+                message: format!("prompt for {} does not fit its context window", model),
+            });
+        }
+        Ok(())
+    }
+
+    /// Get the default model name
+    pub fn default_model(&self) -> &str {
+        &self.default_model
+    }
+
     /// Get the primary provider name
     pub fn primary_provider(&self) -> &str {
         &self.primary_provider
@@ -147,3 +617,70 @@ impl LLMRouter {
         self.ollama_client.is_some()
     }
 }
+
+#[cfg(any(test, feature = "llm-replay"))]
+fn cassette_request(
+    operation: &str,
+    model: &str,
+    intent: Option<Intent>,
+    prompt: &str,
+) -> cassette::CassetteRequest {
+    cassette::CassetteRequest {
+        operation: operation.to_string(),
+        model: model.to_string(),
+        intent: intent.map(|intent| intent.as_str().to_string()),
+        prompt: prompt.to_string(),
+    }
+}
+
+/// Errors that already say what went wrong (Ollama down, model missing, load
+/// timed out) keep their category; anything else is an LLM error
+fn ollama_error(err: anyhow::Error) -> anyhow::Error {
+    if err.chain().any(|cause| cause.is::<crate::JarvisError>()) {
+        err
+    } else {
+        crate::JarvisError::llm("ollama", format!("{:#}", err)).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_context_windows() {
+        assert_eq!(known_context_window("gpt-4o-mini"), Some(128_000));
+        assert_eq!(known_context_window("gpt-4"), Some(8_192));
+        assert_eq!(known_context_window("openai/gpt-4-turbo"), Some(128_000));
+        assert_eq!(
+            known_context_window("claude-3-5-sonnet-latest"),
+            Some(200_000)
+        );
+        assert_eq!(known_context_window("llama3.1:8b"), None);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_prompt_is_rejected_before_the_backend() {
+        let mut config = crate::config::Config::default();
+        // Nothing listens here, so no context length is discovered
+        config.llm.ollama_url = "http://127.0.0.1:9".to_string();
+        config.llm.context_window = 100;
+        let router = LLMRouter::new(&config).await.unwrap();
+
+        assert_eq!(router.context_window(), 100);
+        assert_eq!(router.context_window_for("claude-3-opus"), 200_000);
+
+        let prompt = "word ".repeat(500);
+        let err = router.generate(&prompt, None).await.unwrap_err();
+        match crate::JarvisError::from_anyhow(&err) {
+            crate::JarvisError::TooLong {
+                limit, estimated, ..
+            } => {
+                assert_eq!(limit, 100);
+                assert_eq!(estimated, estimate_tokens(&prompt));
+            }
+            other => panic!("expected TooLong, got {:?}", other),
+        }
+        assert!(router.check_prompt_fits("gpt-4o", &prompt).is_ok());
+    }
+}
@@ -2,10 +2,21 @@
 //!
 //! Provides direct client access to Ollama for local model inference.
 //! Supports chat completions, streaming, and model management.
+//!
+//! A model that isn't in memory yet makes the first request block while
+//! Ollama loads it. [`OllamaClient::wait_until_ready`] loads it up front with
+//! progress reports, and failures say whether Ollama is down, the model is
+//! still loading, or the model isn't installed at all.
 
+use crate::error::JarvisError;
+use crate::net::CheckedSend;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// How often `/api/ps` is polled while a model loads
+const LOAD_POLL_INTERVAL: Duration = Duration::from_millis(500);
 
 /// Client for interacting with Ollama directly
 #[derive(Clone)]
@@ -62,11 +73,180 @@ pub struct OllamaListResponse {
     pub models: Vec<OllamaModel>,
 }
 
+/// A model Ollama holds in memory, from `/api/ps`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OllamaRunningModel {
+    pub name: String,
+    /// Bytes the loaded model occupies
+    #[serde(default)]
+    pub size: u64,
+    /// Bytes of it already placed in VRAM
+    #[serde(default)]
+    pub size_vram: u64,
+    #[serde(default)]
+    pub expires_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaPsResponse {
+    #[serde(default)]
+    models: Vec<OllamaRunningModel>,
+}
+
+/// Whether a model can answer right now
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelReadiness {
+    /// Loaded and answering
+    Ready,
+    /// Installed but not in memory; the next request waits for a load
+    Cold,
+    /// Not installed; `jarvis train pull` fetches it
+    Missing,
+    /// Ollama itself didn't answer
+    Unreachable,
+}
+
+/// Reported while [`OllamaClient::wait_until_ready`] waits on a load
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelLoadProgress {
+    pub model: String,
+    pub elapsed: Duration,
+    /// Share of the model placed in memory, once Ollama lists it in `/api/ps`
+    pub percent: Option<f64>,
+    pub ready: bool,
+}
+
+/// Ollama refused a request because the model is still loading
+#[derive(Debug, Clone)]
+pub struct ModelLoadingError {
+    pub model: String,
+}
+
+impl std::fmt::Display for ModelLoadingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} is still loading in Ollama", self.model)
+    }
+}
+
+impl std::error::Error for ModelLoadingError {}
+
+/// Whether `err` came from a request Ollama turned away mid-load
+pub fn is_model_loading(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| cause.is::<ModelLoadingError>())
+}
+
+fn with_latest_tag(model: &str) -> String {
+    if model.contains(':') {
+        model.to_string()
+    } else {
+        format!("{}:latest", model)
+    }
+}
+
+fn missing_model(model: &str) -> JarvisError {
+    JarvisError::NotFound(format!(
+        "Model '{}' is not installed in Ollama; run `jarvis train pull {}`",
+        model, model
+    ))
+}
+
+/// Turn a failed Ollama response into an error that says which of "model
+/// missing", "model loading", or a plain API failure it was
+fn response_error(model: &str, status: reqwest::StatusCode, body: &str) -> anyhow::Error {
+    let lower = body.to_ascii_lowercase();
+    if status == reqwest::StatusCode::NOT_FOUND
+        || (lower.contains("model") && lower.contains("not found"))
+    {
+        return missing_model(model).into();
+    }
+    if status == reqwest::StatusCode::SERVICE_UNAVAILABLE
+        || lower.contains("loading model")
+        || lower.contains("server busy")
+    {
+        return anyhow::Error::new(ModelLoadingError {
+            model: model.to_string(),
+        })
+        .context(JarvisError::llm(
+            "ollama",
+            format!("{} is still loading ({}): {}", model, status, body),
+        ));
+    }
+    JarvisError::llm("ollama", format!("Ollama API error ({}): {}", status, body)).into()
+}
+
+/// A single progress line from `/api/pull`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OllamaPullProgress {
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OllamaModelDetails {
+    #[serde(default)]
+    pub format: String,
+    #[serde(default)]
+    pub family: String,
+    #[serde(default)]
+    pub parameter_size: String,
+    #[serde(default)]
+    pub quantization_level: String,
+}
+
+/// Response from `/api/show`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OllamaShowResponse {
+    #[serde(default)]
+    pub parameters: String,
+    #[serde(default)]
+    pub template: String,
+    #[serde(default)]
+    pub details: OllamaModelDetails,
+    #[serde(default)]
+    pub model_info: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub modified_at: Option<String>,
+}
+
+impl OllamaShowResponse {
+    /// Context length the model was trained for, from the architecture's
+    /// `<arch>.context_length` entry in `model_info`
+    pub fn context_length(&self) -> Option<usize> {
+        self.model_info
+            .iter()
+            .find(|(key, _)| key.ends_with(".context_length"))
+            .and_then(|(_, value)| value.as_u64())
+            .map(|length| length as usize)
+    }
+}
+
+/// Model metadata combined from `/api/show` and `/api/tags`
+#[derive(Debug, Clone, Serialize)]
+pub struct OllamaModelInfo {
+    pub name: String,
+    pub parameters: String,
+    pub parameter_size: String,
+    pub quantization: String,
+    pub family: String,
+    pub size_bytes: Option<i64>,
+    pub modified_at: Option<String>,
+    /// Context window in tokens, when the model reports one
+    pub context_length: Option<usize>,
+}
+
 impl OllamaClient {
     /// Create a new Ollama client
     pub fn new(base_url: String) -> Self {
         Self {
-            http_client: reqwest::Client::new(),
+            http_client: crate::net::client(),
             base_url,
         }
     }
@@ -99,14 +279,14 @@ impl OllamaClient {
             .http_client
             .post(&url)
             .json(&request)
-            .send()
+            .checked_send()
             .await
             .context("Failed to send request to Ollama")?;
 
         if !response.status().is_success() {
             let status = response.status();
             let error_text = response.text().await.unwrap_or_else(|_| String::from("Unknown error"));
-            anyhow::bail!("Ollama API error ({}): {}", status, error_text);
+            return Err(response_error(model, status, &error_text));
         }
 
         let result: OllamaChatResponse = response
@@ -156,7 +336,7 @@ impl OllamaClient {
         let response = self
             .http_client
             .get(&url)
-            .send()
+            .checked_send()
             .await
             .context("Failed to list Ollama models")?;
 
@@ -172,11 +352,308 @@ impl OllamaClient {
         Ok(result.models)
     }
 
+    /// Generate an embedding vector via `/api/embeddings`
+    pub async fn embeddings(&self, model: &str, prompt: &str) -> Result<Vec<f32>> {
+        #[derive(Deserialize)]
+        struct EmbeddingResponse {
+            embedding: Vec<f32>,
+        }
+
+        let url = format!("{}/api/embeddings", self.base_url);
+        let response = self
+            .http_client
+            .post(&url)
+            .json(&serde_json::json!({ "model": model, "prompt": prompt }))
+            .checked_send()
+            .await
+            .context("Failed to send embedding request to Ollama")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(response_error(model, status, &error_text));
+        }
+
+        let result: EmbeddingResponse = response
+            .json()
+            .await
+            .context("Failed to parse embedding response")?;
+
+        Ok(result.embedding)
+    }
+
+    /// Check whether a model is installed locally. Names without a tag match `:latest`.
+    pub async fn has_model(&self, model: &str) -> Result<bool> {
+        let wanted = with_latest_tag(model);
+        Ok(self.list_models().await?.iter().any(|m| m.name == wanted))
+    }
+
+    /// Models Ollama currently holds in memory
+    pub async fn running_models(&self) -> Result<Vec<OllamaRunningModel>> {
+        let url = format!("{}/api/ps", self.base_url);
+        let response = self
+            .http_client
+            .get(&url)
+            .checked_send()
+            .await
+            .context("Failed to list running Ollama models")?;
+
+        if !response.status().is_success() {
+            anyhow::bail!("Failed to list running models: {}", response.status());
+        }
+
+        let result: OllamaPsResponse = response
+            .json()
+            .await
+            .context("Failed to parse running model list")?;
+        Ok(result.models)
+    }
+
+    /// Whether `model` would answer a request right now
+    pub async fn readiness(&self, model: &str) -> ModelReadiness {
+        let wanted = with_latest_tag(model);
+        let Ok(running) = self.running_models().await else {
+            return ModelReadiness::Unreachable;
+        };
+        if running.iter().any(|m| m.name == wanted) {
+            return ModelReadiness::Ready;
+        }
+        match self.has_model(model).await {
+            Ok(true) => ModelReadiness::Cold,
+            Ok(false) => ModelReadiness::Missing,
+            Err(_) => ModelReadiness::Unreachable,
+        }
+    }
+
+    /// Load `model` into memory, or keep it there, with an empty generation
+    /// request; returns once Ollama has it loaded
+    pub async fn load_model(&self, model: &str) -> Result<()> {
+        let url = format!("{}/api/generate", self.base_url);
+        let response = self
+            .http_client
+            .post(&url)
+            .json(&serde_json::json!({ "model": model, "prompt": "", "stream": false }))
+            .checked_send()
+            .await
+            .with_context(|| format!("Failed to ask Ollama to load {}", model))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(response_error(model, status, &error_text));
+        }
+        Ok(())
+    }
+
+    /// Return once `model` is loaded, loading it if needed and reporting
+    /// progress every poll. Fails with `Network` when Ollama is down,
+    /// `NotFound` when the model isn't installed, and `Timeout` when the
+    /// load outlasts `deadline`.
+    pub async fn wait_until_ready<F>(
+        &self,
+        model: &str,
+        deadline: Duration,
+        mut on_progress: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&ModelLoadProgress),
+    {
+        match self.readiness(model).await {
+            ModelReadiness::Ready => return Ok(()),
+            ModelReadiness::Missing => return Err(missing_model(model).into()),
+            ModelReadiness::Unreachable => {
+                return Err(JarvisError::Network(format!(
+                    "Ollama is not reachable at {}; is `ollama serve` running?",
+                    self.base_url
+                ))
+                .into());
+            }
+            ModelReadiness::Cold => {}
+        }
+
+        let started = Instant::now();
+        let wanted = with_latest_tag(model);
+        let load = self.load_model(model);
+        tokio::pin!(load);
+        let mut poll = tokio::time::interval(LOAD_POLL_INTERVAL);
+
+        loop {
+            let elapsed = started.elapsed();
+            if elapsed >= deadline {
+                return Err(JarvisError::Timeout(format!(
+                    "{} was still loading in Ollama after {}s",
+                    model,
+                    deadline.as_secs()
+                ))
+                .into());
+            }
+
+            tokio::select! {
+                loaded = &mut load => {
+                    match loaded {
+                        // Ollama turned the load itself away while busy; keep polling
+                        Err(e) if is_model_loading(&e) => load.set(self.load_model(model)),
+                        Err(e) => return Err(e),
+                        Ok(()) => {
+                            on_progress(&ModelLoadProgress {
+                                model: model.to_string(),
+                                elapsed: started.elapsed(),
+                                percent: Some(100.0),
+                                ready: true,
+                            });
+                            return Ok(());
+                        }
+                    }
+                }
+                _ = poll.tick() => {
+                    let percent = self
+                        .running_models()
+                        .await
+                        .ok()
+                        .and_then(|running| running.into_iter().find(|m| m.name == wanted))
+                        .filter(|m| m.size > 0)
+                        .map(|m| (m.size_vram as f64 / m.size as f64 * 100.0).min(100.0));
+                    on_progress(&ModelLoadProgress {
+                        model: model.to_string(),
+                        elapsed,
+                        percent,
+                        ready: false,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Pull a model, reporting each progress line from Ollama's stream
+    pub async fn pull_model<F>(&self, model: &str, mut on_progress: F) -> Result<()>
+    where
+        F: FnMut(&OllamaPullProgress),
+    {
+        use futures::stream::StreamExt;
+
+        let url = format!("{}/api/pull", self.base_url);
+        let response = self
+            .http_client
+            .post(&url)
+            .json(&serde_json::json!({ "model": model, "stream": true }))
+            .checked_send()
+            .await
+            .context("Failed to send pull request to Ollama")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_else(|_| String::from("Unknown error"));
+            anyhow::bail!("Ollama pull failed ({}): {}", status, error_text);
+        }
+
+        // Progress is newline-delimited JSON; chunks may split lines
+        let mut stream = response.bytes_stream();
+        let mut buffer = String::new();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.context("Failed to read Ollama pull stream")?;
+            buffer.push_str(&String::from_utf8_lossy(&chunk));
+
+            while let Some(pos) = buffer.find('\n') {
+                let line: String = buffer.drain(..=pos).collect();
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+
+                let progress: OllamaPullProgress = serde_json::from_str(line)
+                    .with_context(|| format!("Failed to parse pull progress: {}", line))?;
+                if let Some(error) = &progress.error {
+                    anyhow::bail!("Ollama pull failed: {}", error);
+                }
+                on_progress(&progress);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remove a model from the local Ollama store
+    pub async fn delete_model(&self, model: &str) -> Result<()> {
+        let url = format!("{}/api/delete", self.base_url);
+        let response = self
+            .http_client
+            .delete(&url)
+            .json(&serde_json::json!({ "model": model }))
+            .checked_send()
+            .await
+            .context("Failed to send delete request to Ollama")?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            anyhow::bail!("Model '{}' is not installed", model);
+        }
+        if !response.status().is_success() {
+            anyhow::bail!("Failed to delete model {}: {}", model, response.status());
+        }
+
+        Ok(())
+    }
+
+    /// Context window `model` reports through `/api/show`, if any
+    pub async fn context_length(&self, model: &str) -> Result<Option<usize>> {
+        Ok(self.show(model).await?.context_length())
+    }
+
+    /// Show parameters, quantization, size, and modification date for a model
+    pub async fn show_model(&self, model: &str) -> Result<OllamaModelInfo> {
+        let show = self.show(model).await?;
+        let context_length = show.context_length();
+
+        // /api/show does not report size; take it from the local model list
+        let listed = self
+            .list_models()
+            .await
+            .ok()
+            .and_then(|models| {
+                models
+                    .into_iter()
+                    .find(|m| m.name == model || m.name == format!("{}:latest", model))
+            });
+
+        Ok(OllamaModelInfo {
+            name: model.to_string(),
+            parameters: show.parameters,
+            parameter_size: show.details.parameter_size,
+            quantization: show.details.quantization_level,
+            family: show.details.family,
+            size_bytes: listed.as_ref().map(|m| m.size),
+            context_length,
+            modified_at: show.modified_at.or(listed.map(|m| m.modified_at)),
+        })
+    }
+
+    async fn show(&self, model: &str) -> Result<OllamaShowResponse> {
+        let url = format!("{}/api/show", self.base_url);
+        let response = self
+            .http_client
+            .post(&url)
+            .json(&serde_json::json!({ "model": model }))
+            .checked_send()
+            .await
+            .context("Failed to send show request to Ollama")?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            anyhow::bail!("Model '{}' is not installed", model);
+        }
+        if !response.status().is_success() {
+            anyhow::bail!("Failed to show model {}: {}", model, response.status());
+        }
+
+        response
+            .json()
+            .await
+            .context("Failed to parse model details")
+    }
+
     /// Check if Ollama is healthy
     pub async fn health_check(&self) -> Result<bool> {
         let url = format!("{}/api/tags", self.base_url);
 
-        match self.http_client.get(&url).send().await {
+        match self.http_client.get(&url).checked_send().await {
             Ok(response) => Ok(response.status().is_success()),
             Err(_) => Ok(false),
         }
@@ -209,7 +686,7 @@ impl OllamaClient {
         model: &str,
         messages: Vec<OllamaMessage>,
         temperature: Option<f32>,
-    ) -> Result<impl futures::Stream<Item = Result<String>>> {
+    ) -> Result<futures::stream::BoxStream<'static, Result<String>>> {
         use futures::stream::StreamExt;
 
         let options = temperature.map(|t| OllamaOptions {
@@ -232,7 +709,7 @@ impl OllamaClient {
             .http_client
             .post(&url)
             .json(&request)
-            .send()
+            .checked_send()
             .await
             .context("Failed to send streaming request to Ollama")?;
 
@@ -254,7 +731,7 @@ impl OllamaClient {
                 })
         });
 
-        Ok(stream)
+        Ok(stream.boxed())
     }
 }
 
@@ -278,4 +755,204 @@ mod tests {
         assert!(json.contains("\"role\":\"user\""));
         assert!(json.contains("\"content\":\"test\""));
     }
+
+    /// Answers each request with the first unused `(path, status, body)`
+    /// for its path; requests with none left get a 500
+    async fn mock_ollama(mut responses: Vec<(&'static str, u16, &'static str)>) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            while !responses.is_empty() {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0u8; 4096];
+                // Read until the headers and the announced body have arrived
+                loop {
+                    let n = socket.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request);
+                    if let Some(end) = text.find("\r\n\r\n") {
+                        let length = text
+                            .lines()
+                            .find_map(|l| {
+                                l.to_ascii_lowercase()
+                                    .strip_prefix("content-length:")
+                                    .map(|v| v.trim().parse::<usize>().unwrap_or(0))
+                            })
+                            .unwrap_or(0);
+                        if request.len() >= end + 4 + length {
+                            break;
+                        }
+                    }
+                    if n == 0 {
+                        break;
+                    }
+                }
+                let request = String::from_utf8_lossy(&request);
+                let line = request.lines().next().unwrap_or_default();
+
+                let (status, body) = match responses
+                    .iter()
+                    .position(|(path, _, _)| line.contains(path))
+                {
+                    Some(at) => {
+                        let (_, status, body) = responses.remove(at);
+                        (status, body)
+                    }
+                    None => (500, "{}"),
+                };
+                let response = format!(
+                    "HTTP/1.1 {} X\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_readiness_distinguishes_missing_cold_and_down() {
+        let tags = r#"{"models":[{"name":"llama3.1:8b","modified_at":"","size":1}]}"#;
+        let base_url = mock_ollama(vec![
+            ("/api/ps", 200, r#"{"models":[]}"#),
+            ("/api/tags", 200, tags),
+            ("/api/ps", 200, r#"{"models":[]}"#),
+            ("/api/tags", 200, tags),
+            (
+                "/api/ps",
+                200,
+                r#"{"models":[{"name":"llama3.1:8b","size":10,"size_vram":10}]}"#,
+            ),
+        ])
+        .await;
+        let client = OllamaClient::new(base_url);
+
+        assert_eq!(
+            client.readiness("qwen2.5:7b").await,
+            ModelReadiness::Missing
+        );
+        assert_eq!(client.readiness("llama3.1:8b").await, ModelReadiness::Cold);
+        assert_eq!(client.readiness("llama3.1:8b").await, ModelReadiness::Ready);
+
+        // Nothing listens on the discard port
+        let down = OllamaClient::new("http://127.0.0.1:9".to_string());
+        assert_eq!(
+            down.readiness("llama3.1:8b").await,
+            ModelReadiness::Unreachable
+        );
+        let err = down
+            .wait_until_ready("llama3.1:8b", Duration::from_secs(1), |_| {})
+            .await
+            .unwrap_err();
+        assert_eq!(JarvisError::from_anyhow(&err).code(), "network");
+    }
+
+    #[tokio::test]
+    async fn test_wait_until_ready_loads_a_cold_model() {
+        let base_url = mock_ollama(vec![
+            ("/api/ps", 200, r#"{"models":[]}"#),
+            (
+                "/api/tags",
+                200,
+                r#"{"models":[{"name":"llama3.1:8b","modified_at":"","size":1}]}"#,
+            ),
+            (
+                "/api/generate",
+                200,
+                r#"{"model":"llama3.1:8b","response":"","done":true}"#,
+            ),
+        ])
+        .await;
+        let client = OllamaClient::new(base_url);
+
+        let mut reports = Vec::new();
+        client
+            .wait_until_ready("llama3.1:8b", Duration::from_secs(5), |p| {
+                reports.push(p.clone())
+            })
+            .await
+            .unwrap();
+        let last = reports.last().unwrap();
+        assert!(last.ready);
+        assert_eq!(last.percent, Some(100.0));
+    }
+
+    #[tokio::test]
+    async fn test_missing_model_suggests_pull() {
+        let base_url = mock_ollama(vec![(
+            "/api/chat",
+            404,
+            r#"{"error":"model \"llama3.1:8b\" not found, try pulling it first"}"#,
+        )])
+        .await;
+        let err = OllamaClient::new(base_url)
+            .complete("llama3.1:8b", "hi", None)
+            .await
+            .unwrap_err();
+        let err = JarvisError::from_anyhow(&err);
+        assert_eq!(err.code(), "not_found");
+        assert!(err.message().contains("jarvis train pull llama3.1:8b"));
+    }
+
+    #[tokio::test]
+    async fn test_show_model_reports_context_length() {
+        let base_url = mock_ollama(vec![
+            (
+                "/api/show",
+                200,
+                r#"{"details":{"family":"llama","parameter_size":"8.0B","quantization_level":"Q4_K_M"},
+                    "model_info":{"general.architecture":"llama","llama.context_length":131072,"llama.embedding_length":4096}}"#,
+            ),
+            (
+                "/api/tags",
+                200,
+                r#"{"models":[{"name":"llama3.1:8b","modified_at":"2024-07-23T12:00:00Z","size":4920753328}]}"#,
+            ),
+        ])
+        .await;
+
+        let info = OllamaClient::new(base_url)
+            .show_model("llama3.1:8b")
+            .await
+            .unwrap();
+        assert_eq!(info.context_length, Some(131072));
+        assert_eq!(info.size_bytes, Some(4920753328));
+    }
+
+    #[test]
+    fn test_loading_responses_are_recognized() {
+        let err = response_error(
+            "llama3.1:8b",
+            reqwest::StatusCode::SERVICE_UNAVAILABLE,
+            "server busy",
+        );
+        assert!(is_model_loading(&err));
+        assert_eq!(JarvisError::from_anyhow(&err).code(), "llm");
+
+        let err = response_error(
+            "llama3.1:8b",
+            reqwest::StatusCode::INTERNAL_SERVER_ERROR,
+            "boom",
+        );
+        assert!(!is_model_loading(&err));
+    }
+
+    #[test]
+    fn test_pull_progress_parsing() {
+        let line = r#"{"status":"pulling 6a0746a1ec1a","digest":"sha256:6a07","total":4661211424,"completed":1048576}"#;
+        let progress: OllamaPullProgress = serde_json::from_str(line).unwrap();
+        assert_eq!(progress.total, Some(4661211424));
+        assert_eq!(progress.completed, Some(1048576));
+
+        let done: OllamaPullProgress = serde_json::from_str(r#"{"status":"success"}"#).unwrap();
+        assert_eq!(done.status, "success");
+        assert!(done.total.is_none());
+    }
 }
@@ -3,11 +3,14 @@
 //! Provides a client for interacting with the Omen AI Gateway for intelligent
 //! model routing, cost optimization, and multi-provider support.
 
+use crate::net::CheckedSend;
 use anyhow::{Context, Result};
 use omen::types::{
     ChatCompletionRequest, ChatCompletionResponse, ChatMessage, MessageContent, OmenConfig,
 };
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
 /// Client for interacting with Omen AI Gateway
 #[derive(Clone)]
@@ -21,7 +24,7 @@ impl OmenClient {
     /// Create a new Omen client
     pub fn new(base_url: String, api_key: Option<String>) -> Self {
         Self {
-            http_client: reqwest::Client::new(),
+            http_client: crate::net::client(),
             base_url,
             api_key,
         }
@@ -47,60 +50,81 @@ impl OmenClient {
         intent: Option<&str>,
         stream: bool,
     ) -> Result<ChatCompletionResponse> {
-        let mut tags = HashMap::new();
-        tags.insert("source".to_string(), "jarvis".to_string());
+        let request = Self::build_request(messages, intent, stream);
+        let (body, _) = self.send(&request).await?;
 
-        if let Some(intent) = intent {
-            tags.insert("intent".to_string(), intent.to_string());
+        serde_json::from_value(body).context("Failed to parse Omen response")
+    }
+
+    /// Generate a reply along with routing metadata: which model and provider
+    /// served it, how long it took, and the token usage Omen reported.
+    ///
+    /// The intent selects the same system prompt as [`code`](Self::code),
+    /// [`system`](Self::system) and [`devops`](Self::devops).
+    pub async fn generate_detailed(
+        &self,
+        prompt: &str,
+        intent: Option<&str>,
+    ) -> Result<OmenGeneration> {
+        let mut messages = Vec::new();
+        if let Some(system) = intent.and_then(system_prompt_for) {
+            messages.push(chat_message("system", system));
         }
+        messages.push(chat_message("user", prompt));
 
-        let request = ChatCompletionRequest {
-            model: "auto".to_string(), // Let Omen choose optimal model
-            messages,
-            temperature: Some(0.7),
-            max_tokens: Some(2048),
-            stream,
-            top_p: None,
-            frequency_penalty: None,
-            presence_penalty: None,
-            stop: None,
-            tools: None,
-            tool_choice: None,
-            tags: Some(tags),
-            omen: Some(OmenConfig {
-                strategy: Some("single".to_string()),
-                budget_usd: Some(0.10),
-                max_latency_ms: Some(5000),
-                ..Default::default()
-            }),
-        };
+        let request = Self::build_request(messages, intent, false);
+        let (body, meta) = self.send(&request).await?;
 
-        let url = format!("{}/chat/completions", self.base_url);
-        tracing::debug!("Sending request to Omen: {}", url);
+        Ok(OmenGeneration {
+            text: response_text(&body),
+            meta,
+        })
+    }
 
-        let mut req_builder = self.http_client.post(&url).json(&request);
+    /// Whether Omen answers its health endpoint within a couple of seconds
+    pub async fn health_check(&self) -> Result<bool> {
+        let url = format!("{}/health", self.root_url());
 
+        match self
+            .http_client
+            .get(&url)
+            .timeout(HEALTH_CHECK_TIMEOUT)
+            .checked_send()
+            .await
+        {
+            Ok(response) => Ok(response.status().is_success()),
+            Err(_) => Ok(false),
+        }
+    }
+
+    /// Models Omen can route to
+    pub async fn list_models(&self) -> Result<Vec<OmenModel>> {
+        #[derive(Deserialize)]
+        struct ModelList {
+            data: Vec<OmenModel>,
+        }
+
+        let url = format!("{}/models", self.base_url);
+        let mut req_builder = self.http_client.get(&url);
         if let Some(ref key) = self.api_key {
             req_builder = req_builder.bearer_auth(key);
         }
 
         let response = req_builder
-            .send()
+            .checked_send()
             .await
-            .context("Failed to send request to Omen")?;
+            .context("Failed to list Omen models")?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_else(|_| String::from("Unknown error"));
-            anyhow::bail!("Omen API error ({}): {}", status, error_text);
+            anyhow::bail!("Failed to list Omen models: {}", response.status());
         }
 
-        let result = response
+        let list: ModelList = response
             .json()
             .await
-            .context("Failed to parse Omen response")?;
+            .context("Failed to parse Omen model list")?;
 
-        Ok(result)
+        Ok(list.data)
     }
 
     /// Send a simple text prompt and get response
@@ -109,13 +133,7 @@ impl OmenClient {
     /// * `prompt` - The text prompt
     /// * `intent` - Optional intent hint (code, system, devops, reason)
     pub async fn complete(&self, prompt: &str, intent: Option<&str>) -> Result<String> {
-        let messages = vec![ChatMessage {
-            role: "user".to_string(),
-            content: MessageContent::Text(prompt.to_string()),
-            name: None,
-            tool_calls: None,
-            tool_call_id: None,
-        }];
+        let messages = vec![chat_message("user", prompt)];
 
         let response = self.chat_completion(messages, intent, false).await?;
 
@@ -133,22 +151,7 @@ impl OmenClient {
         user: &str,
         intent: Option<&str>,
     ) -> Result<String> {
-        let messages = vec![
-            ChatMessage {
-                role: "system".to_string(),
-                content: MessageContent::Text(system.to_string()),
-                name: None,
-                tool_calls: None,
-                tool_call_id: None,
-            },
-            ChatMessage {
-                role: "user".to_string(),
-                content: MessageContent::Text(user.to_string()),
-                name: None,
-                tool_calls: None,
-                tool_call_id: None,
-            },
-        ];
+        let messages = vec![chat_message("system", system), chat_message("user", user)];
 
         let response = self.chat_completion(messages, intent, false).await?;
 
@@ -161,28 +164,22 @@ impl OmenClient {
 
     /// Code generation task
     pub async fn code(&self, request: &str) -> Result<String> {
-        let system = "You are an expert Rust programmer. Generate clean, idiomatic, and well-documented code.";
-        self.complete_with_system(system, request, Some("code"))
-            .await
+        Ok(self.generate_detailed(request, Some("code")).await?.text)
     }
 
     /// System administration task
     pub async fn system(&self, request: &str) -> Result<String> {
-        let system = "You are an expert Linux system administrator specializing in Arch Linux. Provide safe, tested commands with explanations.";
-        self.complete_with_system(system, request, Some("system"))
-            .await
+        Ok(self.generate_detailed(request, Some("system")).await?.text)
     }
 
     /// DevOps task
     pub async fn devops(&self, request: &str) -> Result<String> {
-        let system = "You are an expert DevOps engineer. Provide infrastructure solutions using Docker, Kubernetes, and modern tooling.";
-        self.complete_with_system(system, request, Some("devops"))
-            .await
+        Ok(self.generate_detailed(request, Some("devops")).await?.text)
     }
 
     /// Complex reasoning task
     pub async fn reason(&self, question: &str) -> Result<String> {
-        self.complete(question, Some("reason")).await
+        Ok(self.generate_detailed(question, Some("reason")).await?.text)
     }
 
     /// Get streaming response
@@ -193,13 +190,7 @@ impl OmenClient {
     ) -> Result<impl futures::Stream<Item = Result<String>>> {
         use futures::stream::StreamExt;
 
-        let messages = vec![ChatMessage {
-            role: "user".to_string(),
-            content: MessageContent::Text(prompt.to_string()),
-            name: None,
-            tool_calls: None,
-            tool_call_id: None,
-        }];
+        let messages = vec![chat_message("user", prompt)];
 
         let mut tags = HashMap::new();
         tags.insert("source".to_string(), "jarvis".to_string());
@@ -231,7 +222,7 @@ impl OmenClient {
             req_builder = req_builder.bearer_auth(key);
         }
 
-        let response = req_builder.send().await?;
+        let response = req_builder.checked_send().await?;
 
         let stream = response.bytes_stream().map(|chunk| {
             chunk
@@ -244,6 +235,190 @@ impl OmenClient {
 
         Ok(stream)
     }
+
+    fn build_request(
+        messages: Vec<ChatMessage>,
+        intent: Option<&str>,
+        stream: bool,
+    ) -> ChatCompletionRequest {
+        let mut tags = HashMap::new();
+        tags.insert("source".to_string(), "jarvis".to_string());
+
+        if let Some(intent) = intent {
+            tags.insert("intent".to_string(), intent.to_string());
+        }
+
+        ChatCompletionRequest {
+            model: "auto".to_string(), // Let Omen choose optimal model
+            messages,
+            temperature: Some(0.7),
+            max_tokens: Some(2048),
+            stream,
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            tools: None,
+            tool_choice: None,
+            tags: Some(tags),
+            omen: Some(OmenConfig {
+                strategy: Some("single".to_string()),
+                budget_usd: Some(0.10),
+                max_latency_ms: Some(5000),
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// POST a completion request; returns the raw body and its routing metadata
+    async fn send(
+        &self,
+        request: &ChatCompletionRequest,
+    ) -> Result<(serde_json::Value, OmenResponseMeta)> {
+        let url = format!("{}/chat/completions", self.base_url);
+        tracing::debug!("Sending request to Omen: {}", url);
+
+        let mut req_builder = self.http_client.post(&url).json(request);
+
+        if let Some(ref key) = self.api_key {
+            req_builder = req_builder.bearer_auth(key);
+        }
+
+        let started = Instant::now();
+        let response = req_builder
+            .checked_send()
+            .await
+            .context("Failed to send request to Omen")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| String::from("Unknown error"));
+            anyhow::bail!("Omen API error ({}): {}", status, error_text);
+        }
+
+        let headers = response.headers().clone();
+        let body: serde_json::Value = response
+            .json()
+            .await
+            .context("Failed to parse Omen response")?;
+        let meta = OmenResponseMeta::from_response(&headers, &body, started.elapsed());
+
+        Ok((body, meta))
+    }
+
+    /// Base URL without the trailing `/v1` API prefix
+    fn root_url(&self) -> &str {
+        let base = self.base_url.trim_end_matches('/');
+        base.strip_suffix("/v1").unwrap_or(base)
+    }
+}
+
+/// A model Omen can route requests to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OmenModel {
+    pub id: String,
+    /// Upstream provider, reported as `owned_by` in the OpenAI-style listing
+    #[serde(default, alias = "owned_by")]
+    pub provider: Option<String>,
+}
+
+/// Routing details of a single Omen response. Every field is optional since
+/// older gateways and passthrough providers omit some or all of them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OmenResponseMeta {
+    /// Model that actually served the request (not the requested `auto`)
+    pub served_model: Option<String>,
+    pub provider: Option<String>,
+    /// Latency reported by Omen, or the measured round trip when it reports none
+    pub latency_ms: Option<u64>,
+    pub prompt_tokens: Option<u64>,
+    pub completion_tokens: Option<u64>,
+}
+
+impl OmenResponseMeta {
+    /// Read metadata from `x-omen-*` headers, falling back to the response body
+    fn from_response(
+        headers: &reqwest::header::HeaderMap,
+        body: &serde_json::Value,
+        elapsed: Duration,
+    ) -> Self {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let body_str = |pointer: &str| {
+            body.pointer(pointer)
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+        };
+        let body_u64 = |pointer: &str| body.pointer(pointer).and_then(|v| v.as_u64());
+
+        Self {
+            served_model: header("x-omen-model")
+                .or_else(|| body_str("/omen/model"))
+                .or_else(|| body_str("/model"))
+                .filter(|model| model != "auto"),
+            provider: header("x-omen-provider").or_else(|| body_str("/omen/provider")),
+            latency_ms: header("x-omen-latency-ms")
+                .and_then(|v| v.parse().ok())
+                .or_else(|| body_u64("/omen/latency_ms"))
+                .or(Some(elapsed.as_millis() as u64)),
+            prompt_tokens: body_u64("/usage/prompt_tokens"),
+            completion_tokens: body_u64("/usage/completion_tokens"),
+        }
+    }
+}
+
+/// Reply text plus the metadata of the response that produced it
+#[derive(Debug, Clone)]
+pub struct OmenGeneration {
+    pub text: String,
+    pub meta: OmenResponseMeta,
+}
+
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+fn system_prompt_for(intent: &str) -> Option<&'static str> {
+    match intent {
+        "code" => Some(
+            "You are an expert Rust programmer. Generate clean, idiomatic, and well-documented code.",
+        ),
+        "system" => Some(
+            "You are an expert Linux system administrator specializing in Arch Linux. Provide safe, tested commands with explanations.",
+        ),
+        "devops" => Some(
+            "You are an expert DevOps engineer. Provide infrastructure solutions using Docker, Kubernetes, and modern tooling.",
+        ),
+        _ => None,
+    }
+}
+
+fn chat_message(role: &str, text: &str) -> ChatMessage {
+    ChatMessage {
+        role: role.to_string(),
+        content: MessageContent::Text(text.to_string()),
+        name: None,
+        tool_calls: None,
+        tool_call_id: None,
+    }
+}
+
+/// Text of the first choice; content may be a string or a list of text parts
+fn response_text(body: &serde_json::Value) -> String {
+    match body.pointer("/choices/0/message/content") {
+        Some(serde_json::Value::String(text)) => text.clone(),
+        Some(serde_json::Value::Array(parts)) => parts
+            .iter()
+            .filter_map(|part| part.get("text").and_then(|t| t.as_str()))
+            .collect::<Vec<_>>()
+            .join(""),
+        _ => String::new(),
+    }
 }
 
 #[cfg(test)]
@@ -260,4 +435,116 @@ mod tests {
         assert_eq!(client.base_url, "http://localhost:8080/v1");
         assert!(client.api_key.is_some());
     }
+
+    /// Serve one canned HTTP response and return the base URL
+    async fn mock_omen(headers: &'static str, body: &'static str) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            // Read until the headers and the announced body have arrived
+            loop {
+                let n = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request);
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text
+                        .lines()
+                        .find_map(|l| {
+                            l.to_ascii_lowercase()
+                                .strip_prefix("content-length:")
+                                .map(|v| v.trim().parse::<usize>().unwrap_or(0))
+                        })
+                        .unwrap_or(0);
+                    if request.len() >= end + 4 + length {
+                        break;
+                    }
+                }
+                if n == 0 {
+                    break;
+                }
+            }
+
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\n{}content-length: {}\r\nconnection: close\r\n\r\n{}",
+                headers,
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        format!("http://{}/v1", addr)
+    }
+
+    #[tokio::test]
+    async fn test_generate_detailed_reads_metadata() {
+        let base_url = mock_omen(
+            "x-omen-model: qwen2.5-coder:32b\r\nx-omen-provider: ollama\r\nx-omen-latency-ms: 812\r\n",
+            r#"{"model":"auto","choices":[{"message":{"role":"assistant","content":"fn main() {}"}}],"usage":{"prompt_tokens":42,"completion_tokens":7}}"#,
+        )
+        .await;
+        let client = OmenClient::new(base_url, None);
+
+        let generation = client
+            .generate_detailed("write main", Some("code"))
+            .await
+            .unwrap();
+        assert_eq!(generation.text, "fn main() {}");
+        assert_eq!(
+            generation.meta,
+            OmenResponseMeta {
+                served_model: Some("qwen2.5-coder:32b".to_string()),
+                provider: Some("ollama".to_string()),
+                latency_ms: Some(812),
+                prompt_tokens: Some(42),
+                completion_tokens: Some(7),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_generate_detailed_without_metadata() {
+        let base_url = mock_omen(
+            "",
+            r#"{"choices":[{"message":{"role":"assistant","content":"hello"}}]}"#,
+        )
+        .await;
+        let client = OmenClient::new(base_url, None);
+
+        let generation = client.generate_detailed("hi", None).await.unwrap();
+        assert_eq!(generation.text, "hello");
+        assert_eq!(generation.meta.served_model, None);
+        assert_eq!(generation.meta.provider, None);
+        assert_eq!(generation.meta.prompt_tokens, None);
+        assert_eq!(generation.meta.completion_tokens, None);
+        // Falls back to the measured round trip
+        assert!(generation.meta.latency_ms.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_health_check() {
+        let base_url = mock_omen("", r#"{"status":"ok"}"#).await;
+        assert!(
+            OmenClient::new(base_url, None)
+                .health_check()
+                .await
+                .unwrap()
+        );
+
+        // Nothing listens on the discard port
+        let client = OmenClient::new("http://127.0.0.1:9/v1".to_string(), None);
+        assert!(!client.health_check().await.unwrap());
+    }
+
+    #[test]
+    fn test_root_url_strips_api_prefix() {
+        let client = OmenClient::new("http://omen.lan:8080/v1/".to_string(), None);
+        assert_eq!(client.root_url(), "http://omen.lan:8080");
+    }
 }
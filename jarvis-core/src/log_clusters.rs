@@ -0,0 +1,180 @@
+//! Grouping log messages that differ only in their variable parts
+//!
+//! "Connection from 10.0.0.5 port 51234 refused" and the same line from
+//! 10.0.0.9 are one problem. [`normalize`] replaces addresses, ids, hex, and
+//! numbers with placeholders, and [`fingerprint`] names the result per
+//! identifier. Journal analysis reports clusters this way, and `jarvis logs
+//! follow` deduplicates its annotations by the same fingerprint.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+static UUID: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}\b")
+        .expect("valid regex")
+});
+static IP: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b\d{1,3}(?:\.\d{1,3}){3}(?::\d+)?\b").expect("valid regex"));
+/// Candidates only: a word counts as hex when it mixes digits and letters,
+/// so "deadline" and "1714521600" are left alone
+static HEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b0x[0-9a-fA-F]+\b|\b[0-9a-fA-F]{6,}\b").expect("valid regex"));
+static NUMBER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\d+").expect("valid regex"));
+static SPACE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\s+").expect("valid regex"));
+
+/// `message` with its variable parts replaced by placeholders
+pub fn normalize(message: &str) -> String {
+    let text = UUID.replace_all(message.trim(), "<uuid>");
+    let text = IP.replace_all(&text, "<ip>");
+    let text = HEX.replace_all(&text, |caps: &regex::Captures| {
+        let word = &caps[0];
+        let mixed = word.bytes().any(|b| b.is_ascii_digit())
+            && word.bytes().any(|b| b.is_ascii_alphabetic());
+        if word.starts_with("0x") || mixed {
+            "<hex>".to_string()
+        } else {
+            word.to_string()
+        }
+    });
+    let text = NUMBER.replace_all(&text, "<n>");
+    SPACE.replace_all(&text, " ").into_owned()
+}
+
+/// Stable id of the cluster `message` from `identifier` belongs to
+pub fn fingerprint(identifier: &str, message: &str) -> String {
+    let digest = md5::compute(format!("{}\0{}", identifier, normalize(message)));
+    format!("{:x}", digest)[..16].to_string()
+}
+
+/// Messages sharing a fingerprint
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cluster {
+    pub fingerprint: String,
+    pub identifier: String,
+    /// The normalized message
+    pub template: String,
+    /// The first message seen
+    pub example: String,
+    pub count: usize,
+}
+
+/// Clusters in the order they were first seen
+#[derive(Debug, Default)]
+pub struct Clusters {
+    clusters: Vec<Cluster>,
+    index: HashMap<String, usize>,
+}
+
+impl Clusters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count `message` into its cluster, returning that cluster
+    pub fn add(&mut self, identifier: &str, message: &str) -> &Cluster {
+        let fingerprint = fingerprint(identifier, message);
+        let position = match self.index.get(&fingerprint) {
+            Some(&position) => {
+                self.clusters[position].count += 1;
+                position
+            }
+            None => {
+                self.index.insert(fingerprint.clone(), self.clusters.len());
+                self.clusters.push(Cluster {
+                    fingerprint,
+                    identifier: identifier.to_string(),
+                    template: normalize(message),
+                    example: message.trim().to_string(),
+                    count: 1,
+                });
+                self.clusters.len() - 1
+            }
+        };
+        &self.clusters[position]
+    }
+
+    pub fn get(&self, fingerprint: &str) -> Option<&Cluster> {
+        self.index
+            .get(fingerprint)
+            .map(|&position| &self.clusters[position])
+    }
+
+    pub fn len(&self) -> usize {
+        self.clusters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.clusters.is_empty()
+    }
+
+    /// The `limit` largest clusters, ties in first-seen order
+    pub fn top(&self, limit: usize) -> Vec<Cluster> {
+        let mut clusters = self.clusters.clone();
+        clusters.sort_by(|a, b| b.count.cmp(&a.count));
+        clusters.truncate(limit);
+        clusters
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_variable_parts() {
+        let cases = [
+            (
+                "Failed password for root from 10.0.0.5 port 51234 ssh2",
+                "Failed password for root from <ip> port <n> ssh<n>",
+            ),
+            (
+                "worker 3f2a9c1e-7b4d-4e8f-9a6b-0c1d2e3f4a5b exited",
+                "worker <uuid> exited",
+            ),
+            (
+                "segfault at 0x7f3a2b ip 00007f3a2b1c4d5e",
+                "segfault at <hex> ip <hex>",
+            ),
+            (
+                "nvme0:  I/O   timeout, tag 12",
+                "nvme<n>: I/O timeout, tag <n>",
+            ),
+            ("upstream 127.0.0.1:8080 refused", "upstream <ip> refused"),
+            ("decided to defer 5a", "decided to defer <n>a"),
+        ];
+        for (message, expected) in cases {
+            assert_eq!(normalize(message), expected, "{}", message);
+        }
+    }
+
+    #[test]
+    fn test_clusters_count_by_fingerprint() {
+        let mut clusters = Clusters::new();
+        clusters.add("sshd", "Failed password for root from 10.0.0.5 port 51234");
+        clusters.add("kernel", "nvme0: I/O timeout");
+        let cluster = clusters.add("sshd", "Failed password for root from 10.0.0.9 port 40022");
+        assert_eq!(cluster.count, 2);
+        assert_eq!(
+            cluster.example,
+            "Failed password for root from 10.0.0.5 port 51234"
+        );
+
+        // The same text from another identifier is its own problem
+        clusters.add("nginx", "Failed password for root from 10.0.0.5 port 1");
+        assert_eq!(clusters.len(), 3);
+        assert_ne!(
+            fingerprint("sshd", "timeout 1"),
+            fingerprint("nginx", "timeout 1")
+        );
+        assert_eq!(
+            fingerprint("sshd", "timeout 1"),
+            fingerprint("sshd", "timeout 20")
+        );
+
+        let top = clusters.top(2);
+        assert_eq!(top[0].identifier, "sshd");
+        assert_eq!(top[1].identifier, "kernel");
+    }
+}
@@ -0,0 +1,871 @@
+//! Following a unit's or container's logs live, annotating error bursts
+//!
+//! A [`LogSource`] yields lines from `journalctl --follow` or `docker logs
+//! --follow`; everything after that is shared. [`Follower`] keeps a sliding
+//! window of recent lines and reports a burst when the error-level lines in
+//! the window reach a threshold or one line looks like a crash (a panic,
+//! traceback, or segfault). [`follow`] asks the [`Annotator`] about each
+//! burst in the background so the tail never waits on the model. Annotations
+//! are deduplicated by [`crate::log_clusters`] fingerprint, the same grouping
+//! journal analysis reports, and are spaced at least
+//! [`FollowConfig::min_annotation_interval`] apart in log time.
+
+use crate::llm::{Intent, LLMRouter};
+use crate::log_clusters::{Cluster, Clusters};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use futures::future::BoxFuture;
+use futures::stream::FuturesUnordered;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{HashSet, VecDeque};
+use std::future::Future;
+use std::process::Stdio;
+use std::sync::LazyLock;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdout, Command};
+use tokio::sync::mpsc;
+
+/// Lines of history shown before following starts
+pub const BACKLOG_LINES: usize = 10;
+
+/// The window never holds more lines than this, however chatty the source
+const MAX_WINDOW_LINES: usize = 2000;
+
+static CRASH: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"panicked at|Traceback \(most recent call last\)|Segmentation fault|segfault at|core dumped|Unhandled exception|fatal error:|FATAL EXCEPTION|stack overflow",
+    )
+    .expect("valid regex")
+});
+static ERROR_WORD: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(error|err|fatal|panic|critical|crit|exception|failed)\b")
+        .expect("valid regex")
+});
+static WARNING_WORD: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\b(warn|warning)\b").expect("valid regex"));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Level {
+    Error,
+    Warning,
+    Info,
+}
+
+impl Level {
+    /// From a syslog priority: 0 (emerg) through 3 (err) are errors
+    pub fn from_priority(priority: u8) -> Self {
+        match priority {
+            0..=3 => Level::Error,
+            4 => Level::Warning,
+            _ => Level::Info,
+        }
+    }
+
+    /// Guessed from the text, for sources without priorities
+    pub fn guess(message: &str) -> Self {
+        if CRASH.is_match(message) || ERROR_WORD.is_match(message) {
+            Level::Error
+        } else if WARNING_WORD.is_match(message) {
+            Level::Warning
+        } else {
+            Level::Info
+        }
+    }
+}
+
+/// One line from a source
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LogLine {
+    pub timestamp: DateTime<Utc>,
+    /// The syslog identifier or container name
+    pub identifier: String,
+    pub level: Level,
+    pub message: String,
+}
+
+impl LogLine {
+    pub fn is_crash(&self) -> bool {
+        CRASH.is_match(&self.message)
+    }
+
+    fn render(&self) -> String {
+        format!(
+            "{} {}: {}",
+            self.timestamp.format("%H:%M:%S"),
+            self.identifier,
+            self.message
+        )
+    }
+}
+
+/// Where followed lines come from
+#[async_trait]
+pub trait LogSource: Send {
+    /// What is being followed, e.g. "journal of nginx.service"
+    fn describe(&self) -> String;
+
+    /// The next line, or None once the source has ended. Must be
+    /// cancel-safe: [`follow`] drops the call when something else is ready.
+    async fn next_line(&mut self) -> Result<Option<LogLine>>;
+}
+
+/// `journalctl --follow` for one unit
+pub struct JournalSource {
+    unit: String,
+    child: Child,
+    lines: Lines<BufReader<ChildStdout>>,
+}
+
+impl JournalSource {
+    pub fn spawn(unit: &str) -> Result<Self> {
+        let mut child = Command::new("journalctl")
+            .args(["--follow", "--lines", &BACKLOG_LINES.to_string()])
+            .args(["--output", "json", "--no-pager", "--unit", unit])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            // Ctrl-C is for Jarvis, which ends the session and drops this
+            .process_group(0)
+            .kill_on_drop(true)
+            .spawn()
+            .context("Failed to start journalctl")?;
+        let stdout = child.stdout.take().context("journalctl has no stdout")?;
+        Ok(Self {
+            unit: unit.to_string(),
+            child,
+            lines: BufReader::new(stdout).lines(),
+        })
+    }
+}
+
+#[async_trait]
+impl LogSource for JournalSource {
+    fn describe(&self) -> String {
+        format!("journal of {}", self.unit)
+    }
+
+    async fn next_line(&mut self) -> Result<Option<LogLine>> {
+        while let Some(raw) = self.lines.next_line().await? {
+            if let Some(line) = parse_journal_entry(&raw) {
+                return Ok(Some(line));
+            }
+        }
+        ended(&mut self.child, "journalctl").await?;
+        Ok(None)
+    }
+}
+
+/// `docker logs --follow` for one container, stdout and stderr merged
+pub struct DockerSource {
+    container: String,
+    child: Child,
+    lines: mpsc::Receiver<std::io::Result<String>>,
+}
+
+impl DockerSource {
+    pub fn spawn(container: &str) -> Result<Self> {
+        let mut child = Command::new("docker")
+            .args([
+                "logs",
+                "--follow",
+                "--timestamps",
+                "--tail",
+                &BACKLOG_LINES.to_string(),
+            ])
+            .arg(container)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0)
+            .kill_on_drop(true)
+            .spawn()
+            .context("Failed to start docker logs")?;
+        let stdout = child.stdout.take().context("docker logs has no stdout")?;
+        let stderr = child.stderr.take().context("docker logs has no stderr")?;
+
+        // Receiving from a channel is cancel-safe where a select over two
+        // line readers would not be
+        let (tx, lines) = mpsc::channel(256);
+        forward_lines(stdout, tx.clone());
+        forward_lines(stderr, tx);
+        Ok(Self {
+            container: container.to_string(),
+            child,
+            lines,
+        })
+    }
+}
+
+fn forward_lines(
+    reader: impl AsyncRead + Unpin + Send + 'static,
+    tx: mpsc::Sender<std::io::Result<String>>,
+) {
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(reader).lines();
+        loop {
+            let line = match lines.next_line().await {
+                Ok(Some(line)) => Ok(line),
+                Ok(None) => break,
+                Err(e) => Err(e),
+            };
+            let failed = line.is_err();
+            if tx.send(line).await.is_err() || failed {
+                break;
+            }
+        }
+    });
+}
+
+#[async_trait]
+impl LogSource for DockerSource {
+    fn describe(&self) -> String {
+        format!("container {}", self.container)
+    }
+
+    async fn next_line(&mut self) -> Result<Option<LogLine>> {
+        match self.lines.recv().await {
+            Some(raw) => Ok(Some(parse_docker_line(&self.container, &raw?))),
+            None => {
+                ended(&mut self.child, "docker logs").await?;
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Once a follower's output ends: an error if it exited unsuccessfully
+async fn ended(child: &mut Child, program: &str) -> Result<()> {
+    let status = child.wait().await?;
+    if status.success() {
+        return Ok(());
+    }
+    let mut stderr = String::new();
+    if let Some(mut pipe) = child.stderr.take() {
+        pipe.read_to_string(&mut stderr).await.ok();
+    }
+    anyhow::bail!("{} exited with {}: {}", program, status, stderr.trim())
+}
+
+/// One `journalctl --output json` line; None for entries without a message
+pub fn parse_journal_entry(raw: &str) -> Option<LogLine> {
+    let entry: serde_json::Value = serde_json::from_str(raw).ok()?;
+    let field = |name: &str| entry.get(name).and_then(|value| value.as_str());
+
+    // Messages that aren't valid UTF-8 arrive as arrays of bytes
+    let message = match entry.get("MESSAGE")? {
+        serde_json::Value::String(message) => message.clone(),
+        serde_json::Value::Array(bytes) => {
+            let bytes: Vec<u8> = bytes
+                .iter()
+                .filter_map(|b| b.as_u64())
+                .map(|b| b as u8)
+                .collect();
+            String::from_utf8_lossy(&bytes).into_owned()
+        }
+        _ => return None,
+    };
+    let level = field("PRIORITY")
+        .and_then(|priority| priority.parse().ok())
+        .map(Level::from_priority)
+        .unwrap_or(Level::Info);
+    let identifier = field("SYSLOG_IDENTIFIER")
+        .or_else(|| field("_COMM"))
+        .unwrap_or("unknown")
+        .to_string();
+    let timestamp = field("__REALTIME_TIMESTAMP")
+        .and_then(|micros| micros.parse().ok())
+        .and_then(DateTime::from_timestamp_micros)
+        .unwrap_or_else(Utc::now);
+
+    Some(LogLine {
+        timestamp,
+        identifier,
+        level,
+        message,
+    })
+}
+
+/// One `docker logs --timestamps` line: an RFC 3339 timestamp, then the message
+pub fn parse_docker_line(container: &str, raw: &str) -> LogLine {
+    let (timestamp, message) = match raw.split_once(' ') {
+        Some((stamp, message)) => match DateTime::parse_from_rfc3339(stamp) {
+            Ok(timestamp) => (timestamp.with_timezone(&Utc), message),
+            Err(_) => (Utc::now(), raw),
+        },
+        None => (Utc::now(), raw),
+    };
+    LogLine {
+        timestamp,
+        identifier: container.to_string(),
+        level: Level::guess(message),
+        message: message.to_string(),
+    }
+}
+
+/// What explains a burst
+#[async_trait]
+pub trait Annotator: Send + Sync {
+    async fn annotate(&self, prompt: &str) -> Result<String>;
+}
+
+#[async_trait]
+impl Annotator for LLMRouter {
+    async fn annotate(&self, prompt: &str) -> Result<String> {
+        self.generate_with_intent(prompt, Intent::System).await
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FollowConfig {
+    /// How far back error lines count toward a burst
+    pub window: Duration,
+    /// Error-level lines within the window that make a burst
+    pub error_threshold: usize,
+    /// Log time between two annotations
+    pub min_annotation_interval: Duration,
+    /// Lines of the window sent with a burst
+    pub context_lines: usize,
+}
+
+impl Default for FollowConfig {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(60),
+            error_threshold: 5,
+            min_annotation_interval: Duration::from_secs(120),
+            context_lines: 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BurstReason {
+    /// `errors` error-level lines within the window
+    ErrorRate { errors: usize, window_secs: u64 },
+    /// A panic, traceback, or segfault
+    Crash,
+}
+
+/// An error cluster worth an annotation
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Burst {
+    pub source: String,
+    pub reason: BurstReason,
+    pub cluster: Cluster,
+    /// The newest lines of the window, oldest first
+    pub context: Vec<String>,
+}
+
+impl Burst {
+    pub fn prompt(&self) -> String {
+        let reason = match &self.reason {
+            BurstReason::ErrorRate {
+                errors,
+                window_secs,
+            } => format!(
+                "{} error lines arrived within {} seconds",
+                errors, window_secs
+            ),
+            BurstReason::Crash => "a line looks like a crash".to_string(),
+        };
+        let mut prompt = String::new();
+        if crate::read_only::is_read_only() {
+            prompt.push_str(&format!("{}\n\n", crate::read_only::PROMPT_NOTE));
+        }
+        prompt.push_str(&format!(
+            "You are watching the live logs of the {} on a Linux system; {}. \
+             The line that triggered this was:\n{}\n\nRecent lines:\n{}\n\n\
+             In one short paragraph, say what is most likely going wrong and what to check first.",
+            self.source,
+            reason,
+            self.cluster.example,
+            self.context.join("\n"),
+        ));
+        prompt
+    }
+}
+
+/// Sliding-window burst detection over the lines of one source
+pub struct Follower {
+    source: String,
+    config: FollowConfig,
+    window: VecDeque<LogLine>,
+    clusters: Clusters,
+    annotated: HashSet<String>,
+    last_annotation: Option<DateTime<Utc>>,
+    lines: usize,
+    error_lines: usize,
+    requested: usize,
+    suppressed: usize,
+}
+
+impl Follower {
+    pub fn new(source: &str, config: FollowConfig) -> Self {
+        Self {
+            source: source.to_string(),
+            config,
+            window: VecDeque::new(),
+            clusters: Clusters::new(),
+            annotated: HashSet::new(),
+            last_annotation: None,
+            lines: 0,
+            error_lines: 0,
+            requested: 0,
+            suppressed: 0,
+        }
+    }
+
+    /// Take in one line; a burst comes back when it should be annotated.
+    /// Clusters already annotated are skipped, and bursts closer than the
+    /// minimum interval to the previous annotation are counted as suppressed.
+    pub fn push(&mut self, line: LogLine) -> Option<Burst> {
+        self.lines += 1;
+        while self
+            .window
+            .front()
+            .is_some_and(|oldest| age(oldest.timestamp, line.timestamp) > self.config.window)
+            || self.window.len() >= MAX_WINDOW_LINES
+        {
+            self.window.pop_front();
+        }
+        self.window.push_back(line.clone());
+
+        let crash = line.is_crash();
+        if line.level != Level::Error && !crash {
+            return None;
+        }
+        self.error_lines += 1;
+        let cluster = self.clusters.add(&line.identifier, &line.message).clone();
+
+        let errors = self
+            .window
+            .iter()
+            .filter(|line| line.level == Level::Error)
+            .count();
+        let reason = if crash {
+            BurstReason::Crash
+        } else if errors >= self.config.error_threshold {
+            BurstReason::ErrorRate {
+                errors,
+                window_secs: self.config.window.as_secs(),
+            }
+        } else {
+            return None;
+        };
+
+        if self.annotated.contains(&cluster.fingerprint) {
+            return None;
+        }
+        if self
+            .last_annotation
+            .is_some_and(|last| age(last, line.timestamp) < self.config.min_annotation_interval)
+        {
+            self.suppressed += 1;
+            return None;
+        }
+
+        self.annotated.insert(cluster.fingerprint.clone());
+        self.last_annotation = Some(line.timestamp);
+        self.requested += 1;
+        let skip = self.window.len().saturating_sub(self.config.context_lines);
+        Some(Burst {
+            source: self.source.clone(),
+            reason,
+            cluster,
+            context: self.window.iter().skip(skip).map(LogLine::render).collect(),
+        })
+    }
+
+    pub fn summary(&self) -> FollowSummary {
+        FollowSummary {
+            source: self.source.clone(),
+            lines: self.lines,
+            error_lines: self.error_lines,
+            clusters: self.clusters.top(usize::MAX),
+            annotations_requested: self.requested,
+            annotations: 0,
+            annotations_failed: 0,
+            suppressed: self.suppressed,
+        }
+    }
+}
+
+/// How long before `now` `then` was; zero for lines that arrive out of order
+fn age(then: DateTime<Utc>, now: DateTime<Utc>) -> Duration {
+    (now - then).to_std().unwrap_or_default()
+}
+
+/// What happened while following, for the caller to print
+#[derive(Debug)]
+pub enum FollowEvent {
+    Line(LogLine),
+    Annotation { burst: Burst, text: String },
+    AnnotationFailed { burst: Burst, error: anyhow::Error },
+}
+
+/// A finished session
+#[derive(Debug, Clone, Serialize)]
+pub struct FollowSummary {
+    pub source: String,
+    pub lines: usize,
+    pub error_lines: usize,
+    /// Error clusters seen, largest first
+    pub clusters: Vec<Cluster>,
+    pub annotations_requested: usize,
+    /// Annotations that came back before the session ended
+    pub annotations: usize,
+    pub annotations_failed: usize,
+    /// Bursts not annotated because the previous annotation was too recent
+    pub suppressed: usize,
+}
+
+impl FollowSummary {
+    pub fn render(&self) -> String {
+        let mut text = format!(
+            "📋 Followed the {}: {} lines, {} at error level\n",
+            self.source, self.lines, self.error_lines
+        );
+        text.push_str(&format!("🤖 Annotations: {} generated", self.annotations));
+        if self.annotations_failed > 0 {
+            text.push_str(&format!(", {} failed", self.annotations_failed));
+        }
+        let unfinished = self
+            .annotations_requested
+            .saturating_sub(self.annotations + self.annotations_failed);
+        if unfinished > 0 {
+            text.push_str(&format!(", {} still pending at exit", unfinished));
+        }
+        if self.suppressed > 0 {
+            text.push_str(&format!(", {} bursts rate-limited", self.suppressed));
+        }
+        text.push('\n');
+        if self.clusters.is_empty() {
+            text.push_str("✅ No error clusters\n");
+        } else {
+            text.push_str(&format!("🔴 Error clusters ({}):\n", self.clusters.len()));
+            for cluster in &self.clusters {
+                text.push_str(&format!(
+                    "  {:>5}×  {}: {}\n",
+                    cluster.count, cluster.identifier, cluster.template
+                ));
+            }
+        }
+        text
+    }
+}
+
+/// Follow `source` until it ends or `stop` completes, handing every line
+/// and annotation to `on_event` as it arrives. Annotations still running
+/// when `stop` completes are dropped.
+pub async fn follow(
+    source: &mut dyn LogSource,
+    annotator: &dyn Annotator,
+    config: FollowConfig,
+    stop: impl Future<Output = ()>,
+    mut on_event: impl FnMut(FollowEvent),
+) -> Result<FollowSummary> {
+    let mut follower = Follower::new(&source.describe(), config);
+    let mut pending: FuturesUnordered<BoxFuture<'_, (Burst, Result<String>)>> =
+        FuturesUnordered::new();
+    let mut annotations = 0;
+    let mut failed = 0;
+    let mut source_done = false;
+    tokio::pin!(stop);
+
+    while !(source_done && pending.is_empty()) {
+        tokio::select! {
+            _ = &mut stop => break,
+            line = source.next_line(), if !source_done => match line? {
+                Some(line) => {
+                    let burst = follower.push(line.clone());
+                    on_event(FollowEvent::Line(line));
+                    if let Some(burst) = burst {
+                        pending.push(Box::pin(async move {
+                            let result = annotator.annotate(&burst.prompt()).await;
+                            (burst, result)
+                        }));
+                    }
+                }
+                None => source_done = true,
+            },
+            Some((burst, result)) = pending.next(), if !pending.is_empty() => match result {
+                Ok(text) => {
+                    annotations += 1;
+                    on_event(FollowEvent::Annotation { burst, text: text.trim().to_string() });
+                }
+                Err(error) => {
+                    failed += 1;
+                    on_event(FollowEvent::AnnotationFailed { burst, error });
+                }
+            },
+        }
+    }
+
+    let mut summary = follower.summary();
+    summary.annotations = annotations;
+    summary.annotations_failed = failed;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Lines from a script, then either the end or silence
+    struct ScriptedSource {
+        lines: VecDeque<LogLine>,
+        hang_at_end: bool,
+    }
+
+    #[async_trait]
+    impl LogSource for ScriptedSource {
+        fn describe(&self) -> String {
+            "scripted source".to_string()
+        }
+
+        async fn next_line(&mut self) -> Result<Option<LogLine>> {
+            tokio::task::yield_now().await;
+            match self.lines.pop_front() {
+                Some(line) => Ok(Some(line)),
+                None if self.hang_at_end => std::future::pending().await,
+                None => Ok(None),
+            }
+        }
+    }
+
+    #[derive(Default)]
+    struct FakeAnnotator {
+        prompts: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl Annotator for FakeAnnotator {
+        async fn annotate(&self, prompt: &str) -> Result<String> {
+            self.prompts.lock().unwrap().push(prompt.to_string());
+            if prompt.contains("segfault") {
+                anyhow::bail!("model unavailable");
+            }
+            Ok(" The database is refusing connections. ".to_string())
+        }
+    }
+
+    fn line(second: i64, level: Level, message: &str) -> LogLine {
+        LogLine {
+            timestamp: DateTime::from_timestamp(1_714_550_400 + second, 0).unwrap(),
+            identifier: "api".to_string(),
+            level,
+            message: message.to_string(),
+        }
+    }
+
+    fn config() -> FollowConfig {
+        FollowConfig {
+            window: Duration::from_secs(10),
+            error_threshold: 3,
+            min_annotation_interval: Duration::from_secs(30),
+            context_lines: 4,
+        }
+    }
+
+    #[test]
+    fn test_burst_needs_threshold_within_window() {
+        let mut follower = Follower::new("test", config());
+        assert!(follower.push(line(0, Level::Error, "db error 1")).is_none());
+        assert!(
+            follower
+                .push(line(5, Level::Info, "request served"))
+                .is_none()
+        );
+        assert!(follower.push(line(6, Level::Error, "db error 2")).is_none());
+        // The first error has left the window
+        assert!(
+            follower
+                .push(line(12, Level::Error, "db error 3"))
+                .is_none()
+        );
+
+        let burst = follower.push(line(13, Level::Error, "db error 4")).unwrap();
+        assert_eq!(
+            burst.reason,
+            BurstReason::ErrorRate {
+                errors: 3,
+                window_secs: 10
+            }
+        );
+        assert_eq!(burst.cluster.template, "db error <n>");
+        assert_eq!(burst.cluster.count, 4);
+        assert_eq!(burst.context.len(), 4);
+        assert!(burst.context[3].ends_with("api: db error 4"));
+    }
+
+    #[test]
+    fn test_annotations_deduplicated_and_rate_limited() {
+        let mut follower = Follower::new("test", config());
+        for second in 0..3 {
+            follower.push(line(second, Level::Error, &format!("db error {}", second)));
+        }
+        assert_eq!(follower.summary().annotations_requested, 1);
+
+        // A crash right after the last annotation waits for the interval
+        assert!(
+            follower
+                .push(line(
+                    10,
+                    Level::Info,
+                    "thread 'main' panicked at src/main.rs:4"
+                ))
+                .is_none()
+        );
+
+        // The first cluster again, much later: already annotated
+        for second in 100..105 {
+            assert!(
+                follower
+                    .push(line(second, Level::Error, "db error 9"))
+                    .is_none()
+            );
+        }
+        let burst = follower
+            .push(line(
+                140,
+                Level::Info,
+                "thread 'main' panicked at src/main.rs:7",
+            ))
+            .unwrap();
+        assert_eq!(burst.reason, BurstReason::Crash);
+
+        let summary = follower.summary();
+        assert_eq!(summary.annotations_requested, 2);
+        assert_eq!(summary.suppressed, 1);
+        assert_eq!(summary.clusters.len(), 2);
+        assert_eq!(summary.clusters[0].count, 8);
+    }
+
+    #[tokio::test]
+    async fn test_follow_annotates_without_blocking_the_tail() {
+        let mut lines: VecDeque<LogLine> = (0..3)
+            .map(|second| {
+                line(
+                    second,
+                    Level::Error,
+                    &format!("connection refused to 10.0.0.{}", second),
+                )
+            })
+            .collect();
+        lines.push_back(line(
+            40,
+            Level::Error,
+            "segfault at 0x7f3a2b ip 00007f3a2b1c4d5e",
+        ));
+        lines.push_back(line(41, Level::Info, "restarting"));
+        let mut source = ScriptedSource {
+            lines,
+            hang_at_end: false,
+        };
+        let annotator = FakeAnnotator::default();
+
+        let mut events = Vec::new();
+        let summary = follow(
+            &mut source,
+            &annotator,
+            config(),
+            std::future::pending(),
+            |event| events.push(event),
+        )
+        .await
+        .unwrap();
+
+        let lines = events
+            .iter()
+            .filter(|event| matches!(event, FollowEvent::Line(_)))
+            .count();
+        assert_eq!(lines, 5);
+        let annotation = events
+            .iter()
+            .find_map(|event| match event {
+                FollowEvent::Annotation { burst, text } => Some((burst, text)),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(annotation.0.cluster.template, "connection refused to <ip>");
+        assert_eq!(annotation.1, "The database is refusing connections.");
+        assert!(events.iter().any(|event| matches!(
+            event,
+            FollowEvent::AnnotationFailed { burst, .. } if burst.reason == BurstReason::Crash
+        )));
+
+        assert_eq!(summary.lines, 5);
+        assert_eq!(summary.error_lines, 4);
+        assert_eq!(summary.annotations, 1);
+        assert_eq!(summary.annotations_failed, 1);
+        assert!(
+            annotator.prompts.lock().unwrap()[0]
+                .contains("3 error lines arrived within 10 seconds")
+        );
+        let rendered = summary.render();
+        assert!(rendered.contains("1 generated, 1 failed"));
+        assert!(rendered.contains("connection refused to <ip>"));
+    }
+
+    #[tokio::test]
+    async fn test_follow_stops_cleanly() {
+        let mut source = ScriptedSource {
+            lines: VecDeque::from([line(0, Level::Info, "started")]),
+            hang_at_end: true,
+        };
+        let summary = follow(
+            &mut source,
+            &FakeAnnotator::default(),
+            config(),
+            tokio::time::sleep(Duration::from_millis(20)),
+            |_| {},
+        )
+        .await
+        .unwrap();
+        assert_eq!(summary.lines, 1);
+        assert!(summary.render().contains("No error clusters"));
+    }
+
+    #[test]
+    fn test_journald_and_docker_lines_parse_alike() {
+        let journal = parse_journal_entry(
+            r#"{"__REALTIME_TIMESTAMP":"1714550400000000","PRIORITY":"3","SYSLOG_IDENTIFIER":"nginx","MESSAGE":"upstream timed out"}"#,
+        )
+        .unwrap();
+        assert_eq!(journal.level, Level::Error);
+        assert_eq!(journal.identifier, "nginx");
+        assert_eq!(
+            journal.timestamp,
+            DateTime::from_timestamp(1_714_550_400, 0).unwrap()
+        );
+
+        let bytes =
+            parse_journal_entry(r#"{"PRIORITY":"6","_COMM":"app","MESSAGE":[104,105,255]}"#)
+                .unwrap();
+        assert_eq!(bytes.message, "hi\u{fffd}");
+        assert_eq!(bytes.level, Level::Info);
+        assert!(parse_journal_entry(r#"{"PRIORITY":"6"}"#).is_none());
+
+        let docker = parse_docker_line(
+            "web",
+            "2024-05-01T08:00:00.123456789Z ERROR upstream timed out",
+        );
+        assert_eq!(docker.level, Level::Error);
+        assert_eq!(docker.identifier, "web");
+        assert_eq!(docker.message, "ERROR upstream timed out");
+        assert_eq!(docker.timestamp.timestamp(), 1_714_550_400);
+        assert_eq!(
+            parse_docker_line("web", "2024-05-01T08:00:00Z WARN slow").level,
+            Level::Warning
+        );
+        assert_eq!(
+            parse_docker_line("web", "2024-05-01T08:00:00Z GET / 200").level,
+            Level::Info
+        );
+    }
+}
@@ -1,11 +1,17 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use async_trait::async_trait;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Local, Timelike, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::blockchain_agents::*;
+use crate::config::BtrfsMaintenanceConfig;
+use crate::exec::{CommandRunner, SystemRunner};
+use crate::memory::MemoryStore;
+use crate::notify::{Notification, NotifyEvent, NotifySeverity};
+use crate::operations::{ActiveOperations, OperationGuard};
 
 /// Smart Contract Auditor Agent
 /// Specializes in analyzing and auditing smart contracts for security and optimization
@@ -451,6 +457,33 @@ pub struct MaintenanceWindow {
     pub allowed_days: Vec<String>,
 }
 
+impl MaintenanceWindow {
+    /// Whether `now` falls inside the window. `timezone` is either "local"
+    /// or taken as UTC; equal start and end hours mean all day.
+    pub fn is_open_at(&self, now: DateTime<Utc>) -> bool {
+        let (hour, day) = if self.timezone.eq_ignore_ascii_case("local") {
+            let local = now.with_timezone(&Local);
+            (local.hour(), local.format("%A").to_string())
+        } else {
+            (now.hour(), now.format("%A").to_string())
+        };
+
+        let day_allowed = self.allowed_days.is_empty()
+            || self
+                .allowed_days
+                .iter()
+                .any(|d| d.eq_ignore_ascii_case(&day));
+        let (start, end) = (self.start_hour as u32, self.end_hour as u32);
+        let hour_allowed = match start.cmp(&end) {
+            std::cmp::Ordering::Equal => true,
+            std::cmp::Ordering::Less => hour >= start && hour < end,
+            // Wraps past midnight, e.g. 22 to 4
+            std::cmp::Ordering::Greater => hour >= start || hour < end,
+        };
+        day_allowed && hour_allowed
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScheduledTask {
     pub id: String,
@@ -758,3 +791,784 @@ impl Default for MaintenanceConfig {
         }
     }
 }
+
+/// Document key for per-filesystem btrfs maintenance history
+const BTRFS_HISTORY_KEY: &str = "btrfs_history";
+
+/// A mounted btrfs filesystem; subvolume mounts of one filesystem collapse
+/// into a single entry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BtrfsFilesystem {
+    pub uuid: String,
+    pub mount: String,
+}
+
+/// Chunk allocation from `btrfs filesystem usage -b`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct BtrfsUsage {
+    pub device_size: u64,
+    pub unallocated: u64,
+    pub data_size: u64,
+    pub data_used: u64,
+    pub metadata_size: u64,
+    pub metadata_used: u64,
+}
+
+impl BtrfsUsage {
+    pub fn unallocated_percent(&self) -> f64 {
+        percent(self.unallocated, self.device_size)
+    }
+
+    /// How full the allocated data chunks are
+    pub fn data_fill_percent(&self) -> f64 {
+        percent(self.data_used, self.data_size)
+    }
+
+    /// How full the allocated metadata chunks are
+    pub fn metadata_fill_percent(&self) -> f64 {
+        percent(self.metadata_used, self.metadata_size)
+    }
+}
+
+fn percent(part: u64, whole: u64) -> f64 {
+    if whole == 0 {
+        100.0
+    } else {
+        part as f64 * 100.0 / whole as f64
+    }
+}
+
+/// Parsed `btrfs scrub status`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScrubStatus {
+    pub running: bool,
+    pub percent: Option<f64>,
+    /// Error counts by kind from the error summary, e.g. `csum`, `read`
+    pub errors: BTreeMap<String, u64>,
+}
+
+impl ScrubStatus {
+    pub fn total_errors(&self) -> u64 {
+        self.errors.values().sum()
+    }
+
+    pub fn csum_errors(&self) -> u64 {
+        self.errors.get("csum").copied().unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScrubRecord {
+    pub finished_at: DateTime<Utc>,
+    pub duration_secs: u64,
+    pub errors: u64,
+    pub csum_errors: u64,
+    pub cancelled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BalanceRecord {
+    pub finished_at: DateTime<Utc>,
+    pub duration_secs: u64,
+    /// The allocation figures that called for the balance
+    pub reason: String,
+    pub cancelled: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FilesystemHistory {
+    pub mount: String,
+    pub last_scrub: Option<ScrubRecord>,
+    pub last_balance: Option<BalanceRecord>,
+}
+
+impl FilesystemHistory {
+    /// One line for reports, e.g. "last scrub: 12 days ago, 0 errors"
+    pub fn summary(&self, now: DateTime<Utc>) -> String {
+        let mut summary = match &self.last_scrub {
+            Some(scrub) if scrub.cancelled => {
+                format!(
+                    "last scrub: {}, cancelled",
+                    days_ago(scrub.finished_at, now)
+                )
+            }
+            Some(scrub) => format!(
+                "last scrub: {}, {} errors",
+                days_ago(scrub.finished_at, now),
+                scrub.errors
+            ),
+            None => "never scrubbed".to_string(),
+        };
+        if let Some(balance) = &self.last_balance {
+            summary.push_str(&format!(
+                "; last balance: {}",
+                days_ago(balance.finished_at, now)
+            ));
+        }
+        summary
+    }
+}
+
+fn days_ago(then: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    match (now - then).num_days() {
+        0 => "today".to_string(),
+        1 => "1 day ago".to_string(),
+        days => format!("{} days ago", days),
+    }
+}
+
+/// Maintenance history of every btrfs filesystem, keyed by UUID
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BtrfsHistory {
+    pub filesystems: BTreeMap<String, FilesystemHistory>,
+}
+
+impl BtrfsHistory {
+    pub async fn load(memory: &MemoryStore) -> Result<Self> {
+        match memory.get_document(BTRFS_HISTORY_KEY).await? {
+            Some(data) => serde_json::from_str(&data).context("Corrupt btrfs maintenance history"),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub async fn save(&self, memory: &MemoryStore) -> Result<()> {
+        memory
+            .store_document(BTRFS_HISTORY_KEY, &serde_json::to_string(self)?)
+            .await
+    }
+
+    fn entry(&mut self, fs: &BtrfsFilesystem) -> &mut FilesystemHistory {
+        let entry = self.filesystems.entry(fs.uuid.clone()).or_default();
+        entry.mount = fs.mount.clone();
+        entry
+    }
+}
+
+/// What one maintenance pass did to a filesystem
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BtrfsRunResult {
+    pub filesystem: BtrfsFilesystem,
+    pub scrub: Option<ScrubRecord>,
+    pub balance: Option<BalanceRecord>,
+    /// Why a balance was wanted but not started
+    pub balance_skipped: Option<String>,
+    pub findings: Vec<Finding>,
+}
+
+impl BtrfsRunResult {
+    pub fn notification(&self) -> Option<Notification> {
+        let mount = &self.filesystem.mount;
+        if let Some(scrub) = self.scrub.as_ref().filter(|s| s.errors > 0) {
+            let severity = if scrub.csum_errors > 0 {
+                NotifySeverity::Critical
+            } else {
+                NotifySeverity::Warning
+            };
+            return Some(Notification::new(
+                NotifyEvent::HealthChanged,
+                severity,
+                format!("Btrfs scrub found {} errors on {}", scrub.errors, mount),
+                self.findings
+                    .iter()
+                    .map(|f| f.description.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ));
+        }
+
+        let mut done = Vec::new();
+        if let Some(scrub) = &self.scrub {
+            done.push(if scrub.cancelled {
+                "scrub cancelled".to_string()
+            } else {
+                format!("scrubbed in {} min, no errors", scrub.duration_secs / 60)
+            });
+        }
+        if let Some(balance) = &self.balance {
+            done.push(if balance.cancelled {
+                "balance cancelled".to_string()
+            } else {
+                format!("balanced ({})", balance.reason)
+            });
+        }
+        if done.is_empty() {
+            return None;
+        }
+        Some(Notification::new(
+            NotifyEvent::MaintenanceFinished,
+            NotifySeverity::Info,
+            format!("Btrfs maintenance finished on {}", mount),
+            done.join("; "),
+        ))
+    }
+}
+
+/// Btrfs Maintenance Agent
+/// Scrubs btrfs filesystems on a schedule and balances them when chunk
+/// allocation has drifted, inside a maintenance window and only while the
+/// system is quiet
+#[derive(Debug)]
+pub struct BtrfsMaintenanceAgent {
+    pub config: BtrfsMaintenanceConfig,
+    runner: Arc<dyn CommandRunner>,
+    operations: ActiveOperations,
+}
+
+impl BtrfsMaintenanceAgent {
+    /// Scrubs and balances report progress into `operations`
+    pub fn new(config: BtrfsMaintenanceConfig, operations: ActiveOperations) -> Self {
+        Self {
+            config,
+            runner: Arc::new(SystemRunner::default()),
+            operations,
+        }
+    }
+
+    /// Run btrfs through `runner` instead of spawning it directly
+    pub fn with_runner(mut self, runner: Arc<dyn CommandRunner>) -> Self {
+        self.runner = runner;
+        self
+    }
+
+    pub fn window(&self) -> MaintenanceWindow {
+        MaintenanceWindow {
+            start_hour: self.config.window_start_hour,
+            end_hour: self.config.window_end_hour,
+            timezone: "local".to_string(),
+            allowed_days: self.config.allowed_days.clone(),
+        }
+    }
+
+    /// Why maintenance may not start at `now`, if it may not
+    pub fn gate(
+        &self,
+        window: &MaintenanceWindow,
+        now: DateTime<Utc>,
+        load_per_cpu: f64,
+    ) -> Option<String> {
+        if !window.is_open_at(now) {
+            return Some("outside the maintenance window".to_string());
+        }
+        if load_per_cpu > self.config.max_load_per_cpu {
+            return Some(format!(
+                "load {:.2} per CPU is above {:.2}",
+                load_per_cpu, self.config.max_load_per_cpu
+            ));
+        }
+        None
+    }
+
+    /// Load the history, run what is due, and save the history again
+    pub async fn run_pass(&self, memory: &MemoryStore) -> Result<Vec<BtrfsRunResult>> {
+        let now = Utc::now();
+        if let Some(reason) = self.gate(&self.window(), now, load_per_cpu().unwrap_or(0.0)) {
+            tracing::debug!("Btrfs maintenance not started: {}", reason);
+            return Ok(Vec::new());
+        }
+
+        let mut history = BtrfsHistory::load(memory).await?;
+        let results = self.run_due(&mut history, now).await;
+        history.save(memory).await?;
+        results
+    }
+
+    /// Scrub each filesystem whose last scrub is older than the interval, and
+    /// balance those whose allocation calls for it. History is updated as
+    /// each operation finishes, so an error part way through loses nothing.
+    pub async fn run_due(
+        &self,
+        history: &mut BtrfsHistory,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<BtrfsRunResult>> {
+        let mut results = Vec::new();
+        for fs in self.detect().await? {
+            let mut result = BtrfsRunResult {
+                filesystem: fs.clone(),
+                scrub: None,
+                balance: None,
+                balance_skipped: None,
+                findings: Vec::new(),
+            };
+
+            if self.scrub_due(history.filesystems.get(&fs.uuid), now) {
+                let (record, status) = self.scrub(&fs).await?;
+                result.findings.extend(scrub_findings(&fs, &status));
+                history.entry(&fs).last_scrub = Some(record.clone());
+                result.scrub = Some(record);
+            }
+
+            let usage = self.usage(&fs.mount).await?;
+            if let Some(reason) = self.balance_reason(&usage) {
+                // Rewriting chunks on a filesystem with bad checksums spreads the damage
+                if result.scrub.as_ref().is_some_and(|s| s.csum_errors > 0) {
+                    result.balance_skipped =
+                        Some(format!("{}, but the scrub found checksum errors", reason));
+                } else {
+                    let record = self.balance(&fs, reason).await?;
+                    history.entry(&fs).last_balance = Some(record.clone());
+                    result.balance = Some(record);
+                }
+            }
+
+            results.push(result);
+        }
+        Ok(results)
+    }
+
+    pub fn scrub_due(&self, history: Option<&FilesystemHistory>, now: DateTime<Utc>) -> bool {
+        let interval = chrono::Duration::days(self.config.scrub_interval_days as i64);
+        match history.and_then(|h| h.last_scrub.as_ref()) {
+            // A cancelled scrub didn't check anything; try again next window
+            Some(scrub) if !scrub.cancelled => now - scrub.finished_at >= interval,
+            _ => true,
+        }
+    }
+
+    /// Why `usage` calls for a balance, if it does: little unallocated space
+    /// left while allocated chunks sit partly empty
+    pub fn balance_reason(&self, usage: &BtrfsUsage) -> Option<String> {
+        let threshold = self.config.balance_chunk_usage_percent as f64;
+        let unallocated = usage.unallocated_percent();
+        if unallocated >= self.config.balance_min_unallocated_percent {
+            return None;
+        }
+        let (data, metadata) = (usage.data_fill_percent(), usage.metadata_fill_percent());
+        if data >= threshold && metadata >= threshold {
+            return None;
+        }
+        Some(format!(
+            "{:.0}% unallocated, data chunks {:.0}% full, metadata chunks {:.0}% full",
+            unallocated, data, metadata
+        ))
+    }
+
+    /// Mounted btrfs filesystems, one per UUID, preferring the shortest mount path
+    pub async fn detect(&self) -> Result<Vec<BtrfsFilesystem>> {
+        let output = self
+            .run("findmnt", &["-rn", "-t", "btrfs", "-o", "UUID,TARGET"])
+            .await
+            .unwrap_or_default();
+
+        let mut by_uuid: BTreeMap<String, String> = BTreeMap::new();
+        for line in output.lines() {
+            let Some((uuid, mount)) = line.split_once(' ') else {
+                continue;
+            };
+            // findmnt -r escapes spaces in paths as \x20
+            let mount = mount.replace("\\x20", " ");
+            let current = by_uuid
+                .entry(uuid.to_string())
+                .or_insert_with(|| mount.clone());
+            if mount.len() < current.len() {
+                *current = mount;
+            }
+        }
+        Ok(by_uuid
+            .into_iter()
+            .map(|(uuid, mount)| BtrfsFilesystem { uuid, mount })
+            .collect())
+    }
+
+    pub async fn usage(&self, mount: &str) -> Result<BtrfsUsage> {
+        let output = self
+            .run("btrfs", &["filesystem", "usage", "-b", mount])
+            .await?;
+        Ok(parse_btrfs_usage(&output))
+    }
+
+    /// Start a scrub and poll it to completion, honoring cancel requests
+    pub async fn scrub(&self, fs: &BtrfsFilesystem) -> Result<(ScrubRecord, ScrubStatus)> {
+        let guard = self.operations.start("btrfs scrub", &fs.mount, true);
+        let started = Utc::now();
+        self.run("btrfs", &["scrub", "start", &fs.mount]).await?;
+
+        let mut cancelled = false;
+        let status = loop {
+            let status =
+                parse_scrub_status(&self.run("btrfs", &["scrub", "status", &fs.mount]).await?);
+            guard.set_progress(status.percent, None);
+            // After a cancel, the status read above is the final one
+            if !status.running || cancelled {
+                break status;
+            }
+            if self.cancel_if_requested(&guard, "scrub", &fs.mount).await? {
+                cancelled = true;
+                continue;
+            }
+            self.poll_wait().await;
+        };
+
+        let record = ScrubRecord {
+            finished_at: Utc::now(),
+            duration_secs: (Utc::now() - started).num_seconds().max(0) as u64,
+            errors: status.total_errors(),
+            csum_errors: status.csum_errors(),
+            cancelled,
+        };
+        Ok((record, status))
+    }
+
+    /// Run a filtered balance in the background and poll it to completion
+    pub async fn balance(&self, fs: &BtrfsFilesystem, reason: String) -> Result<BalanceRecord> {
+        let guard = self.operations.start("btrfs balance", &fs.mount, true);
+        let started = Utc::now();
+        let dusage = format!("-dusage={}", self.config.balance_chunk_usage_percent);
+        let musage = format!("-musage={}", self.config.balance_chunk_usage_percent);
+        self.run(
+            "btrfs",
+            &["balance", "start", "--bg", &dusage, &musage, &fs.mount],
+        )
+        .await?;
+
+        let mut cancelled = false;
+        loop {
+            // "No balance found" once it is done
+            let status = self
+                .run("btrfs", &["balance", "status", &fs.mount])
+                .await
+                .unwrap_or_default();
+            if !status.contains("is running") || cancelled {
+                break;
+            }
+            guard.set_progress(parse_balance_percent(&status), None);
+            if self
+                .cancel_if_requested(&guard, "balance", &fs.mount)
+                .await?
+            {
+                cancelled = true;
+                continue;
+            }
+            self.poll_wait().await;
+        }
+
+        Ok(BalanceRecord {
+            finished_at: Utc::now(),
+            duration_secs: (Utc::now() - started).num_seconds().max(0) as u64,
+            reason,
+            cancelled,
+        })
+    }
+
+    async fn cancel_if_requested(
+        &self,
+        guard: &OperationGuard,
+        operation: &str,
+        mount: &str,
+    ) -> Result<bool> {
+        if !guard.cancel_requested() {
+            return Ok(false);
+        }
+        tracing::info!("Cancelling btrfs {} on {}", operation, mount);
+        self.run("btrfs", &[operation, "cancel", mount]).await?;
+        Ok(true)
+    }
+
+    async fn poll_wait(&self) {
+        tokio::time::sleep(std::time::Duration::from_secs(
+            self.config.poll_interval_secs,
+        ))
+        .await;
+    }
+
+    async fn run(&self, program: &str, args: &[&str]) -> Result<String> {
+        let output = self.runner.output(program, args).await?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "{} {} failed: {}",
+                program,
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+}
+
+/// 1-minute load average divided by the number of CPUs
+pub fn load_per_cpu() -> Option<f64> {
+    let loadavg = std::fs::read_to_string("/proc/loadavg").ok()?;
+    let load: f64 = loadavg.split_whitespace().next()?.parse().ok()?;
+    let cpus = std::thread::available_parallelism().map_or(1, |n| n.get());
+    Some(load / cpus as f64)
+}
+
+fn scrub_findings(fs: &BtrfsFilesystem, status: &ScrubStatus) -> Vec<Finding> {
+    if status.total_errors() == 0 {
+        return Vec::new();
+    }
+    let counts: Vec<String> = status
+        .errors
+        .iter()
+        .map(|(kind, n)| format!("{}={}", kind, n))
+        .collect();
+    let evidence = vec![Evidence {
+        evidence_type: EvidenceType::LogEntry,
+        data: format!("btrfs scrub status {}: {}", fs.mount, counts.join(" ")),
+        timestamp: Utc::now(),
+        source: "btrfs scrub".to_string(),
+    }];
+
+    if status.csum_errors() > 0 {
+        vec![Finding {
+            category: FindingCategory::Maintenance,
+            title: format!("Checksum errors on {}", fs.mount),
+            description: format!(
+                "Scrub of {} found {} checksum errors; data on disk no longer matches what was written. \
+                 Check `btrfs device stats {}` and SMART data, and restore affected files from backup.",
+                fs.mount,
+                status.csum_errors(),
+                fs.mount
+            ),
+            impact: ImpactLevel::Critical,
+            urgency: UrgencyLevel::Critical,
+            evidence,
+        }]
+    } else {
+        vec![Finding {
+            category: FindingCategory::Maintenance,
+            title: format!("Scrub errors on {}", fs.mount),
+            description: format!(
+                "Scrub of {} reported {}; check `btrfs device stats {}`.",
+                fs.mount,
+                counts.join(", "),
+                fs.mount
+            ),
+            impact: ImpactLevel::High,
+            urgency: UrgencyLevel::High,
+            evidence,
+        }]
+    }
+}
+
+pub fn parse_btrfs_usage(output: &str) -> BtrfsUsage {
+    let mut usage = BtrfsUsage::default();
+    for line in output.lines().map(str::trim) {
+        if let Some(value) = line.strip_prefix("Device size:") {
+            usage.device_size = value.trim().parse().unwrap_or(0);
+        } else if let Some(value) = line.strip_prefix("Device unallocated:") {
+            usage.unallocated = value.trim().parse().unwrap_or(0);
+        } else if line.starts_with("Data,") || line.starts_with("Metadata,") {
+            // "Data,single: Size:8589934592, Used:6442450944 (75.00%)"
+            let field = |name: &str| -> u64 {
+                line.split(name)
+                    .nth(1)
+                    .and_then(|rest| {
+                        rest.split(|c: char| !c.is_ascii_digit())
+                            .next()
+                            .and_then(|n| n.parse().ok())
+                    })
+                    .unwrap_or(0)
+            };
+            // Profiles add up while a conversion is in progress
+            if line.starts_with("Data,") {
+                usage.data_size += field("Size:");
+                usage.data_used += field("Used:");
+            } else {
+                usage.metadata_size += field("Size:");
+                usage.metadata_used += field("Used:");
+            }
+        }
+    }
+    usage
+}
+
+pub fn parse_scrub_status(output: &str) -> ScrubStatus {
+    let mut status = ScrubStatus::default();
+    for line in output.lines().map(str::trim) {
+        if let Some(value) = line.strip_prefix("Status:") {
+            status.running = value.trim() == "running";
+        } else if let Some(value) = line.strip_prefix("Bytes scrubbed:") {
+            // "30.00GiB  (25.00%)"
+            status.percent = value
+                .split_once('(')
+                .and_then(|(_, rest)| rest.split('%').next())
+                .and_then(|p| p.trim().parse().ok());
+        } else if let Some(value) = line.strip_prefix("Error summary:") {
+            // "no errors found" or "csum=2 read=1"
+            for (kind, count) in value.split_whitespace().filter_map(|t| t.split_once('=')) {
+                if let Ok(count) = count.parse::<u64>() {
+                    *status.errors.entry(kind.to_string()).or_default() += count;
+                }
+            }
+        }
+    }
+    status
+}
+
+/// "2 out of about 10 chunks balanced (3 considered),  80% left"
+fn parse_balance_percent(status: &str) -> Option<f64> {
+    let left: f64 = status
+        .split('%')
+        .next()?
+        .rsplit(|c: char| c.is_whitespace() || c == ',')
+        .next()?
+        .parse()
+        .ok()?;
+    Some(100.0 - left)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::RecordingRunner;
+    use chrono::TimeZone;
+
+    const USAGE_TIGHT: &str = "Overall:
+    Device size:                  500000000000
+    Device allocated:             480000000000
+    Device unallocated:            20000000000
+
+Data,single: Size:460000000000, Used:250000000000 (54.35%)
+   /dev/nvme0n1p2  460000000000
+
+Metadata,DUP: Size:10000000000, Used:8000000000 (80.00%)
+   /dev/nvme0n1p2  20000000000
+";
+
+    fn test_agent(runner: RecordingRunner) -> (BtrfsMaintenanceAgent, Arc<RecordingRunner>) {
+        let runner = Arc::new(runner);
+        let config = BtrfsMaintenanceConfig {
+            enabled: true,
+            poll_interval_secs: 0,
+            ..Default::default()
+        };
+        let agent =
+            BtrfsMaintenanceAgent::new(config, ActiveOperations::new()).with_runner(runner.clone());
+        (agent, runner)
+    }
+
+    #[test]
+    fn test_parse_scrub_status() {
+        let running = parse_scrub_status(
+            "UUID:             3c1d\nStatus:           running\n\
+             Bytes scrubbed:   30.00GiB  (25.00%)\nError summary:    no errors found\n",
+        );
+        assert!(running.running);
+        assert_eq!(running.percent, Some(25.0));
+        assert_eq!(running.total_errors(), 0);
+
+        let finished =
+            parse_scrub_status("Status:           finished\nError summary:    csum=2 read=1\n");
+        assert!(!finished.running);
+        assert_eq!(finished.csum_errors(), 2);
+        assert_eq!(finished.total_errors(), 3);
+    }
+
+    #[test]
+    fn test_balance_only_when_allocation_drifted() {
+        let (agent, _) = test_agent(RecordingRunner::new());
+        let usage = parse_btrfs_usage(USAGE_TIGHT);
+        assert_eq!(usage.unallocated_percent(), 4.0);
+
+        let reason = agent.balance_reason(&usage).unwrap();
+        assert_eq!(
+            reason,
+            "4% unallocated, data chunks 54% full, metadata chunks 80% full"
+        );
+
+        let roomy = BtrfsUsage {
+            unallocated: 200_000_000_000,
+            ..usage
+        };
+        assert!(agent.balance_reason(&roomy).is_none());
+    }
+
+    #[test]
+    fn test_gate_respects_window_and_load() {
+        let (agent, _) = test_agent(RecordingRunner::new());
+        let window = MaintenanceWindow {
+            start_hour: 22,
+            end_hour: 4,
+            timezone: "UTC".to_string(),
+            allowed_days: vec!["Sunday".to_string()],
+        };
+        // 2026-10-18 is a Sunday
+        let sunday_night = Utc.with_ymd_and_hms(2026, 10, 18, 23, 0, 0).unwrap();
+        let sunday_noon = Utc.with_ymd_and_hms(2026, 10, 18, 12, 0, 0).unwrap();
+        let monday_night = Utc.with_ymd_and_hms(2026, 10, 19, 23, 0, 0).unwrap();
+
+        assert!(agent.gate(&window, sunday_night, 0.1).is_none());
+        assert!(agent.gate(&window, sunday_noon, 0.1).is_some());
+        assert!(agent.gate(&window, monday_night, 0.1).is_some());
+        assert!(
+            agent
+                .gate(&window, sunday_night, 2.0)
+                .unwrap()
+                .contains("load")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scrub_with_checksum_errors_is_critical_and_blocks_balance() {
+        let (agent, runner) = test_agent(
+            RecordingRunner::new()
+                .respond("findmnt", "3c1d / \n3c1d /home\n")
+                .respond(
+                    "btrfs scrub status",
+                    "Status:           finished\nBytes scrubbed:   120.00GiB  (100.00%)\n\
+                     Error summary:    csum=4\n",
+                )
+                .respond("btrfs filesystem usage", USAGE_TIGHT),
+        );
+        let mut history = BtrfsHistory::default();
+        let now = Utc::now();
+
+        let results = agent.run_due(&mut history, now).await.unwrap();
+        assert_eq!(results.len(), 1);
+        let result = &results[0];
+        assert_eq!(result.filesystem.mount, "/");
+        assert_eq!(result.scrub.as_ref().unwrap().csum_errors, 4);
+        assert!(matches!(result.findings[0].urgency, UrgencyLevel::Critical));
+        assert!(result.balance.is_none());
+        assert!(result.balance_skipped.is_some());
+        assert!(
+            !runner
+                .calls()
+                .iter()
+                .any(|c| c.starts_with("btrfs balance"))
+        );
+
+        let notification = result.notification().unwrap();
+        assert_eq!(notification.severity, NotifySeverity::Critical);
+        assert_eq!(
+            history.filesystems["3c1d"].summary(now + chrono::Duration::days(12)),
+            "last scrub: 12 days ago, 4 errors"
+        );
+
+        // Not due again until the interval passes
+        let (agent, runner) = test_agent(RecordingRunner::new().respond("findmnt", "3c1d /\n"));
+        agent.run_due(&mut history, now).await.unwrap();
+        assert!(!runner.calls().iter().any(|c| c.starts_with("btrfs scrub")));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_cancel_request_stops_running_scrub() {
+        // The fake status never leaves "running"; only the cancel ends the scrub
+        let (agent, runner) = test_agent(RecordingRunner::new().respond(
+            "btrfs scrub status",
+            "Status:           running\nBytes scrubbed:   1.00GiB  (10.00%)\n",
+        ));
+        let fs = BtrfsFilesystem {
+            uuid: "3c1d".to_string(),
+            mount: "/".to_string(),
+        };
+
+        let operations = agent.operations.clone();
+        let canceller = tokio::spawn(async move {
+            loop {
+                if let Some(op) = operations.list().first() {
+                    assert_eq!(op.kind, "btrfs scrub");
+                    assert!(operations.request_cancel(op.id));
+                    return;
+                }
+                tokio::task::yield_now().await;
+            }
+        });
+
+        let (record, _) = agent.scrub(&fs).await.unwrap();
+        canceller.await.unwrap();
+        assert!(record.cancelled);
+        assert!(runner.calls().contains(&"btrfs scrub cancel /".to_string()));
+        assert!(agent.operations.list().is_empty());
+    }
+}
@@ -0,0 +1,186 @@
+//! Audit log for MCP tool invocations
+
+use async_trait::async_trait;
+use glyph::protocol::{ReadResourceResult, ResourceContents};
+use glyph::server::Resource;
+use serde_json::Value;
+
+use crate::journal::{ActionRecord, ActionType};
+use crate::memory::MemoryStore;
+use crate::types::{AuditEntry, AuditStatus};
+
+const REDACTED: &str = "***REDACTED***";
+
+/// Argument keys whose values are never written to the audit log
+const SECRET_KEY_PATTERNS: &[&str] = &[
+    "password",
+    "passwd",
+    "secret",
+    "token",
+    "api_key",
+    "apikey",
+    "authorization",
+    "private_key",
+    "credential",
+];
+
+/// Return a copy of the tool arguments with secret-looking values replaced
+pub fn redact_arguments(args: &Value) -> Value {
+    match args {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, value)| {
+                    let lower = key.to_lowercase();
+                    if SECRET_KEY_PATTERNS.iter().any(|p| lower.contains(p)) {
+                        (key.clone(), Value::String(REDACTED.to_string()))
+                    } else {
+                        (key.clone(), redact_arguments(value))
+                    }
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(redact_arguments).collect()),
+        other => other.clone(),
+    }
+}
+
+/// The journal record for a tool call that changes the system, or `None`
+/// for read-only and rate-limited calls. Calls made with `confirm: false`
+/// only preview the change and are recorded as dry runs.
+pub fn action_record(entry: &AuditEntry) -> Option<ActionRecord> {
+    if entry.status == AuditStatus::RateLimited {
+        return None;
+    }
+    let action = entry.action.as_deref()?;
+    let arg = |key: &str| entry.arguments.get(key).and_then(|v| v.as_str());
+    let confirmed = entry
+        .arguments
+        .get("confirm")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+
+    let (operation_type, target, dry_run) = match (entry.tool.as_str(), action) {
+        ("jarvis_package_manager", "install" | "remove") => (
+            ActionType::PackageTransaction,
+            arg("package").unwrap_or("").to_string(),
+            !confirmed,
+        ),
+        ("jarvis_package_manager", "update") => (
+            ActionType::PackageTransaction,
+            arg("package").unwrap_or("system").to_string(),
+            !confirmed,
+        ),
+        ("jarvis_docker", "start" | "stop" | "restart" | "vm-start" | "vm-stop") => (
+            ActionType::ServiceOperation,
+            arg("target").unwrap_or("").to_string(),
+            false,
+        ),
+        ("jarvis_power", "poweroff" | "reboot" | "suspend" | "wake") => (
+            ActionType::Command,
+            arg("host").unwrap_or("localhost").to_string(),
+            !confirmed,
+        ),
+        _ => return None,
+    };
+
+    Some(
+        ActionRecord::new(entry.id.clone(), operation_type, action, target)
+            .with_result(entry.status)
+            .with_dry_run(dry_run),
+    )
+}
+
+/// MCP resource exposing the most recent audit entries as JSON
+pub struct AuditLogResource {
+    memory: MemoryStore,
+    limit: i32,
+}
+
+impl AuditLogResource {
+    pub fn new(memory: MemoryStore, limit: i32) -> Self {
+        Self { memory, limit }
+    }
+}
+
+#[async_trait]
+impl Resource for AuditLogResource {
+    fn uri(&self) -> &str {
+        "jarvis://audit/recent"
+    }
+
+    fn name(&self) -> &str {
+        "jarvis_audit_log"
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("Most recent Jarvis MCP tool invocations (arguments redacted)")
+    }
+
+    fn mime_type(&self) -> Option<&str> {
+        Some("application/json")
+    }
+
+    async fn read(&self) -> Result<ReadResourceResult, glyph::Error> {
+        let entries = self
+            .memory
+            .recent_audit_entries(self.limit)
+            .await
+            .map_err(|e| glyph::Error::ToolExecution(format!("Failed to read audit log: {}", e)))?;
+
+        let body = serde_json::to_string_pretty(&entries)
+            .map_err(|e| glyph::Error::ToolExecution(format!("Failed to encode audit log: {}", e)))?;
+
+        Ok(ReadResourceResult::new(vec![ResourceContents::text(
+            self.uri(),
+            &body,
+        )]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_redacts_nested_secrets() {
+        let args = json!({
+            "action": "install",
+            "api_key": "sk-123",
+            "auth": { "Authorization": "Bearer abc", "user": "chris" }
+        });
+
+        let redacted = redact_arguments(&args);
+        assert_eq!(redacted["action"], "install");
+        assert_eq!(redacted["api_key"], REDACTED);
+        assert_eq!(redacted["auth"]["Authorization"], REDACTED);
+        assert_eq!(redacted["auth"]["user"], "chris");
+    }
+
+    #[test]
+    fn test_action_record_for_mutating_calls() {
+        let mut entry = AuditEntry {
+            id: "call-1".to_string(),
+            timestamp: chrono::Utc::now(),
+            tool: "jarvis_package_manager".to_string(),
+            action: Some("install".to_string()),
+            caller: Some("stdio".to_string()),
+            arguments: json!({ "action": "install", "package": "htop" }),
+            duration_ms: 12,
+            status: AuditStatus::Success,
+            error: None,
+        };
+
+        let record = action_record(&entry).unwrap();
+        assert_eq!(record.operation_id, "call-1");
+        assert_eq!(record.operation_type, ActionType::PackageTransaction);
+        assert_eq!(record.target, "htop");
+        assert!(record.dry_run);
+
+        entry.arguments["confirm"] = json!(true);
+        assert!(!action_record(&entry).unwrap().dry_run);
+
+        entry.action = Some("search".to_string());
+        assert!(action_record(&entry).is_none());
+    }
+}
@@ -0,0 +1,309 @@
+//! Reuse of LLM analyses across repeated docker diagnostics
+//!
+//! Dashboards poll `diagnose` and `health` every few seconds, and each call
+//! used to cost an LLM round trip even when nothing had changed. The facts
+//! (container states, log tail, stats) are still gathered on every call; only
+//! the analysis is reused. It is keyed by a fingerprint of the facts, with
+//! stats bucketed and uptimes dropped so that a container merely staying up
+//! doesn't count as a change. Identical facts within the TTL reuse the
+//! previous analysis; changed facts get a new one, but no more often than
+//! the minimum interval per subject.
+
+use crate::config::DiagnosticsCacheConfig;
+use regex::Regex;
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+static PERCENT: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\d+(?:\.\d+)?)%").unwrap());
+static PARENTHESIZED: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\([^)]*\)").unwrap());
+
+/// Width of the buckets CPU and memory percentages fall into
+const PERCENT_BUCKET: f64 = 10.0;
+
+/// Where an analysis came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnalysisSource {
+    /// Generated for this call
+    Fresh,
+    /// Reused: the facts are the same as last time
+    Cached,
+    /// Reused although the facts changed, because the last analysis of this
+    /// subject is younger than the minimum interval
+    Throttled,
+}
+
+#[derive(Debug, Clone)]
+pub struct Analysis {
+    pub text: String,
+    pub source: AnalysisSource,
+    /// Time since the analysis was generated
+    pub age: Duration,
+}
+
+impl Analysis {
+    /// Section heading, e.g. "AI Analysis (cached, 42s old)"
+    pub fn heading(&self, title: &str) -> String {
+        match self.source {
+            AnalysisSource::Fresh => title.to_string(),
+            AnalysisSource::Cached => format!("{} (cached, {}s old)", title, self.age.as_secs()),
+            AnalysisSource::Throttled => format!(
+                "{} (from {}s ago, before the latest changes)",
+                title,
+                self.age.as_secs()
+            ),
+        }
+    }
+}
+
+/// Cache effectiveness since the tool started
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub throttled: u64,
+}
+
+struct Entry {
+    fingerprint: u64,
+    text: String,
+    generated: Instant,
+}
+
+pub struct DiagnosticsCache {
+    config: DiagnosticsCacheConfig,
+    /// One lock per subject, held while its analysis is generated, so
+    /// concurrent identical calls wait for one LLM call instead of each
+    /// making their own
+    subjects: Mutex<HashMap<String, Arc<tokio::sync::Mutex<Option<Entry>>>>>,
+    stats: Mutex<CacheStats>,
+}
+
+impl DiagnosticsCache {
+    pub fn new(config: DiagnosticsCacheConfig) -> Self {
+        Self {
+            config,
+            subjects: Mutex::new(HashMap::new()),
+            stats: Mutex::new(CacheStats::default()),
+        }
+    }
+
+    /// The analysis of `subject` (e.g. `diagnose:web`) given facts with
+    /// `fingerprint`, from the cache or from `generate`. Failures are
+    /// returned and not cached.
+    pub async fn analyze<F, Fut>(
+        &self,
+        subject: &str,
+        fingerprint: u64,
+        generate: F,
+    ) -> anyhow::Result<Analysis>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = anyhow::Result<String>>,
+    {
+        if !self.config.enabled {
+            self.stats.lock().unwrap().misses += 1;
+            return Ok(Analysis {
+                text: generate().await?,
+                source: AnalysisSource::Fresh,
+                age: Duration::ZERO,
+            });
+        }
+
+        let slot = self
+            .subjects
+            .lock()
+            .unwrap()
+            .entry(subject.to_string())
+            .or_default()
+            .clone();
+        let mut entry = slot.lock().await;
+
+        if let Some(previous) = entry.as_ref() {
+            let age = previous.generated.elapsed();
+            let source = if previous.fingerprint == fingerprint && age < self.config.ttl() {
+                Some(AnalysisSource::Cached)
+            } else if age < self.config.min_interval() {
+                Some(AnalysisSource::Throttled)
+            } else {
+                None
+            };
+            if let Some(source) = source {
+                let mut stats = self.stats.lock().unwrap();
+                match source {
+                    AnalysisSource::Cached => stats.hits += 1,
+                    _ => stats.throttled += 1,
+                }
+                return Ok(Analysis {
+                    text: previous.text.clone(),
+                    source,
+                    age,
+                });
+            }
+        }
+
+        self.stats.lock().unwrap().misses += 1;
+        let text = generate().await?;
+        *entry = Some(Entry {
+            fingerprint,
+            text: text.clone(),
+            generated: Instant::now(),
+        });
+        Ok(Analysis {
+            text,
+            source: AnalysisSource::Fresh,
+            age: Duration::ZERO,
+        })
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        *self.stats.lock().unwrap()
+    }
+
+    /// `{"_diagnostics": ...}` metadata for a tool result
+    pub fn metadata(&self, analysis: Option<&Analysis>) -> Value {
+        json!({
+            "_diagnostics": {
+                "cached": analysis.is_some_and(|a| a.source != AnalysisSource::Fresh),
+                "source": analysis.map(|a| a.source),
+                "age_secs": analysis.map(|a| a.age.as_secs()),
+                "cache": self.stats(),
+            }
+        })
+    }
+}
+
+/// Fingerprint of the facts an analysis is based on
+pub fn fingerprint(facts: &[&str]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    facts.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The percentages in `stats`, rounded down to buckets, so `CPU: 0.13%` and
+/// `CPU: 0.41%` read the same
+pub fn bucket_percentages(stats: &str) -> String {
+    PERCENT
+        .captures_iter(stats)
+        .filter_map(|c| c[1].parse::<f64>().ok())
+        .map(|p| format!("{}%", (p / PERCENT_BUCKET).floor() * PERCENT_BUCKET))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A `docker ps` status without its uptime: `Up 3 hours (unhealthy)` becomes
+/// `Up (unhealthy)`, `Exited (1) 2 days ago` becomes `Exited (1)`
+pub fn container_state(status: &str) -> String {
+    let state = status.split_whitespace().next().unwrap_or_default();
+    std::iter::once(state)
+        .chain(PARENTHESIZED.find_iter(status).map(|m| m.as_str()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn config(ttl_secs: u64, min_interval_secs: u64) -> DiagnosticsCacheConfig {
+        DiagnosticsCacheConfig {
+            enabled: true,
+            ttl_secs,
+            min_interval_secs,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_identical_facts_reuse_the_analysis_until_they_change() {
+        let cache = DiagnosticsCache::new(config(300, 0));
+        let calls = AtomicUsize::new(0);
+        let generate = || async {
+            let n = calls.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(format!("analysis {}", n))
+        };
+
+        let first = cache.analyze("diagnose:web", 1, generate).await.unwrap();
+        let second = cache.analyze("diagnose:web", 1, generate).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(first.source, AnalysisSource::Fresh);
+        assert_eq!(second.source, AnalysisSource::Cached);
+        assert_eq!(second.text, "analysis 1");
+
+        // Other subjects and changed facts are analyzed again
+        cache.analyze("diagnose:db", 1, generate).await.unwrap();
+        let changed = cache.analyze("diagnose:web", 2, generate).await.unwrap();
+        assert_eq!(changed.text, "analysis 3");
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 1,
+                misses: 3,
+                throttled: 0
+            }
+        );
+
+        let meta = cache.metadata(Some(&second));
+        assert_eq!(meta["_diagnostics"]["cached"], true);
+        assert_eq!(meta["_diagnostics"]["cache"]["hits"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_changed_facts_are_throttled_and_failures_not_cached() {
+        let cache = DiagnosticsCache::new(config(300, 60));
+        let calls = AtomicUsize::new(0);
+        let generate = || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Ok("analysis".to_string())
+        };
+
+        cache.analyze("health", 1, generate).await.unwrap();
+        let throttled = cache.analyze("health", 2, generate).await.unwrap();
+        assert_eq!(throttled.source, AnalysisSource::Throttled);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let failing = || async { anyhow::bail!("ollama is down") };
+        assert!(cache.analyze("diagnose:web", 1, failing).await.is_err());
+        let retried = cache.analyze("diagnose:web", 1, generate).await.unwrap();
+        assert_eq!(retried.source, AnalysisSource::Fresh);
+
+        // Disabled, every call goes to the LLM
+        let cache = DiagnosticsCache::new(DiagnosticsCacheConfig {
+            enabled: false,
+            ..config(300, 60)
+        });
+        cache.analyze("health", 1, generate).await.unwrap();
+        cache.analyze("health", 1, generate).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn test_fingerprints_ignore_uptime_and_stat_jitter() {
+        assert_eq!(container_state("Up 3 hours (unhealthy)"), "Up (unhealthy)");
+        assert_eq!(
+            container_state("Up About an hour (healthy)"),
+            "Up (healthy)"
+        );
+        assert_eq!(container_state("Exited (1) 2 days ago"), "Exited (1)");
+
+        let quiet = bucket_percentages("CPU: 0.13% | Memory: 120MiB / 2GiB (5.86%)");
+        let jitter = bucket_percentages("CPU: 0.41% | Memory: 121MiB / 2GiB (5.91%)");
+        let busy = bucket_percentages("CPU: 87.50% | Memory: 121MiB / 2GiB (5.91%)");
+        assert_eq!(quiet, jitter);
+        assert_ne!(quiet, busy);
+        assert_eq!(
+            fingerprint(&["running", &quiet]),
+            fingerprint(&["running", &jitter])
+        );
+        assert_ne!(
+            fingerprint(&["running", &quiet]),
+            fingerprint(&["exited", &quiet])
+        );
+    }
+}
@@ -0,0 +1,278 @@
+//! Rate limiting, deadlines, read-only mode, and auditing wrapper for MCP tools
+
+use async_trait::async_trait;
+use chrono::Utc;
+use glyph::protocol::{CallToolResult, Content, ToolInputSchema};
+use glyph::server::Tool;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use crate::config::TraceConfig;
+use crate::journal;
+use crate::mcp::audit::{self, redact_arguments};
+use crate::mcp::rate_limit::RateLimiter;
+use crate::mcp::timeout::ToolTimeouts;
+use crate::mcp::tools::{changes_system, read_only_description};
+use crate::memory::MemoryStore;
+use crate::trace::{self, Trace, TraceReport};
+use crate::types::{AuditEntry, AuditStatus};
+
+tokio::task_local! {
+    static CONNECTION: String;
+}
+
+/// Run `future` on behalf of a network client. Calls it makes are rate
+/// limited and audited as `caller`, whatever `_caller` the client sends, so
+/// one client can't spend another's budget or write entries in its name.
+pub async fn as_caller<F: Future>(caller: String, future: F) -> F::Output {
+    CONNECTION.scope(caller, future).await
+}
+
+/// Shared guard state for every tool registered on a server
+#[derive(Clone)]
+pub struct ToolGuard {
+    limiter: Arc<RateLimiter>,
+    audit: Option<MemoryStore>,
+    caller: String,
+    traces: Option<(MemoryStore, TraceConfig)>,
+    timeouts: ToolTimeouts,
+}
+
+impl ToolGuard {
+    /// `caller` identifies the transport-level client (e.g. "stdio", "ws:127.0.0.1:7332")
+    /// and is used when the call itself does not carry a `_caller` argument
+    /// and isn't made under [`as_caller`].
+    pub fn new(limiter: Arc<RateLimiter>, audit: Option<MemoryStore>, caller: impl Into<String>) -> Self {
+        Self {
+            limiter,
+            audit,
+            caller: caller.into(),
+            traces: None,
+            timeouts: ToolTimeouts::default(),
+        }
+    }
+
+    /// Abandon calls that run past these deadlines instead of the defaults
+    pub fn with_timeouts(mut self, timeouts: ToolTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Keep traces of tool calls slower than `config.persist_threshold_ms`
+    pub fn with_traces(mut self, memory: MemoryStore, config: TraceConfig) -> Self {
+        self.traces = Some((memory, config));
+        self
+    }
+
+    async fn keep_if_slow(&self, report: &TraceReport) {
+        if let Some((memory, config)) = &self.traces {
+            if let Err(e) = report.persist_if_slow(memory, config).await {
+                tracing::warn!("Failed to store trace for {}: {}", report.request, e);
+            }
+        }
+    }
+
+    async fn record(&self, entry: AuditEntry) {
+        if let Some(record) = audit::action_record(&entry) {
+            journal::emit(&record);
+        }
+        if let Some(memory) = &self.audit {
+            if let Err(e) = memory.append_audit_entry(&entry).await {
+                tracing::warn!("Failed to write MCP audit entry for {}: {}", entry.tool, e);
+            }
+        }
+    }
+}
+
+/// Wraps a tool so every call is rate limited, bounded by a deadline, and
+/// written to the audit log. In read-only mode the description says so and
+/// calls that would change the system are refused before the tool runs.
+pub struct GuardedTool<T: Tool> {
+    inner: T,
+    guard: ToolGuard,
+    description: Option<String>,
+}
+
+impl<T: Tool> GuardedTool<T> {
+    pub fn new(inner: T, guard: ToolGuard) -> Self {
+        let description = inner.description().map(|description| {
+            if crate::read_only::is_read_only() {
+                read_only_description(inner.name(), description)
+            } else {
+                description.to_string()
+            }
+        });
+        Self {
+            inner,
+            guard,
+            description,
+        }
+    }
+}
+
+#[async_trait]
+impl<T: Tool + Send + Sync> Tool for GuardedTool<T> {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    fn input_schema(&self) -> ToolInputSchema {
+        self.inner.input_schema()
+    }
+
+    async fn call(&self, args: Option<Value>) -> Result<CallToolResult, glyph::Error> {
+        let tool = self.inner.name().to_string();
+        let action = args
+            .as_ref()
+            .and_then(|v| v.get("action"))
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+        let caller = CONNECTION
+            .try_with(|caller| caller.clone())
+            .ok()
+            .unwrap_or_else(|| {
+                args.as_ref()
+                    .and_then(|v| v.get("_caller"))
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string())
+                    .unwrap_or_else(|| self.guard.caller.clone())
+            });
+        let include_trace = args
+            .as_ref()
+            .and_then(|v| v.get("include_trace"))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        let arguments = args.as_ref().map(redact_arguments).unwrap_or(Value::Null);
+
+        let mut entry = AuditEntry {
+            id: uuid::Uuid::new_v4().to_string(),
+            timestamp: Utc::now(),
+            tool: tool.clone(),
+            action: action.clone(),
+            caller: Some(caller.clone()),
+            arguments,
+            duration_ms: 0,
+            status: AuditStatus::Success,
+            error: None,
+        };
+
+        if let Err(limited) = self.guard.limiter.check(&tool, action.as_deref(), &caller) {
+            let retry_after_secs = limited.retry_after.as_secs_f64().ceil() as u64;
+            tracing::warn!(
+                "Rate limit exceeded for {} (retry after {}s)",
+                limited.key,
+                retry_after_secs
+            );
+
+            entry.status = AuditStatus::RateLimited;
+            entry.error = Some(format!("retry after {}s", retry_after_secs));
+            self.guard.record(entry).await;
+
+            let error = json!({
+                "error": "rate_limited",
+                "tool": tool,
+                "action": action,
+                "retry_after_secs": retry_after_secs,
+                "message": format!(
+                    "Rate limit exceeded for {}; retry in {}s",
+                    tool, retry_after_secs
+                ),
+            });
+            return Err(glyph::Error::ToolExecution(error.to_string()));
+        }
+
+        if crate::read_only::is_read_only()
+            && args.as_ref().is_some_and(|args| changes_system(&tool, args))
+        {
+            let refusal = crate::read_only::refusal(&match &action {
+                Some(action) => format!("run {} {}", tool, action),
+                None => format!("run {}", tool),
+            });
+            entry.status = AuditStatus::Error;
+            entry.error = Some(refusal.to_string());
+            self.guard.record(entry).await;
+
+            let error = json!({
+                "error": refusal.code(),
+                "tool": tool,
+                "action": action,
+                "message": refusal.message(),
+                "suggestion": "Give the user the exact command so they can run it themselves",
+            });
+            return Err(glyph::Error::ToolExecution(error.to_string()));
+        }
+
+        // Join the caller's trace when there is one; otherwise this call is the request
+        let (trace, owns_trace) = match trace::current() {
+            Some(trace) => (trace, false),
+            None => {
+                let request = match &action {
+                    Some(action) => format!("{}:{}", tool, action),
+                    None => tool.clone(),
+                };
+                (Trace::new(request), true)
+            }
+        };
+
+        let deadline = self.guard.timeouts.deadline_for(&tool, action.as_deref());
+        let start = std::time::Instant::now();
+        let (mut result, timed_out) = trace
+            .scope(async {
+                let phase = trace::phase("tool");
+                phase.attr("tool", &tool);
+                if let Some(action) = &action {
+                    phase.attr("action", action);
+                }
+                let call = self.inner.call(args);
+                tokio::pin!(call);
+                tokio::select! {
+                    result = &mut call => {
+                        phase.attr("status", if result.is_ok() { "ok" } else { "error" });
+                        (result, None)
+                    }
+                    _ = tokio::time::sleep(deadline) => {
+                        // Read the trace while `call` is alive; dropping it kills its subprocesses
+                        let stuck = self.guard.timeouts.timed_out(
+                            &tool,
+                            action.as_deref(),
+                            deadline,
+                            &trace.report(),
+                        );
+                        tracing::warn!("{}", stuck.message());
+                        phase.attr("status", "timeout");
+                        let error = glyph::Error::ToolExecution(stuck.to_json().to_string());
+                        (Err(error), Some(stuck))
+                    }
+                }
+            })
+            .await;
+        entry.duration_ms = start.elapsed().as_millis() as u64;
+
+        let report = trace.report();
+        if owns_trace {
+            self.guard.keep_if_slow(&report).await;
+        }
+        if include_trace {
+            if let Ok(result) = &mut result {
+                result
+                    .content
+                    .push(Content::text(&json!({ "_trace": report }).to_string()));
+            }
+        }
+
+        if let Err(e) = &result {
+            entry.status = AuditStatus::Error;
+            entry.error = Some(match &timed_out {
+                Some(stuck) => stuck.message(),
+                None => e.to_string(),
+            });
+        }
+        self.guard.record(entry).await;
+
+        result
+    }
+}
@@ -1,5 +1,17 @@
+pub mod audit;
+pub mod diagnostics_cache;
+pub mod guard;
+pub mod network;
+pub mod rate_limit;
 pub mod server;
+pub mod timeout;
 pub mod tools;
 
-pub use server::run_mcp_server;
+pub use audit::{AuditLogResource, redact_arguments};
+pub use diagnostics_cache::{CacheStats, DiagnosticsCache};
+pub use guard::{GuardedTool, ToolGuard};
+pub use network::{NetworkServer, Peer};
+pub use rate_limit::{RateLimitExceeded, RateLimiter};
+pub use server::{JarvisTools, ToolContext, run_mcp_server};
+pub use timeout::{ToolTimedOut, ToolTimeouts};
 pub use tools::*;
@@ -0,0 +1,483 @@
+//! MCP over TCP and WebSocket for clients on other machines
+//!
+//! Speaks the same JSON-RPC as the stdio server: newline-delimited messages
+//! over TCP (optionally TLS), one message per text frame over WebSocket.
+//! Each connection is a session of its own. Tool calls made through it are
+//! rate limited and audited as `<transport>:<client>@<address>`, where the
+//! client is the TLS peer's certificate name or the `clientInfo.name` it sent
+//! in `initialize`. Calls run concurrently, so a slow one doesn't hold up a
+//! `ping`.
+//!
+//! When a token is configured a TCP client must send it as
+//! `params.auth_token` of `initialize`; anything else first, or a wrong
+//! token, gets a `-32001` error and the connection is closed. The WebSocket
+//! endpoint sits behind the HTTP API's bearer token instead. Connections past
+//! `max_connections` are turned away, and one without a message or a call in
+//! flight for `idle_timeout` is closed.
+
+use anyhow::Result;
+use futures::{Stream, StreamExt};
+use glyph::server::{Resource, Tool};
+use serde_json::{Value, json};
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::sync::{Semaphore, mpsc};
+use tokio::task::JoinSet;
+use tracing::{debug, info, warn};
+
+use crate::mcp::guard;
+use crate::tls::{self, ReloadableTlsAcceptor};
+
+/// MCP revision answered when the client doesn't ask for one
+pub const PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// Longest message accepted over TCP
+const MAX_MESSAGE_BYTES: u64 = 4 * 1024 * 1024;
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Missing or wrong `auth_token`
+pub const UNAUTHORIZED: i64 = -32001;
+/// Every connection slot is taken
+pub const SERVER_BUSY: i64 = -32002;
+
+/// Who is on the other end of a connection
+#[derive(Debug, Clone)]
+pub struct Peer {
+    /// "tcp" or "ws"
+    pub transport: &'static str,
+    pub addr: SocketAddr,
+    /// Name from the client's TLS certificate
+    pub name: Option<String>,
+    /// The transport already checked the token, as the HTTP API does
+    pub authenticated: bool,
+}
+
+impl Peer {
+    pub fn new(transport: &'static str, addr: SocketAddr) -> Self {
+        Self {
+            transport,
+            addr,
+            name: None,
+            authenticated: false,
+        }
+    }
+
+    pub fn with_name(mut self, name: Option<String>) -> Self {
+        self.name = name;
+        self
+    }
+
+    pub fn authenticated(mut self, authenticated: bool) -> Self {
+        self.authenticated = authenticated;
+        self
+    }
+}
+
+impl fmt::Display for Peer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.transport, self.addr)
+    }
+}
+
+/// One client's view of the connection
+struct Session {
+    peer: Peer,
+    /// `clientInfo.name` from `initialize`
+    client: Option<String>,
+    authenticated: bool,
+}
+
+impl Session {
+    /// Identity written to the audit log and used for rate limit buckets
+    fn caller(&self) -> String {
+        match self.peer.name.as_ref().or(self.client.as_ref()) {
+            Some(name) => format!("{}:{}@{}", self.peer.transport, name, self.peer.addr.ip()),
+            None => format!("{}:{}", self.peer.transport, self.peer.addr.ip()),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Serves a set of tools and resources to network clients
+pub struct NetworkServer {
+    tools: Vec<Arc<dyn Tool + Send + Sync>>,
+    resources: Vec<Arc<dyn Resource + Send + Sync>>,
+    token: Option<String>,
+    idle_timeout: Duration,
+    connections: Arc<Semaphore>,
+}
+
+impl NetworkServer {
+    pub fn new(
+        tools: Vec<Arc<dyn Tool + Send + Sync>>,
+        resources: Vec<Arc<dyn Resource + Send + Sync>>,
+    ) -> Self {
+        Self {
+            tools,
+            resources,
+            token: None,
+            idle_timeout: Duration::from_secs(900),
+            connections: Arc::new(Semaphore::new(16)),
+        }
+    }
+
+    /// Require this token in `initialize`
+    pub fn with_token(mut self, token: Option<String>) -> Self {
+        self.token = token;
+        self
+    }
+
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    pub fn with_max_connections(mut self, max_connections: usize) -> Self {
+        self.connections = Arc::new(Semaphore::new(max_connections));
+        self
+    }
+
+    /// Accept TCP clients on `listener` until the task is dropped, over TLS
+    /// when an acceptor is given
+    pub async fn serve_tcp(
+        self: Arc<Self>,
+        listener: TcpListener,
+        tls: Option<Arc<ReloadableTlsAcceptor>>,
+    ) -> Result<()> {
+        info!(
+            "MCP server listening on tcp://{}{}",
+            listener.local_addr()?,
+            if tls.is_some() { " (TLS)" } else { "" }
+        );
+
+        let Some(acceptor) = tls else {
+            loop {
+                let (stream, addr) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        warn!("Failed to accept MCP connection: {}", e);
+                        continue;
+                    }
+                };
+                tokio::spawn(Arc::clone(&self).serve_stream(stream, Peer::new("tcp", addr)));
+            }
+        };
+
+        let mut accepted = tls::accept_tls(listener, acceptor);
+        while let Some(stream) = accepted.recv().await {
+            let (tcp, connection) = stream.get_ref();
+            let Ok(addr) = tcp.peer_addr() else {
+                continue;
+            };
+            let name = connection
+                .peer_certificates()
+                .and_then(|chain| chain.first())
+                .and_then(|cert| tls::peer_identities(&cert.0).into_iter().next());
+            let peer = Peer::new("tcp", addr).with_name(name);
+            tokio::spawn(Arc::clone(&self).serve_stream(stream, peer));
+        }
+        Ok(())
+    }
+
+    /// Run a session over a byte stream of newline-delimited messages, then
+    /// shut the stream down once every response is written
+    pub async fn serve_stream<S>(self: Arc<Self>, stream: S, peer: Peer)
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (reader, mut writer) = tokio::io::split(stream);
+        let (tx, mut rx) = mpsc::channel::<String>(32);
+        let writing = tokio::spawn(async move {
+            while let Some(mut message) = rx.recv().await {
+                message.push('\n');
+                if writer.write_all(message.as_bytes()).await.is_err()
+                    || writer.flush().await.is_err()
+                {
+                    break;
+                }
+            }
+            let _ = writer.shutdown().await;
+        });
+
+        self.run_session(peer, lines(reader), tx).await;
+        let _ = writing.await;
+    }
+
+    /// Read messages from `incoming` and send responses to `outgoing` until
+    /// the client goes away, idles out, or fails to authenticate. Returns
+    /// once every call in flight has sent its response.
+    pub async fn run_session<I>(
+        self: Arc<Self>,
+        peer: Peer,
+        mut incoming: I,
+        outgoing: mpsc::Sender<String>,
+    ) where
+        I: Stream<Item = std::io::Result<String>> + Unpin,
+    {
+        let Ok(_permit) = Arc::clone(&self.connections).try_acquire_owned() else {
+            warn!("Turned away MCP client {}: too many connections", peer);
+            let busy = RpcError::new(SERVER_BUSY, "Too many MCP connections; try again later");
+            let _ = outgoing.send(response(Value::Null, Err(busy))).await;
+            return;
+        };
+
+        let mut session = Session {
+            authenticated: self.token.is_none() || peer.authenticated,
+            peer,
+            client: None,
+        };
+        info!("MCP client connected: {}", session.peer);
+
+        let mut calls = JoinSet::new();
+        loop {
+            let next = tokio::select! {
+                next = tokio::time::timeout(self.idle_timeout, incoming.next()) => next,
+                Some(_) = calls.join_next(), if !calls.is_empty() => continue,
+            };
+            let line = match next {
+                Err(_) if calls.is_empty() => {
+                    info!("Closing idle MCP connection {}", session.caller());
+                    break;
+                }
+                Err(_) => continue,
+                Ok(None) => break,
+                Ok(Some(Err(e))) => {
+                    debug!("MCP connection {} failed: {}", session.peer, e);
+                    break;
+                }
+                Ok(Some(Ok(line))) => line,
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let message: Value = match serde_json::from_str(&line) {
+                Ok(message) => message,
+                Err(e) => {
+                    let error = RpcError::new(PARSE_ERROR, format!("Invalid JSON: {}", e));
+                    let _ = outgoing.send(response(Value::Null, Err(error))).await;
+                    continue;
+                }
+            };
+            // Responses to requests we never make
+            let Some(method) = message.get("method").and_then(|m| m.as_str()) else {
+                continue;
+            };
+            let id = message.get("id").cloned();
+            let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+            if method == "initialize" {
+                let result = self.initialize(&mut session, &params);
+                let refused = result.is_err();
+                if let Some(id) = id {
+                    let _ = outgoing.send(response(id, result)).await;
+                }
+                if refused {
+                    warn!("MCP client {} failed to authenticate", session.peer);
+                    break;
+                }
+                continue;
+            }
+            if !session.authenticated {
+                warn!(
+                    "MCP client {} sent {} before authenticating",
+                    session.peer, method
+                );
+                let error = RpcError::new(UNAUTHORIZED, "Send initialize with auth_token first");
+                let _ = outgoing
+                    .send(response(id.unwrap_or(Value::Null), Err(error)))
+                    .await;
+                break;
+            }
+            // Notifications need no answer
+            let Some(id) = id else {
+                continue;
+            };
+
+            let server = Arc::clone(&self);
+            let caller = session.caller();
+            let method = method.to_string();
+            let outgoing = outgoing.clone();
+            calls.spawn(async move {
+                let result = guard::as_caller(caller, server.request(&method, params)).await;
+                let _ = outgoing.send(response(id, result)).await;
+            });
+        }
+
+        while calls.join_next().await.is_some() {}
+        info!("MCP client disconnected: {}", session.caller());
+    }
+
+    fn initialize(&self, session: &mut Session, params: &Value) -> Result<Value, RpcError> {
+        if !session.authenticated {
+            let token = params.get("auth_token").and_then(|t| t.as_str());
+            if token.is_none() || token != self.token.as_deref() {
+                return Err(RpcError::new(UNAUTHORIZED, "Missing or invalid auth_token"));
+            }
+            session.authenticated = true;
+        }
+        session.client = params
+            .pointer("/clientInfo/name")
+            .and_then(|n| n.as_str())
+            .map(str::to_string);
+
+        let mut capabilities = json!({ "tools": {} });
+        if !self.resources.is_empty() {
+            capabilities["resources"] = json!({});
+        }
+        Ok(json!({
+            "protocolVersion": params
+                .get("protocolVersion")
+                .and_then(|v| v.as_str())
+                .unwrap_or(PROTOCOL_VERSION),
+            "capabilities": capabilities,
+            "serverInfo": { "name": "jarvis", "version": env!("CARGO_PKG_VERSION") },
+        }))
+    }
+
+    async fn request(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            "ping" => Ok(json!({})),
+            "tools/list" => {
+                let tools: Vec<Value> = self
+                    .tools
+                    .iter()
+                    .map(|tool| {
+                        json!({
+                            "name": tool.name(),
+                            "description": tool.description(),
+                            "inputSchema": tool.input_schema(),
+                        })
+                    })
+                    .collect();
+                Ok(json!({ "tools": tools }))
+            }
+            "tools/call" => {
+                let name = params
+                    .get("name")
+                    .and_then(|n| n.as_str())
+                    .unwrap_or_default();
+                let tool = self
+                    .tools
+                    .iter()
+                    .find(|tool| tool.name() == name)
+                    .ok_or_else(|| {
+                        RpcError::new(INVALID_PARAMS, format!("Unknown tool: {}", name))
+                    })?;
+                // A failed call is a result the model should see, not a protocol error
+                match tool.call(params.get("arguments").cloned()).await {
+                    Ok(result) => Ok(json!(result)),
+                    Err(e) => {
+                        let message = match e {
+                            glyph::Error::ToolExecution(message) => message,
+                            other => other.to_string(),
+                        };
+                        Ok(json!({
+                            "content": [{ "type": "text", "text": message }],
+                            "isError": true,
+                        }))
+                    }
+                }
+            }
+            "resources/list" => {
+                let resources: Vec<Value> = self
+                    .resources
+                    .iter()
+                    .map(|resource| {
+                        json!({
+                            "uri": resource.uri(),
+                            "name": resource.name(),
+                            "description": resource.description(),
+                            "mimeType": resource.mime_type(),
+                        })
+                    })
+                    .collect();
+                Ok(json!({ "resources": resources }))
+            }
+            "resources/read" => {
+                let uri = params
+                    .get("uri")
+                    .and_then(|u| u.as_str())
+                    .unwrap_or_default();
+                let resource = self
+                    .resources
+                    .iter()
+                    .find(|resource| resource.uri() == uri)
+                    .ok_or_else(|| {
+                        RpcError::new(INVALID_PARAMS, format!("Unknown resource: {}", uri))
+                    })?;
+                let contents = resource
+                    .read()
+                    .await
+                    .map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))?;
+                Ok(json!(contents))
+            }
+            other => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("Unknown method: {}", other),
+            )),
+        }
+    }
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> String {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": error.code, "message": error.message },
+        }),
+    }
+    .to_string()
+}
+
+/// Newline-delimited messages read from `reader`, refusing overlong ones
+fn lines<R>(reader: R) -> impl Stream<Item = std::io::Result<String>> + Unpin
+where
+    R: AsyncRead + Unpin,
+{
+    Box::pin(futures::stream::unfold(
+        BufReader::new(reader),
+        |mut reader| async move {
+            let mut line = String::new();
+            let read = (&mut reader)
+                .take(MAX_MESSAGE_BYTES + 1)
+                .read_line(&mut line)
+                .await;
+            match read {
+                Ok(0) => None,
+                Ok(_) if line.len() as u64 > MAX_MESSAGE_BYTES => Some((
+                    Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "message too long",
+                    )),
+                    reader,
+                )),
+                Ok(_) => Some((Ok(line), reader)),
+                Err(e) => Some((Err(e), reader)),
+            }
+        },
+    ))
+}
@@ -0,0 +1,135 @@
+//! Token bucket rate limiting for MCP tool invocations
+//!
+//! Buckets are keyed by tool, action, and caller so that one noisy client
+//! cannot exhaust the budget of another.
+
+use crate::config::{RateLimitConfig, RateLimitRule};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Returned when a call is rejected by the limiter
+#[derive(Debug, Clone)]
+pub struct RateLimitExceeded {
+    pub key: String,
+    pub retry_after: Duration,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Per-tool, per-client token bucket limiter
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Resolve the rule for a tool/action: "tool:action" override, then "tool"
+    /// override, then the destructive default, then the global default.
+    pub fn rule_for(&self, tool: &str, action: Option<&str>) -> RateLimitRule {
+        if let Some(action) = action {
+            if let Some(rule) = self.config.per_tool.get(&format!("{}:{}", tool, action)) {
+                return *rule;
+            }
+        }
+
+        if let Some(rule) = self.config.per_tool.get(tool) {
+            return *rule;
+        }
+
+        match action {
+            Some(action) if self.config.destructive_actions.iter().any(|a| a == action) => {
+                self.config.destructive
+            }
+            _ => self.config.default,
+        }
+    }
+
+    /// Consume one token for this call, or report how long to wait
+    pub fn check(
+        &self,
+        tool: &str,
+        action: Option<&str>,
+        caller: &str,
+    ) -> Result<(), RateLimitExceeded> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+
+        let rule = self.rule_for(tool, action);
+        let key = match action {
+            Some(action) => format!("{}:{}@{}", tool, action, caller),
+            None => format!("{}@{}", tool, caller),
+        };
+
+        let capacity = rule.burst.max(1) as f64;
+        let refill_per_sec = rule.per_minute as f64 / 60.0;
+        let now = Instant::now();
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        let bucket = buckets.entry(key.clone()).or_insert(Bucket {
+            tokens: capacity,
+            last_refill: now,
+        });
+
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * refill_per_sec).min(capacity);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+
+        let retry_after = if refill_per_sec > 0.0 {
+            Duration::from_secs_f64((1.0 - bucket.tokens) / refill_per_sec)
+        } else {
+            Duration::from_secs(60)
+        };
+
+        Err(RateLimitExceeded { key, retry_after })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_destructive_actions_use_stricter_rule() {
+        let limiter = RateLimiter::new(RateLimitConfig::default());
+
+        let rule = limiter.rule_for("jarvis_package_manager", Some("install"));
+        assert_eq!(rule.burst, 2);
+
+        let rule = limiter.rule_for("jarvis_package_manager", Some("search"));
+        assert_eq!(rule.burst, 30);
+    }
+
+    #[test]
+    fn test_bucket_exhaustion_returns_retry_after() {
+        let limiter = RateLimiter::new(RateLimitConfig::default());
+
+        assert!(limiter.check("jarvis_docker", Some("stop"), "stdio").is_ok());
+        assert!(limiter.check("jarvis_docker", Some("stop"), "stdio").is_ok());
+
+        let err = limiter
+            .check("jarvis_docker", Some("stop"), "stdio")
+            .unwrap_err();
+        assert!(err.retry_after > Duration::ZERO);
+
+        // Other callers have their own bucket
+        assert!(limiter.check("jarvis_docker", Some("stop"), "ws").is_ok());
+    }
+}
@@ -1,27 +1,184 @@
 //! Jarvis MCP Server
 
-use anyhow::Result;
-use glyph::server::ServerBuilder;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use glyph::protocol::{CallToolResult, ReadResourceResult, ToolInputSchema};
+use glyph::server::{Resource, ServerBuilder, Tool};
+use serde_json::Value;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use crate::config::{McpConfig, RemoteConfig, SystemConfig, TraceConfig};
+use crate::llm::LLMRouter;
+use crate::mcp::audit::AuditLogResource;
+use crate::mcp::guard::{GuardedTool, ToolGuard};
+use crate::mcp::network::NetworkServer;
+use crate::mcp::rate_limit::RateLimiter;
+use crate::mcp::timeout::ToolTimeouts;
 use crate::mcp::tools::*;
+use crate::memory::MemoryStore;
+use crate::plugins::load_plugins;
+use crate::tls::ReloadableTlsAcceptor;
+
+/// Number of entries exposed through the `jarvis://audit/recent` resource
+const AUDIT_RESOURCE_LIMIT: i32 = 50;
+
+/// What the tools need from the rest of Jarvis
+#[derive(Clone)]
+pub struct ToolContext {
+    pub llm_router: Option<LLMRouter>,
+    pub memory: Option<MemoryStore>,
+    pub system: SystemConfig,
+    pub remote: RemoteConfig,
+    pub trace: TraceConfig,
+    /// Listed in the `jarvis_system_status` description
+    pub capabilities: Vec<String>,
+}
+
+/// The tools and resources every transport serves, guarded
+pub struct JarvisTools {
+    pub tools: Vec<Arc<dyn Tool + Send + Sync>>,
+    pub resources: Vec<Arc<dyn Resource + Send + Sync>>,
+}
+
+impl JarvisTools {
+    /// Built-in and plugin tools, rate limited and audited as `caller` unless
+    /// the transport names the client per connection
+    pub fn new(mcp_config: &McpConfig, context: ToolContext, caller: &str) -> Self {
+        let limiter = Arc::new(RateLimiter::new(mcp_config.rate_limits.clone()));
+        let builtin_names: Vec<&str> = BUILTIN_TOOLS.iter().map(|(name, _)| *name).collect();
+        let plugins = load_plugins(&mcp_config.plugins, &builtin_names);
+        plugins.log_errors();
+        let audit = if mcp_config.audit_enabled { context.memory.clone() } else { None };
+
+        let mut guard = ToolGuard::new(limiter, audit.clone(), caller)
+            .with_timeouts(ToolTimeouts::new(mcp_config.timeouts.clone()));
+        if let Some(memory) = &context.memory {
+            guard = guard.with_traces(memory.clone(), context.trace.clone());
+        }
+
+        let package_tool = PackageManagerTool::new(context.system.package_holds.clone())
+            .with_files_db_max_age(context.system.files_db_max_age())
+            .with_idempotency_window(mcp_config.idempotency_window());
+        let docker_tool = DockerTool::new(context.llm_router.clone())
+            .with_diagnostics_cache(mcp_config.diagnostics_cache.clone());
+
+        let mut tools: Vec<Arc<dyn Tool + Send + Sync>> = vec![
+            Arc::new(GuardedTool::new(SystemStatusTool::new(context.capabilities), guard.clone())),
+            Arc::new(GuardedTool::new(package_tool, guard.clone())),
+            Arc::new(GuardedTool::new(docker_tool, guard.clone())),
+            Arc::new(GuardedTool::new(PowerTool::new(context.remote), guard.clone())),
+            Arc::new(GuardedTool::new(SnapshotsTool::new(), guard.clone())),
+        ];
+        for plugin in plugins.tools {
+            tracing::info!("Registering plugin tool {} from {}", plugin.spec.name, plugin.source.display());
+            tools.push(Arc::new(GuardedTool::new(PluginMcpTool::new(plugin), guard.clone())));
+        }
+
+        let mut resources: Vec<Arc<dyn Resource + Send + Sync>> = Vec::new();
+        if let Some(memory) = audit {
+            resources.push(Arc::new(AuditLogResource::new(memory, AUDIT_RESOURCE_LIMIT)));
+        }
+
+        Self { tools, resources }
+    }
+
+    /// A server for TCP and WebSocket clients, with the connection settings
+    /// of `[mcp]`; `token` is the one clients must send
+    pub fn network_server(self, mcp_config: &McpConfig, token: Option<String>) -> NetworkServer {
+        NetworkServer::new(self.tools, self.resources)
+            .with_token(token)
+            .with_idle_timeout(mcp_config.idle_timeout())
+            .with_max_connections(mcp_config.max_connections)
+    }
+}
+
+/// Lets glyph's servers register the shared tools
+struct SharedTool(Arc<dyn Tool + Send + Sync>);
+
+#[async_trait]
+impl Tool for SharedTool {
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.0.description()
+    }
+
+    fn input_schema(&self) -> ToolInputSchema {
+        self.0.input_schema()
+    }
+
+    async fn call(&self, args: Option<Value>) -> Result<CallToolResult, glyph::Error> {
+        self.0.call(args).await
+    }
+}
+
+struct SharedResource(Arc<dyn Resource + Send + Sync>);
+
+#[async_trait]
+impl Resource for SharedResource {
+    fn uri(&self) -> &str {
+        self.0.uri()
+    }
+
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.0.description()
+    }
+
+    fn mime_type(&self) -> Option<&str> {
+        self.0.mime_type()
+    }
+
+    async fn read(&self) -> Result<ReadResourceResult, glyph::Error> {
+        self.0.read().await
+    }
+}
 
 /// Run Jarvis MCP server
-pub async fn run_mcp_server(transport: &str, address: Option<&str>, llm_router: Option<crate::llm::LLMRouter>) -> Result<()> {
+pub async fn run_mcp_server(
+    transport: &str,
+    address: Option<&str>,
+    llm_router: Option<crate::llm::LLMRouter>,
+    mcp_config: &McpConfig,
+    memory: Option<MemoryStore>,
+    system: crate::config::SystemConfig,
+    remote: crate::config::RemoteConfig,
+    trace_config: crate::config::TraceConfig,
+    capabilities: Vec<String>,
+) -> Result<()> {
     tracing::info!("Starting Jarvis MCP server with transport: {}", transport);
 
     let builder = ServerBuilder::new()
         .with_server_info("jarvis", env!("CARGO_PKG_VERSION"));
+    let context = ToolContext {
+        llm_router,
+        memory,
+        system,
+        remote,
+        trace: trace_config,
+        capabilities,
+    };
 
     // Configure transport and run server
     match transport {
         "stdio" => {
             tracing::info!("Using stdio transport");
             let mut server_with_transport = builder.for_stdio();
+            let jarvis = JarvisTools::new(mcp_config, context, "stdio");
 
             // Register tools
             tracing::info!("Registering Jarvis tools");
-            server_with_transport.server().register_tool(SystemStatusTool).await?;
-            server_with_transport.server().register_tool(PackageManagerTool).await?;
-            server_with_transport.server().register_tool(DockerTool::new(llm_router.clone())).await?;
+            for tool in jarvis.tools {
+                server_with_transport.server().register_tool(SharedTool(tool)).await?;
+            }
+            for resource in jarvis.resources {
+                server_with_transport.server().register_resource(SharedResource(resource)).await?;
+            }
 
             tracing::info!("Jarvis MCP server ready");
             server_with_transport.run().await?;
@@ -30,17 +187,41 @@ pub async fn run_mcp_server(transport: &str, address: Option<&str>, llm_router:
             let addr = address.unwrap_or("127.0.0.1:7332");
             tracing::info!("Using WebSocket transport on {}", addr);
             let mut server_with_transport = builder.for_websocket(addr).await?;
+            let jarvis = JarvisTools::new(mcp_config, context, &format!("ws:{}", addr));
 
             // Register tools
             tracing::info!("Registering Jarvis tools");
-            server_with_transport.server().register_tool(SystemStatusTool).await?;
-            server_with_transport.server().register_tool(PackageManagerTool).await?;
-            server_with_transport.server().register_tool(DockerTool::new(llm_router)).await?;
+            for tool in jarvis.tools {
+                server_with_transport.server().register_tool(SharedTool(tool)).await?;
+            }
+            for resource in jarvis.resources {
+                server_with_transport.server().register_resource(SharedResource(resource)).await?;
+            }
 
             tracing::info!("Jarvis MCP server ready");
             server_with_transport.run().await?;
         },
-        _ => return Err(anyhow::anyhow!("Unsupported transport: {} (supported: stdio, ws, websocket)", transport)),
+        "tcp" => {
+            let addr = address.unwrap_or("127.0.0.1:7332");
+            tracing::info!("Using TCP transport on {}", addr);
+            let listener = TcpListener::bind(addr)
+                .await
+                .with_context(|| format!("Failed to bind the MCP server to {}", addr))?;
+            let tls = if mcp_config.tls.is_enabled() {
+                let acceptor = Arc::new(ReloadableTlsAcceptor::new(mcp_config.tls.clone())?);
+                #[cfg(unix)]
+                acceptor.reload_on_sighup()?;
+                Some(acceptor)
+            } else {
+                None
+            };
+            let server = JarvisTools::new(mcp_config, context, &format!("tcp:{}", addr))
+                .network_server(mcp_config, mcp_config.token.clone());
+
+            tracing::info!("Jarvis MCP server ready");
+            Arc::new(server).serve_tcp(listener, tls).await?;
+        },
+        _ => return Err(anyhow::anyhow!("Unsupported transport: {} (supported: stdio, ws, websocket, tcp)", transport)),
     };
 
     Ok(())
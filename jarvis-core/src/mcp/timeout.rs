@@ -0,0 +1,393 @@
+//! Deadlines for MCP tool calls
+//!
+//! [`GuardedTool`](crate::mcp::GuardedTool) races every call against the
+//! deadline resolved here. Losing the race drops the call, and with it the
+//! subprocess it was waiting on: every runner spawns with `kill_on_drop`. The
+//! same holds when the transport drops a call its client cancelled, so tools
+//! need no cancellation support of their own.
+//!
+//! What a call was stuck on comes from its trace: `subprocess` phases that
+//! never finished, plus the holder of the pacman database lock when one of
+//! them was a package manager.
+
+use crate::config::ToolTimeoutConfig;
+use crate::trace::TraceReport;
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Created by libalpm for the length of a transaction; the holder keeps it open
+pub use crate::pacman_lock::DB_LOCK_PATH as PACMAN_DB_LOCK;
+
+/// Programs that take the pacman database lock
+const LOCKING_PROGRAMS: &[&str] = &["pacman", "yay", "paru"];
+
+/// Resolves the deadline of each call
+#[derive(Debug, Clone)]
+pub struct ToolTimeouts {
+    config: ToolTimeoutConfig,
+    lock: PathBuf,
+}
+
+impl ToolTimeouts {
+    pub fn new(config: ToolTimeoutConfig) -> Self {
+        Self {
+            config,
+            lock: PathBuf::from(PACMAN_DB_LOCK),
+        }
+    }
+
+    /// Look for the pacman lock somewhere other than [`PACMAN_DB_LOCK`]
+    pub fn with_lock_path(mut self, lock: impl Into<PathBuf>) -> Self {
+        self.lock = lock.into();
+        self
+    }
+
+    /// "tool:action" override, then "tool" override, then the default
+    pub fn deadline_for(&self, tool: &str, action: Option<&str>) -> Duration {
+        let per_tool = &self.config.per_tool;
+        let secs = action
+            .and_then(|action| per_tool.get(&format!("{}:{}", tool, action)))
+            .or_else(|| per_tool.get(tool))
+            .copied()
+            .unwrap_or(self.config.default_secs);
+        Duration::from_secs(secs)
+    }
+
+    /// Describe a call abandoned after `deadline`; `report` must be taken
+    /// before the call is dropped, while its phases are still open
+    pub fn timed_out(
+        &self,
+        tool: &str,
+        action: Option<&str>,
+        deadline: Duration,
+        report: &TraceReport,
+    ) -> ToolTimedOut {
+        let commands: Vec<String> = report
+            .phases
+            .iter()
+            .filter(|p| p.name == "subprocess" && p.duration_ms.is_none())
+            .filter_map(|p| p.attributes.get("command").cloned())
+            .collect();
+
+        let mut waiting_on = Vec::new();
+        if commands.iter().any(|c| takes_pacman_lock(c))
+            && let Some(lock) = describe_pacman_lock(&self.lock)
+        {
+            waiting_on.push(lock);
+        }
+        waiting_on.extend(commands);
+
+        ToolTimedOut {
+            tool: tool.to_string(),
+            action: action.map(str::to_string),
+            timeout_secs: deadline.as_secs(),
+            waiting_on,
+        }
+    }
+}
+
+impl Default for ToolTimeouts {
+    fn default() -> Self {
+        Self::new(ToolTimeoutConfig::default())
+    }
+}
+
+/// A call that ran past its deadline
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolTimedOut {
+    pub tool: String,
+    pub action: Option<String>,
+    pub timeout_secs: u64,
+    /// Most specific first: a lock holder, then the commands that were killed
+    pub waiting_on: Vec<String>,
+}
+
+impl ToolTimedOut {
+    pub fn message(&self) -> String {
+        let call = match &self.action {
+            Some(action) => format!("{}:{}", self.tool, action),
+            None => self.tool.clone(),
+        };
+        let mut message = format!("{} timed out after {}s", call, self.timeout_secs);
+        if !self.waiting_on.is_empty() {
+            message.push_str(&format!(" waiting on {}", self.waiting_on.join("; ")));
+        }
+        message
+    }
+
+    /// Error payload in the shape of the guard's `rate_limited` error
+    pub fn to_json(&self) -> Value {
+        json!({
+            "error": "timeout",
+            "tool": self.tool,
+            "action": self.action,
+            "timeout_secs": self.timeout_secs,
+            "waiting_on": self.waiting_on,
+            "message": self.message(),
+        })
+    }
+}
+
+/// Whether a traced command line runs a program that locks the pacman database
+fn takes_pacman_lock(command: &str) -> bool {
+    command
+        .split_whitespace()
+        .find(|word| *word != "sudo")
+        .and_then(|program| program.rsplit('/').next())
+        .is_some_and(|program| LOCKING_PROGRAMS.contains(&program))
+}
+
+/// A process with the lock file open
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockHolder {
+    pub pid: u32,
+    /// Short name from `/proc/<pid>/comm`
+    pub command: String,
+}
+
+/// Find the process holding `lock` open. Only processes whose descriptors we
+/// may read are seen, so a root pacman is invisible to an unprivileged
+/// server; see [`describe_pacman_lock`] for the fallback.
+pub fn lock_holder(lock: &Path) -> Option<LockHolder> {
+    let lock = lock.canonicalize().ok()?;
+    processes()
+        .find(|(pid, _)| {
+            std::fs::read_dir(format!("/proc/{}/fd", pid))
+                .into_iter()
+                .flatten()
+                .flatten()
+                .any(|fd| std::fs::read_link(fd.path()).is_ok_and(|target| target == lock))
+        })
+        .map(|(pid, command)| LockHolder { pid, command })
+}
+
+/// What a blocked package manager is waiting for, or `None` when `lock` does
+/// not exist
+pub fn describe_pacman_lock(lock: &Path) -> Option<String> {
+    if !lock.exists() {
+        return None;
+    }
+    if let Some(holder) = lock_holder(lock) {
+        return Some(format!(
+            "pacman database lock held by PID {} ({})",
+            holder.pid, holder.command
+        ));
+    }
+    // Anyone else's pacman is most likely the holder, even when its
+    // descriptors are out of reach
+    if let Some((pid, command)) =
+        processes().find(|(_, command)| LOCKING_PROGRAMS.contains(&command.as_str()))
+    {
+        return Some(format!(
+            "pacman database lock, probably held by PID {} ({})",
+            pid, command
+        ));
+    }
+    Some(format!(
+        "pacman database lock {} with no process holding it; remove it if no package manager is running",
+        lock.display()
+    ))
+}
+
+/// Every process other than this one, with its short name
+fn processes() -> impl Iterator<Item = (u32, String)> {
+    let own = std::process::id();
+    std::fs::read_dir("/proc")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+        .filter(move |pid| *pid != own)
+        .filter_map(|pid| {
+            let comm = std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+            Some((pid, comm.trim_end().to_string()))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RateLimitConfig;
+    use crate::exec::{CommandRunner, SystemRunner};
+    use crate::mcp::{GuardedTool, RateLimiter, ToolGuard};
+    use async_trait::async_trait;
+    use glyph::protocol::{CallToolResult, Content, ToolInputSchema};
+    use glyph::server::Tool;
+    use std::process::{Child, Command, Stdio};
+    use std::sync::Arc;
+
+    /// Holds `path` open the way pacman holds its lock
+    fn hold_open(path: &Path) -> Child {
+        let child = Command::new("sleep")
+            .arg("30")
+            .stdout(std::fs::File::create(path).unwrap())
+            .stdin(Stdio::null())
+            .spawn()
+            .unwrap();
+        // Until the exec, the child still goes by the test thread's name
+        let comm = format!("/proc/{}/comm", child.id());
+        while std::fs::read_to_string(&comm).unwrap() != "sleep\n" {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        child
+    }
+
+    #[test]
+    fn test_deadline_prefers_most_specific_override() {
+        let mut config = ToolTimeoutConfig::default();
+        config.per_tool.insert("jarvis_docker".to_string(), 60);
+        config
+            .per_tool
+            .insert("jarvis_docker:diagnose".to_string(), 240);
+        let timeouts = ToolTimeouts::new(config);
+
+        let secs = |tool, action| timeouts.deadline_for(tool, action).as_secs();
+        assert_eq!(secs("jarvis_docker", Some("diagnose")), 240);
+        assert_eq!(secs("jarvis_docker", Some("logs")), 60);
+        assert_eq!(secs("jarvis_power", Some("wake")), 120);
+        assert_eq!(secs("jarvis_package_manager", Some("update")), 300);
+    }
+
+    #[test]
+    fn test_pacman_lock_holder_is_named() {
+        let dir = tempfile::tempdir().unwrap();
+        let lock = dir.path().join("db.lck");
+        assert_eq!(describe_pacman_lock(&lock), None);
+
+        let mut holder = hold_open(&lock);
+        let found = lock_holder(&lock);
+        let described = describe_pacman_lock(&lock);
+        holder.kill().unwrap();
+        holder.wait().unwrap();
+
+        assert_eq!(
+            found,
+            Some(LockHolder {
+                pid: holder.id(),
+                command: "sleep".to_string()
+            })
+        );
+        assert_eq!(
+            described.unwrap(),
+            format!("pacman database lock held by PID {} (sleep)", holder.id())
+        );
+
+        // Left behind by a crashed pacman
+        assert!(lock_holder(&lock).is_none());
+        assert!(
+            describe_pacman_lock(&lock)
+                .unwrap()
+                .contains("with no process holding it")
+        );
+    }
+
+    #[test]
+    fn test_timeout_names_lock_only_for_package_managers() {
+        let dir = tempfile::tempdir().unwrap();
+        let lock = dir.path().join("db.lck");
+        let mut holder = hold_open(&lock);
+        let timeouts = ToolTimeouts::default().with_lock_path(&lock);
+
+        let trace = crate::trace::Trace::new("jarvis_package_manager:install");
+        let finished = trace.phase("subprocess");
+        finished.attr("command", "pacman -Si ripgrep");
+        drop(finished);
+        let running = trace.phase("subprocess");
+        running.attr("command", "sudo pacman -S --noconfirm ripgrep");
+        let report = trace.report();
+        let deadline = Duration::from_secs(300);
+
+        let stuck =
+            timeouts.timed_out("jarvis_package_manager", Some("install"), deadline, &report);
+        holder.kill().unwrap();
+        holder.wait().unwrap();
+
+        assert_eq!(
+            stuck.waiting_on,
+            vec![
+                format!("pacman database lock held by PID {} (sleep)", holder.id()),
+                "sudo pacman -S --noconfirm ripgrep".to_string(),
+            ]
+        );
+        assert!(stuck.message().starts_with(
+            "jarvis_package_manager:install timed out after 300s waiting on pacman database lock"
+        ));
+
+        let trace = crate::trace::Trace::new("jarvis_docker:inspect");
+        let inspect = trace.phase("subprocess");
+        inspect.attr("command", "docker inspect ollama");
+        let stuck = timeouts.timed_out("jarvis_docker", Some("inspect"), deadline, &trace.report());
+        assert_eq!(stuck.waiting_on, vec!["docker inspect ollama"]);
+    }
+
+    /// Writes its PID where the test can find it, then hangs
+    struct HangingTool {
+        pid_file: PathBuf,
+    }
+
+    #[async_trait]
+    impl Tool for HangingTool {
+        fn name(&self) -> &str {
+            "jarvis_hang"
+        }
+
+        fn description(&self) -> Option<&str> {
+            None
+        }
+
+        fn input_schema(&self) -> ToolInputSchema {
+            ToolInputSchema::object()
+        }
+
+        async fn call(&self, _args: Option<Value>) -> Result<CallToolResult, glyph::Error> {
+            let script = format!("echo $$ > {}; exec sleep 30", self.pid_file.display());
+            SystemRunner::default()
+                .output("sh", &["-c", &script])
+                .await
+                .map_err(|e| glyph::Error::ToolExecution(e.to_string()))?;
+            Ok(CallToolResult::success(vec![Content::text("finished")]))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_guard_kills_subprocess_on_timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        let pid_file = dir.path().join("pid");
+        let mut config = ToolTimeoutConfig::default();
+        config.per_tool.insert("jarvis_hang".to_string(), 1);
+
+        let limiter = Arc::new(RateLimiter::new(RateLimitConfig::default()));
+        let guard = ToolGuard::new(limiter, None, "test").with_timeouts(ToolTimeouts::new(config));
+        let tool = GuardedTool::new(
+            HangingTool {
+                pid_file: pid_file.clone(),
+            },
+            guard,
+        );
+
+        let started = std::time::Instant::now();
+        let Err(err) = tool.call(Some(json!({ "action": "wait" }))).await else {
+            panic!("the call should have timed out");
+        };
+        let err = err.to_string();
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(err.contains("\"error\":\"timeout\""), "{}", err);
+        assert!(err.contains("jarvis_hang:wait timed out after 1s waiting on sh -c echo"));
+
+        // kill_on_drop reaps in the background; give it a moment
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        let stat = format!("/proc/{}/stat", pid.trim());
+        let mut gone = false;
+        for _ in 0..50 {
+            gone = std::fs::read_to_string(&stat)
+                .map_or(true, |s| s.split_whitespace().nth(2) == Some("Z"));
+            if gone {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(gone, "sleep {} survived the timeout", pid.trim());
+    }
+}
@@ -6,10 +6,37 @@ use glyph::protocol::{ToolInputSchema, CallToolResult, Content};
 use serde_json::{json, Value};
 use sysinfo::System;
 use std::collections::HashMap;
-use tokio::process::Command;
+use std::sync::Arc;
+use crate::config::DiagnosticsCacheConfig;
+use crate::exec::{CommandRunner, SystemRunner};
+use crate::idempotency::{self, Replays};
+use crate::mcp::diagnostics_cache::{bucket_percentages, container_state, fingerprint, Analysis, CacheStats, DiagnosticsCache};
+use crate::package_files;
+use crate::pacman_lock;
+use crate::preflight::{preflight, PackageAction, PreflightReport};
+use crate::time_range::TimeRange;
 
 /// System status tool
-pub struct SystemStatusTool;
+pub struct SystemStatusTool {
+    /// What the agent on this host can do, e.g. `package_management`
+    capabilities: Vec<String>,
+    description: String,
+}
+
+impl SystemStatusTool {
+    /// `capabilities` are listed in the tool description, so a client sees
+    /// them during tool discovery, and in every status report
+    pub fn new(capabilities: Vec<String>) -> Self {
+        let mut description = "Check Linux system status (CPU, memory, disk, processes)".to_string();
+        if !capabilities.is_empty() {
+            description.push_str(&format!(". Agent capabilities: {}", capabilities.join(", ")));
+        }
+        Self {
+            capabilities,
+            description,
+        }
+    }
+}
 
 #[async_trait]
 impl Tool for SystemStatusTool {
@@ -18,7 +45,7 @@ impl Tool for SystemStatusTool {
     }
 
     fn description(&self) -> Option<&str> {
-        Some("Check Linux system status (CPU, memory, disk, processes)")
+        Some(&self.description)
     }
 
     fn input_schema(&self) -> ToolInputSchema {
@@ -66,12 +93,173 @@ impl Tool for SystemStatusTool {
             output.push_str(&format!("Swap: {:.2} GB / {:.2} GB\n", swap_used_gb, swap_total_gb));
         }
 
+        if !self.capabilities.is_empty() {
+            output.push_str(&format!("\nAgent capabilities: {}\n", self.capabilities.join(", ")));
+        }
+        if crate::read_only::is_read_only() {
+            output.push_str("\nMode: read-only (Jarvis observes and advises; changes are refused)\n");
+        }
+
         Ok(CallToolResult::success(vec![Content::text(&output)]))
     }
 }
 
+/// Actions that change the system, and so honor `idempotency_key`
+const CHANGING_ACTIONS: &[&str] = &["install", "remove", "update"];
+
 /// Package manager tool for Arch Linux (pacman/yay/paru)
-pub struct PackageManagerTool;
+pub struct PackageManagerTool {
+    holds: Vec<String>,
+    runner: Arc<dyn CommandRunner>,
+    files_db_max_age: std::time::Duration,
+    /// Outcomes of confirmed changes by idempotency key, so a client's retry
+    /// doesn't install or update twice
+    idempotency: Replays<Result<String, String>>,
+    idempotency_window: std::time::Duration,
+}
+
+impl PackageManagerTool {
+    /// `holds` are packages that pre-flight reports flag and that are never changed
+    pub fn new(holds: Vec<String>) -> Self {
+        Self {
+            holds,
+            runner: Arc::new(SystemRunner::default()),
+            files_db_max_age: crate::package_files::DEFAULT_FILES_DB_MAX_AGE,
+            idempotency: Replays::new(),
+            idempotency_window: idempotency::DEFAULT_WINDOW,
+        }
+    }
+
+    /// How long the outcome of a call with an `idempotency_key` is replayed
+    pub fn with_idempotency_window(mut self, window: std::time::Duration) -> Self {
+        self.idempotency_window = window;
+        self
+    }
+
+    /// Spawn package manager commands through `runner`
+    pub fn with_runner(mut self, runner: Arc<dyn CommandRunner>) -> Self {
+        self.runner = runner;
+        self
+    }
+
+    /// Refresh the file database before owns/provides when it is older than `max_age`
+    pub fn with_files_db_max_age(mut self, max_age: std::time::Duration) -> Self {
+        self.files_db_max_age = max_age;
+        self
+    }
+
+    /// Run the pre-flight check. Returns the text to send back when the
+    /// operation must not proceed, or the report when it may.
+    async fn check(
+        &self,
+        action: PackageAction,
+        targets: &[String],
+        confirm: bool,
+    ) -> Result<Result<PreflightReport, String>, glyph::Error> {
+        let report = preflight(action, targets, &self.holds)
+            .await
+            .map_err(|e| glyph::Error::ToolExecution(format!("Pre-flight check failed: {}", e)))?;
+        let report_json = serde_json::to_string_pretty(&report).unwrap_or_default();
+
+        if !confirm {
+            return Ok(Err(format!(
+                "{}\n🚨 Nothing was changed. Re-run with confirm=true to proceed.\n\n```json\n{}\n```",
+                report.render(),
+                report_json
+            )));
+        }
+
+        if !report.held.is_empty() {
+            return Err(glyph::Error::ToolExecution(format!(
+                "Refusing to {}: {} on the hold list. Remove them from system.package_holds first.\n\n{}",
+                action,
+                report.held.join(", "),
+                report.render()
+            )));
+        }
+
+        tracing::info!("Package {} proceeding after pre-flight: {}", action, report_json);
+        Ok(Ok(report))
+    }
+
+    async fn run_action(&self, args: &Value, action: &str, package: Option<&str>, manager: &str, confirm: bool) -> Result<String, glyph::Error> {
+        if manager == "flatpak" {
+            return flatpak_action(self.runner.as_ref(), action, package, confirm).await;
+        }
+        // pacman would only fail with "unable to lock database"
+        if confirm && CHANGING_ACTIONS.contains(&action) {
+            let lock_path = std::path::Path::new(pacman_lock::DB_LOCK_PATH);
+            if let Some(report) = db_lock_report(self.runner.as_ref(), lock_path).await? {
+                return Ok(report);
+            }
+        }
+
+        let output = match action {
+            "search" => {
+                let pkg = package.ok_or_else(|| {
+                    glyph::Error::ToolExecution("Package name required for search".to_string())
+                })?;
+                search_package(self.runner.as_ref(), manager, pkg).await?
+            }
+            "info" => {
+                let pkg = package.ok_or_else(|| {
+                    glyph::Error::ToolExecution("Package name required for info".to_string())
+                })?;
+                package_info(self.runner.as_ref(), manager, pkg).await?
+            }
+            "install" => {
+                let pkg = package.ok_or_else(|| {
+                    glyph::Error::ToolExecution("Package name required for install".to_string())
+                })?;
+                match self.check(PackageAction::Install, &[pkg.to_string()], confirm).await? {
+                    Ok(report) => format!("{}\n{}", report.render(), install_package(self.runner.as_ref(), manager, pkg, confirm).await?),
+                    Err(preview) => preview,
+                }
+            }
+            "remove" => {
+                let pkg = package.ok_or_else(|| {
+                    glyph::Error::ToolExecution("Package name required for remove".to_string())
+                })?;
+                match self.check(PackageAction::Remove, &[pkg.to_string()], confirm).await? {
+                    Ok(report) => format!("{}\n{}", report.render(), remove_package(self.runner.as_ref(), manager, pkg, confirm).await?),
+                    Err(preview) => preview,
+                }
+            }
+            "update" => {
+                match self.check(PackageAction::Update, &[], confirm).await? {
+                    Ok(report) => format!("{}\n{}", report.render(), update_system(self.runner.as_ref(), manager, confirm).await?),
+                    Err(preview) => preview,
+                }
+            }
+            "list-installed" => {
+                list_installed_packages(self.runner.as_ref(), manager).await?
+            }
+            "list-updates" => {
+                list_available_updates(self.runner.as_ref(), manager, crate::net::is_offline()).await?
+            }
+            "owns" | "provides" => {
+                let target = args.get("path").and_then(|v| v.as_str()).or(package).ok_or_else(|| {
+                    glyph::Error::ToolExecution(format!("File path required for {}", action))
+                })?;
+                let result = if action == "owns" {
+                    package_files::owns(self.runner.as_ref(), target, self.files_db_max_age)
+                        .await
+                        .map(|r| to_pretty_json(&r))
+                } else {
+                    package_files::provides(self.runner.as_ref(), target, self.files_db_max_age)
+                        .await
+                        .map(|r| to_pretty_json(&r))
+                };
+                result.map_err(|e| glyph::Error::ToolExecution(format!("{} lookup failed: {}", action, e)))?
+            }
+            _ => {
+                return Err(glyph::Error::ToolExecution(format!("Unknown action: {}", action)));
+            }
+        };
+
+        Ok(output)
+    }
+}
 
 #[async_trait]
 impl Tool for PackageManagerTool {
@@ -80,7 +268,7 @@ impl Tool for PackageManagerTool {
     }
 
     fn description(&self) -> Option<&str> {
-        Some("Manage Arch Linux packages (search, info, install, remove, update) with pacman/yay/paru")
+        Some("Manage Arch Linux packages (search, info, install, remove, update) with pacman/yay/paru, find which package owns or provides a file, and manage Flatpak apps with manager=flatpak")
     }
 
     fn input_schema(&self) -> ToolInputSchema {
@@ -90,7 +278,7 @@ impl Tool for PackageManagerTool {
             json!({
                 "type": "string",
                 "description": "Action to perform",
-                "enum": ["search", "info", "install", "remove", "update", "list-installed", "list-updates"]
+                "enum": ["search", "info", "install", "remove", "update", "list-installed", "list-updates", "owns", "provides"]
             })
         );
         properties.insert(
@@ -100,12 +288,19 @@ impl Tool for PackageManagerTool {
                 "description": "Package name (required for search, info, install, remove)"
             })
         );
+        properties.insert(
+            "path".to_string(),
+            json!({
+                "type": "string",
+                "description": "File path for owns (e.g. /usr/bin/foo), or file or name for provides (e.g. libssl.so.3)"
+            })
+        );
         properties.insert(
             "manager".to_string(),
             json!({
                 "type": "string",
                 "description": "Package manager to use",
-                "enum": ["pacman", "yay", "paru"],
+                "enum": ["pacman", "yay", "paru", "flatpak"],
                 "default": "pacman"
             })
         );
@@ -113,10 +308,17 @@ impl Tool for PackageManagerTool {
             "confirm".to_string(),
             json!({
                 "type": "boolean",
-                "description": "Skip confirmation prompts (use with caution)",
+                "description": "Execute install/remove/update. When false, only a pre-flight report of what would change is returned",
                 "default": false
             })
         );
+        properties.insert(
+            "idempotency_key".to_string(),
+            json!({
+                "type": "string",
+                "description": "Unique key for a confirmed install/remove/update. Retrying with the same key returns the first call's result instead of running it again"
+            })
+        );
 
         ToolInputSchema::object()
             .with_properties(properties)
@@ -136,43 +338,23 @@ impl Tool for PackageManagerTool {
         let manager = args.get("manager").and_then(|v| v.as_str()).unwrap_or("pacman");
         let confirm = args.get("confirm").and_then(|v| v.as_bool()).unwrap_or(false);
 
-        let output = match action {
-            "search" => {
-                let pkg = package.ok_or_else(|| {
-                    glyph::Error::ToolExecution("Package name required for search".to_string())
-                })?;
-                search_package(manager, pkg).await?
-            }
-            "info" => {
-                let pkg = package.ok_or_else(|| {
-                    glyph::Error::ToolExecution("Package name required for info".to_string())
-                })?;
-                package_info(manager, pkg).await?
-            }
-            "install" => {
-                let pkg = package.ok_or_else(|| {
-                    glyph::Error::ToolExecution("Package name required for install".to_string())
-                })?;
-                install_package(manager, pkg, confirm).await?
-            }
-            "remove" => {
-                let pkg = package.ok_or_else(|| {
-                    glyph::Error::ToolExecution("Package name required for remove".to_string())
-                })?;
-                remove_package(manager, pkg, confirm).await?
-            }
-            "update" => {
-                update_system(manager, confirm).await?
-            }
-            "list-installed" => {
-                list_installed_packages(manager).await?
-            }
-            "list-updates" => {
-                list_available_updates(manager).await?
-            }
-            _ => {
-                return Err(glyph::Error::ToolExecution(format!("Unknown action: {}", action)));
+        let output = match args.get("idempotency_key").and_then(|v| v.as_str()) {
+            Some(key) if confirm && CHANGING_ACTIONS.contains(&action) => {
+                let request = json!({ "action": action, "package": package, "manager": manager }).to_string();
+                let outcome = self.idempotency
+                    .run(key, &request, self.idempotency_window, async {
+                        self.run_action(&args, action, package, manager, confirm).await.map_err(|e| e.to_string())
+                    })
+                    .await
+                    .map_err(|e| glyph::Error::ToolExecution(e.to_string()))?;
+                let output = outcome.value.map_err(glyph::Error::ToolExecution)?;
+                if outcome.replayed {
+                    format!("↩️ Idempotency key '{}' was already used for this {}; nothing was run again. Its result:\n\n{}", key, action, output)
+                } else {
+                    output
+                }
             }
+            _ => self.run_action(&args, action, package, manager, confirm).await?,
         };
 
         Ok(CallToolResult::success(vec![Content::text(&output)]))
@@ -181,16 +363,109 @@ impl Tool for PackageManagerTool {
 
 // Helper functions for package management
 
-async fn search_package(manager: &str, package: &str) -> Result<String, glyph::Error> {
+fn to_pretty_json<T: serde::Serialize>(value: &T) -> String {
+    serde_json::to_string_pretty(value).unwrap_or_else(|e| format!("{{\"error\": \"{}\"}}", e))
+}
+
+/// The database lock with its age and holders, as text followed by JSON;
+/// `None` when the database isn't locked
+async fn db_lock_report(runner: &dyn CommandRunner, path: &std::path::Path) -> Result<Option<String>, glyph::Error> {
+    let lock = pacman_lock::check(runner, path)
+        .await
+        .map_err(|e| glyph::Error::ToolExecution(format!("Failed to check the package database lock: {}", e)))?;
+    Ok(lock.map(|lock| {
+        format!(
+            "🔒 {}\nNothing was changed.\n\n```json\n{}\n```",
+            lock,
+            to_pretty_json(&json!({ "db_lock": lock, "stale": lock.is_stale() }))
+        )
+    }))
+}
+
+/// Flatpak actions; list/update output is JSON so callers can consume it directly
+async fn flatpak_action(runner: &dyn CommandRunner, action: &str, package: Option<&str>, confirm: bool) -> Result<String, glyph::Error> {
+    let to_tool_error = |e: anyhow::Error| glyph::Error::ToolExecution(e.to_string());
+
+    if !crate::flatpak::is_available().await {
+        return Err(glyph::Error::ToolExecution("flatpak is not installed".to_string()));
+    }
+
+    match action {
+        "list-installed" => {
+            let refs = crate::flatpak::list_installed().await.map_err(to_tool_error)?;
+            Ok(to_pretty_json(&refs))
+        }
+        "list-updates" => {
+            let updates = crate::flatpak::check_updates().await.map_err(to_tool_error)?;
+            Ok(to_pretty_json(&updates))
+        }
+        "update" => {
+            let refs: Vec<String> = package.map(|p| vec![p.to_string()]).unwrap_or_default();
+            if !confirm {
+                let updates = crate::flatpak::check_updates().await.map_err(to_tool_error)?;
+                let pending: Vec<_> = updates
+                    .into_iter()
+                    .filter(|u| refs.is_empty() || refs.contains(&u.application))
+                    .collect();
+                return Ok(format!(
+                    "🚨 Flatpak update requires confirmation. Nothing was changed.\n\
+                     Re-run with confirm=true to apply these updates:\n\n{}",
+                    to_pretty_json(&pending)
+                ));
+            }
+            let result = crate::flatpak::update(&refs).await.map_err(to_tool_error)?;
+            Ok(to_pretty_json(&result))
+        }
+        "install" | "remove" => {
+            let pkg = package.ok_or_else(|| {
+                glyph::Error::ToolExecution(format!("Application ID required for {}", action))
+            })?;
+            let verb = if action == "install" { "install" } else { "uninstall" };
+            if !confirm {
+                return Ok(format!(
+                    "🚨 Flatpak {} requires confirmation. Nothing was changed.\n\n\
+                     Would run: flatpak {} --noninteractive -y {}\n\
+                     Re-run with confirm=true to proceed.",
+                    action, verb, pkg
+                ));
+            }
+            let output = runner.output("flatpak", &[verb, "--noninteractive", "-y", pkg])
+                .await
+                .map_err(|e| glyph::Error::ToolExecution(format!("Failed to run flatpak: {}", e)))?;
+            Ok(to_pretty_json(&json!({
+                "operation": format!("flatpak_{}", verb),
+                "application": pkg,
+                "success": output.status.success(),
+                "output": String::from_utf8_lossy(&output.stdout),
+                "error": String::from_utf8_lossy(&output.stderr),
+            })))
+        }
+        "search" | "info" => {
+            let pkg = package.ok_or_else(|| {
+                glyph::Error::ToolExecution(format!("Application ID required for {}", action))
+            })?;
+            let output = runner.output("flatpak", &[action, pkg])
+                .await
+                .map_err(|e| glyph::Error::ToolExecution(format!("Failed to run flatpak: {}", e)))?;
+            Ok(format!(
+                "=== Flatpak {}: {} ===\n\n{}",
+                action,
+                pkg,
+                String::from_utf8_lossy(&output.stdout)
+            ))
+        }
+        _ => Err(glyph::Error::ToolExecution(format!("Unknown action: {}", action))),
+    }
+}
+
+async fn search_package(runner: &dyn CommandRunner, manager: &str, package: &str) -> Result<String, glyph::Error> {
     let (cmd, args) = match manager {
         "pacman" => ("pacman", vec!["-Ss", package]),
         "yay" | "paru" => (manager, vec!["-Ss", package]),
         _ => return Err(glyph::Error::ToolExecution(format!("Unknown package manager: {}", manager))),
     };
 
-    let output = Command::new(cmd)
-        .args(&args)
-        .output()
+    let output = runner.output(cmd, &args)
         .await
         .map_err(|e| glyph::Error::ToolExecution(format!("Failed to run {}: {}", cmd, e)))?;
 
@@ -205,16 +480,14 @@ async fn search_package(manager: &str, package: &str) -> Result<String, glyph::E
     Ok(format!("=== Package Search: {} ===\n\n{}\n\n(Showing first 20 results)", package, lines.join("\n")))
 }
 
-async fn package_info(manager: &str, package: &str) -> Result<String, glyph::Error> {
+async fn package_info(runner: &dyn CommandRunner, manager: &str, package: &str) -> Result<String, glyph::Error> {
     let (cmd, args) = match manager {
         "pacman" => ("pacman", vec!["-Si", package]),
         "yay" | "paru" => (manager, vec!["-Si", package]),
         _ => return Err(glyph::Error::ToolExecution(format!("Unknown package manager: {}", manager))),
     };
 
-    let output = Command::new(cmd)
-        .args(&args)
-        .output()
+    let output = runner.output(cmd, &args)
         .await
         .map_err(|e| glyph::Error::ToolExecution(format!("Failed to run {}: {}", cmd, e)))?;
 
@@ -228,7 +501,7 @@ async fn package_info(manager: &str, package: &str) -> Result<String, glyph::Err
     Ok(format!("=== Package Info: {} ===\n\n{}", package, stdout))
 }
 
-async fn install_package(manager: &str, package: &str, confirm: bool) -> Result<String, glyph::Error> {
+async fn install_package(runner: &dyn CommandRunner, manager: &str, package: &str, confirm: bool) -> Result<String, glyph::Error> {
     if !confirm {
         return Ok(format!(
             "🚨 Package installation requires confirmation.\n\n\
@@ -246,9 +519,7 @@ async fn install_package(manager: &str, package: &str, confirm: bool) -> Result<
         _ => return Err(glyph::Error::ToolExecution(format!("Unknown package manager: {}", manager))),
     };
 
-    let output = Command::new(cmd)
-        .args(&args)
-        .output()
+    let output = runner.output(cmd, &args)
         .await
         .map_err(|e| glyph::Error::ToolExecution(format!("Failed to run {}: {}", cmd, e)))?;
 
@@ -262,7 +533,7 @@ async fn install_package(manager: &str, package: &str, confirm: bool) -> Result<
     Ok(format!("✅ Successfully installed: {}\n\n{}", package, stdout))
 }
 
-async fn remove_package(manager: &str, package: &str, confirm: bool) -> Result<String, glyph::Error> {
+async fn remove_package(runner: &dyn CommandRunner, manager: &str, package: &str, confirm: bool) -> Result<String, glyph::Error> {
     if !confirm {
         return Ok(format!(
             "🚨 Package removal requires confirmation.\n\n\
@@ -279,9 +550,7 @@ async fn remove_package(manager: &str, package: &str, confirm: bool) -> Result<S
         _ => return Err(glyph::Error::ToolExecution(format!("Unknown package manager: {}", manager))),
     };
 
-    let output = Command::new(cmd)
-        .args(&args)
-        .output()
+    let output = runner.output(cmd, &args)
         .await
         .map_err(|e| glyph::Error::ToolExecution(format!("Failed to run {}: {}", cmd, e)))?;
 
@@ -295,7 +564,7 @@ async fn remove_package(manager: &str, package: &str, confirm: bool) -> Result<S
     Ok(format!("✅ Successfully removed: {}\n\n{}", package, stdout))
 }
 
-async fn update_system(manager: &str, confirm: bool) -> Result<String, glyph::Error> {
+async fn update_system(runner: &dyn CommandRunner, manager: &str, confirm: bool) -> Result<String, glyph::Error> {
     if !confirm {
         return Ok(format!(
             "🚨 System update requires confirmation.\n\n\
@@ -313,9 +582,7 @@ async fn update_system(manager: &str, confirm: bool) -> Result<String, glyph::Er
         _ => return Err(glyph::Error::ToolExecution(format!("Unknown package manager: {}", manager))),
     };
 
-    let output = Command::new(cmd)
-        .args(&args)
-        .output()
+    let output = runner.output(cmd, &args)
         .await
         .map_err(|e| glyph::Error::ToolExecution(format!("Failed to run {}: {}", cmd, e)))?;
 
@@ -329,15 +596,13 @@ async fn update_system(manager: &str, confirm: bool) -> Result<String, glyph::Er
     Ok(format!("✅ System update complete:\n\n{}", stdout))
 }
 
-async fn list_installed_packages(manager: &str) -> Result<String, glyph::Error> {
+async fn list_installed_packages(runner: &dyn CommandRunner, manager: &str) -> Result<String, glyph::Error> {
     let (cmd, args) = match manager {
         "pacman" | "yay" | "paru" => ("pacman", vec!["-Q"]),
         _ => return Err(glyph::Error::ToolExecution(format!("Unknown package manager: {}", manager))),
     };
 
-    let output = Command::new(cmd)
-        .args(&args)
-        .output()
+    let output = runner.output(cmd, &args)
         .await
         .map_err(|e| glyph::Error::ToolExecution(format!("Failed to run {}: {}", cmd, e)))?;
 
@@ -347,16 +612,22 @@ async fn list_installed_packages(manager: &str) -> Result<String, glyph::Error>
     Ok(format!("=== Installed Packages ===\n\nTotal: {} packages\n\n{}", count, stdout))
 }
 
-async fn list_available_updates(manager: &str) -> Result<String, glyph::Error> {
-    let (cmd, args) = match manager {
-        "pacman" => ("sh", vec!["-c", "checkupdates"]),
-        "yay" | "paru" => (manager, vec!["-Qu"]),
+/// Offline, compares against the local sync databases only and skips the AUR
+async fn list_available_updates(runner: &dyn CommandRunner, manager: &str, offline: bool) -> Result<String, glyph::Error> {
+    let (cmd, args) = match (manager, offline) {
+        ("pacman", false) => ("sh", vec!["-c", "checkupdates"]),
+        ("pacman", true) => ("pacman", vec!["-Qu"]),
+        ("yay" | "paru", false) => (manager, vec!["-Qu"]),
+        ("yay" | "paru", true) => (manager, vec!["-Qu", "--repo"]),
         _ => return Err(glyph::Error::ToolExecution(format!("Unknown package manager: {}", manager))),
     };
+    let note = if offline {
+        "📴 Offline: checked against the last synced databases; AUR skipped\n\n"
+    } else {
+        ""
+    };
 
-    let output = Command::new(cmd)
-        .args(&args)
-        .output()
+    let output = runner.output(cmd, &args)
         .await
         .map_err(|e| glyph::Error::ToolExecution(format!("Failed to run {}: {}", cmd, e)))?;
 
@@ -364,25 +635,48 @@ async fn list_available_updates(manager: &str) -> Result<String, glyph::Error> {
     let stderr = String::from_utf8_lossy(&output.stderr);
 
     if stdout.is_empty() && !output.status.success() {
-        return Ok(format!("✅ System is up to date!\n\n{}", stderr));
+        return Ok(format!("{}✅ System is up to date!\n\n{}", note, stderr));
     }
 
     let count = stdout.lines().count();
-    Ok(format!("=== Available Updates ===\n\n{} packages can be updated:\n\n{}", count, stdout))
+    Ok(format!("{}=== Available Updates ===\n\n{} packages can be updated:\n\n{}", note, count, stdout))
 }
 
 /// Docker and KVM/Libvirt management tool with LLM diagnostics
 pub struct DockerTool {
     llm_router: Option<crate::llm::LLMRouter>,
+    runner: Arc<dyn CommandRunner>,
+    diagnostics: DiagnosticsCache,
 }
 
 impl DockerTool {
     pub fn new(llm_router: Option<crate::llm::LLMRouter>) -> Self {
-        Self { llm_router }
+        Self {
+            llm_router,
+            runner: Arc::new(SystemRunner::default()),
+            diagnostics: DiagnosticsCache::new(DiagnosticsCacheConfig::default()),
+        }
     }
 
     pub fn without_llm() -> Self {
-        Self { llm_router: None }
+        Self::new(None)
+    }
+
+    /// Spawn docker and virsh commands through `runner`
+    pub fn with_runner(mut self, runner: Arc<dyn CommandRunner>) -> Self {
+        self.runner = runner;
+        self
+    }
+
+    /// Reuse LLM analyses in `diagnose` and `health` as `config` allows
+    pub fn with_diagnostics_cache(mut self, config: DiagnosticsCacheConfig) -> Self {
+        self.diagnostics = DiagnosticsCache::new(config);
+        self
+    }
+
+    /// How often `diagnose` and `health` reused an analysis so far
+    pub fn diagnostics_stats(&self) -> CacheStats {
+        self.diagnostics.stats()
     }
 }
 
@@ -425,6 +719,13 @@ impl Tool for DockerTool {
                 "default": 50
             })
         );
+        properties.insert(
+            "since".to_string(),
+            json!({
+                "type": "string",
+                "description": "Time range for logs, e.g. \"2h\", \"yesterday\", \"since boot\""
+            })
+        );
         properties.insert(
             "llm_assist".to_string(),
             json!({
@@ -450,97 +751,109 @@ impl Tool for DockerTool {
 
         let target = args.get("target").and_then(|v| v.as_str());
         let tail = args.get("tail").and_then(|v| v.as_i64()).unwrap_or(50);
+        let since = args
+            .get("since")
+            .and_then(|v| v.as_str())
+            .map(crate::time_range::parse_range)
+            .transpose()
+            .map_err(|e| glyph::Error::ToolExecution(format!("Invalid 'since': {}", e)))?;
         let llm_assist = args.get("llm_assist").and_then(|v| v.as_bool()).unwrap_or(true);
 
+        // Cache metadata, for the actions whose analysis may be reused
+        let mut metadata = None;
         let output = match action {
             // Docker commands
-            "list" | "ps" => docker_list().await?,
+            "list" | "ps" => docker_list(self.runner.as_ref()).await?,
             "inspect" => {
                 let container = target.ok_or_else(|| {
                     glyph::Error::ToolExecution("Container name required for inspect".to_string())
                 })?;
-                docker_inspect(container).await?
+                docker_inspect(self.runner.as_ref(), container).await?
             }
             "logs" => {
                 let container = target.ok_or_else(|| {
                     glyph::Error::ToolExecution("Container name required for logs".to_string())
                 })?;
-                docker_logs(container, tail as usize).await?
+                docker_logs(self.runner.as_ref(), container, tail as usize, since).await?
             }
             "start" => {
                 let container = target.ok_or_else(|| {
                     glyph::Error::ToolExecution("Container name required for start".to_string())
                 })?;
-                docker_start(container).await?
+                docker_start(self.runner.as_ref(), container).await?
             }
             "stop" => {
                 let container = target.ok_or_else(|| {
                     glyph::Error::ToolExecution("Container name required for stop".to_string())
                 })?;
-                docker_stop(container).await?
+                docker_stop(self.runner.as_ref(), container).await?
             }
             "restart" => {
                 let container = target.ok_or_else(|| {
                     glyph::Error::ToolExecution("Container name required for restart".to_string())
                 })?;
-                docker_restart(container).await?
+                docker_restart(self.runner.as_ref(), container).await?
             }
             "stats" => {
                 let container = target.ok_or_else(|| {
                     glyph::Error::ToolExecution("Container name required for stats".to_string())
                 })?;
-                docker_stats(container).await?
+                docker_stats(self.runner.as_ref(), container).await?
             }
             "diagnose" => {
                 let container = target.ok_or_else(|| {
                     glyph::Error::ToolExecution("Container name required for diagnose".to_string())
                 })?;
-                docker_diagnose(container, &self.llm_router, llm_assist).await?
+                let (report, analysis) = docker_diagnose(self.runner.as_ref(), container, &self.llm_router, llm_assist, &self.diagnostics).await?;
+                metadata = Some(self.diagnostics.metadata(analysis.as_ref()));
+                report
             }
             "health" => {
-                docker_health_overview(&self.llm_router, llm_assist).await?
+                let (report, analysis) = docker_health_overview(self.runner.as_ref(), &self.llm_router, llm_assist, &self.diagnostics).await?;
+                metadata = Some(self.diagnostics.metadata(analysis.as_ref()));
+                report
             }
             "network-inspect" => {
                 let container = target.ok_or_else(|| {
                     glyph::Error::ToolExecution("Container name required for network-inspect".to_string())
                 })?;
-                docker_network_inspect(container, &self.llm_router, llm_assist).await?
+                docker_network_inspect(self.runner.as_ref(), container, &self.llm_router, llm_assist).await?
             }
             "volume-inspect" => {
-                docker_volume_inspect(&self.llm_router, llm_assist).await?
+                docker_volume_inspect(self.runner.as_ref(), &self.llm_router, llm_assist).await?
             }
             "profile" => {
                 let container = target.ok_or_else(|| {
                     glyph::Error::ToolExecution("Container name required for profile".to_string())
                 })?;
-                docker_performance_profile(container, &self.llm_router, llm_assist).await?
+                docker_performance_profile(self.runner.as_ref(), container, &self.llm_router, llm_assist).await?
             }
 
             // KVM/Libvirt commands
-            "vm-list" => vm_list().await?,
+            "vm-list" => vm_list(self.runner.as_ref()).await?,
             "vm-status" => {
                 let vm = target.ok_or_else(|| {
                     glyph::Error::ToolExecution("VM name required for vm-status".to_string())
                 })?;
-                vm_status(vm).await?
+                vm_status(self.runner.as_ref(), vm).await?
             }
             "vm-start" => {
                 let vm = target.ok_or_else(|| {
                     glyph::Error::ToolExecution("VM name required for vm-start".to_string())
                 })?;
-                vm_start(vm).await?
+                vm_start(self.runner.as_ref(), vm).await?
             }
             "vm-stop" => {
                 let vm = target.ok_or_else(|| {
                     glyph::Error::ToolExecution("VM name required for vm-stop".to_string())
                 })?;
-                vm_stop(vm).await?
+                vm_stop(self.runner.as_ref(), vm).await?
             }
             "vm-info" => {
                 let vm = target.ok_or_else(|| {
                     glyph::Error::ToolExecution("VM name required for vm-info".to_string())
                 })?;
-                vm_info(vm, &self.llm_router, llm_assist).await?
+                vm_info(self.runner.as_ref(), vm, &self.llm_router, llm_assist).await?
             }
 
             _ => {
@@ -548,16 +861,18 @@ impl Tool for DockerTool {
             }
         };
 
-        Ok(CallToolResult::success(vec![Content::text(&output)]))
+        let mut content = vec![Content::text(&output)];
+        if let Some(metadata) = metadata {
+            content.push(Content::text(&metadata.to_string()));
+        }
+        Ok(CallToolResult::success(content))
     }
 }
 
 // Docker helper functions
 
-async fn docker_list() -> Result<String, glyph::Error> {
-    let output = Command::new("docker")
-        .args(&["ps", "-a", "--format", "table {{.ID}}\\t{{.Names}}\\t{{.Status}}\\t{{.Image}}"])
-        .output()
+async fn docker_list(runner: &dyn CommandRunner) -> Result<String, glyph::Error> {
+    let output = runner.output("docker", &["ps", "-a", "--format", "table {{.ID}}\\t{{.Names}}\\t{{.Status}}\\t{{.Image}}"])
         .await
         .map_err(|e| glyph::Error::ToolExecution(format!("Failed to run docker ps: {}", e)))?;
 
@@ -571,10 +886,8 @@ async fn docker_list() -> Result<String, glyph::Error> {
     Ok(format!("=== Docker Containers ===\n\n{}", stdout))
 }
 
-async fn docker_inspect(container: &str) -> Result<String, glyph::Error> {
-    let output = Command::new("docker")
-        .args(&["inspect", container])
-        .output()
+async fn docker_inspect(runner: &dyn CommandRunner, container: &str) -> Result<String, glyph::Error> {
+    let output = runner.output("docker", &["inspect", container])
         .await
         .map_err(|e| glyph::Error::ToolExecution(format!("Failed to inspect container: {}", e)))?;
 
@@ -588,10 +901,22 @@ async fn docker_inspect(container: &str) -> Result<String, glyph::Error> {
     Ok(format!("=== Container Inspect: {} ===\n\n{}", container, stdout))
 }
 
-async fn docker_logs(container: &str, tail: usize) -> Result<String, glyph::Error> {
-    let output = Command::new("docker")
-        .args(&["logs", "--tail", &tail.to_string(), container])
-        .output()
+/// The last `tail` lines, or every line in `since` when a range is given
+async fn docker_logs(
+    runner: &dyn CommandRunner,
+    container: &str,
+    tail: usize,
+    since: Option<TimeRange>,
+) -> Result<String, glyph::Error> {
+    let tail_arg = tail.to_string();
+    let bounds = since.map(|range| (range.start.to_rfc3339(), range.end.to_rfc3339()));
+    let mut args = vec!["logs"];
+    match &bounds {
+        Some((start, end)) => args.extend(["--since", start.as_str(), "--until", end.as_str()]),
+        None => args.extend(["--tail", tail_arg.as_str()]),
+    }
+    args.push(container);
+    let output = runner.output("docker", &args)
         .await
         .map_err(|e| glyph::Error::ToolExecution(format!("Failed to get logs: {}", e)))?;
 
@@ -601,13 +926,15 @@ async fn docker_logs(container: &str, tail: usize) -> Result<String, glyph::Erro
     // Docker logs can write to stderr even on success
     let combined = format!("{}{}", stdout, stderr);
 
-    Ok(format!("=== Container Logs: {} (last {} lines) ===\n\n{}", container, tail, combined))
+    let scope = match since {
+        Some(range) => format!("since {}", range.start.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M")),
+        None => format!("last {} lines", tail),
+    };
+    Ok(format!("=== Container Logs: {} ({}) ===\n\n{}", container, scope, combined))
 }
 
-async fn docker_start(container: &str) -> Result<String, glyph::Error> {
-    let output = Command::new("docker")
-        .args(&["start", container])
-        .output()
+async fn docker_start(runner: &dyn CommandRunner, container: &str) -> Result<String, glyph::Error> {
+    let output = runner.output("docker", &["start", container])
         .await
         .map_err(|e| glyph::Error::ToolExecution(format!("Failed to start container: {}", e)))?;
 
@@ -621,10 +948,8 @@ async fn docker_start(container: &str) -> Result<String, glyph::Error> {
     Ok(format!("✅ Started container: {}\n\n{}", container, stdout))
 }
 
-async fn docker_stop(container: &str) -> Result<String, glyph::Error> {
-    let output = Command::new("docker")
-        .args(&["stop", container])
-        .output()
+async fn docker_stop(runner: &dyn CommandRunner, container: &str) -> Result<String, glyph::Error> {
+    let output = runner.output("docker", &["stop", container])
         .await
         .map_err(|e| glyph::Error::ToolExecution(format!("Failed to stop container: {}", e)))?;
 
@@ -638,10 +963,8 @@ async fn docker_stop(container: &str) -> Result<String, glyph::Error> {
     Ok(format!("✅ Stopped container: {}\n\n{}", container, stdout))
 }
 
-async fn docker_restart(container: &str) -> Result<String, glyph::Error> {
-    let output = Command::new("docker")
-        .args(&["restart", container])
-        .output()
+async fn docker_restart(runner: &dyn CommandRunner, container: &str) -> Result<String, glyph::Error> {
+    let output = runner.output("docker", &["restart", container])
         .await
         .map_err(|e| glyph::Error::ToolExecution(format!("Failed to restart container: {}", e)))?;
 
@@ -655,10 +978,8 @@ async fn docker_restart(container: &str) -> Result<String, glyph::Error> {
     Ok(format!("✅ Restarted container: {}\n\n{}", container, stdout))
 }
 
-async fn docker_stats(container: &str) -> Result<String, glyph::Error> {
-    let output = Command::new("docker")
-        .args(&["stats", "--no-stream", "--format", "table {{.Container}}\\t{{.CPUPerc}}\\t{{.MemUsage}}\\t{{.MemPerc}}\\t{{.NetIO}}\\t{{.BlockIO}}", container])
-        .output()
+async fn docker_stats(runner: &dyn CommandRunner, container: &str) -> Result<String, glyph::Error> {
+    let output = runner.output("docker", &["stats", "--no-stream", "--format", "table {{.Container}}\\t{{.CPUPerc}}\\t{{.MemUsage}}\\t{{.MemPerc}}\\t{{.NetIO}}\\t{{.BlockIO}}", container])
         .await
         .map_err(|e| glyph::Error::ToolExecution(format!("Failed to get stats: {}", e)))?;
 
@@ -672,19 +993,22 @@ async fn docker_stats(container: &str) -> Result<String, glyph::Error> {
     Ok(format!("=== Container Stats: {} ===\n\n{}", container, stdout))
 }
 
+/// The diagnostic report of `container`, and the LLM analysis in it if any.
+/// The facts are gathered on every call; the analysis comes from `cache`
+/// while they are unchanged.
 async fn docker_diagnose(
+    runner: &dyn CommandRunner,
     container: &str,
     llm_router: &Option<crate::llm::LLMRouter>,
     llm_assist: bool,
-) -> Result<String, glyph::Error> {
+    cache: &DiagnosticsCache,
+) -> Result<(String, Option<Analysis>), glyph::Error> {
     // Gather diagnostic information
     let mut diagnostics = String::new();
     diagnostics.push_str(&format!("=== Diagnostic Report: {} ===\n\n", container));
 
     // Get container status
-    let status_output = Command::new("docker")
-        .args(&["inspect", "--format", "{{.State.Status}} | {{.State.ExitCode}} | {{.State.Error}}", container])
-        .output()
+    let status_output = runner.output("docker", &["inspect", "--format", "{{.State.Status}} | {{.State.ExitCode}} | {{.State.Error}}", container])
         .await
         .map_err(|e| glyph::Error::ToolExecution(format!("Failed to get status: {}", e)))?;
 
@@ -692,9 +1016,7 @@ async fn docker_diagnose(
     diagnostics.push_str(&format!("Status: {}\n", status.trim()));
 
     // Get recent logs
-    let logs_output = Command::new("docker")
-        .args(&["logs", "--tail", "20", container])
-        .output()
+    let logs_output = runner.output("docker", &["logs", "--tail", "20", container])
         .await
         .map_err(|e| glyph::Error::ToolExecution(format!("Failed to get logs: {}", e)))?;
 
@@ -705,9 +1027,7 @@ async fn docker_diagnose(
     diagnostics.push_str(&format!("\nRecent Logs (last 20 lines):\n{}\n", combined_logs));
 
     // Get resource usage
-    let stats_output = Command::new("docker")
-        .args(&["stats", "--no-stream", "--format", "CPU: {{.CPUPerc}} | Memory: {{.MemUsage}} ({{.MemPerc}})", container])
-        .output()
+    let stats_output = runner.output("docker", &["stats", "--no-stream", "--format", "CPU: {{.CPUPerc}} | Memory: {{.MemUsage}} ({{.MemPerc}})", container])
         .await
         .map_err(|e| glyph::Error::ToolExecution(format!("Failed to get stats: {}", e)))?;
 
@@ -715,21 +1035,25 @@ async fn docker_diagnose(
     diagnostics.push_str(&format!("\nResource Usage:\n{}\n", stats.trim()));
 
     // Use LLM to analyze if available
+    let mut analysis = None;
     if llm_assist {
         if let Some(router) = llm_router {
-            diagnostics.push_str("\n=== AI Analysis ===\n\n");
-
             let prompt = format!(
                 "Analyze this Docker container diagnostic information and provide troubleshooting recommendations:\n\n{}",
                 diagnostics
             );
+            let facts = fingerprint(&[status.trim(), &combined_logs, &bucket_percentages(&stats)]);
+            let subject = format!("diagnose:{}", container);
 
-            match router.generate_with_intent(&prompt, crate::llm::Intent::DevOps).await {
-                Ok(analysis) => {
-                    diagnostics.push_str(&analysis);
+            match cache.analyze(&subject, facts, || router.generate_with_intent(&prompt, crate::llm::Intent::DevOps)).await {
+                Ok(result) => {
+                    diagnostics.push_str(&format!("\n=== {} ===\n\n", result.heading("AI Analysis")));
+                    diagnostics.push_str(&result.text);
                     diagnostics.push_str("\n");
+                    analysis = Some(result);
                 }
                 Err(e) => {
+                    diagnostics.push_str("\n=== AI Analysis ===\n\n");
                     diagnostics.push_str(&format!("⚠️ LLM analysis unavailable: {}\n", e));
                 }
             }
@@ -738,20 +1062,22 @@ async fn docker_diagnose(
         }
     }
 
-    Ok(diagnostics)
+    Ok((diagnostics, analysis))
 }
 
+/// The health overview of all containers, and the LLM recommendations in it
+/// if any, from `cache` while the container states are unchanged
 async fn docker_health_overview(
+    runner: &dyn CommandRunner,
     llm_router: &Option<crate::llm::LLMRouter>,
     llm_assist: bool,
-) -> Result<String, glyph::Error> {
+    cache: &DiagnosticsCache,
+) -> Result<(String, Option<Analysis>), glyph::Error> {
     let mut report = String::new();
     report.push_str("=== Docker Health Overview ===\n\n");
 
     // Get all containers
-    let ps_output = Command::new("docker")
-        .args(&["ps", "-a", "--format", "{{.Names}}|{{.Status}}|{{.Image}}"])
-        .output()
+    let ps_output = runner.output("docker", &["ps", "-a", "--format", "{{.Names}}|{{.Status}}|{{.Image}}"])
         .await
         .map_err(|e| glyph::Error::ToolExecution(format!("Failed to list containers: {}", e)))?;
 
@@ -760,10 +1086,13 @@ async fn docker_health_overview(
     let mut running = 0;
     let mut stopped = 0;
     let mut unhealthy = 0;
+    // Container states without uptimes, which change on every call
+    let mut states = Vec::new();
 
     for line in containers.lines() {
         let parts: Vec<&str> = line.split('|').collect();
         if parts.len() >= 2 {
+            states.push(format!("{}|{}", parts[0], container_state(parts[1])));
             if parts[1].contains("Up") {
                 running += 1;
             } else {
@@ -781,9 +1110,7 @@ async fn docker_health_overview(
     report.push_str(&format!("Unhealthy: {} ❌\n\n", unhealthy));
 
     // Docker system info
-    let info_output = Command::new("docker")
-        .args(&["system", "df"])
-        .output()
+    let info_output = runner.output("docker", &["system", "df"])
         .await
         .map_err(|e| glyph::Error::ToolExecution(format!("Failed to get system info: {}", e)))?;
 
@@ -791,36 +1118,38 @@ async fn docker_health_overview(
     report.push_str(&format!("Disk Usage:\n{}\n", disk_usage));
 
     // LLM recommendations
+    let mut analysis = None;
     if llm_assist && unhealthy > 0 {
         if let Some(router) = llm_router {
-            report.push_str("\n=== AI Recommendations ===\n\n");
-
             let prompt = format!(
                 "There are {} unhealthy Docker containers. Provide recommendations for troubleshooting and maintaining Docker health.\n\nCurrent state:\n{}",
                 unhealthy, report
             );
+            let states = states.join("\n");
+            let facts = fingerprint(&[&states, &bucket_percentages(&disk_usage)]);
 
-            match router.generate_with_intent(&prompt, crate::llm::Intent::DevOps).await {
-                Ok(recommendations) => {
-                    report.push_str(&recommendations);
+            match cache.analyze("health", facts, || router.generate_with_intent(&prompt, crate::llm::Intent::DevOps)).await {
+                Ok(result) => {
+                    report.push_str(&format!("\n=== {} ===\n\n", result.heading("AI Recommendations")));
+                    report.push_str(&result.text);
                     report.push_str("\n");
+                    analysis = Some(result);
                 }
                 Err(e) => {
+                    report.push_str("\n=== AI Recommendations ===\n\n");
                     report.push_str(&format!("⚠️ LLM recommendations unavailable: {}\n", e));
                 }
             }
         }
     }
 
-    Ok(report)
+    Ok((report, analysis))
 }
 
 // KVM/Libvirt helper functions
 
-async fn vm_list() -> Result<String, glyph::Error> {
-    let output = Command::new("virsh")
-        .args(&["list", "--all"])
-        .output()
+async fn vm_list(runner: &dyn CommandRunner) -> Result<String, glyph::Error> {
+    let output = runner.output("virsh", &["list", "--all"])
         .await
         .map_err(|e| glyph::Error::ToolExecution(format!("Failed to run virsh: {}", e)))?;
 
@@ -834,10 +1163,8 @@ async fn vm_list() -> Result<String, glyph::Error> {
     Ok(format!("=== KVM Virtual Machines ===\n\n{}", stdout))
 }
 
-async fn vm_status(vm: &str) -> Result<String, glyph::Error> {
-    let output = Command::new("virsh")
-        .args(&["domstate", vm])
-        .output()
+async fn vm_status(runner: &dyn CommandRunner, vm: &str) -> Result<String, glyph::Error> {
+    let output = runner.output("virsh", &["domstate", vm])
         .await
         .map_err(|e| glyph::Error::ToolExecution(format!("Failed to get VM status: {}", e)))?;
 
@@ -851,10 +1178,8 @@ async fn vm_status(vm: &str) -> Result<String, glyph::Error> {
     Ok(format!("=== VM Status: {} ===\n\n{}", vm, stdout))
 }
 
-async fn vm_start(vm: &str) -> Result<String, glyph::Error> {
-    let output = Command::new("virsh")
-        .args(&["start", vm])
-        .output()
+async fn vm_start(runner: &dyn CommandRunner, vm: &str) -> Result<String, glyph::Error> {
+    let output = runner.output("virsh", &["start", vm])
         .await
         .map_err(|e| glyph::Error::ToolExecution(format!("Failed to start VM: {}", e)))?;
 
@@ -868,10 +1193,8 @@ async fn vm_start(vm: &str) -> Result<String, glyph::Error> {
     Ok(format!("✅ Started VM: {}\n\n{}", vm, stdout))
 }
 
-async fn vm_stop(vm: &str) -> Result<String, glyph::Error> {
-    let output = Command::new("virsh")
-        .args(&["shutdown", vm])
-        .output()
+async fn vm_stop(runner: &dyn CommandRunner, vm: &str) -> Result<String, glyph::Error> {
+    let output = runner.output("virsh", &["shutdown", vm])
         .await
         .map_err(|e| glyph::Error::ToolExecution(format!("Failed to stop VM: {}", e)))?;
 
@@ -886,6 +1209,7 @@ async fn vm_stop(vm: &str) -> Result<String, glyph::Error> {
 }
 
 async fn vm_info(
+    runner: &dyn CommandRunner,
     vm: &str,
     llm_router: &Option<crate::llm::LLMRouter>,
     llm_assist: bool,
@@ -894,9 +1218,7 @@ async fn vm_info(
     info.push_str(&format!("=== VM Information: {} ===\n\n", vm));
 
     // Get VM info
-    let info_output = Command::new("virsh")
-        .args(&["dominfo", vm])
-        .output()
+    let info_output = runner.output("virsh", &["dominfo", vm])
         .await
         .map_err(|e| glyph::Error::ToolExecution(format!("Failed to get VM info: {}", e)))?;
 
@@ -904,9 +1226,7 @@ async fn vm_info(
     info.push_str(&format!("{}\n", dominfo));
 
     // Get CPU stats
-    let cpu_output = Command::new("virsh")
-        .args(&["cpu-stats", vm])
-        .output()
+    let cpu_output = runner.output("virsh", &["cpu-stats", vm])
         .await;
 
     if let Ok(cpu_output) = cpu_output {
@@ -942,6 +1262,7 @@ async fn vm_info(
 // Enhanced diagnostic functions
 
 async fn docker_network_inspect(
+    runner: &dyn CommandRunner,
     container: &str,
     llm_router: &Option<crate::llm::LLMRouter>,
     llm_assist: bool,
@@ -950,9 +1271,7 @@ async fn docker_network_inspect(
     report.push_str(&format!("=== Network Diagnostics: {} ===\n\n", container));
 
     // Get network settings
-    let net_output = Command::new("docker")
-        .args(&["inspect", "--format", "{{json .NetworkSettings}}", container])
-        .output()
+    let net_output = runner.output("docker", &["inspect", "--format", "{{json .NetworkSettings}}", container])
         .await
         .map_err(|e| glyph::Error::ToolExecution(format!("Failed to inspect network: {}", e)))?;
 
@@ -996,9 +1315,7 @@ async fn docker_network_inspect(
 
     // Test connectivity
     report.push_str("\nConnectivity Test:\n");
-    let ping_output = Command::new("docker")
-        .args(&["exec", container, "sh", "-c", "ping -c 1 8.8.8.8 || echo 'Ping failed'"])
-        .output()
+    let ping_output = runner.output("docker", &["exec", container, "sh", "-c", "ping -c 1 8.8.8.8 || echo 'Ping failed'"])
         .await;
 
     if let Ok(ping_output) = ping_output {
@@ -1036,6 +1353,7 @@ async fn docker_network_inspect(
 }
 
 async fn docker_volume_inspect(
+    runner: &dyn CommandRunner,
     llm_router: &Option<crate::llm::LLMRouter>,
     llm_assist: bool,
 ) -> Result<String, glyph::Error> {
@@ -1043,9 +1361,7 @@ async fn docker_volume_inspect(
     report.push_str("=== Docker Volume Analysis ===\n\n");
 
     // List volumes
-    let volumes_output = Command::new("docker")
-        .args(&["volume", "ls", "--format", "{{.Name}}|{{.Driver}}|{{.Mountpoint}}"])
-        .output()
+    let volumes_output = runner.output("docker", &["volume", "ls", "--format", "{{.Name}}|{{.Driver}}|{{.Mountpoint}}"])
         .await
         .map_err(|e| glyph::Error::ToolExecution(format!("Failed to list volumes: {}", e)))?;
 
@@ -1055,9 +1371,7 @@ async fn docker_volume_inspect(
     report.push_str(&format!("Total Volumes: {}\n\n", volume_lines.len()));
 
     // Get disk usage
-    let df_output = Command::new("docker")
-        .args(&["system", "df", "-v"])
-        .output()
+    let df_output = runner.output("docker", &["system", "df", "-v"])
         .await
         .map_err(|e| glyph::Error::ToolExecution(format!("Failed to get disk usage: {}", e)))?;
 
@@ -1065,9 +1379,7 @@ async fn docker_volume_inspect(
     report.push_str(&format!("Disk Usage:\n{}\n", disk_usage));
 
     // Identify orphaned volumes
-    let orphans_output = Command::new("docker")
-        .args(&["volume", "ls", "-f", "dangling=true", "--format", "{{.Name}}"])
-        .output()
+    let orphans_output = runner.output("docker", &["volume", "ls", "-f", "dangling=true", "--format", "{{.Name}}"])
         .await;
 
     if let Ok(orphans_output) = orphans_output {
@@ -1108,6 +1420,7 @@ async fn docker_volume_inspect(
 }
 
 async fn docker_performance_profile(
+    runner: &dyn CommandRunner,
     container: &str,
     llm_router: &Option<crate::llm::LLMRouter>,
     llm_assist: bool,
@@ -1124,9 +1437,7 @@ async fn docker_performance_profile(
     for i in 0..5 {
         tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
 
-        let stats_output = Command::new("docker")
-            .args(&["stats", "--no-stream", "--format", "{{.CPUPerc}}|{{.MemUsage}}", container])
-            .output()
+        let stats_output = runner.output("docker", &["stats", "--no-stream", "--format", "{{.CPUPerc}}|{{.MemUsage}}", container])
             .await
             .map_err(|e| glyph::Error::ToolExecution(format!("Failed to get stats: {}", e)))?;
 
@@ -1166,9 +1477,7 @@ async fn docker_performance_profile(
     }
 
     // Get process list
-    let top_output = Command::new("docker")
-        .args(&["top", container])
-        .output()
+    let top_output = runner.output("docker", &["top", container])
         .await;
 
     if let Ok(top_output) = top_output {
@@ -1179,9 +1488,7 @@ async fn docker_performance_profile(
     }
 
     // Get I/O stats
-    let io_output = Command::new("docker")
-        .args(&["stats", "--no-stream", "--format", "{{.BlockIO}}|{{.NetIO}}", container])
-        .output()
+    let io_output = runner.output("docker", &["stats", "--no-stream", "--format", "{{.BlockIO}}|{{.NetIO}}", container])
         .await;
 
     if let Ok(io_output) = io_output {
@@ -1218,3 +1525,699 @@ async fn docker_performance_profile(
 
     Ok(report)
 }
+
+/// Power control tool: Wake-on-LAN and poweroff/reboot/suspend
+pub struct PowerTool {
+    remote: crate::config::RemoteConfig,
+}
+
+impl PowerTool {
+    /// Hosts, MAC addresses, and remote mutation policy come from `remote`
+    pub fn new(remote: crate::config::RemoteConfig) -> Self {
+        Self { remote }
+    }
+}
+
+#[async_trait]
+impl Tool for PowerTool {
+    fn name(&self) -> &str {
+        "jarvis_power"
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("Wake a configured host over the network, or power off, reboot, or suspend this machine or a configured remote host")
+    }
+
+    fn input_schema(&self) -> ToolInputSchema {
+        let mut properties = HashMap::new();
+        properties.insert(
+            "action".to_string(),
+            json!({
+                "type": "string",
+                "description": "Action to perform",
+                "enum": ["wake", "poweroff", "reboot", "suspend"]
+            })
+        );
+        properties.insert(
+            "host".to_string(),
+            json!({
+                "type": "string",
+                "description": "Host name from config (required for wake; omit for this machine)"
+            })
+        );
+        properties.insert(
+            "confirm".to_string(),
+            json!({
+                "type": "boolean",
+                "description": "Must be true for poweroff, reboot, and suspend",
+                "default": false
+            })
+        );
+
+        ToolInputSchema::object()
+            .with_properties(properties)
+            .with_required(vec!["action".to_string()])
+    }
+
+    async fn call(&self, args: Option<Value>) -> Result<CallToolResult, glyph::Error> {
+        let args = args.ok_or_else(|| {
+            glyph::Error::ToolExecution("Missing arguments".to_string())
+        })?;
+
+        let action: crate::power::PowerAction = args.get("action")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| glyph::Error::ToolExecution("Missing 'action' parameter".to_string()))?
+            .parse()
+            .map_err(|e: anyhow::Error| glyph::Error::ToolExecution(e.to_string()))?;
+        let host = args.get("host").and_then(|v| v.as_str());
+        let confirm = args.get("confirm").and_then(|v| v.as_bool()).unwrap_or(false);
+
+        let timeout = std::time::Duration::from_secs(120);
+        let outcome = if action == crate::power::PowerAction::Wake {
+            let host = host.ok_or_else(|| {
+                glyph::Error::ToolExecution("Host name required for wake".to_string())
+            })?;
+            crate::power::wake(host, &self.remote, timeout).await
+        } else {
+            let executor = crate::power::executor_for(host, &self.remote);
+            if !confirm {
+                return Ok(CallToolResult::success(vec![Content::text(&format!(
+                    "⚠️ This will {} {}. Call again with confirm=true to proceed.",
+                    action,
+                    executor.host_label()
+                ))]));
+            }
+            crate::power::power_action(&executor, action, timeout).await
+        }
+        .map_err(|e| glyph::Error::ToolExecution(e.to_string()))?;
+
+        let icon = if outcome.verified { "✅" } else { "⚠️" };
+        let output = format!("{} {} {}: {}\n\n{}", icon, outcome.action, outcome.host, outcome.message, to_pretty_json(&outcome));
+        Ok(CallToolResult::success(vec![Content::text(&output)]))
+    }
+}
+
+/// Snapper snapshot browsing: list, diff, and restore single files or directories
+pub struct SnapshotsTool {
+    snapshots: crate::snapshots::Snapshots,
+}
+
+impl SnapshotsTool {
+    pub fn new() -> Self {
+        Self { snapshots: crate::snapshots::Snapshots::default() }
+    }
+
+    /// Spawn snapper and cp through `runner`
+    pub fn with_runner(mut self, runner: Arc<dyn CommandRunner>) -> Self {
+        self.snapshots = crate::snapshots::Snapshots::with_runner(runner);
+        self
+    }
+}
+
+impl Default for SnapshotsTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Entries shown per list in a diff before the rest is summarized
+const DIFF_LIST_LIMIT: usize = 100;
+
+#[async_trait]
+impl Tool for SnapshotsTool {
+    fn name(&self) -> &str {
+        "jarvis_snapshots"
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("List snapper snapshots, show which files under a path changed between two snapshots, and restore a file or directory from a snapshot (to a .restored copy unless confirmed in place)")
+    }
+
+    fn input_schema(&self) -> ToolInputSchema {
+        let mut properties = HashMap::new();
+        properties.insert(
+            "action".to_string(),
+            json!({
+                "type": "string",
+                "description": "Action to perform",
+                "enum": ["list", "diff", "restore"]
+            })
+        );
+        properties.insert(
+            "config".to_string(),
+            json!({
+                "type": "string",
+                "description": "Snapper config",
+                "default": "root"
+            })
+        );
+        properties.insert(
+            "snapshot".to_string(),
+            json!({
+                "type": "integer",
+                "description": "Snapshot to restore from (restore)"
+            })
+        );
+        properties.insert(
+            "from".to_string(),
+            json!({
+                "type": "integer",
+                "description": "Older snapshot (diff)"
+            })
+        );
+        properties.insert(
+            "to".to_string(),
+            json!({
+                "type": "integer",
+                "description": "Newer snapshot; 0 is the live system (diff)",
+                "default": 0
+            })
+        );
+        properties.insert(
+            "path".to_string(),
+            json!({
+                "type": "string",
+                "description": "Absolute path to compare below (diff, default /) or to restore (restore)"
+            })
+        );
+        properties.insert(
+            "target".to_string(),
+            json!({
+                "type": "string",
+                "description": "Absolute path to restore to; defaults to the path with a .restored suffix"
+            })
+        );
+        properties.insert(
+            "confirm".to_string(),
+            json!({
+                "type": "boolean",
+                "description": "Must be true to restore over the original path",
+                "default": false
+            })
+        );
+
+        ToolInputSchema::object()
+            .with_properties(properties)
+            .with_required(vec!["action".to_string()])
+    }
+
+    async fn call(&self, args: Option<Value>) -> Result<CallToolResult, glyph::Error> {
+        let args = args.ok_or_else(|| {
+            glyph::Error::ToolExecution("Missing arguments".to_string())
+        })?;
+
+        let action = args.get("action")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| glyph::Error::ToolExecution("Missing 'action' parameter".to_string()))?;
+        let config = args.get("config").and_then(|v| v.as_str()).unwrap_or("root");
+        let number = |key: &str| {
+            args.get(key)
+                .and_then(|v| v.as_u64())
+                .and_then(|n| u32::try_from(n).ok())
+        };
+        let to_error = |e: anyhow::Error| glyph::Error::ToolExecution(format!("{:#}", e));
+
+        let output = match action {
+            "list" => {
+                let snapshots = self.snapshots.list(config).await.map_err(to_error)?;
+                if snapshots.is_empty() {
+                    format!("No snapshots in snapper config {}", config)
+                } else {
+                    let lines: Vec<String> = snapshots
+                        .iter()
+                        .map(|s| {
+                            let pair = s.pre_id.map(|pre| format!(" (pre #{})", pre)).unwrap_or_default();
+                            format!("#{} {}{} {} {}", s.id, s.kind, pair, s.date, s.description)
+                        })
+                        .collect();
+                    format!("📸 {} snapshots in {}:\n{}", snapshots.len(), config, lines.join("\n"))
+                }
+            }
+            "diff" => {
+                let from = number("from").ok_or_else(|| {
+                    glyph::Error::ToolExecution("Snapshot number 'from' required for diff".to_string())
+                })?;
+                let to = number("to").unwrap_or(0);
+                let path = args.get("path").and_then(|v| v.as_str()).unwrap_or("/");
+                let diff = self.snapshots.diff(config, from, to, path).await.map_err(to_error)?;
+
+                let mut out = format!(
+                    "Changes below {} from #{} to #{}: {} added, {} removed, {} modified",
+                    diff.path, diff.from, diff.to, diff.added.len(), diff.removed.len(), diff.modified.len()
+                );
+                for (sign, files) in [("+", &diff.added), ("-", &diff.removed), ("~", &diff.modified)] {
+                    for file in files.iter().take(DIFF_LIST_LIMIT) {
+                        out.push_str(&format!("\n{} {}", sign, file));
+                    }
+                    if files.len() > DIFF_LIST_LIMIT {
+                        out.push_str(&format!("\n{} … {} more", sign, files.len() - DIFF_LIST_LIMIT));
+                    }
+                }
+                out
+            }
+            "restore" => {
+                let snapshot = number("snapshot").ok_or_else(|| {
+                    glyph::Error::ToolExecution("Snapshot number required for restore".to_string())
+                })?;
+                let path = args.get("path").and_then(|v| v.as_str()).ok_or_else(|| {
+                    glyph::Error::ToolExecution("Path required for restore".to_string())
+                })?;
+                let target = args.get("target").and_then(|v| v.as_str());
+                let confirm = args.get("confirm").and_then(|v| v.as_bool()).unwrap_or(false);
+
+                match self.snapshots.restore(config, snapshot, path, target, confirm).await.map_err(to_error)? {
+                    crate::snapshots::RestorePlan::NeedsConfirmation { source, target } => format!(
+                        "⚠️ This will overwrite {} with {} from snapshot #{}. Call again with confirm=true to proceed.",
+                        target.display(),
+                        source.display(),
+                        snapshot
+                    ),
+                    crate::snapshots::RestorePlan::Done(restored) => format!(
+                        "✅ Restored {} from snapshot #{} to {}",
+                        path,
+                        restored.snapshot,
+                        restored.target.display()
+                    ),
+                }
+            }
+            _ => {
+                return Err(glyph::Error::ToolExecution(format!("Unknown action: {}", action)));
+            }
+        };
+
+        Ok(CallToolResult::success(vec![Content::text(&output)]))
+    }
+}
+
+/// Names and descriptions of the tools compiled into Jarvis
+pub const BUILTIN_TOOLS: &[(&str, &str)] = &[
+    ("jarvis_system_status", "Check Linux system status (CPU, memory, disk, processes)"),
+    ("jarvis_package_manager", "Search, install, remove, and update packages, and find which package owns a file"),
+    ("jarvis_docker", "Manage Docker containers and KVM virtual machines"),
+    ("jarvis_power", "Wake-on-LAN and poweroff/reboot/suspend of hosts"),
+    ("jarvis_snapshots", "List and diff snapper snapshots and restore files from them"),
+];
+
+/// Actions of the built-in tools that change the system, which read-only
+/// mode refuses. Package changes only count once confirmed; unconfirmed they
+/// return a pre-flight report.
+pub const MUTATING_ACTIONS: &[(&str, &[&str])] = &[
+    ("jarvis_package_manager", CHANGING_ACTIONS),
+    ("jarvis_docker", &["start", "stop", "restart", "vm-start", "vm-stop"]),
+    ("jarvis_power", &["wake", "poweroff", "reboot", "suspend"]),
+    ("jarvis_snapshots", &["restore"]),
+];
+
+/// Whether calling `tool` with `args` would change the system
+pub fn changes_system(tool: &str, args: &Value) -> bool {
+    let action = args.get("action").and_then(|v| v.as_str()).unwrap_or_default();
+    let mutating = MUTATING_ACTIONS
+        .iter()
+        .any(|(name, actions)| *name == tool && actions.contains(&action));
+    match tool {
+        "jarvis_package_manager" => {
+            mutating && args.get("confirm").and_then(|v| v.as_bool()).unwrap_or(false)
+        }
+        _ => mutating,
+    }
+}
+
+/// `description` as a client sees it while read-only mode is on
+pub fn read_only_description(tool: &str, description: &str) -> String {
+    let description = description.trim_end_matches('.');
+    match MUTATING_ACTIONS.iter().find(|(name, _)| *name == tool) {
+        Some((_, actions)) => format!(
+            "{}. Read-only mode: {} are refused; give the user the command to run themselves",
+            description,
+            actions.join(", ")
+        ),
+        None if BUILTIN_TOOLS.iter().any(|(name, _)| *name == tool) => {
+            format!("{}. Jarvis is in read-only mode", description)
+        }
+        None => format!(
+            "{}. Read-only mode: commands that would change the system are refused",
+            description
+        ),
+    }
+}
+
+/// A user-defined tool loaded from a plugin file
+pub struct PluginMcpTool {
+    plugin: crate::plugins::PluginTool,
+}
+
+impl PluginMcpTool {
+    pub fn new(plugin: crate::plugins::PluginTool) -> Self {
+        Self { plugin }
+    }
+}
+
+#[async_trait]
+impl Tool for PluginMcpTool {
+    fn name(&self) -> &str {
+        &self.plugin.spec.name
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some(&self.plugin.spec.description)
+    }
+
+    fn input_schema(&self) -> ToolInputSchema {
+        let parameters = &self.plugin.spec.parameters;
+        let properties: HashMap<String, Value> = parameters
+            .get("properties")
+            .and_then(|p| p.as_object())
+            .map(|p| p.clone().into_iter().collect())
+            .unwrap_or_default();
+        let required: Vec<String> = parameters
+            .get("required")
+            .and_then(|r| r.as_array())
+            .map(|r| r.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect())
+            .unwrap_or_default();
+
+        ToolInputSchema::object()
+            .with_properties(properties)
+            .with_required(required)
+    }
+
+    async fn call(&self, args: Option<Value>) -> Result<CallToolResult, glyph::Error> {
+        let args = args.unwrap_or_else(|| json!({}));
+        let output = self.plugin
+            .run(&args)
+            .await
+            .map_err(|e| glyph::Error::ToolExecution(e.to_string()))?;
+
+        Ok(CallToolResult::success(vec![Content::text(&output)]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::{fake_output, RecordingRunner};
+
+    #[tokio::test]
+    async fn test_list_available_updates_counts_fixture() {
+        let runner = RecordingRunner::new()
+            .respond("yay -Qu", "linux 6.9.1-1 -> 6.9.2-1\nmesa 24.1.0-1 -> 24.1.1-1\n");
+        let output = list_available_updates(&runner, "yay", false).await.unwrap();
+
+        assert!(output.contains("2 packages can be updated"));
+        assert_eq!(runner.calls(), vec!["yay -Qu"]);
+
+        // checkupdates exits 2 with no output when nothing is pending
+        let runner = RecordingRunner::new().respond_with("sh -c checkupdates", fake_output(2, "", ""));
+        let output = list_available_updates(&runner, "pacman", false).await.unwrap();
+        assert!(output.contains("System is up to date"));
+
+        // Offline: no database sync, no AUR
+        let runner = RecordingRunner::new();
+        let output = list_available_updates(&runner, "paru", true).await.unwrap();
+        assert!(output.starts_with("📴 Offline"));
+        assert_eq!(runner.calls(), vec!["paru -Qu --repo"]);
+    }
+
+    #[tokio::test]
+    async fn test_package_changes_require_confirmation() {
+        let runner = RecordingRunner::new();
+        let output = install_package(&runner, "pacman", "ripgrep", false).await.unwrap();
+        assert!(output.contains("requires confirmation"));
+        assert!(remove_package(&runner, "pacman", "ripgrep", false).await.is_ok());
+        assert!(update_system(&runner, "pacman", false).await.is_ok());
+        assert!(runner.calls().is_empty());
+
+        install_package(&runner, "pacman", "ripgrep", true).await.unwrap();
+        assert_eq!(runner.calls(), vec!["sudo pacman -S --noconfirm ripgrep"]);
+    }
+
+    #[tokio::test]
+    async fn test_db_lock_report_shows_holder_or_staleness() {
+        let dir = tempfile::tempdir().unwrap();
+        let lock = dir.path().join("db.lck");
+        let runner = RecordingRunner::new();
+        assert!(db_lock_report(&runner, &lock).await.unwrap().is_none());
+
+        std::fs::write(&lock, "").unwrap();
+        let runner = RecordingRunner::new().respond("pgrep", "4242 pacman -Syu\n");
+        let report = db_lock_report(&runner, &lock).await.unwrap().unwrap();
+        assert!(report.contains("locked by pacman -Syu (pid 4242)"));
+        assert!(report.contains("\"stale\": false"));
+
+        let runner = RecordingRunner::new().respond_with("pgrep", fake_output(1, "", ""));
+        let report = db_lock_report(&runner, &lock).await.unwrap().unwrap();
+        assert!(report.contains("jarvis arch unlock-db"));
+        assert!(report.contains("\"stale\": true"));
+        assert!(report.contains("\"holders\": []"));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_manager_spawns_nothing() {
+        let runner = RecordingRunner::new();
+        assert!(search_package(&runner, "apt", "vim").await.is_err());
+        assert!(runner.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_docker_health_overview_from_fixture() {
+        let runner = RecordingRunner::new().respond(
+            "docker ps",
+            "web|Up 3 hours (healthy)|nginx:latest\n\
+             db|Up 3 hours (unhealthy)|postgres:16\n\
+             job|Exited (0) 2 days ago|alpine\n",
+        );
+        let cache = DiagnosticsCache::new(DiagnosticsCacheConfig::default());
+        let (report, analysis) = docker_health_overview(&runner, &None, false, &cache).await.unwrap();
+
+        assert!(report.contains("Total Containers: 3"));
+        assert!(report.contains("Running: 2"));
+        assert!(report.contains("Stopped: 1"));
+        assert!(report.contains("Unhealthy: 1"));
+        assert!(analysis.is_none());
+        assert_eq!(runner.calls().len(), 2);
+    }
+
+    /// An Ollama that has llama3.1:8b loaded and answers every chat; returns
+    /// its URL and the number of chats so far
+    async fn counting_ollama() -> (String, Arc<std::sync::atomic::AtomicUsize>) {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let chats = Arc::new(AtomicUsize::new(0));
+        let counter = chats.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0u8; 8192];
+                // Read until the headers and the announced body have arrived
+                loop {
+                    let n = socket.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request);
+                    if let Some(end) = text.find("\r\n\r\n") {
+                        let length = text
+                            .lines()
+                            .find_map(|l| {
+                                l.to_ascii_lowercase()
+                                    .strip_prefix("content-length:")
+                                    .map(|v| v.trim().parse::<usize>().unwrap_or(0))
+                            })
+                            .unwrap_or(0);
+                        if request.len() >= end + 4 + length {
+                            break;
+                        }
+                    }
+                    if n == 0 {
+                        break;
+                    }
+                }
+                let line = String::from_utf8_lossy(&request).lines().next().unwrap_or_default().to_string();
+                let (status, body) = if line.contains("/api/ps") {
+                    (200, r#"{"models":[{"name":"llama3.1:8b","size":1,"size_vram":1}]}"#)
+                } else if line.contains("/api/chat") {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    (200, r#"{"model":"llama3.1:8b","message":{"role":"assistant","content":"Restart it."},"done":true}"#)
+                } else {
+                    (404, "{}")
+                };
+                let response = format!(
+                    "HTTP/1.1 {} X\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        (url, chats)
+    }
+
+    #[tokio::test]
+    async fn test_repeated_diagnose_asks_the_llm_once() {
+        use std::sync::atomic::Ordering;
+
+        let (url, chats) = counting_ollama().await;
+        let mut config = crate::config::Config::default();
+        config.llm.ollama_url = url;
+        let router = crate::llm::LLMRouter::new(&config).await.unwrap();
+        let runner = Arc::new(
+            RecordingRunner::new()
+                .respond("docker inspect", "exited | 1 | \n")
+                .respond("docker logs", "panic: connection refused\n")
+                .respond("docker stats", "CPU: 0.00% | Memory: 0B / 0B (0.00%)\n"),
+        );
+        let tool = DockerTool::new(Some(router)).with_runner(runner.clone());
+        let diagnose = json!({ "action": "diagnose", "target": "web" });
+
+        tool.call(Some(diagnose.clone())).await.unwrap();
+        tool.call(Some(diagnose)).await.unwrap();
+
+        assert_eq!(chats.load(Ordering::SeqCst), 1);
+        assert_eq!(tool.diagnostics_stats(), CacheStats { hits: 1, misses: 1, throttled: 0 });
+        // The facts were gathered both times
+        assert_eq!(runner.calls().iter().filter(|c| c.starts_with("docker logs")).count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_docker_failure_is_reported_not_raised() {
+        let runner = RecordingRunner::new().respond_with(
+            "docker start",
+            fake_output(1, "", "Error: No such container: ghost"),
+        );
+        let output = docker_start(&runner, "ghost").await.unwrap();
+        assert!(output.starts_with("❌ Start failed"));
+        assert!(output.contains("No such container"));
+    }
+
+    #[tokio::test]
+    async fn test_docker_tool_requires_target_before_spawning() {
+        let runner = Arc::new(RecordingRunner::new());
+        let tool = DockerTool::without_llm().with_runner(runner.clone());
+
+        let result = tool.call(Some(json!({ "action": "stop" }))).await;
+        assert!(result.is_err());
+        assert!(runner.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_docker_logs_in_a_time_range() {
+        let runner = RecordingRunner::new();
+        let range = TimeRange {
+            start: "2024-05-01T08:00:00Z".parse().unwrap(),
+            end: "2024-05-01T10:00:00Z".parse().unwrap(),
+        };
+        docker_logs(&runner, "nginx", 50, Some(range)).await.unwrap();
+        docker_logs(&runner, "nginx", 50, None).await.unwrap();
+        assert_eq!(
+            runner.calls(),
+            vec![
+                "docker logs --since 2024-05-01T08:00:00+00:00 --until 2024-05-01T10:00:00+00:00 nginx",
+                "docker logs --tail 50 nginx",
+            ]
+        );
+
+        let runner = Arc::new(RecordingRunner::new());
+        let tool = DockerTool::without_llm().with_runner(runner.clone());
+        let result = tool
+            .call(Some(json!({ "action": "logs", "target": "nginx", "since": "soon" })))
+            .await;
+        assert!(result.is_err());
+        assert!(runner.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_owns_requires_a_path_before_spawning() {
+        let runner = Arc::new(RecordingRunner::new());
+        let tool = PackageManagerTool::new(vec![]).with_runner(runner.clone());
+
+        let result = tool.call(Some(json!({ "action": "owns" }))).await;
+        assert!(result.is_err());
+        assert!(runner.calls().is_empty());
+
+        tool.call(Some(json!({ "action": "owns", "path": "/usr/bin/rg" })))
+            .await
+            .unwrap();
+        assert_eq!(runner.calls()[0], "pacman -Qo /usr/bin/rg");
+    }
+
+    /// Every action of every built-in tool, and whether it changes the
+    /// system. A new action fails this until it is classified here and, if it
+    /// mutates, in `MUTATING_ACTIONS`.
+    #[test]
+    fn test_every_action_is_classified() {
+        let table: &[(&str, &[(&str, bool)])] = &[
+            ("jarvis_package_manager", &[
+                ("search", false), ("info", false), ("install", true), ("remove", true),
+                ("update", true), ("list-installed", false), ("list-updates", false),
+                ("owns", false), ("provides", false),
+            ]),
+            ("jarvis_docker", &[
+                ("list", false), ("ps", false), ("inspect", false), ("logs", false),
+                ("start", true), ("stop", true), ("restart", true), ("stats", false),
+                ("diagnose", false), ("health", false), ("network-inspect", false),
+                ("volume-inspect", false), ("profile", false), ("vm-list", false),
+                ("vm-status", false), ("vm-start", true), ("vm-stop", true), ("vm-info", false),
+            ]),
+            ("jarvis_power", &[
+                ("wake", true), ("poweroff", true), ("reboot", true), ("suspend", true),
+            ]),
+            ("jarvis_snapshots", &[("diff", false), ("list", false), ("restore", true)]),
+        ];
+        let package = PackageManagerTool::new(vec![]);
+        let docker = DockerTool::new(None);
+        let power = PowerTool::new(Default::default());
+        let snapshots = SnapshotsTool::new();
+        let schemas = [
+            (package.name(), package.input_schema()),
+            (docker.name(), docker.input_schema()),
+            (power.name(), power.input_schema()),
+            (snapshots.name(), snapshots.input_schema()),
+        ];
+
+        for (tool, schema) in schemas.iter() {
+            let (_, expected) = table
+                .iter()
+                .find(|(name, _)| name == tool)
+                .unwrap_or_else(|| panic!("{} is not classified", tool));
+            let schema = serde_json::to_value(schema).unwrap();
+            let mut actions: Vec<&str> = schema["properties"]["action"]["enum"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|a| a.as_str().unwrap())
+                .collect();
+            actions.sort();
+            let mut classified: Vec<&str> = expected.iter().map(|(action, _)| *action).collect();
+            classified.sort();
+            assert_eq!(actions, classified, "{}", tool);
+
+            for (action, mutating) in expected.iter() {
+                let args = json!({ "action": action, "confirm": true });
+                assert_eq!(changes_system(tool, &args), *mutating, "{} {}", tool, action);
+            }
+        }
+        // jarvis_system_status has no actions
+        assert_eq!(schemas.len() + 1, BUILTIN_TOOLS.len());
+
+        // Unconfirmed package changes only report what they would do
+        assert!(!changes_system("jarvis_package_manager", &json!({ "action": "install" })));
+    }
+
+    #[tokio::test]
+    async fn test_snapshots_tool_refuses_without_snapper() {
+        let runner = Arc::new(
+            RecordingRunner::new().respond_with("snapper --version", fake_output(127, "", "")),
+        );
+        let tool = SnapshotsTool::new().with_runner(runner.clone());
+
+        let result = tool.call(Some(json!({ "action": "list" }))).await;
+        assert!(result.is_err());
+        let result = tool
+            .call(Some(json!({ "action": "restore", "snapshot": 42, "path": "/etc/fstab" })))
+            .await;
+        assert!(result.is_err());
+        assert_eq!(runner.calls(), vec!["snapper --version", "snapper --version"]);
+    }
+}
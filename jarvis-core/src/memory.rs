@@ -1,14 +1,25 @@
-use crate::types::{AgentTask, Conversation, Message, MessageMetadata, MessageRole};
+use crate::memory_recovery::{self, OpenOptions, Recovery, SalvageReport};
+use crate::metrics::{self, MetricSample, MetricSeries, StoredPoint};
+use crate::types::{
+    AgentTask, AuditEntry, AuditStatus, Conversation, ConversationSummary, Message,
+    MessageMetadata, MessageRole,
+};
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use sqlx::sqlite::SqlitePoolOptions;
 use sqlx::{Pool, Row, Sqlite, SqlitePool};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use uuid::Uuid;
 
 #[derive(Clone)]
 pub struct MemoryStore {
     pool: Pool<Sqlite>,
+    path: Option<PathBuf>,
+    recovery: Option<Recovery>,
+    /// Set when the store fell back to memory, with the reason
+    unavailable: Option<String>,
     context_manager: ContextManager,
     embedding_cache: EmbeddingCache,
     session_state: SessionState,
@@ -164,25 +175,59 @@ pub enum InteractionType {
 
 impl MemoryStore {
     pub async fn new(database_path: &str) -> Result<Self> {
+        Self::open(database_path, &OpenOptions::default()).await
+    }
+
+    /// Open the database at `database_path`, moving it aside and starting
+    /// empty if it is damaged (see [`MemoryStore::recovery`]) and failing
+    /// with [`DatabaseLocked`](crate::memory_recovery::DatabaseLocked) if
+    /// another process keeps it locked
+    pub async fn open(database_path: &str, options: &OpenOptions) -> Result<Self> {
         let expanded_path = shellexpand::tilde(database_path);
         tracing::debug!("Database path: {} -> {}", database_path, expanded_path);
+        let path = Path::new(&*expanded_path);
 
         // Create parent directory if it doesn't exist
-        if let Some(parent) = std::path::Path::new(&*expanded_path).parent() {
+        if let Some(parent) = path.parent() {
             tracing::debug!("Creating parent directory: {:?}", parent);
             tokio::fs::create_dir_all(parent).await?;
         }
 
-        // Ensure the database file exists by touching it
-        if !std::path::Path::new(&*expanded_path).exists() {
-            tracing::debug!("Creating database file: {}", expanded_path);
-            tokio::fs::write(&*expanded_path, "").await?;
-        }
+        let (pool, recovery) = memory_recovery::open(path, options).await?;
+        Self::with_pool(pool, Some(path.to_path_buf()), recovery, None).await
+    }
 
-        let db_url = format!("sqlite:{}", expanded_path);
-        tracing::debug!("Database URL: {}", db_url);
-        let pool = SqlitePool::connect(&db_url).await?;
+    /// A store that lives in memory and is gone when the process exits
+    pub async fn ephemeral(reason: impl Into<String>) -> Result<Self> {
+        // Every connection to `:memory:` is its own database, so keep exactly one
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect("sqlite::memory:")
+            .await?;
+        Self::with_pool(pool, None, None, Some(reason.into())).await
+    }
+
+    /// [`MemoryStore::new`], or an ephemeral store when the database can't
+    /// be opened, so a locked or unwritable file doesn't stop jarvis
+    pub async fn open_or_ephemeral(database_path: &str) -> Result<Self> {
+        match Self::new(database_path).await {
+            Ok(store) => Ok(store),
+            Err(e) => {
+                let reason = format!("{:#}", e);
+                tracing::warn!("⚠️ Running without persistent memory: {}", reason);
+                Self::ephemeral(reason).await
+            }
+        }
+    }
 
+    async fn with_pool(
+        pool: SqlitePool,
+        path: Option<PathBuf>,
+        recovery: Option<Recovery>,
+        unavailable: Option<String>,
+    ) -> Result<Self> {
         // Initialize the database schema manually for now
         // TODO: Implement proper migrations
         sqlx::query(
@@ -221,23 +266,106 @@ impl MemoryStore {
                 updated_at TEXT NOT NULL
             );
             
+            CREATE TABLE IF NOT EXISTS audit_log (
+                id TEXT PRIMARY KEY,
+                timestamp TEXT NOT NULL,
+                tool TEXT NOT NULL,
+                action TEXT,
+                caller TEXT,
+                arguments TEXT NOT NULL,
+                duration_ms INTEGER NOT NULL,
+                status TEXT NOT NULL,
+                error TEXT
+            );
+            
             CREATE INDEX IF NOT EXISTS idx_messages_conversation_id ON messages (conversation_id);
             CREATE INDEX IF NOT EXISTS idx_messages_created_at ON messages (created_at);
             CREATE INDEX IF NOT EXISTS idx_tasks_created_at ON tasks (created_at);
             CREATE INDEX IF NOT EXISTS idx_tasks_status ON tasks (status);
+            CREATE INDEX IF NOT EXISTS idx_audit_log_timestamp ON audit_log (timestamp DESC);
+            
+            CREATE TABLE IF NOT EXISTS metrics (
+                name TEXT NOT NULL,
+                labels TEXT NOT NULL,
+                timestamp INTEGER NOT NULL,
+                resolution INTEGER NOT NULL,
+                value REAL NOT NULL,
+                min REAL NOT NULL,
+                max REAL NOT NULL,
+                samples INTEGER NOT NULL
+            );
+            
+            CREATE INDEX IF NOT EXISTS idx_metrics_name_timestamp ON metrics (name, timestamp);
+            CREATE INDEX IF NOT EXISTS idx_metrics_resolution_timestamp ON metrics (resolution, timestamp);
             "#,
         )
         .execute(&pool)
         .await?;
 
-        Ok(Self { 
+        Ok(Self {
             pool,
+            path,
+            recovery,
+            unavailable,
             context_manager: ContextManager::new(),
             embedding_cache: EmbeddingCache::new(),
             session_state: SessionState::new(),
         })
     }
 
+    /// The damaged database this store replaced when it was opened
+    pub fn recovery(&self) -> Option<&Recovery> {
+        self.recovery.as_ref()
+    }
+
+    /// Whether what is stored outlives the process
+    pub fn is_persistent(&self) -> bool {
+        self.unavailable.is_none()
+    }
+
+    /// Why the database couldn't be opened, for ephemeral stores
+    pub fn unavailable_reason(&self) -> Option<&str> {
+        self.unavailable.as_deref()
+    }
+
+    /// The database file, for stores that have one
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Fail with an explanation when `feature` needs history this store
+    /// doesn't keep
+    pub fn require_history(&self, feature: &str) -> Result<()> {
+        match &self.unavailable {
+            None => Ok(()),
+            Some(reason) => anyhow::bail!(
+                "{} needs the memory database, which could not be opened ({}). \
+                 Stop whatever holds it or fix its permissions, then try again",
+                feature,
+                reason
+            ),
+        }
+    }
+
+    /// Write a consistent copy of the database to `dest`
+    pub async fn export(&self, dest: &Path) -> Result<()> {
+        memory_recovery::export(&self.pool, dest).await
+    }
+
+    /// Merge every readable row of the database at `source` into this one
+    pub async fn salvage(&self, source: &Path) -> Result<SalvageReport> {
+        memory_recovery::salvage(&self.pool, source).await
+    }
+
+    /// Close the pool, checkpointing the WAL into the database file
+    pub async fn close(&self) {
+        self.pool.close().await;
+    }
+
+    pub(crate) fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+
     pub async fn create_conversation(&self, title: &str) -> Result<Conversation> {
         let id = Uuid::new_v4();
         let now = Utc::now();
@@ -358,6 +486,52 @@ impl MemoryStore {
         Ok(messages)
     }
 
+    /// Mark a conversation as active now, so it sorts first in listings
+    pub async fn touch_conversation(&self, conversation_id: Uuid) -> Result<()> {
+        sqlx::query("UPDATE conversations SET updated_at = ? WHERE id = ?")
+            .bind(Utc::now().to_rfc3339())
+            .bind(conversation_id.to_string())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Most recently active conversations first
+    pub async fn list_conversations(&self, limit: i32) -> Result<Vec<ConversationSummary>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT c.id, c.title, c.created_at, c.updated_at,
+                (SELECT COUNT(*) FROM messages m WHERE m.conversation_id = c.id) AS message_count,
+                (SELECT m.content FROM messages m
+                    WHERE m.conversation_id = c.id AND m.role = 'user'
+                    ORDER BY m.created_at ASC LIMIT 1) AS first_message
+            FROM conversations c
+            ORDER BY c.updated_at DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut conversations = Vec::new();
+        for row in rows {
+            let created_at: String = row.get("created_at");
+            let updated_at: String = row.get("updated_at");
+            conversations.push(ConversationSummary {
+                id: row.get("id"),
+                title: row.get("title"),
+                message_count: row.get("message_count"),
+                first_message: row.get("first_message"),
+                created_at: DateTime::parse_from_rfc3339(&created_at)?.with_timezone(&Utc),
+                updated_at: DateTime::parse_from_rfc3339(&updated_at)?.with_timezone(&Utc),
+            });
+        }
+
+        Ok(conversations)
+    }
+
     pub async fn store_task(&self, task: &AgentTask) -> Result<()> {
         let task_type_str = format!("{:?}", task.task_type);
         let status_str = format!("{:?}", task.status);
@@ -450,6 +624,190 @@ impl MemoryStore {
         Ok(())
     }
 
+    /// Append an MCP tool invocation to the audit log. Entries are never updated or deleted.
+    pub async fn append_audit_entry(&self, entry: &AuditEntry) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO audit_log (id, timestamp, tool, action, caller, arguments, duration_ms, status, error) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(&entry.id)
+        .bind(entry.timestamp.to_rfc3339())
+        .bind(&entry.tool)
+        .bind(&entry.action)
+        .bind(&entry.caller)
+        .bind(serde_json::to_string(&entry.arguments)?)
+        .bind(entry.duration_ms as i64)
+        .bind(entry.status.to_string())
+        .bind(&entry.error)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Get the most recent audit entries, newest first
+    pub async fn recent_audit_entries(&self, limit: i32) -> Result<Vec<AuditEntry>> {
+        let rows = sqlx::query_as::<_, (String, String, String, Option<String>, Option<String>, String, i64, String, Option<String>)>(
+            "SELECT id, timestamp, tool, action, caller, arguments, duration_ms, status, error FROM audit_log ORDER BY timestamp DESC LIMIT ?"
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            entries.push(AuditEntry {
+                id: row.0,
+                timestamp: DateTime::parse_from_rfc3339(&row.1)?.with_timezone(&Utc),
+                tool: row.2,
+                action: row.3,
+                caller: row.4,
+                arguments: serde_json::from_str(&row.5)?,
+                duration_ms: row.6 as u64,
+                status: row.7.parse::<AuditStatus>().map_err(|e| anyhow::anyhow!(e))?,
+                error: row.8,
+            });
+        }
+
+        Ok(entries)
+    }
+
+    /// Audit entries recorded at or after `since`, oldest first
+    pub async fn audit_entries_since(&self, since: DateTime<Utc>) -> Result<Vec<AuditEntry>> {
+        let rows = sqlx::query_as::<_, (String, String, String, Option<String>, Option<String>, String, i64, String, Option<String>)>(
+            "SELECT id, timestamp, tool, action, caller, arguments, duration_ms, status, error FROM audit_log WHERE timestamp >= ? ORDER BY timestamp ASC"
+        )
+        .bind(since.to_rfc3339())
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            entries.push(AuditEntry {
+                id: row.0,
+                timestamp: DateTime::parse_from_rfc3339(&row.1)?.with_timezone(&Utc),
+                tool: row.2,
+                action: row.3,
+                caller: row.4,
+                arguments: serde_json::from_str(&row.5)?,
+                duration_ms: row.6 as u64,
+                status: row.7.parse::<AuditStatus>().map_err(|e| anyhow::anyhow!(e))?,
+                error: row.8,
+            });
+        }
+
+        Ok(entries)
+    }
+
+    /// Task counts grouped by (task type, status) for tasks created at or after `since`
+    pub async fn task_counts_since(&self, since: DateTime<Utc>) -> Result<Vec<(String, String, i64)>> {
+        let rows = sqlx::query_as::<_, (String, String, i64)>(
+            "SELECT task_type, status, COUNT(*) FROM tasks WHERE created_at >= ? GROUP BY task_type, status ORDER BY task_type"
+        )
+        .bind(since.to_rfc3339())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows)
+    }
+
+    /// Creation times of messages with the given role ("user", "assistant", ...) since `since`
+    pub async fn message_times_since(&self, role: &str, since: DateTime<Utc>) -> Result<Vec<DateTime<Utc>>> {
+        let rows = sqlx::query("SELECT created_at FROM messages WHERE role = ? AND created_at >= ? ORDER BY created_at ASC")
+            .bind(role)
+            .bind(since.to_rfc3339())
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.iter()
+            .map(|r| Ok(DateTime::parse_from_rfc3339(&r.get::<String, _>(0))?.with_timezone(&Utc)))
+            .collect()
+    }
+
+    /// Store health metric samples
+    pub async fn record_metrics(&self, samples: &[MetricSample]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        for sample in samples {
+            insert_metric_point(&mut tx, &StoredPoint::from_sample(sample)).await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Values of `metric` between `start` and `end`, one series per label set,
+    /// summarized per `step`
+    pub async fn query_range(
+        &self,
+        metric: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        step: chrono::Duration,
+    ) -> Result<Vec<MetricSeries>> {
+        let rows = sqlx::query(
+            "SELECT name, labels, timestamp, resolution, value, min, max, samples FROM metrics \
+             WHERE name = ?1 AND timestamp >= ?2 AND timestamp < ?3 ORDER BY timestamp ASC",
+        )
+        .bind(metric)
+        .bind(start.timestamp())
+        .bind(end.timestamp())
+        .fetch_all(&self.pool)
+        .await?;
+
+        let points: Vec<StoredPoint> = rows.iter().map(metric_point_from_row).collect();
+        Ok(metrics::bucket_range(
+            &points,
+            start.timestamp(),
+            step.num_seconds(),
+        ))
+    }
+
+    /// Names of the metrics recorded since `since`
+    pub async fn metric_names_since(&self, since: DateTime<Utc>) -> Result<Vec<String>> {
+        let rows = sqlx::query_as::<_, (String,)>(
+            "SELECT DISTINCT name FROM metrics WHERE timestamp >= ? ORDER BY name",
+        )
+        .bind(since.timestamp())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|(name,)| name).collect())
+    }
+
+    /// Average aging metric points into coarser buckets, see `metrics::ROLLUPS`.
+    /// Returns how many points were replaced.
+    pub async fn downsample_metrics(&self, now: DateTime<Utc>) -> Result<usize> {
+        let mut replaced = 0;
+        for rollup in metrics::ROLLUPS {
+            let cutoff = rollup.cutoff(now.timestamp());
+            let mut tx = self.pool.begin().await?;
+            let rows = sqlx::query(
+                "SELECT name, labels, timestamp, resolution, value, min, max, samples FROM metrics \
+                 WHERE resolution = ?1 AND timestamp < ?2",
+            )
+            .bind(rollup.from)
+            .bind(cutoff)
+            .fetch_all(&mut *tx)
+            .await?;
+            if rows.is_empty() {
+                continue;
+            }
+
+            let points: Vec<StoredPoint> = rows.iter().map(metric_point_from_row).collect();
+            sqlx::query("DELETE FROM metrics WHERE resolution = ?1 AND timestamp < ?2")
+                .bind(rollup.from)
+                .bind(cutoff)
+                .execute(&mut *tx)
+                .await?;
+            for point in metrics::downsample(&points, rollup.to) {
+                insert_metric_point(&mut tx, &point).await?;
+            }
+            tx.commit().await?;
+            replaced += points.len();
+        }
+
+        Ok(replaced)
+    }
+
     /// Enhanced context-aware memory operations
     
     /// Store context entry with automatic relevance scoring
@@ -784,6 +1142,41 @@ impl MemoryStore {
 
 // Implementation for new structs
 
+async fn insert_metric_point(
+    tx: &mut sqlx::Transaction<'_, Sqlite>,
+    point: &StoredPoint,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO metrics (name, labels, timestamp, resolution, value, min, max, samples) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+    )
+    .bind(&point.name)
+    .bind(&point.labels)
+    .bind(point.timestamp)
+    .bind(point.resolution)
+    .bind(point.avg)
+    .bind(point.min)
+    .bind(point.max)
+    .bind(point.count)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+fn metric_point_from_row(row: &sqlx::sqlite::SqliteRow) -> StoredPoint {
+    StoredPoint {
+        name: row.get(0),
+        labels: row.get(1),
+        timestamp: row.get(2),
+        resolution: row.get(3),
+        avg: row.get(4),
+        min: row.get(5),
+        max: row.get(6),
+        count: row.get(7),
+    }
+}
+
 impl ContextManager {
     pub fn new() -> Self {
         Self {
@@ -0,0 +1,668 @@
+//! Opening the memory database when it is damaged or busy
+//!
+//! [`open`] runs `PRAGMA integrity_check` before handing out a pool. A
+//! damaged file is moved aside as `<name>.corrupt-<time>` and replaced with an
+//! empty one, so jarvis keeps working and the old data can still be salvaged
+//! with [`salvage`] (`jarvis memory repair`) or restored from an export
+//! (`jarvis memory import`). A database another process holds locked is
+//! retried a few times, then reported with the processes that have it open.
+
+use crate::pacman_lock::LockHolder;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection, SqlitePoolOptions};
+use sqlx::{ConnectOptions, Connection, Sqlite, SqlitePool};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Rows copied per statement while salvaging
+const SALVAGE_BATCH: i64 = 256;
+/// Rowids probed one by one past an unreadable page before giving up on a
+/// table
+const MAX_PROBES: u32 = 10_000;
+
+// SQLite primary result codes
+const SQLITE_BUSY: i64 = 5;
+const SQLITE_LOCKED: i64 = 6;
+const SQLITE_CORRUPT: i64 = 11;
+const SQLITE_NOTADB: i64 = 26;
+
+/// How hard [`open`] tries before giving up on a locked database
+#[derive(Debug, Clone)]
+pub struct OpenOptions {
+    pub attempts: u32,
+    /// Wait before the second attempt; doubles after each
+    pub retry_delay: Duration,
+    /// How long each attempt waits for the lock
+    pub busy_timeout: Duration,
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            retry_delay: Duration::from_secs(1),
+            busy_timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+/// The database was damaged and replaced with an empty one
+#[derive(Debug, Clone, Serialize)]
+pub struct Recovery {
+    pub path: PathBuf,
+    /// Where the damaged file was moved
+    pub damaged: PathBuf,
+    /// What the integrity check or SQLite reported
+    pub reason: String,
+    /// Newest `jarvis memory export` next to the database
+    pub latest_export: Option<PathBuf>,
+}
+
+impl Recovery {
+    /// What to run to get the old data back
+    pub fn hint(&self) -> String {
+        match &self.latest_export {
+            Some(export) => format!(
+                "restore the latest export with `jarvis memory import {}`, or salvage what \
+                 the damaged copy still holds with `jarvis memory repair`",
+                export.display()
+            ),
+            None => {
+                "salvage what the damaged copy still holds with `jarvis memory repair`".to_string()
+            }
+        }
+    }
+}
+
+impl fmt::Display for Recovery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The memory database {} was damaged ({}) and moved to {}; starting with an empty one. To recover, {}",
+            self.path.display(),
+            self.reason,
+            self.damaged.display(),
+            self.hint()
+        )
+    }
+}
+
+/// Another process kept the database locked through every attempt
+#[derive(Debug, Clone)]
+pub struct DatabaseLocked {
+    pub path: PathBuf,
+    /// Other processes with the file open
+    pub holders: Vec<LockHolder>,
+}
+
+impl fmt::Display for DatabaseLocked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The memory database {} is locked", self.path.display())?;
+        match self.holders.as_slice() {
+            [] => write!(f, " by a process that could not be identified"),
+            holders => {
+                let holders: Vec<String> = holders
+                    .iter()
+                    .map(|h| format!("{} (pid {})", h.command, h.pid))
+                    .collect();
+                write!(f, " by {}", holders.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for DatabaseLocked {}
+
+/// Why an attempt to open the database failed
+enum Failure {
+    Locked,
+    Damaged(String),
+    Other(anyhow::Error),
+}
+
+/// A pool on the database at `path`, creating it if missing and replacing it
+/// with an empty one if it is damaged
+pub async fn open(path: &Path, options: &OpenOptions) -> Result<(SqlitePool, Option<Recovery>)> {
+    let mut recovery = None;
+    let mut delay = options.retry_delay;
+    let mut attempt = 1;
+    loop {
+        match check(path, options.busy_timeout).await {
+            Ok(()) => break,
+            Err(Failure::Damaged(reason)) if recovery.is_none() => {
+                let damaged = move_aside(path).await?;
+                let recovered = Recovery {
+                    path: path.to_path_buf(),
+                    damaged,
+                    reason,
+                    latest_export: latest_export(path),
+                };
+                tracing::warn!("⚠️ {}", recovered);
+                recovery = Some(recovered);
+            }
+            Err(Failure::Damaged(reason)) => {
+                anyhow::bail!(
+                    "The memory database {} is still damaged after recreating it: {}",
+                    path.display(),
+                    reason
+                )
+            }
+            Err(Failure::Locked) if attempt < options.attempts => {
+                tracing::info!(
+                    "Memory database {} is locked, retrying in {:?}",
+                    path.display(),
+                    delay
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(Failure::Locked) => {
+                return Err(DatabaseLocked {
+                    path: path.to_path_buf(),
+                    holders: lock_holders(path),
+                }
+                .into());
+            }
+            Err(Failure::Other(e)) => return Err(e),
+        }
+    }
+
+    let pool = SqlitePoolOptions::new()
+        .connect_with(SqliteConnectOptions::new().filename(path))
+        .await
+        .with_context(|| format!("Failed to open the memory database {}", path.display()))?;
+    Ok((pool, recovery))
+}
+
+/// Open one connection and run the integrity check
+async fn check(path: &Path, busy_timeout: Duration) -> Result<(), Failure> {
+    let mut conn = SqliteConnectOptions::new()
+        .filename(path)
+        .create_if_missing(true)
+        .busy_timeout(busy_timeout)
+        .connect()
+        .await
+        .map_err(classify)?;
+    let verdict = integrity_check(&mut conn).await;
+    let _ = conn.close().await;
+    match verdict {
+        Ok(problems) if problems.is_empty() => Ok(()),
+        Ok(problems) => Err(Failure::Damaged(problems.join("; "))),
+        Err(e) => Err(classify(e)),
+    }
+}
+
+/// What `PRAGMA integrity_check` found wrong; empty when the file is sound
+async fn integrity_check(conn: &mut SqliteConnection) -> Result<Vec<String>, sqlx::Error> {
+    let rows: Vec<String> = sqlx::query_scalar("PRAGMA integrity_check(10)")
+        .fetch_all(&mut *conn)
+        .await?;
+    Ok(rows.into_iter().filter(|row| row != "ok").collect())
+}
+
+fn classify(error: sqlx::Error) -> Failure {
+    match result_code(&error) {
+        Some(SQLITE_BUSY | SQLITE_LOCKED) => Failure::Locked,
+        Some(SQLITE_CORRUPT | SQLITE_NOTADB) => Failure::Damaged(error.to_string()),
+        _ => Failure::Other(error.into()),
+    }
+}
+
+/// The primary SQLite result code of a database error
+fn result_code(error: &sqlx::Error) -> Option<i64> {
+    let code = error.as_database_error()?.code()?;
+    Some(code.parse::<i64>().ok()? & 0xff)
+}
+
+fn is_damage(error: &sqlx::Error) -> bool {
+    matches!(result_code(error), Some(SQLITE_CORRUPT | SQLITE_NOTADB))
+}
+
+/// Rename the database, and its WAL so the pair can still be read together,
+/// to `<name>.corrupt-<time>`; returns the new path
+async fn move_aside(path: &Path) -> Result<PathBuf> {
+    let damaged = sibling(
+        path,
+        &format!(".corrupt-{}", Utc::now().format("%Y%m%dT%H%M%S")),
+    );
+    tokio::fs::rename(path, &damaged).await.with_context(|| {
+        format!(
+            "Failed to move the damaged database {} aside",
+            path.display()
+        )
+    })?;
+    let wal = sibling(path, "-wal");
+    if wal.exists() {
+        tokio::fs::rename(&wal, sibling(&damaged, "-wal")).await?;
+    }
+    let _ = tokio::fs::remove_file(sibling(path, "-shm")).await;
+    Ok(damaged)
+}
+
+/// `path` with `suffix` appended to its file name
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Where `jarvis memory export` writes by default
+pub fn exports_dir(path: &Path) -> PathBuf {
+    path.parent().unwrap_or(Path::new(".")).join("exports")
+}
+
+/// Newest export of the database at `path`
+pub fn latest_export(path: &Path) -> Option<PathBuf> {
+    newest(&exports_dir(path), |name| name.ends_with(".db"))
+}
+
+/// Newest copy [`open`] moved aside from the database at `path`
+pub fn latest_damaged(path: &Path) -> Option<PathBuf> {
+    let prefix = format!("{}.corrupt-", path.file_name()?.to_string_lossy());
+    newest(path.parent()?, |name| {
+        name.starts_with(&prefix) && !name.ends_with("-wal")
+    })
+}
+
+/// The entry of `dir` whose name matches and sorts last; names carry a
+/// timestamp, so that is the newest
+fn newest(dir: &Path, matches: impl Fn(&str) -> bool) -> Option<PathBuf> {
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .is_some_and(|name| matches(&name.to_string_lossy()))
+        })
+        .max()
+}
+
+/// Other processes with the database or its WAL open, found through
+/// `/proc/<pid>/fd`
+pub fn lock_holders(path: &Path) -> Vec<LockHolder> {
+    let own = std::process::id();
+    open_by(path)
+        .into_iter()
+        .filter(|holder| holder.pid != own)
+        .collect()
+}
+
+/// Every process with the database, its WAL, or its shared memory open
+fn open_by(path: &Path) -> Vec<LockHolder> {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let files = [path.clone(), sibling(&path, "-wal"), sibling(&path, "-shm")];
+    let Ok(processes) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+
+    let mut holders = Vec::new();
+    for process in processes.filter_map(|entry| entry.ok()) {
+        let Some(pid) = process
+            .file_name()
+            .to_str()
+            .and_then(|s| s.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        let Ok(fds) = std::fs::read_dir(process.path().join("fd")) else {
+            continue;
+        };
+        let has_open = fds
+            .filter_map(|fd| std::fs::read_link(fd.ok()?.path()).ok())
+            .any(|target| files.contains(&target));
+        if has_open {
+            let command = std::fs::read(process.path().join("cmdline"))
+                .map(|cmdline| {
+                    String::from_utf8_lossy(&cmdline)
+                        .split('\0')
+                        .filter(|part| !part.is_empty())
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .unwrap_or_default();
+            holders.push(LockHolder { pid, command });
+        }
+    }
+    holders
+}
+
+/// Write a consistent copy of the database to `dest`
+pub async fn export(pool: &SqlitePool, dest: &Path) -> Result<()> {
+    if let Some(parent) = dest.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    if dest.exists() {
+        anyhow::bail!("{} already exists", dest.display());
+    }
+    sqlx::query("VACUUM INTO ?")
+        .bind(dest.to_string_lossy().as_ref())
+        .execute(pool)
+        .await
+        .with_context(|| format!("Failed to export the memory database to {}", dest.display()))?;
+    Ok(())
+}
+
+/// What [`salvage`] copied out of one table
+#[derive(Debug, Clone, Serialize)]
+pub struct TableSalvage {
+    pub table: String,
+    /// Rows copied that the target didn't already have
+    pub rows: u64,
+    /// Rowids that could not be read
+    pub unreadable: u64,
+    /// Why the rest of the table was skipped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SalvageReport {
+    pub source: PathBuf,
+    pub tables: Vec<TableSalvage>,
+}
+
+impl SalvageReport {
+    pub fn rows(&self) -> u64 {
+        self.tables.iter().map(|t| t.rows).sum()
+    }
+
+    pub fn unreadable(&self) -> u64 {
+        self.tables.iter().map(|t| t.unreadable).sum()
+    }
+}
+
+/// Copy every row still readable in `source` into the database in `pool`,
+/// keeping rows the target already has. Tables the target lacks are created
+/// from the source's schema. Unreadable pages are stepped over rowid by
+/// rowid, the way the sqlite3 shell's `.recover` does, so one bad page costs
+/// the rows on it rather than the table.
+pub async fn salvage(pool: &SqlitePool, source: &Path) -> Result<SalvageReport> {
+    let mut conn = pool.acquire().await?;
+    sqlx::query("ATTACH DATABASE ? AS salvage")
+        .bind(source.to_string_lossy().as_ref())
+        .execute(&mut *conn)
+        .await
+        .with_context(|| format!("Failed to attach {}", source.display()))?;
+
+    let result = salvage_attached(&mut conn, source).await;
+    let _ = sqlx::query("DETACH DATABASE salvage")
+        .execute(&mut *conn)
+        .await;
+    result
+}
+
+async fn salvage_attached(conn: &mut SqliteConnection, source: &Path) -> Result<SalvageReport> {
+    let schema: Vec<(String, String)> = sqlx::query_as(
+        "SELECT name, sql FROM salvage.sqlite_master \
+         WHERE type = 'table' AND name NOT LIKE 'sqlite_%' AND sql IS NOT NULL",
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .with_context(|| {
+        format!(
+            "The schema of {} is unreadable; nothing can be salvaged from it",
+            source.display()
+        )
+    })?;
+    let existing: Vec<String> =
+        sqlx::query_scalar("SELECT name FROM main.sqlite_master WHERE type = 'table'")
+            .fetch_all(&mut *conn)
+            .await?;
+
+    let mut tables = Vec::new();
+    for (table, sql) in schema {
+        if !existing.contains(&table)
+            && let Err(e) = sqlx::query(&sql).execute(&mut *conn).await
+        {
+            tables.push(TableSalvage {
+                table,
+                rows: 0,
+                unreadable: 0,
+                error: Some(format!("Failed to create the table: {}", e)),
+            });
+            continue;
+        }
+        tables.push(salvage_table(conn, &table).await);
+    }
+    Ok(SalvageReport {
+        source: source.to_path_buf(),
+        tables,
+    })
+}
+
+async fn salvage_table(conn: &mut SqliteConnection, table: &str) -> TableSalvage {
+    let name = format!("\"{}\"", table.replace('"', "\"\""));
+    let mut report = TableSalvage {
+        table: table.to_string(),
+        rows: 0,
+        unreadable: 0,
+        error: None,
+    };
+    let next_rowids = format!(
+        "SELECT rowid FROM salvage.{} WHERE rowid > ? ORDER BY rowid LIMIT {}",
+        name, SALVAGE_BATCH
+    );
+    let copy_range = format!(
+        "INSERT OR IGNORE INTO main.{0} SELECT * FROM salvage.{0} WHERE rowid BETWEEN ? AND ?",
+        name
+    );
+
+    let mut after = i64::MIN;
+    loop {
+        let rowids: Vec<i64> = match sqlx::query_scalar(&next_rowids)
+            .bind(after)
+            .fetch_all(&mut *conn)
+            .await
+        {
+            Ok(rowids) if rowids.is_empty() => break,
+            Ok(rowids) => rowids,
+            Err(e) if is_damage(&e) => match probe_next(conn, &name, after, &mut report).await {
+                Some(rowid) => vec![rowid],
+                None => break,
+            },
+            Err(e) => {
+                report.error = Some(e.to_string());
+                break;
+            }
+        };
+        let (first, last) = (rowids[0], rowids[rowids.len() - 1]);
+
+        match sqlx::query(&copy_range)
+            .bind(first)
+            .bind(last)
+            .execute(&mut *conn)
+            .await
+        {
+            Ok(done) => report.rows += done.rows_affected(),
+            // A row in the range spills onto a bad page; copy them singly
+            Err(e) if is_damage(&e) => {
+                for rowid in rowids {
+                    match sqlx::query(&copy_range)
+                        .bind(rowid)
+                        .bind(rowid)
+                        .execute(&mut *conn)
+                        .await
+                    {
+                        Ok(done) => report.rows += done.rows_affected(),
+                        Err(_) => report.unreadable += 1,
+                    }
+                }
+            }
+            Err(e) => {
+                report.error = Some(e.to_string());
+                break;
+            }
+        }
+        after = last;
+    }
+    report
+}
+
+/// The next readable rowid after `after`, looked up one at a time because
+/// the page holding the rows after it is unreadable
+async fn probe_next(
+    conn: &mut SqliteConnection,
+    name: &str,
+    after: i64,
+    report: &mut TableSalvage,
+) -> Option<i64> {
+    let lookup = format!("SELECT rowid FROM salvage.{} WHERE rowid = ?", name);
+    let mut rowid = after.max(0);
+    for _ in 0..MAX_PROBES {
+        rowid = rowid.checked_add(1)?;
+        match sqlx::query_scalar::<Sqlite, i64>(&lookup)
+            .bind(rowid)
+            .fetch_optional(&mut *conn)
+            .await
+        {
+            Ok(Some(found)) => return Some(found),
+            Ok(None) => {}
+            Err(_) => report.unreadable += 1,
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryStore;
+
+    fn quick() -> OpenOptions {
+        OpenOptions {
+            attempts: 2,
+            retry_delay: Duration::from_millis(50),
+            busy_timeout: Duration::from_millis(50),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_garbage_file_is_moved_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("jarvis.db");
+        std::fs::write(&path, "this is not a database ".repeat(200)).unwrap();
+
+        let memory = MemoryStore::open(path.to_str().unwrap(), &quick())
+            .await
+            .unwrap();
+        let recovery = memory.recovery().unwrap();
+        assert!(recovery.damaged.exists());
+        assert_eq!(latest_damaged(&path).as_ref(), Some(&recovery.damaged));
+        assert!(recovery.hint().contains("jarvis memory repair"));
+
+        memory.store_document("key", "value").await.unwrap();
+        assert_eq!(
+            memory.get_document("key").await.unwrap().as_deref(),
+            Some("value")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_damaged_pages_are_salvaged() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("jarvis.db");
+        let memory = MemoryStore::new(path.to_str().unwrap()).await.unwrap();
+        for i in 0..400 {
+            memory
+                .store_document(&format!("doc-{}", i), &"x".repeat(1000))
+                .await
+                .unwrap();
+        }
+        memory.close().await;
+
+        // Overwrite pages in the middle of the documents table
+        let mut bytes = std::fs::read(&path).unwrap();
+        assert!(bytes.len() > 60 * 4096);
+        for page in 30..40 {
+            bytes[page * 4096..(page + 1) * 4096].fill(0xA5);
+        }
+        std::fs::write(&path, bytes).unwrap();
+
+        let memory = MemoryStore::open(path.to_str().unwrap(), &quick())
+            .await
+            .unwrap();
+        let damaged = memory.recovery().unwrap().damaged.clone();
+        assert!(memory.get_document("doc-0").await.unwrap().is_none());
+
+        let report = salvage(memory.pool(), &damaged).await.unwrap();
+        let documents = report
+            .tables
+            .iter()
+            .find(|t| t.table == "documents")
+            .unwrap();
+        assert!(documents.rows > 200 && documents.rows < 400, "{:?}", report);
+        assert!(documents.unreadable > 0);
+        assert!(memory.get_document("doc-0").await.unwrap().is_some());
+        assert!(memory.get_document("doc-399").await.unwrap().is_some());
+
+        // Salvaging again adds nothing
+        let again = salvage(memory.pool(), &damaged).await.unwrap();
+        assert_eq!(again.rows(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_locked_database_names_the_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("jarvis.db");
+        MemoryStore::new(path.to_str().unwrap())
+            .await
+            .unwrap()
+            .close()
+            .await;
+
+        let mut holder = SqliteConnectOptions::new()
+            .filename(&path)
+            .connect()
+            .await
+            .unwrap();
+        sqlx::query("PRAGMA locking_mode = EXCLUSIVE")
+            .execute(&mut holder)
+            .await
+            .unwrap();
+        sqlx::query("BEGIN EXCLUSIVE")
+            .execute(&mut holder)
+            .await
+            .unwrap();
+
+        let err = open(&path, &quick()).await.unwrap_err();
+        let locked = err.downcast_ref::<DatabaseLocked>().unwrap();
+        assert_eq!(locked.path, path);
+        // This process is the holder, and never lists itself
+        assert!(locked.holders.is_empty());
+        assert!(open_by(&path).iter().any(|h| h.pid == std::process::id()));
+
+        holder.close().await.unwrap();
+        let (_pool, recovery) = open(&path, &quick()).await.unwrap();
+        assert!(recovery.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_export_and_import() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("jarvis.db");
+        let memory = MemoryStore::new(path.to_str().unwrap()).await.unwrap();
+        memory.store_document("kept", "old").await.unwrap();
+
+        let export = exports_dir(&path).join("jarvis-1.db");
+        super::export(memory.pool(), &export).await.unwrap();
+        assert_eq!(latest_export(&path), Some(export.clone()));
+        assert!(super::export(memory.pool(), &export).await.is_err());
+
+        let other = MemoryStore::new(dir.path().join("other.db").to_str().unwrap())
+            .await
+            .unwrap();
+        other.store_document("kept", "new").await.unwrap();
+        let report = salvage(other.pool(), &export).await.unwrap();
+        assert_eq!(report.unreadable(), 0);
+        // Rows the target already has win
+        assert_eq!(
+            other.get_document("kept").await.unwrap().as_deref(),
+            Some("new")
+        );
+    }
+}
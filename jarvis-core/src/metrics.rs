@@ -0,0 +1,438 @@
+//! Time series of health metrics
+//!
+//! Samples are stored raw and thinned as they age: after a day they are
+//! averaged into five-minute buckets, after a week into hourly ones. Every
+//! stored point keeps the min, max, and sample count of what it replaced, so
+//! averages over downsampled data still weigh each original sample equally.
+
+use crate::report::SPARK_BARS;
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use sysinfo::{Disks, System};
+
+pub const FIVE_MINUTES: i64 = 5 * 60;
+pub const HOUR: i64 = 60 * 60;
+
+/// One step of downsampling: points at `from` resolution older than `after`
+/// seconds are merged into `to`-second buckets
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rollup {
+    pub from: i64,
+    pub to: i64,
+    pub after: i64,
+}
+
+impl Rollup {
+    /// Points before this unix time are rolled up. It falls on a bucket
+    /// boundary, so no bucket is merged while samples for it can still arrive.
+    pub fn cutoff(&self, now: i64) -> i64 {
+        (now - self.after).div_euclid(self.to) * self.to
+    }
+}
+
+/// Raw samples become five-minute averages after 24h, those become hourly
+/// averages after 7 days
+pub const ROLLUPS: [Rollup; 2] = [
+    Rollup {
+        from: 0,
+        to: FIVE_MINUTES,
+        after: 24 * HOUR,
+    },
+    Rollup {
+        from: FIVE_MINUTES,
+        to: HOUR,
+        after: 7 * 24 * HOUR,
+    },
+];
+
+/// A single reading, e.g. `disk.used_percent{mount="/"} 71.5`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricSample {
+    pub name: String,
+    pub labels: BTreeMap<String, String>,
+    pub value: f64,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl MetricSample {
+    pub fn new(name: &str, value: f64, timestamp: DateTime<Utc>) -> Self {
+        Self {
+            name: name.to_string(),
+            labels: BTreeMap::new(),
+            value,
+            timestamp,
+        }
+    }
+
+    pub fn with_label(mut self, key: &str, value: &str) -> Self {
+        self.labels.insert(key.to_string(), value.to_string());
+        self
+    }
+}
+
+/// A row of the metrics table: a raw sample, or the summary of a bucket of them
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredPoint {
+    pub name: String,
+    /// Labels as JSON with sorted keys, so equal label sets compare equal
+    pub labels: String,
+    /// Sample time, or the start of the bucket, in unix seconds
+    pub timestamp: i64,
+    /// Bucket width in seconds; 0 for raw samples
+    pub resolution: i64,
+    pub avg: f64,
+    pub min: f64,
+    pub max: f64,
+    pub count: i64,
+}
+
+impl StoredPoint {
+    pub fn from_sample(sample: &MetricSample) -> Self {
+        Self {
+            name: sample.name.clone(),
+            labels: serde_json::to_string(&sample.labels).unwrap_or_else(|_| "{}".to_string()),
+            timestamp: sample.timestamp.timestamp(),
+            resolution: 0,
+            avg: sample.value,
+            min: sample.value,
+            max: sample.value,
+            count: 1,
+        }
+    }
+
+    fn merge(&mut self, other: &StoredPoint) {
+        let count = self.count + other.count;
+        self.avg = (self.avg * self.count as f64 + other.avg * other.count as f64) / count as f64;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.count = count;
+    }
+}
+
+/// Merge points into `bucket`-second buckets aligned to the epoch, one point
+/// per metric, label set, and bucket
+pub fn downsample(points: &[StoredPoint], bucket: i64) -> Vec<StoredPoint> {
+    let mut buckets: BTreeMap<(&str, &str, i64), StoredPoint> = BTreeMap::new();
+    for point in points {
+        let start = point.timestamp.div_euclid(bucket) * bucket;
+        buckets
+            .entry((point.name.as_str(), point.labels.as_str(), start))
+            .and_modify(|merged| merged.merge(point))
+            .or_insert_with(|| StoredPoint {
+                timestamp: start,
+                resolution: bucket,
+                ..point.clone()
+            });
+    }
+    buckets.into_values().collect()
+}
+
+/// Summary of one step of a queried range
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RangePoint {
+    pub timestamp: DateTime<Utc>,
+    pub avg: f64,
+    pub min: f64,
+    pub max: f64,
+    pub count: i64,
+}
+
+/// One label set's values over a queried range
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricSeries {
+    pub name: String,
+    pub labels: BTreeMap<String, String>,
+    /// Steps that have data, oldest first
+    pub points: Vec<RangePoint>,
+}
+
+impl MetricSeries {
+    /// Min, sample-weighted average, and max over the whole range
+    pub fn summary(&self) -> Option<(f64, f64, f64)> {
+        let count: i64 = self.points.iter().map(|p| p.count).sum();
+        if count == 0 {
+            return None;
+        }
+        let sum: f64 = self.points.iter().map(|p| p.avg * p.count as f64).sum();
+        let min = self
+            .points
+            .iter()
+            .map(|p| p.min)
+            .fold(f64::INFINITY, f64::min);
+        let max = self
+            .points
+            .iter()
+            .map(|p| p.max)
+            .fold(f64::NEG_INFINITY, f64::max);
+        Some((min, sum / count as f64, max))
+    }
+
+    /// The series name with its labels, e.g. `disk.used_percent{mount=/home}`
+    pub fn display_name(&self) -> String {
+        if self.labels.is_empty() {
+            return self.name.clone();
+        }
+        let labels: Vec<String> = self
+            .labels
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect();
+        format!("{}{{{}}}", self.name, labels.join(","))
+    }
+}
+
+/// Group stored points by label set into `step`-second steps counted from `start`
+pub fn bucket_range(points: &[StoredPoint], start: i64, step: i64) -> Vec<MetricSeries> {
+    let step = step.max(1);
+    let mut steps: BTreeMap<&str, BTreeMap<i64, StoredPoint>> = BTreeMap::new();
+    for point in points {
+        let index = (point.timestamp - start).div_euclid(step);
+        steps
+            .entry(point.labels.as_str())
+            .or_default()
+            .entry(index)
+            .and_modify(|merged| merged.merge(point))
+            .or_insert_with(|| point.clone());
+    }
+
+    steps
+        .into_iter()
+        .filter_map(|(labels, steps)| {
+            let name = steps.values().next()?.name.clone();
+            Some(MetricSeries {
+                name,
+                labels: serde_json::from_str(labels).unwrap_or_default(),
+                points: steps
+                    .into_iter()
+                    .map(|(index, p)| RangePoint {
+                        timestamp: Utc.timestamp_opt(start + index * step, 0).unwrap(),
+                        avg: p.avg,
+                        min: p.min,
+                        max: p.max,
+                        count: p.count,
+                    })
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+/// `▁▃▅█` style bar scaled between the series' own lowest and highest value,
+/// so a slow creep from 60% to 65% still shows
+pub fn sparkline(values: &[f64]) -> String {
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let span = max - min;
+    values
+        .iter()
+        .map(|v| {
+            if span <= f64::EPSILON {
+                return SPARK_BARS[SPARK_BARS.len() / 2];
+            }
+            let level = ((v - min) / span * (SPARK_BARS.len() - 1) as f64).round() as usize;
+            SPARK_BARS[level.min(SPARK_BARS.len() - 1)]
+        })
+        .collect()
+}
+
+/// Reads CPU, memory, swap, load, and per-filesystem disk usage. CPU usage
+/// is measured between two samples, so the first one has none.
+pub struct HostSampler {
+    system: System,
+    disks: Disks,
+    primed: bool,
+}
+
+impl Default for HostSampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HostSampler {
+    pub fn new() -> Self {
+        Self {
+            system: System::new(),
+            disks: Disks::new_with_refreshed_list(),
+            primed: false,
+        }
+    }
+
+    pub fn sample(&mut self, now: DateTime<Utc>) -> Vec<MetricSample> {
+        self.system.refresh_cpu();
+        self.system.refresh_memory();
+        self.disks.refresh_list();
+
+        let mut samples = Vec::new();
+        if self.primed {
+            samples.push(MetricSample::new(
+                "cpu.usage_percent",
+                self.system.global_cpu_info().cpu_usage() as f64,
+                now,
+            ));
+        }
+        self.primed = true;
+
+        if self.system.total_memory() > 0 {
+            samples.push(MetricSample::new(
+                "memory.used_percent",
+                percent(self.system.used_memory(), self.system.total_memory()),
+                now,
+            ));
+        }
+        if self.system.total_swap() > 0 {
+            samples.push(MetricSample::new(
+                "swap.used_percent",
+                percent(self.system.used_swap(), self.system.total_swap()),
+                now,
+            ));
+        }
+        samples.push(MetricSample::new(
+            "load.1m",
+            System::load_average().one,
+            now,
+        ));
+
+        for disk in self.disks.list() {
+            if disk.total_space() == 0 || disk.is_removable() {
+                continue;
+            }
+            let used = disk.total_space() - disk.available_space();
+            samples.push(
+                MetricSample::new("disk.used_percent", percent(used, disk.total_space()), now)
+                    .with_label("mount", &disk.mount_point().to_string_lossy()),
+            );
+        }
+        samples
+    }
+}
+
+fn percent(used: u64, total: u64) -> f64 {
+    used as f64 / total as f64 * 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(name: &str, labels: &str, timestamp: i64, value: f64) -> StoredPoint {
+        StoredPoint {
+            name: name.to_string(),
+            labels: labels.to_string(),
+            timestamp,
+            resolution: 0,
+            avg: value,
+            min: value,
+            max: value,
+            count: 1,
+        }
+    }
+
+    /// Apply every rollup the way `MemoryStore::downsample_metrics` does
+    fn apply_rollups(mut points: Vec<StoredPoint>, now: i64) -> Vec<StoredPoint> {
+        for rollup in ROLLUPS {
+            let cutoff = rollup.cutoff(now);
+            let (due, mut kept): (Vec<_>, Vec<_>) = points
+                .into_iter()
+                .partition(|p| p.resolution == rollup.from && p.timestamp < cutoff);
+            kept.extend(downsample(&due, rollup.to));
+            points = kept;
+        }
+        points
+    }
+
+    #[test]
+    fn test_downsample_keeps_weighted_average_min_and_max() {
+        // Ten minutes of samples every 30s, values 0..20
+        let points: Vec<_> = (0..20)
+            .map(|i| raw("memory.used_percent", "{}", 3600 + i * 30, i as f64))
+            .collect();
+
+        let five_minute = downsample(&points, FIVE_MINUTES);
+        assert_eq!(five_minute.len(), 2);
+        assert_eq!(five_minute[0].timestamp, 3600);
+        assert_eq!(five_minute[0].resolution, FIVE_MINUTES);
+        assert_eq!(five_minute[0].count, 10);
+        assert_eq!(five_minute[0].avg, 4.5);
+        assert_eq!((five_minute[1].min, five_minute[1].max), (10.0, 19.0));
+
+        // Uneven buckets must not skew the hourly average
+        let mut uneven = five_minute.clone();
+        uneven.push(raw("memory.used_percent", "{}", 4500, 100.0));
+        let hourly = downsample(&downsample(&uneven, FIVE_MINUTES), HOUR);
+        assert_eq!(hourly.len(), 1);
+        assert_eq!(hourly[0].count, 21);
+        assert!((hourly[0].avg - (190.0 + 100.0) / 21.0).abs() < 1e-9);
+        assert_eq!((hourly[0].min, hourly[0].max), (0.0, 100.0));
+    }
+
+    #[test]
+    fn test_rollups_follow_sample_age() {
+        let now = 10 * 24 * HOUR + 17;
+        // One sample a minute for nine days, for two mounts
+        let points: Vec<_> = (0..9 * 24 * 60)
+            .flat_map(|i| {
+                let t = now - i * 60;
+                [
+                    raw("disk.used_percent", r#"{"mount":"/"}"#, t, 50.0),
+                    raw("disk.used_percent", r#"{"mount":"/home"}"#, t, 80.0),
+                ]
+            })
+            .collect();
+        let total = points.len() as i64;
+
+        let rolled = apply_rollups(points, now);
+        assert_eq!(rolled.iter().map(|p| p.count).sum::<i64>(), total);
+
+        let raw_cutoff = ROLLUPS[0].cutoff(now);
+        let hourly_cutoff = ROLLUPS[1].cutoff(now);
+        for point in &rolled {
+            let expected = if point.timestamp >= raw_cutoff {
+                0
+            } else if point.timestamp >= hourly_cutoff {
+                FIVE_MINUTES
+            } else {
+                HOUR
+            };
+            assert_eq!(point.resolution, expected, "point at {}", point.timestamp);
+            if point.resolution > 0 {
+                assert_eq!(point.timestamp % point.resolution, 0);
+            }
+            let value = if point.labels.contains("/home") {
+                80.0
+            } else {
+                50.0
+            };
+            assert_eq!((point.avg, point.min, point.max), (value, value, value));
+        }
+        // Full buckets hold every sample they cover
+        assert!(
+            rolled
+                .iter()
+                .filter(|p| p.resolution == HOUR && p.timestamp > now - 8 * 24 * HOUR)
+                .all(|p| p.count == 60)
+        );
+
+        // Running again changes nothing
+        assert_eq!(apply_rollups(rolled.clone(), now), rolled);
+    }
+
+    #[test]
+    fn test_bucket_range_and_sparkline() {
+        let points = vec![
+            raw("load.1m", "{}", 0, 1.0),
+            raw("load.1m", "{}", 10, 3.0),
+            raw("load.1m", "{}", 130, 2.0),
+        ];
+        let series = bucket_range(&points, 0, 60);
+        assert_eq!(series.len(), 1);
+        assert_eq!(series[0].points.len(), 2);
+        assert_eq!(series[0].points[0].avg, 2.0);
+        assert_eq!(series[0].points[1].timestamp.timestamp(), 120);
+        assert_eq!(series[0].summary(), Some((1.0, 2.0, 3.0)));
+
+        assert_eq!(sparkline(&[60.0, 62.5, 65.0]), "▁▅█");
+        assert_eq!(sparkline(&[3.0, 3.0]), "▅▅");
+    }
+}
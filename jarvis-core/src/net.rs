@@ -0,0 +1,316 @@
+//! Outbound HTTP: proxies and offline mode
+//!
+//! Every reqwest client is built with [`client_builder`] so proxy settings
+//! apply everywhere, and every request goes out through
+//! [`CheckedSend::checked_send`] so offline mode can refuse it before a
+//! connection is attempted. Settings come from `[network]` in the config,
+//! falling back to the usual `HTTP_PROXY`/`HTTPS_PROXY`/`NO_PROXY`
+//! variables; `JARVIS_OFFLINE=1` forces offline mode.
+
+use crate::config::NetworkConfig;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::{LazyLock, RwLock};
+
+/// Hosts that are never proxied and stay reachable offline
+const LOCAL_HOSTS: &[&str] = &["localhost", "127.0.0.1", "::1"];
+
+/// Explicit proxies; unset fields fall back to the environment
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProxyConfig {
+    /// Proxy for plain `http://` requests
+    #[serde(default)]
+    pub http: Option<String>,
+    /// Proxy for `https://` requests
+    #[serde(default)]
+    pub https: Option<String>,
+    /// Hosts or domain suffixes that bypass the proxy; localhost always does
+    #[serde(default)]
+    pub no_proxy: Vec<String>,
+}
+
+/// Effective settings after merging config and environment
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NetSettings {
+    pub http_proxy: Option<String>,
+    pub https_proxy: Option<String>,
+    pub no_proxy: Vec<String>,
+    pub offline: bool,
+}
+
+static SETTINGS: LazyLock<RwLock<NetSettings>> =
+    LazyLock::new(|| RwLock::new(NetSettings::from_env(&ProxyConfig::default(), false)));
+
+/// A request refused because Jarvis is in offline mode
+#[derive(Debug, Clone)]
+pub struct OfflineError {
+    pub url: String,
+}
+
+impl fmt::Display for OfflineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Offline mode: not connecting to {} (unset [network] offline or JARVIS_OFFLINE to allow it)",
+            self.url
+        )
+    }
+}
+
+impl std::error::Error for OfflineError {}
+
+impl NetSettings {
+    /// Config values first, then `HTTPS_PROXY`/`HTTP_PROXY`/`NO_PROXY`
+    /// (either case); `JARVIS_OFFLINE` turns offline mode on
+    pub fn from_env(proxy: &ProxyConfig, offline: bool) -> Self {
+        let no_proxy = if proxy.no_proxy.is_empty() {
+            env_var("NO_PROXY")
+                .map(|list| {
+                    list.split(',')
+                        .map(|h| h.trim().to_string())
+                        .filter(|h| !h.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default()
+        } else {
+            proxy.no_proxy.clone()
+        };
+
+        Self {
+            http_proxy: proxy.http.clone().or_else(|| env_var("HTTP_PROXY")),
+            https_proxy: proxy.https.clone().or_else(|| env_var("HTTPS_PROXY")),
+            no_proxy,
+            offline: offline || env_var("JARVIS_OFFLINE").is_some_and(|v| v != "0"),
+        }
+    }
+
+    /// Refuse `url` in offline mode unless it points at this machine
+    pub fn check(&self, url: &reqwest::Url) -> Result<(), OfflineError> {
+        if !self.offline || url.host_str().is_some_and(is_local_host) {
+            return Ok(());
+        }
+        Err(OfflineError {
+            url: url.to_string(),
+        })
+    }
+
+    /// A builder with these proxies and nothing taken from the environment
+    /// behind its back
+    pub fn client_builder(&self) -> Result<reqwest::ClientBuilder> {
+        let mut builder = reqwest::Client::builder().no_proxy();
+        let bypass = self.bypass_list();
+        if let Some(proxy) = &self.http_proxy {
+            builder = builder.proxy(
+                reqwest::Proxy::http(proxy)?.no_proxy(reqwest::NoProxy::from_string(&bypass)),
+            );
+        }
+        if let Some(proxy) = &self.https_proxy {
+            builder = builder.proxy(
+                reqwest::Proxy::https(proxy)?.no_proxy(reqwest::NoProxy::from_string(&bypass)),
+            );
+        }
+        Ok(builder)
+    }
+
+    fn bypass_list(&self) -> String {
+        LOCAL_HOSTS
+            .iter()
+            .map(|h| h.to_string())
+            .chain(self.no_proxy.iter().cloned())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+/// Apply `[network]` settings process-wide; call after loading the config
+pub fn configure(config: &NetworkConfig) {
+    let settings = NetSettings::from_env(&config.proxy, config.offline);
+    if settings.offline {
+        tracing::info!("Offline mode: only localhost will be contacted");
+    }
+    *SETTINGS.write().unwrap() = settings;
+}
+
+/// Turn offline mode on for this process, e.g. for `--offline`
+pub fn set_offline(offline: bool) {
+    SETTINGS.write().unwrap().offline = offline;
+}
+
+pub fn settings() -> NetSettings {
+    SETTINGS.read().unwrap().clone()
+}
+
+pub fn is_offline() -> bool {
+    SETTINGS.read().unwrap().offline
+}
+
+/// Client builder with the configured proxies; the single place clients come from
+pub fn client_builder() -> reqwest::ClientBuilder {
+    let settings = settings();
+    settings.client_builder().unwrap_or_else(|e| {
+        tracing::warn!("Ignoring invalid proxy setting: {}", e);
+        reqwest::Client::builder()
+    })
+}
+
+/// Client with the configured proxies and default options
+pub fn client() -> reqwest::Client {
+    client_builder().build().unwrap_or_default()
+}
+
+/// Whether `host` (as found in a URL) is this machine
+pub fn is_local_host(host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    host.eq_ignore_ascii_case("localhost")
+        || host.to_ascii_lowercase().ends_with(".localhost")
+        || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name)
+        .or_else(|_| std::env::var(name.to_lowercase()))
+        .ok()
+        .filter(|v| !v.is_empty())
+}
+
+/// Sending that honors offline mode
+#[async_trait]
+pub trait CheckedSend {
+    /// Like `send`, but fails with [`OfflineError`] instead of connecting to
+    /// a remote host while offline
+    async fn checked_send(self) -> Result<reqwest::Response>;
+}
+
+#[async_trait]
+impl CheckedSend for reqwest::RequestBuilder {
+    async fn checked_send(self) -> Result<reqwest::Response> {
+        send_with(&settings(), self).await
+    }
+}
+
+async fn send_with(
+    settings: &NetSettings,
+    request: reqwest::RequestBuilder,
+) -> Result<reqwest::Response> {
+    let (client, request) = request.build_split();
+    let request = request?;
+    settings.check(request.url())?;
+    Ok(client.execute(request).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Accept one connection, answer 200, and return the request line
+    async fn serve_once(listener: TcpListener) -> String {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut buf = vec![0u8; 4096];
+        let n = socket.read(&mut buf).await.unwrap();
+        socket
+            .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        String::from_utf8_lossy(&buf[..n])
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_builder_routes_through_configured_proxy() {
+        let proxy = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let settings = NetSettings {
+            http_proxy: Some(format!("http://{}", proxy.local_addr().unwrap())),
+            ..Default::default()
+        };
+        let seen = tokio::spawn(serve_once(proxy));
+
+        let client = settings.client_builder().unwrap().build().unwrap();
+        let response = client
+            .get("http://aur.archlinux.invalid/rpc/?v=5")
+            .send()
+            .await
+            .unwrap();
+
+        assert!(response.status().is_success());
+        // A proxied request carries the absolute URL
+        assert_eq!(
+            seen.await.unwrap(),
+            "GET http://aur.archlinux.invalid/rpc/?v=5 HTTP/1.1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_localhost_bypasses_proxy() {
+        let ollama = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!(
+            "http://127.0.0.1:{}/api/tags",
+            ollama.local_addr().unwrap().port()
+        );
+        let settings = NetSettings {
+            // Nothing listens here; using it would fail the request
+            http_proxy: Some("http://127.0.0.1:9".to_string()),
+            ..Default::default()
+        };
+        let seen = tokio::spawn(serve_once(ollama));
+
+        let client = settings.client_builder().unwrap().build().unwrap();
+        client.get(&url).send().await.unwrap();
+        assert_eq!(seen.await.unwrap(), "GET /api/tags HTTP/1.1");
+    }
+
+    #[tokio::test]
+    async fn test_offline_refuses_remote_hosts_without_connecting() {
+        let settings = NetSettings {
+            offline: true,
+            ..Default::default()
+        };
+        let remote = reqwest::Url::parse("https://security.archlinux.org/issues.json").unwrap();
+        let err = settings.check(&remote).unwrap_err();
+        assert!(err.to_string().contains("security.archlinux.org"));
+
+        for local in [
+            "http://localhost:11434/api/tags",
+            "http://127.0.0.1:8080/v1",
+            "http://[::1]:11434/",
+        ] {
+            assert!(
+                settings.check(&reqwest::Url::parse(local).unwrap()).is_ok(),
+                "{}",
+                local
+            );
+        }
+
+        // Nothing may reach the network, not even the configured proxy
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let settings = NetSettings {
+            http_proxy: Some(format!("http://{}", listener.local_addr().unwrap())),
+            offline: true,
+            ..Default::default()
+        };
+        let client = settings.client_builder().unwrap().build().unwrap();
+        let result = send_with(&settings, client.get("http://example.invalid/")).await;
+
+        assert!(result.unwrap_err().downcast_ref::<OfflineError>().is_some());
+        let accepted =
+            tokio::time::timeout(std::time::Duration::from_millis(100), listener.accept()).await;
+        assert!(accepted.is_err(), "offline request reached the network");
+    }
+
+    #[test]
+    fn test_local_hosts() {
+        assert!(is_local_host("localhost"));
+        assert!(is_local_host("ollama.localhost"));
+        assert!(is_local_host("127.0.0.53"));
+        assert!(is_local_host("[::1]"));
+        assert!(!is_local_host("aur.archlinux.org"));
+        assert!(!is_local_host("192.168.1.10"));
+    }
+}
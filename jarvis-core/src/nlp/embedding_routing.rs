@@ -0,0 +1,460 @@
+//! Embedding-based routing of queries the rules don't recognise
+//!
+//! The middle tier of [`CommandParser::parse`](super::CommandParser::parse):
+//! every example utterance in `routing_examples.toml` and the user's examples
+//! file is embedded once, and a query goes to the tool and action of its
+//! nearest example when the cosine similarity reaches the threshold. Only the
+//! query is embedded per request, so with a warm model this answers in
+//! milliseconds where the LLM parse takes seconds. Example vectors are cached
+//! on disk per embedding model.
+
+use super::CommandIntent;
+use crate::config::NlpConfig;
+use crate::llm::LLMRouter;
+use crate::llm::context_window::cosine_similarity;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use tokio::sync::OnceCell;
+
+const BUILTIN: &str = include_str!("routing_examples.toml");
+
+/// Marks where the package, container, or host goes in an example utterance
+pub const TARGET: &str = "{target}";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ExamplesFile {
+    #[serde(default)]
+    example: Vec<ExampleSet>,
+}
+
+/// Utterances that all mean one tool action
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExampleSet {
+    pub tool: String,
+    pub action: String,
+    /// Inferred from the tool and action when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub intent: Option<CommandIntent>,
+    pub utterances: Vec<String>,
+}
+
+impl ExampleSet {
+    pub fn intent(&self) -> CommandIntent {
+        self.intent
+            .unwrap_or_else(|| intent_for(&self.tool, &self.action))
+    }
+}
+
+/// The intent a tool action serves
+pub fn intent_for(tool: &str, action: &str) -> CommandIntent {
+    match (tool, action) {
+        ("jarvis_system_status", _) => CommandIntent::SystemStatus,
+        ("jarvis_package_manager", _) => CommandIntent::PackageManagement,
+        ("jarvis_docker", "diagnose") => CommandIntent::Troubleshooting,
+        ("jarvis_docker", action) if action.starts_with("vm-") => CommandIntent::VMManagement,
+        ("jarvis_docker", _) => CommandIntent::DockerManagement,
+        ("jarvis_power", _) => CommandIntent::PowerManagement,
+        _ => CommandIntent::Information,
+    }
+}
+
+/// The examples shipped with Jarvis
+pub fn builtin_examples() -> Vec<ExampleSet> {
+    toml::from_str::<ExamplesFile>(BUILTIN)
+        .expect("built-in routing_examples.toml is valid")
+        .example
+}
+
+/// Examples from a user file; a missing file has none
+pub fn load_examples(path: &Path) -> Result<Vec<ExampleSet>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let file: ExamplesFile = toml::from_str(&content)
+        .with_context(|| format!("Invalid NLP examples in {}", path.display()))?;
+    Ok(file.example)
+}
+
+/// Add `example` to the end of the user file, creating it if needed
+pub fn append_example(path: &Path, example: &ExampleSet) -> Result<()> {
+    let block = toml::to_string(&ExamplesFile {
+        example: vec![example.clone()],
+    })?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    write!(file, "\n{}", block)?;
+    Ok(())
+}
+
+/// Computes embedding vectors; the LLM router's Ollama backend in practice
+#[async_trait]
+pub trait Embedder: Send + Sync {
+    /// Model the vectors come from; cached vectors are only reused for it
+    fn model(&self) -> &str;
+    async fn embed(&self, text: &str) -> Result<Vec<f32>>;
+}
+
+#[async_trait]
+impl Embedder for LLMRouter {
+    fn model(&self) -> &str {
+        self.embedding_model()
+    }
+
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        LLMRouter::embed(self, text).await
+    }
+}
+
+/// The example nearest to a query
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RouteMatch {
+    pub tool: String,
+    pub action: String,
+    pub intent: CommandIntent,
+    pub example: String,
+    pub similarity: f32,
+}
+
+impl RouteMatch {
+    /// The query word in the place of the example's `{target}`
+    pub fn target(&self, query: &str) -> Option<String> {
+        fill_target(&self.example, query)
+    }
+}
+
+struct Embedded {
+    /// Index into `EmbeddingRouter::examples`
+    set: usize,
+    utterance: String,
+    vector: Vec<f32>,
+}
+
+/// Example vectors of one embedding model, by utterance
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct EmbeddingCache {
+    model: String,
+    vectors: HashMap<String, Vec<f32>>,
+}
+
+impl EmbeddingCache {
+    /// The cache at `path` if it holds vectors of `model`, otherwise an empty one
+    fn load(path: &Path, model: &str) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<Self>(&content).ok())
+            .filter(|cache| cache.model == model)
+            .unwrap_or_else(|| Self {
+                model: model.to_string(),
+                vectors: HashMap::new(),
+            })
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+/// Nearest-example routing over a fixed set of examples
+pub struct EmbeddingRouter {
+    examples: Vec<ExampleSet>,
+    threshold: f32,
+    cache_path: Option<PathBuf>,
+    embedded: OnceCell<Vec<Embedded>>,
+}
+
+impl EmbeddingRouter {
+    pub fn new(examples: Vec<ExampleSet>, threshold: f32) -> Self {
+        Self {
+            examples,
+            threshold,
+            cache_path: None,
+            embedded: OnceCell::new(),
+        }
+    }
+
+    /// Built-in examples plus the user's, with vectors cached in
+    /// `~/.cache/jarvis/nlp_embeddings.json`
+    pub fn from_config(config: &NlpConfig) -> Self {
+        let mut examples = builtin_examples();
+        if let Some(path) = config.examples_path() {
+            match load_examples(&path) {
+                Ok(user) => examples.extend(user),
+                Err(e) => tracing::warn!("Ignoring user NLP examples: {:#}", e),
+            }
+        }
+        Self::new(examples, config.similarity_threshold).with_cache(
+            dirs::cache_dir().map(|dir| dir.join("jarvis").join("nlp_embeddings.json")),
+        )
+    }
+
+    /// Keep example vectors in `path` between runs
+    pub fn with_cache(mut self, path: Option<PathBuf>) -> Self {
+        self.cache_path = path;
+        self
+    }
+
+    pub fn threshold(&self) -> f32 {
+        self.threshold
+    }
+
+    pub fn examples(&self) -> &[ExampleSet] {
+        &self.examples
+    }
+
+    /// Embed every example now rather than on the first query
+    pub async fn warm(&self, embedder: &dyn Embedder) -> Result<()> {
+        self.embedded(embedder).await.map(|_| ())
+    }
+
+    /// The example nearest to `query`, whether or not it reaches the
+    /// threshold; `None` when there are no examples
+    pub async fn nearest(&self, embedder: &dyn Embedder, query: &str) -> Result<Option<RouteMatch>> {
+        let embedded = self.embedded(embedder).await?;
+        let vector = embedder.embed(query).await?;
+        let nearest = embedded
+            .iter()
+            .map(|example| (example, cosine_similarity(&vector, &example.vector)))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+
+        Ok(nearest.map(|(example, similarity)| {
+            let set = &self.examples[example.set];
+            RouteMatch {
+                tool: set.tool.clone(),
+                action: set.action.clone(),
+                intent: set.intent(),
+                example: example.utterance.clone(),
+                similarity,
+            }
+        }))
+    }
+
+    /// Whether `route` is close enough to act on
+    pub fn accepts(&self, route: &RouteMatch) -> bool {
+        route.similarity >= self.threshold
+    }
+
+    async fn embedded(&self, embedder: &dyn Embedder) -> Result<&[Embedded]> {
+        self.embedded
+            .get_or_try_init(|| self.embed_examples(embedder))
+            .await
+            .map(|embedded| embedded.as_slice())
+    }
+
+    /// Vectors for every example utterance, embedding only those the cache
+    /// doesn't have
+    async fn embed_examples(&self, embedder: &dyn Embedder) -> Result<Vec<Embedded>> {
+        let mut cache = match &self.cache_path {
+            Some(path) => EmbeddingCache::load(path, embedder.model()),
+            None => EmbeddingCache {
+                model: embedder.model().to_string(),
+                vectors: HashMap::new(),
+            },
+        };
+
+        let mut changed = false;
+        let mut embedded = Vec::new();
+        for (set, example) in self.examples.iter().enumerate() {
+            for utterance in &example.utterances {
+                let vector = match cache.vectors.get(utterance) {
+                    Some(vector) => vector.clone(),
+                    None => {
+                        let vector = embedder
+                            .embed(utterance)
+                            .await
+                            .with_context(|| format!("Failed to embed example '{}'", utterance))?;
+                        cache.vectors.insert(utterance.clone(), vector.clone());
+                        changed = true;
+                        vector
+                    }
+                };
+                embedded.push(Embedded {
+                    set,
+                    utterance: utterance.clone(),
+                    vector,
+                });
+            }
+        }
+
+        // Utterances that were edited or removed don't stay in the cache
+        let current: HashSet<&str> = embedded.iter().map(|e| e.utterance.as_str()).collect();
+        let before = cache.vectors.len();
+        cache.vectors.retain(|utterance, _| current.contains(utterance.as_str()));
+        changed |= cache.vectors.len() != before;
+
+        if changed
+            && let Some(path) = &self.cache_path
+            && let Err(e) = cache.save(path)
+        {
+            tracing::warn!("Failed to cache NLP example embeddings: {}", e);
+        }
+        Ok(embedded)
+    }
+}
+
+/// Lowercase words with surrounding punctuation removed
+fn words(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .map(|word| {
+            word.trim_matches(|c: char| !(c.is_alphanumeric() || c == '{' || c == '}'))
+                .to_lowercase()
+        })
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// The query word in the place of `{target}` in `template`: the word after
+/// the template word just before the placeholder, or failing that the word
+/// before the template word just after it
+fn fill_target(template: &str, query: &str) -> Option<String> {
+    let template = words(template);
+    let query = words(query);
+    let at = template.iter().position(|word| word == TARGET)?;
+
+    let after_anchor = at
+        .checked_sub(1)
+        .and_then(|i| query.iter().position(|word| *word == template[i]))
+        .and_then(|i| query.get(i + 1));
+    let before_anchor = template
+        .get(at + 1)
+        .and_then(|anchor| query.iter().position(|word| word == anchor))
+        .and_then(|i| i.checked_sub(1))
+        .and_then(|i| query.get(i));
+    after_anchor.or(before_anchor).cloned()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::hash::{DefaultHasher, Hash, Hasher};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const DIMENSIONS: usize = 256;
+
+    /// Bag-of-words vectors: queries sharing more words are more similar
+    #[derive(Default)]
+    pub(crate) struct WordEmbedder {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Embedder for WordEmbedder {
+        fn model(&self) -> &str {
+            "words"
+        }
+
+        async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let mut vector = vec![0.0; DIMENSIONS];
+            for word in words(text) {
+                let mut hasher = DefaultHasher::new();
+                word.hash(&mut hasher);
+                vector[hasher.finish() as usize % DIMENSIONS] += 1.0;
+            }
+            Ok(vector)
+        }
+    }
+
+    #[test]
+    fn test_builtin_examples_parse() {
+        let examples = builtin_examples();
+        assert!(examples.len() > 10);
+        for example in &examples {
+            assert!(!example.utterances.is_empty(), "{}:{}", example.tool, example.action);
+            assert!(
+                crate::mcp::BUILTIN_TOOLS.iter().any(|(name, _)| *name == example.tool),
+                "{}",
+                example.tool
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_nearest_example_routes_a_paraphrase() {
+        let router = EmbeddingRouter::new(builtin_examples(), 0.6);
+        let embedder = WordEmbedder::default();
+
+        let route = router
+            .nearest(&embedder, "show me the output of grafana")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!((route.tool.as_str(), route.action.as_str()), ("jarvis_docker", "logs"));
+        assert_eq!(route.intent, CommandIntent::DockerManagement);
+        assert!(router.accepts(&route));
+        assert_eq!(route.target("show me the output of grafana").as_deref(), Some("grafana"));
+
+        let unrelated = router
+            .nearest(&embedder, "bake a chocolate cake")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!router.accepts(&unrelated), "{:?}", unrelated);
+    }
+
+    #[tokio::test]
+    async fn test_example_vectors_are_cached_per_model() {
+        let cache = std::env::temp_dir().join(format!("jarvis-nlp-{}.json", uuid::Uuid::new_v4()));
+        let examples = builtin_examples();
+        let utterances: usize = examples.iter().map(|e| e.utterances.len()).sum();
+
+        let first = WordEmbedder::default();
+        EmbeddingRouter::new(examples.clone(), 0.6)
+            .with_cache(Some(cache.clone()))
+            .warm(&first)
+            .await
+            .unwrap();
+        assert_eq!(first.calls.load(Ordering::SeqCst), utterances);
+
+        // A new process only embeds the query
+        let second = WordEmbedder::default();
+        let router = EmbeddingRouter::new(examples, 0.6).with_cache(Some(cache.clone()));
+        router.nearest(&second, "uninstall nano").await.unwrap();
+        assert_eq!(second.calls.load(Ordering::SeqCst), 1);
+
+        std::fs::remove_file(cache).unwrap();
+    }
+
+    #[test]
+    fn test_fill_target() {
+        assert_eq!(fill_target("put {target} on this machine", "could you put neovim on this machine?").as_deref(), Some("neovim"));
+        assert_eq!(fill_target("{target} keeps restarting", "my nextcloud keeps restarting").as_deref(), Some("nextcloud"));
+        assert_eq!(fill_target("{target} keeps restarting", "it is broken"), None);
+        assert_eq!(fill_target("upgrade everything", "upgrade everything"), None);
+    }
+
+    #[test]
+    fn test_appended_examples_load_back() {
+        let path = std::env::temp_dir().join(format!("jarvis-nlp-{}.toml", uuid::Uuid::new_v4()));
+        let correction = ExampleSet {
+            tool: "jarvis_docker".to_string(),
+            action: "diagnose".to_string(),
+            intent: None,
+            utterances: vec!["grafana is acting weird".to_string()],
+        };
+        append_example(&path, &correction).unwrap();
+        append_example(&path, &ExampleSet { action: "logs".to_string(), ..correction.clone() }).unwrap();
+
+        let loaded = load_examples(&path).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0], correction);
+        assert_eq!(loaded[0].intent(), CommandIntent::Troubleshooting);
+        assert_eq!(loaded[1].action, "logs");
+        std::fs::remove_file(path).unwrap();
+    }
+}
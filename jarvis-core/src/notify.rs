@@ -0,0 +1,453 @@
+//! Notifications for agent events
+//!
+//! Maintenance results, health transitions, and update availability are
+//! pushed to the configured backends (desktop, ntfy, Gotify, webhook).
+//! Delivery happens on a background task fed by a bounded queue, so the
+//! operation that raised an event never waits on a slow or dead endpoint.
+
+use crate::config::NotificationConfig;
+use crate::net::CheckedSend;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifySeverity {
+    Info,
+    Warning,
+    Critical,
+}
+
+/// What kind of event a notification reports; used for per-event filtering
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifyEvent {
+    MaintenanceFinished,
+    MaintenanceFailed,
+    HealthChanged,
+    UpdatesAvailable,
+    OperationCompleted,
+    ReportReady,
+    /// A remediation command from the Wazuh manager ran
+    ActiveResponse,
+    /// A security scan found new findings or saw old ones resolved
+    SecurityFindings,
+    /// A correlated incident opened, grew, or resolved
+    Incident,
+    Test,
+}
+
+impl NotifyEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotifyEvent::MaintenanceFinished => "maintenance_finished",
+            NotifyEvent::MaintenanceFailed => "maintenance_failed",
+            NotifyEvent::HealthChanged => "health_changed",
+            NotifyEvent::UpdatesAvailable => "updates_available",
+            NotifyEvent::OperationCompleted => "operation_completed",
+            NotifyEvent::ReportReady => "report_ready",
+            NotifyEvent::ActiveResponse => "active_response",
+            NotifyEvent::SecurityFindings => "security_findings",
+            NotifyEvent::Incident => "incident",
+            NotifyEvent::Test => "test",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    pub event: NotifyEvent,
+    pub severity: NotifySeverity,
+    pub title: String,
+    pub body: String,
+    pub host: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl Notification {
+    pub fn new(event: NotifyEvent, severity: NotifySeverity, title: impl Into<String>, body: impl Into<String>) -> Self {
+        Self {
+            event,
+            severity,
+            title: title.into(),
+            body: body.into(),
+            host: hostname(),
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+#[async_trait]
+pub trait NotifyBackend: Send + Sync {
+    fn name(&self) -> &str;
+    /// Lowest severity this backend delivers
+    fn min_severity(&self) -> NotifySeverity;
+    async fn send(&self, notification: &Notification) -> Result<()>;
+}
+
+/// Desktop notifications over DBus, only when a user session bus is present
+pub struct DesktopBackend {
+    min_severity: NotifySeverity,
+}
+
+#[async_trait]
+impl NotifyBackend for DesktopBackend {
+    fn name(&self) -> &str {
+        "desktop"
+    }
+
+    fn min_severity(&self) -> NotifySeverity {
+        self.min_severity
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<()> {
+        if std::env::var_os("DBUS_SESSION_BUS_ADDRESS").is_none() {
+            return Err(anyhow::anyhow!("No desktop session bus available"));
+        }
+
+        let urgency = match notification.severity {
+            NotifySeverity::Info => notify_rust::Urgency::Low,
+            NotifySeverity::Warning => notify_rust::Urgency::Normal,
+            NotifySeverity::Critical => notify_rust::Urgency::Critical,
+        };
+        let title = notification.title.clone();
+        let body = notification.body.clone();
+
+        // notify-rust talks to DBus synchronously
+        tokio::task::spawn_blocking(move || {
+            notify_rust::Notification::new()
+                .appname("Jarvis")
+                .summary(&title)
+                .body(&body)
+                .urgency(urgency)
+                .show()
+                .map(|_| ())
+        })
+        .await?
+        .context("Failed to show desktop notification")
+    }
+}
+
+/// ntfy.sh or a self-hosted ntfy server
+pub struct NtfyBackend {
+    client: reqwest::Client,
+    url: String,
+    topic: String,
+    token: Option<String>,
+    min_severity: NotifySeverity,
+}
+
+#[async_trait]
+impl NotifyBackend for NtfyBackend {
+    fn name(&self) -> &str {
+        "ntfy"
+    }
+
+    fn min_severity(&self) -> NotifySeverity {
+        self.min_severity
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<()> {
+        let priority = match notification.severity {
+            NotifySeverity::Info => 2,
+            NotifySeverity::Warning => 4,
+            NotifySeverity::Critical => 5,
+        };
+        let payload = serde_json::json!({
+            "topic": self.topic,
+            "title": notification.title,
+            "message": notification.body,
+            "priority": priority,
+            "tags": [notification.event.as_str()],
+        });
+
+        let mut request = self.client.post(self.url.trim_end_matches('/')).json(&payload);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        request.checked_send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Gotify push server
+pub struct GotifyBackend {
+    client: reqwest::Client,
+    url: String,
+    token: String,
+    min_severity: NotifySeverity,
+}
+
+#[async_trait]
+impl NotifyBackend for GotifyBackend {
+    fn name(&self) -> &str {
+        "gotify"
+    }
+
+    fn min_severity(&self) -> NotifySeverity {
+        self.min_severity
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<()> {
+        let priority = match notification.severity {
+            NotifySeverity::Info => 2,
+            NotifySeverity::Warning => 5,
+            NotifySeverity::Critical => 8,
+        };
+        let payload = serde_json::json!({
+            "title": notification.title,
+            "message": notification.body,
+            "priority": priority,
+        });
+
+        self.client
+            .post(format!("{}/message", self.url.trim_end_matches('/')))
+            .header("X-Gotify-Key", &self.token)
+            .json(&payload)
+            .checked_send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Generic JSON webhook; the body is the serialized `Notification`
+pub struct WebhookBackend {
+    client: reqwest::Client,
+    url: String,
+    min_severity: NotifySeverity,
+}
+
+#[async_trait]
+impl NotifyBackend for WebhookBackend {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    fn min_severity(&self) -> NotifySeverity {
+        self.min_severity
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<()> {
+        self.client
+            .post(&self.url)
+            .json(notification)
+            .checked_send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Fan-out to every backend that accepts a notification's severity
+#[derive(Clone)]
+pub struct Notifier {
+    tx: Option<mpsc::Sender<Notification>>,
+    dispatch: Arc<Dispatch>,
+}
+
+struct Dispatch {
+    backends: Vec<Box<dyn NotifyBackend>>,
+    /// Minimum severity per event type; events not listed are always eligible
+    event_filters: HashMap<NotifyEvent, NotifySeverity>,
+}
+
+impl Dispatch {
+    fn accepts(&self, notification: &Notification) -> bool {
+        self.event_filters
+            .get(&notification.event)
+            .is_none_or(|min| notification.severity >= *min)
+    }
+
+    async fn deliver(&self, notification: &Notification) -> Vec<(String, Result<()>)> {
+        let mut results = Vec::new();
+        for backend in &self.backends {
+            if notification.severity < backend.min_severity() {
+                continue;
+            }
+            let result = backend.send(notification).await;
+            if let Err(e) = &result {
+                tracing::warn!("{} notification failed: {}", backend.name(), e);
+            }
+            results.push((backend.name().to_string(), result));
+        }
+        results
+    }
+}
+
+impl Notifier {
+    /// Build the backends from config and start the delivery task. Must be
+    /// called from within a tokio runtime.
+    pub fn from_config(config: &NotificationConfig) -> Self {
+        let dispatch = Arc::new(Dispatch {
+            backends: if config.enabled { build_backends(config) } else { Vec::new() },
+            event_filters: config.events.clone(),
+        });
+
+        if dispatch.backends.is_empty() {
+            return Self { tx: None, dispatch };
+        }
+
+        let (tx, mut rx) = mpsc::channel::<Notification>(config.queue_size.max(1));
+        let worker = dispatch.clone();
+        tokio::spawn(async move {
+            while let Some(notification) = rx.recv().await {
+                worker.deliver(&notification).await;
+            }
+        });
+
+        Self { tx: Some(tx), dispatch }
+    }
+
+    /// A notifier that drops everything
+    pub fn disabled() -> Self {
+        Self {
+            tx: None,
+            dispatch: Arc::new(Dispatch {
+                backends: Vec::new(),
+                event_filters: HashMap::new(),
+            }),
+        }
+    }
+
+    pub fn backend_names(&self) -> Vec<&str> {
+        self.dispatch.backends.iter().map(|b| b.name()).collect()
+    }
+
+    /// Queue a notification without waiting for delivery. When the queue is
+    /// full the notification is dropped rather than blocking the caller.
+    pub fn notify(&self, notification: Notification) {
+        let Some(tx) = &self.tx else {
+            return;
+        };
+        if !self.dispatch.accepts(&notification) {
+            return;
+        }
+        if let Err(e) = tx.try_send(notification) {
+            tracing::warn!("Dropping notification: {}", e);
+        }
+    }
+
+    /// Deliver immediately and report each backend's result; for `jarvis notify test`
+    pub async fn send_now(&self, notification: &Notification) -> Vec<(String, Result<()>)> {
+        self.dispatch.deliver(notification).await
+    }
+}
+
+fn build_backends(config: &NotificationConfig) -> Vec<Box<dyn NotifyBackend>> {
+    let client = crate::net::client_builder()
+        .timeout(std::time::Duration::from_secs(10))
+        .build()
+        .unwrap_or_default();
+    let mut backends: Vec<Box<dyn NotifyBackend>> = Vec::new();
+
+    if config.desktop.enabled {
+        backends.push(Box::new(DesktopBackend {
+            min_severity: config.desktop.min_severity,
+        }));
+    }
+    if let Some(ntfy) = &config.ntfy {
+        backends.push(Box::new(NtfyBackend {
+            client: client.clone(),
+            url: ntfy.url.clone(),
+            topic: ntfy.topic.clone(),
+            token: ntfy.token.clone(),
+            min_severity: ntfy.min_severity,
+        }));
+    }
+    if let Some(gotify) = &config.gotify {
+        backends.push(Box::new(GotifyBackend {
+            client: client.clone(),
+            url: gotify.url.clone(),
+            token: gotify.token.clone(),
+            min_severity: gotify.min_severity,
+        }));
+    }
+    if let Some(webhook) = &config.webhook {
+        backends.push(Box::new(WebhookBackend {
+            client,
+            url: webhook.url.clone(),
+            min_severity: webhook.min_severity,
+        }));
+    }
+
+    backends
+}
+
+/// Tracks the last severity per component so only transitions into Warning
+/// or Critical produce a notification, not every check that stays bad
+#[derive(Debug, Default)]
+pub struct HealthTransitions {
+    last: HashMap<String, NotifySeverity>,
+}
+
+impl HealthTransitions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record `severity` for `component`; returns the previous severity when
+    /// this is a transition that should be reported
+    pub fn observe(&mut self, component: &str, severity: NotifySeverity) -> Option<NotifySeverity> {
+        self.change(component, severity)
+            .filter(|_| severity > NotifySeverity::Info)
+    }
+
+    /// Like `observe`, but recoveries to Info count as transitions too
+    pub fn change(&mut self, component: &str, severity: NotifySeverity) -> Option<NotifySeverity> {
+        let previous = self
+            .last
+            .insert(component.to_string(), severity)
+            .unwrap_or(NotifySeverity::Info);
+
+        (severity != previous).then_some(previous)
+    }
+}
+
+fn hostname() -> Option<String> {
+    std::fs::read_to_string("/etc/hostname")
+        .ok()
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_health_transitions_deduplicate() {
+        let mut transitions = HealthTransitions::new();
+
+        assert_eq!(transitions.observe("disk", NotifySeverity::Info), None);
+        assert_eq!(transitions.observe("disk", NotifySeverity::Warning), Some(NotifySeverity::Info));
+        assert_eq!(transitions.observe("disk", NotifySeverity::Warning), None);
+        assert_eq!(transitions.observe("disk", NotifySeverity::Critical), Some(NotifySeverity::Warning));
+        // Recovery is not reported, but re-entering Warning is
+        assert_eq!(transitions.observe("disk", NotifySeverity::Info), None);
+        assert_eq!(transitions.observe("disk", NotifySeverity::Warning), Some(NotifySeverity::Info));
+
+        assert_eq!(transitions.change("disk", NotifySeverity::Info), Some(NotifySeverity::Warning));
+        assert_eq!(transitions.change("disk", NotifySeverity::Info), None);
+    }
+
+    #[test]
+    fn test_event_filters() {
+        let mut event_filters = HashMap::new();
+        event_filters.insert(NotifyEvent::MaintenanceFinished, NotifySeverity::Warning);
+        let dispatch = Dispatch {
+            backends: Vec::new(),
+            event_filters,
+        };
+
+        let routine = Notification::new(NotifyEvent::MaintenanceFinished, NotifySeverity::Info, "Cleanup", "done");
+        let failed = Notification::new(NotifyEvent::MaintenanceFailed, NotifySeverity::Info, "Update", "failed");
+        assert!(!dispatch.accepts(&routine));
+        assert!(dispatch.accepts(&failed));
+    }
+}
@@ -0,0 +1,147 @@
+//! Registry of long-running operations in progress
+//!
+//! Operations that take minutes or hours (scrubs, balances, large upgrades)
+//! register here so status commands can show what is running and how far
+//! along it is. Registration is tied to a guard: when the operation finishes,
+//! however it finishes, the guard drops and the entry disappears.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+/// A snapshot of one running operation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperationProgress {
+    pub id: Uuid,
+    /// What kind of work this is, e.g. "btrfs scrub"
+    pub kind: String,
+    /// What it is working on, e.g. a mount point
+    pub target: String,
+    pub started_at: DateTime<Utc>,
+    /// Percent complete, when the operation can tell
+    pub percent: Option<f64>,
+    pub message: Option<String>,
+    pub cancellable: bool,
+    pub cancel_requested: bool,
+}
+
+/// Shared between everything that starts or observes operations
+#[derive(Debug, Clone, Default)]
+pub struct ActiveOperations {
+    entries: Arc<Mutex<HashMap<Uuid, OperationProgress>>>,
+}
+
+impl ActiveOperations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register an operation; it stays listed until the guard drops
+    pub fn start(&self, kind: &str, target: &str, cancellable: bool) -> OperationGuard {
+        let id = Uuid::new_v4();
+        self.entries.lock().unwrap().insert(
+            id,
+            OperationProgress {
+                id,
+                kind: kind.to_string(),
+                target: target.to_string(),
+                started_at: Utc::now(),
+                percent: None,
+                message: None,
+                cancellable,
+                cancel_requested: false,
+            },
+        );
+        OperationGuard {
+            id,
+            operations: self.clone(),
+        }
+    }
+
+    /// Running operations, oldest first
+    pub fn list(&self) -> Vec<OperationProgress> {
+        let mut list: Vec<_> = self.entries.lock().unwrap().values().cloned().collect();
+        list.sort_by_key(|op| op.started_at);
+        list
+    }
+
+    /// Ask a cancellable operation to stop. The operation notices on its next
+    /// progress check; returns false when `id` is unknown or not cancellable.
+    pub fn request_cancel(&self, id: Uuid) -> bool {
+        match self.entries.lock().unwrap().get_mut(&id) {
+            Some(op) if op.cancellable => {
+                op.cancel_requested = true;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Held by the running operation; dropping it unregisters the operation
+#[derive(Debug)]
+pub struct OperationGuard {
+    id: Uuid,
+    operations: ActiveOperations,
+}
+
+impl OperationGuard {
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    pub fn set_progress(&self, percent: Option<f64>, message: Option<String>) {
+        if let Some(op) = self.operations.entries.lock().unwrap().get_mut(&self.id) {
+            op.percent = percent;
+            op.message = message;
+        }
+    }
+
+    pub fn cancel_requested(&self) -> bool {
+        self.operations
+            .entries
+            .lock()
+            .unwrap()
+            .get(&self.id)
+            .is_some_and(|op| op.cancel_requested)
+    }
+}
+
+impl Drop for OperationGuard {
+    fn drop(&mut self) {
+        self.operations.entries.lock().unwrap().remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guard_tracks_progress_and_cancellation() {
+        let operations = ActiveOperations::new();
+        let guard = operations.start("btrfs scrub", "/", true);
+        guard.set_progress(Some(42.0), Some("12 GiB scrubbed".to_string()));
+
+        let listed = operations.list();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].percent, Some(42.0));
+
+        assert!(!guard.cancel_requested());
+        assert!(operations.request_cancel(guard.id()));
+        assert!(guard.cancel_requested());
+
+        drop(guard);
+        assert!(operations.list().is_empty());
+    }
+
+    #[test]
+    fn test_uncancellable_operations_refuse_cancel() {
+        let operations = ActiveOperations::new();
+        let guard = operations.start("pacman upgrade", "system", false);
+        assert!(!operations.request_cancel(guard.id()));
+        assert!(!operations.request_cancel(Uuid::new_v4()));
+    }
+}
@@ -0,0 +1,382 @@
+//! In-process cache of the local package database
+//!
+//! Scanners, pre-flight, and history correlation all ask which packages are
+//! installed, at which version, since when, and whether they came from a
+//! repository. Spawning pacman and re-parsing thousands of lines for each of
+//! those questions adds seconds to composite operations, so the local
+//! database under `/var/lib/pacman/local` is read once, one `desc` file per
+//! package, and indexed by name. Whether a package is foreign takes the sync
+//! databases, which are compressed archives, so that comes from a single
+//! `pacman -Qmq`.
+//!
+//! Every transaction appends to `pacman.log`, so the index is reloaded when
+//! the log changes size or modification time. Our own transactions call
+//! [`PackageCache::invalidate`] as well, in case both land within the
+//! filesystem's timestamp resolution.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock};
+use std::time::SystemTime;
+use tokio::sync::Mutex;
+
+use crate::exec::{CommandRunner, SystemRunner};
+
+pub const LOCAL_DB_DIR: &str = "/var/lib/pacman/local";
+pub const PACMAN_LOG: &str = "/var/log/pacman.log";
+
+/// One installed package, as recorded in its local database `desc` file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstalledPackage {
+    pub name: String,
+    pub version: String,
+    pub description: Option<String>,
+    pub url: Option<String>,
+    pub packager: Option<String>,
+    pub build_date: Option<DateTime<Utc>>,
+    /// When the package was last installed or upgraded
+    pub install_date: Option<DateTime<Utc>>,
+    /// Bytes on disk
+    pub installed_size: u64,
+    /// Installed on request rather than as a dependency
+    pub explicit: bool,
+    /// In no configured repository: built from the AUR or locally
+    pub foreign: bool,
+}
+
+/// Installed packages by name
+#[derive(Debug, Clone, Default)]
+pub struct PackageIndex {
+    packages: HashMap<String, InstalledPackage>,
+}
+
+impl PackageIndex {
+    pub fn from_packages(packages: impl IntoIterator<Item = InstalledPackage>) -> Self {
+        Self {
+            packages: packages
+                .into_iter()
+                .map(|package| (package.name.clone(), package))
+                .collect(),
+        }
+    }
+
+    /// Read every package's `desc` under a local database directory.
+    /// Entries that aren't package directories, such as `ALPM_DB_VERSION`,
+    /// are skipped.
+    pub fn load(local_db: &Path) -> Result<Self> {
+        let entries = std::fs::read_dir(local_db)
+            .with_context(|| format!("Failed to read {}", local_db.display()))?;
+        let mut packages = HashMap::new();
+        for entry in entries {
+            let desc = entry?.path().join("desc");
+            let Ok(text) = std::fs::read_to_string(&desc) else {
+                continue;
+            };
+            match parse_desc(&text) {
+                Some(package) => {
+                    packages.insert(package.name.clone(), package);
+                }
+                None => tracing::warn!("Skipping unreadable {}", desc.display()),
+            }
+        }
+        Ok(Self { packages })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&InstalledPackage> {
+        self.packages.get(name)
+    }
+
+    pub fn version(&self, name: &str) -> Option<&str> {
+        self.get(name).map(|package| package.version.as_str())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.packages.contains_key(name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.packages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.packages.is_empty()
+    }
+
+    /// All packages, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = &InstalledPackage> {
+        self.packages.values()
+    }
+
+    /// Name -> version of every package, like `pacman -Q`
+    pub fn versions(&self) -> BTreeMap<String, String> {
+        self.iter()
+            .map(|package| (package.name.clone(), package.version.clone()))
+            .collect()
+    }
+
+    /// Packages in no repository, by name, like `pacman -Qm`
+    pub fn foreign(&self) -> Vec<&InstalledPackage> {
+        let mut foreign: Vec<_> = self.iter().filter(|package| package.foreign).collect();
+        foreign.sort_by(|a, b| a.name.cmp(&b.name));
+        foreign
+    }
+
+    /// Packages installed or upgraded at or after `since`, oldest first
+    pub fn installed_since(&self, since: DateTime<Utc>) -> Vec<&InstalledPackage> {
+        let mut recent: Vec<_> = self
+            .iter()
+            .filter(|package| package.install_date.is_some_and(|at| at >= since))
+            .collect();
+        recent.sort_by_key(|package| package.install_date);
+        recent
+    }
+
+    fn mark_foreign<'a>(&mut self, names: impl IntoIterator<Item = &'a str>) {
+        for name in names {
+            if let Some(package) = self.packages.get_mut(name) {
+                package.foreign = true;
+            }
+        }
+    }
+}
+
+/// Parse a local database `desc` file: `%FIELD%` headers, each followed by
+/// its values one per line and a blank line. `%REASON%` is 1 for
+/// dependencies and absent for explicitly installed packages.
+pub fn parse_desc(text: &str) -> Option<InstalledPackage> {
+    let mut fields: HashMap<&str, &str> = HashMap::new();
+    let mut lines = text.lines();
+    while let Some(line) = lines.next() {
+        let Some(field) = line.strip_prefix('%').and_then(|l| l.strip_suffix('%')) else {
+            continue;
+        };
+        if let Some(value) = lines.next().filter(|value| !value.is_empty()) {
+            fields.insert(field, value);
+        }
+    }
+    let date = |field: &str| {
+        fields
+            .get(field)
+            .and_then(|secs| secs.parse().ok())
+            .and_then(|secs| DateTime::from_timestamp(secs, 0))
+    };
+    let text = |field: &str| fields.get(field).map(|value| value.to_string());
+
+    Some(InstalledPackage {
+        name: text("NAME")?,
+        version: text("VERSION")?,
+        description: text("DESC"),
+        url: text("URL"),
+        packager: text("PACKAGER"),
+        build_date: date("BUILDDATE"),
+        install_date: date("INSTALLDATE"),
+        installed_size: fields
+            .get("SIZE")
+            .and_then(|size| size.parse().ok())
+            .unwrap_or(0),
+        explicit: fields.get("REASON") != Some(&"1"),
+        foreign: false,
+    })
+}
+
+/// Size and modification time of `pacman.log`, which every transaction changes
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct LogStamp {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl LogStamp {
+    async fn read(log: &Path) -> Option<Self> {
+        let metadata = tokio::fs::metadata(log).await.ok()?;
+        Some(Self {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+}
+
+static SHARED: LazyLock<Arc<PackageCache>> = LazyLock::new(|| Arc::new(PackageCache::new()));
+
+/// The local package database, loaded on first use and reloaded after
+/// transactions
+#[derive(Debug)]
+pub struct PackageCache {
+    local_db: PathBuf,
+    log: PathBuf,
+    runner: Arc<dyn CommandRunner>,
+    loaded: Mutex<Option<(Option<LogStamp>, Arc<PackageIndex>)>>,
+}
+
+impl Default for PackageCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PackageCache {
+    pub fn new() -> Self {
+        Self {
+            local_db: PathBuf::from(LOCAL_DB_DIR),
+            log: PathBuf::from(PACMAN_LOG),
+            runner: Arc::new(SystemRunner::default()),
+            loaded: Mutex::new(None),
+        }
+    }
+
+    /// The cache every component in this process shares, so one load serves
+    /// the scanners, pre-flight, and the package manager alike
+    pub fn shared() -> Arc<PackageCache> {
+        SHARED.clone()
+    }
+
+    /// Read the local database and watch the log at other paths
+    pub fn with_paths(mut self, local_db: impl Into<PathBuf>, log: impl Into<PathBuf>) -> Self {
+        self.local_db = local_db.into();
+        self.log = log.into();
+        self
+    }
+
+    /// Ask for foreign packages through `runner` instead of the local system
+    pub fn with_runner(mut self, runner: Arc<dyn CommandRunner>) -> Self {
+        self.runner = runner;
+        self
+    }
+
+    /// The current index, reloaded first when `pacman.log` changed since it
+    /// was read or the cache was invalidated. Concurrent callers wait for
+    /// one load rather than each reading the database.
+    pub async fn index(&self) -> Result<Arc<PackageIndex>> {
+        let mut loaded = self.loaded.lock().await;
+        let stamp = LogStamp::read(&self.log).await;
+        if let Some((loaded_stamp, index)) = loaded.as_ref()
+            && *loaded_stamp == stamp
+        {
+            return Ok(index.clone());
+        }
+
+        let index = Arc::new(self.load().await?);
+        tracing::debug!("Loaded {} installed packages", index.len());
+        *loaded = Some((stamp, index.clone()));
+        Ok(index)
+    }
+
+    /// Drop the index so the next lookup reloads it; call after a transaction
+    pub async fn invalidate(&self) {
+        *self.loaded.lock().await = None;
+    }
+
+    async fn load(&self) -> Result<PackageIndex> {
+        let local_db = self.local_db.clone();
+        let mut index = tokio::task::spawn_blocking(move || PackageIndex::load(&local_db))
+            .await
+            .context("Package database load panicked")??;
+
+        // Exits 1 when nothing is foreign, which is an answer too
+        match self.runner.output("pacman", &["-Qmq"]).await {
+            Ok(output) => {
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                index.mark_foreign(stdout.lines().map(str::trim));
+            }
+            Err(e) => tracing::warn!("Could not tell foreign packages apart: {}", e),
+        }
+        Ok(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::RecordingRunner;
+    use crate::preflight::parse_installed_sizes;
+
+    const FIXTURE_DB: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/pacman/local");
+
+    fn cache(log: &Path) -> PackageCache {
+        PackageCache::new()
+            .with_paths(FIXTURE_DB, log)
+            .with_runner(Arc::new(RecordingRunner::new().respond(
+                "pacman -Qmq",
+                include_str!("../tests/fixtures/pacman/Qmq.txt"),
+            )))
+    }
+
+    #[test]
+    fn test_parse_desc() {
+        let package = parse_desc(include_str!(
+            "../tests/fixtures/pacman/local/nano-8.2-1/desc"
+        ))
+        .unwrap();
+
+        assert_eq!(package.name, "nano");
+        assert_eq!(package.version, "8.2-1");
+        assert_eq!(package.installed_size, 2_663_383);
+        assert_eq!(
+            package.install_date,
+            DateTime::from_timestamp(1_729_067_412, 0)
+        );
+        assert!(package.explicit);
+        assert!(!package.foreign);
+        assert_eq!(parse_desc("%NAME%\nnano\n"), None);
+    }
+
+    /// The fixture database and the pacman output recorded from it agree
+    #[tokio::test]
+    async fn test_index_matches_pacman_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("pacman.log");
+        std::fs::write(&log, "").unwrap();
+        let index = cache(&log).index().await.unwrap();
+
+        let queried: BTreeMap<String, String> = include_str!("../tests/fixtures/pacman/Q.txt")
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .map(|(name, version)| (name.to_string(), version.to_string()))
+            .collect();
+        assert_eq!(index.versions(), queried);
+
+        let info = include_str!("../tests/fixtures/pacman/Qi.txt");
+        for (name, size) in parse_installed_sizes(info) {
+            // -Qi rounds to two decimals of the unit it picks
+            let cached = index.get(&name).unwrap().installed_size;
+            assert!(cached.abs_diff(size) * 100 <= cached, "{} size", name);
+        }
+        let dependencies: Vec<&str> = info
+            .split("\n\n")
+            .filter(|block| block.contains("Installed as a dependency"))
+            .filter_map(|block| block.lines().next()?.split_once(':'))
+            .map(|(_, name)| name.trim())
+            .collect();
+        assert_eq!(dependencies, vec!["libfoo"]);
+        assert!(!index.get("libfoo").unwrap().explicit);
+
+        let foreign: Vec<&str> = index.foreign().iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(
+            foreign,
+            include_str!("../tests/fixtures/pacman/Qmq.txt")
+                .lines()
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_index_reloads_after_log_change_or_invalidate() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("pacman.log");
+        std::fs::write(&log, "[2024-10-16T08:30:12+0200] [ALPM] upgraded nano\n").unwrap();
+        let cache = cache(&log);
+
+        let first = cache.index().await.unwrap();
+        assert!(Arc::ptr_eq(&first, &cache.index().await.unwrap()));
+
+        std::fs::write(&log, "[2024-10-16T08:30:12+0200] [ALPM] upgraded nano\n[2024-10-17T09:00:00+0200] [ALPM] installed yay-bin\n").unwrap();
+        let reloaded = cache.index().await.unwrap();
+        assert!(!Arc::ptr_eq(&first, &reloaded));
+
+        cache.invalidate().await;
+        assert!(!Arc::ptr_eq(&reloaded, &cache.index().await.unwrap()));
+    }
+}
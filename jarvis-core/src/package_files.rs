@@ -0,0 +1,452 @@
+//! Package file ownership
+//!
+//! Answers "what package owns /usr/bin/foo" and "what provides libssl.so.3".
+//! Files on disk are looked up in the local database with `pacman -Qo`;
+//! anything not installed goes to the sync file database (`pacman -F`), which
+//! is refreshed first when it is older than `[system] files_db_max_age_hours`.
+//! Names that match no file fall back to a `pacman -Ss` provider search.
+
+use crate::exec::CommandRunner;
+use anyhow::Result;
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::time::Duration;
+
+/// Used when the config does not set `files_db_max_age_hours`
+pub const DEFAULT_FILES_DB_MAX_AGE: Duration = Duration::from_secs(72 * 3600);
+
+/// Most files from one error message resolved for a diagnosis
+const MAX_MENTIONED_FILES: usize = 5;
+
+/// Installed package owning a file, or a repository package shipping it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileOwner {
+    pub path: String,
+    pub package: String,
+    pub version: String,
+    /// Repository, for owners found in the sync file database
+    pub repo: Option<String>,
+    pub installed: bool,
+    /// When the installed package was last installed or upgraded
+    pub installed_at: Option<DateTime<Utc>>,
+}
+
+impl FileOwner {
+    /// One line for prompts, e.g. "libfoo.so belongs to package bar 1.2-1,
+    /// upgraded 2 days ago"
+    pub fn describe(&self, now: DateTime<Utc>) -> String {
+        let mut line = if self.installed {
+            format!(
+                "{} belongs to package {} {}",
+                self.path, self.package, self.version
+            )
+        } else {
+            format!(
+                "{} is shipped by {}/{} {}, which is not installed",
+                self.path,
+                self.repo.as_deref().unwrap_or("?"),
+                self.package,
+                self.version
+            )
+        };
+        if let Some(at) = self.installed_at {
+            line.push_str(&format!(", upgraded {}", age(now - at)));
+        }
+        line
+    }
+}
+
+fn age(elapsed: chrono::Duration) -> String {
+    match elapsed.num_days() {
+        0 if elapsed.num_hours() == 0 => "just now".to_string(),
+        0 => format!("{} hours ago", elapsed.num_hours()),
+        1 => "1 day ago".to_string(),
+        days => format!("{} days ago", days),
+    }
+}
+
+/// A package that provides a file or name
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Provider {
+    pub repo: String,
+    pub package: String,
+    pub version: String,
+    /// The matching file, for file database hits
+    pub path: Option<String>,
+    pub installed: bool,
+}
+
+/// Age and refresh of the sync file database before a query used it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FilesDbState {
+    /// Hours since the oldest `.files` database was written; `None` when
+    /// there is none yet
+    pub age_hours: Option<u64>,
+    pub refreshed: bool,
+    /// Why a needed refresh failed; the query ran against the old data
+    pub refresh_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OwnsResult {
+    pub path: String,
+    pub owners: Vec<FileOwner>,
+    /// Present when the file was not installed and the file database was used
+    pub files_db: Option<FilesDbState>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProvidesResult {
+    pub query: String,
+    pub providers: Vec<Provider>,
+    pub files_db: FilesDbState,
+}
+
+/// The package owning `path`: the installed one, else whichever repository
+/// packages ship it
+pub async fn owns(runner: &dyn CommandRunner, path: &str, max_age: Duration) -> Result<OwnsResult> {
+    let output = runner.output("pacman", &["-Qo", path]).await?;
+    if output.status.success() {
+        let mut owners = parse_owned_by(&String::from_utf8_lossy(&output.stdout));
+        for owner in &mut owners {
+            owner.installed_at = installed_at(runner, &owner.package).await;
+        }
+        return Ok(OwnsResult {
+            path: path.to_string(),
+            owners,
+            files_db: None,
+        });
+    }
+
+    // Not installed, or a bare name like libssl.so.3 that -Qo only looks
+    // up in PATH
+    let files_db = ensure_files_db(runner, max_age).await;
+    let providers = search_files(runner, path).await?;
+    let names: Vec<&str> = providers.iter().map(|p| p.package.as_str()).collect();
+    let installed = if names.is_empty() {
+        HashSet::new()
+    } else {
+        installed_packages(runner, &names).await?
+    };
+    let mut owners = Vec::new();
+    for provider in providers {
+        let is_installed = installed.contains(&provider.package);
+        owners.push(FileOwner {
+            installed_at: match is_installed {
+                true => installed_at(runner, &provider.package).await,
+                false => None,
+            },
+            path: provider.path.unwrap_or_else(|| path.to_string()),
+            package: provider.package,
+            version: provider.version,
+            repo: Some(provider.repo),
+            installed: is_installed,
+        });
+    }
+    Ok(OwnsResult {
+        path: path.to_string(),
+        owners,
+        files_db: Some(files_db),
+    })
+}
+
+/// Packages providing `name`: files matching it in the file database, or
+/// failing that, packages whose name or description matches
+pub async fn provides(
+    runner: &dyn CommandRunner,
+    name: &str,
+    max_age: Duration,
+) -> Result<ProvidesResult> {
+    let files_db = ensure_files_db(runner, max_age).await;
+    let mut providers = search_files(runner, name).await?;
+    if providers.is_empty() {
+        let output = runner.output("pacman", &["-Ss", name]).await?;
+        providers = parse_search(&String::from_utf8_lossy(&output.stdout));
+    } else {
+        let names: Vec<&str> = providers.iter().map(|p| p.package.as_str()).collect();
+        let installed = installed_packages(runner, &names).await?;
+        for provider in &mut providers {
+            provider.installed = installed.contains(&provider.package);
+        }
+    }
+
+    Ok(ProvidesResult {
+        query: name.to_string(),
+        providers,
+        files_db,
+    })
+}
+
+/// Owners of the absolute paths and shared libraries named in `text`, one
+/// line each, for diagnosis prompts
+pub async fn describe_mentioned_files(
+    runner: &dyn CommandRunner,
+    text: &str,
+    max_age: Duration,
+) -> Vec<String> {
+    let now = Utc::now();
+    let mut lines = Vec::new();
+    for file in mentioned_files(text) {
+        let owners = match owns(runner, &file, max_age).await {
+            Ok(result) => result.owners,
+            Err(e) => {
+                tracing::debug!("Owner lookup for {} failed: {}", file, e);
+                continue;
+            }
+        };
+        match owners.first() {
+            Some(owner) => lines.push(owner.describe(now)),
+            None => lines.push(format!("{} is not shipped by any known package", file)),
+        }
+    }
+    lines
+}
+
+/// Absolute paths and `lib*.so*` names in an error message
+pub fn mentioned_files(text: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    text.split(|c: char| c.is_whitespace() || "'\"`()[]{}<>,;=".contains(c))
+        .map(|word| word.trim_end_matches([':', '.', '?', '!']))
+        .filter(|word| {
+            let path = word.starts_with('/') && word.len() > 1 && word[1..].contains('/');
+            let library = word.starts_with("lib") && word.contains(".so");
+            path || library
+        })
+        .filter(|word| seen.insert(word.to_string()))
+        .take(MAX_MENTIONED_FILES)
+        .map(str::to_string)
+        .collect()
+}
+
+/// Refresh the file database when its oldest part is older than `max_age`.
+/// A failed refresh is recorded, not fatal: old data still answers most
+/// questions.
+async fn ensure_files_db(runner: &dyn CommandRunner, max_age: Duration) -> FilesDbState {
+    let mut state = FilesDbState {
+        age_hours: files_db_age(runner).await.map(|age| age.as_secs() / 3600),
+        ..Default::default()
+    };
+    let stale = state
+        .age_hours
+        .is_none_or(|hours| hours * 3600 > max_age.as_secs());
+    if !stale {
+        return state;
+    }
+
+    match runner.output("sudo", &["-n", "pacman", "-Fy"]).await {
+        Ok(output) if output.status.success() => state.refreshed = true,
+        Ok(output) => {
+            state.refresh_error = Some(String::from_utf8_lossy(&output.stderr).trim().to_string())
+        }
+        Err(e) => state.refresh_error = Some(e.to_string()),
+    }
+    state
+}
+
+/// Time since the oldest `.files` database was written
+async fn files_db_age(runner: &dyn CommandRunner) -> Option<Duration> {
+    let output = runner
+        .output("sh", &["-c", "stat -c %Y /var/lib/pacman/sync/*.files"])
+        .await
+        .ok()?;
+    let oldest = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.trim().parse::<i64>().ok())
+        .min()?;
+    let age = Utc::now().timestamp() - oldest;
+    Some(Duration::from_secs(age.max(0) as u64))
+}
+
+async fn search_files(runner: &dyn CommandRunner, target: &str) -> Result<Vec<Provider>> {
+    let output = runner
+        .output("pacman", &["-F", "--machinereadable", target])
+        .await?;
+    Ok(parse_machine_readable(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+async fn installed_packages(runner: &dyn CommandRunner, names: &[&str]) -> Result<HashSet<String>> {
+    let mut args = vec!["-Qq"];
+    args.extend_from_slice(names);
+    // Exits 1 when any name is missing but still lists the installed ones
+    let output = runner.output("pacman", &args).await?;
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| line.trim().to_string())
+        .collect())
+}
+
+async fn installed_at(runner: &dyn CommandRunner, package: &str) -> Option<DateTime<Utc>> {
+    let output = runner.output("pacman", &["-Qi", package]).await.ok()?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.trim() == "Install Date")
+        .and_then(|(_, value)| parse_pacman_date(value))
+}
+
+/// `pacman -Qi` dates in the C locale, e.g. "Mon Oct  7 09:12:44 2024"
+fn parse_pacman_date(value: &str) -> Option<DateTime<Utc>> {
+    let normalized = value.split_whitespace().collect::<Vec<_>>().join(" ");
+    let naive = NaiveDateTime::parse_from_str(&normalized, "%a %b %e %H:%M:%S %Y").ok()?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|at| at.with_timezone(&Utc))
+}
+
+/// `pacman -Qo` lines: "/usr/bin/foo is owned by bar 1.2-1"
+fn parse_owned_by(stdout: &str) -> Vec<FileOwner> {
+    stdout
+        .lines()
+        .filter_map(|line| {
+            let (path, owner) = line.split_once(" is owned by ")?;
+            let (package, version) = owner.trim().split_once(' ')?;
+            Some(FileOwner {
+                path: path.trim().to_string(),
+                package: package.to_string(),
+                version: version.trim().to_string(),
+                repo: None,
+                installed: true,
+                installed_at: None,
+            })
+        })
+        .collect()
+}
+
+/// `pacman -F --machinereadable` records: repo, package, version, and path,
+/// separated by NUL
+fn parse_machine_readable(stdout: &str) -> Vec<Provider> {
+    stdout
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\0');
+            Some(Provider {
+                repo: fields.next()?.to_string(),
+                package: fields.next()?.to_string(),
+                version: fields.next()?.to_string(),
+                path: fields
+                    .next()
+                    .map(|path| format!("/{}", path.trim_start_matches('/'))),
+                installed: false,
+            })
+        })
+        .collect()
+}
+
+/// `pacman -Ss` headers: "core/openssl 3.3.2-1 [installed]"; the indented
+/// description lines are skipped
+fn parse_search(stdout: &str) -> Vec<Provider> {
+    stdout
+        .lines()
+        .filter(|line| !line.starts_with(char::is_whitespace))
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            let (repo, package) = words.next()?.split_once('/')?;
+            Some(Provider {
+                repo: repo.to_string(),
+                package: package.to_string(),
+                version: words.next()?.to_string(),
+                path: None,
+                installed: line.contains("[installed"),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::{RecordingRunner, fake_output};
+
+    #[tokio::test]
+    async fn test_owns_installed_file() {
+        let runner = RecordingRunner::new()
+            .respond("pacman -Qo", "/usr/bin/rg is owned by ripgrep 14.1.0-1\n")
+            .respond(
+                "pacman -Qi",
+                "Name            : ripgrep\nInstall Date    : Mon Oct  7 09:12:44 2024\n",
+            );
+        let result = owns(&runner, "/usr/bin/rg", DEFAULT_FILES_DB_MAX_AGE)
+            .await
+            .unwrap();
+
+        assert!(result.files_db.is_none());
+        let owner = &result.owners[0];
+        assert_eq!(owner.package, "ripgrep");
+        assert_eq!(owner.version, "14.1.0-1");
+        let installed_at = owner.installed_at.unwrap();
+        let line = owner.describe(installed_at + chrono::Duration::days(2));
+        assert_eq!(
+            line,
+            "/usr/bin/rg belongs to package ripgrep 14.1.0-1, upgraded 2 days ago"
+        );
+        assert_eq!(
+            runner.calls(),
+            vec!["pacman -Qo /usr/bin/rg", "pacman -Qi ripgrep"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_owns_falls_back_to_a_refreshed_file_database() {
+        let stale = Utc::now().timestamp() - 10 * 24 * 3600;
+        let runner = RecordingRunner::new()
+            .respond_with(
+                "pacman -Qo",
+                fake_output(1, "", "error: No package owns /usr/bin/foo\n"),
+            )
+            .respond("sh -c stat", &format!("{}\n{}\n", stale, stale + 60))
+            .respond("pacman -F", "extra\0foo-tools\01.2-1\0usr/bin/foo\n");
+        let result = owns(&runner, "/usr/bin/foo", DEFAULT_FILES_DB_MAX_AGE)
+            .await
+            .unwrap();
+
+        let files_db = result.files_db.unwrap();
+        assert_eq!(files_db.age_hours, Some(240));
+        assert!(files_db.refreshed);
+        assert_eq!(result.owners[0].package, "foo-tools");
+        assert_eq!(result.owners[0].repo.as_deref(), Some("extra"));
+        assert!(!result.owners[0].installed);
+        assert!(runner.calls().contains(&"sudo -n pacman -Fy".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_provides_searches_packages_when_no_file_matches() {
+        let fresh = Utc::now().timestamp();
+        let runner = RecordingRunner::new()
+            .respond("sh -c stat", &format!("{}\n", fresh))
+            .respond("pacman -F", "")
+            .respond(
+                "pacman -Ss",
+                "extra/jre-openjdk 23.0.1-1 [installed]\n    OpenJDK Java runtime\nextra/jre21-openjdk 21.0.5-1\n    OpenJDK Java 21 runtime\n",
+            );
+        let result = provides(&runner, "java-runtime", DEFAULT_FILES_DB_MAX_AGE)
+            .await
+            .unwrap();
+
+        assert!(!result.files_db.refreshed);
+        assert_eq!(result.providers.len(), 2);
+        assert!(result.providers[0].installed);
+        assert!(!result.providers[1].installed);
+        assert!(!runner.calls().iter().any(|call| call.contains("-Fy")));
+    }
+
+    #[test]
+    fn test_mentioned_files() {
+        let files = mentioned_files(
+            "foo: error while loading shared libraries: libssl.so.3: cannot open shared object file\n\
+             /usr/bin/foo: line 3: '/etc/foo/foo.conf' not found, see /usr/share/doc/foo.",
+        );
+        assert_eq!(
+            files,
+            vec![
+                "libssl.so.3",
+                "/usr/bin/foo",
+                "/etc/foo/foo.conf",
+                "/usr/share/doc/foo"
+            ]
+        );
+    }
+}
@@ -0,0 +1,197 @@
+//! Pacman database lock
+//!
+//! A pacman run that dies leaves `/var/lib/pacman/db.lck` behind, and every
+//! transaction after it fails with "unable to lock database". [`check`]
+//! tells a lock that a running package manager holds from a stale one, which
+//! [`remove_stale`] deletes once nothing could be holding it.
+
+use crate::exec::CommandRunner;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+pub const DB_LOCK_PATH: &str = "/var/lib/pacman/db.lck";
+
+/// Process names that take the lock: pacman and the libalpm frontends
+const LOCKING_PROCESSES: &str = "pacman|yay|paru|pamac|pamac-daemon|packagekitd";
+
+/// A running package manager that may hold the lock
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockHolder {
+    pub pid: u32,
+    /// Command line as `pgrep -a` shows it
+    pub command: String,
+}
+
+/// The package database is locked. With no `holders` the lock is stale,
+/// left by a run that died, and safe to remove.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockHeld {
+    pub path: PathBuf,
+    /// Seconds since the lock file was created
+    pub age_secs: u64,
+    pub holders: Vec<LockHolder>,
+}
+
+impl LockHeld {
+    pub fn is_stale(&self) -> bool {
+        self.holders.is_empty()
+    }
+
+    /// e.g. "3 h"
+    pub fn age(&self) -> String {
+        describe_age(self.age_secs)
+    }
+}
+
+impl fmt::Display for LockHeld {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.holders.first() {
+            Some(holder) => write!(
+                f,
+                "The pacman database is locked by {} (pid {}) for {}; wait for it to finish",
+                holder.command,
+                holder.pid,
+                self.age()
+            ),
+            None => write!(
+                f,
+                "The pacman database has a stale lock ({}, {} old) and no package manager is running; \
+                 remove it with `jarvis arch unlock-db`",
+                self.path.display(),
+                self.age()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LockHeld {}
+
+fn describe_age(secs: u64) -> String {
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{} min", secs / 60),
+        3600..86400 => format!("{} h", secs / 3600),
+        _ => format!("{} days", secs / 86400),
+    }
+}
+
+/// The lock at `path` and who may hold it; `None` when there is no lock
+pub async fn check(runner: &dyn CommandRunner, path: &Path) -> Result<Option<LockHeld>> {
+    let metadata = match tokio::fs::metadata(path).await {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("Failed to inspect {}", path.display())),
+    };
+    let age_secs = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .map(|age| age.as_secs())
+        .unwrap_or(0);
+
+    Ok(Some(LockHeld {
+        path: path.to_path_buf(),
+        age_secs,
+        holders: running_package_managers(runner).await?,
+    }))
+}
+
+/// Package manager processes running now
+pub async fn running_package_managers(runner: &dyn CommandRunner) -> Result<Vec<LockHolder>> {
+    let output = runner
+        .output("pgrep", &["-a", "-x", LOCKING_PROCESSES])
+        .await
+        .context("Failed to look for running package managers")?;
+
+    // pgrep exits 1 when nothing matched
+    match output.status.code() {
+        Some(0) => Ok(parse_pgrep(&String::from_utf8_lossy(&output.stdout))),
+        Some(1) => Ok(Vec::new()),
+        _ => anyhow::bail!(
+            "Failed to look for running package managers: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ),
+    }
+}
+
+/// `pgrep -a` lines, e.g. "4242 pacman -Syu"
+fn parse_pgrep(output: &str) -> Vec<LockHolder> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (pid, command) = line.trim().split_once(' ')?;
+            Some(LockHolder {
+                pid: pid.parse().ok()?,
+                command: command.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Delete the lock at `path` if it is stale; `false` when there was none.
+/// Checks again first, so a package manager that started since the lock was
+/// reported keeps it.
+pub async fn remove_stale(runner: &dyn CommandRunner, path: &Path) -> Result<bool> {
+    let Some(lock) = check(runner, path).await? else {
+        return Ok(false);
+    };
+    if !lock.is_stale() {
+        return Err(lock.into());
+    }
+
+    let target = path.to_string_lossy();
+    let output = runner
+        .output("rm", &["-f", &target])
+        .await
+        .with_context(|| format!("Failed to remove {}", target))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to remove {}: {}",
+            target,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    tracing::info!("Removed stale pacman lock {} ({} old)", target, lock.age());
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::RecordingRunner;
+
+    #[test]
+    fn test_parse_pgrep_lines() {
+        let holders = parse_pgrep("4242 pacman -Syu\n977 /usr/bin/pamac-daemon\n");
+        assert_eq!(
+            holders,
+            vec![
+                LockHolder {
+                    pid: 4242,
+                    command: "pacman -Syu".to_string()
+                },
+                LockHolder {
+                    pid: 977,
+                    command: "/usr/bin/pamac-daemon".to_string()
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_lock_runs_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let runner = RecordingRunner::new();
+
+        let lock = check(&runner, &dir.path().join("db.lck")).await.unwrap();
+        assert!(lock.is_none());
+        assert!(
+            !remove_stale(&runner, &dir.path().join("db.lck"))
+                .await
+                .unwrap()
+        );
+        assert!(runner.calls().is_empty());
+    }
+}
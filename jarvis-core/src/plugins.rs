@@ -0,0 +1,399 @@
+//! Declarative tool plugins
+//!
+//! Each `*.toml` file in the plugin directory describes one tool: a name, a
+//! description, a JSON schema for its parameters, and an argv template. The
+//! program must be on the `allowed_commands` list and runs without a shell,
+//! under a timeout, with `{{param}}` placeholders filled from the call
+//! arguments. A broken file is reported and skipped; it never stops the
+//! others from loading.
+
+use crate::config::PluginsConfig;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::process::Command;
+
+/// Longest timeout a plugin may ask for
+const MAX_TIMEOUT_SECS: u64 = 600;
+
+/// A plugin file as written by the user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PluginToolSpec {
+    pub name: String,
+    pub description: String,
+    /// JSON schema for the arguments; must be `type = "object"`
+    #[serde(default = "empty_object_schema")]
+    pub parameters: Value,
+    /// Program and arguments, e.g. `["upsc", "{{ups}}@localhost", "battery.charge"]`
+    pub command: Vec<String>,
+    pub timeout_secs: Option<u64>,
+}
+
+fn empty_object_schema() -> Value {
+    serde_json::json!({ "type": "object", "properties": {} })
+}
+
+/// A validated plugin and the file it came from
+#[derive(Debug, Clone)]
+pub struct PluginTool {
+    pub spec: PluginToolSpec,
+    pub source: PathBuf,
+    pub timeout: Duration,
+    pub max_output_bytes: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LoadedPlugins {
+    pub tools: Vec<PluginTool>,
+    /// Files that failed to parse or validate
+    pub errors: Vec<(PathBuf, String)>,
+}
+
+impl LoadedPlugins {
+    /// (name, description) pairs for the NLP router's tool list
+    pub fn descriptions(&self) -> Vec<(String, String)> {
+        self.tools
+            .iter()
+            .map(|t| (t.spec.name.clone(), t.spec.description.clone()))
+            .collect()
+    }
+
+    pub fn log_errors(&self) {
+        for (path, error) in &self.errors {
+            tracing::warn!("Skipping tool plugin {}: {}", path.display(), error);
+        }
+    }
+}
+
+/// Load every plugin in the configured directory. `reserved` are names that
+/// plugins may not take (the built-in tools).
+pub fn load_plugins(config: &PluginsConfig, reserved: &[&str]) -> LoadedPlugins {
+    let mut loaded = LoadedPlugins::default();
+    if !config.enabled {
+        return loaded;
+    }
+
+    let dir = PathBuf::from(shellexpand::tilde(&config.dir).to_string());
+    let mut paths: Vec<PathBuf> = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|ext| ext == "toml"))
+            .collect(),
+        // No plugin directory just means no plugins
+        Err(_) => return loaded,
+    };
+    paths.sort();
+
+    for path in paths {
+        match load_plugin(&path, config) {
+            Ok(tool) if reserved.contains(&tool.spec.name.as_str()) => loaded.errors.push((
+                path,
+                format!("'{}' is a built-in tool name", tool.spec.name),
+            )),
+            Ok(tool) if loaded.tools.iter().any(|t| t.spec.name == tool.spec.name) => loaded
+                .errors
+                .push((path, format!("duplicate tool name '{}'", tool.spec.name))),
+            Ok(tool) => loaded.tools.push(tool),
+            Err(e) => loaded.errors.push((path, format!("{:#}", e))),
+        }
+    }
+
+    loaded
+}
+
+pub fn load_plugin(path: &Path, config: &PluginsConfig) -> Result<PluginTool> {
+    let text = std::fs::read_to_string(path).context("unreadable")?;
+    let spec: PluginToolSpec = toml::from_str(&text).context("invalid TOML")?;
+    validate(&spec, config)?;
+
+    Ok(PluginTool {
+        timeout: Duration::from_secs(spec.timeout_secs.unwrap_or(config.default_timeout_secs)),
+        max_output_bytes: config.max_output_bytes,
+        source: path.to_path_buf(),
+        spec,
+    })
+}
+
+pub fn validate(spec: &PluginToolSpec, config: &PluginsConfig) -> Result<()> {
+    let valid_name = spec
+        .name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_lowercase())
+        && spec
+            .name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !valid_name {
+        anyhow::bail!(
+            "name '{}' must be lowercase letters, digits, and underscores",
+            spec.name
+        );
+    }
+    if spec.description.trim().is_empty() {
+        anyhow::bail!("description is empty");
+    }
+
+    if spec.parameters.get("type").and_then(|t| t.as_str()) != Some("object") {
+        anyhow::bail!("parameters must be a JSON schema with type = \"object\"");
+    }
+    let properties = spec
+        .parameters
+        .get("properties")
+        .and_then(|p| p.as_object())
+        .cloned()
+        .unwrap_or_default();
+
+    let program = spec.command.first().context("command is empty")?;
+    if program.contains("{{") {
+        anyhow::bail!("the program itself cannot be a placeholder");
+    }
+    if !config.allowed_commands.iter().any(|c| c == program) {
+        anyhow::bail!("'{}' is not in [mcp.plugins] allowed_commands", program);
+    }
+
+    for arg in &spec.command {
+        for placeholder in placeholders(arg) {
+            if !properties.contains_key(placeholder) {
+                anyhow::bail!(
+                    "placeholder {{{{{}}}}} has no matching parameter",
+                    placeholder
+                );
+            }
+        }
+    }
+
+    if let Some(timeout) = spec.timeout_secs {
+        if timeout == 0 || timeout > MAX_TIMEOUT_SECS {
+            anyhow::bail!("timeout_secs must be between 1 and {}", MAX_TIMEOUT_SECS);
+        }
+    }
+    Ok(())
+}
+
+/// Names inside `{{...}}` in one argv element
+fn placeholders(arg: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = arg;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        names.push(&rest[start + 2..start + 2 + len]);
+        rest = &rest[start + 2 + len + 2..];
+    }
+    names
+}
+
+impl PluginTool {
+    /// Fill the argv template from `args`, checking them against the schema
+    pub fn render_command(&self, args: &Value) -> Result<Vec<String>> {
+        let properties = self
+            .spec
+            .parameters
+            .get("properties")
+            .and_then(|p| p.as_object());
+        let required: Vec<&str> = self
+            .spec
+            .parameters
+            .get("required")
+            .and_then(|r| r.as_array())
+            .map(|r| r.iter().filter_map(|v| v.as_str()).collect())
+            .unwrap_or_default();
+
+        let mut argv = Vec::new();
+        for template in &self.spec.command {
+            let names = placeholders(template);
+            let mut rendered = template.clone();
+            let mut filled = 0;
+
+            for name in &names {
+                let schema = properties.and_then(|p| p.get(*name));
+                let text = match args
+                    .get(*name)
+                    .or_else(|| schema.and_then(|s| s.get("default")))
+                {
+                    Some(value) => {
+                        filled += 1;
+                        argument_text(name, value, schema)?
+                    }
+                    None if required.contains(name) => {
+                        anyhow::bail!("missing required parameter '{}'", name)
+                    }
+                    None => String::new(),
+                };
+                rendered = rendered.replace(&format!("{{{{{}}}}}", name), &text);
+            }
+
+            // An argument built only from omitted optional parameters is left out
+            if !names.is_empty() && filled == 0 {
+                continue;
+            }
+            argv.push(rendered);
+        }
+        Ok(argv)
+    }
+
+    /// Run the plugin and return its combined output
+    pub async fn run(&self, args: &Value) -> Result<String> {
+        let argv = self.render_command(args)?;
+        let program_args: Vec<&str> = argv[1..].iter().map(String::as_str).collect();
+        crate::read_only::check_command(&argv[0], &program_args)?;
+        let output = tokio::time::timeout(
+            self.timeout,
+            Command::new(&argv[0])
+                .args(&argv[1..])
+                .kill_on_drop(true)
+                .output(),
+        )
+        .await
+        .map_err(|_| {
+            anyhow::anyhow!(
+                "{} timed out after {}s",
+                self.spec.name,
+                self.timeout.as_secs()
+            )
+        })?
+        .with_context(|| format!("Failed to run {}", argv[0]))?;
+
+        let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !stderr.trim().is_empty() {
+            text.push_str("\n[stderr]\n");
+            text.push_str(&stderr);
+        }
+        if text.len() > self.max_output_bytes {
+            let mut end = self.max_output_bytes;
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            text.truncate(end);
+            text.push_str("\n[...output truncated]");
+        }
+
+        if !output.status.success() {
+            anyhow::bail!("{} exited with {}: {}", argv[0], output.status, text.trim());
+        }
+        Ok(text)
+    }
+}
+
+/// Stringify one argument, enforcing the schema's type and enum
+fn argument_text(name: &str, value: &Value, schema: Option<&Value>) -> Result<String> {
+    if let Some(allowed) = schema
+        .and_then(|s| s.get("enum"))
+        .and_then(|e| e.as_array())
+    {
+        if !allowed.contains(value) {
+            anyhow::bail!(
+                "'{}' must be one of {}",
+                name,
+                Value::Array(allowed.clone())
+            );
+        }
+    }
+
+    let expected = schema.and_then(|s| s.get("type")).and_then(|t| t.as_str());
+    let text = match (expected, value) {
+        (Some("integer"), Value::Number(n)) if n.is_i64() || n.is_u64() => n.to_string(),
+        (Some("number"), Value::Number(n)) => n.to_string(),
+        (Some("boolean"), Value::Bool(b)) => b.to_string(),
+        (Some("string") | None, Value::String(s)) => s.clone(),
+        (None, Value::Number(n)) => n.to_string(),
+        (None, Value::Bool(b)) => b.to_string(),
+        _ => anyhow::bail!(
+            "'{}' must be of type {}",
+            name,
+            expected.unwrap_or("string")
+        ),
+    };
+
+    // Arguments go straight to argv, so the only injection left is option smuggling
+    if text.starts_with('-') && expected != Some("integer") && expected != Some("number") {
+        anyhow::bail!("'{}' may not start with '-'", name);
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> PluginsConfig {
+        PluginsConfig {
+            allowed_commands: vec!["upsc".to_string()],
+            ..PluginsConfig::default()
+        }
+    }
+
+    fn ups_tool() -> PluginTool {
+        let spec: PluginToolSpec = toml::from_str(
+            r#"
+            name = "ups_status"
+            description = "Query the UPS"
+            command = ["upsc", "{{ups}}@{{host}}", "{{variable}}"]
+
+            [parameters]
+            type = "object"
+            required = ["ups"]
+
+            [parameters.properties.ups]
+            type = "string"
+
+            [parameters.properties.host]
+            type = "string"
+            default = "localhost"
+
+            [parameters.properties.variable]
+            type = "string"
+            "#,
+        )
+        .unwrap();
+        validate(&spec, &config()).unwrap();
+        PluginTool {
+            spec,
+            source: PathBuf::from("ups.toml"),
+            timeout: Duration::from_secs(5),
+            max_output_bytes: 1024,
+        }
+    }
+
+    #[test]
+    fn test_render_command() {
+        let tool = ups_tool();
+        assert_eq!(
+            tool.render_command(&serde_json::json!({"ups": "eaton"}))
+                .unwrap(),
+            vec!["upsc", "eaton@localhost"]
+        );
+        assert_eq!(
+            tool.render_command(&serde_json::json!({"ups": "eaton", "variable": "battery.charge"}))
+                .unwrap(),
+            vec!["upsc", "eaton@localhost", "battery.charge"]
+        );
+        assert!(tool.render_command(&serde_json::json!({})).is_err());
+        assert!(
+            tool.render_command(&serde_json::json!({"ups": "--help"}))
+                .is_err()
+        );
+        assert!(tool.render_command(&serde_json::json!({"ups": 5})).is_err());
+    }
+
+    #[test]
+    fn test_validation() {
+        let mut spec = ups_tool().spec;
+        spec.command[0] = "rm".to_string();
+        assert!(validate(&spec, &config()).is_err());
+
+        let mut spec = ups_tool().spec;
+        spec.command.push("{{undeclared}}".to_string());
+        assert!(validate(&spec, &config()).is_err());
+
+        let mut spec = ups_tool().spec;
+        spec.name = "UPS Status".to_string();
+        assert!(validate(&spec, &config()).is_err());
+    }
+}
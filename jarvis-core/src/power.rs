@@ -0,0 +1,272 @@
+//! Power control for homelab hosts
+//!
+//! Wake-on-LAN for sleeping machines, and poweroff/reboot/suspend of the local
+//! machine or a configured remote host. Every operation checks its own outcome
+//! before reporting success: a woken host must answer pings, and a host being
+//! shut down must drop its SSH connection.
+
+use crate::config::RemoteConfig;
+use crate::remote::{CommandExecutor, SshTarget};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::process::Command;
+
+/// Standard WOL port; port 7 is the other common choice
+const WOL_PORT: u16 = 9;
+const DEFAULT_BROADCAST: &str = "255.255.255.255";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerAction {
+    Wake,
+    Poweroff,
+    Reboot,
+    Suspend,
+}
+
+impl PowerAction {
+    /// Everything except wake changes the state of a running machine
+    pub fn is_destructive(&self) -> bool {
+        !matches!(self, PowerAction::Wake)
+    }
+
+    pub fn systemctl_verb(&self) -> &'static str {
+        match self {
+            PowerAction::Wake => unreachable!("wake is not a systemctl action"),
+            PowerAction::Poweroff => "poweroff",
+            PowerAction::Reboot => "reboot",
+            PowerAction::Suspend => "suspend",
+        }
+    }
+}
+
+impl FromStr for PowerAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "wake" | "wol" | "on" => Ok(PowerAction::Wake),
+            "poweroff" | "shutdown" | "off" => Ok(PowerAction::Poweroff),
+            "reboot" | "restart" => Ok(PowerAction::Reboot),
+            "suspend" | "sleep" => Ok(PowerAction::Suspend),
+            _ => Err(anyhow::anyhow!(
+                "Unknown power action '{}' (wake, poweroff, reboot, suspend)",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for PowerAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            PowerAction::Wake => "wake",
+            PowerAction::Poweroff => "poweroff",
+            PowerAction::Reboot => "reboot",
+            PowerAction::Suspend => "suspend",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Result of a power operation after checking what actually happened
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PowerOutcome {
+    pub host: String,
+    pub action: PowerAction,
+    /// The expected state change was observed
+    pub verified: bool,
+    pub message: String,
+    pub elapsed_ms: u64,
+}
+
+/// Parse `aa:bb:cc:dd:ee:ff` or `aa-bb-cc-dd-ee-ff`
+pub fn parse_mac(mac: &str) -> Result<[u8; 6]> {
+    let parts: Vec<&str> = mac.split([':', '-']).collect();
+    if parts.len() != 6 {
+        return Err(anyhow::anyhow!("Invalid MAC address: {}", mac));
+    }
+
+    let mut bytes = [0u8; 6];
+    for (byte, part) in bytes.iter_mut().zip(parts) {
+        *byte = u8::from_str_radix(part, 16)
+            .with_context(|| format!("Invalid MAC address: {}", mac))?;
+    }
+    Ok(bytes)
+}
+
+/// Six 0xFF bytes followed by the MAC repeated sixteen times
+pub fn magic_packet(mac: &[u8; 6]) -> [u8; 102] {
+    let mut packet = [0xFFu8; 102];
+    for chunk in packet[6..].chunks_mut(6) {
+        chunk.copy_from_slice(mac);
+    }
+    packet
+}
+
+pub async fn send_magic_packet(mac: &[u8; 6], broadcast: &str) -> Result<()> {
+    let socket = UdpSocket::bind("0.0.0.0:0")
+        .await
+        .context("Failed to open UDP socket")?;
+    socket.set_broadcast(true)?;
+    socket
+        .send_to(&magic_packet(mac), (broadcast, WOL_PORT))
+        .await
+        .with_context(|| format!("Failed to send magic packet to {}", broadcast))?;
+    Ok(())
+}
+
+/// Ping `host` once a second until it answers or `timeout` passes
+pub async fn wait_until_reachable(host: &str, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        let reachable = Command::new("ping")
+            .args(["-c", "1", "-W", "1", host])
+            .output()
+            .await
+            .map(|o| o.status.success())
+            .unwrap_or(false);
+        if reachable {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+    false
+}
+
+/// Wake a configured host by its stored MAC and wait for it to answer pings
+pub async fn wake(name: &str, config: &RemoteConfig, timeout: Duration) -> Result<PowerOutcome> {
+    let entry = config
+        .hosts
+        .get(name)
+        .ok_or_else(|| anyhow::anyhow!("Unknown host '{}'; add it under [remote.hosts]", name))?;
+    let mac = entry.mac.as_deref().ok_or_else(|| {
+        anyhow::anyhow!("No MAC address for '{}'; set `mac` under [remote.hosts.{}]", name, name)
+    })?;
+    let mac = parse_mac(mac)?;
+    let broadcast = entry.wol_broadcast.as_deref().unwrap_or(DEFAULT_BROADCAST);
+
+    let started = Instant::now();
+    send_magic_packet(&mac, broadcast).await?;
+    let verified = wait_until_reachable(&entry.host, timeout).await;
+
+    Ok(PowerOutcome {
+        host: name.to_string(),
+        action: PowerAction::Wake,
+        verified,
+        message: if verified {
+            format!("{} is up", entry.host)
+        } else {
+            format!(
+                "Magic packet sent, but {} did not answer within {}s",
+                entry.host,
+                timeout.as_secs()
+            )
+        },
+        elapsed_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+/// Power off, reboot, or suspend the machine behind `executor`.
+///
+/// Remote hosts also require `remote_mutations_allowed`. Callers are
+/// responsible for asking the user first.
+pub async fn power_action(
+    executor: &CommandExecutor,
+    action: PowerAction,
+    timeout: Duration,
+) -> Result<PowerOutcome> {
+    if action == PowerAction::Wake {
+        return Err(anyhow::anyhow!("Use power::wake for Wake-on-LAN"));
+    }
+    executor.ensure_mutation_allowed(&action.to_string())?;
+
+    let started = Instant::now();
+    // --no-block queues the job and returns, so ssh exits cleanly before the link drops
+    let output = executor
+        .run_privileged("systemctl", &["--no-block", action.systemctl_verb()])
+        .await?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "systemctl {} failed on {}: {}",
+            action.systemctl_verb(),
+            executor.host_label(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let (verified, message) = match executor {
+        CommandExecutor::Ssh(target) => {
+            if wait_for_disconnect(executor, timeout).await {
+                (true, format!("{} dropped its connection", target.name))
+            } else {
+                (
+                    false,
+                    format!(
+                        "{} accepted {} but was still reachable after {}s",
+                        target.name,
+                        action,
+                        timeout.as_secs()
+                    ),
+                )
+            }
+        }
+        // We are the machine going down; the best we can confirm is the queued job
+        CommandExecutor::Local => (true, format!("{} requested", action)),
+    };
+
+    Ok(PowerOutcome {
+        host: executor.host_label(),
+        action,
+        verified,
+        message,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+/// Executor for a power target: the local machine, or a configured/ad-hoc host
+pub fn executor_for(host: Option<&str>, config: &RemoteConfig) -> CommandExecutor {
+    match host {
+        None | Some("localhost") | Some("local") => CommandExecutor::Local,
+        Some(host) => CommandExecutor::ssh(SshTarget::resolve(host, config)),
+    }
+}
+
+async fn wait_for_disconnect(executor: &CommandExecutor, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        if executor.run("true", &[]).await.is_err() {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mac() {
+        assert_eq!(
+            parse_mac("aa:bb:cc:00:11:22").unwrap(),
+            [0xaa, 0xbb, 0xcc, 0x00, 0x11, 0x22]
+        );
+        assert_eq!(parse_mac("AA-BB-CC-00-11-22").unwrap()[0], 0xaa);
+        assert!(parse_mac("aa:bb:cc").is_err());
+        assert!(parse_mac("zz:bb:cc:00:11:22").is_err());
+    }
+
+    #[test]
+    fn test_magic_packet() {
+        let mac = [1, 2, 3, 4, 5, 6];
+        let packet = magic_packet(&mac);
+        assert_eq!(&packet[..6], &[0xFF; 6]);
+        assert_eq!(&packet[6..12], &mac);
+        assert_eq!(&packet[96..], &mac);
+    }
+}
@@ -0,0 +1,496 @@
+//! Pre-flight reports for package operations
+//!
+//! Asks pacman what an install/remove/update would do (`--print`) without
+//! touching the system, and turns the answer into a [`PreflightReport`] that is
+//! shown to the user before anything is executed. Installed versions and sizes
+//! come from the shared [`PackageCache`].
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use std::process::Output;
+
+use crate::aur_comments::{self, AurComments};
+use crate::exec::{CommandRunner, SystemRunner};
+use crate::package_cache::{PackageCache, PackageIndex};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PackageAction {
+    Install,
+    Remove,
+    Update,
+}
+
+impl std::fmt::Display for PackageAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PackageAction::Install => write!(f, "install"),
+            PackageAction::Remove => write!(f, "remove"),
+            PackageAction::Update => write!(f, "update"),
+        }
+    }
+}
+
+/// Where a package comes from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PackageOrigin {
+    Repo(String),
+    Aur,
+    Local,
+}
+
+/// One package touched by the planned transaction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreflightPackage {
+    pub name: String,
+    pub old_version: Option<String>,
+    pub new_version: Option<String>,
+    pub origin: PackageOrigin,
+    pub download_bytes: u64,
+    pub installed_size_delta: i64,
+    /// Requested by the user rather than pulled in as a dependency
+    pub explicit: bool,
+}
+
+/// What a package operation would do if executed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreflightReport {
+    pub action: PackageAction,
+    pub targets: Vec<String>,
+    /// Packages that would be installed or upgraded
+    pub installs: Vec<PreflightPackage>,
+    /// Packages that would be removed, including conflict removals
+    pub removals: Vec<PreflightPackage>,
+    /// Installed packages that conflict with the transaction
+    pub conflicts: Vec<String>,
+    pub download_bytes: u64,
+    pub installed_size_delta: i64,
+    /// Packages in the transaction that are on the user's hold list
+    pub held: Vec<String>,
+    pub warnings: Vec<String>,
+    /// Pinned comments on the AUR pages of AUR packages being installed
+    #[serde(default)]
+    pub aur_comments: Vec<AurComments>,
+}
+
+impl PreflightReport {
+    /// Dependencies pulled in beyond the requested targets
+    pub fn dependencies(&self) -> Vec<&str> {
+        self.installs
+            .iter()
+            .filter(|p| !p.explicit)
+            .map(|p| p.name.as_str())
+            .collect()
+    }
+
+    /// AUR packages in the transaction
+    pub fn aur_packages(&self) -> Vec<&str> {
+        self.installs
+            .iter()
+            .filter(|p| p.origin == PackageOrigin::Aur)
+            .map(|p| p.name.as_str())
+            .collect()
+    }
+
+    /// True when the operation does more than the user literally asked for
+    pub fn needs_attention(&self) -> bool {
+        !self.held.is_empty()
+            || !self.conflicts.is_empty()
+            || !self.aur_packages().is_empty()
+            || self.removals.iter().any(|p| !p.explicit)
+    }
+
+    /// Human-readable summary for terminals and MCP text responses
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "=== Pre-flight: {} {} ===", self.action, self.targets.join(" "));
+
+        if !self.installs.is_empty() {
+            let _ = writeln!(out, "\nInstall/upgrade ({}):", self.installs.len());
+            for pkg in &self.installs {
+                let version = match (&pkg.old_version, &pkg.new_version) {
+                    (Some(old), Some(new)) => format!("{} -> {}", old, new),
+                    (None, Some(new)) => new.clone(),
+                    _ => String::new(),
+                };
+                let origin = match &pkg.origin {
+                    PackageOrigin::Repo(repo) => repo.clone(),
+                    PackageOrigin::Aur => "AUR".to_string(),
+                    PackageOrigin::Local => "local".to_string(),
+                };
+                let marker = if pkg.explicit { "" } else { " (dependency)" };
+                let _ = writeln!(out, "  • {} {} [{}]{}", pkg.name, version, origin, marker);
+            }
+        }
+
+        if !self.removals.is_empty() {
+            let _ = writeln!(out, "\nRemove ({}):", self.removals.len());
+            for pkg in &self.removals {
+                let marker = if pkg.explicit { "" } else { " (dependency/conflict)" };
+                let _ = writeln!(
+                    out,
+                    "  • {} {}{}",
+                    pkg.name,
+                    pkg.old_version.as_deref().unwrap_or(""),
+                    marker
+                );
+            }
+        }
+
+        if !self.conflicts.is_empty() {
+            let _ = writeln!(out, "\n⚠️  Conflicts: {}", self.conflicts.join(", "));
+        }
+        if !self.held.is_empty() {
+            let _ = writeln!(out, "\n🔒 On hold list: {}", self.held.join(", "));
+        }
+        let aur = self.aur_packages();
+        if !aur.is_empty() {
+            let _ = writeln!(out, "\n⚠️  From AUR (unofficial): {}", aur.join(", "));
+        }
+        for comments in &self.aur_comments {
+            let rendered = comments.render();
+            if !rendered.is_empty() {
+                let _ = write!(out, "\n{}", rendered);
+            }
+        }
+        for warning in &self.warnings {
+            let _ = writeln!(out, "\n⚠️  {}", warning);
+        }
+
+        let _ = writeln!(
+            out,
+            "\nDownload: {}  Installed size change: {}",
+            format_bytes(self.download_bytes as i64),
+            format_bytes(self.installed_size_delta)
+        );
+        out
+    }
+}
+
+/// Build a pre-flight report for `action` on `targets` without changing anything
+pub async fn preflight(action: PackageAction, targets: &[String], holds: &[String]) -> Result<PreflightReport> {
+    let installed = PackageCache::shared().index().await?;
+
+    let mut report = PreflightReport {
+        action,
+        targets: targets.to_vec(),
+        installs: Vec::new(),
+        removals: Vec::new(),
+        conflicts: Vec::new(),
+        download_bytes: 0,
+        installed_size_delta: 0,
+        held: Vec::new(),
+        warnings: Vec::new(),
+        aur_comments: Vec::new(),
+    };
+
+    match action {
+        PackageAction::Install | PackageAction::Update => {
+            let mut args = vec!["-S".to_string()];
+            if action == PackageAction::Update {
+                args.push("-u".to_string());
+            }
+            args.extend(["--print".to_string(), "--print-format".to_string(), "%n|%v|%r|%s".to_string()]);
+
+            // Targets missing from the sync databases can only come from the AUR
+            let mut repo_targets = Vec::new();
+            for target in targets {
+                if in_sync_db(target).await {
+                    repo_targets.push(target.clone());
+                } else {
+                    report.installs.push(PreflightPackage {
+                        name: target.clone(),
+                        old_version: installed.version(target).map(str::to_string),
+                        new_version: None,
+                        origin: PackageOrigin::Aur,
+                        download_bytes: 0,
+                        installed_size_delta: 0,
+                        explicit: true,
+                    });
+                }
+            }
+            if action == PackageAction::Install && repo_targets.is_empty() {
+                return Ok(with_aur_comments(finish(report, holds)).await);
+            }
+            args.extend(repo_targets.iter().cloned());
+
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            let output = pacman(&args)
+                .await
+                .context("Failed to run pacman --print")?;
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let stderr = String::from_utf8_lossy(&output.stderr);
+
+            let planned = parse_print_output(&stdout);
+            let names: Vec<String> = planned.iter().map(|p| p.name.clone()).collect();
+            let new_sizes = installed_sizes("-Si", &names).await;
+
+            for mut pkg in planned {
+                pkg.old_version = installed.version(&pkg.name).map(str::to_string);
+                pkg.explicit = action == PackageAction::Update || targets.contains(&pkg.name);
+                pkg.installed_size_delta = *new_sizes.get(&pkg.name).unwrap_or(&0) as i64
+                    - installed_size(&installed, &pkg.name) as i64;
+                report.installs.push(pkg);
+            }
+
+            for (_, other) in parse_conflicts(&stderr) {
+                if installed.contains(&other) && !report.conflicts.contains(&other) {
+                    report.conflicts.push(other.clone());
+                    report.removals.push(PreflightPackage {
+                        old_version: installed.version(&other).map(str::to_string),
+                        installed_size_delta: -(installed_size(&installed, &other) as i64),
+                        name: other,
+                        new_version: None,
+                        origin: PackageOrigin::Local,
+                        download_bytes: 0,
+                        explicit: false,
+                    });
+                }
+            }
+
+            if !output.status.success() && report.conflicts.is_empty() {
+                report.warnings.push(format!("pacman reported: {}", stderr.trim()));
+            }
+        }
+        PackageAction::Remove => {
+            let mut args = vec!["-Rns", "--print", "--print-format", "%n|%v"];
+            args.extend(targets.iter().map(String::as_str));
+            let output = pacman(&args)
+                .await
+                .context("Failed to run pacman -Rns --print")?;
+            let stdout = String::from_utf8_lossy(&output.stdout);
+
+            for line in stdout.lines() {
+                let mut parts = line.split('|');
+                let (Some(name), version) = (parts.next(), parts.next()) else {
+                    continue;
+                };
+                let name = name.trim().to_string();
+                if name.is_empty() {
+                    continue;
+                }
+                report.removals.push(PreflightPackage {
+                    old_version: version.map(|v| v.trim().to_string()),
+                    new_version: None,
+                    origin: PackageOrigin::Local,
+                    download_bytes: 0,
+                    installed_size_delta: -(installed_size(&installed, &name) as i64),
+                    explicit: targets.contains(&name),
+                    name,
+                });
+            }
+
+            if !output.status.success() {
+                report.warnings.push(format!(
+                    "pacman reported: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ));
+            }
+        }
+    }
+
+    Ok(with_aur_comments(finish(report, holds)).await)
+}
+
+/// Attach the pinned AUR comments of the AUR packages being installed, which
+/// is where maintainers post install-time warnings
+async fn with_aur_comments(mut report: PreflightReport) -> PreflightReport {
+    let packages: Vec<String> = report.aur_packages().into_iter().map(str::to_string).collect();
+    for package in packages {
+        report.aur_comments.push(aur_comments::pinned_comments(&package).await);
+    }
+    report
+}
+
+fn finish(mut report: PreflightReport, holds: &[String]) -> PreflightReport {
+    let hold_set: HashSet<&str> = holds.iter().map(|h| h.as_str()).collect();
+    report.held = report
+        .installs
+        .iter()
+        .chain(report.removals.iter())
+        .filter(|p| hold_set.contains(p.name.as_str()))
+        .map(|p| p.name.clone())
+        .collect();
+    report.download_bytes = report.installs.iter().map(|p| p.download_bytes).sum();
+    report.installed_size_delta = report
+        .installs
+        .iter()
+        .chain(report.removals.iter())
+        .map(|p| p.installed_size_delta)
+        .sum();
+    report
+}
+
+/// Parse `--print-format "%n|%v|%r|%s"` output
+pub fn parse_print_output(output: &str) -> Vec<PreflightPackage> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.trim().split('|').collect();
+            if parts.len() < 4 {
+                return None;
+            }
+            Some(PreflightPackage {
+                name: parts[0].to_string(),
+                old_version: None,
+                new_version: Some(parts[1].to_string()),
+                origin: PackageOrigin::Repo(parts[2].to_string()),
+                download_bytes: parts[3].parse().unwrap_or(0),
+                installed_size_delta: 0,
+                explicit: false,
+            })
+        })
+        .collect()
+}
+
+/// Parse ":: foo and bar are in conflict" lines into (incoming, installed) pairs
+pub fn parse_conflicts(output: &str) -> Vec<(String, String)> {
+    let Ok(re) = Regex::new(r"::\s+(\S+?)(?:-[^\s-]+-[^\s-]+)?\s+and\s+(\S+?)(?:-[^\s-]+-[^\s-]+)?\s+are in conflict") else {
+        return Vec::new();
+    };
+    re.captures_iter(output)
+        .map(|c| (c[1].to_string(), c[2].to_string()))
+        .collect()
+}
+
+/// Parse "Name" / "Installed Size" pairs from `pacman -Si`/`-Qi` output
+pub fn parse_installed_sizes(output: &str) -> HashMap<String, u64> {
+    let mut sizes = HashMap::new();
+    let mut current: Option<String> = None;
+
+    for line in output.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        match key.trim() {
+            "Name" => current = Some(value.trim().to_string()),
+            "Installed Size" => {
+                if let (Some(name), Some(bytes)) = (current.take(), parse_size(value.trim())) {
+                    sizes.insert(name, bytes);
+                }
+            }
+            _ => {}
+        }
+    }
+    sizes
+}
+
+/// Parse pacman's human-readable sizes, e.g. "12.34 MiB"
+pub fn parse_size(value: &str) -> Option<u64> {
+    let mut parts = value.split_whitespace();
+    let number: f64 = parts.next()?.parse().ok()?;
+    let multiplier = match parts.next().unwrap_or("B") {
+        "B" => 1.0,
+        "KiB" => 1024.0,
+        "MiB" => 1024.0 * 1024.0,
+        "GiB" => 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    Some((number * multiplier) as u64)
+}
+
+fn format_bytes(bytes: i64) -> String {
+    let sign = if bytes < 0 { "-" } else { "" };
+    let abs = bytes.unsigned_abs() as f64;
+    if abs >= 1024.0 * 1024.0 * 1024.0 {
+        format!("{}{:.2} GiB", sign, abs / (1024.0 * 1024.0 * 1024.0))
+    } else if abs >= 1024.0 * 1024.0 {
+        format!("{}{:.2} MiB", sign, abs / (1024.0 * 1024.0))
+    } else if abs >= 1024.0 {
+        format!("{}{:.2} KiB", sign, abs / 1024.0)
+    } else {
+        format!("{}{} B", sign, abs)
+    }
+}
+
+fn installed_size(installed: &PackageIndex, name: &str) -> u64 {
+    installed
+        .get(name)
+        .map_or(0, |package| package.installed_size)
+}
+
+async fn installed_sizes(flag: &str, names: &[String]) -> HashMap<String, u64> {
+    if names.is_empty() {
+        return HashMap::new();
+    }
+    let mut args = vec![flag];
+    args.extend(names.iter().map(String::as_str));
+    match pacman(&args).await {
+        Ok(output) => parse_installed_sizes(&String::from_utf8_lossy(&output.stdout)),
+        Err(e) => {
+            tracing::warn!("Failed to query package sizes: {}", e);
+            HashMap::new()
+        }
+    }
+}
+
+async fn in_sync_db(package: &str) -> bool {
+    pacman(&["-Si", package])
+        .await
+        .map(|o| o.status.success())
+        .unwrap_or(false)
+}
+
+/// Run pacman through `SystemRunner`, whose C locale keeps the English
+/// labels and sizes the parsers here expect
+async fn pacman(args: &[&str]) -> Result<Output> {
+    SystemRunner::default().output("pacman", args).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_print_output() {
+        let output = "neovim|0.10.0-1|extra|7340032\nlibvterm|0.3.3-1|extra|40960\n";
+        let packages = parse_print_output(output);
+
+        assert_eq!(packages.len(), 2);
+        assert_eq!(packages[0].name, "neovim");
+        assert_eq!(packages[0].origin, PackageOrigin::Repo("extra".to_string()));
+        assert_eq!(packages[1].download_bytes, 40960);
+    }
+
+    #[test]
+    fn test_parse_conflicts() {
+        let stderr = ":: iptables-nft-1:1.8.10-1 and iptables-1:1.8.10-1 are in conflict. Remove iptables? [y/N]";
+        let conflicts = parse_conflicts(stderr);
+        assert_eq!(conflicts, vec![("iptables-nft".to_string(), "iptables".to_string())]);
+    }
+
+    #[test]
+    fn test_parse_installed_sizes() {
+        let output = "Name            : neovim\nVersion         : 0.10.0-1\nInstalled Size  : 25.50 MiB\n\n\
+                      Name            : libvterm\nInstalled Size  : 120.00 KiB\n";
+        let sizes = parse_installed_sizes(output);
+
+        assert_eq!(sizes["neovim"], (25.5 * 1024.0 * 1024.0) as u64);
+        assert_eq!(sizes["libvterm"], 120 * 1024);
+    }
+
+    #[test]
+    fn test_held_packages_flagged() {
+        let report = PreflightReport {
+            action: PackageAction::Update,
+            targets: vec![],
+            installs: parse_print_output("linux|6.9.1-1|core|140000000\n"),
+            removals: vec![],
+            conflicts: vec![],
+            download_bytes: 0,
+            installed_size_delta: 0,
+            held: vec![],
+            warnings: vec![],
+            aur_comments: vec![],
+        };
+        let report = finish(report, &["linux".to_string()]);
+
+        assert_eq!(report.held, vec!["linux".to_string()]);
+        assert_eq!(report.download_bytes, 140000000);
+        assert!(report.needs_attention());
+    }
+}
@@ -0,0 +1,596 @@
+//! Privilege elevation for operations that need root
+//!
+//! Operations declare the [`Capability`] they need root for, and
+//! [`ElevatingRunner`] runs the matching commands the way the host's
+//! [`Strategy`] allows: directly when Jarvis already runs as root, through
+//! `sudo -n` against a narrow sudoers rule, through `pkexec` when the Jarvis
+//! polkit policy is installed, or not at all. A refused command fails fast
+//! with [`NotPermitted`], which says how to allow it, instead of waiting on a
+//! password prompt nobody will answer.
+//!
+//! The commands a capability covers are the patterns its sudoers rule lists,
+//! so a rule from [`sudoers`] allows exactly what the runner elevates.
+
+use crate::exec::{CommandRunner, RunOptions};
+use crate::file_access::name_matches;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+use std::sync::Arc;
+
+const SUDO: &str = "/usr/bin/sudo";
+const PKEXEC: &str = "/usr/bin/pkexec";
+
+/// What `sudo -n` prints instead of prompting, or when no rule matches
+const SUDO_REFUSALS: &[&str] = &[
+    "a password is required",
+    "a terminal is required",
+    "is not allowed to execute",
+];
+
+/// pkexec's exit codes for a refused or failed authorization
+const PKEXEC_REFUSED: &[i32] = &[126, 127];
+
+/// Stands in for the arguments of a pattern when asking sudo about it
+const PROBE_ARGUMENT: &str = "jarvis-doctor-probe";
+
+/// What an operation needs root for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// Package transactions, cache cleanup, and the mirrorlist
+    Packages,
+    /// Starting, stopping, enabling, and disabling units
+    Services,
+    /// Reading the whole system journal and vacuuming it
+    Journal,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 3] = [
+        Capability::Packages,
+        Capability::Services,
+        Capability::Journal,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Capability::Packages => "packages",
+            Capability::Services => "services",
+            Capability::Journal => "journal",
+        }
+    }
+
+    /// e.g. "package operations", for messages
+    pub fn describe(self) -> &'static str {
+        match self {
+            Capability::Packages => "package operations",
+            Capability::Services => "service operations",
+            Capability::Journal => "journal access",
+        }
+    }
+
+    /// The commands the capability covers, as sudoers patterns where `*`
+    /// matches any arguments
+    pub fn commands(self) -> &'static [&'static str] {
+        match self {
+            Capability::Packages => &[
+                "/usr/bin/pacman -S *",
+                "/usr/bin/pacman -Syu --noconfirm",
+                "/usr/bin/pacman -Syuw --noconfirm",
+                "/usr/bin/pacman -R *",
+                "/usr/bin/pacman -Rs *",
+                "/usr/bin/pacman -Sc",
+                "/usr/bin/pacman -Sc *",
+                "/usr/bin/pacman -Fy",
+                "/usr/bin/rm -f /var/lib/pacman/db.lck",
+                "/usr/bin/paccache -r",
+                "/usr/bin/reflector * --save /etc/pacman.d/mirrorlist",
+            ],
+            Capability::Services => &[
+                "/usr/bin/systemctl start *",
+                "/usr/bin/systemctl stop *",
+                "/usr/bin/systemctl restart *",
+                "/usr/bin/systemctl reload *",
+                "/usr/bin/systemctl enable *",
+                "/usr/bin/systemctl disable *",
+            ],
+            Capability::Journal => &[
+                "/usr/bin/journalctl --vacuum-time=*",
+                "/usr/bin/journalctl --vacuum-size=*",
+                "/usr/bin/journalctl --no-pager *",
+            ],
+        }
+    }
+
+    /// The capability whose commands include this one, if any
+    pub fn of_command(program: &str, args: &[&str]) -> Option<Capability> {
+        let line = std::iter::once(absolute(program).as_str())
+            .chain(args.iter().copied())
+            .collect::<Vec<_>>()
+            .join(" ");
+        Capability::ALL
+            .into_iter()
+            .find(|capability| capability.commands().iter().any(|p| name_matches(p, &line)))
+    }
+
+    /// A sudoers rule letting `user` run the capability's commands, and
+    /// nothing else, without a password
+    pub fn sudoers_rule(self, user: &str) -> String {
+        let alias = format!("JARVIS_{}", self.name().to_uppercase());
+        let commands = self
+            .commands()
+            .iter()
+            .map(|command| escape_sudoers(command))
+            .collect::<Vec<_>>()
+            .join(", \\\n    ");
+        format!(
+            "# {}\nCmnd_Alias {} = \\\n    {}\n{} ALL=(root) NOPASSWD: {}\n",
+            self.describe(),
+            alias,
+            commands,
+            user,
+            alias
+        )
+    }
+
+    /// A command sudo can be asked about with `sudo -l`, e.g.
+    /// `/usr/bin/pacman -S jarvis-doctor-probe`
+    fn probe(self) -> Vec<String> {
+        self.commands()[0]
+            .replace('*', PROBE_ARGUMENT)
+            .split_whitespace()
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A sudoers.d file with one rule per capability, for `jarvis doctor --sudoers`
+pub fn sudoers(user: &str, capabilities: &[Capability]) -> String {
+    let mut file = format!(
+        "# /etc/sudoers.d/jarvis: what {} may run as root, and nothing more\n\
+         # Check with `visudo -cf <file>`, then install it with mode 0440.\n\
+         # Package scripts run as root, so the package rule still trusts\n\
+         # whoever controls jarvis; leave it out to keep jarvis read-only there.\n",
+        user
+    );
+    for capability in capabilities {
+        file.push('\n');
+        file.push_str(&capability.sudoers_rule(user));
+    }
+    file
+}
+
+/// Escape the characters sudoers treats specially inside command arguments
+fn escape_sudoers(command: &str) -> String {
+    let mut escaped = String::with_capacity(command.len());
+    for c in command.chars() {
+        if matches!(c, '\\' | ',' | ':' | '=') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Commands without a path are the ones in /usr/bin, where Arch keeps them
+/// all; sudoers rules and polkit actions name full paths
+fn absolute(program: &str) -> String {
+    if program.starts_with('/') {
+        program.to_string()
+    } else {
+        format!("/usr/bin/{}", program)
+    }
+}
+
+/// How commands that need root get it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategy {
+    /// Jarvis runs as root; nothing to elevate
+    Root,
+    /// `sudo -n`, so a missing rule fails instead of prompting
+    Sudo,
+    /// `pkexec`, authorized by the Jarvis polkit policy and rules
+    Polkit,
+    /// Nothing that needs root runs
+    ReadOnly,
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Strategy::Root => "root",
+            Strategy::Sudo => "sudo",
+            Strategy::Polkit => "polkit",
+            Strategy::ReadOnly => "read_only",
+        })
+    }
+}
+
+/// The configured strategy; `auto` picks root, polkit, sudo, then read-only
+/// in that order, depending on what the host has
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrivilegeMode {
+    #[default]
+    Auto,
+    Sudo,
+    Polkit,
+    ReadOnly,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PrivilegeConfig {
+    #[serde(default)]
+    pub strategy: PrivilegeMode,
+    /// The Jarvis polkit policy; `auto` uses polkit when it is installed
+    #[serde(default = "default_polkit_policy")]
+    pub polkit_policy: PathBuf,
+}
+
+fn default_polkit_policy() -> PathBuf {
+    PathBuf::from("/usr/share/polkit-1/actions/org.ghostkellz.jarvis.policy")
+}
+
+impl Default for PrivilegeConfig {
+    fn default() -> Self {
+        Self {
+            strategy: PrivilegeMode::default(),
+            polkit_policy: default_polkit_policy(),
+        }
+    }
+}
+
+/// Whether this process runs with an effective uid of 0
+pub fn running_as_root() -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    // /proc/self belongs to the effective uid of the process
+    std::fs::metadata("/proc/self")
+        .map(|meta| meta.uid() == 0)
+        .unwrap_or(false)
+}
+
+/// Whether the no_new_privs flag is set (e.g. by systemd's
+/// `NoNewPrivileges=`), which stops sudo and pkexec from gaining root
+fn no_new_privileges() -> bool {
+    std::fs::read_to_string("/proc/self/status").is_ok_and(|status| {
+        status
+            .lines()
+            .any(|line| line.split_whitespace().collect::<Vec<_>>() == ["NoNewPrivs:", "1"])
+    })
+}
+
+/// The strategy in effect on this host, and why it was picked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Privilege {
+    strategy: Strategy,
+    reason: String,
+}
+
+impl Privilege {
+    pub fn new(strategy: Strategy, reason: impl Into<String>) -> Self {
+        Self {
+            strategy,
+            reason: reason.into(),
+        }
+    }
+
+    /// Pick the strategy `config` asks for, or for `auto` the best one the
+    /// host supports
+    pub fn detect(config: &PrivilegeConfig) -> Self {
+        if config.strategy == PrivilegeMode::ReadOnly {
+            return Self::new(Strategy::ReadOnly, "configured read-only");
+        }
+        if running_as_root() {
+            return Self::new(Strategy::Root, "running as root");
+        }
+        if no_new_privileges() {
+            return Self::new(
+                Strategy::ReadOnly,
+                "no_new_privs is set (NoNewPrivileges=), so sudo and pkexec can't gain root",
+            );
+        }
+        match config.strategy {
+            PrivilegeMode::Sudo => Self::new(Strategy::Sudo, "configured"),
+            PrivilegeMode::Polkit => Self::new(Strategy::Polkit, "configured"),
+            _ if config.polkit_policy.exists() && Path::new(PKEXEC).exists() => Self::new(
+                Strategy::Polkit,
+                format!(
+                    "polkit policy installed at {}",
+                    config.polkit_policy.display()
+                ),
+            ),
+            _ if Path::new(SUDO).exists() => Self::new(Strategy::Sudo, "sudo is installed"),
+            _ => Self::new(
+                Strategy::ReadOnly,
+                "neither sudo nor the polkit policy is installed",
+            ),
+        }
+    }
+
+    pub fn strategy(&self) -> Strategy {
+        self.strategy
+    }
+
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+
+    /// How an operation needing `capabilities` will run, or why it can't
+    pub fn elevation(&self, capabilities: &[Capability]) -> Result<Elevation, NotPermitted> {
+        if let (Some(&capability), Strategy::ReadOnly) = (capabilities.first(), self.strategy) {
+            return Err(NotPermitted {
+                capability,
+                strategy: self.strategy,
+                command: None,
+                reason: self.reason.clone(),
+            });
+        }
+        Ok(Elevation {
+            strategy: self.strategy,
+            capabilities: capabilities.to_vec(),
+            elevated: !capabilities.is_empty()
+                && matches!(self.strategy, Strategy::Sudo | Strategy::Polkit),
+        })
+    }
+}
+
+/// How an operation ran, for its result metadata and the audit log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Elevation {
+    pub strategy: Strategy,
+    /// What the operation needed root for; empty if nothing
+    pub capabilities: Vec<Capability>,
+    /// Whether its commands ran through sudo or pkexec
+    pub elevated: bool,
+}
+
+/// A command that needs root was refused, or would have been
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotPermitted {
+    pub capability: Capability,
+    pub strategy: Strategy,
+    /// The refused command line, if one was tried
+    pub command: Option<String>,
+    /// Why the strategy is in effect
+    pub reason: String,
+}
+
+impl NotPermitted {
+    /// The elevation recorded for an operation that was refused
+    pub fn elevation(&self) -> Elevation {
+        Elevation {
+            strategy: self.strategy,
+            capabilities: vec![self.capability],
+            elevated: false,
+        }
+    }
+}
+
+impl fmt::Display for NotPermitted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let command = self.command.as_deref().unwrap_or("the command");
+        match self.strategy {
+            Strategy::Sudo => write!(
+                f,
+                "sudo refused `{}` without a password; allow {} with the sudoers rule `jarvis doctor --sudoers` prints, or run jarvis as root",
+                command,
+                self.capability.describe()
+            ),
+            Strategy::Polkit => write!(
+                f,
+                "polkit did not authorize `{}`; check that the jarvis rules in /etc/polkit-1/rules.d cover this user",
+                command
+            ),
+            Strategy::Root => write!(f, "`{}` was refused even as root", command),
+            Strategy::ReadOnly => write!(
+                f,
+                "{} need root and jarvis is read-only here ({}); run it as root, install the polkit policy, or add the sudoers rule `jarvis doctor --sudoers` prints",
+                self.capability.describe(),
+                self.reason
+            ),
+        }
+    }
+}
+
+impl std::error::Error for NotPermitted {}
+
+/// Runs commands through `inner`, elevating those a [`Capability`] covers
+#[derive(Debug)]
+pub struct ElevatingRunner {
+    inner: Arc<dyn CommandRunner>,
+    privilege: Privilege,
+}
+
+impl ElevatingRunner {
+    pub fn new(inner: Arc<dyn CommandRunner>, privilege: Privilege) -> Self {
+        Self { inner, privilege }
+    }
+}
+
+#[async_trait]
+impl CommandRunner for ElevatingRunner {
+    async fn run(&self, program: &str, args: &[&str], options: &RunOptions) -> Result<Output> {
+        let Some(capability) = Capability::of_command(program, args) else {
+            return self.inner.run(program, args, options).await;
+        };
+        let program = absolute(program);
+        let command = std::iter::once(program.as_str())
+            .chain(args.iter().copied())
+            .collect::<Vec<_>>();
+        let refused = || NotPermitted {
+            capability,
+            strategy: self.privilege.strategy,
+            command: Some(command.join(" ")),
+            reason: self.privilege.reason.clone(),
+        };
+
+        match self.privilege.strategy {
+            Strategy::Root => self.inner.run(&program, args, options).await,
+            Strategy::ReadOnly => Err(refused().into()),
+            Strategy::Sudo => {
+                let sudo_args: Vec<&str> = std::iter::once("-n")
+                    .chain(command.iter().copied())
+                    .collect();
+                let output = self.inner.run(SUDO, &sudo_args, options).await?;
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                if !output.status.success() && SUDO_REFUSALS.iter().any(|r| stderr.contains(r)) {
+                    return Err(refused().into());
+                }
+                Ok(output)
+            }
+            Strategy::Polkit => {
+                let output = self.inner.run(PKEXEC, &command, options).await?;
+                if output
+                    .status
+                    .code()
+                    .is_some_and(|code| PKEXEC_REFUSED.contains(&code))
+                {
+                    return Err(refused().into());
+                }
+                Ok(output)
+            }
+        }
+    }
+}
+
+/// Whether sudo would run the capability's commands without a password,
+/// asked with `sudo -n -l` so nothing is run
+pub async fn sudo_allows(runner: &dyn CommandRunner, capability: Capability) -> bool {
+    let probe = capability.probe();
+    let args: Vec<&str> = ["-n", "-l"]
+        .into_iter()
+        .chain(probe.iter().map(String::as_str))
+        .collect();
+    runner
+        .output(SUDO, &args)
+        .await
+        .is_ok_and(|output| output.status.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::{RecordingRunner, fake_output};
+
+    #[test]
+    fn test_commands_map_to_the_capability_their_rule_covers() {
+        let of = |line: &str| {
+            let mut words = line.split(' ');
+            let program = words.next().unwrap();
+            Capability::of_command(program, &words.collect::<Vec<_>>())
+        };
+        assert_eq!(
+            of("pacman -S --noconfirm ripgrep"),
+            Some(Capability::Packages)
+        );
+        assert_eq!(
+            of("/usr/bin/pacman -Syuw --noconfirm"),
+            Some(Capability::Packages)
+        );
+        assert_eq!(
+            of("reflector --latest 20 --sort rate --save /etc/pacman.d/mirrorlist"),
+            Some(Capability::Packages)
+        );
+        assert_eq!(
+            of("rm -f /var/lib/pacman/db.lck"),
+            Some(Capability::Packages)
+        );
+        assert_eq!(of("systemctl restart nginx"), Some(Capability::Services));
+        assert_eq!(
+            of("journalctl --vacuum-time=2weeks"),
+            Some(Capability::Journal)
+        );
+
+        // Queries run as the caller
+        assert_eq!(of("/usr/bin/pacman -Qi nano"), None);
+        assert_eq!(of("/usr/bin/pacman -Sup --print-format %n"), None);
+        assert_eq!(of("reflector --latest 20 --sort rate"), None);
+        assert_eq!(of("systemctl status nginx"), None);
+    }
+
+    #[test]
+    fn test_sudoers_rules_list_commands_not_all() {
+        let file = sudoers("jarvis", &[Capability::Services, Capability::Journal]);
+        assert!(
+            file.contains("Cmnd_Alias JARVIS_SERVICES = \\\n    /usr/bin/systemctl start *, \\\n")
+        );
+        assert!(file.contains("jarvis ALL=(root) NOPASSWD: JARVIS_JOURNAL\n"));
+        assert!(file.contains("/usr/bin/journalctl --vacuum-time\\=*"));
+        assert!(!file.contains("JARVIS_PACKAGES"));
+        assert!(!file.contains("NOPASSWD: ALL"));
+    }
+
+    #[test]
+    fn test_read_only_refuses_operations_that_need_root() {
+        let read_only = Privilege::new(Strategy::ReadOnly, "configured read-only");
+        let refused = read_only.elevation(&[Capability::Packages]).unwrap_err();
+        assert_eq!(refused.capability, Capability::Packages);
+        assert!(refused.to_string().contains("jarvis doctor --sudoers"));
+        assert!(!read_only.elevation(&[]).unwrap().elevated);
+
+        let sudo = Privilege::new(Strategy::Sudo, "sudo is installed");
+        assert!(sudo.elevation(&[Capability::Services]).unwrap().elevated);
+        assert!(!sudo.elevation(&[]).unwrap().elevated);
+        let root = Privilege::new(Strategy::Root, "running as root");
+        assert!(!root.elevation(&[Capability::Services]).unwrap().elevated);
+    }
+
+    #[tokio::test]
+    async fn test_sudo_refusals_fail_fast_and_queries_are_not_elevated() {
+        let inner = Arc::new(RecordingRunner::new().respond_with(
+            "/usr/bin/sudo -n /usr/bin/pacman -R",
+            fake_output(1, "", "sudo: a password is required\n"),
+        ));
+        let runner =
+            ElevatingRunner::new(inner.clone(), Privilege::new(Strategy::Sudo, "configured"));
+
+        runner
+            .output("/usr/bin/pacman", &["-Qi", "nano"])
+            .await
+            .unwrap();
+        runner
+            .output("systemctl", &["restart", "nginx"])
+            .await
+            .unwrap();
+        let error = runner
+            .output("/usr/bin/pacman", &["-R", "nano"])
+            .await
+            .unwrap_err();
+        let refused = error.downcast_ref::<NotPermitted>().unwrap();
+        assert_eq!(refused.command.as_deref(), Some("/usr/bin/pacman -R nano"));
+        assert_eq!(
+            inner.calls(),
+            vec![
+                "/usr/bin/pacman -Qi nano",
+                "/usr/bin/sudo -n /usr/bin/systemctl restart nginx",
+                "/usr/bin/sudo -n /usr/bin/pacman -R nano",
+            ]
+        );
+
+        // Read-only never spawns what it would have to elevate
+        let inner = Arc::new(RecordingRunner::new());
+        let runner = ElevatingRunner::new(
+            inner.clone(),
+            Privilege::new(Strategy::ReadOnly, "configured"),
+        );
+        assert!(
+            runner
+                .output("pacman", &["-Syu", "--noconfirm"])
+                .await
+                .is_err()
+        );
+        assert!(inner.calls().is_empty());
+    }
+}
@@ -0,0 +1,613 @@
+//! Read-only observer mode
+//!
+//! With `read_only = true` in the config, `--read-only`, or
+//! `JARVIS_READ_ONLY=1`, Jarvis inspects, scans, analyzes, and advises but
+//! changes nothing. The mode is enforced where commands are spawned:
+//! [`SystemRunner`](crate::exec::SystemRunner), SSH, and plugin tools call
+//! [`check_command`] first, so a command this module classifies as mutating
+//! fails with [`JarvisError::ReadOnlyMode`] before any process starts.
+//! Agent operations and MCP tool actions that change the system are refused
+//! up front by their own classification, so the refusal names what was
+//! asked for rather than its first command.
+//!
+//! This is unrelated to the privilege strategy of the same name, which only
+//! means Jarvis has no way to become root on this host.
+
+use crate::error::JarvisError;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// What language models are told while the mode is on, so they advise
+/// instead of offering to act
+pub const PROMPT_NOTE: &str = "Jarvis is in read-only mode on this machine: it can inspect \
+everything but change nothing. When a change is needed, give the exact command for the user to \
+run themselves instead of offering to run it.";
+
+/// Apply `read_only` from the config process-wide; `JARVIS_READ_ONLY` also
+/// turns the mode on
+pub fn configure(read_only: bool) {
+    let read_only =
+        read_only || std::env::var("JARVIS_READ_ONLY").is_ok_and(|v| !v.is_empty() && v != "0");
+    if read_only {
+        tracing::info!("Read-only mode: nothing on this machine will be changed");
+    }
+    set_read_only(read_only);
+}
+
+/// Turn read-only mode on for this process, e.g. for `--read-only`
+pub fn set_read_only(read_only: bool) {
+    READ_ONLY.store(read_only, Ordering::SeqCst);
+}
+
+pub fn is_read_only() -> bool {
+    READ_ONLY.load(Ordering::SeqCst)
+}
+
+/// The error for refusing to do `what`, e.g. "apply fixes" or "run InstallPackage"
+pub fn refusal(what: &str) -> JarvisError {
+    JarvisError::ReadOnlyMode(format!(
+        "refusing to {} because it would change the system; run it yourself if you mean to, \
+         or turn off read_only to let Jarvis do it",
+        what
+    ))
+}
+
+/// Refuse to do `what`, which changes the system, while the mode is on
+pub fn check(what: &str) -> Result<(), JarvisError> {
+    if is_read_only() {
+        return Err(refusal(what));
+    }
+    Ok(())
+}
+
+/// Refuse a mutating command line while the mode is on
+pub fn check_command(program: &str, args: &[&str]) -> Result<(), JarvisError> {
+    if is_read_only() && is_mutating_command(program, args) {
+        let line = std::iter::once(program)
+            .chain(args.iter().copied())
+            .collect::<Vec<_>>()
+            .join(" ");
+        return Err(refusal(&format!("run `{}`", line)));
+    }
+    Ok(())
+}
+
+/// Programs that change the system whatever their arguments
+const MUTATING_PROGRAMS: &[&str] = &[
+    "rm",
+    "rmdir",
+    "mv",
+    "cp",
+    "dd",
+    "tee",
+    "install",
+    "ln",
+    "mkdir",
+    "touch",
+    "truncate",
+    "shred",
+    "chmod",
+    "chown",
+    "chgrp",
+    "mkfs",
+    "mount",
+    "umount",
+    "kill",
+    "pkill",
+    "killall",
+    "reboot",
+    "poweroff",
+    "shutdown",
+    "halt",
+    "useradd",
+    "userdel",
+    "usermod",
+    "groupadd",
+    "passwd",
+    "makepkg",
+    "pacstrap",
+    "pacman-key",
+    "wakeonlan",
+    "etherwake",
+    "rtcwake",
+    "systemd-nspawn",
+];
+
+/// Subcommands that change the system, for programs whose first
+/// non-option argument says what they do
+const MUTATING_SUBCOMMANDS: &[(&str, &[&str])] = &[
+    (
+        "systemctl",
+        &[
+            "start",
+            "stop",
+            "restart",
+            "reload",
+            "try-restart",
+            "reload-or-restart",
+            "try-reload-or-restart",
+            "enable",
+            "disable",
+            "reenable",
+            "mask",
+            "unmask",
+            "link",
+            "preset",
+            "preset-all",
+            "revert",
+            "edit",
+            "set-property",
+            "set-default",
+            "isolate",
+            "kill",
+            "reset-failed",
+            "daemon-reload",
+            "daemon-reexec",
+            "poweroff",
+            "reboot",
+            "halt",
+            "kexec",
+            "suspend",
+            "hibernate",
+            "hybrid-sleep",
+            "suspend-then-hibernate",
+        ],
+    ),
+    (
+        "docker",
+        &[
+            "start",
+            "stop",
+            "restart",
+            "kill",
+            "pause",
+            "unpause",
+            "rm",
+            "rmi",
+            "run",
+            "create",
+            "exec",
+            "pull",
+            "push",
+            "build",
+            "commit",
+            "cp",
+            "load",
+            "import",
+            "tag",
+            "rename",
+            "update",
+            "prune",
+            "up",
+            "down",
+            "connect",
+            "disconnect",
+        ],
+    ),
+    (
+        "podman",
+        &[
+            "start",
+            "stop",
+            "restart",
+            "kill",
+            "pause",
+            "unpause",
+            "rm",
+            "rmi",
+            "run",
+            "create",
+            "exec",
+            "pull",
+            "push",
+            "build",
+            "commit",
+            "cp",
+            "load",
+            "import",
+            "tag",
+            "rename",
+            "update",
+            "prune",
+            "up",
+            "down",
+            "connect",
+            "disconnect",
+        ],
+    ),
+    (
+        "virsh",
+        &[
+            "start",
+            "shutdown",
+            "destroy",
+            "reboot",
+            "reset",
+            "suspend",
+            "resume",
+            "undefine",
+            "define",
+            "create",
+            "autostart",
+            "setmem",
+            "setvcpus",
+            "managedsave",
+            "snapshot-create",
+            "snapshot-create-as",
+            "snapshot-revert",
+            "snapshot-delete",
+        ],
+    ),
+    (
+        "flatpak",
+        &[
+            "install",
+            "update",
+            "upgrade",
+            "uninstall",
+            "remove",
+            "repair",
+            "remote-add",
+            "remote-delete",
+            "remote-modify",
+            "override",
+            "mask",
+            "pin",
+        ],
+    ),
+    (
+        "snapper",
+        &[
+            "create",
+            "delete",
+            "modify",
+            "undochange",
+            "rollback",
+            "cleanup",
+            "create-config",
+        ],
+    ),
+    (
+        "btrfs",
+        &["scrub", "balance", "subvolume", "property", "device"],
+    ),
+    (
+        "nft",
+        &[
+            "add", "insert", "delete", "flush", "replace", "create", "destroy", "-f",
+        ],
+    ),
+    (
+        "ip",
+        &["add", "del", "delete", "set", "flush", "change", "replace"],
+    ),
+];
+
+/// Options that take a value, which must not be mistaken for a subcommand,
+/// e.g. `root` in `snapper -c root list`
+const VALUE_OPTIONS: &[(&str, &[&str])] = &[
+    (
+        "systemctl",
+        &[
+            "-t",
+            "--type",
+            "-p",
+            "--property",
+            "-H",
+            "--host",
+            "-M",
+            "--machine",
+            "-s",
+            "--signal",
+            "--state",
+            "-o",
+            "--output",
+            "-n",
+            "--lines",
+            "--root",
+        ],
+    ),
+    (
+        "docker",
+        &[
+            "-H",
+            "--host",
+            "-c",
+            "--context",
+            "-l",
+            "--log-level",
+            "--config",
+            "-f",
+            "--file",
+            "-p",
+            "--project-name",
+            "--profile",
+            "--env-file",
+        ],
+    ),
+    (
+        "podman",
+        &[
+            "-H",
+            "--host",
+            "-c",
+            "--context",
+            "-l",
+            "--log-level",
+            "--config",
+            "-f",
+            "--file",
+            "-p",
+            "--project-name",
+            "--profile",
+            "--env-file",
+        ],
+    ),
+    (
+        "virsh",
+        &["-c", "--connect", "-l", "--log", "-e", "--escape"],
+    ),
+    ("snapper", &["-c", "--config", "-r", "--root"]),
+    ("ip", &["-n", "-netns", "-f", "-family"]),
+];
+
+/// Docker and podman group commands by object, e.g. `docker container rm`
+const CONTAINER_OBJECTS: &[&str] = &[
+    "container",
+    "image",
+    "volume",
+    "network",
+    "system",
+    "compose",
+    "builder",
+];
+
+/// Read-only subcommands of `btrfs` groups that otherwise change things
+const BTRFS_READS: &[&str] = &["status", "show", "list", "get", "usage", "stats"];
+
+/// Whether running `program` with `args` could change the system
+pub fn is_mutating_command(program: &str, args: &[&str]) -> bool {
+    let program = Path::new(program)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(program);
+    if MUTATING_PROGRAMS.contains(&program) {
+        return true;
+    }
+
+    match program {
+        // Elevation runs the command that follows; `sudo -l` only lists rules
+        "sudo" | "doas" | "pkexec" | "run0" => match wrapped_command(args) {
+            Some((options, _, _))
+                if options
+                    .iter()
+                    .any(|a| matches!(*a, "-l" | "--list" | "-v" | "--validate")) =>
+            {
+                false
+            }
+            Some((_, program, args)) => is_mutating_command(program, args),
+            None => false,
+        },
+        "pacman" => pacman_mutates(args, false),
+        "yay" | "paru" => pacman_mutates(args, true),
+        "paccache" => args
+            .iter()
+            .any(|a| matches!(*a, "-r" | "--remove" | "-m" | "--move") || is_short(a, 'r')),
+        "reflector" => args
+            .iter()
+            .any(|a| *a == "--save" || a.starts_with("--save=")),
+        "journalctl" => args.iter().any(|a| {
+            a.starts_with("--vacuum") || matches!(*a, "--rotate" | "--flush" | "--relinquish-var")
+        }),
+        "sed" => args
+            .iter()
+            .any(|a| *a == "--in-place" || a.starts_with("--in-place=") || is_short(a, 'i')),
+        // A script could do anything
+        "sh" | "bash" | "zsh" | "dash" => args.iter().any(|a| *a == "-c"),
+        _ => subcommand_mutates(program, args),
+    }
+}
+
+fn subcommand_mutates(program: &str, args: &[&str]) -> bool {
+    let Some((_, mutating)) = MUTATING_SUBCOMMANDS.iter().find(|(p, _)| *p == program) else {
+        return false;
+    };
+    let takes_value = VALUE_OPTIONS
+        .iter()
+        .find(|(p, _)| *p == program)
+        .map_or(&[][..], |(_, options)| *options);
+    let mut words = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') || mutating.contains(arg) {
+            words.push(*arg);
+        } else if takes_value.contains(arg) {
+            args.next();
+        }
+    }
+    let mut words = words.into_iter();
+    let Some(first) = words.next() else {
+        return false;
+    };
+    match program {
+        "docker" | "podman" if CONTAINER_OBJECTS.contains(&first) => {
+            words.next().is_some_and(|verb| mutating.contains(&verb))
+        }
+        "btrfs" if mutating.contains(&first) => {
+            !words.next().is_some_and(|verb| BTRFS_READS.contains(&verb))
+        }
+        // `ip addr add ...`: the object comes first, the verb after it
+        "ip" => std::iter::once(first)
+            .chain(words.take(1))
+            .any(|w| mutating.contains(&w)),
+        _ => mutating.contains(&first),
+    }
+}
+
+/// pacman's operation is its first option; only some of -S's modifiers
+/// leave the system alone. With no arguments yay and paru upgrade everything.
+fn pacman_mutates(args: &[&str], helper: bool) -> bool {
+    let Some(operation) = args.iter().find(|a| a.starts_with('-')) else {
+        return helper && args.is_empty();
+    };
+    let flags: Vec<char> = if operation.starts_with("--") {
+        Vec::new()
+    } else {
+        operation.chars().skip(1).collect()
+    };
+    let long = |name: &str| args.contains(&name);
+
+    match flags.first() {
+        // -Fy only refreshes the files database, which doctor and `owns` rely on
+        Some('Q' | 'T' | 'F' | 'G' | 'P') => false,
+        Some('S') => {
+            // Search, info, list, groups, and print-only report; -Sc, -Sy, and the rest change things
+            let reads = flags[1..]
+                .iter()
+                .any(|f| matches!(f, 's' | 'i' | 'l' | 'g' | 'p'))
+                && !flags[1..].iter().any(|f| matches!(f, 'y' | 'u' | 'c'));
+            !reads
+        }
+        Some('D') => !flags.contains(&'k'),
+        Some(_) => true,
+        None => {
+            long("--sync")
+                && !(long("--search") || long("--info") || long("--list") || long("--groups"))
+                || long("--remove")
+                || long("--upgrade")
+                || long("--database")
+                || long("--sysupgrade")
+        }
+    }
+}
+
+/// An elevation tool's own options, then the command it runs: the first
+/// argument that isn't one of its options, and everything after it
+fn wrapped_command<'a>(args: &'a [&'a str]) -> Option<(&'a [&'a str], &'a str, &'a [&'a str])> {
+    let mut i = 0;
+    while i < args.len() {
+        match args[i] {
+            // Options of sudo and doas that take a value
+            "-u" | "-g" | "-C" | "-D" | "-h" | "-p" | "-r" | "-t" | "-U" | "--user" | "--group" => {
+                i += 2
+            }
+            arg if arg.starts_with('-') => i += 1,
+            program => return Some((&args[..i], program, &args[i + 1..])),
+        }
+    }
+    None
+}
+
+/// Whether `arg` is a bundle of short options including `flag`, e.g. `-rk` for r
+fn is_short(arg: &str, flag: char) -> bool {
+    arg.len() > 1 && arg.starts_with('-') && !arg.starts_with("--") && arg[1..].contains(flag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mutates(line: &str) -> bool {
+        let mut words = line.split_whitespace();
+        let program = words.next().unwrap();
+        is_mutating_command(program, &words.collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_command_classification_table() {
+        let cases = [
+            // Packages
+            ("pacman -S --noconfirm ripgrep", true),
+            ("pacman -Syu --noconfirm", true),
+            ("pacman -Syuw --noconfirm", true),
+            ("pacman -Sc", true),
+            ("pacman -Rs foo", true),
+            ("pacman -U /tmp/foo.pkg.tar.zst", true),
+            ("pacman -D --asdeps foo", true),
+            ("/usr/bin/pacman -Qu", false),
+            ("pacman -Qi linux", false),
+            ("pacman -Ss neovim", false),
+            ("pacman -Si neovim", false),
+            ("pacman -Sp --print-format %n foo", false),
+            ("pacman -Fy", false),
+            ("pacman -Dk", false),
+            ("paru", true),
+            ("yay -Qua", false),
+            ("paccache -r", true),
+            ("paccache -dk2", false),
+            (
+                "reflector --latest 20 --save /etc/pacman.d/mirrorlist",
+                true,
+            ),
+            ("reflector --latest 20", false),
+            ("checkupdates", false),
+            ("flatpak update -y", true),
+            ("flatpak remote-ls --updates", false),
+            // Services and the journal
+            ("systemctl restart sshd", true),
+            ("systemctl --user stop foo", true),
+            ("systemctl daemon-reload", true),
+            ("systemctl status sshd", false),
+            ("systemctl is-active sshd", false),
+            ("systemctl list-units --failed", false),
+            ("journalctl --vacuum-time=2weeks", true),
+            ("journalctl --no-pager -u sshd", false),
+            // Containers and VMs
+            ("docker start web", true),
+            ("docker container prune -f", true),
+            ("docker compose up -d", true),
+            ("docker ps -a", false),
+            ("docker logs --tail 50 web", false),
+            ("docker container ls", false),
+            ("docker image inspect nginx", false),
+            ("virsh start win11", true),
+            ("virsh shutdown win11", true),
+            ("virsh list --all", false),
+            ("virsh dominfo win11", false),
+            // Filesystems, snapshots, the network
+            ("cp -a /.snapshots/5/snapshot/etc/fstab /etc/fstab", true),
+            ("rm -rf /tmp/x", true),
+            ("sed -i s/a/b/ /etc/foo", true),
+            ("sed s/a/b/ /etc/foo", false),
+            ("snapper -c root create", true),
+            ("snapper -c root list", false),
+            ("snapper -c root status 4..0", false),
+            ("btrfs scrub start /", true),
+            ("btrfs scrub status /", false),
+            ("btrfs balance cancel /", true),
+            ("btrfs filesystem usage -b /", false),
+            ("nft add rule inet jarvis input drop", true),
+            ("nft list table inet jarvis", false),
+            ("ip addr add 10.0.0.2/24 dev eth0", true),
+            ("ip -j addr show", false),
+            ("df -h", false),
+            ("sh -c rm", true),
+            // Elevation wraps the real command
+            ("sudo -n /usr/bin/pacman -S foo", true),
+            ("sudo -n -l /usr/bin/pacman -S jarvis-doctor-probe", false),
+            ("sudo -n pacman -Fy", false),
+            ("pkexec /usr/bin/systemctl restart sshd", true),
+            ("sudo -u nobody ls", false),
+            ("sudo ls -l /root", false),
+            ("sudo systemctl -l restart sshd", true),
+            ("systemctl -t service stop sshd", true),
+            ("docker compose -f stack.yml down", true),
+            ("docker logs -f web", false),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(mutates(line), expected, "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_refusal_is_typed() {
+        let err = refusal("run InstallPackage");
+        assert_eq!(err.code(), "read_only_mode");
+        assert!(
+            err.message()
+                .starts_with("refusing to run InstallPackage because")
+        );
+    }
+}
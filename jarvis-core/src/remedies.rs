@@ -0,0 +1,547 @@
+//! Rule-based fixes for well-known problems
+//!
+//! `jarvis fix` checks the issue against this knowledge base before asking
+//! the LLM, so a stale pacman lock or a full `/boot` gets its mechanical fix
+//! even when Ollama is down. Rules come from `remedies.toml`, compiled into
+//! the binary, and `~/.config/jarvis/remedies.toml`, which can replace,
+//! disable, or add rules. The file format is described at the top of the
+//! built-in one.
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use crate::remote::CommandExecutor;
+
+const BUILTIN: &str = include_str!("remedies.toml");
+
+/// Matches below this confidence after probing aren't shown
+pub const MIN_CONFIDENCE: f64 = 0.3;
+
+/// A match at least this confident is answered without the LLM
+pub const CONFIDENT: f64 = 0.8;
+
+#[derive(Debug, Clone, Deserialize)]
+struct RulesFile {
+    #[serde(default)]
+    rule: Vec<RuleSpec>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RuleSpec {
+    id: String,
+    #[serde(default)]
+    title: String,
+    #[serde(default = "default_confidence")]
+    confidence: f64,
+    #[serde(default)]
+    patterns: Vec<String>,
+    #[serde(default)]
+    probe: Vec<Probe>,
+    #[serde(default)]
+    step: Vec<FixStep>,
+    #[serde(default)]
+    disabled: bool,
+}
+
+fn default_confidence() -> f64 {
+    0.6
+}
+
+/// A check on the live system that confirms or contradicts a match
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Probe {
+    PathExists(String),
+    PathMissing(String),
+    /// No process with exactly this name is running
+    ProcessAbsent(String),
+    /// The filesystem holding `path` is more than `percent` full
+    DiskAbove {
+        path: String,
+        percent: u8,
+    },
+    /// `command`'s stdout matches `pattern`, whatever its exit status
+    CommandMatches {
+        command: Vec<String>,
+        pattern: String,
+    },
+}
+
+impl Probe {
+    fn fill(&self, captures: &HashMap<String, String>) -> Option<Probe> {
+        let fill = |s: &String| fill_placeholders(s, captures);
+        Some(match self {
+            Probe::PathExists(path) => Probe::PathExists(fill(path)?),
+            Probe::PathMissing(path) => Probe::PathMissing(fill(path)?),
+            Probe::ProcessAbsent(name) => Probe::ProcessAbsent(fill(name)?),
+            Probe::DiskAbove { path, percent } => Probe::DiskAbove {
+                path: fill(path)?,
+                percent: *percent,
+            },
+            Probe::CommandMatches { command, pattern } => Probe::CommandMatches {
+                command: command.iter().map(fill).collect::<Option<_>>()?,
+                pattern: pattern.clone(),
+            },
+        })
+    }
+
+    /// Whether the probe confirms the match; `None` when it couldn't run
+    async fn run(&self, executor: &CommandExecutor) -> Option<bool> {
+        match self {
+            Probe::PathExists(path) => test_path(executor, path).await,
+            Probe::PathMissing(path) => test_path(executor, path).await.map(|exists| !exists),
+            Probe::ProcessAbsent(name) => {
+                let output = executor.run("pgrep", &["-x", name]).await.ok()?;
+                // pgrep exits 1 when nothing matched and higher on errors
+                match output.status.code() {
+                    Some(0) => Some(false),
+                    Some(1) => Some(true),
+                    _ => None,
+                }
+            }
+            Probe::DiskAbove { path, percent } => {
+                let stdout = executor
+                    .run_stdout("df", &["--output=pcent", path])
+                    .await
+                    .ok()?;
+                let used: u8 = stdout
+                    .lines()
+                    .nth(1)?
+                    .trim()
+                    .trim_end_matches('%')
+                    .parse()
+                    .ok()?;
+                Some(used > *percent)
+            }
+            Probe::CommandMatches { command, pattern } => {
+                let (program, args) = command.split_first()?;
+                let args: Vec<&str> = args.iter().map(String::as_str).collect();
+                let output = executor.run(program, &args).await.ok()?;
+                let pattern = Regex::new(pattern).ok()?;
+                Some(pattern.is_match(&String::from_utf8_lossy(&output.stdout)))
+            }
+        }
+    }
+}
+
+async fn test_path(executor: &CommandExecutor, path: &str) -> Option<bool> {
+    let output = executor.run("test", &["-e", path]).await.ok()?;
+    match output.status.code() {
+        Some(0) => Some(true),
+        Some(1) => Some(false),
+        _ => None,
+    }
+}
+
+/// One step of a fix: a shell command, or a Jarvis operation
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct FixStep {
+    pub description: String,
+    #[serde(default)]
+    pub command: Option<String>,
+    /// Jarvis subcommand, e.g. `arch mirrors`
+    #[serde(default)]
+    pub operation: Option<String>,
+}
+
+impl FixStep {
+    fn fill(&self, captures: &HashMap<String, String>) -> Option<FixStep> {
+        let fill = |s: &Option<String>| match s {
+            Some(s) => fill_placeholders(s, captures).map(Some),
+            None => Some(None),
+        };
+        Some(FixStep {
+            description: self.description.clone(),
+            command: fill(&self.command)?,
+            operation: fill(&self.operation)?,
+        })
+    }
+
+    /// What to run, as typed at a shell
+    pub fn command_line(&self) -> Option<String> {
+        match (&self.command, &self.operation) {
+            (Some(command), _) => Some(command.clone()),
+            (None, Some(operation)) => Some(format!("jarvis {}", operation)),
+            (None, None) => None,
+        }
+    }
+}
+
+static PLACEHOLDER: std::sync::LazyLock<Regex> =
+    std::sync::LazyLock::new(|| Regex::new(r"\{([a-z_]+)\}").expect("valid regex"));
+
+/// `template` with `{name}` replaced from `captures`; `None` if any is missing
+fn fill_placeholders(template: &str, captures: &HashMap<String, String>) -> Option<String> {
+    let mut missing = false;
+    let filled =
+        PLACEHOLDER.replace_all(template, |c: &regex::Captures| match captures.get(&c[1]) {
+            Some(value) => value.clone(),
+            None => {
+                missing = true;
+                String::new()
+            }
+        });
+    (!missing).then(|| filled.into_owned())
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    id: String,
+    title: String,
+    confidence: f64,
+    patterns: Vec<Regex>,
+    probes: Vec<Probe>,
+    steps: Vec<FixStep>,
+}
+
+impl Rule {
+    fn compile(spec: RuleSpec) -> Result<Self> {
+        let patterns = spec
+            .patterns
+            .iter()
+            .map(|p| Regex::new(p).with_context(|| format!("Invalid pattern in rule {}", spec.id)))
+            .collect::<Result<Vec<_>>>()?;
+        if patterns.is_empty() {
+            anyhow::bail!("Rule {} has no patterns", spec.id);
+        }
+        if !(0.0..=1.0).contains(&spec.confidence) {
+            anyhow::bail!("Rule {} has confidence outside 0.0-1.0", spec.id);
+        }
+        Ok(Self {
+            title: if spec.title.is_empty() {
+                spec.id.clone()
+            } else {
+                spec.title
+            },
+            id: spec.id,
+            confidence: spec.confidence,
+            patterns,
+            probes: spec.probe,
+            steps: spec.step,
+        })
+    }
+}
+
+/// A rule that matched, with its placeholders filled
+#[derive(Debug, Clone, PartialEq)]
+pub struct Remedy {
+    pub id: String,
+    pub title: String,
+    /// 0.0-1.0; from the rule, then adjusted by [`Remedy::probe`]
+    pub confidence: f64,
+    /// The text that matched
+    pub evidence: String,
+    pub steps: Vec<FixStep>,
+    probes: Vec<Probe>,
+}
+
+impl Remedy {
+    /// Run the rule's probes and adjust the confidence by their results
+    pub async fn probe(&mut self, executor: &CommandExecutor) {
+        let mut results = Vec::with_capacity(self.probes.len());
+        for probe in &self.probes {
+            results.push(probe.run(executor).await);
+        }
+        self.confidence = adjust_confidence(self.confidence, &results);
+    }
+
+    /// Numbered steps under a heading with the confidence
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{} ({:.0}% confidence)\n  matched: {}",
+            self.title,
+            self.confidence * 100.0,
+            self.evidence
+        );
+        for (i, step) in self.steps.iter().enumerate() {
+            let _ = writeln!(out, "  {}. {}", i + 1, step.description);
+            if let Some(command) = step.command_line() {
+                let _ = writeln!(out, "       {}", command);
+            }
+        }
+        out
+    }
+}
+
+/// Each confirming probe moves `base` halfway to certainty, each
+/// contradicting one halves it; probes that couldn't run change nothing
+pub fn adjust_confidence(base: f64, results: &[Option<bool>]) -> f64 {
+    results
+        .iter()
+        .fold(base, |confidence, result| match result {
+            Some(true) => confidence + (1.0 - confidence) / 2.0,
+            Some(false) => confidence / 2.0,
+            None => confidence,
+        })
+}
+
+#[derive(Debug, Clone)]
+pub struct KnowledgeBase {
+    rules: Vec<Rule>,
+}
+
+impl KnowledgeBase {
+    /// The rules compiled into the binary
+    pub fn builtin() -> Self {
+        Self::from_toml(BUILTIN).expect("built-in remedies.toml is valid")
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self> {
+        let file: RulesFile = toml::from_str(content)?;
+        let rules = file
+            .rule
+            .into_iter()
+            .filter(|spec| !spec.disabled)
+            .map(Rule::compile)
+            .collect::<Result<_>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Built-in rules with the user's file applied over them
+    pub fn load() -> Result<Self> {
+        let mut kb = Self::builtin();
+        if let Some(path) = user_rules_path().filter(|p| p.exists()) {
+            kb.apply_overrides_from(&path)?;
+        }
+        Ok(kb)
+    }
+
+    fn apply_overrides_from(&mut self, path: &Path) -> Result<()> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let file: RulesFile =
+            toml::from_str(&content).with_context(|| format!("Invalid {}", path.display()))?;
+        for spec in file.rule {
+            self.rules.retain(|rule| rule.id != spec.id);
+            if !spec.disabled {
+                self.rules
+                    .push(Rule::compile(spec).with_context(|| format!("In {}", path.display()))?);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn rule_ids(&self) -> Vec<&str> {
+        self.rules.iter().map(|rule| rule.id.as_str()).collect()
+    }
+
+    /// Rules whose patterns occur in `text`, most confident first, before probing
+    pub fn matches(&self, text: &str) -> Vec<Remedy> {
+        let mut remedies: Vec<Remedy> = self
+            .rules
+            .iter()
+            .filter_map(|rule| {
+                // The first pattern that matches decides the placeholders
+                let captures = rule.patterns.iter().find_map(|p| p.captures(text))?;
+                let named: HashMap<String, String> = rule
+                    .patterns
+                    .iter()
+                    .flat_map(|p| p.capture_names().flatten())
+                    .filter_map(|name| {
+                        captures
+                            .name(name)
+                            .map(|m| (name.to_string(), m.as_str().to_string()))
+                    })
+                    .collect();
+                Some(Remedy {
+                    id: rule.id.clone(),
+                    title: rule.title.clone(),
+                    confidence: rule.confidence,
+                    evidence: captures[0].trim().to_string(),
+                    steps: rule.steps.iter().filter_map(|s| s.fill(&named)).collect(),
+                    probes: rule.probes.iter().filter_map(|p| p.fill(&named)).collect(),
+                })
+            })
+            .collect();
+        sort_by_confidence(&mut remedies);
+        remedies
+    }
+
+    /// Matches for `text`, probed on the host behind `executor`, without
+    /// those the probes ruled out
+    pub async fn diagnose(&self, text: &str, executor: &CommandExecutor) -> Vec<Remedy> {
+        let mut remedies = self.matches(text);
+        for remedy in &mut remedies {
+            remedy.probe(executor).await;
+        }
+        remedies.retain(|remedy| remedy.confidence >= MIN_CONFIDENCE);
+        sort_by_confidence(&mut remedies);
+        remedies
+    }
+}
+
+fn sort_by_confidence(remedies: &mut [Remedy]) {
+    remedies.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+}
+
+/// `~/.config/jarvis/remedies.toml`
+pub fn user_rules_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("jarvis").join("remedies.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Captured error output for each built-in rule, named after its id
+    const FIXTURES: &[(&str, &str)] = &[
+        (
+            "pacman-db-lock",
+            include_str!("../tests/fixtures/remedies/pacman-db-lock.txt"),
+        ),
+        (
+            "partial-upgrade",
+            include_str!("../tests/fixtures/remedies/partial-upgrade.txt"),
+        ),
+        (
+            "boot-full",
+            include_str!("../tests/fixtures/remedies/boot-full.txt"),
+        ),
+        (
+            "archlinux-keyring",
+            include_str!("../tests/fixtures/remedies/archlinux-keyring.txt"),
+        ),
+        (
+            "mirror-404",
+            include_str!("../tests/fixtures/remedies/mirror-404.txt"),
+        ),
+        (
+            "missing-environment-file",
+            include_str!("../tests/fixtures/remedies/missing-environment-file.txt"),
+        ),
+    ];
+
+    #[test]
+    fn test_every_rule_matches_its_fixture_first() {
+        let kb = KnowledgeBase::builtin();
+        let mut ids = kb.rule_ids();
+        ids.sort();
+        let mut covered: Vec<&str> = FIXTURES.iter().map(|(id, _)| *id).collect();
+        covered.sort();
+        assert_eq!(ids, covered, "each built-in rule needs a fixture");
+
+        for (id, fixture) in FIXTURES {
+            let remedies = kb.matches(fixture);
+            assert_eq!(
+                remedies.first().map(|r| r.id.as_str()),
+                Some(*id),
+                "{}",
+                fixture
+            );
+            assert!(!remedies[0].steps.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_captures_fill_steps() {
+        let kb = KnowledgeBase::builtin();
+
+        let unit = &kb.matches(FIXTURES[5].1)[0];
+        assert_eq!(
+            unit.steps[2].command_line().unwrap(),
+            "sudo systemctl restart grafana.service"
+        );
+        assert_eq!(
+            unit.probes[0],
+            Probe::CommandMatches {
+                command: ["systemctl", "show", "-p", "ActiveState", "grafana.service"]
+                    .map(String::from)
+                    .to_vec(),
+                pattern: "ActiveState=failed".to_string(),
+            }
+        );
+
+        let lock = &kb.matches(FIXTURES[0].1)[0];
+        assert_eq!(
+            lock.steps[0].command_line().unwrap(),
+            "jarvis arch unlock-db"
+        );
+
+        let library = &kb.matches(FIXTURES[1].1)[0];
+        assert_eq!(
+            library.steps[0].command_line().unwrap(),
+            "pacman -F libpython3.12.so.1.0"
+        );
+        // Without a library name the lookup step is left out
+        let symbol =
+            &kb.matches("symbol lookup error: /usr/bin/gimp: undefined symbol: gegl_init")[0];
+        assert_eq!(symbol.steps[0].command_line().unwrap(), "sudo pacman -Syu");
+
+        let mirror = &kb.matches(FIXTURES[4].1)[0];
+        assert!(
+            mirror
+                .steps
+                .iter()
+                .any(|s| s.command_line().unwrap() == "jarvis arch mirrors")
+        );
+        assert!(mirror.render().contains("mirror.example.net"));
+
+        assert!(kb.matches("nginx returns 502 for every request").is_empty());
+    }
+
+    #[test]
+    fn test_probe_results_adjust_confidence() {
+        assert_eq!(adjust_confidence(0.6, &[Some(true)]), 0.8);
+        assert_eq!(adjust_confidence(0.6, &[Some(false)]), 0.3);
+        assert_eq!(adjust_confidence(0.6, &[None, Some(true), Some(true)]), 0.9);
+    }
+
+    #[test]
+    fn test_user_rules_override_builtin() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("remedies.toml");
+        std::fs::write(
+            &path,
+            r#"
+[[rule]]
+id = "mirror-404"
+disabled = true
+
+[[rule]]
+id = "pacman-db-lock"
+title = "Our lock"
+confidence = 0.9
+patterns = ['unable to lock database']
+
+[[rule.step]]
+description = "Ask the on-call admin"
+command = "page-admin pacman-lock"
+
+[[rule]]
+id = "docker-socket"
+patterns = ['Cannot connect to the Docker daemon']
+"#,
+        )
+        .unwrap();
+
+        let mut kb = KnowledgeBase::builtin();
+        kb.apply_overrides_from(&path).unwrap();
+
+        assert!(!kb.rule_ids().contains(&"mirror-404"));
+        assert!(
+            kb.matches(FIXTURES[4].1)
+                .iter()
+                .all(|r| r.id != "mirror-404")
+        );
+        let lock = &kb.matches(FIXTURES[0].1)[0];
+        assert_eq!((lock.title.as_str(), lock.confidence), ("Our lock", 0.9));
+        assert_eq!(lock.steps.len(), 1);
+        assert_eq!(
+            kb.matches("Cannot connect to the Docker daemon at unix:///var/run/docker.sock")[0].id,
+            "docker-socket"
+        );
+
+        std::fs::write(&path, "[[rule]]\nid = \"bad\"\npatterns = ['(unclosed']\n").unwrap();
+        assert!(
+            KnowledgeBase::builtin()
+                .apply_overrides_from(&path)
+                .is_err()
+        );
+    }
+}
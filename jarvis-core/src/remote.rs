@@ -0,0 +1,335 @@
+//! Remote execution over SSH
+//!
+//! Lets the CLI run its probes against another host while the LLM and memory
+//! stay local. Commands are routed through the system `ssh` client so the
+//! user's existing keys, agent, and `~/.ssh/config` all apply. Connections
+//! are shared per host through [`crate::ssh_pool`].
+
+use crate::config::{RemoteConfig, RemoteHostConfig};
+use crate::exec::{C_LOCALE, CommandRunner, RunOptions, SystemRunner};
+use crate::ssh_pool::{self, SshPool};
+use anyhow::{Context, Result};
+use std::process::Output;
+
+/// How a remote host escalates privileges
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Escalation {
+    /// Never escalate; privileged commands fail
+    #[default]
+    None,
+    /// `sudo -n`, requires a NOPASSWD rule for the remote user
+    Sudo,
+    /// `doas -n`, requires a `nopass` rule for the remote user
+    Doas,
+}
+
+/// A resolved SSH destination
+#[derive(Debug, Clone)]
+pub struct SshTarget {
+    /// Alias from config, or the destination itself for ad-hoc hosts
+    pub name: String,
+    pub destination: String,
+    pub port: Option<u16>,
+    pub identity_file: Option<String>,
+    pub ssh_options: Vec<String>,
+    pub escalation: Escalation,
+    pub mutations_allowed: bool,
+    pub connect_timeout_secs: u64,
+}
+
+impl SshTarget {
+    /// Resolve `--host` against configured aliases, falling back to treating it
+    /// as a plain `user@server` destination with default options
+    pub fn resolve(host: &str, config: &RemoteConfig) -> Self {
+        match config.hosts.get(host) {
+            Some(entry) => Self::from_config(host, entry, config),
+            None => Self {
+                name: host.to_string(),
+                destination: host.to_string(),
+                port: None,
+                identity_file: None,
+                ssh_options: Vec::new(),
+                escalation: Escalation::None,
+                mutations_allowed: config.remote_mutations_allowed,
+                connect_timeout_secs: config.connect_timeout_secs,
+            },
+        }
+    }
+
+    fn from_config(name: &str, entry: &RemoteHostConfig, config: &RemoteConfig) -> Self {
+        let destination = match &entry.user {
+            Some(user) => format!("{}@{}", user, entry.host),
+            None => entry.host.clone(),
+        };
+
+        Self {
+            name: name.to_string(),
+            destination,
+            port: entry.port,
+            identity_file: entry.identity_file.clone(),
+            ssh_options: entry.ssh_options.clone(),
+            escalation: entry.escalation,
+            mutations_allowed: config.remote_mutations_allowed,
+            connect_timeout_secs: config.connect_timeout_secs,
+        }
+    }
+
+    /// Options passed to every `ssh` invocation for this host, before the
+    /// destination
+    pub(crate) fn connection_args(&self) -> Vec<String> {
+        // BatchMode: never block on a password or host-key prompt we can't answer
+        let mut args = vec![
+            "-o".to_string(),
+            "BatchMode=yes".to_string(),
+            "-o".to_string(),
+            format!("ConnectTimeout={}", self.connect_timeout_secs),
+        ];
+        if let Some(port) = self.port {
+            args.push("-p".to_string());
+            args.push(port.to_string());
+        }
+        if let Some(identity) = &self.identity_file {
+            args.push("-i".to_string());
+            args.push(shellexpand::tilde(identity).to_string());
+        }
+        for option in &self.ssh_options {
+            args.push("-o".to_string());
+            args.push(option.clone());
+        }
+        args
+    }
+}
+
+/// Where probe commands run
+#[derive(Debug, Clone, Default)]
+pub enum CommandExecutor {
+    #[default]
+    Local,
+    Ssh(SshTarget),
+}
+
+impl CommandExecutor {
+    pub fn ssh(target: SshTarget) -> Self {
+        Self::Ssh(target)
+    }
+
+    pub fn is_remote(&self) -> bool {
+        matches!(self, Self::Ssh(_))
+    }
+
+    /// Human-readable name of the host commands run on
+    pub fn host_label(&self) -> String {
+        match self {
+            Self::Local => "localhost".to_string(),
+            Self::Ssh(target) => target.name.clone(),
+        }
+    }
+
+    /// Run a command and capture its output
+    pub async fn run(&self, program: &str, args: &[&str]) -> Result<Output> {
+        self.run_pooled(ssh_pool::pool(), program, args).await
+    }
+
+    /// [`run`](Self::run) with SSH sessions taken from `pool`
+    pub async fn run_pooled(&self, pool: &SshPool, program: &str, args: &[&str]) -> Result<Output> {
+        match self {
+            Self::Local => SystemRunner::default()
+                .output(program, args)
+                .await
+                .with_context(|| format!("Failed to run {}", program)),
+            Self::Ssh(target) => {
+                crate::read_only::check_command(program, args)?;
+                let output = pool.exec(target, &remote_command(program, args)).await?;
+
+                // ssh reserves 255 for its own failures (auth, connect, host key)
+                if output.status.code() == Some(255) {
+                    return Err(anyhow::anyhow!(
+                        "SSH connection to {} failed: {}",
+                        target.destination,
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    ));
+                }
+                Ok(output)
+            }
+        }
+    }
+
+    /// Run a command and return stdout, or an error carrying stderr
+    pub async fn run_stdout(&self, program: &str, args: &[&str]) -> Result<String> {
+        let output = self.run(program, args).await?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "{} failed on {}: {}",
+                program,
+                self.host_label(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    /// Whether `program` exists on the target host
+    pub async fn has_command(&self, program: &str) -> bool {
+        self.run("sh", &["-c", &format!("command -v {}", shell_quote(program))])
+            .await
+            .map(|o| o.status.success())
+            .unwrap_or(false)
+    }
+
+    /// Run a command that needs root. Locally this defers to the caller's own
+    /// privileges; remotely it uses the host's configured non-interactive
+    /// escalation and refuses when none is configured.
+    pub async fn run_privileged(&self, program: &str, args: &[&str]) -> Result<Output> {
+        let target = match self {
+            Self::Local => return self.run(program, args).await,
+            Self::Ssh(target) => target,
+        };
+
+        let escalate = match target.escalation {
+            Escalation::Sudo => "sudo",
+            Escalation::Doas => "doas",
+            Escalation::None => {
+                return Err(anyhow::anyhow!(
+                    "`{}` needs root on {}, but no escalation is configured. Set \
+                     `escalation = \"sudo\"` under [remote.hosts.{}] and add a NOPASSWD \
+                     rule for this command on the remote host.",
+                    program,
+                    target.name,
+                    target.name
+                ));
+            }
+        };
+
+        let mut escalated = vec!["-n", program];
+        escalated.extend_from_slice(args);
+        let output = self.run(escalate, &escalated).await?;
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() && stderr.contains("password") {
+            return Err(anyhow::anyhow!(
+                "{} on {} asked for a password for `{}`. Remote escalation must be \
+                 passwordless; add a NOPASSWD rule for this command.",
+                escalate,
+                target.name,
+                program
+            ));
+        }
+        Ok(output)
+    }
+
+    /// Refuse state-changing operations in read-only mode, and on remote hosts
+    /// unless explicitly allowed
+    pub fn ensure_mutation_allowed(&self, operation: &str) -> Result<()> {
+        crate::read_only::check(operation)?;
+        match self {
+            Self::Local => Ok(()),
+            Self::Ssh(target) if target.mutations_allowed => Ok(()),
+            Self::Ssh(target) => Err(crate::JarvisError::PermissionDenied(format!(
+                "Refusing to {} on remote host {}: remote mutations are disabled. \
+                 Set `remote_mutations_allowed = true` under [remote] to enable them.",
+                operation, target.name
+            ))
+            .into()),
+        }
+    }
+}
+
+/// Lets runner-based lookups probe whichever host the executor targets.
+/// Over SSH the options are not forwarded; the remote side gets the usual
+/// C locale from [`remote_command`].
+#[async_trait::async_trait]
+impl CommandRunner for CommandExecutor {
+    async fn run(&self, program: &str, args: &[&str], options: &RunOptions) -> Result<Output> {
+        match self {
+            Self::Local => SystemRunner::default().run(program, args, options).await,
+            Self::Ssh(_) => CommandExecutor::run(self, program, args).await,
+        }
+    }
+}
+
+/// Quote a single argument for a POSIX shell
+pub fn shell_quote(arg: &str) -> String {
+    let safe = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,@%+".contains(c));
+    if safe {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+/// Build the command line the remote shell will run
+pub fn shell_join(program: &str, args: &[&str]) -> String {
+    std::iter::once(program)
+        .chain(args.iter().copied())
+        .map(shell_quote)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The command line run over SSH. The remote login shell brings the remote
+/// user's locale, so the C locale is forced there as `SystemRunner` does here.
+pub fn remote_command(program: &str, args: &[&str]) -> String {
+    let mut line = "env -u LANGUAGE".to_string();
+    for (key, value) in C_LOCALE {
+        line.push_str(&format!(" {}={}", key, value));
+    }
+    format!("{} {}", line, shell_join(program, args))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_join_quotes_unsafe_args() {
+        assert_eq!(shell_join("systemctl", &["status", "sshd"]), "systemctl status sshd");
+        assert_eq!(
+            shell_join("sh", &["-c", "echo it's here"]),
+            r"sh -c 'echo it'\''s here'"
+        );
+        assert_eq!(shell_join("echo", &[""]), "echo ''");
+    }
+
+    #[test]
+    fn test_remote_command_forces_c_locale() {
+        assert_eq!(
+            remote_command("pacman", &["-Qu"]),
+            "env -u LANGUAGE LC_ALL=C LANG=C pacman -Qu"
+        );
+    }
+
+    #[test]
+    fn test_resolve_alias_and_adhoc() {
+        let mut config = RemoteConfig::default();
+        config.hosts.insert(
+            "homelab".to_string(),
+            RemoteHostConfig {
+                host: "10.0.0.5".to_string(),
+                user: Some("admin".to_string()),
+                port: Some(2222),
+                identity_file: None,
+                ssh_options: vec![],
+                escalation: Escalation::Sudo,
+                mac: None,
+                wol_broadcast: None,
+            },
+        );
+
+        let alias = SshTarget::resolve("homelab", &config);
+        assert_eq!(alias.destination, "admin@10.0.0.5");
+        assert_eq!(alias.port, Some(2222));
+        assert!(!alias.mutations_allowed);
+
+        let adhoc = SshTarget::resolve("me@nas", &config);
+        assert_eq!(adhoc.destination, "me@nas");
+        assert_eq!(adhoc.escalation, Escalation::None);
+
+        let executor = CommandExecutor::ssh(alias);
+        assert!(executor.ensure_mutation_allowed("fix").is_err());
+        assert!(CommandExecutor::Local.ensure_mutation_allowed("fix").is_ok());
+    }
+}
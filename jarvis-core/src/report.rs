@@ -0,0 +1,1146 @@
+//! Periodic system reports
+//!
+//! Gathering and rendering are separate: `gather` collects a `ReportData`
+//! snapshot for a time window, and the `render_*` functions turn it into
+//! markdown or HTML. The GhostFlow reporting workflow uses the gathering half
+//! with its own presentation.
+
+use crate::config::ReportConfig;
+use crate::exec::{CommandRunner, SystemRunner};
+use crate::fleet::{self, FleetThresholds, HostHealth, HostStatus};
+use crate::incidents::{Incident, IncidentLog, format_duration};
+use crate::llm::LLMRouter;
+use crate::maintenance_agents::BtrfsHistory;
+use crate::memory::MemoryStore;
+use crate::metrics;
+use crate::remote::CommandExecutor;
+use crate::severity::Severity;
+use crate::time_range::{self, TimeRange};
+use crate::trivy::ImageScanCache;
+use crate::types::AuditStatus;
+use crate::vuln::{AcknowledgedFinding, Acknowledgements};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Recorded metrics shown in the health section, see `metrics::HostSampler`
+/// and `sensors::SensorSnapshot::metrics`
+const TREND_METRICS: [&str; 8] = [
+    "cpu.usage_percent",
+    "memory.used_percent",
+    "swap.used_percent",
+    "load.1m",
+    "disk.used_percent",
+    "sensor.temperature_c",
+    "sensor.power_w",
+    "battery.health_percent",
+];
+
+/// Memory store document holding the last arch-audit output, used offline
+const AUDIT_CACHE_KEY: &str = "arch_audit_cache";
+
+pub(crate) const SPARK_BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Used when no `--template` is given; `{{title}}` and `{{content}}` are replaced
+const DEFAULT_HTML_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>{{title}}</title>
+<style>
+  body { font-family: system-ui, sans-serif; max-width: 52rem; margin: 2rem auto; padding: 0 1rem; color: #222; }
+  h1 { border-bottom: 2px solid #444; padding-bottom: .3rem; }
+  h2 { margin-top: 2rem; color: #333; }
+  code { background: #f2f2f2; padding: 0 .25rem; border-radius: 3px; }
+</style>
+</head>
+<body>
+{{content}}
+</body>
+</html>
+"#;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ReportWindow {
+    pub since: DateTime<Utc>,
+    pub until: DateTime<Utc>,
+}
+
+impl ReportWindow {
+    /// Window from user input, e.g. `ReportWindow::parse("7d")` or
+    /// `ReportWindow::parse("yesterday")`; see [`time_range`]
+    pub fn parse(input: &str) -> Result<Self> {
+        time_range::parse_range(input).map(Self::from)
+    }
+
+    /// Calendar days covered by the window, oldest first
+    pub fn days(&self) -> Vec<NaiveDate> {
+        let mut days = Vec::new();
+        let mut day = self.since.date_naive();
+        while day <= self.until.date_naive() {
+            days.push(day);
+            day = day.succ_opt().unwrap_or(day);
+            if days.len() > 366 {
+                break;
+            }
+        }
+        days
+    }
+
+    pub fn contains(&self, t: DateTime<Utc>) -> bool {
+        t >= self.since && t <= self.until
+    }
+}
+
+impl From<TimeRange> for ReportWindow {
+    fn from(range: TimeRange) -> Self {
+        Self {
+            since: range.start,
+            until: range.end,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperationsSummary {
+    /// (task type, status) -> count
+    pub tasks: BTreeMap<String, BTreeMap<String, i64>>,
+    pub tool_calls: usize,
+    pub tool_errors: usize,
+    pub tool_calls_per_day: Vec<u32>,
+    /// Most recent failed tool calls, "tool:action — error"
+    pub recent_failures: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageLogEntry {
+    pub timestamp: DateTime<Utc>,
+    /// installed, upgraded, downgraded, removed
+    pub action: String,
+    pub package: String,
+    pub version: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageChanges {
+    pub entries: Vec<PackageLogEntry>,
+    pub changes_per_day: Vec<u32>,
+}
+
+impl PackageChanges {
+    pub fn count(&self, action: &str) -> usize {
+        self.entries.iter().filter(|e| e.action == action).count()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecurityFinding {
+    pub package: String,
+    pub severity: Severity,
+    pub advisories: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecuritySummary {
+    /// False when `arch-audit` isn't installed
+    pub scanned: bool,
+    /// Findings that still count, minus acknowledged advisories
+    pub findings: Vec<SecurityFinding>,
+    /// Advisories accepted with `jarvis vuln ack`, listed but not counted
+    #[serde(default)]
+    pub acknowledged: Vec<AcknowledgedFinding>,
+    /// Set when offline: the findings come from the last online scan, taken then
+    #[serde(default)]
+    pub cached_at: Option<DateTime<Utc>>,
+    /// Vulnerable packages in container images from the last `[trivy]` scan,
+    /// minus acknowledged advisories
+    #[serde(default)]
+    pub images: Vec<SecurityFinding>,
+}
+
+impl SecuritySummary {
+    /// Unacknowledged findings per severity, container images included
+    pub fn severity_counts(&self) -> BTreeMap<Severity, usize> {
+        let mut counts = BTreeMap::new();
+        for finding in self.findings.iter().chain(&self.images) {
+            *counts.entry(finding.severity).or_default() += 1;
+        }
+        counts
+    }
+
+    /// Critical or high findings are critical, anything else a warning.
+    /// Acknowledged advisories never affect this.
+    pub fn health(&self) -> HostHealth {
+        let severe = self
+            .findings
+            .iter()
+            .chain(&self.images)
+            .any(|f| f.severity.meets(Severity::High));
+        if severe {
+            HostHealth::Critical
+        } else if self.findings.is_empty() && self.images.is_empty() {
+            HostHealth::Healthy
+        } else {
+            HostHealth::Warning
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthTrend {
+    pub journal_errors_per_day: Vec<u32>,
+    /// Current state from the fleet probe set
+    pub current: HostStatus,
+    /// Recorded health metrics, one entry per metric and label set
+    #[serde(default)]
+    pub metrics: Vec<MetricTrend>,
+}
+
+/// A recorded metric over the report window
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricTrend {
+    /// Metric name with labels, e.g. `disk.used_percent{mount=/}`
+    pub series: String,
+    pub daily_averages: Vec<f64>,
+    pub min: f64,
+    pub avg: f64,
+    pub max: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LlmUsage {
+    pub responses: usize,
+    pub responses_per_day: Vec<u32>,
+}
+
+/// Everything a report needs, gathered for one window
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportData {
+    pub host: String,
+    pub window: ReportWindow,
+    pub generated_at: DateTime<Utc>,
+    pub operations: OperationsSummary,
+    pub packages: PackageChanges,
+    pub security: SecuritySummary,
+    pub health: HealthTrend,
+    pub llm: LlmUsage,
+    /// Scrub and balance history of btrfs filesystems
+    #[serde(default)]
+    pub btrfs: BtrfsHistory,
+    /// Incidents open at any time in the window, oldest first
+    #[serde(default)]
+    pub incidents: Vec<Incident>,
+}
+
+/// Collect report data for `window`. Sources that are unavailable on this
+/// machine produce empty sections rather than failing the whole report.
+pub async fn gather(memory: &MemoryStore, window: ReportWindow) -> Result<ReportData> {
+    let host = std::fs::read_to_string("/etc/hostname")
+        .map(|h| h.trim().to_string())
+        .unwrap_or_else(|_| "localhost".to_string());
+
+    Ok(ReportData {
+        host: host.clone(),
+        window,
+        generated_at: Utc::now(),
+        operations: gather_operations(memory, window).await?,
+        packages: gather_package_changes(window).await,
+        security: gather_security(memory).await,
+        health: gather_health(memory, &host, window).await,
+        llm: gather_llm_usage(memory, window).await?,
+        btrfs: BtrfsHistory::load(memory).await.unwrap_or_else(|e| {
+            tracing::warn!("Ignoring btrfs maintenance history: {}", e);
+            BtrfsHistory::default()
+        }),
+        incidents: match IncidentLog::load(memory).await {
+            Ok(log) => log.between(window.since, window.until),
+            Err(e) => {
+                tracing::warn!("Ignoring incident history: {}", e);
+                Vec::new()
+            }
+        },
+    })
+}
+
+pub async fn gather_operations(
+    memory: &MemoryStore,
+    window: ReportWindow,
+) -> Result<OperationsSummary> {
+    let mut tasks: BTreeMap<String, BTreeMap<String, i64>> = BTreeMap::new();
+    for (task_type, status, count) in memory.task_counts_since(window.since).await? {
+        tasks.entry(task_type).or_default().insert(status, count);
+    }
+
+    let audit: Vec<_> = memory
+        .audit_entries_since(window.since)
+        .await?
+        .into_iter()
+        .filter(|e| window.contains(e.timestamp))
+        .collect();
+    let failures: Vec<_> = audit
+        .iter()
+        .filter(|e| e.status == AuditStatus::Error)
+        .collect();
+
+    Ok(OperationsSummary {
+        tasks,
+        tool_calls: audit.len(),
+        tool_errors: failures.len(),
+        tool_calls_per_day: per_day(&window, audit.iter().map(|e| e.timestamp)),
+        recent_failures: failures
+            .iter()
+            .rev()
+            .take(5)
+            .map(|e| {
+                let tool = match &e.action {
+                    Some(action) => format!("{}:{}", e.tool, action),
+                    None => e.tool.clone(),
+                };
+                format!(
+                    "{} — {}",
+                    tool,
+                    e.error.as_deref().unwrap_or("unknown error")
+                )
+            })
+            .collect(),
+    })
+}
+
+pub async fn gather_package_changes(window: ReportWindow) -> PackageChanges {
+    let log = tokio::fs::read_to_string("/var/log/pacman.log")
+        .await
+        .unwrap_or_default();
+    let entries = parse_pacman_log(&log, &window);
+    let changes_per_day = per_day(&window, entries.iter().map(|e| e.timestamp));
+
+    PackageChanges {
+        entries,
+        changes_per_day,
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedAudit {
+    taken_at: DateTime<Utc>,
+    output: String,
+}
+
+/// arch-audit output and, when it came from the cache, when it was taken.
+/// arch-audit downloads the advisory feed on every run, so offline the last
+/// online result is reused instead.
+async fn arch_audit_output(memory: &MemoryStore) -> Option<(String, Option<DateTime<Utc>>)> {
+    if crate::net::is_offline() {
+        let cached: CachedAudit =
+            serde_json::from_str(&memory.get_document(AUDIT_CACHE_KEY).await.ok()??).ok()?;
+        return Some((cached.output, Some(cached.taken_at)));
+    }
+
+    let output = SystemRunner::default()
+        .output("arch-audit", &["--format", "%n|%s|%c"])
+        .await
+        .ok()?;
+    let output = String::from_utf8_lossy(&output.stdout).to_string();
+
+    let cached = CachedAudit {
+        taken_at: Utc::now(),
+        output: output.clone(),
+    };
+    if let Err(e) = memory
+        .store_document(
+            AUDIT_CACHE_KEY,
+            &serde_json::to_string(&cached).unwrap_or_default(),
+        )
+        .await
+    {
+        tracing::warn!("Failed to cache arch-audit results: {}", e);
+    }
+    Some((output, None))
+}
+
+pub async fn gather_security(memory: &MemoryStore) -> SecuritySummary {
+    let acknowledgements = Acknowledgements::load(memory).await.unwrap_or_else(|e| {
+        tracing::warn!("Ignoring vulnerability acknowledgements: {}", e);
+        Acknowledgements::default()
+    });
+    let now = Utc::now();
+    let image_findings = ImageScanCache::load(memory)
+        .await
+        .map(|cache| cache.findings())
+        .unwrap_or_else(|e| {
+            tracing::warn!("Ignoring container image scans: {}", e);
+            Vec::new()
+        });
+    let (images, mut acknowledged) = acknowledgements.triage(image_findings, now);
+
+    let Some((output, cached_at)) = arch_audit_output(memory).await else {
+        return SecuritySummary {
+            scanned: false,
+            findings: Vec::new(),
+            acknowledged,
+            cached_at: None,
+            images,
+        };
+    };
+
+    let (findings, mut packages_acknowledged) =
+        acknowledgements.triage(parse_arch_audit(&output), now);
+    packages_acknowledged.append(&mut acknowledged);
+
+    SecuritySummary {
+        scanned: true,
+        findings,
+        acknowledged: packages_acknowledged,
+        cached_at,
+        images,
+    }
+}
+
+pub async fn gather_health(memory: &MemoryStore, host: &str, window: ReportWindow) -> HealthTrend {
+    let since = window.since.format("%Y-%m-%d %H:%M:%S").to_string();
+    let journal = SystemRunner::default()
+        .output(
+            "journalctl",
+            &[
+                "-p",
+                "err",
+                "--since",
+                &since,
+                "-o",
+                "short-iso",
+                "--no-pager",
+                "-q",
+            ],
+        )
+        .await
+        .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
+        .unwrap_or_default();
+
+    let error_days = journal
+        .lines()
+        .filter_map(|line| line.get(..10))
+        .filter_map(|day| NaiveDate::parse_from_str(day, "%Y-%m-%d").ok());
+    let days = window.days();
+    let mut journal_errors_per_day = vec![0u32; days.len()];
+    for day in error_days {
+        if let Some(i) = days.iter().position(|d| *d == day) {
+            journal_errors_per_day[i] += 1;
+        }
+    }
+
+    let current = fleet::probe_host(
+        host,
+        &CommandExecutor::Local,
+        std::time::Duration::from_secs(30),
+        &FleetThresholds::default(),
+    )
+    .await;
+
+    HealthTrend {
+        journal_errors_per_day,
+        current,
+        metrics: gather_metric_trends(memory, window).await,
+    }
+}
+
+/// Daily averages of the metrics jarvisd records, for those with data in the window
+pub async fn gather_metric_trends(memory: &MemoryStore, window: ReportWindow) -> Vec<MetricTrend> {
+    let mut trends = Vec::new();
+    for metric in TREND_METRICS {
+        let series = match memory
+            .query_range(metric, window.since, window.until, Duration::days(1))
+            .await
+        {
+            Ok(series) => series,
+            Err(e) => {
+                tracing::warn!("Skipping {} trend: {}", metric, e);
+                continue;
+            }
+        };
+        for series in series {
+            let Some((min, avg, max)) = series.summary() else {
+                continue;
+            };
+            trends.push(MetricTrend {
+                series: series.display_name(),
+                daily_averages: series.points.iter().map(|p| p.avg).collect(),
+                min,
+                avg,
+                max,
+            });
+        }
+    }
+    trends
+}
+
+pub async fn gather_llm_usage(memory: &MemoryStore, window: ReportWindow) -> Result<LlmUsage> {
+    let responses = memory
+        .message_times_since("assistant", window.since)
+        .await?;
+    Ok(LlmUsage {
+        responses: responses.len(),
+        responses_per_day: per_day(&window, responses.into_iter()),
+    })
+}
+
+/// Upgrades worth listing individually; everything else is just counted
+fn is_notable_package(name: &str) -> bool {
+    name.starts_with("linux")
+        || matches!(
+            name,
+            "glibc" | "systemd" | "mesa" | "amd-ucode" | "intel-ucode"
+        )
+        || name.starts_with("nvidia")
+}
+
+/// Bucket timestamps into the window's calendar days
+fn per_day(window: &ReportWindow, times: impl Iterator<Item = DateTime<Utc>>) -> Vec<u32> {
+    let days = window.days();
+    let mut counts = vec![0u32; days.len()];
+    for t in times.filter(|t| window.contains(*t)) {
+        if let Some(i) = days.iter().position(|d| *d == t.date_naive()) {
+            counts[i] += 1;
+        }
+    }
+    counts
+}
+
+/// Package transactions from pacman.log, e.g.
+/// `[2024-05-01T10:00:00+0200] [ALPM] upgraded linux (6.8.1-1 -> 6.8.2-1)`
+pub fn parse_pacman_log(log: &str, window: &ReportWindow) -> Vec<PackageLogEntry> {
+    log.lines()
+        .filter(|line| line.contains("[ALPM]"))
+        .filter_map(|line| {
+            let timestamp = fleet::parse_pacman_log_time(line)?;
+            if !window.contains(timestamp) {
+                return None;
+            }
+            let rest = line.split("[ALPM] ").nth(1)?;
+            let mut words = rest.splitn(3, ' ');
+            let action = words.next()?;
+            if !matches!(action, "installed" | "upgraded" | "downgraded" | "removed") {
+                return None;
+            }
+            let package = words.next()?;
+            let version = words
+                .next()
+                .unwrap_or("")
+                .trim_matches(|c| c == '(' || c == ')');
+
+            Some(PackageLogEntry {
+                timestamp,
+                action: action.to_string(),
+                package: package.to_string(),
+                version: version.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// `arch-audit --format "%n|%s|%c"`: `openssl|High|CVE-2024-0001,CVE-2024-0002`
+pub fn parse_arch_audit(output: &str) -> Vec<SecurityFinding> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut cols = line.split('|');
+            let package = cols.next()?.trim();
+            if package.is_empty() {
+                return None;
+            }
+            Some(SecurityFinding {
+                package: package.to_string(),
+                // arch-audit prints "Unknown" for unrated advisories; count
+                // them as medium rather than hide them
+                severity: cols
+                    .next()
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(Severity::Medium),
+                advisories: cols
+                    .next()
+                    .unwrap_or("")
+                    .split([',', ' '])
+                    .filter(|s| !s.is_empty())
+                    .map(|s| s.to_string())
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+/// `▁▃▅█` style bar for a series
+pub fn sparkline(values: &[u32]) -> String {
+    let max = values.iter().copied().max().unwrap_or(0);
+    if max == 0 {
+        return SPARK_BARS[0].to_string().repeat(values.len());
+    }
+    values
+        .iter()
+        .map(|v| SPARK_BARS[((*v as usize) * (SPARK_BARS.len() - 1)) / max as usize])
+        .collect()
+}
+
+/// Compare the second half of a series with the first half
+pub fn trend_arrow(values: &[u32]) -> &'static str {
+    let mid = values.len() / 2;
+    let (first, second) = values.split_at(mid);
+    let first: u32 = first.iter().sum();
+    let second: u32 = second.iter().sum();
+    if second > first + first / 5 {
+        "↑"
+    } else if second + second / 5 < first {
+        "↓"
+    } else {
+        "→"
+    }
+}
+
+fn trend(values: &[u32]) -> String {
+    format!("`{}` {}", sparkline(values), trend_arrow(values))
+}
+
+/// Ask the LLM for a short executive summary of the gathered data
+pub async fn executive_summary(llm: &LLMRouter, data: &ReportData) -> Result<String> {
+    let facts = render_markdown(data, None);
+    let prompt = format!(
+        "You are summarizing a weekly Linux system report for its owner. Write one \
+         paragraph (at most 5 sentences) covering what changed, anything that needs \
+         attention, and whether the system looks healthy. Plain prose, no headings.\n\n{}",
+        facts
+    );
+    Ok(llm.generate(&prompt, None).await?.trim().to_string())
+}
+
+/// Render the report as markdown, with the executive summary first when given
+pub fn render_markdown(data: &ReportData, summary: Option<&str>) -> String {
+    let mut out = String::new();
+    out.push_str(&format!("# Jarvis Report — {}\n\n", data.host));
+    out.push_str(&format!(
+        "_{} to {} · generated {}_\n\n",
+        data.window.since.format("%Y-%m-%d %H:%M"),
+        data.window.until.format("%Y-%m-%d %H:%M"),
+        data.generated_at.format("%Y-%m-%d %H:%M UTC")
+    ));
+
+    if let Some(summary) = summary {
+        out.push_str("## Executive Summary\n\n");
+        out.push_str(summary);
+        out.push_str("\n\n");
+    }
+
+    // Operations
+    let ops = &data.operations;
+    out.push_str("## Operations\n\n");
+    for (task_type, statuses) in &ops.tasks {
+        let counts: Vec<String> = statuses
+            .iter()
+            .map(|(s, n)| format!("{} {}", n, s.to_lowercase()))
+            .collect();
+        out.push_str(&format!("- **{}**: {}\n", task_type, counts.join(", ")));
+    }
+    out.push_str(&format!(
+        "- **Tool calls**: {} ({} failed) {}\n",
+        ops.tool_calls,
+        ops.tool_errors,
+        trend(&ops.tool_calls_per_day)
+    ));
+    for failure in &ops.recent_failures {
+        out.push_str(&format!("  - ❌ {}\n", failure));
+    }
+    out.push('\n');
+
+    // Packages
+    let pkgs = &data.packages;
+    out.push_str("## Package Changes\n\n");
+    out.push_str(&format!(
+        "- {} upgraded, {} installed, {} removed, {} downgraded {}\n",
+        pkgs.count("upgraded"),
+        pkgs.count("installed"),
+        pkgs.count("removed"),
+        pkgs.count("downgraded"),
+        trend(&pkgs.changes_per_day)
+    ));
+    let notable: Vec<&PackageLogEntry> = pkgs
+        .entries
+        .iter()
+        .filter(|e| e.action != "upgraded" || is_notable_package(&e.package))
+        .take(15)
+        .collect();
+    for entry in notable {
+        out.push_str(&format!(
+            "  - {} `{}` {}\n",
+            entry.action, entry.package, entry.version
+        ));
+    }
+    out.push('\n');
+
+    // Security
+    out.push_str("## Security\n\n");
+    if !data.security.scanned {
+        out.push_str(security_unavailable());
+        out.push_str(&render_image_findings(&data.security));
+    } else {
+        out.push_str(&render_security(&data.security));
+    }
+    out.push('\n');
+
+    // Incidents
+    out.push_str("## Incidents\n\n");
+    out.push_str(&render_incidents(&data.incidents, data.generated_at));
+    out.push('\n');
+
+    // Health
+    let health = &data.health;
+    out.push_str("## System Health\n\n");
+    out.push_str(&format!(
+        "- **Status**: {} {:?}\n",
+        health.current.health.icon(),
+        health.current.health
+    ));
+    out.push_str(&format!(
+        "- **Journal errors**: {} {}\n",
+        health.journal_errors_per_day.iter().sum::<u32>(),
+        trend(&health.journal_errors_per_day)
+    ));
+    if let Some(updates) = health.current.pending_updates {
+        out.push_str(&format!("- **Pending updates**: {}\n", updates));
+    }
+    if let Some(disk) = health.current.worst_disk() {
+        out.push_str(&format!(
+            "- **Fullest filesystem**: {} at {}%\n",
+            disk.mount, disk.used_percent
+        ));
+    }
+    for metric in &health.metrics {
+        out.push_str(&format!(
+            "- **{}**: avg {:.1}, min {:.1}, max {:.1} `{}`\n",
+            metric.series,
+            metric.avg,
+            metric.min,
+            metric.max,
+            metrics::sparkline(&metric.daily_averages)
+        ));
+    }
+    for unit in &health.current.failed_units {
+        out.push_str(&format!("- ❌ Failed unit `{}`\n", unit));
+    }
+    for fs in data.btrfs.filesystems.values() {
+        out.push_str(&format!(
+            "- **Btrfs `{}`**: {}\n",
+            fs.mount,
+            fs.summary(data.generated_at)
+        ));
+    }
+    out.push('\n');
+
+    // LLM usage
+    out.push_str("## LLM Usage\n\n");
+    out.push_str(&format!(
+        "- **Responses**: {} {}\n",
+        data.llm.responses,
+        trend(&data.llm.responses_per_day)
+    ));
+
+    out
+}
+
+/// One bullet per incident with its span and likely causes
+pub fn render_incidents(incidents: &[Incident], now: DateTime<Utc>) -> String {
+    if incidents.is_empty() {
+        return "- No incidents\n".to_string();
+    }
+    let mut out = String::new();
+    for incident in incidents {
+        let span = match incident.resolved_at {
+            Some(_) => format!("resolved after {}", format_duration(incident.duration(now))),
+            None => format!("open for {}", format_duration(incident.duration(now))),
+        };
+        out.push_str(&format!(
+            "- {} **{}** ({}) — {}, {}; {} events across {}\n",
+            if incident.is_open() { "🚨" } else { "✅" },
+            incident.title,
+            incident.severity,
+            incident.opened_at.format("%Y-%m-%d %H:%M"),
+            span,
+            incident.timeline.len(),
+            incident.components.join(", ")
+        ));
+        for cause in &incident.causes {
+            out.push_str(&format!("  - Likely cause: {}\n", cause));
+        }
+    }
+    out
+}
+
+/// Markdown bullets for a security summary: unacknowledged findings first,
+/// then acknowledged advisories with their reason and expiry
+/// Why there is no vulnerability data
+pub fn security_unavailable() -> &'static str {
+    if crate::net::is_offline() {
+        "- Offline, and no earlier `arch-audit` scan is cached; no vulnerability data\n"
+    } else {
+        "- `arch-audit` is not installed; no vulnerability data\n"
+    }
+}
+
+pub fn render_security(security: &SecuritySummary) -> String {
+    let mut out = String::new();
+    if let Some(taken_at) = security.cached_at {
+        out.push_str(&format!(
+            "- 📴 Offline: advisories as of the last scan, {}\n",
+            taken_at.format("%Y-%m-%d %H:%M UTC")
+        ));
+    }
+    if security.findings.is_empty() && security.images.is_empty() {
+        out.push_str("- ✅ No installed packages with unacknowledged vulnerabilities\n");
+    } else {
+        let counts: Vec<String> = security
+            .severity_counts()
+            .iter()
+            .rev()
+            .map(|(severity, n)| format!("{} {}", n, severity.as_str()))
+            .collect();
+        out.push_str(&format!(
+            "- **Status**: {} {}\n",
+            security.health().icon(),
+            counts.join(", ")
+        ));
+        for finding in &security.findings {
+            out.push_str(&format!(
+                "- ⚠️ `{}` ({}): {}\n",
+                finding.package,
+                finding.severity,
+                finding.advisories.join(", ")
+            ));
+        }
+    }
+    out.push_str(&render_image_findings(security));
+
+    if !security.acknowledged.is_empty() {
+        out.push_str("- **Acknowledged**:\n");
+        for ack in &security.acknowledged {
+            let expiry = match ack.until {
+                Some(until) => format!("until {}", until),
+                None => "no expiry".to_string(),
+            };
+            out.push_str(&format!(
+                "  - `{}` {} ({}): {} — {}\n",
+                ack.package, ack.advisory, ack.severity, ack.reason, expiry
+            ));
+        }
+    }
+    out
+}
+
+/// Markdown bullets for vulnerable packages in container images; empty when
+/// there are none
+pub fn render_image_findings(security: &SecuritySummary) -> String {
+    if security.images.is_empty() {
+        return String::new();
+    }
+    let mut out = String::from("- **Container images**:\n");
+    for finding in &security.images {
+        out.push_str(&format!(
+            "  - ⚠️ `{}` ({}): {}\n",
+            finding.package,
+            finding.severity,
+            finding.advisories.join(", ")
+        ));
+    }
+    out
+}
+
+/// Render the report as HTML. `template` may contain `{{title}}` and `{{content}}`.
+pub fn render_html(data: &ReportData, summary: Option<&str>, template: Option<&str>) -> String {
+    let content = markdown_to_html(&render_markdown(data, summary));
+    template
+        .unwrap_or(DEFAULT_HTML_TEMPLATE)
+        .replace(
+            "{{title}}",
+            &escape_html(&format!("Jarvis Report — {}", data.host)),
+        )
+        .replace("{{content}}", &content)
+}
+
+/// Just enough markdown for the reports rendered above: headings, nested
+/// bullets, emphasis, and inline code
+fn markdown_to_html(markdown: &str) -> String {
+    let mut html = String::new();
+    let mut list_depth = 0;
+
+    for line in markdown.lines() {
+        let indent = line.len() - line.trim_start().len();
+        let trimmed = line.trim_start();
+
+        if let Some(item) = trimmed.strip_prefix("- ") {
+            let depth = indent / 2 + 1;
+            while list_depth < depth {
+                html.push_str("<ul>\n");
+                list_depth += 1;
+            }
+            while list_depth > depth {
+                html.push_str("</ul>\n");
+                list_depth -= 1;
+            }
+            html.push_str(&format!("<li>{}</li>\n", inline_html(item)));
+            continue;
+        }
+
+        while list_depth > 0 {
+            html.push_str("</ul>\n");
+            list_depth -= 1;
+        }
+
+        if let Some(heading) = trimmed.strip_prefix("## ") {
+            html.push_str(&format!("<h2>{}</h2>\n", inline_html(heading)));
+        } else if let Some(heading) = trimmed.strip_prefix("# ") {
+            html.push_str(&format!("<h1>{}</h1>\n", inline_html(heading)));
+        } else if !trimmed.is_empty() {
+            html.push_str(&format!("<p>{}</p>\n", inline_html(trimmed)));
+        }
+    }
+    while list_depth > 0 {
+        html.push_str("</ul>\n");
+        list_depth -= 1;
+    }
+
+    html
+}
+
+fn inline_html(text: &str) -> String {
+    let mut html = String::new();
+    let escaped = escape_html(text);
+    let (mut code, mut bold, mut italic) = (false, false, false);
+    let mut chars = escaped.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '`' => {
+                html.push_str(if code { "</code>" } else { "<code>" });
+                code = !code;
+            }
+            '*' if !code && chars.peek() == Some(&'*') => {
+                chars.next();
+                html.push_str(if bold { "</strong>" } else { "<strong>" });
+                bold = !bold;
+            }
+            '_' if !code && (italic || html.is_empty()) => {
+                html.push_str(if italic { "</em>" } else { "<em>" });
+                italic = !italic;
+            }
+            _ => html.push(c),
+        }
+    }
+    html
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Next fire time of a five-field cron schedule after `after`
+pub fn next_scheduled(expr: &str, after: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    // The cron crate expects a leading seconds field
+    let schedule = cron::Schedule::from_str(&format!("0 {}", expr))
+        .with_context(|| format!("Invalid report schedule '{}'", expr))?;
+    schedule
+        .after(&after)
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("Report schedule '{}' never fires", expr))
+}
+
+/// Gather, render, and write a report using the `[reports]` settings.
+/// Returns the paths written, markdown first.
+pub async fn generate_scheduled(
+    config: &ReportConfig,
+    memory: &MemoryStore,
+    llm: Option<&LLMRouter>,
+) -> Result<Vec<PathBuf>> {
+    let data = gather(memory, ReportWindow::parse(&config.since)?).await?;
+    let summary = match llm.filter(|_| config.llm_summary) {
+        Some(llm) => executive_summary(llm, &data)
+            .await
+            .map_err(|e| tracing::warn!("Report summary failed: {}", e))
+            .ok(),
+        None => None,
+    };
+
+    let dir = PathBuf::from(shellexpand::tilde(&config.output_dir).to_string());
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+    let stem = format!("jarvis-report-{}", data.generated_at.format("%Y-%m-%d"));
+
+    let markdown_path = dir.join(format!("{}.md", stem));
+    tokio::fs::write(&markdown_path, render_markdown(&data, summary.as_deref())).await?;
+    let mut written = vec![markdown_path];
+
+    if config.html {
+        let template = match &config.html_template {
+            Some(path) => Some(tokio::fs::read_to_string(shellexpand::tilde(path).as_ref()).await?),
+            None => None,
+        };
+        let html_path = dir.join(format!("{}.html", stem));
+        tokio::fs::write(
+            &html_path,
+            render_html(&data, summary.as_deref(), template.as_deref()),
+        )
+        .await?;
+        written.push(html_path);
+    }
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window() -> ReportWindow {
+        ReportWindow {
+            since: DateTime::parse_from_rfc3339("2024-05-01T00:00:00Z")
+                .unwrap()
+                .with_timezone(&Utc),
+            until: DateTime::parse_from_rfc3339("2024-05-07T23:59:59Z")
+                .unwrap()
+                .with_timezone(&Utc),
+        }
+    }
+
+    #[test]
+    fn test_window_parse() {
+        let window = ReportWindow::parse("48h").unwrap();
+        assert_eq!(window.until - window.since, Duration::hours(48));
+        assert!(window.contains(window.since) && window.contains(window.until));
+        assert!(!window.contains(window.until + Duration::seconds(1)));
+        assert!(ReportWindow::parse("soon").is_err());
+    }
+
+    #[test]
+    fn test_parse_pacman_log() {
+        let log = "[2024-04-30T10:00:00+0000] [ALPM] upgraded old (1-1 -> 2-1)\n\
+                   [2024-05-02T10:00:00+0000] [PACMAN] Running 'pacman -Syu'\n\
+                   [2024-05-02T10:01:00+0000] [ALPM] upgraded linux (6.8.1-1 -> 6.8.2-1)\n\
+                   [2024-05-03T09:00:00+0000] [ALPM] installed ripgrep (14.1.0-1)\n\
+                   [2024-05-03T09:05:00+0000] [ALPM] removed nano (8.0-1)\n\
+                   [2024-05-03T09:05:00+0000] [ALPM] running 'systemd-daemon-reload.hook'...\n";
+        let entries = parse_pacman_log(log, &window());
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].package, "linux");
+        assert_eq!(entries[0].version, "6.8.1-1 -> 6.8.2-1");
+        assert_eq!(entries[2].action, "removed");
+
+        let per_day = per_day(&window(), entries.iter().map(|e| e.timestamp));
+        assert_eq!(per_day, vec![0, 1, 2, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_sparkline_and_trend() {
+        assert_eq!(sparkline(&[0, 4, 8]), "▁▄█");
+        assert_eq!(sparkline(&[0, 0]), "▁▁");
+        assert_eq!(trend_arrow(&[1, 1, 5, 5]), "↑");
+        assert_eq!(trend_arrow(&[5, 5, 1, 1]), "↓");
+        assert_eq!(trend_arrow(&[3, 3, 3, 3]), "→");
+    }
+
+    #[test]
+    fn test_markdown_to_html() {
+        let html =
+            markdown_to_html("## Security\n\n- ⚠️ `openssl` (High)\n  - nested\n- **Bold**: x\n");
+        assert!(html.contains("<h2>Security</h2>"));
+        assert!(html.contains("<li>⚠️ <code>openssl</code> (High)</li>"));
+        assert!(html.contains("<ul>\n<li>nested</li>\n</ul>"));
+        assert!(html.contains("<strong>Bold</strong>"));
+    }
+
+    #[test]
+    fn test_acknowledged_findings_do_not_count() {
+        let security = SecuritySummary {
+            scanned: true,
+            findings: vec![SecurityFinding {
+                package: "curl".to_string(),
+                severity: Severity::Medium,
+                advisories: vec!["CVE-2024-0003".to_string()],
+            }],
+            acknowledged: vec![AcknowledgedFinding {
+                package: "openssl".to_string(),
+                severity: Severity::High,
+                advisory: "CVE-2024-0001".to_string(),
+                reason: "not exposed".to_string(),
+                until: NaiveDate::from_ymd_opt(2025, 6, 1),
+            }],
+            cached_at: None,
+            images: Vec::new(),
+        };
+
+        assert_eq!(security.health(), HostHealth::Warning);
+        assert_eq!(security.severity_counts().get(&Severity::High), None);
+
+        let markdown = render_security(&security);
+        assert!(markdown.contains("1 medium"));
+        assert!(
+            markdown.contains("`openssl` CVE-2024-0001 (High): not exposed — until 2025-06-01")
+        );
+    }
+
+    #[test]
+    fn test_container_image_findings_count_toward_health() {
+        let security = SecuritySummary {
+            scanned: false,
+            findings: Vec::new(),
+            acknowledged: Vec::new(),
+            cached_at: None,
+            images: vec![SecurityFinding {
+                package: "libssl3 in nginx:1.25".to_string(),
+                severity: Severity::Critical,
+                advisories: vec!["CVE-2024-5535".to_string()],
+            }],
+        };
+
+        assert_eq!(security.health(), HostHealth::Critical);
+        assert_eq!(
+            security.severity_counts().get(&Severity::Critical),
+            Some(&1)
+        );
+        assert_eq!(
+            render_image_findings(&security),
+            "- **Container images**:\n  - ⚠️ `libssl3 in nginx:1.25` (Critical): CVE-2024-5535\n"
+        );
+    }
+
+    #[test]
+    fn test_render_incidents() {
+        use crate::incidents::{Correlator, IncidentsConfig, Signal, SignalKind};
+
+        let start = window().since;
+        let mut correlator = Correlator::new(IncidentsConfig::default());
+        correlator.observe(Signal::new(
+            SignalKind::PackageChange,
+            "openssl",
+            Severity::Info,
+            "package_transaction openssl completed",
+            start,
+        ));
+        correlator.observe(Signal::new(
+            SignalKind::HealthDegraded,
+            "nginx.service",
+            Severity::Critical,
+            "nginx.service is now critical",
+            start + Duration::minutes(5),
+        ));
+        let incidents = correlator.open_incidents().to_vec();
+
+        assert_eq!(render_incidents(&[], start), "- No incidents\n");
+        assert_eq!(
+            render_incidents(&incidents, start + Duration::hours(2)),
+            "- 🚨 **nginx.service is now critical** (Critical) — 2024-05-01 00:00, open for 2h 0m; \
+             2 events across nginx.service, openssl\n  - Likely cause: package_transaction openssl \
+             completed (5m before nginx.service is now critical)\n"
+        );
+    }
+}
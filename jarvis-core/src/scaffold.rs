@@ -0,0 +1,221 @@
+//! Project scaffolding from LLM output
+//!
+//! `jarvis write` asks the model to annotate each fenced code block with the
+//! file it belongs to (```` ```rust:src/main.rs ````). This module parses those
+//! blocks and writes them into a target directory without clobbering anything
+//! that was already there.
+
+use anyhow::{Context, Result};
+use std::collections::BTreeSet;
+use std::path::{Component, Path, PathBuf};
+
+/// Appended to the user's description so the response can be parsed
+pub const SCAFFOLD_INSTRUCTIONS: &str = "\
+Respond with complete files, not fragments. Put every file in its own fenced code \
+block whose info string is `<language>:<relative path>`, for example ```rust:src/main.rs \
+or ```toml:Cargo.toml. For a Rust project, list crate dependencies in a ```dependencies \
+block with one `cargo add` argument list per line (e.g. `clap --features derive`) \
+instead of writing Cargo.toml. Keep explanations short and outside the code blocks.";
+
+/// A file the model asked us to create
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratedFile {
+    /// Relative to the output directory
+    pub path: PathBuf,
+    pub language: String,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ScaffoldPlan {
+    pub files: Vec<GeneratedFile>,
+    /// `cargo add` argument lists from a ```dependencies block
+    pub dependencies: Vec<String>,
+}
+
+impl ScaffoldPlan {
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn is_rust(&self) -> bool {
+        self.files.iter().any(|f| {
+            f.language == "rust"
+                || f.path.extension().is_some_and(|e| e == "rs")
+                || f.path == Path::new("Cargo.toml")
+        })
+    }
+
+    pub fn has_manifest(&self) -> bool {
+        self.files.iter().any(|f| f.path == Path::new("Cargo.toml"))
+    }
+
+    /// Files in the plan that already exist under `root`
+    pub fn conflicts(&self, root: &Path) -> Vec<PathBuf> {
+        self.files
+            .iter()
+            .map(|f| root.join(&f.path))
+            .filter(|p| p.exists())
+            .collect()
+    }
+
+    /// Write every file under `root`. Existing files are only replaced with `force`.
+    pub fn write(&self, root: &Path, force: bool) -> Result<Vec<PathBuf>> {
+        if !force {
+            let conflicts = self.conflicts(root);
+            if !conflicts.is_empty() {
+                let list: Vec<String> = conflicts.iter().map(|p| p.display().to_string()).collect();
+                return Err(anyhow::anyhow!(
+                    "Refusing to overwrite existing files (use --force): {}",
+                    list.join(", ")
+                ));
+            }
+        }
+
+        let mut written = Vec::new();
+        for file in &self.files {
+            let path = root.join(&file.path);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create {}", parent.display()))?;
+            }
+            std::fs::write(&path, &file.content)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            written.push(file.path.clone());
+        }
+        Ok(written)
+    }
+}
+
+/// Extract annotated code blocks from a model response. Blocks without a
+/// path, and paths that would escape the output directory, are skipped.
+pub fn parse_response(response: &str) -> ScaffoldPlan {
+    let mut plan = ScaffoldPlan::default();
+    let mut lines = response.lines();
+
+    while let Some(line) = lines.next() {
+        let Some(info) = line.trim_start().strip_prefix("```") else {
+            continue;
+        };
+        let info = info.trim();
+
+        let mut body = Vec::new();
+        for inner in lines.by_ref() {
+            if inner.trim_start().starts_with("```") {
+                break;
+            }
+            body.push(inner);
+        }
+
+        if info == "dependencies" {
+            plan.dependencies.extend(
+                body.iter()
+                    .map(|l| l.trim().trim_start_matches("cargo add ").to_string())
+                    .filter(|l| !l.is_empty() && !l.starts_with('#')),
+            );
+            continue;
+        }
+
+        let Some((language, path)) = info.split_once(':') else {
+            continue;
+        };
+        let path = PathBuf::from(path.trim());
+        if !is_safe_relative(&path) {
+            tracing::warn!("Skipping generated file with unsafe path {}", path.display());
+            continue;
+        }
+
+        let mut content = body.join("\n");
+        content.push('\n');
+        // A later block for the same path replaces the earlier one (repair passes)
+        plan.files.retain(|f| f.path != path);
+        plan.files.push(GeneratedFile {
+            path,
+            language: language.trim().to_lowercase(),
+            content,
+        });
+    }
+
+    plan
+}
+
+/// Relative, and no `..` or root components
+fn is_safe_relative(path: &Path) -> bool {
+    !path.as_os_str().is_empty()
+        && path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+/// `a Rust CLI with clap` -> `a-rust-cli-with-clap`, capped at a few words
+pub fn slug(description: &str) -> String {
+    let slug = description
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .take(6)
+        .collect::<Vec<_>>()
+        .join("-");
+    if slug.is_empty() { "project".to_string() } else { slug }
+}
+
+/// Indented tree of `files` under `root`, sorted by path
+pub fn render_tree(root: &Path, files: &[PathBuf]) -> String {
+    let mut entries = BTreeSet::new();
+    for file in files {
+        let mut prefix = PathBuf::new();
+        let components: Vec<_> = file.components().collect();
+        for (i, component) in components.iter().enumerate() {
+            prefix.push(component);
+            entries.insert((prefix.clone(), i + 1 < components.len()));
+        }
+    }
+
+    let mut out = format!("{}/\n", root.display());
+    for (path, is_dir) in entries {
+        let depth = path.components().count();
+        let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        out.push_str(&format!(
+            "{}{}{}\n",
+            "  ".repeat(depth),
+            name,
+            if is_dir { "/" } else { "" }
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESPONSE: &str = "Here you go:\n\n\
+        ```rust:src/main.rs\nfn main() {\n    println!(\"hi\");\n}\n```\n\n\
+        ```dependencies\nclap --features derive\ncargo add anyhow\n```\n\n\
+        ```bash\ncargo run\n```\n\
+        ```text:../../etc/passwd\nnope\n```\n";
+
+    #[test]
+    fn test_parse_response() {
+        let plan = parse_response(RESPONSE);
+
+        assert_eq!(plan.files.len(), 1);
+        assert_eq!(plan.files[0].path, PathBuf::from("src/main.rs"));
+        assert_eq!(plan.files[0].content, "fn main() {\n    println!(\"hi\");\n}\n");
+        assert_eq!(plan.dependencies, vec!["clap --features derive", "anyhow"]);
+        assert!(plan.is_rust());
+        assert!(!plan.has_manifest());
+    }
+
+    #[test]
+    fn test_slug_and_tree() {
+        assert_eq!(slug("a Rust CLI with clap!"), "a-rust-cli-with-clap");
+        assert_eq!(slug("???"), "project");
+
+        let tree = render_tree(
+            Path::new("out"),
+            &[PathBuf::from("src/main.rs"), PathBuf::from("Cargo.toml")],
+        );
+        assert_eq!(tree, "out/\n  Cargo.toml\n  src/\n    main.rs\n");
+    }
+}
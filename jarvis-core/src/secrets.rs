@@ -0,0 +1,232 @@
+//! Credentials referenced from config rather than written into it
+//!
+//! A [`SecretRef`] names where a credential lives: `env:NAME` reads an
+//! environment variable, `file:PATH` reads a file that only its owner may
+//! read, and `keyring:SERVICE/ACCOUNT` asks the desktop keyring through
+//! `secret-tool`. Resolved values come back as [`Secret`], whose `Debug`
+//! output is redacted so they don't end up in logs or error messages.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::exec::{CommandRunner, SystemRunner};
+
+/// Where a credential is read from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum SecretRef {
+    Env(String),
+    File(PathBuf),
+    Keyring { service: String, account: String },
+}
+
+impl FromStr for SecretRef {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (scheme, target) = s
+            .split_once(':')
+            .filter(|(_, target)| !target.is_empty())
+            .context(
+                "Secret references look like env:NAME, file:PATH, or keyring:SERVICE/ACCOUNT",
+            )?;
+        match scheme {
+            "env" => Ok(SecretRef::Env(target.to_string())),
+            "file" => Ok(SecretRef::File(PathBuf::from(
+                shellexpand::tilde(target).to_string(),
+            ))),
+            "keyring" => {
+                let (service, account) = target
+                    .split_once('/')
+                    .filter(|(s, a)| !s.is_empty() && !a.is_empty())
+                    .context("Keyring references look like keyring:SERVICE/ACCOUNT")?;
+                Ok(SecretRef::Keyring {
+                    service: service.to_string(),
+                    account: account.to_string(),
+                })
+            }
+            _ => anyhow::bail!(
+                "Unknown secret source '{}'; use env:, file:, or keyring:",
+                scheme
+            ),
+        }
+    }
+}
+
+impl TryFrom<String> for SecretRef {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<SecretRef> for String {
+    fn from(reference: SecretRef) -> Self {
+        reference.to_string()
+    }
+}
+
+/// The reference as written in config; never the value
+impl fmt::Display for SecretRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretRef::Env(name) => write!(f, "env:{}", name),
+            SecretRef::File(path) => write!(f, "file:{}", path.display()),
+            SecretRef::Keyring { service, account } => {
+                write!(f, "keyring:{}/{}", service, account)
+            }
+        }
+    }
+}
+
+impl SecretRef {
+    pub async fn resolve(&self) -> Result<Secret> {
+        self.resolve_with(&SystemRunner::default()).await
+    }
+
+    /// Resolve, running `secret-tool` through `runner` for keyring references
+    pub async fn resolve_with(&self, runner: &dyn CommandRunner) -> Result<Secret> {
+        let value = match self {
+            SecretRef::Env(name) => std::env::var(name)
+                .with_context(|| format!("Environment variable {} is not set", name))?,
+            SecretRef::File(path) => {
+                check_permissions(path)?;
+                tokio::fs::read_to_string(path)
+                    .await
+                    .with_context(|| format!("Failed to read secret file {}", path.display()))?
+            }
+            SecretRef::Keyring { service, account } => {
+                let output = runner
+                    .output(
+                        "secret-tool",
+                        &["lookup", "service", service, "account", account],
+                    )
+                    .await
+                    .context("Failed to run secret-tool; is libsecret installed?")?;
+                if !output.status.success() {
+                    anyhow::bail!("No keyring entry for {}", self);
+                }
+                String::from_utf8(output.stdout).context("Keyring entry is not UTF-8")?
+            }
+        };
+
+        let value = value.trim();
+        if value.is_empty() {
+            anyhow::bail!("Secret {} is empty", self);
+        }
+        Ok(Secret(value.to_string()))
+    }
+}
+
+/// Key files shared with the group or everyone are refused, as ssh does
+fn check_permissions(path: &std::path::Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = std::fs::metadata(path)
+        .with_context(|| format!("Failed to read secret file {}", path.display()))?
+        .permissions()
+        .mode();
+    if mode & 0o077 != 0 {
+        anyhow::bail!(
+            "Secret file {} is readable by other users (mode {:o}); chmod 600 it",
+            path.display(),
+            mode & 0o777
+        );
+    }
+    Ok(())
+}
+
+/// A resolved credential
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(<redacted>)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::{RecordingRunner, fake_output};
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_parse_secret_refs() {
+        assert_eq!(
+            "env:DEPLOYER_KEY".parse::<SecretRef>().unwrap(),
+            SecretRef::Env("DEPLOYER_KEY".to_string())
+        );
+        assert_eq!(
+            "keyring:jarvis/deployer".parse::<SecretRef>().unwrap(),
+            SecretRef::Keyring {
+                service: "jarvis".to_string(),
+                account: "deployer".to_string(),
+            }
+        );
+        let file: SecretRef = serde_json::from_value(serde_json::json!("file:/etc/key")).unwrap();
+        assert_eq!(file.to_string(), "file:/etc/key");
+
+        assert!("DEPLOYER_KEY".parse::<SecretRef>().is_err());
+        assert!("env:".parse::<SecretRef>().is_err());
+        assert!("keyring:jarvis".parse::<SecretRef>().is_err());
+        assert!("vault:kv/deployer".parse::<SecretRef>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_env_and_file() {
+        unsafe { std::env::set_var("JARVIS_SECRETS_TEST_KEY", " 0xabc\n") };
+        let secret = SecretRef::Env("JARVIS_SECRETS_TEST_KEY".to_string())
+            .resolve()
+            .await
+            .unwrap();
+        assert_eq!(secret.expose(), "0xabc");
+        assert_eq!(format!("{:?}", secret), "Secret(<redacted>)");
+        assert!(
+            SecretRef::Env("JARVIS_SECRETS_TEST_UNSET".to_string())
+                .resolve()
+                .await
+                .is_err()
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("deployer.key");
+        std::fs::write(&path, "0xdef\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        let reference = SecretRef::File(path.clone());
+        let error = reference.resolve().await.unwrap_err();
+        assert!(error.to_string().contains("readable by other users"));
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        assert_eq!(reference.resolve().await.unwrap().expose(), "0xdef");
+    }
+
+    #[tokio::test]
+    async fn test_resolve_keyring_through_secret_tool() {
+        let reference: SecretRef = "keyring:jarvis/deployer".parse().unwrap();
+        let runner = RecordingRunner::new().respond(
+            "secret-tool lookup service jarvis account deployer",
+            "0x123\n",
+        );
+        let secret = reference.resolve_with(&runner).await.unwrap();
+        assert_eq!(secret.expose(), "0x123");
+
+        let runner = RecordingRunner::new().respond_with("secret-tool", fake_output(1, "", ""));
+        let error = reference.resolve_with(&runner).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "No keyring entry for keyring:jarvis/deployer"
+        );
+    }
+}
@@ -0,0 +1,387 @@
+//! Signed self-update from GitHub releases
+//!
+//! Each release publishes one plain binary per target triple, named
+//! `jarvis-<version>-<triple>` (and `jarvisd-...`), with a minisign signature
+//! beside it as `<asset>.minisig`. A download is only installed if its
+//! signature verifies against the public key embedded at build time and the
+//! signature's trusted comment names that exact file, so an older signed
+//! binary can't be passed off as a newer one. The new binary is written next
+//! to the old one and renamed over it: the rename is atomic, and a running
+//! process keeps executing the old inode until it exits.
+
+use anyhow::{Context, Result, bail};
+use minisign_verify::{PublicKey, Signature};
+use reqwest::header::{ACCEPT, USER_AGENT};
+use semver::Version;
+use serde::Deserialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::error::JarvisError;
+use crate::net::{self, CheckedSend};
+
+const RELEASES_API: &str = "https://api.github.com/repos/ghostkellz/jarvis/releases";
+
+/// Minisign public key release artifacts are signed with, embedded from
+/// `JARVIS_RELEASE_PUBKEY` when the release is built. Builds without one
+/// (from source, from a distribution package) can't self-update.
+pub const RELEASE_PUBLIC_KEY: Option<&str> = option_env!("JARVIS_RELEASE_PUBKEY");
+
+/// A GitHub release, as far as self-update cares
+#[derive(Debug, Clone, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    #[serde(default)]
+    pub html_url: String,
+    #[serde(default)]
+    pub assets: Vec<ReleaseAsset>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReleaseAsset {
+    pub name: String,
+    pub browser_download_url: String,
+    #[serde(default)]
+    pub size: u64,
+}
+
+impl Release {
+    /// The version in the tag, with or without a leading `v`
+    pub fn version(&self) -> Result<Version> {
+        let tag = self.tag_name.trim_start_matches('v');
+        Version::parse(tag)
+            .with_context(|| format!("Release tag {} is not a version", self.tag_name))
+    }
+
+    pub fn asset(&self, name: &str) -> Option<&ReleaseAsset> {
+        self.assets.iter().find(|asset| asset.name == name)
+    }
+}
+
+/// How a release relates to the running version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateKind {
+    UpToDate,
+    Upgrade,
+    Downgrade,
+}
+
+pub fn compare(current: &Version, release: &Version) -> UpdateKind {
+    match release.cmp(current) {
+        std::cmp::Ordering::Greater => UpdateKind::Upgrade,
+        std::cmp::Ordering::Equal => UpdateKind::UpToDate,
+        std::cmp::Ordering::Less => UpdateKind::Downgrade,
+    }
+}
+
+/// Triple of this build, as used in asset names
+pub fn target_triple() -> String {
+    let env = if cfg!(target_env = "musl") {
+        "musl"
+    } else {
+        "gnu"
+    };
+    format!(
+        "{}-unknown-{}-{}",
+        std::env::consts::ARCH,
+        std::env::consts::OS,
+        env
+    )
+}
+
+/// Asset name of `binary` at `version` for `triple`
+pub fn asset_name(binary: &str, version: &Version, triple: &str) -> String {
+    format!("{}-{}-{}", binary, version, triple)
+}
+
+/// The latest release, or the one tagged `version` (`1.2.0` or `v1.2.0`)
+pub async fn fetch_release(version: Option<&str>) -> Result<Release> {
+    let url = match version {
+        Some(version) => format!("{}/tags/v{}", RELEASES_API, version.trim_start_matches('v')),
+        None => format!("{}/latest", RELEASES_API),
+    };
+    let response = net::client()
+        .get(&url)
+        .header(USER_AGENT, concat!("jarvis/", env!("CARGO_PKG_VERSION")))
+        .header(ACCEPT, "application/vnd.github+json")
+        .timeout(Duration::from_secs(30))
+        .checked_send()
+        .await?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Err(JarvisError::NotFound(match version {
+            Some(version) => format!("No release tagged v{}", version.trim_start_matches('v')),
+            None => "No published release".to_string(),
+        })
+        .into());
+    }
+    Ok(response.error_for_status()?.json().await?)
+}
+
+/// Download one release asset
+pub async fn download(asset: &ReleaseAsset) -> Result<Vec<u8>> {
+    let response = net::client()
+        .get(&asset.browser_download_url)
+        .header(USER_AGENT, concat!("jarvis/", env!("CARGO_PKG_VERSION")))
+        .timeout(Duration::from_secs(300))
+        .checked_send()
+        .await?
+        .error_for_status()
+        .with_context(|| format!("Failed to download {}", asset.name))?;
+    Ok(response.bytes().await?.to_vec())
+}
+
+/// Check that `signature` (the contents of a `.minisig` file) signs `data`
+/// under `public_key` (base64, or a two-line `minisign.pub`), and that its
+/// trusted comment names `file_name`
+pub fn verify_signature(
+    data: &[u8],
+    signature: &str,
+    public_key: &str,
+    file_name: &str,
+) -> Result<()> {
+    let public_key = if public_key.trim().contains('\n') {
+        PublicKey::decode(public_key.trim())
+    } else {
+        PublicKey::from_base64(public_key.trim())
+    }
+    .map_err(|e| anyhow::anyhow!("Invalid release public key: {}", e))?;
+    let signature = Signature::decode(signature)
+        .map_err(|e| anyhow::anyhow!("Invalid signature for {}: {}", file_name, e))?;
+
+    public_key
+        .verify(data, &signature, false)
+        .map_err(|e| anyhow::anyhow!("Signature check of {} failed: {}", file_name, e))?;
+
+    // minisign puts `file:<name>` in the trusted comment, which the global
+    // signature covers
+    let expected = format!("file:{}", file_name);
+    if !signature
+        .trusted_comment()
+        .split('\t')
+        .any(|field| field == expected)
+    {
+        bail!(
+            "Signature of {} was made for a different file (trusted comment: {})",
+            file_name,
+            signature.trusted_comment()
+        );
+    }
+    Ok(())
+}
+
+/// A new binary written beside the one it replaces, not yet in place.
+/// Dropping it without [`StagedBinary::commit`] removes the temp file.
+#[derive(Debug)]
+pub struct StagedBinary {
+    temp: Option<PathBuf>,
+    target: PathBuf,
+}
+
+impl StagedBinary {
+    /// Write `contents` to a temp file in `target`'s directory, with
+    /// `target`'s permissions (or 0755 if it doesn't exist)
+    pub fn stage(target: &Path, contents: &[u8]) -> Result<Self> {
+        let dir = target
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        let name = target
+            .file_name()
+            .with_context(|| format!("{} is not a file path", target.display()))?
+            .to_string_lossy();
+        let temp = dir.join(format!(".{}.update-{}", name, std::process::id()));
+        let mode = fs::metadata(target)
+            .map(|meta| meta.permissions().mode() & 0o7777)
+            .unwrap_or(0o755);
+
+        let mut file = match OpenOptions::new().write(true).create_new(true).open(&temp) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+                return Err(JarvisError::PermissionDenied(format!(
+                    "Cannot write to {}; re-run with sudo, or update through the package manager that installed {}",
+                    dir.display(),
+                    target.display()
+                ))
+                .into());
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to create {}", temp.display()));
+            }
+        };
+        let staged = Self {
+            temp: Some(temp),
+            target: target.to_path_buf(),
+        };
+        file.write_all(contents)?;
+        file.set_permissions(fs::Permissions::from_mode(mode))?;
+        file.sync_all()?;
+        Ok(staged)
+    }
+
+    /// Where the new binary waits
+    pub fn path(&self) -> &Path {
+        self.temp.as_deref().unwrap_or(&self.target)
+    }
+
+    pub fn target(&self) -> &Path {
+        &self.target
+    }
+
+    /// Rename the new binary over the target. Writing into a running
+    /// executable fails with "text file busy", but replacing its directory
+    /// entry is fine: the running process keeps the old inode.
+    pub fn commit(mut self) -> Result<()> {
+        let Some(temp) = self.temp.take() else {
+            return Ok(());
+        };
+        if let Err(e) = fs::rename(&temp, &self.target) {
+            let _ = fs::remove_file(&temp);
+            return Err(e).with_context(|| format!("Failed to replace {}", self.target.display()));
+        }
+        // Make the rename itself durable
+        if let Some(dir) = self
+            .target
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            && let Ok(dir) = File::open(dir)
+        {
+            let _ = dir.sync_all();
+        }
+        Ok(())
+    }
+}
+
+impl Drop for StagedBinary {
+    fn drop(&mut self) {
+        if let Some(temp) = self.temp.take() {
+            let _ = fs::remove_file(temp);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD;
+    use blake2::{Blake2b512, Digest};
+    use ed25519_dalek::{Signer, SigningKey};
+    use std::io::Read;
+
+    const KEY_ID: [u8; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+
+    fn test_key(seed: u8) -> (SigningKey, String) {
+        let key = SigningKey::from_bytes(&[seed; 32]);
+        let mut public = b"Ed".to_vec();
+        public.extend_from_slice(&KEY_ID);
+        public.extend_from_slice(key.verifying_key().as_bytes());
+        (key, STANDARD.encode(public))
+    }
+
+    /// A prehashed minisign signature, as `minisign -S` writes it
+    fn sign(key: &SigningKey, data: &[u8], trusted_comment: &str) -> String {
+        let signature = key.sign(&Blake2b512::digest(data)).to_bytes();
+        let mut line = b"ED".to_vec();
+        line.extend_from_slice(&KEY_ID);
+        line.extend_from_slice(&signature);
+
+        let mut global = signature.to_vec();
+        global.extend_from_slice(trusted_comment.as_bytes());
+        let global = key.sign(&global).to_bytes();
+
+        format!(
+            "untrusted comment: signature from minisign secret key\n{}\ntrusted comment: {}\n{}\n",
+            STANDARD.encode(line),
+            trusted_comment,
+            STANDARD.encode(global)
+        )
+    }
+
+    #[test]
+    fn test_signature_must_match_key_data_and_file_name() {
+        let (key, public) = test_key(7);
+        let name = "jarvis-0.3.0-x86_64-unknown-linux-gnu";
+        let binary = b"\x7fELF new jarvis";
+        let signature = sign(
+            &key,
+            binary,
+            &format!("timestamp:1760000000\tfile:{}\thashed", name),
+        );
+
+        verify_signature(binary, &signature, &public, name).unwrap();
+        // minisign.pub format works too
+        let pub_file = format!("untrusted comment: minisign public key\n{}\n", public);
+        verify_signature(binary, &signature, &pub_file, name).unwrap();
+
+        let tampered = verify_signature(b"\x7fELF evil jarvis", &signature, &public, name);
+        assert!(tampered.unwrap_err().to_string().contains("failed"));
+
+        let (_, other_public) = test_key(9);
+        assert!(verify_signature(binary, &signature, &other_public, name).is_err());
+
+        // A genuine signature of an older release can't stand in for this one
+        let old = sign(
+            &key,
+            binary,
+            "timestamp:1750000000\tfile:jarvis-0.2.0-x86_64-unknown-linux-gnu\thashed",
+        );
+        let err = verify_signature(binary, &old, &public, name).unwrap_err();
+        assert!(err.to_string().contains("different file"));
+
+        // Editing the trusted comment breaks the global signature
+        let forged = old.replace("jarvis-0.2.0", "jarvis-0.3.0");
+        assert!(verify_signature(binary, &forged, &public, name).is_err());
+    }
+
+    #[test]
+    fn test_staged_binary_replaces_target_atomically() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("jarvis");
+        fs::write(&target, b"old binary").unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o750)).unwrap();
+        // Stands in for the running process, which holds the old inode
+        let mut running = File::open(&target).unwrap();
+
+        let staged = StagedBinary::stage(&target, b"new binary").unwrap();
+        assert_eq!(fs::read(&target).unwrap(), b"old binary");
+        assert_eq!(fs::read(staged.path()).unwrap(), b"new binary");
+        staged.commit().unwrap();
+
+        assert_eq!(fs::read(&target).unwrap(), b"new binary");
+        let mode = fs::metadata(&target).unwrap().permissions().mode() & 0o7777;
+        assert_eq!(mode, 0o750);
+        let mut old = String::new();
+        running.read_to_string(&mut old).unwrap();
+        assert_eq!(old, "old binary");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        // Abandoning an update leaves the target alone and no temp file behind
+        let staged = StagedBinary::stage(&target, b"newer binary").unwrap();
+        drop(staged);
+        assert_eq!(fs::read(&target).unwrap(), b"new binary");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_release_versions_and_assets() {
+        let release: Release = serde_json::from_str(
+            r#"{"tag_name": "v0.3.0", "assets": [
+                {"name": "jarvis-0.3.0-x86_64-unknown-linux-gnu",
+                 "browser_download_url": "https://example.invalid/jarvis", "size": 10}
+            ]}"#,
+        )
+        .unwrap();
+        let version = release.version().unwrap();
+        let name = asset_name("jarvis", &version, "x86_64-unknown-linux-gnu");
+        assert!(release.asset(&name).is_some());
+        assert!(release.asset(&format!("{}.minisig", name)).is_none());
+
+        let current = Version::parse("0.2.0").unwrap();
+        assert_eq!(compare(&current, &version), UpdateKind::Upgrade);
+        assert_eq!(compare(&version, &version), UpdateKind::UpToDate);
+        assert_eq!(compare(&version, &current), UpdateKind::Downgrade);
+    }
+}
@@ -0,0 +1,872 @@
+//! Hardware sensors from sysfs: temperatures, fans, package power, batteries
+//!
+//! hwmon chips under `/sys/class/hwmon` report millidegrees and RPM, with
+//! optional `_label`, `_max`, `_min`, and `_crit` files beside each input.
+//! Only readings worth watching are kept: the CPU package (coretemp's
+//! "Package id", k10temp's Tctl/Tdie), NVMe composite temperatures, labeled
+//! sensors of other chips such as the chipset and VRMs, and fans that spin or
+//! should. GPU chips are left to [`crate::gpu`]. Package power comes from the
+//! RAPL energy counters under `/sys/class/powercap`, so it needs two samples;
+//! batteries come from `/sys/class/power_supply`. Anything missing,
+//! unreadable, or implausible is skipped rather than reported as zero.
+
+use crate::fleet::HostHealth;
+use crate::metrics::MetricSample;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// hwmon drivers whose readings [`crate::gpu`] reports
+const GPU_CHIPS: &[&str] = &["amdgpu", "radeon", "nouveau", "i915", "xe"];
+
+/// Temperatures outside this range are sensor garbage: unconnected inputs
+/// read -128, 127, or 255
+const PLAUSIBLE_TEMP_C: std::ops::RangeInclusive<f64> = -20.0..=125.0;
+
+/// Driver crit values outside this range are ignored in favour of the config
+const PLAUSIBLE_CRIT_C: std::ops::RangeInclusive<f64> = 40.0..=150.0;
+
+/// Degrees below a driver's crit value where the warning starts, when the
+/// driver has no max value
+const WARNING_MARGIN_C: f64 = 10.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SensorsConfig {
+    /// Read sensors on each jarvisd health check
+    pub enabled: bool,
+    /// Consecutive health checks a threshold must stay breached before an alert
+    pub sustain_checks: u32,
+    /// Used for temperatures whose driver gives no crit value
+    pub temperature_warning_c: f64,
+    pub temperature_critical_c: f64,
+    /// Full-charge capacity as a percentage of design capacity
+    pub battery_health_warning_percent: f64,
+    /// Per-sensor overrides keyed by sensor id, e.g. `"nvme0/Composite"`
+    pub thresholds: BTreeMap<String, Thresholds>,
+}
+
+impl Default for SensorsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            sustain_checks: 3,
+            temperature_warning_c: 80.0,
+            temperature_critical_c: 90.0,
+            battery_health_warning_percent: 70.0,
+            thresholds: BTreeMap::new(),
+        }
+    }
+}
+
+/// Where a reading turns into a warning or becomes critical. Temperatures
+/// and power breach upward; fans breach when they drop below.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Thresholds {
+    pub warning: Option<f64>,
+    pub critical: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SensorKind {
+    Temperature,
+    Fan,
+    Power,
+}
+
+impl SensorKind {
+    pub fn unit(&self) -> &'static str {
+        match self {
+            SensorKind::Temperature => "°C",
+            SensorKind::Fan => "RPM",
+            SensorKind::Power => "W",
+        }
+    }
+
+    /// Name the reading is recorded under, see [`crate::metrics`]
+    pub fn metric(&self) -> &'static str {
+        match self {
+            SensorKind::Temperature => "sensor.temperature_c",
+            SensorKind::Fan => "sensor.fan_rpm",
+            SensorKind::Power => "sensor.power_w",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SensorReading {
+    /// `chip/label`, e.g. "coretemp/Package id 0"; overrides are keyed by it
+    pub id: String,
+    pub chip: String,
+    pub label: String,
+    pub kind: SensorKind,
+    pub value: f64,
+    pub thresholds: Thresholds,
+}
+
+impl SensorReading {
+    fn new(chip: &str, label: &str, kind: SensorKind, value: f64, thresholds: Thresholds) -> Self {
+        Self {
+            id: format!("{}/{}", chip, label),
+            chip: chip.to_string(),
+            label: label.to_string(),
+            kind,
+            value,
+            thresholds,
+        }
+    }
+
+    pub fn assess(&self) -> HostHealth {
+        let breached = |limit: Option<f64>| {
+            limit.is_some_and(|limit| match self.kind {
+                SensorKind::Fan => self.value < limit,
+                _ => self.value >= limit,
+            })
+        };
+        if breached(self.thresholds.critical) {
+            HostHealth::Critical
+        } else if breached(self.thresholds.warning) {
+            HostHealth::Warning
+        } else {
+            HostHealth::Healthy
+        }
+    }
+
+    /// e.g. "coretemp/Package id 0 is at 96 °C (critical at 100)"
+    pub fn describe(&self) -> String {
+        if self.kind == SensorKind::Fan && self.value <= 0.0 {
+            return format!("{} has stopped", self.id);
+        }
+        let relation = if self.kind == SensorKind::Fan {
+            "below"
+        } else {
+            "at"
+        };
+        let limit = match self.assess() {
+            HostHealth::Critical => self.thresholds.critical.map(|c| ("critical", c)),
+            HostHealth::Warning => self.thresholds.warning.map(|w| ("warning", w)),
+            _ => None,
+        };
+        let mut text = format!("{} is at {:.0} {}", self.id, self.value, self.kind.unit());
+        if let Some((level, limit)) = limit {
+            text.push_str(&format!(" ({} {} {:.0})", level, relation, limit));
+        }
+        text
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatteryHealth {
+    /// e.g. "BAT0"
+    pub name: String,
+    /// None when the firmware doesn't count cycles
+    pub cycle_count: Option<u32>,
+    /// In µWh or µAh, whichever the battery reports
+    pub design_capacity: f64,
+    pub full_capacity: f64,
+    pub health_percent: f64,
+}
+
+impl BatteryHealth {
+    pub fn id(&self) -> String {
+        format!("battery/{}", self.name)
+    }
+
+    pub fn assess(&self, config: &SensorsConfig) -> HostHealth {
+        if self.health_percent < config.battery_health_warning_percent {
+            HostHealth::Warning
+        } else {
+            HostHealth::Healthy
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let mut text = format!(
+            "{} holds {:.0}% of its design capacity",
+            self.name, self.health_percent
+        );
+        if let Some(cycles) = self.cycle_count {
+            text.push_str(&format!(" after {} cycles", cycles));
+        }
+        text
+    }
+}
+
+/// One round of readings
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SensorSnapshot {
+    pub sensors: Vec<SensorReading>,
+    pub batteries: Vec<BatteryHealth>,
+}
+
+impl SensorSnapshot {
+    /// Labeled samples for the metrics store
+    pub fn metrics(&self, now: DateTime<Utc>) -> Vec<MetricSample> {
+        let mut samples: Vec<MetricSample> = self
+            .sensors
+            .iter()
+            .map(|sensor| {
+                MetricSample::new(sensor.kind.metric(), sensor.value, now)
+                    .with_label("chip", &sensor.chip)
+                    .with_label("sensor", &sensor.label)
+            })
+            .collect();
+        for battery in &self.batteries {
+            samples.push(
+                MetricSample::new("battery.health_percent", battery.health_percent, now)
+                    .with_label("battery", &battery.name),
+            );
+            if let Some(cycles) = battery.cycle_count {
+                samples.push(
+                    MetricSample::new("battery.cycle_count", cycles as f64, now)
+                        .with_label("battery", &battery.name),
+                );
+            }
+        }
+        samples
+    }
+
+    /// Health of every sensor and battery by id, with what it reads
+    pub fn assess(&self, config: &SensorsConfig) -> Vec<(String, HostHealth, String)> {
+        let sensors = self
+            .sensors
+            .iter()
+            .map(|sensor| (sensor.id.clone(), sensor.assess(), sensor.describe()));
+        let batteries = self
+            .batteries
+            .iter()
+            .map(|battery| (battery.id(), battery.assess(config), battery.describe()));
+        sensors.chain(batteries).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sensors.is_empty() && self.batteries.is_empty()
+    }
+}
+
+/// Reads sensors under a sysfs root, keeping the RAPL energy counters
+/// between samples to turn them into watts
+pub struct SensorSampler {
+    sysfs: PathBuf,
+    /// Energy counter and sample time per RAPL zone
+    energy: HashMap<String, (u64, DateTime<Utc>)>,
+}
+
+impl Default for SensorSampler {
+    fn default() -> Self {
+        Self::new("/sys")
+    }
+}
+
+impl SensorSampler {
+    pub fn new(sysfs: impl Into<PathBuf>) -> Self {
+        Self {
+            sysfs: sysfs.into(),
+            energy: HashMap::new(),
+        }
+    }
+
+    /// Every reading, with thresholds from the drivers overridden by `config`.
+    /// Package power appears from the second sample on.
+    pub fn sample(&mut self, config: &SensorsConfig, now: DateTime<Utc>) -> SensorSnapshot {
+        let mut sensors = read_hwmon(&self.sysfs.join("class/hwmon"), config);
+        sensors.extend(self.read_rapl(now));
+        for sensor in &mut sensors {
+            if let Some(overrides) = config.thresholds.get(&sensor.id) {
+                sensor.thresholds.warning = overrides.warning.or(sensor.thresholds.warning);
+                sensor.thresholds.critical = overrides.critical.or(sensor.thresholds.critical);
+            }
+        }
+        SensorSnapshot {
+            sensors,
+            batteries: read_batteries(&self.sysfs.join("class/power_supply")),
+        }
+    }
+
+    fn read_rapl(&mut self, now: DateTime<Utc>) -> Vec<SensorReading> {
+        let mut readings = Vec::new();
+        // Top-level zones are packages ("intel-rapl:0"); subzones ("intel-rapl:0:0")
+        // are parts of them. AMD CPUs use the same interface.
+        for zone in subdirectories(&self.sysfs.join("class/powercap")) {
+            let Some(zone_name) = zone.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            if !zone_name.starts_with("intel-rapl:") || zone_name.matches(':').count() != 1 {
+                continue;
+            }
+            // energy_uj is root-only on current kernels
+            let Some(energy) = read_number::<u64>(&zone.join("energy_uj")) else {
+                continue;
+            };
+            let label = read_trimmed(&zone.join("name")).unwrap_or_else(|| zone_name.to_string());
+            let previous = self.energy.insert(zone_name.to_string(), (energy, now));
+            let Some((previous_energy, previous_at)) = previous else {
+                continue;
+            };
+            let seconds = (now - previous_at).num_milliseconds() as f64 / 1000.0;
+            if seconds <= 0.0 {
+                continue;
+            }
+            let delta = if energy >= previous_energy {
+                energy - previous_energy
+            } else {
+                // The counter wrapped
+                match read_number::<u64>(&zone.join("max_energy_range_uj")) {
+                    Some(range) if range > previous_energy => range - previous_energy + energy,
+                    _ => continue,
+                }
+            };
+            readings.push(SensorReading::new(
+                "rapl",
+                &label,
+                SensorKind::Power,
+                delta as f64 / 1_000_000.0 / seconds,
+                Thresholds::default(),
+            ));
+        }
+        readings
+    }
+}
+
+/// Temperatures and fans of every hwmon chip under `hwmon`
+pub fn read_hwmon(hwmon: &Path, config: &SensorsConfig) -> Vec<SensorReading> {
+    let mut readings = Vec::new();
+    let mut chips = subdirectories(hwmon);
+    chips.sort();
+    for chip_dir in chips {
+        let Some(name) = read_trimmed(&chip_dir.join("name")) else {
+            continue;
+        };
+        if GPU_CHIPS.contains(&name.as_str()) {
+            continue;
+        }
+        let chip = chip_name(&chip_dir, &name);
+        readings.extend(read_temperatures(&chip_dir, &name, &chip, config));
+        readings.extend(read_fans(&chip_dir, &chip));
+    }
+    readings
+}
+
+/// NVMe drives all register as "nvme", so they are told apart by device
+fn chip_name(chip_dir: &Path, name: &str) -> String {
+    if name == "nvme"
+        && let Ok(device) = fs::read_link(chip_dir.join("device"))
+        && let Some(device) = device.file_name().and_then(|d| d.to_str())
+    {
+        return device.to_string();
+    }
+    name.to_string()
+}
+
+fn read_temperatures(
+    chip_dir: &Path,
+    driver: &str,
+    chip: &str,
+    config: &SensorsConfig,
+) -> Vec<SensorReading> {
+    let indices = input_indices(chip_dir, "temp");
+    let label_of = |index: u32| read_trimmed(&chip_dir.join(format!("temp{}_label", index)));
+    // Tdie is Tctl without the fan-curve offset, so only one of them is kept
+    let has_tdie = indices
+        .iter()
+        .any(|&index| label_of(index).as_deref() == Some("Tdie"));
+
+    let mut readings = Vec::new();
+    for index in indices {
+        let file = |suffix: &str| chip_dir.join(format!("temp{}_{}", index, suffix));
+        let Some(value) = read_number::<f64>(&file("input")).map(|m| m / 1000.0) else {
+            continue;
+        };
+        if !PLAUSIBLE_TEMP_C.contains(&value) {
+            continue;
+        }
+        let label = match (driver, label_of(index)) {
+            ("coretemp", Some(label)) if label.starts_with("Package id") => label,
+            ("coretemp", _) => continue,
+            ("k10temp" | "zenpower", Some(label))
+                if label == "Tdie" || (label == "Tctl" && !has_tdie) =>
+            {
+                label
+            }
+            ("k10temp" | "zenpower", _) => continue,
+            ("nvme", Some(label)) if label == "Composite" => label,
+            ("nvme", None) if index == 1 => "Composite".to_string(),
+            ("nvme", _) => continue,
+            (driver, None) if driver.starts_with("pch_") => "PCH".to_string(),
+            (_, Some(label)) => label,
+            (_, None) => continue,
+        };
+
+        let crit = read_number::<f64>(&file("crit"))
+            .map(|m| m / 1000.0)
+            .filter(|c| PLAUSIBLE_CRIT_C.contains(c));
+        let max = read_number::<f64>(&file("max"))
+            .map(|m| m / 1000.0)
+            .filter(|m| PLAUSIBLE_CRIT_C.contains(m));
+        let thresholds = match crit {
+            Some(crit) => Thresholds {
+                warning: Some(
+                    max.filter(|max| *max < crit)
+                        .unwrap_or(crit - WARNING_MARGIN_C),
+                ),
+                critical: Some(crit),
+            },
+            None => Thresholds {
+                warning: Some(config.temperature_warning_c),
+                critical: Some(config.temperature_critical_c),
+            },
+        };
+        readings.push(SensorReading::new(
+            chip,
+            &label,
+            SensorKind::Temperature,
+            value,
+            thresholds,
+        ));
+    }
+    readings
+}
+
+fn read_fans(chip_dir: &Path, chip: &str) -> Vec<SensorReading> {
+    let mut readings = Vec::new();
+    for index in input_indices(chip_dir, "fan") {
+        let file = |suffix: &str| chip_dir.join(format!("fan{}_{}", index, suffix));
+        let Some(rpm) = read_number::<f64>(&file("input")) else {
+            continue;
+        };
+        let min = read_number::<f64>(&file("min")).filter(|min| *min > 0.0);
+        // An empty header reads 0; a stopped fan with a minimum is a stall
+        if (rpm <= 0.0 && min.is_none()) || rpm > 30_000.0 {
+            continue;
+        }
+        let label = read_trimmed(&file("label")).unwrap_or_else(|| format!("fan{}", index));
+        let thresholds = Thresholds {
+            warning: min,
+            critical: min.map(|_| 1.0),
+        };
+        readings.push(SensorReading::new(
+            chip,
+            &label,
+            SensorKind::Fan,
+            rpm,
+            thresholds,
+        ));
+    }
+    readings
+}
+
+/// Batteries under `power_supply`; peripherals such as mice are left out
+pub fn read_batteries(power_supply: &Path) -> Vec<BatteryHealth> {
+    let mut batteries = Vec::new();
+    let mut supplies = subdirectories(power_supply);
+    supplies.sort();
+    for supply in supplies {
+        if read_trimmed(&supply.join("type")).as_deref() != Some("Battery")
+            || read_trimmed(&supply.join("scope")).as_deref() == Some("Device")
+        {
+            continue;
+        }
+        let capacity = |kind: &str| {
+            let design = read_number::<f64>(&supply.join(format!("{}_full_design", kind)))?;
+            let full = read_number::<f64>(&supply.join(format!("{}_full", kind)))?;
+            (design > 0.0 && full > 0.0).then_some((design, full))
+        };
+        let Some((design, full)) = capacity("energy").or_else(|| capacity("charge")) else {
+            continue;
+        };
+        let Some(name) = supply.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        batteries.push(BatteryHealth {
+            name: name.to_string(),
+            cycle_count: read_number::<u32>(&supply.join("cycle_count")).filter(|c| *c > 0),
+            design_capacity: design,
+            full_capacity: full,
+            health_percent: full * 100.0 / design,
+        });
+    }
+    batteries
+}
+
+/// The N of every `{prefix}N_input` in `dir`, ascending
+fn input_indices(dir: &Path, prefix: &str) -> Vec<u32> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut indices: Vec<u32> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let name = name.to_str()?;
+            name.strip_prefix(prefix)?
+                .strip_suffix("_input")?
+                .parse()
+                .ok()
+        })
+        .collect();
+    indices.sort_unstable();
+    indices
+}
+
+fn subdirectories(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    // sysfs class entries are symlinks to the device directories
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect()
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    let text = fs::read_to_string(path).ok()?;
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+fn read_number<T: std::str::FromStr>(path: &Path) -> Option<T> {
+    read_trimmed(path)?.parse().ok()
+}
+
+/// Turns per-check health into alerts only once a breach has lasted
+/// [`SensorsConfig::sustain_checks`] checks, so a compile spike doesn't page
+#[derive(Debug, Default)]
+pub struct BreachTracker {
+    /// Consecutive checks at warning or worse, and at critical
+    streaks: HashMap<String, (u32, u32)>,
+}
+
+impl BreachTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The sustained health of each assessed id; ids missing from
+    /// `assessments` start over when they return
+    pub fn observe(
+        &mut self,
+        assessments: &[(String, HostHealth, String)],
+        required: u32,
+    ) -> Vec<(String, HostHealth, String)> {
+        let required = required.max(1);
+        self.streaks
+            .retain(|id, _| assessments.iter().any(|(seen, _, _)| seen == id));
+        assessments
+            .iter()
+            .map(|(id, health, description)| {
+                let (warning, critical) = self.streaks.entry(id.clone()).or_default();
+                *warning = if *health >= HostHealth::Warning {
+                    *warning + 1
+                } else {
+                    0
+                };
+                *critical = if *health >= HostHealth::Critical {
+                    *critical + 1
+                } else {
+                    0
+                };
+                let sustained = if *critical >= required {
+                    HostHealth::Critical
+                } else if *warning >= required {
+                    HostHealth::Warning
+                } else {
+                    HostHealth::Healthy
+                };
+                (id.clone(), sustained, description.clone())
+            })
+            .collect()
+    }
+}
+
+/// One line per reading, temperatures first, then fans, power, and batteries
+pub fn render(snapshot: &SensorSnapshot, config: &SensorsConfig) -> String {
+    let mut out = String::new();
+    for kind in [SensorKind::Temperature, SensorKind::Fan, SensorKind::Power] {
+        for sensor in snapshot.sensors.iter().filter(|s| s.kind == kind) {
+            out.push_str(&format!(
+                "{} {}\n",
+                sensor.assess().icon(),
+                sensor.describe()
+            ));
+        }
+    }
+    for battery in &snapshot.batteries {
+        out.push_str(&format!(
+            "{} {}\n",
+            battery.assess(config).icon(),
+            battery.describe()
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `files` (relative path, contents) under a fake sysfs root
+    fn fake_sysfs(files: &[(&str, &str)]) -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        for (path, contents) in files {
+            let path = root.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        root
+    }
+
+    fn find<'a>(snapshot: &'a SensorSnapshot, id: &str) -> &'a SensorReading {
+        snapshot
+            .sensors
+            .iter()
+            .find(|s| s.id == id)
+            .unwrap_or_else(|| panic!("no {} in {:?}", id, snapshot.sensors))
+    }
+
+    #[test]
+    fn test_discovers_the_sensors_worth_watching() {
+        let root = fake_sysfs(&[
+            // Intel CPU: the package is kept, cores are not
+            ("class/hwmon/hwmon0/name", "coretemp\n"),
+            ("class/hwmon/hwmon0/temp1_input", "96000\n"),
+            ("class/hwmon/hwmon0/temp1_label", "Package id 0\n"),
+            ("class/hwmon/hwmon0/temp1_max", "80000\n"),
+            ("class/hwmon/hwmon0/temp1_crit", "100000\n"),
+            ("class/hwmon/hwmon0/temp2_input", "60000\n"),
+            ("class/hwmon/hwmon0/temp2_label", "Core 0\n"),
+            // NVMe composite without a label, named after its device
+            ("class/hwmon/hwmon1/name", "nvme\n"),
+            ("class/hwmon/hwmon1/temp1_input", "41850\n"),
+            ("class/hwmon/hwmon1/temp1_crit", "85000\n"),
+            ("class/hwmon/hwmon1/temp2_input", "45850\n"),
+            ("class/hwmon/hwmon1/temp2_label", "Sensor 1\n"),
+            // Super I/O: labeled temps and fans; garbage and empty headers skipped
+            ("class/hwmon/hwmon2/name", "nct6798\n"),
+            ("class/hwmon/hwmon2/temp1_input", "52000\n"),
+            ("class/hwmon/hwmon2/temp1_label", "VRM\n"),
+            ("class/hwmon/hwmon2/temp2_input", "127000\n"),
+            ("class/hwmon/hwmon2/temp2_label", "AUXTIN0\n"),
+            ("class/hwmon/hwmon2/temp3_input", "40000\n"),
+            ("class/hwmon/hwmon2/temp4_input", "not a number\n"),
+            ("class/hwmon/hwmon2/temp4_label", "PCH_CHIP_TEMP\n"),
+            ("class/hwmon/hwmon2/fan1_input", "1180\n"),
+            ("class/hwmon/hwmon2/fan1_label", "CPU fan\n"),
+            ("class/hwmon/hwmon2/fan2_input", "0\n"),
+            ("class/hwmon/hwmon2/fan3_input", "0\n"),
+            ("class/hwmon/hwmon2/fan3_min", "300\n"),
+            // AMD CPU: Tdie wins over the offset Tctl
+            ("class/hwmon/hwmon3/name", "k10temp\n"),
+            ("class/hwmon/hwmon3/temp1_input", "75000\n"),
+            ("class/hwmon/hwmon3/temp1_label", "Tctl\n"),
+            ("class/hwmon/hwmon3/temp2_input", "65000\n"),
+            ("class/hwmon/hwmon3/temp2_label", "Tdie\n"),
+            // Left to the GPU module; a chip without a name is skipped
+            ("class/hwmon/hwmon4/name", "amdgpu\n"),
+            ("class/hwmon/hwmon4/temp1_input", "70000\n"),
+            ("class/hwmon/hwmon5/temp1_input", "70000\n"),
+        ]);
+        fs::create_dir_all(root.path().join("devices/nvme0")).unwrap();
+        std::os::unix::fs::symlink(
+            root.path().join("devices/nvme0"),
+            root.path().join("class/hwmon/hwmon1/device"),
+        )
+        .unwrap();
+
+        let config = SensorsConfig::default();
+        let snapshot = SensorSampler::new(root.path()).sample(&config, Utc::now());
+        let ids: Vec<&str> = snapshot.sensors.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            vec![
+                "coretemp/Package id 0",
+                "nvme0/Composite",
+                "nct6798/VRM",
+                "nct6798/CPU fan",
+                "nct6798/fan3",
+                "k10temp/Tdie",
+            ]
+        );
+
+        let package = find(&snapshot, "coretemp/Package id 0");
+        assert_eq!(package.value, 96.0);
+        assert_eq!(
+            package.thresholds,
+            Thresholds {
+                warning: Some(80.0),
+                critical: Some(100.0)
+            }
+        );
+        assert_eq!(package.assess(), HostHealth::Warning);
+
+        // Seeded from crit alone, and from the config without a crit
+        let nvme = find(&snapshot, "nvme0/Composite");
+        assert_eq!(nvme.thresholds.critical, Some(85.0));
+        assert_eq!(nvme.thresholds.warning, Some(75.0));
+        assert_eq!(
+            find(&snapshot, "nct6798/VRM").thresholds.critical,
+            Some(90.0)
+        );
+
+        // A stalled fan with a minimum is critical
+        assert_eq!(
+            find(&snapshot, "nct6798/CPU fan").assess(),
+            HostHealth::Healthy
+        );
+        assert_eq!(
+            find(&snapshot, "nct6798/fan3").assess(),
+            HostHealth::Critical
+        );
+        assert!(snapshot.batteries.is_empty());
+    }
+
+    #[test]
+    fn test_config_overrides_driver_thresholds() {
+        let root = fake_sysfs(&[
+            ("class/hwmon/hwmon0/name", "coretemp"),
+            ("class/hwmon/hwmon0/temp1_input", "96000"),
+            ("class/hwmon/hwmon0/temp1_label", "Package id 0"),
+            ("class/hwmon/hwmon0/temp1_crit", "100000"),
+        ]);
+        let mut config = SensorsConfig::default();
+        config.thresholds.insert(
+            "coretemp/Package id 0".to_string(),
+            Thresholds {
+                warning: Some(98.0),
+                critical: None,
+            },
+        );
+        let snapshot = SensorSampler::new(root.path()).sample(&config, Utc::now());
+        let package = &snapshot.sensors[0];
+        assert_eq!(package.thresholds.warning, Some(98.0));
+        assert_eq!(package.thresholds.critical, Some(100.0));
+        assert_eq!(package.assess(), HostHealth::Healthy);
+    }
+
+    #[test]
+    fn test_rapl_power_from_two_samples() {
+        let root = fake_sysfs(&[
+            ("class/powercap/intel-rapl:0/name", "package-0"),
+            ("class/powercap/intel-rapl:0/energy_uj", "262000000"),
+            (
+                "class/powercap/intel-rapl:0/max_energy_range_uj",
+                "262143328850",
+            ),
+            ("class/powercap/intel-rapl:0:0/name", "core"),
+            ("class/powercap/intel-rapl:0:0/energy_uj", "1000"),
+        ]);
+        let config = SensorsConfig::default();
+        let mut sampler = SensorSampler::new(root.path());
+        let start = Utc::now();
+        assert!(sampler.sample(&config, start).sensors.is_empty());
+
+        fs::write(
+            root.path().join("class/powercap/intel-rapl:0/energy_uj"),
+            "412000000",
+        )
+        .unwrap();
+        let snapshot = sampler.sample(&config, start + chrono::Duration::seconds(10));
+        assert_eq!(snapshot.sensors.len(), 1);
+        assert_eq!(snapshot.sensors[0].id, "rapl/package-0");
+        assert_eq!(snapshot.sensors[0].value, 15.0);
+
+        // A wrapped counter still gives the energy since the last sample
+        fs::write(
+            root.path().join("class/powercap/intel-rapl:0/energy_uj"),
+            "0",
+        )
+        .unwrap();
+        let snapshot = sampler.sample(&config, start + chrono::Duration::seconds(20));
+        assert!((snapshot.sensors[0].value - 26_173.132_885).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_battery_health() {
+        let root = fake_sysfs(&[
+            ("class/power_supply/AC/type", "Mains"),
+            ("class/power_supply/BAT0/type", "Battery"),
+            ("class/power_supply/BAT0/energy_full_design", "57000000"),
+            ("class/power_supply/BAT0/energy_full", "37050000"),
+            ("class/power_supply/BAT0/cycle_count", "512"),
+            ("class/power_supply/BAT1/type", "Battery"),
+            ("class/power_supply/BAT1/charge_full_design", "4000000"),
+            ("class/power_supply/BAT1/charge_full", "3800000"),
+            ("class/power_supply/BAT1/cycle_count", "0"),
+            ("class/power_supply/hidpp_battery_0/type", "Battery"),
+            ("class/power_supply/hidpp_battery_0/scope", "Device"),
+            ("class/power_supply/BAT2/type", "Battery"),
+            ("class/power_supply/BAT2/energy_full_design", "0"),
+        ]);
+        let config = SensorsConfig::default();
+        let batteries = read_batteries(&root.path().join("class/power_supply"));
+        assert_eq!(batteries.len(), 2);
+
+        assert_eq!(batteries[0].name, "BAT0");
+        assert_eq!(batteries[0].health_percent, 65.0);
+        assert_eq!(batteries[0].cycle_count, Some(512));
+        assert_eq!(batteries[0].assess(&config), HostHealth::Warning);
+        assert_eq!(batteries[1].health_percent, 95.0);
+        assert_eq!(batteries[1].cycle_count, None);
+
+        let snapshot = SensorSnapshot {
+            sensors: Vec::new(),
+            batteries,
+        };
+        let metrics = snapshot.metrics(Utc::now());
+        assert_eq!(metrics.len(), 3);
+        assert_eq!(metrics[0].name, "battery.health_percent");
+        assert_eq!(metrics[0].labels["battery"], "BAT0");
+    }
+
+    #[test]
+    fn test_missing_sysfs_reads_nothing() {
+        let root = tempfile::tempdir().unwrap();
+        let snapshot = SensorSampler::new(root.path().join("nope"))
+            .sample(&SensorsConfig::default(), Utc::now());
+        assert!(snapshot.is_empty());
+    }
+
+    #[test]
+    fn test_alerts_only_for_sustained_breaches() {
+        let mut tracker = BreachTracker::new();
+        let check = |health| vec![("cpu".to_string(), health, "cpu is hot".to_string())];
+        let sustained = |result: Vec<(String, HostHealth, String)>| result[0].1;
+
+        assert_eq!(
+            sustained(tracker.observe(&check(HostHealth::Critical), 3)),
+            HostHealth::Healthy
+        );
+        assert_eq!(
+            sustained(tracker.observe(&check(HostHealth::Warning), 3)),
+            HostHealth::Healthy
+        );
+        assert_eq!(
+            sustained(tracker.observe(&check(HostHealth::Critical), 3)),
+            HostHealth::Warning
+        );
+        assert_eq!(
+            sustained(tracker.observe(&check(HostHealth::Critical), 3)),
+            HostHealth::Warning
+        );
+        assert_eq!(
+            sustained(tracker.observe(&check(HostHealth::Critical), 3)),
+            HostHealth::Critical
+        );
+        assert_eq!(
+            sustained(tracker.observe(&check(HostHealth::Healthy), 3)),
+            HostHealth::Healthy
+        );
+
+        // A sensor that vanishes starts over
+        tracker.observe(&check(HostHealth::Warning), 2);
+        tracker.observe(&[], 2);
+        assert_eq!(
+            sustained(tracker.observe(&check(HostHealth::Warning), 2)),
+            HostHealth::Healthy
+        );
+    }
+}
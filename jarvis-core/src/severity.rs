@@ -0,0 +1,156 @@
+//! Canonical severity for findings, alerts, and anomalies
+//!
+//! Modules keep their own severity types where those carry extra meaning, but
+//! convert to `Severity` wherever findings cross a module boundary, so
+//! filtering and the SIEM mapping work the same everywhere. Deserialization
+//! accepts the spellings earlier versions stored ("High", "high", "Warning",
+//! "Emergency") so historical records still load.
+
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::notify::NotifySeverity;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    #[default]
+    Info,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Severity {
+    pub const ALL: [Severity; 5] = [
+        Severity::Info,
+        Severity::Low,
+        Severity::Medium,
+        Severity::High,
+        Severity::Critical,
+    ];
+
+    /// Lowercase name, as serialized
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+            Severity::Critical => "critical",
+        }
+    }
+
+    pub fn meets(self, min: Severity) -> bool {
+        self >= min
+    }
+}
+
+/// Items whose severity is at least `min`, in their original order
+pub fn at_least<'a, T>(
+    items: &'a [T],
+    min: Severity,
+    severity: impl Fn(&T) -> Severity,
+) -> impl Iterator<Item = &'a T> {
+    items.iter().filter(move |item| severity(item).meets(min))
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Severity::Info => "Info",
+            Severity::Low => "Low",
+            Severity::Medium => "Medium",
+            Severity::High => "High",
+            Severity::Critical => "Critical",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Severity {
+    type Err = anyhow::Error;
+
+    /// Case-insensitive; also accepts the names used by older alert and
+    /// advisory formats
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "info" | "informational" | "none" => Ok(Severity::Info),
+            "low" => Ok(Severity::Low),
+            "medium" | "moderate" | "warning" => Ok(Severity::Medium),
+            "high" | "important" => Ok(Severity::High),
+            "critical" | "emergency" => Ok(Severity::Critical),
+            other => anyhow::bail!(
+                "Unknown severity '{}' (expected info, low, medium, high, or critical)",
+                other
+            ),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Severity {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl From<Severity> for NotifySeverity {
+    fn from(severity: Severity) -> Self {
+        match severity {
+            Severity::Info | Severity::Low => NotifySeverity::Info,
+            Severity::Medium | Severity::High => NotifySeverity::Warning,
+            Severity::Critical => NotifySeverity::Critical,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    struct Stored {
+        severity: Severity,
+    }
+
+    #[test]
+    fn test_historical_spellings_deserialize() {
+        for (json, expected) in [
+            // SecuritySeverity and RiskLevel records
+            (r#"{"severity":"High"}"#, Severity::High),
+            // jarvis-nv anomalies and Wazuh events
+            (r#"{"severity":"medium"}"#, Severity::Medium),
+            // Monitoring alerts
+            (r#"{"severity":"Warning"}"#, Severity::Medium),
+            (r#"{"severity":"Emergency"}"#, Severity::Critical),
+            // arch-audit
+            (r#"{"severity":"Critical"}"#, Severity::Critical),
+        ] {
+            let stored: Stored = serde_json::from_str(json).unwrap();
+            assert_eq!(stored.severity, expected, "{}", json);
+        }
+        assert!(serde_json::from_str::<Stored>(r#"{"severity":"bogus"}"#).is_err());
+    }
+
+    #[test]
+    fn test_serializes_lowercase_and_round_trips() {
+        for severity in Severity::ALL {
+            let json = serde_json::to_string(&severity).unwrap();
+            assert_eq!(json, format!("\"{}\"", severity.as_str()));
+            assert_eq!(serde_json::from_str::<Severity>(&json).unwrap(), severity);
+        }
+    }
+
+    #[test]
+    fn test_ordering_and_filter() {
+        assert!(Severity::Critical > Severity::High);
+        assert!(Severity::Low > Severity::Info);
+
+        let findings = [Severity::Low, Severity::Critical, Severity::Medium];
+        let serious: Vec<_> = at_least(&findings, Severity::Medium, |s| *s).collect();
+        assert_eq!(serious, vec![&Severity::Critical, &Severity::Medium]);
+    }
+}
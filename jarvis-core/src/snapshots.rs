@@ -0,0 +1,491 @@
+//! Browsing snapper snapshots and restoring files from them
+//!
+//! Snapper is the only backend: snapshots are listed and compared with its
+//! CLI, and files are copied out of `<subvolume>/.snapshots/<n>/snapshot`.
+//! Every path is checked twice before anything is read: lexically, so it is
+//! absolute, free of `..`, and inside the config's subvolume, and again after
+//! symlinks in its parent directories are resolved, so a link inside the
+//! snapshot can't point the copy at the live system. A restore never
+//! overwrites the original unless asked to; by default it writes next to it
+//! with a `.restored` suffix.
+
+use crate::exec::{CommandRunner, SystemRunner};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+/// Appended to the original path when a restore has no explicit target
+pub const RESTORED_SUFFIX: &str = ".restored";
+
+/// One snapper snapshot
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    #[serde(rename = "number")]
+    pub id: u32,
+    /// `single`, `pre`, or `post`
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// The `pre` snapshot a `post` snapshot pairs with
+    #[serde(rename = "pre-number", default)]
+    pub pre_id: Option<u32>,
+    /// Local time, e.g. `2025-05-01 10:00:01`; empty for the live system (#0)
+    #[serde(default)]
+    pub date: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub cleanup: String,
+}
+
+/// Files that differ between two snapshots under a path
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotDiff {
+    pub from: u32,
+    pub to: u32,
+    pub path: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// Content, type, permission, owner, or attribute changes
+    pub modified: Vec<String>,
+}
+
+/// Outcome of a restore
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Restored {
+    pub snapshot: u32,
+    pub source: PathBuf,
+    pub target: PathBuf,
+    pub in_place: bool,
+}
+
+/// What a restore would do, returned instead of copying when it needs
+/// confirmation
+#[derive(Debug, Clone, PartialEq)]
+pub enum RestorePlan {
+    Done(Restored),
+    /// Restoring over the original needs `confirm`
+    NeedsConfirmation {
+        source: PathBuf,
+        target: PathBuf,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Snapshots {
+    runner: Arc<dyn CommandRunner>,
+}
+
+impl Default for Snapshots {
+    fn default() -> Self {
+        Self {
+            runner: Arc::new(SystemRunner::default()),
+        }
+    }
+}
+
+impl Snapshots {
+    /// Run snapper and cp through `runner` instead of spawning them directly
+    pub fn with_runner(runner: Arc<dyn CommandRunner>) -> Self {
+        Self { runner }
+    }
+
+    /// Fails unless snapper is installed; every other call checks this first
+    pub async fn backend(&self) -> Result<()> {
+        match self.runner.output("snapper", &["--version"]).await {
+            Ok(output) if output.status.success() => Ok(()),
+            _ => anyhow::bail!("snapper is not installed; snapshot browsing needs it"),
+        }
+    }
+
+    async fn snapper(&self, config: &str, args: &[&str]) -> Result<String> {
+        self.backend().await?;
+        let mut full = vec!["--jsonout", "-c", config];
+        full.extend_from_slice(args);
+        let output = self.runner.output("snapper", &full).await?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "snapper {} failed: {}",
+                args.first().copied().unwrap_or_default(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    /// Snapshots of `config`, oldest first, without the live system (#0)
+    pub async fn list(&self, config: &str) -> Result<Vec<Snapshot>> {
+        let json = self.snapper(config, &["list"]).await?;
+        let mut configs: HashMap<String, Vec<Snapshot>> =
+            serde_json::from_str(&json).context("Unexpected snapper list output")?;
+        let mut snapshots = configs.remove(config).unwrap_or_default();
+        snapshots.retain(|s| s.id != 0);
+        Ok(snapshots)
+    }
+
+    /// Mount point of the subvolume `config` snapshots
+    async fn subvolume(&self, config: &str) -> Result<PathBuf> {
+        let json = self.snapper(config, &["get-config"]).await?;
+        let settings: HashMap<String, String> =
+            serde_json::from_str(&json).context("Unexpected snapper get-config output")?;
+        settings
+            .get("SUBVOLUME")
+            .map(PathBuf::from)
+            .ok_or_else(|| anyhow::anyhow!("snapper config {} has no SUBVOLUME", config))
+    }
+
+    /// Files added, removed, and modified between snapshots `from` and `to`
+    /// (#0 is the live system) at or below `path`
+    pub async fn diff(&self, config: &str, from: u32, to: u32, path: &str) -> Result<SnapshotDiff> {
+        let filter = checked_path(path)?;
+        self.backend().await?;
+        let range = format!("{}..{}", from, to);
+        let output = self
+            .runner
+            .output("snapper", &["-c", config, "status", &range])
+            .await?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "snapper status failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        let mut diff = SnapshotDiff {
+            from,
+            to,
+            path: filter.display().to_string(),
+            ..Default::default()
+        };
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            // "c..... /etc/pacman.conf": six status columns, then the path
+            let Some((flags, file)) = line.split_once(' ') else {
+                continue;
+            };
+            if !Path::new(file).starts_with(&filter) {
+                continue;
+            }
+            let list = match flags.chars().next() {
+                Some('+') => &mut diff.added,
+                Some('-') => &mut diff.removed,
+                _ => &mut diff.modified,
+            };
+            list.push(file.to_string());
+        }
+        Ok(diff)
+    }
+
+    /// Copy `path` as it was in `snapshot` to `target`, or next to the
+    /// original with a `.restored` suffix. Overwriting the original needs
+    /// `confirm`; any other existing target is refused.
+    pub async fn restore(
+        &self,
+        config: &str,
+        snapshot: u32,
+        path: &str,
+        target: Option<&str>,
+        confirm: bool,
+    ) -> Result<RestorePlan> {
+        if snapshot == 0 {
+            anyhow::bail!("Snapshot #0 is the live system; pick an earlier snapshot");
+        }
+        let original = checked_path(path)?;
+        let subvolume = self.subvolume(config).await?;
+        let source = snapshot_source(&subvolume, snapshot, &original)?;
+
+        let target = match target {
+            Some(target) => checked_path(target)?,
+            None => {
+                let mut restored = original.clone().into_os_string();
+                restored.push(RESTORED_SUFFIX);
+                PathBuf::from(restored)
+            }
+        };
+        let in_place = target == original;
+        if in_place && !confirm {
+            return Ok(RestorePlan::NeedsConfirmation { source, target });
+        }
+        if !in_place && target.symlink_metadata().is_ok() {
+            anyhow::bail!("{} already exists; choose another target", target.display());
+        }
+
+        let (source_arg, target_arg) = (source.to_string_lossy(), target.to_string_lossy());
+        // -T copies a directory onto the target rather than into it
+        let output = self
+            .runner
+            .output(
+                "cp",
+                &["-a", "-T", "--reflink=auto", "--", &source_arg, &target_arg],
+            )
+            .await?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "Copying {} failed: {}",
+                source.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(RestorePlan::Done(Restored {
+            snapshot,
+            source,
+            target,
+            in_place,
+        }))
+    }
+}
+
+/// `path` as given, once it is known to be absolute and free of `..`
+fn checked_path(path: &str) -> Result<PathBuf> {
+    let path = Path::new(path);
+    if !path.is_absolute() {
+        anyhow::bail!("Path must be absolute: {}", path.display());
+    }
+    if path.components().any(|c| matches!(c, Component::ParentDir)) {
+        anyhow::bail!("Path must not contain '..': {}", path.display());
+    }
+    // Drops `.` and repeated separators
+    Ok(path.components().collect())
+}
+
+/// Where `original` lives inside snapshot `snapshot` of `subvolume`. The
+/// parent directory is resolved and must stay inside the snapshot; the last
+/// component is left alone, so a symlink is copied as a link.
+fn snapshot_source(subvolume: &Path, snapshot: u32, original: &Path) -> Result<PathBuf> {
+    let relative = original.strip_prefix(subvolume).map_err(|_| {
+        anyhow::anyhow!(
+            "{} is outside the snapshotted subvolume {}",
+            original.display(),
+            subvolume.display()
+        )
+    })?;
+    let (Some(parent), Some(name)) = (relative.parent(), relative.file_name()) else {
+        anyhow::bail!("Choose a file or directory inside {}", subvolume.display());
+    };
+
+    let root = subvolume
+        .join(".snapshots")
+        .join(snapshot.to_string())
+        .join("snapshot");
+    let root = root.canonicalize().with_context(|| {
+        format!(
+            "Snapshot #{} is not available at {}",
+            snapshot,
+            root.display()
+        )
+    })?;
+    let parent = root.join(parent).canonicalize().with_context(|| {
+        format!(
+            "{} does not exist in snapshot #{}",
+            original.display(),
+            snapshot
+        )
+    })?;
+    if !parent.starts_with(&root) {
+        anyhow::bail!(
+            "{} resolves outside snapshot #{}",
+            original.display(),
+            snapshot
+        );
+    }
+
+    let source = parent.join(name);
+    if source.symlink_metadata().is_err() {
+        anyhow::bail!(
+            "{} does not exist in snapshot #{}",
+            original.display(),
+            snapshot
+        );
+    }
+    Ok(source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::{RecordingRunner, fake_output};
+    use std::fs;
+
+    const LIST: &str = r#"{
+      "root": [
+        { "subvolume": "/", "number": 0, "default": false, "active": false, "type": "single",
+          "pre-number": null, "date": "", "user": "root", "used-space": null, "cleanup": "",
+          "description": "current", "userdata": null },
+        { "subvolume": "/", "number": 41, "default": false, "active": false, "type": "pre",
+          "pre-number": null, "date": "2025-05-01 10:00:01", "user": "root", "used-space": null,
+          "cleanup": "number", "description": "pacman -Syu", "userdata": null },
+        { "subvolume": "/", "number": 42, "default": false, "active": false, "type": "post",
+          "pre-number": 41, "date": "2025-05-01 10:02:37", "user": "root", "used-space": null,
+          "cleanup": "number", "description": "linux openssl", "userdata": null }
+      ]
+    }"#;
+
+    const STATUS: &str = "c..... /etc/pacman.d/mirrorlist\n\
+        +..... /etc/pacman.d/hooks/jarvis.hook\n\
+        -..... /etc/pacman.d/old.conf\n\
+        .p.... /etc/pacman.dx\n\
+        c..... /usr/lib/libssl.so.3\n";
+
+    /// A fake subvolume with one snapshot, #42, holding `etc/pacman.conf`,
+    /// `etc/hooks/`, and a symlink that points out of the snapshot
+    fn subvolume() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let snapshot = dir.path().join(".snapshots/42/snapshot");
+        fs::create_dir_all(snapshot.join("etc/hooks")).unwrap();
+        fs::write(snapshot.join("etc/pacman.conf"), "[options]\n").unwrap();
+        fs::write(snapshot.join("etc/hooks/a.hook"), "").unwrap();
+        std::os::unix::fs::symlink("/etc", snapshot.join("escape")).unwrap();
+        fs::create_dir_all(dir.path().join("etc")).unwrap();
+        dir
+    }
+
+    fn snapper(subvolume: &Path) -> Arc<RecordingRunner> {
+        let config = format!(r#"{{ "SUBVOLUME": "{}" }}"#, subvolume.display());
+        Arc::new(
+            RecordingRunner::new()
+                .respond("snapper --jsonout -c root list", LIST)
+                .respond("snapper --jsonout -c root get-config", &config)
+                .respond("snapper -c root status 41..42", STATUS),
+        )
+    }
+
+    fn at(dir: &tempfile::TempDir, path: &str) -> String {
+        format!("{}{}", dir.path().display(), path)
+    }
+
+    #[tokio::test]
+    async fn test_list_and_diff_parse_snapper_output() {
+        let snapshots = Snapshots::with_runner(snapper(Path::new("/")));
+
+        let list = snapshots.list("root").await.unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[1].id, 42);
+        assert_eq!(list[1].kind, "post");
+        assert_eq!(list[1].pre_id, Some(41));
+        assert_eq!(list[1].description, "linux openssl");
+
+        let diff = snapshots
+            .diff("root", 41, 42, "/etc/pacman.d/")
+            .await
+            .unwrap();
+        assert_eq!(diff.path, "/etc/pacman.d");
+        assert_eq!(diff.added, vec!["/etc/pacman.d/hooks/jarvis.hook"]);
+        assert_eq!(diff.removed, vec!["/etc/pacman.d/old.conf"]);
+        // A sibling sharing the prefix is not under the path
+        assert_eq!(diff.modified, vec!["/etc/pacman.d/mirrorlist"]);
+    }
+
+    #[tokio::test]
+    async fn test_missing_backend_refuses_everything() {
+        let runner = Arc::new(
+            RecordingRunner::new()
+                .respond_with("snapper --version", fake_output(127, "", "not found")),
+        );
+        let snapshots = Snapshots::with_runner(runner.clone());
+
+        assert!(snapshots.list("root").await.is_err());
+        assert!(snapshots.diff("root", 41, 42, "/etc").await.is_err());
+        let restored = snapshots
+            .restore("root", 42, "/etc/pacman.conf", None, false)
+            .await;
+        assert!(
+            restored
+                .unwrap_err()
+                .to_string()
+                .contains("snapper is not installed")
+        );
+        assert!(runner.calls().iter().all(|c| !c.starts_with("cp")));
+    }
+
+    #[tokio::test]
+    async fn test_restore_defaults_to_a_restored_copy() {
+        let dir = subvolume();
+        let runner = snapper(dir.path());
+        let snapshots = Snapshots::with_runner(runner.clone());
+
+        let plan = snapshots
+            .restore("root", 42, &at(&dir, "/etc/pacman.conf"), None, false)
+            .await
+            .unwrap();
+        let RestorePlan::Done(restored) = plan else {
+            panic!("expected a restore, got {:?}", plan);
+        };
+        assert!(!restored.in_place);
+        assert_eq!(
+            restored.target,
+            PathBuf::from(at(&dir, "/etc/pacman.conf.restored"))
+        );
+        let snapshot = dir
+            .path()
+            .canonicalize()
+            .unwrap()
+            .join(".snapshots/42/snapshot");
+        assert_eq!(
+            runner.calls().last().unwrap(),
+            &format!(
+                "cp -a -T --reflink=auto -- {} {}",
+                snapshot.join("etc/pacman.conf").display(),
+                at(&dir, "/etc/pacman.conf.restored")
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_restore_in_place_needs_confirmation() {
+        let dir = subvolume();
+        let runner = snapper(dir.path());
+        let snapshots = Snapshots::with_runner(runner.clone());
+        let hooks = at(&dir, "/etc/hooks");
+
+        let plan = snapshots
+            .restore("root", 42, &hooks, Some(&hooks), false)
+            .await
+            .unwrap();
+        assert!(matches!(plan, RestorePlan::NeedsConfirmation { .. }));
+        assert!(runner.calls().iter().all(|c| !c.starts_with("cp")));
+
+        let plan = snapshots
+            .restore("root", 42, &hooks, Some(&hooks), true)
+            .await
+            .unwrap();
+        assert!(matches!(
+            plan,
+            RestorePlan::Done(Restored { in_place: true, .. })
+        ));
+        assert!(runner.calls().last().unwrap().starts_with("cp -a -T"));
+    }
+
+    #[tokio::test]
+    async fn test_restore_refuses_paths_that_escape_the_snapshot() {
+        let dir = subvolume();
+        let runner = snapper(dir.path());
+        let snapshots = Snapshots::with_runner(runner.clone());
+
+        for (path, error) in [
+            ("etc/pacman.conf".to_string(), "must be absolute"),
+            (at(&dir, "/etc/../../etc/shadow"), "must not contain '..'"),
+            (
+                "/etc/shadow".to_string(),
+                "outside the snapshotted subvolume",
+            ),
+            (at(&dir, "/escape/shadow"), "resolves outside snapshot #42"),
+            (
+                at(&dir, "/etc/missing.conf"),
+                "does not exist in snapshot #42",
+            ),
+            (at(&dir, ""), "Choose a file or directory"),
+        ] {
+            let result = snapshots.restore("root", 42, &path, None, true).await;
+            let message = result.unwrap_err().to_string();
+            assert!(message.contains(error), "{}: {}", path, message);
+        }
+
+        // An existing target other than the original is never overwritten
+        fs::write(at(&dir, "/etc/pacman.conf.restored"), "").unwrap();
+        let result = snapshots
+            .restore("root", 42, &at(&dir, "/etc/pacman.conf"), None, true)
+            .await;
+        assert!(result.unwrap_err().to_string().contains("already exists"));
+        assert!(runner.calls().iter().all(|c| !c.starts_with("cp")));
+    }
+}
@@ -0,0 +1,603 @@
+//! Shared SSH sessions and multi-host command fan-out
+//!
+//! A plain `ssh` per command pays for the TCP handshake, key exchange, and
+//! authentication every time, which dominates a probe round of many small
+//! commands. The pool keeps one OpenSSH ControlMaster per host and runs later
+//! commands as channels multiplexed over it. Masters are started with
+//! `ControlPersist`, so they close themselves once idle even when the process
+//! that opened them exits without cleaning up.
+
+use crate::remote::{CommandExecutor, SshTarget};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::PathBuf;
+use std::process::{Output, Stdio};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+use tokio::sync::{Mutex as AsyncMutex, Semaphore};
+
+/// How long an unused master stays up
+pub const DEFAULT_IDLE: Duration = Duration::from_secs(300);
+
+/// A session used more recently than this is assumed alive without asking
+const HEALTH_CHECK_AFTER: Duration = Duration::from_secs(30);
+
+/// Opens sessions to hosts; tests substitute an in-memory transport
+#[async_trait]
+pub trait SshTransport: Send + Sync {
+    async fn connect(&self, target: &SshTarget) -> Result<Arc<dyn SshConnection>>;
+}
+
+/// An open session that commands can share
+#[async_trait]
+pub trait SshConnection: Send + Sync {
+    /// Run a command line through the remote user's shell
+    async fn exec(&self, command: &str) -> Result<Output>;
+    async fn is_alive(&self) -> bool;
+    async fn close(&self);
+}
+
+/// Multiplexes over OpenSSH control sockets, so the user's keys, agent, and
+/// `~/.ssh/config` still apply
+pub struct OpenSshTransport {
+    control_dir: PathBuf,
+    idle: Duration,
+}
+
+impl OpenSshTransport {
+    pub fn new(control_dir: PathBuf, idle: Duration) -> Self {
+        Self { control_dir, idle }
+    }
+
+    /// Unix sockets have a short path limit, so the socket is named by a hash
+    /// of where it connects to
+    fn socket_path(&self, target: &SshTarget) -> PathBuf {
+        let mut hasher = DefaultHasher::new();
+        pool_key(target).hash(&mut hasher);
+        self.control_dir
+            .join(format!("{:016x}.sock", hasher.finish()))
+    }
+}
+
+impl Default for OpenSshTransport {
+    fn default() -> Self {
+        let runtime = dirs::runtime_dir().unwrap_or_else(std::env::temp_dir);
+        Self::new(runtime.join("jarvis-ssh"), DEFAULT_IDLE)
+    }
+}
+
+#[async_trait]
+impl SshTransport for OpenSshTransport {
+    async fn connect(&self, target: &SshTarget) -> Result<Arc<dyn SshConnection>> {
+        create_private_dir(&self.control_dir)?;
+        let connection = OpenSshConnection {
+            socket: self.socket_path(target),
+            target: target.clone(),
+        };
+
+        // Another jarvis process may already hold a master for this host
+        if connection.socket.exists() {
+            if connection.is_alive().await {
+                return Ok(Arc::new(connection));
+            }
+            let _ = std::fs::remove_file(&connection.socket);
+        }
+
+        // `-f` leaves the master running in the background holding whatever
+        // stdio it was given, so its errors go to a log file rather than a pipe
+        // that would never close
+        let log = connection.socket.with_extension("log");
+        let status = Command::new("ssh")
+            .args(["-M", "-N", "-f", "-S"])
+            .arg(&connection.socket)
+            .arg("-o")
+            .arg(format!("ControlPersist={}", self.idle.as_secs()))
+            .arg("-E")
+            .arg(&log)
+            .args(target.connection_args())
+            .arg(&target.destination)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .status()
+            .await
+            .context("Failed to run ssh; is openssh installed?")?;
+
+        let errors = std::fs::read_to_string(&log).unwrap_or_default();
+        let _ = std::fs::remove_file(&log);
+        if !status.success() {
+            anyhow::bail!(
+                "SSH connection to {} failed: {}",
+                target.destination,
+                errors.trim()
+            );
+        }
+        Ok(Arc::new(connection))
+    }
+}
+
+struct OpenSshConnection {
+    socket: PathBuf,
+    target: SshTarget,
+}
+
+impl OpenSshConnection {
+    fn control(&self, operation: &str) -> Command {
+        let mut command = Command::new("ssh");
+        command
+            .arg("-S")
+            .arg(&self.socket)
+            .args(["-O", operation])
+            .arg(&self.target.destination)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true);
+        command
+    }
+}
+
+#[async_trait]
+impl SshConnection for OpenSshConnection {
+    async fn exec(&self, command: &str) -> Result<Output> {
+        // ControlMaster=no: if the master is gone, connect directly instead of
+        // becoming a new master for just this command
+        Command::new("ssh")
+            .arg("-S")
+            .arg(&self.socket)
+            .args(["-o", "ControlMaster=no"])
+            .args(self.target.connection_args())
+            .arg(&self.target.destination)
+            .arg("--")
+            .arg(command)
+            .stdin(Stdio::null())
+            // Timed-out probes must not leave ssh sessions behind
+            .kill_on_drop(true)
+            .output()
+            .await
+            .context("Failed to run ssh; is openssh installed?")
+    }
+
+    async fn is_alive(&self) -> bool {
+        self.control("check")
+            .status()
+            .await
+            .map(|status| status.success())
+            .unwrap_or(false)
+    }
+
+    async fn close(&self) {
+        let _ = self.control("exit").status().await;
+    }
+}
+
+fn create_private_dir(dir: &std::path::Path) -> Result<()> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))
+            .with_context(|| format!("Failed to restrict {:?}", dir))?;
+    }
+    Ok(())
+}
+
+/// Targets that reach the same account the same way share a session
+fn pool_key(target: &SshTarget) -> String {
+    format!(
+        "{}:{}:{}",
+        target.destination,
+        target.port.unwrap_or(22),
+        target.identity_file.as_deref().unwrap_or("")
+    )
+}
+
+struct Session {
+    connection: Arc<dyn SshConnection>,
+    last_used: Instant,
+}
+
+type Slot = Arc<AsyncMutex<Option<Session>>>;
+
+/// Usage counters since the pool was created
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PoolStats {
+    pub open_sessions: usize,
+    /// New sessions opened, including replacements
+    pub connects: u64,
+    /// Commands that ran over an already open session
+    pub reuses: u64,
+    /// Sessions found dead and replaced
+    pub reconnects: u64,
+    pub connect_failures: u64,
+}
+
+impl PoolStats {
+    /// Share of session checkouts that didn't need a new connection
+    pub fn reuse_rate(&self) -> f64 {
+        let checkouts = self.reuses + self.connects;
+        if checkouts == 0 {
+            0.0
+        } else {
+            self.reuses as f64 / checkouts as f64
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "{} open, {:.0}% reuse, {} reconnects, {} failed connects",
+            self.open_sessions,
+            self.reuse_rate() * 100.0,
+            self.reconnects,
+            self.connect_failures
+        )
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    open: AtomicUsize,
+    connects: AtomicU64,
+    reuses: AtomicU64,
+    reconnects: AtomicU64,
+    failures: AtomicU64,
+}
+
+/// One lazily opened session per host. Commands to the same host share it
+/// concurrently; a session idle long enough to have died is checked before
+/// reuse and replaced if it has.
+pub struct SshPool {
+    transport: Arc<dyn SshTransport>,
+    slots: Mutex<HashMap<String, Slot>>,
+    counters: Counters,
+}
+
+impl SshPool {
+    pub fn new(transport: Arc<dyn SshTransport>) -> Self {
+        Self {
+            transport,
+            slots: Mutex::new(HashMap::new()),
+            counters: Counters::default(),
+        }
+    }
+
+    fn slot(&self, target: &SshTarget) -> Slot {
+        self.slots
+            .lock()
+            .unwrap()
+            .entry(pool_key(target))
+            .or_default()
+            .clone()
+    }
+
+    /// The host's session, opening one if there is none or it has died.
+    /// Concurrent callers for one host wait for a single connect.
+    async fn checkout(&self, target: &SshTarget) -> Result<Arc<dyn SshConnection>> {
+        let slot = self.slot(target);
+        let mut session = slot.lock().await;
+
+        if let Some(existing) = session.as_mut() {
+            if existing.last_used.elapsed() < HEALTH_CHECK_AFTER
+                || existing.connection.is_alive().await
+            {
+                existing.last_used = Instant::now();
+                self.counters.reuses.fetch_add(1, Ordering::Relaxed);
+                return Ok(existing.connection.clone());
+            }
+            tracing::debug!("SSH session to {} died; reconnecting", target.destination);
+            *session = None;
+            self.counters.open.fetch_sub(1, Ordering::Relaxed);
+            self.counters.reconnects.fetch_add(1, Ordering::Relaxed);
+        }
+
+        match self.transport.connect(target).await {
+            Ok(connection) => {
+                self.counters.connects.fetch_add(1, Ordering::Relaxed);
+                self.counters.open.fetch_add(1, Ordering::Relaxed);
+                *session = Some(Session {
+                    connection: connection.clone(),
+                    last_used: Instant::now(),
+                });
+                Ok(connection)
+            }
+            Err(e) => {
+                self.counters.failures.fetch_add(1, Ordering::Relaxed);
+                Err(e)
+            }
+        }
+    }
+
+    /// Drop `connection` from the pool if it is still the host's session
+    async fn discard(&self, target: &SshTarget, connection: &Arc<dyn SshConnection>) {
+        let slot = self.slot(target);
+        let mut session = slot.lock().await;
+        if session
+            .as_ref()
+            .is_some_and(|s| Arc::ptr_eq(&s.connection, connection))
+        {
+            *session = None;
+            self.counters.open.fetch_sub(1, Ordering::Relaxed);
+            self.counters.reconnects.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Run a command line on `target` over its shared session. A command
+    /// that fails because the session died under it is retried once on a
+    /// fresh one.
+    pub async fn exec(&self, target: &SshTarget, command: &str) -> Result<Output> {
+        let connection = self.checkout(target).await?;
+        let output = connection.exec(command).await?;
+        // ssh reserves 255 for its own failures, which include a lost master
+        if output.status.code() != Some(255) || connection.is_alive().await {
+            return Ok(output);
+        }
+        self.discard(target, &connection).await;
+        self.checkout(target).await?.exec(command).await
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            open_sessions: self.counters.open.load(Ordering::Relaxed),
+            connects: self.counters.connects.load(Ordering::Relaxed),
+            reuses: self.counters.reuses.load(Ordering::Relaxed),
+            reconnects: self.counters.reconnects.load(Ordering::Relaxed),
+            connect_failures: self.counters.failures.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Close every session now rather than waiting for them to idle out
+    pub async fn close_all(&self) {
+        let slots: Vec<Slot> = self.slots.lock().unwrap().drain().map(|(_, s)| s).collect();
+        for slot in slots {
+            if let Some(session) = slot.lock().await.take() {
+                session.connection.close().await;
+                self.counters.open.fetch_sub(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// The process-wide pool that [`CommandExecutor`] runs SSH commands through
+pub fn pool() -> &'static SshPool {
+    static POOL: OnceLock<SshPool> = OnceLock::new();
+    POOL.get_or_init(|| SshPool::new(Arc::new(OpenSshTransport::default())))
+}
+
+/// One command's outcome on one host
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandResult {
+    pub command: String,
+    /// `None` when the command didn't finish
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    /// Why the command couldn't be run or didn't finish
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+impl CommandResult {
+    pub fn success(&self) -> bool {
+        self.error.is_none() && self.exit_code == Some(0)
+    }
+}
+
+/// Every command's outcome on one host, in the order they were given
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostResults {
+    pub host: String,
+    pub results: Vec<CommandResult>,
+    /// From the first command starting to the last one finishing
+    pub duration_ms: u64,
+}
+
+/// Run every shell command on every host, with at most `concurrency`
+/// commands in flight across all hosts. Each command gets its own `timeout`;
+/// a host that is down fails its own commands without holding up the rest.
+/// Results keep the order of `hosts` and `commands`.
+pub async fn fan_out(
+    pool: &SshPool,
+    hosts: &[(String, CommandExecutor)],
+    commands: &[String],
+    concurrency: usize,
+    timeout: Duration,
+) -> Vec<HostResults> {
+    let permits = Semaphore::new(concurrency.max(1));
+    let permits = &permits;
+
+    futures::future::join_all(hosts.iter().map(|(name, executor)| async move {
+        let started = Instant::now();
+        let results = futures::future::join_all(commands.iter().map(|command| async move {
+            let _permit = permits.acquire().await.expect("semaphore is never closed");
+            run_one(pool, executor, command, timeout).await
+        }))
+        .await;
+        HostResults {
+            host: name.clone(),
+            results,
+            duration_ms: started.elapsed().as_millis() as u64,
+        }
+    }))
+    .await
+}
+
+async fn run_one(
+    pool: &SshPool,
+    executor: &CommandExecutor,
+    command: &str,
+    timeout: Duration,
+) -> CommandResult {
+    let started = Instant::now();
+    let run = executor.run_pooled(pool, "sh", &["-c", command]);
+    let result = tokio::time::timeout(timeout, run).await;
+
+    let mut outcome = CommandResult {
+        command: command.to_string(),
+        exit_code: None,
+        stdout: String::new(),
+        stderr: String::new(),
+        error: None,
+        duration_ms: started.elapsed().as_millis() as u64,
+    };
+    match result {
+        Ok(Ok(output)) => {
+            outcome.exit_code = output.status.code();
+            outcome.stdout = String::from_utf8_lossy(&output.stdout).to_string();
+            outcome.stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        }
+        Ok(Err(e)) => outcome.error = Some(e.to_string()),
+        Err(_) => outcome.error = Some(format!("timed out after {}s", timeout.as_secs())),
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RemoteConfig;
+    use crate::exec::fake_output;
+    use std::sync::atomic::AtomicBool;
+
+    /// Sessions that echo the command back, sleeping so fan-out overlaps
+    #[derive(Default)]
+    struct MockTransport {
+        connects: AtomicU64,
+        refuse: Mutex<Vec<String>>,
+        in_flight: Arc<AtomicUsize>,
+        peak: Arc<AtomicUsize>,
+        opened: Mutex<Vec<Arc<MockConnection>>>,
+    }
+
+    struct MockConnection {
+        alive: AtomicBool,
+        in_flight: Arc<AtomicUsize>,
+        peak: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl SshTransport for MockTransport {
+        async fn connect(&self, target: &SshTarget) -> Result<Arc<dyn SshConnection>> {
+            if self.refuse.lock().unwrap().contains(&target.destination) {
+                anyhow::bail!(
+                    "SSH connection to {} failed: No route to host",
+                    target.destination
+                );
+            }
+            self.connects.fetch_add(1, Ordering::SeqCst);
+            let connection = Arc::new(MockConnection {
+                alive: AtomicBool::new(true),
+                in_flight: self.in_flight.clone(),
+                peak: self.peak.clone(),
+            });
+            self.opened.lock().unwrap().push(connection.clone());
+            Ok(connection)
+        }
+    }
+
+    #[async_trait]
+    impl SshConnection for MockConnection {
+        async fn exec(&self, command: &str) -> Result<Output> {
+            if !self.alive.load(Ordering::SeqCst) {
+                return Ok(fake_output(
+                    255,
+                    "",
+                    "mux_client_request_session: read failed",
+                ));
+            }
+            let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(fake_output(0, command, ""))
+        }
+
+        async fn is_alive(&self) -> bool {
+            self.alive.load(Ordering::SeqCst)
+        }
+
+        async fn close(&self) {
+            self.alive.store(false, Ordering::SeqCst);
+        }
+    }
+
+    fn host(name: &str) -> (String, CommandExecutor) {
+        let target = SshTarget::resolve(name, &RemoteConfig::default());
+        (name.to_string(), CommandExecutor::ssh(target))
+    }
+
+    #[tokio::test]
+    async fn test_fan_out_reuses_one_session_per_host() {
+        let transport = Arc::new(MockTransport::default());
+        let pool = SshPool::new(transport.clone());
+        let hosts = vec![host("nas"), host("router"), host("ws")];
+        let commands: Vec<String> = (0..5).map(|i| format!("echo {}", i)).collect();
+
+        let results = fan_out(&pool, &hosts, &commands, 4, Duration::from_secs(5)).await;
+
+        let names: Vec<&str> = results.iter().map(|h| h.host.as_str()).collect();
+        assert_eq!(names, vec!["nas", "router", "ws"]);
+        for host in &results {
+            assert!(host.results.iter().all(CommandResult::success));
+            assert!(host.results[3].stdout.ends_with("sh -c 'echo 3'"));
+        }
+        assert!(transport.peak.load(Ordering::SeqCst) <= 4);
+        assert!(transport.peak.load(Ordering::SeqCst) > 1);
+
+        let stats = pool.stats();
+        assert_eq!(transport.connects.load(Ordering::SeqCst), 3);
+        assert_eq!(stats.open_sessions, 3);
+        assert_eq!(stats.connects, 3);
+        assert_eq!(stats.reuses, 12);
+        assert!((stats.reuse_rate() - 0.8).abs() < 1e-9);
+
+        pool.close_all().await;
+        assert_eq!(pool.stats().open_sessions, 0);
+    }
+
+    #[tokio::test]
+    async fn test_dead_session_is_replaced() {
+        let transport = Arc::new(MockTransport::default());
+        let pool = SshPool::new(transport.clone());
+        let (_, CommandExecutor::Ssh(target)) = host("nas") else {
+            unreachable!()
+        };
+
+        pool.exec(&target, "uptime").await.unwrap();
+        transport.opened.lock().unwrap()[0].close().await;
+
+        let output = pool.exec(&target, "uptime").await.unwrap();
+        assert!(output.status.success());
+        let stats = pool.stats();
+        assert_eq!(stats.connects, 2);
+        assert_eq!(stats.reconnects, 1);
+        assert_eq!(stats.open_sessions, 1);
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_host_fails_only_its_commands() {
+        let transport = Arc::new(MockTransport::default());
+        transport.refuse.lock().unwrap().push("down".to_string());
+        let pool = SshPool::new(transport.clone());
+        let hosts = vec![host("down"), host("up")];
+
+        let results = fan_out(
+            &pool,
+            &hosts,
+            &["true".to_string()],
+            8,
+            Duration::from_secs(5),
+        )
+        .await;
+
+        let error = results[0].results[0].error.as_deref().unwrap();
+        assert!(error.contains("No route to host"));
+        assert!(results[1].results[0].success());
+        assert_eq!(pool.stats().connect_failures, 1);
+    }
+}
@@ -30,6 +30,46 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_metrics_survive_downsampling() -> anyhow::Result<()> {
+        use crate::metrics::MetricSample;
+        use chrono::{Duration, Utc};
+
+        let temp_db = tempfile::NamedTempFile::new().unwrap();
+        let memory = MemoryStore::new(temp_db.path().to_str().unwrap()).await?;
+
+        // Memory creeping up 2% every 12 hours for nine days, sampled every 10 minutes
+        let now = Utc::now();
+        let samples: Vec<_> = (0..9 * 24 * 6)
+            .map(|i| {
+                let value = 40.0 + (i / 72) as f64 * 2.0;
+                let at = now - Duration::minutes(10 * (9 * 24 * 6 - i));
+                MetricSample::new("memory.used_percent", value, at)
+            })
+            .collect();
+        memory.record_metrics(&samples).await?;
+
+        let since = now - Duration::days(10);
+        let before = memory
+            .query_range("memory.used_percent", since, now, Duration::days(1))
+            .await?;
+        assert!(memory.downsample_metrics(now).await? > 0);
+        let after = memory
+            .query_range("memory.used_percent", since, now, Duration::days(1))
+            .await?;
+
+        assert_eq!(after.len(), 1);
+        let (min, avg, max) = after[0].summary().unwrap();
+        let (raw_min, raw_avg, raw_max) = before[0].summary().unwrap();
+        assert_eq!((min, max), (raw_min, raw_max));
+        assert!((avg - raw_avg).abs() < 1e-9);
+        assert_eq!(after[0].points.len(), before[0].points.len());
+
+        // Nothing left to roll up
+        assert_eq!(memory.downsample_metrics(now).await?, 0);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_llm_router_creation() -> JarvisResult<()> {
         let config = Config::default();
@@ -0,0 +1,1010 @@
+//! Time ranges from user input
+//!
+//! Every `--since`, API `since=`, report window, log query, and time
+//! mentioned in a natural-language request goes through [`parse_range`], so
+//! "24h", "last 2 hours", "yesterday", "since monday", "since boot", and
+//! "2024-05-01T08:00:00Z" mean the same thing everywhere. Scheduling uses
+//! [`parse_when`] for points ahead of now ("in 1 hour", "tomorrow 03:00").
+//!
+//! Minutes and hours are exact durations. Days, weeks, months, and dates
+//! without an offset follow the local wall clock, so "last 1d" across a
+//! daylight saving change is 23 or 25 hours long. A local time the clocks
+//! pass twice, or a date like "03/04/2024", fails with [`AmbiguousTime`]
+//! listing the readings instead of guessing.
+
+use anyhow::{Context, Result};
+use chrono::{
+    DateTime, Datelike, Days, Duration, Local, LocalResult, Months, NaiveDate, NaiveDateTime,
+    NaiveTime, Offset, TimeZone, Utc, Weekday,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Shown with every parse error
+const EXAMPLES: &str =
+    "try 24h, last 2 hours, yesterday, since monday, since boot, or 2024-05-01T08:00:00Z";
+
+/// Longest phrase [`TimeContext::find`] tries, in words
+const MAX_PHRASE_WORDS: usize = 5;
+
+/// A window of time, normalized to UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeRange {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl TimeRange {
+    pub fn duration(&self) -> Duration {
+        self.end - self.start
+    }
+
+    pub fn contains(&self, t: DateTime<Utc>) -> bool {
+        t >= self.start && t <= self.end
+    }
+}
+
+/// One reading of an ambiguous input
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Interpretation {
+    pub description: String,
+    pub time: DateTime<Utc>,
+}
+
+/// Input that names more than one point in time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AmbiguousTime {
+    pub input: String,
+    pub interpretations: Vec<Interpretation>,
+}
+
+impl fmt::Display for AmbiguousTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let readings: Vec<&str> = self
+            .interpretations
+            .iter()
+            .map(|i| i.description.as_str())
+            .collect();
+        write!(
+            f,
+            "'{}' is ambiguous: it could be {}",
+            self.input,
+            readings.join(" or ")
+        )
+    }
+}
+
+impl std::error::Error for AmbiguousTime {}
+
+/// Parse `input` relative to now in the local timezone
+pub fn parse_range(input: &str) -> Result<TimeRange> {
+    TimeContext::local().range(input)
+}
+
+/// Parse a point at or after now in the local timezone, e.g. "in 2 hours"
+pub fn parse_when(input: &str) -> Result<DateTime<Utc>> {
+    TimeContext::local().when(input)
+}
+
+/// The first time range mentioned in free text, with the words that named it
+pub fn find_range(text: &str) -> Option<(TimeRange, String)> {
+    TimeContext::local().find(text)
+}
+
+/// `input` as an adverbial phrase for messages: "in the last 7d",
+/// "yesterday", "since monday", "on 2024-05-01"
+pub fn describe(input: &str) -> String {
+    let s = normalize(input);
+    let typed = input.split_whitespace().collect::<Vec<_>>().join(" ");
+    if s.starts_with(|c: char| c.is_ascii_digit())
+        && (parse_span(&s).is_some() || s.parse::<u32>().is_ok())
+    {
+        format!("in the last {}", typed)
+    } else if s.starts_with("last ") || s.starts_with("past ") {
+        format!("in the {}", typed)
+    } else if ["since ", "from ", "between ", "this "]
+        .iter()
+        .any(|p| s.starts_with(p))
+        || matches!(s.as_str(), "today" | "yesterday")
+    {
+        typed
+    } else if NaiveDate::parse_from_str(&s, "%Y-%m-%d").is_ok() || parse_weekday(&s).is_some() {
+        format!("on {}", typed)
+    } else {
+        format!("since {}", typed)
+    }
+}
+
+/// "Now" and the facts relative inputs are resolved against
+#[derive(Debug, Clone)]
+pub struct TimeContext<Tz: TimeZone> {
+    now: DateTime<Tz>,
+    boot: Option<DateTime<Utc>>,
+}
+
+impl TimeContext<Local> {
+    /// The current time in the local timezone, and this boot
+    pub fn local() -> Self {
+        Self::new(Local::now()).with_boot(boot_time())
+    }
+}
+
+impl<Tz: TimeZone> TimeContext<Tz>
+where
+    Tz::Offset: fmt::Display,
+{
+    pub fn new(now: DateTime<Tz>) -> Self {
+        Self { now, boot: None }
+    }
+
+    /// When the system booted, for "since boot"
+    pub fn with_boot(mut self, boot: Option<DateTime<Utc>>) -> Self {
+        self.boot = boot;
+        self
+    }
+
+    /// A window ending at or before now
+    pub fn range(&self, input: &str) -> Result<TimeRange> {
+        let s = normalize(input);
+        anyhow::ensure!(!s.is_empty(), "Empty time range ({})", EXAMPLES);
+        let now = self.now.with_timezone(&Utc);
+
+        if let Some(rest) = s.strip_prefix("since ") {
+            let start = self.past_point(rest, input)?.start(self);
+            anyhow::ensure!(start <= now, "'{}' is in the future", input.trim());
+            return Ok(TimeRange { start, end: now });
+        }
+        if let Some((from, to)) = split_between(&s) {
+            let start = self.past_point(from, input)?.start(self);
+            let end = match self.past_point(to, input)? {
+                Point::Day(day) => self.start_of_day(day + Days::new(1)).min(now),
+                point => point.start(self),
+            };
+            anyhow::ensure!(start < end, "'{}' ends before it starts", input.trim());
+            return Ok(TimeRange { start, end });
+        }
+        match s.as_str() {
+            "this week" => {
+                let monday =
+                    self.today() - Days::new(self.today().weekday().num_days_from_monday() as u64);
+                return Ok(TimeRange {
+                    start: self.start_of_day(monday),
+                    end: now,
+                });
+            }
+            "this month" => {
+                let first = self.today().with_day(1).unwrap_or(self.today());
+                return Ok(TimeRange {
+                    start: self.start_of_day(first),
+                    end: now,
+                });
+            }
+            _ => {}
+        }
+        let span = match s.strip_prefix("last ").or_else(|| s.strip_prefix("past ")) {
+            Some(rest) => {
+                parse_span(rest).or_else(|| parse_unit(rest).map(|unit| Span { n: 1, unit }))
+            }
+            // A bare number is days, as `--since 7` always was
+            None => parse_span(&s).or_else(|| s.parse().ok().map(|n| Span { n, unit: Unit::Day })),
+        };
+        if let Some(span) = span {
+            return Ok(TimeRange {
+                start: self.back(span)?,
+                end: now,
+            });
+        }
+
+        // A day on its own is that whole day; anything finer runs until now
+        match self.past_point(&s, input)? {
+            Point::Day(day) => {
+                let start = self.start_of_day(day);
+                anyhow::ensure!(start <= now, "'{}' is in the future", input.trim());
+                Ok(TimeRange {
+                    start,
+                    end: self.start_of_day(day + Days::new(1)).min(now),
+                })
+            }
+            Point::Instant(start) => {
+                anyhow::ensure!(start <= now, "'{}' is in the future", input.trim());
+                Ok(TimeRange { start, end: now })
+            }
+        }
+    }
+
+    /// A point at or after now: "now", "in 2 hours", "tomorrow 03:00",
+    /// "saturday at 02:30", or an absolute time
+    pub fn when(&self, input: &str) -> Result<DateTime<Utc>> {
+        let s = normalize(input);
+        let s = s.strip_prefix("at ").unwrap_or(&s);
+        let now = self.now.with_timezone(&Utc);
+        if s == "now" {
+            return Ok(now);
+        }
+        if let Some(rest) = s.strip_prefix("in ") {
+            let span = parse_span(rest).with_context(|| {
+                format!(
+                    "Invalid delay '{}' (try in 30 minutes, in 2 hours)",
+                    input.trim()
+                )
+            })?;
+            return self.forward(span);
+        }
+
+        let (day_part, time) = split_time_of_day(s);
+        let time = time
+            .transpose()
+            .with_context(|| format!("Invalid time in '{}'", input.trim()))?;
+        let when = match (day_part, time) {
+            // A bare time is its next occurrence
+            ("", Some(time)) => {
+                let today = self.local_time(self.today().and_time(time), input)?;
+                if today > now {
+                    today
+                } else {
+                    self.local_time((self.today() + Days::new(1)).and_time(time), input)?
+                }
+            }
+            (day, time) => match parse_weekday(day) {
+                // A weekday is the next one still ahead, today included
+                Some(weekday) => {
+                    let time = time.unwrap_or(NaiveTime::MIN);
+                    let mut found = None;
+                    for ahead in 0..=7 {
+                        let date = self.today() + Days::new(ahead);
+                        if date.weekday() != weekday {
+                            continue;
+                        }
+                        let candidate = self.local_time(date.and_time(time), input)?;
+                        if candidate > now {
+                            found = Some(candidate);
+                            break;
+                        }
+                    }
+                    found.context("no such weekday ahead")?
+                }
+                None => match (self.point(day, input)?, time) {
+                    (Point::Day(date), Some(time)) => {
+                        self.local_time(date.and_time(time), input)?
+                    }
+                    (Point::Day(date), None) => self.start_of_day(date),
+                    (Point::Instant(_), Some(_)) => {
+                        anyhow::bail!("Invalid time '{}' ({})", input.trim(), EXAMPLES)
+                    }
+                    (Point::Instant(at), None) => at,
+                },
+            },
+        };
+        anyhow::ensure!(when >= now, "'{}' is in the past", input.trim());
+        Ok(when)
+    }
+
+    /// The first time range mentioned in `text`, e.g. "logs of nginx since
+    /// yesterday" gives yesterday's midnight until now
+    pub fn find(&self, text: &str) -> Option<(TimeRange, String)> {
+        let words: Vec<String> = text
+            .split_whitespace()
+            .map(|w| {
+                w.trim_matches(|c: char| {
+                    matches!(c, ',' | '?' | '!' | ';' | '"' | '\'' | '(' | ')')
+                })
+                .trim_end_matches('.')
+                .to_lowercase()
+            })
+            .collect();
+        let starts = [
+            "last",
+            "past",
+            "since",
+            "from",
+            "between",
+            "this",
+            "today",
+            "yesterday",
+        ];
+        for (i, word) in words.iter().enumerate() {
+            if !starts.contains(&word.as_str()) {
+                continue;
+            }
+            // Longest phrase first, so "since monday 08:00" beats "since monday"
+            for end in (i + 1..=(i + MAX_PHRASE_WORDS).min(words.len())).rev() {
+                let phrase = words[i..end].join(" ");
+                if let Ok(range) = self.range(&phrase) {
+                    return Some((range, phrase));
+                }
+            }
+        }
+        None
+    }
+
+    fn today(&self) -> NaiveDate {
+        self.now.date_naive()
+    }
+
+    /// A point that can't be after now, for the start of a range
+    fn past_point(&self, s: &str, input: &str) -> Result<Point> {
+        if let Some(weekday) = s.strip_prefix("last ").and_then(parse_weekday) {
+            // "last monday" is the one before today, even on a Monday
+            let back = (self.today().weekday().num_days_from_monday() + 6
+                - weekday.num_days_from_monday())
+                % 7
+                + 1;
+            return Ok(Point::Day(self.today() - Days::new(back as u64)));
+        }
+        if let Some(weekday) = parse_weekday(s) {
+            let back = (self.today().weekday().num_days_from_monday() + 7
+                - weekday.num_days_from_monday())
+                % 7;
+            return Ok(Point::Day(self.today() - Days::new(back as u64)));
+        }
+        let (day_part, time) = split_time_of_day(s);
+        match time {
+            // A bare time is its latest occurrence
+            Some(Ok(time)) if day_part.is_empty() => {
+                let today = self.local_time(self.today().and_time(time), input)?;
+                if today <= self.now.with_timezone(&Utc) {
+                    Ok(Point::Instant(today))
+                } else {
+                    let yesterday = self.today() - Days::new(1);
+                    Ok(Point::Instant(
+                        self.local_time(yesterday.and_time(time), input)?,
+                    ))
+                }
+            }
+            Some(Ok(time)) => match self.past_point(day_part, input)? {
+                Point::Day(date) => {
+                    Ok(Point::Instant(self.local_time(date.and_time(time), input)?))
+                }
+                Point::Instant(_) => {
+                    anyhow::bail!("Invalid time '{}' ({})", input.trim(), EXAMPLES)
+                }
+            },
+            _ => self.point(s, input),
+        }
+    }
+
+    /// Forms that mean the same in the past and the future
+    fn point(&self, s: &str, input: &str) -> Result<Point> {
+        let now = self.now.with_timezone(&Utc);
+        match s {
+            "now" => return Ok(Point::Instant(now)),
+            "boot" => {
+                return self
+                    .boot
+                    .map(Point::Instant)
+                    .context("Boot time is unknown (/proc/stat has no btime)");
+            }
+            "today" => return Ok(Point::Day(self.today())),
+            "yesterday" => return Ok(Point::Day(self.today() - Days::new(1))),
+            "tomorrow" => return Ok(Point::Day(self.today() + Days::new(1))),
+            _ => {}
+        }
+        if let Some(span) = s.strip_suffix(" ago").and_then(parse_span) {
+            return self.back(span).map(Point::Instant);
+        }
+        if let Ok(at) = DateTime::parse_from_rfc3339(s) {
+            return Ok(Point::Instant(at.with_timezone(&Utc)));
+        }
+        if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            return Ok(Point::Day(date));
+        }
+        for format in [
+            "%Y-%m-%d %H:%M:%S",
+            "%Y-%m-%d %H:%M",
+            "%Y-%m-%dt%H:%M:%S",
+            "%Y-%m-%dt%H:%M",
+        ] {
+            if let Ok(local) = NaiveDateTime::parse_from_str(s, format) {
+                return self.local_time(local, input).map(Point::Instant);
+            }
+        }
+        // Day-first with dots, as written in most of Europe
+        if let Ok(date) = NaiveDate::parse_from_str(s, "%d.%m.%Y") {
+            return Ok(Point::Day(date));
+        }
+        if let Some(date) = self.slashed_date(s, input)? {
+            return Ok(Point::Day(date));
+        }
+        anyhow::bail!("Unrecognized time '{}' ({})", input.trim(), EXAMPLES)
+    }
+
+    /// "05/13/2024" or "13/05/2024"; both readings are offered when either works
+    fn slashed_date(&self, s: &str, input: &str) -> Result<Option<NaiveDate>> {
+        let parts: Vec<&str> = s.split('/').collect();
+        let [a, b, year] = parts.as_slice() else {
+            return Ok(None);
+        };
+        let (Ok(a), Ok(b), Ok(year)) = (a.parse::<u32>(), b.parse::<u32>(), year.parse::<i32>())
+        else {
+            return Ok(None);
+        };
+        let month_first = NaiveDate::from_ymd_opt(year, a, b);
+        let day_first = NaiveDate::from_ymd_opt(year, b, a);
+        match (month_first, day_first) {
+            (Some(m), Some(d)) if m != d => Err(AmbiguousTime {
+                input: input.trim().to_string(),
+                interpretations: [m, d]
+                    .into_iter()
+                    .map(|date| Interpretation {
+                        description: date.format("%B %-d, %Y").to_string(),
+                        time: self.start_of_day(date),
+                    })
+                    .collect(),
+            }
+            .into()),
+            (Some(date), _) | (None, Some(date)) => Ok(Some(date)),
+            (None, None) => anyhow::bail!("'{}' is not a date", input.trim()),
+        }
+    }
+
+    /// A wall-clock time the user typed, which must name exactly one instant
+    fn local_time(&self, local: NaiveDateTime, input: &str) -> Result<DateTime<Utc>> {
+        match self.now.timezone().from_local_datetime(&local) {
+            LocalResult::Single(at) => Ok(at.with_timezone(&Utc)),
+            LocalResult::Ambiguous(first, second) => Err(AmbiguousTime {
+                input: input.trim().to_string(),
+                interpretations: [first, second]
+                    .into_iter()
+                    .map(|at| Interpretation {
+                        description: at.format("%Y-%m-%d %H:%M %:z").to_string(),
+                        time: at.with_timezone(&Utc),
+                    })
+                    .collect(),
+            }
+            .into()),
+            LocalResult::None => anyhow::bail!(
+                "'{}' does not exist in the local timezone; the clocks skip it for daylight saving",
+                input.trim()
+            ),
+        }
+    }
+
+    /// A wall-clock time Jarvis computed: the earlier instant when it
+    /// happens twice, the end of the gap when it is skipped
+    fn lenient_local(&self, local: NaiveDateTime) -> DateTime<Utc> {
+        let tz = self.now.timezone();
+        let mut candidate = local;
+        // Gaps are at most an hour or two in practice
+        for _ in 0..8 {
+            if let Some(at) = tz.from_local_datetime(&candidate).earliest() {
+                return at.with_timezone(&Utc);
+            }
+            candidate += Duration::minutes(30);
+        }
+        local.and_utc() - Duration::seconds(self.now.offset().fix().local_minus_utc() as i64)
+    }
+
+    fn start_of_day(&self, date: NaiveDate) -> DateTime<Utc> {
+        self.lenient_local(date.and_time(NaiveTime::MIN))
+    }
+
+    fn back(&self, span: Span) -> Result<DateTime<Utc>> {
+        self.shift(span, false)
+    }
+
+    fn forward(&self, span: Span) -> Result<DateTime<Utc>> {
+        self.shift(span, true)
+    }
+
+    fn shift(&self, span: Span, forward: bool) -> Result<DateTime<Utc>> {
+        let now = self.now.with_timezone(&Utc);
+        let exact = |d: Option<Duration>| {
+            let d = d.context("Time span is out of range")?;
+            let at = if forward {
+                now.checked_add_signed(d)
+            } else {
+                now.checked_sub_signed(d)
+            };
+            at.context("Time span is out of range")
+        };
+        let local = self.now.naive_local();
+        let n = span.n.max(0) as u64;
+        let shifted = match span.unit {
+            Unit::Minute => return exact(Duration::try_minutes(span.n)),
+            Unit::Hour => return exact(Duration::try_hours(span.n)),
+            Unit::Day | Unit::Week => {
+                let days = Days::new(if span.unit == Unit::Week { n * 7 } else { n });
+                if forward {
+                    local.checked_add_days(days)
+                } else {
+                    local.checked_sub_days(days)
+                }
+            }
+            Unit::Month => {
+                let months = Months::new(u32::try_from(n).unwrap_or(u32::MAX));
+                if forward {
+                    local.checked_add_months(months)
+                } else {
+                    local.checked_sub_months(months)
+                }
+            }
+        };
+        shifted
+            .map(|local| self.lenient_local(local))
+            .context("Time span is out of range")
+    }
+}
+
+/// A parsed point: a whole day, or an instant
+#[derive(Debug, Clone, Copy)]
+enum Point {
+    Day(NaiveDate),
+    Instant(DateTime<Utc>),
+}
+
+impl Point {
+    fn start<Tz: TimeZone>(self, context: &TimeContext<Tz>) -> DateTime<Utc>
+    where
+        Tz::Offset: fmt::Display,
+    {
+        match self {
+            Point::Day(date) => context.start_of_day(date),
+            Point::Instant(at) => at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Unit {
+    Minute,
+    Hour,
+    Day,
+    Week,
+    Month,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Span {
+    n: i64,
+    unit: Unit,
+}
+
+/// "7d", "24 h", "2 hours", "an hour", "a week"
+fn parse_span(s: &str) -> Option<Span> {
+    let s = s.trim();
+    let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(digits);
+    let (n, unit) = if number.is_empty() {
+        let (article, unit) = s.split_once(' ')?;
+        matches!(article, "a" | "an" | "one").then_some((1, unit))?
+    } else {
+        (number.parse().ok()?, unit.trim())
+    };
+    Some(Span {
+        n,
+        unit: parse_unit(unit)?,
+    })
+}
+
+fn parse_unit(unit: &str) -> Option<Unit> {
+    match unit {
+        "m" | "min" | "mins" | "minute" | "minutes" => Some(Unit::Minute),
+        "h" | "hr" | "hrs" | "hour" | "hours" => Some(Unit::Hour),
+        "d" | "day" | "days" => Some(Unit::Day),
+        "w" | "wk" | "wks" | "week" | "weeks" => Some(Unit::Week),
+        "mo" | "month" | "months" => Some(Unit::Month),
+        _ => None,
+    }
+}
+
+fn parse_weekday(s: &str) -> Option<Weekday> {
+    match s {
+        "mon" | "monday" => Some(Weekday::Mon),
+        "tue" | "tues" | "tuesday" => Some(Weekday::Tue),
+        "wed" | "wednesday" => Some(Weekday::Wed),
+        "thu" | "thur" | "thurs" | "thursday" => Some(Weekday::Thu),
+        "fri" | "friday" => Some(Weekday::Fri),
+        "sat" | "saturday" => Some(Weekday::Sat),
+        "sun" | "sunday" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+/// Split "tomorrow at 03:00" into ("tomorrow", 03:00). The time is the last
+/// word when it looks like one; "2024-05-01 14:00" stays whole for the
+/// datetime formats.
+fn split_time_of_day(s: &str) -> (&str, Option<Result<NaiveTime>>) {
+    let (head, last) = match s.rsplit_once(' ') {
+        Some((head, last)) => (head.trim_end(), last),
+        None => ("", s),
+    };
+    let looks_like_time =
+        last.contains(':') && last.chars().all(|c| c.is_ascii_digit() || c == ':');
+    if !looks_like_time || NaiveDate::parse_from_str(head, "%Y-%m-%d").is_ok() {
+        return (s, None);
+    }
+    let head = head.strip_suffix(" at").unwrap_or(head);
+    let time = NaiveTime::parse_from_str(last, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(last, "%H:%M:%S"))
+        .map_err(anyhow::Error::from);
+    (head, Some(time))
+}
+
+/// "from A to B", "between A and B", or "A..B"
+fn split_between(s: &str) -> Option<(&str, &str)> {
+    if let Some(rest) = s.strip_prefix("from ") {
+        return rest
+            .split_once(" to ")
+            .or_else(|| rest.split_once(" until "));
+    }
+    if let Some(rest) = s.strip_prefix("between ") {
+        return rest.split_once(" and ");
+    }
+    s.split_once("..").map(|(a, b)| (a.trim(), b.trim()))
+}
+
+fn normalize(input: &str) -> String {
+    input
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// When this boot started, from `btime` in /proc/stat
+fn boot_time() -> Option<DateTime<Utc>> {
+    let stat = std::fs::read_to_string("/proc/stat").ok()?;
+    let btime = stat.lines().find_map(|line| line.strip_prefix("btime "))?;
+    DateTime::from_timestamp(btime.trim().parse().ok()?, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::America::{New_York, Sao_Paulo};
+    use chrono_tz::Europe::Berlin;
+    use chrono_tz::Tz;
+
+    fn at(tz: Tz, local: &str) -> TimeContext<Tz> {
+        let local = NaiveDateTime::parse_from_str(local, "%Y-%m-%d %H:%M").unwrap();
+        TimeContext::new(tz.from_local_datetime(&local).single().unwrap())
+    }
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    fn range(context: &TimeContext<Tz>, input: &str) -> (DateTime<Utc>, DateTime<Utc>) {
+        let range = context
+            .range(input)
+            .unwrap_or_else(|e| panic!("{}: {}", input, e));
+        (range.start, range.end)
+    }
+
+    fn ambiguity(result: Result<impl fmt::Debug>) -> AmbiguousTime {
+        result.unwrap_err().downcast::<AmbiguousTime>().unwrap()
+    }
+
+    #[test]
+    fn test_spans_end_now() {
+        // Wednesday
+        let context = at(Berlin, "2024-05-15 12:00");
+        let now = utc("2024-05-15T10:00:00Z");
+        for (input, start) in [
+            ("24h", "2024-05-14T10:00:00Z"),
+            ("7d", "2024-05-08T10:00:00Z"),
+            ("7", "2024-05-08T10:00:00Z"),
+            ("2w", "2024-05-01T10:00:00Z"),
+            ("30m", "2024-05-15T09:30:00Z"),
+            ("90 minutes", "2024-05-15T08:30:00Z"),
+            ("last 2 hours", "2024-05-15T08:00:00Z"),
+            ("Past 3  Days", "2024-05-12T10:00:00Z"),
+            ("last hour", "2024-05-15T09:00:00Z"),
+            ("an hour", "2024-05-15T09:00:00Z"),
+            ("last week", "2024-05-08T10:00:00Z"),
+            ("last 3 months", "2024-02-15T11:00:00Z"),
+        ] {
+            assert_eq!(range(&context, input), (utc(start), now), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_named_ranges() {
+        let context = at(Berlin, "2024-05-15 12:00").with_boot(Some(utc("2024-05-15T06:12:00Z")));
+        let now = utc("2024-05-15T10:00:00Z");
+        let midnight = utc("2024-05-14T22:00:00Z");
+
+        assert_eq!(range(&context, "today"), (midnight, now));
+        assert_eq!(
+            range(&context, "yesterday"),
+            (utc("2024-05-13T22:00:00Z"), midnight)
+        );
+        assert_eq!(
+            range(&context, "since yesterday"),
+            (utc("2024-05-13T22:00:00Z"), now)
+        );
+        assert_eq!(
+            range(&context, "this week"),
+            (utc("2024-05-12T22:00:00Z"), now)
+        );
+        assert_eq!(
+            range(&context, "since monday"),
+            (utc("2024-05-12T22:00:00Z"), now)
+        );
+        assert_eq!(
+            range(&context, "since mon 08:00"),
+            (utc("2024-05-13T06:00:00Z"), now)
+        );
+        assert_eq!(
+            range(&context, "since last wednesday"),
+            (utc("2024-05-07T22:00:00Z"), now)
+        );
+        assert_eq!(
+            range(&context, "this month"),
+            (utc("2024-04-30T22:00:00Z"), now)
+        );
+        assert_eq!(
+            range(&context, "since boot"),
+            (utc("2024-05-15T06:12:00Z"), now)
+        );
+        assert_eq!(
+            range(&context, "since 2 hours ago"),
+            (utc("2024-05-15T08:00:00Z"), now)
+        );
+        // The latest 14:00 was yesterday's
+        assert_eq!(
+            range(&context, "since 14:00"),
+            (utc("2024-05-14T12:00:00Z"), now)
+        );
+
+        // On a Monday, "monday" is today and "last monday" a week ago
+        let monday = at(Berlin, "2024-05-13 09:00");
+        assert_eq!(
+            range(&monday, "since monday").0,
+            utc("2024-05-12T22:00:00Z")
+        );
+        assert_eq!(
+            range(&monday, "since last monday").0,
+            utc("2024-05-05T22:00:00Z")
+        );
+
+        let err = at(Berlin, "2024-05-15 12:00")
+            .range("since boot")
+            .unwrap_err();
+        assert!(err.to_string().contains("Boot time is unknown"));
+    }
+
+    #[test]
+    fn test_absolute_ranges() {
+        let context = at(Berlin, "2024-05-15 12:00");
+        let now = utc("2024-05-15T10:00:00Z");
+
+        assert_eq!(
+            range(&context, "2024-05-01T08:00:00Z"),
+            (utc("2024-05-01T08:00:00Z"), now)
+        );
+        assert_eq!(
+            range(&context, "since 2024-05-01T08:00:00-04:00"),
+            (utc("2024-05-01T12:00:00Z"), now)
+        );
+        assert_eq!(
+            range(&context, "2024-05-01 14:30"),
+            (utc("2024-05-01T12:30:00Z"), now)
+        );
+        // A date is that whole day, or so much of it as has passed
+        assert_eq!(
+            range(&context, "2024-05-01"),
+            (utc("2024-04-30T22:00:00Z"), utc("2024-05-01T22:00:00Z"))
+        );
+        assert_eq!(
+            range(&context, "2024-05-15"),
+            (utc("2024-05-14T22:00:00Z"), now)
+        );
+        assert_eq!(
+            range(&context, "since 2024-05-01"),
+            (utc("2024-04-30T22:00:00Z"), now)
+        );
+        assert_eq!(
+            range(&context, "from 2024-05-01 to 2024-05-03"),
+            (utc("2024-04-30T22:00:00Z"), utc("2024-05-03T22:00:00Z"))
+        );
+        assert_eq!(
+            range(&context, "2024-05-01 06:00..2024-05-01 18:00"),
+            (utc("2024-05-01T04:00:00Z"), utc("2024-05-01T16:00:00Z"))
+        );
+        assert_eq!(
+            range(&context, "between monday and yesterday"),
+            (utc("2024-05-12T22:00:00Z"), utc("2024-05-14T22:00:00Z"))
+        );
+        assert_eq!(range(&context, "01.05.2024").0, utc("2024-04-30T22:00:00Z"));
+        // Slashed dates read either way when only one reading is a date
+        assert_eq!(range(&context, "05/13/2024").0, utc("2024-05-12T22:00:00Z"));
+        assert_eq!(range(&context, "13/05/2024").0, utc("2024-05-12T22:00:00Z"));
+    }
+
+    #[test]
+    fn test_ambiguous_dates_list_both_readings() {
+        let context = at(Berlin, "2024-05-15 12:00");
+        let ambiguous = ambiguity(context.range("since 03/04/2024"));
+        assert_eq!(ambiguous.input, "since 03/04/2024");
+        let readings: Vec<(&str, DateTime<Utc>)> = ambiguous
+            .interpretations
+            .iter()
+            .map(|i| (i.description.as_str(), i.time))
+            .collect();
+        assert_eq!(
+            readings,
+            vec![
+                ("March 4, 2024", utc("2024-03-03T23:00:00Z")),
+                ("April 3, 2024", utc("2024-04-02T22:00:00Z")),
+            ]
+        );
+        assert_eq!(
+            ambiguous.to_string(),
+            "'since 03/04/2024' is ambiguous: it could be March 4, 2024 or April 3, 2024"
+        );
+
+        // The same day either way is no ambiguity at all
+        assert_eq!(range(&context, "04/04/2024").0, utc("2024-04-03T22:00:00Z"));
+    }
+
+    #[test]
+    fn test_days_follow_the_wall_clock_across_dst() {
+        // Spring forward: 2024-03-10 02:00 EST becomes 03:00 EDT
+        let context = at(New_York, "2024-03-10 12:00");
+        let (start, end) = range(&context, "last 1d");
+        assert_eq!(start, utc("2024-03-09T17:00:00Z"));
+        assert_eq!(end - start, Duration::hours(23));
+        let (start, end) = range(&context, "last 24 hours");
+        assert_eq!(end - start, Duration::hours(24));
+
+        let (start, end) = range(&at(New_York, "2024-03-11 09:00"), "yesterday");
+        assert_eq!(
+            (start, end),
+            (utc("2024-03-10T05:00:00Z"), utc("2024-03-11T04:00:00Z"))
+        );
+        assert_eq!(end - start, Duration::hours(23));
+
+        // Fall back: 2024-11-03 02:00 EDT becomes 01:00 EST
+        let context = at(New_York, "2024-11-03 12:00");
+        let (start, end) = range(&context, "1d");
+        assert_eq!(end - start, Duration::hours(25));
+        assert_eq!(range(&context, "today").0, utc("2024-11-03T04:00:00Z"));
+
+        let (start, end) = range(&at(Berlin, "2024-10-27 12:00"), "today");
+        assert_eq!(
+            (start, end),
+            (utc("2024-10-26T22:00:00Z"), utc("2024-10-27T11:00:00Z"))
+        );
+        assert_eq!(end - start, Duration::hours(13));
+
+        // Sao Paulo once skipped midnight itself; the day began at 01:00
+        let context = at(Sao_Paulo, "2018-11-04 12:00");
+        assert_eq!(range(&context, "today").0, utc("2018-11-04T03:00:00Z"));
+    }
+
+    #[test]
+    fn test_wall_times_the_clocks_repeat_or_skip() {
+        let context = at(New_York, "2024-11-20 12:00");
+        let ambiguous = ambiguity(context.range("since 2024-11-03 01:30"));
+        let readings: Vec<(&str, DateTime<Utc>)> = ambiguous
+            .interpretations
+            .iter()
+            .map(|i| (i.description.as_str(), i.time))
+            .collect();
+        assert_eq!(
+            readings,
+            vec![
+                ("2024-11-03 01:30 -04:00", utc("2024-11-03T05:30:00Z")),
+                ("2024-11-03 01:30 -05:00", utc("2024-11-03T06:30:00Z")),
+            ]
+        );
+        // An offset picks one
+        assert_eq!(
+            range(&context, "since 2024-11-03T01:30:00-05:00").0,
+            utc("2024-11-03T06:30:00Z")
+        );
+
+        let err = context.range("since 2024-03-10 02:30").unwrap_err();
+        assert!(err.to_string().contains("does not exist"), "{}", err);
+        assert!(err.downcast_ref::<AmbiguousTime>().is_none());
+    }
+
+    #[test]
+    fn test_when_looks_ahead() {
+        // Wednesday
+        let context = at(Berlin, "2024-05-15 12:00");
+        let when = |input: &str| {
+            context
+                .when(input)
+                .unwrap_or_else(|e| panic!("{}: {}", input, e))
+        };
+
+        assert_eq!(when("now"), utc("2024-05-15T10:00:00Z"));
+        assert_eq!(when("in 2 hours"), utc("2024-05-15T12:00:00Z"));
+        assert_eq!(when("in a day"), utc("2024-05-16T10:00:00Z"));
+        assert_eq!(when("tomorrow 03:00"), utc("2024-05-16T01:00:00Z"));
+        assert_eq!(when("Tomorrow at 03:00"), utc("2024-05-16T01:00:00Z"));
+        assert_eq!(when("tomorrow"), utc("2024-05-15T22:00:00Z"));
+        // Next occurrence of a bare time or weekday
+        assert_eq!(when("03:00"), utc("2024-05-16T01:00:00Z"));
+        assert_eq!(when("at 18:30"), utc("2024-05-15T16:30:00Z"));
+        assert_eq!(when("saturday at 02:30"), utc("2024-05-18T00:30:00Z"));
+        assert_eq!(when("wednesday 13:00"), utc("2024-05-15T11:00:00Z"));
+        assert_eq!(when("wednesday 11:00"), utc("2024-05-22T09:00:00Z"));
+        assert_eq!(when("2024-06-01 03:00"), utc("2024-06-01T01:00:00Z"));
+
+        assert!(
+            context
+                .when("yesterday")
+                .unwrap_err()
+                .to_string()
+                .contains("in the past")
+        );
+        assert!(context.when("in soon").is_err());
+
+        // Scheduling into the hour the clocks skip is refused, not shifted
+        let err = at(New_York, "2024-03-09 12:00")
+            .when("tomorrow 02:30")
+            .unwrap_err();
+        assert!(err.to_string().contains("does not exist"), "{}", err);
+        let ambiguous = ambiguity(at(New_York, "2024-11-02 12:00").when("tomorrow 01:30"));
+        assert_eq!(ambiguous.interpretations.len(), 2);
+    }
+
+    #[test]
+    fn test_rejects_nonsense_and_the_future() {
+        let context = at(Berlin, "2024-05-15 12:00");
+        for input in [
+            "",
+            "soon",
+            "last 50 lines",
+            "since tomorrow",
+            "2024-06-01",
+            "from today to yesterday",
+            "31/31/2024",
+        ] {
+            assert!(context.range(input).is_err(), "{}", input);
+        }
+        assert!(
+            context
+                .range("soon")
+                .unwrap_err()
+                .to_string()
+                .contains("try 24h")
+        );
+        assert!(context.range("99999999999999999h").is_err());
+    }
+
+    #[test]
+    fn test_find_in_text() {
+        let context = at(Berlin, "2024-05-15 12:00");
+        let find = |text: &str| {
+            context
+                .find(text)
+                .map(|(range, phrase)| (range.start, phrase))
+        };
+
+        assert_eq!(
+            find("show nginx logs since yesterday"),
+            Some((utc("2024-05-13T22:00:00Z"), "since yesterday".to_string()))
+        );
+        assert_eq!(
+            find("errors in the last 2 hours?"),
+            Some((utc("2024-05-15T08:00:00Z"), "last 2 hours".to_string()))
+        );
+        assert_eq!(
+            find("what failed since Monday 08:00"),
+            Some((
+                utc("2024-05-13T06:00:00Z"),
+                "since monday 08:00".to_string()
+            ))
+        );
+        assert_eq!(find("show the last 50 lines of the ollama container"), None);
+        assert_eq!(find("restart the container"), None);
+    }
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(describe("7d"), "in the last 7d");
+        assert_eq!(describe("7"), "in the last 7");
+        assert_eq!(describe("last 2 hours"), "in the last 2 hours");
+        assert_eq!(describe("yesterday"), "yesterday");
+        assert_eq!(describe("since Monday"), "since Monday");
+        assert_eq!(describe("2024-05-01"), "on 2024-05-01");
+        assert_eq!(
+            describe("2024-05-01T08:00:00Z"),
+            "since 2024-05-01T08:00:00Z"
+        );
+    }
+}
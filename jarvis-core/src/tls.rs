@@ -0,0 +1,448 @@
+//! TLS and mutual TLS for Jarvis network services
+//!
+//! Shared by the GhostBridge gRPC server and the GhostFlow HTTP API. Server
+//! certificates are loaded from PEM files; when a client CA is configured every
+//! peer must present a certificate chaining to it, and an optional allowlist
+//! further restricts which subject names (SAN DNS/IP entries or CN) may connect.
+//! Certificates can be reloaded at runtime (SIGHUP) — connections already
+//! established keep the configuration they negotiated with.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::rustls::server::AllowAnyAuthenticatedClient;
+use tokio_rustls::rustls::{Certificate, PrivateKey, RootCertStore, ServerConfig};
+use tokio_rustls::server::TlsStream;
+use tracing::{debug, info, warn};
+
+/// TLS settings for a listening service
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TlsConfig {
+    /// PEM certificate chain presented by the server
+    pub cert_path: Option<PathBuf>,
+    /// PEM private key (PKCS#8, RSA or SEC1) for `cert_path`
+    pub key_path: Option<PathBuf>,
+    /// PEM CA bundle; when set, clients must present a certificate signed by it
+    pub client_ca_path: Option<PathBuf>,
+    /// Client identities (SAN or CN) allowed to connect; `*.example.com` matches one label.
+    /// Empty allows any certificate signed by the client CA.
+    pub allowed_peers: Vec<String>,
+}
+
+impl TlsConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.cert_path.is_some() && self.key_path.is_some()
+    }
+
+    pub fn requires_client_cert(&self) -> bool {
+        self.client_ca_path.is_some()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        match (&self.cert_path, &self.key_path) {
+            (Some(_), None) => anyhow::bail!("TLS cert_path is set but key_path is missing"),
+            (None, Some(_)) => anyhow::bail!("TLS key_path is set but cert_path is missing"),
+            _ => {}
+        }
+
+        if !self.is_enabled() && self.client_ca_path.is_some() {
+            anyhow::bail!("TLS client_ca_path requires cert_path and key_path");
+        }
+        if !self.allowed_peers.is_empty() && !self.requires_client_cert() {
+            anyhow::bail!("TLS allowed_peers requires client_ca_path to authenticate peers");
+        }
+
+        Ok(())
+    }
+
+    /// Build a rustls server configuration from the PEM files on disk
+    pub fn server_config(&self) -> Result<ServerConfig> {
+        self.validate()?;
+        let (Some(cert_path), Some(key_path)) = (&self.cert_path, &self.key_path) else {
+            anyhow::bail!("TLS is not configured");
+        };
+
+        let certs = load_certs(cert_path)?;
+        let key = load_private_key(key_path)?;
+        let builder = ServerConfig::builder().with_safe_defaults();
+
+        let config = match &self.client_ca_path {
+            Some(ca_path) => {
+                let mut roots = RootCertStore::empty();
+                for cert in load_certs(ca_path)? {
+                    roots.add(&cert).with_context(|| {
+                        format!("Invalid CA certificate in {}", ca_path.display())
+                    })?;
+                }
+                builder.with_client_cert_verifier(AllowAnyAuthenticatedClient::new(roots).boxed())
+            }
+            None => builder.with_no_client_auth(),
+        }
+        .with_single_cert(certs, key)
+        .context("Certificate and private key do not match")?;
+
+        Ok(config)
+    }
+
+    /// Whether a peer presenting these identities may connect
+    pub fn peer_allowed(&self, identities: &[String]) -> bool {
+        self.allowed_peers.is_empty()
+            || self.allowed_peers.iter().any(|pattern| {
+                identities
+                    .iter()
+                    .any(|identity| identity_matches(pattern, identity))
+            })
+    }
+}
+
+/// TLS acceptor whose certificates can be swapped without a restart
+pub struct ReloadableTlsAcceptor {
+    config: TlsConfig,
+    current: RwLock<TlsAcceptor>,
+}
+
+impl ReloadableTlsAcceptor {
+    pub fn new(config: TlsConfig) -> Result<Self> {
+        let acceptor = TlsAcceptor::from(Arc::new(config.server_config()?));
+        Ok(Self {
+            config,
+            current: RwLock::new(acceptor),
+        })
+    }
+
+    pub fn config(&self) -> &TlsConfig {
+        &self.config
+    }
+
+    /// Re-read certificates from disk; on error the previous ones stay in use
+    pub fn reload(&self) -> Result<()> {
+        let acceptor = TlsAcceptor::from(Arc::new(self.config.server_config()?));
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = acceptor;
+        Ok(())
+    }
+
+    /// Complete a TLS handshake and enforce the peer allowlist
+    pub async fn accept(&self, stream: TcpStream) -> Result<TlsStream<TcpStream>> {
+        let acceptor = self
+            .current
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        let tls = acceptor
+            .accept(stream)
+            .await
+            .context("TLS handshake failed")?;
+
+        if self.config.requires_client_cert() {
+            let identities = tls
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|chain| chain.first())
+                .map(|cert| peer_identities(&cert.0))
+                .unwrap_or_default();
+            if !self.config.peer_allowed(&identities) {
+                anyhow::bail!("Peer {:?} is not in the TLS allowlist", identities);
+            }
+        }
+
+        Ok(tls)
+    }
+
+    /// Reload certificates whenever the process receives SIGHUP
+    #[cfg(unix)]
+    pub fn reload_on_sighup(self: &Arc<Self>) -> Result<tokio::task::JoinHandle<()>> {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        let mut hangup = signal(SignalKind::hangup()).context("Failed to listen for SIGHUP")?;
+        let acceptor = Arc::clone(self);
+        Ok(tokio::spawn(async move {
+            while hangup.recv().await.is_some() {
+                match acceptor.reload() {
+                    Ok(()) => info!("🔐 Reloaded TLS certificates"),
+                    Err(e) => warn!("TLS reload failed, keeping previous certificates: {:#}", e),
+                }
+            }
+        }))
+    }
+}
+
+/// Accept connections on `listener` and hand over those that pass the TLS handshake.
+/// Handshakes run concurrently, so a slow or hostile client cannot stall the listener.
+pub fn accept_tls(
+    listener: TcpListener,
+    acceptor: Arc<ReloadableTlsAcceptor>,
+) -> mpsc::Receiver<TlsStream<TcpStream>> {
+    let (tx, rx) = mpsc::channel(64);
+
+    tokio::spawn(async move {
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    warn!("Failed to accept connection: {}", e);
+                    continue;
+                }
+            };
+
+            let acceptor = Arc::clone(&acceptor);
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                match acceptor.accept(stream).await {
+                    Ok(tls) => {
+                        let _ = tx.send(tls).await;
+                    }
+                    Err(e) => debug!("Rejected TLS connection from {}: {:#}", peer, e),
+                }
+            });
+
+            if tx.is_closed() {
+                break;
+            }
+        }
+    });
+
+    rx
+}
+
+/// Subject CN plus SAN DNS names and IP addresses of a DER certificate
+pub fn peer_identities(der: &[u8]) -> Vec<String> {
+    use x509_parser::prelude::{FromDer, GeneralName, X509Certificate};
+
+    let Ok((_, cert)) = X509Certificate::from_der(der) else {
+        return Vec::new();
+    };
+
+    let mut identities: Vec<String> = cert
+        .subject()
+        .iter_common_name()
+        .filter_map(|cn| cn.as_str().ok().map(str::to_string))
+        .collect();
+
+    if let Ok(Some(san)) = cert.subject_alternative_name() {
+        for name in &san.value.general_names {
+            match name {
+                GeneralName::DNSName(dns) => identities.push(dns.to_string()),
+                GeneralName::IPAddress(bytes) => {
+                    let ip = match bytes.len() {
+                        4 => <[u8; 4]>::try_from(*bytes).ok().map(std::net::IpAddr::from),
+                        16 => <[u8; 16]>::try_from(*bytes)
+                            .ok()
+                            .map(std::net::IpAddr::from),
+                        _ => None,
+                    };
+                    identities.extend(ip.map(|ip| ip.to_string()));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    identities
+}
+
+fn identity_matches(pattern: &str, identity: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(suffix) => identity
+            .split_once('.')
+            .is_some_and(|(label, rest)| !label.is_empty() && rest.eq_ignore_ascii_case(suffix)),
+        None => pattern.eq_ignore_ascii_case(identity),
+    }
+}
+
+fn load_certs(path: &Path) -> Result<Vec<Certificate>> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("Failed to open certificate {}", path.display()))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .with_context(|| format!("Failed to parse certificate {}", path.display()))?;
+    if certs.is_empty() {
+        anyhow::bail!("No certificates found in {}", path.display());
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn load_private_key(path: &Path) -> Result<PrivateKey> {
+    use rustls_pemfile::Item;
+
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("Failed to open private key {}", path.display()))?;
+    let items = rustls_pemfile::read_all(&mut BufReader::new(file))
+        .with_context(|| format!("Failed to parse private key {}", path.display()))?;
+
+    items
+        .into_iter()
+        .find_map(|item| match item {
+            Item::PKCS8Key(key) | Item::RSAKey(key) | Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .with_context(|| format!("No private key found in {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa};
+    use tokio_rustls::TlsConnector;
+    use tokio_rustls::rustls::{ClientConfig, ServerName};
+
+    struct Pki {
+        dir: tempfile::TempDir,
+        ca: rcgen::Certificate,
+        ca_der: Vec<u8>,
+    }
+
+    impl Pki {
+        fn new() -> Self {
+            let mut params = CertificateParams::new(Vec::new());
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            params
+                .distinguished_name
+                .push(DnType::CommonName, "jarvis test ca");
+            let ca = rcgen::Certificate::from_params(params).unwrap();
+            let ca_der = ca.serialize_der().unwrap();
+
+            let dir = tempfile::tempdir().unwrap();
+            std::fs::write(dir.path().join("ca.pem"), ca.serialize_pem().unwrap()).unwrap();
+            Self { dir, ca, ca_der }
+        }
+
+        /// Issue a leaf certificate signed by the CA; returns (cert DER, key DER)
+        fn issue(&self, name: &str, write_as: Option<&str>) -> (Vec<u8>, Vec<u8>) {
+            let mut params = CertificateParams::new(vec![name.to_string()]);
+            params.distinguished_name.push(DnType::CommonName, name);
+            let cert = rcgen::Certificate::from_params(params).unwrap();
+
+            if let Some(stem) = write_as {
+                std::fs::write(
+                    self.dir.path().join(format!("{}.pem", stem)),
+                    cert.serialize_pem_with_signer(&self.ca).unwrap(),
+                )
+                .unwrap();
+                std::fs::write(
+                    self.dir.path().join(format!("{}.key", stem)),
+                    cert.serialize_private_key_pem(),
+                )
+                .unwrap();
+            }
+
+            (
+                cert.serialize_der_with_signer(&self.ca).unwrap(),
+                cert.serialize_private_key_der(),
+            )
+        }
+
+        fn server_config(&self, allowed_peers: &[&str]) -> TlsConfig {
+            self.issue("localhost", Some("server"));
+            TlsConfig {
+                cert_path: Some(self.dir.path().join("server.pem")),
+                key_path: Some(self.dir.path().join("server.key")),
+                client_ca_path: Some(self.dir.path().join("ca.pem")),
+                allowed_peers: allowed_peers.iter().map(|p| p.to_string()).collect(),
+            }
+        }
+
+        fn connector(&self, client: Option<(Vec<u8>, Vec<u8>)>) -> TlsConnector {
+            let mut roots = RootCertStore::empty();
+            roots.add(&Certificate(self.ca_der.clone())).unwrap();
+            let builder = ClientConfig::builder()
+                .with_safe_defaults()
+                .with_root_certificates(roots);
+            let config = match client {
+                Some((cert, key)) => builder
+                    .with_client_auth_cert(vec![Certificate(cert)], PrivateKey(key))
+                    .unwrap(),
+                None => builder.with_no_client_auth(),
+            };
+            TlsConnector::from(Arc::new(config))
+        }
+    }
+
+    /// Run one handshake and report whether the server accepted the peer
+    async fn server_accepts(acceptor: &ReloadableTlsAcceptor, connector: TlsConnector) -> bool {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let client = tokio::spawn(async move {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let name = ServerName::try_from("localhost").unwrap();
+            let _ = connector.connect(name, stream).await;
+        });
+
+        let (stream, _) = listener.accept().await.unwrap();
+        let accepted = acceptor.accept(stream).await.is_ok();
+        client.await.unwrap();
+        accepted
+    }
+
+    #[test]
+    fn test_cert_without_key_is_rejected() {
+        let config = TlsConfig {
+            cert_path: Some(PathBuf::from("server.pem")),
+            ..TlsConfig::default()
+        };
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("key_path is missing"), "{}", err);
+
+        let config = TlsConfig {
+            key_path: Some(PathBuf::from("server.key")),
+            ..TlsConfig::default()
+        };
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("cert_path is missing"), "{}", err);
+
+        assert!(TlsConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_identity_matching() {
+        assert!(identity_matches("node.ghost", "NODE.ghost"));
+        assert!(identity_matches("*.ghost", "node.ghost"));
+        assert!(!identity_matches("*.ghost", "ghost"));
+        assert!(!identity_matches("*.ghost", "a.b.ghost"));
+    }
+
+    #[tokio::test]
+    async fn test_mtls_rejects_client_without_valid_cert() {
+        let pki = Pki::new();
+        let acceptor = ReloadableTlsAcceptor::new(pki.server_config(&[])).unwrap();
+
+        let trusted = pki.issue("agent.ghost", None);
+        assert!(server_accepts(&acceptor, pki.connector(Some(trusted))).await);
+
+        assert!(!server_accepts(&acceptor, pki.connector(None)).await);
+
+        let rogue_ca = Pki::new();
+        let untrusted = rogue_ca.issue("agent.ghost", None);
+        assert!(!server_accepts(&acceptor, pki.connector(Some(untrusted))).await);
+    }
+
+    #[tokio::test]
+    async fn test_allowlist_rejects_unlisted_peer() {
+        let pki = Pki::new();
+        let acceptor = ReloadableTlsAcceptor::new(pki.server_config(&["*.ghost"])).unwrap();
+
+        let listed = pki.issue("agent.ghost", None);
+        assert!(server_accepts(&acceptor, pki.connector(Some(listed))).await);
+
+        let unlisted = pki.issue("intruder.example", None);
+        assert!(!server_accepts(&acceptor, pki.connector(Some(unlisted))).await);
+    }
+
+    #[tokio::test]
+    async fn test_reload_keeps_previous_config_on_error() {
+        let pki = Pki::new();
+        let acceptor = ReloadableTlsAcceptor::new(pki.server_config(&[])).unwrap();
+
+        std::fs::write(pki.dir.path().join("server.key"), "not a key").unwrap();
+        assert!(acceptor.reload().is_err());
+
+        let client = pki.issue("agent.ghost", None);
+        assert!(server_accepts(&acceptor, pki.connector(Some(client))).await);
+    }
+}
@@ -0,0 +1,385 @@
+//! Per-request timing traces
+//!
+//! A [`Trace`] follows one request (a CLI command or an MCP tool call) from
+//! parsing through tool execution and LLM calls. Code along the way opens
+//! named phases with [`phase`] and tags them with [`annotate`]; both are
+//! no-ops unless the request runs inside [`Trace::scope`], so libraries can
+//! instrument freely without threading a trace through every signature.
+//! Traces slower than `[trace] persist_threshold_ms`, and those [`pin`]ned
+//! along the way, are kept for `jarvis trace list/show`.
+
+use crate::config::TraceConfig;
+use crate::memory::MemoryStore;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use uuid::Uuid;
+
+/// Memory store document holding persisted traces, newest first
+const DOCUMENT_KEY: &str = "traces";
+
+/// Width of the bar column in [`TraceReport::waterfall`]
+const BAR_WIDTH: usize = 30;
+
+tokio::task_local! {
+    static CURRENT: Trace;
+}
+
+/// One timed step of a request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TracePhase {
+    pub name: String,
+    /// Phases open when this one started; nested phases are indented
+    pub depth: usize,
+    /// Start, relative to the start of the trace
+    pub offset_ms: u64,
+    /// `None` while the phase is still running
+    pub duration_ms: Option<u64>,
+    pub attributes: BTreeMap<String, String>,
+}
+
+/// A finished trace
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraceReport {
+    pub id: Uuid,
+    /// What was asked, e.g. "diagnose ollama container" or "jarvis_docker:logs"
+    pub request: String,
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub phases: Vec<TracePhase>,
+    /// Kept however fast it was, e.g. so a routing decision can be corrected
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+#[derive(Debug)]
+struct TraceState {
+    report: TraceReport,
+    started: Instant,
+}
+
+/// Collects phases for one request; clones share the same trace
+#[derive(Debug, Clone)]
+pub struct Trace {
+    state: Arc<Mutex<TraceState>>,
+}
+
+impl Trace {
+    pub fn new(request: impl Into<String>) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(TraceState {
+                report: TraceReport {
+                    id: Uuid::new_v4(),
+                    request: request.into(),
+                    started_at: Utc::now(),
+                    duration_ms: 0,
+                    phases: Vec::new(),
+                    pinned: false,
+                },
+                started: Instant::now(),
+            })),
+        }
+    }
+
+    /// Run `future` with this as the current trace
+    pub async fn scope<F: Future>(&self, future: F) -> F::Output {
+        CURRENT.scope(self.clone(), future).await
+    }
+
+    /// Start a phase on this trace; it ends when the returned guard drops
+    pub fn phase(&self, name: &str) -> Phase {
+        let depth = {
+            let state = self.state.lock().unwrap();
+            let phases = &state.report.phases;
+            phases.iter().filter(|p| p.duration_ms.is_none()).count()
+        };
+        self.phase_at(name, depth)
+    }
+
+    fn phase_at(&self, name: &str, depth: usize) -> Phase {
+        let mut state = self.state.lock().unwrap();
+        let offset_ms = state.started.elapsed().as_millis() as u64;
+        let phases = &mut state.report.phases;
+        phases.push(TracePhase {
+            name: name.to_string(),
+            depth,
+            offset_ms,
+            duration_ms: None,
+            attributes: BTreeMap::new(),
+        });
+        Phase {
+            trace: Some(self.clone()),
+            index: phases.len() - 1,
+            started: Instant::now(),
+        }
+    }
+
+    /// Snapshot of the trace so far, with the total duration up to now
+    pub fn report(&self) -> TraceReport {
+        let state = self.state.lock().unwrap();
+        let mut report = state.report.clone();
+        report.duration_ms = state.started.elapsed().as_millis() as u64;
+        report
+    }
+
+    fn set_attribute(&self, index: usize, key: &str, value: String) {
+        if let Some(phase) = self.state.lock().unwrap().report.phases.get_mut(index) {
+            phase.attributes.insert(key.to_string(), value);
+        }
+    }
+
+    /// Innermost phase that is still running
+    fn open_phase(&self) -> Option<usize> {
+        let state = self.state.lock().unwrap();
+        state
+            .report
+            .phases
+            .iter()
+            .rposition(|p| p.duration_ms.is_none())
+    }
+}
+
+/// The trace of the request being handled on this task, if any
+pub fn current() -> Option<Trace> {
+    CURRENT.try_with(|trace| trace.clone()).ok()
+}
+
+/// Start a phase on the current trace; does nothing outside a trace
+pub fn phase(name: &str) -> Phase {
+    match current() {
+        Some(trace) => trace.phase(name),
+        None => Phase {
+            trace: None,
+            index: 0,
+            started: Instant::now(),
+        },
+    }
+}
+
+/// Keep the current trace regardless of `[trace] persist_threshold_ms`;
+/// does nothing outside a trace
+pub fn pin() {
+    if let Some(trace) = current() {
+        trace.state.lock().unwrap().report.pinned = true;
+    }
+}
+
+/// Tag the innermost running phase of the current trace
+pub fn annotate(key: &str, value: impl Display) {
+    if let Some(trace) = current()
+        && let Some(index) = trace.open_phase()
+    {
+        trace.set_attribute(index, key, value.to_string());
+    }
+}
+
+/// A running phase; records its duration when dropped
+#[derive(Debug)]
+pub struct Phase {
+    trace: Option<Trace>,
+    index: usize,
+    started: Instant,
+}
+
+impl Phase {
+    /// Start a phase directly under this one. For work that overlaps its
+    /// siblings, such as concurrent probes: [`phase`] nests by counting the
+    /// phases still open, which would put each sibling inside the last.
+    pub fn child(&self, name: &str) -> Phase {
+        let Some(trace) = &self.trace else {
+            return Phase {
+                trace: None,
+                index: 0,
+                started: Instant::now(),
+            };
+        };
+        let depth = {
+            let state = trace.state.lock().unwrap();
+            state
+                .report
+                .phases
+                .get(self.index)
+                .map_or(0, |p| p.depth + 1)
+        };
+        trace.phase_at(name, depth)
+    }
+
+    pub fn attr(&self, key: &str, value: impl Display) {
+        if let Some(trace) = &self.trace {
+            trace.set_attribute(self.index, key, value.to_string());
+        }
+    }
+}
+
+impl Drop for Phase {
+    fn drop(&mut self) {
+        if let Some(trace) = &self.trace
+            && let Some(phase) = trace
+                .state
+                .lock()
+                .unwrap()
+                .report
+                .phases
+                .get_mut(self.index)
+        {
+            phase.duration_ms = Some(self.started.elapsed().as_millis() as u64);
+        }
+    }
+}
+
+impl TraceReport {
+    /// One line per phase with a bar showing when it ran, e.g.
+    ///
+    /// ```text
+    /// parse               0ms      3ms █                    method=rule tool=jarvis_docker
+    ///   subprocess        5ms    120ms █████                command=docker inspect ollama
+    /// ```
+    pub fn waterfall(&self) -> String {
+        let total = self.duration_ms.max(1);
+        let name_width = self
+            .phases
+            .iter()
+            .map(|p| p.name.len() + p.depth * 2)
+            .max()
+            .unwrap_or(0);
+
+        let mut out = format!(
+            "⏱️  Trace {} \"{}\": {}ms\n",
+            short_id(self.id),
+            self.request,
+            self.duration_ms
+        );
+        for phase in &self.phases {
+            let duration = phase
+                .duration_ms
+                .unwrap_or(total - phase.offset_ms.min(total));
+            let start = ((phase.offset_ms * BAR_WIDTH as u64 / total) as usize).min(BAR_WIDTH - 1);
+            let width = ((duration * BAR_WIDTH as u64).div_ceil(total) as usize)
+                .clamp(1, BAR_WIDTH - start);
+            let bar = format!("{}{}", " ".repeat(start), "█".repeat(width));
+            let attributes: Vec<String> = phase
+                .attributes
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect();
+            out.push_str(&format!(
+                "  {:<name_width$} {:>6}ms {:>6}ms {:<bar_width$} {}\n",
+                format!("{}{}", "  ".repeat(phase.depth), phase.name),
+                phase.offset_ms,
+                duration,
+                bar,
+                attributes.join(" "),
+                bar_width = BAR_WIDTH,
+            ));
+        }
+        out
+    }
+
+    /// Keep this trace if it took at least the configured threshold or was
+    /// pinned; returns whether it was stored
+    pub async fn persist_if_slow(
+        &self,
+        memory: &MemoryStore,
+        config: &TraceConfig,
+    ) -> Result<bool> {
+        if !self.pinned && self.duration_ms < config.persist_threshold_ms {
+            return Ok(false);
+        }
+        let mut traces = load_traces(memory).await?;
+        traces.insert(0, self.clone());
+        traces.truncate(config.max_stored);
+        memory
+            .store_document(DOCUMENT_KEY, &serde_json::to_string(&traces)?)
+            .await?;
+        Ok(true)
+    }
+}
+
+/// Persisted traces, newest first
+pub async fn load_traces(memory: &MemoryStore) -> Result<Vec<TraceReport>> {
+    match memory.get_document(DOCUMENT_KEY).await? {
+        Some(data) => Ok(serde_json::from_str(&data)?),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// A persisted trace by full id or unique prefix
+pub async fn find_trace(memory: &MemoryStore, id: &str) -> Result<TraceReport> {
+    let matches: Vec<TraceReport> = load_traces(memory)
+        .await?
+        .into_iter()
+        .filter(|t| t.id.to_string().starts_with(id))
+        .collect();
+    match matches.len() {
+        0 => anyhow::bail!("No stored trace matches '{}'", id),
+        1 => Ok(matches.into_iter().next().unwrap()),
+        n => anyhow::bail!("'{}' matches {} traces; use more of the id", id, n),
+    }
+}
+
+pub fn short_id(id: Uuid) -> String {
+    id.to_string()[..8].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_phases_nest_and_collect_attributes() {
+        let trace = Trace::new("diagnose ollama container");
+        trace
+            .scope(async {
+                let parse = phase("parse");
+                parse.attr("method", "rule");
+                drop(parse);
+
+                let _tool = phase("tool");
+                {
+                    let _llm = phase("llm");
+                    annotate("model", "llama3.1:8b");
+                }
+                annotate("tool", "jarvis_docker");
+            })
+            .await;
+
+        let report = trace.report();
+        let names: Vec<(&str, usize)> = report
+            .phases
+            .iter()
+            .map(|p| (p.name.as_str(), p.depth))
+            .collect();
+        assert_eq!(names, vec![("parse", 0), ("tool", 0), ("llm", 1)]);
+        assert!(report.phases.iter().all(|p| p.duration_ms.is_some()));
+        assert_eq!(report.phases[0].attributes["method"], "rule");
+        assert_eq!(report.phases[1].attributes["tool"], "jarvis_docker");
+        assert_eq!(report.phases[2].attributes["model"], "llama3.1:8b");
+
+        let waterfall = report.waterfall();
+        assert!(waterfall.contains("diagnose ollama container"));
+        assert!(waterfall.contains("    llm"));
+        assert!(waterfall.contains("method=rule"));
+    }
+
+    #[tokio::test]
+    async fn test_pin_marks_the_current_trace() {
+        let trace = Trace::new("put neovim on this machine");
+        assert!(!trace.report().pinned);
+        trace.scope(async { pin() }).await;
+        assert!(trace.report().pinned);
+    }
+
+    #[test]
+    fn test_phases_outside_a_trace_are_noops() {
+        let phase = phase("parse");
+        phase.attr("method", "rule");
+        annotate("tool", "jarvis_docker");
+        pin();
+        assert!(current().is_none());
+    }
+}
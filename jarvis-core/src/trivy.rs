@@ -0,0 +1,559 @@
+//! Container image vulnerability scanning with Trivy
+//!
+//! jarvisd scans the images of running containers, plus local images matching
+//! `[trivy] images`, on `[trivy] schedule`. Image scans are slow, so each
+//! result is cached by image digest and an unchanged image is only rescanned
+//! once `cache_days` have passed and new advisories may apply. Security scans,
+//! the Wazuh forwarder, and reports read that cache rather than running Trivy
+//! themselves. With `server` set, Trivy runs in client mode and the
+//! vulnerability database lives on the server.
+
+use crate::exec::{CommandRunner, RunOptions, SystemRunner};
+use crate::file_access::name_matches;
+use crate::memory::MemoryStore;
+use crate::notify::{Notification, NotifyEvent, NotifySeverity};
+use crate::report::SecurityFinding;
+use crate::severity::Severity;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+
+/// Memory store document holding the latest scan of each image digest
+const CACHE_KEY: &str = "trivy_image_scans";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrivyConfig {
+    /// Let jarvisd scan container images on `schedule`
+    pub enabled: bool,
+    /// Trivy executable
+    pub binary: String,
+    /// `trivy server` URL, e.g. "http://scanner.lan:4954"; unset scans with a local database
+    pub server: Option<String>,
+    /// Five-field cron schedule for image scans
+    pub schedule: String,
+    /// Local images to scan besides those of running containers; `*`
+    /// matches any run of characters, e.g. "ghcr.io/me/*"
+    pub images: Vec<String>,
+    /// Rescan an image whose digest hasn't changed after this many days
+    pub cache_days: u64,
+    /// Give up on a single image after this long
+    pub timeout_secs: u64,
+}
+
+impl Default for TrivyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            binary: "trivy".to_string(),
+            server: None,
+            schedule: "0 3 * * *".to_string(),
+            images: Vec::new(),
+            cache_days: 7,
+            timeout_secs: 600,
+        }
+    }
+}
+
+/// One advisory against a package in an image
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImageVulnerability {
+    pub id: String,
+    pub package: String,
+    pub installed_version: String,
+    pub fixed_version: Option<String>,
+    pub severity: Severity,
+    pub title: String,
+    /// Where Trivy found the package, e.g. `nginx:1.25 (debian 12.5)` or a binary's path
+    pub target: String,
+}
+
+/// Trivy's findings for one image digest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageScan {
+    /// Reference the image was scanned under, e.g. `nginx:1.25`
+    pub image: String,
+    pub digest: String,
+    pub scanned_at: DateTime<Utc>,
+    pub vulnerabilities: Vec<ImageVulnerability>,
+}
+
+impl ImageScan {
+    /// One finding per vulnerable package, with the most severe of its
+    /// advisories, so acknowledgements and reports treat images like
+    /// installed packages
+    pub fn findings(&self) -> Vec<SecurityFinding> {
+        let mut packages: BTreeMap<&str, SecurityFinding> = BTreeMap::new();
+        for vulnerability in &self.vulnerabilities {
+            let finding = packages
+                .entry(vulnerability.package.as_str())
+                .or_insert_with(|| SecurityFinding {
+                    package: format!("{} in {}", vulnerability.package, self.image),
+                    severity: vulnerability.severity,
+                    advisories: Vec::new(),
+                });
+            finding.severity = finding.severity.max(vulnerability.severity);
+            if !finding.advisories.contains(&vulnerability.id) {
+                finding.advisories.push(vulnerability.id.clone());
+            }
+        }
+        packages.into_values().collect()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct TrivyReport {
+    #[serde(default)]
+    results: Vec<TrivyResult>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct TrivyResult {
+    #[serde(default)]
+    target: String,
+    /// `null` when the target has no known vulnerabilities
+    #[serde(default)]
+    vulnerabilities: Option<Vec<TrivyVulnerability>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct TrivyVulnerability {
+    #[serde(rename = "VulnerabilityID")]
+    vulnerability_id: String,
+    pkg_name: String,
+    #[serde(default)]
+    installed_version: String,
+    fixed_version: Option<String>,
+    #[serde(default)]
+    severity: String,
+    title: Option<String>,
+}
+
+/// Trivy rates advisories it has no score for `UNKNOWN`; count them as
+/// medium rather than hide them, as with arch-audit
+pub fn map_severity(severity: &str) -> Severity {
+    severity.parse().unwrap_or(Severity::Medium)
+}
+
+/// Vulnerabilities in a `trivy image --format json` report. A package
+/// reported under several targets, such as a Go stdlib compiled into two
+/// binaries, is listed once per advisory.
+pub fn parse_report(json: &str) -> Result<Vec<ImageVulnerability>> {
+    let report: TrivyReport = serde_json::from_str(json).context("Invalid Trivy JSON report")?;
+    let mut seen = HashSet::new();
+    let mut vulnerabilities = Vec::new();
+
+    for result in report.results {
+        for vulnerability in result.vulnerabilities.unwrap_or_default() {
+            let key = (
+                vulnerability.vulnerability_id.clone(),
+                vulnerability.pkg_name.clone(),
+                vulnerability.installed_version.clone(),
+            );
+            if !seen.insert(key) {
+                continue;
+            }
+            vulnerabilities.push(ImageVulnerability {
+                severity: map_severity(&vulnerability.severity),
+                id: vulnerability.vulnerability_id,
+                package: vulnerability.pkg_name,
+                installed_version: vulnerability.installed_version,
+                fixed_version: vulnerability.fixed_version.filter(|v| !v.is_empty()),
+                title: vulnerability.title.unwrap_or_default(),
+                target: result.target.clone(),
+            });
+        }
+    }
+    Ok(vulnerabilities)
+}
+
+/// The latest scan of every image found at the last run, keyed by digest
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImageScanCache {
+    scans: BTreeMap<String, ImageScan>,
+}
+
+impl ImageScanCache {
+    pub async fn load(memory: &MemoryStore) -> Result<Self> {
+        match memory.get_document(CACHE_KEY).await? {
+            Some(data) => serde_json::from_str(&data).context("Corrupt Trivy image scan cache"),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub async fn save(&self, memory: &MemoryStore) -> Result<()> {
+        memory
+            .store_document(CACHE_KEY, &serde_json::to_string(self)?)
+            .await
+    }
+
+    pub fn scans(&self) -> impl Iterator<Item = &ImageScan> {
+        self.scans.values()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scans.is_empty()
+    }
+
+    /// Findings of every cached image
+    pub fn findings(&self) -> Vec<SecurityFinding> {
+        self.scans().flat_map(ImageScan::findings).collect()
+    }
+
+    /// The scan of `digest`, unless it is older than `max_age`
+    fn fresh(&self, digest: &str, max_age: Duration, now: DateTime<Utc>) -> Option<&ImageScan> {
+        self.scans
+            .get(digest)
+            .filter(|scan| now - scan.scanned_at < max_age)
+    }
+}
+
+/// An image to scan and the digest its result is cached under
+#[derive(Debug, Clone, PartialEq)]
+struct ImageTarget {
+    image: String,
+    digest: String,
+}
+
+/// Outcome of one scan run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImageScanRun {
+    /// Images Trivy scanned this run
+    pub scanned: Vec<String>,
+    /// Images whose cached result was still fresh
+    pub cached: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+impl ImageScanRun {
+    /// Notification for a run that failed for some images; findings reach
+    /// the owner through security scans and reports instead
+    pub fn notification(&self) -> Option<Notification> {
+        if self.errors.is_empty() {
+            return None;
+        }
+        Some(Notification::new(
+            NotifyEvent::MaintenanceFailed,
+            NotifySeverity::Warning,
+            "Container image scan failed",
+            self.errors.join("\n"),
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct ImageScanner {
+    config: TrivyConfig,
+    runner: Arc<dyn CommandRunner>,
+}
+
+impl ImageScanner {
+    pub fn new(config: TrivyConfig) -> Self {
+        Self {
+            config,
+            runner: Arc::new(SystemRunner::default()),
+        }
+    }
+
+    /// Run docker and trivy through `runner` instead of spawning them directly
+    pub fn with_runner(mut self, runner: Arc<dyn CommandRunner>) -> Self {
+        self.runner = runner;
+        self
+    }
+
+    /// Whether the configured Trivy executable runs
+    pub async fn available(&self) -> bool {
+        self.runner
+            .output(&self.config.binary, &["--version"])
+            .await
+            .is_ok_and(|output| output.status.success())
+    }
+
+    /// Scan every target image whose cached result is missing or stale, and
+    /// store the results. Images that are gone drop out of the cache.
+    pub async fn run(&self, memory: &MemoryStore) -> Result<ImageScanRun> {
+        let mut cache = ImageScanCache::load(memory).await?;
+        let run = self.refresh(&mut cache, Utc::now()).await?;
+        cache.save(memory).await?;
+        Ok(run)
+    }
+
+    async fn refresh(
+        &self,
+        cache: &mut ImageScanCache,
+        now: DateTime<Utc>,
+    ) -> Result<ImageScanRun> {
+        if !self.available().await {
+            anyhow::bail!(
+                "{} is not installed; install trivy or set [trivy] binary",
+                self.config.binary
+            );
+        }
+
+        let max_age = Duration::days(self.config.cache_days as i64);
+        let mut run = ImageScanRun::default();
+        let mut current = BTreeMap::new();
+        for target in self.targets().await? {
+            if let Some(scan) = cache.fresh(&target.digest, max_age, now) {
+                run.cached.push(target.image);
+                current.insert(target.digest, scan.clone());
+                continue;
+            }
+            match self.scan_image(&target.image).await {
+                Ok(vulnerabilities) => {
+                    run.scanned.push(target.image.clone());
+                    let scan = ImageScan {
+                        image: target.image,
+                        digest: target.digest.clone(),
+                        scanned_at: now,
+                        vulnerabilities,
+                    };
+                    current.insert(target.digest, scan);
+                }
+                Err(e) => {
+                    run.errors.push(format!("{}: {:#}", target.image, e));
+                    // A stale result beats none
+                    if let Some(scan) = cache.scans.remove(&target.digest) {
+                        current.insert(target.digest, scan);
+                    }
+                }
+            }
+        }
+        cache.scans = current;
+        Ok(run)
+    }
+
+    async fn docker(&self, args: &[&str]) -> Result<String> {
+        let output = self.runner.output("docker", args).await?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "docker {} failed: {}",
+                args.first().copied().unwrap_or_default(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    /// Images of running containers, then local images matching a
+    /// configured pattern; each digest once
+    async fn targets(&self) -> Result<Vec<ImageTarget>> {
+        let mut targets: Vec<ImageTarget> = Vec::new();
+
+        let running = self.docker(&["ps", "--format", "{{.Image}}"]).await?;
+        for image in running.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            if targets.iter().any(|t| t.image == image) {
+                continue;
+            }
+            let digest = self
+                .docker(&["image", "inspect", "--format", "{{.Id}}", image])
+                .await?;
+            targets.push(ImageTarget {
+                image: image.to_string(),
+                digest: digest.trim().to_string(),
+            });
+        }
+
+        if !self.config.images.is_empty() {
+            let local = self
+                .docker(&[
+                    "images",
+                    "--no-trunc",
+                    "--format",
+                    "{{.Repository}}:{{.Tag}}\t{{.ID}}",
+                ])
+                .await?;
+            for line in local.lines() {
+                let Some((image, digest)) = line.trim().split_once('\t') else {
+                    continue;
+                };
+                if image.contains("<none>")
+                    || !self
+                        .config
+                        .images
+                        .iter()
+                        .any(|pattern| name_matches(pattern, image))
+                {
+                    continue;
+                }
+                targets.push(ImageTarget {
+                    image: image.to_string(),
+                    digest: digest.trim().to_string(),
+                });
+            }
+        }
+
+        let mut seen = HashSet::new();
+        targets.retain(|t| seen.insert(t.digest.clone()));
+        Ok(targets)
+    }
+
+    async fn scan_image(&self, image: &str) -> Result<Vec<ImageVulnerability>> {
+        let mut args = vec!["image", "--format", "json", "--quiet"];
+        if let Some(server) = &self.config.server {
+            args.extend(["--server", server.as_str()]);
+        }
+        args.push(image);
+
+        let options = RunOptions::default()
+            .with_timeout(std::time::Duration::from_secs(self.config.timeout_secs));
+        let output = self
+            .runner
+            .run(&self.config.binary, &args, &options)
+            .await?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "trivy failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        parse_report(&String::from_utf8_lossy(&output.stdout))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::RecordingRunner;
+    use crate::vuln::Acknowledgements;
+
+    const NGINX_REPORT: &str = include_str!("../tests/fixtures/trivy/nginx.json");
+
+    fn scan(image: &str, digest: &str, scanned_at: DateTime<Utc>) -> ImageScan {
+        ImageScan {
+            image: image.to_string(),
+            digest: digest.to_string(),
+            scanned_at,
+            vulnerabilities: parse_report(NGINX_REPORT).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_report_maps_severities_and_dedups_targets() {
+        let vulnerabilities = parse_report(NGINX_REPORT).unwrap();
+        let severity = |id: &str| {
+            vulnerabilities
+                .iter()
+                .find(|v| v.id == id)
+                .map(|v| v.severity)
+                .unwrap()
+        };
+
+        assert_eq!(severity("CVE-2024-5535"), Severity::Critical);
+        assert_eq!(severity("CVE-2024-4741"), Severity::High);
+        assert_eq!(severity("CVE-2023-45853"), Severity::Medium);
+        assert_eq!(severity("CVE-2011-3374"), Severity::Low);
+        assert_eq!(severity("TEMP-0841856-B18BAF"), Severity::Medium);
+
+        // The stdlib advisory is reported for two binaries
+        assert_eq!(vulnerabilities.len(), 6);
+        let stdlib: Vec<_> = vulnerabilities
+            .iter()
+            .filter(|v| v.package == "stdlib")
+            .collect();
+        assert_eq!(stdlib.len(), 1);
+        assert_eq!(stdlib[0].target, "usr/local/bin/healthcheck");
+        assert_eq!(stdlib[0].fixed_version.as_deref(), Some("1.21.9, 1.22.2"));
+
+        let zlib = vulnerabilities
+            .iter()
+            .find(|v| v.package == "zlib1g")
+            .unwrap();
+        assert_eq!(zlib.fixed_version, None);
+    }
+
+    #[test]
+    fn test_acknowledged_image_advisories_are_triaged_out() {
+        let now = Utc::now();
+        let findings = scan("nginx:1.25", "sha256:aaa", now).findings();
+        let libssl = findings
+            .iter()
+            .find(|f| f.package == "libssl3 in nginx:1.25")
+            .unwrap();
+        assert_eq!(libssl.severity, Severity::Critical);
+        assert_eq!(libssl.advisories, vec!["CVE-2024-5535", "CVE-2024-4741"]);
+
+        let mut acks = Acknowledgements::default();
+        acks.acknowledge("cve-2024-5535", "ALPN unused", None, now)
+            .unwrap();
+        acks.acknowledge("CVE-2011-3374", "apt-key unused", None, now)
+            .unwrap();
+        let (open, acknowledged) = acks.triage(findings, now);
+
+        assert_eq!(acknowledged.len(), 2);
+        assert!(open.iter().all(|f| f.package != "apt in nginx:1.25"));
+        let libssl = open
+            .iter()
+            .find(|f| f.package == "libssl3 in nginx:1.25")
+            .unwrap();
+        assert_eq!(libssl.advisories, vec!["CVE-2024-4741"]);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_rescans_only_missing_or_stale_digests() {
+        let now = Utc::now();
+        let runner = Arc::new(
+            RecordingRunner::new()
+                .respond("docker ps", "nginx:1.25\nredis:7\nnginx:1.25\n")
+                .respond(
+                    "docker image inspect --format {{.Id}} nginx:1.25",
+                    "sha256:aaa\n",
+                )
+                .respond(
+                    "docker image inspect --format {{.Id}} redis:7",
+                    "sha256:bbb\n",
+                )
+                .respond(
+                    "docker images",
+                    "ghcr.io/me/app:latest\tsha256:ccc\n\
+                     ghcr.io/me/app:<none>\tsha256:ddd\n\
+                     docker.io/library/nginx:1.25\tsha256:aaa\n\
+                     postgres:16\tsha256:eee\n",
+                )
+                .respond("trivy image", NGINX_REPORT),
+        );
+        let scanner = ImageScanner::new(TrivyConfig {
+            images: vec!["ghcr.io/me/*".to_string(), "*nginx*".to_string()],
+            ..Default::default()
+        })
+        .with_runner(runner.clone());
+
+        let mut cache = ImageScanCache::default();
+        for (image, digest, age) in [
+            ("nginx:1.25", "sha256:aaa", 1),
+            ("redis:7", "sha256:bbb", 8),
+            ("mariadb:11", "sha256:fff", 1),
+        ] {
+            let scan = scan(image, digest, now - Duration::days(age));
+            cache.scans.insert(digest.to_string(), scan);
+        }
+
+        let run = scanner.refresh(&mut cache, now).await.unwrap();
+        assert_eq!(run.cached, vec!["nginx:1.25"]);
+        assert_eq!(run.scanned, vec!["redis:7", "ghcr.io/me/app:latest"]);
+        assert!(run.errors.is_empty());
+
+        let trivy_calls: Vec<String> = runner
+            .calls()
+            .into_iter()
+            .filter(|c| c.starts_with("trivy image"))
+            .collect();
+        assert_eq!(
+            trivy_calls,
+            vec![
+                "trivy image --format json --quiet redis:7",
+                "trivy image --format json --quiet ghcr.io/me/app:latest",
+            ]
+        );
+
+        // The image no container uses anymore is dropped
+        let digests: Vec<&str> = cache.scans().map(|s| s.digest.as_str()).collect();
+        assert_eq!(digests, vec!["sha256:aaa", "sha256:bbb", "sha256:ccc"]);
+        assert!(cache.scans().all(|s| s.vulnerabilities.len() == 6));
+    }
+}
@@ -56,6 +56,18 @@ pub struct Conversation {
     pub updated_at: DateTime<Utc>,
 }
 
+/// Conversation listing entry without its messages
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationSummary {
+    pub id: String,
+    pub title: String,
+    pub message_count: i64,
+    /// First user message, for previews
+    pub first_message: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentTask {
     pub id: String,
@@ -108,6 +120,10 @@ pub struct GitContext {
     pub current_branch: String,
     pub dirty: bool,
     pub last_commit: String,
+    /// Modified, staged, and untracked paths relative to the repo root
+    pub dirty_files: Vec<String>,
+    /// Most recent commits as "<short id> <subject>", newest first
+    pub recent_commits: Vec<String>,
 }
 
 impl Default for GitContext {
@@ -117,7 +133,34 @@ impl Default for GitContext {
             current_branch: String::new(),
             dirty: false,
             last_commit: String::new(),
+            dirty_files: Vec::new(),
+            recent_commits: Vec::new(),
+        }
+    }
+}
+
+impl GitContext {
+    /// Compact multi-line summary for prompt context
+    pub fn summary(&self) -> String {
+        let mut out = format!(
+            "Git Repository: {} (branch {}, HEAD {})\n",
+            self.repo_path, self.current_branch, self.last_commit
+        );
+        if !self.dirty_files.is_empty() {
+            let shown: Vec<&str> = self.dirty_files.iter().take(20).map(|s| s.as_str()).collect();
+            out.push_str(&format!("Modified Files: {}", shown.join(", ")));
+            if self.dirty_files.len() > shown.len() {
+                out.push_str(&format!(" (+{} more)", self.dirty_files.len() - shown.len()));
+            }
+            out.push('\n');
+        }
+        if !self.recent_commits.is_empty() {
+            out.push_str("Recent Commits:\n");
+            for commit in &self.recent_commits {
+                out.push_str(&format!("  {}\n", commit));
+            }
         }
+        out
     }
 }
 
@@ -143,3 +186,56 @@ impl Default for SystemInfo {
         }
     }
 }
+
+/// How CLI commands render their results
+#[derive(Debug, Clone, Copy, Default, PartialEq, clap::ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Pretty,
+    Json,
+}
+
+/// A single recorded MCP tool invocation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub id: String,
+    pub timestamp: DateTime<Utc>,
+    pub tool: String,
+    pub action: Option<String>,
+    pub caller: Option<String>,
+    /// Call arguments with secret-looking values redacted
+    pub arguments: serde_json::Value,
+    pub duration_ms: u64,
+    pub status: AuditStatus,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum AuditStatus {
+    Success,
+    Error,
+    RateLimited,
+}
+
+impl std::fmt::Display for AuditStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuditStatus::Success => write!(f, "success"),
+            AuditStatus::Error => write!(f, "error"),
+            AuditStatus::RateLimited => write!(f, "rate_limited"),
+        }
+    }
+}
+
+impl std::str::FromStr for AuditStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "success" => Ok(AuditStatus::Success),
+            "error" => Ok(AuditStatus::Error),
+            "rate_limited" => Ok(AuditStatus::RateLimited),
+            other => Err(format!("Unknown audit status: {}", other)),
+        }
+    }
+}
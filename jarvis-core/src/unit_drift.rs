@@ -0,0 +1,278 @@
+//! systemd unit drift detection
+//!
+//! Compares the unit systemd actually loads with the file its package ships.
+//! Drop-ins and full copies under `/etc` are the supported way to customize a
+//! unit and count as expected overrides; an edited file under
+//! `/usr/lib/systemd/system` is silently lost on the next upgrade and usually
+//! the surprise behind an odd failure, so it is reported separately.
+
+use crate::remote::CommandExecutor;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+const VENDOR_UNIT_DIR: &str = "/usr/lib/systemd/system";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DriftKind {
+    /// Loaded exactly as packaged
+    Clean,
+    /// Customized through `/etc` or `/run`, as systemd intends
+    ExpectedOverride,
+    /// The package-shipped unit file itself was edited
+    ModifiedVendorFile,
+}
+
+/// Where a unit's effective configuration comes from, for one unit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnitDrift {
+    pub unit: String,
+    /// Main unit file systemd loaded
+    pub fragment_path: Option<String>,
+    /// Package owning the vendor unit file
+    pub package: Option<String>,
+    /// An `/etc` copy replaces the packaged unit entirely
+    pub replaces_vendor_file: bool,
+    /// Drop-ins from `/etc` or `/run`; vendor drop-ins are not listed
+    pub overrides: Vec<String>,
+    /// pacman's mtree data no longer matches the vendor unit file
+    pub vendor_file_modified: bool,
+    pub drift: DriftKind,
+}
+
+impl UnitDrift {
+    /// A few lines for diagnosis prompts and reports
+    pub fn summary(&self) -> String {
+        let mut out = format!("Unit {}: {}\n", self.unit, self.describe());
+        if let Some(path) = &self.fragment_path {
+            out.push_str(&format!("  Loaded from: {}\n", path));
+        }
+        if let Some(package) = &self.package {
+            out.push_str(&format!("  Packaged by: {}\n", package));
+        }
+        if self.vendor_file_modified {
+            out.push_str(&format!(
+                "  Vendor file {}/{} differs from the package and will be overwritten on upgrade\n",
+                VENDOR_UNIT_DIR, self.unit
+            ));
+        }
+        if self.replaces_vendor_file {
+            out.push_str(&format!(
+                "  Full copy in /etc replaces the packaged {}/{}\n",
+                VENDOR_UNIT_DIR, self.unit
+            ));
+        }
+        for path in &self.overrides {
+            out.push_str(&format!("  Override: {}\n", path));
+        }
+        out
+    }
+
+    /// How the unit differs from its package, in a few words
+    pub fn describe(&self) -> &'static str {
+        match self.drift {
+            DriftKind::Clean => "matches the packaged unit",
+            DriftKind::ExpectedOverride => "customized with overrides",
+            DriftKind::ModifiedVendorFile => "packaged unit file was modified in place",
+        }
+    }
+}
+
+/// Drift for every enabled service
+pub async fn scan_enabled_services(executor: &CommandExecutor) -> Result<Vec<UnitDrift>> {
+    let stdout = executor
+        .run_stdout(
+            "systemctl",
+            &[
+                "list-unit-files",
+                "--type=service",
+                "--state=enabled",
+                "--no-legend",
+                "--plain",
+            ],
+        )
+        .await?;
+
+    let mut report = Vec::new();
+    for unit in stdout.lines().filter_map(|l| l.split_whitespace().next()) {
+        // Template units are only meaningful through their instances
+        if unit.contains("@.") {
+            continue;
+        }
+        report.push(inspect_unit(executor, unit).await?);
+    }
+    Ok(report)
+}
+
+/// Compare one unit's effective configuration with its packaged version
+pub async fn inspect_unit(executor: &CommandExecutor, unit: &str) -> Result<UnitDrift> {
+    let unit = unit_name(unit);
+    let cat = executor
+        .run("systemctl", &["cat", "--no-pager", &unit])
+        .await?;
+    let sources = parse_cat_sources(&String::from_utf8_lossy(&cat.stdout));
+    let fragment_path = sources.first().cloned();
+    let overrides: Vec<String> = sources
+        .iter()
+        .skip(1)
+        .filter(|path| is_admin_path(path))
+        .cloned()
+        .collect();
+
+    let vendor_path = format!("{}/{}", VENDOR_UNIT_DIR, unit);
+    let has_vendor_file = executor
+        .run("test", &["-f", &vendor_path])
+        .await
+        .map(|o| o.status.success())
+        .unwrap_or(false);
+    let replaces_vendor_file =
+        has_vendor_file && fragment_path.as_deref().is_some_and(is_admin_path);
+
+    let package = if has_vendor_file {
+        executor
+            .run_stdout("pacman", &["-Qqo", &vendor_path])
+            .await
+            .ok()
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty())
+    } else {
+        None
+    };
+
+    let vendor_file_modified = match &package {
+        Some(package) => {
+            // -Qkk exits non-zero whenever anything in the package differs
+            let check = executor.run("pacman", &["-Qkk", package]).await?;
+            let report = format!(
+                "{}{}",
+                String::from_utf8_lossy(&check.stdout),
+                String::from_utf8_lossy(&check.stderr)
+            );
+            mtree_reports_modified(&report, &vendor_path)
+        }
+        None => false,
+    };
+
+    Ok(UnitDrift {
+        drift: classify(
+            vendor_file_modified,
+            replaces_vendor_file || !overrides.is_empty(),
+        ),
+        unit,
+        fragment_path,
+        package,
+        replaces_vendor_file,
+        overrides,
+        vendor_file_modified,
+    })
+}
+
+/// `nginx` -> `nginx.service`; names with a unit suffix are kept
+pub fn unit_name(name: &str) -> String {
+    let name = name.trim();
+    let has_suffix = [
+        ".service", ".socket", ".timer", ".mount", ".path", ".target",
+    ]
+    .iter()
+    .any(|suffix| name.ends_with(suffix));
+    if has_suffix {
+        name.to_string()
+    } else {
+        format!("{}.service", name)
+    }
+}
+
+fn classify(vendor_file_modified: bool, overridden: bool) -> DriftKind {
+    if vendor_file_modified {
+        DriftKind::ModifiedVendorFile
+    } else if overridden {
+        DriftKind::ExpectedOverride
+    } else {
+        DriftKind::Clean
+    }
+}
+
+/// Files administrators and runtime tools use to customize units
+fn is_admin_path(path: &str) -> bool {
+    path.starts_with("/etc/systemd/") || path.starts_with("/run/systemd/")
+}
+
+/// File paths from the `# /path` headers `systemctl cat` prints before each
+/// file, fragment first
+fn parse_cat_sources(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| line.strip_prefix("# /"))
+        .map(|path| format!("/{}", path.trim()))
+        .collect()
+}
+
+/// Whether `pacman -Qkk` flags `path` with a content mismatch. Modification
+/// time alone doesn't count; reinstalling touches it without changing anything.
+fn mtree_reports_modified(output: &str, path: &str) -> bool {
+    output.lines().any(|line| {
+        line.contains(&format!("{} (", path))
+            && (line.contains("checksum mismatch") || line.contains("Size mismatch"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cat_sources() {
+        let output = "# /etc/systemd/system/nginx.service\n\
+                      [Unit]\n\
+                      Description=nginx\n\
+                      \n\
+                      # /usr/lib/systemd/system/nginx.service.d/10-vendor.conf\n\
+                      [Service]\n\
+                      # /etc/systemd/system/nginx.service.d/override.conf\n\
+                      [Service]\n\
+                      LimitNOFILE=65536\n";
+        let sources = parse_cat_sources(output);
+
+        assert_eq!(
+            sources,
+            vec![
+                "/etc/systemd/system/nginx.service",
+                "/usr/lib/systemd/system/nginx.service.d/10-vendor.conf",
+                "/etc/systemd/system/nginx.service.d/override.conf",
+            ]
+        );
+        assert!(is_admin_path(&sources[0]));
+        assert!(!is_admin_path(&sources[1]));
+    }
+
+    #[test]
+    fn test_mtree_reports_modified() {
+        let output = "warning: nginx: /usr/lib/systemd/system/nginx.service (Modification time mismatch)\n\
+                      warning: nginx: /usr/lib/systemd/system/nginx.service (SHA256 checksum mismatch)\n\
+                      nginx: 60 total files, 0 altered files\n";
+        assert!(mtree_reports_modified(
+            output,
+            "/usr/lib/systemd/system/nginx.service"
+        ));
+
+        let touched_only =
+            "warning: sshd: /usr/lib/systemd/system/sshd.service (Modification time mismatch)\n";
+        assert!(!mtree_reports_modified(
+            touched_only,
+            "/usr/lib/systemd/system/sshd.service"
+        ));
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify(false, false), DriftKind::Clean);
+        assert_eq!(classify(false, true), DriftKind::ExpectedOverride);
+        assert_eq!(classify(true, true), DriftKind::ModifiedVendorFile);
+    }
+
+    #[test]
+    fn test_unit_name() {
+        assert_eq!(unit_name("nginx"), "nginx.service");
+        assert_eq!(unit_name("fstrim.timer"), "fstrim.timer");
+    }
+}
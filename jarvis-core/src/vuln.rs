@@ -0,0 +1,266 @@
+//! Vulnerability acknowledgements
+//!
+//! `jarvis vuln ack` records an advisory as a known, accepted risk. Scans still
+//! list acknowledged findings, but in their own section and outside the
+//! severity counts that drive health status and alerts. An acknowledgement can
+//! carry an expiry date, after which the advisory counts again on its own.
+
+use crate::memory::MemoryStore;
+use crate::report::SecurityFinding;
+use crate::severity::Severity;
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const DOCUMENT_KEY: &str = "vuln_acknowledgements";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Acknowledgement {
+    pub advisory: String,
+    pub reason: String,
+    /// Last day (UTC) the acknowledgement applies; `None` never expires
+    pub until: Option<NaiveDate>,
+    pub acknowledged_at: DateTime<Utc>,
+}
+
+impl Acknowledgement {
+    pub fn is_active_at(&self, now: DateTime<Utc>) -> bool {
+        self.until.is_none_or(|until| now.date_naive() <= until)
+    }
+}
+
+/// One advisory of a scan finding that is currently acknowledged
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AcknowledgedFinding {
+    pub package: String,
+    pub severity: Severity,
+    pub advisory: String,
+    pub reason: String,
+    pub until: Option<NaiveDate>,
+}
+
+/// Every recorded acknowledgement, keyed by advisory id
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Acknowledgements {
+    entries: BTreeMap<String, Acknowledgement>,
+}
+
+impl Acknowledgements {
+    pub async fn load(memory: &MemoryStore) -> Result<Self> {
+        match memory.get_document(DOCUMENT_KEY).await? {
+            Some(data) => {
+                serde_json::from_str(&data).context("Corrupt vulnerability acknowledgements")
+            }
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub async fn save(&self, memory: &MemoryStore) -> Result<()> {
+        memory
+            .store_document(DOCUMENT_KEY, &serde_json::to_string(self)?)
+            .await
+    }
+
+    /// Record or replace the acknowledgement for `advisory`
+    pub fn acknowledge(
+        &mut self,
+        advisory: &str,
+        reason: &str,
+        until: Option<NaiveDate>,
+        now: DateTime<Utc>,
+    ) -> Result<&Acknowledgement> {
+        let advisory = normalize_advisory(advisory);
+        if advisory.is_empty() {
+            anyhow::bail!("Advisory id must not be empty");
+        }
+        if reason.trim().is_empty() {
+            anyhow::bail!("An acknowledgement needs a reason");
+        }
+        if let Some(until) = until.filter(|until| *until < now.date_naive()) {
+            anyhow::bail!("Expiry date {} is already in the past", until);
+        }
+
+        let acknowledgement = Acknowledgement {
+            advisory: advisory.clone(),
+            reason: reason.trim().to_string(),
+            until,
+            acknowledged_at: now,
+        };
+        self.entries.insert(advisory.clone(), acknowledgement);
+        Ok(&self.entries[&advisory])
+    }
+
+    pub fn remove(&mut self, advisory: &str) -> Option<Acknowledgement> {
+        self.entries.remove(&normalize_advisory(advisory))
+    }
+
+    /// All acknowledgements, including expired ones
+    pub fn iter(&self) -> impl Iterator<Item = &Acknowledgement> {
+        self.entries.values()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The acknowledgement for `advisory`, if one exists and hasn't expired
+    pub fn active(&self, advisory: &str, now: DateTime<Utc>) -> Option<&Acknowledgement> {
+        self.entries
+            .get(&normalize_advisory(advisory))
+            .filter(|ack| ack.is_active_at(now))
+    }
+
+    /// Split scan findings into those that still count and acknowledged
+    /// advisories. A package stays in the first list with whatever advisories
+    /// are not acknowledged.
+    pub fn triage(
+        &self,
+        findings: Vec<SecurityFinding>,
+        now: DateTime<Utc>,
+    ) -> (Vec<SecurityFinding>, Vec<AcknowledgedFinding>) {
+        let mut open = Vec::new();
+        let mut acknowledged = Vec::new();
+
+        for mut finding in findings {
+            let advisories = std::mem::take(&mut finding.advisories);
+            let had_advisories = !advisories.is_empty();
+            for advisory in advisories {
+                match self.active(&advisory, now) {
+                    Some(ack) => acknowledged.push(AcknowledgedFinding {
+                        package: finding.package.clone(),
+                        severity: finding.severity,
+                        advisory,
+                        reason: ack.reason.clone(),
+                        until: ack.until,
+                    }),
+                    None => finding.advisories.push(advisory),
+                }
+            }
+            if !had_advisories || !finding.advisories.is_empty() {
+                open.push(finding);
+            }
+        }
+
+        (open, acknowledged)
+    }
+}
+
+/// Advisory ids are matched case-insensitively, e.g. `cve-2024-0001`
+pub fn normalize_advisory(advisory: &str) -> String {
+    advisory.trim().to_uppercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(timestamp: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(timestamp)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn date(day: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(day, "%Y-%m-%d").unwrap()
+    }
+
+    fn finding(package: &str, severity: &str, advisories: &[&str]) -> SecurityFinding {
+        SecurityFinding {
+            package: package.to_string(),
+            severity: severity.parse().unwrap(),
+            advisories: advisories.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_acknowledgement_expires_after_until_day() {
+        let mut acks = Acknowledgements::default();
+        let now = at("2025-05-01T12:00:00Z");
+        acks.acknowledge(
+            "cve-2024-0001",
+            "not exposed",
+            Some(date("2025-06-01")),
+            now,
+        )
+        .unwrap();
+
+        assert!(acks.active("CVE-2024-0001", now).is_some());
+        assert!(
+            acks.active("CVE-2024-0001", at("2025-06-01T23:59:59Z"))
+                .is_some()
+        );
+        assert!(
+            acks.active("CVE-2024-0001", at("2025-06-02T00:00:00Z"))
+                .is_none()
+        );
+        // Expired entries are kept so `vuln list` can show them
+        assert_eq!(acks.iter().count(), 1);
+    }
+
+    #[test]
+    fn test_acknowledge_validation() {
+        let mut acks = Acknowledgements::default();
+        let now = at("2025-05-01T12:00:00Z");
+        assert!(acks.acknowledge("CVE-2024-0001", "  ", None, now).is_err());
+        assert!(acks.acknowledge(" ", "reason", None, now).is_err());
+        assert!(
+            acks.acknowledge("CVE-2024-0001", "reason", Some(date("2025-04-30")), now)
+                .is_err()
+        );
+        assert!(
+            acks.acknowledge("CVE-2024-0001", "reason", None, now)
+                .is_ok()
+        );
+        assert!(acks.remove("cve-2024-0001").is_some());
+        assert!(acks.is_empty());
+    }
+
+    #[test]
+    fn test_triage_splits_acknowledged_advisories() {
+        let mut acks = Acknowledgements::default();
+        let now = at("2025-05-01T12:00:00Z");
+        acks.acknowledge("CVE-2024-0001", "mitigated", None, now)
+            .unwrap();
+        acks.acknowledge("CVE-2024-0003", "not exposed", None, now)
+            .unwrap();
+
+        let findings = vec![
+            finding("openssl", "High", &["CVE-2024-0001", "CVE-2024-0002"]),
+            finding("curl", "Medium", &["CVE-2024-0003"]),
+            finding("zlib", "Low", &[]),
+        ];
+        let (open, acknowledged) = acks.triage(findings, now);
+
+        assert_eq!(open.len(), 2);
+        assert_eq!(open[0].package, "openssl");
+        assert_eq!(open[0].advisories, vec!["CVE-2024-0002"]);
+        assert_eq!(open[1].package, "zlib");
+
+        assert_eq!(acknowledged.len(), 2);
+        assert_eq!(acknowledged[0].advisory, "CVE-2024-0001");
+        assert_eq!(acknowledged[1].package, "curl");
+        assert_eq!(acknowledged[1].reason, "not exposed");
+    }
+
+    #[test]
+    fn test_expired_acknowledgement_resurfaces_in_triage() {
+        let mut acks = Acknowledgements::default();
+        acks.acknowledge(
+            "CVE-2024-0003",
+            "waiting on upstream",
+            Some(date("2025-06-01")),
+            at("2025-05-01T12:00:00Z"),
+        )
+        .unwrap();
+        let findings = || vec![finding("curl", "Medium", &["CVE-2024-0003"])];
+
+        let (open, acknowledged) = acks.triage(findings(), at("2025-05-15T00:00:00Z"));
+        assert!(open.is_empty());
+        assert_eq!(acknowledged.len(), 1);
+
+        let (open, acknowledged) = acks.triage(findings(), at("2025-06-02T08:00:00Z"));
+        assert_eq!(open.len(), 1);
+        assert!(acknowledged.is_empty());
+    }
+}
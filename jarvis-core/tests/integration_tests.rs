@@ -207,9 +207,9 @@ async fn test_mcp_system_status_tool() {
     // Note: This test requires the Tool trait from glyph
     // It's a compile-time check that the tool implements the interface correctly
 
-    let tool = SystemStatusTool;
+    let tool = SystemStatusTool::new(vec!["package_management".to_string()]);
     assert_eq!(tool.name(), "jarvis_system_status");
-    assert!(tool.description().is_some());
+    assert!(tool.description().unwrap().ends_with("Agent capabilities: package_management"));
 
     println!("✅ SystemStatusTool interface test passed");
 }
@@ -312,6 +312,49 @@ async fn integration_test_docker_diagnostics() {
     println!("   Running containers: {}", containers.lines().count());
 }
 
+#[tokio::test]
+#[ignore] // Requires an Arch host with pacman
+async fn integration_test_package_cache_matches_pacman() {
+    use jarvis_core::package_cache::PackageCache;
+    use jarvis_core::{CommandRunner, SystemRunner};
+
+    let index = PackageCache::new()
+        .index()
+        .await
+        .expect("Failed to load the local package database");
+    let pacman = |args: &'static [&'static str]| async move {
+        let output = SystemRunner::default()
+            .output("pacman", args)
+            .await
+            .expect("pacman failed");
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+
+    let versions: std::collections::BTreeMap<String, String> = pacman(&["-Q"])
+        .await
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .map(|(name, version)| (name.to_string(), version.to_string()))
+        .collect();
+    assert_eq!(index.versions(), versions);
+
+    let foreign: Vec<String> = pacman(&["-Qmq"]).await.lines().map(str::to_string).collect();
+    let cached: Vec<String> = index.foreign().iter().map(|p| p.name.clone()).collect();
+    assert_eq!(cached, foreign);
+
+    let explicit = pacman(&["-Qeq"]).await;
+    for name in explicit.lines() {
+        assert!(index.get(name).unwrap().explicit, "{} is explicit", name);
+    }
+    assert_eq!(
+        index.iter().filter(|p| p.explicit).count(),
+        explicit.lines().count()
+    );
+
+    println!("✅ Package cache matches pacman");
+    println!("   Packages: {} ({} foreign)", index.len(), cached.len());
+}
+
 // Performance tests
 
 #[tokio::test]
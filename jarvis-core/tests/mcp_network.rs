@@ -0,0 +1,203 @@
+//! MCP over TCP, end to end
+//!
+//! A minimal client speaks newline-delimited JSON-RPC to a server on an
+//! ephemeral port, the way an editor on another machine would.
+
+use glyph::server::Tool;
+use jarvis_core::MemoryStore;
+use jarvis_core::config::RateLimitConfig;
+use jarvis_core::mcp::{GuardedTool, NetworkServer, RateLimiter, SystemStatusTool, ToolGuard};
+use serde_json::{Value, json};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::TcpStream;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+
+const TOKEN: &str = "test-token";
+
+struct Client {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    writer: OwnedWriteHalf,
+    next_id: u64,
+}
+
+impl Client {
+    async fn connect(addr: SocketAddr) -> Self {
+        let (reader, writer) = TcpStream::connect(addr).await.unwrap().into_split();
+        Self {
+            lines: BufReader::new(reader).lines(),
+            writer,
+            next_id: 1,
+        }
+    }
+
+    async fn send(&mut self, message: Value) {
+        let mut line = message.to_string();
+        line.push('\n');
+        self.writer.write_all(line.as_bytes()).await.unwrap();
+    }
+
+    /// The next message from the server, or `None` once it closed the connection
+    async fn receive(&mut self) -> Option<Value> {
+        let line = tokio::time::timeout(Duration::from_secs(10), self.lines.next_line())
+            .await
+            .expect("server did not answer")
+            .unwrap()?;
+        Some(serde_json::from_str(&line).unwrap())
+    }
+
+    async fn request(&mut self, method: &str, params: Value) -> Value {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))
+            .await;
+        let response = self.receive().await.expect("connection closed");
+        assert_eq!(response["id"], id);
+        response
+    }
+
+    async fn initialize(&mut self, name: &str, token: &str) -> Value {
+        let response = self
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": "2024-11-05",
+                    "capabilities": {},
+                    "clientInfo": { "name": name, "version": "0.1" },
+                    "auth_token": token,
+                }),
+            )
+            .await;
+        self.send(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }))
+            .await;
+        response
+    }
+}
+
+/// Serve `jarvis_system_status` on an ephemeral port, auditing into `memory`
+async fn serve(memory: Option<MemoryStore>, idle: Duration, max_connections: usize) -> SocketAddr {
+    let limiter = Arc::new(RateLimiter::new(RateLimitConfig::default()));
+    let guard = ToolGuard::new(limiter, memory, "tcp");
+    let tools: Vec<Arc<dyn Tool + Send + Sync>> = vec![Arc::new(GuardedTool::new(
+        SystemStatusTool::new(Vec::new()),
+        guard,
+    ))];
+    let server = NetworkServer::new(tools, Vec::new())
+        .with_token(Some(TOKEN.to_string()))
+        .with_idle_timeout(idle)
+        .with_max_connections(max_connections);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(Arc::new(server).serve_tcp(listener, None));
+    addr
+}
+
+#[tokio::test]
+async fn test_system_status_over_tcp() {
+    let dir = tempfile::tempdir().unwrap();
+    let memory = MemoryStore::new(dir.path().join("jarvis.db").to_str().unwrap())
+        .await
+        .unwrap();
+    let addr = serve(Some(memory.clone()), Duration::from_secs(60), 4).await;
+
+    let mut client = Client::connect(addr).await;
+    let init = client.initialize("test-editor", TOKEN).await;
+    assert_eq!(init["result"]["serverInfo"]["name"], "jarvis");
+    assert_eq!(init["result"]["protocolVersion"], "2024-11-05");
+
+    let tools = client.request("tools/list", json!({})).await;
+    let names: Vec<&str> = tools["result"]["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|tool| tool["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["jarvis_system_status"]);
+
+    // A client can't pick the identity it is audited under
+    let status = client
+        .request(
+            "tools/call",
+            json!({
+                "name": "jarvis_system_status",
+                "arguments": { "verbose": true, "_caller": "stdio" },
+            }),
+        )
+        .await;
+    let text = status["result"]["content"][0]["text"].as_str().unwrap();
+    assert!(text.contains("Jarvis System Status"), "{}", text);
+
+    let unknown = client
+        .request("tools/call", json!({ "name": "nope" }))
+        .await;
+    assert_eq!(unknown["error"]["code"], -32602);
+    let ping = client.request("ping", json!({})).await;
+    assert_eq!(ping["result"], json!({}));
+
+    // Closing the write half ends the session cleanly
+    client.writer.shutdown().await.unwrap();
+    assert!(client.receive().await.is_none());
+
+    let entries = memory.recent_audit_entries(10).await.unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].tool, "jarvis_system_status");
+    assert_eq!(
+        entries[0].caller.as_deref(),
+        Some("tcp:test-editor@127.0.0.1")
+    );
+}
+
+#[tokio::test]
+async fn test_wrong_token_closes_connection() {
+    let addr = serve(None, Duration::from_secs(60), 4).await;
+
+    let mut client = Client::connect(addr).await;
+    let init = client
+        .request("initialize", json!({ "auth_token": "guess" }))
+        .await;
+    assert_eq!(init["error"]["code"], -32001);
+    assert!(client.receive().await.is_none());
+
+    // Skipping initialize is refused the same way
+    let mut client = Client::connect(addr).await;
+    let list = client.request("tools/list", json!({})).await;
+    assert_eq!(list["error"]["code"], -32001);
+    assert!(client.receive().await.is_none());
+}
+
+#[tokio::test]
+async fn test_concurrent_clients_limit_and_idle_timeout() {
+    let addr = serve(None, Duration::from_secs(1), 2).await;
+
+    let mut first = Client::connect(addr).await;
+    let mut second = Client::connect(addr).await;
+    first.initialize("first", TOKEN).await;
+    second.initialize("second", TOKEN).await;
+
+    // Both slots are taken
+    let mut third = Client::connect(addr).await;
+    let busy = third.receive().await.unwrap();
+    assert_eq!(busy["error"]["code"], -32002);
+    assert!(third.receive().await.is_none());
+
+    first.request("ping", json!({})).await;
+    let status = second
+        .request("tools/call", json!({ "name": "jarvis_system_status" }))
+        .await;
+    assert!(status["result"]["content"].is_array());
+
+    // Keep the first one talking past the idle timeout; the second goes quiet
+    for _ in 0..5 {
+        first.request("ping", json!({})).await;
+        tokio::time::sleep(Duration::from_millis(300)).await;
+    }
+    assert!(second.receive().await.is_none());
+
+    // Its slot is free again
+    let mut fourth = Client::connect(addr).await;
+    let init = fourth.initialize("fourth", TOKEN).await;
+    assert!(init["result"].is_object());
+}
@@ -0,0 +1,50 @@
+//! Read-only mode refuses mutating commands before anything spawns
+//!
+//! The mode is process-wide, so this lives in its own test binary rather
+//! than among the library's parallel unit tests.
+
+use jarvis_core::{CommandExecutor, CommandRunner, JarvisError, SystemRunner, read_only};
+
+#[tokio::test]
+async fn test_read_only_mode_refuses_before_spawning() {
+    read_only::set_read_only(true);
+    let runner = SystemRunner::default();
+
+    // A program that doesn't exist would fail to spawn with NotFound; the
+    // refusal comes first
+    let err = runner
+        .output("/nonexistent/pacman", &["-S", "--noconfirm", "ripgrep"])
+        .await
+        .unwrap_err();
+    assert_eq!(JarvisError::from_anyhow(&err).code(), "read_only_mode");
+
+    let err = runner
+        .output("/nonexistent/systemctl", &["restart", "sshd"])
+        .await
+        .unwrap_err();
+    assert_eq!(JarvisError::from_anyhow(&err).code(), "read_only_mode");
+
+    // Inspection still runs
+    let err = runner
+        .output("/nonexistent/pacman", &["-Qu"])
+        .await
+        .unwrap_err();
+    assert_eq!(JarvisError::from_anyhow(&err).code(), "not_found");
+
+    let err = CommandExecutor::Local
+        .ensure_mutation_allowed("apply fixes")
+        .unwrap_err();
+    assert_eq!(JarvisError::from_anyhow(&err).code(), "read_only_mode");
+
+    read_only::set_read_only(false);
+    let err = runner
+        .output("/nonexistent/pacman", &["-Syu"])
+        .await
+        .unwrap_err();
+    assert_eq!(JarvisError::from_anyhow(&err).code(), "not_found");
+    assert!(
+        CommandExecutor::Local
+            .ensure_mutation_allowed("apply fixes")
+            .is_ok()
+    );
+}
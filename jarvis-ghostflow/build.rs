@@ -0,0 +1,31 @@
+//! Builds the libzeke stand-in for the `zeke-mock` feature's tests
+
+fn main() {
+    #[cfg(feature = "zeke-mock")]
+    build_zeke_mock();
+}
+
+/// Compile tests/zeke_mock.c into a shared library the tests load at
+/// runtime, the way the real libzeke is loaded, and pass its path on as
+/// ZEKE_MOCK_LIB
+#[cfg(feature = "zeke-mock")]
+fn build_zeke_mock() {
+    use std::path::PathBuf;
+
+    let source = "tests/zeke_mock.c";
+    println!("cargo:rerun-if-changed={}", source);
+
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR").expect("OUT_DIR is set by cargo"));
+    let library = out_dir.join("libzeke_mock.so");
+    let status = cc::Build::new()
+        .get_compiler()
+        .to_command()
+        .args(["-shared", "-fPIC", "-o"])
+        .arg(&library)
+        .arg(source)
+        .status()
+        .expect("failed to run the C compiler");
+    assert!(status.success(), "compiling {} failed", source);
+
+    println!("cargo:rustc-env=ZEKE_MOCK_LIB={}", library.display());
+}
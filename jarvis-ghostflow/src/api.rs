@@ -1,18 +1,23 @@
 use anyhow::Result;
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
-    response::Json,
+    http::{header, StatusCode},
+    middleware,
+    response::{IntoResponse, Json},
     routing::{delete, get, post, put},
-    Router,
+    Extension, Router,
 };
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::Arc;
 use tower_http::cors::CorsLayer;
 use tracing::{info, warn};
 use uuid::Uuid;
 
+use crate::auth::{self, ApiKeyRecord, ApiKeyStore, AuthenticatedKey, IssuedApiKey, Scope};
+use crate::budget::{BudgetLimit, BudgetReport, BudgetScope, BudgetStatus, Budgets};
+use crate::namespace::{self, QuotaExceeded};
+use crate::templates::{self, WorkflowTemplate};
 use crate::workflow_engine::{
     WorkflowEngine, Workflow, ExecutionMode, ExecutionResult, WorkflowMetrics
 };
@@ -21,6 +26,7 @@ use crate::workflow_engine::{
 #[derive(Clone)]
 pub struct ApiState {
     pub workflow_engine: Arc<WorkflowEngine>,
+    pub api_keys: Arc<ApiKeyStore>,
 }
 
 /// API error response
@@ -35,6 +41,22 @@ pub struct SuccessResponse<T> {
     pub data: T,
 }
 
+/// Error response whose status follows the error's JarvisError category,
+/// so a missing record is a 404 and a timed-out backend a 504; a namespace
+/// at its quota gets a 429
+fn api_error(context: &str, err: anyhow::Error) -> (StatusCode, Json<ErrorResponse>) {
+    let status = if QuotaExceeded::find(&err).is_some() {
+        StatusCode::TOO_MANY_REQUESTS
+    } else {
+        let category = jarvis_core::JarvisError::from_anyhow(&err);
+        StatusCode::from_u16(category.http_status())
+            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    };
+    (status, Json(ErrorResponse {
+        error: format!("{}: {}", context, err),
+    }))
+}
+
 /// Workflow creation request
 #[derive(Deserialize)]
 pub struct CreateWorkflowRequest {
@@ -53,9 +75,34 @@ pub struct ExecuteWorkflowRequest {
     pub execution_mode: Option<String>,
 }
 
+/// Template instantiation request; text values are read as the parameter's type
+#[derive(Deserialize)]
+pub struct InstantiateTemplateRequest {
+    #[serde(default)]
+    pub parameters: BTreeMap<String, serde_json::Value>,
+}
+
+/// API key creation request
+#[derive(Deserialize)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    /// Namespace the key is bound to
+    #[serde(default = "namespace::default_namespace")]
+    pub namespace: String,
+}
+
+/// `?namespace=` query parameter; keys other than admin keys may only name
+/// their own namespace
+#[derive(Deserialize)]
+pub struct NamespaceQuery {
+    pub namespace: Option<String>,
+}
+
 /// Workflow list query parameters
 #[derive(Deserialize)]
 pub struct WorkflowListQuery {
+    pub namespace: Option<String>,
     pub tag: Option<String>,
     pub folder: Option<String>,
     pub limit: Option<u32>,
@@ -71,10 +118,27 @@ pub fn create_router(state: ApiState) -> Router {
         .route("/api/workflows/:id", get(get_workflow))
         .route("/api/workflows/:id", put(update_workflow))
         .route("/api/workflows/:id", delete(delete_workflow))
+        .route("/api/workflows/:id/export", get(export_workflow))
+        .route("/api/workflows/import", post(import_workflow))
+
+        // Workflow templates
+        .route("/api/templates", get(list_templates))
+        .route("/api/templates/:name", get(get_template))
+        .route("/api/templates/:name/instantiate", post(instantiate_template))
         
         // Workflow execution endpoints
         .route("/api/workflows/:id/execute", post(execute_workflow))
+        .route("/api/workflows/:id/webhook", post(webhook_trigger))
+        .route("/api/executions", get(list_executions))
         .route("/api/executions/:id", get(get_execution))
+        .route("/api/executions/:id", delete(delete_execution))
+        .route("/api/executions/:id/resume", post(resume_execution))
+        
+        // LLM budgets
+        .route("/api/budgets", get(get_budgets))
+        .route("/api/budgets/global", put(set_global_budget))
+        .route("/api/budgets/workflows/:id", put(set_workflow_budget))
+        .route("/api/budgets/namespaces/:namespace", put(set_namespace_budget))
         
         // Node management endpoints
         .route("/api/node-types", get(list_node_types))
@@ -84,18 +148,96 @@ pub fn create_router(state: ApiState) -> Router {
         .route("/api/metrics", get(get_metrics))
         .route("/api/health", get(health_check))
         
+        // API key management
+        .route("/api/keys", post(create_api_key))
+        .route("/api/keys", get(list_api_keys))
+        .route("/api/keys/:id", delete(revoke_api_key))
+        
         // WebSocket endpoint for real-time updates
         .route("/ws", get(websocket_handler))
         
+        .layer(middleware::from_fn_with_state(state.clone(), auth::require_api_key))
         .layer(CorsLayer::permissive())
         .with_state(state)
 }
 
-/// Create new workflow
+fn is_admin(key: &ApiKeyRecord) -> bool {
+    key.scopes.contains(&Scope::Admin)
+}
+
+/// Namespace a request acts in: the key's own, or the one an admin key asked
+/// for with `?namespace=`
+fn target_namespace(
+    key: &ApiKeyRecord,
+    requested: Option<String>,
+) -> Result<String, (StatusCode, Json<ErrorResponse>)> {
+    match requested {
+        Some(requested) if !key.can_access(&requested) => {
+            Err((StatusCode::FORBIDDEN, Json(ErrorResponse {
+                error: format!(
+                    "API key '{}' is bound to namespace '{}'",
+                    key.name, key.namespace
+                ),
+            })))
+        }
+        Some(requested) => Ok(requested),
+        None => Ok(key.namespace.clone()),
+    }
+}
+
+/// Namespace to filter a listing by; admin keys see every namespace unless
+/// they ask for one
+fn namespace_filter(
+    key: &ApiKeyRecord,
+    requested: Option<String>,
+) -> Result<Option<String>, (StatusCode, Json<ErrorResponse>)> {
+    if is_admin(key) {
+        Ok(requested)
+    } else {
+        target_namespace(key, requested).map(Some)
+    }
+}
+
+/// A workflow the key may see; workflows of other namespaces are reported
+/// as missing so their ids don't leak
+async fn visible_workflow(
+    state: &ApiState,
+    key: &ApiKeyRecord,
+    workflow_id: Uuid,
+) -> Result<Workflow, (StatusCode, Json<ErrorResponse>)> {
+    state.workflow_engine.get_workflow(workflow_id).await
+        .map_err(|e| api_error("Failed to get workflow", e))?
+        .filter(|workflow| key.can_access(&workflow.namespace))
+        .ok_or_else(|| {
+            (StatusCode::NOT_FOUND, Json(ErrorResponse {
+                error: "Workflow not found".to_string(),
+            }))
+        })
+}
+
+/// An execution the key may see, like [`visible_workflow`]
+async fn visible_execution(
+    state: &ApiState,
+    key: &ApiKeyRecord,
+    execution_id: Uuid,
+) -> Result<ExecutionResult, (StatusCode, Json<ErrorResponse>)> {
+    state.workflow_engine.get_execution(execution_id).await
+        .filter(|execution| key.can_access(&execution.namespace))
+        .ok_or_else(|| {
+            (StatusCode::NOT_FOUND, Json(ErrorResponse {
+                error: "Execution not found".to_string(),
+            }))
+        })
+}
+
+/// Create new workflow in the key's namespace or the one asked for
 async fn create_workflow(
     State(state): State<ApiState>,
+    Extension(AuthenticatedKey(key)): Extension<AuthenticatedKey>,
+    Query(query): Query<NamespaceQuery>,
     Json(request): Json<CreateWorkflowRequest>,
 ) -> Result<Json<SuccessResponse<Workflow>>, (StatusCode, Json<ErrorResponse>)> {
+    let namespace = target_namespace(&key, query.namespace)?;
     let workflow = Workflow {
         id: Uuid::new_v4(),
         name: request.name,
@@ -130,21 +272,23 @@ async fn create_workflow(
         metadata: crate::workflow_engine::WorkflowMetadata {
             created_at: chrono::Utc::now(),
             updated_at: chrono::Utc::now(),
-            created_by: "api".to_string(), // TODO: Get from auth
+            created_by: key.name.clone(),
             tags: request.tags.unwrap_or_default(),
             folder: None,
+            template: None,
         },
         state: crate::workflow_engine::WorkflowState::Active,
+        namespace,
     };
 
     let workflow_id = state.workflow_engine.create_workflow(workflow.clone()).await
         .map_err(|e| {
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse {
+            (StatusCode::BAD_REQUEST, Json(ErrorResponse {
                 error: format!("Failed to create workflow: {}", e),
             }))
         })?;
 
-    info!("Created workflow via API: {}", workflow_id);
+    info!("Created workflow via API: {} in namespace {}", workflow_id, workflow.namespace);
     
     Ok(Json(SuccessResponse {
         data: workflow,
@@ -154,16 +298,18 @@ async fn create_workflow(
 /// List workflows
 async fn list_workflows(
     State(state): State<ApiState>,
+    Extension(AuthenticatedKey(key)): Extension<AuthenticatedKey>,
     Query(query): Query<WorkflowListQuery>,
 ) -> Result<Json<SuccessResponse<Vec<Workflow>>>, (StatusCode, Json<ErrorResponse>)> {
+    let namespace = namespace_filter(&key, query.namespace)?;
     let mut workflows = state.workflow_engine.list_workflows().await
-        .map_err(|e| {
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse {
-                error: format!("Failed to list workflows: {}", e),
-            }))
-        })?;
+        .map_err(|e| api_error("Failed to list workflows", e))?;
 
     // Apply filters
+    if let Some(namespace) = &namespace {
+        workflows.retain(|w| &w.namespace == namespace);
+    }
+
     if let Some(tag) = &query.tag {
         workflows.retain(|w| w.metadata.tags.contains(tag));
     }
@@ -197,19 +343,10 @@ async fn list_workflows(
 /// Get workflow by ID
 async fn get_workflow(
     State(state): State<ApiState>,
+    Extension(AuthenticatedKey(key)): Extension<AuthenticatedKey>,
     Path(workflow_id): Path<Uuid>,
 ) -> Result<Json<SuccessResponse<Workflow>>, (StatusCode, Json<ErrorResponse>)> {
-    let workflow = state.workflow_engine.get_workflow(workflow_id).await
-        .map_err(|e| {
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse {
-                error: format!("Failed to get workflow: {}", e),
-            }))
-        })?
-        .ok_or_else(|| {
-            (StatusCode::NOT_FOUND, Json(ErrorResponse {
-                error: "Workflow not found".to_string(),
-            }))
-        })?;
+    let workflow = visible_workflow(&state, &key, workflow_id).await?;
 
     Ok(Json(SuccessResponse {
         data: workflow,
@@ -219,21 +356,12 @@ async fn get_workflow(
 /// Update workflow
 async fn update_workflow(
     State(state): State<ApiState>,
+    Extension(AuthenticatedKey(key)): Extension<AuthenticatedKey>,
     Path(workflow_id): Path<Uuid>,
     Json(request): Json<CreateWorkflowRequest>,
 ) -> Result<Json<SuccessResponse<Workflow>>, (StatusCode, Json<ErrorResponse>)> {
     // Get existing workflow
-    let mut workflow = state.workflow_engine.get_workflow(workflow_id).await
-        .map_err(|e| {
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse {
-                error: format!("Failed to get workflow: {}", e),
-            }))
-        })?
-        .ok_or_else(|| {
-            (StatusCode::NOT_FOUND, Json(ErrorResponse {
-                error: "Workflow not found".to_string(),
-            }))
-        })?;
+    let mut workflow = visible_workflow(&state, &key, workflow_id).await?;
 
     // Update workflow fields
     workflow.name = request.name;
@@ -261,11 +389,7 @@ async fn update_workflow(
     }
 
     state.workflow_engine.update_workflow(workflow.clone()).await
-        .map_err(|e| {
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse {
-                error: format!("Failed to update workflow: {}", e),
-            }))
-        })?;
+        .map_err(|e| api_error("Failed to update workflow", e))?;
 
     info!("Updated workflow via API: {}", workflow_id);
 
@@ -277,34 +401,143 @@ async fn update_workflow(
 /// Delete workflow
 async fn delete_workflow(
     State(state): State<ApiState>,
+    Extension(AuthenticatedKey(key)): Extension<AuthenticatedKey>,
     Path(workflow_id): Path<Uuid>,
 ) -> Result<Json<SuccessResponse<()>>, (StatusCode, Json<ErrorResponse>)> {
+    visible_workflow(&state, &key, workflow_id).await?;
     state.workflow_engine.delete_workflow(workflow_id).await
+        .map_err(|e| api_error("Failed to delete workflow", e))?;
+
+    info!("Deleted workflow via API: {}", workflow_id);
+
+    Ok(Json(SuccessResponse {
+        data: (),
+    }))
+}
+
+/// Export workflow as portable YAML
+async fn export_workflow(
+    State(state): State<ApiState>,
+    Extension(AuthenticatedKey(key)): Extension<AuthenticatedKey>,
+    Path(workflow_id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    visible_workflow(&state, &key, workflow_id).await?;
+    let yaml = state.workflow_engine.export_yaml(workflow_id).await
         .map_err(|e| {
+            (StatusCode::NOT_FOUND, Json(ErrorResponse {
+                error: format!("Failed to export workflow: {}", e),
+            }))
+        })?;
+
+    Ok(([(header::CONTENT_TYPE, "application/yaml")], yaml))
+}
+
+/// Import workflow from portable YAML (request body) into the key's
+/// namespace or the one asked for
+async fn import_workflow(
+    State(state): State<ApiState>,
+    Extension(AuthenticatedKey(key)): Extension<AuthenticatedKey>,
+    Query(query): Query<NamespaceQuery>,
+    body: String,
+) -> Result<Json<SuccessResponse<Workflow>>, (StatusCode, Json<ErrorResponse>)> {
+    let namespace = target_namespace(&key, query.namespace)?;
+    let workflow_id = state.workflow_engine.import_yaml_into(&body, &namespace).await
+        .map_err(|e| {
+            (StatusCode::BAD_REQUEST, Json(ErrorResponse {
+                error: format!("Invalid workflow: {:#}", e),
+            }))
+        })?;
+
+    let workflow = state.workflow_engine.get_workflow(workflow_id).await
+        .ok()
+        .flatten()
+        .ok_or_else(|| {
             (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse {
-                error: format!("Failed to delete workflow: {}", e),
+                error: "Imported workflow disappeared".to_string(),
             }))
         })?;
 
-    info!("Deleted workflow via API: {}", workflow_id);
+    info!("Imported workflow via API: {}", workflow_id);
 
     Ok(Json(SuccessResponse {
-        data: (),
+        data: workflow,
+    }))
+}
+
+/// List the built-in workflow templates
+async fn list_templates() -> Result<Json<SuccessResponse<Vec<WorkflowTemplate>>>, (StatusCode, Json<ErrorResponse>)> {
+    let templates = templates::builtin_templates()
+        .map_err(|e| api_error("Failed to load templates", e))?;
+
+    Ok(Json(SuccessResponse {
+        data: templates,
+    }))
+}
+
+/// Get a workflow template by name
+async fn get_template(
+    Path(name): Path<String>,
+) -> Result<Json<SuccessResponse<WorkflowTemplate>>, (StatusCode, Json<ErrorResponse>)> {
+    let template = templates::find_template(&name)
+        .map_err(|e| api_error("Failed to get template", e))?;
+
+    Ok(Json(SuccessResponse {
+        data: template,
+    }))
+}
+
+/// Render a template with the given parameters and create the workflow in
+/// the key's namespace or the one asked for
+async fn instantiate_template(
+    State(state): State<ApiState>,
+    Extension(AuthenticatedKey(key)): Extension<AuthenticatedKey>,
+    Path(name): Path<String>,
+    Query(query): Query<NamespaceQuery>,
+    Json(request): Json<InstantiateTemplateRequest>,
+) -> Result<Json<SuccessResponse<Workflow>>, (StatusCode, Json<ErrorResponse>)> {
+    let namespace = target_namespace(&key, query.namespace)?;
+    templates::find_template(&name)
+        .map_err(|e| api_error("Failed to get template", e))?;
+
+    let workflow_id = state.workflow_engine
+        .instantiate_template_into(&name, &request.parameters, &namespace).await
+        .map_err(|e| {
+            (StatusCode::BAD_REQUEST, Json(ErrorResponse {
+                error: format!("Invalid template parameters: {:#}", e),
+            }))
+        })?;
+
+    let workflow = state.workflow_engine.get_workflow(workflow_id).await
+        .ok()
+        .flatten()
+        .ok_or_else(|| {
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse {
+                error: "Instantiated workflow disappeared".to_string(),
+            }))
+        })?;
+
+    info!("Instantiated template {} as workflow {}", name, workflow_id);
+
+    Ok(Json(SuccessResponse {
+        data: workflow,
     }))
 }
 
 /// Execute workflow
 async fn execute_workflow(
     State(state): State<ApiState>,
+    Extension(AuthenticatedKey(key)): Extension<AuthenticatedKey>,
     Path(workflow_id): Path<Uuid>,
     Json(request): Json<ExecuteWorkflowRequest>,
 ) -> Result<Json<SuccessResponse<ExecutionResult>>, (StatusCode, Json<ErrorResponse>)> {
+    visible_workflow(&state, &key, workflow_id).await?;
     let execution_mode = match request.execution_mode.as_deref() {
         Some("manual") => ExecutionMode::Manual,
         Some("trigger") => ExecutionMode::Trigger,
         Some("webhook") => ExecutionMode::Webhook,
         Some("scheduled") => ExecutionMode::Scheduled,
         Some("integration") => ExecutionMode::Integration,
+        Some("parallel") => ExecutionMode::Parallel,
         _ => ExecutionMode::Manual,
     };
 
@@ -315,11 +548,7 @@ async fn execute_workflow(
         trigger_data,
         execution_mode,
     ).await
-    .map_err(|e| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse {
-            error: format!("Failed to execute workflow: {}", e),
-        }))
-    })?;
+    .map_err(|e| api_error("Failed to execute workflow", e))?;
 
     info!("Executed workflow via API: {} -> {}", workflow_id, result.execution_id);
 
@@ -328,15 +557,237 @@ async fn execute_workflow(
     }))
 }
 
+/// Trigger a workflow from an external webhook; the request body becomes the trigger data
+async fn webhook_trigger(
+    State(state): State<ApiState>,
+    Extension(AuthenticatedKey(key)): Extension<AuthenticatedKey>,
+    Path(workflow_id): Path<Uuid>,
+    body: Option<Json<serde_json::Value>>,
+) -> Result<Json<SuccessResponse<ExecutionResult>>, (StatusCode, Json<ErrorResponse>)> {
+    visible_workflow(&state, &key, workflow_id).await?;
+    let trigger_data = body.map(|Json(body)| body).unwrap_or_else(|| serde_json::json!({}));
+
+    let result = state.workflow_engine.execute_workflow(
+        workflow_id,
+        trigger_data,
+        ExecutionMode::Webhook,
+    ).await
+    .map_err(|e| api_error("Failed to execute workflow", e))?;
+
+    info!("Executed workflow via webhook: {} -> {}", workflow_id, result.execution_id);
+
+    Ok(Json(SuccessResponse {
+        data: result,
+    }))
+}
+
+/// Create an API key; the token is only ever returned here
+async fn create_api_key(
+    State(state): State<ApiState>,
+    Json(request): Json<CreateApiKeyRequest>,
+) -> Result<(StatusCode, Json<SuccessResponse<IssuedApiKey>>), (StatusCode, Json<ErrorResponse>)> {
+    let issued = state.api_keys.create(&request.name, &request.scopes, &request.namespace).await
+        .map_err(|e| {
+            (StatusCode::BAD_REQUEST, Json(ErrorResponse {
+                error: format!("Failed to create API key: {}", e),
+            }))
+        })?;
+
+    info!("Created API key: {} ({}) in namespace {}", issued.key.name, issued.key.id, issued.key.namespace);
+
+    Ok((StatusCode::CREATED, Json(SuccessResponse {
+        data: issued,
+    })))
+}
+
+/// List API keys without their secrets, optionally of one namespace
+async fn list_api_keys(
+    State(state): State<ApiState>,
+    Query(query): Query<NamespaceQuery>,
+) -> Result<Json<SuccessResponse<Vec<ApiKeyRecord>>>, (StatusCode, Json<ErrorResponse>)> {
+    let mut keys = state.api_keys.list().await
+        .map_err(|e| {
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse {
+                error: format!("Failed to list API keys: {}", e),
+            }))
+        })?;
+    if let Some(namespace) = &query.namespace {
+        keys.retain(|key| &key.namespace == namespace);
+    }
+
+    Ok(Json(SuccessResponse {
+        data: keys,
+    }))
+}
+
+/// Revoke an API key
+async fn revoke_api_key(
+    State(state): State<ApiState>,
+    Path(key_id): Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    let revoked = state.api_keys.revoke(&key_id).await
+        .map_err(|e| {
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse {
+                error: format!("Failed to revoke API key: {}", e),
+            }))
+        })?;
+
+    if !revoked {
+        return Err((StatusCode::NOT_FOUND, Json(ErrorResponse {
+            error: format!("No active API key with id {}", key_id),
+        })));
+    }
+
+    info!("Revoked API key: {}", key_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// List finished and paused executions, newest first
+async fn list_executions(
+    State(state): State<ApiState>,
+    Extension(AuthenticatedKey(key)): Extension<AuthenticatedKey>,
+    Query(query): Query<NamespaceQuery>,
+) -> Result<Json<SuccessResponse<Vec<ExecutionResult>>>, (StatusCode, Json<ErrorResponse>)> {
+    let namespace = namespace_filter(&key, query.namespace)?;
+    let executions = state.workflow_engine.list_executions(namespace.as_deref()).await;
+
+    Ok(Json(SuccessResponse {
+        data: executions,
+    }))
+}
+
 /// Get execution result
 async fn get_execution(
-    _State(_state): State<ApiState>,
-    Path(_execution_id): Path<Uuid>,
+    State(state): State<ApiState>,
+    Extension(AuthenticatedKey(key)): Extension<AuthenticatedKey>,
+    Path(execution_id): Path<Uuid>,
 ) -> Result<Json<SuccessResponse<ExecutionResult>>, (StatusCode, Json<ErrorResponse>)> {
-    // TODO: Implement execution storage and retrieval
-    Err((StatusCode::NOT_IMPLEMENTED, Json(ErrorResponse {
-        error: "Execution history not implemented yet".to_string(),
-    })))
+    let execution = visible_execution(&state, &key, execution_id).await?;
+
+    Ok(Json(SuccessResponse {
+        data: execution,
+    }))
+}
+
+/// Delete a stored execution, freeing its namespace's stored executions quota
+async fn delete_execution(
+    State(state): State<ApiState>,
+    Extension(AuthenticatedKey(key)): Extension<AuthenticatedKey>,
+    Path(execution_id): Path<Uuid>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    visible_execution(&state, &key, execution_id).await?;
+    state.workflow_engine.delete_execution(execution_id).await
+        .map_err(|e| api_error("Failed to delete execution", e))?;
+
+    info!("Deleted execution via API: {}", execution_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Resume an execution paused on an LLM budget
+async fn resume_execution(
+    State(state): State<ApiState>,
+    Extension(AuthenticatedKey(key)): Extension<AuthenticatedKey>,
+    Path(execution_id): Path<Uuid>,
+) -> Result<Json<SuccessResponse<ExecutionResult>>, (StatusCode, Json<ErrorResponse>)> {
+    visible_execution(&state, &key, execution_id).await?;
+    let result = state.workflow_engine.resume_execution(execution_id).await
+        .map_err(|e| api_error("Failed to resume execution", e))?;
+
+    info!("Resumed execution via API: {}", execution_id);
+
+    Ok(Json(SuccessResponse {
+        data: result,
+    }))
+}
+
+fn budgets(state: &ApiState) -> Result<&Arc<Budgets>, (StatusCode, Json<ErrorResponse>)> {
+    state.workflow_engine.budgets().ok_or_else(|| {
+        (StatusCode::NOT_FOUND, Json(ErrorResponse {
+            error: "LLM budgets are not configured".to_string(),
+        }))
+    })
+}
+
+/// Every LLM budget with its usage, and the executions paused on one; keys
+/// other than admin keys only see their own namespace's
+async fn get_budgets(
+    State(state): State<ApiState>,
+    Extension(AuthenticatedKey(key)): Extension<AuthenticatedKey>,
+) -> Result<Json<SuccessResponse<BudgetReport>>, (StatusCode, Json<ErrorResponse>)> {
+    let mut report = budgets(&state)?.report();
+    report.paused_executions = state.workflow_engine.paused_executions().await;
+
+    if !is_admin(&key) {
+        let workflows: HashSet<Uuid> = state.workflow_engine.list_workflows().await
+            .map_err(|e| api_error("Failed to list workflows", e))?
+            .into_iter()
+            .filter(|workflow| workflow.namespace == key.namespace)
+            .map(|workflow| workflow.id)
+            .collect();
+        let executions: HashSet<Uuid> = state.workflow_engine
+            .list_executions(Some(&key.namespace)).await
+            .into_iter()
+            .map(|execution| execution.execution_id)
+            .collect();
+
+        report.workflows.retain(|status| {
+            matches!(&status.scope, BudgetScope::Workflow(id) if workflows.contains(id))
+        });
+        report.namespaces.retain(|status| {
+            matches!(&status.scope, BudgetScope::Namespace(namespace) if *namespace == key.namespace)
+        });
+        report.paused_executions.retain(|id| executions.contains(id));
+    }
+
+    Ok(Json(SuccessResponse {
+        data: report,
+    }))
+}
+
+/// Replace the global LLM budget
+async fn set_global_budget(
+    State(state): State<ApiState>,
+    Json(limit): Json<BudgetLimit>,
+) -> Result<Json<SuccessResponse<BudgetStatus>>, (StatusCode, Json<ErrorResponse>)> {
+    set_budget(&state, BudgetScope::Global, limit)
+}
+
+/// Replace a workflow's LLM budget
+async fn set_workflow_budget(
+    State(state): State<ApiState>,
+    Path(workflow_id): Path<Uuid>,
+    Json(limit): Json<BudgetLimit>,
+) -> Result<Json<SuccessResponse<BudgetStatus>>, (StatusCode, Json<ErrorResponse>)> {
+    set_budget(&state, BudgetScope::Workflow(workflow_id), limit)
+}
+
+/// Replace a namespace's LLM budget
+async fn set_namespace_budget(
+    State(state): State<ApiState>,
+    Path(namespace): Path<String>,
+    Json(limit): Json<BudgetLimit>,
+) -> Result<Json<SuccessResponse<BudgetStatus>>, (StatusCode, Json<ErrorResponse>)> {
+    namespace::validate_name(&namespace).map_err(|e| {
+        (StatusCode::BAD_REQUEST, Json(ErrorResponse {
+            error: e.to_string(),
+        }))
+    })?;
+    set_budget(&state, BudgetScope::Namespace(namespace), limit)
+}
+
+fn set_budget(
+    state: &ApiState,
+    scope: BudgetScope,
+    limit: BudgetLimit,
+) -> Result<Json<SuccessResponse<BudgetStatus>>, (StatusCode, Json<ErrorResponse>)> {
+    let status = budgets(state)?.set_limit(scope.clone(), limit)
+        .map_err(|e| api_error("Failed to save budget", e))?;
+
+    info!("{} set to {:?} via API", scope, limit);
+
+    Ok(Json(SuccessResponse {
+        data: status,
+    }))
 }
 
 /// List available node types
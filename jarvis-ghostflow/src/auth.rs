@@ -0,0 +1,618 @@
+//! API key authentication for the GhostFlow HTTP API
+//!
+//! Keys are issued as `gf_<id>_<secret>` tokens. Only the SHA-256 hash of the
+//! secret is persisted, so a key can be shown exactly once at creation time.
+//! Every request except the health check must carry the token as
+//! `Authorization: Bearer <token>` and hold the scope its route requires.
+//! Each key is bound to a namespace and only sees what belongs to it, except
+//! admin keys, which see every namespace.
+
+use axum::{
+    extract::{Request, State},
+    http::{header, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, TimeZone, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{Row, SqlitePool};
+use std::fmt;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::api::{ApiState, ErrorResponse};
+use crate::namespace;
+use crate::{GhostFlowError, Result};
+
+const TOKEN_PREFIX: &str = "gf_";
+
+/// Permission granted to an API key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    /// Read workflows, executions, node types and metrics
+    Read,
+    /// Execute workflows, trigger webhooks and delete executions
+    Execute,
+    /// Manage workflows and API keys in every namespace; implies every
+    /// other scope
+    Admin,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::Read => "read",
+            Scope::Execute => "execute",
+            Scope::Admin => "admin",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "read" => Some(Scope::Read),
+            "execute" => Some(Scope::Execute),
+            "admin" => Some(Scope::Admin),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Stored API key metadata; never contains the secret
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiKeyRecord {
+    pub id: String,
+    pub name: String,
+    pub namespace: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl ApiKeyRecord {
+    pub fn has_scope(&self, required: Scope) -> bool {
+        self.scopes.contains(&Scope::Admin) || self.scopes.contains(&required)
+    }
+
+    pub fn is_revoked(&self) -> bool {
+        self.revoked_at.is_some()
+    }
+
+    /// Whether the key may see what belongs to `namespace`
+    pub fn can_access(&self, namespace: &str) -> bool {
+        self.scopes.contains(&Scope::Admin) || self.namespace == namespace
+    }
+}
+
+/// Newly created key together with its one-time plaintext token
+#[derive(Debug, Clone, Serialize)]
+pub struct IssuedApiKey {
+    #[serde(flatten)]
+    pub key: ApiKeyRecord,
+    pub token: String,
+}
+
+/// The key that authenticated the current request, available as a request extension
+#[derive(Debug, Clone)]
+pub struct AuthenticatedKey(pub ApiKeyRecord);
+
+/// SQLite-backed store of hashed API keys and their audit trail
+pub struct ApiKeyStore {
+    pool: SqlitePool,
+}
+
+impl ApiKeyStore {
+    /// Create the tables; keys issued before namespaces existed are bound
+    /// to the default namespace
+    pub async fn open(pool: SqlitePool) -> Result<Self> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS api_keys (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                namespace TEXT NOT NULL DEFAULT 'default',
+                secret_hash TEXT NOT NULL,
+                scopes TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                last_used_at INTEGER,
+                revoked_at INTEGER
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+        namespace::migrate_table(&pool, "api_keys", "namespace").await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS api_audit (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                key_id TEXT,
+                method TEXT NOT NULL,
+                path TEXT NOT NULL,
+                status INTEGER NOT NULL,
+                timestamp INTEGER NOT NULL
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        Ok(Self { pool })
+    }
+
+    /// Create a key bound to `namespace` and return it with its plaintext
+    /// token
+    pub async fn create(
+        &self,
+        name: &str,
+        scopes: &[Scope],
+        namespace: &str,
+    ) -> Result<IssuedApiKey> {
+        if scopes.is_empty() {
+            return Err(GhostFlowError::Config(
+                "An API key needs at least one scope".to_string(),
+            ));
+        }
+        namespace::validate_name(namespace)?;
+
+        let id = Uuid::new_v4().simple().to_string();
+        let mut secret = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut secret);
+        let secret = hex::encode(secret);
+        let created_at = Utc::now();
+
+        sqlx::query(
+            "INSERT INTO api_keys (id, name, namespace, secret_hash, scopes, created_at) VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&id)
+        .bind(name)
+        .bind(namespace)
+        .bind(hash_secret(&secret))
+        .bind(join_scopes(scopes))
+        .bind(created_at.timestamp_millis())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(IssuedApiKey {
+            token: format!("{}{}_{}", TOKEN_PREFIX, id, secret),
+            key: ApiKeyRecord {
+                id,
+                name: name.to_string(),
+                namespace: namespace.to_string(),
+                scopes: scopes.to_vec(),
+                created_at,
+                last_used_at: None,
+                revoked_at: None,
+            },
+        })
+    }
+
+    pub async fn list(&self) -> Result<Vec<ApiKeyRecord>> {
+        let rows = sqlx::query(
+            "SELECT id, name, namespace, scopes, created_at, last_used_at, revoked_at FROM api_keys ORDER BY created_at",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().map(record_from_row).collect())
+    }
+
+    /// Revoke a key; returns false if no active key had that id
+    pub async fn revoke(&self, id: &str) -> Result<bool> {
+        let result =
+            sqlx::query("UPDATE api_keys SET revoked_at = ? WHERE id = ? AND revoked_at IS NULL")
+                .bind(Utc::now().timestamp_millis())
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Resolve a token to its active key, or `None` if it is unknown, malformed or revoked
+    pub async fn verify(&self, token: &str) -> Result<Option<ApiKeyRecord>> {
+        let Some((id, secret)) = token
+            .strip_prefix(TOKEN_PREFIX)
+            .and_then(|rest| rest.split_once('_'))
+        else {
+            return Ok(None);
+        };
+
+        let Some(row) = sqlx::query(
+            "SELECT id, name, namespace, secret_hash, scopes, created_at, last_used_at, revoked_at FROM api_keys WHERE id = ?",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?
+        else {
+            return Ok(None);
+        };
+
+        let stored: String = row.get("secret_hash");
+        if !constant_time_eq(stored.as_bytes(), hash_secret(secret).as_bytes()) {
+            return Ok(None);
+        }
+
+        let mut record = record_from_row(&row);
+        if record.is_revoked() {
+            return Ok(None);
+        }
+
+        let now = Utc::now();
+        sqlx::query("UPDATE api_keys SET last_used_at = ? WHERE id = ?")
+            .bind(now.timestamp_millis())
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        record.last_used_at = Some(now);
+
+        Ok(Some(record))
+    }
+
+    /// Create the first admin key if the store is empty
+    pub async fn bootstrap_admin(&self) -> Result<Option<IssuedApiKey>> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM api_keys")
+            .fetch_one(&self.pool)
+            .await?;
+        if count > 0 {
+            return Ok(None);
+        }
+
+        self.create(
+            "bootstrap-admin",
+            &[Scope::Admin],
+            namespace::DEFAULT_NAMESPACE,
+        )
+        .await
+        .map(Some)
+    }
+
+    pub async fn record_audit(
+        &self,
+        key_id: Option<&str>,
+        method: &Method,
+        path: &str,
+        status: StatusCode,
+    ) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO api_audit (key_id, method, path, status, timestamp) VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(key_id)
+        .bind(method.as_str())
+        .bind(path)
+        .bind(status.as_u16() as i64)
+        .bind(Utc::now().timestamp_millis())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// Scope a request needs, or `None` for public routes
+pub fn required_scope(method: &Method, path: &str) -> Option<Scope> {
+    if path == "/api/health" {
+        return None;
+    }
+    if path == "/api/keys" || path.starts_with("/api/keys/") {
+        return Some(Scope::Admin);
+    }
+    if *method == Method::GET || *method == Method::HEAD || *method == Method::OPTIONS {
+        return Some(Scope::Read);
+    }
+    if *method == Method::POST && (path.ends_with("/execute") || path.ends_with("/webhook")) {
+        return Some(Scope::Execute);
+    }
+    // Deleting executions is how a namespace frees its stored-executions quota
+    if *method == Method::DELETE && path.starts_with("/api/executions/") {
+        return Some(Scope::Execute);
+    }
+    Some(Scope::Admin)
+}
+
+/// Middleware enforcing API keys and scopes on every route, including `/ws` and webhooks
+pub async fn require_api_key(
+    State(state): State<ApiState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+
+    let Some(required) = required_scope(&method, &path) else {
+        return next.run(request).await;
+    };
+
+    let token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string());
+
+    let key = match token {
+        Some(token) => match state.api_keys.verify(&token).await {
+            Ok(key) => key,
+            Err(e) => {
+                warn!("API key lookup failed: {}", e);
+                return auth_error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to verify API key".to_string(),
+                );
+            }
+        },
+        None => None,
+    };
+
+    let response = match &key {
+        None => {
+            let mut response = auth_error(
+                StatusCode::UNAUTHORIZED,
+                "Missing or invalid API key".to_string(),
+            );
+            response.headers_mut().insert(
+                header::WWW_AUTHENTICATE,
+                header::HeaderValue::from_static("Bearer"),
+            );
+            response
+        }
+        Some(key) if !key.has_scope(required) => auth_error(
+            StatusCode::FORBIDDEN,
+            format!("API key lacks required scope '{}'", required),
+        ),
+        Some(key) => {
+            request
+                .extensions_mut()
+                .insert(AuthenticatedKey(key.clone()));
+            next.run(request).await
+        }
+    };
+
+    let key_id = key.as_ref().map(|key| key.id.as_str());
+    if let Err(e) = state
+        .api_keys
+        .record_audit(key_id, &method, &path, response.status())
+        .await
+    {
+        warn!(
+            "Failed to record audit entry for {} {}: {}",
+            method, path, e
+        );
+    }
+
+    response
+}
+
+/// Print a bootstrap admin key if none exist yet
+pub async fn bootstrap(store: &ApiKeyStore) -> Result<()> {
+    if let Some(issued) = store.bootstrap_admin().await? {
+        info!("Created bootstrap admin API key {}", issued.key.id);
+        println!("🔑 GhostFlow bootstrap admin API key (shown only once):");
+        println!("   {}", issued.token);
+    }
+    Ok(())
+}
+
+fn auth_error(status: StatusCode, error: String) -> Response {
+    (status, Json(ErrorResponse { error })).into_response()
+}
+
+fn hash_secret(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn join_scopes(scopes: &[Scope]) -> String {
+    scopes
+        .iter()
+        .map(Scope::as_str)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn millis_to_datetime(millis: i64) -> DateTime<Utc> {
+    Utc.timestamp_millis_opt(millis)
+        .single()
+        .unwrap_or_default()
+}
+
+fn record_from_row(row: &sqlx::sqlite::SqliteRow) -> ApiKeyRecord {
+    let scopes: String = row.get("scopes");
+    ApiKeyRecord {
+        id: row.get("id"),
+        name: row.get("name"),
+        namespace: row.get("namespace"),
+        scopes: scopes.split(',').filter_map(Scope::parse).collect(),
+        created_at: millis_to_datetime(row.get("created_at")),
+        last_used_at: row
+            .get::<Option<i64>, _>("last_used_at")
+            .map(millis_to_datetime),
+        revoked_at: row
+            .get::<Option<i64>, _>("revoked_at")
+            .map(millis_to_datetime),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn store() -> ApiKeyStore {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        ApiKeyStore::open(pool).await.unwrap()
+    }
+
+    #[test]
+    fn test_required_scope() {
+        assert_eq!(required_scope(&Method::GET, "/api/health"), None);
+        assert_eq!(
+            required_scope(&Method::GET, "/api/workflows"),
+            Some(Scope::Read)
+        );
+        assert_eq!(required_scope(&Method::GET, "/ws"), Some(Scope::Read));
+        assert_eq!(
+            required_scope(&Method::POST, "/api/workflows/abc/execute"),
+            Some(Scope::Execute)
+        );
+        assert_eq!(
+            required_scope(&Method::POST, "/api/workflows/abc/webhook"),
+            Some(Scope::Execute)
+        );
+        assert_eq!(
+            required_scope(&Method::POST, "/api/workflows"),
+            Some(Scope::Admin)
+        );
+        assert_eq!(
+            required_scope(&Method::DELETE, "/api/executions/abc"),
+            Some(Scope::Execute)
+        );
+        assert_eq!(
+            required_scope(&Method::GET, "/api/keys"),
+            Some(Scope::Admin)
+        );
+        assert_eq!(
+            required_scope(&Method::DELETE, "/api/keys/abc"),
+            Some(Scope::Admin)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_verify_revoke() {
+        let store = store().await;
+        let issued = store
+            .create("ci", &[Scope::Execute], "default")
+            .await
+            .unwrap();
+        assert!(issued.token.starts_with(TOKEN_PREFIX));
+
+        let key = store.verify(&issued.token).await.unwrap().unwrap();
+        assert_eq!(key.id, issued.key.id);
+        assert!(key.has_scope(Scope::Execute));
+        assert!(!key.has_scope(Scope::Read));
+        assert!(key.last_used_at.is_some());
+
+        let tampered = format!("{}0", issued.token);
+        assert!(store.verify(&tampered).await.unwrap().is_none());
+        assert!(store.verify("not-a-token").await.unwrap().is_none());
+
+        assert!(store.revoke(&key.id).await.unwrap());
+        assert!(!store.revoke(&key.id).await.unwrap());
+        assert!(store.verify(&issued.token).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_secret_is_not_stored() {
+        let store = store().await;
+        let issued = store.create("ci", &[Scope::Read], "default").await.unwrap();
+        let (_, secret) = issued.token.rsplit_once('_').unwrap();
+
+        let stored: String = sqlx::query_scalar("SELECT secret_hash FROM api_keys")
+            .fetch_one(&store.pool)
+            .await
+            .unwrap();
+        assert_ne!(stored, secret);
+        assert_eq!(stored, hash_secret(secret));
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_only_when_empty() {
+        let store = store().await;
+        let admin = store.bootstrap_admin().await.unwrap().unwrap();
+        assert!(admin.key.has_scope(Scope::Read));
+        assert!(store.bootstrap_admin().await.unwrap().is_none());
+        assert_eq!(store.list().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_audit_records_key_id() {
+        let store = store().await;
+        let issued = store.create("ci", &[Scope::Read], "default").await.unwrap();
+        store
+            .record_audit(
+                Some(&issued.key.id),
+                &Method::GET,
+                "/api/workflows",
+                StatusCode::OK,
+            )
+            .await
+            .unwrap();
+
+        let key_id: Option<String> = sqlx::query_scalar("SELECT key_id FROM api_audit")
+            .fetch_one(&store.pool)
+            .await
+            .unwrap();
+        assert_eq!(key_id.as_deref(), Some(issued.key.id.as_str()));
+    }
+
+    #[tokio::test]
+    async fn test_keys_are_bound_to_their_namespace() {
+        let store = store().await;
+        let friend = store
+            .create("friend", &[Scope::Read, Scope::Execute], "friend")
+            .await
+            .unwrap();
+        let admin = store
+            .create("me", &[Scope::Admin], "default")
+            .await
+            .unwrap();
+
+        let friend = store.verify(&friend.token).await.unwrap().unwrap();
+        assert_eq!(friend.namespace, "friend");
+        assert!(friend.can_access("friend"));
+        assert!(!friend.can_access("default"));
+        // Admin keys cross namespaces
+        assert!(admin.key.can_access("friend"));
+
+        assert!(store
+            .create("bad", &[Scope::Read], "Friend's")
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_keys_from_before_namespaces_move_to_default() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::query(
+            "CREATE TABLE api_keys (id TEXT PRIMARY KEY, name TEXT NOT NULL, secret_hash TEXT NOT NULL, scopes TEXT NOT NULL, created_at INTEGER NOT NULL, last_used_at INTEGER, revoked_at INTEGER)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO api_keys (id, name, secret_hash, scopes, created_at) VALUES ('old', 'ci', ?, 'read', 0)",
+        )
+        .bind(hash_secret("secret"))
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let store = ApiKeyStore::open(pool).await.unwrap();
+        let key = store.verify("gf_old_secret").await.unwrap().unwrap();
+        assert_eq!(key.namespace, "default");
+        // Opening again finds the column already there
+        ApiKeyStore::open(store.pool.clone()).await.unwrap();
+        assert_eq!(store.list().await.unwrap().len(), 1);
+    }
+}
@@ -1,11 +1,13 @@
 use anyhow::{Context, Result};
 use clap::Parser;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use tracing::{info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+use jarvis_core::tls::TlsConfig;
 use jarvis_ghostflow::{
-    create_ghostflow_server, IntegrationConfig, JarvisGhostFlowIntegration
+    create_ghostflow_server, GhostFlowConfig, IntegrationConfig, JarvisGhostFlowIntegration
 };
 
 /// GhostFlow Server - n8n-style workflow automation with Jarvis AI integration
@@ -38,6 +40,35 @@ struct Args {
     #[arg(long, default_value = "./workflows")]
     workflow_storage_path: String,
 
+    /// API key database path
+    #[arg(long, default_value = "./ghostflow-auth.db")]
+    auth_database_path: String,
+
+    /// PEM certificate for serving the API over TLS
+    #[arg(long, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+
+    /// PEM private key for --tls-cert
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    /// PEM CA bundle; enables mutual TLS and requires client certificates
+    #[arg(long)]
+    tls_client_ca: Option<PathBuf>,
+
+    /// Client certificate identity (SAN or CN) allowed to connect; repeatable
+    #[arg(long = "tls-allowed-peer")]
+    tls_allowed_peers: Vec<String>,
+
+    /// Start an Arch agent for jarvis.arch.operation nodes
+    #[arg(long)]
+    arch_agent: bool,
+
+    /// GhostFlow config file; its [budgets] section caps LLM token use and
+    /// cost, its [quotas] section what each namespace may run and store
+    #[arg(long)]
+    config: Option<PathBuf>,
+
     /// Run demo workflow on startup
     #[arg(long)]
     run_demo: bool,
@@ -77,6 +108,15 @@ async fn main() -> Result<()> {
         info!("Demo workflow execution enabled");
     }
 
+    let (budgets, quotas) = match &args.config {
+        Some(path) => {
+            let config = GhostFlowConfig::from_file(path)
+                .map_err(|e| anyhow::anyhow!("Failed to load {}: {}", path.display(), e))?;
+            (Some(config.budgets), config.quotas)
+        }
+        None => (None, Default::default()),
+    };
+
     // Create integration config
     let config = IntegrationConfig {
         api_address: args.api_address,
@@ -85,6 +125,16 @@ async fn main() -> Result<()> {
         enable_websockets: args.enable_websockets,
         enable_metrics: args.enable_metrics,
         workflow_storage_path: args.workflow_storage_path,
+        auth_database_path: args.auth_database_path,
+        tls: TlsConfig {
+            cert_path: args.tls_cert,
+            key_path: args.tls_key,
+            client_ca_path: args.tls_client_ca,
+            allowed_peers: args.tls_allowed_peers,
+        },
+        arch_agent: args.arch_agent,
+        budgets,
+        quotas,
     };
 
     // Create and start GhostFlow server
@@ -108,6 +158,8 @@ async fn main() -> Result<()> {
     info!("  • GET  /api/workflows/:id    - Get workflow");
     info!("  • PUT  /api/workflows/:id    - Update workflow");
     info!("  • POST /api/workflows/:id/execute - Execute workflow");
+    info!("  • GET  /api/executions       - List executions");
+    info!("  • GET  /api/budgets          - LLM budgets and paused executions");
     info!("  • GET  /api/node-types       - List available node types");
 
     // Print some usage examples
@@ -0,0 +1,597 @@
+//! LLM token and cost budgets for workflows
+//!
+//! Every call the LLM router node makes is priced from the configured price
+//! table and charged to the workflow that made it, to the workflow's
+//! namespace and to the global budget. Before each call the node checks all
+//! three, and once any is used up the
+//! call is refused with [`BudgetExceeded`]. Depending on `on_exhausted` the
+//! execution then fails like it would on any node error, or pauses in the
+//! `BudgetExceeded` state until an admin raises the budget and resumes it.
+//!
+//! Usage, and limits raised through the API, are written to `state_path` so
+//! a restart doesn't hand every workflow a fresh budget. Budgets never reset
+//! on their own.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::namespace::DEFAULT_NAMESPACE;
+use crate::GhostFlowError;
+
+/// A token and/or cost cap; a cap left unset is unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct BudgetLimit {
+    #[serde(default)]
+    pub max_tokens: Option<u64>,
+    #[serde(default)]
+    pub max_cost_usd: Option<f64>,
+}
+
+impl BudgetLimit {
+    /// Whether `used` leaves nothing to spend
+    pub fn exhausted_by(&self, used: &Usage) -> bool {
+        self.max_tokens.is_some_and(|max| used.tokens >= max)
+            || self.max_cost_usd.is_some_and(|max| used.cost_usd >= max)
+    }
+}
+
+/// Tokens used and dollars spent
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    pub tokens: u64,
+    pub cost_usd: f64,
+}
+
+impl Usage {
+    fn add(&mut self, other: Usage) {
+        self.tokens += other.tokens;
+        self.cost_usd += other.cost_usd;
+    }
+}
+
+/// What a model costs per 1000 tokens
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub prompt_per_1k_usd: f64,
+    pub completion_per_1k_usd: f64,
+}
+
+/// What happens to an execution whose LLM call is refused
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetAction {
+    /// The node fails, and the execution with it unless the node continues
+    /// on failure
+    #[default]
+    Fail,
+    /// The execution pauses in the `BudgetExceeded` state until resumed
+    Pause,
+}
+
+/// `budgets` section of the GhostFlow config
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BudgetConfig {
+    /// Cap on all workflows' calls together
+    #[serde(default)]
+    pub global: BudgetLimit,
+    /// Caps per workflow id
+    #[serde(default)]
+    pub workflows: HashMap<Uuid, BudgetLimit>,
+    /// Caps per namespace, over all of its workflows together
+    #[serde(default)]
+    pub namespaces: HashMap<String, BudgetLimit>,
+    #[serde(default)]
+    pub on_exhausted: BudgetAction,
+    /// Prices per model, with `default` for models not listed. Calls to
+    /// unpriced models cost nothing but still count against token caps.
+    #[serde(default)]
+    pub prices: HashMap<String, ModelPrice>,
+    /// Where usage and raised limits are kept
+    #[serde(default = "default_state_path")]
+    pub state_path: PathBuf,
+}
+
+fn default_state_path() -> PathBuf {
+    PathBuf::from("./ghostflow-budgets.json")
+}
+
+impl Default for BudgetConfig {
+    fn default() -> Self {
+        Self {
+            global: BudgetLimit::default(),
+            workflows: HashMap::new(),
+            namespaces: HashMap::new(),
+            on_exhausted: BudgetAction::default(),
+            prices: HashMap::new(),
+            state_path: default_state_path(),
+        }
+    }
+}
+
+impl BudgetConfig {
+    /// Price of a call to `model`
+    pub fn price(&self, model: &str, prompt_tokens: u64, completion_tokens: u64) -> f64 {
+        let Some(price) = self.prices.get(model).or_else(|| self.prices.get("default")) else {
+            return 0.0;
+        };
+        (prompt_tokens as f64 / 1000.0) * price.prompt_per_1k_usd
+            + (completion_tokens as f64 / 1000.0) * price.completion_per_1k_usd
+    }
+}
+
+/// Which budget a limit or usage belongs to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetScope {
+    Global,
+    Workflow(Uuid),
+    Namespace(String),
+}
+
+impl fmt::Display for BudgetScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BudgetScope::Global => write!(f, "Global LLM budget"),
+            BudgetScope::Workflow(id) => write!(f, "LLM budget of workflow {}", id),
+            BudgetScope::Namespace(name) => write!(f, "LLM budget of namespace {}", name),
+        }
+    }
+}
+
+/// Returned instead of making an LLM call once a budget is used up
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("{scope} is used up ({} tokens, ${:.4} spent); raise it to continue", .used.tokens, .used.cost_usd)]
+pub struct BudgetExceeded {
+    pub scope: BudgetScope,
+    pub limit: BudgetLimit,
+    pub used: Usage,
+}
+
+impl BudgetExceeded {
+    /// The budget `error` says ran out, if that's why a node failed
+    pub fn find(error: &anyhow::Error) -> Option<&BudgetExceeded> {
+        error.downcast_ref::<BudgetExceeded>().or_else(|| {
+            match error.downcast_ref::<GhostFlowError>() {
+                Some(GhostFlowError::BudgetExceeded(exceeded)) => Some(exceeded),
+                _ => None,
+            }
+        })
+    }
+}
+
+/// A budget's limit and what's been used of it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BudgetStatus {
+    pub scope: BudgetScope,
+    pub limit: BudgetLimit,
+    pub used: Usage,
+    pub remaining_tokens: Option<u64>,
+    pub remaining_cost_usd: Option<f64>,
+    pub exhausted: bool,
+}
+
+/// Every budget, for `GET /api/budgets`
+#[derive(Debug, Clone, Serialize)]
+pub struct BudgetReport {
+    pub on_exhausted: BudgetAction,
+    pub global: BudgetStatus,
+    /// Workflows with a limit or any usage
+    pub workflows: Vec<BudgetStatus>,
+    /// Namespaces with a limit or any usage
+    pub namespaces: Vec<BudgetStatus>,
+    /// Executions waiting in the `BudgetExceeded` state
+    pub paused_executions: Vec<Uuid>,
+}
+
+/// What's persisted to `state_path`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct BudgetState {
+    #[serde(default)]
+    global: Usage,
+    #[serde(default)]
+    workflows: BTreeMap<Uuid, Usage>,
+    #[serde(default)]
+    namespaces: BTreeMap<String, Usage>,
+    /// Limits set through the API, replacing the configured ones
+    #[serde(default)]
+    global_limit: Option<BudgetLimit>,
+    #[serde(default)]
+    workflow_limits: BTreeMap<Uuid, BudgetLimit>,
+    #[serde(default)]
+    namespace_limits: BTreeMap<String, BudgetLimit>,
+}
+
+/// Budget usage, shared by the LLM router nodes, the engine and the API
+pub struct Budgets {
+    config: BudgetConfig,
+    /// `None` keeps usage in memory only
+    path: Option<PathBuf>,
+    state: Mutex<BudgetState>,
+    /// Spend per execution still running or paused, for execution events
+    executions: Mutex<HashMap<Uuid, Usage>>,
+    /// Namespace of each workflow, as told by the engine; workflows it
+    /// never mentioned are charged to the default namespace
+    workflow_namespaces: Mutex<HashMap<Uuid, String>>,
+}
+
+impl Budgets {
+    /// Budgets carrying on from the usage saved at `config.state_path`
+    pub fn open(config: BudgetConfig) -> Result<Self> {
+        let path = config.state_path.clone();
+        let state = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)
+                .with_context(|| format!("Invalid budget state in {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BudgetState::default(),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", path.display()))
+            }
+        };
+        Ok(Self::build(config, Some(path), state))
+    }
+
+    /// Budgets that start from nothing and aren't saved
+    pub fn in_memory(config: BudgetConfig) -> Self {
+        Self::build(config, None, BudgetState::default())
+    }
+
+    fn build(config: BudgetConfig, path: Option<PathBuf>, state: BudgetState) -> Self {
+        Self {
+            config,
+            path,
+            state: Mutex::new(state),
+            executions: Mutex::new(HashMap::new()),
+            workflow_namespaces: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn on_exhausted(&self) -> BudgetAction {
+        self.config.on_exhausted
+    }
+
+    /// Charge `workflow_id`'s calls to `namespace` from now on
+    pub fn set_namespace(&self, workflow_id: Uuid, namespace: &str) {
+        self.workflow_namespaces
+            .lock()
+            .unwrap()
+            .insert(workflow_id, namespace.to_string());
+    }
+
+    fn namespace_of(&self, workflow_id: Uuid) -> String {
+        self.workflow_namespaces
+            .lock()
+            .unwrap()
+            .get(&workflow_id)
+            .cloned()
+            .unwrap_or_else(|| DEFAULT_NAMESPACE.to_string())
+    }
+
+    /// Refuse when the global budget, `workflow_id`'s or its namespace's is
+    /// used up
+    pub fn check(&self, workflow_id: Uuid) -> std::result::Result<(), BudgetExceeded> {
+        let namespace = self.namespace_of(workflow_id);
+        let state = self.state.lock().unwrap();
+        for scope in [
+            BudgetScope::Global,
+            BudgetScope::Namespace(namespace),
+            BudgetScope::Workflow(workflow_id),
+        ] {
+            let limit = self.limit(&state, &scope);
+            let used = Self::used(&state, &scope);
+            if limit.exhausted_by(&used) {
+                return Err(BudgetExceeded { scope, limit, used });
+            }
+        }
+        Ok(())
+    }
+
+    /// Price a call to `model` and charge it to the workflow, its namespace,
+    /// the global budget and the execution; returns what the call used
+    pub fn charge(
+        &self,
+        workflow_id: Uuid,
+        execution_id: Uuid,
+        model: &str,
+        prompt_tokens: u64,
+        completion_tokens: u64,
+    ) -> Usage {
+        let usage = Usage {
+            tokens: prompt_tokens + completion_tokens,
+            cost_usd: self.config.price(model, prompt_tokens, completion_tokens),
+        };
+        self.executions
+            .lock()
+            .unwrap()
+            .entry(execution_id)
+            .or_default()
+            .add(usage);
+
+        let namespace = self.namespace_of(workflow_id);
+        let mut state = self.state.lock().unwrap();
+        state.global.add(usage);
+        state.workflows.entry(workflow_id).or_default().add(usage);
+        state.namespaces.entry(namespace).or_default().add(usage);
+        // A call that went through is never refused after the fact
+        if let Err(e) = self.save(&state) {
+            warn!("Failed to save LLM budget usage: {:#}", e);
+        }
+        usage
+    }
+
+    /// What `execution_id` has spent so far
+    pub fn execution_usage(&self, execution_id: Uuid) -> Usage {
+        self.executions
+            .lock()
+            .unwrap()
+            .get(&execution_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Stop tracking a finished execution's spend
+    pub fn finish_execution(&self, execution_id: Uuid) {
+        self.executions.lock().unwrap().remove(&execution_id);
+    }
+
+    /// Replace the limit of `scope`, e.g. to raise a used up budget, and
+    /// save it
+    pub fn set_limit(&self, scope: BudgetScope, limit: BudgetLimit) -> Result<BudgetStatus> {
+        let mut state = self.state.lock().unwrap();
+        match &scope {
+            BudgetScope::Global => state.global_limit = Some(limit),
+            BudgetScope::Workflow(id) => {
+                state.workflow_limits.insert(*id, limit);
+            }
+            BudgetScope::Namespace(name) => {
+                state.namespace_limits.insert(name.clone(), limit);
+            }
+        }
+        self.save(&state)?;
+        Ok(self.status_of(&state, scope))
+    }
+
+    pub fn status(&self, scope: BudgetScope) -> BudgetStatus {
+        self.status_of(&self.state.lock().unwrap(), scope)
+    }
+
+    /// The global budget and every workflow and namespace budget with a
+    /// limit or usage
+    pub fn report(&self) -> BudgetReport {
+        let state = self.state.lock().unwrap();
+        let mut workflow_ids: Vec<Uuid> = self
+            .config
+            .workflows
+            .keys()
+            .chain(state.workflow_limits.keys())
+            .chain(state.workflows.keys())
+            .copied()
+            .collect();
+        workflow_ids.sort();
+        workflow_ids.dedup();
+        let mut namespaces: Vec<String> = self
+            .config
+            .namespaces
+            .keys()
+            .chain(state.namespace_limits.keys())
+            .chain(state.namespaces.keys())
+            .cloned()
+            .collect();
+        namespaces.sort();
+        namespaces.dedup();
+
+        BudgetReport {
+            on_exhausted: self.config.on_exhausted,
+            global: self.status_of(&state, BudgetScope::Global),
+            workflows: workflow_ids
+                .into_iter()
+                .map(|id| self.status_of(&state, BudgetScope::Workflow(id)))
+                .collect(),
+            namespaces: namespaces
+                .into_iter()
+                .map(|name| self.status_of(&state, BudgetScope::Namespace(name)))
+                .collect(),
+            paused_executions: Vec::new(),
+        }
+    }
+
+    fn limit(&self, state: &BudgetState, scope: &BudgetScope) -> BudgetLimit {
+        match scope {
+            BudgetScope::Global => state.global_limit.unwrap_or(self.config.global),
+            BudgetScope::Workflow(id) => state
+                .workflow_limits
+                .get(id)
+                .or_else(|| self.config.workflows.get(id))
+                .copied()
+                .unwrap_or_default(),
+            BudgetScope::Namespace(name) => state
+                .namespace_limits
+                .get(name)
+                .or_else(|| self.config.namespaces.get(name))
+                .copied()
+                .unwrap_or_default(),
+        }
+    }
+
+    fn used(state: &BudgetState, scope: &BudgetScope) -> Usage {
+        match scope {
+            BudgetScope::Global => state.global,
+            BudgetScope::Workflow(id) => state.workflows.get(id).copied().unwrap_or_default(),
+            BudgetScope::Namespace(name) => state.namespaces.get(name).copied().unwrap_or_default(),
+        }
+    }
+
+    fn status_of(&self, state: &BudgetState, scope: BudgetScope) -> BudgetStatus {
+        let limit = self.limit(state, &scope);
+        let used = Self::used(state, &scope);
+        BudgetStatus {
+            scope,
+            limit,
+            used,
+            remaining_tokens: limit.max_tokens.map(|max| max.saturating_sub(used.tokens)),
+            remaining_cost_usd: limit.max_cost_usd.map(|max| (max - used.cost_usd).max(0.0)),
+            exhausted: limit.exhausted_by(&used),
+        }
+    }
+
+    fn save(&self, state: &BudgetState) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        write_atomically(path, &serde_json::to_vec_pretty(state)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+/// Write through a temporary file so a crash never leaves half a state file
+fn write_atomically(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    std::fs::write(&temporary, content)?;
+    std::fs::rename(&temporary, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(state_path: PathBuf, workflow_id: Uuid) -> BudgetConfig {
+        BudgetConfig {
+            global: BudgetLimit {
+                max_tokens: None,
+                max_cost_usd: Some(1.0),
+            },
+            workflows: HashMap::from([(
+                workflow_id,
+                BudgetLimit {
+                    max_tokens: Some(3000),
+                    max_cost_usd: None,
+                },
+            )]),
+            namespaces: HashMap::new(),
+            on_exhausted: BudgetAction::Pause,
+            prices: HashMap::from([
+                (
+                    "gpt-fake".to_string(),
+                    ModelPrice {
+                        prompt_per_1k_usd: 0.5,
+                        completion_per_1k_usd: 1.0,
+                    },
+                ),
+                (
+                    "default".to_string(),
+                    ModelPrice {
+                        prompt_per_1k_usd: 0.0,
+                        completion_per_1k_usd: 0.25,
+                    },
+                ),
+            ]),
+            state_path,
+        }
+    }
+
+    #[test]
+    fn test_price_table_falls_back_to_default() {
+        let config = config(PathBuf::new(), Uuid::new_v4());
+        assert_eq!(config.price("gpt-fake", 1000, 2000), 2.5);
+        assert_eq!(config.price("llama", 1000, 2000), 0.5);
+        assert_eq!(BudgetConfig::default().price("llama", 1000, 2000), 0.0);
+    }
+
+    #[test]
+    fn test_caps_refuse_calls_until_raised_and_survive_restarts() {
+        let path = std::env::temp_dir().join(format!("ghostflow-budgets-{}.json", Uuid::new_v4()));
+        let workflow = Uuid::new_v4();
+        let other = Uuid::new_v4();
+        let execution = Uuid::new_v4();
+
+        let budgets = Budgets::open(config(path.clone(), workflow)).unwrap();
+        budgets.check(workflow).unwrap();
+        budgets.charge(workflow, execution, "llama", 1000, 1000);
+        budgets.charge(workflow, execution, "llama", 500, 500);
+        assert_eq!(
+            budgets.execution_usage(execution),
+            Usage {
+                tokens: 3000,
+                cost_usd: 0.375
+            }
+        );
+        let exceeded = budgets.check(workflow).unwrap_err();
+        assert_eq!(exceeded.scope, BudgetScope::Workflow(workflow));
+        assert!(exceeded.to_string().contains("3000 tokens"));
+        // Other workflows only answer to the global budget
+        budgets.check(other).unwrap();
+
+        // Usage carries over a restart; a raised limit lets calls through
+        let budgets = Budgets::open(config(path.clone(), workflow)).unwrap();
+        assert!(budgets.status(BudgetScope::Workflow(workflow)).exhausted);
+        let raised = budgets
+            .set_limit(
+                BudgetScope::Workflow(workflow),
+                BudgetLimit {
+                    max_tokens: Some(5000),
+                    max_cost_usd: None,
+                },
+            )
+            .unwrap();
+        assert_eq!(raised.remaining_tokens, Some(2000));
+        budgets.check(workflow).unwrap();
+
+        // The global cap covers every workflow
+        budgets.charge(other, Uuid::new_v4(), "gpt-fake", 1000, 1000);
+        assert_eq!(budgets.check(workflow).unwrap_err().scope, BudgetScope::Global);
+
+        let budgets = Budgets::open(config(path.clone(), workflow)).unwrap();
+        let report = budgets.report();
+        assert!(report.global.exhausted);
+        assert_eq!(report.workflows.len(), 2);
+        assert_eq!(
+            budgets.status(BudgetScope::Workflow(workflow)).limit.max_tokens,
+            Some(5000)
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_namespace_budget_covers_its_workflows_only() {
+        let mut config = config(PathBuf::new(), Uuid::new_v4());
+        config.global = BudgetLimit::default();
+        config.namespaces.insert(
+            "friend".to_string(),
+            BudgetLimit {
+                max_tokens: Some(1500),
+                max_cost_usd: None,
+            },
+        );
+        let budgets = Budgets::in_memory(config);
+        let (first, second, mine) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        budgets.set_namespace(first, "friend");
+        budgets.set_namespace(second, "friend");
+
+        budgets.charge(first, Uuid::new_v4(), "llama", 500, 500);
+        budgets.check(second).unwrap();
+        budgets.charge(second, Uuid::new_v4(), "llama", 250, 250);
+
+        let exceeded = budgets.check(first).unwrap_err();
+        assert_eq!(exceeded.scope, BudgetScope::Namespace("friend".to_string()));
+        assert!(exceeded
+            .to_string()
+            .starts_with("LLM budget of namespace friend is used up"));
+        // Workflows outside the namespace are charged to the default one
+        budgets.check(mine).unwrap();
+        budgets.charge(mine, Uuid::new_v4(), "llama", 50, 50);
+
+        let report = budgets.report();
+        assert_eq!(report.namespaces.len(), 2);
+        assert_eq!(
+            report.namespaces[0].scope,
+            BudgetScope::Namespace("default".to_string())
+        );
+        assert_eq!(report.namespaces[0].used.tokens, 100);
+        assert!(report.namespaces[1].exhausted);
+    }
+}
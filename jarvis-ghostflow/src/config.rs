@@ -1,3 +1,5 @@
+use crate::budget::BudgetConfig;
+use crate::namespace::QuotaConfig;
 use crate::{BlockchainConfig, LLMProviderConfig, NetworkOptimizationConfig};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -35,6 +37,14 @@ pub struct GhostFlowConfig {
     
     /// Logging and monitoring settings
     pub monitoring: MonitoringConfig,
+    
+    /// LLM token and cost budgets
+    #[serde(default)]
+    pub budgets: BudgetConfig,
+
+    /// Per-namespace execution and memory quotas
+    #[serde(default)]
+    pub quotas: QuotaConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -531,6 +541,8 @@ impl Default for GhostFlowConfig {
             integrations: IntegrationConfig::default(),
             security: SecurityConfig::default(),
             monitoring: MonitoringConfig::default(),
+            budgets: BudgetConfig::default(),
+            quotas: QuotaConfig::default(),
         }
     }
 }
@@ -0,0 +1,136 @@
+//! EIP-1559 fee suggestions from recent fee history
+
+use super::rpc::{self, EvmProvider, FeeHistory};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Blocks of history a suggestion looks at
+pub const HISTORY_BLOCKS: u64 = 10;
+
+/// How eagerly to bid for inclusion
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeStrategy {
+    Slow,
+    #[default]
+    Normal,
+    Fast,
+}
+
+impl FeeStrategy {
+    /// Priority fee percentile paid by recent blocks' transactions
+    fn percentile(self) -> f64 {
+        match self {
+            FeeStrategy::Slow => 10.0,
+            FeeStrategy::Normal => 50.0,
+            FeeStrategy::Fast => 90.0,
+        }
+    }
+
+    /// Full blocks in a row the max fee still covers; each raises the base
+    /// fee by up to 12.5%
+    fn headroom_blocks(self) -> u32 {
+        match self {
+            FeeStrategy::Slow => 1,
+            FeeStrategy::Normal => 3,
+            FeeStrategy::Fast => 6,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fees {
+    pub max_fee_per_gas: u128,
+    pub max_priority_fee_per_gas: u128,
+}
+
+/// Fees for the next block under `strategy`, never above `cap` wei per gas.
+/// Fails when the next base fee alone is above the cap, since such a
+/// transaction would sit in the mempool.
+pub fn suggest(history: &FeeHistory, strategy: FeeStrategy, cap: Option<u128>) -> Result<Fees> {
+    let base_fee = *history.base_fees.last().context("Fee history is empty")?;
+    let priority_fee = median(&history.rewards);
+
+    let mut headroom = base_fee;
+    for _ in 0..strategy.headroom_blocks() {
+        headroom = headroom.saturating_mul(9).div_ceil(8);
+    }
+    let mut fees = Fees {
+        max_fee_per_gas: headroom.saturating_add(priority_fee),
+        max_priority_fee_per_gas: priority_fee,
+    };
+
+    if let Some(cap) = cap {
+        if base_fee > cap {
+            anyhow::bail!(
+                "Next base fee is {:.2} gwei, above the {:.2} gwei limit",
+                gwei(base_fee),
+                gwei(cap)
+            );
+        }
+        fees.max_fee_per_gas = fees.max_fee_per_gas.min(cap);
+        fees.max_priority_fee_per_gas = fees
+            .max_priority_fee_per_gas
+            .min(fees.max_fee_per_gas - base_fee);
+    }
+    Ok(fees)
+}
+
+/// [`suggest`] from the node's fee history
+pub async fn estimate(
+    provider: &dyn EvmProvider,
+    strategy: FeeStrategy,
+    cap: Option<u128>,
+) -> Result<Fees> {
+    let history = rpc::fee_history(provider, HISTORY_BLOCKS, strategy.percentile()).await?;
+    suggest(&history, strategy, cap)
+}
+
+pub fn gwei(wei: u128) -> f64 {
+    wei as f64 / 1e9
+}
+
+fn median(values: &[u128]) -> u128 {
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable();
+    sorted.get(sorted.len() / 2).copied().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GWEI: u128 = 1_000_000_000;
+
+    fn history() -> FeeHistory {
+        FeeHistory {
+            base_fees: vec![8 * GWEI, 9 * GWEI, 8 * GWEI],
+            rewards: vec![2 * GWEI, GWEI, 3 * GWEI],
+        }
+    }
+
+    #[test]
+    fn test_strategies_add_base_fee_headroom() {
+        let slow = suggest(&history(), FeeStrategy::Slow, None).unwrap();
+        assert_eq!(slow.max_priority_fee_per_gas, 2 * GWEI);
+        assert_eq!(slow.max_fee_per_gas, 9 * GWEI + 2 * GWEI);
+
+        let fast = suggest(&history(), FeeStrategy::Fast, None).unwrap();
+        // 8 gwei grown by 12.5% six times is about 16.2 gwei
+        assert!(fast.max_fee_per_gas > 18 * GWEI);
+        assert!(fast.max_fee_per_gas > slow.max_fee_per_gas);
+    }
+
+    #[test]
+    fn test_cap_limits_fees_and_rejects_expensive_blocks() {
+        let capped = suggest(&history(), FeeStrategy::Fast, Some(10 * GWEI)).unwrap();
+        assert_eq!(capped.max_fee_per_gas, 10 * GWEI);
+        assert_eq!(capped.max_priority_fee_per_gas, 2 * GWEI);
+
+        let error = suggest(&history(), FeeStrategy::Normal, Some(5 * GWEI)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Next base fee is 8.00 gwei, above the 5.00 gwei limit"
+        );
+    }
+}
@@ -0,0 +1,20 @@
+//! Sending transactions to EVM chains
+//!
+//! Just enough of Ethereum for the transaction node: [`signing`] builds and
+//! signs EIP-1559 transactions, [`rpc`] talks JSON-RPC to a node through an
+//! [`EvmProvider`] that tests replace with an in-memory chain, [`fees`]
+//! turns fee history into bids, [`nonce`] keeps concurrent sends from one
+//! account from colliding, and [`send`] ties them together from gas
+//! estimation to a confirmed receipt.
+
+pub mod fees;
+pub mod nonce;
+pub mod rpc;
+pub mod send;
+pub mod signing;
+
+pub use fees::{FeeStrategy, Fees};
+pub use nonce::NonceTracker;
+pub use rpc::{EvmProvider, HttpProvider, Receipt, RpcError};
+pub use send::{Outcome, Request, SendOptions, Sent};
+pub use signing::{Address, Signer};
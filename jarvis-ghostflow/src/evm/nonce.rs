@@ -0,0 +1,57 @@
+//! Nonce assignment for concurrent sends from one account
+
+use super::rpc::{self, EvmProvider};
+use super::signing::Address;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+/// Hands out nonces per sender. Sends from one sender take turns from
+/// reservation to broadcast, and each starts from the higher of the node's
+/// pending count and the last nonce handed out, so two executions never sign
+/// with the same nonce even when the node hasn't seen the first one yet.
+#[derive(Debug, Default)]
+pub struct NonceTracker {
+    senders: Mutex<HashMap<Address, Arc<AsyncMutex<Option<u64>>>>>,
+}
+
+impl NonceTracker {
+    /// Wait for `sender`'s turn and pick its next nonce
+    pub async fn reserve(&self, sender: Address, provider: &dyn EvmProvider) -> Result<NonceSlot> {
+        let slot = self
+            .senders
+            .lock()
+            .unwrap()
+            .entry(sender)
+            .or_default()
+            .clone();
+        let next = slot.lock_owned().await;
+        let pending = rpc::pending_nonce(provider, sender).await?;
+        let nonce = next.map_or(pending, |next| next.max(pending));
+        Ok(NonceSlot { next, nonce })
+    }
+}
+
+/// A reserved nonce; the sender's next send waits until this is dropped
+#[derive(Debug)]
+pub struct NonceSlot {
+    next: OwnedMutexGuard<Option<u64>>,
+    nonce: u64,
+}
+
+impl NonceSlot {
+    pub fn nonce(&self) -> u64 {
+        self.nonce
+    }
+
+    /// The node accepted a transaction with this nonce
+    pub fn commit(mut self) {
+        *self.next = Some(self.nonce + 1);
+    }
+
+    /// The node rejected the nonce; start over from its pending count
+    pub fn reset(mut self) {
+        *self.next = None;
+    }
+}
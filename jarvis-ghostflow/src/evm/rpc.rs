@@ -0,0 +1,338 @@
+//! JSON-RPC access to an EVM node
+
+use super::signing::{from_hex, to_hex, Address};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use jarvis_core::net::CheckedSend;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Sends JSON-RPC requests; tests substitute an in-memory chain
+#[async_trait]
+pub trait EvmProvider: Send + Sync {
+    /// The `result` of calling `method`; an `error` response comes back as
+    /// an [`RpcError`]
+    async fn request(&self, method: &str, params: Value) -> Result<Value>;
+}
+
+#[derive(Debug, Clone, Deserialize, thiserror::Error)]
+#[error("RPC error {code}: {message}")]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(default)]
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    /// Why a call reverted, from the revert data when the node attached it
+    /// and from the message otherwise
+    pub fn revert_reason(&self) -> Option<String> {
+        if let Some(reason) = self
+            .data
+            .as_ref()
+            .and_then(Value::as_str)
+            .and_then(|data| from_hex(data).ok())
+            .and_then(|data| decode_revert(&data))
+        {
+            return Some(reason);
+        }
+        self.message
+            .strip_prefix("execution reverted")
+            .map(|rest| rest.trim_start_matches(':').trim())
+            .map(|rest| {
+                if rest.is_empty() {
+                    "reverted without a reason".to_string()
+                } else {
+                    rest.to_string()
+                }
+            })
+    }
+
+    /// The node rejected the nonce, so the cached one is stale
+    pub fn is_nonce_error(&self) -> bool {
+        let message = self.message.to_lowercase();
+        [
+            "nonce too low",
+            "already known",
+            "replacement transaction underpriced",
+        ]
+        .iter()
+        .any(|marker| message.contains(marker))
+    }
+}
+
+/// Why `error` says a call reverted, if it was a revert
+pub fn revert_reason(error: &anyhow::Error) -> Option<String> {
+    error
+        .downcast_ref::<RpcError>()
+        .and_then(RpcError::revert_reason)
+}
+
+/// Talks to a node over HTTP
+pub struct HttpProvider {
+    url: String,
+    next_id: AtomicU64,
+}
+
+impl HttpProvider {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            next_id: AtomicU64::new(1),
+        }
+    }
+}
+
+#[async_trait]
+impl EvmProvider for HttpProvider {
+    async fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": self.next_id.fetch_add(1, Ordering::Relaxed),
+            "method": method,
+            "params": params,
+        });
+        let mut response: Value = jarvis_core::net::client()
+            .post(&self.url)
+            .json(&body)
+            .checked_send()
+            .await
+            .with_context(|| format!("Failed to reach RPC endpoint for {}", method))?
+            .error_for_status()?
+            .json()
+            .await
+            .with_context(|| format!("Invalid JSON-RPC response to {}", method))?;
+
+        if let Some(error) = response.get("error") {
+            let error: RpcError = serde_json::from_value(error.clone())
+                .with_context(|| format!("Malformed error response to {}", method))?;
+            return Err(error.into());
+        }
+        response
+            .get_mut("result")
+            .map(Value::take)
+            .with_context(|| format!("Response to {} has no result", method))
+    }
+}
+
+/// A JSON-RPC quantity
+pub fn quantity(value: u128) -> String {
+    format!("0x{:x}", value)
+}
+
+pub fn parse_quantity(value: &Value) -> Result<u128> {
+    let text = value
+        .as_str()
+        .with_context(|| format!("Expected a hex quantity, got {}", value))?;
+    let digits = text.strip_prefix("0x").unwrap_or(text);
+    u128::from_str_radix(digits, 16).with_context(|| format!("'{}' is not a quantity", text))
+}
+
+fn parse_u64(value: &Value) -> Result<u64> {
+    u64::try_from(parse_quantity(value)?).context("Quantity does not fit in 64 bits")
+}
+
+pub async fn chain_id(provider: &dyn EvmProvider) -> Result<u64> {
+    parse_u64(&provider.request("eth_chainId", json!([])).await?)
+}
+
+pub async fn block_number(provider: &dyn EvmProvider) -> Result<u64> {
+    parse_u64(&provider.request("eth_blockNumber", json!([])).await?)
+}
+
+/// Transactions `address` has sent, counting those still in the mempool
+pub async fn pending_nonce(provider: &dyn EvmProvider, address: Address) -> Result<u64> {
+    let count = provider
+        .request(
+            "eth_getTransactionCount",
+            json!([address.to_string(), "pending"]),
+        )
+        .await?;
+    parse_u64(&count)
+}
+
+pub async fn estimate_gas(provider: &dyn EvmProvider, call: &Value) -> Result<u64> {
+    parse_u64(&provider.request("eth_estimateGas", json!([call])).await?)
+}
+
+/// Return data of `call` run against `block`
+pub async fn call(provider: &dyn EvmProvider, call: &Value, block: &str) -> Result<Vec<u8>> {
+    let result = provider.request("eth_call", json!([call, block])).await?;
+    from_hex(result.as_str().unwrap_or("0x"))
+}
+
+/// Base fees and one reward percentile over recent blocks
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeeHistory {
+    /// One per block, plus the next block's at the end
+    pub base_fees: Vec<u128>,
+    /// The requested percentile of priority fees, one per block
+    pub rewards: Vec<u128>,
+}
+
+pub async fn fee_history(
+    provider: &dyn EvmProvider,
+    blocks: u64,
+    percentile: f64,
+) -> Result<FeeHistory> {
+    let history = provider
+        .request(
+            "eth_feeHistory",
+            json!([quantity(blocks as u128), "latest", [percentile]]),
+        )
+        .await?;
+    let base_fees = history["baseFeePerGas"]
+        .as_array()
+        .context("Fee history has no base fees; the chain may predate EIP-1559")?
+        .iter()
+        .map(parse_quantity)
+        .collect::<Result<_>>()?;
+    let rewards = history["reward"]
+        .as_array()
+        .map(|blocks| {
+            blocks
+                .iter()
+                .filter_map(|block| block.get(0))
+                .map(parse_quantity)
+                .collect::<Result<_>>()
+        })
+        .transpose()?
+        .unwrap_or_default();
+    Ok(FeeHistory { base_fees, rewards })
+}
+
+/// Broadcast a signed transaction; the node answers with its hash
+pub async fn send_raw(provider: &dyn EvmProvider, raw: &[u8]) -> Result<String> {
+    let hash = provider
+        .request("eth_sendRawTransaction", json!([to_hex(raw)]))
+        .await?;
+    hash.as_str()
+        .map(str::to_string)
+        .context("eth_sendRawTransaction returned no hash")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Receipt {
+    pub transaction_hash: String,
+    pub block_number: u64,
+    pub success: bool,
+    pub gas_used: u64,
+    pub effective_gas_price: u128,
+}
+
+/// `None` while the transaction is not in a block
+pub async fn receipt(provider: &dyn EvmProvider, hash: &str) -> Result<Option<Receipt>> {
+    let receipt = provider
+        .request("eth_getTransactionReceipt", json!([hash]))
+        .await?;
+    if receipt.is_null() || receipt["blockNumber"].is_null() {
+        return Ok(None);
+    }
+    Ok(Some(Receipt {
+        transaction_hash: hash.to_string(),
+        block_number: parse_u64(&receipt["blockNumber"])?,
+        success: parse_quantity(&receipt["status"])? == 1,
+        gas_used: parse_u64(&receipt["gasUsed"])?,
+        effective_gas_price: parse_quantity(&receipt["effectiveGasPrice"])?,
+    }))
+}
+
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// Readable form of revert data: the message of `Error(string)`, the code
+/// of `Panic(uint256)`, or the selector of a custom error
+pub fn decode_revert(data: &[u8]) -> Option<String> {
+    if data.len() < 4 {
+        return None;
+    }
+    let (selector, body) = data.split_at(4);
+    if selector == ERROR_SELECTOR {
+        let offset = word(body, 0)?;
+        let len = word(body, offset)?;
+        let start = offset.checked_add(32)?;
+        let message = body.get(start..start.checked_add(len)?)?;
+        return Some(String::from_utf8_lossy(message).into_owned());
+    }
+    if selector == PANIC_SELECTOR {
+        let code = word(body, 0)?;
+        let meaning = match code {
+            0x01 => "assertion failed",
+            0x11 => "arithmetic overflow or underflow",
+            0x12 => "division by zero",
+            0x21 => "invalid enum value",
+            0x31 => "pop from an empty array",
+            0x32 => "array index out of bounds",
+            0x41 => "out of memory",
+            _ => "panic",
+        };
+        return Some(format!("{} (panic 0x{:02x})", meaning, code));
+    }
+    Some(format!("custom error {}", to_hex(selector)))
+}
+
+/// The 32-byte ABI word at `offset`, if it fits in a usize
+fn word(body: &[u8], offset: usize) -> Option<usize> {
+    let word = body.get(offset..offset.checked_add(32)?)?;
+    let (high, low) = word.split_at(24);
+    if high.iter().any(|&b| b != 0) {
+        return None;
+    }
+    Some(u64::from_be_bytes(low.try_into().ok()?) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_revert_reasons() {
+        // require(false, "Not enough balance")
+        let error = from_hex(concat!(
+            "0x08c379a0",
+            "0000000000000000000000000000000000000000000000000000000000000020",
+            "0000000000000000000000000000000000000000000000000000000000000012",
+            "4e6f7420656e6f7567682062616c616e63650000000000000000000000000000",
+        ))
+        .unwrap();
+        assert_eq!(decode_revert(&error).unwrap(), "Not enough balance");
+
+        let panic = from_hex(concat!(
+            "0x4e487b71",
+            "0000000000000000000000000000000000000000000000000000000000000011",
+        ))
+        .unwrap();
+        assert_eq!(
+            decode_revert(&panic).unwrap(),
+            "arithmetic overflow or underflow (panic 0x11)"
+        );
+
+        assert_eq!(
+            decode_revert(&[0xde, 0xad, 0xbe, 0xef]).unwrap(),
+            "custom error 0xdeadbeef"
+        );
+        assert_eq!(decode_revert(&[]), None);
+        // A truncated Error(string) is not trusted
+        assert_eq!(decode_revert(&error[..40]), None);
+
+        let rpc = RpcError {
+            code: 3,
+            message: "execution reverted: Ownable: caller is not the owner".to_string(),
+            data: None,
+        };
+        assert_eq!(
+            rpc.revert_reason().unwrap(),
+            "Ownable: caller is not the owner"
+        );
+    }
+
+    #[test]
+    fn test_quantities() {
+        assert_eq!(quantity(0), "0x0");
+        assert_eq!(quantity(21_000), "0x5208");
+        assert_eq!(parse_quantity(&json!("0x5208")).unwrap(), 21_000);
+        assert!(parse_quantity(&json!(21000)).is_err());
+    }
+}
@@ -0,0 +1,289 @@
+//! Estimate, sign, broadcast, and wait for a transaction to confirm
+
+use super::fees::{self, FeeStrategy, Fees};
+use super::nonce::NonceTracker;
+use super::rpc::{self, EvmProvider, Receipt, RpcError};
+use super::signing::{to_hex, Address, Eip1559Transaction, Signer};
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+
+/// What to send; unset gas and fee fields are estimated
+#[derive(Debug, Clone, Default)]
+pub struct Request {
+    /// `None` deploys `data` as a contract
+    pub to: Option<Address>,
+    pub value: u128,
+    pub data: Vec<u8>,
+    pub gas_limit: Option<u64>,
+    pub max_fee_per_gas: Option<u128>,
+    pub max_priority_fee_per_gas: Option<u128>,
+}
+
+impl Request {
+    /// The request as an `eth_call` / `eth_estimateGas` call object
+    pub fn call(&self, from: Address) -> Value {
+        let mut call = json!({
+            "from": from.to_string(),
+            "value": rpc::quantity(self.value),
+            "data": to_hex(&self.data),
+        });
+        if let Some(to) = self.to {
+            call["to"] = json!(to.to_string());
+        }
+        call
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SendOptions {
+    /// Estimated gas is multiplied by this for the gas limit
+    pub gas_multiplier: f64,
+    pub fee_strategy: FeeStrategy,
+    /// Highest max fee per gas, in wei
+    pub fee_cap: Option<u128>,
+    /// Blocks, counting the one including the transaction, to wait for
+    pub confirmations: u64,
+    pub receipt_timeout: Duration,
+    pub poll_interval: Duration,
+    /// Times to re-sign with a fresh nonce when the node rejects one
+    pub nonce_retries: u32,
+}
+
+impl Default for SendOptions {
+    fn default() -> Self {
+        Self {
+            gas_multiplier: 1.2,
+            fee_strategy: FeeStrategy::Normal,
+            fee_cap: None,
+            confirmations: 1,
+            receipt_timeout: Duration::from_secs(120),
+            poll_interval: Duration::from_secs(1),
+            nonce_retries: 3,
+        }
+    }
+}
+
+/// A transaction the node accepted
+#[derive(Debug, Clone)]
+pub struct Sent {
+    pub hash: String,
+    pub from: Address,
+    pub nonce: u64,
+    pub gas_limit: u64,
+    pub fees: Fees,
+}
+
+#[derive(Debug, Clone)]
+pub enum Outcome {
+    /// Mined, succeeded, and buried under enough blocks
+    Confirmed { sent: Sent, receipt: Receipt },
+    /// Gas estimation showed it would revert, so nothing was sent, or it
+    /// reverted once mined
+    Reverted {
+        sent: Option<Sent>,
+        receipt: Option<Receipt>,
+        reason: String,
+    },
+    /// Broadcast but not confirmed in time; it may still be mined
+    TimedOut {
+        sent: Sent,
+        receipt: Option<Receipt>,
+    },
+}
+
+/// Gas limit for `request`: its own, or the estimate times the multiplier.
+/// `Err(reason)` inside means the estimate reverted.
+pub async fn gas_limit(
+    provider: &dyn EvmProvider,
+    from: Address,
+    request: &Request,
+    multiplier: f64,
+) -> Result<std::result::Result<u64, String>> {
+    if let Some(limit) = request.gas_limit {
+        return Ok(Ok(limit));
+    }
+    match rpc::estimate_gas(provider, &request.call(from)).await {
+        Ok(estimate) => Ok(Ok((estimate as f64 * multiplier.max(1.0)).ceil() as u64)),
+        Err(e) => match rpc::revert_reason(&e) {
+            Some(reason) => Ok(Err(reason)),
+            None => Err(e.context("Gas estimation failed")),
+        },
+    }
+}
+
+/// Fees for `request`, estimating those it doesn't set
+pub async fn fees_for(
+    provider: &dyn EvmProvider,
+    request: &Request,
+    options: &SendOptions,
+) -> Result<Fees> {
+    if let (Some(max_fee), Some(priority_fee)) =
+        (request.max_fee_per_gas, request.max_priority_fee_per_gas)
+    {
+        return Ok(Fees {
+            max_fee_per_gas: max_fee,
+            max_priority_fee_per_gas: priority_fee.min(max_fee),
+        });
+    }
+    let suggested = fees::estimate(provider, options.fee_strategy, options.fee_cap).await?;
+    let max_fee = request.max_fee_per_gas.unwrap_or(suggested.max_fee_per_gas);
+    Ok(Fees {
+        max_fee_per_gas: max_fee,
+        max_priority_fee_per_gas: request
+            .max_priority_fee_per_gas
+            .unwrap_or(suggested.max_priority_fee_per_gas)
+            .min(max_fee),
+    })
+}
+
+pub async fn send(
+    provider: &dyn EvmProvider,
+    signer: &Signer,
+    nonces: &NonceTracker,
+    chain_id: u64,
+    request: &Request,
+    options: &SendOptions,
+) -> Result<Outcome> {
+    let from = signer.address();
+    let gas_limit = match gas_limit(provider, from, request, options.gas_multiplier).await? {
+        Ok(limit) => limit,
+        Err(reason) => {
+            return Ok(Outcome::Reverted {
+                sent: None,
+                receipt: None,
+                reason,
+            })
+        }
+    };
+    let fees = fees_for(provider, request, options).await?;
+    let sent = broadcast(
+        provider, signer, nonces, chain_id, request, gas_limit, fees, options,
+    )
+    .await?;
+    wait(provider, sent, request, options).await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn broadcast(
+    provider: &dyn EvmProvider,
+    signer: &Signer,
+    nonces: &NonceTracker,
+    chain_id: u64,
+    request: &Request,
+    gas_limit: u64,
+    fees: Fees,
+    options: &SendOptions,
+) -> Result<Sent> {
+    let from = signer.address();
+    let mut retries = 0;
+    loop {
+        let slot = nonces.reserve(from, provider).await?;
+        let transaction = Eip1559Transaction {
+            chain_id,
+            nonce: slot.nonce(),
+            max_priority_fee_per_gas: fees.max_priority_fee_per_gas,
+            max_fee_per_gas: fees.max_fee_per_gas,
+            gas_limit,
+            to: request.to,
+            value: request.value,
+            data: request.data.clone(),
+        };
+        let raw = transaction.encode_signed(signer)?;
+
+        match rpc::send_raw(provider, &raw).await {
+            Ok(hash) => {
+                let nonce = slot.nonce();
+                slot.commit();
+                return Ok(Sent {
+                    hash,
+                    from,
+                    nonce,
+                    gas_limit,
+                    fees,
+                });
+            }
+            Err(e)
+                if retries < options.nonce_retries
+                    && e.downcast_ref::<RpcError>()
+                        .is_some_and(RpcError::is_nonce_error) =>
+            {
+                tracing::warn!("Nonce {} for {} was rejected: {}", slot.nonce(), from, e);
+                slot.reset();
+                retries += 1;
+            }
+            Err(e) => return Err(e.context("Node rejected the transaction")),
+        }
+    }
+}
+
+/// Poll for the receipt until it has `confirmations` or the timeout passes.
+/// The receipt is fetched again each time, so a reorg that drops the
+/// transaction puts it back to waiting.
+async fn wait(
+    provider: &dyn EvmProvider,
+    sent: Sent,
+    request: &Request,
+    options: &SendOptions,
+) -> Result<Outcome> {
+    let deadline = Instant::now() + options.receipt_timeout;
+    loop {
+        let receipt = rpc::receipt(provider, &sent.hash).await?;
+        if let Some(receipt) = &receipt {
+            if !receipt.success {
+                let reason = replay_revert(provider, &sent, request, receipt).await;
+                return Ok(Outcome::Reverted {
+                    sent: Some(sent),
+                    receipt: Some(receipt.clone()),
+                    reason,
+                });
+            }
+            let head = rpc::block_number(provider).await?;
+            if head + 1 >= receipt.block_number + options.confirmations.max(1) {
+                let receipt = receipt.clone();
+                return Ok(Outcome::Confirmed { sent, receipt });
+            }
+        }
+
+        if Instant::now() >= deadline {
+            return Ok(Outcome::TimedOut { sent, receipt });
+        }
+        tokio::time::sleep(options.poll_interval).await;
+    }
+}
+
+/// Receipts don't carry revert data, so rerun the call on the state before
+/// the transaction's block to get it
+async fn replay_revert(
+    provider: &dyn EvmProvider,
+    sent: &Sent,
+    request: &Request,
+    receipt: &Receipt,
+) -> String {
+    let mut call = request.call(sent.from);
+    call["gas"] = json!(rpc::quantity(sent.gas_limit as u128));
+    let block = rpc::quantity(receipt.block_number.saturating_sub(1) as u128);
+    let replayed = rpc::call(provider, &call, &block).await;
+    match replayed.as_ref().err().and_then(rpc::revert_reason) {
+        Some(reason) => reason,
+        None if receipt.gas_used >= sent.gas_limit => {
+            format!("ran out of gas (limit {})", sent.gas_limit)
+        }
+        None => "reverted without a reason".to_string(),
+    }
+}
+
+/// The amount of ether `wei` is, for display
+pub fn ether(wei: u128) -> f64 {
+    wei as f64 / 1e18
+}
+
+/// Parse a wei amount written in decimal or `0x` hex
+pub fn parse_wei(value: &str) -> Result<u128> {
+    let value = value.trim();
+    match value.strip_prefix("0x") {
+        Some(digits) => u128::from_str_radix(digits, 16),
+        None => value.parse(),
+    }
+    .with_context(|| format!("'{}' is not an amount of wei", value))
+}
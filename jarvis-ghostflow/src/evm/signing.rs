@@ -0,0 +1,267 @@
+//! Keys, addresses, and EIP-1559 transaction encoding
+
+use anyhow::{Context, Result};
+use k256::ecdsa::{SigningKey, VerifyingKey};
+use sha3::{Digest, Keccak256};
+use std::fmt;
+use std::str::FromStr;
+
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    Keccak256::digest(data).into()
+}
+
+/// Hex with a `0x` prefix, as JSON-RPC expects data
+pub fn to_hex(bytes: &[u8]) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+/// Bytes from hex with or without a `0x` prefix
+pub fn from_hex(value: &str) -> Result<Vec<u8>> {
+    let digits = value.strip_prefix("0x").unwrap_or(value);
+    hex::decode(digits).with_context(|| format!("'{}' is not hex", value))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Address(pub [u8; 20]);
+
+impl Address {
+    pub fn from_public_key(key: &VerifyingKey) -> Self {
+        let point = key.to_encoded_point(false);
+        let hash = keccak256(&point.as_bytes()[1..]);
+        let mut address = [0u8; 20];
+        address.copy_from_slice(&hash[12..]);
+        Self(address)
+    }
+}
+
+impl FromStr for Address {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let bytes = from_hex(s)?;
+        let address: [u8; 20] = bytes
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("'{}' is not a 20-byte address", s))?;
+        Ok(Self(address))
+    }
+}
+
+/// Lowercase hex; checksummed casing is accepted on input but not produced
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&to_hex(&self.0))
+    }
+}
+
+impl fmt::Debug for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// A secp256k1 signing key and the address it controls
+pub struct Signer {
+    key: SigningKey,
+    address: Address,
+}
+
+impl Signer {
+    pub fn from_hex(key: &str) -> Result<Self> {
+        let bytes = from_hex(key.trim()).context("Signing key is not hex")?;
+        let key = SigningKey::from_slice(&bytes).context("Signing key is not a secp256k1 key")?;
+        let address = Address::from_public_key(key.verifying_key());
+        Ok(Self { key, address })
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// `(y_parity, r, s)` over a 32-byte hash
+    pub fn sign(&self, hash: &[u8; 32]) -> Result<(u8, [u8; 32], [u8; 32])> {
+        let (signature, recovery) = self
+            .key
+            .sign_prehash_recoverable(hash)
+            .context("Failed to sign transaction")?;
+        let bytes = signature.to_bytes();
+        let mut r = [0u8; 32];
+        let mut s = [0u8; 32];
+        r.copy_from_slice(&bytes[..32]);
+        s.copy_from_slice(&bytes[32..]);
+        Ok((recovery.is_y_odd() as u8, r, s))
+    }
+}
+
+/// Never prints the key
+impl fmt::Debug for Signer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Signer")
+            .field("address", &self.address)
+            .finish()
+    }
+}
+
+/// A type-2 transaction with an empty access list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Eip1559Transaction {
+    pub chain_id: u64,
+    pub nonce: u64,
+    pub max_priority_fee_per_gas: u128,
+    pub max_fee_per_gas: u128,
+    pub gas_limit: u64,
+    /// `None` deploys a contract
+    pub to: Option<Address>,
+    pub value: u128,
+    pub data: Vec<u8>,
+}
+
+impl Eip1559Transaction {
+    fn fields(&self) -> Vec<Vec<u8>> {
+        vec![
+            rlp::uint(self.chain_id as u128),
+            rlp::uint(self.nonce as u128),
+            rlp::uint(self.max_priority_fee_per_gas),
+            rlp::uint(self.max_fee_per_gas),
+            rlp::uint(self.gas_limit as u128),
+            rlp::bytes(self.to.as_ref().map_or(&[][..], |to| &to.0[..])),
+            rlp::uint(self.value),
+            rlp::bytes(&self.data),
+            rlp::list(&[]),
+        ]
+    }
+
+    /// What gets signed: `keccak256(0x02 || rlp(fields))`
+    pub fn signing_hash(&self) -> [u8; 32] {
+        let mut payload = vec![0x02];
+        payload.extend(rlp::list(&self.fields()));
+        keccak256(&payload)
+    }
+
+    /// The raw transaction for `eth_sendRawTransaction`; its keccak256 is
+    /// the transaction hash
+    pub fn encode_signed(&self, signer: &Signer) -> Result<Vec<u8>> {
+        let (y_parity, r, s) = signer.sign(&self.signing_hash())?;
+        let mut fields = self.fields();
+        fields.push(rlp::uint(y_parity as u128));
+        fields.push(rlp::bytes(strip_zeros(&r)));
+        fields.push(rlp::bytes(strip_zeros(&s)));
+
+        let mut raw = vec![0x02];
+        raw.extend(rlp::list(&fields));
+        Ok(raw)
+    }
+}
+
+fn strip_zeros(bytes: &[u8]) -> &[u8] {
+    let start = bytes.iter().position(|&b| b != 0).unwrap_or(bytes.len());
+    &bytes[start..]
+}
+
+/// The subset of RLP that transactions need
+mod rlp {
+    pub fn bytes(data: &[u8]) -> Vec<u8> {
+        if data.len() == 1 && data[0] < 0x80 {
+            return data.to_vec();
+        }
+        let mut out = header(0x80, data.len());
+        out.extend_from_slice(data);
+        out
+    }
+
+    /// Integers are big-endian without leading zeros; zero is empty
+    pub fn uint(value: u128) -> Vec<u8> {
+        bytes(super::strip_zeros(&value.to_be_bytes()))
+    }
+
+    /// A list of already encoded items
+    pub fn list(items: &[Vec<u8>]) -> Vec<u8> {
+        let payload: Vec<u8> = items.concat();
+        let mut out = header(0xc0, payload.len());
+        out.extend(payload);
+        out
+    }
+
+    fn header(offset: u8, len: usize) -> Vec<u8> {
+        if len <= 55 {
+            return vec![offset + len as u8];
+        }
+        let len_bytes = (len as u64).to_be_bytes();
+        let len_bytes = super::strip_zeros(&len_bytes);
+        let mut out = vec![offset + 55 + len_bytes.len() as u8];
+        out.extend_from_slice(len_bytes);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The first account anvil and hardhat create
+    const ANVIL_KEY: &str = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+
+    #[test]
+    fn test_rlp_vectors() {
+        assert_eq!(hex::encode(rlp::bytes(b"dog")), "83646f67");
+        assert_eq!(
+            hex::encode(rlp::list(&[rlp::bytes(b"cat"), rlp::bytes(b"dog")])),
+            "c88363617483646f67"
+        );
+        assert_eq!(hex::encode(rlp::uint(0)), "80");
+        assert_eq!(hex::encode(rlp::uint(15)), "0f");
+        assert_eq!(hex::encode(rlp::uint(1024)), "820400");
+        assert_eq!(hex::encode(rlp::list(&[])), "c0");
+
+        let long = [b'a'; 56];
+        assert_eq!(&rlp::bytes(&long)[..2], &[0xb8, 56]);
+    }
+
+    #[test]
+    fn test_keys_and_addresses() {
+        assert_eq!(
+            hex::encode(keccak256(b"")),
+            "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+        );
+        let signer = Signer::from_hex(ANVIL_KEY).unwrap();
+        assert_eq!(
+            signer.address().to_string(),
+            "0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266"
+        );
+        assert_eq!(
+            "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266"
+                .parse::<Address>()
+                .unwrap(),
+            signer.address()
+        );
+        assert!(!format!("{:?}", signer).contains("ac0974"));
+        assert!("0x1234".parse::<Address>().is_err());
+    }
+
+    #[test]
+    fn test_signature_recovers_the_sender() {
+        let signer = Signer::from_hex(ANVIL_KEY).unwrap();
+        let transaction = Eip1559Transaction {
+            chain_id: 31337,
+            nonce: 0,
+            max_priority_fee_per_gas: 1_000_000_000,
+            max_fee_per_gas: 2_000_000_000,
+            gas_limit: 21_000,
+            to: Some(
+                "0x70997970c51812dc3a010c7d01b50e0d17dc79c8"
+                    .parse()
+                    .unwrap(),
+            ),
+            value: 1_000_000_000_000_000_000,
+            data: vec![],
+        };
+        let raw = transaction.encode_signed(&signer).unwrap();
+        assert_eq!(raw[0], 0x02);
+
+        let hash = transaction.signing_hash();
+        let (y_parity, r, s) = signer.sign(&hash).unwrap();
+        let signature = k256::ecdsa::Signature::from_scalars(r, s).unwrap();
+        let recovery = k256::ecdsa::RecoveryId::new(y_parity == 1, false);
+        let key = VerifyingKey::recover_from_prehash(&hash, &signature, recovery).unwrap();
+        assert_eq!(Address::from_public_key(&key), signer.address());
+    }
+}
@@ -151,6 +151,12 @@ impl FFIHandle {
     pub fn as_ptr(&self) -> *mut c_void {
         self.ptr
     }
+    
+    /// Give up the pointer, e.g. to hand it to the component's destructor;
+    /// the handle is null afterwards
+    pub fn take(&mut self) -> *mut c_void {
+        std::mem::replace(&mut self.ptr, ptr::null_mut())
+    }
 }
 
 impl Drop for FFIHandle {
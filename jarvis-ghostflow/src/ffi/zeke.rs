@@ -0,0 +1,386 @@
+/// FFI bindings for Zeke - the Zig code assistant's completion and analysis
+///
+/// The library exports a handle-based C API:
+///
+/// ```c
+/// int  zeke_init(const char *config_json, void **engine);
+/// int  zeke_complete(void *engine, const char *request_json, char **response_json);
+/// int  zeke_analyze(void *engine, const char *request_json, char **response_json);
+/// int  zeke_version(char **version);
+/// void zeke_free_string(char *s);
+/// void zeke_destroy(void *engine);
+/// ```
+///
+/// Each `int` is an `FFIStatus` code. Returned strings belong to the library
+/// and go back through `zeke_free_string`; on failure the response, if set,
+/// is an error message rather than JSON. The library is loaded at runtime, so
+/// where it isn't installed `ZekeEngine::detect` reports it unavailable.
+use super::{AsyncFFIWrapper, FFIConfig, FFIError, FFIHandle, FFIResult, FFIStatus, FFIUtils};
+use libloading::Library;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::ffi::{c_char, c_int, c_void, CStr};
+use std::ptr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Tried in order when `FFIConfig::library_path` is empty
+pub const DEFAULT_LIBRARY_PATHS: &[&str] = &[
+    "libzeke.so",
+    "/usr/local/lib/libzeke.so",
+    "/usr/lib/libzeke.so",
+];
+
+type ZekeInitFn = unsafe extern "C" fn(*const c_char, *mut *mut c_void) -> c_int;
+type ZekeRequestFn = unsafe extern "C" fn(*mut c_void, *const c_char, *mut *mut c_char) -> c_int;
+type ZekeVersionFn = unsafe extern "C" fn(*mut *mut c_char) -> c_int;
+type ZekeFreeStringFn = unsafe extern "C" fn(*mut c_char);
+type ZekeDestroyFn = unsafe extern "C" fn(*mut c_void);
+
+/// Code completion at a cursor between `prefix` and `suffix`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompletionRequest {
+    pub language: String,
+    pub prefix: String,
+    #[serde(default)]
+    pub suffix: String,
+    pub path: Option<String>,
+    pub max_suggestions: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Completion {
+    pub text: String,
+    #[serde(default)]
+    pub confidence: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompletionResponse {
+    pub completions: Vec<Completion>,
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalysisRequest {
+    pub language: String,
+    pub code: String,
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CodeIssue {
+    pub line: u32,
+    #[serde(default)]
+    pub column: u32,
+    pub severity: String,
+    pub message: String,
+    #[serde(default)]
+    pub suggestion: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalysisResponse {
+    pub issues: Vec<CodeIssue>,
+    #[serde(default)]
+    pub summary: String,
+}
+
+/// Entry points resolved from the loaded library. The function pointers
+/// stay valid for as long as `_library`, which is dropped with them.
+struct ZekeApi {
+    init: ZekeInitFn,
+    complete: ZekeRequestFn,
+    analyze: ZekeRequestFn,
+    version: ZekeVersionFn,
+    free_string: ZekeFreeStringFn,
+    destroy: ZekeDestroyFn,
+    _library: Library,
+}
+
+impl ZekeApi {
+    fn load(path: &str) -> FFIResult<Self> {
+        let library = unsafe { Library::new(path) }
+            .map_err(|e| FFIError::LibraryNotFound(format!("{}: {}", path, e)))?;
+        unsafe {
+            Ok(Self {
+                init: symbol(&library, "zeke_init")?,
+                complete: symbol(&library, "zeke_complete")?,
+                analyze: symbol(&library, "zeke_analyze")?,
+                version: symbol(&library, "zeke_version")?,
+                free_string: symbol(&library, "zeke_free_string")?,
+                destroy: symbol(&library, "zeke_destroy")?,
+                _library: library,
+            })
+        }
+    }
+
+    /// Copy out a string the library returned and give it back
+    unsafe fn take_string(&self, ptr: *mut c_char) -> FFIResult<String> {
+        if ptr.is_null() {
+            return Err(FFIError::NullPointer);
+        }
+        let text = FFIUtils::c_string_to_rust(ptr);
+        (self.free_string)(ptr);
+        text
+    }
+}
+
+unsafe fn symbol<T: Copy>(library: &Library, name: &str) -> FFIResult<T> {
+    let symbol_name = format!("{}\0", name);
+    library
+        .get::<T>(symbol_name.as_bytes())
+        .map(|symbol| *symbol)
+        .map_err(|_| FFIError::FunctionNotFound(name.to_string()))
+}
+
+/// Owns the engine handle and destroys it on drop. Calls are serialized:
+/// the library doesn't promise that one engine may be used concurrently.
+struct EngineHandle {
+    api: Arc<ZekeApi>,
+    handle: Mutex<FFIHandle>,
+}
+
+// The raw handle is only touched under the mutex
+unsafe impl Send for EngineHandle {}
+unsafe impl Sync for EngineHandle {}
+
+impl EngineHandle {
+    fn call(&self, entry: ZekeRequestFn, request: &CStr) -> FFIResult<String> {
+        let handle = self
+            .handle
+            .lock()
+            .map_err(|_| FFIError::ZigRuntimeError("Zeke engine lock poisoned".to_string()))?;
+        let mut response: *mut c_char = ptr::null_mut();
+        let status = FFIStatus::from(unsafe { entry(handle.as_ptr(), request.as_ptr(), &mut response) });
+        if status.is_success() {
+            return unsafe { self.api.take_string(response) };
+        }
+
+        let detail = if response.is_null() {
+            String::new()
+        } else {
+            unsafe { self.api.take_string(response) }
+                .map(|message| format!(": {}", message))
+                .unwrap_or_default()
+        };
+        Err(FFIError::ZigRuntimeError(format!("Zeke returned {:?}{}", status, detail)))
+    }
+}
+
+impl Drop for EngineHandle {
+    fn drop(&mut self) {
+        let handle = self.handle.get_mut().unwrap_or_else(|e| e.into_inner());
+        let engine = handle.take();
+        if !engine.is_null() {
+            unsafe { (self.api.destroy)(engine) };
+        }
+    }
+}
+
+/// A Zeke engine; clones share it, and the last one destroys it
+#[derive(Clone)]
+pub struct ZekeEngine {
+    engine: Arc<EngineHandle>,
+    timeout: Duration,
+    version: String,
+}
+
+/// Whether Zeke can serve requests on this machine
+pub enum ZekeAvailability {
+    Available(ZekeEngine),
+    /// Why not, e.g. the library isn't installed
+    Unavailable(String),
+}
+
+impl ZekeAvailability {
+    pub fn engine(&self) -> Option<&ZekeEngine> {
+        match self {
+            Self::Available(engine) => Some(engine),
+            Self::Unavailable(_) => None,
+        }
+    }
+}
+
+impl ZekeEngine {
+    /// Load the library from `config.library_path` (or the default
+    /// locations) and create an engine configured with `config`
+    pub fn load(config: &FFIConfig) -> FFIResult<Self> {
+        let paths: Vec<&str> = if config.library_path.is_empty() {
+            DEFAULT_LIBRARY_PATHS.to_vec()
+        } else {
+            vec![config.library_path.as_str()]
+        };
+
+        let mut last_error = FFIError::LibraryNotFound("libzeke.so".to_string());
+        for path in paths {
+            match ZekeApi::load(path) {
+                Ok(api) => return Self::start(Arc::new(api), config),
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Like `load`, but a missing or broken library is an answer rather than
+    /// an error, so callers can fall back to something else
+    pub fn detect(config: &FFIConfig) -> ZekeAvailability {
+        match Self::load(config) {
+            Ok(engine) => ZekeAvailability::Available(engine),
+            Err(e) => {
+                tracing::info!("Zeke unavailable: {}", e);
+                ZekeAvailability::Unavailable(e.to_string())
+            }
+        }
+    }
+
+    fn start(api: Arc<ZekeApi>, config: &FFIConfig) -> FFIResult<Self> {
+        let config_json = FFIUtils::struct_to_json_c_string(config)?;
+        let mut engine: *mut c_void = ptr::null_mut();
+        FFIStatus::from(unsafe { (api.init)(config_json.as_ptr(), &mut engine) }).to_result()?;
+        if engine.is_null() {
+            return Err(FFIError::NullPointer);
+        }
+
+        let mut version_ptr: *mut c_char = ptr::null_mut();
+        let version = if FFIStatus::from(unsafe { (api.version)(&mut version_ptr) }).is_success() {
+            unsafe { api.take_string(version_ptr) }.unwrap_or_default()
+        } else {
+            String::new()
+        };
+
+        Ok(Self {
+            engine: Arc::new(EngineHandle {
+                api,
+                handle: Mutex::new(FFIHandle::new(engine)),
+            }),
+            timeout: Duration::from_millis(config.timeout_ms.max(1) as u64),
+            version,
+        })
+    }
+
+    /// Version string the library reports
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    pub async fn complete(&self, request: &CompletionRequest) -> FFIResult<CompletionResponse> {
+        self.request(|api| api.complete, request).await
+    }
+
+    pub async fn analyze(&self, request: &AnalysisRequest) -> FFIResult<AnalysisResponse> {
+        self.request(|api| api.analyze, request).await
+    }
+
+    /// Send `request` as JSON to the entry point `select` picks, on the
+    /// blocking pool and bounded by the configured timeout
+    async fn request<Req, Resp>(&self, select: fn(&ZekeApi) -> ZekeRequestFn, request: &Req) -> FFIResult<Resp>
+    where
+        Req: Serialize,
+        Resp: DeserializeOwned + Send + 'static,
+    {
+        let request = FFIUtils::struct_to_json_c_string(request)?;
+        let engine = self.engine.clone();
+        AsyncFFIWrapper::execute_with_timeout(
+            move || {
+                let response = engine.call(select(&engine.api), &request)?;
+                serde_json::from_str(&response).map_err(FFIError::JsonSerializationFailed)
+            },
+            self.timeout,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_library_is_unavailable() {
+        let config = FFIConfig {
+            library_path: "/nonexistent/libzeke.so".to_string(),
+            ..FFIConfig::default()
+        };
+        match ZekeEngine::detect(&config) {
+            ZekeAvailability::Unavailable(reason) => assert!(reason.contains("/nonexistent/libzeke.so")),
+            ZekeAvailability::Available(_) => panic!("loaded a library that doesn't exist"),
+        }
+    }
+
+    /// Against the C shim in `tests/zeke_mock.c`, built by build.rs
+    #[cfg(feature = "zeke-mock")]
+    #[tokio::test]
+    async fn test_engine_against_mock_library() {
+        let config = FFIConfig {
+            library_path: env!("ZEKE_MOCK_LIB").to_string(),
+            timeout_ms: 200,
+            ..FFIConfig::default()
+        };
+        // Held open so the shim's counter survives engines unloading it
+        let mock = unsafe { Library::new(env!("ZEKE_MOCK_LIB")) }.unwrap();
+        let destroyed_count: unsafe extern "C" fn() -> c_int =
+            unsafe { symbol(&mock, "zeke_mock_destroyed") }.unwrap();
+        let destroyed = || unsafe { destroyed_count() };
+
+        let engine = ZekeEngine::load(&config).unwrap();
+        assert_eq!(engine.version(), "zeke-mock 0.1.0");
+
+        let completion = engine
+            .complete(&CompletionRequest {
+                language: "rust".to_string(),
+                prefix: "fn main() {\n    ".to_string(),
+                suffix: "\n}".to_string(),
+                path: None,
+                max_suggestions: 1,
+            })
+            .await
+            .unwrap();
+        assert_eq!(completion.completions[0].text, "println!(\"done\");");
+        assert_eq!(completion.model.as_deref(), Some("zeke-mock"));
+
+        let analysis = engine
+            .analyze(&AnalysisRequest {
+                language: "rust".to_string(),
+                code: "let x = y.unwrap();".to_string(),
+                path: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(analysis.issues.len(), 1);
+        assert_eq!(analysis.issues[0].suggestion.as_deref(), Some("use ? instead"));
+
+        // The library's error message comes through
+        let error = engine
+            .complete(&CompletionRequest {
+                language: String::new(),
+                prefix: String::new(),
+                suffix: String::new(),
+                path: None,
+                max_suggestions: 1,
+            })
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("unsupported language"), "{}", error);
+
+        // Only the last clone destroys the engine
+        let before = destroyed();
+        let clone = engine.clone();
+        drop(engine);
+        assert_eq!(destroyed(), before);
+        drop(clone);
+        assert_eq!(destroyed(), before + 1);
+
+        let slow = ZekeEngine::load(&config).unwrap();
+        let timed_out = slow
+            .analyze(&AnalysisRequest {
+                language: "rust".to_string(),
+                code: "SLOW".to_string(),
+                path: None,
+            })
+            .await
+            .unwrap_err();
+        assert!(timed_out.to_string().contains("timed out"), "{}", timed_out);
+    }
+}
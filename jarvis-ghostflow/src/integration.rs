@@ -1,20 +1,34 @@
 use anyhow::{Context, Result};
 use axum::Router;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use jarvis_core::tls::{self, ReloadableTlsAcceptor, TlsConfig};
 use serde::{Deserialize, Serialize};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
 use std::net::SocketAddr;
+use std::str::FromStr;
 use std::sync::Arc;
 use tokio::sync::RwLock;
+use tower::Service;
 use tower_http::cors::CorsLayer;
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
+use jarvis_arch::{ArchAgent, ArchLinuxAgent};
 
 use crate::api::{ApiState, create_router};
+use crate::auth::{self, ApiKeyStore};
+use crate::budget::{BudgetConfig, Budgets};
+use crate::namespace::QuotaConfig;
+use crate::nodes::arch_operation::ArchOperationNode;
 use crate::workflow_engine::WorkflowEngine;
 use crate::network::QuicNetworkLayer;
 
+/// The Arch agent shared by every `jarvis.arch.operation` node
+pub type ArchAgentHandle = Arc<RwLock<dyn ArchAgent>>;
+
 /// Main integration bridge between Jarvis and GhostFlow
 pub struct JarvisGhostFlowIntegration {
     workflow_engine: Arc<WorkflowEngine>,
     network_layer: QuicNetworkLayer,
+    arch_agent: Option<ArchAgentHandle>,
     api_server: Option<ApiServer>,
     config: IntegrationConfig,
 }
@@ -28,6 +42,25 @@ pub struct IntegrationConfig {
     pub enable_websockets: bool,
     pub enable_metrics: bool,
     pub workflow_storage_path: String,
+    /// SQLite database holding hashed API keys and the API audit log
+    #[serde(default = "default_auth_database_path")]
+    pub auth_database_path: String,
+    /// TLS for the API server; plaintext when no certificate is configured
+    #[serde(default)]
+    pub tls: TlsConfig,
+    /// Start an Arch agent so workflows can run `jarvis.arch.operation` nodes
+    #[serde(default)]
+    pub arch_agent: bool,
+    /// Charge LLM calls to these budgets; usage isn't tracked without them
+    #[serde(default)]
+    pub budgets: Option<BudgetConfig>,
+    /// Per-namespace execution and memory quotas; unlimited by default
+    #[serde(default)]
+    pub quotas: QuotaConfig,
+}
+
+fn default_auth_database_path() -> String {
+    "./ghostflow-auth.db".to_string()
 }
 
 /// API server handle
@@ -38,9 +71,21 @@ pub struct ApiServer {
 impl JarvisGhostFlowIntegration {
     /// Create new integration instance
     pub async fn new(config: IntegrationConfig) -> Result<Self> {
+        config.tls.validate()
+            .context("Invalid API TLS configuration")?;
+        
+        let workflow_engine = match &config.budgets {
+            Some(budgets) => {
+                let budgets = Budgets::open(budgets.clone())
+                    .context("Failed to load LLM budget usage")?;
+                WorkflowEngine::with_budgets(Arc::new(budgets))
+            }
+            None => WorkflowEngine::new(),
+        };
         let workflow_engine = Arc::new(
-            WorkflowEngine::new()
+            workflow_engine
                 .context("Failed to create workflow engine")?
+                .with_quotas(config.quotas.clone()),
         );
         
         let network_layer = QuicNetworkLayer::new().await
@@ -49,6 +94,7 @@ impl JarvisGhostFlowIntegration {
         Ok(Self {
             workflow_engine,
             network_layer,
+            arch_agent: None,
             api_server: None,
             config,
         })
@@ -62,6 +108,19 @@ impl JarvisGhostFlowIntegration {
         self.workflow_engine.initialize_default_nodes().await
             .context("Failed to initialize default nodes")?;
         
+        if self.arch_agent.is_none() && self.config.arch_agent {
+            let mut agent = ArchLinuxAgent::new();
+            agent.initialize(jarvis_arch::Config::load_with_defaults()).await
+                .context("Failed to initialize Arch agent")?;
+            self.arch_agent = Some(Arc::new(RwLock::new(agent)));
+        }
+        if let Some(agent) = &self.arch_agent {
+            self.workflow_engine
+                .register_node(Box::new(ArchOperationNode::with_agent(agent.clone())))
+                .await;
+            info!("Arch agent operations available to workflows");
+        }
+        
         // Initialize network layer if enabled
         if self.config.enable_quic {
             self.network_layer.start().await
@@ -79,8 +138,23 @@ impl JarvisGhostFlowIntegration {
 
     /// Start the API server
     async fn start_api_server(&mut self) -> Result<()> {
+        let options = SqliteConnectOptions::from_str(&self.config.auth_database_path)
+            .context("Invalid auth database path")?
+            .create_if_missing(true);
+        let pool = SqlitePoolOptions::new()
+            .connect_with(options)
+            .await
+            .context("Failed to open auth database")?;
+        let api_keys = Arc::new(
+            ApiKeyStore::open(pool).await
+                .context("Failed to initialize API key store")?
+        );
+        auth::bootstrap(&api_keys).await
+            .context("Failed to create bootstrap admin key")?;
+
         let api_state = ApiState {
             workflow_engine: self.workflow_engine.clone(),
+            api_keys,
         };
         
         let app = create_router(api_state)
@@ -92,10 +166,22 @@ impl JarvisGhostFlowIntegration {
         let listener = tokio::net::TcpListener::bind(address).await
             .context("Failed to bind API server")?;
         
-        let handle = tokio::spawn(async move {
-            axum::serve(listener, app).await
-                .context("API server error")
-        });
+        let handle = if self.config.tls.is_enabled() {
+            let acceptor = Arc::new(
+                ReloadableTlsAcceptor::new(self.config.tls.clone())
+                    .context("Failed to load API TLS certificates")?
+            );
+            acceptor.reload_on_sighup()?;
+            info!("TLS enabled for GhostFlow API (client certificates required: {})",
+                self.config.tls.requires_client_cert());
+            
+            tokio::spawn(serve_tls(listener, app, acceptor))
+        } else {
+            tokio::spawn(async move {
+                axum::serve(listener, app).await
+                    .context("API server error")
+            })
+        };
         
         self.api_server = Some(ApiServer { handle });
         
@@ -113,6 +199,18 @@ impl JarvisGhostFlowIntegration {
         &self.network_layer
     }
 
+    /// Use an agent the caller already runs instead of starting one; call
+    /// before `initialize`
+    pub fn with_arch_agent(mut self, agent: ArchAgentHandle) -> Self {
+        self.arch_agent = Some(agent);
+        self
+    }
+
+    /// The Arch agent workflows run operations on, if any
+    pub fn arch_agent(&self) -> Option<&ArchAgentHandle> {
+        self.arch_agent.as_ref()
+    }
+
     /// Shutdown the integration
     pub async fn shutdown(self) -> Result<()> {
         info!("Shutting down Jarvis-GhostFlow integration");
@@ -136,7 +234,7 @@ impl JarvisGhostFlowIntegration {
     pub async fn create_demo_workflow(&self) -> Result<uuid::Uuid> {
         use crate::workflow_engine::{
             Workflow, WorkflowNode, Connection, Position, WorkflowSettings, 
-            WorkflowMetadata, WorkflowState, CallerPolicy
+            WorkflowMetadata, WorkflowState, CallerPolicy, FailurePolicy
         };
         use std::collections::HashMap;
 
@@ -154,6 +252,7 @@ impl JarvisGhostFlowIntegration {
             retry_on_fail: false,
             retry_count: 0,
             timeout_seconds: Some(30),
+            on_failure: FailurePolicy::StopWorkflow,
         });
         
         // LLM Router node
@@ -170,6 +269,7 @@ impl JarvisGhostFlowIntegration {
             retry_on_fail: true,
             retry_count: 3,
             timeout_seconds: Some(60),
+            on_failure: FailurePolicy::StopWorkflow,
         });
         
         // Memory node
@@ -185,6 +285,7 @@ impl JarvisGhostFlowIntegration {
             retry_on_fail: false,
             retry_count: 0,
             timeout_seconds: Some(10),
+            on_failure: FailurePolicy::StopWorkflow,
         });
         
         let connections = vec![
@@ -204,6 +305,7 @@ impl JarvisGhostFlowIntegration {
         
         let workflow = Workflow {
             id: workflow_id,
+            namespace: crate::namespace::default_namespace(),
             name: "Demo AI Workflow".to_string(),
             description: Some("A demonstration workflow showing Jarvis AI integration".to_string()),
             version: "1.0.0".to_string(),
@@ -217,6 +319,7 @@ impl JarvisGhostFlowIntegration {
                 save_data_error: true,
                 save_manual_executions: true,
                 caller_policy: CallerPolicy::WorkflowsFromSameOwner,
+                max_parallel_nodes: 4,
             },
             metadata: WorkflowMetadata {
                 created_at: chrono::Utc::now(),
@@ -224,6 +327,7 @@ impl JarvisGhostFlowIntegration {
                 created_by: "system".to_string(),
                 tags: vec!["demo".to_string(), "ai".to_string(), "jarvis".to_string()],
                 folder: Some("examples".to_string()),
+                template: None,
             },
             state: WorkflowState::Active,
         };
@@ -261,6 +365,33 @@ impl JarvisGhostFlowIntegration {
     }
 }
 
+/// Serve the API over TLS; each connection keeps the certificates it was accepted with
+async fn serve_tls(
+    listener: tokio::net::TcpListener,
+    app: Router,
+    acceptor: Arc<ReloadableTlsAcceptor>,
+) -> Result<()> {
+    let mut connections = tls::accept_tls(listener, acceptor);
+    
+    while let Some(stream) = connections.recv().await {
+        let app = app.clone();
+        tokio::spawn(async move {
+            let service = hyper::service::service_fn(move |request: hyper::Request<hyper::body::Incoming>| {
+                app.clone().call(request)
+            });
+            
+            if let Err(e) = hyper_util::server::conn::auto::Builder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                .await
+            {
+                debug!("TLS connection closed with error: {}", e);
+            }
+        });
+    }
+    
+    Ok(())
+}
+
 impl Default for IntegrationConfig {
     fn default() -> Self {
         Self {
@@ -270,6 +401,11 @@ impl Default for IntegrationConfig {
             enable_websockets: true,
             enable_metrics: true,
             workflow_storage_path: "./workflows".to_string(),
+            auth_database_path: default_auth_database_path(),
+            tls: TlsConfig::default(),
+            arch_agent: false,
+            budgets: None,
+            quotas: QuotaConfig::default(),
         }
     }
 }
@@ -8,13 +8,25 @@ pub mod orchestration;
 pub mod blockchain;
 pub mod network;
 pub mod workflow_engine;
+pub mod workflow_format;
 pub mod api;
+pub mod auth;
+pub mod budget;
+pub mod namespace;
+pub mod templates;
+pub mod ffi;
+pub mod evm;
 
 // Re-export main components
 pub use config::GhostFlowConfig;
-pub use integration::{JarvisGhostFlowBridge, JarvisGhostFlowIntegration, IntegrationConfig, create_ghostflow_server};
-pub use workflow_engine::{WorkflowEngine, Workflow, WorkflowNode, ExecutionResult, ExecutionMode};
+pub use integration::{ArchAgentHandle, JarvisGhostFlowBridge, JarvisGhostFlowIntegration, IntegrationConfig, create_ghostflow_server};
+pub use workflow_engine::{WorkflowEngine, Workflow, WorkflowNode, ExecutionResult, ExecutionMode, FailurePolicy};
+pub use workflow_format::WorkflowDocument;
+pub use templates::{TemplateSource, WorkflowTemplate};
 pub use api::{ApiState, create_router};
+pub use auth::{ApiKeyStore, Scope};
+pub use budget::{BudgetConfig, BudgetExceeded, Budgets};
+pub use namespace::{QuotaConfig, QuotaExceeded, Quotas};
 pub use nodes::*;
 pub use server::GhostFlowServer;
 pub use types::*;
@@ -42,6 +54,12 @@ pub enum GhostFlowError {
     
     #[error("Agent orchestration error: {0}")]
     Orchestration(String),
+    
+    #[error(transparent)]
+    BudgetExceeded(#[from] budget::BudgetExceeded),
+    
+    #[error(transparent)]
+    QuotaExceeded(#[from] namespace::QuotaExceeded),
 }
 
 pub type Result<T> = std::result::Result<T, GhostFlowError>;
\ No newline at end of file
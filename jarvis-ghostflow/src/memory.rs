@@ -0,0 +1,650 @@
+//! Namespaced key/value state for workflow nodes
+//!
+//! Backs the key/value operations of the `jarvis.memory` node. Every entry
+//! belongs to a tenant, the workflow namespace (see [`crate::namespace`])
+//! that wrote it, and within that to a key namespace, so neither other
+//! tenants nor other workflows (or individual executions) see its keys.
+//! Values are stored as JSON text and come back with their type intact.
+//! Entries may carry a TTL: expired entries are hidden and removed on read,
+//! and a background task sweeps whatever is never read again.
+
+use crate::namespace::{self, Quotas};
+use crate::{GhostFlowError, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+
+/// One stored value
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KvEntry {
+    pub key: String,
+    pub value: serde_json::Value,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+pub struct KvStore {
+    pool: SqlitePool,
+    /// Serializes read-modify-write operations such as `incr`, and the
+    /// entry count checked against the memory quota with the insert
+    write_lock: Mutex<()>,
+    sweeper: JoinHandle<()>,
+    quotas: Option<Arc<Quotas>>,
+}
+
+impl KvStore {
+    /// Create the table, moving entries from before tenants existed into the
+    /// default namespace, and start sweeping expired entries every
+    /// `sweep_interval`
+    pub async fn open(pool: SqlitePool, sweep_interval: Duration) -> Result<Self> {
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS kv_entries (
+                tenant TEXT NOT NULL DEFAULT 'default',
+                namespace TEXT NOT NULL,
+                key TEXT NOT NULL,
+                value TEXT NOT NULL,
+                expires_at INTEGER,
+                updated_at TEXT NOT NULL
+            )
+        "#,
+        )
+        .execute(&pool)
+        .await?;
+        namespace::migrate_table(&pool, "kv_entries", "tenant").await?;
+
+        // Keys were once unique per namespace alone
+        sqlx::query("DROP INDEX IF EXISTS idx_kv_entries_namespace_key")
+            .execute(&pool)
+            .await?;
+        sqlx::query(
+            r#"
+            CREATE UNIQUE INDEX IF NOT EXISTS idx_kv_entries_tenant_namespace_key
+            ON kv_entries(tenant, namespace, key)
+        "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE INDEX IF NOT EXISTS idx_kv_entries_expires_at
+            ON kv_entries(expires_at) WHERE expires_at IS NOT NULL
+        "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        let sweeper = tokio::spawn(sweep_loop(pool.clone(), sweep_interval));
+
+        Ok(Self {
+            pool,
+            write_lock: Mutex::new(()),
+            sweeper,
+            quotas: None,
+        })
+    }
+
+    /// Refuse new keys once a tenant holds its `max_memory_entries`
+    pub fn with_quotas(mut self, quotas: Arc<Quotas>) -> Self {
+        self.quotas = Some(quotas);
+        self
+    }
+
+    pub async fn get(&self, tenant: &str, namespace: &str, key: &str) -> Result<Option<KvEntry>> {
+        let row = sqlx::query(
+            "SELECT key, value, expires_at FROM kv_entries WHERE tenant = ? AND namespace = ? AND key = ?",
+        )
+        .bind(tenant)
+        .bind(namespace)
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let entry = entry_from_row(&row)?;
+        if is_expired(&entry, Utc::now()) {
+            sqlx::query(
+                "DELETE FROM kv_entries WHERE tenant = ? AND namespace = ? AND key = ? AND expires_at <= ?",
+            )
+            .bind(tenant)
+            .bind(namespace)
+            .bind(key)
+            .bind(Utc::now().timestamp_millis())
+            .execute(&self.pool)
+            .await?;
+            return Ok(None);
+        }
+        Ok(Some(entry))
+    }
+
+    /// Insert or replace a value. `ttl` of `None` keeps it until deleted.
+    pub async fn set(
+        &self,
+        tenant: &str,
+        namespace: &str,
+        key: &str,
+        value: &serde_json::Value,
+        ttl: Option<Duration>,
+    ) -> Result<KvEntry> {
+        let _guard = self.write_lock.lock().await;
+        if self.get(tenant, namespace, key).await?.is_none() {
+            self.check_quota(tenant).await?;
+        }
+        self.upsert(tenant, namespace, key, value, ttl).await
+    }
+
+    /// Returns whether a live entry was removed
+    pub async fn delete(&self, tenant: &str, namespace: &str, key: &str) -> Result<bool> {
+        let _guard = self.write_lock.lock().await;
+        let existed = self.get(tenant, namespace, key).await?.is_some();
+        sqlx::query("DELETE FROM kv_entries WHERE tenant = ? AND namespace = ? AND key = ?")
+            .bind(tenant)
+            .bind(namespace)
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+        Ok(existed)
+    }
+
+    /// Live entries in `namespace`, optionally limited to keys starting with `prefix`
+    pub async fn list(
+        &self,
+        tenant: &str,
+        namespace: &str,
+        prefix: Option<&str>,
+    ) -> Result<Vec<KvEntry>> {
+        let now = Utc::now();
+        let rows = sqlx::query(
+            r#"
+            SELECT key, value, expires_at FROM kv_entries
+            WHERE tenant = ? AND namespace = ? AND (expires_at IS NULL OR expires_at > ?)
+            ORDER BY key
+        "#,
+        )
+        .bind(tenant)
+        .bind(namespace)
+        .bind(now.timestamp_millis())
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut entries = Vec::with_capacity(rows.len());
+        for row in &rows {
+            let entry = entry_from_row(row)?;
+            if prefix.is_none_or(|p| entry.key.starts_with(p)) {
+                entries.push(entry);
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Add `delta` to a numeric value, treating a missing or expired key as 0.
+    /// Integers stay integers unless either side is a float. `ttl` applies to
+    /// newly created keys only; an existing key keeps its expiry.
+    pub async fn incr(
+        &self,
+        tenant: &str,
+        namespace: &str,
+        key: &str,
+        delta: &serde_json::Value,
+        ttl: Option<Duration>,
+    ) -> Result<KvEntry> {
+        let _guard = self.write_lock.lock().await;
+
+        let current = self.get(tenant, namespace, key).await?;
+        let base = current
+            .as_ref()
+            .map(|e| e.value.clone())
+            .unwrap_or(serde_json::json!(0));
+        let value = add_numbers(&base, delta).ok_or_else(|| {
+            GhostFlowError::NodeExecution(format!(
+                "Cannot increment '{}': {} + {} is not numeric",
+                key, base, delta
+            ))
+        })?;
+
+        match current {
+            Some(existing) => {
+                let remaining = existing
+                    .expires_at
+                    .map(|at| (at - Utc::now()).to_std().unwrap_or_default());
+                self.upsert(tenant, namespace, key, &value, remaining).await
+            }
+            None => {
+                self.check_quota(tenant).await?;
+                self.upsert(tenant, namespace, key, &value, ttl).await
+            }
+        }
+    }
+
+    /// Live entries `tenant` holds across all of its key namespaces
+    pub async fn count(&self, tenant: &str) -> Result<usize> {
+        let count: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM kv_entries WHERE tenant = ? AND (expires_at IS NULL OR expires_at > ?)",
+        )
+        .bind(tenant)
+        .bind(Utc::now().timestamp_millis())
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(count as usize)
+    }
+
+    /// Delete every expired entry, returning how many were removed
+    pub async fn sweep(&self) -> Result<u64> {
+        sweep_expired(&self.pool).await
+    }
+
+    /// Refuse a new key when `tenant` is at its memory quota; call with the
+    /// write lock held so concurrent writers can't both take the last place
+    async fn check_quota(&self, tenant: &str) -> Result<()> {
+        let Some(quotas) = &self.quotas else {
+            return Ok(());
+        };
+        if quotas.quota(tenant).max_memory_entries.is_none() {
+            return Ok(());
+        }
+        quotas.check_memory(tenant, self.count(tenant).await?)?;
+        Ok(())
+    }
+
+    async fn upsert(
+        &self,
+        tenant: &str,
+        namespace: &str,
+        key: &str,
+        value: &serde_json::Value,
+        ttl: Option<Duration>,
+    ) -> Result<KvEntry> {
+        let now = Utc::now();
+        let expires_at = ttl
+            .map(|ttl| chrono::Duration::from_std(ttl).map(|ttl| now + ttl))
+            .transpose()
+            .map_err(|_| GhostFlowError::NodeExecution("TTL is too large".to_string()))?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO kv_entries (tenant, namespace, key, value, expires_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT(tenant, namespace, key) DO UPDATE SET
+                value = excluded.value,
+                expires_at = excluded.expires_at,
+                updated_at = excluded.updated_at
+        "#,
+        )
+        .bind(tenant)
+        .bind(namespace)
+        .bind(key)
+        .bind(serde_json::to_string(value)?)
+        .bind(expires_at.map(|at| at.timestamp_millis()))
+        .bind(now.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(KvEntry {
+            key: key.to_string(),
+            value: value.clone(),
+            expires_at,
+        })
+    }
+}
+
+impl Drop for KvStore {
+    fn drop(&mut self) {
+        self.sweeper.abort();
+    }
+}
+
+async fn sweep_loop(pool: SqlitePool, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        if pool.is_closed() {
+            break;
+        }
+        match sweep_expired(&pool).await {
+            Ok(0) => {}
+            Ok(removed) => tracing::debug!("Swept {} expired memory entries", removed),
+            Err(e) => tracing::warn!("Memory TTL sweep failed: {}", e),
+        }
+    }
+}
+
+async fn sweep_expired(pool: &SqlitePool) -> Result<u64> {
+    let result =
+        sqlx::query("DELETE FROM kv_entries WHERE expires_at IS NOT NULL AND expires_at <= ?")
+            .bind(Utc::now().timestamp_millis())
+            .execute(pool)
+            .await?;
+    Ok(result.rows_affected())
+}
+
+fn entry_from_row(row: &sqlx::sqlite::SqliteRow) -> Result<KvEntry> {
+    let value: String = row.get("value");
+    let expires_at: Option<i64> = row.get("expires_at");
+    Ok(KvEntry {
+        key: row.get("key"),
+        value: serde_json::from_str(&value)?,
+        expires_at: expires_at.and_then(DateTime::from_timestamp_millis),
+    })
+}
+
+fn is_expired(entry: &KvEntry, now: DateTime<Utc>) -> bool {
+    entry.expires_at.is_some_and(|at| at <= now)
+}
+
+fn add_numbers(a: &serde_json::Value, b: &serde_json::Value) -> Option<serde_json::Value> {
+    if let (Some(a), Some(b)) = (a.as_i64(), b.as_i64()) {
+        return a.checked_add(b).map(serde_json::Value::from);
+    }
+    let sum = a.as_f64()? + b.as_f64()?;
+    serde_json::Number::from_f64(sum).map(serde_json::Value::Number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::namespace::{NamespaceQuota, QuotaConfig, QuotaExceeded, QuotaLimit};
+    use serde_json::json;
+
+    const TENANT: &str = crate::namespace::DEFAULT_NAMESPACE;
+
+    async fn open_store() -> KvStore {
+        // A single connection so the in-memory database is shared
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        KvStore::open(pool, Duration::from_secs(3600))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_values_keep_their_type() {
+        let store = open_store().await;
+        for value in [
+            json!(42),
+            json!(1.5),
+            json!("42"),
+            json!(true),
+            json!({"a": [1, null]}),
+        ] {
+            store.set(TENANT, "ns", "k", &value, None).await.unwrap();
+            assert_eq!(
+                store.get(TENANT, "ns", "k").await.unwrap().unwrap().value,
+                value
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ttl_expires_lazily_and_by_sweep() {
+        let store = open_store().await;
+        store
+            .set(
+                TENANT,
+                "ns",
+                "short",
+                &json!(1),
+                Some(Duration::from_millis(20)),
+            )
+            .await
+            .unwrap();
+        store
+            .set(
+                TENANT,
+                "ns",
+                "swept",
+                &json!(2),
+                Some(Duration::from_millis(20)),
+            )
+            .await
+            .unwrap();
+        store
+            .set(TENANT, "ns", "forever", &json!(3), None)
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(40)).await;
+
+        assert!(store.get(TENANT, "ns", "short").await.unwrap().is_none());
+        let keys: Vec<String> = store
+            .list(TENANT, "ns", None)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|e| e.key)
+            .collect();
+        assert_eq!(keys, vec!["forever"]);
+        assert_eq!(store.sweep().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_incr() {
+        let store = open_store().await;
+        assert_eq!(
+            store
+                .incr(TENANT, "ns", "n", &json!(2), None)
+                .await
+                .unwrap()
+                .value,
+            json!(2)
+        );
+        assert_eq!(
+            store
+                .incr(TENANT, "ns", "n", &json!(-5), None)
+                .await
+                .unwrap()
+                .value,
+            json!(-3)
+        );
+        assert_eq!(
+            store
+                .incr(TENANT, "ns", "n", &json!(0.5), None)
+                .await
+                .unwrap()
+                .value,
+            json!(-2.5)
+        );
+
+        store
+            .set(TENANT, "ns", "s", &json!("x"), None)
+            .await
+            .unwrap();
+        assert!(store
+            .incr(TENANT, "ns", "s", &json!(1), None)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_namespaces_do_not_interfere_under_concurrency() {
+        let store = std::sync::Arc::new(open_store().await);
+
+        let writers: Vec<_> = ["execution:a", "execution:b"]
+            .into_iter()
+            .map(|namespace| {
+                let store = store.clone();
+                tokio::spawn(async move {
+                    for i in 0..50 {
+                        store
+                            .set(
+                                TENANT,
+                                namespace,
+                                "result",
+                                &json!({"ns": namespace, "i": i}),
+                                None,
+                            )
+                            .await
+                            .unwrap();
+                        store
+                            .incr(TENANT, namespace, "count", &json!(1), None)
+                            .await
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.await.unwrap();
+        }
+
+        for namespace in ["execution:a", "execution:b"] {
+            let result = store
+                .get(TENANT, namespace, "result")
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(result.value, json!({"ns": namespace, "i": 49}));
+            assert_eq!(
+                store
+                    .get(TENANT, namespace, "count")
+                    .await
+                    .unwrap()
+                    .unwrap()
+                    .value,
+                json!(50)
+            );
+        }
+        assert!(store.delete(TENANT, "execution:a", "result").await.unwrap());
+        assert!(store
+            .get(TENANT, "execution:b", "result")
+            .await
+            .unwrap()
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn test_tenants_keep_separate_keys_and_quotas_under_concurrency() {
+        let quotas = Arc::new(Quotas::new(QuotaConfig {
+            default: NamespaceQuota::default(),
+            namespaces: std::collections::HashMap::from([(
+                "friend".to_string(),
+                NamespaceQuota {
+                    max_memory_entries: Some(5),
+                    ..NamespaceQuota::default()
+                },
+            )]),
+        }));
+        let store = Arc::new(open_store().await.with_quotas(quotas));
+        store
+            .set(TENANT, "global", "k", &json!("mine"), None)
+            .await
+            .unwrap();
+
+        let writers: Vec<_> = (0..20)
+            .map(|i| {
+                let store = store.clone();
+                tokio::spawn(async move {
+                    store
+                        .set("friend", "global", &format!("k{}", i), &json!(i), None)
+                        .await
+                })
+            })
+            .collect();
+        let mut stored = 0;
+        for writer in writers {
+            match writer.await.unwrap() {
+                Ok(_) => stored += 1,
+                Err(GhostFlowError::QuotaExceeded(QuotaExceeded {
+                    namespace,
+                    limit,
+                    max,
+                })) => {
+                    assert_eq!(
+                        (namespace.as_str(), limit, max),
+                        ("friend", QuotaLimit::MemoryEntries, 5)
+                    );
+                }
+                Err(e) => panic!("unexpected error: {}", e),
+            }
+        }
+        assert_eq!(stored, 5);
+        assert_eq!(store.count("friend").await.unwrap(), 5);
+
+        // A full tenant can still change its keys but not add one
+        let key = &store.list("friend", "global", None).await.unwrap()[0].key;
+        store
+            .set("friend", "global", key, &json!("changed"), None)
+            .await
+            .unwrap();
+        assert!(store
+            .incr("friend", "global", "counter", &json!(1), None)
+            .await
+            .is_err());
+
+        // The same key namespace is separate per tenant, and so is the quota
+        assert!(store.get("friend", "global", "k").await.unwrap().is_none());
+        assert_eq!(
+            store
+                .get(TENANT, "global", "k")
+                .await
+                .unwrap()
+                .unwrap()
+                .value,
+            json!("mine")
+        );
+        store
+            .incr(TENANT, "global", "counter", &json!(1), None)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_entries_from_before_tenants_move_to_default() {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::query(
+            "CREATE TABLE kv_entries (namespace TEXT NOT NULL, key TEXT NOT NULL, value TEXT NOT NULL, expires_at INTEGER, updated_at TEXT NOT NULL)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "CREATE UNIQUE INDEX idx_kv_entries_namespace_key ON kv_entries(namespace, key)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO kv_entries VALUES ('global', 'k', '1', NULL, '')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let store = KvStore::open(pool, Duration::from_secs(3600))
+            .await
+            .unwrap();
+        assert_eq!(
+            store
+                .get(TENANT, "global", "k")
+                .await
+                .unwrap()
+                .unwrap()
+                .value,
+            json!(1)
+        );
+        assert!(store.get("friend", "global", "k").await.unwrap().is_none());
+        store
+            .set("friend", "global", "k", &json!(2), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            store
+                .get(TENANT, "global", "k")
+                .await
+                .unwrap()
+                .unwrap()
+                .value,
+            json!(1)
+        );
+    }
+}
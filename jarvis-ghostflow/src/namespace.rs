@@ -0,0 +1,390 @@
+//! Namespaces that keep tenants' workflows apart, and their quotas
+//!
+//! Every workflow, execution, memory entry and API key belongs to one
+//! namespace. A key only sees its own namespace; admin keys see all of them
+//! and pick one with `?namespace=`. Each namespace may cap how many
+//! executions it has queued or running at once, how many finished
+//! executions it keeps, and how many memory entries it holds; a namespace at
+//! a cap is refused with [`QuotaExceeded`]. Its LLM budget lives with the
+//! other budgets under `budgets.namespaces`.
+//!
+//! Anything stored before namespaces existed belongs to `default`.
+
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use crate::{GhostFlowError, Result};
+
+/// Namespace of everything created without one
+pub const DEFAULT_NAMESPACE: &str = "default";
+
+const MAX_NAME_LENGTH: usize = 64;
+
+pub fn default_namespace() -> String {
+    DEFAULT_NAMESPACE.to_string()
+}
+
+/// Namespaces are lowercase letters, digits, `-` and `_`, so they fit in
+/// URLs and config keys unchanged
+pub fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LENGTH
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(GhostFlowError::Config(format!(
+            "Invalid namespace '{}': use up to {} lowercase letters, digits, '-' or '_'",
+            name, MAX_NAME_LENGTH
+        )))
+    }
+}
+
+/// Caps for one namespace; a cap left unset is unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NamespaceQuota {
+    /// Executions queued or running at once
+    #[serde(default)]
+    pub max_concurrent_executions: Option<usize>,
+    /// Finished and paused executions kept; delete some to run more
+    #[serde(default)]
+    pub max_stored_executions: Option<usize>,
+    /// Live key/value memory entries
+    #[serde(default)]
+    pub max_memory_entries: Option<usize>,
+}
+
+/// `quotas` section of the GhostFlow config
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QuotaConfig {
+    /// Quota of every namespace not listed in `namespaces`
+    #[serde(default)]
+    pub default: NamespaceQuota,
+    /// Quotas per namespace name
+    #[serde(default)]
+    pub namespaces: HashMap<String, NamespaceQuota>,
+}
+
+impl QuotaConfig {
+    pub fn quota(&self, namespace: &str) -> NamespaceQuota {
+        self.namespaces
+            .get(namespace)
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+/// Which cap a namespace ran into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaLimit {
+    ConcurrentExecutions,
+    StoredExecutions,
+    MemoryEntries,
+}
+
+impl QuotaLimit {
+    /// The `NamespaceQuota` field setting this cap
+    pub fn key(&self) -> &'static str {
+        match self {
+            QuotaLimit::ConcurrentExecutions => "max_concurrent_executions",
+            QuotaLimit::StoredExecutions => "max_stored_executions",
+            QuotaLimit::MemoryEntries => "max_memory_entries",
+        }
+    }
+}
+
+impl fmt::Display for QuotaLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            QuotaLimit::ConcurrentExecutions => "concurrent executions",
+            QuotaLimit::StoredExecutions => "stored executions",
+            QuotaLimit::MemoryEntries => "memory entries",
+        })
+    }
+}
+
+/// Returned instead of starting an execution or storing a memory entry once
+/// a namespace is at one of its caps
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("Namespace '{namespace}' is at its quota of {max} {limit} ({})", .limit.key())]
+pub struct QuotaExceeded {
+    pub namespace: String,
+    pub limit: QuotaLimit,
+    pub max: usize,
+}
+
+impl QuotaExceeded {
+    /// The quota `error` says was hit, if that's why it failed
+    pub fn find(error: &anyhow::Error) -> Option<&QuotaExceeded> {
+        error.downcast_ref::<QuotaExceeded>().or_else(|| {
+            match error.downcast_ref::<GhostFlowError>() {
+                Some(GhostFlowError::QuotaExceeded(exceeded)) => Some(exceeded),
+                _ => None,
+            }
+        })
+    }
+}
+
+/// Executions a namespace has counted against its quotas
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct NamespaceUsage {
+    /// Queued or running
+    pub running_executions: usize,
+    pub stored_executions: usize,
+}
+
+type UsageMap = Arc<Mutex<HashMap<String, NamespaceUsage>>>;
+
+/// Per-namespace quotas and what's counted against them, shared by the
+/// engine, the memory store and the API
+#[derive(Debug)]
+pub struct Quotas {
+    config: QuotaConfig,
+    usage: UsageMap,
+}
+
+impl Quotas {
+    pub fn new(config: QuotaConfig) -> Self {
+        Self {
+            config,
+            usage: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Quotas that never refuse anything
+    pub fn unlimited() -> Self {
+        Self::new(QuotaConfig::default())
+    }
+
+    pub fn quota(&self, namespace: &str) -> NamespaceQuota {
+        self.config.quota(namespace)
+    }
+
+    pub fn usage(&self, namespace: &str) -> NamespaceUsage {
+        self.usage
+            .lock()
+            .unwrap()
+            .get(namespace)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Count an execution as running until its slot is dropped, or refuse
+    /// it when `namespace` is at its concurrency cap. A `new` execution will
+    /// also need a stored place, so executions already running count toward
+    /// that cap; resumed ones are stored already.
+    pub fn admit(
+        &self,
+        namespace: &str,
+        new: bool,
+    ) -> std::result::Result<ExecutionSlot, QuotaExceeded> {
+        let quota = self.quota(namespace);
+        let mut usage = self.usage.lock().unwrap();
+        let current = usage.entry(namespace.to_string()).or_default();
+
+        let exceeded = |limit, max| QuotaExceeded {
+            namespace: namespace.to_string(),
+            limit,
+            max,
+        };
+        if let Some(max) = quota.max_concurrent_executions {
+            if current.running_executions >= max {
+                return Err(exceeded(QuotaLimit::ConcurrentExecutions, max));
+            }
+        }
+        if let Some(max) = quota.max_stored_executions.filter(|_| new) {
+            if current.stored_executions + current.running_executions >= max {
+                return Err(exceeded(QuotaLimit::StoredExecutions, max));
+            }
+        }
+
+        current.running_executions += 1;
+        Ok(ExecutionSlot {
+            usage: self.usage.clone(),
+            namespace: namespace.to_string(),
+            stored: false,
+        })
+    }
+
+    /// Give back the stored place of a deleted execution
+    pub fn forget_stored(&self, namespace: &str) {
+        if let Some(current) = self.usage.lock().unwrap().get_mut(namespace) {
+            current.stored_executions = current.stored_executions.saturating_sub(1);
+        }
+    }
+
+    /// Refuse a new memory entry when `namespace` already holds `entries`
+    /// and that fills its cap
+    pub fn check_memory(
+        &self,
+        namespace: &str,
+        entries: usize,
+    ) -> std::result::Result<(), QuotaExceeded> {
+        match self.quota(namespace).max_memory_entries {
+            Some(max) if entries >= max => Err(QuotaExceeded {
+                namespace: namespace.to_string(),
+                limit: QuotaLimit::MemoryEntries,
+                max,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Default for Quotas {
+    fn default() -> Self {
+        Self::unlimited()
+    }
+}
+
+/// A running execution's place in its namespace's concurrency quota, given
+/// back on drop
+#[derive(Debug)]
+pub struct ExecutionSlot {
+    usage: UsageMap,
+    namespace: String,
+    stored: bool,
+}
+
+impl ExecutionSlot {
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// Give the slot back; `added` says whether the execution's result took
+    /// a new stored place rather than replacing an earlier one of its own
+    pub fn finish(mut self, added: bool) {
+        self.stored = added;
+    }
+}
+
+impl Drop for ExecutionSlot {
+    fn drop(&mut self) {
+        let mut usage = self.usage.lock().unwrap();
+        let current = usage.entry(self.namespace.clone()).or_default();
+        current.running_executions = current.running_executions.saturating_sub(1);
+        if self.stored {
+            current.stored_executions += 1;
+        }
+    }
+}
+
+/// Add a `column` holding the namespace to `table` if it was created before
+/// namespaces existed; its rows move to the default namespace. Returns
+/// whether the table needed it.
+pub async fn migrate_table(pool: &SqlitePool, table: &str, column: &str) -> Result<bool> {
+    let columns = sqlx::query(&format!("PRAGMA table_info({})", table))
+        .fetch_all(pool)
+        .await?;
+    if columns
+        .iter()
+        .any(|row| row.get::<String, _>("name") == column)
+    {
+        return Ok(false);
+    }
+
+    sqlx::query(&format!(
+        "ALTER TABLE {} ADD COLUMN {} TEXT NOT NULL DEFAULT '{}'",
+        table, column, DEFAULT_NAMESPACE
+    ))
+    .execute(pool)
+    .await?;
+    tracing::info!(
+        "Moved existing {} rows into the '{}' namespace",
+        table,
+        DEFAULT_NAMESPACE
+    );
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quotas() -> Quotas {
+        Quotas::new(QuotaConfig {
+            default: NamespaceQuota {
+                max_concurrent_executions: Some(2),
+                max_stored_executions: Some(3),
+                max_memory_entries: Some(10),
+            },
+            namespaces: HashMap::from([(
+                "friend".to_string(),
+                NamespaceQuota {
+                    max_concurrent_executions: Some(1),
+                    ..NamespaceQuota::default()
+                },
+            )]),
+        })
+    }
+
+    #[test]
+    fn test_validate_name() {
+        for name in ["default", "friend-2", "team_a"] {
+            validate_name(name).unwrap();
+        }
+        for name in ["", "Friend", "a/b", "x y", &"a".repeat(65)] {
+            assert!(validate_name(name).is_err(), "{:?} was accepted", name);
+        }
+    }
+
+    #[test]
+    fn test_concurrency_slots_are_given_back_on_drop() {
+        let quotas = quotas();
+        let first = quotas.admit("friend", true).unwrap();
+        let refused = quotas.admit("friend", true).unwrap_err();
+        assert_eq!(refused.limit, QuotaLimit::ConcurrentExecutions);
+        assert_eq!(
+            refused.to_string(),
+            "Namespace 'friend' is at its quota of 1 concurrent executions (max_concurrent_executions)"
+        );
+        // Other namespaces have their own count
+        let _other = quotas.admit("default", true).unwrap();
+
+        drop(first);
+        assert_eq!(quotas.usage("friend").running_executions, 0);
+        quotas.admit("friend", true).unwrap();
+    }
+
+    #[test]
+    fn test_stored_executions_count_until_forgotten() {
+        let quotas = quotas();
+        for _ in 0..2 {
+            quotas.admit("default", true).unwrap().finish(true);
+        }
+        // One stored place left, and it's taken while this one runs
+        let running = quotas.admit("default", true).unwrap();
+        let refused = quotas.admit("default", true).unwrap_err();
+        assert_eq!(refused.limit, QuotaLimit::StoredExecutions);
+        // Resuming needs no new place
+        quotas.admit("default", false).unwrap().finish(false);
+        running.finish(true);
+        assert_eq!(
+            quotas.usage("default"),
+            NamespaceUsage {
+                running_executions: 0,
+                stored_executions: 3
+            }
+        );
+
+        quotas.forget_stored("default");
+        quotas.admit("default", true).unwrap();
+    }
+
+    #[test]
+    fn test_memory_cap() {
+        let quotas = quotas();
+        quotas.check_memory("default", 9).unwrap();
+        assert_eq!(
+            quotas.check_memory("default", 10).unwrap_err().limit,
+            QuotaLimit::MemoryEntries
+        );
+        quotas.check_memory("friend", 10_000).unwrap();
+    }
+}
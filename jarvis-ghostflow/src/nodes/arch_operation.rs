@@ -0,0 +1,751 @@
+//! Arch agent operations as a workflow node
+//!
+//! `jarvis.arch.operation` runs one [`ArchOperation`] on the Arch agent held
+//! by the integration layer. The config picks the operation and its fixed
+//! parameters; `input_mapping` fills the rest from workflow inputs. The output
+//! has two ports: `result`, the agent's `OperationResult`, and `success`, so a
+//! condition node can branch without digging into the result.
+//!
+//! With `idempotency_key` set, the node reads a key (such as a webhook
+//! delivery id) from the inputs, and a run whose key was seen within the
+//! window gets the earlier run's result instead of running the operation
+//! again, so a retried trigger doesn't update packages twice.
+
+use super::{
+    ExecutionContext, GhostFlowNode, HealthStatus, NodeDefinition, NodeHealth, NodeInstance,
+    NodeOutput,
+};
+use crate::integration::ArchAgentHandle;
+use crate::{ExecutionStatus, GhostFlowError, NodeExecutionResult, Result, WorkflowContext};
+use async_trait::async_trait;
+use jarvis_arch::{ArchOperation, OperationResult};
+use jarvis_core::JarvisError;
+use jarvis_core::idempotency::{self, Replays};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+pub const NODE_TYPE: &str = "jarvis.arch.operation";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchOperationConfig {
+    /// Variant name from `ArchOperation::NAMES`, e.g. `SecurityScan`
+    pub operation: String,
+    /// Fixed operation parameters, e.g. `{ "full_scan": true }`
+    #[serde(default)]
+    pub parameters: serde_json::Map<String, serde_json::Value>,
+    /// Operation parameter -> workflow input it is read from; a source
+    /// starting with `/` is a JSON pointer into the inputs
+    #[serde(default)]
+    pub input_mapping: BTreeMap<String, String>,
+    /// How long the operation may run before the node cancels it. The
+    /// workflow engine sets this from the node's `timeout_seconds`.
+    #[serde(default = "default_timeout_seconds")]
+    pub timeout_seconds: u64,
+    /// Workflow input (or JSON pointer) holding an idempotency key; runs
+    /// with a key seen within the window replay the first run's result
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+    #[serde(default = "default_idempotency_window_seconds")]
+    pub idempotency_window_seconds: u64,
+}
+
+fn default_timeout_seconds() -> u64 {
+    600
+}
+
+fn default_idempotency_window_seconds() -> u64 {
+    idempotency::DEFAULT_WINDOW.as_secs()
+}
+
+impl ArchOperationConfig {
+    pub fn from_value(value: serde_json::Value) -> Result<Self> {
+        let config: Self = serde_json::from_value(value)
+            .map_err(|e| GhostFlowError::Config(format!("Invalid {} config: {}", NODE_TYPE, e)))?;
+        if !ArchOperation::NAMES.contains(&config.operation.as_str()) {
+            return Err(GhostFlowError::Config(format!(
+                "Unknown Arch operation '{}'",
+                config.operation
+            )));
+        }
+        Ok(config)
+    }
+
+    /// The operation, with mapped inputs laid over the fixed parameters
+    pub fn operation(&self, inputs: &serde_json::Value) -> Result<ArchOperation> {
+        let mut parameters = self.parameters.clone();
+        for (parameter, source) in &self.input_mapping {
+            parameters.insert(parameter.clone(), input(inputs, source, parameter)?.clone());
+        }
+
+        // Unit variants serialize as a bare name, the others as `{ name: { ... } }`
+        let encoded = if parameters.is_empty() {
+            serde_json::from_value(json!(self.operation))
+                .or_else(|_| serde_json::from_value(json!({ &self.operation: {} })))
+        } else {
+            serde_json::from_value(json!({ &self.operation: parameters }))
+        };
+        encoded.map_err(|e| {
+            GhostFlowError::NodeExecution(format!(
+                "Invalid parameters for {}: {}",
+                self.operation, e
+            ))
+        })
+    }
+
+    /// The idempotency key in `inputs`, if the node is configured with one
+    pub fn idempotency_key(&self, inputs: &serde_json::Value) -> Result<Option<String>> {
+        let Some(source) = &self.idempotency_key else {
+            return Ok(None);
+        };
+        match input(inputs, source, "idempotency_key")? {
+            serde_json::Value::String(key) => Ok(Some(key.clone())),
+            key @ serde_json::Value::Number(_) => Ok(Some(key.to_string())),
+            _ => Err(GhostFlowError::NodeExecution(format!(
+                "Input '{}' for idempotency_key is not a string or number",
+                source
+            ))),
+        }
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_seconds.max(1))
+    }
+
+    fn idempotency_window(&self) -> Duration {
+        Duration::from_secs(self.idempotency_window_seconds)
+    }
+}
+
+/// Workflow input `source`; one starting with `/` is a JSON pointer
+fn input<'a>(
+    inputs: &'a serde_json::Value,
+    source: &str,
+    parameter: &str,
+) -> Result<&'a serde_json::Value> {
+    let value = if source.starts_with('/') {
+        inputs.pointer(source)
+    } else {
+        inputs.get(source)
+    };
+    value.ok_or_else(|| {
+        GhostFlowError::NodeExecution(format!(
+            "Input '{}' for parameter '{}' is missing",
+            source, parameter
+        ))
+    })
+}
+
+/// Outcomes of runs by idempotency key, shared by a node and its instances
+pub type ArchReplays = Arc<Replays<std::result::Result<OperationResult, String>>>;
+
+/// Run `operation` on `agent`; once `timeout` passes the node asks the agent
+/// to cancel it and waits for the agent to wind down
+pub async fn run_operation(
+    agent: &ArchAgentHandle,
+    operation: ArchOperation,
+    timeout: Duration,
+) -> Result<OperationResult> {
+    let agent = agent.read().await;
+    let operation_id = Uuid::new_v4();
+    let name = operation.name();
+
+    let running = agent.execute_cancellable(operation_id, operation);
+    tokio::pin!(running);
+    let result = match tokio::time::timeout(timeout, &mut running).await {
+        Ok(result) => result,
+        Err(_) => {
+            agent.cancel_operation(operation_id);
+            let _ = running.await;
+            return Err(JarvisError::Timeout(format!(
+                "{} did not finish within {}s",
+                name,
+                timeout.as_secs()
+            ))
+            .into());
+        }
+    };
+    // Dropping this future (the engine cancelling the node) drops the
+    // operation with it, which is how the agent sees that cancellation
+    result.map_err(|e| JarvisError::from_anyhow(&e).into())
+}
+
+/// [`run_operation`], unless the inputs carry an idempotency key seen within
+/// the window, in which case that run's result; also says which it was
+async fn run_once(
+    agent: &ArchAgentHandle,
+    replays: &ArchReplays,
+    config: &ArchOperationConfig,
+    inputs: &serde_json::Value,
+) -> Result<(OperationResult, bool)> {
+    let operation = config.operation(inputs)?;
+    let Some(key) = config.idempotency_key(inputs)? else {
+        let result = run_operation(agent, operation, config.timeout()).await?;
+        return Ok((result, false));
+    };
+
+    let request = serde_json::to_string(&operation)?;
+    // The first run's own error is returned as is; replays get its message
+    let mut failure = None;
+    let outcome = replays
+        .run(&key, &request, config.idempotency_window(), async {
+            run_operation(agent, operation, config.timeout())
+                .await
+                .map_err(|e| {
+                    let message = e.to_string();
+                    failure = Some(e);
+                    message
+                })
+        })
+        .await
+        .map_err(|e| GhostFlowError::NodeExecution(e.to_string()))?;
+    match outcome.value {
+        Ok(result) => Ok((result, outcome.replayed)),
+        Err(message) => Err(failure.unwrap_or(GhostFlowError::NodeExecution(message))),
+    }
+}
+
+/// Node output: the result and its success flag as separate ports
+fn ports(result: &OperationResult) -> Result<serde_json::Value> {
+    Ok(json!({
+        "success": result.succeeded(),
+        "result": serde_json::to_value(result)?,
+    }))
+}
+
+pub struct ArchOperationNode {
+    agent: Option<ArchAgentHandle>,
+    replays: ArchReplays,
+    health: Arc<RwLock<NodeHealth>>,
+}
+
+impl ArchOperationNode {
+    /// Without an agent the node only describes itself; executions fail
+    pub fn new() -> Result<Self> {
+        Ok(Self::build(None))
+    }
+
+    pub fn with_agent(agent: ArchAgentHandle) -> Self {
+        Self::build(Some(agent))
+    }
+
+    fn build(agent: Option<ArchAgentHandle>) -> Self {
+        Self {
+            agent,
+            replays: ArchReplays::default(),
+            health: Arc::new(RwLock::new(NodeHealth {
+                status: HealthStatus::Unknown,
+                message: None,
+                last_execution: None,
+                error_count: 0,
+                success_rate: 0.0,
+            })),
+        }
+    }
+
+    fn agent(&self) -> Result<&ArchAgentHandle> {
+        self.agent.as_ref().ok_or_else(|| {
+            GhostFlowError::Config(
+                "No Arch agent attached; enable it on the GhostFlow integration".to_string(),
+            )
+        })
+    }
+
+    async fn record(&self, success: bool) {
+        let mut health = self.health.write().await;
+        if !success {
+            health.error_count += 1;
+        }
+        health.last_execution = Some(chrono::Utc::now());
+        health.status = match health.error_count {
+            0 => HealthStatus::Healthy,
+            1..=4 => HealthStatus::Warning,
+            _ => HealthStatus::Critical,
+        };
+    }
+}
+
+#[async_trait]
+impl GhostFlowNode for ArchOperationNode {
+    fn node_type(&self) -> &'static str {
+        NODE_TYPE
+    }
+
+    fn display_name(&self) -> &str {
+        "Arch Operation"
+    }
+
+    fn description(&self) -> &str {
+        "Run a package, maintenance, or security operation through the Arch Linux agent"
+    }
+
+    fn input_schema(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "description": "Workflow inputs; input_mapping picks operation parameters from them",
+            "additionalProperties": true
+        })
+    }
+
+    fn output_schema(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "success": {
+                    "type": "boolean",
+                    "description": "Whether the operation succeeded"
+                },
+                "result": {
+                    "type": "object",
+                    "description": "The agent's OperationResult"
+                }
+            }
+        })
+    }
+
+    fn config_schema(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "operation": {
+                    "type": "string",
+                    "description": "Operation to run",
+                    "enum": ArchOperation::NAMES
+                },
+                "parameters": {
+                    "type": "object",
+                    "description": "Fixed operation parameters"
+                },
+                "input_mapping": {
+                    "type": "object",
+                    "description": "Operation parameter -> workflow input (or JSON pointer) it is read from"
+                },
+                "timeout_seconds": {
+                    "type": "integer",
+                    "description": "Cancel the operation after this many seconds",
+                    "default": 600,
+                    "minimum": 1
+                },
+                "idempotency_key": {
+                    "type": "string",
+                    "description": "Workflow input (or JSON pointer) holding an idempotency key, e.g. a webhook delivery id"
+                },
+                "idempotency_window_seconds": {
+                    "type": "integer",
+                    "description": "Runs with a key seen this recently replay the first run's result",
+                    "default": 86400,
+                    "minimum": 0
+                }
+            },
+            "required": ["operation"]
+        })
+    }
+
+    async fn execute(
+        &self,
+        context: &mut WorkflowContext,
+        inputs: HashMap<String, serde_json::Value>,
+        config: HashMap<String, serde_json::Value>,
+    ) -> Result<NodeExecutionResult> {
+        let start_time = Instant::now();
+        let config = ArchOperationConfig::from_value(json!(config))?;
+        let inputs = serde_json::Value::Object(inputs.into_iter().collect());
+
+        let outcome = run_once(self.agent()?, &self.replays, &config, &inputs).await;
+        self.record(outcome.as_ref().is_ok_and(|(r, _)| r.succeeded()))
+            .await;
+
+        let mut metadata = HashMap::new();
+        let (status, output, error) = match outcome {
+            Ok((result, replayed)) => {
+                metadata.insert("replayed".to_string(), json!(replayed));
+                (ExecutionStatus::Success, ports(&result)?, result.error)
+            }
+            Err(e) => (ExecutionStatus::Failure, json!({}), Some(e.to_string())),
+        };
+        Ok(NodeExecutionResult {
+            node_id: context.current_node.clone(),
+            execution_id: context.execution_id,
+            status,
+            output,
+            error,
+            duration_ms: start_time.elapsed().as_millis() as u64,
+            metadata,
+            next_nodes: vec![],
+        })
+    }
+
+    fn validate_config(&self, config: &HashMap<String, serde_json::Value>) -> Result<()> {
+        let config = ArchOperationConfig::from_value(json!(config))?;
+        // With inputs still to come only the name can be checked
+        if config.input_mapping.is_empty() {
+            config.operation(&json!({}))?;
+        }
+        Ok(())
+    }
+
+    async fn health_check(&self) -> NodeHealth {
+        self.health.read().await.clone()
+    }
+}
+
+#[async_trait]
+impl NodeDefinition for ArchOperationNode {
+    fn node_type(&self) -> &'static str {
+        NODE_TYPE
+    }
+
+    fn create_instance(&self) -> anyhow::Result<Box<dyn NodeInstance + Send + Sync>> {
+        let agent = self.agent()?.clone();
+        Ok(Box::new(ArchOperationInstance {
+            agent,
+            replays: self.replays.clone(),
+            config: None,
+        }))
+    }
+}
+
+pub struct ArchOperationInstance {
+    agent: ArchAgentHandle,
+    replays: ArchReplays,
+    config: Option<ArchOperationConfig>,
+}
+
+#[async_trait]
+impl NodeInstance for ArchOperationInstance {
+    async fn configure(&mut self, parameters: serde_json::Value) -> anyhow::Result<()> {
+        self.config = Some(ArchOperationConfig::from_value(parameters)?);
+        Ok(())
+    }
+
+    /// Inputs are the trigger payload's fields plus each finished node's
+    /// output under its id
+    async fn execute(&mut self, context: &ExecutionContext) -> anyhow::Result<NodeOutput> {
+        let config = self
+            .config
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("{} executed before configure", NODE_TYPE))?;
+
+        let mut inputs = context.data.as_object().cloned().unwrap_or_default();
+        for (node_id, output) in &context.node_outputs {
+            inputs.insert(node_id.clone(), output.data.clone());
+        }
+
+        let inputs = serde_json::Value::Object(inputs);
+        let (result, _) = run_once(&self.agent, &self.replays, config, &inputs).await?;
+        Ok(NodeOutput {
+            data: ports(&result)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflow_engine::{
+        ConditionNode, ExecutionMode, ExecutionResult, ExecutionStatus, NodeExecution, StartNode,
+        WorkflowEngine,
+    };
+    use jarvis_arch::{
+        AgentCapability, AgentHealth, AgentStatus, ArchAgent, Config, ExecOptions,
+        OperationRegistry,
+    };
+    use std::sync::Mutex;
+
+    /// Scan finds issues, so cleanup runs; the log branch only runs when it doesn't
+    const SCAN_THEN_CLEANUP: &str = r#"
+kind: GhostFlowWorkflow
+version: v1
+metadata:
+  name: Scan then clean up
+settings:
+  timeout_seconds: 300
+  error_workflow: null
+  save_data_execution_progress: false
+  save_data_success: true
+  save_data_error: true
+  save_manual_executions: false
+  caller_policy: None
+nodes:
+  - id: start
+    type: start
+    position: { x: 0.0, y: 0.0 }
+  - id: scan
+    type: jarvis.arch.operation
+    config:
+      operation: SecurityScan
+      input_mapping: { full_scan: full_scan }
+    position: { x: 200.0, y: 0.0 }
+    timeout_seconds: 60
+  - id: check
+    type: condition
+    config: { field: /scan/success, equals: true }
+    position: { x: 400.0, y: 0.0 }
+  - id: cleanup
+    type: jarvis.arch.operation
+    config:
+      operation: SystemCleanup
+      parameters: { clean_cache: true }
+      input_mapping: { clean_logs: /scan/result/output/logs_dirty }
+    position: { x: 600.0, y: -100.0 }
+  - id: report
+    type: jarvis.arch.operation
+    config: { operation: HealthCheck, parameters: { include_services: false } }
+    position: { x: 600.0, y: 100.0 }
+edges:
+  - { from: start, to: scan }
+  - { from: scan, to: check }
+  - { from: check, from_output: "true", to: cleanup }
+  - { from: check, from_output: "false", to: report }
+"#;
+
+    /// Answers every operation with `success`; `hang` makes SecurityScan
+    /// run until cancelled
+    #[derive(Default)]
+    struct FakeAgent {
+        success: bool,
+        hang: bool,
+        executed: Mutex<Vec<ArchOperation>>,
+        cancelled: Mutex<Vec<Uuid>>,
+        operations: OperationRegistry,
+    }
+
+    #[async_trait]
+    impl ArchAgent for FakeAgent {
+        async fn initialize(&mut self, _config: Config) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn health_check(&self) -> anyhow::Result<AgentHealth> {
+            unimplemented!()
+        }
+
+        fn capabilities(&self) -> Vec<AgentCapability> {
+            Vec::new()
+        }
+
+        async fn execute_operation(
+            &self,
+            operation: ArchOperation,
+        ) -> anyhow::Result<OperationResult> {
+            self.executed.lock().unwrap().push(operation.clone());
+            if self.hang && matches!(operation, ArchOperation::SecurityScan { .. }) {
+                std::future::pending::<()>().await;
+            }
+            Ok(OperationResult {
+                operation,
+                success: self.success,
+                output: json!({ "logs_dirty": true }).into(),
+                error: None,
+                duration_ms: 1,
+                executed_at: chrono::Utc::now(),
+                metadata: HashMap::new(),
+            })
+        }
+
+        async fn execute_operation_with_options(
+            &self,
+            operation: ArchOperation,
+            _options: ExecOptions,
+        ) -> anyhow::Result<OperationResult> {
+            self.execute_operation(operation).await
+        }
+
+        async fn execute_cancellable(
+            &self,
+            operation_id: Uuid,
+            operation: ArchOperation,
+        ) -> anyhow::Result<OperationResult> {
+            self.operations
+                .run(
+                    operation_id,
+                    operation.name(),
+                    self.execute_operation(operation),
+                )
+                .await
+                .unwrap_or_else(|| Err(JarvisError::Cancelled("cancelled".to_string()).into()))
+        }
+
+        fn cancel_operation(&self, operation_id: Uuid) -> bool {
+            self.cancelled.lock().unwrap().push(operation_id);
+            self.operations.cancel(operation_id)
+        }
+
+        async fn get_status(&self) -> anyhow::Result<AgentStatus> {
+            unimplemented!()
+        }
+
+        async fn shutdown(&mut self) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    async fn run(agent: Arc<RwLock<FakeAgent>>, yaml: &str) -> ExecutionResult {
+        let engine = WorkflowEngine::new().unwrap();
+        engine.register_node(Box::new(StartNode::new())).await;
+        engine.register_node(Box::new(ConditionNode::new())).await;
+        engine
+            .register_node(Box::new(ArchOperationNode::with_agent(agent)))
+            .await;
+        let workflow_id = engine.import_yaml(yaml).await.unwrap();
+        engine
+            .execute_workflow(
+                workflow_id,
+                json!({ "full_scan": true }),
+                ExecutionMode::Manual,
+            )
+            .await
+            .unwrap()
+    }
+
+    fn execution<'a>(result: &'a ExecutionResult, node_id: &str) -> &'a NodeExecution {
+        result
+            .node_executions
+            .iter()
+            .find(|n| n.node_id == node_id)
+            .unwrap_or_else(|| panic!("no execution recorded for {}", node_id))
+    }
+
+    #[tokio::test]
+    async fn test_scan_condition_cleanup_workflow() {
+        let agent = Arc::new(RwLock::new(FakeAgent {
+            success: true,
+            ..Default::default()
+        }));
+        let result = run(agent.clone(), SCAN_THEN_CLEANUP).await;
+
+        assert!(matches!(result.status, ExecutionStatus::Success));
+        assert!(matches!(
+            execution(&result, "report").status,
+            ExecutionStatus::Skipped
+        ));
+        let scan = execution(&result, "scan").output_data.as_ref().unwrap();
+        assert_eq!(scan["success"], json!(true));
+        assert_eq!(scan["result"]["output"]["logs_dirty"], json!(true));
+
+        let executed = agent.read().await.executed.lock().unwrap().clone();
+        assert!(matches!(
+            executed.as_slice(),
+            [
+                ArchOperation::SecurityScan { full_scan: true },
+                ArchOperation::SystemCleanup {
+                    clean_cache: true,
+                    clean_logs: true
+                },
+            ]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_failed_scan_takes_the_false_branch() {
+        let agent = Arc::new(RwLock::new(FakeAgent::default()));
+        let result = run(agent.clone(), SCAN_THEN_CLEANUP).await;
+
+        assert!(matches!(result.status, ExecutionStatus::Success));
+        assert!(matches!(
+            execution(&result, "cleanup").status,
+            ExecutionStatus::Skipped
+        ));
+        let executed = agent.read().await.executed.lock().unwrap().clone();
+        assert!(matches!(
+            executed.as_slice(),
+            [
+                ArchOperation::SecurityScan { .. },
+                ArchOperation::HealthCheck { .. }
+            ]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_timeout_cancels_the_operation_on_the_agent() {
+        let agent = Arc::new(RwLock::new(FakeAgent {
+            hang: true,
+            ..Default::default()
+        }));
+        let yaml = SCAN_THEN_CLEANUP.replace("timeout_seconds: 60", "timeout_seconds: 1");
+        let result = run(agent.clone(), &yaml).await;
+
+        assert!(matches!(result.status, ExecutionStatus::Error));
+        let scan = execution(&result, "scan");
+        assert!(scan
+            .error
+            .as_ref()
+            .unwrap()
+            .contains("did not finish within 1s"));
+        assert!(scan.duration_ms.unwrap() < 5000);
+
+        let agent = agent.read().await;
+        assert_eq!(agent.cancelled.lock().unwrap().len(), 1);
+        assert!(agent.operations.is_empty());
+    }
+
+    async fn execute_node(
+        node: &ArchOperationNode,
+        config: &serde_json::Value,
+        delivery: &str,
+    ) -> NodeExecutionResult {
+        let mut context = WorkflowContext::default();
+        let inputs = HashMap::from([("delivery".to_string(), json!({ "id": delivery }))]);
+        let config = serde_json::from_value(config.clone()).unwrap();
+        GhostFlowNode::execute(node, &mut context, inputs, config)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_redelivered_triggers_run_the_operation_once() {
+        let agent = Arc::new(RwLock::new(FakeAgent {
+            success: true,
+            ..Default::default()
+        }));
+        let node = ArchOperationNode::with_agent(agent.clone());
+        let config = json!({
+            "operation": "UpdatePackages",
+            "idempotency_key": "/delivery/id",
+        });
+
+        let runs =
+            futures::future::join_all((0..4).map(|_| execute_node(&node, &config, "delivery-1")))
+                .await;
+        assert_eq!(agent.read().await.executed.lock().unwrap().len(), 1);
+        assert!(runs.iter().all(|r| r.output["success"] == json!(true)));
+        let replayed = runs
+            .iter()
+            .filter(|r| r.metadata["replayed"] == json!(true))
+            .count();
+        assert_eq!(replayed, 3);
+
+        // A new delivery runs again
+        execute_node(&node, &config, "delivery-2").await;
+        assert_eq!(agent.read().await.executed.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_config_maps_inputs_onto_parameters() {
+        let config = ArchOperationConfig::from_value(json!({
+            "operation": "RemovePackage",
+            "parameters": { "remove_deps": false },
+            "input_mapping": { "package": "/alert/package" },
+        }))
+        .unwrap();
+        let operation = config
+            .operation(&json!({ "alert": { "package": "evil-bin" } }))
+            .unwrap();
+        assert!(matches!(
+            operation,
+            ArchOperation::RemovePackage { ref package, remove_deps: false } if package == "evil-bin"
+        ));
+        assert!(config.operation(&json!({})).is_err());
+
+        let unit = ArchOperationConfig::from_value(json!({ "operation": "StageUpdates" })).unwrap();
+        assert!(matches!(
+            unit.operation(&json!({})).unwrap(),
+            ArchOperation::StageUpdates
+        ));
+        assert!(ArchOperationConfig::from_value(json!({ "operation": "FormatDisk" })).is_err());
+    }
+}
@@ -1,14 +1,19 @@
-use super::{GhostFlowNode, NodeHealth, HealthStatus};
-use crate::{Result, WorkflowContext, NodeExecutionResult, ExecutionStatus, BlockchainConfig, GasSettings};
+use super::{
+    ExecutionContext, GhostFlowNode, HealthStatus, NodeDefinition, NodeHealth, NodeInstance,
+    NodeOutput,
+};
+use crate::evm::{self, rpc, send, signing, EvmProvider, FeeStrategy, HttpProvider, NonceTracker};
+use crate::{Result, WorkflowContext, NodeExecutionResult, ExecutionStatus, BlockchainConfig, GasSettings, GhostFlowError};
+use anyhow::Context;
 use async_trait::async_trait;
 use jarvis_agent::{BlockchainMonitorAgent, AIBlockchainAnalyzer, MonitoringConfig, AnalysisType};
+use jarvis_core::secrets::SecretRef;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
-use uuid::Uuid;
 use chrono::Utc;
 
 /// Blockchain Monitor Node for tracking blockchain networks and smart contracts
@@ -18,10 +23,21 @@ pub struct BlockchainMonitorNode {
     health: Arc<RwLock<NodeHealth>>,
 }
 
+pub const TRANSACTION_NODE_TYPE: &str = "jarvis.blockchain.transaction";
+
 /// Blockchain Transaction Node for executing transactions with gas optimization
+///
+/// Sends estimate gas, bid EIP-1559 fees from recent fee history, take a
+/// nonce from a tracker shared by every execution of the node, and wait for
+/// the receipt to reach the configured depth. The output leaves through the
+/// `success` port, or through `failure` with the decoded revert reason when
+/// the transaction reverts, times out, or is rejected.
 pub struct TransactionNode {
     analyzer: Arc<RwLock<Option<AIBlockchainAnalyzer>>>,
     config: TransactionConfig,
+    /// Replaces the HTTP provider built from `rpc_url`
+    provider: Option<Arc<dyn EvmProvider>>,
+    nonces: Arc<NonceTracker>,
     health: Arc<RwLock<NodeHealth>>,
 }
 
@@ -36,13 +52,31 @@ pub struct BlockchainMonitorConfig {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct TransactionConfig {
     pub default_network: String,
     pub gas_optimization: bool,
     pub simulation_before_send: bool,
+    /// Cap on the max fee per gas
     pub max_gas_price_gwei: u64,
     pub slippage_tolerance: f64,
+    /// Times a send is re-signed with a fresh nonce after the node rejects one
     pub retry_attempts: u32,
+    /// JSON-RPC endpoint of the chain
+    pub rpc_url: Option<String>,
+    /// Refuse endpoints serving another chain
+    pub chain_id: Option<u64>,
+    /// Where the signing key is read from, e.g. `env:DEPLOYER_KEY`,
+    /// `file:~/.config/jarvis/deployer.key`, or `keyring:jarvis/deployer`
+    pub signer: Option<SecretRef>,
+    /// Estimated gas is multiplied by this for the gas limit
+    pub gas_multiplier: f64,
+    pub fee_strategy: FeeStrategy,
+    /// Blocks, counting the one including the transaction, before a send
+    /// counts as confirmed
+    pub confirmations: u64,
+    pub receipt_timeout_seconds: u64,
+    pub poll_interval_ms: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -67,9 +101,12 @@ pub struct BlockchainMonitorInput {
 pub struct TransactionInput {
     pub action: TransactionAction,
     pub network: String,
-    pub transaction_data: TransactionData,
+    /// Required for every action except `get_transaction_status`
+    pub transaction_data: Option<TransactionData>,
     pub gas_settings: Option<GasSettings>,
     pub simulate_first: Option<bool>,
+    /// Transaction `get_transaction_status` looks up
+    pub transaction_hash: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -84,6 +121,7 @@ pub enum MonitorAction {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum TransactionAction {
     SendTransaction,
     SimulateTransaction,
@@ -101,10 +139,13 @@ pub struct BlockRange {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransactionData {
+    /// Empty deploys `data` as a contract
     pub to: String,
+    /// Wei, in decimal or `0x` hex
     pub value: Option<String>,
     pub data: Option<String>,
     pub gas_limit: Option<u64>,
+    /// Wei per gas; unset fees are estimated
     pub max_fee_per_gas: Option<u64>,
     pub max_priority_fee_per_gas: Option<u64>,
 }
@@ -365,9 +406,20 @@ impl BlockchainMonitorNode {
 
 impl TransactionNode {
     pub fn new() -> Result<Self> {
-        Ok(Self {
+        Ok(Self::build(None))
+    }
+
+    /// Send through `provider` instead of the configured `rpc_url`
+    pub fn with_provider(provider: Arc<dyn EvmProvider>) -> Self {
+        Self::build(Some(provider))
+    }
+
+    fn build(provider: Option<Arc<dyn EvmProvider>>) -> Self {
+        Self {
             analyzer: Arc::new(RwLock::new(None)),
             config: TransactionConfig::default(),
+            provider,
+            nonces: Arc::new(NonceTracker::default()),
             health: Arc::new(RwLock::new(NodeHealth {
                 status: HealthStatus::Unknown,
                 message: None,
@@ -375,7 +427,16 @@ impl TransactionNode {
                 error_count: 0,
                 success_rate: 0.0,
             })),
-        })
+        }
+    }
+
+    fn provider(&self, config: &TransactionConfig) -> Result<Arc<dyn EvmProvider>> {
+        match &self.provider {
+            Some(provider) => Ok(provider.clone()),
+            None => config
+                .provider()
+                .map_err(|e| GhostFlowError::Config(e.to_string())),
+        }
     }
 
     async fn initialize_analyzer(&self, config: &HashMap<String, serde_json::Value>) -> Result<()> {
@@ -393,31 +454,6 @@ impl TransactionNode {
         Ok(())
     }
 
-    async fn simulate_transaction(&self, input: &TransactionInput) -> Result<TransactionOutput> {
-        // Simulate transaction execution
-        let simulation_results = SimulationResults {
-            will_succeed: true,
-            estimated_gas: 21000,
-            revert_reason: None,
-            state_changes: vec![],
-            events_emitted: vec![],
-        };
-
-        Ok(TransactionOutput {
-            action_performed: TransactionAction::SimulateTransaction,
-            success: true,
-            transaction_hash: None,
-            gas_used: None,
-            gas_price: None,
-            total_cost_eth: None,
-            simulation_results: Some(simulation_results),
-            optimization_suggestions: Some(vec![
-                "Consider batching multiple operations".to_string(),
-                "Use CREATE2 for deterministic addresses".to_string(),
-            ]),
-        })
-    }
-
     async fn optimize_gas(&self, input: &TransactionInput) -> Result<TransactionOutput> {
         let analyzer = self.analyzer.read().await;
         let _analyzer = analyzer.as_ref().ok_or_else(|| 
@@ -443,24 +479,6 @@ impl TransactionNode {
         })
     }
 
-    async fn send_transaction(&self, input: &TransactionInput) -> Result<TransactionOutput> {
-        // In real implementation, this would interact with blockchain networks
-        // For now, simulate transaction sending
-        
-        let transaction_hash = format!("0x{}", Uuid::new_v4().to_string().replace("-", ""));
-        
-        Ok(TransactionOutput {
-            action_performed: TransactionAction::SendTransaction,
-            success: true,
-            transaction_hash: Some(transaction_hash),
-            gas_used: Some(21000),
-            gas_price: Some(25_000_000_000),
-            total_cost_eth: Some(0.000525),
-            simulation_results: None,
-            optimization_suggestions: None,
-        })
-    }
-
     async fn update_health_metrics(&self, success: bool, execution_time_ms: u64) {
         let mut health = self.health.write().await;
         
@@ -479,6 +497,325 @@ impl TransactionNode {
     }
 }
 
+impl TransactionConfig {
+    pub fn from_value(value: serde_json::Value) -> Result<Self> {
+        let config: Self = serde_json::from_value(value).map_err(|e| {
+            GhostFlowError::Config(format!("Invalid {} config: {}", TRANSACTION_NODE_TYPE, e))
+        })?;
+        if config.max_gas_price_gwei == 0 {
+            return Err(GhostFlowError::Config(
+                "max_gas_price_gwei must be greater than 0".to_string(),
+            ));
+        }
+        if config.gas_multiplier.is_nan() || config.gas_multiplier < 1.0 {
+            return Err(GhostFlowError::Config(
+                "gas_multiplier must be at least 1.0".to_string(),
+            ));
+        }
+        Ok(config)
+    }
+
+    fn send_options(&self) -> evm::SendOptions {
+        evm::SendOptions {
+            gas_multiplier: self.gas_multiplier,
+            fee_strategy: self.fee_strategy,
+            fee_cap: Some(self.max_gas_price_gwei as u128 * 1_000_000_000),
+            confirmations: self.confirmations,
+            receipt_timeout: Duration::from_secs(self.receipt_timeout_seconds),
+            poll_interval: Duration::from_millis(self.poll_interval_ms.max(1)),
+            nonce_retries: self.retry_attempts,
+        }
+    }
+
+    fn provider(&self) -> anyhow::Result<Arc<dyn EvmProvider>> {
+        let url = self
+            .rpc_url
+            .as_ref()
+            .context("No rpc_url configured for the transaction node")?;
+        Ok(Arc::new(HttpProvider::new(url.clone())))
+    }
+
+    async fn signer(&self) -> anyhow::Result<evm::Signer> {
+        let reference = self
+            .signer
+            .as_ref()
+            .context("No signer configured; set it to an env:, file:, or keyring: reference")?;
+        let key = reference.resolve().await?;
+        evm::Signer::from_hex(key.expose())
+            .with_context(|| format!("Invalid signing key in {}", reference))
+    }
+
+    /// The endpoint's chain, which must be the configured one if any
+    async fn chain_id(&self, provider: &dyn EvmProvider) -> anyhow::Result<u64> {
+        let served = rpc::chain_id(provider).await?;
+        match self.chain_id {
+            Some(expected) if expected != served => anyhow::bail!(
+                "RPC endpoint serves chain {}, not the configured {}",
+                served,
+                expected
+            ),
+            _ => Ok(served),
+        }
+    }
+}
+
+impl TransactionData {
+    fn request(&self) -> anyhow::Result<evm::Request> {
+        let to = if self.to.is_empty() {
+            None
+        } else {
+            Some(self.to.parse()?)
+        };
+        Ok(evm::Request {
+            to,
+            value: self.value.as_deref().map(send::parse_wei).transpose()?.unwrap_or(0),
+            data: self
+                .data
+                .as_deref()
+                .map(signing::from_hex)
+                .transpose()?
+                .unwrap_or_default(),
+            gas_limit: self.gas_limit,
+            max_fee_per_gas: self.max_fee_per_gas.map(u128::from),
+            max_priority_fee_per_gas: self.max_priority_fee_per_gas.map(u128::from),
+        })
+    }
+}
+
+impl TransactionInput {
+    fn request(&self) -> anyhow::Result<evm::Request> {
+        self.transaction_data
+            .as_ref()
+            .context("transaction_data is required")?
+            .request()
+    }
+}
+
+/// Wei amounts in outputs; gas prices fit comfortably
+fn wei(amount: u128) -> u64 {
+    u64::try_from(amount).unwrap_or(u64::MAX)
+}
+
+/// Output leaving through the `success` port
+fn success_port(action: &TransactionAction, mut fields: serde_json::Value) -> serde_json::Value {
+    fields["branch"] = json!("success");
+    fields["success"] = json!(true);
+    fields["action_performed"] = json!(action);
+    fields
+}
+
+/// Output leaving through the `failure` port; `status` is `reverted`,
+/// `timeout`, or `rejected`
+fn failure_port(
+    action: &TransactionAction,
+    status: &str,
+    error: String,
+    mut fields: serde_json::Value,
+) -> serde_json::Value {
+    fields["branch"] = json!("failure");
+    fields["success"] = json!(false);
+    fields["action_performed"] = json!(action);
+    fields["status"] = json!(status);
+    fields["error"] = json!(error);
+    fields
+}
+
+/// Carry out `input`. Outcomes a workflow should branch on, including
+/// errors from the node or the chain, come back on the `failure` port.
+pub async fn run_transaction(
+    provider: &dyn EvmProvider,
+    nonces: &NonceTracker,
+    config: &TransactionConfig,
+    input: &TransactionInput,
+) -> serde_json::Value {
+    let outcome = match input.action {
+        TransactionAction::SendTransaction => send_transaction(provider, nonces, config, input).await,
+        TransactionAction::SimulateTransaction => simulate_transaction(provider, config, input).await,
+        TransactionAction::GetGasEstimate => estimate_transaction(provider, config, input).await,
+        TransactionAction::GetTransactionStatus => transaction_status(provider, input).await,
+        TransactionAction::OptimizeGas | TransactionAction::CancelTransaction => Err(
+            anyhow::anyhow!("{} is not supported by this node", json!(input.action)),
+        ),
+    };
+    outcome.unwrap_or_else(|e| failure_port(&input.action, "rejected", format!("{:#}", e), json!({})))
+}
+
+async fn send_transaction(
+    provider: &dyn EvmProvider,
+    nonces: &NonceTracker,
+    config: &TransactionConfig,
+    input: &TransactionInput,
+) -> anyhow::Result<serde_json::Value> {
+    let action = &input.action;
+    let request = input.request()?;
+    let signer = config.signer().await?;
+    let chain_id = config.chain_id(provider).await?;
+
+    // Gas estimation simulates the call; with a fixed gas limit only an
+    // explicit simulation does
+    if request.gas_limit.is_some() && input.simulate_first.unwrap_or(config.simulation_before_send) {
+        if let Err(e) = rpc::call(provider, &request.call(signer.address()), "latest").await {
+            let reason = rpc::revert_reason(&e).ok_or(e)?;
+            return Ok(reverted(action, &reason, json!({})));
+        }
+    }
+
+    let outcome = send::send(provider, &signer, nonces, chain_id, &request, &config.send_options()).await?;
+    Ok(match outcome {
+        evm::Outcome::Confirmed { sent, receipt } => success_port(
+            action,
+            json!({
+                "status": "confirmed",
+                "transaction_hash": receipt.transaction_hash,
+                "block_number": receipt.block_number,
+                "gas_used": receipt.gas_used,
+                "effective_gas_price": wei(receipt.effective_gas_price),
+                "total_cost_eth": send::ether(receipt.gas_used as u128 * receipt.effective_gas_price),
+                "from": sent.from.to_string(),
+                "nonce": sent.nonce,
+                "gas_limit": sent.gas_limit,
+                "confirmations": config.confirmations.max(1),
+            }),
+        ),
+        evm::Outcome::Reverted { sent, receipt, reason } => reverted(
+            action,
+            &reason,
+            json!({
+                "transaction_hash": sent.as_ref().map(|s| &s.hash),
+                "nonce": sent.as_ref().map(|s| s.nonce),
+                "block_number": receipt.as_ref().map(|r| r.block_number),
+                "gas_used": receipt.as_ref().map(|r| r.gas_used),
+            }),
+        ),
+        evm::Outcome::TimedOut { sent, receipt } => failure_port(
+            action,
+            "timeout",
+            format!(
+                "{} was not confirmed within {}s; it may still be mined",
+                sent.hash, config.receipt_timeout_seconds
+            ),
+            json!({
+                "transaction_hash": sent.hash,
+                "nonce": sent.nonce,
+                "block_number": receipt.as_ref().map(|r| r.block_number),
+            }),
+        ),
+    })
+}
+
+fn reverted(action: &TransactionAction, reason: &str, mut fields: serde_json::Value) -> serde_json::Value {
+    fields["revert_reason"] = json!(reason);
+    failure_port(action, "reverted", format!("Transaction reverted: {}", reason), fields)
+}
+
+/// Estimates run from the signer's address when one is configured, since
+/// the call may depend on the sender
+async fn caller(config: &TransactionConfig) -> anyhow::Result<evm::Address> {
+    match config.signer {
+        Some(_) => Ok(config.signer().await?.address()),
+        None => Ok(evm::Address([0; 20])),
+    }
+}
+
+async fn estimate_transaction(
+    provider: &dyn EvmProvider,
+    config: &TransactionConfig,
+    input: &TransactionInput,
+) -> anyhow::Result<serde_json::Value> {
+    let request = input.request()?;
+    let from = caller(config).await?;
+    let gas_limit = match send::gas_limit(provider, from, &request, config.gas_multiplier).await? {
+        Ok(limit) => limit,
+        Err(reason) => return Ok(reverted(&input.action, &reason, json!({}))),
+    };
+    let fees = send::fees_for(provider, &request, &config.send_options()).await?;
+    Ok(success_port(
+        &input.action,
+        json!({
+            "gas_limit": gas_limit,
+            "max_fee_per_gas": wei(fees.max_fee_per_gas),
+            "max_priority_fee_per_gas": wei(fees.max_priority_fee_per_gas),
+            "fee_strategy": config.fee_strategy,
+            "max_cost_eth": send::ether(gas_limit as u128 * fees.max_fee_per_gas),
+        }),
+    ))
+}
+
+async fn simulate_transaction(
+    provider: &dyn EvmProvider,
+    config: &TransactionConfig,
+    input: &TransactionInput,
+) -> anyhow::Result<serde_json::Value> {
+    let request = input.request()?;
+    let from = caller(config).await?;
+    let return_data = match rpc::call(provider, &request.call(from), "latest").await {
+        Ok(data) => data,
+        Err(e) => {
+            let reason = rpc::revert_reason(&e).ok_or(e)?;
+            let results = SimulationResults {
+                will_succeed: false,
+                estimated_gas: 0,
+                revert_reason: Some(reason.clone()),
+                state_changes: vec![],
+                events_emitted: vec![],
+            };
+            return Ok(reverted(&input.action, &reason, json!({ "simulation_results": results })));
+        }
+    };
+    let estimated_gas = rpc::estimate_gas(provider, &request.call(from)).await?;
+    let results = SimulationResults {
+        will_succeed: true,
+        estimated_gas,
+        revert_reason: None,
+        state_changes: vec![],
+        events_emitted: vec![],
+    };
+    Ok(success_port(
+        &input.action,
+        json!({
+            "simulation_results": results,
+            "return_data": signing::to_hex(&return_data),
+        }),
+    ))
+}
+
+/// A pending transaction leaves through `success` with status `pending`
+async fn transaction_status(
+    provider: &dyn EvmProvider,
+    input: &TransactionInput,
+) -> anyhow::Result<serde_json::Value> {
+    let hash = input
+        .transaction_hash
+        .as_deref()
+        .context("transaction_hash is required")?;
+    let Some(receipt) = rpc::receipt(provider, hash).await? else {
+        return Ok(success_port(
+            &input.action,
+            json!({ "status": "pending", "transaction_hash": hash }),
+        ));
+    };
+    let head = rpc::block_number(provider).await?;
+    let fields = json!({
+        "transaction_hash": hash,
+        "block_number": receipt.block_number,
+        "gas_used": receipt.gas_used,
+        "effective_gas_price": wei(receipt.effective_gas_price),
+        "confirmations": (head + 1).saturating_sub(receipt.block_number),
+    });
+    if receipt.success {
+        let mut fields = fields;
+        fields["status"] = json!("confirmed");
+        Ok(success_port(&input.action, fields))
+    } else {
+        Ok(failure_port(
+            &input.action,
+            "reverted",
+            "Transaction reverted".to_string(),
+            fields,
+        ))
+    }
+}
+
 // Implement GhostFlowNode for BlockchainMonitorNode
 #[async_trait]
 impl GhostFlowNode for BlockchainMonitorNode {
@@ -679,7 +1016,7 @@ impl GhostFlowNode for BlockchainMonitorNode {
 #[async_trait]
 impl GhostFlowNode for TransactionNode {
     fn node_type(&self) -> &'static str {
-        "jarvis.blockchain.transaction"
+        TRANSACTION_NODE_TYPE
     }
 
     fn display_name(&self) -> &str {
@@ -687,7 +1024,7 @@ impl GhostFlowNode for TransactionNode {
     }
 
     fn description(&self) -> &str {
-        "Execute blockchain transactions with AI-powered gas optimization and simulation"
+        "Send EVM transactions with gas estimation, EIP-1559 fees, nonce management, and receipt tracking"
     }
 
     fn input_schema(&self) -> serde_json::Value {
@@ -706,12 +1043,14 @@ impl GhostFlowNode for TransactionNode {
                 },
                 "transaction_data": {
                     "type": "object",
-                    "description": "Transaction details",
+                    "description": "Transaction details; required except for get_transaction_status",
                     "properties": {
-                        "to": { "type": "string", "description": "Recipient address" },
-                        "value": { "type": "string", "description": "Value to send (in wei)" },
-                        "data": { "type": "string", "description": "Transaction data" },
-                        "gas_limit": { "type": "integer", "description": "Gas limit" }
+                        "to": { "type": "string", "description": "Recipient address; empty deploys data as a contract" },
+                        "value": { "type": "string", "description": "Value to send in wei, decimal or 0x hex" },
+                        "data": { "type": "string", "description": "Calldata as 0x hex" },
+                        "gas_limit": { "type": "integer", "description": "Gas limit; estimated when unset" },
+                        "max_fee_per_gas": { "type": "integer", "description": "Max fee per gas in wei; estimated when unset" },
+                        "max_priority_fee_per_gas": { "type": "integer", "description": "Priority fee per gas in wei; estimated when unset" }
                     },
                     "required": ["to"]
                 },
@@ -721,29 +1060,41 @@ impl GhostFlowNode for TransactionNode {
                 },
                 "simulate_first": {
                     "type": "boolean",
-                    "description": "Simulate before sending",
-                    "default": true
+                    "description": "Simulate before sending when the gas limit is fixed; defaults to simulation_before_send"
+                },
+                "transaction_hash": {
+                    "type": "string",
+                    "description": "Transaction to look up with get_transaction_status"
                 }
             },
-            "required": ["action", "network", "transaction_data"]
+            "required": ["action", "network"]
         })
     }
 
     fn output_schema(&self) -> serde_json::Value {
         json!({
             "type": "object",
+            "description": "Leaves through the port named in branch",
             "properties": {
+                "branch": { "type": "string", "enum": ["success", "failure"] },
                 "action_performed": { "type": "string" },
                 "success": { "type": "boolean" },
+                "status": {
+                    "type": "string",
+                    "enum": ["confirmed", "pending", "reverted", "timeout", "rejected"]
+                },
                 "transaction_hash": { "type": "string" },
+                "block_number": { "type": "integer" },
                 "gas_used": { "type": "integer" },
-                "gas_price": { "type": "integer" },
+                "gas_limit": { "type": "integer" },
+                "effective_gas_price": { "type": "integer", "description": "Wei per gas actually paid" },
                 "total_cost_eth": { "type": "number" },
-                "simulation_results": { "type": "object" },
-                "optimization_suggestions": {
-                    "type": "array",
-                    "items": { "type": "string" }
-                }
+                "from": { "type": "string" },
+                "nonce": { "type": "integer" },
+                "confirmations": { "type": "integer" },
+                "revert_reason": { "type": "string", "description": "Decoded revert reason on the failure port" },
+                "error": { "type": "string" },
+                "simulation_results": { "type": "object" }
             }
         })
     }
@@ -765,17 +1116,57 @@ impl GhostFlowNode for TransactionNode {
                 "simulation_before_send": {
                     "type": "boolean",
                     "default": true,
-                    "description": "Always simulate before sending"
+                    "description": "Simulate sends with a fixed gas limit, which skip estimation"
                 },
                 "max_gas_price_gwei": {
                     "type": "integer",
                     "default": 100,
-                    "description": "Maximum gas price in gwei"
+                    "description": "Maximum fee per gas in gwei"
                 },
                 "retry_attempts": {
                     "type": "integer",
                     "default": 3,
-                    "description": "Number of retry attempts"
+                    "description": "Times to re-sign with a fresh nonce after the node rejects one"
+                },
+                "rpc_url": {
+                    "type": "string",
+                    "description": "JSON-RPC endpoint of the chain"
+                },
+                "chain_id": {
+                    "type": "integer",
+                    "description": "Refuse endpoints serving another chain"
+                },
+                "signer": {
+                    "type": "string",
+                    "description": "Signing key reference: env:NAME, file:PATH, or keyring:SERVICE/ACCOUNT"
+                },
+                "gas_multiplier": {
+                    "type": "number",
+                    "default": 1.2,
+                    "minimum": 1.0,
+                    "description": "Safety multiplier on estimated gas"
+                },
+                "fee_strategy": {
+                    "type": "string",
+                    "enum": ["slow", "normal", "fast"],
+                    "default": "normal",
+                    "description": "Priority fee percentile and base fee headroom to bid with"
+                },
+                "confirmations": {
+                    "type": "integer",
+                    "default": 1,
+                    "minimum": 1,
+                    "description": "Blocks, counting the including one, before a send is confirmed"
+                },
+                "receipt_timeout_seconds": {
+                    "type": "integer",
+                    "default": 120,
+                    "description": "Give up waiting for confirmation after this long"
+                },
+                "poll_interval_ms": {
+                    "type": "integer",
+                    "default": 1000,
+                    "description": "How often to poll for the receipt"
                 }
             }
         })
@@ -788,75 +1179,54 @@ impl GhostFlowNode for TransactionNode {
         config: HashMap<String, serde_json::Value>,
     ) -> Result<crate::NodeExecutionResult> {
         let start_time = Instant::now();
-        
-        if self.analyzer.read().await.is_none() {
-            self.initialize_analyzer(&config).await?;
-        }
-
+        let settings = TransactionConfig::from_value(json!(config))?;
         let input: TransactionInput = serde_json::from_value(serde_json::Value::Object(
             inputs.into_iter().collect()
         ))?;
 
         let result = match input.action {
-            TransactionAction::SimulateTransaction => self.simulate_transaction(&input).await,
-            TransactionAction::OptimizeGas => self.optimize_gas(&input).await,
-            TransactionAction::SendTransaction => self.send_transaction(&input).await,
+            TransactionAction::OptimizeGas => {
+                if self.analyzer.read().await.is_none() {
+                    self.initialize_analyzer(&config).await?;
+                }
+                self.optimize_gas(&input)
+                    .await
+                    .and_then(|output| serde_json::to_value(output).map_err(GhostFlowError::from))
+            }
             _ => {
-                Ok(TransactionOutput {
-                    action_performed: input.action.clone(),
-                    success: false,
-                    transaction_hash: None,
-                    gas_used: None,
-                    gas_price: None,
-                    total_cost_eth: None,
-                    simulation_results: None,
-                    optimization_suggestions: Some(vec!["Action not implemented yet".to_string()]),
-                })
+                let provider = self.provider(&settings)?;
+                Ok(run_transaction(provider.as_ref(), &self.nonces, &settings, &input).await)
             }
         };
 
-        match result {
+        let (status, output, error) = match result {
+            Ok(output) if output["success"] == json!(true) => (ExecutionStatus::Success, output, None),
             Ok(output) => {
-                self.update_health_metrics(output.success, start_time.elapsed().as_millis() as u64).await;
-                
-                Ok(crate::NodeExecutionResult {
-                    node_id: "blockchain_transaction".to_string(),
-                    execution_id: context.execution_id,
-                    status: if output.success { ExecutionStatus::Success } else { ExecutionStatus::Failure },
-                    output: serde_json::to_value(output)?,
-                    error: None,
-                    duration_ms: start_time.elapsed().as_millis() as u64,
-                    metadata: HashMap::new(),
-                    next_nodes: vec![],
-                })
-            }
-            Err(e) => {
-                self.update_health_metrics(false, start_time.elapsed().as_millis() as u64).await;
-                
-                Ok(crate::NodeExecutionResult {
-                    node_id: "blockchain_transaction".to_string(),
-                    execution_id: context.execution_id,
-                    status: ExecutionStatus::Failure,
-                    output: json!({}),
-                    error: Some(e.to_string()),
-                    duration_ms: start_time.elapsed().as_millis() as u64,
-                    metadata: HashMap::new(),
-                    next_nodes: vec![],
-                })
+                let error = output["error"].as_str().map(str::to_string);
+                (ExecutionStatus::Failure, output, error)
             }
-        }
+            Err(e) => (ExecutionStatus::Failure, json!({}), Some(e.to_string())),
+        };
+        self.update_health_metrics(
+            matches!(status, ExecutionStatus::Success),
+            start_time.elapsed().as_millis() as u64,
+        )
+        .await;
+
+        Ok(crate::NodeExecutionResult {
+            node_id: context.current_node.clone(),
+            execution_id: context.execution_id,
+            status,
+            output,
+            error,
+            duration_ms: start_time.elapsed().as_millis() as u64,
+            metadata: HashMap::new(),
+            next_nodes: vec![],
+        })
     }
 
     fn validate_config(&self, config: &HashMap<String, serde_json::Value>) -> Result<()> {
-        if let Some(max_gas_price) = config.get("max_gas_price_gwei") {
-            if let Some(price) = max_gas_price.as_u64() {
-                if price == 0 {
-                    return Err(crate::GhostFlowError::Config(
-                        "max_gas_price_gwei must be greater than 0".to_string()
-                    ));
-                }
-            }
-        }
+        TransactionConfig::from_value(json!(config))?;
         Ok(())
     }
 
@@ -865,6 +1235,60 @@ impl GhostFlowNode for TransactionNode {
     }
 }
 
+#[async_trait]
+impl NodeDefinition for TransactionNode {
+    fn node_type(&self) -> &'static str {
+        TRANSACTION_NODE_TYPE
+    }
+
+    fn create_instance(&self) -> anyhow::Result<Box<dyn NodeInstance + Send + Sync>> {
+        Ok(Box::new(TransactionInstance {
+            provider: self.provider.clone(),
+            nonces: self.nonces.clone(),
+            config: None,
+        }))
+    }
+}
+
+/// Instances share the node's nonce tracker, so parallel branches sending
+/// from one account take turns
+pub struct TransactionInstance {
+    provider: Option<Arc<dyn EvmProvider>>,
+    nonces: Arc<NonceTracker>,
+    config: Option<TransactionConfig>,
+}
+
+#[async_trait]
+impl NodeInstance for TransactionInstance {
+    async fn configure(&mut self, parameters: serde_json::Value) -> anyhow::Result<()> {
+        self.config = Some(TransactionConfig::from_value(parameters)?);
+        Ok(())
+    }
+
+    /// Inputs are the trigger payload's fields plus each finished node's
+    /// output under its id
+    async fn execute(&mut self, context: &ExecutionContext) -> anyhow::Result<NodeOutput> {
+        let config = self
+            .config
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("{} executed before configure", TRANSACTION_NODE_TYPE))?;
+
+        let mut inputs = context.data.as_object().cloned().unwrap_or_default();
+        for (node_id, output) in &context.node_outputs {
+            inputs.insert(node_id.clone(), output.data.clone());
+        }
+        let input: TransactionInput = serde_json::from_value(serde_json::Value::Object(inputs))?;
+
+        let provider = match &self.provider {
+            Some(provider) => provider.clone(),
+            None => config.provider()?,
+        };
+        Ok(NodeOutput {
+            data: run_transaction(provider.as_ref(), &self.nonces, config, &input).await,
+        })
+    }
+}
+
 // Default implementations
 impl Default for BlockchainMonitorConfig {
     fn default() -> Self {
@@ -893,6 +1317,14 @@ impl Default for TransactionConfig {
             max_gas_price_gwei: 100,
             slippage_tolerance: 0.01,
             retry_attempts: 3,
+            rpc_url: None,
+            chain_id: None,
+            signer: None,
+            gas_multiplier: 1.2,
+            fee_strategy: FeeStrategy::Normal,
+            confirmations: 1,
+            receipt_timeout_seconds: 120,
+            poll_interval_ms: 1000,
         }
     }
 }
@@ -924,4 +1356,324 @@ impl Default for MonitoringMetrics {
             uptime_percentage: 100.0,
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evm::RpcError;
+    use crate::workflow_engine::{
+        ExecutionMode, ExecutionResult, ExecutionStatus, MergeNode, NodeExecution, StartNode,
+        WorkflowEngine,
+    };
+    use std::sync::Mutex;
+
+    /// The first account anvil creates
+    const ANVIL_KEY: &str = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+    const KEY_VARIABLE: &str = "JARVIS_TEST_DEPLOYER_KEY";
+
+    /// `Error("Not enough balance")`
+    const REVERT_DATA: &str = concat!(
+        "0x08c379a0",
+        "0000000000000000000000000000000000000000000000000000000000000020",
+        "0000000000000000000000000000000000000000000000000000000000000012",
+        "4e6f7420656e6f7567682062616c616e63650000000000000000000000000000",
+    );
+
+    /// Sends to `confirmed` on success and `alert` on failure
+    const SEND_WORKFLOW: &str = r#"
+kind: GhostFlowWorkflow
+version: v1
+metadata:
+  name: Send and branch
+settings:
+  timeout_seconds: 300
+  error_workflow: null
+  save_data_execution_progress: false
+  save_data_success: true
+  save_data_error: true
+  save_manual_executions: false
+  caller_policy: None
+nodes:
+  - id: start
+    type: start
+    position: { x: 0.0, y: 0.0 }
+  - id: send
+    type: jarvis.blockchain.transaction
+    config:
+      signer: env:JARVIS_TEST_DEPLOYER_KEY
+      chain_id: 31337
+      confirmations: 3
+      poll_interval_ms: 1
+    position: { x: 200.0, y: 0.0 }
+  - id: confirmed
+    type: merge
+    position: { x: 400.0, y: -100.0 }
+  - id: alert
+    type: merge
+    position: { x: 400.0, y: 100.0 }
+edges:
+  - { from: start, to: send }
+  - { from: send, from_output: success, to: confirmed }
+  - { from: send, from_output: failure, to: alert }
+"#;
+
+    /// A chain that mines each transaction into its own block, and whose
+    /// pending nonce never counts them, like a node behind a load balancer
+    #[derive(Default)]
+    struct MockChain {
+        /// Gas estimation and calls revert
+        estimate_reverts: bool,
+        /// Transactions revert once mined
+        mined_reverts: bool,
+        /// Transactions are never mined
+        unmined: bool,
+        state: Mutex<ChainState>,
+    }
+
+    #[derive(Default)]
+    struct ChainState {
+        head: u64,
+        /// Block each transaction was mined in, by hash
+        mined: HashMap<String, u64>,
+        /// Nonces of the transactions sent
+        nonces: Vec<u64>,
+    }
+
+    impl MockChain {
+        fn nonces(&self) -> Vec<u64> {
+            self.state.lock().unwrap().nonces.clone()
+        }
+    }
+
+    #[async_trait]
+    impl EvmProvider for MockChain {
+        async fn request(
+            &self,
+            method: &str,
+            params: serde_json::Value,
+        ) -> anyhow::Result<serde_json::Value> {
+            let reverted = || RpcError {
+                code: 3,
+                message: "execution reverted".to_string(),
+                data: Some(json!(REVERT_DATA)),
+            };
+            let mut state = self.state.lock().unwrap();
+            Ok(match method {
+                "eth_chainId" => json!("0x7a69"),
+                "eth_getTransactionCount" => json!("0x0"),
+                "eth_estimateGas" if self.estimate_reverts => return Err(reverted().into()),
+                "eth_estimateGas" => json!("0x5208"),
+                "eth_call" if self.estimate_reverts || self.mined_reverts => {
+                    return Err(reverted().into())
+                }
+                "eth_call" => json!("0x"),
+                "eth_feeHistory" => json!({
+                    "baseFeePerGas": ["0x3b9aca00", "0x3b9aca00"],
+                    "reward": [["0x3b9aca00"]],
+                }),
+                "eth_sendRawTransaction" => {
+                    let raw = signing::from_hex(params[0].as_str().unwrap())?;
+                    state.nonces.push(nonce_of(&raw));
+                    let hash = signing::to_hex(&signing::keccak256(&raw));
+                    if !self.unmined {
+                        state.head += 1;
+                        let block = state.head;
+                        state.mined.insert(hash.clone(), block);
+                    }
+                    json!(hash)
+                }
+                "eth_getTransactionReceipt" => match state.mined.get(params[0].as_str().unwrap()) {
+                    Some(block) => json!({
+                        "blockNumber": rpc::quantity(*block as u128),
+                        "status": if self.mined_reverts { "0x0" } else { "0x1" },
+                        "gasUsed": "0x5208",
+                        "effectiveGasPrice": "0x3b9aca00",
+                    }),
+                    None => serde_json::Value::Null,
+                },
+                // Every poll sees a new block
+                "eth_blockNumber" => {
+                    state.head += 1;
+                    json!(rpc::quantity(state.head as u128))
+                }
+                _ => anyhow::bail!("unexpected {}", method),
+            })
+        }
+    }
+
+    /// Nonce of a signed type-2 transaction: after the type byte and list
+    /// header comes the chain id (`0x827a69`), then the nonce
+    fn nonce_of(raw: &[u8]) -> u64 {
+        let header = if raw[1] > 0xf7 { 1 + (raw[1] - 0xf7) as usize } else { 1 };
+        let item = &raw[1 + header + 3..];
+        match item[0] {
+            byte if byte < 0x80 => byte as u64,
+            byte => item[1..=(byte - 0x80) as usize]
+                .iter()
+                .fold(0, |nonce, &b| nonce << 8 | b as u64),
+        }
+    }
+
+    fn send_input() -> serde_json::Value {
+        json!({
+            "action": "send_transaction",
+            "network": "ethereum",
+            "transaction_data": {
+                "to": "0x70997970c51812dc3a010c7d01b50e0d17dc79c8",
+                "value": "1000000000000000000",
+            },
+        })
+    }
+
+    async fn run(chain: Arc<MockChain>) -> ExecutionResult {
+        std::env::set_var(KEY_VARIABLE, ANVIL_KEY);
+        let engine = WorkflowEngine::new().unwrap();
+        engine.register_node(Box::new(StartNode::new())).await;
+        engine.register_node(Box::new(MergeNode::new())).await;
+        engine
+            .register_node(Box::new(TransactionNode::with_provider(chain)))
+            .await;
+        let workflow_id = engine.import_yaml(SEND_WORKFLOW).await.unwrap();
+        engine
+            .execute_workflow(workflow_id, send_input(), ExecutionMode::Manual)
+            .await
+            .unwrap()
+    }
+
+    fn execution<'a>(result: &'a ExecutionResult, node_id: &str) -> &'a NodeExecution {
+        result
+            .node_executions
+            .iter()
+            .find(|n| n.node_id == node_id)
+            .unwrap_or_else(|| panic!("no execution recorded for {}", node_id))
+    }
+
+    async fn execute_node(
+        node: &TransactionNode,
+        config: serde_json::Value,
+        input: serde_json::Value,
+    ) -> NodeExecutionResult {
+        std::env::set_var(KEY_VARIABLE, ANVIL_KEY);
+        let mut context = WorkflowContext::default();
+        let inputs = serde_json::from_value(input).unwrap();
+        let mut config: HashMap<String, serde_json::Value> = serde_json::from_value(config).unwrap();
+        config.insert("signer".to_string(), json!(format!("env:{}", KEY_VARIABLE)));
+        config.insert("poll_interval_ms".to_string(), json!(1));
+        GhostFlowNode::execute(node, &mut context, inputs, config)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_send_waits_for_confirmations() {
+        let chain = Arc::new(MockChain::default());
+        let result = run(chain.clone()).await;
+
+        assert!(matches!(result.status, ExecutionStatus::Success));
+        assert!(matches!(
+            execution(&result, "alert").status,
+            ExecutionStatus::Skipped
+        ));
+        let send = execution(&result, "send").output_data.as_ref().unwrap();
+        assert_eq!(send["status"], json!("confirmed"));
+        assert_eq!(send["block_number"], json!(1));
+        assert_eq!(send["from"], json!("0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266"));
+        assert_eq!(send["gas_limit"], json!(25_200));
+        assert_eq!(send["effective_gas_price"], json!(1_000_000_000));
+        // Mined in block 1, confirmed once the head reached block 3
+        assert_eq!(chain.state.lock().unwrap().head, 3);
+        assert_eq!(chain.nonces(), vec![0]);
+    }
+
+    #[tokio::test]
+    async fn test_reverting_estimate_takes_the_failure_port() {
+        let chain = Arc::new(MockChain {
+            estimate_reverts: true,
+            ..Default::default()
+        });
+        let result = run(chain.clone()).await;
+
+        assert!(matches!(result.status, ExecutionStatus::Success));
+        assert!(matches!(
+            execution(&result, "confirmed").status,
+            ExecutionStatus::Skipped
+        ));
+        let send = execution(&result, "send").output_data.as_ref().unwrap();
+        assert_eq!(send["branch"], json!("failure"));
+        assert_eq!(send["status"], json!("reverted"));
+        assert_eq!(send["revert_reason"], json!("Not enough balance"));
+        assert!(chain.nonces().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_revert_after_mining_is_decoded_by_replaying_the_call() {
+        let chain = Arc::new(MockChain {
+            mined_reverts: true,
+            ..Default::default()
+        });
+        let node = TransactionNode::with_provider(chain.clone());
+        let mut input = send_input();
+        input["transaction_data"]["gas_limit"] = json!(50_000);
+        input["simulate_first"] = json!(false);
+
+        let result = execute_node(&node, json!({}), input).await;
+        assert!(matches!(result.status, crate::ExecutionStatus::Failure));
+        assert_eq!(result.output["status"], json!("reverted"));
+        assert_eq!(result.output["block_number"], json!(1));
+        assert!(result.output["transaction_hash"].is_string());
+        assert_eq!(
+            result.error.as_deref(),
+            Some("Transaction reverted: Not enough balance")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unmined_send_times_out() {
+        let chain = Arc::new(MockChain {
+            unmined: true,
+            ..Default::default()
+        });
+        let node = TransactionNode::with_provider(chain.clone());
+
+        let result = execute_node(&node, json!({ "receipt_timeout_seconds": 0 }), send_input()).await;
+        assert_eq!(result.output["branch"], json!("failure"));
+        assert_eq!(result.output["status"], json!("timeout"));
+        assert_eq!(result.output["nonce"], json!(0));
+        assert!(result.error.unwrap().contains("may still be mined"));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_sends_get_consecutive_nonces() {
+        let chain = Arc::new(MockChain::default());
+        let node = TransactionNode::with_provider(chain.clone());
+
+        let results = futures::future::join_all(
+            (0..3).map(|_| execute_node(&node, json!({}), send_input())),
+        )
+        .await;
+        assert!(results.iter().all(|r| r.output["success"] == json!(true)));
+
+        let mut nonces = chain.nonces();
+        nonces.sort_unstable();
+        assert_eq!(nonces, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_config_validation() {
+        let config = TransactionConfig::from_value(json!({
+            "signer": "keyring:jarvis/deployer",
+            "fee_strategy": "fast",
+        }))
+        .unwrap();
+        assert_eq!(config.fee_strategy, FeeStrategy::Fast);
+        assert_eq!(config.confirmations, 1);
+        assert_eq!(
+            config.signer.unwrap().to_string(),
+            "keyring:jarvis/deployer"
+        );
+
+        assert!(TransactionConfig::from_value(json!({ "gas_multiplier": 0.5 })).is_err());
+        assert!(TransactionConfig::from_value(json!({ "max_gas_price_gwei": 0 })).is_err());
+        assert!(TransactionConfig::from_value(json!({ "signer": "0xac0974" })).is_err());
+    }
+}
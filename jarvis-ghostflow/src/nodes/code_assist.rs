@@ -0,0 +1,435 @@
+//! Code completion and review as a workflow node
+//!
+//! `jarvis.code_assist` asks Zeke when its library is installed and the LLM
+//! router otherwise, so a workflow using it runs on either kind of machine.
+//! A Zeke call that fails falls back to the router too. The output's
+//! `backend` says which one answered, and `fallback_reason` says why it
+//! wasn't Zeke.
+
+use super::{GhostFlowNode, HealthStatus, NodeHealth};
+use crate::ffi::zeke::{
+    AnalysisRequest, CodeIssue, Completion, CompletionRequest, ZekeAvailability, ZekeEngine,
+};
+use crate::ffi::{FFIConfig, FFIResult};
+use crate::{ExecutionStatus, GhostFlowError, NodeExecutionResult, Result, WorkflowContext};
+use async_trait::async_trait;
+use jarvis_core::{Config as JarvisConfig, LLMRouter};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::RwLock;
+
+pub const NODE_TYPE: &str = "jarvis.code_assist";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CodeAssistAction {
+    Complete,
+    Analyze,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CodeAssistInput {
+    pub action: CodeAssistAction,
+    pub language: String,
+    /// The code to analyze, or the text before the cursor to complete
+    pub code: String,
+    /// The text after the cursor when completing
+    #[serde(default)]
+    pub suffix: String,
+    pub path: Option<String>,
+    #[serde(default = "default_max_suggestions")]
+    pub max_suggestions: u32,
+}
+
+fn default_max_suggestions() -> u32 {
+    3
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CodeAssistOutput {
+    /// `zeke` or `llm`
+    pub backend: String,
+    pub completions: Vec<Completion>,
+    pub issues: Vec<CodeIssue>,
+    pub summary: String,
+    pub fallback_reason: Option<String>,
+}
+
+pub struct CodeAssistNode {
+    zeke: ZekeAvailability,
+    llm_router: Arc<RwLock<Option<LLMRouter>>>,
+    health: Arc<RwLock<NodeHealth>>,
+}
+
+impl CodeAssistNode {
+    pub fn new() -> Result<Self> {
+        Ok(Self::with_ffi_config(&FFIConfig::default()))
+    }
+
+    /// Load Zeke with `config`; when it can't be loaded the node still
+    /// works, through the LLM router
+    pub fn with_ffi_config(config: &FFIConfig) -> Self {
+        let zeke = ZekeEngine::detect(config);
+        let message = match &zeke {
+            ZekeAvailability::Available(engine) => format!("Using Zeke {}", engine.version()),
+            ZekeAvailability::Unavailable(reason) => {
+                format!("Zeke unavailable, using the LLM router: {}", reason)
+            }
+        };
+        Self {
+            zeke,
+            llm_router: Arc::new(RwLock::new(None)),
+            health: Arc::new(RwLock::new(NodeHealth {
+                status: HealthStatus::Unknown,
+                message: Some(message),
+                last_execution: None,
+                error_count: 0,
+                success_rate: 0.0,
+            })),
+        }
+    }
+
+    /// The backend requests go to first
+    pub fn backend(&self) -> &'static str {
+        if self.zeke.engine().is_some() {
+            "zeke"
+        } else {
+            "llm"
+        }
+    }
+
+    async fn assist(&self, input: &CodeAssistInput) -> Result<CodeAssistOutput> {
+        let fallback_reason = match &self.zeke {
+            ZekeAvailability::Available(engine) => {
+                let error = match self.assist_with_zeke(engine, input).await {
+                    Ok(output) => return Ok(output),
+                    Err(e) => e,
+                };
+                tracing::warn!("Zeke failed, falling back to the LLM router: {}", error);
+                error.to_string()
+            }
+            ZekeAvailability::Unavailable(reason) => reason.clone(),
+        };
+
+        let mut output = self.assist_with_llm(input).await?;
+        output.fallback_reason = Some(fallback_reason);
+        Ok(output)
+    }
+
+    async fn assist_with_zeke(
+        &self,
+        engine: &ZekeEngine,
+        input: &CodeAssistInput,
+    ) -> FFIResult<CodeAssistOutput> {
+        let mut output = CodeAssistOutput {
+            backend: "zeke".to_string(),
+            ..CodeAssistOutput::default()
+        };
+        match input.action {
+            CodeAssistAction::Complete => {
+                let response = engine
+                    .complete(&CompletionRequest {
+                        language: input.language.clone(),
+                        prefix: input.code.clone(),
+                        suffix: input.suffix.clone(),
+                        path: input.path.clone(),
+                        max_suggestions: input.max_suggestions,
+                    })
+                    .await?;
+                output.completions = response.completions;
+            }
+            CodeAssistAction::Analyze => {
+                let response = engine
+                    .analyze(&AnalysisRequest {
+                        language: input.language.clone(),
+                        code: input.code.clone(),
+                        path: input.path.clone(),
+                    })
+                    .await?;
+                output.issues = response.issues;
+                output.summary = response.summary;
+            }
+        }
+        Ok(output)
+    }
+
+    async fn assist_with_llm(&self, input: &CodeAssistInput) -> Result<CodeAssistOutput> {
+        if self.llm_router.read().await.is_none() {
+            let config = JarvisConfig::load(None)
+                .await
+                .map_err(|e| GhostFlowError::Config(e.to_string()))?;
+            let router = LLMRouter::new(&config)
+                .await
+                .map_err(|e| GhostFlowError::NodeExecution(e.to_string()))?;
+            *self.llm_router.write().await = Some(router);
+        }
+
+        let router_guard = self.llm_router.read().await;
+        let router = router_guard.as_ref().ok_or_else(|| {
+            GhostFlowError::NodeExecution("LLM Router not initialized".to_string())
+        })?;
+        let response = router
+            .generate(&llm_prompt(input), None)
+            .await
+            .map_err(|e| GhostFlowError::NodeExecution(e.to_string()))?;
+        Ok(parse_llm_response(input.action, &response))
+    }
+
+    async fn update_health_metrics(&self, success: bool) {
+        let mut health = self.health.write().await;
+        if !success {
+            health.error_count += 1;
+        }
+        health.last_execution = Some(chrono::Utc::now());
+        health.status = if health.error_count == 0 {
+            HealthStatus::Healthy
+        } else if health.error_count < 5 {
+            HealthStatus::Warning
+        } else {
+            HealthStatus::Critical
+        };
+    }
+}
+
+/// Ask for the same shape Zeke answers with, one item per line
+fn llm_prompt(input: &CodeAssistInput) -> String {
+    match input.action {
+        CodeAssistAction::Complete => format!(
+            "Complete the {} code at <CURSOR>. Reply with only the code to insert there, \
+             without explanation or code fences.\n\n{}<CURSOR>{}",
+            input.language, input.code, input.suffix
+        ),
+        CodeAssistAction::Analyze => format!(
+            "Review this {} code. List each problem on its own line as \
+             `LINE: SEVERITY: MESSAGE`, where SEVERITY is error, warning or info, \
+             then end with one line starting `Summary:`.\n\n{}",
+            input.language, input.code
+        ),
+    }
+}
+
+fn parse_llm_response(action: CodeAssistAction, response: &str) -> CodeAssistOutput {
+    let mut output = CodeAssistOutput {
+        backend: "llm".to_string(),
+        ..CodeAssistOutput::default()
+    };
+    match action {
+        CodeAssistAction::Complete => {
+            let text = strip_code_fence(response);
+            if !text.is_empty() {
+                output.completions.push(Completion {
+                    text: text.to_string(),
+                    confidence: 0.0,
+                });
+            }
+        }
+        CodeAssistAction::Analyze => {
+            for line in response.lines().map(str::trim) {
+                if let Some(summary) = line.strip_prefix("Summary:") {
+                    output.summary = summary.trim().to_string();
+                    continue;
+                }
+                let mut parts = line.trim_start_matches(['-', '*', ' ']).splitn(3, ':');
+                let (Some(number), Some(severity), Some(message)) =
+                    (parts.next(), parts.next(), parts.next())
+                else {
+                    continue;
+                };
+                let Ok(number) = number.trim().trim_start_matches("Line").trim().parse() else {
+                    continue;
+                };
+                output.issues.push(CodeIssue {
+                    line: number,
+                    column: 0,
+                    severity: severity.trim().to_lowercase(),
+                    message: message.trim().to_string(),
+                    suggestion: None,
+                });
+            }
+        }
+    }
+    output
+}
+
+fn strip_code_fence(text: &str) -> &str {
+    let text = text.trim();
+    let Some(fenced) = text.strip_prefix("```") else {
+        return text;
+    };
+    let body = fenced.split_once('\n').map_or("", |(_, body)| body);
+    body.trim_end()
+        .strip_suffix("```")
+        .unwrap_or(body)
+        .trim_end()
+}
+
+#[async_trait]
+impl GhostFlowNode for CodeAssistNode {
+    fn node_type(&self) -> &'static str {
+        NODE_TYPE
+    }
+
+    fn display_name(&self) -> &str {
+        "Code Assist"
+    }
+
+    fn description(&self) -> &str {
+        "Code completion and analysis through Zeke, or the LLM router where Zeke isn't installed"
+    }
+
+    fn input_schema(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "action": {
+                    "type": "string",
+                    "enum": ["complete", "analyze"]
+                },
+                "language": {
+                    "type": "string",
+                    "description": "Language of the code, e.g. rust"
+                },
+                "code": {
+                    "type": "string",
+                    "description": "Code to analyze, or the text before the cursor to complete"
+                },
+                "suffix": {
+                    "type": "string",
+                    "description": "Text after the cursor when completing"
+                },
+                "path": {
+                    "type": "string",
+                    "description": "File the code is from"
+                },
+                "max_suggestions": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "default": 3
+                }
+            },
+            "required": ["action", "language", "code"]
+        })
+    }
+
+    fn output_schema(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "backend": {
+                    "type": "string",
+                    "enum": ["zeke", "llm"]
+                },
+                "completions": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "text": { "type": "string" },
+                            "confidence": { "type": "number" }
+                        }
+                    }
+                },
+                "issues": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "line": { "type": "integer" },
+                            "column": { "type": "integer" },
+                            "severity": { "type": "string" },
+                            "message": { "type": "string" },
+                            "suggestion": { "type": "string" }
+                        }
+                    }
+                },
+                "summary": { "type": "string" },
+                "fallback_reason": {
+                    "type": "string",
+                    "description": "Why the LLM router answered instead of Zeke"
+                }
+            }
+        })
+    }
+
+    fn config_schema(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {}
+        })
+    }
+
+    async fn execute(
+        &self,
+        context: &mut WorkflowContext,
+        inputs: HashMap<String, serde_json::Value>,
+        _config: HashMap<String, serde_json::Value>,
+    ) -> Result<NodeExecutionResult> {
+        let start_time = Instant::now();
+        let input: CodeAssistInput =
+            serde_json::from_value(serde_json::Value::Object(inputs.into_iter().collect()))?;
+
+        let (status, output, error) = match self.assist(&input).await {
+            Ok(output) => (
+                ExecutionStatus::Success,
+                serde_json::to_value(output)?,
+                None,
+            ),
+            Err(e) => (ExecutionStatus::Failure, json!({}), Some(e.to_string())),
+        };
+        self.update_health_metrics(error.is_none()).await;
+
+        Ok(NodeExecutionResult {
+            node_id: "code_assist".to_string(),
+            execution_id: context.execution_id,
+            status,
+            output,
+            error,
+            duration_ms: start_time.elapsed().as_millis() as u64,
+            metadata: HashMap::new(),
+            next_nodes: vec![],
+        })
+    }
+
+    fn validate_config(&self, _config: &HashMap<String, serde_json::Value>) -> Result<()> {
+        Ok(())
+    }
+
+    async fn health_check(&self) -> NodeHealth {
+        self.health.read().await.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_missing_zeke_falls_back_to_llm() {
+        let node = CodeAssistNode::with_ffi_config(&FFIConfig {
+            library_path: "/nonexistent/libzeke.so".to_string(),
+            ..FFIConfig::default()
+        });
+        assert_eq!(node.backend(), "llm");
+        let health = node.health_check().await;
+        assert!(health.message.unwrap().starts_with("Zeke unavailable"));
+    }
+
+    #[test]
+    fn test_parse_llm_response() {
+        let review = "1: warning: unused variable `x`\n- 3: ERROR: missing semicolon\nnot an issue\nSummary: two problems";
+        let output = parse_llm_response(CodeAssistAction::Analyze, review);
+        assert_eq!(output.issues.len(), 2);
+        assert_eq!(output.issues[1].line, 3);
+        assert_eq!(output.issues[1].severity, "error");
+        assert_eq!(output.summary, "two problems");
+
+        let output = parse_llm_response(
+            CodeAssistAction::Complete,
+            "```rust\nprintln!(\"hi\");\n```",
+        );
+        assert_eq!(output.completions[0].text, "println!(\"hi\");");
+    }
+}
@@ -0,0 +1,563 @@
+//! Fan-out task delegation for the orchestrator node
+//!
+//! Sends one task to several agents at once and combines their answers with an
+//! aggregation strategy. Agent failures and timeouts are recorded per agent; the
+//! fan-out as a whole only fails when the strategy can't be satisfied by the
+//! agents that did answer.
+
+use super::orchestrator::TaskDefinition;
+use crate::{AgentType, GhostFlowError, Result};
+use async_trait::async_trait;
+use jarvis_core::LLMRouter;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AggregationStrategy {
+    /// The first agent to succeed wins; the rest are cancelled
+    FirstSuccess,
+    /// Array of every successful result, in target order
+    All,
+    /// The answer given by more than half of the agents that responded
+    MajorityVote,
+    /// Every successful result handed to the LLM router to synthesize
+    LlmMerge,
+}
+
+/// `fan_out` section of the orchestrator node config
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FanOutConfig {
+    /// Agent ids to delegate to
+    #[serde(default)]
+    pub agents: Vec<String>,
+    /// Select every agent of this type instead (e.g. `llm_router`)
+    #[serde(default)]
+    pub capability: Option<String>,
+    #[serde(default = "default_agent_timeout_seconds")]
+    pub agent_timeout_seconds: u64,
+    pub strategy: AggregationStrategy,
+    /// JSON pointer into each result to vote on (majority_vote), e.g. `/label`
+    #[serde(default)]
+    pub vote_field: Option<String>,
+    /// Extra instructions for llm_merge
+    #[serde(default)]
+    pub merge_prompt: Option<String>,
+}
+
+fn default_agent_timeout_seconds() -> u64 {
+    60
+}
+
+/// Runs a task on one agent
+#[async_trait]
+pub trait AgentExecutor: Send + Sync {
+    async fn execute(&self, agent_id: &str, task: &TaskDefinition) -> Result<serde_json::Value>;
+}
+
+/// Combines several agent answers into one (llm_merge)
+#[async_trait]
+pub trait ResultSynthesizer: Send + Sync {
+    async fn synthesize(
+        &self,
+        task: &TaskDefinition,
+        outcomes: &[AgentOutcome],
+        instructions: Option<&str>,
+    ) -> Result<serde_json::Value>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutcomeStatus {
+    Succeeded,
+    Failed,
+    /// Not needed once another agent satisfied first_success
+    Canceled,
+}
+
+/// What one agent did with the task
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentOutcome {
+    pub agent_id: String,
+    pub status: OutcomeStatus,
+    pub result: Option<serde_json::Value>,
+    pub error: Option<String>,
+    pub duration_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FanOutResult {
+    pub strategy: AggregationStrategy,
+    /// Whether the strategy produced an answer
+    pub satisfied: bool,
+    pub result: Option<serde_json::Value>,
+    /// Tally per distinct answer (majority_vote only)
+    pub votes: Option<BTreeMap<String, usize>>,
+    pub agents: Vec<AgentOutcome>,
+}
+
+impl FanOutResult {
+    /// One line per agent that failed, for the node's error list
+    pub fn agent_errors(&self) -> Vec<String> {
+        self.agents
+            .iter()
+            .filter_map(|a| {
+                a.error
+                    .as_ref()
+                    .filter(|_| a.status == OutcomeStatus::Failed)
+                    .map(|e| format!("Agent {} failed: {}", a.agent_id, e))
+            })
+            .collect()
+    }
+}
+
+/// Name used by the `capability` selector for an agent type
+pub fn capability_name(agent_type: &AgentType) -> String {
+    match agent_type {
+        AgentType::LLMRouter => "llm_router".to_string(),
+        AgentType::MemoryManager => "memory_manager".to_string(),
+        AgentType::BlockchainMonitor => "blockchain_monitor".to_string(),
+        AgentType::NetworkOptimizer => "network_optimizer".to_string(),
+        AgentType::TaskOrchestrator => "task_orchestrator".to_string(),
+        AgentType::Custom(name) => name.clone(),
+    }
+}
+
+/// Run `task` on every agent in `agent_ids` concurrently and aggregate
+pub async fn fan_out(
+    agent_ids: &[String],
+    task: &TaskDefinition,
+    config: &FanOutConfig,
+    executor: Arc<dyn AgentExecutor>,
+    synthesizer: Option<Arc<dyn ResultSynthesizer>>,
+) -> Result<FanOutResult> {
+    if agent_ids.is_empty() {
+        return Err(GhostFlowError::NodeExecution(
+            "Fan-out has no target agents".to_string(),
+        ));
+    }
+    if config.strategy == AggregationStrategy::LlmMerge && synthesizer.is_none() {
+        return Err(GhostFlowError::Config(
+            "llm_merge needs an LLM router".to_string(),
+        ));
+    }
+
+    let timeout = Duration::from_secs(config.agent_timeout_seconds.max(1));
+    let mut running = JoinSet::new();
+    for (index, agent_id) in agent_ids.iter().enumerate() {
+        let executor = executor.clone();
+        let task = task.clone();
+        let agent_id = agent_id.clone();
+        running.spawn(async move {
+            let started = Instant::now();
+            let result =
+                match tokio::time::timeout(timeout, executor.execute(&agent_id, &task)).await {
+                    Ok(Ok(value)) => Ok(value),
+                    Ok(Err(e)) => Err(e.to_string()),
+                    Err(_) => Err(format!("timed out after {}s", timeout.as_secs())),
+                };
+            let duration_ms = Some(started.elapsed().as_millis() as u64);
+            let outcome = match result {
+                Ok(value) => AgentOutcome {
+                    agent_id,
+                    status: OutcomeStatus::Succeeded,
+                    result: Some(value),
+                    error: None,
+                    duration_ms,
+                },
+                Err(error) => AgentOutcome {
+                    agent_id,
+                    status: OutcomeStatus::Failed,
+                    result: None,
+                    error: Some(error),
+                    duration_ms,
+                },
+            };
+            (index, outcome)
+        });
+    }
+
+    let mut outcomes: Vec<Option<AgentOutcome>> = vec![None; agent_ids.len()];
+    while let Some(joined) = running.join_next().await {
+        let (index, outcome) = joined
+            .map_err(|e| GhostFlowError::NodeExecution(format!("Agent task panicked: {}", e)))?;
+        let done = config.strategy == AggregationStrategy::FirstSuccess
+            && outcome.status == OutcomeStatus::Succeeded;
+        outcomes[index] = Some(outcome);
+        if done {
+            running.abort_all();
+            break;
+        }
+    }
+
+    let outcomes: Vec<AgentOutcome> = outcomes
+        .into_iter()
+        .zip(agent_ids)
+        .map(|(outcome, agent_id)| {
+            outcome.unwrap_or_else(|| AgentOutcome {
+                agent_id: agent_id.clone(),
+                status: OutcomeStatus::Canceled,
+                result: None,
+                error: None,
+                duration_ms: None,
+            })
+        })
+        .collect();
+
+    let successes: Vec<&serde_json::Value> = outcomes
+        .iter()
+        .filter(|o| o.status == OutcomeStatus::Succeeded)
+        .filter_map(|o| o.result.as_ref())
+        .collect();
+
+    let mut votes = None;
+    let result = match config.strategy {
+        AggregationStrategy::FirstSuccess => successes.first().map(|v| (*v).clone()),
+        AggregationStrategy::All => (!successes.is_empty())
+            .then(|| serde_json::Value::Array(successes.iter().map(|v| (*v).clone()).collect())),
+        AggregationStrategy::MajorityVote => {
+            let (winner, tally) = majority(&successes, config.vote_field.as_deref());
+            votes = Some(tally);
+            winner
+        }
+        AggregationStrategy::LlmMerge => match (&synthesizer, successes.is_empty()) {
+            (Some(synthesizer), false) => Some(
+                synthesizer
+                    .synthesize(task, &outcomes, config.merge_prompt.as_deref())
+                    .await?,
+            ),
+            _ => None,
+        },
+    };
+
+    Ok(FanOutResult {
+        strategy: config.strategy,
+        satisfied: result.is_some(),
+        result,
+        votes,
+        agents: outcomes,
+    })
+}
+
+/// The answer held by a strict majority of voters, plus the full tally.
+/// Results without `vote_field` abstain.
+fn majority(
+    results: &[&serde_json::Value],
+    vote_field: Option<&str>,
+) -> (Option<serde_json::Value>, BTreeMap<String, usize>) {
+    let mut tally: BTreeMap<String, usize> = BTreeMap::new();
+    let mut answers: BTreeMap<String, serde_json::Value> = BTreeMap::new();
+
+    for result in results {
+        let vote = match vote_field {
+            Some(pointer) => result.pointer(pointer),
+            None => Some(*result),
+        };
+        let Some(vote) = vote else {
+            continue;
+        };
+        let key = match vote {
+            serde_json::Value::String(s) => s.trim().to_lowercase(),
+            other => other.to_string(),
+        };
+        *tally.entry(key.clone()).or_default() += 1;
+        answers.entry(key).or_insert_with(|| vote.clone());
+    }
+
+    let voters: usize = tally.values().sum();
+    let winner = tally
+        .iter()
+        .find(|(_, count)| **count * 2 > voters)
+        .and_then(|(key, _)| answers.get(key).cloned());
+    (winner, tally)
+}
+
+/// Default agent executor until orchestrated agents run real work: marks the
+/// task done after a short delay
+pub struct SimulatedAgentExecutor;
+
+#[async_trait]
+impl AgentExecutor for SimulatedAgentExecutor {
+    async fn execute(&self, agent_id: &str, task: &TaskDefinition) -> Result<serde_json::Value> {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        Ok(serde_json::json!({
+            "task_id": task.task_id,
+            "agent_id": agent_id,
+            "result": "Task completed successfully",
+            "execution_time_ms": 100
+        }))
+    }
+}
+
+/// llm_merge through the Jarvis LLM router
+pub struct LlmRouterSynthesizer {
+    router: LLMRouter,
+}
+
+impl LlmRouterSynthesizer {
+    pub fn new(router: LLMRouter) -> Self {
+        Self { router }
+    }
+}
+
+#[async_trait]
+impl ResultSynthesizer for LlmRouterSynthesizer {
+    async fn synthesize(
+        &self,
+        task: &TaskDefinition,
+        outcomes: &[AgentOutcome],
+        instructions: Option<&str>,
+    ) -> Result<serde_json::Value> {
+        let answers: Vec<String> = outcomes
+            .iter()
+            .filter_map(|o| {
+                o.result
+                    .as_ref()
+                    .map(|r| format!("Agent {}:\n{}", o.agent_id, r))
+            })
+            .collect();
+
+        let prompt = format!(
+            "Several agents worked on the same task independently.\n\n\
+             Task {} ({:?}):\n{}\n\n\
+             Their answers:\n\n{}\n\n\
+             {}",
+            task.task_id,
+            task.task_type,
+            task.input_data,
+            answers.join("\n\n"),
+            instructions.unwrap_or(
+                "Combine them into a single best answer. Resolve disagreements and \
+                 drop anything only one agent claims without support."
+            ),
+        );
+
+        let merged = self
+            .router
+            .generate(&prompt, None)
+            .await
+            .map_err(|e| GhostFlowError::NodeExecution(format!("llm_merge failed: {}", e)))?;
+        Ok(serde_json::Value::String(merged))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::orchestrator::TaskType;
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    /// Each agent id maps to a fixed delay and answer; `Err` answers fail
+    struct MockAgents(HashMap<String, (u64, std::result::Result<serde_json::Value, String>)>);
+
+    impl MockAgents {
+        fn new(agents: &[(&str, u64, std::result::Result<serde_json::Value, &str>)]) -> Arc<Self> {
+            Arc::new(Self(
+                agents
+                    .iter()
+                    .map(|(id, delay, answer)| {
+                        (
+                            id.to_string(),
+                            (*delay, answer.clone().map_err(|e| e.to_string())),
+                        )
+                    })
+                    .collect(),
+            ))
+        }
+    }
+
+    #[async_trait]
+    impl AgentExecutor for MockAgents {
+        async fn execute(
+            &self,
+            agent_id: &str,
+            _task: &TaskDefinition,
+        ) -> Result<serde_json::Value> {
+            let (delay, answer) = &self.0[agent_id];
+            tokio::time::sleep(Duration::from_millis(*delay)).await;
+            answer.clone().map_err(GhostFlowError::NodeExecution)
+        }
+    }
+
+    struct ConcatSynthesizer;
+
+    #[async_trait]
+    impl ResultSynthesizer for ConcatSynthesizer {
+        async fn synthesize(
+            &self,
+            _task: &TaskDefinition,
+            outcomes: &[AgentOutcome],
+            _instructions: Option<&str>,
+        ) -> Result<serde_json::Value> {
+            let parts: Vec<String> = outcomes
+                .iter()
+                .filter_map(|o| o.result.as_ref()?.as_str().map(|s| s.to_string()))
+                .collect();
+            Ok(json!(parts.join("+")))
+        }
+    }
+
+    fn task() -> TaskDefinition {
+        TaskDefinition {
+            task_id: "classify".to_string(),
+            task_type: TaskType::LLMGeneration,
+            input_data: json!({"text": "disk is full"}),
+            dependencies: vec![],
+            timeout: None,
+            parallel: true,
+        }
+    }
+
+    fn config(strategy: AggregationStrategy) -> FanOutConfig {
+        FanOutConfig {
+            agents: vec![],
+            capability: None,
+            agent_timeout_seconds: 5,
+            strategy,
+            vote_field: None,
+            merge_prompt: None,
+        }
+    }
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_first_success_skips_failures_and_cancels_the_rest() {
+        let agents = MockAgents::new(&[
+            ("broken", 1, Err("model unavailable")),
+            ("fast", 20, Ok(json!("fast answer"))),
+            ("slow", 2000, Ok(json!("slow answer"))),
+        ]);
+        let result = fan_out(
+            &ids(&["broken", "fast", "slow"]),
+            &task(),
+            &config(AggregationStrategy::FirstSuccess),
+            agents,
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert!(result.satisfied);
+        assert_eq!(result.result, Some(json!("fast answer")));
+        let statuses: Vec<OutcomeStatus> = result.agents.iter().map(|a| a.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                OutcomeStatus::Failed,
+                OutcomeStatus::Succeeded,
+                OutcomeStatus::Canceled
+            ]
+        );
+        assert_eq!(
+            result.agent_errors(),
+            vec!["Agent broken failed: Node execution error: model unavailable"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_all_collects_successes_in_target_order() {
+        let agents = MockAgents::new(&[
+            ("a", 30, Ok(json!(1))),
+            ("b", 1, Err("boom")),
+            ("c", 1, Ok(json!(3))),
+        ]);
+        let result = fan_out(
+            &ids(&["a", "b", "c"]),
+            &task(),
+            &config(AggregationStrategy::All),
+            agents,
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert!(result.satisfied);
+        assert_eq!(result.result, Some(json!([1, 3])));
+        assert_eq!(result.agents[1].status, OutcomeStatus::Failed);
+    }
+
+    #[tokio::test]
+    async fn test_majority_vote_on_a_field() {
+        let agents = MockAgents::new(&[
+            ("a", 1, Ok(json!({"label": "Disk", "confidence": 0.9}))),
+            ("b", 1, Ok(json!({"label": "disk", "confidence": 0.7}))),
+            ("c", 1, Ok(json!({"label": "network"}))),
+            ("d", 1, Err("timeout")),
+        ]);
+        let mut cfg = config(AggregationStrategy::MajorityVote);
+        cfg.vote_field = Some("/label".to_string());
+        let result = fan_out(&ids(&["a", "b", "c", "d"]), &task(), &cfg, agents, None)
+            .await
+            .unwrap();
+
+        assert!(result.satisfied);
+        assert_eq!(result.result, Some(json!("Disk")));
+        let votes = result.votes.unwrap();
+        assert_eq!(votes["disk"], 2);
+        assert_eq!(votes["network"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_majority_vote_tie_is_unsatisfied() {
+        let agents = MockAgents::new(&[("a", 1, Ok(json!("yes"))), ("b", 1, Ok(json!("no")))]);
+        let result = fan_out(
+            &ids(&["a", "b"]),
+            &task(),
+            &config(AggregationStrategy::MajorityVote),
+            agents,
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert!(!result.satisfied);
+        assert_eq!(result.result, None);
+    }
+
+    #[tokio::test]
+    async fn test_llm_merge_gets_only_successful_answers() {
+        let agents = MockAgents::new(&[
+            ("a", 1, Ok(json!("alpha"))),
+            ("b", 1, Err("boom")),
+            ("c", 5, Ok(json!("gamma"))),
+        ]);
+        let result = fan_out(
+            &ids(&["a", "b", "c"]),
+            &task(),
+            &config(AggregationStrategy::LlmMerge),
+            agents,
+            Some(Arc::new(ConcatSynthesizer)),
+        )
+        .await
+        .unwrap();
+
+        assert!(result.satisfied);
+        assert_eq!(result.result, Some(json!("alpha+gamma")));
+    }
+
+    #[tokio::test]
+    async fn test_agent_timeout_is_reported() {
+        let agents = MockAgents::new(&[
+            ("sleepy", 3000, Ok(json!("late"))),
+            ("ok", 1, Ok(json!("on time"))),
+        ]);
+        let mut cfg = config(AggregationStrategy::All);
+        cfg.agent_timeout_seconds = 1;
+        let result = fan_out(&ids(&["sleepy", "ok"]), &task(), &cfg, agents, None)
+            .await
+            .unwrap();
+
+        assert_eq!(result.result, Some(json!(["on time"])));
+        assert_eq!(
+            result.agents[0].error.as_deref(),
+            Some("timed out after 1s")
+        );
+    }
+}
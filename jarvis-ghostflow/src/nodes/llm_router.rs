@@ -1,4 +1,8 @@
-use super::{GhostFlowNode, NodeHealth, HealthStatus};
+use super::{
+    ExecutionContext, GhostFlowNode, HealthStatus, NodeDefinition, NodeHealth, NodeInstance,
+    NodeOutput,
+};
+use crate::budget::Budgets;
 use crate::{Result, WorkflowContext, NodeExecutionResult, ExecutionStatus, LLMProviderConfig};
 use async_trait::async_trait;
 use jarvis_core::{LLMRouter, Config as JarvisConfig};
@@ -11,12 +15,48 @@ use tokio::sync::RwLock;
 use uuid::Uuid;
 
 /// Smart LLM Router Node that leverages Jarvis's intelligent provider selection
+#[derive(Clone)]
 pub struct LLMRouterNode {
-    llm_router: Arc<RwLock<Option<LLMRouter>>>,
+    llm_router: Arc<RwLock<Option<Arc<dyn LlmBackend>>>>,
+    budgets: Option<Arc<Budgets>>,
     config: LLMRouterConfig,
     health: Arc<RwLock<NodeHealth>>,
 }
 
+/// Where the node's prompts go; the Jarvis LLM router unless replaced
+#[async_trait]
+pub trait LlmBackend: Send + Sync {
+    async fn generate(&self, prompt: &str, system_context: Option<&str>) -> Result<Generation>;
+}
+
+/// A response and the tokens it took, for usage accounting
+#[derive(Debug, Clone)]
+pub struct Generation {
+    pub text: String,
+    pub model: String,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+#[async_trait]
+impl LlmBackend for LLMRouter {
+    /// The router doesn't report usage, so tokens are estimated
+    async fn generate(&self, prompt: &str, system_context: Option<&str>) -> Result<Generation> {
+        let text = LLMRouter::generate(self, prompt, system_context).await?;
+        Ok(Generation {
+            model: "auto-selected".to_string(),
+            prompt_tokens: estimate_tokens(prompt) + system_context.map_or(0, estimate_tokens),
+            completion_tokens: estimate_tokens(&text),
+            text,
+        })
+    }
+}
+
+/// Rough estimation: ~4 characters per token
+fn estimate_tokens(text: &str) -> u64 {
+    (text.len() / 4) as u64
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LLMRouterConfig {
     pub providers: Vec<LLMProviderConfig>,
@@ -63,6 +103,7 @@ impl LLMRouterNode {
     pub fn new() -> Result<Self> {
         Ok(Self {
             llm_router: Arc::new(RwLock::new(None)),
+            budgets: None,
             config: LLMRouterConfig::default(),
             health: Arc::new(RwLock::new(NodeHealth {
                 status: HealthStatus::Unknown,
@@ -74,12 +115,24 @@ impl LLMRouterNode {
         })
     }
 
+    /// Check `budgets` before each call and charge calls to them
+    pub fn with_budgets(mut self, budgets: Arc<Budgets>) -> Self {
+        self.budgets = Some(budgets);
+        self
+    }
+
+    /// Send prompts to `backend` instead of a router built from the node config
+    pub fn with_backend(mut self, backend: Arc<dyn LlmBackend>) -> Self {
+        self.llm_router = Arc::new(RwLock::new(Some(backend)));
+        self
+    }
+
     async fn initialize_llm_router(&self, config: &HashMap<String, serde_json::Value>) -> Result<()> {
         // Create Jarvis config from node config
         let jarvis_config = self.create_jarvis_config(config)?;
         let router = LLMRouter::new(&jarvis_config).await?;
         
-        *self.llm_router.write().await = Some(router);
+        *self.llm_router.write().await = Some(Arc::new(router));
         Ok(())
     }
 
@@ -113,16 +166,27 @@ impl LLMRouterNode {
         Ok(jarvis_config)
     }
 
-    async fn execute_llm_request(&self, input: &LLMRouterInput) -> Result<LLMRouterOutput> {
+    /// Run `input` for the workflow execution identified by `workflow_id` and
+    /// `execution_id`, refusing it when their budget is used up
+    async fn execute_llm_request(
+        &self,
+        input: &LLMRouterInput,
+        workflow_id: Uuid,
+        execution_id: Uuid,
+    ) -> Result<LLMRouterOutput> {
         let start_time = Instant::now();
         let mut attempts = Vec::new();
+
+        if let Some(budgets) = &self.budgets {
+            budgets.check(workflow_id)?;
+        }
         
         let router_guard = self.llm_router.read().await;
         let router = router_guard.as_ref()
             .ok_or_else(|| crate::GhostFlowError::NodeExecution("LLM Router not initialized".to_string()))?;
 
         // Try generating response
-        let response = if input.stream.unwrap_or(false) && self.config.enable_streaming {
+        let generation = if input.stream.unwrap_or(false) && self.config.enable_streaming {
             // For streaming, we'd need to handle this differently in a real implementation
             // For now, fall back to regular generation
             router.generate(&input.prompt, input.system_context.as_deref()).await?
@@ -131,16 +195,25 @@ impl LLMRouterNode {
         };
 
         let execution_time = start_time.elapsed().as_millis() as u64;
-        let tokens_consumed = self.estimate_tokens(&response);
-        let cost_estimate = self.estimate_cost(&response, "primary");
+        let tokens_consumed = generation.prompt_tokens + generation.completion_tokens;
+        let cost_estimate = match &self.budgets {
+            Some(budgets) => budgets.charge(
+                workflow_id,
+                execution_id,
+                &generation.model,
+                generation.prompt_tokens,
+                generation.completion_tokens,
+            ).cost_usd,
+            None => self.estimate_cost(&generation.text, "primary"),
+        };
 
         // Update health metrics
         self.update_health_metrics(true, execution_time).await;
 
         Ok(LLMRouterOutput {
-            response,
+            response: generation.text,
             provider_used: "jarvis-router".to_string(), // Would be dynamic in real implementation
-            model_used: "auto-selected".to_string(),
+            model_used: generation.model,
             tokens_consumed,
             execution_time_ms: execution_time,
             cost_estimate,
@@ -149,13 +222,8 @@ impl LLMRouterNode {
         })
     }
 
-    fn estimate_tokens(&self, text: &str) -> u64 {
-        // Rough estimation: ~4 characters per token
-        (text.len() / 4) as u64
-    }
-
     fn estimate_cost(&self, text: &str, provider: &str) -> f64 {
-        let tokens = self.estimate_tokens(text) as f64;
+        let tokens = estimate_tokens(text) as f64;
         let cost_per_1k = match provider {
             "openai" => 0.002,
             "claude" => 0.008,
@@ -355,7 +423,7 @@ impl GhostFlowNode for LLMRouterNode {
         ))?;
 
         // Execute LLM request
-        match self.execute_llm_request(&input).await {
+        match self.execute_llm_request(&input, context.workflow_id, context.execution_id).await {
             Ok(output) => {
                 // Store result in workflow context for memory
                 if let Some(memory_context) = &mut context.memory_context {
@@ -389,6 +457,10 @@ impl GhostFlowNode for LLMRouterNode {
             Err(e) => {
                 self.update_health_metrics(false, start_time.elapsed().as_millis() as u64).await;
                 
+                let mut metadata = HashMap::new();
+                if let crate::GhostFlowError::BudgetExceeded(exceeded) = &e {
+                    metadata.insert("budget_exceeded".to_string(), json!(exceeded.scope));
+                }
                 Ok(crate::NodeExecutionResult {
                     node_id: "llm_router".to_string(),
                     execution_id: context.execution_id,
@@ -396,7 +468,7 @@ impl GhostFlowNode for LLMRouterNode {
                     output: json!({}),
                     error: Some(e.to_string()),
                     duration_ms: start_time.elapsed().as_millis() as u64,
-                    metadata: HashMap::new(),
+                    metadata,
                     next_nodes: vec![],
                 })
             }
@@ -436,6 +508,52 @@ impl GhostFlowNode for LLMRouterNode {
     }
 }
 
+#[async_trait]
+impl NodeDefinition for LLMRouterNode {
+    fn node_type(&self) -> &'static str {
+        "llm_router"
+    }
+
+    fn create_instance(&self) -> anyhow::Result<Box<dyn NodeInstance + Send + Sync>> {
+        Ok(Box::new(LLMRouterInstance {
+            node: self.clone(),
+            parameters: HashMap::new(),
+        }))
+    }
+}
+
+/// One workflow node's use of the router; its parameters are the prompt
+/// fields of `LLMRouterInput` plus the node config
+pub struct LLMRouterInstance {
+    node: LLMRouterNode,
+    parameters: HashMap<String, serde_json::Value>,
+}
+
+#[async_trait]
+impl NodeInstance for LLMRouterInstance {
+    async fn configure(&mut self, parameters: serde_json::Value) -> anyhow::Result<()> {
+        self.parameters = serde_json::from_value(parameters)?;
+        Ok(())
+    }
+
+    async fn execute(&mut self, context: &ExecutionContext) -> anyhow::Result<NodeOutput> {
+        if self.node.llm_router.read().await.is_none() {
+            self.node.initialize_llm_router(&self.parameters).await?;
+        }
+
+        let input: LLMRouterInput = serde_json::from_value(json!(self.parameters))?;
+        let output = self.node
+            .execute_llm_request(&input, context.workflow_id, context.execution_id)
+            .await;
+        if output.is_err() {
+            self.node.update_health_metrics(false, 0).await;
+        }
+        Ok(NodeOutput {
+            data: serde_json::to_value(output?)?,
+        })
+    }
+}
+
 impl Default for LLMRouterConfig {
     fn default() -> Self {
         Self {
@@ -461,4 +579,189 @@ impl Default for LLMRouterConfig {
             timeout_seconds: 60,
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::budget::{BudgetAction, BudgetConfig, BudgetLimit, BudgetScope, ModelPrice};
+    use crate::workflow_engine::{
+        ExecutionMode, ExecutionResult, ExecutionStatus, StartNode, WorkflowEngine,
+    };
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Three questions in a row, each a call to the fake model
+    const THREE_QUESTIONS: &str = r#"
+kind: GhostFlowWorkflow
+version: v1
+metadata:
+  name: Three questions
+settings:
+  timeout_seconds: 300
+  error_workflow: null
+  save_data_execution_progress: false
+  save_data_success: true
+  save_data_error: true
+  save_manual_executions: false
+  caller_policy: None
+nodes:
+  - id: start
+    type: start
+    position: { x: 0.0, y: 0.0 }
+  - id: first
+    type: llm_router
+    config: { prompt: "What changed?" }
+    position: { x: 200.0, y: 0.0 }
+  - id: second
+    type: llm_router
+    config: { prompt: "Why?" }
+    position: { x: 400.0, y: 0.0 }
+  - id: third
+    type: llm_router
+    config: { prompt: "What next?" }
+    position: { x: 600.0, y: 0.0 }
+edges:
+  - { from: start, to: first }
+  - { from: first, to: second }
+  - { from: second, to: third }
+"#;
+
+    /// Every call takes 500 prompt and 1000 completion tokens
+    #[derive(Default)]
+    struct FakeLlm {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl LlmBackend for FakeLlm {
+        async fn generate(&self, prompt: &str, _system_context: Option<&str>) -> Result<Generation> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(Generation {
+                text: format!("Answer to {}", prompt),
+                model: "fake-model".to_string(),
+                prompt_tokens: 500,
+                completion_tokens: 1000,
+            })
+        }
+    }
+
+    /// $0.025 per call
+    fn budget_config(on_exhausted: BudgetAction) -> BudgetConfig {
+        BudgetConfig {
+            on_exhausted,
+            prices: HashMap::from([(
+                "fake-model".to_string(),
+                ModelPrice {
+                    prompt_per_1k_usd: 0.01,
+                    completion_per_1k_usd: 0.02,
+                },
+            )]),
+            ..BudgetConfig::default()
+        }
+    }
+
+    async fn engine(budgets: Arc<Budgets>, llm: Arc<FakeLlm>) -> (WorkflowEngine, Uuid) {
+        let engine = WorkflowEngine::with_budgets(budgets.clone()).unwrap();
+        engine.register_node(Box::new(StartNode::new())).await;
+        let node = LLMRouterNode::new().unwrap().with_backend(llm).with_budgets(budgets);
+        engine.register_node(Box::new(node)).await;
+        let workflow_id = engine.import_yaml(THREE_QUESTIONS).await.unwrap();
+        (engine, workflow_id)
+    }
+
+    fn tokens(max_tokens: u64) -> BudgetLimit {
+        BudgetLimit {
+            max_tokens: Some(max_tokens),
+            max_cost_usd: None,
+        }
+    }
+
+    fn costs(result: &ExecutionResult) -> Vec<(&str, f64)> {
+        result
+            .node_executions
+            .iter()
+            .filter(|n| n.node_type == "llm_router")
+            .map(|n| (n.node_id.as_str(), (n.cumulative_cost_usd * 1000.0).round() / 1000.0))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_looping_workflow_pauses_at_its_cap_and_resumes_once_raised() {
+        let budgets = Arc::new(Budgets::in_memory(budget_config(BudgetAction::Pause)));
+        let llm = Arc::new(FakeLlm::default());
+        let (engine, workflow_id) = engine(budgets.clone(), llm.clone()).await;
+        // Four calls' worth of tokens
+        let scope = BudgetScope::Workflow(workflow_id);
+        budgets.set_limit(scope.clone(), tokens(6000)).unwrap();
+
+        let mut runs = Vec::new();
+        while runs.len() < 5 {
+            let result = engine
+                .execute_workflow(workflow_id, json!({}), ExecutionMode::Manual)
+                .await
+                .unwrap();
+            let paused = matches!(result.status, ExecutionStatus::BudgetExceeded);
+            runs.push(result);
+            if paused {
+                break;
+            }
+        }
+        assert_eq!(runs.len(), 2);
+        assert_eq!(llm.calls.load(Ordering::SeqCst), 4);
+        assert!(matches!(runs[0].status, ExecutionStatus::Success));
+        assert_eq!(
+            costs(&runs[0]),
+            [("first", 0.025), ("second", 0.05), ("third", 0.075)]
+        );
+
+        let paused = &runs[1];
+        assert_eq!(costs(paused), [("first", 0.025), ("second", 0.025)]);
+        assert!(matches!(
+            paused.node_executions.last().unwrap().status,
+            ExecutionStatus::BudgetExceeded
+        ));
+        assert!(paused.error.as_ref().unwrap().contains("used up"));
+        assert_eq!(engine.paused_executions().await, [paused.execution_id]);
+        assert!(budgets.status(scope.clone()).exhausted);
+
+        // Still used up: it pauses again without calling the model
+        let again = engine.resume_execution(paused.execution_id).await.unwrap();
+        assert!(matches!(again.status, ExecutionStatus::BudgetExceeded));
+        assert_eq!(llm.calls.load(Ordering::SeqCst), 4);
+
+        budgets.set_limit(scope, tokens(12_000)).unwrap();
+        let resumed = engine.resume_execution(paused.execution_id).await.unwrap();
+        assert!(matches!(resumed.status, ExecutionStatus::Success));
+        assert_eq!(resumed.execution_id, paused.execution_id);
+        // The first question isn't asked again
+        assert_eq!(llm.calls.load(Ordering::SeqCst), 6);
+        assert_eq!((resumed.cost_usd * 1000.0).round() / 1000.0, 0.075);
+        assert_eq!(
+            costs(&resumed).last().copied(),
+            Some(("third", 0.075))
+        );
+        let third = resumed.node_executions.last().unwrap();
+        assert_eq!(
+            third.output_data.as_ref().unwrap()["response"],
+            json!("Answer to What next?")
+        );
+        assert!(engine.paused_executions().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_exhausted_budget_fails_the_execution_by_default() {
+        let mut config = budget_config(BudgetAction::Fail);
+        config.global = tokens(1500);
+        let budgets = Arc::new(Budgets::in_memory(config));
+        let llm = Arc::new(FakeLlm::default());
+        let (engine, workflow_id) = engine(budgets, llm.clone()).await;
+
+        let result = engine
+            .execute_workflow(workflow_id, json!({}), ExecutionMode::Manual)
+            .await
+            .unwrap();
+        assert!(matches!(result.status, ExecutionStatus::Error));
+        assert!(result.error.unwrap().contains("Global LLM budget is used up"));
+        assert_eq!(llm.calls.load(Ordering::SeqCst), 1);
+        assert!(engine.paused_executions().await.is_empty());
+    }
+}
@@ -1,23 +1,28 @@
 use super::{GhostFlowNode, NodeHealth, HealthStatus};
 use crate::{Result, WorkflowContext, NodeExecutionResult, ExecutionStatus, MemoryContext, ContextEntry, ContextEntryType};
+use crate::memory::{KvEntry, KvStore};
+use crate::namespace::{self, Quotas};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
+use sqlx::Row;
 
 /// Context Memory Node with persistent workflow memory using ZQLite backend
 pub struct MemoryNode {
     memory_store: Arc<RwLock<Option<MemoryStore>>>,
     config: MemoryNodeConfig,
     health: Arc<RwLock<NodeHealth>>,
+    quotas: Option<Arc<Quotas>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct MemoryNodeConfig {
     pub database_path: String,
     pub enable_zqlite: bool,
@@ -27,6 +32,38 @@ pub struct MemoryNodeConfig {
     pub retention_days: u32,
     pub auto_cleanup: bool,
     pub similarity_threshold: f64,
+    /// Explicit key/value namespace; overrides `scope`
+    pub namespace: Option<String>,
+    pub scope: MemoryScope,
+    /// TTL for set/incr when the input doesn't give one
+    pub default_ttl_seconds: Option<u64>,
+    pub sweep_interval_seconds: u64,
+}
+
+/// Automatic key/value namespace when no explicit one is configured
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryScope {
+    /// Shared by every workflow in the workflow's namespace
+    Global,
+    /// Shared by all executions of one workflow
+    #[default]
+    Workflow,
+    /// Private to a single execution
+    Execution,
+}
+
+impl MemoryNodeConfig {
+    pub fn namespace_for(&self, context: &WorkflowContext) -> String {
+        if let Some(namespace) = self.namespace.as_deref().filter(|n| !n.is_empty()) {
+            return namespace.to_string();
+        }
+        match self.scope {
+            MemoryScope::Global => "global".to_string(),
+            MemoryScope::Workflow => format!("workflow:{}", context.workflow_id),
+            MemoryScope::Execution => format!("execution:{}", context.execution_id),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,9 +74,19 @@ pub struct MemoryInput {
     pub search_query: Option<String>,
     pub search_limit: Option<usize>,
     pub metadata: Option<HashMap<String, serde_json::Value>>,
+    /// Key for get/set/delete/incr
+    pub key: Option<String>,
+    /// Value for set; any JSON type
+    pub value: Option<serde_json::Value>,
+    /// Amount for incr, default 1
+    pub delta: Option<serde_json::Value>,
+    pub ttl_seconds: Option<u64>,
+    /// Key prefix filter for list
+    pub prefix: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum MemoryAction {
     Store,
     Search,
@@ -49,6 +96,10 @@ pub enum MemoryAction {
     GetContext,
     ClearContext,
     AnalyzePatterns,
+    Get,
+    Set,
+    List,
+    Incr,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -60,6 +111,10 @@ pub struct MemoryOutput {
     pub patterns: Option<Vec<MemoryPattern>>,
     pub total_entries: usize,
     pub storage_size_bytes: u64,
+    /// Key/value namespace the action ran in
+    pub namespace: Option<String>,
+    /// Entries read or written by get/set/list/incr
+    pub values: Vec<KvEntry>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -74,6 +129,7 @@ pub struct MemoryPattern {
 /// Internal memory store that can use ZQLite or SQLite
 pub struct MemoryStore {
     connection: Option<sqlx::Pool<sqlx::Sqlite>>,
+    kv: KvStore,
     zqlite_enabled: bool,
     embedding_cache: HashMap<String, Vec<f32>>,
 }
@@ -90,9 +146,16 @@ impl MemoryNode {
                 error_count: 0,
                 success_rate: 0.0,
             })),
+            quotas: None,
         })
     }
 
+    /// Hold each namespace to its `max_memory_entries`
+    pub fn with_quotas(mut self, quotas: Arc<Quotas>) -> Self {
+        self.quotas = Some(quotas);
+        self
+    }
+
     async fn initialize_memory_store(&self, config: &HashMap<String, serde_json::Value>) -> Result<()> {
         let db_path = config.get("database_path")
             .and_then(|v| v.as_str())
@@ -109,14 +172,26 @@ impl MemoryNode {
         }
 
         // Initialize SQLite connection
-        let connection_string = format!("sqlite:{}", db_path);
-        let pool = sqlx::SqlitePool::connect(&connection_string).await?;
+        let options = sqlx::sqlite::SqliteConnectOptions::new()
+            .filename(db_path)
+            .create_if_missing(true);
+        let pool = sqlx::SqlitePool::connect_with(options).await?;
         
         // Create tables
         self.create_tables(&pool).await?;
 
+        let sweep_interval = config.get("sweep_interval_seconds")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(60)
+            .max(1);
+        let mut kv = KvStore::open(pool.clone(), Duration::from_secs(sweep_interval)).await?;
+        if let Some(quotas) = &self.quotas {
+            kv = kv.with_quotas(quotas.clone());
+        }
+
         let store = MemoryStore {
             connection: Some(pool),
+            kv,
             zqlite_enabled: enable_zqlite,
             embedding_cache: HashMap::new(),
         };
@@ -129,6 +204,7 @@ impl MemoryNode {
         sqlx::query(r#"
             CREATE TABLE IF NOT EXISTS context_entries (
                 id TEXT PRIMARY KEY,
+                namespace TEXT NOT NULL DEFAULT 'default',
                 workflow_id TEXT NOT NULL,
                 execution_id TEXT,
                 content TEXT NOT NULL,
@@ -139,6 +215,7 @@ impl MemoryNode {
                 created_at TEXT DEFAULT CURRENT_TIMESTAMP
             )
         "#).execute(pool).await?;
+        namespace::migrate_table(pool, "context_entries", "namespace").await?;
 
         sqlx::query(r#"
             CREATE TABLE IF NOT EXISTS memory_sessions (
@@ -185,10 +262,11 @@ impl MemoryNode {
 
         sqlx::query(r#"
             INSERT INTO context_entries 
-            (id, workflow_id, execution_id, content, entry_type, timestamp, metadata, embedding)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            (id, namespace, workflow_id, execution_id, content, entry_type, timestamp, metadata, embedding)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
         "#)
         .bind(entry_id.to_string())
+        .bind(&workflow_context.namespace)
         .bind(workflow_context.workflow_id.to_string())
         .bind(workflow_context.execution_id.to_string())
         .bind(content)
@@ -215,6 +293,8 @@ impl MemoryNode {
             patterns: None,
             total_entries: 1,
             storage_size_bytes: content.len() as u64,
+            namespace: None,
+            values: vec![],
         })
     }
 
@@ -274,6 +354,57 @@ impl MemoryNode {
             patterns: None,
             total_entries: entries.len(),
             storage_size_bytes: 0,
+            namespace: None,
+            values: vec![],
+        })
+    }
+
+    /// get/set/delete/list/incr against the namespaced key/value store
+    async fn key_value(
+        &self,
+        workflow_context: &WorkflowContext,
+        input: &MemoryInput,
+        config: &MemoryNodeConfig,
+    ) -> Result<MemoryOutput> {
+        let store = self.memory_store.read().await;
+        let store = store.as_ref().ok_or_else(|| 
+            crate::GhostFlowError::NodeExecution("Memory store not initialized".to_string()))?;
+
+        let tenant = workflow_context.namespace.as_str();
+        let namespace = config.namespace_for(workflow_context);
+        let key = || input.key.as_deref().ok_or_else(|| crate::GhostFlowError::NodeExecution(
+            format!("Key is required for {:?} action", input.action)));
+        let ttl = input.ttl_seconds.or(config.default_ttl_seconds).map(Duration::from_secs);
+
+        let (success, values) = match input.action {
+            MemoryAction::Get => {
+                let entry = store.kv.get(tenant, &namespace, key()?).await?;
+                (entry.is_some(), entry.into_iter().collect())
+            }
+            MemoryAction::Set => {
+                let value = input.value.as_ref().ok_or_else(|| 
+                    crate::GhostFlowError::NodeExecution("Value is required for set action".to_string()))?;
+                (true, vec![store.kv.set(tenant, &namespace, key()?, value, ttl).await?])
+            }
+            MemoryAction::Delete => (store.kv.delete(tenant, &namespace, key()?).await?, vec![]),
+            MemoryAction::List => (true, store.kv.list(tenant, &namespace, input.prefix.as_deref()).await?),
+            MemoryAction::Incr => {
+                let delta = input.delta.clone().unwrap_or(json!(1));
+                (true, vec![store.kv.incr(tenant, &namespace, key()?, &delta, ttl).await?])
+            }
+            _ => unreachable!("not a key/value action"),
+        };
+
+        Ok(MemoryOutput {
+            action_performed: input.action.clone(),
+            success,
+            entries: vec![],
+            context_summary: None,
+            patterns: None,
+            total_entries: values.len(),
+            storage_size_bytes: 0,
+            namespace: Some(namespace),
+            values,
         })
     }
 
@@ -331,6 +462,8 @@ impl MemoryNode {
             patterns: Some(patterns),
             total_entries: 0,
             storage_size_bytes: 0,
+            namespace: None,
+            values: vec![],
         })
     }
 
@@ -372,7 +505,7 @@ impl GhostFlowNode for MemoryNode {
             "properties": {
                 "action": {
                     "type": "string",
-                    "enum": ["store", "search", "retrieve", "update", "delete", "get_context", "clear_context", "analyze_patterns"],
+                    "enum": ["store", "search", "retrieve", "update", "delete", "get_context", "clear_context", "analyze_patterns", "get", "set", "list", "incr"],
                     "description": "The memory action to perform"
                 },
                 "content": {
@@ -396,6 +529,26 @@ impl GhostFlowNode for MemoryNode {
                 "metadata": {
                     "type": "object",
                     "description": "Additional metadata for the entry"
+                },
+                "key": {
+                    "type": "string",
+                    "description": "Key for get, set, delete, and incr"
+                },
+                "value": {
+                    "description": "Value for set; any JSON type, returned unchanged by get"
+                },
+                "delta": {
+                    "type": "number",
+                    "description": "Amount to add for incr",
+                    "default": 1
+                },
+                "ttl_seconds": {
+                    "type": "integer",
+                    "description": "Expire a set or newly created incr key after this many seconds"
+                },
+                "prefix": {
+                    "type": "string",
+                    "description": "Only list keys starting with this prefix"
                 }
             },
             "required": ["action"]
@@ -429,6 +582,14 @@ impl GhostFlowNode for MemoryNode {
                 "total_entries": {
                     "type": "integer",
                     "description": "Total number of entries affected"
+                },
+                "namespace": {
+                    "type": "string",
+                    "description": "Key/value namespace used by get, set, delete, list, and incr"
+                },
+                "values": {
+                    "type": "array",
+                    "description": "Key/value entries read or written, each with key, value, and expires_at"
                 }
             }
         })
@@ -467,6 +628,25 @@ impl GhostFlowNode for MemoryNode {
                     "type": "integer",
                     "description": "Number of days to retain entries",
                     "default": 30
+                },
+                "namespace": {
+                    "type": "string",
+                    "description": "Explicit key/value namespace; overrides scope"
+                },
+                "scope": {
+                    "type": "string",
+                    "enum": ["global", "workflow", "execution"],
+                    "description": "Automatic key/value namespace when none is given",
+                    "default": "workflow"
+                },
+                "default_ttl_seconds": {
+                    "type": "integer",
+                    "description": "TTL for set and incr when the input has none"
+                },
+                "sweep_interval_seconds": {
+                    "type": "integer",
+                    "description": "How often expired entries are purged",
+                    "default": 60
                 }
             }
         })
@@ -490,11 +670,20 @@ impl GhostFlowNode for MemoryNode {
             inputs.into_iter().collect()
         ))?;
 
+        let node_config: MemoryNodeConfig = serde_json::from_value(serde_json::Value::Object(
+            config.into_iter().collect()
+        ))?;
+
         // Execute the requested memory action
         let result = match input.action {
             MemoryAction::Store => self.store_entry(context, &input).await,
             MemoryAction::Search => self.search_entries(context, &input).await,
             MemoryAction::AnalyzePatterns => self.analyze_patterns(context).await,
+            MemoryAction::Get
+            | MemoryAction::Set
+            | MemoryAction::Delete
+            | MemoryAction::List
+            | MemoryAction::Incr => self.key_value(context, &input, &node_config).await,
             _ => {
                 // Implement other actions as needed
                 Ok(MemoryOutput {
@@ -505,6 +694,8 @@ impl GhostFlowNode for MemoryNode {
                     patterns: None,
                     total_entries: 0,
                     storage_size_bytes: 0,
+                    namespace: None,
+                    values: vec![],
                 })
             }
         };
@@ -552,6 +743,15 @@ impl GhostFlowNode for MemoryNode {
                 }
             }
         }
+
+        if let Some(scope) = config.get("scope") {
+            serde_json::from_value::<MemoryScope>(scope.clone()).map_err(|_| {
+                crate::GhostFlowError::Config(format!(
+                    "Unknown memory scope {}; expected global, workflow, or execution",
+                    scope
+                ))
+            })?;
+        }
         
         Ok(())
     }
@@ -572,6 +772,10 @@ impl Default for MemoryNodeConfig {
             retention_days: 30,
             auto_cleanup: true,
             similarity_threshold: 0.8,
+            namespace: None,
+            scope: MemoryScope::Workflow,
+            default_ttl_seconds: None,
+            sweep_interval_seconds: 60,
         }
     }
 }
\ No newline at end of file
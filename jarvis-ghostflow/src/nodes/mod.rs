@@ -2,6 +2,9 @@ pub mod llm_router;
 pub mod memory;
 pub mod orchestrator;
 pub mod blockchain;
+pub mod fan_out;
+pub mod arch_operation;
+pub mod code_assist;
 
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
@@ -80,6 +83,8 @@ impl NodeFactory {
             "jarvis.orchestrator" => Ok(Box::new(orchestrator::OrchestratorNode::new()?)),
             "jarvis.blockchain.monitor" => Ok(Box::new(blockchain::BlockchainMonitorNode::new()?)),
             "jarvis.blockchain.transaction" => Ok(Box::new(blockchain::TransactionNode::new()?)),
+            "jarvis.arch.operation" => Ok(Box::new(arch_operation::ArchOperationNode::new()?)),
+            "jarvis.code_assist" => Ok(Box::new(code_assist::CodeAssistNode::new()?)),
             _ => Err(crate::GhostFlowError::NodeExecution(
                 format!("Unknown node type: {}", node_type)
             )),
@@ -123,6 +128,20 @@ impl NodeFactory {
                 category: "Blockchain".to_string(),
                 version: "1.0.0".to_string(),
             },
+            NodeInfo {
+                node_type: "jarvis.arch.operation".to_string(),
+                display_name: "Arch Operation".to_string(),
+                description: "Run package, maintenance, and security operations through the Arch agent".to_string(),
+                category: "System".to_string(),
+                version: "1.0.0".to_string(),
+            },
+            NodeInfo {
+                node_type: "jarvis.code_assist".to_string(),
+                display_name: "Code Assist".to_string(),
+                description: "Code completion and analysis through Zeke, falling back to the LLM router".to_string(),
+                category: "AI/LLM".to_string(),
+                version: "1.0.0".to_string(),
+            },
         ]
     }
 }
@@ -1,4 +1,8 @@
 use super::{GhostFlowNode, NodeHealth, HealthStatus};
+use super::fan_out::{
+    self, AgentExecutor, AggregationStrategy, FanOutConfig, LlmRouterSynthesizer, OutcomeStatus,
+    ResultSynthesizer, SimulatedAgentExecutor,
+};
 use crate::{Result, WorkflowContext, NodeExecutionResult, ExecutionStatus, AgentState, AgentType, AgentStatus, AgentMetrics};
 use async_trait::async_trait;
 use jarvis_agent::{BlockchainAgentOrchestrator, AgentStatus as JarvisAgentStatus, AgentMessage};
@@ -16,6 +20,9 @@ pub struct OrchestratorNode {
     orchestrator: Arc<RwLock<Option<MultiAgentOrchestrator>>>,
     config: OrchestratorConfig,
     health: Arc<RwLock<NodeHealth>>,
+    executor: Arc<dyn AgentExecutor>,
+    /// Built on first llm_merge unless provided
+    synthesizer: Arc<RwLock<Option<Arc<dyn ResultSynthesizer>>>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -47,6 +54,7 @@ pub struct OrchestratorInput {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum OrchestratorAction {
     SpawnAgents,
     KillAgent,
@@ -56,6 +64,8 @@ pub enum OrchestratorAction {
     HealthCheck,
     Rebalance,
     GetMetrics,
+    /// Send the task to several agents and aggregate (config `fan_out`)
+    FanOut,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -161,9 +171,23 @@ impl OrchestratorNode {
                 error_count: 0,
                 success_rate: 0.0,
             })),
+            executor: Arc::new(SimulatedAgentExecutor),
+            synthesizer: Arc::new(RwLock::new(None)),
         })
     }
 
+    /// Run agent tasks through `executor` instead of the built-in simulation
+    pub fn with_executor(mut self, executor: Arc<dyn AgentExecutor>) -> Self {
+        self.executor = executor;
+        self
+    }
+
+    /// Use `synthesizer` for llm_merge instead of the configured LLM router
+    pub fn with_synthesizer(mut self, synthesizer: Arc<dyn ResultSynthesizer>) -> Self {
+        self.synthesizer = Arc::new(RwLock::new(Some(synthesizer)));
+        self
+    }
+
     async fn initialize_orchestrator(&self, config: &HashMap<String, serde_json::Value>) -> Result<()> {
         let (tx, rx) = mpsc::unbounded_channel();
         
@@ -335,6 +359,114 @@ impl OrchestratorNode {
         })
     }
 
+    async fn execute_fan_out(&self, task: &TaskDefinition, config: &FanOutConfig) -> Result<OrchestratorOutput> {
+        let agent_ids = {
+            let orchestrator = self.orchestrator.read().await;
+            let orchestrator = orchestrator.as_ref().ok_or_else(|| 
+                crate::GhostFlowError::NodeExecution("Orchestrator not initialized".to_string()))?;
+            Self::resolve_fan_out_targets(orchestrator, config)?
+        };
+
+        let synthesizer = if config.strategy == AggregationStrategy::LlmMerge {
+            Some(self.llm_synthesizer().await?)
+        } else {
+            None
+        };
+
+        let start_time = Instant::now();
+        let result = fan_out::fan_out(&agent_ids, task, config, self.executor.clone(), synthesizer).await?;
+
+        let mut orchestrator = self.orchestrator.write().await;
+        let orchestrator = orchestrator.as_mut().ok_or_else(|| 
+            crate::GhostFlowError::NodeExecution("Orchestrator not initialized".to_string()))?;
+
+        for outcome in &result.agents {
+            if let Some(agent) = orchestrator.agents.get_mut(&outcome.agent_id) {
+                match outcome.status {
+                    OutcomeStatus::Succeeded => {
+                        agent.state.status = AgentStatus::Completed;
+                        agent.state.progress = 100.0;
+                        agent.state.metrics.execution_count += 1;
+                    }
+                    OutcomeStatus::Failed => {
+                        agent.state.status = AgentStatus::Failed;
+                        agent.state.error_message = outcome.error.clone();
+                    }
+                    OutcomeStatus::Canceled => {}
+                }
+                agent.state.updated_at = Utc::now();
+            }
+        }
+
+        if result.satisfied {
+            orchestrator.metrics.completed_tasks += 1;
+        } else {
+            orchestrator.metrics.failed_tasks += 1;
+        }
+        orchestrator.metrics.average_task_duration_ms =
+            (orchestrator.metrics.average_task_duration_ms + start_time.elapsed().as_millis() as f64) / 2.0;
+
+        let mut errors = result.agent_errors();
+        if !result.satisfied {
+            errors.push(format!("Fan-out strategy {:?} could not be satisfied", result.strategy));
+        }
+
+        let mut task_results = HashMap::new();
+        task_results.insert("fan_out".to_string(), serde_json::to_value(&result)?);
+
+        Ok(OrchestratorOutput {
+            action_performed: OrchestratorAction::FanOut,
+            success: result.satisfied,
+            agent_states: orchestrator.agents.values().map(|a| a.state.clone()).collect(),
+            task_results,
+            coordination_metrics: orchestrator.metrics.clone(),
+            resource_usage: self.calculate_resource_usage(orchestrator).await,
+            errors,
+        })
+    }
+
+    /// Explicit agent ids, or every agent whose type matches `capability`
+    fn resolve_fan_out_targets(orchestrator: &MultiAgentOrchestrator, config: &FanOutConfig) -> Result<Vec<String>> {
+        if !config.agents.is_empty() {
+            if let Some(unknown) = config.agents.iter().find(|id| !orchestrator.agents.contains_key(*id)) {
+                return Err(crate::GhostFlowError::NodeExecution(
+                    format!("Fan-out target agent {} does not exist", unknown)
+                ));
+            }
+            return Ok(config.agents.clone());
+        }
+
+        let capability = config.capability.as_deref().ok_or_else(|| crate::GhostFlowError::Config(
+            "fan_out needs either agents or capability".to_string()))?;
+        let mut agent_ids: Vec<String> = orchestrator.agents.values()
+            .filter(|agent| fan_out::capability_name(&agent.agent_type) == capability)
+            .map(|agent| agent.id.clone())
+            .collect();
+        agent_ids.sort();
+
+        if agent_ids.is_empty() {
+            return Err(crate::GhostFlowError::NodeExecution(
+                format!("No agents with capability '{}'", capability)
+            ));
+        }
+        Ok(agent_ids)
+    }
+
+    async fn llm_synthesizer(&self) -> Result<Arc<dyn ResultSynthesizer>> {
+        if let Some(synthesizer) = self.synthesizer.read().await.as_ref() {
+            return Ok(synthesizer.clone());
+        }
+
+        let config = jarvis_core::Config::load(None).await
+            .map_err(|e| crate::GhostFlowError::Config(format!("Failed to load Jarvis config: {}", e)))?;
+        let router = jarvis_core::LLMRouter::new(&config).await
+            .map_err(|e| crate::GhostFlowError::Config(format!("Failed to start LLM router: {}", e)))?;
+        let synthesizer: Arc<dyn ResultSynthesizer> = Arc::new(LlmRouterSynthesizer::new(router));
+
+        *self.synthesizer.write().await = Some(synthesizer.clone());
+        Ok(synthesizer)
+    }
+
     async fn select_agents_for_task(
         &self,
         orchestrator: &MultiAgentOrchestrator,
@@ -462,7 +594,7 @@ impl GhostFlowNode for OrchestratorNode {
             "properties": {
                 "action": {
                     "type": "string",
-                    "enum": ["spawn_agents", "kill_agent", "get_status", "execute_task", "coordinate", "health_check", "rebalance", "get_metrics"],
+                    "enum": ["spawn_agents", "kill_agent", "get_status", "execute_task", "coordinate", "health_check", "rebalance", "get_metrics", "fan_out"],
                     "description": "The orchestration action to perform"
                 },
                 "agent_configs": {
@@ -528,7 +660,7 @@ impl GhostFlowNode for OrchestratorNode {
                 },
                 "task_results": {
                     "type": "object",
-                    "description": "Results from executed tasks"
+                    "description": "Results from executed tasks; fan_out puts the aggregated result, vote tally, and per-agent outcomes under \"fan_out\""
                 },
                 "coordination_metrics": {
                     "type": "object",
@@ -568,6 +700,35 @@ impl GhostFlowNode for OrchestratorNode {
                     "description": "Enable load balancing across agents",
                     "default": true
                 },
+                "fan_out": {
+                    "type": "object",
+                    "description": "Targets and aggregation for the fan_out action",
+                    "properties": {
+                        "agents": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "Agent ids to delegate to"
+                        },
+                        "capability": {
+                            "type": "string",
+                            "description": "Delegate to every agent of this type instead, e.g. llm_router"
+                        },
+                        "agent_timeout_seconds": { "type": "integer", "default": 60 },
+                        "strategy": {
+                            "type": "string",
+                            "enum": ["first_success", "all", "majority_vote", "llm_merge"]
+                        },
+                        "vote_field": {
+                            "type": "string",
+                            "description": "JSON pointer to the value majority_vote compares, e.g. /label"
+                        },
+                        "merge_prompt": {
+                            "type": "string",
+                            "description": "Extra instructions for llm_merge"
+                        }
+                    },
+                    "required": ["strategy"]
+                },
                 "resource_limits": {
                     "type": "object",
                     "description": "Resource limits for orchestration",
@@ -620,6 +781,21 @@ impl GhostFlowNode for OrchestratorNode {
                 }
             }
             OrchestratorAction::GetStatus => self.get_orchestrator_status().await,
+            OrchestratorAction::FanOut => {
+                let fan_out_config = config.get("fan_out")
+                    .cloned()
+                    .map(serde_json::from_value::<FanOutConfig>)
+                    .transpose()?;
+                match (&input.task_definition, fan_out_config) {
+                    (Some(task_def), Some(fan_out_config)) => self.execute_fan_out(task_def, &fan_out_config).await,
+                    (None, _) => Err(crate::GhostFlowError::NodeExecution(
+                        "Task definition required for fan_out action".to_string()
+                    )),
+                    (_, None) => Err(crate::GhostFlowError::Config(
+                        "fan_out action requires a fan_out section in the node config".to_string()
+                    )),
+                }
+            }
             _ => {
                 // Implement other actions as needed
                 Ok(OrchestratorOutput {
@@ -677,6 +853,12 @@ impl GhostFlowNode for OrchestratorNode {
     }
 
     fn validate_config(&self, config: &HashMap<String, serde_json::Value>) -> Result<()> {
+        if let Some(fan_out_config) = config.get("fan_out") {
+            serde_json::from_value::<FanOutConfig>(fan_out_config.clone()).map_err(|e| {
+                crate::GhostFlowError::Config(format!("Invalid fan_out config: {}", e))
+            })?;
+        }
+
         // Validate max concurrent agents
         if let Some(max_agents) = config.get("max_concurrent_agents") {
             if let Some(max) = max_agents.as_u64() {
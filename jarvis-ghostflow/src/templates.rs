@@ -0,0 +1,492 @@
+//! Built-in workflow templates
+//!
+//! A template is a workflow in the portable YAML format (see
+//! [`crate::workflow_format`]) wrapped with typed parameters:
+//!
+//! ```yaml
+//! kind: GhostFlowWorkflowTemplate
+//! version: v1
+//! name: nightly-update-with-snapshot
+//! template_version: 1.0.0
+//! description: ...
+//! parameters:
+//!   - name: schedule
+//!     type: cron
+//!     default: "0 3 * * *"
+//! workflow:
+//!   kind: GhostFlowWorkflow
+//!   ...
+//!       config: { cron: ${schedule} }
+//! ```
+//!
+//! A string that is only `${name}` becomes the parameter's value with its
+//! type, so integers and lists stay integers and lists; `${name}` inside
+//! longer text is replaced by the value as text. `{{input}}` and other
+//! runtime placeholders are left alone. Parameters without a default must be
+//! set. The rendered workflow records its template, version, and parameter
+//! values in its metadata so it can be re-rendered when the template changes.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::workflow_format::WorkflowDocument;
+
+pub const TEMPLATE_KIND: &str = "GhostFlowWorkflowTemplate";
+pub const TEMPLATE_FORMAT_VERSION: &str = "v1";
+
+const BUILTIN: &[&str] = &[
+    include_str!("templates/security-audit-and-report.yaml"),
+    include_str!("templates/nightly-update-with-snapshot.yaml"),
+    include_str!("templates/container-health-escalation.yaml"),
+    include_str!("templates/log-anomaly-triage.yaml"),
+];
+
+/// A parameterized workflow
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorkflowTemplate {
+    pub kind: String,
+    pub version: String,
+    pub name: String,
+    /// Version of the template itself, recorded on every instance
+    pub template_version: String,
+    pub description: String,
+    #[serde(default)]
+    pub parameters: Vec<TemplateParameter>,
+    /// The workflow document, with `${name}` placeholders
+    pub workflow: serde_yaml::Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TemplateParameter {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub param_type: ParameterType,
+    #[serde(default)]
+    pub description: String,
+    /// Parameters without a default are required
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<serde_json::Value>,
+    /// Bounds for `integer` and `number` parameters
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub minimum: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maximum: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParameterType {
+    String,
+    Integer,
+    Number,
+    Boolean,
+    /// A list of strings; given as text it is split on commas
+    StringList,
+    /// Five-field cron expression
+    Cron,
+    /// An http(s) URL
+    Url,
+    /// Anything `jarvis_core::time_range` reads, e.g. "1h" or "since boot"
+    TimeRange,
+}
+
+/// Where an instantiated workflow came from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateSource {
+    pub name: String,
+    pub version: String,
+    /// Every parameter's value, defaults included
+    pub parameters: BTreeMap<String, serde_json::Value>,
+}
+
+/// The templates shipped with GhostFlow, in gallery order
+pub fn builtin_templates() -> Result<Vec<WorkflowTemplate>> {
+    BUILTIN.iter().map(|yaml| WorkflowTemplate::from_yaml(yaml)).collect()
+}
+
+/// The built-in template called `name`
+pub fn find_template(name: &str) -> Result<WorkflowTemplate> {
+    builtin_templates()?
+        .into_iter()
+        .find(|t| t.name == name)
+        .ok_or_else(|| {
+            jarvis_core::JarvisError::NotFound(format!("No workflow template named '{}'", name))
+                .into()
+        })
+}
+
+impl WorkflowTemplate {
+    pub fn from_yaml(yaml: &str) -> Result<Self> {
+        let template: Self =
+            serde_yaml::from_str(yaml).context("Invalid workflow template")?;
+        if template.kind != TEMPLATE_KIND || template.version != TEMPLATE_FORMAT_VERSION {
+            return Err(anyhow::anyhow!(
+                "Template '{}' is '{}/{}', expected '{}/{}'",
+                template.name,
+                template.kind,
+                template.version,
+                TEMPLATE_KIND,
+                TEMPLATE_FORMAT_VERSION
+            ));
+        }
+        Ok(template)
+    }
+
+    /// Check `values` against the parameters and fill in defaults. Text
+    /// values are read as the parameter's type, so `--set retries=3` works.
+    pub fn resolve(
+        &self,
+        values: &BTreeMap<String, serde_json::Value>,
+    ) -> Result<BTreeMap<String, serde_json::Value>> {
+        if let Some(unknown) = values
+            .keys()
+            .find(|key| !self.parameters.iter().any(|p| &p.name == *key))
+        {
+            let names: Vec<&str> = self.parameters.iter().map(|p| p.name.as_str()).collect();
+            return Err(anyhow::anyhow!(
+                "Template '{}' has no parameter '{}' (parameters: {})",
+                self.name,
+                unknown,
+                names.join(", ")
+            ));
+        }
+
+        let mut resolved = BTreeMap::new();
+        for parameter in &self.parameters {
+            let value = match values.get(&parameter.name).or(parameter.default.as_ref()) {
+                Some(value) => parameter
+                    .check(value.clone())
+                    .map_err(|e| anyhow::anyhow!("Parameter '{}': {}", parameter.name, e))?,
+                None => {
+                    return Err(anyhow::anyhow!(
+                        "Parameter '{}' is required ({})",
+                        parameter.name,
+                        parameter.description
+                    ))
+                }
+            };
+            resolved.insert(parameter.name.clone(), value);
+        }
+        Ok(resolved)
+    }
+
+    /// The workflow document for `values`, tagged with this template. Call
+    /// `validate` on the result before importing it.
+    pub fn render(&self, values: &BTreeMap<String, serde_json::Value>) -> Result<WorkflowDocument> {
+        let parameters = self.resolve(values)?;
+        let rendered = substitute(self.workflow.clone(), &parameters)
+            .with_context(|| format!("Template '{}' is broken", self.name))?;
+        let yaml = serde_yaml::to_string(&rendered)?;
+        let mut document = WorkflowDocument::from_yaml(&yaml)
+            .with_context(|| format!("Template '{}' is broken", self.name))?;
+        document.metadata.template = Some(TemplateSource {
+            name: self.name.clone(),
+            version: self.template_version.clone(),
+            parameters,
+        });
+        Ok(document)
+    }
+}
+
+impl TemplateParameter {
+    /// `value` as this parameter's type, or why it can't be
+    fn check(&self, value: serde_json::Value) -> std::result::Result<serde_json::Value, String> {
+        use serde_json::Value;
+
+        let value = match (self.param_type, value) {
+            (ParameterType::Integer, Value::String(s)) => s
+                .trim()
+                .parse::<i64>()
+                .map(Value::from)
+                .map_err(|_| format!("'{}' is not an integer", s))?,
+            (ParameterType::Number, Value::String(s)) => s
+                .trim()
+                .parse::<f64>()
+                .map(Value::from)
+                .map_err(|_| format!("'{}' is not a number", s))?,
+            (ParameterType::Boolean, Value::String(s)) => match s.trim() {
+                "true" | "yes" | "on" => Value::Bool(true),
+                "false" | "no" | "off" => Value::Bool(false),
+                _ => return Err(format!("'{}' is not true or false", s)),
+            },
+            (ParameterType::StringList, Value::String(s)) => Value::from(
+                s.split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .collect::<Vec<_>>(),
+            ),
+            (_, value) => value,
+        };
+
+        let matches = match self.param_type {
+            ParameterType::String
+            | ParameterType::Cron
+            | ParameterType::Url
+            | ParameterType::TimeRange => value.is_string(),
+            ParameterType::Integer => value.is_i64() || value.is_u64(),
+            ParameterType::Number => value.is_number(),
+            ParameterType::Boolean => value.is_boolean(),
+            ParameterType::StringList => value
+                .as_array()
+                .is_some_and(|items| items.iter().all(|item| item.is_string())),
+        };
+        if !matches {
+            return Err(format!("expected {}, got {}", self.param_type.as_str(), value));
+        }
+
+        if let Some(n) = value.as_f64() {
+            if let Some(min) = self.minimum.filter(|min| n < *min) {
+                return Err(format!("{} is below the minimum of {}", n, min));
+            }
+            if let Some(max) = self.maximum.filter(|max| n > *max) {
+                return Err(format!("{} is above the maximum of {}", n, max));
+            }
+        }
+
+        let text = value.as_str().unwrap_or_default();
+        match self.param_type {
+            ParameterType::Cron => {
+                jarvis_core::report::next_scheduled(text, chrono::Utc::now()).map_err(|_| {
+                    format!(
+                        "'{}' is not a cron schedule (minute hour day-of-month month day-of-week)",
+                        text
+                    )
+                })?;
+            }
+            ParameterType::Url => {
+                let url = reqwest::Url::parse(text).map_err(|e| format!("'{}': {}", text, e))?;
+                if !matches!(url.scheme(), "http" | "https") {
+                    return Err(format!("'{}' is not an http(s) URL", text));
+                }
+            }
+            ParameterType::TimeRange => {
+                jarvis_core::time_range::parse_range(text).map_err(|e| e.to_string())?;
+            }
+            _ => {}
+        }
+        Ok(value)
+    }
+}
+
+impl ParameterType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ParameterType::String => "string",
+            ParameterType::Integer => "integer",
+            ParameterType::Number => "number",
+            ParameterType::Boolean => "boolean",
+            ParameterType::StringList => "string_list",
+            ParameterType::Cron => "cron",
+            ParameterType::Url => "url",
+            ParameterType::TimeRange => "time_range",
+        }
+    }
+}
+
+/// Replace `${name}` placeholders throughout `value`
+fn substitute(
+    value: serde_yaml::Value,
+    parameters: &BTreeMap<String, serde_json::Value>,
+) -> Result<serde_yaml::Value> {
+    use serde_yaml::Value;
+
+    Ok(match value {
+        Value::String(s) => {
+            if let Some(name) = whole_placeholder(&s) {
+                let value = parameters
+                    .get(name)
+                    .ok_or_else(|| anyhow::anyhow!("unknown parameter '${{{}}}'", name))?;
+                serde_yaml::to_value(value)?
+            } else {
+                Value::String(interpolate(&s, parameters)?)
+            }
+        }
+        Value::Sequence(items) => Value::Sequence(
+            items
+                .into_iter()
+                .map(|item| substitute(item, parameters))
+                .collect::<Result<_>>()?,
+        ),
+        Value::Mapping(mapping) => Value::Mapping(
+            mapping
+                .into_iter()
+                .map(|(key, value)| Ok((key, substitute(value, parameters)?)))
+                .collect::<Result<_>>()?,
+        ),
+        other => other,
+    })
+}
+
+/// "name" when `s` is exactly "${name}"
+fn whole_placeholder(s: &str) -> Option<&str> {
+    let name = s.trim().strip_prefix("${")?.strip_suffix('}')?;
+    (!name.contains('}')).then_some(name.trim())
+}
+
+fn interpolate(s: &str, parameters: &BTreeMap<String, serde_json::Value>) -> Result<String> {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| anyhow::anyhow!("unterminated placeholder in '{}'", s))?;
+        let name = after[..end].trim();
+        let value = parameters
+            .get(name)
+            .ok_or_else(|| anyhow::anyhow!("unknown parameter '${{{}}}'", name))?;
+        out.push_str(&display(value));
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// A parameter value as it reads inside text
+fn display(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Array(items) if items.is_empty() => "none".to_string(),
+        serde_json::Value::Array(items) => items
+            .iter()
+            .map(display)
+            .collect::<Vec<_>>()
+            .join(", "),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflow_engine::WorkflowEngine;
+    use serde_json::json;
+
+    fn values(pairs: &[(&str, serde_json::Value)]) -> BTreeMap<String, serde_json::Value> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_every_builtin_template_instantiates() {
+        let engine = WorkflowEngine::new().unwrap();
+        engine.initialize_default_nodes().await.unwrap();
+
+        let templates = builtin_templates().unwrap();
+        let names: Vec<&str> = templates.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "security-audit-and-report",
+                "nightly-update-with-snapshot",
+                "container-health-escalation",
+                "log-anomaly-triage",
+            ]
+        );
+
+        for template in &templates {
+            let set = values(&[("notify_url", json!("https://ntfy.example/ops"))]);
+            let id = engine
+                .instantiate_template(&template.name, &set)
+                .await
+                .unwrap_or_else(|e| panic!("{}: {:#}", template.name, e));
+            let workflow = engine.get_workflow(id).await.unwrap().unwrap();
+
+            let source = workflow.metadata.template.as_ref().unwrap();
+            assert_eq!(source.name, template.name);
+            assert_eq!(source.version, template.template_version);
+            assert_eq!(source.parameters["notify_url"], "https://ntfy.example/ops");
+            assert_eq!(
+                source.parameters.len(),
+                template.parameters.len(),
+                "{}",
+                template.name
+            );
+
+            // Nothing is left unrendered, and the source survives export
+            let yaml = engine.export_yaml(id).await.unwrap();
+            assert!(!yaml.contains("${"), "{}", yaml);
+            let exported = WorkflowDocument::from_yaml(&yaml).unwrap();
+            assert_eq!(exported.metadata.template.as_ref(), Some(source));
+        }
+    }
+
+    #[test]
+    fn test_values_keep_their_types() {
+        let template = find_template("nightly-update-with-snapshot").unwrap();
+        let document = template
+            .render(&values(&[
+                ("notify_url", json!("http://127.0.0.1:9000/hook")),
+                ("update_timeout_seconds", json!("7200")),
+                ("backup_destination", json!("/srv/backup")),
+            ]))
+            .unwrap();
+
+        let update = document.nodes.iter().find(|n| n.id == "update").unwrap();
+        assert_eq!(update.timeout_seconds, Some(7200));
+        assert_eq!(update.config["timeout_seconds"], 7200);
+        let backup = document.nodes.iter().find(|n| n.id == "backup").unwrap();
+        assert_eq!(backup.config["parameters"]["destination"], "/srv/backup");
+        let trigger = document.nodes.iter().find(|n| n.id == "trigger").unwrap();
+        assert_eq!(trigger.config["cron"], "0 3 * * *");
+
+        let template = find_template("security-audit-and-report").unwrap();
+        let document = template
+            .render(&values(&[
+                ("notify_url", json!("https://hooks.example/audit")),
+                ("hosts", json!("web1, db1")),
+                ("full_scan", json!("no")),
+            ]))
+            .unwrap();
+        let trigger = document.nodes.iter().find(|n| n.id == "trigger").unwrap();
+        assert_eq!(trigger.config["hosts"], json!(["web1", "db1"]));
+        let scan = document.nodes.iter().find(|n| n.id == "scan").unwrap();
+        assert_eq!(scan.config["parameters"]["full_scan"], false);
+        let report = document.nodes.iter().find(|n| n.id == "report").unwrap();
+        let prompt = report.config["prompt"].as_str().unwrap();
+        assert!(prompt.contains("for web1, db1,"), "{}", prompt);
+        // Runtime placeholders are not template parameters
+        assert!(prompt.contains("{{input}}"), "{}", prompt);
+    }
+
+    #[test]
+    fn test_parameters_are_checked() {
+        let template = find_template("container-health-escalation").unwrap();
+        let error = |set: &[(&str, serde_json::Value)]| {
+            template.render(&values(set)).unwrap_err().to_string()
+        };
+        let url = ("notify_url", json!("https://hooks.example/ops"));
+
+        assert!(error(&[]).contains("'notify_url' is required"));
+        assert!(error(&[url.clone(), ("check_retries", json!(50))]).contains("above the maximum of 10"));
+        assert!(error(&[url.clone(), ("check_retries", json!("many"))]).contains("not an integer"));
+        assert!(error(&[url.clone(), ("schedule", json!("every day"))]).contains("not a cron schedule"));
+        assert!(error(&[url.clone(), ("retries", json!(1))]).contains("has no parameter 'retries'"));
+        assert!(error(&[("notify_url", json!("ftp://hooks.example"))]).contains("not an http(s) URL"));
+
+        let template = find_template("log-anomaly-triage").unwrap();
+        let error = template
+            .render(&values(&[url, ("since", json!("soon"))]))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Parameter 'since'"), "{}", error);
+
+        assert!(find_template("teleport-everything").is_err());
+    }
+
+    #[test]
+    fn test_unknown_placeholder_is_a_broken_template() {
+        let mut template = find_template("log-anomaly-triage").unwrap();
+        template.workflow["metadata"]["name"] = "Triage ${host}".into();
+        let error = template
+            .render(&values(&[("notify_url", json!("https://hooks.example/ops"))]))
+            .unwrap_err();
+        assert!(format!("{:#}", error).contains("unknown parameter '${host}'"));
+    }
+}
@@ -8,6 +8,9 @@ use uuid::Uuid;
 pub struct WorkflowContext {
     pub workflow_id: Uuid,
     pub execution_id: Uuid,
+    /// Namespace of the workflow; memory the nodes keep is stored under it
+    #[serde(default = "crate::namespace::default_namespace")]
+    pub namespace: String,
     pub current_node: String,
     pub variables: HashMap<String, serde_json::Value>,
     pub memory_context: Option<MemoryContext>,
@@ -235,6 +238,7 @@ impl Default for WorkflowContext {
         Self {
             workflow_id: Uuid::new_v4(),
             execution_id: Uuid::new_v4(),
+            namespace: crate::namespace::default_namespace(),
             current_node: String::new(),
             variables: HashMap::new(),
             memory_context: None,
@@ -1,11 +1,18 @@
 use anyhow::{Context, Result};
+use jarvis_core::net::CheckedSend;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::{mpsc, RwLock};
+use tokio::task::JoinSet;
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
+use crate::budget::{BudgetAction, BudgetExceeded, Budgets};
+use crate::namespace::{self, ExecutionSlot, QuotaConfig, Quotas};
+use crate::templates::{self, TemplateSource};
+use crate::workflow_format::WorkflowDocument;
 use crate::nodes::{
     NodeDefinition, NodeInstance, NodeOutput, ExecutionContext,
     llm_router::LLMRouterNode,
@@ -14,18 +21,34 @@ use crate::nodes::{
     blockchain::BlockchainNode,
 };
 
+/// How long past its `timeout_seconds` a node may take to wind down before
+/// the engine drops it
+const NODE_TIMEOUT_GRACE: Duration = Duration::from_secs(5);
+
+/// Ports every connection may use; a branching node's output leaves through
+/// these as well as through the port it chose
+const DEFAULT_PORTS: &[&str] = &["main", "output"];
+
 /// Main workflow execution engine
 pub struct WorkflowEngine {
     workflows: Arc<RwLock<HashMap<Uuid, Workflow>>>,
     node_registry: Arc<RwLock<HashMap<String, Box<dyn NodeDefinition + Send + Sync>>>>,
     execution_queue: mpsc::UnboundedSender<ExecutionRequest>,
     metrics: WorkflowMetrics,
+    budgets: Option<Arc<Budgets>>,
+    paused: Arc<RwLock<HashMap<Uuid, PausedExecution>>>,
+    quotas: Arc<Quotas>,
+    /// Results of finished and paused executions, kept until deleted
+    executions: Arc<RwLock<HashMap<Uuid, ExecutionResult>>>,
 }
 
 /// Workflow definition structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Workflow {
     pub id: Uuid,
+    /// Tenant the workflow belongs to; its executions and memory do too
+    #[serde(default = "namespace::default_namespace")]
+    pub namespace: String,
     pub name: String,
     pub description: Option<String>,
     pub version: String,
@@ -47,6 +70,18 @@ pub struct WorkflowNode {
     pub retry_on_fail: bool,
     pub retry_count: u32,
     pub timeout_seconds: Option<u32>,
+    #[serde(default)]
+    pub on_failure: FailurePolicy,
+}
+
+/// What happens to the rest of the workflow when a node fails
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum FailurePolicy {
+    /// Fail the execution and cancel any nodes still running
+    #[default]
+    StopWorkflow,
+    /// Record the error and let dependents run without this node's output
+    Continue,
 }
 
 /// Connection between nodes
@@ -75,6 +110,13 @@ pub struct WorkflowSettings {
     pub save_data_error: bool,
     pub save_manual_executions: bool,
     pub caller_policy: CallerPolicy,
+    /// Upper bound on concurrently running nodes in `ExecutionMode::Parallel`
+    #[serde(default = "default_max_parallel_nodes")]
+    pub max_parallel_nodes: usize,
+}
+
+fn default_max_parallel_nodes() -> usize {
+    4
 }
 
 /// Workflow metadata
@@ -85,10 +127,13 @@ pub struct WorkflowMetadata {
     pub created_by: String,
     pub tags: Vec<String>,
     pub folder: Option<String>,
+    /// The template this workflow was instantiated from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<TemplateSource>,
 }
 
 /// Workflow execution state
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum WorkflowState {
     Active,
     Paused,
@@ -111,6 +156,19 @@ pub struct ExecutionRequest {
     pub trigger_data: serde_json::Value,
     pub execution_mode: ExecutionMode,
     pub response_sender: Option<mpsc::UnboundedSender<ExecutionResult>>,
+    /// Carry on with a paused execution instead of starting a new one
+    pub resume: Option<PausedExecution>,
+    /// The execution's place in its namespace's quotas
+    pub slot: ExecutionSlot,
+}
+
+/// An execution waiting for an LLM budget to be raised
+#[derive(Debug, Clone)]
+pub struct PausedExecution {
+    pub trigger_data: serde_json::Value,
+    pub execution_mode: ExecutionMode,
+    /// The execution as it stood when it paused
+    pub result: ExecutionResult,
 }
 
 /// Execution mode
@@ -121,6 +179,9 @@ pub enum ExecutionMode {
     Webhook,
     Scheduled,
     Integration,
+    /// Run every node whose dependencies have finished concurrently, up to
+    /// `WorkflowSettings::max_parallel_nodes`. Other modes run one node at a time.
+    Parallel,
 }
 
 /// Execution result
@@ -128,6 +189,8 @@ pub enum ExecutionMode {
 pub struct ExecutionResult {
     pub execution_id: Uuid,
     pub workflow_id: Uuid,
+    #[serde(default = "namespace::default_namespace")]
+    pub namespace: String,
     pub status: ExecutionStatus,
     pub start_time: chrono::DateTime<chrono::Utc>,
     pub end_time: Option<chrono::DateTime<chrono::Utc>>,
@@ -135,6 +198,9 @@ pub struct ExecutionResult {
     pub data: serde_json::Value,
     pub error: Option<String>,
     pub node_executions: Vec<NodeExecution>,
+    /// LLM spend of the execution, resumed runs included
+    #[serde(default)]
+    pub cost_usd: f64,
 }
 
 /// Individual node execution result
@@ -149,6 +215,9 @@ pub struct NodeExecution {
     pub input_data: serde_json::Value,
     pub output_data: Option<serde_json::Value>,
     pub error: Option<String>,
+    /// LLM spend of the execution when this node finished
+    #[serde(default)]
+    pub cumulative_cost_usd: f64,
 }
 
 /// Execution status
@@ -159,6 +228,10 @@ pub enum ExecutionStatus {
     Error,
     Canceled,
     Waiting,
+    /// Not run because no branch leading to it was taken
+    Skipped,
+    /// Paused because an LLM budget is used up; resumable once it's raised
+    BudgetExceeded,
 }
 
 /// Workflow metrics
@@ -174,16 +247,31 @@ pub struct WorkflowMetrics {
 impl WorkflowEngine {
     /// Create new workflow engine
     pub fn new() -> Result<Self> {
+        Self::build(None)
+    }
+
+    /// Create a workflow engine whose LLM calls are charged to `budgets`
+    pub fn with_budgets(budgets: Arc<Budgets>) -> Result<Self> {
+        Self::build(Some(budgets))
+    }
+
+    fn build(budgets: Option<Arc<Budgets>>) -> Result<Self> {
         let (tx, mut rx) = mpsc::unbounded_channel::<ExecutionRequest>();
         
         let workflows = Arc::new(RwLock::new(HashMap::new()));
         let node_registry = Arc::new(RwLock::new(HashMap::new()));
+        let paused = Arc::new(RwLock::new(HashMap::new()));
+        let executions = Arc::new(RwLock::new(HashMap::new()));
         
         let engine = Self {
             workflows: workflows.clone(),
             node_registry: node_registry.clone(),
             execution_queue: tx,
             metrics: WorkflowMetrics::default(),
+            budgets: budgets.clone(),
+            paused: paused.clone(),
+            quotas: Arc::new(Quotas::unlimited()),
+            executions: executions.clone(),
         };
         
         // Start execution processor
@@ -195,6 +283,9 @@ impl WorkflowEngine {
                     request,
                     workflows_clone.clone(),
                     node_registry_clone.clone(),
+                    budgets.clone(),
+                    paused.clone(),
+                    executions.clone(),
                 ).await;
             }
         });
@@ -203,13 +294,30 @@ impl WorkflowEngine {
         Ok(engine)
     }
 
+    /// Hold each namespace to its quota from `config`; call before
+    /// `initialize_default_nodes` so memory nodes are held to it too
+    pub fn with_quotas(mut self, config: QuotaConfig) -> Self {
+        self.quotas = Arc::new(Quotas::new(config));
+        self
+    }
+
+    /// The quotas executions and memory entries are counted against
+    pub fn quotas(&self) -> &Arc<Quotas> {
+        &self.quotas
+    }
+
     /// Initialize with default node types
     pub async fn initialize_default_nodes(&self) -> Result<()> {
         let mut registry = self.node_registry.write().await;
         
         // Register core Jarvis nodes
-        registry.insert("llm_router".to_string(), Box::new(LLMRouterNode::new()));
-        registry.insert("memory".to_string(), Box::new(MemoryNode::new()));
+        let llm_router = match &self.budgets {
+            Some(budgets) => LLMRouterNode::new()?.with_budgets(budgets.clone()),
+            None => LLMRouterNode::new()?,
+        };
+        registry.insert("llm_router".to_string(), Box::new(llm_router));
+        let memory = MemoryNode::new()?.with_quotas(self.quotas.clone());
+        registry.insert("memory".to_string(), Box::new(memory));
         registry.insert("orchestrator".to_string(), Box::new(OrchestratorNode::new()));
         registry.insert("blockchain".to_string(), Box::new(BlockchainNode::new()));
         
@@ -221,13 +329,25 @@ impl WorkflowEngine {
         registry.insert("http_request".to_string(), Box::new(HttpRequestNode::new()));
         registry.insert("webhook".to_string(), Box::new(WebhookNode::new()));
         registry.insert("schedule_trigger".to_string(), Box::new(ScheduleTriggerNode::new()));
+        registry.insert("condition".to_string(), Box::new(ConditionNode::new()));
         
         info!("Default nodes registered in workflow engine");
         Ok(())
     }
 
+    /// Register a node type, replacing any registered under the same name
+    pub async fn register_node(&self, node: Box<dyn NodeDefinition + Send + Sync>) {
+        let node_type = node.node_type().to_string();
+        self.node_registry.write().await.insert(node_type.clone(), node);
+        debug!("Registered node type: {}", node_type);
+    }
+
     /// Create new workflow
     pub async fn create_workflow(&self, workflow: Workflow) -> Result<Uuid> {
+        namespace::validate_name(&workflow.namespace)?;
+        if let Some(budgets) = &self.budgets {
+            budgets.set_namespace(workflow.id, &workflow.namespace);
+        }
         let mut workflows = self.workflows.write().await;
         let workflow_id = workflow.id;
         workflows.insert(workflow_id, workflow);
@@ -248,13 +368,14 @@ impl WorkflowEngine {
         Ok(workflows.values().cloned().collect())
     }
 
-    /// Update workflow
-    pub async fn update_workflow(&self, workflow: Workflow) -> Result<()> {
+    /// Update workflow; it stays in the namespace it was created in
+    pub async fn update_workflow(&self, mut workflow: Workflow) -> Result<()> {
         let mut workflows = self.workflows.write().await;
         let workflow_id = workflow.id;
         
         if let Some(existing) = workflows.get_mut(&workflow_id) {
-            existing.updated_at = chrono::Utc::now();
+            workflow.metadata.updated_at = chrono::Utc::now();
+            workflow.namespace = existing.namespace.clone();
             *existing = workflow;
             info!("Updated workflow: {}", workflow_id);
             Ok(())
@@ -275,13 +396,70 @@ impl WorkflowEngine {
         }
     }
 
-    /// Execute workflow
+    /// Export a workflow in the portable YAML format
+    pub async fn export_yaml(&self, workflow_id: Uuid) -> Result<String> {
+        let workflow = self.get_workflow(workflow_id).await?
+            .ok_or_else(|| anyhow::anyhow!("Workflow not found: {}", workflow_id))?;
+        WorkflowDocument::from_workflow(&workflow).to_yaml()
+    }
+
+    /// Validate a portable YAML workflow and create it under a new id
+    pub async fn import_yaml(&self, yaml: &str) -> Result<Uuid> {
+        self.import_yaml_into(yaml, namespace::DEFAULT_NAMESPACE).await
+    }
+
+    /// `import_yaml` into `namespace`
+    pub async fn import_yaml_into(&self, yaml: &str, namespace: &str) -> Result<Uuid> {
+        let document = WorkflowDocument::from_yaml(yaml)?;
+        self.import_document(document, namespace).await
+    }
+
+    /// Render built-in template `name` with `parameters`, validate the
+    /// result, and create it under a new id
+    pub async fn instantiate_template(
+        &self,
+        name: &str,
+        parameters: &std::collections::BTreeMap<String, serde_json::Value>,
+    ) -> Result<Uuid> {
+        self.instantiate_template_into(name, parameters, namespace::DEFAULT_NAMESPACE).await
+    }
+
+    /// `instantiate_template` into `namespace`
+    pub async fn instantiate_template_into(
+        &self,
+        name: &str,
+        parameters: &std::collections::BTreeMap<String, serde_json::Value>,
+        namespace: &str,
+    ) -> Result<Uuid> {
+        let document = templates::find_template(name)?.render(parameters)?;
+        self.import_document(document, namespace).await
+    }
+
+    async fn import_document(&self, document: WorkflowDocument, namespace: &str) -> Result<Uuid> {
+        let engine_node_types: Vec<String> = self.node_registry.read().await
+            .keys()
+            .cloned()
+            .collect();
+        document.validate(&engine_node_types)?;
+        let mut workflow = document.into_workflow();
+        workflow.namespace = namespace.to_string();
+        self.create_workflow(workflow).await
+    }
+
+    /// Execute workflow, unless its namespace is at its concurrent or
+    /// stored executions quota
     pub async fn execute_workflow(
         &self,
         workflow_id: Uuid,
         trigger_data: serde_json::Value,
         execution_mode: ExecutionMode,
     ) -> Result<ExecutionResult> {
+        let namespace = self.get_workflow(workflow_id).await?
+            .map(|workflow| workflow.namespace)
+            .ok_or_else(|| anyhow::Error::new(jarvis_core::JarvisError::NotFound(
+                format!("Workflow not found: {}", workflow_id)
+            )))?;
+        let slot = self.quotas.admit(&namespace, true)?;
         let (tx, mut rx) = mpsc::unbounded_channel::<ExecutionResult>();
         
         let request = ExecutionRequest {
@@ -289,6 +467,40 @@ impl WorkflowEngine {
             trigger_data,
             execution_mode,
             response_sender: Some(tx),
+            resume: None,
+            slot,
+        };
+        
+        self.execution_queue.send(request)
+            .context("Failed to queue execution request")?;
+        
+        rx.recv().await
+            .ok_or_else(|| anyhow::anyhow!("Execution result not received"))
+    }
+
+    /// Carry on with an execution paused on an LLM budget, typically after
+    /// the budget was raised. Nodes that finished before the pause keep
+    /// their outputs and aren't run again; if the budget is still used up
+    /// the execution pauses again.
+    pub async fn resume_execution(&self, execution_id: Uuid) -> Result<ExecutionResult> {
+        let not_paused = || anyhow::Error::new(jarvis_core::JarvisError::NotFound(
+            format!("No paused execution with id {}", execution_id)
+        ));
+        let namespace = self.paused.read().await.get(&execution_id)
+            .map(|paused| paused.result.namespace.clone())
+            .ok_or_else(not_paused)?;
+        let slot = self.quotas.admit(&namespace, false)?;
+        let paused = self.paused.write().await.remove(&execution_id)
+            .ok_or_else(not_paused)?;
+        let (tx, mut rx) = mpsc::unbounded_channel::<ExecutionResult>();
+        
+        let request = ExecutionRequest {
+            workflow_id: paused.result.workflow_id,
+            trigger_data: paused.trigger_data.clone(),
+            execution_mode: paused.execution_mode.clone(),
+            response_sender: Some(tx),
+            resume: Some(paused),
+            slot,
         };
         
         self.execution_queue.send(request)
@@ -298,23 +510,101 @@ impl WorkflowEngine {
             .ok_or_else(|| anyhow::anyhow!("Execution result not received"))
     }
 
+    /// Executions waiting in the `BudgetExceeded` state
+    pub async fn paused_executions(&self) -> Vec<Uuid> {
+        let mut ids: Vec<Uuid> = self.paused.read().await.keys().copied().collect();
+        ids.sort();
+        ids
+    }
+
+    /// The budgets LLM calls are charged to, if any
+    pub fn budgets(&self) -> Option<&Arc<Budgets>> {
+        self.budgets.as_ref()
+    }
+
+    /// A finished or paused execution
+    pub async fn get_execution(&self, execution_id: Uuid) -> Option<ExecutionResult> {
+        self.executions.read().await.get(&execution_id).cloned()
+    }
+
+    /// Finished and paused executions, newest first, of `namespace` or of
+    /// every namespace
+    pub async fn list_executions(&self, namespace: Option<&str>) -> Vec<ExecutionResult> {
+        let mut executions: Vec<ExecutionResult> = self.executions.read().await
+            .values()
+            .filter(|execution| namespace.is_none_or(|namespace| execution.namespace == namespace))
+            .cloned()
+            .collect();
+        executions.sort_by(|a, b| b.start_time.cmp(&a.start_time));
+        executions
+    }
+
+    /// Delete a finished or paused execution, freeing its place in the
+    /// namespace's stored executions quota
+    pub async fn delete_execution(&self, execution_id: Uuid) -> Result<()> {
+        let removed = self.executions.write().await.remove(&execution_id)
+            .ok_or_else(|| anyhow::Error::new(jarvis_core::JarvisError::NotFound(
+                format!("No stored execution with id {}", execution_id)
+            )))?;
+        if self.paused.write().await.remove(&execution_id).is_some() {
+            if let Some(budgets) = &self.budgets {
+                budgets.finish_execution(execution_id);
+            }
+        }
+        self.quotas.forget_stored(&removed.namespace);
+        info!("Deleted execution: {}", execution_id);
+        Ok(())
+    }
+
     /// Process execution request
     async fn process_execution_request(
         request: ExecutionRequest,
         workflows: Arc<RwLock<HashMap<Uuid, Workflow>>>,
         node_registry: Arc<RwLock<HashMap<String, Box<dyn NodeDefinition + Send + Sync>>>>,
+        budgets: Option<Arc<Budgets>>,
+        paused: Arc<RwLock<HashMap<Uuid, PausedExecution>>>,
+        executions: Arc<RwLock<HashMap<Uuid, ExecutionResult>>>,
     ) {
-        let execution_id = Uuid::new_v4();
-        let start_time = chrono::Utc::now();
+        let namespace = request.slot.namespace().to_string();
+        let execution = match request.resume {
+            Some(paused) => {
+                let mut previous = paused.result;
+                // Which branches get skipped is decided again as the run goes on
+                previous.node_executions.retain(|n| !matches!(n.status, ExecutionStatus::Skipped));
+                previous.status = ExecutionStatus::Running;
+                previous.error = None;
+                previous.end_time = None;
+                previous.duration_ms = None;
+                previous
+            }
+            None => ExecutionResult {
+                execution_id: Uuid::new_v4(),
+                workflow_id: request.workflow_id,
+                namespace: namespace.clone(),
+                status: ExecutionStatus::Running,
+                start_time: chrono::Utc::now(),
+                end_time: None,
+                duration_ms: None,
+                data: serde_json::json!({}),
+                error: None,
+                node_executions: vec![],
+                cost_usd: 0.0,
+            },
+        };
+        let execution_id = execution.execution_id;
+        let start_time = execution.start_time;
         
         debug!("Processing execution request: {} for workflow: {}", execution_id, request.workflow_id);
         
+        let trigger_data = request.trigger_data.clone();
+        let execution_mode = request.execution_mode.clone();
         let result = match Self::execute_workflow_internal(
-            execution_id,
-            request.workflow_id,
+            execution,
             request.trigger_data,
+            request.execution_mode,
             workflows,
             node_registry,
+            budgets.clone(),
         ).await {
             Ok(mut result) => {
                 result.end_time = Some(chrono::Utc::now());
@@ -330,6 +620,7 @@ impl WorkflowEngine {
                 ExecutionResult {
                     execution_id,
                     workflow_id: request.workflow_id,
+                    namespace,
                     status: ExecutionStatus::Error,
                     start_time,
                     end_time: Some(chrono::Utc::now()),
@@ -339,10 +630,24 @@ impl WorkflowEngine {
                     data: serde_json::json!({}),
                     error: Some(e.to_string()),
                     node_executions: vec![],
+                    cost_usd: budgets.as_ref()
+                        .map_or(0.0, |budgets| budgets.execution_usage(execution_id).cost_usd),
                 }
             }
         };
         
+        if matches!(result.status, ExecutionStatus::BudgetExceeded) {
+            paused.write().await.insert(execution_id, PausedExecution {
+                trigger_data,
+                execution_mode,
+                result: result.clone(),
+            });
+        } else if let Some(budgets) = &budgets {
+            budgets.finish_execution(execution_id);
+        }
+        let replaced = executions.write().await.insert(execution_id, result.clone());
+        request.slot.finish(replaced.is_none());
+        
         if let Some(sender) = request.response_sender {
             if let Err(e) = sender.send(result) {
                 error!("Failed to send execution result: {}", e);
@@ -352,12 +657,14 @@ impl WorkflowEngine {
 
     /// Internal workflow execution logic
     async fn execute_workflow_internal(
-        execution_id: Uuid,
-        workflow_id: Uuid,
+        mut execution_result: ExecutionResult,
         trigger_data: serde_json::Value,
+        execution_mode: ExecutionMode,
         workflows: Arc<RwLock<HashMap<Uuid, Workflow>>>,
         node_registry: Arc<RwLock<HashMap<String, Box<dyn NodeDefinition + Send + Sync>>>>,
+        budgets: Option<Arc<Budgets>>,
     ) -> Result<ExecutionResult> {
+        let (execution_id, workflow_id) = (execution_result.execution_id, execution_result.workflow_id);
         let workflow = {
             let workflows_guard = workflows.read().await;
             workflows_guard.get(&workflow_id)
@@ -369,17 +676,20 @@ impl WorkflowEngine {
             return Err(anyhow::anyhow!("Workflow is not active: {:?}", workflow.state));
         }
 
-        let mut execution_result = ExecutionResult {
-            execution_id,
-            workflow_id,
-            status: ExecutionStatus::Running,
-            start_time: chrono::Utc::now(),
-            end_time: None,
-            duration_ms: None,
-            data: serde_json::json!({}),
-            error: None,
-            node_executions: vec![],
-        };
+        // Nodes that finished before the execution paused, with their outputs
+        let mut finished: HashMap<String, Option<NodeOutput>> = execution_result.node_executions.iter()
+            .filter_map(|n| match n.status {
+                ExecutionStatus::Success => Some((n.node_id.clone(), Some(NodeOutput {
+                    data: n.output_data.clone().unwrap_or_default(),
+                }))),
+                ExecutionStatus::Error => Some((n.node_id.clone(), None)),
+                _ => None,
+            })
+            .collect();
+        let spent = || budgets.as_ref()
+            .map_or(0.0, |budgets| budgets.execution_usage(execution_id).cost_usd);
+        let pauses = budgets.as_ref()
+            .is_some_and(|budgets| budgets.on_exhausted() == BudgetAction::Pause);
 
         // Find start nodes
         let start_nodes = workflow.nodes.iter()
@@ -391,7 +701,12 @@ impl WorkflowEngine {
             return Err(anyhow::anyhow!("No start node found in workflow"));
         }
 
-        // Execute workflow nodes
+        let graph = DependencyGraph::build(&workflow)?;
+        let parallelism = match execution_mode {
+            ExecutionMode::Parallel => workflow.settings.max_parallel_nodes.max(1),
+            _ => 1,
+        };
+
         let mut execution_context = ExecutionContext {
             workflow_id,
             execution_id,
@@ -399,67 +714,183 @@ impl WorkflowEngine {
             node_outputs: HashMap::new(),
         };
 
-        // Topological sort for node execution order
-        let execution_order = Self::calculate_execution_order(&workflow)?;
-        
-        for node_id in execution_order {
-            if let Some(node) = workflow.nodes.get(&node_id) {
+        let mut pending = graph.in_degree.clone();
+        // Incoming connections that carried data, per node
+        let mut live: HashMap<String, usize> = HashMap::new();
+        let mut ready = graph.roots();
+        let mut running = JoinSet::new();
+        // Spawned but not yet joined, with their start times
+        let mut in_flight: HashMap<String, chrono::DateTime<chrono::Utc>> = HashMap::new();
+        let mut failure: Option<String> = None;
+        let mut budget_pause: Option<String> = None;
+
+        loop {
+            while failure.is_none() && budget_pause.is_none() && running.len() < parallelism {
+                let Some(node_id) = ready.pop_front() else {
+                    break;
+                };
+                let node = workflow.nodes[&node_id].clone();
+                if let Some(output) = finished.remove(&node_id) {
+                    debug!("Node {} finished before the execution paused", node_id);
+                    graph.release(&node_id, output.as_ref(), &mut pending, &mut live, &mut ready);
+                    if let Some(output) = output {
+                        execution_context.node_outputs.insert(node_id, output);
+                    }
+                    continue;
+                }
                 if node.disabled {
                     debug!("Skipping disabled node: {}", node_id);
+                    graph.release(&node_id, None, &mut pending, &mut live, &mut ready);
+                    continue;
+                }
+                if graph.in_degree[&node_id] > 0 && !live.contains_key(&node_id) {
+                    debug!("Skipping node {}: no branch leading to it was taken", node_id);
+                    let now = chrono::Utc::now();
+                    execution_result.node_executions.push(NodeExecution {
+                        node_id: node_id.clone(),
+                        node_type: node.node_type.clone(),
+                        status: ExecutionStatus::Skipped,
+                        start_time: now,
+                        end_time: Some(now),
+                        duration_ms: Some(0),
+                        input_data: node.parameters.clone(),
+                        output_data: None,
+                        error: None,
+                        cumulative_cost_usd: spent(),
+                    });
+                    graph.skip(&node_id, &mut pending, &mut ready);
                     continue;
                 }
 
                 let node_start_time = chrono::Utc::now();
-                let node_execution_result = Self::execute_node(
-                    node,
-                    &mut execution_context,
-                    &node_registry,
-                ).await;
+                in_flight.insert(node_id.clone(), node_start_time);
+
+                // A node is only ready once all of its dependencies finished,
+                // so this snapshot always carries their outputs
+                let context = execution_context.clone();
+                let registry = node_registry.clone();
+                running.spawn(async move {
+                    let output = Self::execute_node(&node, &context, &registry).await;
+                    (node_id, node_start_time, chrono::Utc::now(), output)
+                });
+            }
 
-                let node_end_time = chrono::Utc::now();
-                let node_duration = (node_end_time - node_start_time).num_milliseconds() as u64;
-
-                let node_execution = match node_execution_result {
-                    Ok(output) => {
-                        execution_context.node_outputs.insert(node_id.clone(), output.clone());
-                        
-                        NodeExecution {
-                            node_id: node_id.clone(),
-                            node_type: node.node_type.clone(),
-                            status: ExecutionStatus::Success,
-                            start_time: node_start_time,
-                            end_time: Some(node_end_time),
-                            duration_ms: Some(node_duration),
-                            input_data: node.parameters.clone(),
-                            output_data: Some(output.data),
-                            error: None,
-                        }
+            let Some(joined) = running.join_next().await else {
+                break;
+            };
+            let (node_id, node_start_time, node_end_time, output) = match joined {
+                Ok(done) => done,
+                // Aborted after a sibling failed; recorded below
+                Err(e) if e.is_cancelled() => continue,
+                Err(e) => return Err(anyhow::anyhow!("Node task panicked: {}", e)),
+            };
+            in_flight.remove(&node_id);
+
+            let node = &workflow.nodes[&node_id];
+            let node_duration = (node_end_time - node_start_time).num_milliseconds() as u64;
+
+            match output {
+                Ok(output) => {
+                    execution_result.node_executions.push(NodeExecution {
+                        node_id: node_id.clone(),
+                        node_type: node.node_type.clone(),
+                        status: ExecutionStatus::Success,
+                        start_time: node_start_time,
+                        end_time: Some(node_end_time),
+                        duration_ms: Some(node_duration),
+                        input_data: node.parameters.clone(),
+                        output_data: Some(output.data.clone()),
+                        error: None,
+                        cumulative_cost_usd: spent(),
+                    });
+                    graph.release(&node_id, Some(&output), &mut pending, &mut live, &mut ready);
+                    execution_context.node_outputs.insert(node_id.clone(), output);
+                }
+                Err(e) if pauses && BudgetExceeded::find(&e).is_some() => {
+                    warn!("Pausing execution {} at node {}: {}", execution_id, node_id, e);
+
+                    execution_result.node_executions.push(NodeExecution {
+                        node_id: node_id.clone(),
+                        node_type: node.node_type.clone(),
+                        status: ExecutionStatus::BudgetExceeded,
+                        start_time: node_start_time,
+                        end_time: Some(node_end_time),
+                        duration_ms: Some(node_duration),
+                        input_data: node.parameters.clone(),
+                        output_data: None,
+                        error: Some(e.to_string()),
+                        cumulative_cost_usd: spent(),
+                    });
+                    // Nodes already running finish; nothing new starts
+                    if budget_pause.is_none() {
+                        budget_pause = Some(format!("Node {} paused: {}", node_id, e));
                     }
-                    Err(e) => {
-                        error!("Node execution failed: {} - {}", node_id, e);
-                        
-                        let node_execution = NodeExecution {
-                            node_id: node_id.clone(),
-                            node_type: node.node_type.clone(),
-                            status: ExecutionStatus::Error,
-                            start_time: node_start_time,
-                            end_time: Some(node_end_time),
-                            duration_ms: Some(node_duration),
-                            input_data: node.parameters.clone(),
-                            output_data: None,
-                            error: Some(e.to_string()),
-                        };
-
-                        execution_result.node_executions.push(node_execution);
-                        execution_result.status = ExecutionStatus::Error;
-                        execution_result.error = Some(format!("Node {} failed: {}", node_id, e));
-                        
-                        return Ok(execution_result);
+                }
+                Err(e) => {
+                    error!("Node execution failed: {} - {}", node_id, e);
+
+                    execution_result.node_executions.push(NodeExecution {
+                        node_id: node_id.clone(),
+                        node_type: node.node_type.clone(),
+                        status: ExecutionStatus::Error,
+                        start_time: node_start_time,
+                        end_time: Some(node_end_time),
+                        duration_ms: Some(node_duration),
+                        input_data: node.parameters.clone(),
+                        output_data: None,
+                        error: Some(e.to_string()),
+                        cumulative_cost_usd: spent(),
+                    });
+
+                    match node.on_failure {
+                        FailurePolicy::Continue => {
+                            warn!("Continuing past failed node {} per its on_failure policy", node_id);
+                            graph.release(&node_id, None, &mut pending, &mut live, &mut ready);
+                        }
+                        FailurePolicy::StopWorkflow => {
+                            if failure.is_none() {
+                                failure = Some(format!("Node {} failed: {}", node_id, e));
+                            }
+                            running.abort_all();
+                        }
                     }
-                };
+                }
+            }
+        }
 
-                execution_result.node_executions.push(node_execution);
+        execution_result.cost_usd = spent();
+
+        if let Some(error) = failure {
+            let canceled_at = chrono::Utc::now();
+            let mut canceled: Vec<_> = in_flight.into_iter().collect();
+            canceled.sort();
+            for (node_id, node_start_time) in canceled {
+                let node = &workflow.nodes[&node_id];
+                execution_result.node_executions.push(NodeExecution {
+                    node_id: node_id.clone(),
+                    node_type: node.node_type.clone(),
+                    status: ExecutionStatus::Canceled,
+                    start_time: node_start_time,
+                    end_time: Some(canceled_at),
+                    duration_ms: Some((canceled_at - node_start_time).num_milliseconds() as u64),
+                    input_data: node.parameters.clone(),
+                    output_data: None,
+                    error: Some("Canceled after another node failed".to_string()),
+                    cumulative_cost_usd: execution_result.cost_usd,
+                });
             }
+
+            execution_result.status = ExecutionStatus::Error;
+            execution_result.error = Some(error);
+            return Ok(execution_result);
+        }
+
+        if let Some(reason) = budget_pause {
+            execution_result.status = ExecutionStatus::BudgetExceeded;
+            execution_result.error = Some(reason);
+            execution_result.data = serde_json::to_value(execution_context.node_outputs)?;
+            info!("Workflow execution paused until its LLM budget is raised: {}", execution_id);
+            return Ok(execution_result);
         }
 
         execution_result.status = ExecutionStatus::Success;
@@ -472,82 +903,137 @@ impl WorkflowEngine {
     /// Execute individual node
     async fn execute_node(
         node: &WorkflowNode,
-        context: &mut ExecutionContext,
+        context: &ExecutionContext,
         node_registry: &Arc<RwLock<HashMap<String, Box<dyn NodeDefinition + Send + Sync>>>>,
     ) -> Result<NodeOutput> {
-        let registry = node_registry.read().await;
-        
-        if let Some(node_def) = registry.get(&node.node_type) {
-            // Create node instance
-            let mut node_instance = node_def.create_instance()?;
-            
-            // Configure node
-            node_instance.configure(node.parameters.clone()).await?;
-            
-            // Execute node
-            debug!("Executing node: {} ({})", node.id, node.node_type);
-            node_instance.execute(context).await
-        } else {
-            Err(anyhow::anyhow!("Unknown node type: {}", node.node_type))
+        // Don't hold the registry lock while the node runs
+        let mut node_instance = {
+            let registry = node_registry.read().await;
+            let node_def = registry.get(&node.node_type)
+                .ok_or_else(|| anyhow::anyhow!("Unknown node type: {}", node.node_type))?;
+            node_def.create_instance()?
+        };
+
+        // Nodes that can stop cleanly read their limit as `timeout_seconds`
+        let mut parameters = node.parameters.clone();
+        if let (Some(seconds), Some(fields)) = (node.timeout_seconds, parameters.as_object_mut()) {
+            fields.entry("timeout_seconds").or_insert(seconds.into());
         }
+
+        // Configure node
+        node_instance.configure(parameters).await?;
+
+        // Execute node
+        debug!("Executing node: {} ({})", node.id, node.node_type);
+        let Some(seconds) = node.timeout_seconds else {
+            return node_instance.execute(context).await;
+        };
+        let limit = Duration::from_secs(seconds.into()) + NODE_TIMEOUT_GRACE;
+        tokio::time::timeout(limit, node_instance.execute(context)).await
+            .map_err(|_| anyhow::Error::new(jarvis_core::JarvisError::Timeout(
+                format!("Node {} timed out after {}s", node.id, seconds)
+            )))?
     }
 
-    /// Calculate execution order using topological sort
-    fn calculate_execution_order(workflow: &Workflow) -> Result<Vec<String>> {
-        let mut in_degree = HashMap::new();
-        let mut graph = HashMap::new();
-        
-        // Initialize in-degree and graph
-        for node_id in workflow.nodes.keys() {
-            in_degree.insert(node_id.clone(), 0);
-            graph.insert(node_id.clone(), Vec::new());
-        }
-        
-        // Build graph and calculate in-degrees
+    /// Get workflow metrics
+    pub fn get_metrics(&self) -> &WorkflowMetrics {
+        &self.metrics
+    }
+}
+
+/// Node dependencies derived from a workflow's connections
+struct DependencyGraph {
+    /// Incoming connections per node
+    in_degree: HashMap<String, usize>,
+    /// Nodes fed by each node, with the output port feeding them
+    dependents: HashMap<String, Vec<(String, String)>>,
+}
+
+impl DependencyGraph {
+    /// Build the graph, rejecting dangling connections and cycles
+    fn build(workflow: &Workflow) -> Result<Self> {
+        let mut in_degree: HashMap<String, usize> = workflow.nodes.keys()
+            .map(|id| (id.clone(), 0))
+            .collect();
+        let mut dependents: HashMap<String, Vec<(String, String)>> = HashMap::new();
+
         for connection in &workflow.connections {
-            graph.entry(connection.source_node.clone())
-                .or_insert_with(Vec::new)
-                .push(connection.target_node.clone());
-            
+            for node_id in [&connection.source_node, &connection.target_node] {
+                if !workflow.nodes.contains_key(node_id) {
+                    return Err(anyhow::anyhow!("Connection references unknown node: {}", node_id));
+                }
+            }
+            dependents.entry(connection.source_node.clone())
+                .or_default()
+                .push((connection.source_output.clone(), connection.target_node.clone()));
             *in_degree.entry(connection.target_node.clone()).or_insert(0) += 1;
         }
-        
-        // Topological sort using Kahn's algorithm
-        let mut queue = Vec::new();
-        let mut result = Vec::new();
-        
-        // Find nodes with no incoming edges
-        for (node_id, degree) in &in_degree {
-            if *degree == 0 {
-                queue.push(node_id.clone());
+
+        let graph = Self { in_degree, dependents };
+
+        // Kahn's algorithm; anything left unvisited sits on a cycle
+        let mut pending = graph.in_degree.clone();
+        let mut queue = graph.roots();
+        let mut visited = 0;
+        while let Some(node_id) = queue.pop_front() {
+            visited += 1;
+            graph.skip(&node_id, &mut pending, &mut queue);
+        }
+        if visited != workflow.nodes.len() {
+            return Err(anyhow::anyhow!("Circular dependency detected in workflow"));
+        }
+
+        Ok(graph)
+    }
+
+    /// Nodes without dependencies, in a stable order
+    fn roots(&self) -> VecDeque<String> {
+        let mut roots: Vec<String> = self.in_degree.iter()
+            .filter(|(_, degree)| **degree == 0)
+            .map(|(id, _)| id.clone())
+            .collect();
+        roots.sort();
+        roots.into()
+    }
+
+    /// Mark `node_id` finished and queue dependents that have nothing left to
+    /// wait on. Dependents on a port `output` took, or on every port when
+    /// there is no output to branch on, count as fed.
+    fn release(
+        &self,
+        node_id: &str,
+        output: Option<&NodeOutput>,
+        pending: &mut HashMap<String, usize>,
+        live: &mut HashMap<String, usize>,
+        ready: &mut VecDeque<String>,
+    ) {
+        for (port, dependent) in self.dependents.get(node_id).into_iter().flatten() {
+            if output.map_or(true, |output| port_is_live(port, output)) {
+                *live.entry(dependent.clone()).or_insert(0) += 1;
             }
         }
-        
-        while let Some(node_id) = queue.pop() {
-            result.push(node_id.clone());
-            
-            if let Some(neighbors) = graph.get(&node_id) {
-                for neighbor in neighbors {
-                    if let Some(degree) = in_degree.get_mut(neighbor) {
-                        *degree -= 1;
-                        if *degree == 0 {
-                            queue.push(neighbor.clone());
-                        }
-                    }
+        self.skip(node_id, pending, ready);
+    }
+
+    /// Mark `node_id` done without feeding its dependents
+    fn skip(&self, node_id: &str, pending: &mut HashMap<String, usize>, ready: &mut VecDeque<String>) {
+        for (_, dependent) in self.dependents.get(node_id).into_iter().flatten() {
+            if let Some(degree) = pending.get_mut(dependent) {
+                *degree -= 1;
+                if *degree == 0 {
+                    ready.push_back(dependent.clone());
                 }
             }
         }
-        
-        if result.len() != workflow.nodes.len() {
-            return Err(anyhow::anyhow!("Circular dependency detected in workflow"));
-        }
-        
-        Ok(result)
     }
+}
 
-    /// Get workflow metrics
-    pub fn get_metrics(&self) -> &WorkflowMetrics {
-        &self.metrics
+/// Whether a connection leaving through `port` carries `output`. A node that
+/// branches names the port it took in a `branch` field of its output.
+fn port_is_live(port: &str, output: &NodeOutput) -> bool {
+    match output.data.get("branch").and_then(|b| b.as_str()) {
+        Some(branch) => port == branch || DEFAULT_PORTS.contains(&port),
+        None => true,
     }
 }
 
@@ -755,7 +1241,7 @@ impl NodeInstance for HttpRequestNodeInstance {
     }
 
     async fn execute(&mut self, _context: &ExecutionContext) -> Result<NodeOutput> {
-        let client = reqwest::Client::new();
+        let client = jarvis_core::net::client();
         let mut request = match self.method.as_str() {
             "GET" => client.get(&self.url),
             "POST" => client.post(&self.url),
@@ -768,7 +1254,7 @@ impl NodeInstance for HttpRequestNodeInstance {
             request = request.header(key, value);
         }
 
-        let response = request.send().await?;
+        let response = request.checked_send().await?;
         let status = response.status().as_u16();
         let body = response.text().await?;
 
@@ -854,4 +1340,410 @@ impl NodeInstance for ScheduleTriggerNodeInstance {
             }),
         })
     }
-}
\ No newline at end of file
+}
+
+/// Condition node - picks the `true` or `false` output port
+///
+/// `field` is a JSON pointer into the upstream outputs keyed by node id, with
+/// the trigger payload under `trigger`, e.g. `/scan/success`. The node takes
+/// the `true` port when that value equals `equals` (default `true`).
+pub struct ConditionNode;
+
+impl ConditionNode {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait::async_trait]
+impl NodeDefinition for ConditionNode {
+    fn node_type(&self) -> &'static str {
+        "condition"
+    }
+
+    fn create_instance(&self) -> Result<Box<dyn NodeInstance + Send + Sync>> {
+        Ok(Box::new(ConditionNodeInstance {
+            field: String::new(),
+            equals: serde_json::Value::Bool(true),
+        }))
+    }
+}
+
+pub struct ConditionNodeInstance {
+    field: String,
+    equals: serde_json::Value,
+}
+
+#[async_trait::async_trait]
+impl NodeInstance for ConditionNodeInstance {
+    async fn configure(&mut self, parameters: serde_json::Value) -> Result<()> {
+        self.field = parameters.get("field")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Condition node needs a 'field' JSON pointer"))?
+            .to_string();
+        if let Some(equals) = parameters.get("equals") {
+            self.equals = equals.clone();
+        }
+        Ok(())
+    }
+
+    async fn execute(&mut self, context: &ExecutionContext) -> Result<NodeOutput> {
+        let mut scope = serde_json::Map::new();
+        scope.insert("trigger".to_string(), context.data.clone());
+        for (node_id, output) in &context.node_outputs {
+            scope.insert(node_id.clone(), output.data.clone());
+        }
+
+        let value = serde_json::Value::Object(scope)
+            .pointer(&self.field)
+            .cloned()
+            .unwrap_or(serde_json::Value::Null);
+        let result = value == self.equals;
+
+        Ok(NodeOutput {
+            data: serde_json::json!({
+                "result": result,
+                "value": value,
+                "branch": if result { "true" } else { "false" },
+            }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::namespace::{NamespaceQuota, QuotaExceeded, QuotaLimit};
+
+    /// Sleeps for `millis`, then fails if `fail` is set
+    struct SleepNode;
+
+    #[async_trait::async_trait]
+    impl NodeDefinition for SleepNode {
+        fn node_type(&self) -> &'static str {
+            "sleep"
+        }
+
+        fn create_instance(&self) -> Result<Box<dyn NodeInstance + Send + Sync>> {
+            Ok(Box::new(SleepNodeInstance { millis: 0, fail: false }))
+        }
+    }
+
+    struct SleepNodeInstance {
+        millis: u64,
+        fail: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl NodeInstance for SleepNodeInstance {
+        async fn configure(&mut self, parameters: serde_json::Value) -> Result<()> {
+            self.millis = parameters["millis"].as_u64().unwrap_or(0);
+            self.fail = parameters["fail"].as_bool().unwrap_or(false);
+            Ok(())
+        }
+
+        async fn execute(&mut self, _context: &ExecutionContext) -> Result<NodeOutput> {
+            tokio::time::sleep(std::time::Duration::from_millis(self.millis)).await;
+            if self.fail {
+                return Err(anyhow::anyhow!("sleep node failed"));
+            }
+            Ok(NodeOutput {
+                data: serde_json::json!({ "slept_ms": self.millis }),
+            })
+        }
+    }
+
+    fn node(id: &str, node_type: &str, parameters: serde_json::Value) -> WorkflowNode {
+        WorkflowNode {
+            id: id.to_string(),
+            node_type: node_type.to_string(),
+            position: Position { x: 0.0, y: 0.0 },
+            parameters,
+            disabled: false,
+            retry_on_fail: false,
+            retry_count: 0,
+            timeout_seconds: None,
+            on_failure: FailurePolicy::StopWorkflow,
+        }
+    }
+
+    fn connect(source: &str, target: &str) -> Connection {
+        Connection {
+            source_node: source.to_string(),
+            source_output: "output".to_string(),
+            target_node: target.to_string(),
+            target_input: "input".to_string(),
+        }
+    }
+
+    /// start -> (left, right) -> merge
+    fn diamond(left: WorkflowNode, right: WorkflowNode) -> Workflow {
+        let mut nodes = HashMap::new();
+        for node in [
+            node("start", "start", serde_json::json!({})),
+            left,
+            right,
+            node("merge", "merge", serde_json::json!({})),
+        ] {
+            nodes.insert(node.id.clone(), node);
+        }
+
+        Workflow {
+            id: Uuid::new_v4(),
+            namespace: namespace::default_namespace(),
+            name: "diamond".to_string(),
+            description: None,
+            version: "1.0.0".to_string(),
+            nodes,
+            connections: vec![
+                connect("start", "left"),
+                connect("start", "right"),
+                connect("left", "merge"),
+                connect("right", "merge"),
+            ],
+            settings: WorkflowSettings {
+                timeout_seconds: 30,
+                error_workflow: None,
+                save_data_execution_progress: false,
+                save_data_success: false,
+                save_data_error: false,
+                save_manual_executions: false,
+                caller_policy: CallerPolicy::None,
+                max_parallel_nodes: 4,
+            },
+            metadata: WorkflowMetadata {
+                created_at: chrono::Utc::now(),
+                updated_at: chrono::Utc::now(),
+                created_by: "test".to_string(),
+                tags: vec![],
+                folder: None,
+                template: None,
+            },
+            state: WorkflowState::Active,
+        }
+    }
+
+    async fn engine(quotas: QuotaConfig) -> WorkflowEngine {
+        let engine = WorkflowEngine::new().unwrap().with_quotas(quotas);
+        {
+            let mut registry = engine.node_registry.write().await;
+            registry.insert("start".to_string(), Box::new(StartNode::new()));
+            registry.insert("merge".to_string(), Box::new(MergeNode::new()));
+            registry.insert("sleep".to_string(), Box::new(SleepNode));
+        }
+        engine
+    }
+
+    /// A diamond in `namespace` whose left branch sleeps for `millis`
+    fn sleeper(namespace: &str, millis: u64) -> Workflow {
+        let mut workflow = diamond(
+            node("left", "sleep", serde_json::json!({ "millis": millis })),
+            node("right", "sleep", serde_json::json!({})),
+        );
+        workflow.namespace = namespace.to_string();
+        workflow
+    }
+
+    async fn run(workflow: Workflow, mode: ExecutionMode) -> ExecutionResult {
+        let engine = engine(QuotaConfig::default()).await;
+        let workflow_id = engine.create_workflow(workflow).await.unwrap();
+        engine.execute_workflow(workflow_id, serde_json::json!({}), mode).await.unwrap()
+    }
+
+    fn execution<'a>(result: &'a ExecutionResult, node_id: &str) -> &'a NodeExecution {
+        result.node_executions.iter()
+            .find(|n| n.node_id == node_id)
+            .unwrap_or_else(|| panic!("no execution recorded for {}", node_id))
+    }
+
+    fn overlaps(a: &NodeExecution, b: &NodeExecution) -> bool {
+        a.start_time < b.end_time.unwrap() && b.start_time < a.end_time.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_parallel_diamond_runs_middle_nodes_concurrently() {
+        let workflow = diamond(
+            node("left", "sleep", serde_json::json!({ "millis": 200 })),
+            node("right", "sleep", serde_json::json!({ "millis": 200 })),
+        );
+        let result = run(workflow, ExecutionMode::Parallel).await;
+
+        assert!(matches!(result.status, ExecutionStatus::Success));
+        let (left, right, merge) = (
+            execution(&result, "left"),
+            execution(&result, "right"),
+            execution(&result, "merge"),
+        );
+        assert!(overlaps(left, right));
+        assert!(merge.start_time >= left.end_time.unwrap());
+        assert!(merge.start_time >= right.end_time.unwrap());
+
+        let merged = merge.output_data.as_ref().unwrap();
+        assert!(merged.get("left").is_some() && merged.get("right").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_sequential_modes_run_one_node_at_a_time() {
+        let workflow = diamond(
+            node("left", "sleep", serde_json::json!({ "millis": 50 })),
+            node("right", "sleep", serde_json::json!({ "millis": 50 })),
+        );
+        let result = run(workflow, ExecutionMode::Manual).await;
+
+        assert!(matches!(result.status, ExecutionStatus::Success));
+        assert!(!overlaps(execution(&result, "left"), execution(&result, "right")));
+    }
+
+    #[tokio::test]
+    async fn test_failure_cancels_in_flight_siblings() {
+        let workflow = diamond(
+            node("left", "sleep", serde_json::json!({ "millis": 10, "fail": true })),
+            node("right", "sleep", serde_json::json!({ "millis": 5000 })),
+        );
+        let result = run(workflow, ExecutionMode::Parallel).await;
+
+        assert!(matches!(result.status, ExecutionStatus::Error));
+        assert!(matches!(execution(&result, "left").status, ExecutionStatus::Error));
+        assert!(matches!(execution(&result, "right").status, ExecutionStatus::Canceled));
+        assert!(result.node_executions.iter().all(|n| n.node_id != "merge"));
+        assert!(result.duration_ms.unwrap() < 5000);
+    }
+
+    #[tokio::test]
+    async fn test_continue_policy_lets_dependents_run() {
+        let mut left = node("left", "sleep", serde_json::json!({ "fail": true }));
+        left.on_failure = FailurePolicy::Continue;
+        let workflow = diamond(left, node("right", "sleep", serde_json::json!({ "millis": 10 })));
+        let result = run(workflow, ExecutionMode::Parallel).await;
+
+        assert!(matches!(result.status, ExecutionStatus::Success));
+        assert!(matches!(execution(&result, "left").status, ExecutionStatus::Error));
+        assert!(matches!(execution(&result, "merge").status, ExecutionStatus::Success));
+    }
+
+    #[test]
+    fn test_dependency_graph_rejects_cycles() {
+        let mut workflow = diamond(
+            node("left", "sleep", serde_json::json!({})),
+            node("right", "sleep", serde_json::json!({})),
+        );
+        workflow.connections.push(connect("merge", "left"));
+        assert!(DependencyGraph::build(&workflow).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_executions_quota_holds_under_load() {
+        let engine = Arc::new(
+            engine(QuotaConfig {
+                default: NamespaceQuota {
+                    max_concurrent_executions: Some(2),
+                    ..NamespaceQuota::default()
+                },
+                namespaces: HashMap::new(),
+            })
+            .await,
+        );
+        let friend = engine
+            .create_workflow(sleeper("friend", 100))
+            .await
+            .unwrap();
+        let mine = engine
+            .create_workflow(sleeper("default", 100))
+            .await
+            .unwrap();
+
+        let runs: Vec<_> = (0..10)
+            .flat_map(|_| [friend, mine])
+            .map(|workflow_id| {
+                let engine = engine.clone();
+                tokio::spawn(async move {
+                    let result = engine
+                        .execute_workflow(workflow_id, serde_json::json!({}), ExecutionMode::Manual)
+                        .await;
+                    (workflow_id, result)
+                })
+            })
+            .collect();
+
+        let mut finished: HashMap<Uuid, usize> = HashMap::new();
+        for run in runs {
+            let (workflow_id, result) = run.await.unwrap();
+            match result {
+                Ok(result) => {
+                    assert!(matches!(result.status, ExecutionStatus::Success));
+                    *finished.entry(workflow_id).or_default() += 1;
+                }
+                Err(e) => {
+                    let exceeded = QuotaExceeded::find(&e).expect("refused by a quota");
+                    assert_eq!(exceeded.limit, QuotaLimit::ConcurrentExecutions);
+                    assert!(e.to_string().contains("max_concurrent_executions"));
+                }
+            }
+        }
+        // Each namespace ran up to its own cap, whatever the other did
+        assert_eq!(finished[&friend], 2);
+        assert_eq!(finished[&mine], 2);
+
+        assert_eq!(engine.quotas().usage("friend").running_executions, 0);
+        engine
+            .execute_workflow(friend, serde_json::json!({}), ExecutionMode::Manual)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stored_executions_quota_frees_up_on_delete() {
+        let engine = engine(QuotaConfig {
+            default: NamespaceQuota::default(),
+            namespaces: HashMap::from([(
+                "friend".to_string(),
+                NamespaceQuota {
+                    max_stored_executions: Some(2),
+                    ..NamespaceQuota::default()
+                },
+            )]),
+        })
+        .await;
+        let friend = engine.create_workflow(sleeper("friend", 0)).await.unwrap();
+        let execute =
+            || engine.execute_workflow(friend, serde_json::json!({}), ExecutionMode::Manual);
+
+        let first = execute().await.unwrap();
+        assert_eq!(first.namespace, "friend");
+        execute().await.unwrap();
+        let refused = execute().await.unwrap_err();
+        assert_eq!(
+            QuotaExceeded::find(&refused).unwrap().limit,
+            QuotaLimit::StoredExecutions
+        );
+
+        assert_eq!(engine.list_executions(Some("friend")).await.len(), 2);
+        assert!(engine.list_executions(Some("default")).await.is_empty());
+        engine.delete_execution(first.execution_id).await.unwrap();
+        assert!(engine.get_execution(first.execution_id).await.is_none());
+        assert!(engine.delete_execution(first.execution_id).await.is_err());
+        execute().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_updated_workflow_stays_in_its_namespace() {
+        let engine = engine(QuotaConfig::default()).await;
+        let workflow_id = engine.create_workflow(sleeper("friend", 0)).await.unwrap();
+
+        let mut moved = engine.get_workflow(workflow_id).await.unwrap().unwrap();
+        moved.namespace = "default".to_string();
+        engine.update_workflow(moved).await.unwrap();
+        assert_eq!(
+            engine
+                .get_workflow(workflow_id)
+                .await
+                .unwrap()
+                .unwrap()
+                .namespace,
+            "friend"
+        );
+
+        let invalid = sleeper("Not Valid", 0);
+        assert!(engine.create_workflow(invalid).await.is_err());
+    }
+}
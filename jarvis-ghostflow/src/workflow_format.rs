@@ -0,0 +1,579 @@
+//! Portable YAML format for workflow definitions
+//!
+//! ```yaml
+//! kind: GhostFlowWorkflow
+//! version: v1
+//! metadata:
+//!   name: Summarize logs
+//!   tags: [ops]
+//! settings: { ... }
+//! nodes:
+//!   - id: start
+//!     type: start
+//!     position: { x: 100.0, y: 100.0 }
+//!   - id: summarize
+//!     type: jarvis.llm_router
+//!     config: { default_provider: ollama }
+//!     position: { x: 300.0, y: 100.0 }
+//! edges:
+//!   - from: start
+//!     to: summarize
+//! triggers:
+//!   - node: start
+//!     type: start
+//! ```
+//!
+//! Workflow ids are not part of the document; an import always creates a new
+//! workflow. Nodes and edges are written in a stable order so exports diff cleanly.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::nodes::NodeFactory;
+use crate::templates::TemplateSource;
+use crate::workflow_engine::{
+    Connection, FailurePolicy, Position, Workflow, WorkflowMetadata, WorkflowNode,
+    WorkflowSettings, WorkflowState,
+};
+
+pub const WORKFLOW_KIND: &str = "GhostFlowWorkflow";
+pub const FORMAT_VERSION: &str = "v1";
+
+/// Node types that start an execution rather than transform data
+const TRIGGER_NODE_TYPES: &[&str] = &["start", "webhook", "schedule_trigger"];
+
+/// A workflow as written to and read from YAML
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorkflowDocument {
+    pub kind: String,
+    pub version: String,
+    pub metadata: DocumentMetadata,
+    pub settings: WorkflowSettings,
+    #[serde(default = "default_state")]
+    pub state: WorkflowState,
+    pub nodes: Vec<DocumentNode>,
+    #[serde(default)]
+    pub edges: Vec<DocumentEdge>,
+    #[serde(default)]
+    pub triggers: Vec<DocumentTrigger>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DocumentMetadata {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Version of the workflow itself, not of this format
+    #[serde(default = "default_workflow_version")]
+    pub workflow_version: String,
+    #[serde(default)]
+    pub created_by: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folder: Option<String>,
+    /// Set on workflows instantiated from a template
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<TemplateSource>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DocumentNode {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub node_type: String,
+    #[serde(default = "empty_config")]
+    pub config: serde_json::Value,
+    pub position: Position,
+    #[serde(default)]
+    pub disabled: bool,
+    #[serde(default)]
+    pub retry_on_fail: bool,
+    #[serde(default)]
+    pub retry_count: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_seconds: Option<u32>,
+    #[serde(default)]
+    pub on_failure: FailurePolicy,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DocumentEdge {
+    pub from: String,
+    #[serde(default = "default_port")]
+    pub from_output: String,
+    pub to: String,
+    #[serde(default = "default_port")]
+    pub to_input: String,
+}
+
+/// A node that starts executions; derived from the node list on export
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DocumentTrigger {
+    pub node: String,
+    #[serde(rename = "type")]
+    pub trigger_type: String,
+}
+
+fn default_state() -> WorkflowState {
+    WorkflowState::Active
+}
+
+fn default_workflow_version() -> String {
+    "1.0.0".to_string()
+}
+
+fn default_port() -> String {
+    "main".to_string()
+}
+
+fn empty_config() -> serde_json::Value {
+    serde_json::json!({})
+}
+
+impl WorkflowDocument {
+    pub fn from_workflow(workflow: &Workflow) -> Self {
+        let mut nodes: Vec<DocumentNode> = workflow
+            .nodes
+            .iter()
+            .map(|(id, node)| DocumentNode {
+                id: id.clone(),
+                node_type: node.node_type.clone(),
+                config: node.parameters.clone(),
+                position: node.position.clone(),
+                disabled: node.disabled,
+                retry_on_fail: node.retry_on_fail,
+                retry_count: node.retry_count,
+                timeout_seconds: node.timeout_seconds,
+                on_failure: node.on_failure,
+            })
+            .collect();
+        nodes.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let triggers = nodes
+            .iter()
+            .filter(|n| TRIGGER_NODE_TYPES.contains(&n.node_type.as_str()))
+            .map(|n| DocumentTrigger {
+                node: n.id.clone(),
+                trigger_type: n.node_type.clone(),
+            })
+            .collect();
+
+        // Edges keep their authored order
+        let edges = workflow
+            .connections
+            .iter()
+            .map(|c| DocumentEdge {
+                from: c.source_node.clone(),
+                from_output: c.source_output.clone(),
+                to: c.target_node.clone(),
+                to_input: c.target_input.clone(),
+            })
+            .collect();
+
+        Self {
+            kind: WORKFLOW_KIND.to_string(),
+            version: FORMAT_VERSION.to_string(),
+            metadata: DocumentMetadata {
+                name: workflow.name.clone(),
+                description: workflow.description.clone(),
+                workflow_version: workflow.version.clone(),
+                created_by: workflow.metadata.created_by.clone(),
+                created_at: Some(workflow.metadata.created_at),
+                updated_at: Some(workflow.metadata.updated_at),
+                tags: workflow.metadata.tags.clone(),
+                folder: workflow.metadata.folder.clone(),
+                template: workflow.metadata.template.clone(),
+            },
+            settings: workflow.settings.clone(),
+            state: workflow.state.clone(),
+            nodes,
+            edges,
+            triggers,
+        }
+    }
+
+    pub fn to_yaml(&self) -> Result<String> {
+        serde_yaml::to_string(self).context("Failed to serialize workflow to YAML")
+    }
+
+    /// Parse a document, checking the kind/version header before the body so a
+    /// wrong file type gets a clear error instead of a field-level one
+    pub fn from_yaml(yaml: &str) -> Result<Self> {
+        let raw: serde_yaml::Value =
+            serde_yaml::from_str(yaml).context("Workflow file is not valid YAML")?;
+
+        match raw.get("kind").and_then(|k| k.as_str()) {
+            Some(WORKFLOW_KIND) => {}
+            Some(other) => {
+                return Err(anyhow::anyhow!(
+                    "Unsupported document kind '{}', expected '{}'",
+                    other,
+                    WORKFLOW_KIND
+                ))
+            }
+            None => return Err(anyhow::anyhow!("Missing 'kind: {}' header", WORKFLOW_KIND)),
+        }
+        match raw.get("version").and_then(|v| v.as_str()) {
+            Some(FORMAT_VERSION) => {}
+            Some(other) => {
+                return Err(anyhow::anyhow!(
+                    "Unsupported workflow format version '{}', expected '{}'",
+                    other,
+                    FORMAT_VERSION
+                ))
+            }
+            None => {
+                return Err(anyhow::anyhow!(
+                    "Missing 'version: {}' header",
+                    FORMAT_VERSION
+                ))
+            }
+        }
+
+        serde_yaml::from_value(raw).context("Invalid workflow document")
+    }
+
+    /// Check node types, node configs, edges, and triggers.
+    /// `engine_node_types` are the types registered with the workflow engine;
+    /// types from `NodeFactory` are always accepted.
+    pub fn validate(&self, engine_node_types: &[String]) -> Result<()> {
+        let factory_types: HashSet<String> = NodeFactory::list_available_nodes()
+            .into_iter()
+            .map(|info| info.node_type)
+            .collect();
+
+        let mut ids = HashSet::new();
+        for node in &self.nodes {
+            if !ids.insert(node.id.as_str()) {
+                return Err(anyhow::anyhow!("Duplicate node id '{}'", node.id));
+            }
+
+            if factory_types.contains(&node.node_type) {
+                let schema = NodeFactory::create_node(&node.node_type)
+                    .map_err(|e| anyhow::anyhow!("{}", e))?
+                    .config_schema();
+                validate_against_schema(&node.config, &schema).map_err(|e| {
+                    anyhow::anyhow!("Node '{}' ({}): {}", node.id, node.node_type, e)
+                })?;
+            } else if !engine_node_types.contains(&node.node_type) {
+                return Err(anyhow::anyhow!(
+                    "Node '{}' uses unknown node type '{}'",
+                    node.id,
+                    node.node_type
+                ));
+            }
+        }
+
+        for edge in &self.edges {
+            for end in [&edge.from, &edge.to] {
+                if !ids.contains(end.as_str()) {
+                    return Err(anyhow::anyhow!(
+                        "Edge {} -> {} references unknown node '{}'",
+                        edge.from,
+                        edge.to,
+                        end
+                    ));
+                }
+            }
+        }
+
+        for trigger in &self.triggers {
+            let node = self
+                .nodes
+                .iter()
+                .find(|n| n.id == trigger.node)
+                .ok_or_else(|| {
+                    anyhow::anyhow!("Trigger references unknown node '{}'", trigger.node)
+                })?;
+            if node.node_type != trigger.trigger_type {
+                return Err(anyhow::anyhow!(
+                    "Trigger '{}' is declared as '{}' but the node is '{}'",
+                    trigger.node,
+                    trigger.trigger_type,
+                    node.node_type
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Build a workflow with a fresh id in the default namespace. Call
+    /// `validate` first.
+    pub fn into_workflow(self) -> Workflow {
+        let now = chrono::Utc::now();
+        let nodes: HashMap<String, WorkflowNode> = self
+            .nodes
+            .into_iter()
+            .map(|n| {
+                (
+                    n.id.clone(),
+                    WorkflowNode {
+                        id: n.id,
+                        node_type: n.node_type,
+                        position: n.position,
+                        parameters: n.config,
+                        disabled: n.disabled,
+                        retry_on_fail: n.retry_on_fail,
+                        retry_count: n.retry_count,
+                        timeout_seconds: n.timeout_seconds,
+                        on_failure: n.on_failure,
+                    },
+                )
+            })
+            .collect();
+
+        Workflow {
+            id: uuid::Uuid::new_v4(),
+            namespace: crate::namespace::default_namespace(),
+            name: self.metadata.name,
+            description: self.metadata.description,
+            version: self.metadata.workflow_version,
+            nodes,
+            connections: self
+                .edges
+                .into_iter()
+                .map(|e| Connection {
+                    source_node: e.from,
+                    source_output: e.from_output,
+                    target_node: e.to,
+                    target_input: e.to_input,
+                })
+                .collect(),
+            settings: self.settings,
+            metadata: WorkflowMetadata {
+                created_at: self.metadata.created_at.unwrap_or(now),
+                updated_at: self.metadata.updated_at.unwrap_or(now),
+                created_by: self.metadata.created_by,
+                tags: self.metadata.tags,
+                folder: self.metadata.folder,
+                template: self.metadata.template,
+            },
+            state: self.state,
+        }
+    }
+}
+
+/// The subset of JSON Schema that node config schemas use: object
+/// properties with `type`, `enum`, and `required`
+fn validate_against_schema(
+    value: &serde_json::Value,
+    schema: &serde_json::Value,
+) -> std::result::Result<(), String> {
+    let Some(config) = value.as_object() else {
+        return Err("config must be a mapping".to_string());
+    };
+
+    if let Some(required) = schema.get("required").and_then(|r| r.as_array()) {
+        for field in required.iter().filter_map(|f| f.as_str()) {
+            if !config.contains_key(field) {
+                return Err(format!("missing required config field '{}'", field));
+            }
+        }
+    }
+
+    let properties = schema.get("properties").and_then(|p| p.as_object());
+    for (key, value) in config {
+        let Some(property) = properties.and_then(|p| p.get(key)) else {
+            if schema.get("additionalProperties") == Some(&serde_json::Value::Bool(false)) {
+                return Err(format!("unknown config field '{}'", key));
+            }
+            continue;
+        };
+
+        if let Some(expected) = property.get("type").and_then(|t| t.as_str()) {
+            let matches = match expected {
+                "string" => value.is_string(),
+                "boolean" => value.is_boolean(),
+                "integer" => value.is_i64() || value.is_u64(),
+                "number" => value.is_number(),
+                "array" => value.is_array(),
+                "object" => value.is_object(),
+                _ => true,
+            };
+            if !matches {
+                return Err(format!(
+                    "config field '{}' must be of type {}",
+                    key, expected
+                ));
+            }
+        }
+
+        if let Some(allowed) = property.get("enum").and_then(|e| e.as_array()) {
+            if !allowed.contains(value) {
+                return Err(format!(
+                    "config field '{}' must be one of {}",
+                    key,
+                    serde_json::Value::Array(allowed.clone())
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workflow_engine::CallerPolicy;
+
+    fn sample_workflow() -> Workflow {
+        let mut nodes = HashMap::new();
+        for (id, node_type, x, parameters) in [
+            ("start", "start", 100.0, serde_json::json!({})),
+            (
+                "remember",
+                "jarvis.memory",
+                312.5,
+                serde_json::json!({ "retention_days": 7 }),
+            ),
+        ] {
+            nodes.insert(
+                id.to_string(),
+                WorkflowNode {
+                    id: id.to_string(),
+                    node_type: node_type.to_string(),
+                    position: Position { x, y: -42.25 },
+                    parameters,
+                    disabled: false,
+                    retry_on_fail: true,
+                    retry_count: 2,
+                    timeout_seconds: Some(15),
+                    on_failure: FailurePolicy::Continue,
+                },
+            );
+        }
+
+        Workflow {
+            id: uuid::Uuid::new_v4(),
+            namespace: crate::namespace::default_namespace(),
+            name: "Remember input".to_string(),
+            description: Some("Stores the trigger payload".to_string()),
+            version: "2.1.0".to_string(),
+            nodes,
+            connections: vec![Connection {
+                source_node: "start".to_string(),
+                source_output: "output".to_string(),
+                target_node: "remember".to_string(),
+                target_input: "input".to_string(),
+            }],
+            settings: WorkflowSettings {
+                timeout_seconds: 120,
+                error_workflow: None,
+                save_data_execution_progress: true,
+                save_data_success: false,
+                save_data_error: true,
+                save_manual_executions: false,
+                caller_policy: CallerPolicy::WorkflowsFromAnyOwner,
+                max_parallel_nodes: 2,
+            },
+            metadata: WorkflowMetadata {
+                created_at: chrono::Utc::now(),
+                updated_at: chrono::Utc::now(),
+                created_by: "ckelley".to_string(),
+                tags: vec!["ops".to_string()],
+                folder: Some("examples".to_string()),
+                template: None,
+            },
+            state: WorkflowState::Paused,
+        }
+    }
+
+    fn engine_types() -> Vec<String> {
+        vec!["start".to_string()]
+    }
+
+    #[test]
+    fn test_round_trip_preserves_workflow() {
+        let original = sample_workflow();
+        let yaml = WorkflowDocument::from_workflow(&original)
+            .to_yaml()
+            .unwrap();
+
+        let document = WorkflowDocument::from_yaml(&yaml).unwrap();
+        document.validate(&engine_types()).unwrap();
+        assert_eq!(
+            document.triggers,
+            vec![DocumentTrigger {
+                node: "start".to_string(),
+                trigger_type: "start".to_string(),
+            }]
+        );
+
+        let imported = document.into_workflow();
+        assert_ne!(imported.id, original.id);
+        let remember = &imported.nodes["remember"];
+        assert_eq!((remember.position.x, remember.position.y), (312.5, -42.25));
+        assert_eq!(remember.on_failure, FailurePolicy::Continue);
+        assert_eq!(imported.metadata.created_at, original.metadata.created_at);
+
+        // Everything but the id survives, so a second export is identical
+        let again = WorkflowDocument::from_workflow(&imported)
+            .to_yaml()
+            .unwrap();
+        assert_eq!(again, yaml);
+    }
+
+    #[test]
+    fn test_unknown_node_type_is_named() {
+        let yaml = WorkflowDocument::from_workflow(&sample_workflow())
+            .to_yaml()
+            .unwrap()
+            .replace("type: jarvis.memory", "type: jarvis.teleporter");
+
+        let error = WorkflowDocument::from_yaml(&yaml)
+            .unwrap()
+            .validate(&engine_types())
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("unknown node type 'jarvis.teleporter'"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_config_checked_against_schema() {
+        let yaml = WorkflowDocument::from_workflow(&sample_workflow())
+            .to_yaml()
+            .unwrap()
+            .replace("retention_days: 7", "retention_days: forever");
+
+        let error = WorkflowDocument::from_yaml(&yaml)
+            .unwrap()
+            .validate(&engine_types())
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("'retention_days' must be of type integer"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_header_is_checked_first() {
+        let error = WorkflowDocument::from_yaml("kind: Deployment\nversion: v1\n")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Unsupported document kind 'Deployment'"));
+
+        let error = WorkflowDocument::from_yaml("kind: GhostFlowWorkflow\nversion: v9\n")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("version 'v9'"));
+    }
+}
@@ -0,0 +1,83 @@
+/*
+ * Stand-in for libzeke, built into a shared library by build.rs under the
+ * zeke-mock feature so the FFI bindings can be tested without Zig.
+ */
+#define _POSIX_C_SOURCE 200809L
+
+#include <stdlib.h>
+#include <string.h>
+#include <time.h>
+
+struct engine {
+    int requests;
+};
+
+static int destroyed = 0;
+
+int zeke_init(const char *config_json, void **engine)
+{
+    if (!config_json || !engine)
+        return 2;
+    *engine = calloc(1, sizeof(struct engine));
+    return *engine ? 0 : 3;
+}
+
+int zeke_complete(void *engine, const char *request_json, char **response_json)
+{
+    if (!engine)
+        return 4;
+    ((struct engine *)engine)->requests++;
+    if (strstr(request_json, "\"language\":\"\"")) {
+        *response_json = strdup("unsupported language");
+        return 2;
+    }
+    *response_json = strdup(
+        "{\"completions\":[{\"text\":\"println!(\\\"done\\\");\",\"confidence\":0.9}],"
+        "\"model\":\"zeke-mock\"}");
+    return 0;
+}
+
+int zeke_analyze(void *engine, const char *request_json, char **response_json)
+{
+    if (!engine)
+        return 4;
+    ((struct engine *)engine)->requests++;
+    if (strstr(request_json, "SLOW")) {
+        struct timespec delay = {0, 500 * 1000 * 1000};
+        nanosleep(&delay, NULL);
+    }
+    if (strstr(request_json, "unwrap()")) {
+        *response_json = strdup(
+            "{\"issues\":[{\"line\":1,\"column\":9,\"severity\":\"warning\","
+            "\"message\":\"unwrap may panic\",\"suggestion\":\"use ? instead\"}],"
+            "\"summary\":\"1 issue\"}");
+    } else {
+        *response_json = strdup("{\"issues\":[],\"summary\":\"no issues\"}");
+    }
+    return 0;
+}
+
+int zeke_version(char **version)
+{
+    *version = strdup("zeke-mock 0.1.0");
+    return 0;
+}
+
+void zeke_free_string(char *s)
+{
+    free(s);
+}
+
+void zeke_destroy(void *engine)
+{
+    if (engine) {
+        free(engine);
+        destroyed++;
+    }
+}
+
+/* Engines destroyed so far, for checking the Rust destructor */
+int zeke_mock_destroyed(void)
+{
+    return destroyed;
+}
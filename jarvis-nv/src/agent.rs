@@ -6,8 +6,10 @@
  */
 
 use anyhow::{Context, Result};
+use jarvis_core::bus::{self, BusEvent};
+use jarvis_core::severity::Severity;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 use tokio::sync::{Mutex, RwLock};
 use tokio::time::{Duration, Instant};
@@ -15,10 +17,12 @@ use tracing::{debug, error, info, warn};
 
 use crate::ai::{InferenceRequest, InferenceResponse, OllamaManager};
 use crate::bridge::GhostBridge;
-use crate::config::AgentConfig;
+use crate::buffer::RingBuffer;
+use crate::config::{AgentConfig, BufferConfig};
 use crate::gpu::GpuManager;
 use crate::metrics::MetricsCollector;
 use crate::node::NodeManager;
+use crate::prediction::{self, CategoryReport, PredictionEvaluator};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentStatus {
@@ -39,7 +43,7 @@ pub struct Anomaly {
     pub id: String,
     pub timestamp: chrono::DateTime<chrono::Utc>,
     pub category: String, // "performance", "security", "network", "transaction"
-    pub severity: String, // "low", "medium", "high", "critical"
+    pub severity: Severity,
     pub score: f64,       // 0.0 to 1.0
     pub description: String,
     pub affected_component: String,
@@ -66,13 +70,21 @@ pub struct Optimization {
 pub struct Prediction {
     pub id: String,
     pub timestamp: chrono::DateTime<chrono::Utc>,
-    pub category: String,  // "price", "congestion", "demand", "performance"
+    pub category: String,  // "congestion", "demand", "performance"
     pub timeframe: String, // "1h", "24h", "7d", "30d"
     pub predicted_value: f64,
     pub confidence: f64,
     pub current_value: f64,
     pub trend: String, // "increasing", "decreasing", "stable", "volatile"
     pub accuracy_score: Option<f64>, // Filled in after timeframe passes
+    /// Forecaster that produced `predicted_value`
+    #[serde(default)]
+    pub model: String,
+    /// When the realized value is read back for evaluation
+    #[serde(default)]
+    pub target_time: chrono::DateTime<chrono::Utc>,
+    #[serde(default)]
+    pub realized_value: Option<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -85,6 +97,55 @@ pub struct LearningMetrics {
     pub feature_importance: HashMap<String, f64>,
     pub model_version: String,
     pub last_update: chrono::DateTime<chrono::Utc>,
+    /// Rolling accuracy and selected forecaster per prediction category
+    #[serde(default)]
+    pub prediction_accuracy: BTreeMap<String, CategoryReport>,
+    #[serde(default)]
+    pub predictions_evaluated: u64,
+    #[serde(default)]
+    pub predictions_pending: usize,
+}
+
+/// The numbers the agent learns from, pulled out of one system snapshot
+/// so the history doesn't hold every component's full status document
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FeatureSample {
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub gpu_utilization_percent: Option<f64>,
+    pub gpu_memory_utilization_percent: Option<f64>,
+    pub gpu_temperature_celsius: Option<f64>,
+    pub gpu_power_draw_watts: Option<f64>,
+    pub avg_inference_time_ms: Option<f64>,
+    pub block_height: Option<f64>,
+    pub peer_count: Option<f64>,
+    pub sync_progress: Option<f64>,
+    pub bridge_active_connections: Option<f64>,
+    pub bridge_avg_response_time_ms: Option<f64>,
+    pub bridge_failed_requests: Option<f64>,
+}
+
+impl FeatureSample {
+    /// Extract the features from a `collect_system_data` snapshot
+    pub fn from_system_data(data: &serde_json::Value) -> Self {
+        let number = |pointer: &str| data.pointer(pointer).and_then(|v| v.as_f64());
+        Self {
+            timestamp: data
+                .get("timestamp")
+                .and_then(|t| serde_json::from_value(t.clone()).ok())
+                .unwrap_or_else(chrono::Utc::now),
+            gpu_utilization_percent: number("/gpu/gpu_info/utilization_gpu"),
+            gpu_memory_utilization_percent: number("/gpu/gpu_info/utilization_memory"),
+            gpu_temperature_celsius: number("/gpu/gpu_info/temperature"),
+            gpu_power_draw_watts: number("/gpu/gpu_info/power_draw"),
+            avg_inference_time_ms: number("/gpu/stats/avg_inference_time_ms"),
+            block_height: number("/node/nodes/ghostchain/block_height"),
+            peer_count: number("/node/nodes/ghostchain/peer_count"),
+            sync_progress: number("/node/nodes/ghostchain/sync_progress"),
+            bridge_active_connections: number("/bridge/active_connections"),
+            bridge_avg_response_time_ms: number("/bridge/avg_response_time_ms"),
+            bridge_failed_requests: number("/bridge/failed_requests"),
+        }
+    }
 }
 
 pub struct NvAgent {
@@ -99,13 +160,14 @@ pub struct NvAgent {
 
     // Agent state
     agent_status: Arc<RwLock<AgentStatus>>,
-    anomalies: Arc<Mutex<VecDeque<Anomaly>>>,
-    optimizations: Arc<Mutex<VecDeque<Optimization>>>,
-    predictions: Arc<Mutex<VecDeque<Prediction>>>,
+    anomalies: Arc<Mutex<RingBuffer<Anomaly>>>,
+    optimizations: Arc<Mutex<RingBuffer<Optimization>>>,
+    predictions: Arc<Mutex<RingBuffer<Prediction>>>,
     learning_metrics: Arc<RwLock<LearningMetrics>>,
+    prediction_evaluator: Arc<Mutex<PredictionEvaluator>>,
 
     // Analysis state
-    historical_data: Arc<Mutex<VecDeque<serde_json::Value>>>,
+    historical_data: Arc<Mutex<RingBuffer<FeatureSample>>>,
     pattern_cache: Arc<RwLock<HashMap<String, Vec<f64>>>>,
     model_states: Arc<RwLock<HashMap<String, serde_json::Value>>>,
 
@@ -119,6 +181,7 @@ impl NvAgent {
     /// Create new AI agent
     pub async fn new(
         config: &AgentConfig,
+        buffers: &BufferConfig,
         gpu_manager: Arc<GpuManager>,
         metrics_collector: Arc<MetricsCollector>,
         node_manager: Arc<NodeManager>,
@@ -148,9 +211,18 @@ impl NvAgent {
                 learning_progress: 0.0,
                 last_activity: chrono::Utc::now(),
             })),
-            anomalies: Arc::new(Mutex::new(VecDeque::new())),
-            optimizations: Arc::new(Mutex::new(VecDeque::new())),
-            predictions: Arc::new(Mutex::new(VecDeque::new())),
+            anomalies: Arc::new(Mutex::new(RingBuffer::new(
+                buffers.anomalies,
+                buffers.max_buffer_bytes,
+            ))),
+            optimizations: Arc::new(Mutex::new(RingBuffer::new(
+                buffers.optimizations,
+                buffers.max_buffer_bytes,
+            ))),
+            predictions: Arc::new(Mutex::new(RingBuffer::new(
+                buffers.predictions,
+                buffers.max_buffer_bytes,
+            ))),
             learning_metrics: Arc::new(RwLock::new(LearningMetrics {
                 timestamp: chrono::Utc::now(),
                 model_accuracy: 0.0,
@@ -160,8 +232,15 @@ impl NvAgent {
                 feature_importance: HashMap::new(),
                 model_version: "1.0.0".to_string(),
                 last_update: chrono::Utc::now(),
+                prediction_accuracy: BTreeMap::new(),
+                predictions_evaluated: 0,
+                predictions_pending: 0,
             })),
-            historical_data: Arc::new(Mutex::new(VecDeque::new())),
+            prediction_evaluator: Arc::new(Mutex::new(PredictionEvaluator::new())),
+            historical_data: Arc::new(Mutex::new(RingBuffer::new(
+                buffers.historical_data,
+                buffers.max_buffer_bytes,
+            ))),
             pattern_cache: Arc::new(RwLock::new(HashMap::new())),
             model_states: Arc::new(RwLock::new(HashMap::new())),
             is_running: Arc::new(RwLock::new(false)),
@@ -250,13 +329,28 @@ impl NvAgent {
         Ok(())
     }
 
+    /// Halve (or restore) the history buffers' limits
+    pub async fn set_low_memory(&self, low_memory: bool) {
+        self.anomalies.lock().await.set_low_memory(low_memory);
+        self.optimizations.lock().await.set_low_memory(low_memory);
+        self.predictions.lock().await.set_low_memory(low_memory);
+        self.historical_data.lock().await.set_low_memory(low_memory);
+    }
+
     /// Get agent status
     pub async fn get_status(&self) -> Result<serde_json::Value> {
         let status = self.agent_status.read().await;
         let anomalies = self.anomalies.lock().await;
         let optimizations = self.optimizations.lock().await;
         let predictions = self.predictions.lock().await;
+        let historical_data = self.historical_data.lock().await;
         let learning_metrics = self.learning_metrics.read().await;
+        let buffers = [
+            ("anomalies", anomalies.stats()),
+            ("optimizations", optimizations.stats()),
+            ("predictions", predictions.stats()),
+            ("historical_data", historical_data.stats()),
+        ];
 
         Ok(serde_json::json!({
             "status": *status,
@@ -268,6 +362,8 @@ impl NvAgent {
                 "recent_optimizations": optimizations.iter().rev().take(5).collect::<Vec<_>>(),
                 "recent_predictions": predictions.iter().rev().take(5).collect::<Vec<_>>()
             },
+            "buffers": buffers.iter().copied().collect::<HashMap<_, _>>(),
+            "buffers_estimated_bytes": buffers.iter().map(|(_, b)| b.estimated_bytes).sum::<usize>(),
             "learning": *learning_metrics,
             "capabilities": {
                 "anomaly_detection": self.config.capabilities.anomaly_detection,
@@ -369,12 +465,13 @@ impl NvAgent {
         if !detected_anomalies.is_empty() {
             let mut anomalies = self.anomalies.lock().await;
             for anomaly in &detected_anomalies {
-                anomalies.push_back(anomaly.clone());
-            }
-
-            // Keep only recent anomalies
-            while anomalies.len() > 1000 {
-                anomalies.pop_front();
+                anomalies.push(anomaly.clone());
+                bus::publish(BusEvent::AnomalyDetected {
+                    category: anomaly.category.clone(),
+                    component: anomaly.affected_component.clone(),
+                    severity: anomaly.severity,
+                    description: anomaly.description.clone(),
+                });
             }
 
             // Update agent statistics
@@ -422,12 +519,7 @@ impl NvAgent {
         if !optimizations.is_empty() {
             let mut opts = self.optimizations.lock().await;
             for optimization in &optimizations {
-                opts.push_back(optimization.clone());
-            }
-
-            // Keep only recent optimizations
-            while opts.len() > 500 {
-                opts.pop_front();
+                opts.push(optimization.clone());
             }
 
             // Update agent statistics
@@ -438,39 +530,32 @@ impl NvAgent {
         Ok(optimizations)
     }
 
-    /// Generate predictions
-    pub async fn generate_predictions(
-        &self,
-        historical_data: &[serde_json::Value],
-    ) -> Result<Vec<Prediction>> {
+    /// Generate predictions from the collected metrics history, each with
+    /// the forecaster currently most accurate for its category
+    pub async fn generate_predictions(&self) -> Result<Vec<Prediction>> {
         if !self.config.capabilities.predictive_analytics {
             return Ok(Vec::new());
         }
 
         debug!("🔮 Generating predictions...");
+        let history = self.metrics_collector.history().await;
+        let now = chrono::Utc::now();
         let mut predictions = Vec::new();
-
-        // Predict network congestion
-        let congestion_prediction = self.predict_network_congestion(historical_data).await?;
-        predictions.push(congestion_prediction);
-
-        // Predict transaction volume
-        let tx_volume_prediction = self.predict_transaction_volume(historical_data).await?;
-        predictions.push(tx_volume_prediction);
-
-        // Predict gas prices
-        let gas_price_prediction = self.predict_gas_prices(historical_data).await?;
-        predictions.push(gas_price_prediction);
+        {
+            let mut evaluator = self.prediction_evaluator.lock().await;
+            for (category, pointer, timeframe) in prediction::CATEGORIES {
+                if let Some(prediction) =
+                    evaluator.predict(category, pointer, timeframe, &history, now)
+                {
+                    predictions.push(prediction);
+                }
+            }
+        }
 
         // Store predictions
         let mut preds = self.predictions.lock().await;
         for prediction in &predictions {
-            preds.push_back(prediction.clone());
-        }
-
-        // Keep only recent predictions
-        while preds.len() > 200 {
-            preds.pop_front();
+            preds.push(prediction.clone());
         }
 
         Ok(predictions)
@@ -541,16 +626,11 @@ impl NvAgent {
             }
         }
 
-        // Store data for learning
-        {
-            let mut historical = self.historical_data.lock().await;
-            historical.push_back(system_data);
-
-            // Keep only recent data
-            while historical.len() > 10000 {
-                historical.pop_front();
-            }
-        }
+        // Store features for learning
+        self.historical_data
+            .lock()
+            .await
+            .push(FeatureSample::from_system_data(&system_data));
 
         self.clear_current_task().await;
         Ok(())
@@ -602,14 +682,8 @@ impl NvAgent {
                 interval.tick().await;
 
                 // Generate predictions
-                let historical = agent.historical_data.lock().await;
-                let data: Vec<_> = historical.iter().cloned().collect();
-                drop(historical);
-
-                if data.len() > 10 {
-                    if let Err(e) = agent.generate_predictions(&data).await {
-                        error!("❌ Failed to generate predictions: {}", e);
-                    }
+                if let Err(e) = agent.generate_predictions().await {
+                    error!("❌ Failed to generate predictions: {}", e);
                 }
             }
         })
@@ -618,26 +692,64 @@ impl NvAgent {
     /// Start learning loop
     async fn start_learning_loop(self: &Arc<Self>) -> tokio::task::JoinHandle<()> {
         let is_running = Arc::clone(&self.is_running);
-        let learning_metrics = Arc::clone(&self.learning_metrics);
+        let agent = Arc::clone(self);
 
         tokio::spawn(async move {
             let mut interval = tokio::time::interval(Duration::from_secs(600)); // 10 minutes
 
             while *is_running.read().await {
                 interval.tick().await;
+                agent.evaluate_predictions().await;
+            }
+        })
+    }
 
-                // Update learning metrics
-                let mut metrics = learning_metrics.write().await;
-                metrics.model_accuracy = 0.95 + (rand::random::<f64>() - 0.5) * 0.05;
-                metrics.training_loss = 0.05 + (rand::random::<f64>() - 0.5) * 0.02;
-                metrics.last_update = chrono::Utc::now();
+    /// Score predictions whose timeframe has passed against the realized
+    /// metrics and fold the results into the learning metrics
+    async fn evaluate_predictions(&self) {
+        let history = self.metrics_collector.history().await;
+        let now = chrono::Utc::now();
+
+        let (evaluations, reports, overall, pending) = {
+            let mut evaluator = self.prediction_evaluator.lock().await;
+            let evaluations = evaluator.evaluate_due(&history, now);
+            (
+                evaluations,
+                evaluator.reports(),
+                evaluator.overall_accuracy(),
+                evaluator.pending(),
+            )
+        };
 
-                debug!(
-                    "🎓 Learning metrics updated - Accuracy: {:.3}, Loss: {:.3}",
-                    metrics.model_accuracy, metrics.training_loss
-                );
+        {
+            let mut predictions = self.predictions.lock().await;
+            for evaluation in &evaluations {
+                if let Some(prediction) = predictions
+                    .iter_mut()
+                    .find(|p| p.id == evaluation.prediction_id)
+                {
+                    prediction.accuracy_score = Some(evaluation.accuracy());
+                    prediction.realized_value = Some(evaluation.realized_value);
+                }
             }
-        })
+        }
+
+        let mut metrics = self.learning_metrics.write().await;
+        if let Some(overall) = overall {
+            metrics.model_accuracy = overall;
+            metrics.training_loss = 1.0 - overall;
+        }
+        metrics.predictions_evaluated += evaluations.len() as u64;
+        metrics.predictions_pending = pending;
+        metrics.prediction_accuracy = reports;
+        metrics.last_update = now;
+
+        debug!(
+            "🎓 Evaluated {} predictions - Accuracy: {:.3}, Pending: {}",
+            evaluations.len(),
+            metrics.model_accuracy,
+            pending
+        );
     }
 
     /// Start data collection task
@@ -1019,7 +1131,7 @@ impl NvAgent {
                     id: uuid::Uuid::new_v4().to_string(),
                     timestamp: chrono::Utc::now(),
                     category: "performance".to_string(),
-                    severity: "high".to_string(),
+                    severity: Severity::High,
                     score: 0.85,
                     description: "GPU temperature exceeds safe threshold".to_string(),
                     affected_component: "GPU".to_string(),
@@ -1074,55 +1186,4 @@ impl NvAgent {
         // Simplified network optimization
         Ok(None)
     }
-
-    async fn predict_network_congestion(
-        &self,
-        historical_data: &[serde_json::Value],
-    ) -> Result<Prediction> {
-        Ok(Prediction {
-            id: uuid::Uuid::new_v4().to_string(),
-            timestamp: chrono::Utc::now(),
-            category: "congestion".to_string(),
-            timeframe: "1h".to_string(),
-            predicted_value: 0.75,
-            confidence: 0.85,
-            current_value: 0.65,
-            trend: "increasing".to_string(),
-            accuracy_score: None,
-        })
-    }
-
-    async fn predict_transaction_volume(
-        &self,
-        historical_data: &[serde_json::Value],
-    ) -> Result<Prediction> {
-        Ok(Prediction {
-            id: uuid::Uuid::new_v4().to_string(),
-            timestamp: chrono::Utc::now(),
-            category: "demand".to_string(),
-            timeframe: "24h".to_string(),
-            predicted_value: 150000.0,
-            confidence: 0.78,
-            current_value: 125000.0,
-            trend: "increasing".to_string(),
-            accuracy_score: None,
-        })
-    }
-
-    async fn predict_gas_prices(
-        &self,
-        historical_data: &[serde_json::Value],
-    ) -> Result<Prediction> {
-        Ok(Prediction {
-            id: uuid::Uuid::new_v4().to_string(),
-            timestamp: chrono::Utc::now(),
-            category: "price".to_string(),
-            timeframe: "1h".to_string(),
-            predicted_value: 25.5,
-            confidence: 0.82,
-            current_value: 22.3,
-            trend: "increasing".to_string(),
-            accuracy_score: None,
-        })
-    }
 }
@@ -6,13 +6,20 @@
  */
 
 use anyhow::{Context, Result};
+use jarvis_core::tls::{self, ReloadableTlsAcceptor};
 use quinn::{ClientConfig, Endpoint, ServerConfig};
 use rustls::{Certificate, PrivateKey, ServerConfig as RustlsServerConfig};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::task::{Context as TaskContext, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::{Mutex, RwLock};
 use tokio::time::{Duration, Instant};
+use tokio_rustls::server::TlsStream;
+use tonic::transport::server::{Connected, TcpConnectInfo};
 use tonic::{Request, Response, Status, transport::Server};
 use tracing::{debug, error, info, warn};
 
@@ -336,14 +343,45 @@ impl GhostBridge {
         };
 
         let service = GhostBridgeServer::new(service_impl);
-
-        let server_future = Server::builder().add_service(service).serve(addr);
-
-        let handle = tokio::spawn(async move {
-            if let Err(e) = server_future.await {
-                error!("❌ gRPC server error: {}", e);
-            }
-        });
+        let router = Server::builder().add_service(service);
+
+        let handle = if self.config.tls.is_enabled() {
+            let acceptor = Arc::new(
+                ReloadableTlsAcceptor::new(self.config.tls.clone())
+                    .context("Failed to load GhostBridge TLS certificates")?,
+            );
+            acceptor.reload_on_sighup()?;
+
+            let listener = TcpListener::bind(addr)
+                .await
+                .context("Failed to bind gRPC listener")?;
+            let connections = tls::accept_tls(listener, acceptor);
+            let incoming =
+                futures_util::stream::unfold(connections, |mut connections| async move {
+                    let stream = connections.recv().await?;
+                    Some((Ok::<_, std::io::Error>(TlsConnection(stream)), connections))
+                });
+
+            info!(
+                "🔐 gRPC TLS enabled{}",
+                if self.config.tls.requires_client_cert() {
+                    " with client certificate verification"
+                } else {
+                    ""
+                }
+            );
+            tokio::spawn(async move {
+                if let Err(e) = router.serve_with_incoming(incoming).await {
+                    error!("❌ gRPC server error: {}", e);
+                }
+            })
+        } else {
+            tokio::spawn(async move {
+                if let Err(e) = router.serve(addr).await {
+                    error!("❌ gRPC server error: {}", e);
+                }
+            })
+        };
 
         // Store the handle for cleanup
         // Note: In a real implementation, we'd store this properly
@@ -603,3 +641,45 @@ impl GhostBridge {
         })
     }
 }
+
+/// TLS-terminated gRPC connection handed to tonic
+struct TlsConnection(TlsStream<TcpStream>);
+
+impl Connected for TlsConnection {
+    type ConnectInfo = TcpConnectInfo;
+
+    fn connect_info(&self) -> Self::ConnectInfo {
+        self.0.get_ref().0.connect_info()
+    }
+}
+
+impl AsyncRead for TlsConnection {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TlsConnection {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,208 @@
+/*!
+ * Bounded in-memory history buffers for JARVIS-NV
+ *
+ * Metric, health check, and analysis histories are kept in ring buffers with
+ * both an entry capacity and an approximate byte budget, where an entry's
+ * size is estimated from its serialized JSON length. In low-memory mode both
+ * limits are halved, so a node under memory pressure sheds history instead of
+ * growing.
+ */
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::io;
+use sysinfo::System;
+
+/// Sizes a buffer reports in `get_status`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct BufferStats {
+    pub len: usize,
+    pub capacity: usize,
+    pub estimated_bytes: usize,
+    pub max_bytes: usize,
+}
+
+/// A FIFO history that drops its oldest entries to stay within `capacity`
+/// entries and roughly `max_bytes`
+#[derive(Debug, Clone)]
+pub struct RingBuffer<T> {
+    entries: VecDeque<(T, usize)>,
+    capacity: usize,
+    max_bytes: usize,
+    estimated_bytes: usize,
+    low_memory: bool,
+}
+
+impl<T: Serialize> RingBuffer<T> {
+    pub fn new(capacity: usize, max_bytes: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity: capacity.max(1),
+            max_bytes,
+            estimated_bytes: 0,
+            low_memory: false,
+        }
+    }
+
+    /// Append `entry`, evicting the oldest entries that no longer fit. The
+    /// newest entry is always kept, even if it alone exceeds the byte budget.
+    pub fn push(&mut self, entry: T) {
+        let size = estimate_size(&entry);
+        self.estimated_bytes += size;
+        self.entries.push_back((entry, size));
+        self.evict();
+    }
+
+    /// Halve (or restore) the capacity and byte budget, evicting right away
+    /// when they shrink
+    pub fn set_low_memory(&mut self, low_memory: bool) {
+        self.low_memory = low_memory;
+        self.evict();
+    }
+
+    /// Entry capacity currently in force
+    pub fn capacity(&self) -> usize {
+        if self.low_memory {
+            (self.capacity / 2).max(1)
+        } else {
+            self.capacity
+        }
+    }
+
+    /// Byte budget currently in force
+    pub fn max_bytes(&self) -> usize {
+        if self.low_memory {
+            self.max_bytes / 2
+        } else {
+            self.max_bytes
+        }
+    }
+
+    /// Serialized size of the entries as they were pushed; changes made
+    /// through `iter_mut` aren't reflected
+    pub fn estimated_bytes(&self) -> usize {
+        self.estimated_bytes
+    }
+
+    pub fn stats(&self) -> BufferStats {
+        BufferStats {
+            len: self.len(),
+            capacity: self.capacity(),
+            estimated_bytes: self.estimated_bytes,
+            max_bytes: self.max_bytes(),
+        }
+    }
+
+    fn evict(&mut self) {
+        let (capacity, max_bytes) = (self.capacity(), self.max_bytes());
+        while self.entries.len() > capacity
+            || (self.estimated_bytes > max_bytes && self.entries.len() > 1)
+        {
+            let Some((_, size)) = self.entries.pop_front() else {
+                break;
+            };
+            self.estimated_bytes -= size;
+        }
+    }
+}
+
+impl<T> RingBuffer<T> {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Oldest to newest
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &T> + ExactSizeIterator {
+        self.entries.iter().map(|(entry, _)| entry)
+    }
+
+    pub fn iter_mut(&mut self) -> impl DoubleEndedIterator<Item = &mut T> + ExactSizeIterator {
+        self.entries.iter_mut().map(|(entry, _)| entry)
+    }
+}
+
+/// Length of `value` serialized as JSON, without building the string
+fn estimate_size<T: Serialize>(value: &T) -> usize {
+    struct Counter(usize);
+
+    impl io::Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0 += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut counter = Counter(0);
+    match serde_json::to_writer(&mut counter, value) {
+        Ok(()) => counter.0 + std::mem::size_of::<T>(),
+        Err(_) => std::mem::size_of::<T>(),
+    }
+}
+
+/// Whether available system memory is below `threshold_percent` of the total
+pub fn under_memory_pressure(system: &mut System, threshold_percent: f64) -> bool {
+    system.refresh_memory();
+    let total = system.total_memory();
+    total > 0 && (system.available_memory() as f64 / total as f64) * 100.0 < threshold_percent
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Sample {
+        timestamp: i64,
+        label: String,
+        values: Vec<f64>,
+    }
+
+    #[test]
+    fn test_memory_stays_under_cap() {
+        let max_bytes = 256 * 1024;
+        let mut buffer = RingBuffer::new(10_000, max_bytes);
+        for i in 0..100_000 {
+            buffer.push(Sample {
+                timestamp: i,
+                label: format!("sample-{}", i),
+                values: vec![i as f64; 8],
+            });
+            assert!(buffer.estimated_bytes() <= max_bytes);
+        }
+        assert!(buffer.len() < 10_000);
+        assert_eq!(buffer.iter().last().unwrap().timestamp, 99_999);
+
+        let recounted: usize = buffer.iter().map(estimate_size).sum();
+        assert_eq!(recounted, buffer.estimated_bytes());
+
+        let full = buffer.len();
+        buffer.set_low_memory(true);
+        assert!(buffer.len() <= full / 2 + 1);
+        assert!(buffer.estimated_bytes() <= max_bytes / 2);
+        assert_eq!(buffer.stats().max_bytes, max_bytes / 2);
+    }
+
+    #[test]
+    fn test_capacity_evicts_oldest() {
+        let mut buffer = RingBuffer::new(3, usize::MAX);
+        for i in 0..5 {
+            buffer.push(i);
+        }
+        assert_eq!(buffer.iter().copied().collect::<Vec<_>>(), vec![2, 3, 4]);
+
+        buffer.set_low_memory(true);
+        assert_eq!(buffer.iter().copied().collect::<Vec<_>>(), vec![4]);
+        buffer.set_low_memory(false);
+        buffer.push(5);
+        assert_eq!(buffer.stats().capacity, 3);
+        assert_eq!(buffer.len(), 2);
+    }
+}
@@ -6,6 +6,8 @@
  */
 
 use anyhow::{Context, Result};
+use jarvis_core::bus::BusConfig;
+use jarvis_core::tls::TlsConfig;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 use tokio::fs;
@@ -20,6 +22,11 @@ pub struct JarvisNvConfig {
     pub agent: AgentConfig,
     pub metrics: MetricsConfig,
     pub security: SecurityConfig,
+    #[serde(default)]
+    pub buffers: BufferConfig,
+    /// Event bus shared with jarvisd
+    #[serde(default)]
+    pub bus: BusConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -100,6 +107,38 @@ pub struct NodeMonitoringConfig {
     pub block_monitoring: bool,
     pub peer_monitoring: bool,
     pub mempool_monitoring: bool,
+    /// How the polling intervals follow chain activity
+    #[serde(default)]
+    pub polling: PollingConfig,
+}
+
+/// Bounds on the adaptive polling intervals. The configured intervals are
+/// where polling starts; busy chains tighten them toward `floor_seconds` and
+/// quiet ones relax them toward `ceiling_seconds`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PollingConfig {
+    /// Keep the configured intervals fixed when false
+    pub adaptive: bool,
+    pub floor_seconds: u64,
+    pub ceiling_seconds: u64,
+    /// Consecutive busy (or quiet) readings before an interval changes
+    pub hysteresis_samples: u32,
+    /// Pending transactions growing by this factor between readings count as
+    /// a spike
+    pub mempool_spike_ratio: f64,
+}
+
+impl Default for PollingConfig {
+    fn default() -> Self {
+        Self {
+            adaptive: true,
+            floor_seconds: 2,
+            ceiling_seconds: 300,
+            hysteresis_samples: 3,
+            mempool_spike_ratio: 2.0,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -152,6 +191,8 @@ pub struct BridgeConfig {
     pub authentication: AuthConfig,
     pub rate_limiting: RateLimitConfig,
     pub load_balancing: LoadBalanceConfig,
+    #[serde(default)]
+    pub tls: TlsConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -251,6 +292,40 @@ pub struct MetricsExportConfig {
     pub flush_interval_seconds: u64,
 }
 
+/// Limits on the in-memory histories the node manager and agent keep
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BufferConfig {
+    pub node_metrics: usize,
+    pub health_checks: usize,
+    pub block_times: usize,
+    pub anomalies: usize,
+    pub optimizations: usize,
+    pub predictions: usize,
+    pub historical_data: usize,
+    /// Approximate serialized size each buffer may reach
+    pub max_buffer_bytes: usize,
+    /// Halve every buffer's limits while available memory is below this
+    /// percentage of the total
+    pub low_memory_threshold_percent: f64,
+}
+
+impl Default for BufferConfig {
+    fn default() -> Self {
+        Self {
+            node_metrics: 1440, // 24 hours at 1-minute intervals
+            health_checks: 50,
+            block_times: 100,
+            anomalies: 1000,
+            optimizations: 500,
+            predictions: 200,
+            historical_data: 10000,
+            max_buffer_bytes: 16 * 1024 * 1024,
+            low_memory_threshold_percent: 10.0,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SecurityConfig {
     pub enabled: bool,
@@ -350,6 +425,7 @@ impl Default for JarvisNvConfig {
                     block_monitoring: true,
                     peer_monitoring: true,
                     mempool_monitoring: true,
+                    polling: PollingConfig::default(),
                 },
                 integration: NodeIntegrationConfig {
                     auto_restart_node: false,
@@ -406,6 +482,7 @@ impl Default for JarvisNvConfig {
                     health_check_interval_seconds: 30,
                     max_retries: 3,
                 },
+                tls: TlsConfig::default(),
             },
             agent: AgentConfig {
                 enabled: true,
@@ -480,6 +557,8 @@ impl Default for JarvisNvConfig {
                     },
                 },
             },
+            buffers: BufferConfig::default(),
+            bus: BusConfig::default(),
         }
     }
 }
@@ -544,6 +623,36 @@ impl JarvisNvConfig {
             anyhow::bail!("Invalid port number: {}", self.web5.port);
         }
 
+        // Validate TLS configuration
+        self.bridge
+            .tls
+            .validate()
+            .context("Invalid bridge TLS configuration")?;
+
+        // Validate buffer limits
+        if !(0.0..=100.0).contains(&self.buffers.low_memory_threshold_percent) {
+            anyhow::bail!(
+                "Invalid low memory threshold: {}%",
+                self.buffers.low_memory_threshold_percent
+            );
+        }
+
+        // Validate polling bounds
+        let polling = &self.node.monitoring.polling;
+        if polling.floor_seconds == 0 || polling.floor_seconds > polling.ceiling_seconds {
+            anyhow::bail!(
+                "Invalid polling bounds: floor {}s, ceiling {}s",
+                polling.floor_seconds,
+                polling.ceiling_seconds
+            );
+        }
+        if polling.mempool_spike_ratio <= 1.0 {
+            anyhow::bail!(
+                "Invalid mempool spike ratio: {}",
+                polling.mempool_spike_ratio
+            );
+        }
+
         // Validate agent thresholds
         if self.agent.thresholds.anomaly_score_threshold < 0.0
             || self.agent.thresholds.anomaly_score_threshold > 1.0
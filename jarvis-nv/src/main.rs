@@ -22,12 +22,15 @@ use tracing::{debug, error, info, warn};
 mod agent;
 mod ai;
 mod bridge;
+mod buffer;
 mod config;
 mod gpu;
 mod metrics;
 mod node;
 mod nvcore;
 mod orchestrator;
+mod polling;
+mod prediction;
 mod web5;
 
 use agent::NvAgent;
@@ -72,7 +75,7 @@ impl JarvisNv {
 
         // Initialize node manager (GhostChain/ZVM integration)
         let node_manager = Arc::new(
-            NodeManager::new(&config.node, &config.web5)
+            NodeManager::new(&config.node, &config.web5, &config.buffers)
                 .await
                 .context("Failed to initialize node manager")?,
         );
@@ -88,6 +91,7 @@ impl JarvisNv {
         let agent = Arc::new(
             NvAgent::new(
                 &config.agent,
+                &config.buffers,
                 gpu_manager.clone(),
                 metrics_collector.clone(),
                 node_manager.clone(),
@@ -141,6 +145,9 @@ impl JarvisNv {
             .await
             .context("Failed to start NV Agent")?;
 
+        self.start_memory_monitor();
+        self.start_bus();
+
         info!("✅ JARVIS-NV services started successfully");
 
         // Wait for shutdown signal
@@ -150,6 +157,63 @@ impl JarvisNv {
         self.shutdown().await
     }
 
+    /// Switch the history buffers into low-memory mode while available
+    /// system memory is below the configured threshold, and back after
+    fn start_memory_monitor(&self) -> tokio::task::JoinHandle<()> {
+        let threshold = self.config.buffers.low_memory_threshold_percent;
+        let node_manager = Arc::clone(&self.node_manager);
+        let agent = Arc::clone(&self.agent);
+
+        tokio::spawn(async move {
+            let mut system = sysinfo::System::new();
+            let mut low_memory = false;
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(30));
+
+            loop {
+                interval.tick().await;
+
+                let pressure = buffer::under_memory_pressure(&mut system, threshold);
+                if pressure == low_memory {
+                    continue;
+                }
+                low_memory = pressure;
+                if low_memory {
+                    warn!(
+                        "⚠️ Available memory below {}%, halving history buffers",
+                        threshold
+                    );
+                } else {
+                    info!("Memory pressure cleared, restoring history buffers");
+                }
+                node_manager.set_low_memory(low_memory).await;
+                agent.set_low_memory(low_memory).await;
+            }
+        })
+    }
+
+    /// Join jarvisd's event bus: anomalies go out, and what the host's other
+    /// agents report (package upgrades, maintenance) shows up in the log
+    /// next to them. Connecting is retried in the background.
+    fn start_bus(&self) {
+        if !self.config.bus.enabled {
+            return;
+        }
+        let client = jarvis_core::bus::BusClient::connect(&self.config.bus, "jarvis-nv");
+        let mut events = client.subscribe();
+        jarvis_core::bus::install(client);
+
+        tokio::spawn(async move {
+            use tokio::sync::broadcast::error::RecvError;
+            loop {
+                match events.recv().await {
+                    Ok(message) => info!("📨 {}: {}", message.source, message.event.summary()),
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
     /// Wait for shutdown signal
     async fn wait_for_shutdown(&self) {
         tokio::select! {
@@ -207,6 +207,11 @@ impl MetricsCollector {
         Ok(())
     }
 
+    /// Collected metrics, oldest first
+    pub async fn history(&self) -> Vec<serde_json::Value> {
+        self.metrics_history.lock().await.clone()
+    }
+
     /// Get current metrics status
     pub async fn get_status(&self) -> Result<serde_json::Value> {
         let uptime = self.start_time.elapsed();
@@ -326,10 +331,13 @@ impl MetricsCollector {
         }
 
         // Update node metrics
-        if self.config.node_metrics {
+        let node_metrics = if self.config.node_metrics {
             let node_metrics = self.get_node_metrics().await?;
             self.update_node_prometheus_metrics(&node_metrics).await?;
-        }
+            Some(node_metrics)
+        } else {
+            None
+        };
 
         // Update network metrics
         if self.config.network_metrics {
@@ -342,6 +350,7 @@ impl MetricsCollector {
         let combined_metrics = serde_json::json!({
             "timestamp": chrono::Utc::now(),
             "system": system_metrics,
+            "node": node_metrics,
             "uptime": self.start_time.elapsed().as_secs()
         });
 
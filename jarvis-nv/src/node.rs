@@ -6,6 +6,7 @@
  */
 
 use anyhow::{Context, Result};
+use jarvis_core::net::CheckedSend;
 #[cfg(feature = "node-integration")]
 use ethers::providers::{Http, Middleware, Provider, StreamExt, Ws};
 #[cfg(feature = "node-integration")]
@@ -18,7 +19,14 @@ use tokio::time::{Duration, Instant};
 use tracing::{debug, error, info, warn};
 use url::Url;
 
-use crate::config::{NodeConfig, Web5Config};
+use crate::buffer::RingBuffer;
+use crate::config::{BufferConfig, NodeConfig, Web5Config};
+use crate::polling::AdaptivePolling;
+
+/// Starting interval of the HTTP block poll
+const BLOCK_POLL_INTERVAL: Duration = Duration::from_secs(10);
+/// Starting interval of the metrics collection
+const METRICS_INTERVAL: Duration = Duration::from_secs(60);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NodeStatus {
@@ -87,13 +95,14 @@ pub struct NodeManager {
 
     // State tracking
     node_status: Arc<RwLock<HashMap<String, NodeStatus>>>,
-    node_metrics: Arc<Mutex<Vec<NodeMetrics>>>,
+    node_metrics: Arc<Mutex<RingBuffer<NodeMetrics>>>,
     zvm_status: Arc<RwLock<Option<ZvmStatus>>>,
-    health_checks: Arc<Mutex<Vec<NodeHealthCheck>>>,
+    health_checks: Arc<Mutex<RingBuffer<NodeHealthCheck>>>,
 
     // Performance tracking
-    block_times: Arc<Mutex<Vec<Duration>>>,
-    tx_throughput: Arc<Mutex<Vec<f64>>>,
+    block_times: Arc<Mutex<RingBuffer<Duration>>>,
+    tx_throughput: Arc<Mutex<RingBuffer<f64>>>,
+    polling: Arc<Mutex<AdaptivePolling>>,
 
     // Runtime state
     is_running: Arc<RwLock<bool>>,
@@ -103,7 +112,11 @@ pub struct NodeManager {
 
 impl NodeManager {
     /// Create new node manager
-    pub async fn new(config: &NodeConfig, web5_config: &Web5Config) -> Result<Self> {
+    pub async fn new(
+        config: &NodeConfig,
+        web5_config: &Web5Config,
+        buffers: &BufferConfig,
+    ) -> Result<Self> {
         info!("🔗 Initializing Node Manager");
 
         let mut manager = Self {
@@ -112,11 +125,29 @@ impl NodeManager {
             ghostchain_provider: None,
             ghostchain_ws_provider: None,
             node_status: Arc::new(RwLock::new(HashMap::new())),
-            node_metrics: Arc::new(Mutex::new(Vec::new())),
+            node_metrics: Arc::new(Mutex::new(RingBuffer::new(
+                buffers.node_metrics,
+                buffers.max_buffer_bytes,
+            ))),
             zvm_status: Arc::new(RwLock::new(None)),
-            health_checks: Arc::new(Mutex::new(Vec::new())),
-            block_times: Arc::new(Mutex::new(Vec::new())),
-            tx_throughput: Arc::new(Mutex::new(Vec::new())),
+            health_checks: Arc::new(Mutex::new(RingBuffer::new(
+                buffers.health_checks,
+                buffers.max_buffer_bytes,
+            ))),
+            block_times: Arc::new(Mutex::new(RingBuffer::new(
+                buffers.block_times,
+                buffers.max_buffer_bytes,
+            ))),
+            tx_throughput: Arc::new(Mutex::new(RingBuffer::new(
+                buffers.block_times,
+                buffers.max_buffer_bytes,
+            ))),
+            polling: Arc::new(Mutex::new(AdaptivePolling::new(
+                &config.monitoring.polling,
+                Duration::from_secs(config.monitoring.check_interval_seconds),
+                BLOCK_POLL_INTERVAL,
+                METRICS_INTERVAL,
+            ))),
             is_running: Arc::new(RwLock::new(false)),
             last_block_hash: Arc::new(RwLock::new(None)),
             start_time: Instant::now(),
@@ -171,12 +202,27 @@ impl NodeManager {
         Ok(())
     }
 
+    /// Halve (or restore) the history buffers' limits
+    pub async fn set_low_memory(&self, low_memory: bool) {
+        self.node_metrics.lock().await.set_low_memory(low_memory);
+        self.health_checks.lock().await.set_low_memory(low_memory);
+        self.block_times.lock().await.set_low_memory(low_memory);
+        self.tx_throughput.lock().await.set_low_memory(low_memory);
+    }
+
     /// Get current node status
     pub async fn get_status(&self) -> Result<serde_json::Value> {
         let node_status = self.node_status.read().await;
         let zvm_status = self.zvm_status.read().await;
         let is_running = *self.is_running.read().await;
         let uptime = self.start_time.elapsed();
+        let buffers = [
+            ("node_metrics", self.node_metrics.lock().await.stats()),
+            ("health_checks", self.health_checks.lock().await.stats()),
+            ("block_times", self.block_times.lock().await.stats()),
+            ("tx_throughput", self.tx_throughput.lock().await.stats()),
+        ];
+        let polling = self.polling.lock().await.status();
 
         Ok(serde_json::json!({
             "running": is_running,
@@ -188,8 +234,11 @@ impl NodeManager {
                 "check_interval_seconds": self.config.monitoring.check_interval_seconds,
                 "performance_metrics": self.config.monitoring.performance_metrics,
                 "transaction_monitoring": self.config.monitoring.transaction_monitoring,
-                "block_monitoring": self.config.monitoring.block_monitoring
-            }
+                "block_monitoring": self.config.monitoring.block_monitoring,
+                "polling": polling
+            },
+            "buffers": buffers.iter().copied().collect::<HashMap<_, _>>(),
+            "buffers_estimated_bytes": buffers.iter().map(|(_, b)| b.estimated_bytes).sum::<usize>()
         }))
     }
 
@@ -309,16 +358,13 @@ impl NodeManager {
         let node_status = Arc::clone(&self.node_status);
         let last_block_hash = Arc::clone(&self.last_block_hash);
         let block_times = Arc::clone(&self.block_times);
+        let polling = Arc::clone(&self.polling);
 
         tokio::spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_secs(
-                config.monitoring.check_interval_seconds,
-            ));
-
             while *is_running.read().await {
-                interval.tick().await;
-
-                if config.monitoring.enabled {
+                // A WebSocket subscription already delivers every block
+                let websocket = polling.lock().await.websocket();
+                if config.monitoring.enabled && !websocket {
                     if let Some(provider) = &provider {
                         if let Err(e) = Self::update_node_status(
                             provider,
@@ -326,6 +372,7 @@ impl NodeManager {
                             &node_status,
                             &last_block_hash,
                             &block_times,
+                            &polling,
                         )
                         .await
                         {
@@ -333,6 +380,13 @@ impl NodeManager {
                         }
                     }
                 }
+
+                let wait = {
+                    let mut polling = polling.lock().await;
+                    polling.observe(Instant::now());
+                    polling.status_interval()
+                };
+                tokio::time::sleep(wait).await;
             }
         })
     }
@@ -343,7 +397,8 @@ impl NodeManager {
         config: &NodeConfig,
         node_status: &Arc<RwLock<HashMap<String, NodeStatus>>>,
         last_block_hash: &Arc<RwLock<Option<H256>>>,
-        block_times: &Arc<Mutex<Vec<Duration>>>,
+        block_times: &Arc<Mutex<RingBuffer<Duration>>>,
+        polling: &Arc<Mutex<AdaptivePolling>>,
     ) -> Result<()> {
         debug!("🔍 Updating node status...");
 
@@ -363,14 +418,14 @@ impl NodeManager {
                     let timestamp = block.timestamp;
                     let block_time = Duration::from_secs(timestamp.as_u64());
                     times.push(block_time);
-
-                    // Keep only last 100 block times
-                    if times.len() > 100 {
-                        times.drain(0..50);
-                    }
                 }
             }
             *last_hash = block.hash;
+            polling.lock().await.record_block(
+                Instant::now(),
+                block_number.as_u64(),
+                Some(block.transactions.len()),
+            );
 
             // Update status
             let status = NodeStatus {
@@ -415,11 +470,6 @@ impl NodeManager {
 
                 let mut checks = health_checks.lock().await;
                 checks.push(health_check);
-
-                // Keep only last 50 health checks
-                if checks.len() > 50 {
-                    checks.drain(0..25);
-                }
             }
         })
     }
@@ -522,14 +572,11 @@ impl NodeManager {
         let node_status = Arc::clone(&self.node_status);
         let block_times = Arc::clone(&self.block_times);
         let tx_throughput = Arc::clone(&self.tx_throughput);
+        let polling = Arc::clone(&self.polling);
         let config = self.config.clone();
 
         tokio::spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_secs(60)); // Collect metrics every minute
-
             while *is_running.read().await {
-                interval.tick().await;
-
                 if config.monitoring.performance_metrics {
                     let metrics =
                         Self::collect_node_metrics(&node_status, &block_times, &tx_throughput)
@@ -537,12 +584,10 @@ impl NodeManager {
 
                     let mut metrics_vec = node_metrics.lock().await;
                     metrics_vec.push(metrics);
-
-                    // Keep only last 1440 metrics (24 hours at 1-minute intervals)
-                    if metrics_vec.len() > 1440 {
-                        metrics_vec.drain(0..720);
-                    }
                 }
+
+                let wait = polling.lock().await.metrics_interval();
+                tokio::time::sleep(wait).await;
             }
         })
     }
@@ -550,8 +595,8 @@ impl NodeManager {
     /// Collect node metrics
     async fn collect_node_metrics(
         node_status: &Arc<RwLock<HashMap<String, NodeStatus>>>,
-        block_times: &Arc<Mutex<Vec<Duration>>>,
-        tx_throughput: &Arc<Mutex<Vec<f64>>>,
+        block_times: &Arc<Mutex<RingBuffer<Duration>>>,
+        tx_throughput: &Arc<Mutex<RingBuffer<f64>>>,
     ) -> NodeMetrics {
         let status_map = node_status.read().await;
         let times = block_times.lock().await;
@@ -608,7 +653,7 @@ impl NodeManager {
         let start_time = Instant::now();
 
         // Test ZVM endpoint connectivity
-        let endpoint_reachable = match reqwest::get(&config.endpoint).await {
+        let endpoint_reachable = match jarvis_core::net::client().get(&config.endpoint).checked_send().await {
             Ok(_) => true,
             Err(_) => false,
         };
@@ -779,57 +824,63 @@ impl NodeManager {
         let node_status = self.node_status.clone();
         let node_metrics = self.node_metrics.clone();
         let is_running = self.is_running.clone();
+        let polling = self.polling.clone();
+        let mempool_monitoring = self.config.monitoring.mempool_monitoring;
 
         tokio::spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_secs(10));
-
             while *is_running.read().await {
-                interval.tick().await;
-
-                // Get latest block
-                if let Ok(Some(block)) =
-                    provider.get_block(ethers::types::BlockNumber::Latest).await
-                {
-                    debug!(
-                        "📦 Latest GhostChain block: #{}",
-                        block.number.unwrap_or_default()
-                    );
+                // Blocks come over HTTP only without a WebSocket subscription;
+                // peers, gas price, and pending transactions always do
+                let websocket = polling.lock().await.websocket();
+                if !websocket {
+                    if let Ok(Some(block)) =
+                        provider.get_block(ethers::types::BlockNumber::Latest).await
+                    {
+                        debug!(
+                            "📦 Latest GhostChain block: #{}",
+                            block.number.unwrap_or_default()
+                        );
+                        polling.lock().await.record_block(
+                            Instant::now(),
+                            block.number.unwrap_or_default().as_u64(),
+                            Some(block.transactions.len()),
+                        );
 
-                    // Update node status
-                    let mut status_map = node_status.write().await;
-                    if let Some(status) = status_map.get_mut("ghostchain") {
-                        status.block_height = block.number.unwrap_or_default().as_u64();
-                        status.last_block_time =
-                            chrono::DateTime::from_timestamp(block.timestamp.as_u64() as i64, 0)
-                                .unwrap_or_else(chrono::Utc::now);
-                        status.status = "synced".to_string();
-
-                        if let gas_limit = block.gas_limit {
-                            // Calculate gas usage percentage if we have gas used
-                            if let gas_used = block.gas_used {
-                                let gas_used_percentage =
-                                    (gas_used.as_u64() as f64 / gas_limit.as_u64() as f64) * 100.0;
-
-                                // Create metrics entry
-                                let metric = NodeMetrics {
-                                    timestamp: chrono::Utc::now(),
-                                    blocks_processed_per_minute: 6.0, // Assuming 10s block time
-                                    transactions_per_second: block.transactions.len() as f64 / 10.0,
-                                    mempool_size: 0, // Would need separate call to get mempool
-                                    pending_transactions: 0,
-                                    avg_block_time_seconds: 10.0,
-                                    network_hashrate: None,
-                                    difficulty: Some(block.difficulty.as_u64()),
-                                    gas_limit: Some(gas_limit.as_u64()),
-                                    gas_used_percentage: Some(gas_used_percentage),
-                                };
-
-                                let mut metrics_vec = node_metrics.lock().await;
-                                metrics_vec.push(metric);
-
-                                // Keep only last 1000 metrics
-                                if metrics_vec.len() > 1000 {
-                                    metrics_vec.drain(0..100);
+                        // Update node status
+                        let mut status_map = node_status.write().await;
+                        if let Some(status) = status_map.get_mut("ghostchain") {
+                            status.block_height = block.number.unwrap_or_default().as_u64();
+                            status.last_block_time = chrono::DateTime::from_timestamp(
+                                block.timestamp.as_u64() as i64,
+                                0,
+                            )
+                            .unwrap_or_else(chrono::Utc::now);
+                            status.status = "synced".to_string();
+
+                            if let gas_limit = block.gas_limit {
+                                // Calculate gas usage percentage if we have gas used
+                                if let gas_used = block.gas_used {
+                                    let gas_used_percentage = (gas_used.as_u64() as f64
+                                        / gas_limit.as_u64() as f64)
+                                        * 100.0;
+
+                                    // Create metrics entry
+                                    let metric = NodeMetrics {
+                                        timestamp: chrono::Utc::now(),
+                                        blocks_processed_per_minute: 6.0, // Assuming 10s block time
+                                        transactions_per_second: block.transactions.len() as f64
+                                            / 10.0,
+                                        mempool_size: 0, // Would need separate call to get mempool
+                                        pending_transactions: 0,
+                                        avg_block_time_seconds: 10.0,
+                                        network_hashrate: None,
+                                        difficulty: Some(block.difficulty.as_u64()),
+                                        gas_limit: Some(gas_limit.as_u64()),
+                                        gas_used_percentage: Some(gas_used_percentage),
+                                    };
+
+                                    let mut metrics_vec = node_metrics.lock().await;
+                                    metrics_vec.push(metric);
                                 }
                             }
                         }
@@ -855,6 +906,28 @@ impl NodeManager {
                         status.gas_price = Some(gas_price.as_u64());
                     }
                 }
+
+                // Pending transactions, so a mempool spike tightens polling
+                if mempool_monitoring {
+                    if let Ok(pending) = provider
+                        .provider()
+                        .request::<_, ethers::types::U64>(
+                            "eth_getBlockTransactionCountByNumber",
+                            ["pending"],
+                        )
+                        .await
+                    {
+                        polling.lock().await.record_pending(pending.as_u32());
+                    }
+                }
+
+                let wait = {
+                    let polling = polling.lock().await;
+                    polling
+                        .block_poll_interval()
+                        .unwrap_or_else(|| polling.status_interval())
+                };
+                tokio::time::sleep(wait).await;
             }
         });
     }
@@ -864,32 +937,42 @@ impl NodeManager {
     async fn start_ghostchain_ws_monitoring(&self, provider: Arc<Provider<Ws>>) {
         let node_status = self.node_status.clone();
         let is_running = self.is_running.clone();
+        let polling = self.polling.clone();
         let provider_for_blocks = Arc::clone(&provider);
 
         tokio::spawn(async move {
             info!("👂 Starting GhostChain WebSocket event monitoring...");
 
-            // Subscribe to new blocks
+            // Subscribe to new blocks; HTTP polling stops fetching them
+            // while the subscription lasts
             if let Ok(mut stream) = provider_for_blocks.subscribe_blocks().await {
+                polling.lock().await.set_websocket(true);
                 while *is_running.read().await {
-                    if let Some(block) = stream.next().await {
-                        info!(
-                            "🆕 New GhostChain block received: #{}",
-                            block.number.unwrap_or_default()
-                        );
+                    let Some(block) = stream.next().await else {
+                        warn!("⚠️ GhostChain block subscription ended, polling blocks over HTTP");
+                        break;
+                    };
+                    info!(
+                        "🆕 New GhostChain block received: #{}",
+                        block.number.unwrap_or_default()
+                    );
+                    // Headers carry no transactions
+                    polling.lock().await.record_block(
+                        Instant::now(),
+                        block.number.unwrap_or_default().as_u64(),
+                        None,
+                    );
 
-                        // Update status with new block
-                        let mut status_map = node_status.write().await;
-                        if let Some(status) = status_map.get_mut("ghostchain") {
-                            status.block_height = block.number.unwrap_or_default().as_u64();
-                            status.last_block_time = chrono::DateTime::from_timestamp(
-                                block.timestamp.as_u64() as i64,
-                                0,
-                            )
-                            .unwrap_or_else(chrono::Utc::now);
-                        }
+                    // Update status with new block
+                    let mut status_map = node_status.write().await;
+                    if let Some(status) = status_map.get_mut("ghostchain") {
+                        status.block_height = block.number.unwrap_or_default().as_u64();
+                        status.last_block_time =
+                            chrono::DateTime::from_timestamp(block.timestamp.as_u64() as i64, 0)
+                                .unwrap_or_else(chrono::Utc::now);
                     }
                 }
+                polling.lock().await.set_websocket(false);
             }
         });
 
@@ -931,13 +1014,13 @@ impl NodeManager {
         }
 
         // Test ZVM connection via HTTP health check
-        let client = reqwest::Client::new();
+        let client = jarvis_core::net::client();
         let health_url = format!("{}/health", self.config.zvm.endpoint);
 
         match client
             .get(&health_url)
             .timeout(Duration::from_secs(5))
-            .send()
+            .checked_send()
             .await
         {
             Ok(response) => {
@@ -1006,13 +1089,13 @@ impl NodeManager {
             self.config.zvm.zns_resolver
         );
 
-        let client = reqwest::Client::new();
+        let client = jarvis_core::net::client();
         let test_url = format!("{}/resolve/test.ghost", self.config.zvm.zns_resolver);
 
         match client
             .get(&test_url)
             .timeout(Duration::from_secs(5))
-            .send()
+            .checked_send()
             .await
         {
             Ok(response) => {
@@ -1044,13 +1127,13 @@ impl NodeManager {
             self.config.zvm.web5_gateway
         );
 
-        let client = reqwest::Client::new();
+        let client = jarvis_core::net::client();
         let test_url = format!("{}/status", self.config.zvm.web5_gateway);
 
         match client
             .get(&test_url)
             .timeout(Duration::from_secs(5))
-            .send()
+            .checked_send()
             .await
         {
             Ok(response) => {
@@ -0,0 +1,450 @@
+/*!
+ * Adaptive polling intervals for JARVIS-NV
+ *
+ * The node manager's loops start at their configured intervals and scale
+ * them together with chain activity. Each status poll takes a reading: busy
+ * when blocks arrive faster than the status interval or pending transactions
+ * spike, quiet when blocks come more than twice as slowly as it or the chain
+ * carries nothing. A reading must repeat `hysteresis_samples` times before
+ * the intervals halve (busy) or grow by half (quiet), and between the two
+ * thresholds they hold, so a chain near either edge doesn't make them flap.
+ */
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use tokio::time::{Duration, Instant};
+
+use crate::config::PollingConfig;
+
+/// Block arrivals and pending counts kept for a reading
+const RECENT_SAMPLES: usize = 5;
+/// Pending transactions below this never count as a spike
+const MIN_SPIKE_PENDING: u32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Load {
+    Busy,
+    Steady,
+    Quiet,
+}
+
+/// A new chain head as a poll or subscription saw it
+#[derive(Debug, Clone, Copy)]
+struct BlockSeen {
+    at: Instant,
+    height: u64,
+    /// Transactions in the block, when the source carries them
+    transactions: Option<usize>,
+}
+
+/// Recent chain heads and pending transaction counts
+#[derive(Debug, Clone, Default)]
+pub struct ChainActivity {
+    blocks: VecDeque<BlockSeen>,
+    pending: VecDeque<u32>,
+}
+
+impl ChainActivity {
+    /// Note the head at `height`. Heads at or below the last one are
+    /// ignored, so the HTTP poll and a subscription can both report blocks.
+    pub fn record_block(&mut self, at: Instant, height: u64, transactions: Option<usize>) {
+        if self.blocks.back().is_some_and(|last| last.height >= height) {
+            return;
+        }
+        push_bounded(
+            &mut self.blocks,
+            BlockSeen {
+                at,
+                height,
+                transactions,
+            },
+        );
+    }
+
+    pub fn record_pending(&mut self, count: u32) {
+        push_bounded(&mut self.pending, count);
+    }
+
+    /// Mean time per block over the recent heads; a poll that finds the
+    /// head several blocks further counts each of them
+    pub fn block_cadence(&self) -> Option<Duration> {
+        let first = self.blocks.front()?;
+        let last = self.blocks.back()?;
+        let blocks = u32::try_from(last.height - first.height).ok()?;
+        (blocks > 0).then(|| last.at.saturating_duration_since(first.at) / blocks)
+    }
+
+    /// Whether the latest pending count is `ratio` times the mean of the
+    /// readings before it
+    fn pending_spiked(&self, ratio: f64) -> bool {
+        let Some(&latest) = self.pending.back() else {
+            return false;
+        };
+        let earlier = self.pending.len() - 1;
+        if earlier == 0 || latest < MIN_SPIKE_PENDING {
+            return false;
+        }
+        let mean = self
+            .pending
+            .iter()
+            .take(earlier)
+            .map(|&count| count as f64)
+            .sum::<f64>()
+            / earlier as f64;
+        latest as f64 >= ratio * mean.max(1.0)
+    }
+
+    /// The recent blocks were all empty and nothing is pending
+    fn idle(&self) -> bool {
+        self.blocks.len() >= 2
+            && self
+                .blocks
+                .iter()
+                .all(|block| block.transactions == Some(0))
+            && self.pending.back() == Some(&0)
+    }
+
+    /// How busy the chain is next to a poll every `interval`
+    pub fn load(&self, now: Instant, interval: Duration, spike_ratio: f64) -> Load {
+        if self.pending_spiked(spike_ratio) {
+            return Load::Busy;
+        }
+        let Some(last_block) = self.blocks.back() else {
+            return Load::Steady;
+        };
+        // The gap still open since the last block counts once it is the
+        // longest, so a chain that stops producing reads as quiet
+        let since_last = now.saturating_duration_since(last_block.at);
+        let cadence = self.block_cadence().map(|c| c.max(since_last));
+
+        match cadence {
+            Some(cadence) if cadence * 4 < interval * 3 => Load::Busy,
+            Some(cadence) if cadence > interval * 2 => Load::Quiet,
+            None if since_last > interval * 2 => Load::Quiet,
+            _ if self.idle() => Load::Quiet,
+            _ => Load::Steady,
+        }
+    }
+}
+
+fn push_bounded<T>(samples: &mut VecDeque<T>, sample: T) {
+    samples.push_back(sample);
+    while samples.len() > RECENT_SAMPLES {
+        samples.pop_front();
+    }
+}
+
+/// Effective intervals as `get_status` reports them
+#[derive(Debug, Clone, Serialize)]
+pub struct PollingStatus {
+    pub adaptive: bool,
+    pub load: Load,
+    pub status_interval_seconds: f64,
+    /// `None` while a WebSocket subscription delivers blocks
+    pub block_poll_interval_seconds: Option<f64>,
+    pub metrics_interval_seconds: f64,
+    pub block_cadence_seconds: Option<f64>,
+    pub websocket: bool,
+}
+
+/// The node manager's polling intervals, scaled together by chain activity
+#[derive(Debug, Clone)]
+pub struct AdaptivePolling {
+    config: PollingConfig,
+    status: Duration,
+    blocks: Duration,
+    metrics: Duration,
+    activity: ChainActivity,
+    /// Factor on the configured intervals; 1.0 until activity changes it
+    scale: f64,
+    load: Load,
+    streak: u32,
+    websocket: bool,
+}
+
+impl AdaptivePolling {
+    /// Start from the configured status, block, and metrics intervals
+    pub fn new(
+        config: &PollingConfig,
+        status: Duration,
+        blocks: Duration,
+        metrics: Duration,
+    ) -> Self {
+        Self {
+            config: config.clone(),
+            status,
+            blocks,
+            metrics,
+            activity: ChainActivity::default(),
+            scale: 1.0,
+            load: Load::Steady,
+            streak: 0,
+            websocket: false,
+        }
+    }
+
+    pub fn record_block(&mut self, at: Instant, height: u64, transactions: Option<usize>) {
+        self.activity.record_block(at, height, transactions);
+    }
+
+    pub fn record_pending(&mut self, count: u32) {
+        self.activity.record_pending(count);
+    }
+
+    /// A WebSocket subscription delivers new blocks, so they aren't polled
+    pub fn set_websocket(&mut self, subscribed: bool) {
+        self.websocket = subscribed;
+    }
+
+    pub fn websocket(&self) -> bool {
+        self.websocket
+    }
+
+    /// Take a reading of the chain at `now` and rescale the intervals once
+    /// the same reading has repeated enough times; returns the reading
+    pub fn observe(&mut self, now: Instant) -> Load {
+        if !self.config.adaptive {
+            return Load::Steady;
+        }
+        let load = self
+            .activity
+            .load(now, self.status_interval(), self.config.mempool_spike_ratio);
+        if load == Load::Steady || load != self.load {
+            self.streak = 0;
+        }
+        self.load = load;
+        if load == Load::Steady {
+            return load;
+        }
+
+        self.streak += 1;
+        if self.streak >= self.config.hysteresis_samples.max(1) {
+            self.streak = 0;
+            let factor = if load == Load::Busy { 0.5 } else { 1.5 };
+            self.rescale(self.scale * factor);
+        }
+        load
+    }
+
+    /// Keep the status interval within the floor and ceiling
+    fn rescale(&mut self, scale: f64) {
+        let base = self.status.as_secs_f64().max(f64::EPSILON);
+        let min = self.config.floor_seconds as f64 / base;
+        let max = self.config.ceiling_seconds as f64 / base;
+        let scaled = scale.min(max).max(min);
+        if scaled != self.scale {
+            tracing::debug!(
+                "⏱️ Polling interval scale {:.2} → {:.2} ({:?})",
+                self.scale,
+                scaled,
+                self.load
+            );
+        }
+        self.scale = scaled;
+    }
+
+    fn scaled(&self, base: Duration) -> Duration {
+        if !self.config.adaptive {
+            return base;
+        }
+        let secs = (base.as_secs_f64() * self.scale)
+            .max(self.config.floor_seconds as f64)
+            .min(self.config.ceiling_seconds as f64);
+        Duration::from_millis((secs * 1000.0).round() as u64)
+    }
+
+    pub fn status_interval(&self) -> Duration {
+        self.scaled(self.status)
+    }
+
+    /// `None` while a WebSocket subscription delivers blocks
+    pub fn block_poll_interval(&self) -> Option<Duration> {
+        (!self.websocket).then(|| self.scaled(self.blocks))
+    }
+
+    pub fn metrics_interval(&self) -> Duration {
+        self.scaled(self.metrics)
+    }
+
+    pub fn status(&self) -> PollingStatus {
+        PollingStatus {
+            adaptive: self.config.adaptive,
+            load: self.load,
+            status_interval_seconds: self.status_interval().as_secs_f64(),
+            block_poll_interval_seconds: self.block_poll_interval().map(|d| d.as_secs_f64()),
+            metrics_interval_seconds: self.metrics_interval().as_secs_f64(),
+            block_cadence_seconds: self.activity.block_cadence().map(|d| d.as_secs_f64()),
+            websocket: self.websocket,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn polling() -> AdaptivePolling {
+        AdaptivePolling::new(
+            &PollingConfig::default(),
+            Duration::from_secs(30),
+            Duration::from_secs(10),
+            Duration::from_secs(60),
+        )
+    }
+
+    /// A simulated chain and clock
+    struct Chain {
+        clock: Instant,
+        height: u64,
+    }
+
+    impl Chain {
+        fn new() -> Self {
+            Self {
+                clock: Instant::now(),
+                height: 0,
+            }
+        }
+
+        /// Produce a block every `gap` and poll the head every status
+        /// interval, for `for_secs` of simulated time
+        fn run(&mut self, polling: &mut AdaptivePolling, gap: Duration, for_secs: u64) {
+            let end = self.clock + Duration::from_secs(for_secs);
+            let mut next_block = self.clock + gap;
+            while self.clock < end {
+                let next_poll = self.clock + polling.status_interval();
+                while next_block <= next_poll {
+                    self.height += 1;
+                    next_block += gap;
+                }
+                self.clock = next_poll;
+                polling.record_block(self.clock, self.height, Some(20));
+                polling.observe(self.clock);
+            }
+        }
+    }
+
+    #[test]
+    fn test_tightens_under_fast_blocks_and_relaxes_when_quiet() {
+        let mut polling = polling();
+        let mut chain = Chain::new();
+
+        chain.run(&mut polling, Duration::from_secs(3), 600);
+        let busy = polling.status();
+        assert!(busy.status_interval_seconds < 30.0, "{:?}", busy);
+        assert!(busy.status_interval_seconds >= 2.0);
+        assert!(busy.metrics_interval_seconds < 60.0);
+        assert_eq!(busy.block_poll_interval_seconds, Some(2.0));
+
+        chain.run(&mut polling, Duration::from_secs(600), 6 * 3600);
+        let quiet = polling.status();
+        assert!(quiet.status_interval_seconds > 30.0, "{:?}", quiet);
+        assert!(quiet.status_interval_seconds <= 300.0);
+        assert_eq!(quiet.metrics_interval_seconds, 300.0);
+    }
+
+    #[test]
+    fn test_holds_steady_near_cadence() {
+        let mut polling = polling();
+        let mut chain = Chain::new();
+
+        // One block per poll sits between the thresholds
+        chain.run(&mut polling, Duration::from_secs(30), 3600);
+        assert_eq!(polling.status_interval(), Duration::from_secs(30));
+        assert_eq!(polling.status().load, Load::Steady);
+    }
+
+    #[test]
+    fn test_needs_repeated_readings_to_change() {
+        let mut polling = polling();
+        let clock = Instant::now();
+        for i in 0..5 {
+            polling.record_block(clock + Duration::from_secs(i * 3), i, Some(5));
+        }
+
+        let at = clock + Duration::from_secs(13);
+        assert_eq!(polling.observe(at), Load::Busy);
+        assert_eq!(polling.observe(at), Load::Busy);
+        assert_eq!(polling.status_interval(), Duration::from_secs(30));
+        assert_eq!(polling.observe(at), Load::Busy);
+        assert_eq!(polling.status_interval(), Duration::from_secs(15));
+
+        // A steady reading in between restarts the count
+        let at = clock + Duration::from_secs(14);
+        assert_eq!(polling.observe(at), Load::Busy);
+        assert_eq!(polling.observe(at), Load::Busy);
+        assert_eq!(
+            polling.observe(clock + Duration::from_secs(26)),
+            Load::Steady
+        );
+        polling.record_block(clock + Duration::from_secs(27), 5, Some(5));
+        polling.record_block(clock + Duration::from_secs(28), 6, Some(5));
+        let at = clock + Duration::from_secs(28);
+        assert_eq!(polling.observe(at), Load::Busy);
+        assert_eq!(polling.observe(at), Load::Busy);
+        assert_eq!(polling.status_interval(), Duration::from_secs(15));
+        assert_eq!(polling.observe(at), Load::Busy);
+        assert_eq!(polling.status_interval(), Duration::from_millis(7500));
+    }
+
+    #[test]
+    fn test_mempool_spike_tightens() {
+        let mut polling = polling();
+        let clock = Instant::now();
+        polling.record_block(clock, 1, Some(20));
+        polling.record_block(clock + Duration::from_secs(30), 2, Some(20));
+        for count in [40, 50, 45] {
+            polling.record_pending(count);
+        }
+
+        polling.record_pending(400);
+        let at = clock + Duration::from_secs(31);
+        for _ in 0..3 {
+            assert_eq!(polling.observe(at), Load::Busy);
+        }
+        assert_eq!(polling.status_interval(), Duration::from_secs(15));
+    }
+
+    #[test]
+    fn test_idle_chain_relaxes() {
+        let mut polling = polling();
+        let clock = Instant::now();
+        for i in 0..3 {
+            polling.record_block(clock + Duration::from_secs(i * 30), i, Some(0));
+        }
+        polling.record_pending(0);
+
+        let at = clock + Duration::from_secs(61);
+        for _ in 0..3 {
+            assert_eq!(polling.observe(at), Load::Quiet);
+        }
+        assert_eq!(polling.status_interval(), Duration::from_secs(45));
+    }
+
+    #[test]
+    fn test_websocket_drops_block_polling() {
+        let mut polling = polling();
+        assert_eq!(polling.block_poll_interval(), Some(Duration::from_secs(10)));
+        polling.set_websocket(true);
+        assert_eq!(polling.block_poll_interval(), None);
+        assert!(polling.status().websocket);
+    }
+
+    #[test]
+    fn test_fixed_when_not_adaptive() {
+        let config = PollingConfig {
+            adaptive: false,
+            ..PollingConfig::default()
+        };
+        let mut polling = AdaptivePolling::new(
+            &config,
+            Duration::from_secs(30),
+            Duration::from_secs(10),
+            Duration::from_secs(60),
+        );
+        Chain::new().run(&mut polling, Duration::from_secs(1), 600);
+        assert_eq!(polling.status_interval(), Duration::from_secs(30));
+        assert_eq!(polling.status().load, Load::Steady);
+    }
+}
@@ -0,0 +1,505 @@
+/*!
+ * Prediction evaluation and model selection for JARVIS-NV
+ *
+ * Every prediction is made by each candidate forecaster; the one currently
+ * selected for the category is published, the rest are kept as shadows. Once
+ * the prediction's timeframe has passed, the realized value is read from the
+ * metrics history and every candidate is scored against it. Each category
+ * then switches to the candidate with the lowest recent error.
+ */
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use crate::agent::Prediction;
+
+/// Evaluations remembered per candidate and category for rolling accuracy
+const ROLLING_WINDOW: usize = 20;
+
+/// Evaluations a category needs before it may switch candidates
+const MIN_EVALUATIONS: usize = 3;
+
+/// Predictions waiting longer than this for a realized value are dropped
+const MAX_PENDING: usize = 500;
+
+/// What each category predicts and where the realized value lives in a
+/// metrics history entry
+pub const CATEGORIES: &[(&str, &str, &str)] = &[
+    ("congestion", "/node/mempool_size", "1h"),
+    ("demand", "/node/transaction_count_24h", "24h"),
+    ("performance", "/system/cpu_usage_percent", "1h"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ForecastModel {
+    /// The latest value carries forward
+    Last,
+    /// Mean of the last `window` samples
+    MovingAverage,
+    /// Least-squares line through the last `window` samples, extrapolated
+    LinearTrend,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Candidate {
+    pub model: ForecastModel,
+    pub window: usize,
+}
+
+impl Candidate {
+    pub fn name(&self) -> String {
+        match self.model {
+            ForecastModel::Last => "last".to_string(),
+            ForecastModel::MovingAverage => format!("moving_average({})", self.window),
+            ForecastModel::LinearTrend => format!("linear_trend({})", self.window),
+        }
+    }
+
+    /// Value expected `horizon` after the last sample of `series`
+    pub fn forecast(&self, series: &[(DateTime<Utc>, f64)], horizon: Duration) -> Option<f64> {
+        let (last_time, last_value) = *series.last()?;
+        let recent = &series[series.len().saturating_sub(self.window.max(1))..];
+        match self.model {
+            ForecastModel::Last => Some(last_value),
+            ForecastModel::MovingAverage => {
+                Some(recent.iter().map(|(_, v)| v).sum::<f64>() / recent.len() as f64)
+            }
+            ForecastModel::LinearTrend => {
+                if recent.len() < 2 {
+                    return Some(last_value);
+                }
+                // Seconds relative to the last sample keep the numbers small
+                let points: Vec<(f64, f64)> = recent
+                    .iter()
+                    .map(|(t, v)| ((*t - last_time).num_seconds() as f64, *v))
+                    .collect();
+                let n = points.len() as f64;
+                let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+                let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+                let spread: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+                if spread == 0.0 {
+                    return Some(mean_y);
+                }
+                let slope = points
+                    .iter()
+                    .map(|(x, y)| (x - mean_x) * (y - mean_y))
+                    .sum::<f64>()
+                    / spread;
+                Some(mean_y + slope * (horizon.num_seconds() as f64 - mean_x))
+            }
+        }
+    }
+}
+
+/// The forecasters every category chooses between
+pub const CANDIDATES: &[Candidate] = &[
+    Candidate {
+        model: ForecastModel::Last,
+        window: 1,
+    },
+    Candidate {
+        model: ForecastModel::MovingAverage,
+        window: 6,
+    },
+    Candidate {
+        model: ForecastModel::MovingAverage,
+        window: 24,
+    },
+    Candidate {
+        model: ForecastModel::LinearTrend,
+        window: 6,
+    },
+    Candidate {
+        model: ForecastModel::LinearTrend,
+        window: 24,
+    },
+];
+
+/// "1h", "24h", "7d", "30m"
+pub fn parse_timeframe(timeframe: &str) -> Option<Duration> {
+    let split = timeframe.len().checked_sub(1)?;
+    let (amount, unit) = timeframe.split_at(split);
+    let amount: i64 = amount.parse().ok()?;
+    match unit {
+        "m" => Some(Duration::minutes(amount)),
+        "h" => Some(Duration::hours(amount)),
+        "d" => Some(Duration::days(amount)),
+        _ => None,
+    }
+}
+
+/// `(timestamp, value)` samples at `pointer` in metrics history entries
+pub fn series(history: &[serde_json::Value], pointer: &str) -> Vec<(DateTime<Utc>, f64)> {
+    history
+        .iter()
+        .filter_map(|entry| {
+            let timestamp = entry.get("timestamp")?.as_str()?.parse().ok()?;
+            Some((timestamp, entry.pointer(pointer)?.as_f64()?))
+        })
+        .collect()
+}
+
+/// How far a prediction was from what happened
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Evaluation {
+    pub prediction_id: String,
+    pub category: String,
+    pub model: String,
+    pub predicted_value: f64,
+    pub realized_value: f64,
+    pub absolute_error: f64,
+    /// Absolute error as a fraction of the realized value
+    pub percentage_error: f64,
+    pub evaluated_at: DateTime<Utc>,
+}
+
+impl Evaluation {
+    /// 1.0 for a perfect prediction, falling to 0.0 at 100% error
+    pub fn accuracy(&self) -> f64 {
+        (1.0 - self.percentage_error).clamp(0.0, 1.0)
+    }
+}
+
+fn percentage_error(predicted: f64, realized: f64) -> f64 {
+    let absolute = (predicted - realized).abs();
+    if realized == 0.0 {
+        if absolute == 0.0 { 0.0 } else { 1.0 }
+    } else {
+        absolute / realized.abs()
+    }
+}
+
+struct Pending {
+    prediction_id: String,
+    category: String,
+    pointer: String,
+    target_time: DateTime<Utc>,
+    published: usize,
+    /// Forecast of every candidate, by index into `CANDIDATES`
+    forecasts: Vec<Option<f64>>,
+}
+
+#[derive(Default)]
+struct CategoryState {
+    selected: usize,
+    /// Recent percentage errors per candidate
+    errors: Vec<VecDeque<f64>>,
+    /// Recent accuracy of the published predictions
+    published: VecDeque<f64>,
+    evaluations: u64,
+}
+
+impl CategoryState {
+    fn new() -> Self {
+        Self {
+            errors: vec![VecDeque::new(); CANDIDATES.len()],
+            ..Default::default()
+        }
+    }
+
+    fn mean_error(&self, candidate: usize) -> Option<f64> {
+        let errors = &self.errors[candidate];
+        (errors.len() >= MIN_EVALUATIONS).then(|| errors.iter().sum::<f64>() / errors.len() as f64)
+    }
+
+    /// Switch to the candidate with the lowest recent error
+    fn reselect(&mut self) {
+        let best = (0..CANDIDATES.len())
+            .filter_map(|i| self.mean_error(i).map(|e| (i, e)))
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((best, _)) = best {
+            self.selected = best;
+        }
+    }
+}
+
+/// Per-category numbers for the agent status
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CategoryReport {
+    pub model: String,
+    /// Mean accuracy of the recent published predictions
+    pub rolling_accuracy: Option<f64>,
+    pub evaluations: u64,
+    /// Recent mean percentage error per candidate
+    pub candidate_errors: BTreeMap<String, f64>,
+}
+
+#[derive(Default)]
+pub struct PredictionEvaluator {
+    pending: VecDeque<Pending>,
+    categories: HashMap<String, CategoryState>,
+}
+
+impl PredictionEvaluator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The candidate currently published for `category`
+    pub fn selected(&self, category: &str) -> Candidate {
+        CANDIDATES[self.categories.get(category).map_or(0, |s| s.selected)]
+    }
+
+    /// Predict `category` from the metrics `history` and remember every
+    /// candidate's forecast for evaluation; `None` without enough samples
+    pub fn predict(
+        &mut self,
+        category: &str,
+        pointer: &str,
+        timeframe: &str,
+        history: &[serde_json::Value],
+        now: DateTime<Utc>,
+    ) -> Option<Prediction> {
+        let horizon = parse_timeframe(timeframe)?;
+        let samples = series(history, pointer);
+        if samples.len() < 2 {
+            return None;
+        }
+
+        let forecasts: Vec<Option<f64>> = CANDIDATES
+            .iter()
+            .map(|c| c.forecast(&samples, horizon))
+            .collect();
+        let published = self.categories.get(category).map_or(0, |s| s.selected);
+        let predicted_value = forecasts[published]?;
+        let current_value = samples.last()?.1;
+        let target_time = samples.last()?.0 + horizon;
+
+        let change = percentage_error(predicted_value, current_value);
+        let trend = if change < 0.02 {
+            "stable"
+        } else if predicted_value > current_value {
+            "increasing"
+        } else {
+            "decreasing"
+        };
+        let confidence = self.report(category).rolling_accuracy.unwrap_or(0.5);
+
+        let prediction = Prediction {
+            id: uuid::Uuid::new_v4().to_string(),
+            timestamp: now,
+            category: category.to_string(),
+            timeframe: timeframe.to_string(),
+            predicted_value,
+            confidence,
+            current_value,
+            trend: trend.to_string(),
+            accuracy_score: None,
+            model: CANDIDATES[published].name(),
+            target_time,
+            realized_value: None,
+        };
+
+        self.pending.push_back(Pending {
+            prediction_id: prediction.id.clone(),
+            category: category.to_string(),
+            pointer: pointer.to_string(),
+            target_time,
+            published,
+            forecasts,
+        });
+        while self.pending.len() > MAX_PENDING {
+            self.pending.pop_front();
+        }
+        Some(prediction)
+    }
+
+    /// Score every prediction whose timeframe has passed and that the
+    /// history has a realized value for, then retune the categories
+    pub fn evaluate_due(
+        &mut self,
+        history: &[serde_json::Value],
+        now: DateTime<Utc>,
+    ) -> Vec<Evaluation> {
+        let mut evaluations = Vec::new();
+        let mut waiting = VecDeque::new();
+
+        while let Some(pending) = self.pending.pop_front() {
+            let realized = (pending.target_time <= now)
+                .then(|| {
+                    series(history, &pending.pointer)
+                        .into_iter()
+                        .find(|(t, _)| *t >= pending.target_time)
+                })
+                .flatten();
+            let Some((_, realized_value)) = realized else {
+                waiting.push_back(pending);
+                continue;
+            };
+
+            let state = self
+                .categories
+                .entry(pending.category.clone())
+                .or_insert_with(CategoryState::new);
+            for (index, forecast) in pending.forecasts.iter().enumerate() {
+                if let Some(forecast) = forecast {
+                    let errors = &mut state.errors[index];
+                    errors.push_back(percentage_error(*forecast, realized_value));
+                    while errors.len() > ROLLING_WINDOW {
+                        errors.pop_front();
+                    }
+                }
+            }
+
+            let Some(predicted_value) = pending.forecasts[pending.published] else {
+                continue;
+            };
+            let evaluation = Evaluation {
+                prediction_id: pending.prediction_id,
+                category: pending.category,
+                model: CANDIDATES[pending.published].name(),
+                predicted_value,
+                realized_value,
+                absolute_error: (predicted_value - realized_value).abs(),
+                percentage_error: percentage_error(predicted_value, realized_value),
+                evaluated_at: now,
+            };
+            state.published.push_back(evaluation.accuracy());
+            while state.published.len() > ROLLING_WINDOW {
+                state.published.pop_front();
+            }
+            state.evaluations += 1;
+            state.reselect();
+            evaluations.push(evaluation);
+        }
+
+        self.pending = waiting;
+        evaluations
+    }
+
+    pub fn report(&self, category: &str) -> CategoryReport {
+        let Some(state) = self.categories.get(category) else {
+            return CategoryReport {
+                model: self.selected(category).name(),
+                ..Default::default()
+            };
+        };
+        CategoryReport {
+            model: CANDIDATES[state.selected].name(),
+            rolling_accuracy: (!state.published.is_empty())
+                .then(|| state.published.iter().sum::<f64>() / state.published.len() as f64),
+            evaluations: state.evaluations,
+            candidate_errors: (0..CANDIDATES.len())
+                .filter_map(|i| Some((CANDIDATES[i].name(), state.mean_error(i)?)))
+                .collect(),
+        }
+    }
+
+    /// Reports for every category that has been evaluated
+    pub fn reports(&self) -> BTreeMap<String, CategoryReport> {
+        self.categories
+            .keys()
+            .map(|category| (category.clone(), self.report(category)))
+            .collect()
+    }
+
+    /// Mean rolling accuracy across evaluated categories
+    pub fn overall_accuracy(&self) -> Option<f64> {
+        let accuracies: Vec<f64> = self
+            .reports()
+            .values()
+            .filter_map(|r| r.rolling_accuracy)
+            .collect();
+        (!accuracies.is_empty()).then(|| accuracies.iter().sum::<f64>() / accuracies.len() as f64)
+    }
+
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(at: DateTime<Utc>, cpu: f64) -> serde_json::Value {
+        serde_json::json!({ "timestamp": at, "system": { "cpu_usage_percent": cpu } })
+    }
+
+    /// CPU climbing steadily by 1% an hour plus a little noise; only the
+    /// trend candidates can keep up with it
+    #[test]
+    fn test_accuracy_converges_on_the_better_candidate() {
+        let start = Utc::now() - Duration::days(10);
+        let cpu = |hour: i64| 20.0 + hour as f64 + if hour % 2 == 0 { 0.3 } else { -0.3 };
+        let history: Vec<serde_json::Value> = (0..240)
+            .map(|hour| entry(start + Duration::hours(hour), cpu(hour)))
+            .collect();
+
+        let mut evaluator = PredictionEvaluator::new();
+        assert_eq!(evaluator.selected("performance").model, ForecastModel::Last);
+
+        let mut early = Vec::new();
+        let mut late = Vec::new();
+        for hour in 24..230 {
+            let now = start + Duration::hours(hour);
+            let known = &history[..=hour as usize];
+            evaluator
+                .predict("performance", "/system/cpu_usage_percent", "1h", known, now)
+                .unwrap();
+            for evaluation in evaluator.evaluate_due(known, now) {
+                if hour < 40 {
+                    early.push(evaluation.accuracy());
+                } else if hour > 200 {
+                    late.push(evaluation.accuracy());
+                }
+            }
+        }
+
+        let mean = |v: &[f64]| v.iter().sum::<f64>() / v.len() as f64;
+        assert_eq!(
+            evaluator.selected("performance").model,
+            ForecastModel::LinearTrend
+        );
+        assert!(mean(&late) > mean(&early), "{:?} vs {:?}", late, early);
+        assert!(mean(&late) > 0.99);
+
+        let report = evaluator.report("performance");
+        assert!(report.evaluations > 150);
+        assert!(report.candidate_errors["linear_trend(24)"] < report.candidate_errors["last"]);
+    }
+
+    #[test]
+    fn test_predictions_wait_for_a_realized_value() {
+        let start = Utc::now();
+        let history = vec![
+            entry(start, 10.0),
+            entry(start + Duration::minutes(30), 12.0),
+        ];
+        let mut evaluator = PredictionEvaluator::new();
+        let prediction = evaluator
+            .predict(
+                "performance",
+                "/system/cpu_usage_percent",
+                "1h",
+                &history,
+                start,
+            )
+            .unwrap();
+        assert_eq!(prediction.predicted_value, 12.0);
+
+        // Timeframe passed, but nothing was collected after it yet
+        let later = start + Duration::hours(2);
+        assert!(evaluator.evaluate_due(&history, later).is_empty());
+        assert_eq!(evaluator.pending(), 1);
+
+        let mut history = history;
+        history.push(entry(start + Duration::minutes(95), 15.0));
+        let evaluations = evaluator.evaluate_due(&history, later);
+        assert_eq!(evaluations.len(), 1);
+        assert_eq!(evaluations[0].absolute_error, 3.0);
+        assert_eq!(evaluations[0].percentage_error, 0.2);
+        assert_eq!(evaluator.pending(), 0);
+    }
+
+    #[test]
+    fn test_parse_timeframe() {
+        assert_eq!(parse_timeframe("1h"), Some(Duration::hours(1)));
+        assert_eq!(parse_timeframe("7d"), Some(Duration::days(7)));
+        assert_eq!(parse_timeframe("15m"), Some(Duration::minutes(15)));
+        assert_eq!(parse_timeframe("soon"), None);
+        assert_eq!(parse_timeframe(""), None);
+    }
+}
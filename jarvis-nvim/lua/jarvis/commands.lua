@@ -0,0 +1,58 @@
+-- Jarvis user commands
+-- Creates the :Jarvis* commands from the table sent by the Rust side. Each
+-- command hands its invocation to `run`, and argument completion asks
+-- `complete`; anything either raises is shown with vim.notify.
+
+local M = {}
+
+local function report(err)
+  vim.notify('Jarvis: ' .. tostring(err), vim.log.levels.ERROR)
+end
+
+-- specs: list of { name, desc, nargs, range, complete }
+-- run(invocation): invocation is { name, args, range, line1, line2 }
+-- complete(kind, arglead): returns a list of candidates
+function M.register(specs, run, complete)
+  for _, spec in ipairs(specs) do
+    local opts = { nargs = spec.nargs, desc = spec.desc, force = true }
+    if spec.range then
+      opts.range = true
+    end
+    if spec.complete then
+      opts.complete = function(arglead)
+        local ok, items = pcall(complete, spec.complete, arglead)
+        if not ok then
+          report(items)
+          return {}
+        end
+        return items
+      end
+    end
+
+    vim.api.nvim_create_user_command(spec.name, function(cmd)
+      local ok, err = pcall(run, {
+        name = spec.name,
+        args = cmd.args,
+        range = cmd.range,
+        line1 = cmd.line1,
+        line2 = cmd.line2,
+      })
+      if not ok then
+        report(err)
+      end
+    end, opts)
+  end
+end
+
+-- Register over the RPC channel of an attached jarvis-nvim client. Commands
+-- are notifications, so the editor doesn't wait on the model; the client
+-- reports its own failures.
+function M.attach(channel, specs)
+  M.register(specs, function(invocation)
+    vim.rpcnotify(channel, 'jarvis_command', vim.json.encode(invocation))
+  end, function(kind, arglead)
+    return vim.rpcrequest(channel, 'jarvis_complete', kind, arglead)
+  end)
+end
+
+return M
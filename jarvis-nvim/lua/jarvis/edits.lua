@@ -0,0 +1,217 @@
+-- Jarvis edit preview
+-- Shows hunks proposed by Jarvis in a floating window and applies the
+-- accepted ones to the target buffer, each accept as a single undo step.
+
+local M = {}
+
+local ns = vim.api.nvim_create_namespace('jarvis_edits')
+
+local help = 'a: accept hunk  r: reject hunk  A: accept all  R/q: reject rest  ]h/[h: next/prev'
+
+-- Lines added or removed by accepted hunks above `start`
+local function offset_before(state, start)
+  local offset = 0
+  for _, hunk in ipairs(state.hunks) do
+    if hunk.status == 'accepted' and hunk.start < start then
+      offset = offset + #hunk.new_lines - #hunk.old_lines
+    end
+  end
+  return offset
+end
+
+-- Replace the hunk's old lines in the target buffer; refuses if they changed
+local function apply_hunk(state, hunk)
+  local first = hunk.start + offset_before(state, hunk.start)
+  local last = first + #hunk.old_lines
+  local current = vim.api.nvim_buf_get_lines(state.target, first, last, false)
+  if not vim.deep_equal(current, hunk.old_lines) then
+    vim.notify('Jarvis: hunk ' .. (hunk.id + 1) .. ' no longer matches the buffer; skipped', vim.log.levels.WARN)
+    hunk.status = 'rejected'
+    return false
+  end
+  vim.api.nvim_buf_set_lines(state.target, first, last, false, hunk.new_lines)
+  hunk.status = 'accepted'
+  return true
+end
+
+local function render(state)
+  local lines = { help, '' }
+  local highlights = {}
+  state.line_to_hunk = {}
+  state.hunk_lines = {}
+
+  for index, hunk in ipairs(state.hunks) do
+    table.insert(lines, string.format('@@ hunk %d, line %d [%s]', hunk.id + 1, hunk.start + 1, hunk.status))
+    state.hunk_lines[index] = #lines
+    table.insert(highlights, { #lines - 1, 'Title' })
+    state.line_to_hunk[#lines] = index
+    for _, line in ipairs(hunk.old_lines) do
+      table.insert(lines, '- ' .. line)
+      table.insert(highlights, { #lines - 1, 'DiffDelete' })
+      state.line_to_hunk[#lines] = index
+    end
+    for _, line in ipairs(hunk.new_lines) do
+      table.insert(lines, '+ ' .. line)
+      table.insert(highlights, { #lines - 1, 'DiffAdd' })
+      state.line_to_hunk[#lines] = index
+    end
+    table.insert(lines, '')
+  end
+
+  vim.bo[state.buf].modifiable = true
+  vim.api.nvim_buf_set_lines(state.buf, 0, -1, false, lines)
+  vim.bo[state.buf].modifiable = false
+  vim.api.nvim_buf_clear_namespace(state.buf, ns, 0, -1)
+  vim.api.nvim_buf_add_highlight(state.buf, ns, 'Comment', 0, 0, -1)
+  for _, hl in ipairs(highlights) do
+    vim.api.nvim_buf_add_highlight(state.buf, ns, hl[2], hl[1], 0, -1)
+  end
+end
+
+local function pending(state)
+  for _, hunk in ipairs(state.hunks) do
+    if hunk.status == 'pending' then
+      return true
+    end
+  end
+  return false
+end
+
+local function close(state)
+  if vim.api.nvim_win_is_valid(state.win) then
+    vim.api.nvim_win_close(state.win, true)
+  end
+end
+
+-- Re-render, or close once every hunk is decided
+local function refresh(state)
+  if not pending(state) then
+    local accepted = 0
+    for _, hunk in ipairs(state.hunks) do
+      if hunk.status == 'accepted' then
+        accepted = accepted + 1
+      end
+    end
+    vim.notify(string.format('Jarvis: applied %d of %d hunk(s)', accepted, #state.hunks))
+    close(state)
+    return
+  end
+  local cursor = vim.api.nvim_win_get_cursor(state.win)
+  render(state)
+  cursor[1] = math.min(cursor[1], vim.api.nvim_buf_line_count(state.buf))
+  vim.api.nvim_win_set_cursor(state.win, cursor)
+end
+
+local function hunk_under_cursor(state)
+  local row = vim.api.nvim_win_get_cursor(state.win)[1]
+  local index = state.line_to_hunk[row]
+  if index and state.hunks[index].status == 'pending' then
+    return state.hunks[index]
+  end
+end
+
+local function jump(state, direction)
+  local row = vim.api.nvim_win_get_cursor(state.win)[1]
+  local first, last, step = 1, #state.hunks, 1
+  if direction < 0 then
+    first, last, step = #state.hunks, 1, -1
+  end
+  for index = first, last, step do
+    local line = state.hunk_lines[index]
+    if (direction > 0 and line > row) or (direction < 0 and line < row) then
+      vim.api.nvim_win_set_cursor(state.win, { line, 0 })
+      return
+    end
+  end
+end
+
+-- Open the preview for a proposal: { buffer = bufnr, hunks = { { id, start, old_lines, new_lines } } }
+function M.preview(proposal)
+  if not vim.api.nvim_buf_is_valid(proposal.buffer) then
+    vim.notify('Jarvis: the buffer for this edit is gone', vim.log.levels.WARN)
+    return
+  end
+  if #proposal.hunks == 0 then
+    vim.notify('Jarvis: the suggestion matches the buffer already')
+    return
+  end
+
+  local state = { target = proposal.buffer, hunks = {} }
+  for _, hunk in ipairs(proposal.hunks) do
+    hunk.status = 'pending'
+    table.insert(state.hunks, hunk)
+  end
+
+  state.buf = vim.api.nvim_create_buf(false, true)
+  vim.bo[state.buf].bufhidden = 'wipe'
+  vim.bo[state.buf].filetype = 'diff'
+
+  local width = math.floor(vim.o.columns * 0.8)
+  local height = math.floor(vim.o.lines * 0.8)
+  state.win = vim.api.nvim_open_win(state.buf, true, {
+    relative = 'editor',
+    width = width,
+    height = height,
+    col = math.floor((vim.o.columns - width) / 2),
+    row = math.floor((vim.o.lines - height) / 2),
+    anchor = 'NW',
+    style = 'minimal',
+    border = 'rounded',
+    title = ' ✏️ Jarvis Edit: ' .. vim.fn.fnamemodify(vim.api.nvim_buf_get_name(state.target), ':t') .. ' ',
+    title_pos = 'center',
+  })
+
+  render(state)
+  if state.hunk_lines[1] then
+    vim.api.nvim_win_set_cursor(state.win, { state.hunk_lines[1], 0 })
+  end
+
+  local opts = { buffer = state.buf, nowait = true, silent = true }
+  vim.keymap.set('n', 'a', function()
+    local hunk = hunk_under_cursor(state)
+    if hunk then
+      apply_hunk(state, hunk)
+      refresh(state)
+    end
+  end, vim.tbl_extend('force', opts, { desc = 'Jarvis: accept hunk' }))
+
+  vim.keymap.set('n', 'r', function()
+    local hunk = hunk_under_cursor(state)
+    if hunk then
+      hunk.status = 'rejected'
+      refresh(state)
+    end
+  end, vim.tbl_extend('force', opts, { desc = 'Jarvis: reject hunk' }))
+
+  vim.keymap.set('n', 'A', function()
+    -- Every change made in this callback lands in one undo block
+    local joined = false
+    for _, hunk in ipairs(state.hunks) do
+      if hunk.status == 'pending' then
+        if joined then
+          vim.api.nvim_buf_call(state.target, function()
+            pcall(vim.cmd, 'undojoin')
+          end)
+        end
+        joined = apply_hunk(state, hunk) or joined
+      end
+    end
+    refresh(state)
+  end, vim.tbl_extend('force', opts, { desc = 'Jarvis: accept all hunks' }))
+
+  local function reject_rest()
+    for _, hunk in ipairs(state.hunks) do
+      if hunk.status == 'pending' then
+        hunk.status = 'rejected'
+      end
+    end
+    refresh(state)
+  end
+  vim.keymap.set('n', 'R', reject_rest, vim.tbl_extend('force', opts, { desc = 'Jarvis: reject remaining hunks' }))
+  vim.keymap.set('n', 'q', reject_rest, vim.tbl_extend('force', opts, { desc = 'Jarvis: reject remaining hunks' }))
+  vim.keymap.set('n', '<Esc>', reject_rest, opts)
+  vim.keymap.set('n', ']h', function() jump(state, 1) end, opts)
+  vim.keymap.set('n', '[h', function() jump(state, -1) end, opts)
+end
+
+return M
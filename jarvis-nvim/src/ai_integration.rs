@@ -1,3 +1,5 @@
+use crate::context::RequestContext;
+use crate::edits::{self, BufferSnapshot};
 use anyhow::Result;
 use jarvis_core::{LLMRouter, MemoryStore, types::*};
 use std::sync::Arc;
@@ -73,36 +75,44 @@ impl AIIntegration {
         Ok(response)
     }
 
-    pub async fn explain_code(&self, code: &str, language: &str, context: &str) -> Result<String> {
+    pub async fn explain_code(&self, code: &str, context: &RequestContext) -> Result<String> {
         let prompt = format!(
-            "Explain this {} code in detail. Focus on what it does, how it works, and any potential issues:\n\n```{}\n{}\n```\n\nContext: {}",
-            language, language, code, context
+            "Explain this {} code in detail. Focus on what it does, how it works, and any potential issues:\n\n```{}\n{}\n```\n\nContext:\n{}",
+            context.language(),
+            context.language(),
+            code,
+            context.render()
         );
 
         self.send_message(&prompt, Some("Code explanation request"))
             .await
     }
 
-    pub async fn suggest_improvements(&self, code: &str, language: &str) -> Result<String> {
+    pub async fn suggest_improvements(
+        &self,
+        code: &str,
+        context: &RequestContext,
+    ) -> Result<String> {
         let prompt = format!(
-            "Suggest improvements for this {} code. Focus on performance, readability, best practices, and potential bugs:\n\n```{}\n{}\n```",
-            language, language, code
+            "Suggest improvements for this {} code. Focus on performance, readability, best practices, and potential bugs:\n\n```{}\n{}\n```\n\nContext:\n{}",
+            context.language(),
+            context.language(),
+            code,
+            context.render()
         );
 
         self.send_message(&prompt, Some("Code improvement request"))
             .await
     }
 
-    pub async fn fix_errors(
-        &self,
-        code: &str,
-        errors: &[String],
-        language: &str,
-    ) -> Result<String> {
-        let errors_text = errors.join("\n");
+    /// The errors to fix are the diagnostics in `context`
+    pub async fn fix_errors(&self, code: &str, context: &RequestContext) -> Result<String> {
         let prompt = format!(
-            "Fix the following errors in this {} code:\n\nErrors:\n{}\n\nCode:\n```{}\n{}\n```\n\nProvide the corrected code with explanations.",
-            language, errors_text, language, code
+            "Fix the errors in this {} code; the diagnostics are listed in the context below:\n\nCode:\n```{}\n{}\n```\n\nContext:\n{}\n\nProvide the corrected code with explanations.",
+            context.language(),
+            context.language(),
+            code,
+            context.render()
         );
 
         self.send_message(&prompt, Some("Error fixing request"))
@@ -112,32 +122,45 @@ impl AIIntegration {
     pub async fn generate_code(
         &self,
         description: &str,
-        language: &str,
-        context: &str,
+        context: &RequestContext,
     ) -> Result<String> {
         let prompt = format!(
-            "Generate {} code for: {}\n\nContext: {}\n\nProvide clean, well-commented code that follows best practices.",
-            language, description, context
+            "Generate {} code for: {}\n\nContext:\n{}\n\nProvide clean, well-commented code that follows best practices.",
+            context.language(),
+            description,
+            context.render()
         );
 
         self.send_message(&prompt, Some("Code generation request"))
             .await
     }
 
-    pub async fn refactor_code(&self, code: &str, language: &str, goal: &str) -> Result<String> {
+    pub async fn refactor_code(
+        &self,
+        code: &str,
+        context: &RequestContext,
+        goal: &str,
+    ) -> Result<String> {
         let prompt = format!(
-            "Refactor this {} code to {}:\n\n```{}\n{}\n```\n\nProvide the refactored code with explanations of changes.",
-            language, goal, language, code
+            "Refactor this {} code to {}:\n\n```{}\n{}\n```\n\nContext:\n{}\n\nProvide the refactored code with explanations of changes.",
+            context.language(),
+            goal,
+            context.language(),
+            code,
+            context.render()
         );
 
         self.send_message(&prompt, Some("Code refactoring request"))
             .await
     }
 
-    pub async fn add_comments(&self, code: &str, language: &str) -> Result<String> {
+    pub async fn add_comments(&self, code: &str, context: &RequestContext) -> Result<String> {
         let prompt = format!(
-            "Add comprehensive comments to this {} code. Explain complex logic, function purposes, and parameter meanings:\n\n```{}\n{}\n```",
-            language, language, code
+            "Add comprehensive comments to this {} code. Explain complex logic, function purposes, and parameter meanings:\n\n```{}\n{}\n```\n\nContext:\n{}",
+            context.language(),
+            context.language(),
+            code,
+            context.render()
         );
 
         self.send_message(&prompt, Some("Code documentation request"))
@@ -147,38 +170,88 @@ impl AIIntegration {
     pub async fn convert_language(
         &self,
         code: &str,
-        from_lang: &str,
+        context: &RequestContext,
         to_lang: &str,
     ) -> Result<String> {
         let prompt = format!(
             "Convert this {} code to {}. Maintain the same functionality and logic:\n\n```{}\n{}\n```",
-            from_lang, to_lang, from_lang, code
+            context.language(),
+            to_lang,
+            context.language(),
+            code
         );
 
         self.send_message(&prompt, Some("Language conversion request"))
             .await
     }
 
-    pub async fn analyze_performance(&self, code: &str, language: &str) -> Result<String> {
+    pub async fn analyze_performance(
+        &self,
+        code: &str,
+        context: &RequestContext,
+    ) -> Result<String> {
         let prompt = format!(
-            "Analyze the performance characteristics of this {} code. Identify bottlenecks, suggest optimizations, and estimate complexity:\n\n```{}\n{}\n```",
-            language, language, code
+            "Analyze the performance characteristics of this {} code. Identify bottlenecks, suggest optimizations, and estimate complexity:\n\n```{}\n{}\n```\n\nContext:\n{}",
+            context.language(),
+            context.language(),
+            code,
+            context.render()
         );
 
         self.send_message(&prompt, Some("Performance analysis request"))
             .await
     }
 
-    pub async fn generate_tests(&self, code: &str, language: &str) -> Result<String> {
+    pub async fn generate_tests(&self, code: &str, context: &RequestContext) -> Result<String> {
         let prompt = format!(
-            "Generate comprehensive unit tests for this {} code. Include edge cases, error conditions, and normal usage:\n\n```{}\n{}\n```",
-            language, language, code
+            "Generate comprehensive unit tests for this {} code. Include edge cases, error conditions, and normal usage:\n\n```{}\n{}\n```\n\nContext:\n{}",
+            context.language(),
+            context.language(),
+            code,
+            context.render()
         );
 
         self.send_message(&prompt, Some("Test generation request"))
             .await
     }
 
+    /// Edit mode: the reply is SEARCH/REPLACE directives for `buffer`
+    pub async fn edit_buffer(&self, instruction: &str, buffer: &BufferSnapshot) -> Result<String> {
+        let prompt = edits::edit_prompt(instruction, buffer);
+
+        self.send_message(&prompt, Some("Buffer edit request"))
+            .await
+    }
+
+    /// Hover: what a diagnostic means and its most likely fix. Hovers are
+    /// frequent and throwaway, so they stay out of the conversation.
+    pub async fn explain_diagnostic(
+        &self,
+        message: &str,
+        context: &RequestContext,
+    ) -> Result<String> {
+        let prompt = format!(
+            "In two or three sentences of markdown, explain this {} diagnostic and give the most likely fix:\n\n{}\n\nContext:\n{}",
+            context.language(),
+            message,
+            context.render()
+        );
+
+        self.llm.generate(&prompt, None).await
+    }
+
+    /// Hover: what the symbol under the cursor is, judging by its surroundings
+    pub async fn explain_symbol(&self, symbol: &str, context: &RequestContext) -> Result<String> {
+        let prompt = format!(
+            "In two or three sentences of markdown, explain what `{}` is and does in this {} code:\n\nContext:\n{}",
+            symbol,
+            context.language(),
+            context.render()
+        );
+
+        self.llm.generate(&prompt, None).await
+    }
+
     pub async fn system_prompt(&self, query: &str, system_info: &str) -> Result<String> {
         let prompt = format!(
             "System query: {}\n\nSystem context:\n{}",
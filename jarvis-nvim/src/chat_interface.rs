@@ -1,4 +1,5 @@
 use crate::ai_integration::AIIntegration;
+use crate::edits;
 use anyhow::Result;
 use nvim_rs::{Neovim, Value};
 use serde_json::json;
@@ -9,6 +10,8 @@ pub struct ChatInterface {
     nvim: Neovim<UnixStream>,
     ai: Arc<AIIntegration>,
     chat_buffer: Option<i64>,
+    /// Buffer that was current when the chat opened; replies can edit it
+    source_buffer: Option<i64>,
 }
 
 impl ChatInterface {
@@ -17,10 +20,14 @@ impl ChatInterface {
             nvim,
             ai,
             chat_buffer: None,
+            source_buffer: None,
         }
     }
 
     pub async fn open_chat_window(&mut self) -> Result<()> {
+        let source = self.nvim.get_current_buf().await?;
+        self.source_buffer = Some(source.get_number().await?);
+
         // Create a new buffer for chat
         let buf = self.nvim.create_buf(false, true).await?;
         self.chat_buffer = Some(buf.id().await?);
@@ -153,6 +160,7 @@ impl ChatInterface {
             // Add AI response to chat
             self.add_chat_message("🤖 Jarvis", &response, "String")
                 .await?;
+            self.offer_edit(&response).await?;
 
             // Add new prompt line
             self.add_chat_prompt().await?;
@@ -161,6 +169,24 @@ impl ChatInterface {
         Ok(())
     }
 
+    /// Preview the reply as an edit when it has a code block for the source buffer
+    async fn offer_edit(&self, response: &str) -> Result<()> {
+        let Some(source) = self.source_buffer else {
+            return Ok(());
+        };
+        let snapshot = edits::snapshot(&self.nvim, source).await?;
+        match edits::proposal_from_response(response, &snapshot) {
+            Ok(Some(proposal)) if !proposal.is_empty() => {
+                edits::show_preview(&self.nvim, &proposal).await
+            }
+            Ok(_) => Ok(()),
+            Err(e) => {
+                self.add_chat_message("⚠️ Edit", &format!("{:#}", e), "WarningMsg")
+                    .await
+            }
+        }
+    }
+
     async fn add_chat_message(&self, sender: &str, message: &str, highlight: &str) -> Result<()> {
         if let Some(buf_id) = self.chat_buffer {
             let buf = self.nvim.get_buf_from_id(buf_id).await?;
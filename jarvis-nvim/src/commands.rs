@@ -0,0 +1,356 @@
+//! The `:Jarvis*` user commands
+//!
+//! [`COMMANDS`] is the whole command palette: when the client attaches it is
+//! sent to Neovim, which creates one user command per entry. Running a
+//! command notifies the client over the RPC channel (`jarvis_command`), and
+//! argument completion is an RPC request (`jarvis_complete`) answered from
+//! the live system. Adding a command is one entry here; a new [`Action`]
+//! also needs its arm in [`JarvisNvim::run_command`].
+
+use crate::JarvisNvim;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use nvim_rs::{Handler, Neovim, Value};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, OnceLock, Weak};
+use tokio::net::UnixStream;
+
+/// Lua side of the registration; also loaded by the plugin setup script
+pub const COMMANDS_LUA: &str = include_str!("../lua/jarvis/commands.lua");
+
+/// Argument count, as `nvim_create_user_command` spells it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Nargs {
+    #[serde(rename = "0")]
+    None,
+    #[serde(rename = "1")]
+    One,
+    #[serde(rename = "?")]
+    Optional,
+    #[serde(rename = "*")]
+    Any,
+    #[serde(rename = "+")]
+    AtLeastOne,
+}
+
+/// Where a command's argument candidates come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Completion {
+    /// Service units, from systemd
+    Services,
+    /// Docker container names
+    Containers,
+    /// Names of the chat session's attachments
+    Attachments,
+}
+
+/// What a command does once the client receives it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Explain,
+    Improve,
+    Fix,
+    Chat,
+    Generate,
+    Edit,
+    Diagnose,
+    Containers,
+    ToggleInline,
+    Attach,
+    Attachments,
+    Detach,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct UserCommand {
+    pub name: &'static str,
+    pub desc: &'static str,
+    pub nargs: Nargs,
+    /// Accepts a line range, e.g. from a visual selection
+    pub range: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub complete: Option<Completion>,
+    pub action: Action,
+}
+
+pub const COMMANDS: &[UserCommand] = &[
+    UserCommand {
+        name: "JarvisExplain",
+        desc: "Explain the selected lines with Jarvis",
+        nargs: Nargs::None,
+        range: true,
+        complete: None,
+        action: Action::Explain,
+    },
+    UserCommand {
+        name: "JarvisImprove",
+        desc: "Get improvement suggestions from Jarvis",
+        nargs: Nargs::None,
+        range: false,
+        complete: None,
+        action: Action::Improve,
+    },
+    UserCommand {
+        name: "JarvisFix",
+        desc: "Fix the errors on the current line with Jarvis",
+        nargs: Nargs::None,
+        range: false,
+        complete: None,
+        action: Action::Fix,
+    },
+    UserCommand {
+        name: "JarvisChat",
+        desc: "Open the Jarvis chat split",
+        nargs: Nargs::None,
+        range: false,
+        complete: None,
+        action: Action::Chat,
+    },
+    UserCommand {
+        name: "JarvisGenerate",
+        desc: "Generate code with Jarvis",
+        nargs: Nargs::Any,
+        range: false,
+        complete: None,
+        action: Action::Generate,
+    },
+    UserCommand {
+        name: "JarvisEdit",
+        desc: "Have Jarvis edit the current buffer, previewing each hunk",
+        nargs: Nargs::AtLeastOne,
+        range: false,
+        complete: None,
+        action: Action::Edit,
+    },
+    UserCommand {
+        name: "JarvisDiagnose",
+        desc: "Diagnose a systemd service with Jarvis",
+        nargs: Nargs::One,
+        range: false,
+        complete: Some(Completion::Services),
+        action: Action::Diagnose,
+    },
+    UserCommand {
+        name: "JarvisContainers",
+        desc: "List Docker containers, or show one's state and logs",
+        nargs: Nargs::Optional,
+        range: false,
+        complete: Some(Completion::Containers),
+        action: Action::Containers,
+    },
+    UserCommand {
+        name: "JarvisToggleInline",
+        desc: "Turn Jarvis completions from the language server on or off",
+        nargs: Nargs::None,
+        range: false,
+        complete: None,
+        action: Action::ToggleInline,
+    },
+    UserCommand {
+        name: "JarvisAttach",
+        desc: "Attach a file, probe, or the last operation to the chat: file <path> | probe <name> | last-op",
+        nargs: Nargs::AtLeastOne,
+        range: false,
+        complete: None,
+        action: Action::Attach,
+    },
+    UserCommand {
+        name: "JarvisAttachments",
+        desc: "List what is attached to the chat",
+        nargs: Nargs::None,
+        range: false,
+        complete: None,
+        action: Action::Attachments,
+    },
+    UserCommand {
+        name: "JarvisDetach",
+        desc: "Remove an attachment from the chat",
+        nargs: Nargs::One,
+        range: false,
+        complete: Some(Completion::Attachments),
+        action: Action::Detach,
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static UserCommand> {
+    COMMANDS.iter().find(|command| command.name == name)
+}
+
+/// One run of a command, as `require('jarvis.commands')` passes it on
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Invocation {
+    pub name: String,
+    #[serde(default)]
+    pub args: String,
+    /// Number of range items given: 0, 1, or 2
+    #[serde(default)]
+    pub range: u8,
+    #[serde(default)]
+    pub line1: i64,
+    #[serde(default)]
+    pub line2: i64,
+}
+
+impl Invocation {
+    /// The 1-based inclusive line range, when one was given
+    pub fn lines(&self) -> Option<(i64, i64)> {
+        (self.range > 0).then_some((self.line1, self.line2))
+    }
+
+    /// The argument, or `None` when it was left out
+    pub fn arg(&self) -> Option<&str> {
+        Some(self.args.trim()).filter(|arg| !arg.is_empty())
+    }
+}
+
+/// `candidates` starting with `arglead`, sorted and without duplicates
+pub fn matching(mut candidates: Vec<String>, arglead: &str) -> Vec<String> {
+    candidates.retain(|candidate| candidate.starts_with(arglead));
+    candidates.sort();
+    candidates.dedup();
+    candidates
+}
+
+/// Create the commands in Neovim, routed to this client's RPC channel
+pub async fn register(nvim: &Neovim<UnixStream>) -> Result<()> {
+    let info = nvim.get_api_info().await?;
+    let channel = info
+        .first()
+        .and_then(Value::as_i64)
+        .context("Neovim did not report our RPC channel")?;
+    let specs = serde_json::to_string(COMMANDS)?;
+    nvim.exec_lua(
+        r#"
+        local source, channel, specs = ...
+        if not package.loaded['jarvis.commands'] then
+            package.loaded['jarvis.commands'] = assert(load(source, "jarvis.commands"))()
+        end
+        require('jarvis.commands').attach(channel, vim.json.decode(specs))
+        "#,
+        vec![
+            Value::from(COMMANDS_LUA),
+            Value::from(channel),
+            Value::from(specs),
+        ],
+    )
+    .await?;
+    Ok(())
+}
+
+/// Answers the RPC calls the commands make. Bound to the client once it is
+/// constructed; calls before that are refused.
+#[derive(Clone, Default)]
+pub struct RpcHandler {
+    client: Arc<OnceLock<Weak<JarvisNvim>>>,
+}
+
+impl RpcHandler {
+    pub fn bind(&self, client: &Arc<JarvisNvim>) {
+        let _ = self.client.set(Arc::downgrade(client));
+    }
+
+    fn client(&self) -> Result<Arc<JarvisNvim>> {
+        self.client
+            .get()
+            .and_then(Weak::upgrade)
+            .context("Jarvis is not connected yet")
+    }
+}
+
+#[async_trait]
+impl Handler for RpcHandler {
+    type Writer = UnixStream;
+
+    async fn handle_request(
+        &self,
+        name: String,
+        args: Vec<Value>,
+        _nvim: Neovim<UnixStream>,
+    ) -> Result<Value, Value> {
+        if name != "jarvis_complete" {
+            return Err(Value::from(format!("Unknown request: {}", name)));
+        }
+        let kind = args
+            .first()
+            .and_then(Value::as_str)
+            .and_then(|kind| serde_json::from_value(serde_json::Value::from(kind)).ok())
+            .ok_or_else(|| Value::from("Missing or unknown completion kind"))?;
+        let arglead = args.get(1).and_then(Value::as_str).unwrap_or_default();
+
+        let client = self.client().map_err(|e| Value::from(e.to_string()))?;
+        match client.complete(kind, arglead).await {
+            Ok(items) => Ok(Value::Array(items.into_iter().map(Value::from).collect())),
+            Err(e) => Err(Value::from(format!("{:#}", e))),
+        }
+    }
+
+    async fn handle_notify(&self, name: String, args: Vec<Value>, _nvim: Neovim<UnixStream>) {
+        if name != "jarvis_command" {
+            tracing::debug!("Ignoring notification {}", name);
+            return;
+        }
+        let client = match self.client() {
+            Ok(client) => client,
+            Err(e) => {
+                tracing::warn!("{}", e);
+                return;
+            }
+        };
+        let invocation = args
+            .first()
+            .and_then(Value::as_str)
+            .context("Command invocation missing")
+            .and_then(|json| Ok(serde_json::from_str::<Invocation>(json)?));
+        let result = match invocation {
+            Ok(invocation) => client.run_command(invocation).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            client.notify_error(&e).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_table_serializes_for_lua() {
+        let specs = serde_json::to_value(COMMANDS).unwrap();
+        let diagnose = specs
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|spec| spec["name"] == "JarvisDiagnose")
+            .unwrap();
+        assert_eq!(diagnose["nargs"], "1");
+        assert_eq!(diagnose["complete"], "services");
+        assert_eq!(diagnose["range"], false);
+
+        let explain = find("JarvisExplain").unwrap();
+        assert!(explain.range);
+        assert_eq!(serde_json::to_value(explain).unwrap().get("complete"), None);
+
+        let mut names: Vec<_> = COMMANDS.iter().map(|c| c.name).collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), COMMANDS.len());
+    }
+
+    #[test]
+    fn test_invocation_and_matching() {
+        let invocation: Invocation = serde_json::from_str(
+            r#"{"name":"JarvisExplain","args":"","range":2,"line1":4,"line2":9}"#,
+        )
+        .unwrap();
+        assert_eq!(invocation.lines(), Some((4, 9)));
+        assert_eq!(invocation.arg(), None);
+
+        let services = vec!["sshd".to_string(), "nginx".to_string(), "sshd".to_string()];
+        assert_eq!(matching(services.clone(), "ss"), vec!["sshd"]);
+        assert_eq!(matching(services, ""), vec!["nginx", "sshd"]);
+    }
+}
@@ -0,0 +1,480 @@
+//! Buffer and project context for requests from Neovim
+//!
+//! Each request carries the buffer's path and filetype, the lines around
+//! the cursor or selection, and the LSP diagnostics in that range. With
+//! `[nvim] project_context` on, it also gets a short project summary: the
+//! root (nearest `.git` or `Cargo.toml`), the crate's dependencies, and the
+//! sibling modules of the file. Project files are only read through the
+//! `[files]` allowlist, and the whole context is cut to a token budget.
+
+use anyhow::Result;
+use jarvis_core::config::NvimConfig;
+use jarvis_core::file_access::FileAccess;
+use jarvis_core::llm::estimate_tokens;
+use nvim_rs::{Neovim, Value};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::net::UnixStream;
+
+/// Most dependencies and sibling modules listed in a summary
+const MAX_LISTED: usize = 40;
+
+/// A diagnostic inside the requested range
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    /// 0-based line in the buffer
+    pub line: usize,
+    #[serde(default)]
+    pub severity: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProjectSummary {
+    pub root: PathBuf,
+    /// Package name from Cargo.toml
+    pub name: Option<String>,
+    pub dependencies: Vec<String>,
+    /// Other modules next to the current file
+    pub siblings: Vec<String>,
+}
+
+/// What a request knows about where it came from
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RequestContext {
+    pub path: Option<PathBuf>,
+    pub filetype: String,
+    /// 0-based line of the first entry in `lines`
+    pub first_line: usize,
+    /// The cursor line or selection, as 0-based inclusive lines
+    pub focus: (usize, usize),
+    pub lines: Vec<String>,
+    pub diagnostics: Vec<Diagnostic>,
+    pub project: Option<ProjectSummary>,
+    /// Token budget for [`RequestContext::render`]
+    pub token_budget: usize,
+}
+
+impl RequestContext {
+    /// Context with only a language, for callers that have no editor state
+    pub fn for_language(filetype: &str) -> Self {
+        Self {
+            filetype: filetype.to_string(),
+            token_budget: NvimConfig::default().context_token_budget,
+            ..Default::default()
+        }
+    }
+
+    /// Language for prompts and code fences
+    pub fn language(&self) -> &str {
+        if self.filetype.is_empty() {
+            "text"
+        } else {
+            &self.filetype
+        }
+    }
+
+    /// Sections in priority order, dropped or trimmed to fit the budget:
+    /// file, diagnostics, surrounding code, then the project summary
+    pub fn render(&self) -> String {
+        let mut remaining = self.token_budget;
+        let mut sections = Vec::new();
+        let mut push = |section: String, remaining: &mut usize| {
+            let tokens = estimate_tokens(&section);
+            if tokens <= *remaining {
+                *remaining -= tokens;
+                sections.push(section);
+            }
+        };
+
+        let file = match &self.path {
+            Some(path) => format!("File: {} ({})", path.display(), self.language()),
+            None => format!("Language: {}", self.language()),
+        };
+        push(file, &mut remaining);
+
+        if !self.diagnostics.is_empty() {
+            let list: Vec<String> = self
+                .diagnostics
+                .iter()
+                .map(|d| format!("- line {} {}: {}", d.line + 1, d.severity, d.message))
+                .collect();
+            push(format!("Diagnostics:\n{}", list.join("\n")), &mut remaining);
+        }
+
+        if let Some(code) = self.surrounding_code(remaining) {
+            push(code, &mut remaining);
+        }
+
+        if let Some(project) = &self.project {
+            push(render_project(project), &mut remaining);
+        }
+
+        sections.join("\n\n")
+    }
+
+    /// The surrounding lines, dropping those farthest from the focus until
+    /// they fit in `budget` tokens
+    fn surrounding_code(&self, budget: usize) -> Option<String> {
+        if self.lines.is_empty() {
+            return None;
+        }
+        let last = self.first_line + self.lines.len() - 1;
+        let (mut from, mut to) = (self.first_line, last);
+        loop {
+            let slice = &self.lines[from - self.first_line..=to - self.first_line];
+            let code = format!(
+                "Surrounding code (lines {}-{}):\n```{}\n{}\n```",
+                from + 1,
+                to + 1,
+                self.language(),
+                slice.join("\n")
+            );
+            if estimate_tokens(&code) <= budget {
+                return Some(code);
+            }
+            let above = self.focus.0.saturating_sub(from);
+            let below = to.saturating_sub(self.focus.1);
+            if above == 0 && below == 0 {
+                return None;
+            }
+            if above >= below {
+                from += 1;
+            } else {
+                to -= 1;
+            }
+        }
+    }
+}
+
+fn render_project(project: &ProjectSummary) -> String {
+    let mut out = format!("Project root: {}", project.root.display());
+    if let Some(name) = &project.name {
+        out.push_str(&format!("\nCrate: {}", name));
+    }
+    if !project.dependencies.is_empty() {
+        out.push_str(&format!(
+            "\nDependencies: {}",
+            project.dependencies.join(", ")
+        ));
+    }
+    if !project.siblings.is_empty() {
+        out.push_str(&format!(
+            "\nSibling modules: {}",
+            project.siblings.join(", ")
+        ));
+    }
+    out
+}
+
+/// The buffer state read from Neovim in one call
+#[derive(Debug, Deserialize)]
+struct BufferView {
+    path: String,
+    filetype: String,
+    first_line: usize,
+    focus: (usize, usize),
+    lines: Vec<String>,
+    #[serde(default)]
+    diagnostics: Vec<Diagnostic>,
+}
+
+const BUFFER_VIEW_LUA: &str = r#"
+local radius, use_selection = ...
+local buf = vim.api.nvim_get_current_buf()
+local first = vim.api.nvim_win_get_cursor(0)[1] - 1
+local last = first
+if use_selection then
+    local s, e = vim.fn.line("'<"), vim.fn.line("'>")
+    if s > 0 and e >= s then
+        first, last = s - 1, e - 1
+    end
+end
+local from = math.max(0, first - radius)
+local to = math.min(vim.api.nvim_buf_line_count(buf), last + 1 + radius)
+local diagnostics = {}
+for _, d in ipairs(vim.diagnostic.get(buf)) do
+    if d.lnum >= first and d.lnum <= last then
+        table.insert(diagnostics, {
+            line = d.lnum,
+            severity = vim.diagnostic.severity[d.severity] or '',
+            message = d.message,
+        })
+    end
+end
+return vim.json.encode({
+    path = vim.api.nvim_buf_get_name(buf),
+    filetype = vim.bo[buf].filetype,
+    first_line = from,
+    focus = { first, last },
+    lines = vim.api.nvim_buf_get_lines(buf, from, to, false),
+    -- An empty table would encode as an object
+    diagnostics = #diagnostics > 0 and diagnostics or nil,
+})
+"#;
+
+pub struct ContextProvider {
+    config: NvimConfig,
+    files: FileAccess,
+    /// Context window of the model requests go to, in tokens
+    context_window: Option<usize>,
+}
+
+impl ContextProvider {
+    pub fn new(config: NvimConfig, files: FileAccess) -> Self {
+        Self {
+            config,
+            files,
+            context_window: None,
+        }
+    }
+
+    /// Keep rendered context within half of `tokens`, leaving the rest for
+    /// the code, the instructions, and the answer
+    pub fn with_context_window(mut self, tokens: usize) -> Self {
+        self.context_window = Some(tokens);
+        self
+    }
+
+    /// The configured budget, capped by the model's context window
+    pub fn token_budget(&self) -> usize {
+        match self.context_window {
+            Some(window) => self.config.context_token_budget.min(window / 2),
+            None => self.config.context_token_budget,
+        }
+    }
+
+    /// Context for the current buffer; `selection` focuses the last visual
+    /// selection instead of the cursor line
+    pub async fn gather(
+        &self,
+        nvim: &Neovim<UnixStream>,
+        selection: bool,
+    ) -> Result<RequestContext> {
+        let view = nvim
+            .exec_lua(
+                BUFFER_VIEW_LUA,
+                vec![
+                    Value::from(self.config.context_lines as u64),
+                    Value::from(selection),
+                ],
+            )
+            .await?;
+        let view: BufferView = serde_json::from_str(view.as_str().unwrap_or("{}"))?;
+
+        let path = Some(PathBuf::from(&view.path)).filter(|p| p.is_absolute());
+        let project = match &path {
+            Some(path) if self.config.project_context => self.project_summary(path).await,
+            _ => None,
+        };
+
+        Ok(RequestContext {
+            path,
+            filetype: view.filetype,
+            first_line: view.first_line,
+            focus: view.focus,
+            lines: view.lines,
+            diagnostics: view.diagnostics,
+            project,
+            token_budget: self.token_budget(),
+        })
+    }
+
+    /// Summary of the project containing `file`, reading only allowed paths
+    pub async fn project_summary(&self, file: &Path) -> Option<ProjectSummary> {
+        let dir = file.parent()?;
+        let root = self.find_root(dir)?;
+        let mut summary = ProjectSummary {
+            root: root.clone(),
+            ..Default::default()
+        };
+
+        if let Ok(manifest) = self.files.read_to_string(&root.join("Cargo.toml")).await {
+            let (name, dependencies) = parse_manifest(&manifest);
+            summary.name = name;
+            summary.dependencies = dependencies;
+        }
+
+        if let Ok(entries) = self.files.list_dir(dir).await {
+            let current = file.file_stem().and_then(|s| s.to_str());
+            summary.siblings = sibling_modules(&entries, current);
+        }
+
+        Some(summary)
+    }
+
+    /// Nearest allowed ancestor with a `.git` or `Cargo.toml`
+    fn find_root(&self, start: &Path) -> Option<PathBuf> {
+        start
+            .ancestors()
+            .take_while(|dir| self.files.is_allowed(dir))
+            .find(|dir| dir.join(".git").exists() || dir.join("Cargo.toml").exists())
+            .map(Path::to_path_buf)
+    }
+}
+
+/// Package name and dependency names from a Cargo.toml
+fn parse_manifest(manifest: &str) -> (Option<String>, Vec<String>) {
+    let Ok(table) = manifest.parse::<toml::Table>() else {
+        return (None, Vec::new());
+    };
+    let name = table
+        .get("package")
+        .and_then(|p| p.get("name"))
+        .and_then(|n| n.as_str())
+        .map(str::to_string);
+    let mut dependencies: Vec<String> = ["dependencies", "workspace"]
+        .iter()
+        .filter_map(|key| match *key {
+            "workspace" => table.get("workspace")?.get("dependencies")?.as_table(),
+            key => table.get(key)?.as_table(),
+        })
+        .flat_map(|deps| deps.keys().cloned())
+        .collect();
+    dependencies.sort();
+    dependencies.dedup();
+    dependencies.truncate(MAX_LISTED);
+    (name, dependencies)
+}
+
+/// Module-like entries of a directory listing, without the current file
+fn sibling_modules(entries: &[String], current: Option<&str>) -> Vec<String> {
+    entries
+        .iter()
+        .filter_map(|name| match name.rsplit_once('.') {
+            Some((stem, "rs")) => Some(stem.to_string()),
+            Some(_) => None,
+            // Directories are submodules; dotfiles and targets are noise
+            None if !name.starts_with('.') && name != "target" => Some(name.clone()),
+            None => None,
+        })
+        .filter(|stem| Some(stem.as_str()) != current && stem != "mod")
+        .take(MAX_LISTED)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jarvis_core::file_access::FileAccessConfig;
+
+    fn context(lines: usize) -> RequestContext {
+        RequestContext {
+            path: Some(PathBuf::from("/home/me/jarvis/src/lib.rs")),
+            filetype: "rust".to_string(),
+            first_line: 10,
+            focus: (20, 20),
+            lines: (10..10 + lines)
+                .map(|i| format!("let line_{} = {};", i, i))
+                .collect(),
+            diagnostics: vec![Diagnostic {
+                line: 20,
+                severity: "ERROR".to_string(),
+                message: "mismatched types".to_string(),
+            }],
+            project: Some(ProjectSummary {
+                root: PathBuf::from("/home/me/jarvis"),
+                name: Some("jarvis".to_string()),
+                dependencies: vec!["tokio".to_string()],
+                siblings: vec!["config".to_string()],
+            }),
+            token_budget: 2000,
+        }
+    }
+
+    #[test]
+    fn test_render_keeps_focus_under_budget() {
+        let full = context(21).render();
+        assert!(full.contains("File: /home/me/jarvis/src/lib.rs (rust)"));
+        assert!(full.contains("- line 21 ERROR: mismatched types"));
+        assert!(full.contains("lines 11-31"));
+        assert!(full.contains("Dependencies: tokio"));
+
+        let tight = RequestContext {
+            token_budget: 80,
+            ..context(21)
+        };
+        let rendered = tight.render();
+        assert!(estimate_tokens(&rendered) <= 80);
+        assert!(rendered.contains("let line_20 = 20;"));
+        assert!(!rendered.contains("let line_10 = 10;"));
+    }
+
+    #[test]
+    fn test_manifest_and_siblings() {
+        let (name, deps) = parse_manifest(
+            "[package]\nname = \"jarvis-nvim\"\n\n[dependencies]\ntokio = \"1\"\nanyhow = \"1\"\n",
+        );
+        assert_eq!(name.as_deref(), Some("jarvis-nvim"));
+        assert_eq!(deps, ["anyhow", "tokio"]);
+
+        let entries: Vec<String> = [
+            "lib.rs",
+            "edits.rs",
+            "context.rs",
+            "mod.rs",
+            "README.md",
+            "lsp",
+            ".git",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        assert_eq!(
+            sibling_modules(&entries, Some("context")),
+            ["lib", "edits", "lsp"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_project_reads_respect_the_allowlist() {
+        let project = tempfile::tempdir().unwrap();
+        std::fs::create_dir(project.path().join("src")).unwrap();
+        std::fs::write(
+            project.path().join("Cargo.toml"),
+            "[package]\nname = \"demo\"\n[dependencies]\nserde = \"1\"\n",
+        )
+        .unwrap();
+        std::fs::write(project.path().join("src/main.rs"), "").unwrap();
+        std::fs::write(project.path().join("src/util.rs"), "").unwrap();
+        let file = project.path().join("src/main.rs").canonicalize().unwrap();
+
+        let allowed = ContextProvider::new(
+            NvimConfig::default(),
+            FileAccess::new(&FileAccessConfig {
+                allowed_paths: vec![project.path().display().to_string()],
+                ..Default::default()
+            }),
+        );
+        let summary = allowed.project_summary(&file).await.unwrap();
+        assert_eq!(summary.name.as_deref(), Some("demo"));
+        assert_eq!(summary.dependencies, ["serde"]);
+        assert_eq!(summary.siblings, ["util"]);
+
+        let elsewhere = tempfile::tempdir().unwrap();
+        let denied = ContextProvider::new(
+            NvimConfig::default(),
+            FileAccess::new(&FileAccessConfig {
+                allowed_paths: vec![elsewhere.path().display().to_string()],
+                ..Default::default()
+            }),
+        );
+        assert!(denied.project_summary(&file).await.is_none());
+    }
+
+    #[test]
+    fn test_budget_is_capped_by_the_context_window() {
+        let config = NvimConfig {
+            context_token_budget: 6000,
+            ..Default::default()
+        };
+        let files = || FileAccess::new(&FileAccessConfig::default());
+
+        let unbounded = ContextProvider::new(config.clone(), files());
+        assert_eq!(unbounded.token_budget(), 6000);
+        let small = ContextProvider::new(config.clone(), files()).with_context_window(4096);
+        assert_eq!(small.token_budget(), 2048);
+        let large = ContextProvider::new(config, files()).with_context_window(131072);
+        assert_eq!(large.token_budget(), 6000);
+    }
+}
@@ -0,0 +1,363 @@
+//! Edits proposed by the AI, previewed and applied hunk by hunk
+//!
+//! A response can change the current buffer in two ways: a code block
+//! annotated with the buffer's path (```` ```rust:src/main.rs ````) replaces
+//! the whole file, and SEARCH/REPLACE directives from the edit-mode prompt
+//! replace specific lines. Either way the result is diffed against the
+//! buffer and sent to Neovim as hunks that can be accepted one at a time.
+
+use anyhow::{Result, bail};
+use jarvis_core::scaffold;
+use nvim_rs::{Neovim, Value};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tokio::net::UnixStream;
+
+/// Lua side of the preview; also emitted by [`crate::Plugin::get_lua_edit_script`]
+pub const EDITS_LUA: &str = include_str!("../lua/jarvis/edits.lua");
+
+/// Above this many line pairs the diff falls back to a single hunk
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+const SEARCH_MARKER: &str = "<<<<<<< SEARCH";
+const DIVIDER_MARKER: &str = "=======";
+const REPLACE_MARKER: &str = ">>>>>>> REPLACE";
+
+/// Instructions appended to a prompt when the answer should edit the buffer
+pub const EDIT_MODE_INSTRUCTIONS: &str = "Reply only with edits to the file, each in this form:\n\
+\n\
+<<<<<<< SEARCH\n\
+exact lines currently in the file\n\
+=======\n\
+lines that replace them\n\
+>>>>>>> REPLACE\n\
+\n\
+Copy the SEARCH lines exactly, including indentation, and include enough of \
+them to be unique. Use several blocks for changes in different places.";
+
+/// Edit-mode prompt asking for `instruction` to be applied to `buffer`
+pub fn edit_prompt(instruction: &str, buffer: &BufferSnapshot) -> String {
+    format!(
+        "Edit this {} file ({}): {}\n\n{}\n\nFile content:\n```\n{}\n```",
+        buffer.filetype,
+        buffer.name,
+        instruction,
+        EDIT_MODE_INSTRUCTIONS,
+        buffer.lines.join("\n")
+    )
+}
+
+/// The buffer an edit applies to, as sent over the socket
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BufferSnapshot {
+    /// Neovim buffer number
+    pub buffer: i64,
+    /// Buffer name, normally the file path
+    pub name: String,
+    #[serde(default)]
+    pub filetype: String,
+    pub lines: Vec<String>,
+}
+
+/// One contiguous change; `start` is the 0-based line in the original buffer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EditHunk {
+    pub id: usize,
+    pub start: usize,
+    pub old_lines: Vec<String>,
+    pub new_lines: Vec<String>,
+}
+
+/// Hunks against one buffer, ready for preview
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EditProposal {
+    pub buffer: i64,
+    pub hunks: Vec<EditHunk>,
+}
+
+impl EditProposal {
+    pub fn is_empty(&self) -> bool {
+        self.hunks.is_empty()
+    }
+}
+
+/// The edit a response makes to `snapshot`, if it makes one. SEARCH/REPLACE
+/// directives win over annotated code blocks; blocks for other files are
+/// ignored.
+pub fn proposal_from_response(
+    response: &str,
+    snapshot: &BufferSnapshot,
+) -> Result<Option<EditProposal>> {
+    let new_lines = if response.contains(SEARCH_MARKER) {
+        apply_directives(&snapshot.lines, &parse_directives(response)?)?
+    } else {
+        let plan = scaffold::parse_response(response);
+        let Some(file) = plan
+            .files
+            .iter()
+            .rev()
+            .find(|f| targets_buffer(&f.path, &snapshot.name))
+        else {
+            return Ok(None);
+        };
+        file.content.lines().map(str::to_string).collect()
+    };
+
+    Ok(Some(EditProposal {
+        buffer: snapshot.buffer,
+        hunks: diff_lines(&snapshot.lines, &new_lines),
+    }))
+}
+
+/// Whether a block annotated with `path` is meant for the buffer named `name`
+fn targets_buffer(path: &Path, name: &str) -> bool {
+    !name.is_empty() && Path::new(name).ends_with(path)
+}
+
+/// A SEARCH/REPLACE pair from an edit-mode response
+#[derive(Debug, Clone, PartialEq)]
+pub struct EditDirective {
+    pub search: Vec<String>,
+    pub replace: Vec<String>,
+}
+
+pub fn parse_directives(response: &str) -> Result<Vec<EditDirective>> {
+    let mut directives = Vec::new();
+    let mut lines = response.lines();
+
+    while let Some(line) = lines.next() {
+        if line.trim() != SEARCH_MARKER {
+            continue;
+        }
+        let mut search = Vec::new();
+        let mut replace = Vec::new();
+        let mut in_replace = false;
+        let mut closed = false;
+        for inner in lines.by_ref() {
+            match inner.trim() {
+                DIVIDER_MARKER if !in_replace => in_replace = true,
+                REPLACE_MARKER if in_replace => {
+                    closed = true;
+                    break;
+                }
+                _ if in_replace => replace.push(inner.to_string()),
+                _ => search.push(inner.to_string()),
+            }
+        }
+        if !closed {
+            bail!("Unterminated SEARCH/REPLACE block in the response");
+        }
+        directives.push(EditDirective { search, replace });
+    }
+
+    Ok(directives)
+}
+
+/// Apply directives in order; each SEARCH must match lines exactly
+pub fn apply_directives(lines: &[String], directives: &[EditDirective]) -> Result<Vec<String>> {
+    let mut result = lines.to_vec();
+    for directive in directives {
+        if directive.search.is_empty() {
+            // An empty SEARCH appends, which is how models add to an empty file
+            result.extend(directive.replace.iter().cloned());
+            continue;
+        }
+        let Some(at) = result
+            .windows(directive.search.len())
+            .position(|w| w == directive.search.as_slice())
+        else {
+            bail!(
+                "SEARCH block not found in the buffer: {}",
+                directive
+                    .search
+                    .first()
+                    .map(String::as_str)
+                    .unwrap_or_default()
+            );
+        };
+        result.splice(
+            at..at + directive.search.len(),
+            directive.replace.iter().cloned(),
+        );
+    }
+    Ok(result)
+}
+
+/// Line diff grouped into hunks
+pub fn diff_lines(old: &[String], new: &[String]) -> Vec<EditHunk> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    let mut hunks = Vec::new();
+    if old_mid.is_empty() && new_mid.is_empty() {
+        return hunks;
+    }
+    if old_mid.len().saturating_mul(new_mid.len()) > MAX_DIFF_CELLS {
+        hunks.push(EditHunk {
+            id: 0,
+            start: prefix,
+            old_lines: old_mid.to_vec(),
+            new_lines: new_mid.to_vec(),
+        });
+        return hunks;
+    }
+
+    // Longest common subsequence, filled from the end so the walk goes forward
+    let (n, m) = (old_mid.len(), new_mid.len());
+    let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old_mid[i] == new_mid[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut current: Option<EditHunk> = None;
+    while i < n || j < m {
+        if i < n && j < m && old_mid[i] == new_mid[j] {
+            hunks.extend(current.take());
+            i += 1;
+            j += 1;
+            continue;
+        }
+        let hunk = current.get_or_insert_with(|| EditHunk {
+            id: 0,
+            start: prefix + i,
+            old_lines: Vec::new(),
+            new_lines: Vec::new(),
+        });
+        if j < m && (i == n || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            hunk.new_lines.push(new_mid[j].clone());
+            j += 1;
+        } else {
+            hunk.old_lines.push(old_mid[i].clone());
+            i += 1;
+        }
+    }
+    hunks.extend(current);
+
+    for (id, hunk) in hunks.iter_mut().enumerate() {
+        hunk.id = id;
+    }
+    hunks
+}
+
+/// Read the current buffer of `nvim`
+pub async fn snapshot_current(nvim: &Neovim<UnixStream>) -> Result<BufferSnapshot> {
+    let buf = nvim.get_current_buf().await?;
+    snapshot(nvim, buf.get_number().await?).await
+}
+
+pub async fn snapshot(nvim: &Neovim<UnixStream>, buffer: i64) -> Result<BufferSnapshot> {
+    let buf = nvim.get_buf_from_id(buffer).await?;
+    let filetype = nvim
+        .exec_lua("return vim.bo[...].filetype", vec![Value::from(buffer)])
+        .await?;
+    Ok(BufferSnapshot {
+        buffer,
+        name: buf.get_name().await?,
+        filetype: filetype.as_str().unwrap_or_default().to_string(),
+        lines: buf.get_lines(0, -1, false).await?,
+    })
+}
+
+/// Open the accept/reject preview for `proposal` in Neovim
+pub async fn show_preview(nvim: &Neovim<UnixStream>, proposal: &EditProposal) -> Result<()> {
+    let proposal = serde_json::to_string(proposal)?;
+    nvim.exec_lua(
+        r#"
+        local source, proposal = ...
+        if not package.loaded['jarvis.edits'] then
+            package.loaded['jarvis.edits'] = assert(load(source, "jarvis.edits"))()
+        end
+        require('jarvis.edits').preview(vim.json.decode(proposal))
+        "#,
+        vec![Value::from(EDITS_LUA), Value::from(proposal)],
+    )
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_diff_groups_changes_into_hunks() {
+        let old =
+            lines("fn main() {\n    let x = 1;\n    println!(\"{}\", x);\n}\n\nfn unused() {}");
+        let new = lines("fn main() {\n    let x = 2;\n    println!(\"{}\", x);\n}\n");
+
+        let hunks = diff_lines(&old, &new);
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(hunks[0].start, 1);
+        assert_eq!(hunks[0].old_lines, ["    let x = 1;"]);
+        assert_eq!(hunks[0].new_lines, ["    let x = 2;"]);
+        assert_eq!(hunks[1].id, 1);
+        assert_eq!(hunks[1].start, 4);
+        assert_eq!(hunks[1].old_lines, ["", "fn unused() {}"]);
+        assert!(hunks[1].new_lines.is_empty());
+
+        assert!(diff_lines(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_directives_edit_matching_lines() {
+        let buffer = BufferSnapshot {
+            buffer: 3,
+            name: "/home/me/project/src/lib.rs".to_string(),
+            filetype: "rust".to_string(),
+            lines: lines(
+                "use std::fs;\n\npub fn read() -> String {\n    fs::read_to_string(\"a\").unwrap()\n}",
+            ),
+        };
+        let response = "Handle the error:\n\n<<<<<<< SEARCH\npub fn read() -> String {\n    fs::read_to_string(\"a\").unwrap()\n=======\npub fn read() -> std::io::Result<String> {\n    fs::read_to_string(\"a\")\n>>>>>>> REPLACE\n";
+
+        let proposal = proposal_from_response(response, &buffer).unwrap().unwrap();
+        assert_eq!(proposal.buffer, 3);
+        assert_eq!(proposal.hunks.len(), 1);
+        assert_eq!(proposal.hunks[0].start, 2);
+        assert_eq!(
+            proposal.hunks[0].new_lines[0],
+            "pub fn read() -> std::io::Result<String> {"
+        );
+
+        let missing = "<<<<<<< SEARCH\nfn gone() {}\n=======\n>>>>>>> REPLACE";
+        assert!(proposal_from_response(missing, &buffer).is_err());
+    }
+
+    #[test]
+    fn test_code_block_must_target_the_buffer() {
+        let buffer = BufferSnapshot {
+            buffer: 1,
+            name: "/home/me/project/src/lib.rs".to_string(),
+            filetype: "rust".to_string(),
+            lines: lines("pub fn a() {}"),
+        };
+        let other = "```rust:src/main.rs\nfn main() {}\n```";
+        assert!(proposal_from_response(other, &buffer).unwrap().is_none());
+
+        let plain = "```rust\npub fn b() {}\n```";
+        assert!(proposal_from_response(plain, &buffer).unwrap().is_none());
+
+        let ours = "```rust:src/lib.rs\npub fn a() {}\npub fn b() {}\n```";
+        let proposal = proposal_from_response(ours, &buffer).unwrap().unwrap();
+        assert_eq!(proposal.hunks[0].start, 1);
+        assert_eq!(proposal.hunks[0].new_lines, ["pub fn b() {}"]);
+    }
+}
@@ -0,0 +1,310 @@
+//! AI explanations in LSP hovers
+//!
+//! Hovering a line with diagnostics explains each one and its most likely
+//! fix. Explanations are cached per document by line and message hash; an
+//! edit drops those on the lines it touches and moves the ones below it, so
+//! repeated hovers on unchanged code don't ask the model again. Without
+//! diagnostics, the symbol under the cursor is explained only with
+//! `[nvim] hover_symbols` on.
+
+use crate::context::{self, RequestContext};
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use tower_lsp::lsp_types::{
+    Diagnostic, DiagnosticSeverity, Position, Range, TextDocumentContentChangeEvent, Url,
+};
+
+/// An open document as the client last sent it
+#[derive(Debug, Clone, Default)]
+pub struct Document {
+    pub version: i32,
+    pub language_id: String,
+    pub lines: Vec<String>,
+}
+
+impl Document {
+    pub fn new(version: i32, language_id: &str, text: &str) -> Self {
+        Self {
+            version,
+            language_id: language_id.to_string(),
+            lines: split_lines(text),
+        }
+    }
+
+    /// Apply one `didChange` event. Positions are counted in characters,
+    /// which matches the client's UTF-16 columns outside the astral planes.
+    pub fn apply(&mut self, change: &TextDocumentContentChangeEvent) {
+        let Some(range) = change.range else {
+            self.lines = split_lines(&change.text);
+            return;
+        };
+        let mut text = self.lines.join("\n");
+        let start = self.byte_offset(range.start);
+        let end = self.byte_offset(range.end).max(start);
+        text.replace_range(start..end, &change.text);
+        self.lines = split_lines(&text);
+    }
+
+    fn byte_offset(&self, position: Position) -> usize {
+        let line = position.line as usize;
+        if line >= self.lines.len() {
+            return self
+                .lines
+                .iter()
+                .map(|l| l.len() + 1)
+                .sum::<usize>()
+                .saturating_sub(1);
+        }
+        let before: usize = self.lines[..line].iter().map(|l| l.len() + 1).sum();
+        let column = self.lines[line]
+            .char_indices()
+            .nth(position.character as usize)
+            .map(|(i, _)| i)
+            .unwrap_or(self.lines[line].len());
+        before + column
+    }
+
+    /// The identifier under `position` and its range
+    pub fn word_at(&self, position: Position) -> Option<(String, Range)> {
+        let line: Vec<char> = self.lines.get(position.line as usize)?.chars().collect();
+        let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+        let at = (position.character as usize).min(line.len());
+        let start = line[..at]
+            .iter()
+            .rposition(|c| !is_word(c))
+            .map_or(0, |i| i + 1);
+        let end = line[at..]
+            .iter()
+            .position(|c| !is_word(c))
+            .map_or(line.len(), |i| at + i);
+        if start == end {
+            return None;
+        }
+        let range = Range::new(
+            Position::new(position.line, start as u32),
+            Position::new(position.line, end as u32),
+        );
+        Some((line[start..end].iter().collect(), range))
+    }
+
+    /// Request context around `line` for the model: `radius` lines either
+    /// side and the diagnostics among them
+    pub fn request_context(
+        &self,
+        uri: &Url,
+        line: usize,
+        radius: usize,
+        diagnostics: &[Diagnostic],
+        token_budget: usize,
+    ) -> RequestContext {
+        let first_line = line.saturating_sub(radius);
+        let last_line = (line + radius).min(self.lines.len().saturating_sub(1));
+        let lines = self
+            .lines
+            .get(first_line..=last_line)
+            .map(<[String]>::to_vec)
+            .unwrap_or_default();
+        RequestContext {
+            path: uri.to_file_path().ok(),
+            filetype: self.language_id.clone(),
+            first_line,
+            focus: (line, line),
+            lines,
+            diagnostics: diagnostics
+                .iter()
+                .filter(|d| (first_line..=last_line).contains(&(d.range.start.line as usize)))
+                .map(|d| context::Diagnostic {
+                    line: d.range.start.line as usize,
+                    severity: severity_name(d.severity).to_string(),
+                    message: d.message.clone(),
+                })
+                .collect(),
+            project: None,
+            token_budget,
+        }
+    }
+}
+
+fn split_lines(text: &str) -> Vec<String> {
+    text.split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line).to_string())
+        .collect()
+}
+
+pub fn severity_name(severity: Option<DiagnosticSeverity>) -> &'static str {
+    match severity {
+        Some(DiagnosticSeverity::ERROR) => "ERROR",
+        Some(DiagnosticSeverity::WARNING) => "WARN",
+        Some(DiagnosticSeverity::INFORMATION) => "INFO",
+        Some(DiagnosticSeverity::HINT) => "HINT",
+        _ => "",
+    }
+}
+
+struct CachedExplanation {
+    line: u32,
+    key: u64,
+    text: String,
+}
+
+/// Explanations already shown, per document
+#[derive(Default)]
+pub struct HoverCache {
+    entries: HashMap<Url, Vec<CachedExplanation>>,
+}
+
+fn hash_key(subject: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    subject.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl HoverCache {
+    /// The explanation of `subject` (a diagnostic message or symbol) on `line`
+    pub fn get(&self, uri: &Url, line: u32, subject: &str) -> Option<&str> {
+        let key = hash_key(subject);
+        self.entries
+            .get(uri)?
+            .iter()
+            .find(|e| e.line == line && e.key == key)
+            .map(|e| e.text.as_str())
+    }
+
+    pub fn insert(&mut self, uri: &Url, line: u32, subject: &str, text: String) {
+        let key = hash_key(subject);
+        let entries = self.entries.entry(uri.clone()).or_default();
+        entries.retain(|e| !(e.line == line && e.key == key));
+        entries.push(CachedExplanation { line, key, text });
+    }
+
+    /// Drop explanations on the lines a change touched and move those below
+    /// it by the number of lines it added or removed
+    pub fn invalidate(&mut self, uri: &Url, change: &TextDocumentContentChangeEvent) {
+        let Some(range) = change.range else {
+            self.entries.remove(uri);
+            return;
+        };
+        let Some(entries) = self.entries.get_mut(uri) else {
+            return;
+        };
+        let (start, end) = (range.start.line, range.end.line);
+        let added = change.text.matches('\n').count() as i64;
+        let shift = added - (end - start) as i64;
+        entries.retain_mut(|e| {
+            if e.line < start {
+                true
+            } else if e.line > end {
+                e.line = (e.line as i64 + shift) as u32;
+                true
+            } else {
+                false
+            }
+        });
+    }
+
+    pub fn forget(&mut self, uri: &Url) {
+        self.entries.remove(uri);
+    }
+}
+
+/// Cut `text` to `max_chars`, preferring a word boundary, and close a code
+/// fence the cut left open so the rest of the hover still renders
+pub fn truncate_markdown(text: &str, max_chars: usize) -> String {
+    let text = text.trim();
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let cut: String = text.chars().take(max_chars).collect();
+    let cut = match cut.rfind(char::is_whitespace) {
+        Some(i) if i > cut.len() / 2 => &cut[..i],
+        _ => cut.as_str(),
+    };
+    let mut truncated = format!("{}…", cut.trim_end());
+    if truncated.matches("```").count() % 2 == 1 {
+        truncated.push_str("\n```");
+    }
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(range: Option<Range>, text: &str) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range,
+            range_length: None,
+            text: text.to_string(),
+        }
+    }
+
+    fn range(start: (u32, u32), end: (u32, u32)) -> Option<Range> {
+        Some(Range::new(
+            Position::new(start.0, start.1),
+            Position::new(end.0, end.1),
+        ))
+    }
+
+    #[test]
+    fn test_incremental_changes() {
+        let mut document = Document::new(1, "rust", "fn main() {\n    let x = 1;\n}\n");
+
+        document.apply(&change(range((1, 8), (1, 9)), "value"));
+        assert_eq!(document.lines[1], "    let value = 1;");
+
+        document.apply(&change(range((1, 18), (1, 18)), "\n    let y = 2;"));
+        assert_eq!(document.lines.len(), 5);
+        assert_eq!(document.lines[2], "    let y = 2;");
+
+        document.apply(&change(range((1, 0), (3, 0)), ""));
+        assert_eq!(document.lines, vec!["fn main() {", "}", ""]);
+
+        document.apply(&change(None, "replaced"));
+        assert_eq!(document.lines, vec!["replaced"]);
+    }
+
+    #[test]
+    fn test_word_at() {
+        let document = Document::new(1, "rust", "let total_count = items.len();");
+        let (word, range) = document.word_at(Position::new(0, 8)).unwrap();
+        assert_eq!(word, "total_count");
+        assert_eq!(range.start.character, 4);
+        assert_eq!(range.end.character, 15);
+        assert!(document.word_at(Position::new(0, 16)).is_none());
+    }
+
+    #[test]
+    fn test_cache_follows_edits() {
+        let uri = Url::parse("file:///src/main.rs").unwrap();
+        let mut cache = HoverCache::default();
+        cache.insert(&uri, 2, "unused variable", "above".to_string());
+        cache.insert(&uri, 5, "mismatched types", "edited".to_string());
+        cache.insert(&uri, 9, "unused import", "below".to_string());
+
+        assert_eq!(cache.get(&uri, 5, "mismatched types"), Some("edited"));
+        assert!(cache.get(&uri, 5, "unused import").is_none());
+
+        // Line 5 is rewritten as two lines
+        cache.invalidate(&uri, &change(range((5, 0), (5, 10)), "a\nb"));
+        assert_eq!(cache.get(&uri, 2, "unused variable"), Some("above"));
+        assert!(cache.get(&uri, 5, "mismatched types").is_none());
+        assert!(cache.get(&uri, 9, "unused import").is_none());
+        assert_eq!(cache.get(&uri, 10, "unused import"), Some("below"));
+
+        cache.invalidate(&uri, &change(None, ""));
+        assert!(cache.get(&uri, 2, "unused variable").is_none());
+    }
+
+    #[test]
+    fn test_truncate_markdown() {
+        assert_eq!(truncate_markdown("  short  ", 20), "short");
+
+        let cut = truncate_markdown("the quick brown fox jumps over", 17);
+        assert_eq!(cut, "the quick brown…");
+
+        let fenced = truncate_markdown("Use this:\n```rust\nlet x: u32 = y.into();\n```", 30);
+        assert!(fenced.ends_with("\n```"));
+        assert_eq!(fenced.matches("```").count(), 2);
+    }
+}
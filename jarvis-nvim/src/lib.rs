@@ -1,9 +1,15 @@
 pub mod ai_integration;
 pub mod chat_interface;
 pub mod code_actions;
+pub mod commands;
+pub mod context;
+pub mod edits;
+pub mod hover;
 pub mod lsp;
 pub mod nvim_client;
 pub mod plugin;
+pub mod protocol;
+pub mod rustc;
 
 pub use ai_integration::AIIntegration;
 pub use nvim_client::JarvisNvim;
@@ -1,29 +1,235 @@
 use crate::ai_integration::AIIntegration;
+use crate::hover::{self, Document, HoverCache};
+use crate::rustc::{self, RustCompiler, RustcDiagnostic};
 use anyhow::Result;
+use jarvis_core::config::NvimConfig;
+use jarvis_core::exec::SystemRunner;
+use serde::Deserialize;
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::RwLock;
 use tower_lsp::jsonrpc::Result as LspResult;
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer, LspService, Server};
 
+/// Lines either side of the hovered line sent to the model
+const HOVER_CONTEXT_LINES: usize = 10;
+
 pub struct JarvisLspServer {
     client: Client,
     ai: Arc<AIIntegration>,
+    config: NvimConfig,
+    documents: RwLock<HashMap<Url, Document>>,
+    /// Diagnostics the client forwards from its other language servers
+    diagnostics: RwLock<HashMap<Url, Vec<Diagnostic>>>,
+    hover_cache: RwLock<HoverCache>,
+    /// `cargo check` results and `rustc --explain` texts for Rust buffers
+    rustc: RustCompiler,
+    /// Off after `:JarvisToggleInline`; completion requests then get nothing
+    inline_completion: AtomicBool,
+}
+
+/// Params of `jarvis/inlineCompletion`
+#[derive(Debug, Deserialize)]
+struct InlineCompletionParams {
+    enabled: bool,
 }
 
 impl JarvisLspServer {
-    pub fn new(client: Client, ai: Arc<AIIntegration>) -> Self {
-        Self { client, ai }
+    pub fn new(client: Client, ai: Arc<AIIntegration>, config: NvimConfig) -> Self {
+        Self {
+            client,
+            ai,
+            config,
+            documents: RwLock::new(HashMap::new()),
+            diagnostics: RwLock::new(HashMap::new()),
+            hover_cache: RwLock::new(HoverCache::default()),
+            rustc: RustCompiler::new(Arc::new(SystemRunner::default())),
+            inline_completion: AtomicBool::new(true),
+        }
     }
 
-    pub async fn start(ai: Arc<AIIntegration>) -> Result<()> {
+    pub async fn start(ai: Arc<AIIntegration>, config: NvimConfig) -> Result<()> {
         let stdin = tokio::io::stdin();
         let stdout = tokio::io::stdout();
 
-        let (service, socket) = LspService::new(|client| JarvisLspServer::new(client, ai));
+        let (service, socket) =
+            LspService::build(|client| JarvisLspServer::new(client, ai, config))
+                .custom_method("jarvis/diagnostics", JarvisLspServer::diagnostics_changed)
+                .custom_method(
+                    "jarvis/inlineCompletion",
+                    JarvisLspServer::set_inline_completion,
+                )
+                .finish();
         Server::new(stdin, stdout, socket).serve(service).await;
 
         Ok(())
     }
+
+    /// `jarvis/diagnostics`: LSP gives a server no way to read other
+    /// servers' diagnostics, so the plugin forwards them on change
+    async fn diagnostics_changed(&self, params: PublishDiagnosticsParams) {
+        self.diagnostics
+            .write()
+            .await
+            .insert(params.uri, params.diagnostics);
+    }
+
+    /// `jarvis/inlineCompletion`: sent by `:JarvisToggleInline`
+    async fn set_inline_completion(&self, params: InlineCompletionParams) {
+        self.inline_completion.store(params.enabled, Ordering::Relaxed);
+    }
+
+    /// An explanation from the cache, or from the model when the document
+    /// is still at `version` once it answers
+    async fn cached_explanation<F>(
+        &self,
+        uri: &Url,
+        version: i32,
+        line: u32,
+        subject: &str,
+        explain: F,
+    ) -> Result<String>
+    where
+        F: std::future::Future<Output = Result<String>>,
+    {
+        if let Some(text) = self.hover_cache.read().await.get(uri, line, subject) {
+            return Ok(text.to_string());
+        }
+
+        let text = hover::truncate_markdown(&explain.await?, self.config.hover_max_chars);
+        let current = self.documents.read().await.get(uri).map(|d| d.version);
+        if current == Some(version) {
+            self.hover_cache
+                .write()
+                .await
+                .insert(uri, line, subject, text.clone());
+        }
+        Ok(text)
+    }
+
+    /// For a Rust buffer, what the compiler knows about `diagnostic`; `None`
+    /// when that adds nothing to the forwarded message
+    async fn rustc_context(
+        &self,
+        uri: &Url,
+        document: &Document,
+        diagnostic: &Diagnostic,
+    ) -> Option<String> {
+        if document.language_id != "rust" {
+            return None;
+        }
+        let path = uri.to_file_path().ok()?;
+        let checked = self.rustc.check(&path).await.unwrap_or_default();
+        let found = rustc::find(&checked, &path, diagnostic);
+        let code = found
+            .and_then(RustcDiagnostic::code)
+            .map(str::to_string)
+            .or_else(|| rustc::error_code(diagnostic));
+        if found.is_none() && code.is_none() {
+            return None;
+        }
+
+        let explanation = match &code {
+            Some(code) => {
+                let shipped = found
+                    .and_then(|d| d.code.as_ref())
+                    .and_then(|c| c.explanation.as_deref());
+                self.rustc.explain(code, shipped).await
+            }
+            None => None,
+        };
+        Some(rustc::describe(
+            &diagnostic.message,
+            found,
+            code.as_deref(),
+            explanation.as_deref(),
+        ))
+    }
+
+    /// Code actions applying rustc's machine-applicable suggestions, and the
+    /// diagnostics that have none
+    async fn rustc_fixes(
+        &self,
+        uri: &Url,
+        diagnostics: Vec<Diagnostic>,
+    ) -> (Vec<CodeActionOrCommand>, Vec<Diagnostic>) {
+        let document = self.documents.read().await.get(uri).cloned();
+        let (Some(document), Ok(path)) = (document, uri.to_file_path()) else {
+            return (Vec::new(), diagnostics);
+        };
+        if document.language_id != "rust" || diagnostics.is_empty() {
+            return (Vec::new(), diagnostics);
+        }
+
+        let checked = self.rustc.check(&path).await.unwrap_or_default();
+        let mut actions = Vec::new();
+        let mut unfixed = Vec::new();
+        for diagnostic in diagnostics {
+            let fix = rustc::find(&checked, &path, &diagnostic)
+                .and_then(|found| found.fix(&path, &document));
+            match fix {
+                Some(fix) => actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                    title: format!("🔧 rustc: {}", fix.title),
+                    kind: Some(CodeActionKind::QUICK_FIX),
+                    diagnostics: Some(vec![diagnostic]),
+                    edit: Some(fix.edit),
+                    command: None,
+                    is_preferred: Some(true),
+                    disabled: None,
+                    data: None,
+                })),
+                None => unfixed.push(diagnostic),
+            }
+        }
+        (actions, unfixed)
+    }
+
+    async fn explain_diagnostics(
+        &self,
+        uri: &Url,
+        document: &Document,
+        on_line: &[Diagnostic],
+        all: &[Diagnostic],
+    ) -> String {
+        let mut sections = Vec::new();
+        for diagnostic in on_line {
+            let line = diagnostic.range.start.line;
+            let context = document.request_context(
+                uri,
+                line as usize,
+                HOVER_CONTEXT_LINES,
+                all,
+                self.config.context_token_budget,
+            );
+            let explain = async {
+                let message = self
+                    .rustc_context(uri, document, diagnostic)
+                    .await
+                    .unwrap_or_else(|| diagnostic.message.clone());
+                self.ai.explain_diagnostic(&message, &context).await
+            };
+            let explanation = self
+                .cached_explanation(uri, document.version, line, &diagnostic.message, explain)
+                .await
+                .unwrap_or_else(|e| format!("_Jarvis could not explain this: {}_", e));
+
+            let source = diagnostic
+                .source
+                .as_deref()
+                .map(|s| format!(" ({})", s))
+                .unwrap_or_default();
+            sections.push(format!(
+                "**{}**{}: {}\n\n{}",
+                hover::severity_name(diagnostic.severity),
+                source,
+                diagnostic.message,
+                explanation
+            ));
+        }
+        sections.join("\n\n---\n\n")
+    }
 }
 
 #[tower_lsp::async_trait]
@@ -31,8 +237,13 @@ impl LanguageServer for JarvisLspServer {
     async fn initialize(&self, _: InitializeParams) -> LspResult<InitializeResult> {
         Ok(InitializeResult {
             capabilities: ServerCapabilities {
-                text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::INCREMENTAL,
+                text_document_sync: Some(TextDocumentSyncCapability::Options(
+                    TextDocumentSyncOptions {
+                        open_close: Some(true),
+                        change: Some(TextDocumentSyncKind::INCREMENTAL),
+                        save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+                        ..Default::default()
+                    },
                 )),
                 code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
                 hover_provider: Some(HoverProviderCapability::Simple(true)),
@@ -70,24 +281,116 @@ impl LanguageServer for JarvisLspServer {
         Ok(())
     }
 
+    async fn did_open(&self, params: DidOpenTextDocumentParams) {
+        let document = params.text_document;
+        self.documents.write().await.insert(
+            document.uri,
+            Document::new(document.version, &document.language_id, &document.text),
+        );
+    }
+
+    async fn did_change(&self, params: DidChangeTextDocumentParams) {
+        let uri = params.text_document.uri;
+        let mut documents = self.documents.write().await;
+        let Some(document) = documents.get_mut(&uri) else {
+            return;
+        };
+        let mut cache = self.hover_cache.write().await;
+        for change in &params.content_changes {
+            document.apply(change);
+            cache.invalidate(&uri, change);
+        }
+        document.version = params.text_document.version;
+    }
+
+    /// `cargo check` reads the files on disk, so its results are stale
+    async fn did_save(&self, _: DidSaveTextDocumentParams) {
+        self.rustc.invalidate();
+    }
+
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        let uri = params.text_document.uri;
+        self.documents.write().await.remove(&uri);
+        self.diagnostics.write().await.remove(&uri);
+        self.hover_cache.write().await.forget(&uri);
+    }
+
+    /// Explain the diagnostics on the hovered line, or with `hover_symbols`
+    /// on, the symbol under the cursor
     async fn hover(&self, params: HoverParams) -> LspResult<Option<Hover>> {
-        let uri = &params.text_document_position_params.text_document.uri;
-        let position = &params.text_document_position_params.position;
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+        let Some(document) = self.documents.read().await.get(&uri).cloned() else {
+            return Ok(None);
+        };
+        let all = self
+            .diagnostics
+            .read()
+            .await
+            .get(&uri)
+            .cloned()
+            .unwrap_or_default();
+        let on_line: Vec<Diagnostic> = all
+            .iter()
+            .filter(|d| d.range.start.line <= position.line && position.line <= d.range.end.line)
+            .cloned()
+            .collect();
 
-        // This would get the symbol at position and provide AI-powered explanations
-        let content = format!(
-            "Jarvis AI explanation for symbol at {}:{}",
-            position.line, position.character
-        );
+        let (value, range) = if !on_line.is_empty() {
+            let value = self
+                .explain_diagnostics(&uri, &document, &on_line, &all)
+                .await;
+            (value, Some(on_line[0].range))
+        } else if self.config.hover_symbols {
+            let Some((symbol, range)) = document.word_at(position) else {
+                return Ok(None);
+            };
+            let context = document.request_context(
+                &uri,
+                position.line as usize,
+                HOVER_CONTEXT_LINES,
+                &all,
+                self.config.context_token_budget,
+            );
+            let explanation = self
+                .cached_explanation(
+                    &uri,
+                    document.version,
+                    position.line,
+                    &format!("symbol:{}", symbol),
+                    self.ai.explain_symbol(&symbol, &context),
+                )
+                .await;
+            match explanation {
+                Ok(text) => (format!("`{}`\n\n{}", symbol, text), Some(range)),
+                Err(e) => {
+                    self.client
+                        .log_message(MessageType::WARNING, format!("Hover failed: {}", e))
+                        .await;
+                    return Ok(None);
+                }
+            }
+        } else {
+            return Ok(None);
+        };
 
         Ok(Some(Hover {
-            contents: HoverContents::Scalar(MarkedString::String(content)),
-            range: None,
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range,
         }))
     }
 
     async fn code_action(&self, params: CodeActionParams) -> LspResult<Option<CodeActionResponse>> {
-        let mut actions = Vec::new();
+        // rustc's own suggestions are applied as they are; only what they
+        // don't cover goes to the model
+        let has_diagnostics = !params.context.diagnostics.is_empty();
+        let (mut actions, unfixed) = self
+            .rustc_fixes(&params.text_document.uri, params.context.diagnostics)
+            .await;
+        let fixed_by_rustc = !actions.is_empty();
 
         // Add Jarvis-specific code actions
         actions.push(CodeActionOrCommand::CodeAction(CodeAction {
@@ -126,23 +429,25 @@ impl LanguageServer for JarvisLspServer {
             data: None,
         }));
 
-        actions.push(CodeActionOrCommand::CodeAction(CodeAction {
-            title: "🔧 Jarvis: Fix Issues".to_string(),
-            kind: Some(CodeActionKind::QUICK_FIX),
-            diagnostics: Some(params.context.diagnostics),
-            edit: None,
-            command: Some(Command {
-                title: "Fix Issues".to_string(),
-                command: "jarvis.fix".to_string(),
-                arguments: Some(vec![
-                    serde_json::to_value(&params.text_document.uri).unwrap(),
-                    serde_json::to_value(&params.range).unwrap(),
-                ]),
-            }),
-            is_preferred: Some(true),
-            disabled: None,
-            data: None,
-        }));
+        if !(has_diagnostics && unfixed.is_empty()) {
+            actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                title: "🔧 Jarvis: Fix Issues".to_string(),
+                kind: Some(CodeActionKind::QUICK_FIX),
+                diagnostics: Some(unfixed),
+                edit: None,
+                command: Some(Command {
+                    title: "Fix Issues".to_string(),
+                    command: "jarvis.fix".to_string(),
+                    arguments: Some(vec![
+                        serde_json::to_value(&params.text_document.uri).unwrap(),
+                        serde_json::to_value(&params.range).unwrap(),
+                    ]),
+                }),
+                is_preferred: Some(!fixed_by_rustc),
+                disabled: None,
+                data: None,
+            }));
+        }
 
         Ok(Some(actions))
     }
@@ -191,6 +496,10 @@ impl LanguageServer for JarvisLspServer {
     }
 
     async fn completion(&self, params: CompletionParams) -> LspResult<Option<CompletionResponse>> {
+        if !self.inline_completion.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+
         // AI-powered code completion
         let items = vec![CompletionItem {
             label: "jarvis_suggest".to_string(),
@@ -1,5 +1,7 @@
 use anyhow::Result;
 use jarvis_core::{Config, LLMRouter, MemoryStore};
+use jarvis_nvim::context::RequestContext;
+use jarvis_nvim::protocol::{self, Request, Response};
 use jarvis_nvim::{AIIntegration, JarvisNvim, lsp::JarvisLspServer};
 use std::sync::Arc;
 use tokio;
@@ -53,7 +55,7 @@ async fn start_lsp_server() -> Result<()> {
     let ai = Arc::new(AIIntegration::new(llm, memory));
 
     // Start LSP server
-    JarvisLspServer::start(ai).await?;
+    JarvisLspServer::start(ai, config.nvim).await?;
 
     Ok(())
 }
@@ -61,7 +63,8 @@ async fn start_lsp_server() -> Result<()> {
 async fn start_nvim_client(socket_path: &str) -> Result<()> {
     println!("Connecting to Neovim at: {}", socket_path);
 
-    let jarvis = JarvisNvim::new(socket_path).await?;
+    // The :Jarvis* commands dispatch to the client while it's alive
+    let _jarvis = JarvisNvim::attach(socket_path).await?;
 
     // Keep the client running
     loop {
@@ -123,30 +126,45 @@ async fn handle_client(mut stream: UnixStream, ai: Arc<AIIntegration>) -> Result
                     continue;
                 }
 
-                // Parse command
+                if command.starts_with('{') {
+                    let response = match serde_json::from_str::<Request>(command) {
+                        Ok(request) => protocol::handle_request(&ai, request).await,
+                        Err(e) => Response::Error {
+                            message: format!("Invalid request: {}", e),
+                        },
+                    };
+                    writer
+                        .write_all(serde_json::to_string(&response)?.as_bytes())
+                        .await?;
+                    writer.write_all(b"\n").await?;
+                    continue;
+                }
+
+                // Parse command; plain text commands carry no editor context
                 let parts: Vec<&str> = command.splitn(2, ' ').collect();
+                let context = RequestContext::for_language("rust");
                 let response = match parts[0] {
                     "explain" => {
                         let code = parts.get(1).unwrap_or(&"");
-                        ai.explain_code(code, "rust", "")
+                        ai.explain_code(code, &context)
                             .await
                             .unwrap_or_else(|e| format!("Error: {}", e))
                     }
                     "improve" => {
                         let code = parts.get(1).unwrap_or(&"");
-                        ai.suggest_improvements(code, "rust")
+                        ai.suggest_improvements(code, &context)
                             .await
                             .unwrap_or_else(|e| format!("Error: {}", e))
                     }
                     "fix" => {
                         let code = parts.get(1).unwrap_or(&"");
-                        ai.fix_errors(code, &[], "rust")
+                        ai.fix_errors(code, &context)
                             .await
                             .unwrap_or_else(|e| format!("Error: {}", e))
                     }
                     "generate" => {
                         let description = parts.get(1).unwrap_or(&"");
-                        ai.generate_code(description, "rust", "")
+                        ai.generate_code(description, &context)
                             .await
                             .unwrap_or_else(|e| format!("Error: {}", e))
                     }
@@ -1,23 +1,49 @@
-use anyhow::Result;
+use crate::commands::{self, Action, Completion, Invocation, RpcHandler};
+use crate::context::{ContextProvider, RequestContext};
+use crate::edits;
+use anyhow::{Context, Result, bail};
 use jarvis_agent::AgentRunner;
+use jarvis_agent::tools::SystemTools;
+use jarvis_core::chat::{ChatCommand, ChatSession};
+use jarvis_core::file_access::FileAccess;
+use jarvis_core::unit_drift;
 use jarvis_core::{LLMRouter, MemoryStore};
-use nvim_rs::{Neovim, create::tokio as create};
+use nvim_rs::{Neovim, Value as NvimValue, create::tokio as create};
 use serde_json::Value;
 use std::sync::Arc;
 use tokio::net::UnixStream;
+use tokio::sync::Mutex;
 
 pub struct JarvisNvim {
     nvim: Neovim<UnixStream>,
     agent: Arc<AgentRunner>,
     llm: Arc<LLMRouter>,
     memory: Arc<MemoryStore>,
+    context: ContextProvider,
+    tools: SystemTools,
+    /// Chat session attachments go to; started by the first one
+    chat: Mutex<Option<ChatSession>>,
 }
 
 impl JarvisNvim {
     pub async fn new(socket_path: &str) -> Result<Self> {
+        Self::connect(socket_path, RpcHandler::default()).await
+    }
+
+    /// Connect and register the `:Jarvis*` commands, which run on the
+    /// returned client for as long as it is alive
+    pub async fn attach(socket_path: &str) -> Result<Arc<Self>> {
+        let handler = RpcHandler::default();
+        let client = Arc::new(Self::connect(socket_path, handler.clone()).await?);
+        handler.bind(&client);
+        commands::register(&client.nvim).await?;
+        Ok(client)
+    }
+
+    async fn connect(socket_path: &str, handler: RpcHandler) -> Result<Self> {
         // Connect to Neovim
         let stream = UnixStream::connect(socket_path).await?;
-        let (nvim, io_handler) = create::new_unix(stream);
+        let (nvim, io_handler) = create::new_unix(stream, handler);
 
         // Spawn the IO handler
         tokio::spawn(io_handler);
@@ -26,30 +52,116 @@ impl JarvisNvim {
         let config = jarvis_core::Config::load(None).await?;
         let memory = Arc::new(MemoryStore::new(&config.database_path).await?);
         let llm = Arc::new(LLMRouter::new(&config).await?);
-        let agent = Arc::new(AgentRunner::new(memory.clone(), llm.clone()).await?);
+        let agent = Arc::new(
+            AgentRunner::new(memory.clone(), llm.clone())
+                .await?
+                .with_file_access(FileAccess::new(&config.files)),
+        );
+        let context = ContextProvider::new(config.nvim.clone(), FileAccess::new(&config.files))
+            .with_context_window(llm.context_window());
 
         Ok(Self {
             nvim,
             agent,
             llm,
             memory,
+            context,
+            tools: SystemTools::new().await?,
+            chat: Mutex::new(None),
         })
     }
 
+    /// Run one of [`commands::COMMANDS`]
+    pub async fn run_command(&self, invocation: Invocation) -> Result<()> {
+        let Some(command) = commands::find(&invocation.name) else {
+            bail!("Unknown command :{}", invocation.name);
+        };
+        match command.action {
+            Action::Explain => self.explain_lines(invocation.lines()).await,
+            Action::Improve => self.suggest_improvements().await,
+            Action::Fix => self.fix_errors().await,
+            Action::Chat => self.chat_mode().await,
+            Action::Generate => self.generate_code(&invocation.args).await,
+            Action::Edit => self.edit_buffer(&invocation.args).await,
+            Action::Diagnose => match invocation.arg() {
+                Some(service) => self.diagnose_service(service).await,
+                None => bail!("Usage: :{} <service>", command.name),
+            },
+            Action::Containers => self.show_containers(invocation.arg()).await,
+            Action::ToggleInline => self.toggle_inline_completion().await,
+            Action::Attach => {
+                self.chat_command(&format!("/attach {}", invocation.args))
+                    .await
+            }
+            Action::Attachments => self.chat_command("/attachments").await,
+            Action::Detach => {
+                self.chat_command(&format!("/detach {}", invocation.args))
+                    .await
+            }
+        }
+    }
+
+    /// Argument candidates for a command, filtered by what's typed so far
+    pub async fn complete(&self, kind: Completion, arglead: &str) -> Result<Vec<String>> {
+        let candidates = match kind {
+            Completion::Services => self.tools.list_services().await?,
+            Completion::Containers => self.tools.list_containers().await?,
+            Completion::Attachments => match self.chat.lock().await.as_ref() {
+                Some(session) => session
+                    .attachments()
+                    .iter()
+                    .map(|a| a.name.clone())
+                    .collect(),
+                None => Vec::new(),
+            },
+        };
+        Ok(commands::matching(candidates, arglead))
+    }
+
+    /// Show a failed command's error in Neovim
+    pub async fn notify_error(&self, err: &anyhow::Error) {
+        let message = format!("Jarvis: {:#}", err);
+        if let Err(e) = self
+            .nvim
+            .exec_lua(
+                "vim.notify(..., vim.log.levels.ERROR)",
+                vec![NvimValue::from(message.as_str())],
+            )
+            .await
+        {
+            tracing::warn!("Could not show error in Neovim ({}): {}", e, message);
+        }
+    }
+
+    /// Buffer and project context for a request, focused on the last visual
+    /// selection when `selection` is set
+    pub async fn request_context(&self, selection: bool) -> Result<RequestContext> {
+        self.context.gather(&self.nvim, selection).await
+    }
+
     pub async fn explain_selection(&self) -> Result<()> {
-        // Get current visual selection
-        let selection = self.get_visual_selection().await?;
+        self.explain_lines(None).await
+    }
+
+    /// Explain lines `first..=last` (1-based) of the current buffer, or the
+    /// last visual selection without a range
+    pub async fn explain_lines(&self, range: Option<(i64, i64)>) -> Result<()> {
+        let selection = match range {
+            Some((first, last)) => self.get_lines(first, last).await?,
+            None => self.get_visual_selection().await?,
+        };
         if selection.is_empty() {
             return Ok(());
         }
 
-        // Get file context
-        let context = self.get_file_context().await?;
+        let context = self.request_context(true).await?;
 
         // Generate explanation
         let prompt = format!(
-            "Explain this code selection:\n\n```\n{}\n```\n\nFile context:\n{}",
-            selection, context
+            "Explain this code selection:\n\n```{}\n{}\n```\n\nContext:\n{}",
+            context.language(),
+            selection,
+            context.render()
         );
 
         let response = self.llm.generate(&prompt, None).await?;
@@ -63,11 +175,13 @@ impl JarvisNvim {
 
     pub async fn suggest_improvements(&self) -> Result<()> {
         let selection = self.get_visual_selection().await?;
-        let context = self.get_file_context().await?;
+        let context = self.request_context(true).await?;
 
         let prompt = format!(
-            "Suggest improvements for this code:\n\n```\n{}\n```\n\nContext:\n{}",
-            selection, context
+            "Suggest improvements for this code:\n\n```{}\n{}\n```\n\nContext:\n{}",
+            context.language(),
+            selection,
+            context.render()
         );
 
         let response = self.llm.generate(&prompt, None).await?;
@@ -78,9 +192,9 @@ impl JarvisNvim {
     }
 
     pub async fn fix_errors(&self) -> Result<()> {
-        // Get diagnostics from LSP
-        let diagnostics = self.get_diagnostics().await?;
-        if diagnostics.is_empty() {
+        // LSP diagnostics on the current line
+        let context = self.request_context(false).await?;
+        if context.diagnostics.is_empty() {
             self.nvim
                 .echo(&[("No errors found!", None)], false, &Value::Null)
                 .await?;
@@ -91,9 +205,9 @@ impl JarvisNvim {
         let file_content = self.get_buffer_content().await?;
 
         let prompt = format!(
-            "Fix these errors in the code:\n\nErrors:\n{}\n\nCurrent line: {}\n\nFile content:\n{}",
-            diagnostics.join("\n"),
+            "Fix the errors listed in the context:\n\nCurrent line: {}\n\nContext:\n{}\n\nFile content:\n{}",
             current_line,
+            context.render(),
             file_content
         );
 
@@ -119,6 +233,7 @@ impl JarvisNvim {
             "==================".to_string(),
             "".to_string(),
             "Type your questions and press <leader>js to send".to_string(),
+            "Attach context with :JarvisAttach file <path> | probe <name> | last-op".to_string(),
             "".to_string(),
             "> ".to_string(),
         ];
@@ -130,13 +245,27 @@ impl JarvisNvim {
         Ok(())
     }
 
+    /// Run a chat attachment command (`/attach`, `/attachments`, `/detach`)
+    /// against the editor's chat session
+    async fn chat_command(&self, input: &str) -> Result<()> {
+        let command = ChatCommand::parse(input).context("Not a chat command")??;
+        let mut chat = self.chat.lock().await;
+        let session = match chat.take() {
+            Some(session) => chat.insert(session),
+            None => chat.insert(ChatSession::start(&self.memory).await?),
+        };
+        let reply = self.agent.chat_command(session, command).await?;
+        self.show_floating_window("Jarvis Chat", &reply).await
+    }
+
     pub async fn generate_code(&self, description: &str) -> Result<()> {
-        let file_type = self.get_current_filetype().await?;
-        let context = self.get_file_context().await?;
+        let context = self.request_context(false).await?;
 
         let prompt = format!(
             "Generate {} code for: {}\n\nContext:\n{}",
-            file_type, description, context
+            context.language(),
+            description,
+            context.render()
         );
 
         let response = self.llm.generate(&prompt, None).await?;
@@ -147,6 +276,61 @@ impl JarvisNvim {
         Ok(())
     }
 
+    /// Diagnose a service from its status, logs, and unit drift
+    pub async fn diagnose_service(&self, service: &str) -> Result<()> {
+        let unit = unit_drift::unit_name(service);
+        let info = self.tools.diagnose(&unit).await;
+        if info.trim().is_empty() {
+            bail!("No diagnostics for {}", unit);
+        }
+
+        let prompt = format!(
+            "Diagnose this systemd service: {}\n\nDiagnostic Information:\n{}",
+            unit, info
+        );
+        let response = self.llm.generate(&prompt, None).await?;
+        self.show_floating_window(&format!("Jarvis Diagnosis: {}", unit), &response)
+            .await
+    }
+
+    /// The container list, or one container's state and recent logs
+    pub async fn show_containers(&self, name: Option<&str>) -> Result<()> {
+        let report = self.tools.describe_containers(name).await?;
+        self.show_floating_window(name.unwrap_or("Jarvis Containers"), &report)
+            .await
+    }
+
+    /// Flip `vim.g.jarvis_inline_completion` and tell the Jarvis language
+    /// server, which stops offering completions while it is off
+    pub async fn toggle_inline_completion(&self) -> Result<()> {
+        self.nvim
+            .exec_lua(
+                r#"
+                local enabled = vim.g.jarvis_inline_completion == false
+                vim.g.jarvis_inline_completion = enabled
+                for _, client in ipairs(vim.lsp.get_clients({ name = 'jarvis' })) do
+                    client.notify('jarvis/inlineCompletion', { enabled = enabled })
+                end
+                vim.notify('Jarvis inline completion ' .. (enabled and 'on' or 'off'))
+                "#,
+                vec![],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Ask for an edit to the current buffer and preview it hunk by hunk
+    pub async fn edit_buffer(&self, instruction: &str) -> Result<()> {
+        let snapshot = edits::snapshot_current(&self.nvim).await?;
+        let prompt = edits::edit_prompt(instruction, &snapshot);
+
+        let response = self.llm.generate(&prompt, None).await?;
+        match edits::proposal_from_response(&response, &snapshot)? {
+            Some(proposal) => edits::show_preview(&self.nvim, &proposal).await,
+            None => self.show_floating_window("Jarvis Edit", &response).await,
+        }
+    }
+
     async fn get_visual_selection(&self) -> Result<String> {
         // Get visual selection using Neovim API
         let result = self.nvim.eval("getline(\"'<\", \"'>\")").await?;
@@ -161,17 +345,14 @@ impl JarvisNvim {
         }
     }
 
-    async fn get_file_context(&self) -> Result<String> {
-        let filename = self.nvim.eval("expand('%:t')").await?;
-        let filetype = self.nvim.eval("&filetype").await?;
-        let line_count = self.nvim.eval("line('$')").await?;
-
-        Ok(format!(
-            "File: {}\nType: {}\nLines: {}",
-            filename.as_str().unwrap_or("unknown"),
-            filetype.as_str().unwrap_or("unknown"),
-            line_count.as_i64().unwrap_or(0)
-        ))
+    async fn get_lines(&self, first: i64, last: i64) -> Result<String> {
+        let lines = self
+            .nvim
+            .get_current_buf()
+            .await?
+            .get_lines(first - 1, last, false)
+            .await?;
+        Ok(lines.join("\n"))
     }
 
     async fn get_current_line(&self) -> Result<String> {
@@ -184,17 +365,6 @@ impl JarvisNvim {
         Ok(lines.join("\n"))
     }
 
-    async fn get_current_filetype(&self) -> Result<String> {
-        let filetype = self.nvim.eval("&filetype").await?;
-        Ok(filetype.as_str().unwrap_or("text").to_string())
-    }
-
-    async fn get_diagnostics(&self) -> Result<Vec<String>> {
-        // This would integrate with LSP diagnostics
-        // For now, return empty vector
-        Ok(vec![])
-    }
-
     async fn show_floating_window(&self, title: &str, content: &str) -> Result<()> {
         // Create floating window with content
         let lua_code = format!(
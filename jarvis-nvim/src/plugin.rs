@@ -1,6 +1,7 @@
+use crate::commands::{self, Completion, Invocation};
 use crate::{AIIntegration, JarvisNvim};
 use anyhow::Result;
-use mlua::{Function, Lua, Table, UserData, UserDataMethods};
+use mlua::{Function, Lua, LuaSerdeExt, Table, UserData, UserDataMethods};
 use std::sync::Arc;
 
 pub struct Plugin {
@@ -67,30 +68,81 @@ impl Plugin {
         })?;
         jarvis_module.set("generate", generate_fn)?;
 
-        // Register AI functions
-        let ai_clone = self.ai.clone();
-        let ai_explain_fn = lua.create_async_function(
-            move |_, (code, language, context): (String, String, String)| {
-                let ai = ai_clone.clone();
+        let jarvis_clone = self.jarvis.clone();
+        let edit_fn = lua.create_async_function(move |_, instruction: String| {
+            let jarvis = jarvis_clone.clone();
+            async move {
+                jarvis
+                    .edit_buffer(&instruction)
+                    .await
+                    .map_err(mlua::Error::external)
+            }
+        })?;
+        jarvis_module.set("edit", edit_fn)?;
+
+        // The command palette, for require('jarvis.commands').register
+        jarvis_module.set("commands", lua.to_value(commands::COMMANDS)?)?;
+
+        let jarvis_clone = self.jarvis.clone();
+        let run_command_fn = lua.create_async_function(move |lua, invocation: mlua::Value| {
+            let jarvis = jarvis_clone.clone();
+            let invocation = lua.from_value::<Invocation>(invocation);
+            async move {
+                jarvis
+                    .run_command(invocation?)
+                    .await
+                    .map_err(mlua::Error::external)
+            }
+        })?;
+        jarvis_module.set("run_command", run_command_fn)?;
+
+        let jarvis_clone = self.jarvis.clone();
+        let complete_fn =
+            lua.create_async_function(move |lua, (kind, arglead): (mlua::Value, String)| {
+                let jarvis = jarvis_clone.clone();
+                let kind = lua.from_value::<Completion>(kind);
                 async move {
-                    ai.explain_code(&code, &language, &context)
+                    jarvis
+                        .complete(kind?, &arglead)
                         .await
                         .map_err(mlua::Error::external)
                 }
-            },
-        )?;
+            })?;
+        jarvis_module.set("complete", complete_fn)?;
+
+        // Register AI functions; context comes from the current buffer
+        let ai_clone = self.ai.clone();
+        let jarvis_clone = self.jarvis.clone();
+        let ai_explain_fn = lua.create_async_function(move |_, code: String| {
+            let ai = ai_clone.clone();
+            let jarvis = jarvis_clone.clone();
+            async move {
+                let context = jarvis
+                    .request_context(false)
+                    .await
+                    .map_err(mlua::Error::external)?;
+                ai.explain_code(&code, &context)
+                    .await
+                    .map_err(mlua::Error::external)
+            }
+        })?;
         jarvis_module.set("ai_explain", ai_explain_fn)?;
 
         let ai_clone = self.ai.clone();
-        let ai_improve_fn =
-            lua.create_async_function(move |_, (code, language): (String, String)| {
-                let ai = ai_clone.clone();
-                async move {
-                    ai.suggest_improvements(&code, &language)
-                        .await
-                        .map_err(mlua::Error::external)
-                }
-            })?;
+        let jarvis_clone = self.jarvis.clone();
+        let ai_improve_fn = lua.create_async_function(move |_, code: String| {
+            let ai = ai_clone.clone();
+            let jarvis = jarvis_clone.clone();
+            async move {
+                let context = jarvis
+                    .request_context(false)
+                    .await
+                    .map_err(mlua::Error::external)?;
+                ai.suggest_improvements(&code, &context)
+                    .await
+                    .map_err(mlua::Error::external)
+            }
+        })?;
         jarvis_module.set("ai_improve", ai_improve_fn)?;
 
         // Register the module globally
@@ -99,38 +151,25 @@ impl Plugin {
         Ok(())
     }
 
+    /// `require('jarvis.edits')`: the accept/reject preview for proposed edits
+    pub fn get_lua_edit_script(&self) -> &'static str {
+        crate::edits::EDITS_LUA
+    }
+
     pub fn get_lua_setup_script(&self) -> &'static str {
         r#"
 -- Jarvis Neovim Plugin Setup
 local jarvis = require('jarvis')
 
--- Create user commands
-vim.api.nvim_create_user_command('JarvisExplain', function()
-    jarvis.explain()
-end, { desc = 'Explain selected code with Jarvis' })
-
-vim.api.nvim_create_user_command('JarvisImprove', function()
-    jarvis.improve()
-end, { desc = 'Get improvement suggestions from Jarvis' })
-
-vim.api.nvim_create_user_command('JarvisFix', function()
-    jarvis.fix()
-end, { desc = 'Fix errors with Jarvis' })
-
-vim.api.nvim_create_user_command('JarvisChat', function()
-    jarvis.chat()
-end, { desc = 'Open Jarvis chat interface' })
-
-vim.api.nvim_create_user_command('JarvisGenerate', function(opts)
-    jarvis.generate(opts.args)
-end, { nargs = '*', desc = 'Generate code with Jarvis' })
+-- Create user commands from the table in commands.rs
+require('jarvis.commands').register(jarvis.commands, jarvis.run_command, jarvis.complete)
 
 -- Default keymaps
 local function setup_keymaps()
     local opts = { noremap = true, silent = true }
     
     -- Visual mode mappings
-    vim.keymap.set('v', '<leader>je', '<cmd>JarvisExplain<cr>', vim.tbl_extend('force', opts, { desc = 'Jarvis: Explain selection' }))
+    vim.keymap.set('v', '<leader>je', ':JarvisExplain<cr>', vim.tbl_extend('force', opts, { desc = 'Jarvis: Explain selection' }))
     vim.keymap.set('v', '<leader>ji', '<cmd>JarvisImprove<cr>', vim.tbl_extend('force', opts, { desc = 'Jarvis: Improve selection' }))
     vim.keymap.set('v', '<leader>jf', '<cmd>JarvisFix<cr>', vim.tbl_extend('force', opts, { desc = 'Jarvis: Fix selection' }))
     
@@ -142,12 +181,17 @@ local function setup_keymaps()
             vim.cmd('JarvisGenerate ' .. input)
         end
     end, vim.tbl_extend('force', opts, { desc = 'Jarvis: Generate code' }))
+    vim.keymap.set('n', '<leader>jE', function()
+        local input = vim.fn.input('Edit this buffer: ')
+        if input ~= '' then
+            vim.cmd('JarvisEdit ' .. input)
+        end
+    end, vim.tbl_extend('force', opts, { desc = 'Jarvis: Edit buffer' }))
     
     -- Line-specific mappings
     vim.keymap.set('n', '<leader>jl', function()
-        -- Select current line and explain
-        vim.cmd('normal! V')
-        vim.cmd('JarvisExplain')
+        -- Explain the current line as a one-line range
+        vim.cmd('.JarvisExplain')
     end, vim.tbl_extend('force', opts, { desc = 'Jarvis: Explain current line' }))
 end
 
@@ -172,6 +216,34 @@ local function setup(user_config)
     -- Auto commands
     vim.api.nvim_create_augroup('Jarvis', { clear = true })
     
+    -- Forward diagnostics to the Jarvis language server so hovers can explain them
+    vim.api.nvim_create_autocmd('DiagnosticChanged', {
+        group = 'Jarvis',
+        callback = function(args)
+            local clients = vim.lsp.get_clients({ name = 'jarvis', bufnr = args.buf })
+            if #clients == 0 then
+                return
+            end
+            local diagnostics = vim.tbl_map(function(d)
+                return {
+                    range = {
+                        start = { line = d.lnum, character = d.col },
+                        ['end'] = { line = d.end_lnum or d.lnum, character = d.end_col or d.col },
+                    },
+                    severity = d.severity,
+                    message = d.message,
+                    source = d.source,
+                }
+            end, vim.diagnostic.get(args.buf))
+            for _, client in ipairs(clients) do
+                client.notify('jarvis/diagnostics', {
+                    uri = vim.uri_from_bufnr(args.buf),
+                    diagnostics = diagnostics,
+                })
+            end
+        end
+    })
+    
     -- Show Jarvis status in statusline
     if user_config.statusline ~= false then
         vim.api.nvim_create_autocmd('BufEnter', {
@@ -225,5 +297,12 @@ impl UserData for Plugin {
                 .await
                 .map_err(mlua::Error::external)
         });
+
+        methods.add_async_method("edit", |_, this, instruction: String| async move {
+            this.jarvis
+                .edit_buffer(&instruction)
+                .await
+                .map_err(mlua::Error::external)
+        });
     }
 }
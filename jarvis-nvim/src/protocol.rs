@@ -0,0 +1,86 @@
+//! Structured messages on the jarvis-nvim socket
+//!
+//! A line that starts with `{` is a JSON [`Request`] and is answered with a
+//! single JSON [`Response`] line. Other lines are the plain text commands
+//! (`explain <code>`, `chat <message>`, ...) answered with text and `---`.
+
+use crate::AIIntegration;
+use crate::edits::{self, BufferSnapshot, EditProposal};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    /// Chat; with `buffer`, a code block annotated with its path comes back
+    /// as an edit
+    Chat {
+        message: String,
+        #[serde(default)]
+        buffer: Option<BufferSnapshot>,
+    },
+    /// Edit mode: change `buffer` as `instruction` says
+    Edit {
+        instruction: String,
+        buffer: BufferSnapshot,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    Chat {
+        text: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        edit: Option<EditProposal>,
+    },
+    /// Hunks for `require('jarvis.edits').preview`
+    Edit {
+        proposal: EditProposal,
+    },
+    Error {
+        message: String,
+    },
+}
+
+impl Response {
+    fn error(err: anyhow::Error) -> Self {
+        Response::Error {
+            message: format!("{:#}", err),
+        }
+    }
+}
+
+pub async fn handle_request(ai: &AIIntegration, request: Request) -> Response {
+    match request {
+        Request::Chat { message, buffer } => {
+            let text = match ai.send_message(&message, Some("Neovim chat session")).await {
+                Ok(text) => text,
+                Err(e) => return Response::error(e),
+            };
+            // A chat answer that isn't an edit is still a good answer
+            let edit = buffer.and_then(|buffer| {
+                edits::proposal_from_response(&text, &buffer)
+                    .ok()
+                    .flatten()
+                    .filter(|p| !p.is_empty())
+            });
+            Response::Chat { text, edit }
+        }
+        Request::Edit {
+            instruction,
+            buffer,
+        } => {
+            let text = match ai.edit_buffer(&instruction, &buffer).await {
+                Ok(text) => text,
+                Err(e) => return Response::error(e),
+            };
+            match edits::proposal_from_response(&text, &buffer) {
+                Ok(Some(proposal)) => Response::Edit { proposal },
+                Ok(None) => Response::Error {
+                    message: format!("No edit for this buffer in the response:\n{}", text),
+                },
+                Err(e) => Response::error(e),
+            }
+        }
+    }
+}
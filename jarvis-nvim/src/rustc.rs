@@ -0,0 +1,662 @@
+//! Compiler knowledge for Rust diagnostics
+//!
+//! rustc knows more about its own errors than a model reading one line of
+//! message. For Rust buffers, [`RustCompiler`] runs `cargo check` with JSON
+//! output for the package and matches its diagnostics to the ones the client
+//! forwards, so a hover can send the model the error exactly as rustc
+//! renders it, the relevant part of `rustc --explain`, and a hint when the
+//! error sits inside a `#[derive]` expansion. Machine-applicable suggestions
+//! become workspace edits for the fix code action without asking the model
+//! at all. Check results are kept until a file is saved; explanations are
+//! kept per error code, since they only change with the toolchain.
+
+use crate::hover::{self, Document};
+use jarvis_core::exec::{CommandRunner, RunOptions};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tower_lsp::lsp_types::{
+    Diagnostic, NumberOrString, Position, Range, TextEdit, Url, WorkspaceEdit,
+};
+
+/// Longest `cargo check` a hover or code action waits for
+const CHECK_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Longest excerpt of `rustc --explain` sent to the model
+const EXPLAIN_EXCERPT_CHARS: usize = 1200;
+
+/// One diagnostic from rustc's JSON output
+#[derive(Debug, Clone, Deserialize)]
+pub struct RustcDiagnostic {
+    pub message: String,
+    #[serde(default)]
+    pub code: Option<RustcCode>,
+    #[serde(default)]
+    pub level: String,
+    #[serde(default)]
+    pub spans: Vec<RustcSpan>,
+    #[serde(default)]
+    pub children: Vec<RustcDiagnostic>,
+    /// The diagnostic as rustc prints it, span labels and all
+    #[serde(default)]
+    pub rendered: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RustcCode {
+    pub code: String,
+    /// The `--explain` text, which newer compilers ship with the diagnostic
+    #[serde(default)]
+    pub explanation: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RustcSpan {
+    /// Relative to the workspace root `cargo` ran in
+    pub file_name: String,
+    /// 1-based, like the columns
+    pub line_start: u32,
+    pub line_end: u32,
+    pub column_start: u32,
+    pub column_end: u32,
+    #[serde(default)]
+    pub is_primary: bool,
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub suggested_replacement: Option<String>,
+    #[serde(default)]
+    pub suggestion_applicability: Option<Applicability>,
+    /// Source lines the span covers, as rustc read them
+    #[serde(default)]
+    pub text: Vec<SpanLine>,
+    #[serde(default)]
+    pub expansion: Option<Box<SpanExpansion>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpanLine {
+    pub text: String,
+}
+
+/// The macro a span was produced by
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpanExpansion {
+    /// Where the macro was invoked
+    pub span: RustcSpan,
+    /// `#[derive(Clone)]`, `vec!`, ...
+    pub macro_decl_name: String,
+}
+
+/// How sure rustc is that a suggestion is right
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Applicability {
+    MachineApplicable,
+    MaybeIncorrect,
+    HasPlaceholders,
+    Unspecified,
+}
+
+/// A machine-applicable suggestion, ready to apply
+#[derive(Debug, Clone, PartialEq)]
+pub struct RustcFix {
+    /// rustc's wording, e.g. "consider cloning the value"
+    pub title: String,
+    pub edit: WorkspaceEdit,
+}
+
+impl RustcSpan {
+    fn range(&self) -> Range {
+        // rustc counts columns in characters, as Document::apply does
+        Range::new(
+            Position::new(self.line_start - 1, self.column_start - 1),
+            Position::new(self.line_end - 1, self.column_end - 1),
+        )
+    }
+
+    /// Whether `document` still has the source rustc saw under this span
+    fn matches_source(&self, document: &Document) -> bool {
+        let first = (self.line_start - 1) as usize;
+        self.text
+            .iter()
+            .enumerate()
+            .all(|(i, line)| document.lines.get(first + i) == Some(&line.text))
+    }
+}
+
+impl RustcDiagnostic {
+    pub fn code(&self) -> Option<&str> {
+        self.code.as_ref().map(|code| code.code.as_str())
+    }
+
+    pub fn primary_span(&self) -> Option<&RustcSpan> {
+        self.spans.iter().find(|span| span.is_primary)
+    }
+
+    /// Whether this is the diagnostic the client reported as `diagnostic`
+    /// in `path`: same line and message, and the same code if it has one
+    pub fn matches(&self, path: &Path, diagnostic: &Diagnostic) -> bool {
+        let Some(span) = self.primary_span() else {
+            return false;
+        };
+        if let Some(code) = error_code(diagnostic)
+            && self.code() != Some(code.as_str())
+        {
+            return false;
+        }
+        path.ends_with(&span.file_name)
+            && span.line_start == diagnostic.range.start.line + 1
+            && diagnostic.message.starts_with(&self.message)
+    }
+
+    /// A hint when the error is reported inside macro-generated code, which
+    /// points at a line the user never wrote
+    pub fn macro_hint(&self) -> Option<String> {
+        let mut expansion = self.primary_span()?.expansion.as_deref()?;
+        // The outermost expansion is the one written in the source
+        while let Some(outer) = expansion.span.expansion.as_deref() {
+            expansion = outer;
+        }
+        let name = &expansion.macro_decl_name;
+        if name.starts_with("#[derive(") {
+            Some(format!(
+                "This error is inside the code `{}` generates. The derive itself is rarely wrong: a field's type is missing the trait being derived, so implement or derive it for that type, or implement the trait by hand.",
+                name
+            ))
+        } else {
+            Some(format!(
+                "This error is inside the expansion of `{}`; the cause is usually in what is passed to the macro, not in the macro itself.",
+                name
+            ))
+        }
+    }
+
+    /// The first suggestion rustc marks machine-applicable, as an edit,
+    /// provided the lines it touches in `path` are unchanged in `document`
+    pub fn fix(&self, path: &Path, document: &Document) -> Option<RustcFix> {
+        let base = workspace_base(path, &self.primary_span()?.file_name)?;
+        self.children.iter().find_map(|child| {
+            let spans: Vec<&RustcSpan> = child
+                .spans
+                .iter()
+                .filter(|span| span.suggested_replacement.is_some())
+                .collect();
+            if spans.is_empty()
+                || spans.iter().any(|span| {
+                    span.suggestion_applicability != Some(Applicability::MachineApplicable)
+                })
+            {
+                return None;
+            }
+
+            let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+            for span in spans {
+                let file = base.join(&span.file_name);
+                if file == path && !span.matches_source(document) {
+                    return None;
+                }
+                let uri = Url::from_file_path(&file).ok()?;
+                changes.entry(uri).or_default().push(TextEdit::new(
+                    span.range(),
+                    span.suggested_replacement.clone()?,
+                ));
+            }
+            Some(RustcFix {
+                title: child.message.clone(),
+                edit: WorkspaceEdit {
+                    changes: Some(changes),
+                    ..Default::default()
+                },
+            })
+        })
+    }
+}
+
+/// The directory rustc's relative `file_name` resolves against, found by
+/// stripping it from the end of `path`
+fn workspace_base(path: &Path, file_name: &str) -> Option<PathBuf> {
+    path.ancestors()
+        .find(|dir| dir.join(file_name) == path)
+        .map(Path::to_path_buf)
+}
+
+/// Diagnostics from `cargo check --message-format=json` output, or raw
+/// `rustc --error-format=json` output. Summaries without a span ("aborting
+/// due to 2 previous errors") are dropped.
+pub fn parse_messages(output: &str) -> Vec<RustcDiagnostic> {
+    output
+        .lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .filter_map(|value| {
+            let message = match value.get("reason").and_then(|r| r.as_str()) {
+                Some("compiler-message") => value.get("message")?.clone(),
+                Some(_) => return None,
+                None => value,
+            };
+            serde_json::from_value::<RustcDiagnostic>(message).ok()
+        })
+        .filter(|diagnostic| !diagnostic.spans.is_empty())
+        .collect()
+}
+
+/// The rustc error code of a forwarded diagnostic, from its `code` field or
+/// an `error[E0382]` in its message
+pub fn error_code(diagnostic: &Diagnostic) -> Option<String> {
+    if let Some(NumberOrString::String(code)) = &diagnostic.code
+        && is_error_code(code)
+    {
+        return Some(code.clone());
+    }
+    diagnostic
+        .message
+        .match_indices('E')
+        .map(|(i, _)| diagnostic.message.get(i..i + 5).unwrap_or_default())
+        .find(|candidate| is_error_code(candidate))
+        .map(str::to_string)
+}
+
+fn is_error_code(code: &str) -> bool {
+    code.len() == 5 && code.starts_with('E') && code[1..].bytes().all(|b| b.is_ascii_digit())
+}
+
+/// The part of an `--explain` text worth sending with one error: the
+/// summary, the first erroneous example, and the paragraph that explains it
+pub fn explain_excerpt(explanation: &str) -> String {
+    let mut lines = Vec::new();
+    let mut in_fence = false;
+    let mut examples = 0;
+    let mut after_example = false;
+    for line in explanation.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+            if in_fence {
+                // Fences are tagged `compile_fail,E0382`; the model only
+                // needs to know they are Rust
+                lines.push("```rust");
+                continue;
+            }
+            examples += 1;
+        } else if !in_fence && examples > 0 {
+            if line.trim().is_empty() {
+                if after_example {
+                    break;
+                }
+            } else {
+                after_example = true;
+            }
+        }
+        lines.push(line);
+    }
+    hover::truncate_markdown(&lines.join("\n"), EXPLAIN_EXCERPT_CHARS)
+}
+
+/// What the model is asked to explain for a Rust diagnostic: rustc's
+/// rendering when the check found it, a macro hint, and the `--explain`
+/// excerpt for its code
+pub fn describe(
+    message: &str,
+    found: Option<&RustcDiagnostic>,
+    code: Option<&str>,
+    explanation: Option<&str>,
+) -> String {
+    let mut sections = Vec::new();
+    match found.and_then(|d| d.rendered.as_deref()) {
+        Some(rendered) => sections.push(format!("```text\n{}\n```", rendered.trim_end())),
+        None => sections.push(message.to_string()),
+    }
+    if let Some(hint) = found.and_then(RustcDiagnostic::macro_hint) {
+        sections.push(format!("Hint: {}", hint));
+    }
+    if let (Some(code), Some(explanation)) = (code, explanation) {
+        sections.push(format!(
+            "From `rustc --explain {}`:\n\n{}",
+            code,
+            explain_excerpt(explanation)
+        ));
+    }
+    sections.join("\n\n")
+}
+
+/// Nearest ancestor of `file` with a `Cargo.toml`
+fn manifest_for(file: &Path) -> Option<PathBuf> {
+    file.ancestors()
+        .skip(1)
+        .map(|dir| dir.join("Cargo.toml"))
+        .find(|manifest| manifest.exists())
+}
+
+/// Runs `cargo check` and `rustc --explain`, caching both
+#[derive(Debug)]
+pub struct RustCompiler {
+    runner: Arc<dyn CommandRunner>,
+    /// Check results by manifest path
+    checks: Mutex<HashMap<PathBuf, Arc<Vec<RustcDiagnostic>>>>,
+    /// `--explain` text by error code; `None` when rustc has none
+    explanations: Mutex<HashMap<String, Option<String>>>,
+}
+
+impl RustCompiler {
+    pub fn new(runner: Arc<dyn CommandRunner>) -> Self {
+        Self {
+            runner,
+            checks: Mutex::new(HashMap::new()),
+            explanations: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Diagnostics of the package containing `file`, from the cache or a
+    /// fresh `cargo check`; `None` outside a Cargo package or when cargo
+    /// could not run
+    pub async fn check(&self, file: &Path) -> Option<Arc<Vec<RustcDiagnostic>>> {
+        let manifest = manifest_for(file)?;
+        if let Some(checked) = self.checks.lock().unwrap().get(&manifest) {
+            return Some(checked.clone());
+        }
+
+        let manifest_arg = manifest.to_string_lossy();
+        let output = self
+            .runner
+            .run(
+                "cargo",
+                &[
+                    "check",
+                    "--quiet",
+                    "--message-format=json",
+                    "--manifest-path",
+                    &manifest_arg,
+                ],
+                &RunOptions::default().with_timeout(CHECK_TIMEOUT),
+            )
+            .await
+            .map_err(|e| tracing::warn!("cargo check of {} failed: {}", manifest.display(), e))
+            .ok()?;
+        // cargo exits non-zero when the code has errors; they are all on stdout
+        let checked = Arc::new(parse_messages(&String::from_utf8_lossy(&output.stdout)));
+        self.checks
+            .lock()
+            .unwrap()
+            .insert(manifest, checked.clone());
+        Some(checked)
+    }
+
+    /// Drop every check result; called when a file is saved, which can
+    /// change the diagnostics of any package depending on it
+    pub fn invalidate(&self) {
+        self.checks.lock().unwrap().clear();
+    }
+
+    /// The `--explain` text for `code`: `shipped` when the diagnostic came
+    /// with it, otherwise from `rustc --explain`
+    pub async fn explain(&self, code: &str, shipped: Option<&str>) -> Option<String> {
+        if let Some(cached) = self.explanations.lock().unwrap().get(code) {
+            return cached.clone();
+        }
+
+        let explanation = match shipped {
+            Some(text) => Some(text.to_string()),
+            None => self
+                .runner
+                .output("rustc", &["--explain", code])
+                .await
+                .ok()
+                .filter(|output| output.status.success())
+                .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+                .filter(|text| !text.trim().is_empty()),
+        };
+        self.explanations
+            .lock()
+            .unwrap()
+            .insert(code.to_string(), explanation.clone());
+        explanation
+    }
+}
+
+/// The diagnostic in `checked` that the client reported as `diagnostic`
+pub fn find<'a>(
+    checked: &'a [RustcDiagnostic],
+    path: &Path,
+    diagnostic: &Diagnostic,
+) -> Option<&'a RustcDiagnostic> {
+    checked.iter().find(|d| d.matches(path, diagnostic))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jarvis_core::exec::RecordingRunner;
+
+    const CARGO_CHECK: &str = include_str!("../tests/fixtures/rustc/cargo_check.jsonl");
+    const EXPLAIN_E0382: &str = include_str!("../tests/fixtures/rustc/explain_E0382.md");
+
+    /// The source the fixture was checked from
+    const MAIN_RS: &str = r#"struct Token;
+
+#[derive(Clone)]
+struct Session {
+    token: Token,
+}
+
+fn consume(v: Vec<u32>) -> usize {
+    v.len()
+}
+
+fn main() {
+    let v = vec![1, 2, 3];
+    let n = consume(v);
+    println!("{} {}", n, v.len());
+}
+"#;
+
+    fn main_rs() -> PathBuf {
+        PathBuf::from("/home/user/fx/src/main.rs")
+    }
+
+    fn by_code<'a>(diagnostics: &'a [RustcDiagnostic], code: &str) -> &'a RustcDiagnostic {
+        diagnostics.iter().find(|d| d.code() == Some(code)).unwrap()
+    }
+
+    fn forwarded(line: u32, message: &str, code: Option<&str>) -> Diagnostic {
+        Diagnostic {
+            range: Range::new(Position::new(line, 0), Position::new(line, 1)),
+            code: code.map(|c| NumberOrString::String(c.to_string())),
+            source: Some("rustc".to_string()),
+            message: message.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_cargo_messages() {
+        let diagnostics = parse_messages(CARGO_CHECK);
+        let codes: Vec<Option<&str>> = diagnostics.iter().map(RustcDiagnostic::code).collect();
+        assert_eq!(codes, [Some("E0277"), Some("E0382")]);
+
+        let moved = by_code(&diagnostics, "E0382");
+        assert_eq!(moved.level, "error");
+        assert_eq!(moved.primary_span().unwrap().line_start, 15);
+        let rendered = moved.rendered.as_deref().unwrap();
+        assert!(rendered.starts_with("error[E0382]: borrow of moved value: `v`"));
+        assert!(rendered.contains("^ value borrowed here after move"));
+
+        // Raw rustc output has no cargo envelope
+        let raw = serde_json::to_string(&serde_json::json!({
+            "$message_type": "diagnostic",
+            "message": "unused variable: `x`",
+            "spans": [{
+                "file_name": "lib.rs", "line_start": 1, "line_end": 1,
+                "column_start": 5, "column_end": 6, "is_primary": true
+            }]
+        }))
+        .unwrap();
+        assert_eq!(parse_messages(&raw).len(), 1);
+    }
+
+    #[test]
+    fn test_error_code() {
+        let coded = forwarded(14, "borrow of moved value: `v`", Some("E0382"));
+        assert_eq!(error_code(&coded).as_deref(), Some("E0382"));
+
+        let in_message = forwarded(0, "error[E0599]: no method named `foo`", None);
+        assert_eq!(error_code(&in_message).as_deref(), Some("E0599"));
+
+        let lint = forwarded(0, "unused variable: `Elapsed`", Some("unused_variables"));
+        assert!(error_code(&lint).is_none());
+    }
+
+    #[test]
+    fn test_find_forwarded_diagnostic() {
+        let diagnostics = parse_messages(CARGO_CHECK);
+        let path = main_rs();
+
+        let reported = forwarded(
+            14,
+            "borrow of moved value: `v`\nvalue moved here",
+            Some("E0382"),
+        );
+        let found = find(&diagnostics, &path, &reported).unwrap();
+        assert_eq!(found.code(), Some("E0382"));
+
+        let other_line = forwarded(13, "borrow of moved value: `v`", Some("E0382"));
+        assert!(find(&diagnostics, &path, &other_line).is_none());
+        let other_file = Path::new("/home/user/fx/src/lib.rs");
+        assert!(find(&diagnostics, other_file, &reported).is_none());
+    }
+
+    #[test]
+    fn test_derive_hint() {
+        let diagnostics = parse_messages(CARGO_CHECK);
+        let hint = by_code(&diagnostics, "E0277").macro_hint().unwrap();
+        assert!(hint.contains("`#[derive(Clone)]`"), "{}", hint);
+        assert!(by_code(&diagnostics, "E0382").macro_hint().is_none());
+    }
+
+    #[test]
+    fn test_machine_applicable_fix() {
+        let diagnostics = parse_messages(CARGO_CHECK);
+        let path = main_rs();
+        let document = Document::new(1, "rust", MAIN_RS);
+
+        let fix = by_code(&diagnostics, "E0382")
+            .fix(&path, &document)
+            .unwrap();
+        assert_eq!(
+            fix.title,
+            "consider cloning the value if the performance cost is acceptable"
+        );
+        let changes = fix.edit.changes.unwrap();
+        let edits = &changes[&Url::from_file_path(&path).unwrap()];
+        assert_eq!(
+            edits,
+            &[TextEdit::new(
+                Range::new(Position::new(13, 21), Position::new(13, 21)),
+                ".clone()".to_string()
+            )]
+        );
+
+        // Deriving Clone for Token is only MaybeIncorrect: left to the model
+        assert!(
+            by_code(&diagnostics, "E0277")
+                .fix(&path, &document)
+                .is_none()
+        );
+
+        // The buffer moved on since the check
+        let edited = Document::new(2, "rust", &MAIN_RS.replace("consume(v)", "consume(v, 1)"));
+        assert!(by_code(&diagnostics, "E0382").fix(&path, &edited).is_none());
+    }
+
+    #[test]
+    fn test_explain_excerpt() {
+        let excerpt = explain_excerpt(EXPLAIN_E0382);
+        assert!(excerpt.starts_with("A variable was used after its contents have been moved"));
+        assert!(excerpt.contains("```rust\nstruct MyStruct { s: u32 }"));
+        assert!(excerpt.contains("Since `MyStruct` is a type that is not marked `Copy`"));
+        assert!(!excerpt.contains("calculate_length"));
+    }
+
+    #[test]
+    fn test_describe() {
+        let diagnostics = parse_messages(CARGO_CHECK);
+        let derive = by_code(&diagnostics, "E0277");
+        let text = describe(
+            &derive.message,
+            Some(derive),
+            derive.code(),
+            derive.code.as_ref().and_then(|c| c.explanation.as_deref()),
+        );
+        assert!(text.starts_with("```text\nerror[E0277]: the trait bound `Token: Clone`"));
+        assert!(text.contains("in this derive macro expansion"));
+        assert!(text.contains("Hint: This error is inside the code `#[derive(Clone)]`"));
+        assert!(text.contains("From `rustc --explain E0277`:"));
+
+        // No check result: the forwarded message and what the code says
+        let plain = describe(
+            "use of moved value",
+            None,
+            Some("E0382"),
+            Some(EXPLAIN_E0382),
+        );
+        assert!(plain.starts_with("use of moved value\n\nFrom `rustc --explain E0382`"));
+    }
+
+    #[tokio::test]
+    async fn test_explanations_are_cached_per_code() {
+        let runner =
+            Arc::new(RecordingRunner::new().respond("rustc --explain E0382", EXPLAIN_E0382));
+        let compiler = RustCompiler::new(runner.clone());
+
+        let first = compiler.explain("E0382", None).await.unwrap();
+        assert!(first.starts_with("A variable was used after"));
+        assert_eq!(compiler.explain("E0382", None).await.unwrap(), first);
+        assert_eq!(runner.calls(), ["rustc --explain E0382"]);
+
+        // Unknown codes print nothing and are not asked about again
+        assert!(compiler.explain("E9999", None).await.is_none());
+        assert!(compiler.explain("E9999", None).await.is_none());
+
+        // Text shipped with the diagnostic saves the spawn
+        assert_eq!(
+            compiler.explain("E0277", Some("shipped")).await.as_deref(),
+            Some("shipped")
+        );
+        assert_eq!(runner.calls().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_check_is_cached_until_invalidated() {
+        let project = tempfile::tempdir().unwrap();
+        std::fs::write(
+            project.path().join("Cargo.toml"),
+            "[package]\nname = \"fx\"\n",
+        )
+        .unwrap();
+        std::fs::create_dir(project.path().join("src")).unwrap();
+        let file = project.path().join("src/main.rs");
+
+        let runner = Arc::new(RecordingRunner::new().respond("cargo check", CARGO_CHECK));
+        let compiler = RustCompiler::new(runner.clone());
+        assert_eq!(compiler.check(&file).await.unwrap().len(), 2);
+        assert_eq!(compiler.check(&file).await.unwrap().len(), 2);
+        assert_eq!(runner.calls().len(), 1);
+        assert!(runner.calls()[0].ends_with(&format!(
+            "--manifest-path {}",
+            project.path().join("Cargo.toml").display()
+        )));
+
+        compiler.invalidate();
+        compiler.check(&file).await.unwrap();
+        assert_eq!(runner.calls().len(), 2);
+
+        // Not in a Cargo package: nothing to run
+        let loose = tempfile::tempdir().unwrap();
+        assert!(
+            compiler
+                .check(&loose.path().join("main.rs"))
+                .await
+                .is_none()
+        );
+        assert_eq!(runner.calls().len(), 2);
+    }
+}
@@ -1,5 +1,4 @@
 use anyhow::Result;
-use git2::Repository;
 use jarvis_core::types::{GitContext, SystemInfo};
 use std::env;
 use std::path::PathBuf;
@@ -143,35 +142,7 @@ pub struct ResourceUsage {
 }
 
 async fn detect_git_context(working_dir: &PathBuf) -> Result<Option<GitContext>> {
-    match Repository::discover(working_dir) {
-        Ok(repo) => {
-            let head = repo.head()?;
-            let branch_name = head.shorthand().unwrap_or("unknown").to_string();
-
-            let repo_path = repo
-                .workdir()
-                .unwrap_or_else(|| repo.path())
-                .to_string_lossy()
-                .to_string();
-
-            let status = repo.statuses(None)?;
-            let dirty = !status.is_empty();
-
-            let last_commit = if let Ok(commit) = head.peel_to_commit() {
-                commit.id().to_string()[..8].to_string()
-            } else {
-                "unknown".to_string()
-            };
-
-            Ok(Some(GitContext {
-                repo_path,
-                current_branch: branch_name,
-                dirty,
-                last_commit,
-            }))
-        }
-        Err(_) => Ok(None),
-    }
+    crate::git::collect_context(working_dir)
 }
 
 async fn detect_system_info() -> Result<SystemInfo> {
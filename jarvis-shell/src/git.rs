@@ -0,0 +1,187 @@
+//! Git repository context for prompts
+//!
+//! Everything here reads the repository through libgit2; nothing shells out to
+//! `git`. Diffs are rendered per file and only for the files that get picked,
+//! so a huge working tree costs a path scan rather than a full patch.
+
+use anyhow::Result;
+use git2::{Diff, DiffOptions, Patch, Repository, StatusOptions};
+use jarvis_core::types::GitContext;
+use std::path::Path;
+
+/// Commits listed in the context summary
+const RECENT_COMMITS: usize = 5;
+/// Changed files considered for relevance; larger diffs are sampled down to this
+const MAX_CANDIDATE_FILES: usize = 200;
+/// Per-file cap on diff text handed to the prompt
+const MAX_PATCH_BYTES: usize = 6000;
+
+/// Diff of one changed file
+#[derive(Debug, Clone)]
+pub struct FileDiff {
+    pub path: String,
+    pub patch: String,
+}
+
+/// Branch, dirty files, and recent commits for the repo containing `dir`
+pub fn collect_context(dir: &Path) -> Result<Option<GitContext>> {
+    let Ok(repo) = Repository::discover(dir) else {
+        return Ok(None);
+    };
+
+    let repo_path = repo
+        .workdir()
+        .unwrap_or_else(|| repo.path())
+        .to_string_lossy()
+        .to_string();
+
+    // A fresh repo has no HEAD commit yet
+    let (current_branch, last_commit) = match repo.head() {
+        Ok(head) => (
+            head.shorthand().unwrap_or("unknown").to_string(),
+            head.peel_to_commit()
+                .map(|c| c.id().to_string()[..8].to_string())
+                .unwrap_or_else(|_| "unknown".to_string()),
+        ),
+        Err(_) => ("unborn".to_string(), "none".to_string()),
+    };
+
+    let mut options = StatusOptions::new();
+    options.include_untracked(true).recurse_untracked_dirs(false);
+    let dirty_files: Vec<String> = repo
+        .statuses(Some(&mut options))?
+        .iter()
+        .filter_map(|entry| entry.path().map(|p| p.to_string()))
+        .collect();
+
+    Ok(Some(GitContext {
+        repo_path,
+        current_branch,
+        dirty: !dirty_files.is_empty(),
+        last_commit,
+        dirty_files,
+        recent_commits: recent_commits(&repo, RECENT_COMMITS).unwrap_or_default(),
+    }))
+}
+
+fn recent_commits(repo: &Repository, limit: usize) -> Result<Vec<String>> {
+    let mut walk = repo.revwalk()?;
+    walk.push_head()?;
+
+    let mut commits = Vec::new();
+    for oid in walk.take(limit) {
+        let commit = repo.find_commit(oid?)?;
+        commits.push(format!(
+            "{} {}",
+            &commit.id().to_string()[..8],
+            commit.summary().unwrap_or("")
+        ));
+    }
+    Ok(commits)
+}
+
+/// The staged changes (index against HEAD) as a unified diff
+pub fn staged_diff(dir: &Path) -> Result<String> {
+    let repo = Repository::discover(dir)?;
+    let head_tree = repo.head().ok().and_then(|h| h.peel_to_tree().ok());
+    let diff = repo.diff_tree_to_index(head_tree.as_ref(), None, None)?;
+
+    let mut out = String::new();
+    for index in 0..diff.deltas().len() {
+        if let Some(patch) = render_patch(&diff, index, usize::MAX) {
+            out.push_str(&patch);
+        }
+    }
+    Ok(out)
+}
+
+/// Uncommitted changes most relevant to `query`, picked by path keywords.
+/// When the working tree has more changed files than we are willing to look
+/// at, keyword matches are kept and the rest is sampled evenly.
+pub fn relevant_diffs(dir: &Path, query: &str, max_files: usize) -> Result<Vec<FileDiff>> {
+    let repo = Repository::discover(dir)?;
+    let head_tree = repo.head().ok().and_then(|h| h.peel_to_tree().ok());
+    let mut options = DiffOptions::new();
+    options.include_untracked(true).show_untracked_content(true);
+    let diff = repo.diff_tree_to_workdir_with_index(head_tree.as_ref(), Some(&mut options))?;
+
+    let paths: Vec<String> = diff
+        .deltas()
+        .map(|d| {
+            d.new_file()
+                .path()
+                .or_else(|| d.old_file().path())
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_default()
+        })
+        .collect();
+
+    let keywords = query_keywords(query);
+    let mut scored: Vec<(usize, usize)> = paths
+        .iter()
+        .enumerate()
+        .map(|(i, path)| (i, keyword_score(path, &keywords)))
+        .collect();
+
+    if scored.len() > MAX_CANDIDATE_FILES {
+        let (matching, rest): (Vec<_>, Vec<_>) = scored.into_iter().partition(|(_, s)| *s > 0);
+        let room = MAX_CANDIDATE_FILES.saturating_sub(matching.len());
+        let stride = (rest.len() / room.max(1)).max(1);
+        scored = matching
+            .into_iter()
+            .chain(rest.into_iter().step_by(stride).take(room))
+            .collect();
+    }
+
+    // Keyword matches first; with no query hits, the smallest changes are the
+    // cheapest useful context
+    scored.sort_by_key(|(i, score)| (std::cmp::Reverse(*score), diff_size(&diff, *i)));
+
+    Ok(scored
+        .into_iter()
+        .take(max_files)
+        .filter_map(|(i, _)| {
+            render_patch(&diff, i, MAX_PATCH_BYTES).map(|patch| FileDiff {
+                path: paths[i].clone(),
+                patch,
+            })
+        })
+        .collect())
+}
+
+fn render_patch(diff: &Diff, index: usize, max_bytes: usize) -> Option<String> {
+    let mut patch = Patch::from_diff(diff, index).ok()??;
+    let buf = patch.to_buf().ok()?;
+    let text = String::from_utf8_lossy(&buf);
+    if text.len() <= max_bytes {
+        return Some(text.into_owned());
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    Some(format!("{}\n[...diff truncated]\n", &text[..end]))
+}
+
+fn diff_size(diff: &Diff, index: usize) -> usize {
+    Patch::from_diff(diff, index)
+        .ok()
+        .flatten()
+        .and_then(|p| p.line_stats().ok())
+        .map(|(_, additions, deletions)| additions + deletions)
+        .unwrap_or(usize::MAX)
+}
+
+fn query_keywords(query: &str) -> Vec<String> {
+    query
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|w| w.len() >= 3)
+        .map(|w| w.to_string())
+        .collect()
+}
+
+fn keyword_score(path: &str, keywords: &[String]) -> usize {
+    let path = path.to_lowercase();
+    keywords.iter().filter(|k| path.contains(k.as_str())).count()
+}
@@ -1,3 +1,5 @@
 pub mod environment;
+pub mod git;
 
 pub use environment::Environment;
+pub use git::FileDiff;
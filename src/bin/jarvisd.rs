@@ -23,11 +23,38 @@ use jarvis_agent::{
     blockchain_monitor::{BlockchainMonitorAgent, MonitoringConfig},
     orchestrator::{BlockchainAgentOrchestrator, OrchestratorConfig},
 };
+use jarvis_arch::{
+    ArchAgent, ArchLinuxAgent, Config as ArchConfig,
+    http_api::{self, ApiState},
+};
 use jarvis_core::{
-    config::Config, grpc_client::GhostChainClient, llm::LLMRouter, memory::MemoryStore,
+    bus::{self, BusClient, BusEvent, BusMessage, BusServer},
+    config::Config,
+    docker_maintenance::DockerMaintenanceAgent,
+    exec::{CommandRunner, SystemRunner},
+    fleet::HostHealth,
+    grpc_client::GhostChainClient,
+    health_checks::{self, LatestOutcomes},
+    host_profile::{self, HostProfile},
+    incidents::{Correlator, IncidentLog, Signal},
+    llm::LLMRouter,
+    maintenance_agents::BtrfsMaintenanceAgent,
+    mcp::{JarvisTools, NetworkServer, ToolContext},
+    memory::MemoryStore,
+    metrics::HostSampler,
+    notify::{HealthTransitions, Notification, Notifier, NotifyEvent, NotifySeverity},
+    operations::ActiveOperations,
+    remote::CommandExecutor,
+    report,
+    sensors::{BreachTracker, SensorSampler},
+    severity::Severity,
+    ssh_pool::{self, PoolStats},
+    tls::ReloadableTlsAcceptor,
+    trivy::ImageScanner,
 };
+use chrono::{DateTime, Utc};
 use std::{
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::{
         Arc,
         atomic::{AtomicBool, Ordering},
@@ -35,12 +62,31 @@ use std::{
     time::Duration,
 };
 use tokio::{
+    net::TcpListener,
     signal,
-    sync::RwLock,
+    sync::{
+        Mutex, RwLock,
+        broadcast::{self, error::RecvError},
+    },
     time::{interval, sleep},
 };
 use tracing::{debug, error, info, warn};
 
+/// Configuration file shared with the jarvis-arch service
+const ARCH_CONFIG_PATH: &str = "/etc/jarvis/jarvis-arch.toml";
+
+/// Runtime state the daemon writes next to its PID file on each health
+/// check, for `jarvisd status` to show
+#[derive(serde::Serialize, serde::Deserialize)]
+struct StatusSnapshot {
+    updated_at: DateTime<Utc>,
+    ssh_pool: PoolStats,
+}
+
+fn status_file(pid_file: &Path) -> PathBuf {
+    pid_file.with_extension("status.json")
+}
+
 /// Daemon configuration and runtime state
 struct JarvisDaemon {
     config: Arc<RwLock<Config>>,
@@ -48,6 +94,42 @@ struct JarvisDaemon {
     orchestrator: Arc<RwLock<BlockchainAgentOrchestrator>>,
     running: Arc<AtomicBool>,
     pid_file: Option<PathBuf>,
+    notifier: Notifier,
+    health_transitions: Mutex<HealthTransitions>,
+    /// Pending updates at the last check, so unchanged counts aren't re-announced
+    last_update_count: Mutex<usize>,
+    llm_router: LLMRouter,
+    /// When the scheduled report runs next; recomputed after each run and on config reload
+    next_report: Mutex<Option<DateTime<Utc>>>,
+    /// Built on first use and dropped on config reload; keeps unhealthy-since times across ticks
+    docker_agent: Mutex<Option<DockerMaintenanceAgent>>,
+    /// End of the last window checked for due Docker tasks
+    last_docker_check: Mutex<DateTime<Utc>>,
+    /// When `[llm] keep_warm_secs` last re-warmed the default model
+    last_keep_warm: Mutex<Option<DateTime<Utc>>>,
+    /// Long-running operations in progress, with percent complete
+    operations: ActiveOperations,
+    /// Set while a btrfs maintenance pass runs in the background
+    btrfs_running: Arc<AtomicBool>,
+    /// End of the last window checked for a due container image scan
+    last_image_scan_check: Mutex<DateTime<Utc>>,
+    /// Set while a container image scan runs in the background
+    image_scan_running: Arc<AtomicBool>,
+    /// Set while the local host profile is being regenerated
+    profile_running: Arc<AtomicBool>,
+    /// Reads the host metrics recorded on each health check
+    host_sampler: Mutex<HostSampler>,
+    /// Reads hwmon, RAPL, and battery sensors on each health check
+    sensor_sampler: Mutex<SensorSampler>,
+    /// How long each sensor has been past its thresholds
+    sensor_breaches: Mutex<BreachTracker>,
+    /// Results of the `[[health_checks]]` scripts from the last health check,
+    /// also reported by the HTTP API's `/health`
+    custom_checks: LatestOutcomes,
+    /// The event bus broker, while `[bus] enabled`
+    bus_server: Mutex<Option<BusServer>>,
+    /// The HTTP API's state, while `[api] enabled`, for cleanup to prune its idempotency keys
+    api: Mutex<Option<ApiState>>,
 }
 
 impl JarvisDaemon {
@@ -65,10 +147,13 @@ impl JarvisDaemon {
                 .await
                 .context("Failed to load default config")?
         };
+        jarvis_core::net::configure(&config.network);
+        jarvis_core::read_only::configure(config.read_only);
 
-        // Initialize memory store
+        // Initialize memory store; a locked or damaged database shouldn't
+        // keep the daemon from monitoring, so it falls back to memory
         let memory_store = Arc::new(
-            MemoryStore::new(&config.database_path)
+            MemoryStore::open_or_ephemeral(&config.database_path)
                 .await
                 .context("Failed to initialize memory store")?,
         );
@@ -100,6 +185,7 @@ impl JarvisDaemon {
             throughput_threshold: 10.0,
             packet_loss_threshold: 5.0,
             enable_ai_analysis: true,
+            min_alert_severity: Severity::Info,
         };
 
         let monitor_agent = BlockchainMonitorAgent::new(
@@ -137,15 +223,36 @@ impl JarvisDaemon {
             orchestrator_config,
             grpc_client,
             (*memory_store).clone(),
-            llm_router,
+            llm_router.clone(),
         )));
 
+        let notifier = Notifier::from_config(&config.notifications);
+
         Ok(Self {
             config: Arc::new(RwLock::new(config)),
             memory_store,
             orchestrator,
             running: Arc::new(AtomicBool::new(false)),
             pid_file,
+            notifier,
+            health_transitions: Mutex::new(HealthTransitions::new()),
+            last_update_count: Mutex::new(0),
+            llm_router,
+            next_report: Mutex::new(None),
+            docker_agent: Mutex::new(None),
+            last_docker_check: Mutex::new(Utc::now()),
+            last_keep_warm: Mutex::new(None),
+            operations: ActiveOperations::new(),
+            btrfs_running: Arc::new(AtomicBool::new(false)),
+            last_image_scan_check: Mutex::new(Utc::now()),
+            image_scan_running: Arc::new(AtomicBool::new(false)),
+            profile_running: Arc::new(AtomicBool::new(false)),
+            host_sampler: Mutex::new(HostSampler::new()),
+            sensor_sampler: Mutex::new(SensorSampler::default()),
+            sensor_breaches: Mutex::new(BreachTracker::new()),
+            custom_checks: LatestOutcomes::default(),
+            bus_server: Mutex::new(None),
+            api: Mutex::new(None),
         })
     }
 
@@ -170,6 +277,12 @@ impl JarvisDaemon {
                 .context("Failed to start agent orchestrator")?;
         }
 
+        let mcp = self.start_mcp().await?;
+        self.start_http_api(mcp).await?;
+        let remote = self.start_bus().await;
+        self.start_incidents(remote).await;
+        self.notify_memory_state();
+
         info!("Jarvis Daemon started successfully");
 
         // Main daemon loop
@@ -178,6 +291,222 @@ impl JarvisDaemon {
         Ok(())
     }
 
+    /// Tell the user when the memory database was replaced at startup or
+    /// couldn't be opened at all
+    fn notify_memory_state(&self) {
+        if let Some(recovery) = self.memory_store.recovery() {
+            self.notifier.notify(Notification::new(
+                NotifyEvent::HealthChanged,
+                NotifySeverity::Warning,
+                "Jarvis memory database was damaged",
+                recovery.to_string(),
+            ));
+        }
+        if let Some(reason) = self.memory_store.unavailable_reason() {
+            self.notifier.notify(Notification::new(
+                NotifyEvent::HealthChanged,
+                NotifySeverity::Warning,
+                "Jarvis is running without history",
+                format!(
+                    "{}. Metrics, scheduled reports, and the host profile are not kept until \
+                     jarvisd restarts with a database it can open",
+                    reason
+                ),
+            ));
+        }
+    }
+
+    /// Serve the MCP tools to other machines when `[mcp] enabled`: over TCP
+    /// with `transport = "tcp"`, and at the HTTP API's `/mcp` whenever the API
+    /// is on. Clients without TLS client certificates authenticate with
+    /// `[mcp] token`, or `[api] token` when that isn't set.
+    async fn start_mcp(&self) -> Result<Option<Arc<NetworkServer>>> {
+        let config = self.config.read().await;
+        let mcp = config.mcp.clone();
+        if !mcp.enabled {
+            return Ok(None);
+        }
+        let context = ToolContext {
+            llm_router: Some(self.llm_router.clone()),
+            memory: Some((*self.memory_store).clone()),
+            system: config.system.clone(),
+            remote: config.remote.clone(),
+            trace: config.trace.clone(),
+            capabilities: Vec::new(),
+        };
+        let token = mcp.token.clone().or_else(|| config.api.token.clone());
+        drop(config);
+        let server =
+            Arc::new(JarvisTools::new(&mcp, context, "jarvisd").network_server(&mcp, token));
+
+        if mcp.transport == "tcp" {
+            let address = mcp.address.as_deref().unwrap_or("127.0.0.1:7332");
+            let listener = TcpListener::bind(address)
+                .await
+                .with_context(|| format!("Failed to bind the MCP server to {}", address))?;
+            let tls = if mcp.tls.is_enabled() {
+                let acceptor = Arc::new(ReloadableTlsAcceptor::new(mcp.tls.clone())?);
+                acceptor.reload_on_sighup()?;
+                Some(acceptor)
+            } else {
+                None
+            };
+            let tcp = server.clone();
+            tokio::spawn(async move {
+                if let Err(e) = tcp.serve_tcp(listener, tls).await {
+                    error!("MCP server stopped: {}", e);
+                }
+            });
+        }
+        Ok(Some(server))
+    }
+
+    /// Serve the HTTP API when `[api] enabled`, backed by an Arch agent that
+    /// lives as long as the daemon. Bind address and token changes take a restart.
+    async fn start_http_api(&self, mcp: Option<Arc<NetworkServer>>) -> Result<()> {
+        let api = self.config.read().await.api.clone();
+        if !api.enabled {
+            return Ok(());
+        }
+
+        let path = PathBuf::from(ARCH_CONFIG_PATH);
+        let arch_config = if path.exists() {
+            ArchConfig::load_from_file(&path)
+                .with_context(|| format!("Failed to load {}", ARCH_CONFIG_PATH))?
+        } else {
+            ArchConfig::load_with_defaults()
+        };
+        let mut agent = ArchLinuxAgent::new().with_custom_checks(self.custom_checks.clone());
+        agent
+            .initialize(arch_config)
+            .await
+            .context("Failed to start the Arch agent for the HTTP API")?;
+
+        let listener = TcpListener::bind(&api.bind)
+            .await
+            .with_context(|| format!("Failed to bind the HTTP API to {}", api.bind))?;
+        let window = api.idempotency_window();
+        let privilege = agent.privilege().clone();
+        let mut state = ApiState::new(Arc::new(agent), api.token)
+            .with_idempotency_window(window)
+            .with_privilege(privilege);
+        if let Some(mcp) = mcp {
+            state = state.with_mcp(mcp);
+        }
+        *self.api.lock().await = Some(state.clone());
+        tokio::spawn(async move {
+            if let Err(e) = http_api::serve(listener, state).await {
+                error!("HTTP API stopped: {}", e);
+            }
+        });
+        Ok(())
+    }
+
+    /// Serve the event bus and join it, so jarvis-nv sees this host's
+    /// operations and health changes and `jarvis diagnose` can ask what
+    /// happened lately. The daemon runs without it if the socket can't be
+    /// bound. Returns a receiver of the other participants' events.
+    async fn start_bus(&self) -> Option<broadcast::Receiver<BusMessage>> {
+        let config = self.config.read().await.bus.clone();
+        if !config.enabled {
+            return None;
+        }
+
+        match BusServer::bind(&config.socket, config.history).await {
+            Ok(server) => *self.bus_server.lock().await = Some(server),
+            Err(e) => {
+                warn!("Event bus unavailable: {:#}", e);
+                return None;
+            }
+        }
+        let client = BusClient::connect(&config, "jarvisd");
+        let mut events = client.subscribe();
+        let remote = client.subscribe();
+        bus::install(client);
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(message) => info!("📨 {}: {}", message.source, message.event.summary()),
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+        Some(remote)
+    }
+
+    /// Correlate this daemon's own events and those of the other bus
+    /// participants into incidents, keeping them in memory for `jarvis
+    /// incidents` and the report, and notify per incident
+    async fn start_incidents(&self, remote: Option<broadcast::Receiver<BusMessage>>) {
+        let config = self.config.read().await.incidents.clone();
+        if !config.enabled {
+            return;
+        }
+
+        let memory = self.memory_store.clone();
+        let mut log = IncidentLog::load(&memory).await.unwrap_or_else(|e| {
+            warn!("Starting a new incident log: {:#}", e);
+            IncidentLog::default()
+        });
+        let mut correlator = Correlator::new(config.clone()).with_open(log.open().cloned());
+        let mut local = bus::local_events();
+        let mut remote = remote;
+        let notifier = self.notifier.clone();
+
+        tokio::spawn(async move {
+            let mut tick = interval(Duration::from_secs(60));
+            loop {
+                let received = tokio::select! {
+                    message = local.recv() => Some(message),
+                    Some(message) = async {
+                        match remote.as_mut() {
+                            Some(events) => Some(events.recv().await),
+                            None => None,
+                        }
+                    } => Some(message),
+                    _ = tick.tick() => None,
+                };
+                let updates = match received {
+                    Some(Ok(message)) => match Signal::from_message(&message) {
+                        Some(signal) => correlator.observe(signal),
+                        None => continue,
+                    },
+                    Some(Err(RecvError::Lagged(skipped))) => {
+                        warn!("Incident correlation skipped {} events", skipped);
+                        continue;
+                    }
+                    Some(Err(RecvError::Closed)) => {
+                        // Only the bus client's channel closes; local events go on
+                        remote = None;
+                        continue;
+                    }
+                    None => correlator.tick(Utc::now()),
+                };
+                if updates.is_empty() {
+                    continue;
+                }
+
+                for update in &updates {
+                    info!(
+                        "🚨 Incident {} {:?}: {} ({})",
+                        update.incident.short_id(),
+                        update.change,
+                        update.incident.title,
+                        update.incident.severity
+                    );
+                    log.apply(update, config.max_stored);
+                    if update.notify {
+                        notifier.notify(update.notification());
+                    }
+                }
+                if let Err(e) = log.save(&memory).await {
+                    warn!("Failed to store incidents: {}", e);
+                }
+            }
+        });
+    }
+
     /// Stop the daemon service
     async fn stop(&self) -> Result<()> {
         info!("Stopping Jarvis Daemon service...");
@@ -194,12 +523,15 @@ impl JarvisDaemon {
                 .context("Failed to shutdown agent orchestrator")?;
         }
 
+        ssh_pool::pool().close_all().await;
+
         // Remove PID file if it exists
         if let Some(pid_file) = &self.pid_file {
             if pid_file.exists() {
                 std::fs::remove_file(pid_file)
                     .with_context(|| format!("Failed to remove PID file {:?}", pid_file))?;
             }
+            let _ = std::fs::remove_file(status_file(pid_file));
         }
 
         info!("Jarvis Daemon stopped successfully");
@@ -211,6 +543,13 @@ impl JarvisDaemon {
         let mut health_check_interval = interval(Duration::from_secs(30));
         let mut config_reload_interval = interval(Duration::from_secs(300)); // 5 minutes
         let mut cleanup_interval = interval(Duration::from_secs(3600)); // 1 hour
+        let mut update_check_interval = interval(Duration::from_secs(6 * 3600));
+        let mut report_check_interval = interval(Duration::from_secs(60));
+        let mut docker_check_interval = interval(Duration::from_secs(60));
+        let mut btrfs_check_interval = interval(Duration::from_secs(15 * 60));
+        let mut image_scan_check_interval = interval(Duration::from_secs(60));
+        let mut keep_warm_interval = interval(Duration::from_secs(30));
+        let mut profile_check_interval = interval(Duration::from_secs(3600));
 
         loop {
             tokio::select! {
@@ -235,6 +574,47 @@ impl JarvisDaemon {
                     }
                 }
 
+                // Update availability
+                _ = update_check_interval.tick() => {
+                    if let Err(e) = self.check_for_updates().await {
+                        warn!("Update check failed: {}", e);
+                    }
+                }
+
+                // Scheduled reports
+                _ = report_check_interval.tick() => {
+                    if let Err(e) = self.run_scheduled_report().await {
+                        warn!("Scheduled report failed: {}", e);
+                    }
+                }
+
+                // Docker housekeeping
+                _ = docker_check_interval.tick() => {
+                    if let Err(e) = self.run_docker_maintenance().await {
+                        warn!("Docker maintenance failed: {}", e);
+                    }
+                }
+
+                // Btrfs scrubs and balances
+                _ = btrfs_check_interval.tick() => {
+                    self.start_btrfs_maintenance().await;
+                }
+
+                // Container image vulnerability scans
+                _ = image_scan_check_interval.tick() => {
+                    self.start_image_scan().await;
+                }
+
+                // Keep the default model loaded in Ollama
+                _ = keep_warm_interval.tick() => {
+                    self.keep_model_warm().await;
+                }
+
+                // Host profile for prompts
+                _ = profile_check_interval.tick() => {
+                    self.start_host_profile_refresh().await;
+                }
+
                 // Graceful shutdown signals
                 _ = signal::ctrl_c() => {
                     info!("Received SIGINT, shutting down gracefully...");
@@ -262,46 +642,444 @@ impl JarvisDaemon {
         // Check orchestrator health using get_system_health
         {
             let orchestrator = self.orchestrator.read().await;
-            match orchestrator.get_system_health().await {
+            let severity = match orchestrator.get_system_health().await {
                 Ok(health) => {
                     debug!("System health: {:?}", health);
+                    match health["system_health"]["overall_status"].as_str() {
+                        Some("healthy") => NotifySeverity::Info,
+                        _ => NotifySeverity::Warning,
+                    }
                 }
                 Err(e) => {
                     warn!("Health check failed: {}", e);
+                    NotifySeverity::Critical
                 }
+            };
+
+            let transition = self
+                .health_transitions
+                .lock()
+                .await
+                .change("agents", severity);
+            if transition.is_some() {
+                let state = match severity {
+                    NotifySeverity::Critical => "critical",
+                    NotifySeverity::Warning => "degraded",
+                    NotifySeverity::Info => "healthy",
+                };
+                self.report_health_change(
+                    "agents",
+                    severity,
+                    state,
+                    format!("Jarvis agents {}", state),
+                    "One or more daemon agents are not running; see `jarvisd status` and the logs",
+                )
+                .await;
             }
         }
 
-        // Check memory store health - simplified check
-        // Just try to access the store to see if it's responsive
-        let _ = (*self.memory_store).clone();
+        // Record host metrics for `jarvis check trend` and the weekly report;
+        // a failing write also means the memory store is unhealthy
+        let now = Utc::now();
+        let mut samples = self.host_sampler.lock().await.sample(now);
+        samples.extend(self.check_sensors(now).await);
+        samples.extend(self.run_custom_checks(now).await);
+        if let Err(e) = self.memory_store.record_metrics(&samples).await {
+            warn!("Failed to record health metrics: {}", e);
+        }
+        self.write_status_snapshot(now);
 
         debug!("Health check completed");
         Ok(())
     }
 
+    /// Read the hardware sensors, alerting on breaches that lasted
+    /// `[sensors] sustain_checks` health checks; returns the readings as metrics
+    async fn check_sensors(&self, now: DateTime<Utc>) -> Vec<jarvis_core::metrics::MetricSample> {
+        let sensor_config = self.config.read().await.sensors.clone();
+        if !sensor_config.enabled {
+            return Vec::new();
+        }
+        let snapshot = self.sensor_sampler.lock().await.sample(&sensor_config, now);
+        let sustained = self
+            .sensor_breaches
+            .lock()
+            .await
+            .observe(&snapshot.assess(&sensor_config), sensor_config.sustain_checks);
+
+        let mut transitions = self.health_transitions.lock().await;
+        for (id, health, description) in sustained {
+            let severity = match health {
+                HostHealth::Critical => NotifySeverity::Critical,
+                HostHealth::Warning => NotifySeverity::Warning,
+                _ => NotifySeverity::Info,
+            };
+            let component = format!("sensor {}", id);
+            if transitions.change(&component, severity).is_none() {
+                continue;
+            }
+            let state = health_state(severity);
+            self.report_health_change(
+                &component,
+                severity,
+                state,
+                format!("{} {}", id, state),
+                format!("{}; see `jarvis check sensors`", description),
+            )
+            .await;
+        }
+        snapshot.metrics(now)
+    }
+
+    /// Run the `[[health_checks]]` scripts, alerting when one changes state
+    /// like a built-in check; returns what they reported as metrics
+    async fn run_custom_checks(&self, now: DateTime<Utc>) -> Vec<jarvis_core::metrics::MetricSample> {
+        let checks = self.config.read().await.health_checks.clone();
+        let outcomes = health_checks::run_all(&SystemRunner::default(), &checks).await;
+
+        let mut transitions = self.health_transitions.lock().await;
+        for (id, health, description) in health_checks::assess(&outcomes) {
+            let severity = match health {
+                HostHealth::Critical => NotifySeverity::Critical,
+                HostHealth::Warning => NotifySeverity::Warning,
+                _ => NotifySeverity::Info,
+            };
+            if transitions.change(&id, severity).is_none() {
+                continue;
+            }
+            let state = health_state(severity);
+            self.report_health_change(
+                &id,
+                severity,
+                state,
+                format!("{} {}", id, state),
+                format!("{}; see `jarvis doctor`", description),
+            )
+            .await;
+        }
+        drop(transitions);
+
+        let samples = health_checks::metrics(&outcomes, now);
+        *self.custom_checks.write().await = outcomes;
+        samples
+    }
+
+    /// Put a health transition on the bus, where the incident correlator
+    /// picks it up; with incidents off, notify about it directly as before
+    async fn report_health_change(
+        &self,
+        component: &str,
+        severity: NotifySeverity,
+        state: &str,
+        title: String,
+        body: impl Into<String>,
+    ) {
+        bus::publish(BusEvent::HealthStateChanged {
+            component: component.to_string(),
+            state: state.to_string(),
+        });
+        if severity > NotifySeverity::Info && !self.config.read().await.incidents.enabled {
+            self.notifier.notify(Notification::new(
+                NotifyEvent::HealthChanged,
+                severity,
+                title,
+                body,
+            ));
+        }
+    }
+
+    /// Count pending package updates and announce when the number grows
+    async fn check_for_updates(&self) -> Result<()> {
+        if jarvis_core::net::is_offline() {
+            debug!("Offline; skipping the package update check");
+            return Ok(());
+        }
+        debug!("Checking for package updates...");
+
+        // checkupdates (pacman-contrib) syncs a private copy of the databases,
+        // so it works unprivileged and doesn't touch the system sync db
+        let output = SystemRunner::default()
+            .output("checkupdates", &[])
+            .await
+            .context("Failed to run checkupdates; is pacman-contrib installed?")?;
+
+        // Exit code 2 means no updates
+        let updates: Vec<String> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|l| l.split_whitespace().next())
+            .map(|s| s.to_string())
+            .collect();
+
+        let mut last = self.last_update_count.lock().await;
+        if updates.len() > *last {
+            let preview: Vec<&str> = updates.iter().take(10).map(|s| s.as_str()).collect();
+            self.notifier.notify(Notification::new(
+                NotifyEvent::UpdatesAvailable,
+                NotifySeverity::Info,
+                format!("{} package updates available", updates.len()),
+                preview.join(", "),
+            ));
+        }
+        *last = updates.len();
+
+        Ok(())
+    }
+
+    /// Write the `[reports]` report when its cron schedule comes due
+    async fn run_scheduled_report(&self) -> Result<()> {
+        let report_config = self.config.read().await.reports.clone();
+        let Some(schedule) = report_config.schedule.as_deref() else {
+            return Ok(());
+        };
+        // Without stored metrics there is nothing to report on; the startup
+        // notification already said so
+        if !self.memory_store.is_persistent() {
+            return Ok(());
+        }
+
+        let now = Utc::now();
+        let mut next = self.next_report.lock().await;
+        let due = match *next {
+            Some(at) => at,
+            None => {
+                let at = report::next_scheduled(schedule, now)?;
+                info!("Next scheduled report at {}", at);
+                *next = Some(at);
+                return Ok(());
+            }
+        };
+        if now < due {
+            return Ok(());
+        }
+        *next = Some(report::next_scheduled(schedule, now)?);
+        drop(next);
+
+        info!("Generating scheduled report...");
+        let paths =
+            report::generate_scheduled(&report_config, &self.memory_store, Some(&self.llm_router))
+                .await?;
+        info!("Report written to {}", paths[0].display());
+
+        if report_config.notify {
+            self.notifier.notify(Notification::new(
+                NotifyEvent::ReportReady,
+                NotifySeverity::Info,
+                "Jarvis report ready",
+                format!("Report covering the last {} saved to {}", report_config.since, paths[0].display()),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Run the `[docker_maintenance]` tasks that came due since the last tick
+    async fn run_docker_maintenance(&self) -> Result<()> {
+        let docker_config = self.config.read().await.docker_maintenance.clone();
+        if !docker_config.enabled {
+            return Ok(());
+        }
+
+        let now = Utc::now();
+        let since = std::mem::replace(&mut *self.last_docker_check.lock().await, now);
+        let mut agent = self.docker_agent.lock().await;
+        let agent = agent.get_or_insert_with(|| DockerMaintenanceAgent::new(docker_config));
+
+        for task in agent.due_tasks(since, now) {
+            bus::publish(BusEvent::MaintenanceScheduled {
+                task: format!("docker {}", task.name()),
+                at: now,
+            });
+            let result = agent.run(task).await;
+            if !result.actions.is_empty() || !result.success() {
+                info!(
+                    "Docker {}: {} actions, {} errors",
+                    task.name(),
+                    result.actions.len(),
+                    result.errors.len()
+                );
+            }
+            if let Some(notification) = result.notification() {
+                self.notifier.notify(notification);
+            }
+        }
+        Ok(())
+    }
+
+    /// Re-warm the default model once `[llm] keep_warm_secs` has passed, so
+    /// the first request after a quiet spell doesn't wait for a load. The
+    /// first warm-up is a full load, so it runs in the background.
+    async fn keep_model_warm(&self) {
+        let Some(every) = self.config.read().await.llm.keep_warm_secs else {
+            return;
+        };
+
+        let now = Utc::now();
+        let mut last = self.last_keep_warm.lock().await;
+        if last.is_some_and(|at| (now - at).num_seconds() < every as i64) {
+            return;
+        }
+        *last = Some(now);
+
+        let router = self.llm_router.clone();
+        tokio::spawn(async move {
+            debug!("Keeping {} warm", router.default_model());
+            if let Err(e) = router.keep_warm().await {
+                warn!("Keeping {} warm failed: {:#}", router.default_model(), e);
+            }
+        });
+    }
+
+    /// Start a btrfs maintenance pass in the background; scrubs take hours, so
+    /// the event loop must not wait for them. The agent itself checks the
+    /// maintenance window and system load.
+    async fn start_btrfs_maintenance(&self) {
+        let btrfs_config = self.config.read().await.btrfs_maintenance.clone();
+        if !btrfs_config.enabled || self.btrfs_running.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        bus::publish(BusEvent::MaintenanceScheduled {
+            task: "btrfs maintenance".to_string(),
+            at: Utc::now(),
+        });
+        let agent = BtrfsMaintenanceAgent::new(btrfs_config, self.operations.clone());
+        let memory_store = self.memory_store.clone();
+        let notifier = self.notifier.clone();
+        let running = self.btrfs_running.clone();
+        tokio::spawn(async move {
+            match agent.run_pass(&memory_store).await {
+                Ok(results) => {
+                    for notification in results.iter().filter_map(|r| r.notification()) {
+                        notifier.notify(notification);
+                    }
+                }
+                Err(e) => warn!("Btrfs maintenance failed: {}", e),
+            }
+            running.store(false, Ordering::SeqCst);
+        });
+    }
+
+    /// Start a Trivy scan of container images in the background when `[trivy]
+    /// schedule` fired since the last tick; each uncached image takes minutes
+    async fn start_image_scan(&self) {
+        let trivy_config = self.config.read().await.trivy.clone();
+        let now = Utc::now();
+        let since = std::mem::replace(&mut *self.last_image_scan_check.lock().await, now);
+        if !trivy_config.enabled {
+            return;
+        }
+        match report::next_scheduled(&trivy_config.schedule, since) {
+            Ok(at) if at <= now => {}
+            Ok(_) => return,
+            Err(e) => {
+                warn!("Trivy schedule: {}", e);
+                return;
+            }
+        }
+        if self.image_scan_running.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        bus::publish(BusEvent::MaintenanceScheduled {
+            task: "container image scan".to_string(),
+            at: now,
+        });
+        let scanner = ImageScanner::new(trivy_config);
+        let memory_store = self.memory_store.clone();
+        let notifier = self.notifier.clone();
+        let running = self.image_scan_running.clone();
+        tokio::spawn(async move {
+            match scanner.run(&memory_store).await {
+                Ok(run) => {
+                    info!(
+                        "Container image scan: {} scanned, {} cached, {} errors",
+                        run.scanned.len(),
+                        run.cached.len(),
+                        run.errors.len()
+                    );
+                    if let Some(notification) = run.notification() {
+                        notifier.notify(notification);
+                    }
+                }
+                Err(e) => warn!("Container image scan failed: {:#}", e),
+            }
+            running.store(false, Ordering::SeqCst);
+        });
+    }
+
+    /// Regenerate the local host profile once `[host_profile] regenerate_hours`
+    /// has passed. Probing and summarizing takes a while, so it runs in the
+    /// background; the changes it finds are logged.
+    async fn start_host_profile_refresh(&self) {
+        let profile_config = self.config.read().await.host_profile.clone();
+        if !profile_config.enabled
+            || !self.memory_store.is_persistent()
+            || self.profile_running.swap(true, Ordering::SeqCst)
+        {
+            return;
+        }
+
+        let memory_store = self.memory_store.clone();
+        let router = self.llm_router.clone();
+        let running = self.profile_running.clone();
+        tokio::spawn(async move {
+            let executor = CommandExecutor::Local;
+            match HostProfile::load(&memory_store, &executor.host_label()).await {
+                Ok(profile) if profile.is_due(&profile_config, Utc::now()) => {
+                    if let Err(e) =
+                        host_profile::regenerate(&memory_store, &router, &executor, &profile_config)
+                            .await
+                    {
+                        warn!("Host profile regeneration failed: {:#}", e);
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Failed to load the host profile: {:#}", e),
+            }
+            running.store(false, Ordering::SeqCst);
+        });
+    }
+
     /// Reload configuration from file
     async fn reload_config(&self) -> Result<()> {
         debug!("Reloading configuration...");
 
         // Try to load new config
         let new_config = if let Some(config_path) = self.get_config_path().await {
-            match Config::load(Some(config_path.to_str().unwrap())).await {
-                Ok(config) => config,
+            // Same checks as `jarvis config validate`; an invalid file never replaces a working config
+            let validation = match jarvis_core::config_validation::validate_file(&config_path).await {
+                Ok(validation) => validation,
                 Err(e) => {
                     warn!("Failed to reload config from {:?}: {}", config_path, e);
                     return Ok(());
                 }
+            };
+            for issue in &validation.issues {
+                warn!("{:?}: {}", config_path, issue);
+            }
+            match validation.into_result() {
+                Ok(config) => config,
+                Err(_) => {
+                    warn!(
+                        "Not applying {:?}: it has errors, keeping the current configuration",
+                        config_path
+                    );
+                    return Ok(());
+                }
             }
         } else {
             return Ok(());
         };
 
         // Update config
+        jarvis_core::net::configure(&new_config.network);
+        jarvis_core::read_only::configure(new_config.read_only);
         {
             let mut config = self.config.write().await;
             *config = new_config;
         }
+        // Pick up a changed report schedule on the next check
+        *self.next_report.lock().await = None;
+        *self.docker_agent.lock().await = None;
 
         // For now, we'll just log that config was reloaded
         // In the future, we could restart components that need the new config
@@ -316,6 +1094,21 @@ impl JarvisDaemon {
         // Clean up old memory entries - simplified since we don't have this method
         // We could implement this by querying old entries and removing them manually
 
+        // Average aging health metrics into coarser buckets to bound the table
+        match self.memory_store.downsample_metrics(Utc::now()).await {
+            Ok(0) => {}
+            Ok(replaced) => debug!("Downsampled {} metric points", replaced),
+            Err(e) => warn!("Failed to downsample health metrics: {}", e),
+        }
+
+        // Forget API idempotency keys whose window has passed
+        if let Some(api) = self.api.lock().await.as_ref() {
+            let expired = api.idempotency_keys().prune_expired();
+            if expired > 0 {
+                debug!("Dropped {} expired idempotency keys", expired);
+            }
+        }
+
         // Clean up temporary files
         if let Some(temp_dir) = self.get_temp_dir().await {
             if let Err(e) = self.cleanup_temp_files(&temp_dir).await {
@@ -336,6 +1129,23 @@ impl JarvisDaemon {
         Ok(())
     }
 
+    fn write_status_snapshot(&self, now: DateTime<Utc>) {
+        let Some(pid_file) = &self.pid_file else {
+            return;
+        };
+        let snapshot = StatusSnapshot {
+            updated_at: now,
+            ssh_pool: ssh_pool::pool().stats(),
+        };
+        let path = status_file(pid_file);
+        let written = serde_json::to_vec_pretty(&snapshot)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| Ok(std::fs::write(&path, json)?));
+        if let Err(e) = written {
+            debug!("Failed to write status snapshot {:?}: {}", path, e);
+        }
+    }
+
     /// Get current config file path
     async fn get_config_path(&self) -> Option<PathBuf> {
         // This would typically be stored during initialization
@@ -374,6 +1184,15 @@ impl JarvisDaemon {
     }
 }
 
+/// The state published for a health check, sensor, or custom check
+fn health_state(severity: NotifySeverity) -> &'static str {
+    match severity {
+        NotifySeverity::Critical => "critical",
+        NotifySeverity::Warning => "warning",
+        NotifySeverity::Info => "healthy",
+    }
+}
+
 /// Get daemon status from PID file
 async fn get_daemon_status(pid_file: &PathBuf) -> Result<DaemonStatus> {
     if !pid_file.exists() {
@@ -426,7 +1245,14 @@ enum DaemonStatus {
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() {
+    if let Err(e) = run().await {
+        eprintln!("Error: {:?}", e);
+        std::process::exit(jarvis_core::JarvisError::from_anyhow(&e).exit_code());
+    }
+}
+
+async fn run() -> Result<()> {
     // Initialize tracing
     tracing_subscriber::fmt()
         .with_env_filter(
@@ -553,6 +1379,17 @@ async fn main() -> Result<()> {
                 DaemonStatus::Running(pid) => {
                     println!("Jarvis Daemon is running (PID: {})", pid);
 
+                    let snapshot = std::fs::read(status_file(&pid_file))
+                        .ok()
+                        .and_then(|json| serde_json::from_slice::<StatusSnapshot>(&json).ok());
+                    if let Some(snapshot) = snapshot {
+                        println!(
+                            "SSH sessions: {} (as of {})",
+                            snapshot.ssh_pool.summary(),
+                            snapshot.updated_at.format("%Y-%m-%d %H:%M:%S UTC")
+                        );
+                    }
+
                     // TODO: Add more detailed status information
                     // - Uptime
                     // - Agent status
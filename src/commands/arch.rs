@@ -0,0 +1,812 @@
+// src/commands/arch.rs
+//! Drive the Arch Linux maintenance agent directly
+//!
+//! The agent is built in-process once per invocation from the daemon's
+//! configuration, so these commands work whether or not the service runs.
+
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use jarvis_arch::config_backup::{self, ChangeKind, DiffLine, FileChange};
+use jarvis_arch::operation_output::CommandStep;
+use jarvis_arch::zqlite_integration::MaintenanceRecord;
+use jarvis_arch::{
+    ArchAgent, ArchLinuxAgent, ArchOperation, BackupSet, BackupStore, BackupSummary, CleanupReport,
+    Config as ArchConfig, DryRunReport, ExecOptions, OperationOutput, OperationResult,
+    PackageUpdateReport, RollbackPlan, ServiceActionReport,
+};
+use jarvis_core::chat;
+use jarvis_core::pacman_lock::LockHeld;
+use jarvis_core::privilege::Privilege;
+use jarvis_core::report::ReportWindow;
+use jarvis_core::time_range;
+use jarvis_core::{MemoryStore, OutputFormat};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+
+use super::power::confirm;
+
+/// Configuration file shared with the jarvis-arch service
+const ARCH_CONFIG_PATH: &str = "/etc/jarvis/jarvis-arch.toml";
+
+#[derive(Subcommand)]
+pub enum ArchCommands {
+    /// Run a security scan; shows new and resolved findings
+    Scan {
+        /// Include the slower checks
+        #[arg(long)]
+        full: bool,
+        /// Also list findings that were already open before this scan
+        #[arg(long)]
+        all: bool,
+        /// Print every open finding in this format instead of the scan report
+        #[arg(long, value_enum)]
+        format: Option<ScanFormat>,
+    },
+    /// Check packages for known vulnerabilities (all installed when none given)
+    Vuln { packages: Vec<String> },
+    /// Clean the package cache and vacuum the journal (both when neither flag is given)
+    Cleanup {
+        /// Remove old package versions from the pacman cache
+        #[arg(long)]
+        cache: bool,
+        /// Vacuum journal entries older than two weeks
+        #[arg(long)]
+        logs: bool,
+    },
+    /// Rank mirrors with reflector and rewrite the mirrorlist
+    Mirrors {
+        /// Only use mirrors in this country, e.g. DE
+        #[arg(long)]
+        country: Option<String>,
+    },
+    /// Remove pacman's database lock once no package manager is running
+    UnlockDb {
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Check system health, including services
+    Health,
+    /// Show recorded maintenance operations
+    History {
+        /// How far back to look (e.g. 24h, 7d, yesterday, since monday)
+        #[arg(long, default_value = "7d")]
+        since: String,
+        /// Maximum number of records to read
+        #[arg(long, default_value_t = 50)]
+        limit: u32,
+    },
+    /// Take, inspect, and restore configuration backups
+    Backups {
+        #[command(subcommand)]
+        action: BackupCommands,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum BackupCommands {
+    /// Back up the configured paths now and prune by the retention policy
+    Create,
+    /// List backup sets, newest first
+    List {
+        /// Maximum number of sets to list
+        #[arg(long, default_value_t = 50)]
+        limit: u32,
+    },
+    /// Show the files a backup set changed
+    Show {
+        id: String,
+        /// List every file in the set, not just those changed since its parent
+        #[arg(long)]
+        all: bool,
+    },
+    /// Show the files that differ between two backup sets
+    Diff { from: String, to: String },
+    /// Preview what restoring a backup set would change, then restore it
+    Restore {
+        id: String,
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+#[derive(clap::ValueEnum, Clone, Copy)]
+pub enum ScanFormat {
+    /// SARIF 2.1.0, for CI and code scanning dashboards
+    Sarif,
+}
+
+pub async fn handle_arch_command(
+    cmd: ArchCommands,
+    memory: &MemoryStore,
+    format: OutputFormat,
+    dry_run: bool,
+) -> Result<()> {
+    let mut hide_known_findings = false;
+    let (name, operation) = match cmd {
+        ArchCommands::Scan {
+            full,
+            format: Some(ScanFormat::Sarif),
+            ..
+        } => return export_sarif(full).await,
+        ArchCommands::Scan { full, all, .. } => {
+            hide_known_findings = !all;
+            ("scan", ArchOperation::SecurityScan { full_scan: full })
+        }
+        ArchCommands::Vuln { packages } => (
+            "vuln",
+            ArchOperation::VulnerabilityScan {
+                packages: (!packages.is_empty()).then_some(packages),
+            },
+        ),
+        ArchCommands::Cleanup { cache, logs } => {
+            let both = !cache && !logs;
+            (
+                "cleanup",
+                ArchOperation::SystemCleanup {
+                    clean_cache: cache || both,
+                    clean_logs: logs || both,
+                },
+            )
+        }
+        ArchCommands::Mirrors { country } => {
+            ("mirrors", ArchOperation::UpdateMirrorlist { country })
+        }
+        ArchCommands::UnlockDb { yes } => return unlock_db(yes, dry_run).await,
+        ArchCommands::Health => (
+            "health",
+            ArchOperation::HealthCheck {
+                include_services: true,
+            },
+        ),
+        ArchCommands::History { since, limit } => {
+            let window = ReportWindow::parse(&since)?;
+            let agent = start_agent().await?;
+            let db = agent
+                .database()
+                .context("Maintenance history needs the agent database")?;
+            let records: Vec<MaintenanceRecord> = db
+                .get_maintenance_history(limit)
+                .await?
+                .into_iter()
+                .filter(|r| window.contains(r.started_at))
+                .collect();
+            return print_history(&records, &since, format);
+        }
+        ArchCommands::Backups {
+            action: BackupCommands::Create,
+        } => (
+            "backup",
+            ArchOperation::BackupConfigs {
+                destination: load_config()?
+                    .agent
+                    .config_backup
+                    .destination
+                    .display()
+                    .to_string(),
+            },
+        ),
+        ArchCommands::Backups { action } => {
+            return handle_backups(action, format, dry_run).await;
+        }
+    };
+
+    // Fail before building the agent rather than halfway through a change
+    if !dry_run
+        && let Err(refused) =
+            Privilege::detect(&load_config()?.agent.privilege).elevation(&operation.privileges())
+    {
+        anyhow::bail!(
+            "`jarvis arch {}` was refused: {}. Add --dry-run to preview it",
+            name,
+            refused
+        );
+    }
+
+    let agent = start_agent().await?;
+    let mut result = agent
+        .execute_operation_with_options(operation.clone(), ExecOptions { dry_run })
+        .await?;
+    // A run that died left the database locked; clear it and try once more
+    if let Some(lock) = stale_db_lock(&result)
+        && offer_unlock(&agent, &lock).await?
+    {
+        result = agent
+            .execute_operation_with_options(operation, ExecOptions { dry_run })
+            .await?;
+    }
+    // Long-standing findings were shown when they were new; the summary
+    // still counts them
+    if hide_known_findings
+        && let OperationOutput::Scan(scan) = &mut result.output
+        && let Some(findings) = &mut scan.findings
+    {
+        findings.still_present.clear();
+    }
+    // Chat attaches it with `/attach last-op`
+    if let Err(e) = memory
+        .store_document(
+            chat::LAST_OPERATION_DOCUMENT,
+            &serde_json::to_string(&result)?,
+        )
+        .await
+    {
+        tracing::warn!("Failed to record the operation result: {}", e);
+    }
+    print_result(name, &result, format)
+}
+
+/// The stale database lock an operation failed on, if that's why it failed
+fn stale_db_lock(result: &OperationResult) -> Option<LockHeld> {
+    result
+        .output
+        .db_lock()
+        .filter(|lock| lock.is_stale())
+        .cloned()
+}
+
+/// Ask to remove a stale lock when someone is at the terminal to answer;
+/// `true` once it is gone
+async fn offer_unlock(agent: &ArchLinuxAgent, lock: &LockHeld) -> Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        return Ok(false);
+    }
+    let Some(packages) = agent.package_manager() else {
+        return Ok(false);
+    };
+    println!(
+        "🔒 {} is {} old and no package manager is running",
+        lock.path.display(),
+        lock.age()
+    );
+    if !confirm("⚠️ Remove the stale lock and retry?")? {
+        return Ok(false);
+    }
+    packages.remove_stale_db_lock().await
+}
+
+/// Report pacman's database lock and, once confirmed, remove it if no
+/// package manager holds it
+async fn unlock_db(yes: bool, dry_run: bool) -> Result<()> {
+    let agent = start_agent().await?;
+    let packages = agent
+        .package_manager()
+        .context("Unlocking the package database needs the package manager subsystem")?;
+    let Some(lock) = packages.db_lock().await? else {
+        println!("✅ The pacman database is not locked");
+        return Ok(());
+    };
+    if !lock.is_stale() {
+        anyhow::bail!("{}", lock);
+    }
+
+    println!(
+        "🔒 {} is {} old and no package manager is running",
+        lock.path.display(),
+        lock.age()
+    );
+    if dry_run {
+        println!("🧪 Dry run: the lock was not removed");
+        return Ok(());
+    }
+    if !yes && !confirm("⚠️ Remove the stale lock?")? {
+        println!("Cancelled.");
+        return Ok(());
+    }
+    packages.remove_stale_db_lock().await?;
+    println!("✅ Removed {}", lock.path.display());
+    Ok(())
+}
+
+/// Refresh the checks SARIF covers and print every open finding. A check
+/// that can't run leaves its last results in the export.
+async fn export_sarif(full: bool) -> Result<()> {
+    let agent = start_agent().await?;
+    let mut checks = vec![
+        ArchOperation::SecurityScan { full_scan: full },
+        ArchOperation::ValidateConfigs,
+    ];
+    if full {
+        checks.push(ArchOperation::AURSecurityCheck { packages: None });
+    }
+    for operation in checks {
+        let name = operation.name();
+        let error = match agent.execute_operation(operation).await {
+            Ok(result) => match result.failure() {
+                None => continue,
+                Some(error) => error,
+            },
+            Err(e) => e.to_string(),
+        };
+        eprintln!("⚠️  {} failed, exporting its last results: {}", name, error);
+    }
+
+    let sarif = agent.open_findings().await?.to_sarif();
+    println!("{}", serde_json::to_string_pretty(&sarif)?);
+    Ok(())
+}
+
+/// Inspect the configuration backup store, or preview and restore a set
+async fn handle_backups(cmd: BackupCommands, format: OutputFormat, dry_run: bool) -> Result<()> {
+    let store = BackupStore::new(load_config()?.agent.config_backup.destination);
+    match cmd {
+        BackupCommands::Create => unreachable!("runs as an agent operation"),
+        BackupCommands::List { limit } => {
+            // The catalog is only as good as the last backup that updated
+            // it, so the store itself answers when the database is down
+            let agent = start_agent().await?;
+            let summaries = match agent.database() {
+                Some(db) => db.list_backup_sets(limit).await?,
+                None => {
+                    let mut summaries = store.summaries()?;
+                    summaries.reverse();
+                    summaries.truncate(limit as usize);
+                    summaries
+                }
+            };
+            print_backup_list(&summaries, store.root(), format)
+        }
+        BackupCommands::Show { id, all } => {
+            let set = store.load(&id)?;
+            let parent = set.parent.as_deref().map(|p| store.load(p)).transpose()?;
+            let changes = match &parent {
+                Some(parent) if !all => config_backup::diff_sets(parent, &set),
+                _ => set
+                    .files
+                    .keys()
+                    .map(|path| FileChange {
+                        path: path.clone(),
+                        kind: ChangeKind::Added,
+                    })
+                    .collect(),
+            };
+            let summary = set.summary(parent.as_ref());
+            if matches!(format, OutputFormat::Json) {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&serde_json::json!({
+                        "set": summary,
+                        "roots": set.roots,
+                        "files": changes,
+                    }))?
+                );
+                return Ok(());
+            }
+            println!(
+                "🗄️ Backup set {} ({})",
+                set.id,
+                set.created_at.format("%Y-%m-%d %H:%M UTC")
+            );
+            println!(
+                "   {} files, {} changed, {} stored of {}",
+                summary.file_count,
+                summary.changed_files,
+                format_bytes(summary.stored_bytes),
+                format_bytes(summary.total_bytes)
+            );
+            match &set.parent {
+                Some(parent) => println!("   Taken against {}", parent),
+                None => println!("   Full backup"),
+            }
+            print_changes(&changes);
+            Ok(())
+        }
+        BackupCommands::Diff { from, to } => {
+            let changes = config_backup::diff_sets(&store.load(&from)?, &store.load(&to)?);
+            if matches!(format, OutputFormat::Json) {
+                println!("{}", serde_json::to_string_pretty(&changes)?);
+                return Ok(());
+            }
+            if changes.is_empty() {
+                println!("✅ {} and {} back up the same files", from, to);
+                return Ok(());
+            }
+            println!("🔀 {} → {}", from, to);
+            print_changes(&changes);
+            Ok(())
+        }
+        BackupCommands::Restore { id, yes } => {
+            let set = store.load(&id)?;
+            let changes = store.restore_plan(&set)?;
+            if matches!(format, OutputFormat::Json) {
+                println!("{}", serde_json::to_string_pretty(&changes)?);
+            } else {
+                print_restore_preview(&store, &set, &changes)?;
+            }
+            if changes.is_empty() {
+                if !matches!(format, OutputFormat::Json) {
+                    println!("✅ Nothing to restore; the system matches {}", id);
+                }
+                return Ok(());
+            }
+            if dry_run {
+                println!("🧪 Dry run: nothing was restored");
+                return Ok(());
+            }
+            if !jarvis_arch::running_as_root() {
+                anyhow::bail!(
+                    "Restoring configuration files needs root; re-run with sudo, or add --dry-run to preview it"
+                );
+            }
+            if !yes && !confirm(&format!("⚠️ Restore {} files from {}?", changes.len(), id))? {
+                println!("Cancelled.");
+                return Ok(());
+            }
+
+            let agent = start_agent().await?;
+            let result = agent
+                .execute_operation(ArchOperation::RestoreConfigs { source: id })
+                .await?;
+            print_result("backups restore", &result, format)
+        }
+    }
+}
+
+/// Show how a recorded operation can be undone and, once confirmed, undo it
+/// and record the rollback linked to the original
+pub async fn handle_rollback(
+    target: &str,
+    yes: bool,
+    format: OutputFormat,
+    dry_run: bool,
+) -> Result<()> {
+    let agent = start_agent().await?;
+    let plan = agent.plan_rollback(target).await?;
+    print_rollback_plan(&plan, format)?;
+
+    if plan.is_empty() {
+        anyhow::bail!(
+            "No rollback path is left for operation {}",
+            plan.operation_id
+        );
+    }
+    if dry_run {
+        println!("🧪 Dry run: nothing was rolled back");
+        return Ok(());
+    }
+    if !jarvis_arch::running_as_root() {
+        anyhow::bail!(
+            "Rolling back changes system files and needs root; re-run with sudo, or add --dry-run to preview it"
+        );
+    }
+    if !yes && !confirm(&format!("⚠️ Roll back {}?", plan.operation_id))? {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    let record = agent.execute_rollback(&plan).await?;
+    if matches!(format, OutputFormat::Json) {
+        println!("{}", serde_json::to_string_pretty(&record)?);
+    } else if let Some(error) = &record.error_message {
+        println!("❌ Rollback {} failed: {}", record.id, error);
+    } else {
+        println!(
+            "✅ Rolled back {}; recorded as {}",
+            plan.operation_id, record.id
+        );
+    }
+    if record.error_message.is_some() {
+        anyhow::bail!("Rollback of {} failed", plan.operation_id);
+    }
+    Ok(())
+}
+
+/// The agent configuration, or the defaults when the file doesn't exist
+pub(crate) fn load_config() -> Result<ArchConfig> {
+    let path = PathBuf::from(ARCH_CONFIG_PATH);
+    if path.exists() {
+        ArchConfig::load_from_file(&path)
+            .with_context(|| format!("Failed to load {}", ARCH_CONFIG_PATH))
+    } else {
+        Ok(ArchConfig::load_with_defaults())
+    }
+}
+
+/// Build and initialize the agent; subsystems that fail to start are logged
+/// and only matter to operations that need them
+async fn start_agent() -> Result<ArchLinuxAgent> {
+    let mut agent = ArchLinuxAgent::new();
+    agent.initialize(load_config()?).await?;
+    for status in agent
+        .subsystems()
+        .iter()
+        .filter(|s| s.enabled && !s.initialized)
+    {
+        tracing::warn!(
+            "Arch agent subsystem {} unavailable: {}",
+            status.name,
+            status.last_error.as_deref().unwrap_or("not initialized")
+        );
+    }
+    Ok(agent)
+}
+
+fn print_result(name: &str, result: &OperationResult, format: OutputFormat) -> Result<()> {
+    if matches!(format, OutputFormat::Json) {
+        println!("{}", serde_json::to_string_pretty(result)?);
+        return Ok(());
+    }
+
+    // Read-only operations also carry the dry_run marker but return their
+    // usual output rather than a report
+    if result.metadata.contains_key("dry_run")
+        && let OperationOutput::Raw(output) = &result.output
+        && let Ok(report) = serde_json::from_value::<DryRunReport>(output.clone())
+    {
+        print_dry_run(name, &report);
+        return Ok(());
+    }
+
+    // Steps that ran but failed are reported in the output, not as an error
+    let succeeded = result.succeeded();
+    let icon = if succeeded { "✅" } else { "❌" };
+    println!(
+        "{} arch {} {} ({} ms)",
+        icon,
+        name,
+        if succeeded { "completed" } else { "failed" },
+        result.duration_ms
+    );
+    if let Some(error) = &result.error {
+        println!("   {}", error);
+    }
+    match &result.output {
+        OperationOutput::PackageUpdate(report) => print_package_update(report),
+        OperationOutput::Cleanup(CleanupReport { steps, .. })
+        | OperationOutput::ServiceAction(ServiceActionReport { steps, .. }) => {
+            for step in steps {
+                print_step(step);
+            }
+        }
+        output if !output.is_null() => {
+            println!("{}", serde_json::to_string_pretty(output)?);
+        }
+        _ => {}
+    }
+
+    if !succeeded {
+        anyhow::bail!("arch {} failed", name);
+    }
+    Ok(())
+}
+
+fn print_rollback_plan(plan: &RollbackPlan, format: OutputFormat) -> Result<()> {
+    if matches!(format, OutputFormat::Json) {
+        println!("{}", serde_json::to_string_pretty(plan)?);
+        return Ok(());
+    }
+
+    println!(
+        "⏪ Rolling back {} ({})",
+        plan.operation_id, plan.operation_type
+    );
+    for (i, action) in plan.actions.iter().enumerate() {
+        println!("  {}. {}", i + 1, action.describe());
+        println!("     $ {}", action.command());
+    }
+    for reason in &plan.unavailable {
+        println!("  🚫 {}", reason);
+    }
+    for note in &plan.notes {
+        println!("  ℹ️ {}", note);
+    }
+    Ok(())
+}
+
+fn print_dry_run(name: &str, report: &DryRunReport) {
+    println!("🧪 Dry run of arch {}; nothing was changed", name);
+    for action in &report.actions {
+        println!("  Would run: {}", action);
+    }
+    for note in &report.notes {
+        println!("  Would: {}", note);
+    }
+    for preview in &report.previews {
+        let icon = if preview.success { "🔍" } else { "⚠️" };
+        println!("\n{} {}", icon, preview.command);
+        for line in preview.output.lines() {
+            println!("    {}", line);
+        }
+    }
+}
+
+fn print_package_update(report: &PackageUpdateReport) {
+    if let Some(count) = report.packages_updated {
+        println!("   {} packages updated", count);
+    }
+    let affected = report.packages_affected();
+    if !affected.is_empty() {
+        println!("   {}", affected.join(", "));
+    }
+    if let Some(error) = &report.error {
+        println!("   {}", error);
+    }
+    if report.reboot_required == Some(true) {
+        println!("   🔁 Reboot required to finish the update");
+    }
+    if let Some(incomplete) = report.incomplete.as_ref().filter(|i| !i.is_empty()) {
+        println!("   ⚠️ Not applied: {}", incomplete.join(", "));
+    }
+}
+
+fn print_step(step: &CommandStep) {
+    let icon = if step.success { "✅" } else { "❌" };
+    println!("  {} {}", icon, step.command);
+    for line in step.output.lines().chain(step.error.lines()) {
+        println!("      {}", line);
+    }
+}
+
+fn print_backup_list(summaries: &[BackupSummary], root: &Path, format: OutputFormat) -> Result<()> {
+    if matches!(format, OutputFormat::Json) {
+        println!("{}", serde_json::to_string_pretty(summaries)?);
+        return Ok(());
+    }
+    if summaries.is_empty() {
+        println!("📭 No configuration backups in {}", root.display());
+        return Ok(());
+    }
+
+    println!("🗄️ Configuration backups in {}:", root.display());
+    for summary in summaries {
+        println!(
+            "  {:<20} {}  {:>5} files  {:>5} changed  {:>9} stored  {:>9} total",
+            summary.id,
+            summary.created_at.format("%Y-%m-%d %H:%M"),
+            summary.file_count,
+            summary.changed_files,
+            format_bytes(summary.stored_bytes),
+            format_bytes(summary.total_bytes)
+        );
+    }
+    Ok(())
+}
+
+fn print_changes(changes: &[FileChange]) {
+    for change in changes {
+        let marker = match change.kind {
+            ChangeKind::Added => "+",
+            ChangeKind::Modified => "~",
+            ChangeKind::Removed => "-",
+        };
+        println!("  {} {}", marker, change.path.display());
+    }
+}
+
+/// Lines of unchanged context shown around each change
+const DIFF_CONTEXT: usize = 3;
+
+/// What restoring `set` would write, file by file, as a diff from the file
+/// on the system to the backed-up copy
+fn print_restore_preview(store: &BackupStore, set: &BackupSet, changes: &[FileChange]) -> Result<()> {
+    let color = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+    let paint = |code: &str, text: &str| {
+        if color {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            text.to_string()
+        }
+    };
+
+    println!("⏪ Restoring {} would change {} files", set.id, changes.len());
+    for change in changes {
+        let entry = &set.files[&change.path];
+        let backup = store.read(set, &change.path)?;
+        println!("\n{}", paint("1", &format!("📄 {}", change.path.display())));
+
+        let current = match config_backup::current_entry(&change.path)? {
+            Some(current) => current,
+            None => {
+                println!("   (missing; would be created with mode {:o})", entry.mode);
+                for line in String::from_utf8_lossy(&backup).lines() {
+                    println!("{}", paint("32", &format!("+{}", line)));
+                }
+                continue;
+            }
+        };
+        if current.mode != entry.mode {
+            println!("   mode {:o} → {:o}", current.mode, entry.mode);
+        }
+        if (current.uid, current.gid) != (entry.uid, entry.gid) {
+            println!(
+                "   owner {}:{} → {}:{}",
+                current.uid, current.gid, entry.uid, entry.gid
+            );
+        }
+        if current.hash == entry.hash {
+            continue;
+        }
+
+        let on_disk = std::fs::read(&change.path)
+            .with_context(|| format!("Failed to read {}", change.path.display()))?;
+        let Some(lines) = config_backup::line_diff(&on_disk, &backup) else {
+            println!(
+                "   binary or too long to compare: {} → {}",
+                format_bytes(current.size),
+                format_bytes(entry.size)
+            );
+            continue;
+        };
+        // Unchanged lines only within DIFF_CONTEXT of a change
+        let changed: Vec<usize> = lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| !matches!(line, DiffLine::Same(_)))
+            .map(|(i, _)| i)
+            .collect();
+        let near_change = |i: usize| changed.iter().any(|&c| c.abs_diff(i) <= DIFF_CONTEXT);
+        let mut skipped = false;
+        for (i, line) in lines.iter().enumerate() {
+            match line {
+                DiffLine::Same(_) if !near_change(i) => {
+                    if !skipped {
+                        println!("{}", paint("36", "   ..."));
+                        skipped = true;
+                    }
+                    continue;
+                }
+                DiffLine::Same(text) => println!(" {}", text),
+                DiffLine::Removed(text) => println!("{}", paint("31", &format!("-{}", text))),
+                DiffLine::Added(text) => println!("{}", paint("32", &format!("+{}", text))),
+            }
+            skipped = false;
+        }
+    }
+    println!();
+    Ok(())
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+fn print_history(records: &[MaintenanceRecord], since: &str, format: OutputFormat) -> Result<()> {
+    if matches!(format, OutputFormat::Json) {
+        println!("{}", serde_json::to_string_pretty(records)?);
+        return Ok(());
+    }
+    if records.is_empty() {
+        println!("📭 No maintenance recorded {}", time_range::describe(since));
+        return Ok(());
+    }
+
+    println!("🛠️ Maintenance {}:", time_range::describe(since));
+    for record in records {
+        let duration = record
+            .duration_ms
+            .map(|ms| format!("{} ms", ms))
+            .unwrap_or_else(|| "-".to_string());
+        println!(
+            "  {}  {:<22} {:<12} {:>3} packages  {}",
+            record.started_at.format("%Y-%m-%d %H:%M"),
+            record.operation_type,
+            format!("{:?}", record.status),
+            record.packages_affected.len(),
+            duration
+        );
+        let mut ids = format!("      id {}", record.id);
+        if let Some(snapshot) = record.snapshot_id {
+            ids.push_str(&format!(", snapshot #{}", snapshot));
+        }
+        if let Some(original) = record.rollback_of {
+            ids.push_str(&format!(", rolls back {}", original));
+        }
+        println!("{}", ids);
+        if let Some(error) = &record.error_message {
+            println!("      ❌ {}", error);
+        }
+    }
+    Ok(())
+}
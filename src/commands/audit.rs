@@ -0,0 +1,70 @@
+// src/commands/audit.rs
+//! MCP audit log commands
+
+use anyhow::Result;
+use clap::Subcommand;
+use jarvis_core::{AuditStatus, MemoryStore};
+
+#[derive(Subcommand)]
+pub enum AuditCommands {
+    /// Show the most recent MCP tool invocations
+    Tail {
+        /// Number of entries to show
+        #[arg(short = 'n', long, default_value = "20")]
+        limit: i32,
+        /// Print entries as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+pub async fn handle_audit_command(cmd: AuditCommands, memory: &MemoryStore) -> Result<()> {
+    memory.require_history("The audit log")?;
+    match cmd {
+        AuditCommands::Tail { limit, json } => tail_audit_log(memory, limit, json).await,
+    }
+}
+
+async fn tail_audit_log(memory: &MemoryStore, limit: i32, json: bool) -> Result<()> {
+    let mut entries = memory.recent_audit_entries(limit).await?;
+    // Oldest first, like `tail`
+    entries.reverse();
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+
+    if entries.is_empty() {
+        println!("📋 No MCP tool calls recorded yet");
+        return Ok(());
+    }
+
+    println!("📋 MCP Audit Log (last {} entries):", entries.len());
+    for entry in entries {
+        let icon = match entry.status {
+            AuditStatus::Success => "✅",
+            AuditStatus::Error => "❌",
+            AuditStatus::RateLimited => "⏳",
+        };
+        let tool = match &entry.action {
+            Some(action) => format!("{}:{}", entry.tool, action),
+            None => entry.tool.clone(),
+        };
+
+        println!(
+            "{} {} {:<32} {:>6}ms  caller={}  args={}",
+            icon,
+            entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            tool,
+            entry.duration_ms,
+            entry.caller.as_deref().unwrap_or("unknown"),
+            entry.arguments
+        );
+        if let Some(error) = &entry.error {
+            println!("      └─ {}", error);
+        }
+    }
+
+    Ok(())
+}
@@ -4,7 +4,7 @@
 use anyhow::Result;
 use clap::Subcommand;
 use jarvis_agent::{BlockchainAgentOrchestrator, OrchestratorConfig};
-use jarvis_core::Config;
+use jarvis_core::{Config, Intent, LLMRouter};
 use serde_json;
 use tracing::{info, warn};
 
@@ -29,6 +29,17 @@ pub enum BlockchainCommands {
         #[arg(value_enum)]
         analysis_type: AnalysisType,
     },
+    /// Audit a smart contract source file with the LLM
+    Audit {
+        /// Path to the contract source
+        contract: String,
+        /// Security level: basic, standard, strict
+        #[arg(long, default_value = "standard")]
+        security_level: String,
+        /// Ask two LLM backends and merge their findings
+        #[arg(long)]
+        consensus: bool,
+    },
     /// Stop all blockchain agents
     Stop,
 }
@@ -50,6 +61,11 @@ pub async fn handle_blockchain_command(cmd: BlockchainCommands, config: &Config)
         BlockchainCommands::Analyze { analysis_type } => {
             request_analysis(config, analysis_type).await
         }
+        BlockchainCommands::Audit {
+            contract,
+            security_level,
+            consensus,
+        } => audit_contract(config, &contract, &security_level, consensus).await,
         BlockchainCommands::Stop => stop_agents(config).await,
     }
 }
@@ -326,6 +342,40 @@ async fn request_analysis(config: &Config, analysis_type: AnalysisType) -> Resul
     Ok(())
 }
 
+async fn audit_contract(
+    config: &Config,
+    contract: &str,
+    security_level: &str,
+    consensus: bool,
+) -> Result<()> {
+    info!("Auditing contract {} ({})", contract, security_level);
+
+    let source = tokio::fs::read_to_string(contract).await?;
+    let llm_router = LLMRouter::new(config).await?;
+
+    let prompt = format!(
+        "Audit this smart contract at the '{}' security level. List vulnerabilities by \
+         severity, gas optimization opportunities, and concrete remediation steps.\n\n{}",
+        security_level, source
+    );
+
+    println!("🔒 Smart Contract Audit: {}", contract);
+    println!("================================");
+    println!();
+
+    if consensus {
+        let result = llm_router
+            .generate_with_consensus(&prompt, Intent::Code)
+            .await?;
+        println!("{}", result.render());
+    } else {
+        let report = llm_router.generate_with_intent(&prompt, Intent::Code).await?;
+        println!("{}", report);
+    }
+
+    Ok(())
+}
+
 async fn stop_agents(_config: &Config) -> Result<()> {
     info!("Stopping blockchain agents...");
 
@@ -0,0 +1,149 @@
+// src/commands/doctor.rs
+//! `jarvis doctor`: check that this host lets Jarvis do its job
+//!
+//! Reports whether Jarvis may change the system at all (read-only mode), how
+//! operations that need root get it here, and for each capability whether
+//! sudo would allow it without a password. `--sudoers`
+//! prints the narrow rules to install instead of a blanket `ALL`. Configured
+//! `[[health_checks]]` scripts are validated and run once.
+
+use anyhow::Result;
+use jarvis_core::health_checks::{self, CheckOutcome, HealthCheckConfig};
+use jarvis_core::privilege::{self, Capability, Privilege, Strategy};
+use jarvis_core::{OutputFormat, SystemRunner};
+use std::collections::BTreeMap;
+
+/// The account jarvisd and the jarvis-arch service run as
+const SERVICE_USER: &str = "jarvis";
+
+pub async fn handle_doctor(
+    sudoers: bool,
+    user: Option<&str>,
+    checks: &[HealthCheckConfig],
+    format: OutputFormat,
+) -> Result<()> {
+    if sudoers {
+        print!(
+            "{}",
+            privilege::sudoers(user.unwrap_or(SERVICE_USER), &Capability::ALL)
+        );
+        return Ok(());
+    }
+
+    let agent = super::arch::load_config()?.agent;
+    // The jarvis-arch agent's own setting also turns the mode on for it
+    let read_only = jarvis_core::read_only::is_read_only();
+    let agent_read_only = read_only || agent.read_only;
+    let config = agent.privilege;
+    let privilege = Privilege::detect(&config);
+    let runner = SystemRunner::default();
+    let mut sudo = BTreeMap::new();
+    for capability in Capability::ALL {
+        sudo.insert(
+            capability.name(),
+            privilege::sudo_allows(&runner, capability).await,
+        );
+    }
+    let polkit_installed = config.polkit_policy.exists();
+    let custom = custom_checks(&runner, checks).await;
+
+    if matches!(format, OutputFormat::Json) {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&serde_json::json!({
+                "read_only": read_only,
+                "arch_agent_read_only": agent_read_only,
+                "privilege": {
+                    "configured": config.strategy,
+                    "strategy": privilege.strategy(),
+                    "reason": privilege.reason(),
+                    "sudo_without_password": sudo,
+                    "polkit_policy": config.polkit_policy,
+                    "polkit_policy_installed": polkit_installed,
+                },
+                "custom_checks": checks
+                    .iter()
+                    .zip(&custom)
+                    .map(|(check, result)| match result {
+                        Ok(outcome) => serde_json::json!(outcome),
+                        Err(problem) => serde_json::json!({ "name": check.name, "problem": problem }),
+                    })
+                    .collect::<Vec<_>>(),
+            }))?
+        );
+        return Ok(());
+    }
+
+    println!("🩺 Mode");
+    if read_only {
+        println!("  🔒 Read-only: Jarvis observes and advises but changes nothing");
+    } else {
+        println!("  ✏️  Read-write: Jarvis may install, remove, restart, and fix when asked");
+        if agent_read_only {
+            println!("  🔒 The jarvis-arch agent is read-only ([agent] read_only)");
+        }
+    }
+    println!();
+
+    let me = std::env::var("USER").unwrap_or_else(|_| "this user".to_string());
+    println!("🩺 Privilege");
+    println!(
+        "  Strategy: {} ({})",
+        privilege.strategy(),
+        privilege.reason()
+    );
+    for capability in Capability::ALL {
+        if sudo[capability.name()] {
+            println!(
+                "  ✅ sudo lets {} run {} without a password",
+                me,
+                capability.describe()
+            );
+        } else {
+            println!(
+                "  ❌ sudo has no passwordless rule for {} running {}",
+                me,
+                capability.describe()
+            );
+        }
+    }
+    println!(
+        "  {} polkit policy {}",
+        if polkit_installed { "✅" } else { "➖" },
+        config.polkit_policy.display()
+    );
+    if privilege.strategy() != Strategy::Root && !sudo.values().all(|allowed| *allowed) {
+        println!();
+        println!(
+            "Print narrow sudoers rules with `jarvis doctor --sudoers [--user {}]`",
+            SERVICE_USER
+        );
+    }
+
+    if !custom.is_empty() {
+        println!();
+        println!("🩺 Custom checks");
+        for (check, result) in checks.iter().zip(&custom) {
+            match result {
+                Ok(outcome) => println!("  {} {}", outcome.health().icon(), outcome.describe()),
+                Err(problem) => println!("  ❌ {}: {}", check.name, problem),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Each configured check's outcome, or why it cannot run
+async fn custom_checks(
+    runner: &SystemRunner,
+    checks: &[HealthCheckConfig],
+) -> Vec<std::result::Result<CheckOutcome, String>> {
+    let mut results = Vec::new();
+    for check in checks {
+        results.push(match check.problem() {
+            Some(problem) => Err(problem),
+            None => Ok(health_checks::run_check(runner, check).await),
+        });
+    }
+    results
+}
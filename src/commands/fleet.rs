@@ -0,0 +1,87 @@
+// src/commands/fleet.rs
+//! Multi-host status commands
+
+use anyhow::Result;
+use clap::Subcommand;
+use jarvis_core::config::Config;
+use jarvis_core::fleet::fleet_status;
+use jarvis_core::{CommandExecutor, FleetReport, OutputFormat, SshTarget};
+use std::time::Duration;
+
+#[derive(Subcommand)]
+pub enum FleetCommands {
+    /// Probe every host in [remote.hosts] and summarize their health
+    Status {
+        /// Refresh every N seconds until interrupted
+        #[arg(long, value_name = "SECS", num_args = 0..=1, default_missing_value = "30")]
+        watch: Option<u64>,
+        /// Include this machine in the report
+        #[arg(long)]
+        local: bool,
+    },
+}
+
+/// Returns whether any host is critical or unreachable, so the caller can set
+/// the exit code
+pub async fn handle_fleet_command(
+    cmd: FleetCommands,
+    config: &Config,
+    format: OutputFormat,
+) -> Result<bool> {
+    match cmd {
+        FleetCommands::Status { watch, local } => {
+            let mut names: Vec<&String> = config.remote.hosts.keys().collect();
+            names.sort();
+            if names.is_empty() && !local {
+                anyhow::bail!("No hosts configured; add [remote.hosts.<name>] entries or pass --local");
+            }
+
+            let mut hosts: Vec<(String, CommandExecutor)> = names
+                .into_iter()
+                .map(|name| {
+                    let target = SshTarget::resolve(name, &config.remote);
+                    (name.clone(), CommandExecutor::ssh(target))
+                })
+                .collect();
+            if local {
+                hosts.insert(0, ("localhost".to_string(), CommandExecutor::Local));
+            }
+
+            let timeout = Duration::from_secs(config.remote.fleet_timeout_secs);
+            let concurrency = config.remote.fleet_concurrency;
+            let thresholds = &config.remote.fleet_thresholds;
+
+            let Some(interval) = watch else {
+                let report = fleet_status(hosts, timeout, concurrency, thresholds).await;
+                print_report(&report, format)?;
+                return Ok(report.has_critical());
+            };
+
+            loop {
+                let report = fleet_status(hosts.clone(), timeout, concurrency, thresholds).await;
+                if format == OutputFormat::Pretty {
+                    // Clear the screen between refreshes
+                    print!("\x1B[2J\x1B[H");
+                }
+                print_report(&report, format)?;
+                tokio::time::sleep(Duration::from_secs(interval)).await;
+            }
+        }
+    }
+}
+
+fn print_report(report: &FleetReport, format: OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(report)?),
+        OutputFormat::Pretty => {
+            println!(
+                "🌐 Fleet Status ({}) {} {:?}\n",
+                report.generated_at.format("%Y-%m-%d %H:%M:%S UTC"),
+                report.overall().icon(),
+                report.overall()
+            );
+            print!("{}", report.render_table());
+        }
+    }
+    Ok(())
+}
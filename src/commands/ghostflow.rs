@@ -0,0 +1,290 @@
+// src/commands/ghostflow.rs
+//! Workflow import/export and templates against a running GhostFlow server
+
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use jarvis_core::OutputFormat;
+use jarvis_core::net::CheckedSend;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::PathBuf;
+
+#[derive(Subcommand)]
+pub enum GhostflowCommands {
+    /// Export a workflow as portable YAML
+    Export {
+        /// Workflow id
+        id: String,
+        /// Write to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// GhostFlow server URL
+        #[arg(long, env = "GHOSTFLOW_URL", default_value = "http://127.0.0.1:8080")]
+        server: String,
+        /// GhostFlow API key
+        #[arg(long, env = "GHOSTFLOW_API_KEY", hide_env_values = true)]
+        api_key: Option<String>,
+    },
+    /// Import a workflow from portable YAML (`-` reads stdin)
+    Import {
+        file: PathBuf,
+        /// GhostFlow server URL
+        #[arg(long, env = "GHOSTFLOW_URL", default_value = "http://127.0.0.1:8080")]
+        server: String,
+        /// GhostFlow API key
+        #[arg(long, env = "GHOSTFLOW_API_KEY", hide_env_values = true)]
+        api_key: Option<String>,
+    },
+    /// Built-in workflow templates
+    Templates {
+        #[command(subcommand)]
+        action: TemplateCommands,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TemplateCommands {
+    /// List templates and their parameters
+    List {
+        /// GhostFlow server URL
+        #[arg(long, env = "GHOSTFLOW_URL", default_value = "http://127.0.0.1:8080")]
+        server: String,
+        /// GhostFlow API key
+        #[arg(long, env = "GHOSTFLOW_API_KEY", hide_env_values = true)]
+        api_key: Option<String>,
+    },
+    /// Create a workflow from a template
+    Instantiate {
+        /// Template name, e.g. nightly-update-with-snapshot
+        name: String,
+        /// Parameter value as key=value; repeat for each parameter
+        #[arg(long = "set", value_name = "KEY=VALUE")]
+        set: Vec<String>,
+        /// GhostFlow server URL
+        #[arg(long, env = "GHOSTFLOW_URL", default_value = "http://127.0.0.1:8080")]
+        server: String,
+        /// GhostFlow API key
+        #[arg(long, env = "GHOSTFLOW_API_KEY", hide_env_values = true)]
+        api_key: Option<String>,
+    },
+}
+
+pub async fn handle_ghostflow_command(cmd: GhostflowCommands, format: OutputFormat) -> Result<()> {
+    let client = jarvis_core::net::client();
+
+    match cmd {
+        GhostflowCommands::Export {
+            id,
+            output,
+            server,
+            api_key,
+        } => {
+            let url = format!(
+                "{}/api/workflows/{}/export",
+                server.trim_end_matches('/'),
+                id
+            );
+            let response = authorize(client.get(&url), api_key.as_deref())
+                .checked_send()
+                .await
+                .with_context(|| format!("Failed to reach GhostFlow at {}", server))?;
+            let status = response.status();
+            let body = response.text().await?;
+            if !status.is_success() {
+                return Err(anyhow::anyhow!(
+                    "Export failed ({}): {}",
+                    status,
+                    api_error(&body)
+                ));
+            }
+
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, &body)
+                        .with_context(|| format!("Failed to write {}", path.display()))?;
+                    if matches!(format, OutputFormat::Json) {
+                        println!(
+                            "{}",
+                            serde_json::json!({"id": id, "path": path.display().to_string()})
+                        );
+                    } else {
+                        println!("📤 Exported workflow {} to {}", id, path.display());
+                    }
+                }
+                None => print!("{}", body),
+            }
+            Ok(())
+        }
+        GhostflowCommands::Import {
+            file,
+            server,
+            api_key,
+        } => {
+            let yaml = if file.as_os_str() == "-" {
+                let mut yaml = String::new();
+                std::io::stdin()
+                    .read_to_string(&mut yaml)
+                    .context("Failed to read stdin")?;
+                yaml
+            } else {
+                std::fs::read_to_string(&file)
+                    .with_context(|| format!("Failed to read {}", file.display()))?
+            };
+
+            let url = format!("{}/api/workflows/import", server.trim_end_matches('/'));
+            let response = authorize(client.post(&url), api_key.as_deref())
+                .header(reqwest::header::CONTENT_TYPE, "application/yaml")
+                .body(yaml)
+                .checked_send()
+                .await
+                .with_context(|| format!("Failed to reach GhostFlow at {}", server))?;
+            let status = response.status();
+            let body = response.text().await?;
+            if !status.is_success() {
+                return Err(anyhow::anyhow!(
+                    "Import failed ({}): {}",
+                    status,
+                    api_error(&body)
+                ));
+            }
+
+            let workflow: serde_json::Value = serde_json::from_str(&body)?;
+            let workflow = &workflow["data"];
+            if matches!(format, OutputFormat::Json) {
+                println!("{}", serde_json::to_string_pretty(workflow)?);
+            } else {
+                println!(
+                    "📥 Imported workflow '{}' as {}",
+                    workflow["name"].as_str().unwrap_or("unnamed"),
+                    workflow["id"].as_str().unwrap_or("?")
+                );
+            }
+            Ok(())
+        }
+        GhostflowCommands::Templates { action } => handle_template_command(action, format).await,
+    }
+}
+
+async fn handle_template_command(cmd: TemplateCommands, format: OutputFormat) -> Result<()> {
+    let client = jarvis_core::net::client();
+
+    match cmd {
+        TemplateCommands::List { server, api_key } => {
+            let url = format!("{}/api/templates", server.trim_end_matches('/'));
+            let response = authorize(client.get(&url), api_key.as_deref())
+                .checked_send()
+                .await
+                .with_context(|| format!("Failed to reach GhostFlow at {}", server))?;
+            let status = response.status();
+            let body = response.text().await?;
+            if !status.is_success() {
+                return Err(anyhow::anyhow!(
+                    "Listing templates failed ({}): {}",
+                    status,
+                    api_error(&body)
+                ));
+            }
+
+            let templates: serde_json::Value = serde_json::from_str(&body)?;
+            let templates = &templates["data"];
+            if matches!(format, OutputFormat::Json) {
+                println!("{}", serde_json::to_string_pretty(templates)?);
+                return Ok(());
+            }
+            println!("🧩 Workflow templates:");
+            for template in templates.as_array().into_iter().flatten() {
+                println!(
+                    "\n  {} (v{})",
+                    template["name"].as_str().unwrap_or("?"),
+                    template["template_version"].as_str().unwrap_or("?")
+                );
+                println!("    {}", template["description"].as_str().unwrap_or(""));
+                for parameter in template["parameters"].as_array().into_iter().flatten() {
+                    let default = match &parameter["default"] {
+                        serde_json::Value::Null => "required".to_string(),
+                        default => format!("default {}", default),
+                    };
+                    println!(
+                        "    • {} ({}, {}): {}",
+                        parameter["name"].as_str().unwrap_or("?"),
+                        parameter["type"].as_str().unwrap_or("?"),
+                        default,
+                        parameter["description"].as_str().unwrap_or("")
+                    );
+                }
+            }
+            Ok(())
+        }
+        TemplateCommands::Instantiate {
+            name,
+            set,
+            server,
+            api_key,
+        } => {
+            let parameters = parse_set(&set)?;
+            let url = format!(
+                "{}/api/templates/{}/instantiate",
+                server.trim_end_matches('/'),
+                name
+            );
+            let response = authorize(client.post(&url), api_key.as_deref())
+                .json(&serde_json::json!({ "parameters": parameters }))
+                .checked_send()
+                .await
+                .with_context(|| format!("Failed to reach GhostFlow at {}", server))?;
+            let status = response.status();
+            let body = response.text().await?;
+            if !status.is_success() {
+                return Err(anyhow::anyhow!(
+                    "Instantiating '{}' failed ({}): {}",
+                    name,
+                    status,
+                    api_error(&body)
+                ));
+            }
+
+            let workflow: serde_json::Value = serde_json::from_str(&body)?;
+            let workflow = &workflow["data"];
+            if matches!(format, OutputFormat::Json) {
+                println!("{}", serde_json::to_string_pretty(workflow)?);
+            } else {
+                println!(
+                    "🧩 Created workflow '{}' as {} from template {}",
+                    workflow["name"].as_str().unwrap_or("unnamed"),
+                    workflow["id"].as_str().unwrap_or("?"),
+                    name
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+/// `--set key=value` pairs; the server reads each value as its parameter's type
+fn parse_set(pairs: &[String]) -> Result<BTreeMap<String, String>> {
+    pairs
+        .iter()
+        .map(|pair| {
+            let (key, value) = pair
+                .split_once('=')
+                .with_context(|| format!("Expected key=value, got '{}'", pair))?;
+            Ok((key.trim().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Attach the API key as a bearer token when one is configured
+fn authorize(request: reqwest::RequestBuilder, api_key: Option<&str>) -> reqwest::RequestBuilder {
+    match api_key {
+        Some(key) => request.bearer_auth(key),
+        None => request,
+    }
+}
+
+/// The `error` field of an API error body, or the body itself
+fn api_error(body: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|v| v["error"].as_str().map(|s| s.to_string()))
+        .unwrap_or_else(|| body.trim().to_string())
+}
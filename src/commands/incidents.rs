@@ -0,0 +1,123 @@
+// src/commands/incidents.rs
+//! Inspect incidents correlated by jarvisd
+
+use anyhow::Result;
+use chrono::Utc;
+use clap::Subcommand;
+use jarvis_core::incidents::{Incident, IncidentLog, format_duration};
+use jarvis_core::{MemoryStore, OutputFormat};
+
+#[derive(Subcommand)]
+pub enum IncidentsCommands {
+    /// List open incidents and recently resolved ones, newest first
+    List {
+        /// Number of incidents to show
+        #[arg(short = 'n', long, default_value = "20")]
+        limit: usize,
+        /// Only incidents that are still open
+        #[arg(long)]
+        open: bool,
+    },
+    /// Show the timeline, components, and likely causes of one incident
+    Show {
+        /// Incident id or a unique prefix of it
+        id: String,
+    },
+}
+
+pub async fn handle_incidents_command(
+    cmd: IncidentsCommands,
+    memory: &MemoryStore,
+    format: OutputFormat,
+) -> Result<()> {
+    memory.require_history("Incident history")?;
+    let log = IncidentLog::load(memory).await?;
+    match cmd {
+        IncidentsCommands::List { limit, open } => {
+            let incidents: Vec<&Incident> = log
+                .iter()
+                .filter(|incident| !open || incident.is_open())
+                .take(limit)
+                .collect();
+            if matches!(format, OutputFormat::Json) {
+                println!("{}", serde_json::to_string_pretty(&incidents)?);
+                return Ok(());
+            }
+            if incidents.is_empty() {
+                println!("✅ No incidents recorded");
+                return Ok(());
+            }
+
+            let now = Utc::now();
+            println!("🚨 Incidents ({}):", incidents.len());
+            for incident in incidents {
+                let state = if incident.is_open() {
+                    "open"
+                } else {
+                    "resolved"
+                };
+                println!(
+                    "  {}  {}  {:<8} {:<8} {:>7}  {}",
+                    incident.short_id(),
+                    incident.opened_at.format("%Y-%m-%d %H:%M"),
+                    state,
+                    incident.severity.to_string(),
+                    format_duration(incident.duration(now)),
+                    incident.title
+                );
+            }
+            Ok(())
+        }
+        IncidentsCommands::Show { id } => {
+            let incident = log.find(&id)?;
+            if matches!(format, OutputFormat::Json) {
+                println!("{}", serde_json::to_string_pretty(incident)?);
+            } else {
+                print_incident(incident);
+            }
+            Ok(())
+        }
+    }
+}
+
+fn print_incident(incident: &Incident) {
+    let now = Utc::now();
+    println!("🚨 {} — {}", incident.short_id(), incident.title);
+    println!("  Severity:   {}", incident.severity);
+    println!(
+        "  Opened:     {}",
+        incident.opened_at.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+    match incident.resolved_at {
+        Some(resolved) => println!(
+            "  Resolved:   {} (after {})",
+            resolved.format("%Y-%m-%d %H:%M:%S UTC"),
+            format_duration(incident.duration(now))
+        ),
+        None => println!("  Open for:   {}", format_duration(incident.duration(now))),
+    }
+    println!("  Components: {}", incident.components.join(", "));
+    if !incident.degraded.is_empty() {
+        let degraded: Vec<&str> = incident.degraded.iter().map(String::as_str).collect();
+        println!("  Degraded:   {}", degraded.join(", "));
+    }
+
+    if !incident.causes.is_empty() {
+        println!("\nLikely causes:");
+        for cause in &incident.causes {
+            println!("  • {}", cause);
+        }
+    }
+
+    println!("\nTimeline:");
+    for signal in &incident.timeline {
+        println!(
+            "  {}  {:<19} {:<8} [{}] {}",
+            signal.timestamp.format("%m-%d %H:%M:%S"),
+            signal.kind.as_str(),
+            signal.severity.to_string(),
+            signal.source,
+            signal.summary
+        );
+    }
+}
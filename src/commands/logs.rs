@@ -0,0 +1,147 @@
+// src/commands/logs.rs
+//! Live log commands
+
+use anyhow::Result;
+use clap::Subcommand;
+use jarvis_core::OutputFormat;
+use jarvis_core::llm::LLMRouter;
+use jarvis_core::log_follow::{
+    self, Burst, BurstReason, DockerSource, FollowConfig, FollowEvent, JournalSource, Level,
+    LogSource,
+};
+use std::time::Duration;
+
+#[derive(Subcommand)]
+pub enum LogsCommands {
+    /// Tail a unit's journal or a container's logs, annotating error bursts
+    Follow {
+        /// Systemd unit, or container with --container
+        target: String,
+        /// Follow `docker logs` of a container instead of the journal
+        #[arg(long)]
+        container: bool,
+        /// Error lines within the window that count as a burst
+        #[arg(long, default_value = "5")]
+        threshold: usize,
+        /// Sliding window in seconds
+        #[arg(long, default_value = "60")]
+        window: u64,
+        /// Minimum seconds between two annotations
+        #[arg(long, default_value = "120")]
+        interval: u64,
+    },
+}
+
+pub async fn handle_logs_command(
+    cmd: LogsCommands,
+    llm: &LLMRouter,
+    format: OutputFormat,
+) -> Result<()> {
+    match cmd {
+        LogsCommands::Follow {
+            target,
+            container,
+            threshold,
+            window,
+            interval,
+        } => {
+            let mut source: Box<dyn LogSource> = if container {
+                Box::new(DockerSource::spawn(&target)?)
+            } else {
+                Box::new(JournalSource::spawn(&target)?)
+            };
+            let config = FollowConfig {
+                window: Duration::from_secs(window),
+                error_threshold: threshold.max(1),
+                min_annotation_interval: Duration::from_secs(interval),
+                ..FollowConfig::default()
+            };
+            let json = matches!(format, OutputFormat::Json);
+            if !json {
+                println!("📜 Following the {} (Ctrl-C to stop)\n", source.describe());
+            }
+
+            let stop = async {
+                tokio::signal::ctrl_c().await.ok();
+            };
+            let summary = log_follow::follow(source.as_mut(), llm, config, stop, |event| {
+                if json {
+                    print_event_json(&event);
+                } else {
+                    print_event(&event);
+                }
+            })
+            .await?;
+
+            if json {
+                println!("{}", serde_json::json!({ "summary": summary }));
+            } else {
+                println!("\n{}", summary.render());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_event(event: &FollowEvent) {
+    match event {
+        FollowEvent::Line(line) => {
+            let marker = match line.level {
+                Level::Error => "🔴",
+                Level::Warning => "🟡",
+                Level::Info => "  ",
+            };
+            println!(
+                "{} {} {}: {}",
+                marker,
+                line.timestamp
+                    .with_timezone(&chrono::Local)
+                    .format("%H:%M:%S"),
+                line.identifier,
+                line.message
+            );
+        }
+        FollowEvent::Annotation { burst, text } => {
+            println!("┌─ 🤖 Jarvis annotation: {}", describe_burst(burst));
+            for paragraph_line in text.lines() {
+                println!("│ {}", paragraph_line);
+            }
+            println!("└─");
+        }
+        FollowEvent::AnnotationFailed { burst, error } => {
+            println!(
+                "── 🤖 No annotation for {}: {}",
+                describe_burst(burst),
+                error
+            );
+        }
+    }
+}
+
+fn describe_burst(burst: &Burst) -> String {
+    let reason = match &burst.reason {
+        BurstReason::ErrorRate {
+            errors,
+            window_secs,
+        } => format!("{} errors in {}s", errors, window_secs),
+        BurstReason::Crash => "crash".to_string(),
+    };
+    format!(
+        "{} ({}: {})",
+        reason, burst.cluster.identifier, burst.cluster.template
+    )
+}
+
+/// One JSON object per line so the output can be piped while it runs
+fn print_event_json(event: &FollowEvent) {
+    let value = match event {
+        FollowEvent::Line(line) => serde_json::json!({ "line": line }),
+        FollowEvent::Annotation { burst, text } => {
+            serde_json::json!({ "annotation": { "burst": burst, "text": text } })
+        }
+        FollowEvent::AnnotationFailed { burst, error } => {
+            serde_json::json!({ "annotation_failed": { "burst": burst, "error": error.to_string() } })
+        }
+    };
+    println!("{}", value);
+}
@@ -0,0 +1,107 @@
+// src/commands/memory.rs
+//! Back up, restore, and repair the memory database
+
+use anyhow::Result;
+use chrono::Utc;
+use clap::Subcommand;
+use jarvis_core::memory_recovery::{self, SalvageReport};
+use jarvis_core::{MemoryStore, OutputFormat};
+use std::path::PathBuf;
+
+#[derive(Subcommand)]
+pub enum MemoryCommands {
+    /// Write a consistent copy of the database
+    Export {
+        /// Destination file (default: exports/jarvis-<time>.db next to the database)
+        output: Option<PathBuf>,
+    },
+    /// Merge the rows of an exported database into this one
+    Import {
+        /// Database written by `jarvis memory export`
+        path: PathBuf,
+    },
+    /// Salvage the readable rows of a damaged database into the live one
+    Repair {
+        /// Damaged copy to read (default: the newest one moved aside at startup)
+        #[arg(long)]
+        from: Option<PathBuf>,
+    },
+}
+
+pub async fn handle_memory_command(
+    cmd: MemoryCommands,
+    database_path: &str,
+    format: OutputFormat,
+) -> Result<()> {
+    // Opening moves a damaged database aside, which is what repair reads
+    let memory = MemoryStore::new(database_path).await?;
+    let path = memory
+        .path()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(database_path));
+
+    match cmd {
+        MemoryCommands::Export { output } => {
+            if let Some(recovery) = memory.recovery() {
+                anyhow::bail!("{}", recovery);
+            }
+            let dest = output.unwrap_or_else(|| {
+                memory_recovery::exports_dir(&path)
+                    .join(format!("jarvis-{}.db", Utc::now().format("%Y%m%dT%H%M%S")))
+            });
+            memory.export(&dest).await?;
+            if matches!(format, OutputFormat::Json) {
+                println!("{}", serde_json::json!({ "exported": dest }));
+            } else {
+                println!("💾 Exported {} to {}", path.display(), dest.display());
+            }
+        }
+        MemoryCommands::Import { path: source } => {
+            let report = memory.salvage(&source).await?;
+            print_report("Imported", &report, format)?;
+        }
+        MemoryCommands::Repair { from } => {
+            let source = from
+                .or_else(|| memory.recovery().map(|r| r.damaged.clone()))
+                .or_else(|| memory_recovery::latest_damaged(&path));
+            let Some(source) = source else {
+                println!(
+                    "✅ Nothing to repair: {} passes its integrity check and no damaged copy was found",
+                    path.display()
+                );
+                return Ok(());
+            };
+            let report = memory.salvage(&source).await?;
+            print_report("Salvaged", &report, format)?;
+        }
+    }
+    Ok(())
+}
+
+fn print_report(verb: &str, report: &SalvageReport, format: OutputFormat) -> Result<()> {
+    if matches!(format, OutputFormat::Json) {
+        println!("{}", serde_json::to_string_pretty(report)?);
+        return Ok(());
+    }
+
+    println!(
+        "🩹 {} {} rows from {}",
+        verb,
+        report.rows(),
+        report.source.display()
+    );
+    for table in &report.tables {
+        let mut line = format!("  {:<16} {:>8} rows", table.table, table.rows);
+        if table.unreadable > 0 {
+            line.push_str(&format!(", {} unreadable", table.unreadable));
+        }
+        if let Some(error) = &table.error {
+            line.push_str(&format!(" ⚠️ {}", error));
+        }
+        println!("{}", line);
+    }
+    if report.unreadable() > 0 {
+        println!("⚠️ Rows on damaged pages could not be read and are lost");
+    }
+    Ok(())
+}
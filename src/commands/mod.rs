@@ -1,3 +1,39 @@
+pub mod arch;
+pub mod audit;
 pub mod blockchain;
+pub mod doctor;
+pub mod fleet;
+pub mod ghostflow;
+pub mod incidents;
+pub mod logs;
+pub mod memory;
+pub mod nlp;
+pub mod notify;
+pub mod power;
+pub mod profile;
+pub mod report;
+pub mod self_update;
+pub mod tools;
+pub mod trace;
+pub mod trend;
+pub mod vuln;
 
+pub use arch::{ArchCommands, handle_arch_command, handle_rollback};
+pub use audit::{AuditCommands, handle_audit_command};
 pub use blockchain::{BlockchainCommands, handle_blockchain_command};
+pub use doctor::handle_doctor;
+pub use fleet::{FleetCommands, handle_fleet_command};
+pub use ghostflow::{GhostflowCommands, handle_ghostflow_command};
+pub use incidents::{IncidentsCommands, handle_incidents_command};
+pub use logs::{LogsCommands, handle_logs_command};
+pub use memory::{MemoryCommands, handle_memory_command};
+pub use nlp::{NlpCommands, handle_nlp_command};
+pub use notify::{NotifyCommands, handle_notify_command};
+pub use power::{PowerCommands, handle_power_command};
+pub use profile::{ProfileCommands, handle_profile_command};
+pub use report::{ReportCommands, handle_report_command};
+pub use self_update::handle_self_update;
+pub use tools::{ToolsCommands, handle_tools_command};
+pub use trace::{TraceCommands, handle_trace_command};
+pub use trend::show_trend;
+pub use vuln::{VulnCommands, handle_vuln_command};
@@ -0,0 +1,131 @@
+// src/commands/nlp.rs
+//! Try the natural language parser and correct its routing
+
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use jarvis_core::config::Config;
+use jarvis_core::llm::LLMRouter;
+use jarvis_core::mcp::BUILTIN_TOOLS;
+use jarvis_core::nlp::CommandParser;
+use jarvis_core::nlp::embedding_routing::{ExampleSet, append_example};
+use jarvis_core::plugins::load_plugins;
+use jarvis_core::trace::{self, short_id};
+use jarvis_core::{MemoryStore, OutputFormat};
+
+#[derive(Subcommand)]
+pub enum NlpCommands {
+    /// Show which tool and action a query routes to, and how it got there
+    Parse {
+        query: Vec<String>,
+    },
+    /// Teach the router the right tool for a query it got wrong
+    Correct {
+        /// Trace id (or a unique prefix) of the misrouted query
+        trace_id: String,
+        /// Tool the query should have gone to, e.g. jarvis_docker
+        #[arg(long)]
+        tool: String,
+        /// Action of that tool, e.g. diagnose
+        #[arg(long)]
+        action: String,
+    },
+}
+
+pub async fn handle_nlp_command(
+    cmd: NlpCommands,
+    config: &Config,
+    memory: &MemoryStore,
+    llm: &LLMRouter,
+    format: OutputFormat,
+) -> Result<()> {
+    match cmd {
+        NlpCommands::Parse { query } => {
+            let query = query.join(" ");
+            let parser = CommandParser::from_config(Some(llm.clone()), config);
+            let command = parser.parse(&query).await?;
+            if matches!(format, OutputFormat::Json) {
+                println!("{}", serde_json::to_string_pretty(&command)?);
+                return Ok(());
+            }
+
+            let method = trace::current()
+                .and_then(|t| t.report().phases.into_iter().find(|p| p.name == "parse"))
+                .and_then(|p| p.attributes.get("method").cloned())
+                .unwrap_or_default();
+            println!(
+                "🧭 {} {} ({:?}, confidence {:.2}, via {})",
+                command.tool, command.action, command.intent, command.confidence, method
+            );
+            println!("   {}", serde_json::to_string(&command.parameters)?);
+            if jarvis_core::read_only::is_read_only()
+                && let Some(manual) = command.manual_command()
+            {
+                println!("   🔒 Read-only mode, so run it yourself: {}", manual);
+            }
+            // Only embedding and LLM routes are pinned and kept to correct
+            if let Some(trace) = trace::current().filter(|t| t.report().pinned) {
+                println!(
+                    "   Misrouted? jarvis nlp correct {} --tool <tool> --action <action>",
+                    short_id(trace.report().id)
+                );
+            }
+            Ok(())
+        }
+        NlpCommands::Correct {
+            trace_id,
+            tool,
+            action,
+        } => {
+            let stored = trace::find_trace(memory, &trace_id).await?;
+            let query = stored
+                .phases
+                .iter()
+                .find(|p| p.name == "parse")
+                .and_then(|p| p.attributes.get("query"))
+                .with_context(|| {
+                    format!("Trace {} has no parsed query to correct", short_id(stored.id))
+                })?;
+
+            let builtin_names: Vec<&str> = BUILTIN_TOOLS.iter().map(|(name, _)| *name).collect();
+            let plugins = load_plugins(&config.mcp.plugins, &builtin_names);
+            let known = builtin_names.contains(&tool.as_str())
+                || plugins.tools.iter().any(|t| t.spec.name == tool);
+            if !known {
+                anyhow::bail!("Unknown tool '{}'; see `jarvis tools list`", tool);
+            }
+
+            let path = config
+                .nlp
+                .examples_path()
+                .context("Could not find config directory for NLP examples")?;
+            let example = ExampleSet {
+                tool,
+                action,
+                intent: None,
+                utterances: vec![query.clone()],
+            };
+            append_example(&path, &example)?;
+
+            if matches!(format, OutputFormat::Json) {
+                println!(
+                    "{}",
+                    serde_json::json!({
+                        "query": query,
+                        "tool": example.tool,
+                        "action": example.action,
+                        "examples_file": path.display().to_string(),
+                    })
+                );
+            } else {
+                println!(
+                    "📝 \"{}\" now routes to {} {} (added to {})",
+                    query,
+                    example.tool,
+                    example.action,
+                    path.display()
+                );
+            }
+            Ok(())
+        }
+    }
+}
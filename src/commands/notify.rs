@@ -0,0 +1,61 @@
+// src/commands/notify.rs
+//! Notification commands
+
+use anyhow::Result;
+use clap::Subcommand;
+use jarvis_core::config::Config;
+use jarvis_core::notify::{Notification, Notifier, NotifyEvent, NotifySeverity};
+
+#[derive(Subcommand)]
+pub enum NotifyCommands {
+    /// Send a test notification through every configured backend
+    Test {
+        /// Severity of the test message (info, warning, critical)
+        #[arg(long, default_value = "info")]
+        severity: String,
+    },
+}
+
+pub async fn handle_notify_command(cmd: NotifyCommands, config: &Config) -> Result<()> {
+    match cmd {
+        NotifyCommands::Test { severity } => {
+            let severity: NotifySeverity =
+                serde_json::from_value(serde_json::Value::String(severity.to_lowercase()))
+                    .map_err(|_| anyhow::anyhow!("Severity must be info, warning, or critical"))?;
+
+            let notifier = Notifier::from_config(&config.notifications);
+            if notifier.backend_names().is_empty() {
+                println!("📭 No notification backends are enabled; see [notifications] in jarvis.toml");
+                return Ok(());
+            }
+
+            let notification = Notification::new(
+                NotifyEvent::Test,
+                severity,
+                "Jarvis test notification",
+                "If you can read this, notifications are working.",
+            );
+
+            println!("📨 Sending test notification...");
+            let results = notifier.send_now(&notification).await;
+            if results.is_empty() {
+                println!("  (no backend accepts {:?} notifications)", severity);
+            }
+            let mut failed = 0;
+            for (backend, result) in results {
+                match result {
+                    Ok(()) => println!("  ✅ {}", backend),
+                    Err(e) => {
+                        failed += 1;
+                        println!("  ❌ {}: {}", backend, e);
+                    }
+                }
+            }
+
+            if failed > 0 {
+                anyhow::bail!("{} notification backend(s) failed", failed);
+            }
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,112 @@
+// src/commands/power.rs
+//! Wake-on-LAN and power control commands
+
+use anyhow::Result;
+use clap::Subcommand;
+use jarvis_core::config::Config;
+use jarvis_core::power::{self, PowerAction, PowerOutcome};
+use jarvis_core::OutputFormat;
+use std::io::Write;
+use std::time::Duration;
+
+#[derive(Subcommand)]
+pub enum PowerCommands {
+    /// Wake a configured host with a Wake-on-LAN magic packet
+    Wake {
+        /// Host name from [remote.hosts]
+        host: String,
+        /// Seconds to wait for the host to answer pings
+        #[arg(long, default_value = "120")]
+        timeout: u64,
+    },
+    /// Power off this machine or a remote host
+    Shutdown {
+        /// Host name or user@server; omit for this machine
+        host: Option<String>,
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Reboot this machine or a remote host
+    Reboot {
+        host: Option<String>,
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Suspend this machine or a remote host
+    Suspend {
+        host: Option<String>,
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+/// `default_host` is the global `--host`, used when no host is given
+pub async fn handle_power_command(
+    cmd: PowerCommands,
+    config: &Config,
+    default_host: Option<&str>,
+    format: OutputFormat,
+    dry_run: bool,
+) -> Result<()> {
+    let (action, host, yes) = match cmd {
+        PowerCommands::Wake { host, timeout } => {
+            if dry_run {
+                println!("🧪 Dry run: would send a magic packet to {}", host);
+                return Ok(());
+            }
+            println!("📡 Sending magic packet to {}...", host);
+            let outcome = power::wake(&host, &config.remote, Duration::from_secs(timeout)).await?;
+            return print_outcome(&outcome, format);
+        }
+        PowerCommands::Shutdown { host, yes } => (PowerAction::Poweroff, host, yes),
+        PowerCommands::Reboot { host, yes } => (PowerAction::Reboot, host, yes),
+        PowerCommands::Suspend { host, yes } => (PowerAction::Suspend, host, yes),
+    };
+
+    let executor = power::executor_for(host.as_deref().or(default_host), &config.remote);
+    if dry_run {
+        println!(
+            "🧪 Dry run: would run `systemctl --no-block {}` on {}",
+            action.systemctl_verb(),
+            executor.host_label()
+        );
+        return Ok(());
+    }
+    if !yes && !confirm(&format!("⚠️ {} {}?", action, executor.host_label()))? {
+        println!("Cancelled.");
+        return Ok(());
+    }
+
+    let outcome = power::power_action(&executor, action, Duration::from_secs(120)).await?;
+    print_outcome(&outcome, format)
+}
+
+pub(crate) fn confirm(prompt: &str) -> Result<bool> {
+    print!("{} [y/N] ", prompt);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+fn print_outcome(outcome: &PowerOutcome, format: OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(outcome)?),
+        OutputFormat::Pretty => {
+            let icon = if outcome.verified { "✅" } else { "⚠️" };
+            println!(
+                "{} {} {}: {} ({:.1}s)",
+                icon,
+                outcome.action,
+                outcome.host,
+                outcome.message,
+                outcome.elapsed_ms as f64 / 1000.0
+            );
+        }
+    }
+    if !outcome.verified {
+        anyhow::bail!("Could not verify {} on {}", outcome.action, outcome.host);
+    }
+    Ok(())
+}
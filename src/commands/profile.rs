@@ -0,0 +1,90 @@
+// src/commands/profile.rs
+//! Show and regenerate the host profile prepended to prompts
+
+use anyhow::Result;
+use clap::Subcommand;
+use jarvis_core::config::Config;
+use jarvis_core::host_profile::{self, HostProfile};
+use jarvis_core::llm::LLMRouter;
+use jarvis_core::power;
+use jarvis_core::{MemoryStore, OutputFormat};
+
+#[derive(Subcommand)]
+pub enum ProfileCommands {
+    /// Show the current profile of this machine or the --host
+    Show {
+        /// Also list earlier versions and what changed in each
+        #[arg(long)]
+        history: bool,
+    },
+    /// Probe the host and summarize it again now
+    Regenerate,
+}
+
+pub async fn handle_profile_command(
+    cmd: ProfileCommands,
+    config: &Config,
+    memory: &MemoryStore,
+    llm: &LLMRouter,
+    host: Option<&str>,
+    format: OutputFormat,
+) -> Result<()> {
+    let executor = power::executor_for(host, &config.remote);
+    match cmd {
+        ProfileCommands::Show { history } => {
+            let profile = HostProfile::load(memory, &executor.host_label()).await?;
+            if matches!(format, OutputFormat::Json) {
+                println!("{}", serde_json::to_string_pretty(&profile)?);
+                return Ok(());
+            }
+            let Some(latest) = profile.latest() else {
+                println!(
+                    "📭 No profile of {} yet; run `jarvis profile regenerate`",
+                    profile.host
+                );
+                return Ok(());
+            };
+
+            println!(
+                "🖥️ Profile of {} (version {}, generated {} by {}, confirmed {}):",
+                profile.host,
+                latest.version,
+                latest.generated_at.format("%Y-%m-%d %H:%M"),
+                latest.model,
+                latest.confirmed_at.format("%Y-%m-%d %H:%M")
+            );
+            println!("{}", latest.summary);
+            if history {
+                println!("\n📜 History:");
+                for version in profile.versions.iter().rev() {
+                    println!(
+                        "  v{} {}",
+                        version.version,
+                        version.generated_at.format("%Y-%m-%d %H:%M")
+                    );
+                    print!("{}", version.render_changes());
+                }
+            }
+            Ok(())
+        }
+        ProfileCommands::Regenerate => {
+            println!("🔄 Profiling {}...", executor.host_label());
+            let version =
+                host_profile::regenerate(memory, llm, &executor, &config.host_profile).await?;
+            if matches!(format, OutputFormat::Json) {
+                println!("{}", serde_json::to_string_pretty(&version)?);
+                return Ok(());
+            }
+            println!("{}", version.summary);
+            if version.version == 1 {
+                println!("\n✅ First profile stored");
+            } else if version.changed() {
+                println!("\n📝 Changes in version {}:", version.version);
+                print!("{}", version.render_changes());
+            } else {
+                println!("\n✅ Unchanged (version {})", version.version);
+            }
+            Ok(())
+        }
+    }
+}
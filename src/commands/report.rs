@@ -0,0 +1,115 @@
+// src/commands/report.rs
+//! System report commands
+
+use anyhow::Result;
+use clap::Subcommand;
+use jarvis_core::config::Config;
+use jarvis_core::notify::{Notification, Notifier, NotifyEvent, NotifySeverity};
+use jarvis_core::report::{self, ReportWindow};
+use jarvis_core::{LLMRouter, MemoryStore, OutputFormat};
+use std::path::PathBuf;
+
+#[derive(Subcommand)]
+pub enum ReportCommands {
+    /// Summarize operations, package changes, security, health, and LLM usage
+    Generate {
+        /// Time window to cover, e.g. 24h, 7d, this week, yesterday
+        #[arg(long, default_value = "7d")]
+        since: String,
+        /// Write the report here instead of printing it
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Render HTML instead of markdown
+        #[arg(long)]
+        html: bool,
+        /// HTML template with {{title}} and {{content}} placeholders
+        #[arg(long, requires = "html")]
+        template: Option<PathBuf>,
+        /// Skip the LLM executive summary
+        #[arg(long)]
+        no_summary: bool,
+        /// Send a report_ready notification when done
+        #[arg(long, requires = "output")]
+        notify: bool,
+    },
+}
+
+pub async fn handle_report_command(
+    cmd: ReportCommands,
+    config: &Config,
+    memory: &MemoryStore,
+    llm: &LLMRouter,
+    format: OutputFormat,
+) -> Result<()> {
+    match cmd {
+        ReportCommands::Generate {
+            since,
+            output,
+            html,
+            template,
+            no_summary,
+            notify,
+        } => {
+            let window = ReportWindow::parse(&since)?;
+            memory.require_history("A report")?;
+            let data = report::gather(memory, window).await?;
+
+            if matches!(format, OutputFormat::Json) {
+                println!("{}", serde_json::to_string_pretty(&data)?);
+                return Ok(());
+            }
+
+            let summary = if no_summary {
+                None
+            } else {
+                match report::executive_summary(llm, &data).await {
+                    Ok(summary) => Some(summary),
+                    Err(e) => {
+                        eprintln!("⚠️  Skipping executive summary: {}", e);
+                        None
+                    }
+                }
+            };
+
+            let rendered = if html {
+                let template = match template {
+                    Some(path) => Some(tokio::fs::read_to_string(&path).await?),
+                    None => None,
+                };
+                report::render_html(&data, summary.as_deref(), template.as_deref())
+            } else {
+                report::render_markdown(&data, summary.as_deref())
+            };
+
+            let Some(path) = output else {
+                print!("{}", rendered);
+                return Ok(());
+            };
+
+            tokio::fs::write(&path, rendered).await?;
+            println!("📄 Report written to {}", path.display());
+
+            if notify {
+                let notification = Notification::new(
+                    NotifyEvent::ReportReady,
+                    NotifySeverity::Info,
+                    format!("Jarvis report for {}", data.host),
+                    format!(
+                        "Report covering the last {} saved to {}",
+                        since,
+                        path.display()
+                    ),
+                );
+                for (backend, result) in Notifier::from_config(&config.notifications)
+                    .send_now(&notification)
+                    .await
+                {
+                    if let Err(e) = result {
+                        eprintln!("⚠️  {} notification failed: {}", backend, e);
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,144 @@
+// src/commands/self_update.rs
+//! `jarvis self-update`: install a newer signed release over this binary
+
+use anyhow::{Context, Result};
+use jarvis_core::OutputFormat;
+use jarvis_core::self_update::{self, Release, StagedBinary, UpdateKind};
+use semver::Version;
+use std::path::{Path, PathBuf};
+
+/// Binaries a release ships, each replaced if installed next to `jarvis`
+const BINARIES: &[&str] = &["jarvis", "jarvisd"];
+
+pub async fn handle_self_update(
+    check_only: bool,
+    allow_downgrade: bool,
+    version: Option<&str>,
+    format: OutputFormat,
+) -> Result<()> {
+    let current = Version::parse(env!("CARGO_PKG_VERSION"))?;
+    let release = self_update::fetch_release(version).await?;
+    let available = release.version()?;
+    let kind = self_update::compare(&current, &available);
+
+    if check_only {
+        if matches!(format, OutputFormat::Json) {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&serde_json::json!({
+                    "current": current.to_string(),
+                    "available": available.to_string(),
+                    "update_available": kind == UpdateKind::Upgrade,
+                    "url": release.html_url,
+                }))?
+            );
+            return Ok(());
+        }
+        match kind {
+            UpdateKind::UpToDate => println!("✅ jarvis {} is up to date", current),
+            UpdateKind::Upgrade => println!(
+                "⬆️ jarvis {} is available (running {}); install it with `jarvis self-update`",
+                available, current
+            ),
+            UpdateKind::Downgrade => println!(
+                "ℹ️ Release {} is older than the running {}",
+                available, current
+            ),
+        }
+        return Ok(());
+    }
+
+    match kind {
+        UpdateKind::UpToDate => {
+            println!("✅ jarvis {} is up to date", current);
+            return Ok(());
+        }
+        UpdateKind::Downgrade if !allow_downgrade => anyhow::bail!(
+            "Release {} is older than the running {}; pass --allow-downgrade to install it anyway",
+            available,
+            current
+        ),
+        _ => {}
+    }
+
+    let public_key = self_update::RELEASE_PUBLIC_KEY.ok_or_else(|| {
+        anyhow::anyhow!(
+            "This build has no release signing key, so it can't verify downloads; update through the package manager or source checkout it came from"
+        )
+    })?;
+
+    // Verify and stage everything before replacing anything
+    let exe = std::env::current_exe()?
+        .canonicalize()
+        .context("Failed to locate the running binary")?;
+    let mut staged = Vec::new();
+    for binary in BINARIES {
+        let Some(target) = install_path(&exe, binary) else {
+            continue;
+        };
+        println!("⬇️ Downloading {} {}...", binary, available);
+        let new = fetch_verified(&release, binary, &available, public_key).await?;
+        staged.push(StagedBinary::stage(&target, &new)?);
+    }
+
+    let mut replaced = Vec::new();
+    for binary in staged {
+        let target = binary.target().to_path_buf();
+        binary.commit()?;
+        println!("✅ Installed {} {}", target.display(), available);
+        replaced.push(target);
+    }
+
+    let daemon_replaced = replaced.iter().any(|path| path.ends_with("jarvisd"));
+    if daemon_replaced && daemon_is_running().await {
+        println!(
+            "🔄 jarvisd is still running {}; restart it with `sudo systemctl restart jarvisd`",
+            current
+        );
+    }
+    Ok(())
+}
+
+/// Where `binary` is installed: the running executable for `jarvis`, and a
+/// sibling of it for the others, if there is one
+fn install_path(exe: &Path, binary: &str) -> Option<PathBuf> {
+    if binary == "jarvis" {
+        return Some(exe.to_path_buf());
+    }
+    let sibling = exe.with_file_name(binary);
+    sibling.exists().then_some(sibling)
+}
+
+/// Download `binary` from `release` with its signature, and check one
+/// against the other
+async fn fetch_verified(
+    release: &Release,
+    binary: &str,
+    version: &Version,
+    public_key: &str,
+) -> Result<Vec<u8>> {
+    let triple = self_update::target_triple();
+    let name = self_update::asset_name(binary, version, &triple);
+    let asset = release.asset(&name).ok_or_else(|| {
+        anyhow::anyhow!("Release {} has no {} build for {}", version, binary, triple)
+    })?;
+    let signature_name = format!("{}.minisig", name);
+    let signature = release.asset(&signature_name).ok_or_else(|| {
+        anyhow::anyhow!("Release {} publishes {} without a signature", version, name)
+    })?;
+
+    let data = self_update::download(asset).await?;
+    let signature = String::from_utf8(self_update::download(signature).await?)
+        .with_context(|| format!("{} is not a minisign signature", signature_name))?;
+    self_update::verify_signature(&data, &signature, public_key, &name)?;
+    println!("🔏 Signature of {} verified", name);
+    Ok(data)
+}
+
+async fn daemon_is_running() -> bool {
+    tokio::process::Command::new("systemctl")
+        .args(["is-active", "--quiet", "jarvisd"])
+        .status()
+        .await
+        .is_ok_and(|status| status.success())
+}
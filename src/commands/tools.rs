@@ -0,0 +1,74 @@
+// src/commands/tools.rs
+//! Built-in and plugin tool listing
+
+use anyhow::Result;
+use clap::Subcommand;
+use jarvis_core::OutputFormat;
+use jarvis_core::config::Config;
+use jarvis_core::mcp::BUILTIN_TOOLS;
+use jarvis_core::plugins::load_plugins;
+
+#[derive(Subcommand)]
+pub enum ToolsCommands {
+    /// Show built-in tools and plugins from the plugin directory
+    List,
+}
+
+pub async fn handle_tools_command(
+    cmd: ToolsCommands,
+    config: &Config,
+    format: OutputFormat,
+) -> Result<()> {
+    match cmd {
+        ToolsCommands::List => {
+            let builtin_names: Vec<&str> = BUILTIN_TOOLS.iter().map(|(name, _)| *name).collect();
+            let plugins = load_plugins(&config.mcp.plugins, &builtin_names);
+
+            if matches!(format, OutputFormat::Json) {
+                let tools: Vec<serde_json::Value> = BUILTIN_TOOLS
+                    .iter()
+                    .map(|(name, description)| {
+                        serde_json::json!({"name": name, "description": description, "source": "built-in"})
+                    })
+                    .chain(plugins.tools.iter().map(|t| {
+                        serde_json::json!({
+                            "name": t.spec.name,
+                            "description": t.spec.description,
+                            "source": t.source.display().to_string(),
+                        })
+                    }))
+                    .collect();
+                let errors: Vec<serde_json::Value> = plugins
+                    .errors
+                    .iter()
+                    .map(|(path, error)| serde_json::json!({"source": path.display().to_string(), "error": error}))
+                    .collect();
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(
+                        &serde_json::json!({"tools": tools, "errors": errors})
+                    )?
+                );
+                return Ok(());
+            }
+
+            println!("🧰 Built-in tools:");
+            for (name, description) in BUILTIN_TOOLS {
+                println!("  {:<26} {}", name, description);
+            }
+
+            println!("\n🔌 Plugins ({}):", config.mcp.plugins.dir);
+            if plugins.tools.is_empty() && plugins.errors.is_empty() {
+                println!("  (none)");
+            }
+            for tool in &plugins.tools {
+                println!("  {:<26} {}", tool.spec.name, tool.spec.description);
+                println!("  {:<26} ↳ {}", "", tool.source.display());
+            }
+            for (path, error) in &plugins.errors {
+                println!("  ❌ {}: {}", path.display(), error);
+            }
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,73 @@
+// src/commands/trace.rs
+//! Inspect stored request traces
+
+use anyhow::Result;
+use clap::Subcommand;
+use jarvis_core::trace::{self, short_id};
+use jarvis_core::{MemoryStore, OutputFormat};
+
+#[derive(Subcommand)]
+pub enum TraceCommands {
+    /// List stored traces of slow requests, newest first
+    List {
+        /// Number of traces to show
+        #[arg(short = 'n', long, default_value = "20")]
+        limit: usize,
+    },
+    /// Show the phase waterfall of one trace
+    Show {
+        /// Trace id or a unique prefix of it
+        id: String,
+    },
+}
+
+pub async fn handle_trace_command(
+    cmd: TraceCommands,
+    memory: &MemoryStore,
+    format: OutputFormat,
+) -> Result<()> {
+    memory.require_history("Trace history")?;
+    match cmd {
+        TraceCommands::List { limit } => {
+            let mut traces = trace::load_traces(memory).await?;
+            traces.truncate(limit);
+            if matches!(format, OutputFormat::Json) {
+                println!("{}", serde_json::to_string_pretty(&traces)?);
+                return Ok(());
+            }
+            if traces.is_empty() {
+                println!("⏱️  No slow requests recorded yet");
+                return Ok(());
+            }
+
+            println!("⏱️  Slow requests ({}):", traces.len());
+            for trace in traces {
+                let slowest = trace
+                    .phases
+                    .iter()
+                    .filter(|p| p.depth == 0)
+                    .max_by_key(|p| p.duration_ms.unwrap_or_default())
+                    .map(|p| format!("slowest: {}", p.name))
+                    .unwrap_or_default();
+                println!(
+                    "  {}  {}  {:>7}ms  {:<40} {}",
+                    short_id(trace.id),
+                    trace.started_at.format("%Y-%m-%d %H:%M:%S"),
+                    trace.duration_ms,
+                    trace.request,
+                    slowest
+                );
+            }
+            Ok(())
+        }
+        TraceCommands::Show { id } => {
+            let trace = trace::find_trace(memory, &id).await?;
+            if matches!(format, OutputFormat::Json) {
+                println!("{}", serde_json::to_string_pretty(&trace)?);
+            } else {
+                print!("{}", trace.waterfall());
+            }
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,64 @@
+// src/commands/trend.rs
+//! `jarvis check trend`: recorded health metrics over time
+
+use anyhow::Result;
+use chrono::Duration;
+use jarvis_core::metrics;
+use jarvis_core::time_range;
+use jarvis_core::{MemoryStore, OutputFormat};
+
+/// Points in the sparkline of a full window
+const TREND_WIDTH: i64 = 48;
+
+/// Show `metric` over `since` (e.g. 7d, yesterday) as a sparkline with min/avg/max
+pub async fn show_trend(
+    memory: &MemoryStore,
+    metric: &str,
+    since: &str,
+    format: OutputFormat,
+) -> Result<()> {
+    memory.require_history("`jarvis check trend`")?;
+    let range = time_range::parse_range(since)?;
+    let (start, end) = (range.start, range.end);
+    let step = Duration::seconds((range.duration().num_seconds() / TREND_WIDTH).max(60));
+    let when = time_range::describe(since);
+    let series = memory.query_range(metric, start, end, step).await?;
+
+    if matches!(format, OutputFormat::Json) {
+        println!("{}", serde_json::to_string_pretty(&series)?);
+        return Ok(());
+    }
+    if series.is_empty() {
+        let known = memory.metric_names_since(start).await?;
+        if known.is_empty() {
+            anyhow::bail!(
+                "No metrics recorded {}; jarvisd records them on each health check",
+                when
+            );
+        }
+        anyhow::bail!(
+            "No '{}' samples {}; recorded metrics: {}",
+            metric,
+            when,
+            known.join(", ")
+        );
+    }
+
+    println!("📈 {} {}", metric, when);
+    for series in &series {
+        let Some((min, avg, max)) = series.summary() else {
+            continue;
+        };
+        let values: Vec<f64> = series.points.iter().map(|p| p.avg).collect();
+        println!("\n  {}", series.display_name());
+        println!("  {}", metrics::sparkline(&values));
+        println!(
+            "  min {:.1}  avg {:.1}  max {:.1}  (latest {:.1})",
+            min,
+            avg,
+            max,
+            values.last().copied().unwrap_or(avg)
+        );
+    }
+    Ok(())
+}
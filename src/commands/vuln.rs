@@ -0,0 +1,131 @@
+// src/commands/vuln.rs
+//! Vulnerability scan and acknowledgement commands
+
+use anyhow::{Context, Result};
+use chrono::{NaiveDate, Utc};
+use clap::Subcommand;
+use jarvis_core::report;
+use jarvis_core::vuln::Acknowledgements;
+use jarvis_core::{MemoryStore, OutputFormat};
+
+#[derive(Subcommand)]
+pub enum VulnCommands {
+    /// Scan installed packages with arch-audit; container images come from the last Trivy scan
+    Scan,
+    /// Accept an advisory as a known risk so it stops counting toward health and alerts
+    Ack {
+        /// Advisory id, e.g. CVE-2024-3094
+        advisory: String,
+        /// Why the risk is acceptable
+        #[arg(long)]
+        reason: String,
+        /// Last day the acknowledgement applies (YYYY-MM-DD); counts again afterwards
+        #[arg(long, value_name = "DATE")]
+        until: Option<String>,
+    },
+    /// List acknowledgements, including expired ones
+    List,
+    /// Remove an acknowledgement
+    Remove {
+        /// Advisory id
+        advisory: String,
+    },
+}
+
+pub async fn handle_vuln_command(
+    cmd: VulnCommands,
+    memory: &MemoryStore,
+    format: OutputFormat,
+) -> Result<()> {
+    match cmd {
+        VulnCommands::Scan => {
+            let security = report::gather_security(memory).await;
+            if matches!(format, OutputFormat::Json) {
+                println!("{}", serde_json::to_string_pretty(&security)?);
+                return Ok(());
+            }
+            if !security.scanned && security.images.is_empty() {
+                if jarvis_core::net::is_offline() {
+                    anyhow::bail!("Offline, and no earlier arch-audit scan is cached");
+                }
+                anyhow::bail!(
+                    "arch-audit is not installed; install it to scan for vulnerabilities"
+                );
+            }
+            println!("🛡️ Vulnerability scan:");
+            if !security.scanned {
+                print!("{}", report::security_unavailable());
+            }
+            print!("{}", report::render_security(&security));
+            Ok(())
+        }
+        VulnCommands::Ack {
+            advisory,
+            reason,
+            until,
+        } => {
+            let until = until
+                .map(|day| {
+                    NaiveDate::parse_from_str(&day, "%Y-%m-%d")
+                        .with_context(|| format!("Invalid date '{}' (expected YYYY-MM-DD)", day))
+                })
+                .transpose()?;
+
+            let mut acks = Acknowledgements::load(memory).await?;
+            let ack = acks
+                .acknowledge(&advisory, &reason, until, Utc::now())?
+                .clone();
+            acks.save(memory).await?;
+
+            match ack.until {
+                Some(until) => println!("✅ Acknowledged {} until {}", ack.advisory, until),
+                None => println!("✅ Acknowledged {} with no expiry", ack.advisory),
+            }
+            Ok(())
+        }
+        VulnCommands::List => {
+            let acks = Acknowledgements::load(memory).await?;
+            if matches!(format, OutputFormat::Json) {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&acks.iter().collect::<Vec<_>>())?
+                );
+                return Ok(());
+            }
+            if acks.is_empty() {
+                println!("📭 No acknowledged vulnerabilities");
+                return Ok(());
+            }
+
+            let now = Utc::now();
+            println!("🛡️ Acknowledged vulnerabilities:");
+            for ack in acks.iter() {
+                let (icon, expiry) = match ack.until {
+                    Some(until) if !ack.is_active_at(now) => ("⌛", format!("expired {}", until)),
+                    Some(until) => ("✅", format!("until {}", until)),
+                    None => ("✅", "no expiry".to_string()),
+                };
+                println!(
+                    "  {} {:<18} {:<16} {} (acknowledged {})",
+                    icon,
+                    ack.advisory,
+                    expiry,
+                    ack.reason,
+                    ack.acknowledged_at.format("%Y-%m-%d")
+                );
+            }
+            Ok(())
+        }
+        VulnCommands::Remove { advisory } => {
+            let mut acks = Acknowledgements::load(memory).await?;
+            match acks.remove(&advisory) {
+                Some(ack) => {
+                    acks.save(memory).await?;
+                    println!("🗑️ Removed acknowledgement for {}", ack.advisory);
+                    Ok(())
+                }
+                None => anyhow::bail!("No acknowledgement for {}", advisory),
+            }
+        }
+    }
+}
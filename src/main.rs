@@ -1,13 +1,26 @@
 use anyhow::Result;
 use clap::{Parser, Subcommand};
-use jarvis_agent::AgentRunner;
-use jarvis_core::{config::Config, llm::LLMRouter, memory::MemoryStore};
+use jarvis_agent::{AgentRunner, WriteOptions};
+use jarvis_core::file_access::FileAccess;
+use jarvis_core::{
+    CommandExecutor, OutputFormat, SshTarget, config::Config, llm::LLMRouter, memory::MemoryStore,
+};
+use jarvis_core::input;
 use jarvis_shell::Environment;
+use std::path::PathBuf;
 use tracing::{Level, info};
 use tracing_subscriber;
 
 mod commands;
-use commands::{BlockchainCommands, handle_blockchain_command};
+use commands::{
+    ArchCommands, AuditCommands, BlockchainCommands, FleetCommands, GhostflowCommands,
+    IncidentsCommands, LogsCommands, MemoryCommands, NlpCommands, NotifyCommands, PowerCommands, ProfileCommands, ReportCommands, ToolsCommands,
+    TraceCommands, VulnCommands, handle_arch_command, handle_audit_command,
+    handle_blockchain_command, handle_doctor, handle_fleet_command, handle_ghostflow_command,
+    handle_incidents_command, handle_logs_command, handle_memory_command, handle_nlp_command, handle_notify_command, handle_power_command, handle_profile_command, handle_report_command, handle_rollback,
+    handle_self_update, handle_tools_command, handle_trace_command, handle_vuln_command,
+    show_trend,
+};
 
 #[derive(Parser)]
 #[command(name = "jarvis")]
@@ -22,34 +35,88 @@ struct Cli {
 
     #[arg(short, long, global = true)]
     config: Option<String>,
+
+    /// Output format for command results
+    #[arg(short, long, global = true, value_enum, default_value = "pretty")]
+    output: OutputFormat,
+
+    /// Run probes on another machine over SSH (`user@server` or a [remote.hosts] name)
+    #[arg(long, global = true)]
+    host: Option<String>,
+
+    /// Report what fix, power, and arch commands would do without changing anything
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    /// Contact nothing but localhost; use cached data where there is some
+    #[arg(long, global = true)]
+    offline: bool,
+
+    /// Observe and advise but change nothing on the system
+    #[arg(long, global = true)]
+    read_only: bool,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Explain system components or files
     Explain {
-        /// What to explain (e.g., "my snapper timeline", "this error log")
+        /// What to explain (e.g., "my snapper timeline", "this error log"); `-` reads stdin
         query: Vec<String>,
+        /// Attach a text file (repeatable)
+        #[arg(short, long = "file")]
+        files: Vec<PathBuf>,
+        /// Explain the currently staged git diff
+        #[arg(long)]
+        staged: bool,
     },
     /// Diagnose system issues
     Diagnose {
-        /// Service or component to diagnose
+        /// Service or component to diagnose; `-` reads stdin
         target: Vec<String>,
+        /// Attach a text file (repeatable)
+        #[arg(short, long = "file")]
+        files: Vec<PathBuf>,
     },
     /// Write code or scripts
     Write {
         /// What to write (e.g., "a Rust CLI with clap")
         description: Vec<String>,
+        /// Directory for generated files (default: ./generated-<slug>)
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Overwrite files that already exist
+        #[arg(long)]
+        force: bool,
+        /// Run `cargo check` and let the LLM repair errors once
+        #[arg(long)]
+        verify: bool,
+        /// Draft a conventional-commit message for the staged changes instead
+        #[arg(long, conflicts_with_all = ["out", "force", "verify"])]
+        commit_message: bool,
     },
     /// Check system status
     Check {
-        /// What to check (e.g., "btrfs mount status")
+        /// What to check (e.g., "btrfs mount status", "gpu"), or
+        /// `trend <metric>` for a recorded metric over time
         target: Vec<String>,
+        /// Time window for `check trend`, e.g. 24h, 7d, since monday
+        #[arg(long, default_value = "7d")]
+        since: String,
     },
     /// Fix issues automatically
     Fix {
-        /// Issue description or error message
+        /// Issue description or error message; `-` reads stdin
         issue: Vec<String>,
+        /// Attach a text file (repeatable)
+        #[arg(short, long = "file")]
+        files: Vec<PathBuf>,
+        /// Ask two LLM backends and merge their answers
+        #[arg(long)]
+        consensus: bool,
+        /// Undo a recorded maintenance operation (an id, or `last`) instead
+        #[arg(long, value_name = "OPERATION")]
+        rollback: Option<String>,
     },
     /// Blockchain operations and optimization
     Blockchain {
@@ -61,8 +128,119 @@ enum Commands {
         #[command(subcommand)]
         action: TrainCommands,
     },
-    /// Interactive chat mode
-    Chat,
+    /// Inspect the MCP tool audit log
+    Audit {
+        #[command(subcommand)]
+        action: AuditCommands,
+    },
+    /// Inspect timing traces of slow requests
+    Trace {
+        #[command(subcommand)]
+        action: TraceCommands,
+    },
+    /// Inspect incidents jarvisd correlated from health changes and alerts
+    Incidents {
+        #[command(subcommand)]
+        action: IncidentsCommands,
+    },
+    /// Follow service and container logs live
+    Logs {
+        #[command(subcommand)]
+        action: LogsCommands,
+    },
+    /// Try natural language routing and correct misrouted queries
+    Nlp {
+        #[command(subcommand)]
+        action: NlpCommands,
+    },
+    /// Status across all configured hosts
+    Fleet {
+        #[command(subcommand)]
+        action: FleetCommands,
+    },
+    /// Wake, power off, reboot, or suspend hosts
+    Power {
+        #[command(subcommand)]
+        action: PowerCommands,
+    },
+    /// Show or regenerate the host summary prepended to prompts
+    Profile {
+        #[command(subcommand)]
+        action: ProfileCommands,
+    },
+    /// Test and manage notifications
+    Notify {
+        #[command(subcommand)]
+        action: NotifyCommands,
+    },
+    /// Generate system reports
+    Report {
+        #[command(subcommand)]
+        action: ReportCommands,
+    },
+    /// Run Arch Linux agent operations directly (scan, cleanup, mirrors, ...)
+    Arch {
+        #[command(subcommand)]
+        action: ArchCommands,
+    },
+    /// Undo a recorded maintenance operation from its snapshot or package cache
+    Rollback {
+        /// Operation id from `jarvis arch history`, or `last`
+        operation: String,
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Scan for vulnerabilities and manage acknowledged advisories
+    Vuln {
+        #[command(subcommand)]
+        action: VulnCommands,
+    },
+    /// List built-in and plugin tools
+    Tools {
+        #[command(subcommand)]
+        action: ToolsCommands,
+    },
+    /// Import and export GhostFlow workflows
+    Ghostflow {
+        #[command(subcommand)]
+        action: GhostflowCommands,
+    },
+    /// Interactive chat mode; sessions are saved and can be resumed
+    Chat {
+        /// Continue a previous session (`last` for the most recent)
+        #[arg(long, value_name = "ID|last")]
+        resume: Option<String>,
+        /// List saved chat sessions
+        #[arg(long, conflicts_with = "resume")]
+        list: bool,
+    },
+    /// Install the latest signed release over this binary (and jarvisd beside it)
+    SelfUpdate {
+        /// Only report whether a newer release is available
+        #[arg(long)]
+        check_only: bool,
+        /// Install a release older than the running version
+        #[arg(long)]
+        allow_downgrade: bool,
+        /// Install this release (e.g. 0.3.1) instead of the latest
+        #[arg(long)]
+        version: Option<String>,
+    },
+    /// Check how this host is set up for Jarvis, e.g. how it gets root
+    Doctor {
+        /// Print sudoers rules for what Jarvis runs as root, one per capability
+        #[arg(long)]
+        sudoers: bool,
+        /// Account the sudoers rules are for (default: jarvis)
+        #[arg(long, requires = "sudoers")]
+        user: Option<String>,
+    },
+    /// Export, import, or repair the memory database
+    Memory {
+        #[command(subcommand)]
+        action: MemoryCommands,
+    },
     /// Configure Jarvis
     Config {
         #[command(subcommand)]
@@ -83,6 +261,12 @@ enum TrainCommands {
     List,
     /// Load a specific model
     Load { model_name: String },
+    /// Pull a model from the Ollama registry
+    Pull { model_name: String },
+    /// Remove a locally installed model
+    Rm { model_name: String },
+    /// Show model parameters, quantization, and size
+    Show { model_name: String },
 }
 
 #[derive(Subcommand)]
@@ -93,10 +277,23 @@ enum ConfigCommands {
     Init,
     /// Set configuration values
     Set { key: String, value: String },
+    /// Check a config file for unknown keys, bad values, and missing settings
+    Validate {
+        /// File to check (defaults to the active config)
+        file: Option<String>,
+    },
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() {
+    if let Err(e) = run().await {
+        eprintln!("Error: {:?}", e);
+        // Scripts can tell a refused fix (77) from a missing config (78) and so on
+        std::process::exit(jarvis_core::JarvisError::from_anyhow(&e).exit_code());
+    }
+}
+
+async fn run() -> Result<()> {
     let cli = Cli::parse();
 
     // Initialize logging
@@ -125,6 +322,35 @@ async fn main() -> Result<()> {
                     Config::set(&key, &value).await?;
                     println!("✅ Set {} = {}", key, value);
                 }
+                ConfigCommands::Validate { file } => {
+                    let path = match file.as_deref().or(cli.config.as_deref()) {
+                        Some(p) => std::path::PathBuf::from(p),
+                        None => dirs::config_dir()
+                            .ok_or_else(|| anyhow::anyhow!("Could not find config directory"))?
+                            .join("jarvis")
+                            .join("jarvis.toml"),
+                    };
+                    let validation =
+                        jarvis_core::config_validation::validate_file(&path).await?;
+
+                    for issue in &validation.issues {
+                        let icon = match issue.level {
+                            jarvis_core::config_validation::IssueLevel::Error => "❌",
+                            jarvis_core::config_validation::IssueLevel::Warning => "⚠️",
+                        };
+                        println!("{} {}", icon, issue);
+                    }
+                    let errors = validation.errors().count();
+                    if errors > 0 {
+                        return Err(jarvis_core::JarvisError::Config(format!(
+                            "{} has {} error(s)",
+                            path.display(),
+                            errors
+                        ))
+                        .into());
+                    }
+                    println!("✅ {} is valid", path.display());
+                }
             }
             return Ok(());
         }
@@ -133,39 +359,216 @@ async fn main() -> Result<()> {
 
     // Load configuration for other commands
     let config = Config::load(cli.config.as_deref()).await?;
+    jarvis_core::net::configure(&config.network);
+    if cli.offline {
+        jarvis_core::net::set_offline(true);
+    }
+    jarvis_core::read_only::configure(config.read_only);
+    if cli.read_only {
+        jarvis_core::read_only::set_read_only(true);
+    }
+
+    // Self-update needs neither the database nor an LLM
+    if let Commands::SelfUpdate {
+        check_only,
+        allow_downgrade,
+        version,
+    } = &cli.command
+    {
+        if cli.host.is_some() || cli.dry_run {
+            anyhow::bail!(
+                "self-update only updates this machine; use --check-only to see what it would install"
+            );
+        }
+        return handle_self_update(
+            *check_only,
+            *allow_downgrade,
+            version.as_deref(),
+            cli.output,
+        )
+        .await;
+    }
+
+    // Doctor only inspects this host
+    if let Commands::Doctor { sudoers, user } = &cli.command {
+        return handle_doctor(
+            *sudoers,
+            user.as_deref(),
+            &config.health_checks,
+            cli.output,
+        )
+        .await;
+    }
+
+    // Memory commands work on the database file itself
+    if let Commands::Memory { action } = cli.command {
+        return handle_memory_command(action, &config.database_path, cli.output).await;
+    }
 
-    // Initialize core components
-    let memory = MemoryStore::new(&config.database_path).await?;
-    let llm_router = LLMRouter::new(&config).await?;
+    // Initialize core components; a damaged database is replaced, and one
+    // that can't be opened leaves jarvis running without history
+    let memory = MemoryStore::open_or_ephemeral(&config.database_path).await?;
+    if let Some(recovery) = memory.recovery() {
+        eprintln!("⚠️ {}", recovery);
+    }
+    if let Some(reason) = memory.unavailable_reason() {
+        eprintln!(
+            "⚠️ Running without history, nothing from this session will be kept: {}",
+            reason
+        );
+    }
+    let llm_router = LLMRouter::new(&config)
+        .await?
+        .with_load_progress(std::sync::Arc::new(show_model_load));
     let environment = Environment::detect().await?;
-    let agent_runner = AgentRunner::new(memory.clone(), llm_router.clone()).await?;
+    let mut agent_runner = AgentRunner::new(memory.clone(), llm_router.clone())
+        .await?
+        .with_files_db_max_age(config.system.files_db_max_age())
+        .with_probe_limits(
+            config.system.probe_timeout(),
+            config.system.probe_deadline(),
+        )
+        .with_sensors(config.sensors.clone())
+        .with_health_checks(config.health_checks.clone())
+        .with_bus(config.bus.clone())
+        .with_host_profile(config.host_profile.clone())
+        .with_file_access(FileAccess::new(&config.files));
+
+    if cli.dry_run
+        && !matches!(
+            cli.command,
+            Commands::Fix { .. }
+                | Commands::Power { .. }
+                | Commands::Arch { .. }
+                | Commands::Rollback { .. }
+        )
+    {
+        anyhow::bail!("--dry-run is only supported for fix, power, arch, and rollback");
+    }
+
+    if let Some(host) = &cli.host {
+        if !matches!(
+            cli.command,
+            Commands::Explain { .. }
+                | Commands::Diagnose { .. }
+                | Commands::Check { .. }
+                | Commands::Fix { .. }
+                | Commands::Power { .. }
+                | Commands::Profile { .. }
+        ) {
+            anyhow::bail!(
+                "--host is only supported for explain, diagnose, check, fix, power, and profile"
+            );
+        }
+        let target = SshTarget::resolve(host, &config.remote);
+        info!("🌐 Running against remote host {}", target.destination);
+        agent_runner = agent_runner.with_executor(CommandExecutor::ssh(target));
+    }
+
+    // Explain, diagnose, check, fix, and nlp parse are timed phase by phase;
+    // slow ones are kept for `jarvis trace`, and `-v` prints every one
+    let trace = request_trace(&cli.command);
 
     // Route commands
     match cli.command {
-        Commands::Explain { query } => {
-            let query_str = query.join(" ");
+        Commands::Explain {
+            mut query,
+            files,
+            staged,
+        } => {
+            if staged && query.is_empty() {
+                query.push("the staged changes".to_string());
+            }
+            let (query_str, mut input) = with_attached_input(query, &files)?;
+            if staged {
+                let diff = jarvis_shell::git::staged_diff(&environment.working_directory)?;
+                if diff.trim().is_empty() {
+                    anyhow::bail!("Nothing is staged");
+                }
+                let staged_input = format!("--- staged diff ---\n{}", diff);
+                input = Some(match input {
+                    Some(other) => format!("{}\n\n{}", other, staged_input),
+                    None => staged_input,
+                });
+            }
             info!("📚 Explaining: {}", query_str);
-            agent_runner.explain(&query_str, &environment).await?;
+            trace
+                .scope(agent_runner.explain(&query_str, input.as_deref(), &environment, cli.output))
+                .await?;
         }
-        Commands::Diagnose { target } => {
-            let target_str = target.join(" ");
+        Commands::Diagnose { target, files } => {
+            let (target_str, input) = with_attached_input(target, &files)?;
             info!("🔍 Diagnosing: {}", target_str);
-            agent_runner.diagnose(&target_str, &environment).await?;
+            trace
+                .scope(agent_runner.diagnose(&target_str, input.as_deref(), &environment))
+                .await?;
         }
-        Commands::Write { description } => {
+        Commands::Write {
+            description,
+            out,
+            force,
+            verify,
+            commit_message,
+        } => {
+            if commit_message {
+                agent_runner.commit_message(&environment).await?;
+                return Ok(());
+            }
             let desc_str = description.join(" ");
             info!("✍️ Writing: {}", desc_str);
-            agent_runner.write_code(&desc_str, &environment).await?;
+            let options = WriteOptions { out, force, verify };
+            agent_runner
+                .write_code(&desc_str, &options, &environment)
+                .await?;
         }
-        Commands::Check { target } => {
+        Commands::Check { target, since }
+            if target.first().map(String::as_str) == Some("trend") =>
+        {
+            let [_, metric] = target.as_slice() else {
+                anyhow::bail!(
+                    "Usage: jarvis check trend <metric> [--since 7d], e.g. memory.used_percent"
+                );
+            };
+            if cli.host.is_some() {
+                anyhow::bail!(
+                    "check trend reads metrics recorded on this machine; --host is not supported"
+                );
+            }
+            show_trend(&memory, metric, &since, cli.output).await?;
+        }
+        Commands::Check { target, .. } => {
             let target_str = target.join(" ");
             info!("✅ Checking: {}", target_str);
-            agent_runner.check_status(&target_str, &environment).await?;
+            trace
+                .scope(agent_runner.check_status(&target_str, &environment))
+                .await?;
+        }
+        Commands::Fix {
+            rollback: Some(operation),
+            ..
+        } => {
+            if cli.host.is_some() {
+                anyhow::bail!("--rollback only works on this machine");
+            }
+            handle_rollback(&operation, false, cli.output, cli.dry_run).await?;
         }
-        Commands::Fix { issue } => {
-            let issue_str = issue.join(" ");
+        Commands::Fix {
+            issue,
+            files,
+            consensus,
+            rollback: None,
+        } => {
+            let (issue_str, input) = with_attached_input(issue, &files)?;
             info!("🔧 Fixing: {}", issue_str);
-            agent_runner.fix_issue(&issue_str, &environment).await?;
+            trace
+                .scope(agent_runner.fix_issue(
+                    &issue_str,
+                    input.as_deref(),
+                    &environment,
+                    consensus,
+                    cli.dry_run,
+                ))
+                .await?;
         }
         Commands::Train { action } => match action {
             TrainCommands::Start {
@@ -185,19 +588,169 @@ async fn main() -> Result<()> {
                 info!("📥 Loading model: {}", model_name);
                 agent_runner.load_model(&model_name).await?;
             }
+            TrainCommands::Pull { model_name } => {
+                agent_runner.pull_model(&model_name, cli.output).await?;
+            }
+            TrainCommands::Rm { model_name } => {
+                agent_runner.remove_model(&model_name, cli.output).await?;
+            }
+            TrainCommands::Show { model_name } => {
+                agent_runner.show_model(&model_name, cli.output).await?;
+            }
         },
-        Commands::Chat => {
-            info!("💬 Entering interactive chat mode...");
-            agent_runner.interactive_chat(&environment).await?;
+        Commands::Chat { resume, list } => {
+            if list {
+                agent_runner.list_chat_sessions(cli.output).await?;
+            } else {
+                info!("💬 Entering interactive chat mode...");
+                agent_runner
+                    .interactive_chat(&environment, resume.as_deref())
+                    .await?;
+            }
         }
         Commands::Config { .. } => {
             // Config commands are handled earlier, this should never be reached
             unreachable!("Config commands should be handled earlier")
         }
+        Commands::SelfUpdate { .. } => {
+            unreachable!("self-update is handled before the agent starts")
+        }
+        Commands::Doctor { .. } => {
+            unreachable!("doctor is handled before the agent starts")
+        }
+        Commands::Memory { .. } => {
+            unreachable!("memory commands are handled before the agent starts")
+        }
         Commands::Blockchain { blockchain_command } => {
             handle_blockchain_command(blockchain_command, &config).await?;
         }
+        Commands::Audit { action } => {
+            handle_audit_command(action, &memory).await?;
+        }
+        Commands::Trace { action } => {
+            handle_trace_command(action, &memory, cli.output).await?;
+        }
+        Commands::Incidents { action } => {
+            handle_incidents_command(action, &memory, cli.output).await?;
+        }
+        Commands::Logs { action } => {
+            handle_logs_command(action, &llm_router, cli.output).await?;
+        }
+        Commands::Nlp { action } => {
+            trace
+                .scope(handle_nlp_command(action, &config, &memory, &llm_router, cli.output))
+                .await?;
+        }
+        Commands::Notify { action } => {
+            handle_notify_command(action, &config).await?;
+        }
+        Commands::Tools { action } => {
+            handle_tools_command(action, &config, cli.output).await?;
+        }
+        Commands::Ghostflow { action } => {
+            handle_ghostflow_command(action, cli.output).await?;
+        }
+        Commands::Report { action } => {
+            handle_report_command(action, &config, &memory, &llm_router, cli.output).await?;
+        }
+        Commands::Vuln { action } => {
+            handle_vuln_command(action, &memory, cli.output).await?;
+        }
+        Commands::Arch { action } => {
+            handle_arch_command(action, &memory, cli.output, cli.dry_run).await?;
+        }
+        Commands::Rollback { operation, yes } => {
+            handle_rollback(&operation, yes, cli.output, cli.dry_run).await?;
+        }
+        Commands::Power { action } => {
+            handle_power_command(action, &config, cli.host.as_deref(), cli.output, cli.dry_run)
+                .await?;
+        }
+        Commands::Profile { action } => {
+            handle_profile_command(
+                action,
+                &config,
+                &memory,
+                &llm_router,
+                cli.host.as_deref(),
+                cli.output,
+            )
+            .await?;
+        }
+        Commands::Fleet { action } => {
+            if handle_fleet_command(action, &config, cli.output).await? {
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let report = trace.report();
+    if !report.phases.is_empty() {
+        if cli.verbose {
+            eprint!("{}", report.waterfall());
+        }
+        if let Err(e) = report.persist_if_slow(&memory, &config.trace).await {
+            tracing::warn!("Failed to store trace: {}", e);
+        }
     }
 
     Ok(())
 }
+
+/// "⏳ loading llama3.1:8b… 37%" on stderr while Ollama loads a cold model
+fn show_model_load(progress: &jarvis_core::llm::ModelLoadProgress) {
+    use std::io::Write;
+
+    if progress.ready {
+        eprintln!(
+            "\r✅ {} loaded in {}s          ",
+            progress.model,
+            progress.elapsed.as_secs()
+        );
+        return;
+    }
+    let done = match progress.percent {
+        Some(percent) => format!("{:.0}%", percent),
+        None => format!("{}s", progress.elapsed.as_secs()),
+    };
+    eprint!("\r⏳ loading {}… {}   ", progress.model, done);
+    let _ = std::io::stderr().flush();
+}
+
+/// A trace named after the request, e.g. "diagnose ollama container"
+fn request_trace(command: &Commands) -> jarvis_core::trace::Trace {
+    let request = match command {
+        Commands::Explain { query, .. } => format!("explain {}", query.join(" ")),
+        Commands::Diagnose { target, .. } => format!("diagnose {}", target.join(" ")),
+        Commands::Check { target, .. } => format!("check {}", target.join(" ")),
+        Commands::Fix { issue, .. } => format!("fix {}", issue.join(" ")),
+        Commands::Nlp {
+            action: NlpCommands::Parse { query },
+        } => query.join(" "),
+        _ => String::new(),
+    };
+    jarvis_core::trace::Trace::new(request.trim_end())
+}
+
+/// Split `-` out of the query words and read `--file` attachments plus stdin
+/// (when `-` is given or stdin is piped, e.g. `journalctl -u nginx | jarvis explain`)
+fn with_attached_input(words: Vec<String>, files: &[PathBuf]) -> Result<(String, Option<String>)> {
+    let wants_stdin = words.iter().any(|w| w == "-");
+    let query = words
+        .into_iter()
+        .filter(|w| w != "-")
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let input = input::collect(files, wants_stdin || input::stdin_is_piped())?;
+    if query.is_empty() && input.is_none() {
+        anyhow::bail!("Nothing to work with: pass a description, --file, or pipe input on stdin");
+    }
+
+    let query = if query.is_empty() {
+        "the provided input".to_string()
+    } else {
+        query
+    };
+    Ok((query, input))
+}